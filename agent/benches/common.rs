@@ -18,7 +18,10 @@ use std::time::Instant;
 
 use criterion::*;
 
-use deepflow_agent::common::CaptureNetworkTyper;
+use deepflow_agent::{
+    common::CaptureNetworkTyper,
+    config::{config::TagFilterOperator, handler::BlacklistTrie},
+};
 
 fn bench_tap_typer(c: &mut Criterion) {
     c.bench_function("get_tap_type_by_vlan", |b| {
@@ -34,5 +37,43 @@ fn bench_tap_typer(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_tap_typer);
+fn bench_blacklist_trie(c: &mut Criterion) {
+    let rule = |operator: &str, value: &str| TagFilterOperator {
+        field_name: "endpoint".to_string(),
+        operator: operator.to_string(),
+        value: value.to_string(),
+    };
+    let inputs = [
+        "/api/v1/users/1",
+        "/metrics/cpu",
+        "/healthz",
+        "/api/v2/orders",
+    ];
+    for (name, rules) in [
+        ("blacklist_trie_prefix", vec![rule("prefix", "/metrics")]),
+        (
+            "blacklist_trie_prefix_and_regex",
+            vec![
+                rule("prefix", "/metrics"),
+                rule("regex", "^/(healthz|livez|readyz)"),
+            ],
+        ),
+    ] {
+        c.bench_function(name, |b| {
+            let trie = BlacklistTrie::new(rules.clone()).unwrap();
+
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                for i in 0..iters {
+                    let _ = trie
+                        .endpoint
+                        .is_on_blacklist(inputs[i as usize % inputs.len()]);
+                }
+                start.elapsed()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_tap_typer, bench_blacklist_trie);
 criterion_main!(benches);
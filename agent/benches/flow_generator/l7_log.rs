@@ -16,7 +16,7 @@
 
 use std::path::Path;
 use std::time::Instant;
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
 use criterion::*;
 
@@ -26,10 +26,16 @@ use deepflow_agent::{
     _benchmark_report as benchmark_report,
     _benchmark_session_peer_seq_no_assert as benchmark_session_peer_seq_no_assert,
     _meta_flow_perf_update as meta_flow_perf_update,
-    common::l7_protocol_log::{L7ProtocolParserInterface, ParseParam},
+    common::{
+        flow::{L4Protocol, L7Protocol},
+        l7_protocol_log::{L7ProtocolBitmap, L7ProtocolParserInterface, ParseParam},
+    },
+    config::config::{ApplicationProtocolInference, PortPrefilterMode},
+    flow_generator::perf::L7ProtocolChecker,
     utils::test_utils::Capture,
     HttpLog,
 };
+use public::utils::bitmap::parse_u16_range_list_to_bitmap;
 
 pub(super) fn bench(c: &mut Criterion) {
     c.bench_function("perf_stats_report", |b| {
@@ -111,4 +117,51 @@ pub(super) fn bench(c: &mut Criterion) {
             start.elapsed()
         })
     });
+    let enabled = L7ProtocolBitmap::from(
+        ApplicationProtocolInference::default()
+            .enabled_protocols
+            .as_slice(),
+    );
+    let ports = HashMap::from(
+        [
+            (L7Protocol::Http1, "80,8080"),
+            (L7Protocol::Http2, "80,8080"),
+            (L7Protocol::MySQL, "3306"),
+            (L7Protocol::Redis, "6379"),
+            (L7Protocol::Kafka, "9092"),
+        ]
+        .map(|(p, r)| (p, parse_u16_range_list_to_bitmap(r, false).unwrap())),
+    );
+    for mode in [PortPrefilterMode::Strict, PortPrefilterMode::Prefer] {
+        let checker = L7ProtocolChecker::new(&enabled, &ports).with_prefilter_mode(mode);
+        c.bench_function(
+            &format!("l7_protocol_checker_{:?}", mode).to_lowercase(),
+            |b| {
+                b.iter_custom(|iters| {
+                    let start = Instant::now();
+                    for i in 0..iters {
+                        // the first candidate is what most flows on configured ports need
+                        let port = [80, 3306, 6379, 18080][i as usize % 4];
+                        black_box(checker.possible_protocols(L4Protocol::Tcp, port).next());
+                    }
+                    start.elapsed()
+                })
+            },
+        );
+        // flows of unknown protocols go through all candidates, which shows the cost of the
+        // fallback pass in prefer mode and that strict mode stays as before
+        c.bench_function(
+            &format!("l7_protocol_checker_{:?}_exhausted", mode).to_lowercase(),
+            |b| {
+                b.iter_custom(|iters| {
+                    let start = Instant::now();
+                    for i in 0..iters {
+                        let port = [80, 3306, 6379, 18080][i as usize % 4];
+                        black_box(checker.possible_protocols(L4Protocol::Tcp, port).count());
+                    }
+                    start.elapsed()
+                })
+            },
+        );
+    }
 }
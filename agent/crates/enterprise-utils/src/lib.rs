@@ -241,6 +241,7 @@ pub mod l7 {
                 pub resp_data_flags: DataFlags, // 仅 TnsPacketType 为 TnsTypeData 时有值
                 pub resp_data_id: Option<DataId>, // 仅 TnsPacketType 为 TnsTypeData 时有值
                 pub auth_session_id: Option<String>,
+                pub error_code: Option<u32>, // ORA-xxxxx，仅错误的 TnsTypeData 有值
             }
 
             pub enum Body {
@@ -286,6 +287,12 @@ pub mod l7 {
                 pub int_compress: bool,
                 // 0x04 结构有差异，个别结构有一个额外的 0x01 字节
                 pub resp_0x04_extra_byte: bool,
+                // NLS 字符集 ID，用于解码 SQL 文本
+                pub charset_id: u16,
+                // 解析 OALL8 中捆绑的多个调用
+                pub bundled_calls: bool,
+                // 解析仅包含 fetch 的请求和响应
+                pub fetch_calls: bool,
             }
 
             pub struct Frame<'a> {
@@ -35,10 +35,17 @@ fn generate_protobuf() -> Result<(), Box<dyn Error>> {
         .out_dir("src/proto")
         .compile(&["../../../message/agent.proto"], &["../../../message"])?;
     tonic_build::configure()
-        .build_server(false)
+        .build_server(true)
         .out_dir("src/proto/integration")
         .compile(
-            &["../../../message/opentelemetry/opentelemetry/proto/trace/v1/trace.proto"],
+            &[
+                "../../../message/opentelemetry/opentelemetry/proto/trace/v1/trace.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/metrics/v1/metrics.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/logs/v1/logs.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/trace/v1/trace_service.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/metrics/v1/metrics_service.proto",
+                "../../../message/opentelemetry/opentelemetry/proto/collector/logs/v1/logs_service.proto",
+            ],
             &["../../../message/opentelemetry"],
         )?;
 
@@ -194,6 +194,11 @@ impl RefCountable for StatsCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.max_alive.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "block_size",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.batch_size as u64),
+            ),
         ]
     }
 }
@@ -561,6 +566,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn block_size_counter() {
+        let allocator: Allocator<u64> = Allocator::new(1024);
+        let counters = allocator.counter().upgrade().unwrap().get_counters();
+        let block_size = counters.iter().find(|c| c.0 == "block_size").unwrap();
+        assert!(matches!(block_size.2, CounterValue::Unsigned(8192)));
+    }
+
     #[test]
     fn batch_allocation() {
         let mut size = 65536;
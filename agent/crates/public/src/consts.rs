@@ -208,6 +208,8 @@ pub const IPV6_HEADER_ADJUST: usize = IPV6_HEADER_SIZE - IPV4_HEADER_SIZE;
 
 pub const NPB_VXLAN_FLAGS: u8 = 0xff;
 pub const NPB_DEFAULT_PORT: u16 = 4789;
+pub const NPB_GPE_VXLAN_FLAGS: u8 = GPE_VXLAN_FLAGS_INSTANCE_MASK | GPE_VXLAN_FLAGS_PROTOCOL_MASK;
+pub const NPB_GPE_DEFAULT_PORT: u16 = 4790;
 
 pub const TCP_OPT_FLAG_WIN_SCALE: u8 = 0b1;
 pub const TCP_OPT_FLAG_MSS: u8 = 0b10;
@@ -216,6 +218,7 @@ pub const TCP_OPT_FLAG_SACK: u8 = 0x38; // 0011 1000, 同时也表示SACK的字
 
 pub const TCP_OPT_WIN_SCALE_LEN: usize = 3;
 pub const TCP_OPT_MSS_LEN: usize = 4;
+pub const TCP_OPT_TIMESTAMPS_LEN: usize = 10;
 
 // IPVS: https://github.com/yubo/ip_vs_ca
 pub const TCP_OPT_ADDRESS_IPVS: u8 = 200;
@@ -370,11 +373,17 @@ pub const LE_GPE_VXLAN_PROTO_UDP_DPORT: u16 = 0xB612; // 0x12B6(4790)'s LittleEn
 pub const LE_VXLAN_PROTO_UDP_DPORT2: u16 = 0x1821; // 0x2118(8472)'s LittleEndian
 pub const LE_VXLAN_PROTO_UDP_DPORT3: u16 = 0x801A; // 0x1A80(6784)'s LittleEndian
 pub const LE_TRANSPARENT_ETHERNET_BRIDGEING: u16 = 0x5865; // 0x6558(25944)'s LittleEndian
+pub const LE_MPLS_UNICAST_PROTO_TYPE: u16 = 0x4788; // 0x8847's LittleEndian
 pub const LE_GENEVE_PROTO_UDP_DPORT: u16 = 0xc117; // 0x17c1(6081)'s LittleEndian
 
 pub const VXLAN_FLAGS: u8 = 8;
 pub const TUNNEL_TIER_LIMIT: u8 = 2;
 
+pub const MPLS_LABEL_SIZE: usize = 4;
+pub const MPLS_BOTTOM_OF_STACK_MASK: u32 = 1 << 8;
+pub const MPLS_LABEL_SHIFT: u32 = 12;
+pub const MPLS_LABEL_LIMIT_DEFAULT: u8 = 4;
+
 pub const VXLAN_FLAGS_OFFSET: usize = UDP_PACKET_SIZE + vxlan::FLAGS_OFFSET;
 pub const VXLAN_SEQ_OFFSET: usize = UDP_PACKET_SIZE + vxlan::SEQUENCE_OFFSET;
 pub const VXLAN_VNI_OFFSET: usize = UDP_PACKET_SIZE + vxlan::VNI_OFFSET;
@@ -397,6 +406,7 @@ pub const GPE_VXLAN_FLAGS_INSTANCE_MASK: u8 = 0x8;
 pub const GPE_VXLAN_FLAGS_PROTOCOL_MASK: u8 = 0x4;
 pub const GPE_VXLAN_FLAGS_VERSION_MASK: u8 = 0x30;
 
+pub const GPE_VXLAN_NEXT_PROTOCOL_ETHERNET: u8 = 3;
 pub const GPE_VXLAN_NEXT_PROTOCOL_NSH: u8 = 4;
 
 pub const GPE_VXLAN_FLAGS_OFFSET: usize = 0;
@@ -468,6 +478,14 @@ pub const GENEVE_PROTOCOL_OFFSET: usize = 2;
 pub const GENEVE_VNI_OFFSET: usize = 4;
 
 pub const GENEVE_OPTION_LENGTH_MASK: u8 = 0x3f;
+pub const GENEVE_OPTION_HEADER_SIZE: usize = 4;
+pub const GENEVE_OPTION_CLASS_OFFSET: usize = 0;
+pub const GENEVE_OPTION_TYPE_OFFSET: usize = 2;
+pub const GENEVE_OPTION_DATA_LENGTH_OFFSET: usize = 3;
+pub const GENEVE_OPTION_DATA_LENGTH_MASK: u8 = 0x1f;
+pub const GENEVE_OPTION_SELECTOR_LIMIT: usize = 8;
+pub const GENEVE_METADATA_SIZE_LIMIT: usize = 64;
+pub const GENEVE_METADATA_SIZE_DEFAULT: u8 = 32;
 
 pub const GENEVE_VERSION_SHIFT: u8 = 6;
 pub const GENEVE_VNI_SHIFT: u32 = 8;
@@ -33,6 +33,7 @@ impl EthernetType {
     pub const ARP: Self = Self(0x0806);
     pub const IPV6: Self = Self(0x86DD);
     pub const DOT1Q: Self = Self(0x8100);
+    pub const MPLS_UNICAST: Self = Self(0x8847);
     pub const TRANSPARENT_ETHERNET_BRIDGING: Self = Self(0x6558);
     pub const QINQ: Self = Self(0x88a8);
     pub const LINK_LAYER_DISCOVERY: Self = Self(0x88cc);
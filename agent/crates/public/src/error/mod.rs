@@ -32,6 +32,8 @@ pub enum Error {
     LibpcapError(String),
     #[error("vhost user error {0}")]
     VhostUserError(String),
+    #[error("dpdk error {0}")]
+    DpdkError(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
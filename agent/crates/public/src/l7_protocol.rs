@@ -75,6 +75,7 @@ pub enum L7Protocol {
     Redis = 80,
     MongoDB = 81,
     Memcached = 82,
+    Elasticsearch = 83,
 
     // MQ
     Kafka = 100,
@@ -91,6 +92,8 @@ pub enum L7Protocol {
     DNS = 120,
     TLS = 121,
     Ping = 122,
+    LDAP = 123,
+    SMTP = 124,
 
     Custom = 127,
 
@@ -110,6 +113,8 @@ impl L7Protocol {
             | Self::SomeIp
             | Self::Ping
             | Self::Triple
+            | Self::LDAP
+            | Self::SMTP
             | Self::Custom => true,
             _ => false,
         }
@@ -135,6 +140,7 @@ impl From<String> for L7Protocol {
             "postgresql" => Self::PostgreSQL,
             "redis" => Self::Redis,
             "memcached" => Self::Memcached,
+            "elasticsearch" => Self::Elasticsearch,
             "kafka" => Self::Kafka,
             "mqtt" => Self::MQTT,
             "amqp" => Self::AMQP,
@@ -151,6 +157,8 @@ impl From<String> for L7Protocol {
             "triple" => Self::Triple,
             "tls" => Self::TLS,
             "ping" => Self::Ping,
+            "ldap" => Self::LDAP,
+            "smtp" => Self::SMTP,
             "some/ip" | "someip" => Self::SomeIp,
             "netsign" | "net-sign" | "net_sign" => Self::NetSign,
             _ => Self::Unknown,
@@ -176,6 +184,8 @@ pub enum CustomProtocol {
     So(u8, String),
     #[serde(serialize_with = "serialize_arc_string")]
     CustomPolicy(Arc<String>),
+    #[serde(serialize_with = "serialize_arc_string")]
+    CustomText(Arc<String>),
 }
 
 fn serialize_arc_string<S>(arc: &Arc<String>, serializer: S) -> Result<S::Ok, S::Error>
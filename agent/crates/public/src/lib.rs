@@ -34,6 +34,7 @@ pub mod rpc;
 pub mod segment_map;
 pub mod sender;
 pub mod throttle;
+pub mod token_bucket;
 pub mod types;
 pub mod utils;
 
@@ -42,3 +43,4 @@ pub mod netns;
 
 pub use leaky_bucket::LeakyBucket;
 pub use throttle::Throttle;
+pub use token_bucket::{TokenBucket, TokenBucketConfig};
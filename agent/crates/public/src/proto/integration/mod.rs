@@ -26,10 +26,37 @@ pub mod opentelemetry {
                 include!("opentelemetry.proto.trace.v1.rs");
             }
         }
+        pub mod metrics {
+            pub mod v1 {
+                include!("opentelemetry.proto.metrics.v1.rs");
+            }
+        }
+        pub mod logs {
+            pub mod v1 {
+                include!("opentelemetry.proto.logs.v1.rs");
+            }
+        }
         pub mod resource {
             pub mod v1 {
                 include!("opentelemetry.proto.resource.v1.rs");
             }
         }
+        pub mod collector {
+            pub mod trace {
+                pub mod v1 {
+                    include!("opentelemetry.proto.collector.trace.v1.rs");
+                }
+            }
+            pub mod metrics {
+                pub mod v1 {
+                    include!("opentelemetry.proto.collector.metrics.v1.rs");
+                }
+            }
+            pub mod logs {
+                pub mod v1 {
+                    include!("opentelemetry.proto.collector.logs.v1.rs");
+                }
+            }
+        }
     }
 }
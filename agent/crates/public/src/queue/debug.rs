@@ -24,7 +24,7 @@ use std::{
 
 use log::debug;
 
-use super::{bounded, Error, Receiver, Sender, StatsHandle};
+use super::{bounded, bounded_with_policy, Error, OverflowPolicy, Receiver, Sender, StatsHandle};
 
 use crate::debug::{QueueDebugger, QUEUE_LEN};
 
@@ -60,6 +60,22 @@ impl<T: Debug> DebugSender<T> {
         self.send_debug(&msgs);
         self.sender.send_all(msgs)
     }
+
+    pub fn len(&self) -> usize {
+        self.sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sender.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.sender.capacity()
+    }
+
+    pub fn total_overwritten_count(&self) -> u64 {
+        self.sender.total_overwritten_count()
+    }
 }
 
 impl<T> Clone for DebugSender<T> {
@@ -76,7 +92,16 @@ pub fn bounded_with_debug<T>(
     name: &'static str,
     debugger: &QueueDebugger,
 ) -> (DebugSender<T>, Receiver<T>, StatsHandle<T>) {
-    let (sender, receiver, handle) = bounded(size);
+    bounded_with_debug_and_policy(size, name, debugger, OverflowPolicy::default())
+}
+
+pub fn bounded_with_debug_and_policy<T>(
+    size: usize,
+    name: &'static str,
+    debugger: &QueueDebugger,
+    policy: OverflowPolicy,
+) -> (DebugSender<T>, Receiver<T>, StatsHandle<T>) {
+    let (sender, receiver, handle) = bounded_with_policy(size, policy);
 
     let (debug_sender, debug_receiver, _) = bounded(QUEUE_LEN);
     let enabled = Arc::new(AtomicBool::new(false));
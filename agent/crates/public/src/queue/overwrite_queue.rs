@@ -18,6 +18,8 @@ use std::cmp;
 use std::iter::Iterator;
 use std::marker::PhantomData;
 use std::mem::{self, MaybeUninit};
+use std::ptr;
+use std::slice;
 use std::sync::{
     atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     Condvar, Mutex,
@@ -28,7 +30,27 @@ use super::Error;
 use crate::counter as stats;
 
 pub fn bounded<T>(size: usize) -> (Sender<T>, Receiver<T>, StatsHandle<T>) {
-    RefCounter::new(OverwriteQueue::with_capacity(size))
+    bounded_with_policy(size, OverflowPolicy::default())
+}
+
+pub fn bounded_with_policy<T>(
+    size: usize,
+    policy: OverflowPolicy,
+) -> (Sender<T>, Receiver<T>, StatsHandle<T>) {
+    RefCounter::new(OverwriteQueue::with_capacity(size, policy))
+}
+
+// What to do with messages sent to a full queue
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // overwrite the oldest messages in queue
+    #[default]
+    DropOldest,
+    // discard the messages that don't fit
+    DropNewest,
+    // wait for the receiver to free space, discard the messages that still
+    // don't fit after the timeout
+    BlockWithTimeout(Duration),
 }
 
 #[derive(Debug, Default)]
@@ -37,6 +59,10 @@ pub struct Counter {
     pub output: AtomicU64,
     pub overwritten: AtomicU64,
     pub pending: AtomicU64,
+    // discarded by OverflowPolicy::DropNewest
+    pub dropped: AtomicU64,
+    // discarded by OverflowPolicy::BlockWithTimeout
+    pub block_timeout: AtomicU64,
 }
 
 // fixed size MPSC overwrite queue implemented with ring buffer
@@ -52,6 +78,11 @@ struct OverwriteQueue<T: Sized> {
     writer_lock: Mutex<()>,
     notify: Condvar,
 
+    policy: OverflowPolicy,
+    // used by OverflowPolicy::BlockWithTimeout to wait for free space
+    space_lock: Mutex<()>,
+    space_notify: Condvar,
+
     terminated: AtomicBool,
 
     counter: Counter,
@@ -62,7 +93,7 @@ struct OverwriteQueue<T: Sized> {
 }
 
 impl<T> OverwriteQueue<T> {
-    pub fn with_capacity(size: usize) -> Self {
+    pub fn with_capacity(size: usize, policy: OverflowPolicy) -> Self {
         let size = size.next_power_of_two();
         let buffer = {
             let mut v = Vec::with_capacity(size);
@@ -79,6 +110,9 @@ impl<T> OverwriteQueue<T> {
             reader_lock: Mutex::new(()),
             writer_lock: Mutex::new(()),
             notify: Condvar::new(),
+            policy,
+            space_lock: Mutex::new(()),
+            space_notify: Condvar::new(),
             terminated: AtomicBool::new(false),
             counter: Counter::default(),
             total_overwritten_count: AtomicU64::new(0),
@@ -90,7 +124,53 @@ impl<T> OverwriteQueue<T> {
         self.terminated.load(Ordering::Relaxed)
     }
 
-    unsafe fn raw_send(&self, msgs: *const T, count: usize) -> Result<(), Error<T>> {
+    // raw_end must not change, i.e. writer_lock is held
+    fn free_space(&self, raw_end: usize) -> usize {
+        let start = self.start.load(Ordering::Acquire);
+        let end = if raw_end < start {
+            raw_end + 2 * self.size
+        } else {
+            raw_end
+        };
+        self.size - (end - start)
+    }
+
+    // number of messages in queue, only an estimate while senders or receiver are active
+    fn len(&self) -> usize {
+        let start = self.start.load(Ordering::Acquire);
+        let raw_end = self.end.load(Ordering::Acquire);
+        let end = if raw_end < start {
+            raw_end + 2 * self.size
+        } else {
+            raw_end
+        };
+        self.size.min(end - start)
+    }
+
+    // Wait until there is space for count messages or timeout, returns the free space
+    fn wait_for_space(&self, raw_end: usize, count: usize, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut guard = self.space_lock.lock().unwrap();
+        loop {
+            let free_space = self.free_space(raw_end);
+            if free_space >= count || self.terminated.load(Ordering::Acquire) {
+                return free_space;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return free_space;
+            }
+            guard = self
+                .space_notify
+                .wait_timeout(guard, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    // Returns the number of messages accepted, which are the first ones of msgs.
+    // The rest are discarded by the overflow policy and should be dropped by caller.
+    unsafe fn raw_send(&self, msgs: *const T, count: usize) -> Result<usize, Error<T>> {
         if self.terminated.load(Ordering::Acquire) {
             return Err(Error::Terminated(None, None));
         }
@@ -111,8 +191,38 @@ impl<T> OverwriteQueue<T> {
         self.counter
             .pending
             .fetch_max(self.size.min(end - start + count) as u64, Ordering::Relaxed);
+        let mut count = count;
         // queue full
-        if end - start + count > self.size {
+        if end - start + count > self.size && self.policy != OverflowPolicy::DropOldest {
+            let free_space = match self.policy {
+                OverflowPolicy::BlockWithTimeout(timeout) => {
+                    let free_space = self.wait_for_space(raw_end, count, timeout);
+                    if self.terminated.load(Ordering::Acquire) {
+                        return Err(Error::Terminated(None, None));
+                    }
+                    if free_space < count {
+                        self.counter
+                            .block_timeout
+                            .fetch_add((count - free_space) as u64, Ordering::Relaxed);
+                    }
+                    free_space
+                }
+                _ => {
+                    // start could be modified by recv
+                    let free_space = self.free_space(raw_end);
+                    if free_space < count {
+                        self.counter
+                            .dropped
+                            .fetch_add((count - free_space) as u64, Ordering::Relaxed);
+                    }
+                    free_space
+                }
+            };
+            count = count.min(free_space);
+            if count == 0 {
+                return Ok(0);
+            }
+        } else if end - start + count > self.size {
             let _lock = self.reader_lock.lock().unwrap();
             // start could be modified by recv, check again
             let start = self.start.load(Ordering::Acquire);
@@ -159,7 +269,7 @@ impl<T> OverwriteQueue<T> {
             .input
             .fetch_add(count as u64, Ordering::Relaxed);
         self.notify.notify_one();
-        Ok(())
+        Ok(count)
     }
 
     unsafe fn raw_recv_timeout(
@@ -225,6 +335,10 @@ impl<T> OverwriteQueue<T> {
         self.counter
             .output
             .fetch_add(recv_count as u64, Ordering::Relaxed);
+        if let OverflowPolicy::BlockWithTimeout(_) = self.policy {
+            let _lock = self.space_lock.lock().unwrap();
+            self.space_notify.notify_all();
+        }
         Ok(recv_count)
     }
 
@@ -232,6 +346,10 @@ impl<T> OverwriteQueue<T> {
         let _lock = self.reader_lock.lock().unwrap();
         self.terminated.swap(true, Ordering::Release);
         self.notify.notify_one();
+        if let OverflowPolicy::BlockWithTimeout(_) = self.policy {
+            let _lock = self.space_lock.lock().unwrap();
+            self.space_notify.notify_all();
+        }
     }
 }
 
@@ -298,14 +416,36 @@ impl<T> Sender<T> {
         self.counter().queue.terminated()
     }
 
+    pub fn len(&self) -> usize {
+        self.counter().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // size of the queue, rounded up to a power of two
+    pub fn capacity(&self) -> usize {
+        self.counter().queue.size
+    }
+
+    pub fn total_overwritten_count(&self) -> u64 {
+        self.counter()
+            .queue
+            .total_overwritten_count
+            .load(Ordering::Relaxed)
+    }
+
     pub fn send(&self, msg: T) -> Result<(), Error<T>> {
         unsafe {
             match self.counter().queue.raw_send(&msg, 1) {
-                Ok(_) => {
+                Ok(1) => {
                     // don't drop because msg is moved into queue
                     mem::forget(msg);
                     Ok(())
                 }
+                // discarded by overflow policy
+                Ok(_) => Ok(()),
                 Err(Error::Terminated(..)) => Err(Error::Terminated(Some(msg), None)),
                 _ => unreachable!(),
             }
@@ -317,9 +457,15 @@ impl<T> Sender<T> {
     pub fn send_all(&self, msgs: &mut Vec<T>) -> Result<(), Error<T>> {
         unsafe {
             match self.counter().queue.raw_send(msgs.as_ptr(), msgs.len()) {
-                Ok(_) => {
-                    // drop the vector without dropping elements within
+                Ok(sent) => {
+                    // drop the vector without dropping elements moved into queue
+                    let len = msgs.len();
                     msgs.set_len(0);
+                    // and drop the ones discarded by overflow policy
+                    ptr::drop_in_place(slice::from_raw_parts_mut(
+                        msgs.as_mut_ptr().add(sent),
+                        len - sent,
+                    ));
                     Ok(())
                 }
                 Err(Error::Terminated(..)) => Err(Error::Terminated(None, None)),
@@ -491,7 +637,7 @@ impl<T> Drop for StatsHandle<T> {
 impl<T: Send> stats::OwnedCountable for StatsHandle<T> {
     fn get_counters(&self) -> Vec<stats::Counter> {
         let queue = &self.counter().queue;
-        vec![
+        let mut counters = vec![
             (
                 "in",
                 stats::CounterType::Counted,
@@ -512,7 +658,23 @@ impl<T: Send> stats::OwnedCountable for StatsHandle<T> {
                 stats::CounterType::Gauged,
                 stats::CounterValue::Unsigned(queue.counter.pending.swap(0, Ordering::Relaxed)),
             ),
-        ]
+        ];
+        match queue.policy {
+            OverflowPolicy::DropOldest => (),
+            OverflowPolicy::DropNewest => counters.push((
+                "dropped",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(queue.counter.dropped.swap(0, Ordering::Relaxed)),
+            )),
+            OverflowPolicy::BlockWithTimeout(_) => counters.push((
+                "block_timeout",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(
+                    queue.counter.block_timeout.swap(0, Ordering::Relaxed),
+                ),
+            )),
+        }
+        counters
     }
 
     fn closed(&self) -> bool {
@@ -636,6 +798,27 @@ mod tests {
         assert_eq!(c, 0, "new/drop count mismatch: new - drop = {}", c);
     }
 
+    #[test]
+    fn sender_len_and_overwritten() {
+        let (s, r, _) = bounded(4);
+        assert!(s.is_empty());
+        assert_eq!(s.capacity(), 4);
+        assert_eq!(bounded::<u8>(5).0.capacity(), 8);
+
+        s.send_all(&mut (0..3).collect()).unwrap();
+        assert_eq!(s.len(), 3);
+        s.send_all(&mut (3..6).collect()).unwrap();
+        assert_eq!(s.len(), 4);
+        assert_eq!(s.total_overwritten_count(), 2);
+
+        assert_eq!(r.recv_n(3, None).unwrap(), vec![2, 3, 4]);
+        assert_eq!(s.len(), 1);
+        // wrapped around the ring buffer
+        s.send_all(&mut (6..9).collect()).unwrap();
+        assert_eq!(s.len(), 4);
+        assert_eq!(s.total_overwritten_count(), 2);
+    }
+
     #[test]
     fn queue_size_calculation() {
         let c = Arc::new(AtomicUsize::new(0));
@@ -699,6 +882,136 @@ mod tests {
         assert_eq!(c, 0, "new/drop count mismatch: new - drop = {}", c);
     }
 
+    #[test]
+    fn drop_newest() {
+        let c = Arc::new(AtomicUsize::new(0));
+
+        {
+            let (s, r, stats) = bounded_with_policy(2, OverflowPolicy::DropNewest);
+
+            s.send(CountedU64::new(42, c.clone())).unwrap();
+            s.send_all(&mut vec![
+                CountedU64::new(43, c.clone()),
+                CountedU64::new(44, c.clone()),
+            ])
+            .unwrap();
+            s.send(CountedU64::new(45, c.clone())).unwrap();
+
+            let mut co = Vec::with_capacity(4);
+            r.recv_all(&mut co, None).unwrap();
+            assert_eq!(co, vec![42, 43], "expected: [42, 43], result: {:?}", co);
+            assert_eq!(
+                stats
+                    .counter()
+                    .queue
+                    .counter
+                    .dropped
+                    .load(Ordering::Relaxed),
+                2
+            );
+        }
+
+        let c = c.load(Ordering::Acquire);
+        assert_eq!(c, 0, "new/drop count mismatch: new - drop = {}", c);
+    }
+
+    #[test]
+    fn block_with_timeout_consumer_stalled() {
+        let c = Arc::new(AtomicUsize::new(0));
+
+        {
+            let timeout = Duration::from_millis(1);
+            let (s, r, stats) = bounded_with_policy(16, OverflowPolicy::BlockWithTimeout(timeout));
+            let started = Instant::now();
+            let handles = (0..8)
+                .map(|i| {
+                    let s = s.clone();
+                    let c = c.clone();
+                    thread::spawn(move || {
+                        for j in 0..100 {
+                            if j % 2 == 0 {
+                                s.send(CountedU64::new(i * 1000 + j, c.clone())).unwrap();
+                            } else {
+                                s.send_all(&mut vec![
+                                    CountedU64::new(i * 1000 + j, c.clone()),
+                                    CountedU64::new(i * 1000 + j, c.clone()),
+                                ])
+                                .unwrap();
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            // nobody receives, every sender gives up after the timeout
+            for h in handles {
+                h.join().unwrap();
+            }
+            assert!(started.elapsed() < Duration::from_secs(10));
+
+            let counter = &stats.counter().queue.counter;
+            let input = counter.input.load(Ordering::Relaxed);
+            let block_timeout = counter.block_timeout.load(Ordering::Relaxed);
+            assert_eq!(input, 16);
+            assert_eq!(input + block_timeout, 8 * 150);
+
+            let co = r.recv_n(100, None).unwrap();
+            assert_eq!(co.len(), 16);
+        }
+
+        let c = c.load(Ordering::Acquire);
+        assert_eq!(c, 0, "new/drop count mismatch: new - drop = {}", c);
+    }
+
+    #[test]
+    fn block_with_timeout_slow_consumer() {
+        let (s, r, stats) =
+            bounded_with_policy(4, OverflowPolicy::BlockWithTimeout(Duration::from_secs(10)));
+        let handles = (0..4)
+            .map(|_| {
+                let s = s.clone();
+                thread::spawn(move || {
+                    for j in 1..=100 {
+                        s.send(j).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        mem::drop(s);
+
+        let mut sum = 0;
+        for (i, v) in r.enumerate() {
+            if i % 50 == 0 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            sum += v;
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(sum, 4 * 5050, "expected: {}, result: {}", 4 * 5050, sum);
+        assert_eq!(
+            stats
+                .counter()
+                .queue
+                .counter
+                .block_timeout
+                .load(Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[test]
+    fn block_with_timeout_terminate() {
+        let (s, r, _) =
+            bounded_with_policy(1, OverflowPolicy::BlockWithTimeout(Duration::from_secs(60)));
+        s.send(1).unwrap();
+        let h = thread::spawn(move || s.send(2));
+        thread::sleep(Duration::from_millis(10));
+        // receiver dropped while the sender is blocked
+        mem::drop(r);
+        assert_eq!(h.join().unwrap(), Err(Error::Terminated(Some(2), None)));
+    }
+
     #[test]
     #[should_panic]
     fn recv_empty() {
@@ -31,6 +31,11 @@ pub trait Sendable: Debug + Send + 'static {
     }
     // Serialize data to key-value and append to a string
     fn to_kv_string(&self, _: &mut String) {}
+    // Stable key of the flow or tags, data with the same key are sent through the same socket
+    // when there are multiple sockets to the ingester, None to send in turn
+    fn hash_key(&self) -> Option<u64> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy, IntoPrimitive, PartialEq)]
@@ -14,56 +14,113 @@
  * limitations under the License.
  */
 
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use log::warn;
 use rand::prelude::{Rng, SeedableRng, SmallRng};
 
+use crate::counter::{Counter, CounterType, CounterValue, RefCountable};
 use crate::queue::DebugSender;
-use crate::LeakyBucket;
+use crate::token_bucket::{TokenBucket, TokenBucketConfig};
+
+#[derive(Debug, Default)]
+pub struct ThrottleCounter {
+    accepted: AtomicU64,
+    dropped: AtomicU64,
+    // the most of one second's tokens used since the last report, in percentage
+    utilization: AtomicU64,
+}
+
+impl RefCountable for ThrottleCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "accepted",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.accepted.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "utilization",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.utilization.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
 
 const BUFFER_SIZE: usize = 1024;
+
+// Limits the items sent per second with a token bucket refilled continuously at the rate, which
+// allows a burst of one second's items. Items over the rate replace random items in the buffer,
+// so that items after a burst are sampled too.
 pub struct Throttle<T: Debug> {
-    leaky_bucket: LeakyBucket,
+    rate: u64,
+    bucket_config: Arc<TokenBucketConfig>,
+    bucket: TokenBucket,
     period_count: u32,
     buffer: Vec<T>,
     output_queue: DebugSender<T>,
     small_rng: SmallRng,
+    counter: Arc<ThrottleCounter>,
 }
 
 impl<T: Debug> Throttle<T> {
-    pub fn new(rate: u64, output_queue: DebugSender<T>) -> Self {
+    // rate 0 for unlimited
+    pub fn new(rate: u64, output_queue: DebugSender<T>, counter: Arc<ThrottleCounter>) -> Self {
+        let bucket_config = Arc::new(TokenBucketConfig::new(Some(rate), rate));
         Throttle {
-            leaky_bucket: LeakyBucket::new(Some(rate)),
+            rate,
+            bucket: TokenBucket::new(bucket_config.clone()),
+            bucket_config,
             buffer: Vec::with_capacity(BUFFER_SIZE),
             output_queue,
             small_rng: SmallRng::from_entropy(),
             period_count: 0,
+            counter,
         }
     }
 
     // return false, indicates that the throttle has been reached
     // and the item or cached items will be discarded
-    pub fn send(&mut self, item: T) -> bool {
+    pub fn send(&mut self, now: Duration, item: T) -> bool {
         if self.buffer.len() >= BUFFER_SIZE {
             self.flush();
-            self.period_count = 0;
         }
 
         self.period_count = self.period_count.saturating_add(1);
-        if self.leaky_bucket.acquire(1) {
+        if self.bucket.acquire(now, 1) {
             self.buffer.push(item);
+            self.counter.accepted.fetch_add(1, Ordering::Relaxed);
+            if self.rate > 0 {
+                self.counter
+                    .utilization
+                    .fetch_max(self.bucket.used() * 100 / self.rate, Ordering::Relaxed);
+            }
         } else {
             let index = self.small_rng.gen_range(0..self.period_count) as usize;
             if index < self.buffer.len() {
                 self.buffer[index] = item;
             }
+            self.counter.dropped.fetch_add(1, Ordering::Relaxed);
             return false;
         }
         true
     }
 
     pub fn flush(&mut self) {
+        self.period_count = 0;
         if !self.buffer.is_empty() {
             if let Err(e) = self.output_queue.send_all(&mut self.buffer) {
                 warn!(
@@ -77,6 +134,130 @@ impl<T: Debug> Throttle<T> {
     }
 
     pub fn set_rate(&mut self, rate: u64) {
-        self.leaky_bucket.set_rate(Some(rate));
+        self.rate = rate;
+        self.bucket_config.set(Some(rate), rate);
+    }
+}
+
+// Samples at most `rate` items of each second uniformly, instead of passing the first items
+// of a second until the bucket is empty. Sampled items are held until the second ends.
+pub struct UniformThrottle<T> {
+    rate: u64,
+    second: u64,
+    period_count: u64,
+    buffer: Vec<T>,
+    small_rng: SmallRng,
+}
+
+impl<T> UniformThrottle<T> {
+    pub fn new(rate: u64) -> Self {
+        UniformThrottle {
+            rate,
+            second: 0,
+            period_count: 0,
+            buffer: vec![],
+            small_rng: SmallRng::from_entropy(),
+        }
+    }
+
+    // Items sampled in the seconds before `second` are moved to `output`
+    pub fn expire(&mut self, second: u64, output: &mut Vec<T>) {
+        if second == self.second {
+            return;
+        }
+        output.append(&mut self.buffer);
+        self.second = second;
+        self.period_count = 0;
+    }
+
+    // return false, indicates that the throttle has been reached
+    // and the item or a sampled item is discarded
+    pub fn send(&mut self, second: u64, item: T, output: &mut Vec<T>) -> bool {
+        self.expire(second, output);
+        self.period_count += 1;
+        if (self.buffer.len() as u64) < self.rate {
+            self.buffer.push(item);
+            return true;
+        }
+        let index = self.small_rng.gen_range(0..self.period_count);
+        if index < self.buffer.len() as u64 {
+            self.buffer[index as usize] = item;
+        }
+        false
+    }
+
+    // The items sampled are moved to `output`
+    pub fn flush(&mut self, output: &mut Vec<T>) {
+        output.append(&mut self.buffer);
+        self.period_count = 0;
+    }
+
+    // Items already sampled in the current second are kept
+    pub fn set_rate(&mut self, rate: u64) {
+        self.rate = rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{debug::QueueDebugger, queue::bounded_with_debug};
+
+    #[test]
+    fn throttle_rate() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = bounded_with_debug(1 << 16, "", &queue_debugger);
+        let counter = Arc::new(ThrottleCounter::default());
+        let mut throttle = Throttle::new(1000, sender, counter.clone());
+        // twice the rate for 10 seconds
+        let mut dropped = 0;
+        for i in 0..20000u64 {
+            if !throttle.send(Duration::from_micros(i * 500), i) {
+                dropped += 1;
+            }
+        }
+        throttle.flush();
+
+        // one second of burst and then the rate, the same for l4 and l7 flow logs
+        assert_eq!(dropped, 9001);
+        let mut output = Vec::with_capacity(1 << 16);
+        let _ = receiver.recv_all(&mut output, Some(Duration::from_secs(1)));
+        assert_eq!(output.len(), 20000 - dropped);
+        // items from the tail of the last second are sampled too
+        assert!(output.iter().any(|i| *i >= 19000));
+
+        let counters = counter.get_counters();
+        assert_eq!(
+            counters[0].2,
+            CounterValue::Unsigned(20000 - dropped as u64)
+        );
+        assert_eq!(counters[1].2, CounterValue::Unsigned(dropped as u64));
+        assert_eq!(counters[2].2, CounterValue::Unsigned(100));
+    }
+
+    #[test]
+    fn uniform_throttle() {
+        let mut throttle = UniformThrottle::new(100);
+        let mut output = vec![];
+        let mut dropped = 0;
+        for i in 0..10000 {
+            if !throttle.send(1, i, &mut output) {
+                dropped += 1;
+            }
+        }
+        assert!(output.is_empty());
+        assert_eq!(dropped, 9900);
+
+        throttle.expire(2, &mut output);
+        assert_eq!(output.len(), 100);
+        // items from the tail of the second are sampled too
+        assert!(output.iter().filter(|i| **i >= 5000).count() > 20);
+
+        output.clear();
+        assert!(throttle.send(2, 0, &mut output));
+        assert!(output.is_empty());
+        throttle.flush(&mut output);
+        assert_eq!(output, vec![0]);
     }
 }
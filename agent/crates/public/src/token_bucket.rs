@@ -0,0 +1,254 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// Rate and burst shared by token buckets in different threads
+#[derive(Debug, Default)]
+pub struct TokenBucketConfig {
+    // 0 for unlimited
+    rate: AtomicU64,
+    burst: AtomicU64,
+}
+
+impl TokenBucketConfig {
+    pub fn new(rate: Option<u64>, burst: u64) -> Self {
+        let config = Self::default();
+        config.set(rate, burst);
+        config
+    }
+
+    pub fn set(&self, rate: Option<u64>, burst: u64) {
+        self.rate.store(rate.unwrap_or(0), Ordering::Relaxed);
+        self.burst.store(burst.max(1), Ordering::Relaxed);
+    }
+}
+
+// Token bucket refilled continuously at the configured rate, up to burst tokens
+//
+// Time is passed in by the caller, so that packet timestamps can be used and the bucket can be
+// simulated. It is not thread safe, each thread should have its own bucket.
+#[derive(Clone)]
+pub struct TokenBucket {
+    config: Arc<TokenBucketConfig>,
+
+    rate: u64,
+    burst: u64,
+    // in 1 / NANOS_PER_SEC tokens, so that no refilling is lost between close timestamps
+    tokens: u128,
+    last: Duration,
+}
+
+impl TokenBucket {
+    pub fn new(config: Arc<TokenBucketConfig>) -> Self {
+        Self {
+            config,
+            rate: 0,
+            burst: 0,
+            tokens: 0,
+            last: Duration::ZERO,
+        }
+    }
+
+    fn refill(&mut self, now: Duration) {
+        let rate = self.config.rate.load(Ordering::Relaxed);
+        let burst = self.config.burst.load(Ordering::Relaxed);
+        let full = burst as u128 * NANOS_PER_SEC;
+        if rate != self.rate || burst != self.burst {
+            // starts full on config change
+            self.rate = rate;
+            self.burst = burst;
+            self.tokens = full;
+        } else if now > self.last {
+            self.tokens = full.min(self.tokens + (now - self.last).as_nanos() * rate as u128);
+        }
+        // time going backwards only stops refilling until then
+        self.last = now;
+    }
+
+    pub fn acquire(&mut self, now: Duration, size: u64) -> bool {
+        self.refill(now);
+        if self.rate == 0 {
+            return true;
+        }
+        let size = size as u128 * NANOS_PER_SEC;
+        if self.tokens < size {
+            return false;
+        }
+        self.tokens -= size;
+        true
+    }
+
+    // Tokens used from the burst, 0 when the bucket is full or unlimited
+    pub fn used(&self) -> u64 {
+        if self.rate == 0 {
+            return 0;
+        }
+        self.burst - (self.tokens / NANOS_PER_SEC) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MILLISECOND: Duration = Duration::from_millis(1);
+
+    fn bucket(rate: Option<u64>, burst: u64) -> TokenBucket {
+        TokenBucket::new(Arc::new(TokenBucketConfig::new(rate, burst)))
+    }
+
+    // The limiter replaced, adding rate / 10 tokens every 100ms if it does not overflow one
+    // second worth of tokens
+    struct FixedIntervalLimiter {
+        quantity_per_tick: u64,
+        full: u64,
+        tokens: u64,
+        next_tick: Duration,
+    }
+
+    impl FixedIntervalLimiter {
+        const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+        fn new(rate: u64) -> Self {
+            let quantity_per_tick = 1.max(rate / 10);
+            Self {
+                quantity_per_tick,
+                full: quantity_per_tick * 10,
+                tokens: quantity_per_tick * 10,
+                next_tick: Self::TICK_INTERVAL,
+            }
+        }
+
+        fn acquire(&mut self, now: Duration) -> bool {
+            while now >= self.next_tick {
+                if self.tokens + self.quantity_per_tick <= self.full {
+                    self.tokens += self.quantity_per_tick;
+                }
+                self.next_tick += Self::TICK_INTERVAL;
+            }
+            if self.tokens == 0 {
+                return false;
+            }
+            self.tokens -= 1;
+            true
+        }
+    }
+
+    // Arrival time of each packet in order
+    fn arrivals(pattern: impl Fn(u32) -> u32, millis: u32) -> Vec<Duration> {
+        let mut packets = vec![];
+        for ms in 0..millis {
+            let count = pattern(ms);
+            for i in 0..count {
+                packets.push(MILLISECOND * ms + MILLISECOND * i / count);
+            }
+        }
+        packets
+    }
+
+    fn drops(packets: &[Duration], mut acquire: impl FnMut(Duration) -> bool) -> usize {
+        packets.iter().filter(|t| !acquire(**t)).count()
+    }
+
+    #[test]
+    fn rate_and_burst() {
+        let mut b = bucket(Some(1000), 100);
+        let start = Duration::from_secs(10);
+        for _ in 0..100 {
+            assert!(b.acquire(start, 1));
+        }
+        assert_eq!(b.used(), 100);
+        assert!(!b.acquire(start, 1));
+        // 1 token every millisecond
+        assert!(!b.acquire(start + MILLISECOND / 2, 1));
+        assert!(b.acquire(start + MILLISECOND, 1));
+        assert!(!b.acquire(start + MILLISECOND, 1));
+        // no more than burst after idle
+        assert!(b.acquire(start + Duration::from_secs(60), 100));
+        assert!(!b.acquire(start + Duration::from_secs(60), 1));
+        // going backwards does not refill
+        assert!(!b.acquire(start, 1));
+        assert!(b.acquire(start + MILLISECOND, 1));
+
+        let mut unlimited = bucket(None, 1);
+        assert!(unlimited.acquire(Duration::ZERO, u64::MAX));
+        assert_eq!(unlimited.used(), 0);
+    }
+
+    #[test]
+    fn config_change() {
+        let config = Arc::new(TokenBucketConfig::new(None, 10));
+        let mut b = TokenBucket::new(config.clone());
+        assert!(b.acquire(Duration::ZERO, 100));
+        config.set(Some(10), 10);
+        assert!(b.acquire(Duration::ZERO, 10));
+        assert!(!b.acquire(Duration::ZERO, 1));
+        config.set(Some(10), 20);
+        assert!(b.acquire(Duration::ZERO, 20));
+        assert_eq!(b.used(), 20);
+    }
+
+    #[test]
+    fn steady_arrivals() {
+        let rate = 10000;
+        // exactly the rate, one packet every 100us
+        let packets = arrivals(|_| 10, 10000);
+        let mut b = bucket(Some(rate), rate);
+        let mut old = FixedIntervalLimiter::new(rate);
+        assert_eq!(drops(&packets, |t| b.acquire(t, 1)), 0);
+        assert_eq!(drops(&packets, |t| old.acquire(t)), 0);
+        // burst is never touched
+        assert!(b.used() <= 1);
+    }
+
+    #[test]
+    fn bursty_arrivals() {
+        let rate = 10000;
+        // all packets of every 100ms arrive in the first 10ms, 80% of the rate for 3 seconds and
+        // then 200% for 1 second
+        let pattern = |ms: u32| match (ms / 1000 % 4, ms % 100) {
+            (_, 10..) => 0,
+            (3, _) => 200,
+            _ => 80,
+        };
+        let packets = arrivals(pattern, 40000);
+        assert_eq!(packets.len(), 10 * (3 * 8000 + 20000));
+
+        let mut old = FixedIntervalLimiter::new(rate);
+        let old_drops = drops(&packets, |t| old.acquire(t));
+        let mut b = bucket(Some(rate), rate);
+        let new_drops = drops(&packets, |t| b.acquire(t, 1));
+        let mut larger = bucket(Some(rate), 2 * rate);
+        let larger_drops = drops(&packets, |t| larger.acquire(t, 1));
+        assert!(
+            new_drops <= old_drops,
+            "token bucket drops {new_drops} more than fixed interval limiter {old_drops}"
+        );
+        assert!(
+            larger_drops < new_drops,
+            "larger burst drops {larger_drops} not less than default {new_drops}"
+        );
+    }
+}
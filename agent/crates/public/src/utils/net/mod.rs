@@ -23,6 +23,7 @@ use std::{
 
 use bitflags::bitflags;
 use ipnet::IpNet;
+use regex::Regex;
 use serde::Serialize;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -104,6 +105,8 @@ pub struct Link {
     pub adapter_id: String,
     #[cfg(target_os = "windows")]
     pub device_name: String,
+    #[cfg(target_os = "windows")]
+    pub description: String,
     pub name: String,
     pub flags: LinkFlags,
     #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -338,6 +341,28 @@ pub fn is_link_local_multicast(ip: &IpAddr) -> bool {
     }
 }
 
+// Removes the links matched by `exclude_regex` from the ones matched by the include regex,
+// nothing is removed if it is empty
+pub fn exclude_links_by_name_regex<S: AsRef<str>>(
+    links: Vec<Link>,
+    exclude_regex: S,
+) -> Result<Vec<Link>> {
+    let exclude_regex = exclude_regex.as_ref();
+    if exclude_regex.is_empty() {
+        return Ok(links);
+    }
+    // anchored at the end as the include regex
+    let regex = if exclude_regex.ends_with('$') {
+        Regex::new(exclude_regex)
+    } else {
+        Regex::new(&format!("{}$", exclude_regex))
+    }?;
+    Ok(links
+        .into_iter()
+        .filter(|link| !regex.is_match(&link.name))
+        .collect())
+}
+
 #[derive(Clone, Debug)]
 pub struct IpMacPair {
     pub ip: IpAddr,
@@ -387,6 +412,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn exclude_links() {
+        let links = [
+            "eth0",
+            "veth1a2b",
+            "veth-monitoring0",
+            "veth-monitoring-x",
+            "lo",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Link {
+            if_index: i as u32 + 1,
+            name: name.to_string(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+        let names = |links: Vec<Link>| links.into_iter().map(|l| l.name).collect::<Vec<_>>();
+
+        // empty exclude regex keeps all
+        assert_eq!(
+            names(exclude_links_by_name_regex(links.clone(), "").unwrap()),
+            vec![
+                "eth0",
+                "veth1a2b",
+                "veth-monitoring0",
+                "veth-monitoring-x",
+                "lo"
+            ]
+        );
+        // overlapping with an include regex of veth.*
+        let included = links
+            .iter()
+            .filter(|l| l.name.starts_with("veth"))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names(exclude_links_by_name_regex(included, "^veth-monitoring.*").unwrap()),
+            vec!["veth1a2b"]
+        );
+        // anchored at the end
+        assert_eq!(
+            names(exclude_links_by_name_regex(links.clone(), "veth-monitoring\\d").unwrap()),
+            vec!["eth0", "veth1a2b", "veth-monitoring-x", "lo"]
+        );
+        assert!(exclude_links_by_name_regex(links, "(").is_err());
+    }
+
     #[test]
     fn mac_to_u64() {
         assert_eq!(
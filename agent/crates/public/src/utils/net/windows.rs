@@ -15,11 +15,13 @@
  */
 
 use std::{
+    collections::HashSet,
+    fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     ptr,
 };
 
-use log::{debug, warn};
+use log::{debug, info, warn};
 use pcap;
 use regex::Regex;
 use windows::Win32::{
@@ -240,25 +242,71 @@ pub fn links_by_name_regex<S: AsRef<str>>(regex: S) -> Result<Vec<Link>> {
     } else {
         Regex::new(&format!("{}$", regex))
     }?;
-    Ok(link_list()?
-        .into_iter()
-        .filter(|link| {
-            if !link.flags.contains(LinkFlags::LOOPBACK) {
-                // filter zero mac
-                if link.mac_addr == MacAddr::ZERO {
-                    warn!(
-                        "link {} has invalid mac address {}",
-                        link.name, link.mac_addr
-                    );
-                    return false;
-                }
+    let links = link_list()?.into_iter().filter(|link| {
+        if !link.flags.contains(LinkFlags::LOOPBACK) {
+            // filter zero mac
+            if link.mac_addr == MacAddr::ZERO {
+                warn!(
+                    "link {} has invalid mac address {}",
+                    link.name, link.mac_addr
+                );
+                return false;
             }
-
-            regex.is_match(&link.name)
+        }
+        true
+    });
+    Ok(match_links(&regex, links)
+        .into_iter()
+        .map(|(link, field)| {
+            info!(
+                "link {} ({}, {}) matched interface regex {} by {}",
+                link.name, link.description, link.adapter_id, regex, field
+            );
+            link
         })
         .collect())
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LinkField {
+    FriendlyName,
+    Description,
+    Guid,
+}
+
+impl fmt::Display for LinkField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FriendlyName => write!(f, "friendly name"),
+            Self::Description => write!(f, "description"),
+            Self::Guid => write!(f, "GUID"),
+        }
+    }
+}
+
+// Returns the first field of the link matching regex, in the order of friendly name, description
+// and GUID of the adapter
+fn match_link(regex: &Regex, link: &Link) -> Option<LinkField> {
+    [
+        (LinkField::FriendlyName, &link.name),
+        (LinkField::Description, &link.description),
+        (LinkField::Guid, &link.adapter_id),
+    ]
+    .into_iter()
+    .find(|(_, value)| !value.is_empty() && regex.is_match(value))
+    .map(|(field, _)| field)
+}
+
+// An adapter can be listed by more than one pcap device, only the first one is kept
+fn match_links(regex: &Regex, links: impl IntoIterator<Item = Link>) -> Vec<(Link, LinkField)> {
+    let mut seen = HashSet::new();
+    links
+        .into_iter()
+        .filter_map(|link| match_link(regex, &link).map(|field| (link, field)))
+        .filter(|(link, _)| seen.insert(link.if_index))
+        .collect()
+}
+
 pub fn get_route_src_ip_and_mac(dest_addr: &IpAddr) -> Result<(IpAddr, MacAddr)> {
     route_get(*dest_addr).and_then(|r| {
         get_interface_by_index_from_win32(r.oif_index)
@@ -486,6 +534,15 @@ pub fn get_adapters_addresses() -> Result<(Vec<Link>, Vec<Addr>)> {
                 }
             };
 
+            let description = if adapter.Description.is_null() {
+                String::new()
+            } else {
+                count_len(adapter.Description.0, 0)
+                    .map(|len| std::slice::from_raw_parts(adapter.Description.0, len))
+                    .and_then(|s| String::from_utf16(s).ok())
+                    .unwrap_or_default()
+            };
+
             let adapter_id = match count_len(adapter.AdapterName.0, 0)
                 .map(|len| std::slice::from_raw_parts(adapter.AdapterName.0, len))
                 .and_then(|s| String::from_utf8(s.into()).ok())
@@ -551,6 +608,7 @@ pub fn get_adapters_addresses() -> Result<(Vec<Link>, Vec<Addr>)> {
 
             links.push(Link {
                 name: friendly_name,
+                description,
                 adapter_id,
                 mac_addr,
                 if_index,
@@ -633,3 +691,85 @@ pub fn ipv6_enabled_for_link<S: AsRef<str>>(_: S) -> bool {
     // TODO: implement this
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter(if_index: u32, name: &str, description: &str, adapter_id: &str) -> Link {
+        Link {
+            if_index,
+            name: name.to_string(),
+            description: description.to_string(),
+            adapter_id: adapter_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn adapters() -> Vec<Link> {
+        vec![
+            adapter(
+                3,
+                "Ethernet",
+                "Intel(R) Ethernet Connection (7) I219-LM",
+                "{95BC9BD0-4C29-44FC-B0C7-896326EF378F}",
+            ),
+            adapter(
+                6,
+                "vEthernet (Default Switch)",
+                "Hyper-V Virtual Ethernet Adapter",
+                "{1AF9CCBA-3FEE-4CD1-810F-3761F8A4DE25}",
+            ),
+            adapter(
+                11,
+                "Npcap Loopback Adapter",
+                "Npcap Loopback Adapter",
+                "{7A2B9C3D-1E4F-4A5B-8C6D-9E0F1A2B3C4D}",
+            ),
+        ]
+    }
+
+    fn matched(regex: &str, links: Vec<Link>) -> Vec<(u32, LinkField)> {
+        match_links(&Regex::new(regex).unwrap(), links)
+            .into_iter()
+            .map(|(link, field)| (link.if_index, field))
+            .collect()
+    }
+
+    #[test]
+    fn match_fields() {
+        assert_eq!(
+            matched("^Ethernet", adapters()),
+            vec![(3, LinkField::FriendlyName)]
+        );
+        assert_eq!(
+            matched("^Hyper-V.*", adapters()),
+            vec![(6, LinkField::Description)]
+        );
+        assert_eq!(
+            matched(r"^\{1AF9CCBA-.*\}$", adapters()),
+            vec![(6, LinkField::Guid)]
+        );
+        assert_eq!(
+            matched(".*Ethernet.*", adapters()),
+            vec![(3, LinkField::FriendlyName), (6, LinkField::FriendlyName)]
+        );
+        assert!(matched("^eth0$", adapters()).is_empty());
+    }
+
+    #[test]
+    fn match_dedup() {
+        // friendly name and description of npcap loopback are the same
+        assert_eq!(
+            matched("^Npcap.*", adapters()),
+            vec![(11, LinkField::FriendlyName)]
+        );
+        // the same adapter listed twice by pcap devices
+        let mut links = adapters();
+        links.push(links[1].clone());
+        assert_eq!(
+            matched(".*(Hyper-V|Default Switch).*", links),
+            vec![(6, LinkField::FriendlyName)]
+        );
+    }
+}
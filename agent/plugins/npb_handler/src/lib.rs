@@ -99,7 +99,7 @@ impl NpbHandler {
     pub fn new(
         _id: usize,
         _mtu: usize,
-        _pseudo_tunnel_header: [Vec<u8>; NpbTunnelType::Max as usize],
+        _pseudo_tunnel_header: [[Vec<u8>; NpbTunnelType::Max as usize]; 2],
         _underlay_vlan_header_size: usize,
         _overlay_vlan_mode: VlanMode,
         _ignore_overlay_vlan: bool,
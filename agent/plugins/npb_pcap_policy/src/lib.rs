@@ -24,6 +24,8 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 pub const NOT_SUPPORT: bool = true;
 
+const VLAN_ID_MASK: u32 = 0xfff;
+
 bitflags! {
     #[derive(Default)]
     pub struct TapSide: u8 {
@@ -104,6 +106,7 @@ impl NpbTunnelType {
 pub struct NpbAction {
     acl_gids: Vec<u16>,
     tunnel_ip_ids: Vec<u16>,
+    vlan_id: Option<u16>,
 }
 
 impl Default for NpbAction {
@@ -111,6 +114,7 @@ impl Default for NpbAction {
         Self {
             acl_gids: vec![],
             tunnel_ip_ids: vec![],
+            vlan_id: None,
         }
     }
 }
@@ -129,6 +133,7 @@ impl NpbAction {
         Self {
             acl_gids: vec![acl_gid as u16],
             tunnel_ip_ids: vec![tunnel_ip_id],
+            vlan_id: None,
         }
     }
 
@@ -167,6 +172,28 @@ impl NpbAction {
         &self.tunnel_ip_ids
     }
 
+    pub fn vlan_id(&self) -> Option<u16> {
+        self.vlan_id
+    }
+
+    pub fn set_vlan_id(&mut self, vlan_id: Option<u16>) {
+        self.vlan_id = vlan_id;
+    }
+
+    // VLAN ids of the extra VLAN header, in the order they appear in the packet:
+    // - 802.1Q: the VLAN id of the action, or the lower 12 bits of the tunnel id
+    // - QinQ: the outer tag is the VLAN id of the action, or the upper 12 bits of
+    //   the 24 bits tunnel id, the inner tag is always the lower 12 bits of the tunnel id
+    pub fn extra_vlan_ids(&self, qinq: bool) -> (u16, Option<u16>) {
+        let tunnel_id = self.tunnel_id();
+        let lower = (tunnel_id & VLAN_ID_MASK) as u16;
+        if !qinq {
+            return (self.vlan_id.unwrap_or(lower), None);
+        }
+        let upper = ((tunnel_id >> 12) & VLAN_ID_MASK) as u16;
+        (self.vlan_id.unwrap_or(upper), Some(lower))
+    }
+
     pub fn reverse_tap_side(&mut self) {}
 
     pub fn set_payload_slice(&mut self, _payload_slice: u16) {}
@@ -272,3 +299,20 @@ pub trait DedupOperator: Send + Sync {
     fn is_tor(&self) -> bool;
     fn is_valid(&self, tap_side: TapSide) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extra_vlan_ids() {
+        // the tunnel id of the stub is 100
+        let mut action = NpbAction::default();
+        assert_eq!(action.extra_vlan_ids(false), (100, None));
+        assert_eq!(action.extra_vlan_ids(true), (0, Some(100)));
+
+        action.set_vlan_id(Some(200));
+        assert_eq!(action.extra_vlan_ids(false), (200, None));
+        assert_eq!(action.extra_vlan_ids(true), (200, Some(100)));
+    }
+}
@@ -15,16 +15,341 @@
  */
 
 //! Enterprise Edition Feature: analyzer_mode
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use std::time::Duration;
 
-pub struct PacketDedupMap;
+use public::counter;
+
+pub const DEFAULT_WINDOW: Duration = Duration::from_micros(5);
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+const ETH_TYPE_OFFSET: usize = 12;
+const ETH_HEADER_SIZE: usize = 14;
+const VLAN_HEADER_SIZE: usize = 4;
+const ETH_TYPE_VLAN: u16 = 0x8100;
+const ETH_TYPE_QINQ: u16 = 0x88a8;
+const ETH_TYPE_IPV4: u16 = 0x0800;
+const ETH_TYPE_IPV6: u16 = 0x86dd;
+const IPV4_HEADER_SIZE: usize = 20;
+const IPV6_HEADER_SIZE: usize = 40;
+const IP_PROTO_TCP: u8 = 6;
+const TCP_HEADER_SIZE: usize = 20;
+
+// TTL/ToS related bytes are at the beginning of ip header
+const IP_MASKED_HEADER_SIZE: usize = 12;
+// (offset, mask) of ToS, TTL and header checksum
+const IPV4_TTL_TOS_MASKS: [(usize, u8); 4] = [(1, 0), (8, 0), (10, 0), (11, 0)];
+// (offset, mask) of traffic class and hop limit
+const IPV6_TTL_TOS_MASKS: [(usize, u8); 3] = [(0, 0xf0), (1, 0x0f), (7, 0)];
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketDedupConfig {
+    // packets with the same content arriving within the window are duplicates
+    pub window: Duration,
+    pub table_size: usize,
+    // exclude TTL/ToS (and IPv4 header checksum) from packet hash,
+    // for mirror paths rewriting them
+    pub ignore_ttl_tos: bool,
+}
+
+impl Default for PacketDedupConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            table_size: DEFAULT_TABLE_SIZE,
+            ignore_ttl_tos: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PacketDedupCounter {
+    pub hits: AtomicU64,
+    // duplicates which can not be told apart from a legitimate TCP retransmission,
+    // that is TCP segments with payload and without IPv4 identification
+    pub suspected_false_positives: AtomicU64,
+}
+
+impl counter::RefCountable for PacketDedupCounter {
+    fn get_counters(&self) -> Vec<counter::Counter> {
+        vec![
+            (
+                "hits",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.hits.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "suspected_false_positives",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(
+                    self.suspected_false_positives.swap(0, Ordering::Relaxed),
+                ),
+            ),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    hash: u64,
+    length: usize,
+    timestamp: Duration,
+}
+
+struct IpHeader {
+    offset: usize,
+    is_ipv6: bool,
+    // ipv4 identification, or None for ipv6
+    id: Option<u16>,
+    protocol: u8,
+    header_size: usize,
+    payload_size: usize,
+}
+
+impl IpHeader {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let mut offset = ETH_TYPE_OFFSET;
+        let mut eth_type = read_u16_be(packet, offset)?;
+        while eth_type == ETH_TYPE_VLAN || eth_type == ETH_TYPE_QINQ {
+            offset += VLAN_HEADER_SIZE;
+            eth_type = read_u16_be(packet, offset)?;
+        }
+        let offset = offset + ETH_HEADER_SIZE - ETH_TYPE_OFFSET;
+        match eth_type {
+            ETH_TYPE_IPV4 if packet.len() >= offset + IPV4_HEADER_SIZE => {
+                let header_size = ((packet[offset] & 0xf) as usize) << 2;
+                let total_length = read_u16_be(packet, offset + 2)? as usize;
+                Some(Self {
+                    offset,
+                    is_ipv6: false,
+                    id: read_u16_be(packet, offset + 4),
+                    protocol: packet[offset + 9],
+                    header_size,
+                    payload_size: total_length.saturating_sub(header_size),
+                })
+            }
+            ETH_TYPE_IPV6 if packet.len() >= offset + IPV6_HEADER_SIZE => Some(Self {
+                offset,
+                is_ipv6: true,
+                id: None,
+                protocol: packet[offset + 6],
+                header_size: IPV6_HEADER_SIZE,
+                payload_size: read_u16_be(packet, offset + 4)? as usize,
+            }),
+            _ => None,
+        }
+    }
+
+    fn has_tcp_payload(&self, packet: &[u8]) -> bool {
+        if self.protocol != IP_PROTO_TCP || self.payload_size < TCP_HEADER_SIZE {
+            return false;
+        }
+        let tcp_offset = self.offset + self.header_size;
+        let Some(data_offset) = packet.get(tcp_offset + 12) else {
+            return false;
+        };
+        self.payload_size > ((data_offset >> 4) as usize) << 2
+    }
+}
+
+fn read_u16_be(packet: &[u8], offset: usize) -> Option<u16> {
+    packet
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn fnv_hash(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+pub struct PacketDedupMap {
+    config: PacketDedupConfig,
+    table: Vec<Entry>,
+    counter: Arc<PacketDedupCounter>,
+}
 
 impl PacketDedupMap {
-    pub fn new() -> Self {
-        PacketDedupMap
+    pub fn new(config: PacketDedupConfig, counter: Arc<PacketDedupCounter>) -> Self {
+        PacketDedupMap {
+            table: vec![Entry::default(); config.table_size.max(1)],
+            config,
+            counter,
+        }
+    }
+
+    fn hash(&self, packet: &[u8], ip: Option<&IpHeader>) -> u64 {
+        let Some(ip) = ip.filter(|_| self.config.ignore_ttl_tos) else {
+            return fnv_hash(FNV_OFFSET_BASIS, packet);
+        };
+        let masked_end = ip.offset + IP_MASKED_HEADER_SIZE;
+        let mut header = [0u8; IP_MASKED_HEADER_SIZE];
+        header.copy_from_slice(&packet[ip.offset..masked_end]);
+        let masks: &[(usize, u8)] = if ip.is_ipv6 {
+            &IPV6_TTL_TOS_MASKS
+        } else {
+            &IPV4_TTL_TOS_MASKS
+        };
+        for (offset, mask) in masks {
+            header[*offset] &= mask;
+        }
+        let hash = fnv_hash(FNV_OFFSET_BASIS, &packet[..ip.offset]);
+        let hash = fnv_hash(hash, &header);
+        fnv_hash(hash, &packet[masked_end..])
     }
 
-    pub fn duplicate(&mut self, _: &mut [u8], _: Duration) -> bool {
+    pub fn duplicate(&mut self, packet: &mut [u8], timestamp: Duration) -> bool {
+        if packet.is_empty() {
+            return false;
+        }
+        let ip = IpHeader::parse(packet);
+        let hash = self.hash(packet, ip.as_ref());
+        let index = (hash % self.table.len() as u64) as usize;
+        let entry = &mut self.table[index];
+        if entry.hash == hash
+            && entry.length == packet.len()
+            && timestamp.abs_diff(entry.timestamp) <= self.config.window
+        {
+            self.counter.hits.fetch_add(1, Ordering::Relaxed);
+            if let Some(ip) = ip.as_ref() {
+                if ip.id.unwrap_or_default() == 0 && ip.has_tcp_payload(packet) {
+                    self.counter
+                        .suspected_false_positives
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            return true;
+        }
+        *entry = Entry {
+            hash,
+            length: packet.len(),
+            timestamp,
+        };
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp_packet(id: u16, ttl: u8, tos: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02, 0x08, 0x00];
+        let total_length = (IPV4_HEADER_SIZE + TCP_HEADER_SIZE + payload.len()) as u16;
+        packet.extend_from_slice(&[0x45, tos]);
+        packet.extend_from_slice(&total_length.to_be_bytes());
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.extend_from_slice(&[0x40, 0, ttl, IP_PROTO_TCP]);
+        // checksum changes along with ttl and tos
+        packet.extend_from_slice(&[ttl ^ tos, 0]);
+        packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        packet.extend_from_slice(&[0x30, 0x39, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18]);
+        packet.extend_from_slice(&[0xff, 0xff, 0, 0, 0, 0]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn new_map(config: PacketDedupConfig) -> (PacketDedupMap, Arc<PacketDedupCounter>) {
+        let counter = Arc::new(PacketDedupCounter::default());
+        (PacketDedupMap::new(config, counter.clone()), counter)
+    }
+
+    #[test]
+    fn window_boundary() {
+        let window = Duration::from_micros(20);
+        for (offset, expected) in [
+            (Duration::ZERO, true),
+            (window - Duration::from_nanos(1), true),
+            (window, true),
+            (window + Duration::from_nanos(1), false),
+            (window * 10, false),
+        ] {
+            let (mut map, counter) = new_map(PacketDedupConfig {
+                window,
+                ..Default::default()
+            });
+            let first = Duration::from_secs(100);
+            let mut packet = tcp_packet(1, 64, 0, b"hello");
+            assert!(!map.duplicate(&mut packet.clone(), first));
+            assert_eq!(
+                map.duplicate(&mut packet, first + offset),
+                expected,
+                "offset {:?}",
+                offset
+            );
+            // duplicates may be reordered between queues
+            let mut packet = tcp_packet(1, 64, 0, b"hello");
+            assert_eq!(
+                map.duplicate(&mut packet, first.saturating_sub(offset)),
+                expected,
+                "offset -{:?}",
+                offset
+            );
+            assert_eq!(
+                counter.hits.load(Ordering::Relaxed),
+                if expected { 2 } else { 0 }
+            );
+        }
+    }
+
+    #[test]
+    fn default_window() {
+        let (mut map, _) = new_map(PacketDedupConfig::default());
+        let first = Duration::from_secs(100);
+        let mut packet = tcp_packet(1, 64, 0, b"hello");
+        assert!(!map.duplicate(&mut packet, first));
+        assert!(map.duplicate(&mut packet, first + DEFAULT_WINDOW));
+        assert!(!map.duplicate(&mut packet, first + DEFAULT_WINDOW * 3));
+    }
+
+    #[test]
+    fn different_packets() {
+        let (mut map, _) = new_map(PacketDedupConfig::default());
+        let ts = Duration::from_secs(100);
+        assert!(!map.duplicate(&mut tcp_packet(1, 64, 0, b"hello"), ts));
+        assert!(!map.duplicate(&mut tcp_packet(2, 64, 0, b"hello"), ts));
+        assert!(!map.duplicate(&mut tcp_packet(1, 64, 0, b"world"), ts));
+        assert!(!map.duplicate(&mut [], ts));
+    }
+
+    #[test]
+    fn ignore_ttl_tos() {
+        let ts = Duration::from_secs(100);
+        let (mut map, _) = new_map(PacketDedupConfig::default());
+        assert!(!map.duplicate(&mut tcp_packet(1, 64, 0, b"hello"), ts));
+        assert!(!map.duplicate(&mut tcp_packet(1, 63, 4, b"hello"), ts));
+
+        let (mut map, _) = new_map(PacketDedupConfig {
+            ignore_ttl_tos: true,
+            ..Default::default()
+        });
+        assert!(!map.duplicate(&mut tcp_packet(1, 64, 0, b"hello"), ts));
+        assert!(map.duplicate(&mut tcp_packet(1, 63, 4, b"hello"), ts));
+        assert!(!map.duplicate(&mut tcp_packet(2, 63, 4, b"hello"), ts));
+    }
+
+    #[test]
+    fn suspected_false_positives() {
+        let ts = Duration::from_secs(100);
+        let (mut map, counter) = new_map(PacketDedupConfig::default());
+        assert!(!map.duplicate(&mut tcp_packet(0, 64, 0, b""), ts));
+        assert!(map.duplicate(&mut tcp_packet(0, 64, 0, b""), ts));
+        assert!(!map.duplicate(&mut tcp_packet(1, 64, 0, b"hello"), ts));
+        assert!(map.duplicate(&mut tcp_packet(1, 64, 0, b"hello"), ts));
+        assert_eq!(counter.suspected_false_positives.load(Ordering::Relaxed), 0);
+
+        assert!(!map.duplicate(&mut tcp_packet(0, 64, 0, b"hello"), ts));
+        assert!(map.duplicate(&mut tcp_packet(0, 64, 0, b"hello"), ts));
+        assert_eq!(counter.hits.load(Ordering::Relaxed), 3);
+        assert_eq!(counter.suspected_false_positives.load(Ordering::Relaxed), 1);
+    }
+}
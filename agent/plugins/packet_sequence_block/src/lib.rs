@@ -42,7 +42,7 @@ impl PacketSequenceBlock {
         unimplemented!();
     }
 
-    pub fn append_packet(&self, _: MiniMetaPacket, _: u8) {
+    pub fn append_packet(&self, _: MiniMetaPacket, _: u16) {
         unimplemented!();
     }
 
@@ -82,6 +82,7 @@ impl<'a> MiniMetaPacket<'a> {
         _: u8,
         _: bool,
         _: &'a Option<Vec<u8>>,
+        _: Option<(u32, u32)>,
     ) -> Self {
         MiniMetaPacket { _foo: &None }
     }
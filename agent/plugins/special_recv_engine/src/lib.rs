@@ -16,7 +16,10 @@
 
 //! Enterprise Edition Feature: windows-dispatcher
 
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use std::time::Duration;
 
 use public::counter;
@@ -34,10 +37,20 @@ impl counter::RefCountable for LibpcapCounter {
     }
 }
 
+// Settings of one pcap handle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PcapHandleOptions {
+    pub snap_len: usize,
+    // kernel buffer size in bytes
+    pub buffer_size: usize,
+    pub immediate_mode: bool,
+    pub promisc: bool,
+}
+
 pub struct Libpcap;
 
 impl Libpcap {
-    pub fn new(_: Vec<(&str, isize)>, _: usize, _: usize, _: &QueueDebugger) -> Result<Self> {
+    pub fn new(_: Vec<(&str, isize, PcapHandleOptions)>, _: &QueueDebugger) -> Result<Self> {
         unimplemented!();
     }
 
@@ -54,6 +67,72 @@ impl Libpcap {
     }
 }
 
+// RSS hash fields, same values as RTE_ETH_RSS_*
+pub const RSS_HASH_IPV4: u64 = 1 << 2;
+pub const RSS_HASH_IPV4_TCP: u64 = 1 << 4;
+pub const RSS_HASH_IPV4_UDP: u64 = 1 << 5;
+pub const RSS_HASH_IPV4_SCTP: u64 = 1 << 6;
+pub const RSS_HASH_IPV6: u64 = 1 << 8;
+pub const RSS_HASH_IPV6_TCP: u64 = 1 << 10;
+pub const RSS_HASH_IPV6_UDP: u64 = 1 << 11;
+pub const RSS_HASH_IPV6_SCTP: u64 = 1 << 12;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DpdkRssConf {
+    // empty key means the default key of the NIC
+    pub key: Vec<u8>,
+    pub hash_fields: u64,
+}
+
+// A DPDK port configured with `queue_count` RSS queues, the port is stopped and
+// released when the last queue engine holding it is dropped
+pub struct DpdkPort;
+
+impl DpdkPort {
+    pub fn open(_: u16, _: u16, _: &DpdkRssConf) -> Result<Self> {
+        unimplemented!();
+    }
+
+    pub fn port_id(&self) -> u16 {
+        unimplemented!();
+    }
+}
+
+#[derive(Default)]
+pub struct DpdkQueueCounter {
+    pub rx: AtomicU64,
+    pub rx_bytes: AtomicU64,
+    pub imissed: AtomicU64,
+    pub rx_nombuf: AtomicU64,
+}
+
+impl counter::RefCountable for DpdkQueueCounter {
+    fn get_counters(&self) -> Vec<counter::Counter> {
+        vec![
+            (
+                "rx",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rx_bytes",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.rx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "imissed",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.imissed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rx_nombuf",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.rx_nombuf.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
 pub struct Dpdk;
 
 impl Dpdk {
@@ -61,6 +140,15 @@ impl Dpdk {
         unimplemented!();
     }
 
+    // Polls a single RSS queue of `port`
+    pub fn new_queue(_: Arc<DpdkPort>, _: u16, _: usize) -> Self {
+        unimplemented!();
+    }
+
+    pub fn queue_counter(&self) -> Option<Arc<DpdkQueueCounter>> {
+        unimplemented!();
+    }
+
     pub unsafe fn read(&mut self) -> Result<packet::Packet<'_>> {
         unimplemented!();
     }
@@ -70,10 +158,45 @@ impl Dpdk {
     }
 }
 
+#[derive(Default)]
+pub struct VhostUserCounter {
+    pub rx: AtomicU64,
+    pub rx_bytes: AtomicU64,
+    pub reconnects: AtomicU64,
+}
+
+impl counter::RefCountable for VhostUserCounter {
+    fn get_counters(&self) -> Vec<counter::Counter> {
+        vec![
+            (
+                "rx",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rx_bytes",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.rx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "reconnects",
+                counter::CounterType::Counted,
+                counter::CounterValue::Unsigned(self.reconnects.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Backend of one vhost-user socket, it reconnects by itself when the socket is recreated.
+// Received packets are tagged with `if_index` to identify the socket.
 pub struct VhostUser;
 
 impl VhostUser {
-    pub fn new(_: String, _: usize) -> Self {
+    pub fn new(_: String, _: isize, _: usize) -> Self {
+        unimplemented!();
+    }
+
+    pub fn socket_counter(&self) -> Arc<VhostUserCounter> {
         unimplemented!();
     }
 
@@ -33,8 +33,8 @@ use deepflow_agent::debug::EbpfMessage;
 #[cfg(target_os = "linux")]
 use deepflow_agent::debug::PlatformMessage;
 use deepflow_agent::debug::{
-    Beacon, Client, Message, Module, PolicyMessage, RpcMessage, DEBUG_QUEUE_IDLE_TIMEOUT,
-    DEEPFLOW_AGENT_BEACON,
+    Beacon, CaptureMessage, Client, Message, Module, PolicyMessage, RpcMessage,
+    DEBUG_QUEUE_IDLE_TIMEOUT, DEEPFLOW_AGENT_BEACON,
 };
 use public::{consts::DEFAULT_CONTROLLER_PORT, debug::QueueMessage};
 
@@ -70,10 +70,21 @@ enum ControllerCmd {
     #[cfg(all(target_os = "linux", feature = "libtrace"))]
     /// get information about the ebpf
     Ebpf(EbpfCmd),
+    /// get information about the packet capture
+    Capture(CaptureCmd),
     /// get information about the deepflow-agent
     List,
 }
 
+#[derive(Parser)]
+struct CaptureCmd {
+    /// show effective snaplen, buffer size and immediate mode of libpcap interfaces
+    ///
+    /// eg: deepflow-agent-ctl capture --pcap-interfaces
+    #[clap(long)]
+    pcap_interfaces: bool,
+}
+
 #[derive(Parser)]
 struct QueueCmd {
     /// monitor module
@@ -118,6 +129,16 @@ struct PlatformCmd {
     /// eg: deepflow-agent-ctl platform --mac_mappings
     #[clap(short, long)]
     mac_mappings: bool,
+    /// show interfaces captured after interface_regex and interface_exclude_regex
+    ///
+    /// eg: deepflow-agent-ctl platform --tap-interfaces
+    #[clap(short, long)]
+    tap_interfaces: bool,
+    /// show interfaces of each dispatcher assigned by interface_dispatcher_assignments
+    ///
+    /// eg: deepflow-agent-ctl platform --dispatcher-interfaces
+    #[clap(short, long)]
+    dispatcher_interfaces: bool,
 }
 
 #[derive(Debug, Parser)]
@@ -156,6 +177,22 @@ enum EbpfSubCmd {
     Datadump(EbpfArgs),
     /// monitor cpdbg
     Cpdbg(EbpfArgs),
+    /// regenerate java symbol files
+    RefreshJavaSymbols(RefreshJavaSymbolsArgs),
+    /// show kernel eBPF capabilities and the features degraded by them
+    Capability,
+    /// show golang/openssl uprobe targets attached after retries, retrying or given up
+    UprobeTargets,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Parser)]
+struct RefreshJavaSymbolsArgs {
+    /// Java process id, all java processes if not set
+    ///
+    /// eg: deepflow-agent-ctl ebpf refresh-java-symbols 10001
+    #[clap(parse(try_from_str))]
+    pid: Option<u32>,
 }
 
 #[cfg(target_os = "linux")]
@@ -301,6 +338,7 @@ impl Controller {
             ControllerCmd::Policy(c) => self.policy(c),
             #[cfg(all(target_os = "linux", feature = "libtrace"))]
             ControllerCmd::Ebpf(c) => self.ebpf(c),
+            ControllerCmd::Capture(c) => self.capture(c),
         }
     }
 
@@ -614,6 +652,72 @@ impl Controller {
             }
         }
 
+        if c.tap_interfaces {
+            let msg = Message {
+                module: Module::Platform,
+                msg: PlatformMessage::TapInterfaces(None),
+            };
+            client.send_to(msg)?;
+            println!("Interface Index \t Name");
+
+            loop {
+                let Ok(res) = client.recv::<PlatformMessage>() else {
+                    continue;
+                };
+                match res {
+                    /*
+                    $ deepflow-agent-ctl -p 42700 platform --tap-interfaces
+                    Interface Index          Name
+                    2                        eth0
+                    15                       veth1a2b3c
+                    */
+                    PlatformMessage::TapInterfaces(Some(e)) => {
+                        for (idx, name) in e {
+                            println!("{:<15} \t {}", idx, name);
+                        }
+                    }
+                    PlatformMessage::TapInterfaces(None) => {
+                        return Err(anyhow!("tap interfaces is empty"))
+                    }
+                    PlatformMessage::Fin => return Ok(()),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        if c.dispatcher_interfaces {
+            let msg = Message {
+                module: Module::Platform,
+                msg: PlatformMessage::DispatcherInterfaces(None),
+            };
+            client.send_to(msg)?;
+            println!("Dispatcher \t Interface Index \t Name");
+
+            loop {
+                let Ok(res) = client.recv::<PlatformMessage>() else {
+                    continue;
+                };
+                match res {
+                    /*
+                    $ deepflow-agent-ctl -p 42700 platform --dispatcher-interfaces
+                    Dispatcher       Interface Index         Name
+                    0                2                       eth0
+                    1                15                      veth1a2b3c
+                    */
+                    PlatformMessage::DispatcherInterfaces(Some(e)) => {
+                        for (dispatcher, idx, name) in e {
+                            println!("{:<10} \t {:<15} \t {}", dispatcher, idx, name);
+                        }
+                    }
+                    PlatformMessage::DispatcherInterfaces(None) => {
+                        return Err(anyhow!("dispatcher interfaces is empty"))
+                    }
+                    PlatformMessage::Fin => return Ok(()),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
         if let Some(r) = c.k8s_get {
             if let Resource::Version = r {
                 let msg = Message {
@@ -672,6 +776,51 @@ impl Controller {
         Ok(())
     }
 
+    fn capture(&self, c: CaptureCmd) -> Result<()> {
+        if self.port.is_none() {
+            return Err(anyhow!(ERR_PORT_MSG));
+        }
+        let mut client = self.new_client()?;
+        if c.pcap_interfaces {
+            let msg = Message {
+                module: Module::Capture,
+                msg: CaptureMessage::PcapInterfaces(None),
+            };
+            client.send_to(msg)?;
+            println!(
+                "{:<32} {:<10} {:<12} {}",
+                "Name", "Snaplen", "Buffer", "Immediate"
+            );
+
+            loop {
+                let Ok(res) = client.recv::<CaptureMessage>() else {
+                    continue;
+                };
+                match res {
+                    /*
+                    $ deepflow-agent-ctl -p 42700 capture --pcap-interfaces
+                    Name                             Snaplen    Buffer       Immediate  Promisc
+                    Ethernet 2                       128        16777216     false      true
+                    Management                       65535      67108864     true       false
+                    */
+                    CaptureMessage::PcapInterfaces(Some(e)) => {
+                        for s in e {
+                            println!(
+                                "{:<32} {:<10} {:<12} {:<10} {}",
+                                s.name, s.snap_len, s.buffer_size, s.immediate_mode, s.promisc
+                            );
+                        }
+                    }
+                    CaptureMessage::PcapInterfaces(None) => {
+                        return Err(anyhow!("pcap interfaces is empty"))
+                    }
+                    CaptureMessage::Fin => return Ok(()),
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn policy(&self, c: PolicyCmd) -> Result<()> {
         if self.port.is_none() {
             return Err(anyhow!(ERR_PORT_MSG));
@@ -765,6 +914,24 @@ impl Controller {
                     msg: EbpfMessage::DataDump((arg.pid, arg.name, arg.proto, arg.duration)),
                 })?;
             }
+            EbpfSubCmd::RefreshJavaSymbols(arg) => {
+                client.send_to(Message {
+                    module: Module::Ebpf,
+                    msg: EbpfMessage::RefreshJavaSymbols(arg.pid.unwrap_or_default()),
+                })?;
+            }
+            EbpfSubCmd::Capability => {
+                client.send_to(Message {
+                    module: Module::Ebpf,
+                    msg: EbpfMessage::Capability,
+                })?;
+            }
+            EbpfSubCmd::UprobeTargets => {
+                client.send_to(Message {
+                    module: Module::Ebpf,
+                    msg: EbpfMessage::UprobeTargets,
+                })?;
+            }
         }
 
         loop {
@@ -775,6 +942,55 @@ impl Controller {
                 EbpfMessage::Context((seq, c)) => {
                     println!("SEQ {}: {}", seq, String::from_utf8_lossy(&c))
                 }
+                EbpfMessage::JavaSymbolsRefreshed(r) => match r.error {
+                    Some(e) => println!("PID {}: refresh failed: {}", r.pid, e),
+                    None => println!(
+                        "PID {}: {} bytes written in {:?}{}",
+                        r.pid,
+                        r.bytes,
+                        Duration::from_micros(r.duration_us),
+                        if r.coalesced {
+                            " (coalesced with a refresh in progress)"
+                        } else {
+                            ""
+                        }
+                    ),
+                },
+                EbpfMessage::UprobeTarget(t) => {
+                    println!(
+                        "PID {} {} {}: {} attempts:{} inode:{} hash:{:#x}{}{}",
+                        t.pid,
+                        t.target,
+                        t.path,
+                        t.state,
+                        t.attempts,
+                        t.inode,
+                        t.hash,
+                        t.next_retry_in
+                            .map(|s| format!(" next retry in {s}s"))
+                            .unwrap_or_default(),
+                        if t.reason.is_empty() {
+                            "".to_owned()
+                        } else {
+                            format!(" reason: {}", t.reason)
+                        }
+                    )
+                }
+                EbpfMessage::CapabilityReport(None) => {
+                    println!("ebpf collector is not started")
+                }
+                EbpfMessage::CapabilityReport(Some(r)) => {
+                    println!("btf_vmlinux:       {}", r.btf_vmlinux);
+                    println!("perf_event_array:  {}", r.perf_event_array);
+                    println!("ring_buffer:       {}", r.ring_buffer);
+                    println!("probe_read_kernel: {}", r.probe_read_kernel);
+                    println!("kprobe:            {}", r.kprobe);
+                    println!("uprobe:            {}", r.uprobe);
+                    println!("tracepoint:        {}", r.tracepoint);
+                    println!("perf_event:        {}", r.perf_event);
+                    println!("fentry:            {}", r.fentry);
+                    println!("degraded features: {:?}", r.degraded_features);
+                }
                 EbpfMessage::Done => return Ok(()),
                 EbpfMessage::Error(e) => {
                     println!("{}", e);
@@ -70,6 +70,9 @@ pub struct CollectorCounter {
     stash_len: AtomicU64,
     stash_capacity: AtomicU64,
     stash_shrinks: AtomicU64,
+    drop_by_shedding: AtomicU64,
+    shedding_starts: AtomicU64,
+    shedding_stops: AtomicU64,
     running: Arc<AtomicBool>,
 }
 
@@ -126,10 +129,69 @@ impl RefCountable for CollectorCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.stash_shrinks.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "drop-by-shedding",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.drop_by_shedding.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "shedding-starts",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.shedding_starts.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "shedding-stops",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.shedding_stops.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 }
 
+// Sheds second metrics while the sender queue is congested
+//
+// Shedding starts when the occupancy of the sender queue stays above the high watermark for
+// `second_metrics_shedding_intervals` consecutive flushes, and stops once it falls below the
+// low watermark. Minute metrics are aggregated by their own collectors and are not affected.
+#[derive(Default)]
+struct SecondMetricsShedding {
+    shedding: bool,
+    congested_intervals: u32,
+}
+
+impl SecondMetricsShedding {
+    // returns the new state on transitions
+    fn update(&mut self, len: usize, capacity: usize, config: &CollectorConfig) -> Option<bool> {
+        if !config.second_metrics_shedding_enabled {
+            self.congested_intervals = 0;
+            if self.shedding {
+                self.shedding = false;
+                return Some(false);
+            }
+            return None;
+        }
+        let occupancy = len * 100 / capacity.max(1);
+        if self.shedding {
+            if occupancy < config.second_metrics_shedding_low_watermark as usize {
+                self.shedding = false;
+                return Some(false);
+            }
+            return None;
+        }
+        if occupancy <= config.second_metrics_shedding_high_watermark as usize {
+            self.congested_intervals = 0;
+            return None;
+        }
+        self.congested_intervals += 1;
+        if self.congested_intervals < config.second_metrics_shedding_intervals {
+            return None;
+        }
+        self.congested_intervals = 0;
+        self.shedding = true;
+        Some(true)
+    }
+}
+
 #[derive(Hash, PartialEq, Eq)]
 struct StashKey {
     fast_id: u128,
@@ -342,6 +404,8 @@ struct Stash {
     stash_init_capacity: usize,
     global_thread_id: u8,
     doc_flag: DocumentFlag,
+    // only for second metrics
+    shedding: Option<SecondMetricsShedding>,
     context: Context,
 }
 
@@ -378,6 +442,7 @@ impl Stash {
             history_length: [0; Self::HISTORY_RECORD_COUNT].into(),
             stash_init_capacity,
             doc_flag,
+            shedding: (ctx.metric_type == MetricsType::SECOND).then(SecondMetricsShedding::default),
             context: ctx,
         }
     }
@@ -421,7 +486,7 @@ impl Stash {
                             None
                         }
                     });
-            self.flush_stats();
+            self.flush_stats(config);
             debug!("collector window moved interval={:?} is_tick={} sys_ts={:?} flow_ts={} window={:?}", self.slot_interval, false, timestamp, time_in_second, self.start_time);
             self.start_time = Duration::from_secs(time_in_second);
         }
@@ -465,7 +530,7 @@ impl Stash {
                     ..Default::default()
                 };
                 let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
-                self.add(key, tagger, Meter::Usage(usage_meter));
+                self.add(key, tagger, Meter::Usage(usage_meter), 0);
             }
             let id_map = &acc_flow.id_maps[1];
             for (&acl_gid, &ip_id) in id_map.iter() {
@@ -487,7 +552,7 @@ impl Stash {
                     ..Default::default()
                 };
                 let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
-                self.add(key, tagger, Meter::Usage(usage_meter));
+                self.add(key, tagger, Meter::Usage(usage_meter), 0);
             }
         }
 
@@ -506,6 +571,7 @@ impl Stash {
         directions: &[Direction; 2],
         config: &CollectorConfig,
     ) {
+        let sampling_rate = acc_flow.flow.sampling_rate;
         // edge_stats: If both ends of direction are None or not None, record the
         // statistical data with direction=0 (corresponding tap-side=rest)
         if Direction::from(directions) == Direction::None {
@@ -529,7 +595,7 @@ impl Stash {
                 false,
                 self.context.agent_mode,
             );
-            self.fill_edge_l4_stats(tagger, acc_flow.flow_meter);
+            self.fill_edge_l4_stats(tagger, acc_flow.flow_meter, sampling_rate);
             return;
         }
 
@@ -564,7 +630,7 @@ impl Stash {
                     false,
                     self.context.agent_mode,
                 );
-                self.fill_single_l4_stats(tagger, flow_meter);
+                self.fill_single_l4_stats(tagger, flow_meter, sampling_rate);
             }
             let tagger = get_edge_tagger(
                 self.global_thread_id,
@@ -582,11 +648,11 @@ impl Stash {
             );
             // edge_stats: If the direction of a certain end is known, the statistical data
             // will be recorded with the direction (corresponding tap-side), up to two times
-            self.fill_edge_l4_stats(tagger, acc_flow.flow_meter);
+            self.fill_edge_l4_stats(tagger, acc_flow.flow_meter, sampling_rate);
         }
     }
 
-    fn fill_single_l4_stats(&mut self, tagger: Tagger, flow_meter: FlowMeter) {
+    fn fill_single_l4_stats(&mut self, tagger: Tagger, flow_meter: FlowMeter, sampling_rate: u32) {
         // We collect the single-ended metrics data from Packet, XFlow, EBPF, Otel to the table (vtap_app_port).
         // In the case of signal_source grouping, the single_stats data is not duplicate.
         // Only data whose direction is c|s|local|None has flow_meter.
@@ -596,18 +662,18 @@ impl Stash {
             || tagger.direction == Direction::None
         {
             let key = StashKey::new(&tagger, tagger.ip, None, 0);
-            self.add(key, tagger, Meter::Flow(flow_meter));
+            self.add(key, tagger, Meter::Flow(flow_meter), sampling_rate);
         }
     }
 
-    fn fill_edge_l4_stats(&mut self, tagger: Tagger, flow_meter: FlowMeter) {
+    fn fill_edge_l4_stats(&mut self, tagger: Tagger, flow_meter: FlowMeter, sampling_rate: u32) {
         // network metrics (vtap_flow_edge_port)
         // Packet data and XFlow data have L4 info
         if tagger.signal_source == SignalSource::Packet
             || tagger.signal_source == SignalSource::XFlow
         {
             let key = StashKey::new(&tagger, tagger.ip, Some(tagger.ip1), 0);
-            self.add(key, tagger, Meter::Flow(flow_meter));
+            self.add(key, tagger, Meter::Flow(flow_meter), sampling_rate);
         }
     }
 
@@ -664,7 +730,7 @@ impl Stash {
                             None
                         }
                     });
-            self.flush_stats();
+            self.flush_stats(config);
             debug!("l7 collector window moved interval={:?} is_tick={} sys_ts={:?} flow_ts={} window={:?}", self.slot_interval, false, timestamp, time_in_second, self.start_time);
             self.start_time = Duration::from_secs(time_in_second);
         }
@@ -791,7 +857,7 @@ impl Stash {
                 || tagger.signal_source != SignalSource::Packet
             {
                 let key = StashKey::new(&tagger, tagger.ip, None, endpoint_hash);
-                self.add(key, tagger, Meter::App(app_meter));
+                self.add(key, tagger, Meter::App(app_meter), 0);
             }
         }
     }
@@ -801,7 +867,7 @@ impl Stash {
         // application metrics (vtap_app_edge_port)
         if tagger.l7_protocol != L7Protocol::Unknown || tagger.signal_source == SignalSource::OTel {
             let key = StashKey::new(&tagger, tagger.ip, Some(tagger.ip1), endpoint_hash);
-            self.add(key, tagger, Meter::App(app_meter));
+            self.add(key, tagger, Meter::App(app_meter), 0);
         }
     }
 
@@ -818,24 +884,68 @@ impl Stash {
         }
     }
 
-    fn add(&mut self, key: StashKey, tagger: Tagger, meter: Meter) {
-        match self.inner.entry(key) {
-            Entry::Occupied(mut o) => {
-                let doc = o.get_mut();
+    // `sampling_rate` is the xFlow sampling rate of the meter, 0 if not sampled
+    fn add(&mut self, key: StashKey, tagger: Tagger, meter: Meter, sampling_rate: u32) {
+        let doc = match self.inner.entry(key) {
+            Entry::Occupied(o) => {
+                let doc = o.into_mut();
                 doc.meter.sequential_merge(&meter);
+                doc
             }
             Entry::Vacant(o) => {
                 let mut doc = Document::new(meter);
                 doc.tagger = tagger;
-                o.insert(doc);
+                o.insert(doc)
+            }
+        };
+        if sampling_rate > 0 {
+            doc.flags |= DocumentFlag::SAMPLED;
+            doc.sampling_rate = doc.sampling_rate.max(sampling_rate);
+        }
+    }
+
+    // returns whether the second metrics of this interval are shed
+    fn shed_second_metrics(&mut self, config: &CollectorConfig) -> bool {
+        let Some(shedding) = self.shedding.as_mut() else {
+            return false;
+        };
+        let (len, capacity) = (self.sender.len(), self.sender.capacity());
+        match shedding.update(len, capacity, config) {
+            Some(true) => {
+                warn!(
+                    "{} id=({}) start shedding second metrics, sender queue occupancy {}/{} stays above {}% for {} intervals",
+                    self.context.name,
+                    self.context.id,
+                    len,
+                    capacity,
+                    config.second_metrics_shedding_high_watermark,
+                    config.second_metrics_shedding_intervals
+                );
+                self.counter.shedding_starts.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(false) => {
+                info!(
+                    "{} id=({}) stop shedding second metrics, sender queue occupancy {}/{}",
+                    self.context.name, self.context.id, len, capacity
+                );
+                self.counter.shedding_stops.fetch_add(1, Ordering::Relaxed);
             }
+            None => (),
         }
+        shedding.shedding
     }
 
-    fn flush_stats(&mut self) {
+    fn flush_stats(&mut self, config: &CollectorConfig) {
         self.history_length.rotate_right(1);
         self.history_length[0] = self.inner.len();
 
+        if self.shed_second_metrics(config) {
+            self.counter
+                .drop_by_shedding
+                .fetch_add(self.inner.len() as u64, Ordering::Relaxed);
+            self.inner.clear();
+        }
+
         let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
         for (_, mut doc) in self.inner.drain() {
             if batch.len() >= QUEUE_BATCH_SIZE {
@@ -1305,7 +1415,7 @@ impl Collector {
                         Err(Error::BatchTooLarge(_)) => unreachable!(),
                     }
                 }
-                stash.flush_stats();
+                stash.flush_stats(&config.load());
             })
             .unwrap();
 
@@ -1443,7 +1553,7 @@ impl L7Collector {
                         Err(Error::BatchTooLarge(_)) => unreachable!(),
                     }
                 }
-                stash.flush_stats();
+                stash.flush_stats(&config.load());
             })
             .unwrap();
 
@@ -1487,7 +1597,9 @@ mod tests {
 
     use std::collections::HashSet;
 
-    use crate::common::enums::CaptureNetworkType;
+    use public::{debug::QueueDebugger, proto::metric, queue};
+
+    use crate::{common::enums::CaptureNetworkType, config::ModuleConfig};
 
     use super::*;
 
@@ -1583,4 +1695,118 @@ mod tests {
         let key = StashKey::new(&tagger, Ipv4Addr::UNSPECIFIED.into(), None, 0);
         assert_eq!(map.insert(key), true);
     }
+
+    fn test_stash(
+        metric_type: MetricsType,
+        sender: DebugSender<BoxedDocument>,
+        counter: Arc<CollectorCounter>,
+    ) -> Stash {
+        let ctx = Context {
+            id: 0,
+            name: "test_collector",
+            delay_seconds: 0,
+            metric_type,
+            ntp_diff: Arc::new(AtomicI64::new(0)),
+            agent_mode: RunningMode::Managed,
+        };
+        Stash::new(ctx, sender, counter)
+    }
+
+    fn add_docs(stash: &mut Stash, count: u64) {
+        for i in 0..count {
+            let key = StashKey {
+                endpoint_hash: i,
+                ..Default::default()
+            };
+            stash.add(key, Tagger::default(), Meter::Flow(FlowMeter::default()), 0);
+        }
+    }
+
+    #[test]
+    fn sampled_documents() {
+        let queue_debugger = QueueDebugger::new();
+        let (sender, _receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let mut stash = test_stash(MetricsType::MINUTE, sender, Default::default());
+        let sampled_key = || StashKey {
+            endpoint_hash: 1,
+            ..Default::default()
+        };
+        let flow_meter = || Meter::Flow(FlowMeter::default());
+        stash.add(StashKey::default(), Tagger::default(), flow_meter(), 0);
+        // meters of xFlow sources with different sampling rates are merged into one document
+        for sampling_rate in [100, 400, 0] {
+            stash.add(
+                sampled_key(),
+                Tagger::default(),
+                flow_meter(),
+                sampling_rate,
+            );
+        }
+
+        let doc = &stash.inner[&StashKey::default()];
+        assert!(!doc.flags.contains(DocumentFlag::SAMPLED));
+        assert_eq!(doc.sampling_rate, 0);
+        let doc = stash.inner.remove(&sampled_key()).unwrap();
+        assert!(doc.flags.contains(DocumentFlag::SAMPLED));
+        let pb_doc: metric::Document = doc.into();
+        assert_eq!(pb_doc.sampling_rate, 400);
+    }
+
+    #[test]
+    fn shed_second_metrics_under_pressure() {
+        let mut config = ModuleConfig::default().collector;
+        config.second_metrics_shedding_enabled = true;
+        config.second_metrics_shedding_high_watermark = 50;
+        config.second_metrics_shedding_low_watermark = 25;
+        config.second_metrics_shedding_intervals = 2;
+
+        let queue_debugger = QueueDebugger::new();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let second_counter = Arc::new(CollectorCounter::default());
+        let mut second = test_stash(MetricsType::SECOND, sender.clone(), second_counter.clone());
+        let minute_counter = Arc::new(CollectorCounter::default());
+        let mut minute = test_stash(MetricsType::MINUTE, sender.clone(), minute_counter.clone());
+
+        // 4 second documents are produced every interval, and only 2 are consumed
+        let (mut second_received, mut minute_received) = (0, 0);
+        let mut count = |docs: Vec<BoxedDocument>| {
+            for doc in docs {
+                if doc.0.flags.contains(DocumentFlag::PER_SECOND_METRICS) {
+                    second_received += 1;
+                } else {
+                    minute_received += 1;
+                }
+            }
+        };
+        for interval in 0..14 {
+            add_docs(&mut second, 4);
+            second.flush_stats(&config);
+            if interval == 8 {
+                add_docs(&mut minute, 4);
+                minute.flush_stats(&config);
+            }
+            count(receiver.recv_n(2, Some(Duration::from_millis(10))).unwrap());
+        }
+        while let Ok(docs) = receiver.recv_n(16, Some(Duration::from_millis(10))) {
+            count(docs);
+        }
+
+        // shedding starts at the 7th interval with occupancy above 50% twice, and stops at
+        // the 14th below 25%
+        assert_eq!(second_counter.shedding_starts.load(Ordering::Relaxed), 1);
+        assert_eq!(second_counter.shedding_stops.load(Ordering::Relaxed), 1);
+        assert_eq!(second_counter.drop_by_shedding.load(Ordering::Relaxed), 28);
+        assert_eq!(second_received, 14 * 4 - 28);
+        // minute documents are intact
+        assert_eq!(minute_received, 4);
+        assert_eq!(minute_counter.drop_by_shedding.load(Ordering::Relaxed), 0);
+        assert_eq!(sender.total_overwritten_count(), 0);
+
+        // off unless enabled
+        config.second_metrics_shedding_enabled = false;
+        let mut shedding = SecondMetricsShedding::default();
+        for _ in 0..10 {
+            assert_eq!(shedding.update(16, 16, &config), None);
+        }
+    }
 }
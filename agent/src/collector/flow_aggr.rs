@@ -26,7 +26,6 @@ use thread::JoinHandle;
 use arc_swap::access::Access;
 use log::{debug, info, warn};
 use npb_pcap_policy::NpbTunnelType;
-use rand::prelude::{Rng, SeedableRng, SmallRng};
 
 use super::consts::*;
 
@@ -34,16 +33,18 @@ use crate::collector::types::U16Set;
 use crate::common::Timestamp;
 use crate::common::{
     enums::CaptureNetworkType,
-    flow::{CloseType, HeartbeatAggrKey, PacketDirection},
+    flow::{CloseType, Flow, HeartbeatAggrKey, PacketDirection},
     tagged_flow::{BoxedTaggedFlow, TaggedFlow},
 };
 use crate::config::handler::{CollectorAccess, CollectorConfig};
+use crate::metric::document::TapSide;
 use crate::rpc::get_timestamp;
 use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
 use public::{
     buffer::BatchedBox,
     chrono_map::ChronoMap,
     queue::{DebugSender, Error, Receiver},
+    throttle::{Throttle, ThrottleCounter},
 };
 
 const TIMESTAMP_SLOT_COUNT: usize = SECONDS_IN_MINUTE as usize;
@@ -60,6 +61,55 @@ pub struct FlowAggrCounter {
     stash_shrinks: AtomicU64,
     heartbeat_aggred: AtomicU64,
     heartbeat_cached: AtomicU64,
+    // registered as flow_log_throttle
+    pub throttle: Arc<ThrottleCounter>,
+    // one for each observation point, registered as flow_log_sampling
+    pub sampling: Vec<Arc<SamplingCounter>>,
+}
+
+#[derive(Debug, Default)]
+pub struct SamplingCounter {
+    sampled_out: AtomicU64,
+}
+
+impl RefCountable for SamplingCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        let sampled_out = self.sampled_out.swap(0, Ordering::Relaxed);
+        // most observation points are not sampled, report nothing for them
+        if sampled_out == 0 {
+            return vec![];
+        }
+        vec![(
+            "sampled-out",
+            CounterType::Counted,
+            CounterValue::Unsigned(sampled_out),
+        )]
+    }
+}
+
+// Returns the ratio of the flow sampled by `outputs.flow_log.filters.l4_observation_point_sampling_ratios`,
+// flows with errors and flows required to be stored are not sampled
+fn sampling_ratio(config: &CollectorConfig, flow: &Flow) -> Option<f32> {
+    let ratio = config.l4_log_sampling_ratios[flow.tap_side as usize];
+    if ratio >= 1.0
+        || flow.need_to_store
+        || flow.close_type.is_client_error()
+        || flow.close_type.is_server_error()
+    {
+        return None;
+    }
+    Some(ratio)
+}
+
+// Sampling is keyed on the flow id, so that all the reports of a flow in both directions share the
+// same fate. Flow ids are allocated sequentially, mix the bits before comparing with the ratio.
+fn sampled_in(flow_id: u64, ratio: f32) -> bool {
+    let mut h = flow_id ^ (flow_id >> 33);
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^= h >> 33;
+    ((h >> 40) as f32) < ratio * (1 << 24) as f32
 }
 
 pub struct FlowAggrThread {
@@ -87,7 +137,12 @@ impl FlowAggrThread {
         ntp_diff: Arc<AtomicI64>,
     ) -> (Self, Arc<FlowAggrCounter>) {
         let running = Arc::new(AtomicBool::new(false));
-        let metrics = Arc::new(FlowAggrCounter::default());
+        let metrics = Arc::new(FlowAggrCounter {
+            sampling: (0..=TapSide::MAX as usize)
+                .map(|_| Arc::new(SamplingCounter::default()))
+                .collect(),
+            ..Default::default()
+        });
         (
             Self {
                 id,
@@ -357,6 +412,15 @@ impl FlowAggr {
                                 [u16::from(tagged_flow.flow.flow_key.tap_type) as usize]
                             || tagged_flow.flow.need_to_store
                         {
+                            let flow = &tagged_flow.flow;
+                            if let Some(ratio) = sampling_ratio(&config, flow) {
+                                if !sampled_in(flow.flow_id, ratio) {
+                                    self.metrics.sampling[flow.tap_side as usize]
+                                        .sampled_out
+                                        .fetch_add(1, Ordering::Relaxed);
+                                    continue;
+                                }
+                            }
                             self.minute_merge(&config, tagged_flow);
                         }
                     }
@@ -441,7 +505,7 @@ impl Sender {
         metrics: Arc<FlowAggrCounter>,
     ) -> Self {
         Self {
-            output: ThrottlingQueue::new(output, config.clone()),
+            output: ThrottlingQueue::new(output, config.clone(), metrics.throttle.clone()),
             ntp_diff,
             config,
             metrics,
@@ -471,6 +535,10 @@ impl Sender {
 
         f.flow.acl_gids = Vec::from(acl_gids.list());
 
+        if let Some(ratio) = sampling_ratio(&self.config.load(), &f.flow) {
+            f.flow.flow_log_sampling_ratio = ratio;
+        }
+
         if !f.flow.is_new_flow {
             f.flow.start_time = Timestamp::from_secs(f.flow.start_time_in_minute());
         }
@@ -488,7 +556,7 @@ impl Sender {
         if f.flow.hit_pcap_policy() {
             self.output.send_without_throttling(f);
         } else {
-            if !self.output.send_with_throttling(f) {
+            if !self.output.send_with_throttling(&now, f) {
                 self.metrics
                     .drop_in_throttle
                     .fetch_add(1, Ordering::Relaxed);
@@ -499,81 +567,58 @@ impl Sender {
 
 struct ThrottlingQueue {
     config: CollectorAccess,
-    throttle: u64,
-
-    small_rng: SmallRng,
+    throttle: Throttle<BoxedTaggedFlow>,
+    l4_log_collect_nps_threshold: u64,
 
     last_flush_cache_with_throttling_time: Duration,
     last_flush_cache_without_throttling_time: Duration,
-    period_count: usize,
     output: DebugSender<BoxedTaggedFlow>,
 
-    cache_with_throttling: Vec<BoxedTaggedFlow>,
     cache_without_throttling: Vec<BoxedTaggedFlow>,
 }
 
 impl ThrottlingQueue {
-    const THROTTLE_BUCKET_BITS: u64 = 2;
-    const THROTTLE_BUCKET: u64 = 1 << Self::THROTTLE_BUCKET_BITS; // 2^N。由于发送方是有突发的，需要累积一定时间做采样
-    const MIN_L4_LOG_COLLECT_NPS_THRESHOLD: u64 = 100;
-    const MAX_L4_LOG_COLLECT_NPS_THRESHOLD: u64 = 1000000;
+    const FLUSH_INTERVAL_BITS: u64 = 2; // caches are flushed every 2^N seconds
     const CACHE_WITHOUT_THROTTLING_SIZE: usize = 1024;
 
-    pub fn new(output: DebugSender<BoxedTaggedFlow>, config: CollectorAccess) -> Self {
-        let t: u64 = config.load().l4_log_collect_nps_threshold * Self::THROTTLE_BUCKET;
+    pub fn new(
+        output: DebugSender<BoxedTaggedFlow>,
+        config: CollectorAccess,
+        throttle_counter: Arc<ThrottleCounter>,
+    ) -> Self {
+        let threshold = config.load().l4_log_collect_nps_threshold;
         Self {
             config,
-            throttle: t,
-
-            small_rng: SmallRng::from_entropy(),
+            throttle: Throttle::new(threshold, output.clone(), throttle_counter),
+            l4_log_collect_nps_threshold: threshold,
 
             last_flush_cache_with_throttling_time: Duration::ZERO,
             last_flush_cache_without_throttling_time: Duration::ZERO,
-            period_count: 0,
 
             output,
-            cache_with_throttling: Vec::with_capacity(t as usize),
             cache_without_throttling: Vec::with_capacity(Self::CACHE_WITHOUT_THROTTLING_SIZE),
         }
     }
 
     fn flush_cache_with_throttling(&mut self, now: &Duration) {
-        if now.as_secs() >> Self::THROTTLE_BUCKET_BITS
-            != self.last_flush_cache_with_throttling_time.as_secs() >> Self::THROTTLE_BUCKET_BITS
+        if now.as_secs() >> Self::FLUSH_INTERVAL_BITS
+            != self.last_flush_cache_with_throttling_time.as_secs() >> Self::FLUSH_INTERVAL_BITS
         {
             self.update_throttle();
-            if let Err(e) = self.output.send_all(&mut self.cache_with_throttling) {
-                debug!(
-                    "l4 flow throttle push aggred flow to sender queue failed, because {:?}",
-                    e
-                );
-                self.cache_with_throttling.clear();
-            }
-
+            self.throttle.flush();
             self.last_flush_cache_with_throttling_time = *now;
-            self.period_count = 0;
         }
     }
 
-    pub fn send_with_throttling(&mut self, f: Box<TaggedFlow>) -> bool {
-        self.period_count = self.period_count.saturating_add(1);
-        if self.cache_with_throttling.len() < self.throttle as usize {
-            self.cache_with_throttling.push(BoxedTaggedFlow(f));
-            true
-        } else {
-            let r = self.small_rng.gen_range(0..self.period_count);
-            if r < self.throttle as usize {
-                self.cache_with_throttling[r] = BoxedTaggedFlow(f);
-            }
-            false
-        }
+    pub fn send_with_throttling(&mut self, now: &Duration, f: Box<TaggedFlow>) -> bool {
+        self.throttle.send(*now, BoxedTaggedFlow(f))
     }
 
     fn flush_cache_without_throttling(&mut self, now: &Duration) {
         if self.cache_without_throttling.len() >= Self::CACHE_WITHOUT_THROTTLING_SIZE
-            || now.as_secs() >> Self::THROTTLE_BUCKET_BITS
+            || now.as_secs() >> Self::FLUSH_INTERVAL_BITS
                 != self.last_flush_cache_without_throttling_time.as_secs()
-                    >> Self::THROTTLE_BUCKET_BITS
+                    >> Self::FLUSH_INTERVAL_BITS
         {
             if let Err(e) = self.output.send_all(&mut self.cache_without_throttling) {
                 debug!(
@@ -593,27 +638,82 @@ impl ThrottlingQueue {
 
     pub fn update_throttle(&mut self) {
         let new = self.config.load().l4_log_collect_nps_threshold;
-        if new < Self::MIN_L4_LOG_COLLECT_NPS_THRESHOLD
-            || new > Self::MAX_L4_LOG_COLLECT_NPS_THRESHOLD
-        {
-            debug!(
-                "l4 flow throttle {} is invalid, must in range[{}, {}]",
-                new,
-                Self::MIN_L4_LOG_COLLECT_NPS_THRESHOLD,
-                Self::MAX_L4_LOG_COLLECT_NPS_THRESHOLD
-            );
-            return;
-        }
-        if self.throttle == new * Self::THROTTLE_BUCKET {
+        if self.l4_log_collect_nps_threshold == new {
             return;
         }
 
         info!(
             "l4_log_collect_nps_threshold update from {} to  {}",
-            self.throttle / Self::THROTTLE_BUCKET,
-            new
+            self.l4_log_collect_nps_threshold, new
         );
-        self.throttle = new * Self::THROTTLE_BUCKET;
-        self.cache_with_throttling.truncate(self.throttle as usize);
+        self.l4_log_collect_nps_threshold = new;
+        self.throttle.set_rate(new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arc_swap::{access::Map, ArcSwap};
+    use public::{debug::QueueDebugger, queue};
+
+    use crate::config::ModuleConfig;
+
+    #[test]
+    fn throttle_rate() {
+        let mut module_config = ModuleConfig::default();
+        module_config.collector.l4_log_collect_nps_threshold = 1000;
+        let current_config = Arc::new(ArcSwap::from_pointee(module_config));
+        let access: CollectorAccess = Map::new(current_config, |config| &config.collector);
+
+        let queue_debugger = QueueDebugger::new();
+        let (output_sender, output_receiver, _) =
+            queue::bounded_with_debug(1 << 14, "", &queue_debugger);
+        let counter = Arc::new(ThrottleCounter::default());
+        let mut queue = ThrottlingQueue::new(output_sender, access, counter);
+
+        // twice the rate for 10 seconds, the same load and drops as l7 flow logs
+        let mut dropped = 0;
+        for i in 0..20000u64 {
+            let now = Duration::from_micros(i * 500);
+            if !queue.send_with_throttling(&now, Box::new(TaggedFlow::default())) {
+                dropped += 1;
+            }
+        }
+        queue.throttle.flush();
+        assert_eq!(dropped, 9001);
+
+        let mut output = Vec::with_capacity(1 << 14);
+        let _ = output_receiver.recv_all(&mut output, Some(Duration::from_secs(1)));
+        assert_eq!(output.len(), 20000 - dropped);
+    }
+
+    #[test]
+    fn sample_by_observation_point() {
+        let mut module_config = ModuleConfig::default();
+        module_config.collector.l4_log_sampling_ratios[TapSide::Rest as usize] = 0.1;
+        let config = &module_config.collector;
+
+        let mut flow = Flow::default();
+        flow.tap_side = TapSide::Rest;
+        flow.close_type = CloseType::Finish;
+        assert_eq!(sampling_ratio(config, &flow), Some(0.1));
+        let kept = (0..100000u64).filter(|id| sampled_in(*id, 0.1)).count();
+        assert!(kept > 9000 && kept < 11000, "kept {}", kept);
+        // the same flow id always shares the same fate
+        for id in 0..1000u64 {
+            assert_eq!(sampled_in(id, 0.1), sampled_in(id, 0.1));
+        }
+        assert!((0..1000u64).all(|id| sampled_in(id, 1.0)));
+
+        flow.close_type = CloseType::TcpServerRst;
+        assert_eq!(sampling_ratio(config, &flow), None);
+        flow.close_type = CloseType::Finish;
+        flow.need_to_store = true;
+        assert_eq!(sampling_ratio(config, &flow), None);
+        flow.need_to_store = false;
+        flow.tap_side = TapSide::Client;
+        assert_eq!(sampling_ratio(config, &flow), None);
     }
 }
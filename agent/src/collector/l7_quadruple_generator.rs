@@ -296,7 +296,7 @@ impl SubQuadGen {
             .min(self.stashs.len() - 1);
         let current_span = time_in_second.as_secs() / self.slot_interval;
         let request_span =
-            (time_in_second.as_secs() - l7_stats.time_span as u64) / self.slot_interval;
+            l7_stats.time_in_second.min(time_in_second).as_secs() / self.slot_interval;
         let time_span = (current_span - request_span) as u32;
         let stash = &mut self.stashs[slot];
         let value = stash.meters.get_mut(&l7_stats.flow_id);
@@ -717,7 +717,10 @@ impl L7QuadrupleGenerator {
                 Ok(_) => {
                     if config.enabled {
                         for l7_stat in l7_recv_batch.drain(..) {
-                            let time_in_second = l7_stat.time_in_second;
+                            // the window moves with the raw capture time, and the stats
+                            // are accounted to time_in_second by inject_app_meter
+                            let time_in_second =
+                                Duration::from_secs(l7_stat.capture_time.as_secs());
                             self.handle(&config, Some(l7_stat), time_in_second);
                         }
                         if let Some(q) = self.second_quad_gen.as_mut() {
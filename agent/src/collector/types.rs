@@ -45,6 +45,7 @@ pub struct MiniFlow {
     pub otel_instance: Option<String>,
     pub pod_id: u32,
     pub direction_score: u8,
+    pub sampling_rate: u32,
 }
 
 impl From<&Flow> for MiniFlow {
@@ -65,6 +66,7 @@ impl From<&Flow> for MiniFlow {
             otel_instance: flow.otel_instance.clone(),
             pod_id: flow.pod_id,
             direction_score: flow.direction_score,
+            sampling_rate: flow.sampling_rate,
         }
     }
 }
@@ -16,6 +16,7 @@
 
 use std::fmt;
 use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use log::warn;
 use num_enum::TryFromPrimitive;
@@ -23,7 +24,10 @@ use num_enum::TryFromPrimitive;
 use super::consts::*;
 use super::enums::{EthernetType, IpProtocol};
 
-use crate::utils::bytes;
+use crate::utils::{
+    bytes,
+    stats::{Counter, CounterType, CounterValue, OwnedCountable},
+};
 use serde::Serialize;
 
 use public::proto::agent::DecapType;
@@ -42,6 +46,8 @@ pub enum TunnelType {
     VxlanNsh = DecapType::VxlanNsh as u8,
     Erspan = DecapType::VxlanNsh as u8 + 1,
     Teb = DecapType::VxlanNsh as u8 + 2,
+    Mpls = DecapType::VxlanNsh as u8 + 3,
+    MplsGre = DecapType::VxlanNsh as u8 + 4,
 }
 
 impl From<DecapType> for TunnelType {
@@ -81,6 +87,8 @@ impl fmt::Display for TunnelType {
             TunnelType::VxlanNsh => write!(f, "VXLAN-NSH"),
             TunnelType::Erspan => write!(f, "ERSPAN"),
             TunnelType::Teb => write!(f, "TEB"),
+            TunnelType::Mpls => write!(f, "MPLS"),
+            TunnelType::MplsGre => write!(f, "MPLS-GRE"),
         }
     }
 }
@@ -93,6 +101,8 @@ impl From<&str> for TunnelType {
             "ERSPAN" => TunnelType::Erspan,
             "TEB" => TunnelType::Teb,
             "VXLAN-NSH" => TunnelType::VxlanNsh,
+            "MPLS" => TunnelType::Mpls,
+            "MPLS-GRE" => TunnelType::MplsGre,
             _ => TunnelType::None,
         }
     }
@@ -104,18 +114,93 @@ impl Default for TunnelType {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub struct TunnelTypeBitmap(u16);
+// Packets with more MPLS labels than the limit, they are passed through without decapsulation
+static MPLS_LABEL_LIMIT_EXCEEDED: AtomicU64 = AtomicU64::new(0);
+
+pub struct MplsDecapCounter;
+
+impl OwnedCountable for MplsDecapCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![(
+            "label_limit_exceeded",
+            CounterType::Counted,
+            CounterValue::Unsigned(MPLS_LABEL_LIMIT_EXCEEDED.swap(0, Ordering::Relaxed)),
+        )]
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TunnelTypeBitmap {
+    bitmap: u16,
+    // at most mpls_label_limit labels are popped for MPLS and MPLS-GRE
+    mpls_label_limit: u8,
+    // geneve options to be extracted as tunnel metadata, at most geneve_metadata_limit bytes
+    geneve_options: [GeneveOptionSelector; GENEVE_OPTION_SELECTOR_LIMIT],
+    geneve_option_count: u8,
+    geneve_metadata_limit: u8,
+}
+
+impl Default for TunnelTypeBitmap {
+    fn default() -> Self {
+        Self {
+            bitmap: 0,
+            mpls_label_limit: MPLS_LABEL_LIMIT_DEFAULT,
+            geneve_options: [GeneveOptionSelector::default(); GENEVE_OPTION_SELECTOR_LIMIT],
+            geneve_option_count: 0,
+            geneve_metadata_limit: GENEVE_METADATA_SIZE_DEFAULT,
+        }
+    }
+}
 
 impl TunnelTypeBitmap {
     pub fn new(tunnel_types: &Vec<TunnelType>) -> Self {
-        let mut bitmap = TunnelTypeBitmap(0);
+        let mut bitmap = TunnelTypeBitmap::default();
         for tunnel_type in tunnel_types.iter() {
-            bitmap.0 |= 1 << *tunnel_type as u16;
+            bitmap.add(*tunnel_type);
         }
         bitmap
     }
 
+    pub fn with_mpls_label_limit(mut self, limit: u8) -> Self {
+        self.mpls_label_limit = limit;
+        self
+    }
+
+    pub fn mpls_label_limit(&self) -> u8 {
+        self.mpls_label_limit
+    }
+
+    pub fn with_geneve_options(
+        mut self,
+        selectors: &[GeneveOptionSelector],
+        metadata_limit: u8,
+    ) -> Self {
+        if selectors.len() > GENEVE_OPTION_SELECTOR_LIMIT {
+            warn!(
+                "Too many geneve options {}, only the first {} are extracted.",
+                selectors.len(),
+                GENEVE_OPTION_SELECTOR_LIMIT
+            );
+        }
+        let count = selectors.len().min(GENEVE_OPTION_SELECTOR_LIMIT);
+        self.geneve_options[..count].copy_from_slice(&selectors[..count]);
+        self.geneve_option_count = count as u8;
+        self.geneve_metadata_limit = metadata_limit.min(GENEVE_METADATA_SIZE_LIMIT as u8);
+        self
+    }
+
+    pub fn geneve_options(&self) -> &[GeneveOptionSelector] {
+        &self.geneve_options[..self.geneve_option_count as usize]
+    }
+
+    pub fn geneve_metadata_limit(&self) -> u8 {
+        self.geneve_metadata_limit
+    }
+
     fn init_from_strings(&mut self, tunnel_types: &Vec<String>) {
         for s in tunnel_types {
             let tunnel_type = TunnelType::from(s.as_str());
@@ -128,9 +213,12 @@ impl TunnelTypeBitmap {
     }
 
     pub fn from_slices(tunnel_types: &Vec<u8>, trim_tunnel_types: &Vec<String>) -> Self {
-        let mut bitmap = TunnelTypeBitmap(0);
-        for tunnel_type in tunnel_types.iter() {
-            bitmap.0 |= 1 << *tunnel_type as u16;
+        let mut bitmap = TunnelTypeBitmap::default();
+        for t in tunnel_types.iter() {
+            match TunnelType::try_from(*t) {
+                Ok(tunnel_type) => bitmap.add(tunnel_type),
+                Err(_) => warn!("Unknown tunnel type {}.", t),
+            }
         }
 
         bitmap.init_from_strings(trim_tunnel_types);
@@ -139,7 +227,7 @@ impl TunnelTypeBitmap {
     }
 
     pub fn from_strings(tunnel_types: &Vec<String>) -> Self {
-        let mut bitmap = TunnelTypeBitmap(0);
+        let mut bitmap = TunnelTypeBitmap::default();
 
         bitmap.init_from_strings(tunnel_types);
 
@@ -147,15 +235,22 @@ impl TunnelTypeBitmap {
     }
 
     pub fn add(&mut self, tunnel_type: TunnelType) {
-        self.0 |= 1 << tunnel_type as u16
+        self.bitmap |= 1 << tunnel_type as u16
     }
 
     pub fn has(&self, tunnel_type: TunnelType) -> bool {
-        self.0 & (1 << tunnel_type as u16) != 0
+        self.bitmap & (1 << tunnel_type as u16) != 0
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0 == 0
+        self.bitmap == 0
+    }
+
+    pub fn to_tunnel_types(&self) -> Vec<TunnelType> {
+        (1..u16::BITS as u8)
+            .filter_map(|t| TunnelType::try_from(t).ok())
+            .filter(|t| self.has(*t))
+            .collect()
     }
 }
 
@@ -187,11 +282,121 @@ impl fmt::Display for TunnelTypeBitmap {
         }
         if self.has(TunnelType::Teb) {
             write!(f, "{}{}", separation, TunnelType::Teb)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::Mpls) {
+            write!(f, "{}{}", separation, TunnelType::Mpls)?;
+            separation = " ";
+        }
+        if self.has(TunnelType::MplsGre) {
+            write!(f, "{}{}", separation, TunnelType::MplsGre)?;
+        }
+        if self.has(TunnelType::Mpls) || self.has(TunnelType::MplsGre) {
+            write!(f, " (label limit {})", self.mpls_label_limit)?;
+        }
+        if self.has(TunnelType::Geneve) && self.geneve_option_count > 0 {
+            write!(f, " (geneve options")?;
+            for o in self.geneve_options() {
+                write!(f, " {}", o)?;
+            }
+            write!(f, ", metadata limit {})", self.geneve_metadata_limit)?;
         }
         write!(f, "")
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GeneveOptionSelector {
+    pub class: u16,
+    pub option_type: u8,
+}
+
+impl fmt::Display for GeneveOptionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06x}/{:#04x}", self.class, self.option_type)
+    }
+}
+
+pub struct GeneveOption<'a> {
+    pub class: u16,
+    pub option_type: u8,
+    pub data: &'a [u8],
+}
+
+impl fmt::Display for GeneveOption<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#06x}/{:#04x}/", self.class, self.option_type)?;
+        for b in self.data {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+// Selected geneve options kept in wire format, each option has a 4 bytes header
+// (class, type and length) followed by its data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GeneveMetadata {
+    len: u8,
+    buffer: [u8; GENEVE_METADATA_SIZE_LIMIT],
+}
+
+impl Default for GeneveMetadata {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            buffer: [0; GENEVE_METADATA_SIZE_LIMIT],
+        }
+    }
+}
+
+impl GeneveMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // options exceeding the limit are dropped as a whole
+    fn push(&mut self, option: &[u8], limit: usize) -> bool {
+        let len = self.len as usize;
+        if len + option.len() > limit.min(GENEVE_METADATA_SIZE_LIMIT) {
+            return false;
+        }
+        self.buffer[len..len + option.len()].copy_from_slice(option);
+        self.len += option.len() as u8;
+        true
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = GeneveOption<'_>> {
+        let mut options = &self.buffer[..self.len as usize];
+        std::iter::from_fn(move || {
+            if options.len() < GENEVE_OPTION_HEADER_SIZE {
+                return None;
+            }
+            let size = Self::option_size(options);
+            let option = GeneveOption {
+                class: bytes::read_u16_be(&options[GENEVE_OPTION_CLASS_OFFSET..]),
+                option_type: options[GENEVE_OPTION_TYPE_OFFSET],
+                data: &options[GENEVE_OPTION_HEADER_SIZE..size],
+            };
+            options = &options[size..];
+            Some(option)
+        })
+    }
+
+    fn option_size(option: &[u8]) -> usize {
+        GENEVE_OPTION_HEADER_SIZE
+            + (((option[GENEVE_OPTION_DATA_LENGTH_OFFSET] & GENEVE_OPTION_DATA_LENGTH_MASK)
+                as usize)
+                << 2)
+    }
+}
+
+impl Serialize for GeneveMetadata {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|o| o.to_string()))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TunnelInfo {
     pub src: Ipv4Addr,
@@ -203,6 +408,7 @@ pub struct TunnelInfo {
     pub tier: u8,
     pub is_ipv6: bool,
     pub from: u32, // tunnel source ip
+    pub geneve_metadata: GeneveMetadata,
 }
 
 impl Default for TunnelInfo {
@@ -217,6 +423,7 @@ impl Default for TunnelInfo {
             tier: 0,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         }
     }
 }
@@ -275,7 +482,7 @@ impl TunnelInfo {
                 return self.decapsulate_gpe_vxlan(packet, l2_len);
             }
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
-                return self.decapsulate_geneve(packet, l2_len);
+                return self.decapsulate_geneve(packet, l2_len, tunnel_types);
             }
             _ => {}
         }
@@ -458,11 +665,127 @@ impl TunnelInfo {
             LE_TRANSPARENT_ETHERNET_BRIDGEING if tunnel_types.has(TunnelType::Teb) => {
                 self.decapsulate_teb(packet, l2_len, flags, ip_header_size)
             }
+            LE_MPLS_UNICAST_PROTO_TYPE if tunnel_types.has(TunnelType::MplsGre) => self
+                .decapsulate_mpls_gre(
+                    packet,
+                    l2_len,
+                    flags,
+                    ip_header_size,
+                    tunnel_types.mpls_label_limit(),
+                ),
             _ => 0,
         }
     }
 
-    pub fn decapsulate_geneve(&mut self, packet: &[u8], l2_len: usize) -> usize {
+    // Returns size of the label stack, the bottom of stack label and whether the overlay is ipv6,
+    // only ip overlay is supported
+    fn parse_mpls_labels(payload: &[u8], limit: u8) -> Option<(usize, u32, bool)> {
+        let mut offset = 0;
+        while offset + MPLS_LABEL_SIZE <= payload.len() {
+            let entry = bytes::read_u32_be(&payload[offset..]);
+            offset += MPLS_LABEL_SIZE;
+            if entry & MPLS_BOTTOM_OF_STACK_MASK == 0 {
+                if offset >= limit as usize * MPLS_LABEL_SIZE {
+                    MPLS_LABEL_LIMIT_EXCEEDED.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                continue;
+            }
+            return match payload.get(offset).map(|v| v >> 4) {
+                Some(4) => Some((offset, entry >> MPLS_LABEL_SHIFT, false)),
+                Some(6) => Some((offset, entry >> MPLS_LABEL_SHIFT, true)),
+                _ => None,
+            };
+        }
+        None
+    }
+
+    // 将l2层头放在overlay ip头前，返回新的l2层头开始位置
+    fn move_l2_header(
+        packet: &mut [u8],
+        l2_len: usize,
+        overlay_start: usize,
+        overlay_ipv6: bool,
+    ) -> usize {
+        let start = overlay_start - l2_len;
+        packet.copy_within(0..l2_len, start);
+        let eth_type = if overlay_ipv6 {
+            EthernetType::IPV6
+        } else {
+            EthernetType::IPV4
+        };
+        bytes::write_u16_be(&mut packet[start + l2_len - 2..], u16::from(eth_type));
+        start
+    }
+
+    // MPLS labels follow the l2 header directly, returns start of the decapsulated packet
+    pub fn decapsulate_mpls(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> usize {
+        if !tunnel_types.has(TunnelType::Mpls) || self.tier == TUNNEL_TIER_LIMIT {
+            return 0;
+        }
+        let Some((stack_size, label, overlay_ipv6)) =
+            Self::parse_mpls_labels(&packet[l2_len..], tunnel_types.mpls_label_limit())
+        else {
+            return 0;
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::Mpls;
+            self.id = label;
+        }
+        self.tier += 1;
+
+        Self::move_l2_header(packet, l2_len, l2_len + stack_size, overlay_ipv6)
+    }
+
+    pub fn decapsulate_mpls_gre(
+        &mut self,
+        packet: &mut [u8],
+        l2_len: usize,
+        flags: u16,
+        ip_header_size: usize,
+        label_limit: u8,
+    ) -> usize {
+        if flags & GRE_FLAGS_VER_MASK != 0 {
+            return 0;
+        }
+        let overlay_offset =
+            l2_len + ip_header_size + GRE_HEADER_SIZE_DECAP + Self::calc_gre_option_size(flags);
+        if overlay_offset >= packet.len() {
+            return 0;
+        }
+        let Some((stack_size, label, overlay_ipv6)) =
+            Self::parse_mpls_labels(&packet[overlay_offset..], label_limit)
+        else {
+            return 0;
+        };
+
+        // 仅保存最外层的隧道信息
+        if self.tier == 0 {
+            self.decapsulate_addr(&packet[l2_len..]);
+            self.decapsulate_mac(packet);
+            self.tunnel_type = TunnelType::MplsGre;
+            self.id = label;
+        }
+        self.tier += 1;
+
+        // return offset start from L3
+        Self::move_l2_header(packet, l2_len, overlay_offset + stack_size, overlay_ipv6) - l2_len
+    }
+
+    pub fn decapsulate_geneve(
+        &mut self,
+        packet: &[u8],
+        l2_len: usize,
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> usize {
         let l3_packet = &packet[l2_len..];
         if l3_packet.len() < UDP_PACKET_SIZE + GENEVE_HEADER_SIZE {
             return 0;
@@ -473,6 +796,12 @@ impl TunnelInfo {
         if geneve_header_size == 0 {
             return 0;
         }
+        let Some(metadata) = Self::decapsulate_geneve_options(
+            &l4_payload[GENEVE_HEADER_SIZE..geneve_header_size],
+            tunnel_types,
+        ) else {
+            return 0;
+        };
 
         // 仅保存最外层的隧道信息
         if self.tier == 0 {
@@ -480,6 +809,7 @@ impl TunnelInfo {
             self.decapsulate_mac(packet);
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
+            self.geneve_metadata = metadata;
         }
         self.tier += 1;
 
@@ -550,7 +880,46 @@ impl TunnelInfo {
         )
     }
 
-    pub fn decapsulate_v6_geneve(&mut self, packet: &[u8], l2_len: usize) -> usize {
+    // Returns None if the option lengths do not add up to the option length in geneve header.
+    // Options not selected are skipped, critical or not, since the agent only observes the
+    // traffic and must not drop it as a tunnel endpoint would.
+    fn decapsulate_geneve_options(
+        options: &[u8],
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> Option<GeneveMetadata> {
+        let mut metadata = GeneveMetadata::default();
+        let selectors = tunnel_types.geneve_options();
+        let mut offset = 0;
+        while offset < options.len() {
+            if offset + GENEVE_OPTION_HEADER_SIZE > options.len() {
+                return None;
+            }
+            let option_size = GeneveMetadata::option_size(&options[offset..]);
+            if offset + option_size > options.len() {
+                return None;
+            }
+            let class = bytes::read_u16_be(&options[offset + GENEVE_OPTION_CLASS_OFFSET..]);
+            let option_type = options[offset + GENEVE_OPTION_TYPE_OFFSET];
+            if selectors
+                .iter()
+                .any(|s| s.class == class && s.option_type == option_type)
+            {
+                metadata.push(
+                    &options[offset..offset + option_size],
+                    tunnel_types.geneve_metadata_limit() as usize,
+                );
+            }
+            offset += option_size;
+        }
+        Some(metadata)
+    }
+
+    pub fn decapsulate_v6_geneve(
+        &mut self,
+        packet: &[u8],
+        l2_len: usize,
+        tunnel_types: &TunnelTypeBitmap,
+    ) -> usize {
         let l3_packet = &packet[l2_len..];
         if l3_packet.len() < UDP6_PACKET_SIZE + GENEVE_HEADER_SIZE {
             return 0;
@@ -561,6 +930,12 @@ impl TunnelInfo {
         if geneve_header_size == 0 {
             return 0;
         }
+        let Some(metadata) = Self::decapsulate_geneve_options(
+            &l4_payload[GENEVE_HEADER_SIZE..geneve_header_size],
+            tunnel_types,
+        ) else {
+            return 0;
+        };
 
         // 仅保存最外层的隧道信息
         if self.tier == 0 {
@@ -568,6 +943,7 @@ impl TunnelInfo {
             self.decapsulate_mac(packet);
             self.tunnel_type = TunnelType::Geneve;
             self.id = tunnel_id;
+            self.geneve_metadata = metadata;
             self.is_ipv6 = true;
         }
         self.tier += 1;
@@ -623,7 +999,7 @@ impl TunnelInfo {
                 return self.decapsulate_v6_vxlan(packet, l2_len)
             }
             LE_GENEVE_PROTO_UDP_DPORT if tunnel_types.has(TunnelType::Geneve) => {
-                return self.decapsulate_v6_geneve(packet, l2_len)
+                return self.decapsulate_v6_geneve(packet, l2_len, tunnel_types)
             }
             _ => {}
         }
@@ -765,7 +1141,7 @@ mod tests {
 
     #[test]
     fn bitmap_add() {
-        let mut bitmap = TunnelTypeBitmap(0);
+        let mut bitmap = TunnelTypeBitmap::default();
         assert!(bitmap.is_empty());
         bitmap.add(TunnelType::Ipip);
         bitmap.add(TunnelType::Vxlan);
@@ -787,6 +1163,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("decapsulate_erspan1.pcap")).into();
@@ -821,6 +1198,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap")).into();
@@ -855,6 +1233,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap")).into();
@@ -882,6 +1261,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("decapsulate_test.pcap")).into();
@@ -909,6 +1289,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ff-vxlan.pcap")).into();
@@ -935,6 +1316,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("vmware-gre-teb.pcap")).into();
@@ -962,6 +1344,7 @@ mod tests {
             tier: 1,
             is_ipv6: true,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ip6-vxlan.pcap")).into();
@@ -989,6 +1372,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("ipip.pcap")).into();
@@ -1035,6 +1419,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("geneve.pcap")).into();
@@ -1059,6 +1444,7 @@ mod tests {
             tier: 1,
             is_ipv6: false,
             from: 0,
+            geneve_metadata: GeneveMetadata::default(),
         };
         let mut packets: Vec<Vec<u8>> =
             Capture::load_pcap(Path::new(PCAP_PATH_PREFIX).join("vxlan-nsh.pcap")).into();
@@ -1069,4 +1455,230 @@ mod tests {
         assert_eq!(offset, 74);
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn bitmap_tunnel_types() {
+        let bitmap = TunnelTypeBitmap::from_slices(
+            &vec![1, 2, 8, 9, 15],
+            &vec!["mpls".to_string(), "MPLS-GRE".to_string()],
+        );
+        let tunnel_types = bitmap.to_tunnel_types();
+        assert_eq!(
+            tunnel_types,
+            vec![
+                TunnelType::Vxlan,
+                TunnelType::Ipip,
+                TunnelType::Mpls,
+                TunnelType::MplsGre
+            ]
+        );
+        for t in [TunnelType::Mpls, TunnelType::MplsGre] {
+            assert_eq!(TunnelType::from(t.to_string().as_str()), t);
+        }
+        assert_eq!(
+            bitmap.to_string(),
+            "VXLAN IPIP MPLS MPLS-GRE (label limit 4)"
+        );
+    }
+
+    fn mpls_labels(labels: &[u32]) -> Vec<u8> {
+        let mut bs = vec![];
+        for (i, label) in labels.iter().enumerate() {
+            let mut entry = (label << MPLS_LABEL_SHIFT) | 64;
+            if i == labels.len() - 1 {
+                entry |= MPLS_BOTTOM_OF_STACK_MASK;
+            }
+            bs.extend_from_slice(&entry.to_be_bytes());
+        }
+        bs
+    }
+
+    fn ipv4_packet(protocol: IpProtocol, payload: &[u8]) -> Vec<u8> {
+        let mut bs = vec![0x45, 0];
+        bs.extend_from_slice(&((IPV4_HEADER_SIZE + payload.len()) as u16).to_be_bytes());
+        bs.extend_from_slice(&[0, 0, 0x40, 0, 64, u8::from(protocol), 0, 0]);
+        bs.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        bs.extend_from_slice(payload);
+        bs
+    }
+
+    fn ethernet_header(eth_type: EthernetType) -> Vec<u8> {
+        let mut bs = vec![0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02];
+        bs.extend_from_slice(&u16::from(eth_type).to_be_bytes());
+        bs
+    }
+
+    #[test]
+    fn test_decapsulate_mpls() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Mpls]);
+        let inner = ipv4_packet(IpProtocol::TCP, &[0; 40]);
+        for (labels, bottom) in [(vec![100], 100), (vec![16, 200, 300], 300)] {
+            let mut packet = ethernet_header(EthernetType::MPLS_UNICAST);
+            packet.extend_from_slice(&mpls_labels(&labels));
+            packet.extend_from_slice(&inner);
+
+            let mut actual = TunnelInfo::default();
+            let start = actual.decapsulate_mpls(&mut packet, 14, &bitmap);
+            assert_eq!(start, labels.len() * MPLS_LABEL_SIZE);
+            assert_eq!(
+                &packet[start..start + 14],
+                &ethernet_header(EthernetType::IPV4)[..]
+            );
+            assert_eq!(&packet[start + 14..], &inner[..]);
+            assert_eq!(actual.tunnel_type, TunnelType::Mpls);
+            assert_eq!(actual.id, bottom);
+            assert_eq!(actual.tier, 1);
+            assert_eq!(actual.mac_src, 0x00000002);
+            assert_eq!(actual.mac_dst, 0x00000001);
+        }
+    }
+
+    #[test]
+    fn test_decapsulate_mpls_label_limit() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Mpls]).with_mpls_label_limit(2);
+        let mut packet = ethernet_header(EthernetType::MPLS_UNICAST);
+        packet.extend_from_slice(&mpls_labels(&[16, 200, 300]));
+        packet.extend_from_slice(&ipv4_packet(IpProtocol::TCP, &[0; 40]));
+        let origin = packet.clone();
+
+        let exceeded = MPLS_LABEL_LIMIT_EXCEEDED.load(Ordering::Relaxed);
+        let mut actual = TunnelInfo::default();
+        assert_eq!(actual.decapsulate_mpls(&mut packet, 14, &bitmap), 0);
+        assert_eq!(actual, TunnelInfo::default());
+        assert_eq!(packet, origin);
+        assert!(MPLS_LABEL_LIMIT_EXCEEDED.load(Ordering::Relaxed) > exceeded);
+    }
+
+    #[test]
+    fn test_decapsulate_mpls_gre() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::MplsGre]);
+        let inner = ipv4_packet(IpProtocol::UDP, &[0; 40]);
+        // key present
+        let mut gre = vec![0x20, 0x00, 0x88, 0x47, 0, 0, 0, 1];
+        gre.extend_from_slice(&mpls_labels(&[16, 500]));
+        gre.extend_from_slice(&inner);
+        let mut packet = ethernet_header(EthernetType::IPV4);
+        packet.extend_from_slice(&ipv4_packet(IpProtocol::GRE, &gre));
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, 14, &bitmap);
+        let expected_offset =
+            IPV4_HEADER_SIZE + GRE_HEADER_SIZE_DECAP + GRE_KEY_LEN - 14 + 2 * MPLS_LABEL_SIZE;
+        assert_eq!(offset, expected_offset);
+        assert_eq!(&packet[14 + offset + 14..], &inner[..]);
+        let expected = TunnelInfo {
+            src: Ipv4Addr::new(10, 0, 0, 1),
+            dst: Ipv4Addr::new(10, 0, 0, 2),
+            mac_src: 0x00000002,
+            mac_dst: 0x00000001,
+            id: 500,
+            tunnel_type: TunnelType::MplsGre,
+            tier: 1,
+            is_ipv6: false,
+            from: 0,
+            geneve_metadata: GeneveMetadata::default(),
+        };
+        assert_eq!(actual, expected);
+
+        // not decapsulated if only plain MPLS is enabled
+        let mut actual = TunnelInfo::default();
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Mpls]);
+        assert_eq!(actual.decapsulate(&mut packet, 14, &bitmap), 0);
+    }
+
+    fn geneve_packet(options: &[u8], option_length: u8) -> Vec<u8> {
+        let mut geneve = vec![option_length, 0, 0x65, 0x58, 0, 0, 3, 0];
+        geneve.extend_from_slice(options);
+        geneve.extend_from_slice(&ethernet_header(EthernetType::IPV4));
+        geneve.extend_from_slice(&ipv4_packet(IpProtocol::TCP, &[0; 40]));
+        let mut udp = vec![0x30, 0x39, 0x17, 0xc1];
+        udp.extend_from_slice(&((UDP_HEADER_SIZE + geneve.len()) as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(&geneve);
+        let mut packet = ethernet_header(EthernetType::IPV4);
+        packet.extend_from_slice(&ipv4_packet(IpProtocol::UDP, &udp));
+        packet
+    }
+
+    fn geneve_option(class: u16, option_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut bs = class.to_be_bytes().to_vec();
+        bs.extend_from_slice(&[option_type, (data.len() / 4) as u8]);
+        bs.extend_from_slice(data);
+        bs
+    }
+
+    #[test]
+    fn test_decapsulate_geneve_options() {
+        let selectors = [
+            GeneveOptionSelector {
+                class: 0x0102,
+                option_type: 0x01,
+            },
+            GeneveOptionSelector {
+                class: 0x0102,
+                option_type: 0x02,
+            },
+        ];
+        let bitmap =
+            TunnelTypeBitmap::new(&vec![TunnelType::Geneve]).with_geneve_options(&selectors, 16);
+
+        let mut options = geneve_option(0x0102, 0x01, &[0, 0, 0x30, 0x39]);
+        // unknown critical option
+        options.extend_from_slice(&geneve_option(0xffff, 0x80, &[1; 8]));
+        options.extend_from_slice(&geneve_option(0x0102, 0x02, &[2; 4]));
+        // exceeds the 16 bytes limit
+        options.extend_from_slice(&geneve_option(0x0102, 0x01, &[3; 4]));
+        let mut packet = geneve_packet(&options, (options.len() / 4) as u8);
+
+        let mut actual = TunnelInfo::default();
+        let offset = actual.decapsulate(&mut packet, 14, &bitmap);
+        assert_eq!(
+            offset,
+            UDP_PACKET_SIZE - ETH_HEADER_SIZE + GENEVE_HEADER_SIZE + options.len()
+        );
+        assert_eq!(actual.tunnel_type, TunnelType::Geneve);
+        assert_eq!(actual.id, 3);
+        let extracted = actual
+            .geneve_metadata
+            .iter()
+            .map(|o| (o.class, o.option_type, o.data.to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            extracted,
+            vec![
+                (0x0102, 0x01, vec![0, 0, 0x30, 0x39]),
+                (0x0102, 0x02, vec![2; 4])
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&actual.geneve_metadata).unwrap(),
+            r#"["0x0102/0x01/00003039","0x0102/0x02/02020202"]"#
+        );
+
+        // nothing extracted if no option is configured
+        let mut packet = geneve_packet(&options, (options.len() / 4) as u8);
+        let mut actual = TunnelInfo::default();
+        let bitmap_without_options = TunnelTypeBitmap::new(&vec![TunnelType::Geneve]);
+        assert!(actual.decapsulate(&mut packet, 14, &bitmap_without_options) > 0);
+        assert!(actual.geneve_metadata.is_empty());
+    }
+
+    #[test]
+    fn test_decapsulate_geneve_option_length_mismatch() {
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Geneve]);
+        let options = geneve_option(0x0102, 0x01, &[0; 8]);
+        let mut multiple_options = geneve_option(0x0102, 0x01, &[0; 4]);
+        multiple_options.extend_from_slice(&geneve_option(0x0102, 0x02, &[0; 4]));
+        for packet in [
+            // option data exceeds the option length in geneve header
+            geneve_packet(&options, 2),
+            // the second option exceeds the option length in geneve header
+            geneve_packet(&multiple_options, 3),
+        ] {
+            let mut packet = packet;
+            let mut actual = TunnelInfo::default();
+            assert_eq!(actual.decapsulate(&mut packet, 14, &bitmap), 0);
+            assert_eq!(actual, TunnelInfo::default());
+        }
+    }
 }
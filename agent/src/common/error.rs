@@ -31,3 +31,5 @@ pub enum Error {
     #[error("{0}")]
     InvalidProtocol(String),
 }
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -25,7 +25,7 @@ use log::{error, warn};
 use serde::{Serialize, Serializer};
 
 use super::{
-    decapsulate::TunnelType,
+    decapsulate::{GeneveMetadata, GeneveOption, TunnelType},
     enums::{CaptureNetworkType, EthernetType, IpProtocol, TcpFlags},
     tap_port::TapPort,
 };
@@ -236,6 +236,16 @@ pub struct TunnelField {
     pub tier: u8,
     #[serde(skip)]
     pub is_ipv6: bool,
+    #[serde(
+        rename = "tunnel_tx_options",
+        skip_serializing_if = "GeneveMetadata::is_empty"
+    )]
+    pub tx_options: GeneveMetadata, // 对应发送方向提取的Geneve选项
+    #[serde(
+        rename = "tunnel_rx_options",
+        skip_serializing_if = "GeneveMetadata::is_empty"
+    )]
+    pub rx_options: GeneveMetadata, // 对应接收方向提取的Geneve选项
 }
 
 pub fn mac_low32_to_string<S>(d: &u32, serializer: S) -> Result<S::Ok, S::Error>
@@ -261,6 +271,8 @@ impl Default for TunnelField {
             tunnel_type: TunnelType::default(),
             tier: 0,
             is_ipv6: false,
+            tx_options: GeneveMetadata::default(),
+            rx_options: GeneveMetadata::default(),
         }
     }
 }
@@ -272,6 +284,7 @@ impl TunnelField {
         swap(&mut self.tx_mac0, &mut self.rx_mac0);
         swap(&mut self.tx_mac1, &mut self.rx_mac1);
         swap(&mut self.tx_id, &mut self.rx_id);
+        swap(&mut self.tx_options, &mut self.rx_options);
     }
 }
 
@@ -309,6 +322,26 @@ impl From<TunnelField> for flow_log::TunnelField {
             tunnel_type: f.tunnel_type as u32,
             tier: f.tier as u32,
             is_ipv6: 0,
+            tx_options: f
+                .tx_options
+                .iter()
+                .map(flow_log::TunnelOption::from)
+                .collect(),
+            rx_options: f
+                .rx_options
+                .iter()
+                .map(flow_log::TunnelOption::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<GeneveOption<'_>> for flow_log::TunnelOption {
+    fn from(o: GeneveOption<'_>) -> Self {
+        flow_log::TunnelOption {
+            class: o.class as u32,
+            r#type: o.option_type as u32,
+            data: o.data.to_vec(),
         }
     }
 }
@@ -545,9 +578,11 @@ pub struct L7Stats {
     pub flow_id: u64,
     pub l7_protocol: L7Protocol,
     pub signal_source: SignalSource,
+    // second the stats are accounted to, which is the second of the request for
+    // responses with consistent_timestamp_in_l7_metrics
     pub time_in_second: Duration,
-    // request-reponse time span
-    pub time_span: u32,
+    // raw capture time, the time window of the stats is moved with it
+    pub capture_time: Duration,
     pub biz_type: u8,
     pub is_reversed: bool,
 }
@@ -945,6 +980,10 @@ pub struct Flow {
     pub pod_id: u32,
     pub request_domain: String,
     pub need_to_store: bool,
+    // sampling rate of xFlow flows, 0 if the counters are measured rather than extrapolated
+    pub sampling_rate: u32,
+    // ratio of l4 flow logs kept at the observation point, 0 if not sampled
+    pub flow_log_sampling_ratio: f32,
 }
 
 fn tunnel_is_none(t: &TunnelField) -> bool {
@@ -999,6 +1038,7 @@ impl Flow {
 
         self.close_type = other.close_type;
         self.is_active_service = other.is_active_service;
+        self.sampling_rate = self.sampling_rate.max(other.sampling_rate);
         self.reversed = other.reversed;
         if other.vlan > 0 {
             self.vlan = other.vlan
@@ -1259,6 +1299,8 @@ impl From<Flow> for flow_log::Flow {
             direction_score: f.direction_score as u32,
             request_domain: f.request_domain,
             init_ipid: f.init_ipid,
+            sampling_rate: f.sampling_rate,
+            flow_log_sampling_ratio: f.flow_log_sampling_ratio,
         }
     }
 }
@@ -29,9 +29,9 @@ use crate::{
     flow_generator::{
         protocol_logs::{
             fastcgi::FastCGIInfo, pb_adapter::L7ProtocolSendLog, AmqpInfo, BrpcInfo, DnsInfo,
-            DubboInfo, HttpInfo, KafkaInfo, MemcachedInfo, MongoDBInfo, MqttInfo, MysqlInfo,
-            NatsInfo, OpenWireInfo, PingInfo, PostgreInfo, PulsarInfo, RedisInfo, RocketmqInfo,
-            SofaRpcInfo, TarsInfo, ZmtpInfo,
+            DubboInfo, HttpInfo, KafkaInfo, LdapInfo, MemcachedInfo, MongoDBInfo, MqttInfo,
+            MysqlInfo, NatsInfo, OpenWireInfo, PingInfo, PostgreInfo, PulsarInfo, RedisInfo,
+            RocketmqInfo, SmtpInfo, SofaRpcInfo, TarsInfo, ZmtpInfo,
         },
         AppProtoHead, Result,
     },
@@ -100,6 +100,8 @@ cfg_if::cfg_if! {
             SofaRpcInfo(SofaRpcInfo),
             PingInfo(PingInfo),
             CustomInfo(CustomInfo),
+            LdapInfo(LdapInfo),
+            SmtpInfo(SmtpInfo),
             // add new protocol info below
         );
     } else {
@@ -133,6 +135,8 @@ cfg_if::cfg_if! {
             CustomInfo(CustomInfo),
             Iso8583Info(crate::flow_generator::protocol_logs::rpc::Iso8583Info),
             NetSignInfo(crate::flow_generator::protocol_logs::rpc::NetSignInfo),
+            LdapInfo(LdapInfo),
+            SmtpInfo(SmtpInfo),
             // add new protocol info below
         );
     }
@@ -42,9 +42,9 @@ use crate::flow_generator::protocol_logs::{
     fastcgi::FastCGILog,
     plugin::{custom_wrap::CustomWrapLog, get_custom_log_parser},
     sql::ObfuscateCache,
-    AmqpLog, BrpcLog, DnsLog, DubboLog, HttpLog, KafkaLog, L7ResponseStatus, MemcachedLog,
-    MongoDBLog, MqttLog, MysqlLog, NatsLog, OpenWireLog, PingLog, PostgresqlLog, PulsarLog,
-    RedisLog, RocketmqLog, SofaRpcLog, TarsLog, ZmtpLog,
+    AmqpLog, BrpcLog, DnsLog, DubboLog, HttpLog, KafkaLog, L7ResponseStatus, LdapLog,
+    MemcachedLog, MongoDBLog, MqttLog, MysqlLog, NatsLog, OpenWireLog, PingLog, PostgresqlLog,
+    PulsarLog, RedisLog, RocketmqLog, SmtpLog, SofaRpcLog, TarsLog, ZmtpLog,
 };
 
 use crate::flow_generator::Result;
@@ -182,6 +182,8 @@ cfg_if::cfg_if! {
                 RocketMQ(RocketmqLog),
                 OpenWire(OpenWireLog),
                 Ping(PingLog),
+                LDAP(LdapLog),
+                SMTP(SmtpLog),
                 // add protocol below
             }
         }
@@ -217,6 +219,8 @@ cfg_if::cfg_if! {
                 TLS(crate::flow_generator::protocol_logs::TlsLog),
                 SomeIp(crate::flow_generator::protocol_logs::SomeIpLog),
                 Ping(PingLog),
+                LDAP(LdapLog),
+                SMTP(SmtpLog),
                 // add protocol below
             }
         }
@@ -310,6 +314,11 @@ pub trait L7ProtocolParserInterface {
 
     fn reset(&mut self) {}
 
+    // the protocol which the rest of the flow should be handed over to, e.g. TLS after SMTP STARTTLS
+    fn upgrade_protocol(&self) -> Option<L7ProtocolEnum> {
+        None
+    }
+
     // return perf data
     fn perf_stats(&mut self) -> Vec<L7PerfStats>;
 }
@@ -227,6 +227,8 @@ pub struct MetaPacket<'a> {
     pub queue_hash: u8,
 
     /********** for xFlow (NetFlow/sFlow/NetStream) **********/
+    // one packet is sampled out of `sampling_rate` packets, 0 means not sampled
+    pub sampling_rate: u32,
     // TODO support xFlow
     // pub packet_count: u64,
     // pub packet_bytes: u64,
@@ -415,6 +417,16 @@ impl<'a> MetaPacket<'a> {
                     offset += TCP_OPT_WIN_SCALE_LEN;
                     tcp_data.win_scale = packet[tcp_opt_win_scale_offset];
                 }
+                TcpOptionNumbers::TIMESTAMPS => {
+                    if offset + TCP_OPT_TIMESTAMPS_LEN > payload_offset {
+                        return;
+                    }
+                    tcp_data.timestamps = Some((
+                        read_u32_be(&packet[offset + 2..]),
+                        read_u32_be(&packet[offset + 6..]),
+                    ));
+                    offset += TCP_OPT_TIMESTAMPS_LEN;
+                }
                 TcpOptionNumbers::SACK_PERMITTED => {
                     self.tcp_options_flag |= TCP_OPT_FLAG_SACK_PERMIT;
                     offset += 2;
@@ -1023,6 +1035,12 @@ impl<'a> MetaPacket<'a> {
         self.l4_payload_len as usize
     }
 
+    /// Get the number of packets on the wire this packet stands for.
+    #[inline]
+    pub fn sample_weight(&self) -> u64 {
+        self.sampling_rate.max(1) as u64
+    }
+
     // The socket_id obtained by ebpf from upprobe and kprobe on the same flow,
     // but the application protocols are inconsistent.
     #[inline]
@@ -1479,7 +1497,8 @@ pub struct MetaPacketTcpHeader {
     pub data_offset: u8,
     pub win_scale: u8,
     pub sack_permitted: bool,
-    pub sack: Option<Vec<u8>>, // sack value
+    pub sack: Option<Vec<u8>>,          // sack value
+    pub timestamps: Option<(u32, u32)>, // TSval and TSecr
 }
 
 #[derive(Clone, Debug, Default)]
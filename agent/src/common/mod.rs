@@ -26,11 +26,13 @@ pub mod l7_protocol_log;
 pub mod lookup_key;
 pub mod matched_field;
 pub mod meta_packet;
+pub mod netflow;
 pub mod platform_data;
 pub mod policy;
 pub mod port_range;
 #[cfg(feature = "libtrace")]
 pub mod proc_event;
+pub mod sflow;
 pub(crate) mod tag;
 pub mod tagged_flow;
 pub mod tap_port;
@@ -0,0 +1,927 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use public::counter::{Counter, CounterType, CounterValue, RefCountable};
+
+use super::error::{Error, Result};
+use crate::utils::bytes::{read_u16_be, read_u32_be};
+
+const NETFLOW_VERSION_5: u16 = 5;
+const NETFLOW_VERSION_9: u16 = 9;
+const IPFIX_VERSION: u16 = 10;
+
+const V5_HEADER_LEN: usize = 24;
+const V5_RECORD_LEN: usize = 48;
+const V9_HEADER_LEN: usize = 20;
+const IPFIX_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+
+const V9_TEMPLATE_SET_ID: u16 = 0;
+const V9_OPTIONS_TEMPLATE_SET_ID: u16 = 1;
+const IPFIX_TEMPLATE_SET_ID: u16 = 2;
+const IPFIX_OPTIONS_TEMPLATE_SET_ID: u16 = 3;
+const MIN_DATA_SET_ID: u16 = 256;
+
+const ENTERPRISE_BIT: u16 = 0x8000;
+const VARIABLE_LENGTH: u16 = 0xffff;
+
+// information elements shared by NetFlow v9 and IPFIX
+const IN_BYTES: u16 = 1;
+const IN_PKTS: u16 = 2;
+const PROTOCOL: u16 = 4;
+const TCP_FLAGS: u16 = 6;
+const L4_SRC_PORT: u16 = 7;
+const IPV4_SRC_ADDR: u16 = 8;
+const L4_DST_PORT: u16 = 11;
+const IPV4_DST_ADDR: u16 = 12;
+const LAST_SWITCHED: u16 = 21;
+const FIRST_SWITCHED: u16 = 22;
+const IPV6_SRC_ADDR: u16 = 27;
+const IPV6_DST_ADDR: u16 = 28;
+const OCTET_TOTAL_COUNT: u16 = 85;
+const PACKET_TOTAL_COUNT: u16 = 86;
+const FLOW_START_SECONDS: u16 = 150;
+const FLOW_END_SECONDS: u16 = 151;
+const FLOW_START_MILLISECONDS: u16 = 152;
+const FLOW_END_MILLISECONDS: u16 = 153;
+
+// Templates not refreshed by the exporter within this time are dropped
+pub const TEMPLATE_TIMEOUT: Duration = Duration::from_secs(1800);
+// Data sets waiting for their template are dropped after this time
+pub const PENDING_TIMEOUT: Duration = Duration::from_secs(5);
+const PENDING_LIMIT: usize = 256;
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+// A flow record decoded from NetFlow v5, NetFlow v9 or IPFIX, all versions are
+// converted into this representation before entering the flow pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetflowRecord {
+    pub exporter: IpAddr,
+    // source id of NetFlow v9, observation domain id of IPFIX, engine id of NetFlow v5
+    pub observation_domain: u32,
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub tcp_flags: u8,
+    pub byte_count: u64,
+    pub packet_count: u64,
+    // unix time of the first and last packet of the flow
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl NetflowRecord {
+    fn new(exporter: IpAddr, observation_domain: u32) -> Self {
+        Self {
+            exporter,
+            observation_domain,
+            src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            dst_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            src_port: 0,
+            dst_port: 0,
+            protocol: 0,
+            tcp_flags: 0,
+            byte_count: 0,
+            packet_count: 0,
+            start: Duration::ZERO,
+            end: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TemplateKey {
+    exporter: IpAddr,
+    observation_domain: u32,
+    template_id: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    id: u16,
+    length: u16,
+    // enterprise specific fields are skipped
+    enterprise: bool,
+}
+
+#[derive(Debug)]
+struct Template {
+    fields: Vec<Field>,
+    // length of the shortest possible record, remaining bytes shorter than this are padding
+    min_record_len: usize,
+    updated: Duration,
+}
+
+// Context of the export packet needed to decode a data set
+#[derive(Debug, Clone, Copy)]
+struct ExportHeader {
+    version: u16,
+    // milliseconds since the exporter booted, NetFlow v9 only
+    sys_uptime: u32,
+    export_time: u32,
+}
+
+impl ExportHeader {
+    // Converts a sysUpTime based timestamp of NetFlow v9 to unix time
+    fn uptime_to_unix(&self, uptime: u32) -> Duration {
+        let export_ms = self.export_time as u64 * 1000;
+        let elapsed = self.sys_uptime.wrapping_sub(uptime) as u64;
+        Duration::from_millis(export_ms.saturating_sub(elapsed))
+    }
+}
+
+struct PendingSet {
+    key: TemplateKey,
+    header: ExportHeader,
+    data: Vec<u8>,
+    received: Duration,
+}
+
+#[derive(Default)]
+pub struct NetflowCounter {
+    pub records: AtomicU64,
+    pub template_updates: AtomicU64,
+    pub template_expired: AtomicU64,
+    pub waiting_template: AtomicU64,
+    pub drop_no_template: AtomicU64,
+    pub exporter_restarts: AtomicU64,
+}
+
+impl RefCountable for NetflowCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "template_updates",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.template_updates.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "template_expired",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.template_expired.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "waiting_template",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.waiting_template.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "drop_no_template",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.drop_no_template.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "exporter_restarts",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.exporter_restarts.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+fn truncated(what: &str, offset: usize) -> Error {
+    Error::InvalidProtocol(format!("netflow {what} truncated at {offset}"))
+}
+
+// Reads an unsigned integer with reduced size encoding
+fn read_uint(bs: &[u8]) -> u64 {
+    bs[bs.len().saturating_sub(8)..]
+        .iter()
+        .fold(0, |acc, b| acc << 8 | *b as u64)
+}
+
+// Decodes NetFlow v5, NetFlow v9 and IPFIX export packets, the templates of v9 and
+// IPFIX are cached by (exporter, observation domain, template id)
+pub struct NetflowDecoder {
+    templates: HashMap<TemplateKey, Template>,
+    // last sysUpTime of each NetFlow v9 exporter, used to detect exporter restarts
+    uptimes: HashMap<(IpAddr, u32), u32>,
+    pending: VecDeque<PendingSet>,
+    last_expire: Duration,
+    counter: Arc<NetflowCounter>,
+}
+
+impl NetflowDecoder {
+    pub fn new(counter: Arc<NetflowCounter>) -> Self {
+        Self {
+            templates: HashMap::new(),
+            uptimes: HashMap::new(),
+            pending: VecDeque::new(),
+            last_expire: Duration::ZERO,
+            counter,
+        }
+    }
+
+    pub fn decode(
+        &mut self,
+        exporter: IpAddr,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<Vec<NetflowRecord>> {
+        if now >= self.last_expire + EXPIRE_INTERVAL {
+            self.expire(now);
+            self.last_expire = now;
+        }
+        if data.len() < 2 {
+            return Err(truncated("header", 0));
+        }
+        let records = match read_u16_be(data) {
+            NETFLOW_VERSION_5 => Self::decode_v5(exporter, data)?,
+            NETFLOW_VERSION_9 | IPFIX_VERSION => self.decode_v9_ipfix(exporter, data, now)?,
+            v => {
+                return Err(Error::InvalidProtocol(format!(
+                    "unsupported netflow version {v}"
+                )))
+            }
+        };
+        self.counter
+            .records
+            .fetch_add(records.len() as u64, Ordering::Relaxed);
+        Ok(records)
+    }
+
+    fn expire(&mut self, now: Duration) {
+        let before = self.templates.len();
+        self.templates
+            .retain(|_, t| now.saturating_sub(t.updated) < TEMPLATE_TIMEOUT);
+        self.counter
+            .template_expired
+            .fetch_add((before - self.templates.len()) as u64, Ordering::Relaxed);
+
+        let before = self.pending.len();
+        self.pending
+            .retain(|p| now.saturating_sub(p.received) < PENDING_TIMEOUT);
+        self.counter
+            .drop_no_template
+            .fetch_add((before - self.pending.len()) as u64, Ordering::Relaxed);
+    }
+
+    fn decode_v5(exporter: IpAddr, data: &[u8]) -> Result<Vec<NetflowRecord>> {
+        if data.len() < V5_HEADER_LEN {
+            return Err(truncated("v5 header", 0));
+        }
+        let count = read_u16_be(&data[2..]) as usize;
+        if data.len() < V5_HEADER_LEN + count * V5_RECORD_LEN {
+            return Err(truncated("v5 records", data.len()));
+        }
+        let header = ExportHeader {
+            version: NETFLOW_VERSION_5,
+            sys_uptime: read_u32_be(&data[4..]),
+            export_time: read_u32_be(&data[8..]),
+        };
+        let engine_id = data[21] as u32;
+        Ok(data[V5_HEADER_LEN..V5_HEADER_LEN + count * V5_RECORD_LEN]
+            .chunks_exact(V5_RECORD_LEN)
+            .map(|r| NetflowRecord {
+                src_ip: IpAddr::from(Ipv4Addr::from(read_u32_be(r))),
+                dst_ip: IpAddr::from(Ipv4Addr::from(read_u32_be(&r[4..]))),
+                packet_count: read_u32_be(&r[16..]) as u64,
+                byte_count: read_u32_be(&r[20..]) as u64,
+                start: header.uptime_to_unix(read_u32_be(&r[24..])),
+                end: header.uptime_to_unix(read_u32_be(&r[28..])),
+                src_port: read_u16_be(&r[32..]),
+                dst_port: read_u16_be(&r[34..]),
+                tcp_flags: r[37],
+                protocol: r[38],
+                ..NetflowRecord::new(exporter, engine_id)
+            })
+            .collect())
+    }
+
+    fn decode_v9_ipfix(
+        &mut self,
+        exporter: IpAddr,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<Vec<NetflowRecord>> {
+        let version = read_u16_be(data);
+        let (header, observation_domain, mut offset, end) = if version == NETFLOW_VERSION_9 {
+            if data.len() < V9_HEADER_LEN {
+                return Err(truncated("v9 header", 0));
+            }
+            let header = ExportHeader {
+                version,
+                sys_uptime: read_u32_be(&data[4..]),
+                export_time: read_u32_be(&data[8..]),
+            };
+            (header, read_u32_be(&data[16..]), V9_HEADER_LEN, data.len())
+        } else {
+            if data.len() < IPFIX_HEADER_LEN {
+                return Err(truncated("ipfix header", 0));
+            }
+            let length = read_u16_be(&data[2..]) as usize;
+            if data.len() < length || length < IPFIX_HEADER_LEN {
+                return Err(truncated("ipfix message", data.len()));
+            }
+            let header = ExportHeader {
+                version,
+                sys_uptime: 0,
+                export_time: read_u32_be(&data[4..]),
+            };
+            (header, read_u32_be(&data[12..]), IPFIX_HEADER_LEN, length)
+        };
+
+        if version == NETFLOW_VERSION_9 {
+            self.check_restart(exporter, observation_domain, header.sys_uptime);
+        }
+
+        let mut records = vec![];
+        while offset + SET_HEADER_LEN <= end {
+            let set_id = read_u16_be(&data[offset..]);
+            let set_len = read_u16_be(&data[offset + 2..]) as usize;
+            if set_len < SET_HEADER_LEN || offset + set_len > end {
+                return Err(truncated("set", offset));
+            }
+            let body = &data[offset + SET_HEADER_LEN..offset + set_len];
+            offset += set_len;
+
+            match set_id {
+                V9_TEMPLATE_SET_ID | IPFIX_TEMPLATE_SET_ID => {
+                    if (set_id == V9_TEMPLATE_SET_ID) != (version == NETFLOW_VERSION_9) {
+                        continue;
+                    }
+                    for template_id in
+                        self.parse_templates(version, exporter, observation_domain, body, now)?
+                    {
+                        let key = TemplateKey {
+                            exporter,
+                            observation_domain,
+                            template_id,
+                        };
+                        records.extend(self.replay_pending(key)?);
+                    }
+                }
+                // options records carry exporter metadata only
+                V9_OPTIONS_TEMPLATE_SET_ID | IPFIX_OPTIONS_TEMPLATE_SET_ID => continue,
+                id if id >= MIN_DATA_SET_ID => {
+                    let key = TemplateKey {
+                        exporter,
+                        observation_domain,
+                        template_id: id,
+                    };
+                    match self.templates.get(&key) {
+                        Some(template) => {
+                            Self::decode_data_set(template, key, &header, body, &mut records)?
+                        }
+                        None => self.add_pending(key, header, body, now),
+                    }
+                }
+                _ => continue,
+            }
+        }
+        Ok(records)
+    }
+
+    // sysUpTime going backwards means the exporter restarted, its template ids may now
+    // refer to different layouts
+    fn check_restart(&mut self, exporter: IpAddr, observation_domain: u32, sys_uptime: u32) {
+        let Some(last) = self
+            .uptimes
+            .insert((exporter, observation_domain), sys_uptime)
+        else {
+            return;
+        };
+        // wrapping comparison, sysUpTime overflows after 49.7 days
+        if (sys_uptime.wrapping_sub(last) as i32) >= 0 {
+            return;
+        }
+        self.counter
+            .exporter_restarts
+            .fetch_add(1, Ordering::Relaxed);
+        self.templates
+            .retain(|k, _| k.exporter != exporter || k.observation_domain != observation_domain);
+    }
+
+    // Returns ids of the added or refreshed templates
+    fn parse_templates(
+        &mut self,
+        version: u16,
+        exporter: IpAddr,
+        observation_domain: u32,
+        body: &[u8],
+        now: Duration,
+    ) -> Result<Vec<u16>> {
+        let mut ids = vec![];
+        let mut offset = 0;
+        while offset + 4 <= body.len() {
+            let template_id = read_u16_be(&body[offset..]);
+            let field_count = read_u16_be(&body[offset + 2..]) as usize;
+            offset += 4;
+            let key = TemplateKey {
+                exporter,
+                observation_domain,
+                template_id,
+            };
+            // template withdrawal of IPFIX
+            if field_count == 0 {
+                self.templates.remove(&key);
+                continue;
+            }
+            if template_id < MIN_DATA_SET_ID {
+                return Err(Error::InvalidProtocol(format!(
+                    "netflow invalid template id {template_id}"
+                )));
+            }
+
+            let mut fields = Vec::with_capacity(field_count);
+            let mut min_record_len = 0;
+            for _ in 0..field_count {
+                if offset + 4 > body.len() {
+                    return Err(truncated("template", offset));
+                }
+                let id = read_u16_be(&body[offset..]);
+                let length = read_u16_be(&body[offset + 2..]);
+                offset += 4;
+                // NetFlow v9 has no enterprise numbers, ids above 32767 are vendor
+                // specific fields that are decoded as unknown ones
+                let enterprise = version == IPFIX_VERSION && id & ENTERPRISE_BIT != 0;
+                if enterprise {
+                    // enterprise number
+                    if offset + 4 > body.len() {
+                        return Err(truncated("template", offset));
+                    }
+                    offset += 4;
+                }
+                min_record_len += if length == VARIABLE_LENGTH {
+                    1
+                } else {
+                    length as usize
+                };
+                fields.push(Field {
+                    id: if enterprise { id & !ENTERPRISE_BIT } else { id },
+                    length,
+                    enterprise,
+                });
+            }
+            self.templates.insert(
+                key,
+                Template {
+                    fields,
+                    min_record_len,
+                    updated: now,
+                },
+            );
+            self.counter
+                .template_updates
+                .fetch_add(1, Ordering::Relaxed);
+            ids.push(template_id);
+        }
+        Ok(ids)
+    }
+
+    fn add_pending(&mut self, key: TemplateKey, header: ExportHeader, body: &[u8], now: Duration) {
+        self.counter
+            .waiting_template
+            .fetch_add(1, Ordering::Relaxed);
+        if self.pending.len() >= PENDING_LIMIT {
+            self.pending.pop_front();
+            self.counter
+                .drop_no_template
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        self.pending.push_back(PendingSet {
+            key,
+            header,
+            data: body.to_vec(),
+            received: now,
+        });
+    }
+
+    fn replay_pending(&mut self, key: TemplateKey) -> Result<Vec<NetflowRecord>> {
+        let mut records = vec![];
+        if !self.pending.iter().any(|p| p.key == key) {
+            return Ok(records);
+        }
+        let Some(template) = self.templates.get(&key) else {
+            return Ok(records);
+        };
+        let mut rest = VecDeque::with_capacity(self.pending.len());
+        for p in self.pending.drain(..) {
+            if p.key == key {
+                Self::decode_data_set(template, key, &p.header, &p.data, &mut records)?;
+            } else {
+                rest.push_back(p);
+            }
+        }
+        self.pending = rest;
+        Ok(records)
+    }
+
+    fn decode_data_set(
+        template: &Template,
+        key: TemplateKey,
+        header: &ExportHeader,
+        body: &[u8],
+        records: &mut Vec<NetflowRecord>,
+    ) -> Result<()> {
+        let mut offset = 0;
+        while template.min_record_len > 0 && offset + template.min_record_len <= body.len() {
+            let mut record = NetflowRecord::new(key.exporter, key.observation_domain);
+            let (mut total_bytes, mut total_packets) = (None, None);
+            for field in template.fields.iter() {
+                let mut length = field.length as usize;
+                if field.length == VARIABLE_LENGTH {
+                    length = *body
+                        .get(offset)
+                        .ok_or_else(|| truncated("record", offset))?
+                        as usize;
+                    offset += 1;
+                    if length == 255 {
+                        if offset + 2 > body.len() {
+                            return Err(truncated("record", offset));
+                        }
+                        length = read_u16_be(&body[offset..]) as usize;
+                        offset += 2;
+                    }
+                }
+                if offset + length > body.len() {
+                    return Err(truncated("record", offset));
+                }
+                let value = &body[offset..offset + length];
+                offset += length;
+                if field.enterprise {
+                    continue;
+                }
+                match (field.id, length) {
+                    (IN_BYTES, _) => record.byte_count = read_uint(value),
+                    (IN_PKTS, _) => record.packet_count = read_uint(value),
+                    (OCTET_TOTAL_COUNT, _) => total_bytes = Some(read_uint(value)),
+                    (PACKET_TOTAL_COUNT, _) => total_packets = Some(read_uint(value)),
+                    (PROTOCOL, 1) => record.protocol = value[0],
+                    (TCP_FLAGS, _) => record.tcp_flags = read_uint(value) as u8,
+                    (L4_SRC_PORT, 2) => record.src_port = read_u16_be(value),
+                    (L4_DST_PORT, 2) => record.dst_port = read_u16_be(value),
+                    (IPV4_SRC_ADDR, 4) => {
+                        record.src_ip = IpAddr::from(Ipv4Addr::from(read_u32_be(value)))
+                    }
+                    (IPV4_DST_ADDR, 4) => {
+                        record.dst_ip = IpAddr::from(Ipv4Addr::from(read_u32_be(value)))
+                    }
+                    (IPV6_SRC_ADDR, 16) => {
+                        let bs: [u8; 16] = value.try_into().unwrap();
+                        record.src_ip = IpAddr::from(Ipv6Addr::from(bs));
+                    }
+                    (IPV6_DST_ADDR, 16) => {
+                        let bs: [u8; 16] = value.try_into().unwrap();
+                        record.dst_ip = IpAddr::from(Ipv6Addr::from(bs));
+                    }
+                    (FIRST_SWITCHED, 4) if header.version == NETFLOW_VERSION_9 => {
+                        record.start = header.uptime_to_unix(read_u32_be(value))
+                    }
+                    (LAST_SWITCHED, 4) if header.version == NETFLOW_VERSION_9 => {
+                        record.end = header.uptime_to_unix(read_u32_be(value))
+                    }
+                    (FLOW_START_SECONDS, _) => record.start = Duration::from_secs(read_uint(value)),
+                    (FLOW_END_SECONDS, _) => record.end = Duration::from_secs(read_uint(value)),
+                    (FLOW_START_MILLISECONDS, _) => {
+                        record.start = Duration::from_millis(read_uint(value))
+                    }
+                    (FLOW_END_MILLISECONDS, _) => {
+                        record.end = Duration::from_millis(read_uint(value))
+                    }
+                    _ => (),
+                }
+            }
+            // delta counters are preferred, total counters are used when absent
+            if record.byte_count == 0 {
+                record.byte_count = total_bytes.unwrap_or_default();
+            }
+            if record.packet_count == 0 {
+                record.packet_count = total_packets.unwrap_or_default();
+            }
+            records.push(record);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORTER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+    const EXPORT_TIME: u32 = 1_700_000_000;
+
+    // 5-tuple, bytes, packets, start/end and tcp flags in NetFlow v9 layout
+    const V9_FIELDS: [(u16, u16); 10] = [
+        (IPV4_SRC_ADDR, 4),
+        (IPV4_DST_ADDR, 4),
+        (L4_SRC_PORT, 2),
+        (L4_DST_PORT, 2),
+        (PROTOCOL, 1),
+        (TCP_FLAGS, 1),
+        (IN_BYTES, 4),
+        (IN_PKTS, 4),
+        (FIRST_SWITCHED, 4),
+        (LAST_SWITCHED, 4),
+    ];
+
+    fn set(id: u16, body: &[u8]) -> Vec<u8> {
+        let mut bs = vec![];
+        bs.extend_from_slice(&id.to_be_bytes());
+        bs.extend_from_slice(&((body.len() + SET_HEADER_LEN) as u16).to_be_bytes());
+        bs.extend_from_slice(body);
+        bs
+    }
+
+    fn template_set(set_id: u16, template_id: u16, fields: &[(u16, u16)]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&template_id.to_be_bytes());
+        body.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+        for (id, len) in fields {
+            body.extend_from_slice(&id.to_be_bytes());
+            body.extend_from_slice(&len.to_be_bytes());
+        }
+        set(set_id, &body)
+    }
+
+    fn v9_record(src_port: u16, bytes: u32, packets: u32) -> Vec<u8> {
+        let mut bs = vec![];
+        bs.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        bs.extend_from_slice(&src_port.to_be_bytes());
+        bs.extend_from_slice(&80u16.to_be_bytes());
+        bs.extend_from_slice(&[6, 0x12]);
+        bs.extend_from_slice(&bytes.to_be_bytes());
+        bs.extend_from_slice(&packets.to_be_bytes());
+        // first and last switched 4s and 1s before export
+        bs.extend_from_slice(&6000u32.to_be_bytes());
+        bs.extend_from_slice(&9000u32.to_be_bytes());
+        bs
+    }
+
+    fn v9_packet(sys_uptime: u32, sets: &[Vec<u8>]) -> Vec<u8> {
+        let mut bs = vec![];
+        bs.extend_from_slice(&NETFLOW_VERSION_9.to_be_bytes());
+        bs.extend_from_slice(&(sets.len() as u16).to_be_bytes());
+        for v in [sys_uptime, EXPORT_TIME, 1, 7] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        sets.iter().for_each(|s| bs.extend_from_slice(s));
+        bs
+    }
+
+    fn ipfix_packet(sets: &[Vec<u8>]) -> Vec<u8> {
+        let mut bs = vec![];
+        bs.extend_from_slice(&IPFIX_VERSION.to_be_bytes());
+        let length = IPFIX_HEADER_LEN + sets.iter().map(|s| s.len()).sum::<usize>();
+        bs.extend_from_slice(&(length as u16).to_be_bytes());
+        for v in [EXPORT_TIME, 1, 9] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        sets.iter().for_each(|s| bs.extend_from_slice(s));
+        bs
+    }
+
+    fn decoder() -> (NetflowDecoder, Arc<NetflowCounter>) {
+        let counter = Arc::new(NetflowCounter::default());
+        (NetflowDecoder::new(counter.clone()), counter)
+    }
+
+    #[test]
+    fn v9_template_then_data() {
+        let (mut decoder, _) = decoder();
+        let now = Duration::from_secs(100);
+        let template = v9_packet(10000, &[template_set(0, 256, &V9_FIELDS)]);
+        assert!(decoder.decode(EXPORTER, &template, now).unwrap().is_empty());
+
+        let mut body = v9_record(1234, 1500, 3);
+        body.extend(v9_record(1235, 60, 1));
+        // padding
+        body.extend([0, 0]);
+        let records = decoder
+            .decode(EXPORTER, &v9_packet(10000, &[set(256, &body)]), now)
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        let r = &records[0];
+        assert_eq!(r.exporter, EXPORTER);
+        assert_eq!(r.observation_domain, 7);
+        assert_eq!(r.src_ip, IpAddr::from(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(r.dst_ip, IpAddr::from(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!((r.src_port, r.dst_port, r.protocol), (1234, 80, 6));
+        assert_eq!((r.byte_count, r.packet_count, r.tcp_flags), (1500, 3, 0x12));
+        assert_eq!(r.start, Duration::from_secs(EXPORT_TIME as u64 - 4));
+        assert_eq!(r.end, Duration::from_secs(EXPORT_TIME as u64 - 1));
+        assert_eq!(records[1].src_port, 1235);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let (mut decoder, counter) = decoder();
+        let now = Duration::from_secs(100);
+        let data = v9_packet(10000, &[set(256, &v9_record(1234, 1500, 3))]);
+        assert!(decoder.decode(EXPORTER, &data, now).unwrap().is_empty());
+        assert_eq!(counter.waiting_template.load(Ordering::Relaxed), 1);
+
+        // the same template id of another exporter does not release the data
+        let other = IpAddr::from(Ipv4Addr::new(192, 168, 1, 2));
+        let template = v9_packet(10000, &[template_set(0, 256, &V9_FIELDS)]);
+        assert!(decoder.decode(other, &template, now).unwrap().is_empty());
+
+        let records = decoder.decode(EXPORTER, &template, now).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].byte_count, 1500);
+
+        // pending data sets expire if their template never arrives
+        let data = v9_packet(10000, &[set(257, &v9_record(1234, 1500, 3))]);
+        decoder.decode(EXPORTER, &data, now).unwrap();
+        decoder
+            .decode(EXPORTER, &template, now + PENDING_TIMEOUT)
+            .unwrap();
+        assert_eq!(counter.drop_no_template.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn template_refresh_and_restart() {
+        let (mut decoder, counter) = decoder();
+        let now = Duration::from_secs(100);
+        decoder
+            .decode(
+                EXPORTER,
+                &v9_packet(10000, &[template_set(0, 256, &V9_FIELDS)]),
+                now,
+            )
+            .unwrap();
+
+        // the template is refreshed with a different layout under the same id
+        let fields = [(L4_SRC_PORT, 2), (IN_BYTES, 8)];
+        let mut body = 53u16.to_be_bytes().to_vec();
+        body.extend(9000u64.to_be_bytes());
+        let packet = v9_packet(11000, &[template_set(0, 256, &fields), set(256, &body)]);
+        let records = decoder.decode(EXPORTER, &packet, now).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].src_port, records[0].byte_count), (53, 9000));
+
+        // sysUpTime wraparound is not a restart
+        for uptime in [0x8000_0000, u32::MAX] {
+            decoder
+                .decode(EXPORTER, &v9_packet(uptime, &[]), now)
+                .unwrap();
+        }
+        let records = decoder
+            .decode(EXPORTER, &v9_packet(100, &[set(256, &body)]), now)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(counter.exporter_restarts.load(Ordering::Relaxed), 0);
+
+        // the exporter restarted, its old templates are invalid
+        let records = decoder
+            .decode(EXPORTER, &v9_packet(50, &[set(256, &body)]), now)
+            .unwrap();
+        assert!(records.is_empty());
+        assert_eq!(counter.exporter_restarts.load(Ordering::Relaxed), 1);
+
+        // templates expire when not refreshed
+        decoder
+            .decode(
+                EXPORTER,
+                &v9_packet(60, &[template_set(0, 258, &fields)]),
+                now,
+            )
+            .unwrap();
+        decoder
+            .decode(EXPORTER, &v9_packet(70, &[]), now + TEMPLATE_TIMEOUT)
+            .unwrap();
+        assert_eq!(counter.template_expired.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn v9_vendor_field() {
+        let (mut decoder, _) = decoder();
+        let now = Duration::from_secs(100);
+        // a vendor field of NetFlow v9 is followed by the next field, not an enterprise number
+        let fields = [
+            (L4_SRC_PORT, 2),
+            (ENTERPRISE_BIT | IN_BYTES, 4),
+            (IN_PKTS, 4),
+        ];
+        let mut body = 53u16.to_be_bytes().to_vec();
+        body.extend(9000u32.to_be_bytes());
+        body.extend(3u32.to_be_bytes());
+        let packet = v9_packet(10000, &[template_set(0, 256, &fields), set(256, &body)]);
+        let records = decoder.decode(EXPORTER, &packet, now).unwrap();
+        assert_eq!(records.len(), 1);
+        let r = &records[0];
+        assert_eq!((r.src_port, r.packet_count), (53, 3));
+        // the vendor field is not taken as the standard field of the same low bits
+        assert_eq!(r.byte_count, 0);
+    }
+
+    #[test]
+    fn ipfix() {
+        let (mut decoder, _) = decoder();
+        let now = Duration::from_secs(100);
+        let fields: [(u16, u16); 7] = [
+            (IPV6_SRC_ADDR, 16),
+            (IPV6_DST_ADDR, 16),
+            (PROTOCOL, 1),
+            (PACKET_TOTAL_COUNT, 8),
+            (OCTET_TOTAL_COUNT, 8),
+            (FLOW_START_MILLISECONDS, 8),
+            (FLOW_END_MILLISECONDS, 8),
+        ];
+        let mut body = vec![];
+        body.extend_from_slice(&300u16.to_be_bytes());
+        body.extend_from_slice(&(fields.len() as u16 + 1).to_be_bytes());
+        for (id, len) in fields {
+            body.extend_from_slice(&id.to_be_bytes());
+            body.extend_from_slice(&len.to_be_bytes());
+        }
+        // variable length enterprise field with its enterprise number
+        body.extend_from_slice(&(ENTERPRISE_BIT | 1).to_be_bytes());
+        body.extend_from_slice(&VARIABLE_LENGTH.to_be_bytes());
+        body.extend_from_slice(&12345u32.to_be_bytes());
+        let template = set(IPFIX_TEMPLATE_SET_ID, &body);
+
+        let mut body = vec![];
+        body.extend(Ipv6Addr::LOCALHOST.octets());
+        body.extend(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+        body.push(17);
+        for v in [10u64, 1000, 1_700_000_000_000, 1_700_000_001_500] {
+            body.extend(v.to_be_bytes());
+        }
+        body.extend([3, b'a', b'b', b'c']);
+
+        let records = decoder
+            .decode(EXPORTER, &ipfix_packet(&[template, set(300, &body)]), now)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        let r = &records[0];
+        assert_eq!(r.observation_domain, 9);
+        assert_eq!(r.src_ip, IpAddr::from(Ipv6Addr::LOCALHOST));
+        assert_eq!((r.protocol, r.packet_count, r.byte_count), (17, 10, 1000));
+        assert_eq!(r.start, Duration::from_millis(1_700_000_000_000));
+        assert_eq!(r.end, Duration::from_millis(1_700_000_001_500));
+
+        // template withdrawal
+        let withdrawal = template_set(IPFIX_TEMPLATE_SET_ID, 300, &[]);
+        decoder
+            .decode(EXPORTER, &ipfix_packet(&[withdrawal]), now)
+            .unwrap();
+        assert!(decoder
+            .decode(EXPORTER, &ipfix_packet(&[set(300, &body)]), now)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn v5() {
+        let (mut decoder, _) = decoder();
+        let mut bs = vec![];
+        bs.extend_from_slice(&NETFLOW_VERSION_5.to_be_bytes());
+        bs.extend_from_slice(&1u16.to_be_bytes());
+        for v in [10000u32, EXPORT_TIME, 0, 1] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&[0, 3, 0, 0]);
+        bs.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2, 0, 0, 0, 0, 0, 1, 0, 2]);
+        for v in [3u32, 1500, 6000, 9000] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&[0x04, 0xd2, 0, 80, 0, 0x12, 6, 0]);
+        bs.extend_from_slice(&[0; 8]);
+
+        let records = decoder
+            .decode(EXPORTER, &bs, Duration::from_secs(100))
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        let r = &records[0];
+        assert_eq!(r.observation_domain, 3);
+        assert_eq!((r.src_port, r.dst_port, r.protocol), (1234, 80, 6));
+        assert_eq!((r.byte_count, r.packet_count, r.tcp_flags), (1500, 3, 0x12));
+        assert_eq!(r.start, Duration::from_secs(EXPORT_TIME as u64 - 4));
+
+        assert!(decoder
+            .decode(EXPORTER, &bs[..bs.len() - 1], Duration::from_secs(100))
+            .is_err());
+    }
+}
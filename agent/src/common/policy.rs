@@ -741,24 +741,28 @@ impl TryFrom<agent::FlowAcl> for Acl {
                 dst_ports.unwrap_err()
             ));
         }
-        let npb_actions: Vec<NpbAction> = a
-            .npb_actions
-            .iter()
-            .map(|n| {
-                NpbAction::new(
-                    n.npb_acl_group_id(),
-                    n.tunnel_id(),
-                    n.tunnel_ip()
-                        .parse::<IpAddr>()
-                        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
-                    n.tunnel_ip_id.unwrap_or_default() as u16,
-                    NpbTunnelType::new(n.tunnel_type.unwrap() as u8),
-                    TapSide::new(n.packet_capture_side.unwrap() as u8),
-                    DirectionType::new(n.direction.unwrap_or(1) as u8),
-                    n.payload_slice() as u16,
-                )
-            })
-            .collect();
+        let mut npb_actions: Vec<NpbAction> = Vec::with_capacity(a.npb_actions.len());
+        for n in a.npb_actions.iter() {
+            let mut action = NpbAction::new(
+                n.npb_acl_group_id(),
+                n.tunnel_id(),
+                n.tunnel_ip()
+                    .parse::<IpAddr>()
+                    .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                n.tunnel_ip_id.unwrap_or_default() as u16,
+                NpbTunnelType::new(n.tunnel_type.unwrap() as u8),
+                TapSide::new(n.packet_capture_side.unwrap() as u8),
+                DirectionType::new(n.direction.unwrap_or(1) as u8),
+                n.payload_slice() as u16,
+            );
+            if let Some(vlan_id) = n.vlan_id {
+                if vlan_id == 0 || vlan_id > 4095 {
+                    return Err(format!("Acl npb vlan_id {} out of range.\n", vlan_id));
+                }
+                action.set_vlan_id(Some(vlan_id as u16));
+            }
+            npb_actions.push(action);
+        }
 
         Ok(Acl {
             id: a.id.unwrap_or_default(),
@@ -1121,4 +1125,25 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_acl_npb_vlan_id() {
+        let flow_acl = |vlan_id: Option<u32>| agent::FlowAcl {
+            id: Some(1),
+            npb_actions: vec![agent::NpbAction {
+                tunnel_type: Some(agent::TunnelType::Vxlan as i32),
+                packet_capture_side: Some(agent::PacketCaptureSide::Src as i32),
+                vlan_id,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let acl = Acl::try_from(flow_acl(None)).unwrap();
+        assert_eq!(acl.npb_actions[0].vlan_id(), None);
+        let acl = Acl::try_from(flow_acl(Some(100))).unwrap();
+        assert_eq!(acl.npb_actions[0].vlan_id(), Some(100));
+        assert!(Acl::try_from(flow_acl(Some(0))).is_err());
+        assert!(Acl::try_from(flow_acl(Some(4096))).is_err());
+    }
 }
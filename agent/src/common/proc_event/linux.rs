@@ -41,7 +41,8 @@ const IO_MOUNT_SOURCE_OFFSET: usize = 284;
 const IO_MOUNT_POINT_OFFSET: usize = 796;
 const IO_FILE_DIR_OFFSET: usize = 1052;
 const IO_EVENT_BUFF_SIZE: usize = 1564;
-struct IoEventData {
+const IO_S_DEV_OFFSET: usize = 1572;
+pub struct IoEventData {
     bytes_count: u32, // Number of bytes read and written
     operation: u32,   // 0: write 1: read
     latency: u64,     // Function call delay, in nanoseconds
@@ -51,6 +52,21 @@ struct IoEventData {
     mount_source: Vec<u8>,
     mount_point: Vec<u8>,
     file_dir: Vec<u8>,
+    s_dev: Option<u32>, // kernel internal encoding, major << 20 | minor
+}
+
+impl IoEventData {
+    pub fn mount_point(&self) -> &[u8] {
+        &self.mount_point
+    }
+
+    pub fn file_dir(&self) -> &[u8] {
+        &self.file_dir
+    }
+
+    pub fn s_dev(&self) -> Option<u32> {
+        self.s_dev
+    }
 }
 
 impl TryFrom<&[u8]> for IoEventData {
@@ -79,6 +95,9 @@ impl TryFrom<&[u8]> for IoEventData {
             mount_source: parse_cstring_slice(&raw_data[IO_MOUNT_SOURCE_OFFSET..]),
             mount_point: parse_cstring_slice(&raw_data[IO_MOUNT_POINT_OFFSET..]),
             file_dir: parse_cstring_slice(&raw_data[IO_FILE_DIR_OFFSET..]),
+            s_dev: raw_data
+                .get(IO_S_DEV_OFFSET..IO_S_DEV_OFFSET + 4)
+                .map(read_u32_le),
         };
         Ok(io_event_data)
     }
@@ -202,6 +221,13 @@ impl ProcEvent {
 
         Ok(BoxedProcEvents(Box::new(proc_event)))
     }
+
+    pub fn io_event_data(&self) -> Option<&IoEventData> {
+        match &self.event_data {
+            EventData::IoEvent(d) => Some(d),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,296 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::error::{Error, Result};
+use crate::utils::bytes::read_u32_be;
+
+const SFLOW_VERSION_5: u32 = 5;
+
+const ADDRESS_TYPE_IPV4: u32 = 1;
+const ADDRESS_TYPE_IPV6: u32 = 2;
+
+// sample and record formats of the standard enterprise (0)
+const FORMAT_FLOW_SAMPLE: u32 = 1;
+const FORMAT_FLOW_SAMPLE_EXPANDED: u32 = 3;
+const FORMAT_RAW_PACKET_HEADER: u32 = 1;
+
+// header protocol of the raw packet header record
+pub const HEADER_PROTOCOL_ETHERNET: u32 = 1;
+
+// A sampled packet header in sFlow v5 flow samples
+#[derive(Debug, PartialEq, Eq)]
+pub struct SflowPacketSample<'a> {
+    pub agent: IpAddr,
+    // one packet is sampled out of `sampling_rate` packets, 0 is treated as 1
+    pub sampling_rate: u32,
+    pub input_if_index: u32,
+    pub header_protocol: u32,
+    pub frame_length: u32,
+    pub header: &'a [u8],
+}
+
+impl SflowPacketSample<'_> {
+    // Estimated number of packets on the wire represented by this sample
+    pub fn weight(&self) -> u32 {
+        self.sampling_rate.max(1)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Result<u32> {
+        Ok(read_u32_be(self.bytes(4)?))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < self.offset + len {
+            return Err(Error::InvalidProtocol(format!(
+                "sflow truncated at {} need {len} bytes",
+                self.offset
+            )));
+        }
+        let bs = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bs)
+    }
+
+    // XDR opaque data is padded to 4 bytes
+    fn opaque(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bs = self.bytes(len)?;
+        self.bytes((4 - len % 4) % 4)?;
+        Ok(bs)
+    }
+}
+
+// Parses an sFlow v5 datagram and returns all raw packet header records of its flow
+// samples, every record carries the sampling rate of the sample it belongs to, so
+// datagrams with different sampling rates can be mixed on one port
+pub fn parse_sflow_v5(data: &[u8]) -> Result<Vec<SflowPacketSample<'_>>> {
+    let mut reader = Reader { data, offset: 0 };
+    let version = reader.u32()?;
+    if version != SFLOW_VERSION_5 {
+        return Err(Error::InvalidProtocol(format!(
+            "unsupported sflow version {version}"
+        )));
+    }
+    let agent = match reader.u32()? {
+        ADDRESS_TYPE_IPV4 => {
+            let bs: [u8; 4] = reader.bytes(4)?.try_into().unwrap();
+            IpAddr::from(Ipv4Addr::from(bs))
+        }
+        ADDRESS_TYPE_IPV6 => {
+            let bs: [u8; 16] = reader.bytes(16)?.try_into().unwrap();
+            IpAddr::from(Ipv6Addr::from(bs))
+        }
+        t => {
+            return Err(Error::InvalidProtocol(format!(
+                "unsupported sflow agent address type {t}"
+            )))
+        }
+    };
+    // sub agent id, sequence number and uptime
+    reader.bytes(12)?;
+
+    let mut samples = vec![];
+    for _ in 0..reader.u32()? {
+        let format = reader.u32()?;
+        let length = reader.u32()? as usize;
+        let mut sample = Reader {
+            data: reader.opaque(length)?,
+            offset: 0,
+        };
+        let (sampling_rate, input_if_index) = match format {
+            FORMAT_FLOW_SAMPLE => {
+                // sequence number and source id
+                sample.bytes(8)?;
+                let sampling_rate = sample.u32()?;
+                // sample pool and drops
+                sample.bytes(8)?;
+                let input = sample.u32()?;
+                // output
+                sample.bytes(4)?;
+                // the top 2 bits of input are the format, the rest is the if_index
+                (sampling_rate, input & 0x3fff_ffff)
+            }
+            FORMAT_FLOW_SAMPLE_EXPANDED => {
+                // sequence number, source id type and index
+                sample.bytes(12)?;
+                let sampling_rate = sample.u32()?;
+                // sample pool, drops and input format
+                sample.bytes(12)?;
+                let input = sample.u32()?;
+                // output format and value
+                sample.bytes(8)?;
+                (sampling_rate, input)
+            }
+            // counter samples and vendor specific samples
+            _ => continue,
+        };
+        for _ in 0..sample.u32()? {
+            let format = sample.u32()?;
+            let length = sample.u32()? as usize;
+            let mut record = Reader {
+                data: sample.opaque(length)?,
+                offset: 0,
+            };
+            if format != FORMAT_RAW_PACKET_HEADER {
+                continue;
+            }
+            let header_protocol = record.u32()?;
+            let frame_length = record.u32()?;
+            // stripped
+            record.bytes(4)?;
+            let header_length = record.u32()? as usize;
+            samples.push(SflowPacketSample {
+                agent,
+                sampling_rate,
+                input_if_index,
+                header_protocol,
+                frame_length,
+                header: record.bytes(header_length)?,
+            });
+        }
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_record(header: &[u8], frame_length: u32) -> Vec<u8> {
+        let mut record = vec![];
+        for v in [1, frame_length, 4, header.len() as u32] {
+            record.extend_from_slice(&v.to_be_bytes());
+        }
+        record.extend_from_slice(header);
+        record.resize((record.len() + 3) / 4 * 4, 0);
+
+        let mut bs = vec![];
+        bs.extend_from_slice(&FORMAT_RAW_PACKET_HEADER.to_be_bytes());
+        bs.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        bs.extend_from_slice(&record);
+        bs
+    }
+
+    fn flow_sample(sampling_rate: u32, input: u32, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut sample = vec![];
+        for v in [1, 0, sampling_rate, 0, 0, input, 0, records.len() as u32] {
+            sample.extend_from_slice(&v.to_be_bytes());
+        }
+        records.iter().for_each(|r| sample.extend_from_slice(r));
+
+        let mut bs = vec![];
+        bs.extend_from_slice(&FORMAT_FLOW_SAMPLE.to_be_bytes());
+        bs.extend_from_slice(&(sample.len() as u32).to_be_bytes());
+        bs.extend_from_slice(&sample);
+        bs
+    }
+
+    fn expanded_flow_sample(sampling_rate: u32, input: u32, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut sample = vec![];
+        for v in [
+            1,
+            0,
+            0,
+            sampling_rate,
+            0,
+            0,
+            0,
+            input,
+            0,
+            0,
+            records.len() as u32,
+        ] {
+            sample.extend_from_slice(&v.to_be_bytes());
+        }
+        records.iter().for_each(|r| sample.extend_from_slice(r));
+
+        let mut bs = vec![];
+        bs.extend_from_slice(&FORMAT_FLOW_SAMPLE_EXPANDED.to_be_bytes());
+        bs.extend_from_slice(&(sample.len() as u32).to_be_bytes());
+        bs.extend_from_slice(&sample);
+        bs
+    }
+
+    fn counter_sample() -> Vec<u8> {
+        let mut bs = vec![];
+        for v in [2u32, 8, 0, 0] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs
+    }
+
+    fn datagram(samples: &[Vec<u8>]) -> Vec<u8> {
+        let mut bs = vec![];
+        for v in [SFLOW_VERSION_5, ADDRESS_TYPE_IPV4] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&[10, 0, 0, 1]);
+        for v in [0, 1, 1000, samples.len() as u32] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        samples.iter().for_each(|s| bs.extend_from_slice(s));
+        bs
+    }
+
+    #[test]
+    fn sampling_rates() {
+        let data = datagram(&[
+            flow_sample(4096, 3, &[header_record(&[0xaa; 14], 1500)]),
+            counter_sample(),
+            expanded_flow_sample(
+                512,
+                7,
+                &[header_record(&[0xbb; 15], 64), header_record(&[0xcc], 128)],
+            ),
+            flow_sample(0, 1, &[header_record(&[0xdd; 16], 60)]),
+        ]);
+        let samples = parse_sflow_v5(&data).unwrap();
+        assert_eq!(samples.len(), 4);
+        assert!(samples
+            .iter()
+            .all(|s| s.agent == IpAddr::from(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(
+            samples
+                .iter()
+                .map(|s| (s.sampling_rate, s.input_if_index, s.frame_length))
+                .collect::<Vec<_>>(),
+            vec![(4096, 3, 1500), (512, 7, 64), (512, 7, 128), (0, 1, 60)]
+        );
+        assert_eq!(samples[0].header, &[0xaa; 14]);
+        assert_eq!(samples[1].header, &[0xbb; 15]);
+        assert_eq!(samples[2].header, &[0xcc]);
+        assert_eq!(samples[3].weight(), 1);
+        assert_eq!(samples[0].weight(), 4096);
+    }
+
+    #[test]
+    fn malformed() {
+        let data = datagram(&[flow_sample(4096, 3, &[header_record(&[0xaa; 14], 1500)])]);
+        assert!(parse_sflow_v5(&data[..data.len() - 4]).is_err());
+
+        let mut data = data;
+        data[3] = 4;
+        assert!(parse_sflow_v5(&data).is_err());
+    }
+}
@@ -103,6 +103,10 @@ impl Sendable for BoxedTaggedFlow {
     fn message_type(&self) -> SendMessageType {
         SendMessageType::TaggedFlow
     }
+
+    fn hash_key(&self) -> Option<u64> {
+        Some(self.0.flow.flow_id)
+    }
 }
 
 #[cfg(test)]
@@ -15,12 +15,13 @@
  */
 
 use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
 use std::net::{IpAddr, ToSocketAddrs};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[cfg(feature = "extended_observability")]
@@ -28,6 +29,7 @@ use std::ffi::CString;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use envmnt::{ExpandOptions, ExpansionType};
+use ipnet::IpNet;
 #[cfg(feature = "extended_observability")]
 use libc::c_int;
 #[cfg(feature = "extended_observability")]
@@ -44,22 +46,36 @@ use serde::{
 use thiserror::Error;
 use tokio::runtime::Runtime;
 
-use crate::common::l7_protocol_log::{L7ProtocolBitmap, L7ProtocolParser};
-use crate::dispatcher::recv_engine::DEFAULT_BLOCK_SIZE;
+use crate::common::l7_protocol_log::{
+    get_all_protocol, L7ProtocolBitmap, L7ProtocolParser, L7ProtocolParserInterface,
+};
+use crate::config::handler::BlacklistTrie;
+use crate::dispatcher::recv_engine::{bpf::check_extra_bpf_filter, DEFAULT_BLOCK_SIZE};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 #[cfg(feature = "extended_observability")]
 use crate::ebpf;
-use crate::flow_generator::{DnsLog, MemcachedLog};
+use crate::flow_generator::{DnsLog, LdapLog, MemcachedLog, SmtpLog};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use crate::platform::{OsAppTag, ProcessData};
 use crate::{
-    common::DEFAULT_LOG_FILE, metric::document::TapSide, rpc::Session, trident::RunningMode,
+    common::{
+        DEFAULT_LOG_FILE, GENEVE_METADATA_SIZE_DEFAULT, GENEVE_METADATA_SIZE_LIMIT,
+        GENEVE_OPTION_HEADER_SIZE, GENEVE_OPTION_SELECTOR_LIMIT, IPV6_HEADER_ADJUST,
+        MPLS_LABEL_LIMIT_DEFAULT, NPB_DEFAULT_PORT, NPB_GPE_DEFAULT_PORT, NPB_GPE_VXLAN_FLAGS,
+        NPB_VXLAN_FLAGS,
+    },
+    metric::document::TapSide,
+    rpc::Session,
+    trident::RunningMode,
 };
 
+use packet_dedup::PacketDedupConfig;
 use public::{
     bitmap::Bitmap,
+    enums::CaptureNetworkType,
     l7_protocol::{L7Protocol, L7ProtocolChecker},
     proto::agent,
+    queue::OverflowPolicy,
     utils::bitmap::parse_u16_range_list_to_bitmap,
 };
 
@@ -364,6 +380,11 @@ pub struct ProcessMatcher {
     pub only_with_tag: bool,
     pub ignore: bool,
     pub rewrite_name: String,
+    // 0 means the default per-process rate applies
+    pub max_capture_rate: u64,
+    // overrides inputs.ebpf.profile.off_cpu.min_blocking_time for the matched processes
+    #[serde(with = "humantime_serde")]
+    pub off_cpu_min_blocking_time: Option<Duration>,
     pub enabled_features: Vec<String>,
 }
 
@@ -379,6 +400,8 @@ impl PartialEq for ProcessMatcher {
             && self.only_with_tag == other.only_with_tag
             && self.ignore == other.ignore
             && self.rewrite_name == other.rewrite_name
+            && self.max_capture_rate == other.max_capture_rate
+            && self.off_cpu_min_blocking_time == other.off_cpu_min_blocking_time
             && self.enabled_features == other.enabled_features
     }
 }
@@ -409,11 +432,64 @@ impl Default for ProcessMatcher {
             only_with_tag: false,
             ignore: false,
             rewrite_name: "".to_string(),
+            max_capture_rate: 0,
+            off_cpu_min_blocking_time: None,
             enabled_features: vec![],
         }
     }
 }
 
+impl ProcessMatcher {
+    pub const FEATURE_PROFILE_ON_CPU: &'static str = "ebpf.profile.on_cpu";
+    pub const FEATURE_PROFILE_OFF_CPU: &'static str = "ebpf.profile.off_cpu";
+    pub const FEATURE_PROFILE_MEMORY: &'static str = "ebpf.profile.memory";
+    // implied by a non-zero max_capture_rate, not meant to be listed in enabled_features
+    pub const FEATURE_SOCKET_CAPTURE_RATE: &'static str = "ebpf.socket.capture_rate";
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.enabled_features
+            .iter()
+            .any(|f| f.eq_ignore_ascii_case(feature))
+    }
+
+    // Returns the matchers with implied features filled in.
+    //
+    // The memory profiler follows the on-cpu matchers unless some matcher lists
+    // `ebpf.profile.memory`, in which case only those matchers enable it. This allows
+    // restricting memory profiling to a few processes without touching on-cpu profiling.
+    // Whether the profilers run at all is still decided by their `disabled` switches.
+    //
+    // Matchers with a max_capture_rate get `ebpf.socket.capture_rate` so that the
+    // process listener reports the processes to be rate limited.
+    pub fn resolve_features(matchers: &[ProcessMatcher]) -> Vec<ProcessMatcher> {
+        let memory_listed = matchers
+            .iter()
+            .any(|m| m.has_feature(Self::FEATURE_PROFILE_MEMORY));
+        matchers
+            .iter()
+            .map(|m| {
+                let mut m = m.clone();
+                if memory_listed {
+                    // normalize case so that the process listener finds it
+                    for f in m.enabled_features.iter_mut() {
+                        if f.eq_ignore_ascii_case(Self::FEATURE_PROFILE_MEMORY) {
+                            *f = Self::FEATURE_PROFILE_MEMORY.to_string();
+                        }
+                    }
+                } else if m.has_feature(Self::FEATURE_PROFILE_ON_CPU) {
+                    m.enabled_features
+                        .push(Self::FEATURE_PROFILE_MEMORY.to_string());
+                }
+                if m.max_capture_rate > 0 && !m.has_feature(Self::FEATURE_SOCKET_CAPTURE_RATE) {
+                    m.enabled_features
+                        .push(Self::FEATURE_SOCKET_CAPTURE_RATE.to_string());
+                }
+                m
+            })
+            .collect()
+    }
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 impl ProcessMatcher {
     // TODO: match_languages
@@ -768,17 +844,100 @@ pub struct BondInterface {
     pub slave_interfaces: Vec<String>,
 }
 
+// Packet fanout settings of the interfaces matching interface_regex, the ones not set fall back
+// to AfPacketTunning
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct InterfaceGroup {
+    pub interface_regex: String,
+    pub packet_fanout_mode: Option<u32>,
+    pub packet_fanout_count: Option<usize>,
+}
+
+impl InterfaceGroup {
+    pub fn packet_fanout_mode(&self, tunning: &AfPacketTunning) -> u32 {
+        self.packet_fanout_mode
+            .unwrap_or(tunning.packet_fanout_mode)
+    }
+
+    pub fn packet_fanout_count(&self, tunning: &AfPacketTunning) -> usize {
+        self.packet_fanout_count
+            .unwrap_or(tunning.packet_fanout_count)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct VlanPcpMapping {
+    pub pcp: u16,
+    // None means calculating capture network type from vlan tag
+    #[serde(default)]
+    pub capture_network_type: Option<u16>,
+}
+
+impl VlanPcpMapping {
+    pub const MAX_PCP: u16 = 7;
+    pub const OUTER_VLAN: u16 = 8;
+    pub const INNER_VLAN: u16 = 9;
+}
+
+// A single integer is kept for compatibility, it is the same as a mapping without
+// capture_network_type and may also be 8 (outer vlan) or 9 (inner vlan)
+fn deser_vlan_pcp_mappings<'de, D>(deserializer: D) -> Result<Vec<VlanPcpMapping>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PcpOrMappings {
+        Pcp(u16),
+        Mappings(Vec<VlanPcpMapping>),
+    }
+
+    match PcpOrMappings::deserialize(deserializer)? {
+        PcpOrMappings::Pcp(pcp) => Ok(vec![VlanPcpMapping {
+            pcp,
+            capture_network_type: None,
+        }]),
+        PcpOrMappings::Mappings(v) => Ok(v),
+    }
+}
+
+// Local dispatcher capturing the interfaces matching interface_regex, by its index starting
+// from 0. The first matching assignment wins.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct InterfaceDispatcherAssignment {
+    pub interface_regex: String,
+    pub dispatcher: usize,
+}
+
+// Promiscuous mode of the interfaces matching interface_regex instead of
+// `tunning.interface_promisc_enabled`. The first matching override wins.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct InterfacePromisc {
+    pub interface_regex: String,
+    pub promisc_enabled: bool,
+    // receive all multicast packets when promiscuous mode is disabled
+    pub allmulti_enabled: bool,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct AfPacket {
     pub interface_regex: String,
+    pub interface_exclude_regex: String,
     pub inner_interface_capture_enabled: bool,
     pub inner_interface_regex: String,
     pub bond_interfaces: Vec<BondInterface>,
+    pub interface_groups: Vec<InterfaceGroup>,
+    pub interface_dispatcher_assignments: Vec<InterfaceDispatcherAssignment>,
+    pub interface_promisc_overrides: Vec<InterfacePromisc>,
     pub extra_netns_regex: String,
     pub extra_bpf_filter: String,
     pub src_interfaces: Vec<String>,
-    pub vlan_pcp_in_physical_mirror_traffic: u16,
+    #[serde(deserialize_with = "deser_vlan_pcp_mappings")]
+    pub vlan_pcp_in_physical_mirror_traffic: Vec<VlanPcpMapping>,
     pub bpf_filter_disabled: bool,
     pub skip_npb_bpf: bool,
     pub tunning: AfPacketTunning,
@@ -789,12 +948,16 @@ impl Default for AfPacket {
         Self {
             interface_regex: "^(tap.*|cali.*|veth.*|eth.*|en[osipx].*|lxc.*|lo|[0-9a-f]+_h)$"
                 .to_string(),
+            interface_exclude_regex: "".to_string(),
             inner_interface_capture_enabled: false,
             inner_interface_regex: r"^eth\d+$".to_string(),
             bond_interfaces: vec![],
+            interface_groups: vec![],
+            interface_dispatcher_assignments: vec![],
+            interface_promisc_overrides: vec![],
             extra_netns_regex: "".to_string(),
             extra_bpf_filter: "".to_string(),
-            vlan_pcp_in_physical_mirror_traffic: 0,
+            vlan_pcp_in_physical_mirror_traffic: vec![VlanPcpMapping::default()],
             bpf_filter_disabled: false,
             skip_npb_bpf: false,
             tunning: AfPacketTunning::default(),
@@ -803,6 +966,62 @@ impl Default for AfPacket {
     }
 }
 
+impl AfPacket {
+    fn validate(&self) -> Result<(), String> {
+        let mappings = &self.vlan_pcp_in_physical_mirror_traffic;
+        let mut pcps = HashSet::new();
+        for m in mappings.iter() {
+            let legacy_vlan = mappings.len() == 1
+                && m.capture_network_type.is_none()
+                && (m.pcp == VlanPcpMapping::OUTER_VLAN || m.pcp == VlanPcpMapping::INNER_VLAN);
+            if m.pcp > VlanPcpMapping::MAX_PCP && !legacy_vlan {
+                return Err(format!(
+                    "vlan_pcp_in_physical_mirror_traffic pcp({}) not in [0, {}]",
+                    m.pcp,
+                    VlanPcpMapping::MAX_PCP
+                ));
+            }
+            if !pcps.insert(m.pcp) {
+                return Err(format!(
+                    "duplicate vlan_pcp_in_physical_mirror_traffic pcp({})",
+                    m.pcp
+                ));
+            }
+            if let Some(t) = m.capture_network_type {
+                if CaptureNetworkType::try_from(t).is_err() {
+                    return Err(format!(
+                        "vlan_pcp_in_physical_mirror_traffic pcp({}) capture_network_type({}) invalid",
+                        m.pcp, t
+                    ));
+                }
+            }
+        }
+        for a in self.interface_dispatcher_assignments.iter() {
+            if let Err(e) = Regex::new(&a.interface_regex) {
+                return Err(format!(
+                    "invalid interface_regex {} of interface dispatcher assignment: {e}",
+                    a.interface_regex
+                ));
+            }
+            if a.dispatcher >= self.tunning.packet_fanout_count {
+                return Err(format!(
+                    "dispatcher {} of interface dispatcher assignment {} not in [0, {})",
+                    a.dispatcher, a.interface_regex, self.tunning.packet_fanout_count
+                ));
+            }
+        }
+        for o in self.interface_promisc_overrides.iter() {
+            if let Err(e) = Regex::new(&o.interface_regex) {
+                return Err(format!(
+                    "invalid interface_regex {} of interface promisc override: {e}",
+                    o.interface_regex
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
 pub enum DpdkSource {
     #[default]
@@ -826,13 +1045,95 @@ where
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DpdkPortQueues {
+    pub port_id: u16,
+    pub queue_count: u16,
+}
+
+impl Default for DpdkPortQueues {
+    fn default() -> Self {
+        Self {
+            port_id: 0,
+            queue_count: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DpdkRss {
+    pub hash_key: String,
+    pub hash_fields: Vec<String>,
+}
+
+impl DpdkRss {
+    pub const MAX_QUEUE_COUNT: u16 = 64;
+    pub const HASH_FIELDS: [&'static str; 5] = ["ipv4", "ipv6", "tcp", "udp", "sctp"];
+    // Toeplitz key length of 40 bytes (most NICs) or 52 bytes (i40e/ice)
+    const HASH_KEY_LENGTHS: [usize; 2] = [40, 52];
+
+    pub fn parse_hash_key(&self) -> Result<Vec<u8>, String> {
+        let key = self.hash_key.trim();
+        if key.is_empty() {
+            return Ok(vec![]);
+        }
+        let key = key.replace(':', "");
+        if key.len() % 2 != 0 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("malformed rss hash_key({})", self.hash_key));
+        }
+        let bytes = (0..key.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&key[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        if !Self::HASH_KEY_LENGTHS.contains(&bytes.len()) {
+            return Err(format!(
+                "rss hash_key({}) length {} not in {:?}",
+                self.hash_key,
+                bytes.len(),
+                Self::HASH_KEY_LENGTHS
+            ));
+        }
+        Ok(bytes)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.parse_hash_key()?;
+        if self.hash_fields.is_empty() {
+            return Err("rss hash_fields is empty".to_owned());
+        }
+        for field in self.hash_fields.iter() {
+            let field = field.to_lowercase();
+            if !Self::HASH_FIELDS.contains(&field.as_str()) {
+                return Err(format!("unknown rss hash field({field})"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DpdkRss {
+    fn default() -> Self {
+        Self {
+            hash_key: "".to_owned(),
+            hash_fields: Self::HASH_FIELDS[..4]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Dpdk {
     #[serde(deserialize_with = "to_dpdk_source")]
     pub source: DpdkSource,
     #[serde(with = "humantime_serde")]
     pub reorder_cache_window_size: Duration,
+    pub ports: Vec<DpdkPortQueues>,
+    pub rss: DpdkRss,
 }
 
 impl Default for Dpdk {
@@ -840,14 +1141,79 @@ impl Default for Dpdk {
         Dpdk {
             reorder_cache_window_size: Duration::from_millis(60),
             source: DpdkSource::default(),
+            ports: vec![],
+            rss: DpdkRss::default(),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+impl Dpdk {
+    fn validate(&self) -> Result<(), String> {
+        let mut port_ids = HashSet::new();
+        for port in self.ports.iter() {
+            if !port_ids.insert(port.port_id) {
+                return Err(format!("duplicate dpdk port_id({})", port.port_id));
+            }
+            if port.queue_count == 0 || port.queue_count > DpdkRss::MAX_QUEUE_COUNT {
+                return Err(format!(
+                    "dpdk port {} queue_count({}) not in [1, {}]",
+                    port.port_id,
+                    port.queue_count,
+                    DpdkRss::MAX_QUEUE_COUNT
+                ));
+            }
+        }
+        self.rss.validate()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LibpcapInterface {
+    pub interface_regex: String,
+    pub snap_len: Option<u32>,
+    pub buffer_size: Option<usize>,
+    pub immediate_mode: Option<bool>,
+}
+
+impl LibpcapInterface {
+    const SNAP_LEN_RANGE: (u32, u32) = (64, 65535);
+    const BUFFER_SIZE_RANGE: (usize, usize) = (1 << 16, 1 << 31);
+
+    fn validate(&self) -> Result<(), String> {
+        if Regex::new(&self.interface_regex).is_err() {
+            return Err(format!(
+                "malformed libpcap interface_regex({})",
+                self.interface_regex
+            ));
+        }
+        if let Some(s) = self.snap_len {
+            if s < Self::SNAP_LEN_RANGE.0 || s > Self::SNAP_LEN_RANGE.1 {
+                return Err(format!(
+                    "libpcap interface {} snap_len({s}) not in {:?}",
+                    self.interface_regex,
+                    Self::SNAP_LEN_RANGE
+                ));
+            }
+        }
+        if let Some(s) = self.buffer_size {
+            if s < Self::BUFFER_SIZE_RANGE.0 || s > Self::BUFFER_SIZE_RANGE.1 {
+                return Err(format!(
+                    "libpcap interface {} buffer_size({s}) not in {:?}",
+                    self.interface_regex,
+                    Self::BUFFER_SIZE_RANGE
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Libpcap {
     pub enabled: bool,
+    pub interfaces: Vec<LibpcapInterface>,
 }
 
 impl Default for Libpcap {
@@ -857,6 +1223,7 @@ impl Default for Libpcap {
             enabled: false,
             #[cfg(target_os = "windows")]
             enabled: true,
+            interfaces: vec![],
         }
     }
 }
@@ -864,15 +1231,41 @@ impl Default for Libpcap {
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct VhostUser {
-    pub vhost_socket_path: String,
+    #[serde(deserialize_with = "deser_string_or_strings")]
+    pub vhost_socket_path: Vec<String>,
 }
 
 impl Default for VhostUser {
     fn default() -> Self {
         Self {
-            vhost_socket_path: "".to_string(),
+            vhost_socket_path: vec![],
+        }
+    }
+}
+
+// Accepts both a single string and a list of strings, empty strings and duplicates are removed
+fn deser_string_or_strings<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrStrings {
+        String(String),
+        Strings(Vec<String>),
+    }
+
+    let v = match StringOrStrings::deserialize(deserializer)? {
+        StringOrStrings::String(s) => vec![s],
+        StringOrStrings::Strings(v) => v,
+    };
+    let mut paths: Vec<String> = vec![];
+    for s in v {
+        if !s.is_empty() && !paths.contains(&s) {
+            paths.push(s);
         }
     }
+    Ok(paths)
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -900,25 +1293,101 @@ pub struct SpecialNetwork {
     pub physical_switch: PhysicalSwitch,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct DispatcherQueueCpu {
+    pub queue: usize,
+    pub cpu: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct CbpfTunning {
     pub dispatcher_queue_enabled: bool,
+    pub dispatcher_queue_cpu_pinning: Vec<DispatcherQueueCpu>,
     pub max_capture_packet_size: u32,
     pub raw_packet_buffer_block_size: usize,
     pub raw_packet_queue_size: usize,
     pub max_capture_pps: u64,
+    pub max_capture_pps_burst: u64,
 }
 
 impl Default for CbpfTunning {
     fn default() -> Self {
         Self {
             dispatcher_queue_enabled: false,
+            dispatcher_queue_cpu_pinning: vec![],
             max_capture_packet_size: 65535,
             raw_packet_buffer_block_size: 65536,
             raw_packet_queue_size: 131072,
             max_capture_pps: 1048576,
+            max_capture_pps_burst: 0,
+        }
+    }
+}
+
+impl CbpfTunning {
+    // an auto sized block holds this many packets of max_capture_packet_size
+    const RAW_PACKET_BUFFER_BLOCK_PACKETS: usize = 64;
+    const RAW_PACKET_BUFFER_BLOCK_SIZE_RANGE: (usize, usize) = (65536, 16000000);
+    const PAGE_SIZE: usize = 4096;
+    // CPU_SETSIZE
+    const CPU_ID_LIMIT: usize = 1024;
+
+    // 0 means one second worth of max_capture_pps
+    pub fn max_capture_pps_burst(&self) -> u64 {
+        if self.max_capture_pps_burst != 0 {
+            self.max_capture_pps_burst
+        } else {
+            self.max_capture_pps
+        }
+    }
+
+    // 0 means auto sizing from max_capture_packet_size
+    pub fn raw_packet_buffer_block_size(&self) -> usize {
+        if self.raw_packet_buffer_block_size != 0 {
+            return self.raw_packet_buffer_block_size;
+        }
+        (self.max_capture_packet_size as usize * Self::RAW_PACKET_BUFFER_BLOCK_PACKETS)
+            .next_multiple_of(Self::PAGE_SIZE)
+            .clamp(
+                Self::RAW_PACKET_BUFFER_BLOCK_SIZE_RANGE.0,
+                Self::RAW_PACKET_BUFFER_BLOCK_SIZE_RANGE.1,
+            )
+    }
+
+    pub fn dispatcher_queue_cpu(&self, queue: usize) -> Option<usize> {
+        self.dispatcher_queue_cpu_pinning
+            .iter()
+            .find(|p| p.queue == queue)
+            .map(|p| p.cpu)
+    }
+
+    // pinned cpus must be a subset of global.tunning.cpu_affinity if it is set
+    fn validate(&self, cpu_affinity: &[usize]) -> Result<(), String> {
+        let mut queues = HashSet::new();
+        for p in self.dispatcher_queue_cpu_pinning.iter() {
+            if !queues.insert(p.queue) {
+                return Err(format!(
+                    "duplicate dispatcher_queue_cpu_pinning queue({})",
+                    p.queue
+                ));
+            }
+            if p.cpu >= Self::CPU_ID_LIMIT {
+                return Err(format!(
+                    "dispatcher_queue_cpu_pinning queue({}) cpu({}) not in [0, {})",
+                    p.queue,
+                    p.cpu,
+                    Self::CPU_ID_LIMIT
+                ));
+            }
+            if !cpu_affinity.is_empty() && !cpu_affinity.contains(&p.cpu) {
+                return Err(format!(
+                    "dispatcher_queue_cpu_pinning queue({}) cpu({}) not in cpu_affinity {:?}",
+                    p.queue, p.cpu, cpu_affinity
+                ));
+            }
         }
+        Ok(())
     }
 }
 
@@ -927,24 +1396,86 @@ impl Default for CbpfTunning {
 pub struct PreProcess {
     pub tunnel_decap_protocols: Vec<u8>,
     pub tunnel_trim_protocols: Vec<String>,
+    pub mpls_label_limit: u8,
+    pub geneve_options: Vec<GeneveTlv>,
+    pub geneve_metadata_max_bytes: u8,
     pub packet_segmentation_reassembly: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct GeneveTlv {
+    pub class: u16,
+    #[serde(rename = "type")]
+    pub option_type: u8,
+}
+
 impl Default for PreProcess {
     fn default() -> Self {
         Self {
             tunnel_decap_protocols: vec![1, 2],
             tunnel_trim_protocols: vec![],
+            mpls_label_limit: MPLS_LABEL_LIMIT_DEFAULT,
+            geneve_options: vec![],
+            geneve_metadata_max_bytes: GENEVE_METADATA_SIZE_DEFAULT,
             packet_segmentation_reassembly: vec![],
         }
     }
 }
 
+impl PreProcess {
+    const MPLS_LABEL_LIMIT_RANGE: (u8, u8) = (1, 16);
+    const GENEVE_METADATA_MAX_BYTES_RANGE: (u8, u8) = (
+        GENEVE_OPTION_HEADER_SIZE as u8,
+        GENEVE_METADATA_SIZE_LIMIT as u8,
+    );
+
+    fn validate(&self) -> Result<(), String> {
+        if self.mpls_label_limit < Self::MPLS_LABEL_LIMIT_RANGE.0
+            || self.mpls_label_limit > Self::MPLS_LABEL_LIMIT_RANGE.1
+        {
+            return Err(format!(
+                "mpls_label_limit({}) not in {:?}",
+                self.mpls_label_limit,
+                Self::MPLS_LABEL_LIMIT_RANGE
+            ));
+        }
+        if self.geneve_metadata_max_bytes < Self::GENEVE_METADATA_MAX_BYTES_RANGE.0
+            || self.geneve_metadata_max_bytes > Self::GENEVE_METADATA_MAX_BYTES_RANGE.1
+        {
+            return Err(format!(
+                "geneve_metadata_max_bytes({}) not in {:?}",
+                self.geneve_metadata_max_bytes,
+                Self::GENEVE_METADATA_MAX_BYTES_RANGE
+            ));
+        }
+        if self.geneve_options.len() > GENEVE_OPTION_SELECTOR_LIMIT {
+            return Err(format!(
+                "geneve_options has {} entries, at most {} allowed",
+                self.geneve_options.len(),
+                GENEVE_OPTION_SELECTOR_LIMIT
+            ));
+        }
+        for (i, option) in self.geneve_options.iter().enumerate() {
+            if self.geneve_options[..i].contains(option) {
+                return Err(format!(
+                    "duplicate geneve option class {:#06x} type {:#04x}",
+                    option.class, option.option_type
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct PhysicalMirror {
     pub default_capture_network_type: u16,
     pub packet_dedup_disabled: bool,
+    #[serde(with = "humantime_serde")]
+    pub packet_dedup_window: Duration,
+    pub packet_dedup_table_size: usize,
+    pub packet_dedup_ignore_ttl_tos: bool,
     pub private_cloud_gateway_traffic: bool,
 }
 
@@ -953,11 +1484,50 @@ impl Default for PhysicalMirror {
         Self {
             default_capture_network_type: 3,
             packet_dedup_disabled: false,
+            packet_dedup_window: PacketDedupConfig::default().window,
+            packet_dedup_table_size: PacketDedupConfig::default().table_size,
+            packet_dedup_ignore_ttl_tos: false,
             private_cloud_gateway_traffic: false,
         }
     }
 }
 
+impl PhysicalMirror {
+    const PACKET_DEDUP_WINDOW_RANGE: (Duration, Duration) =
+        (Duration::from_micros(1), Duration::from_millis(100));
+    const PACKET_DEDUP_TABLE_SIZE_RANGE: (usize, usize) = (256, 1 << 20);
+
+    fn validate(&self) -> Result<(), String> {
+        if self.packet_dedup_window < Self::PACKET_DEDUP_WINDOW_RANGE.0
+            || self.packet_dedup_window > Self::PACKET_DEDUP_WINDOW_RANGE.1
+        {
+            return Err(format!(
+                "packet_dedup_window({:?}) not in {:?}",
+                self.packet_dedup_window,
+                Self::PACKET_DEDUP_WINDOW_RANGE
+            ));
+        }
+        if self.packet_dedup_table_size < Self::PACKET_DEDUP_TABLE_SIZE_RANGE.0
+            || self.packet_dedup_table_size > Self::PACKET_DEDUP_TABLE_SIZE_RANGE.1
+        {
+            return Err(format!(
+                "packet_dedup_table_size({}) not in {:?}",
+                self.packet_dedup_table_size,
+                Self::PACKET_DEDUP_TABLE_SIZE_RANGE
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn packet_dedup_config(&self) -> PacketDedupConfig {
+        PacketDedupConfig {
+            window: self.packet_dedup_window,
+            table_size: self.packet_dedup_table_size,
+            ignore_ttl_tos: self.packet_dedup_ignore_ttl_tos,
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Cbpf {
@@ -969,24 +1539,81 @@ pub struct Cbpf {
     pub physical_mirror: PhysicalMirror,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+impl Cbpf {
+    fn validate(&self, npb: &Npb) -> Result<(), String> {
+        self.preprocess.validate()?;
+        self.physical_mirror.validate()?;
+        if self.common.capture_mode == agent::PacketCaptureType::Mirror {
+            // mirrored packets are not sent to the MAC of the interface
+            if let Some(o) = self
+                .af_packet
+                .interface_promisc_overrides
+                .iter()
+                .find(|o| !o.promisc_enabled)
+            {
+                return Err(format!(
+                    "interface promisc override {} disabling promiscuous mode is not supported in mirror capture mode",
+                    o.interface_regex
+                ));
+            }
+        }
+        if self.common.capture_mode != agent::PacketCaptureType::Decap {
+            return Ok(());
+        }
+        // decap mode only strips tunnel headers and counts inner packets,
+        // it has no flow generator, npb or pcap pipeline behind it
+        if self.preprocess.tunnel_decap_protocols.is_empty() {
+            return Err("tunnel_decap_protocols is required in decap capture mode".to_owned());
+        }
+        if self.tunning.dispatcher_queue_enabled {
+            return Err(
+                "dispatcher_queue_enabled is not supported in decap capture mode".to_owned(),
+            );
+        }
+        if self.special_network.libpcap.enabled {
+            return Err("libpcap is not supported in decap capture mode".to_owned());
+        }
+        if !self.special_network.vhost_user.vhost_socket_path.is_empty() {
+            return Err("vhost_user is not supported in decap capture mode".to_owned());
+        }
+        if self.special_network.dpdk.source == DpdkSource::PDump {
+            return Err("dpdk pdump is not supported in decap capture mode".to_owned());
+        }
+        if npb.enabled {
+            return Err(
+                "npb is not supported in decap capture mode, outputs.npb.enabled must be false"
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfSocketUprobeTls {
     pub enabled: bool,
+    pub library_paths: Vec<String>,
+    pub binary_names: Vec<String>,
 }
 
 impl Default for EbpfSocketUprobeTls {
     fn default() -> Self {
-        Self { enabled: false }
+        Self {
+            enabled: false,
+            library_paths: vec![],
+            binary_names: vec![],
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfSocketUprobeGolang {
     pub enabled: bool,
     #[serde(with = "humantime_serde")]
     pub tracing_timeout: Duration,
+    pub offset_overrides: Vec<GolangOffsetOverride>,
 }
 
 impl Default for EbpfSocketUprobeGolang {
@@ -994,56 +1621,204 @@ impl Default for EbpfSocketUprobeGolang {
         Self {
             enabled: false,
             tracing_timeout: Duration::from_secs(120),
+            offset_overrides: vec![],
         }
     }
 }
 
-#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
-#[serde(default)]
-pub struct EbpfSocketUprobeDpdk {
-    pub command: String,
-    pub rx_hooks: Vec<String>,
-    pub tx_hooks: Vec<String>,
+impl EbpfSocketUprobeGolang {
+    fn validate(&self) -> Result<(), String> {
+        for o in self.offset_overrides.iter() {
+            o.validate()?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
-#[serde(default)]
-pub struct EbpfSocketUprobe {
-    pub golang: EbpfSocketUprobeGolang,
-    pub tls: EbpfSocketUprobeTls,
-    pub dpdk: EbpfSocketUprobeDpdk,
+// Go version without revision, prerelease versions such as go1.23rc1 belong to
+// the minor version they precede
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GoVersion {
+    pub major: u32,
+    pub minor: u32,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(default)]
-pub struct EbpfSocketKprobePorts {
-    pub ports: String,
+impl GoVersion {
+    // same as GO_VERSION() in ebpf/user/go_tracer.h
+    pub fn encode(&self, revision: u32) -> u32 {
+        (self.major << 16) + (self.minor << 8) + revision.min(255)
+    }
 }
 
-impl Default for EbpfSocketKprobePorts {
-    fn default() -> Self {
-        Self {
-            ports: "".to_string(),
+impl std::str::FromStr for GoVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s.trim();
+        let version = version.strip_prefix("go").unwrap_or(version);
+        let leading_number = |s: &str| {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            s[..end].parse::<u32>().ok().filter(|n| *n <= 255)
+        };
+        let Some((major, rest)) = version.split_once('.') else {
+            return Err(format!("invalid go version {:?}", s));
+        };
+        match (major.parse::<u32>().ok(), leading_number(rest)) {
+            (Some(major), Some(minor)) if major <= 255 => Ok(Self { major, minor }),
+            _ => Err(format!("invalid go version {:?}", s)),
         }
     }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
-#[serde(default)]
-pub struct EbpfSocketKprobe {
-    pub disabled: bool,
-    pub enable_unix_socket: bool,
-    pub blacklist: EbpfSocketKprobePorts,
-    pub whitelist: EbpfSocketKprobePorts,
+impl<'de> Deserialize<'de> for GoVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| de::Error::invalid_value(Unexpected::Str(&s), &"go1.23 or 1.23"))
+    }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct GolangStructOffset {
+    pub structure: String,
+    pub field: String,
+    pub offset: u32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct GolangOffsetOverride {
+    pub min_version: GoVersion,
+    pub max_version: GoVersion,
+    pub offsets: Vec<GolangStructOffset>,
+}
+
+impl GolangOffsetOverride {
+    // fields in `offsets` of ebpf/user/go_tracer.c
+    const KNOWN_FIELDS: [(&'static str, &'static str); 16] = [
+        ("runtime.g", "goid"),
+        ("crypto/tls.Conn", "conn"),
+        ("internal/poll.FD", "Sysfd"),
+        ("net/http.http2serverConn", "conn"),
+        ("net/http.http2ClientConn", "tconn"),
+        ("net/http.http2clientConnReadLoop", "cc"),
+        (
+            "google.golang.org/grpc/internal/transport.http2Client",
+            "conn",
+        ),
+        (
+            "google.golang.org/grpc/internal/transport.http2Server",
+            "conn",
+        ),
+        (
+            "google.golang.org/grpc/internal/transport.loopyWriter",
+            "framer",
+        ),
+        ("google.golang.org/grpc/internal/transport.framer", "writer"),
+        (
+            "google.golang.org/grpc/internal/transport.bufWriter",
+            "conn",
+        ),
+        (
+            "google.golang.org/grpc/internal/transport.loopyWriter",
+            "side",
+        ),
+        ("net/http.http2MetaHeadersFrame", "Fields"),
+        ("net/http.http2ClientConn", "nextStreamID"),
+        ("net/http.http2FrameHeader", "StreamID"),
+        ("golang.org/x/net/http2.Framer", "w"),
+    ];
+    const OFFSET_ALIGNMENT: u32 = 4;
+    const OFFSET_MAX: u32 = 4096;
+
+    pub fn contains(&self, version: &GoVersion) -> bool {
+        self.min_version <= *version && *version <= self.max_version
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.min_version > self.max_version {
+            return Err(format!(
+                "go offset override min_version {:?} greater than max_version {:?}",
+                self.min_version, self.max_version
+            ));
+        }
+        for o in self.offsets.iter() {
+            if !Self::KNOWN_FIELDS
+                .iter()
+                .any(|(s, f)| *s == o.structure && *f == o.field)
+            {
+                return Err(format!(
+                    "unknown go offset override field {}.{}",
+                    o.structure, o.field
+                ));
+            }
+            if o.offset % Self::OFFSET_ALIGNMENT != 0 || o.offset >= Self::OFFSET_MAX {
+                return Err(format!(
+                    "go offset override {}.{} offset {} not aligned to {} or not less than {}",
+                    o.structure,
+                    o.field,
+                    o.offset,
+                    Self::OFFSET_ALIGNMENT,
+                    Self::OFFSET_MAX
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketUprobeDpdk {
+    pub command: String,
+    pub rx_hooks: Vec<String>,
+    pub tx_hooks: Vec<String>,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketUprobe {
+    pub golang: EbpfSocketUprobeGolang,
+    pub tls: EbpfSocketUprobeTls,
+    pub dpdk: EbpfSocketUprobeDpdk,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobePorts {
+    pub ports: String,
+}
+
+impl Default for EbpfSocketKprobePorts {
+    fn default() -> Self {
+        Self {
+            ports: "".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EbpfSocketKprobe {
+    pub disabled: bool,
+    pub enable_unix_socket: bool,
+    pub blacklist: EbpfSocketKprobePorts,
+    pub whitelist: EbpfSocketKprobePorts,
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfSocketTunning {
     pub max_capture_rate: u64,
+    pub process_max_capture_rate: u64,
     pub syscall_trace_id_disabled: bool,
     pub map_prealloc_disabled: bool,
     pub fentry_enabled: bool,
+    pub io_uring_enabled: bool,
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -1086,13 +1861,17 @@ impl Default for EbpfTcpOptionTrace {
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfFileIoEvent {
     pub collect_mode: usize,
     #[serde(with = "humantime_serde")]
     pub minimal_duration: Duration,
     pub enable_virtual_file_collect: bool,
+    pub include_mount_points: Vec<String>,
+    pub exclude_mount_points: Vec<String>,
+    pub include_devices: Vec<String>,
+    pub exclude_devices: Vec<String>,
 }
 
 impl Default for EbpfFileIoEvent {
@@ -1101,11 +1880,53 @@ impl Default for EbpfFileIoEvent {
             collect_mode: 1,
             minimal_duration: Duration::from_millis(1),
             enable_virtual_file_collect: false,
+            include_mount_points: vec![],
+            exclude_mount_points: vec![],
+            include_devices: vec![],
+            exclude_devices: vec![],
         }
     }
 }
 
-#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+impl EbpfFileIoEvent {
+    // "major:minor" as in /proc/<pid>/mountinfo
+    pub fn parse_device(device: &str) -> Option<(u32, u32)> {
+        let (major, minor) = device.trim().split_once(':')?;
+        let (major, minor) = (major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?);
+        // MINORBITS is 20 in kernel internal dev_t
+        if major >= 1 << 12 || minor >= 1 << 20 {
+            return None;
+        }
+        Some((major, minor))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for path in self
+            .include_mount_points
+            .iter()
+            .chain(self.exclude_mount_points.iter())
+        {
+            if !path.starts_with('/') {
+                return Err(format!(
+                    "io_event mount point {:?} is not an absolute path",
+                    path
+                ));
+            }
+        }
+        for device in self
+            .include_devices
+            .iter()
+            .chain(self.exclude_devices.iter())
+        {
+            if Self::parse_device(device).is_none() {
+                return Err(format!("malformed io_event device {:?}", device));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfFile {
     pub io_event: EbpfFileIoEvent,
@@ -1136,6 +1957,13 @@ pub struct EbpfProfileOffCpu {
     #[serde(with = "humantime_serde")]
     pub min_blocking_time: Duration,
     pub aggregate_by_cpu: bool,
+    pub aggregate_by_thread: bool,
+    pub max_threads_per_process: usize,
+}
+
+impl EbpfProfileOffCpu {
+    // blocking events longer than this are not collected
+    pub const MAX_MIN_BLOCKING_TIME: Duration = Duration::from_secs(3600);
 }
 
 impl Default for EbpfProfileOffCpu {
@@ -1144,6 +1972,8 @@ impl Default for EbpfProfileOffCpu {
             disabled: true,
             min_blocking_time: Duration::from_micros(50),
             aggregate_by_cpu: false,
+            aggregate_by_thread: false,
+            max_threads_per_process: 64,
         }
     }
 }
@@ -1159,6 +1989,8 @@ pub struct EbpfProfileMemory {
     #[serde(with = "humantime_serde")]
     pub sort_interval: Duration,
     pub queue_size: usize,
+    pub allocation_size_histogram: bool,
+    pub histogram_bucket_count: usize,
 }
 
 impl Default for EbpfProfileMemory {
@@ -1170,6 +2002,40 @@ impl Default for EbpfProfileMemory {
             sort_length: 16384,
             sort_interval: Duration::from_millis(1500),
             queue_size: 32768,
+            allocation_size_histogram: false,
+            histogram_bucket_count: 16,
+        }
+    }
+}
+
+impl EbpfProfileMemory {
+    const REPORT_INTERVAL_MIN: Duration = Duration::from_secs(1);
+    const REPORT_INTERVAL_MAX: Duration = Duration::from_secs(60);
+    const HISTOGRAM_BUCKET_COUNT_MIN: usize = 2;
+    const HISTOGRAM_BUCKET_COUNT_MAX: usize = 32;
+
+    fn adjust(&mut self) {
+        let report_interval = self
+            .report_interval
+            .clamp(Self::REPORT_INTERVAL_MIN, Self::REPORT_INTERVAL_MAX);
+        if report_interval != self.report_interval {
+            warn!(
+                "inputs.ebpf.profile.memory.report_interval {:?} out of range, adjusted to {:?}",
+                self.report_interval, report_interval
+            );
+            self.report_interval = report_interval;
+        }
+        // histograms are reset on every report, so they are bound to the same interval
+        let bucket_count = self.histogram_bucket_count.clamp(
+            Self::HISTOGRAM_BUCKET_COUNT_MIN,
+            Self::HISTOGRAM_BUCKET_COUNT_MAX,
+        );
+        if bucket_count != self.histogram_bucket_count {
+            warn!(
+                "inputs.ebpf.profile.memory.histogram_bucket_count {} out of range, adjusted to {}",
+                self.histogram_bucket_count, bucket_count
+            );
+            self.histogram_bucket_count = bucket_count;
         }
     }
 }
@@ -1178,12 +2044,30 @@ impl Default for EbpfProfileMemory {
 #[serde(default)]
 pub struct EbpfProfilePreprocess {
     pub stack_compression: bool,
+    #[serde(deserialize_with = "deser_humantime_with_zero")]
+    pub batch_interval: Duration,
 }
 
 impl Default for EbpfProfilePreprocess {
     fn default() -> Self {
         Self {
             stack_compression: true,
+            batch_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl EbpfProfilePreprocess {
+    const BATCH_INTERVAL_MAX: Duration = Duration::from_secs(60);
+
+    fn adjust(&mut self) {
+        if self.batch_interval > Self::BATCH_INTERVAL_MAX {
+            warn!(
+                "inputs.ebpf.profile.preprocess.batch_interval {:?} out of range, adjusted to {:?}",
+                self.batch_interval,
+                Self::BATCH_INTERVAL_MAX
+            );
+            self.batch_interval = Self::BATCH_INTERVAL_MAX;
         }
     }
 }
@@ -1208,6 +2092,33 @@ impl Default for Unwinding {
     }
 }
 
+impl Unwinding {
+    const PROCESS_MAP_SIZE_MAX: u32 = 131072;
+    const SHARD_MAP_SIZE_MAX: u32 = 4096;
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.dwarf_regex.is_empty() && regex::Regex::new(&self.dwarf_regex).is_err() {
+            return Err(format!("malformed dwarf_regex({})", self.dwarf_regex));
+        }
+        if !(1..=Self::PROCESS_MAP_SIZE_MAX).contains(&self.dwarf_process_map_size) {
+            return Err(format!(
+                "dwarf_process_map_size {} not in [1, {}]",
+                self.dwarf_process_map_size,
+                Self::PROCESS_MAP_SIZE_MAX
+            ));
+        }
+        if !(1..=Self::SHARD_MAP_SIZE_MAX).contains(&self.dwarf_shard_map_size) {
+            return Err(format!(
+                "dwarf_shard_map_size {} not in [1, {}]",
+                self.dwarf_shard_map_size,
+                Self::SHARD_MAP_SIZE_MAX
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct EbpfProfileLanguages {
@@ -1243,12 +2154,18 @@ pub struct EbpfProfile {
 #[serde(default)]
 pub struct EbpfTunning {
     pub collector_queue_size: usize,
+    pub collector_queue_overflow_policy: QueueOverflowPolicy,
+    #[serde(with = "humantime_serde")]
+    pub collector_queue_block_timeout: Duration,
     pub userspace_worker_threads: i32,
     pub kick_kern_nice: i32,
     pub perf_pages_count: u32,
     pub kernel_ring_size: u32,
     pub max_socket_entries: u32,
     pub socket_map_reclaim_threshold: u32,
+    pub socket_map_reclaim_mode: SocketMapReclaimMode,
+    pub socket_map_reclaim_threshold_min: u32,
+    pub socket_map_reclaim_threshold_max: u32,
     pub max_trace_entries: u32,
 }
 
@@ -1256,12 +2173,17 @@ impl Default for EbpfTunning {
     fn default() -> Self {
         Self {
             collector_queue_size: 65535,
+            collector_queue_overflow_policy: QueueOverflowPolicy::DropOldest,
+            collector_queue_block_timeout: Duration::from_millis(10),
             userspace_worker_threads: 1,
             kick_kern_nice: 0,
             perf_pages_count: 128,
             kernel_ring_size: 65536,
             max_socket_entries: 131072,
             socket_map_reclaim_threshold: 120000,
+            socket_map_reclaim_mode: SocketMapReclaimMode::Static,
+            socket_map_reclaim_threshold_min: 65536,
+            socket_map_reclaim_threshold_max: 126976,
             max_trace_entries: 131072,
         }
     }
@@ -1275,9 +2197,55 @@ impl EbpfTunning {
                 self.kick_kern_nice
             ));
         }
+        if self.collector_queue_overflow_policy == QueueOverflowPolicy::BlockWithTimeout
+            && (self.collector_queue_block_timeout < Duration::from_millis(1)
+                || self.collector_queue_block_timeout > Duration::from_secs(1))
+        {
+            return Err(format!(
+                "collector_queue_block_timeout {:?} not in [1ms, 1s]",
+                self.collector_queue_block_timeout
+            ));
+        }
+        if self.socket_map_reclaim_mode == SocketMapReclaimMode::Adaptive
+            && self.socket_map_reclaim_threshold_min > self.socket_map_reclaim_threshold_max
+        {
+            return Err(format!(
+                "socket_map_reclaim_threshold_min {} > socket_map_reclaim_threshold_max {}",
+                self.socket_map_reclaim_threshold_min, self.socket_map_reclaim_threshold_max
+            ));
+        }
 
         Ok(())
     }
+
+    pub fn collector_queue_overflow_policy(&self) -> OverflowPolicy {
+        match self.collector_queue_overflow_policy {
+            QueueOverflowPolicy::DropOldest => OverflowPolicy::DropOldest,
+            QueueOverflowPolicy::DropNewest => OverflowPolicy::DropNewest,
+            QueueOverflowPolicy::BlockWithTimeout => {
+                OverflowPolicy::BlockWithTimeout(self.collector_queue_block_timeout)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SocketMapReclaimMode {
+    // reclaim at socket_map_reclaim_threshold
+    #[default]
+    Static,
+    // adjust the threshold between the bounds by the growth of the socket map
+    Adaptive,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueOverflowPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    BlockWithTimeout,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -1430,6 +2398,91 @@ impl EbpfSocketPreprocess {
         Self::adjust_http2(&mut self.out_of_order_reassembly_protocols);
         Self::adjust_http2(&mut self.segmentation_reassembly_protocols);
     }
+
+    // ICMP is not traced by eBPF socket probes
+    const UNSUPPORTED_PROTOCOLS: [L7Protocol; 1] = [L7Protocol::Ping];
+
+    fn supported_protocols() -> Vec<&'static str> {
+        let mut names = get_all_protocol()
+            .iter()
+            .filter(|p| !Self::UNSUPPORTED_PROTOCOLS.contains(&p.protocol()))
+            .map(|p| p.as_str())
+            .collect::<Vec<_>>();
+        // aliases accepted by L7ProtocolParser::try_from but not listed by get_all_protocol
+        names.extend(["gRPC", "Triple"]);
+        names.sort_by_key(|n| n.to_lowercase());
+        names.dedup();
+        names
+    }
+
+    // Replace protocol names with the case used by L7ProtocolParser, and drop the ones
+    // that are unknown or can not be reassembled so that they do not silently do nothing
+    fn canonicalize_protocols(protocols: &mut Vec<String>, field: &str) {
+        if protocols.is_empty() {
+            return;
+        }
+        let supported = Self::supported_protocols();
+        protocols.retain_mut(|name| {
+            if let Ok(p) = L7ProtocolParser::try_from(name.as_str()) {
+                if !Self::UNSUPPORTED_PROTOCOLS.contains(&p.protocol()) {
+                    return true;
+                }
+            }
+            match supported.iter().find(|s| s.eq_ignore_ascii_case(name)) {
+                Some(s) => {
+                    if name != s {
+                        warn!("{field}: protocol {name} is treated as {s}");
+                        *name = s.to_string();
+                    }
+                    true
+                }
+                None => {
+                    warn!("{field}: protocol {name} is not supported and ignored, supported protocols: {supported:?}");
+                    false
+                }
+            }
+        });
+    }
+
+    fn validate_protocols(&mut self, enabled_protocols: &[String]) {
+        Self::canonicalize_protocols(
+            &mut self.out_of_order_reassembly_protocols,
+            "inputs.ebpf.socket.preprocess.out_of_order_reassembly_protocols",
+        );
+        Self::canonicalize_protocols(
+            &mut self.segmentation_reassembly_protocols,
+            "inputs.ebpf.socket.preprocess.segmentation_reassembly_protocols",
+        );
+
+        // HTTP2, gRPC and Triple share one parser
+        let is_enabled = |bitmap: &L7ProtocolBitmap, p: L7Protocol| match p {
+            L7Protocol::Http2 | L7Protocol::Grpc | L7Protocol::Triple => {
+                bitmap.is_enabled(L7Protocol::Http2)
+                    || bitmap.is_enabled(L7Protocol::Grpc)
+                    || bitmap.is_enabled(L7Protocol::Triple)
+            }
+            _ => bitmap.is_enabled(p),
+        };
+        let enabled = L7ProtocolBitmap::from(enabled_protocols);
+        let out_of_order =
+            L7ProtocolBitmap::from(self.out_of_order_reassembly_protocols.as_slice());
+        for name in self.out_of_order_reassembly_protocols.iter() {
+            if let Ok(p) = L7ProtocolParser::try_from(name.as_str()) {
+                if !is_enabled(&enabled, p.protocol()) {
+                    warn!("inputs.ebpf.socket.preprocess.out_of_order_reassembly_protocols: protocol {name} is not in processors.request_log.application_protocol_inference.enabled_protocols, reassembly is wasted");
+                }
+            }
+        }
+        for name in self.segmentation_reassembly_protocols.iter() {
+            if let Ok(p) = L7ProtocolParser::try_from(name.as_str()) {
+                if !is_enabled(&out_of_order, p.protocol()) {
+                    warn!("inputs.ebpf.socket.preprocess.segmentation_reassembly_protocols: protocol {name} is not in inputs.ebpf.socket.preprocess.out_of_order_reassembly_protocols and does not take effect");
+                } else if !is_enabled(&enabled, p.protocol()) {
+                    warn!("inputs.ebpf.socket.preprocess.segmentation_reassembly_protocols: protocol {name} is not in processors.request_log.application_protocol_inference.enabled_protocols, reassembly is wasted");
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -1441,6 +2494,7 @@ pub struct Ebpf {
     pub profile: EbpfProfile,
     pub tunning: EbpfTunning,
     pub network: EbpfNetwork,
+    pub excluded_namespaces: Vec<String>,
     #[serde(skip)]
     pub java_symbol_file_refresh_defer_interval: i32,
 }
@@ -1454,6 +2508,7 @@ impl Default for Ebpf {
             profile: EbpfProfile::default(),
             tunning: EbpfTunning::default(),
             network: EbpfNetwork::default(),
+            excluded_namespaces: vec![],
             java_symbol_file_refresh_defer_interval: 60,
         }
     }
@@ -1613,6 +2668,35 @@ impl Default for Resources {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrometheusLabelRewriteAction {
+    // rewrites the value if matched, otherwise the value is unchanged
+    #[default]
+    Replace,
+    // drops the label if the value is not matched
+    Keep,
+    // drops the label if the value is matched
+    Drop,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PrometheusLabelRewriteRule {
+    pub label: String,
+    pub regex: String,
+    // replaces all matches in the value, `$1` or `${name}` refers to capture groups,
+    // the value is unchanged if empty
+    pub replacement: String,
+    pub action: PrometheusLabelRewriteAction,
+}
+
+impl PrometheusLabelRewriteRule {
+    pub fn compile(&self) -> Result<Regex, regex::Error> {
+        Regex::new(&self.regex)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct PrometheusExtraLabels {
@@ -1620,6 +2704,8 @@ pub struct PrometheusExtraLabels {
     pub extra_labels: Vec<String>,
     pub label_length: usize,
     pub value_length: usize,
+    // applied in order to the values of extra_labels
+    pub rewrite_rules: Vec<PrometheusLabelRewriteRule>,
 }
 
 impl Default for PrometheusExtraLabels {
@@ -1629,7 +2715,33 @@ impl Default for PrometheusExtraLabels {
             extra_labels: vec![],
             label_length: 1024,
             value_length: 4096,
+            rewrite_rules: vec![],
+        }
+    }
+}
+
+impl PrometheusExtraLabels {
+    fn validate(&self) -> Result<(), String> {
+        for rule in self.rewrite_rules.iter() {
+            // header names are case insensitive
+            if !self
+                .extra_labels
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(&rule.label))
+            {
+                return Err(format!(
+                    "inputs.integration.prometheus_extra_labels.rewrite_rules label({}) not in extra_labels",
+                    rule.label
+                ));
+            }
+            if let Err(e) = rule.compile() {
+                return Err(format!(
+                    "inputs.integration.prometheus_extra_labels.rewrite_rules label({}) has invalid regex: {}",
+                    rule.label, e
+                ));
+            }
         }
+        Ok(())
     }
 }
 
@@ -1653,11 +2765,102 @@ impl Default for FeatureControl {
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SignalSourceFilter {
+    // CIDRs or addresses, empty allows all sources not denied
+    pub allow: Vec<String>,
+    // wins over overlapping allow entries
+    pub deny: Vec<String>,
+}
+
+impl SignalSourceFilter {
+    // Bare addresses are taken as host networks
+    fn parse_networks(list: &[String]) -> Result<Vec<IpNet>, String> {
+        list.iter()
+            .map(|s| {
+                let s = s.trim();
+                s.parse::<IpNet>()
+                    .ok()
+                    .or_else(|| {
+                        let ip = s.parse::<IpAddr>().ok()?;
+                        IpNet::new(ip, if ip.is_ipv4() { 32 } else { 128 }).ok()
+                    })
+                    .map(|net| net.trunc())
+                    .ok_or_else(|| format!("invalid CIDR({})", s))
+            })
+            .collect()
+    }
+
+    pub fn allow_networks(&self) -> Result<Vec<IpNet>, String> {
+        Self::parse_networks(&self.allow)
+    }
+
+    pub fn deny_networks(&self) -> Result<Vec<IpNet>, String> {
+        Self::parse_networks(&self.deny)
+    }
+}
+
+// Sources of requests to the integration listeners, checked before request bodies are read
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SourceFilter {
+    pub trace: SignalSourceFilter,
+    pub metric: SignalSourceFilter,
+    pub log: SignalSourceFilter,
+    pub profile: SignalSourceFilter,
+}
+
+impl SourceFilter {
+    fn validate(&self) -> Result<(), String> {
+        for (name, filter) in [
+            ("trace", &self.trace),
+            ("metric", &self.metric),
+            ("log", &self.log),
+            ("profile", &self.profile),
+        ] {
+            if let Err(e) = filter.allow_networks() {
+                return Err(format!(
+                    "inputs.integration.source_filter.{}.allow {}",
+                    name, e
+                ));
+            }
+            if let Err(e) = filter.deny_networks() {
+                return Err(format!(
+                    "inputs.integration.source_filter.{}.deny {}",
+                    name, e
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Compression {
     pub trace: bool,
     pub profile: bool,
+    // limit of request bodies after decompression, in case of decompression bombs
+    #[serde(deserialize_with = "deser_u64_with_mega_unit")]
+    pub max_decompressed_size: u64,
+}
+
+impl Compression {
+    pub const MAX_DECOMPRESSED_SIZE_RANGE: (u64, u64) = (1 << 20, 1 << 30);
+
+    fn validate(&self) -> Result<(), String> {
+        let (min, max) = Self::MAX_DECOMPRESSED_SIZE_RANGE;
+        if self.max_decompressed_size < min || self.max_decompressed_size > max {
+            return Err(format!(
+                "inputs.integration.compression.max_decompressed_size({}MiB) not in [{}MiB, {}MiB]",
+                self.max_decompressed_size >> 20,
+                min >> 20,
+                max >> 20
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Default for Compression {
@@ -1665,18 +2868,89 @@ impl Default for Compression {
         Self {
             trace: true,
             profile: true,
+            max_decompressed_size: 64 << 20,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Statsd {
+    // UDP port on listen_address, 0 means disabled
+    pub listen_port: u16,
+    // empty means no unix domain socket
+    pub listen_unix_socket_path: String,
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+}
+
+impl Default for Statsd {
+    fn default() -> Self {
+        Self {
+            listen_port: 0,
+            listen_unix_socket_path: "".to_string(),
+            flush_interval: Duration::from_secs(10),
         }
     }
 }
 
+impl Statsd {
+    pub fn enabled(&self) -> bool {
+        self.listen_port != 0 || !self.listen_unix_socket_path.is_empty()
+    }
+
+    pub fn listen_unix_socket_path(&self) -> Option<PathBuf> {
+        if self.listen_unix_socket_path.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(&self.listen_unix_socket_path))
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.flush_interval < Duration::from_secs(1)
+            || self.flush_interval > Duration::from_secs(3600)
+        {
+            return Err(format!(
+                "inputs.integration.statsd.flush_interval({:?}) not in [1s, 1h]",
+                self.flush_interval
+            ));
+        }
+        if let Some(path) = self.listen_unix_socket_path() {
+            if cfg!(not(unix)) {
+                return Err(format!(
+                    "inputs.integration.statsd.listen_unix_socket_path({}) not supported on this platform",
+                    self.listen_unix_socket_path
+                ));
+            }
+            if !path.is_absolute() {
+                return Err(format!(
+                    "inputs.integration.statsd.listen_unix_socket_path({}) is not an absolute path",
+                    self.listen_unix_socket_path
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Integration {
     pub enabled: bool,
+    // 0 disables the TCP listener
     pub listen_port: u16,
+    // empty means all addresses
+    pub listen_address: String,
+    // empty means no unix domain socket
+    pub listen_unix_socket_path: String,
+    // OTLP/gRPC receiver on listen_address, 0 means disabled
+    pub otlp_grpc_listen_port: u16,
+    pub statsd: Statsd,
     pub compression: Compression,
     pub prometheus_extra_labels: PrometheusExtraLabels,
     pub feature_control: FeatureControl,
+    pub source_filter: SourceFilter,
 }
 
 impl Default for Integration {
@@ -1684,24 +2958,80 @@ impl Default for Integration {
         Self {
             enabled: true,
             listen_port: 38086,
+            listen_address: "".to_string(),
+            listen_unix_socket_path: "".to_string(),
+            otlp_grpc_listen_port: 0,
+            statsd: Statsd::default(),
             compression: Compression::default(),
             prometheus_extra_labels: PrometheusExtraLabels::default(),
             feature_control: FeatureControl::default(),
+            source_filter: SourceFilter::default(),
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-pub struct Vector {
-    pub enabled: bool,
-    pub config: serde_yaml::Value,
-}
+impl Integration {
+    pub fn listen_address(&self) -> Option<IpAddr> {
+        self.listen_address.parse().ok()
+    }
 
-impl Default for Vector {
-    fn default() -> Self {
-        Self {
-            enabled: false,
-            config: serde_yaml::Value::Null,
+    pub fn listen_unix_socket_path(&self) -> Option<PathBuf> {
+        if self.listen_unix_socket_path.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(&self.listen_unix_socket_path))
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.listen_address.is_empty() && self.listen_address().is_none() {
+            return Err(format!(
+                "inputs.integration.listen_address({}) invalid",
+                self.listen_address
+            ));
+        }
+        if let Some(path) = self.listen_unix_socket_path() {
+            if cfg!(not(unix)) {
+                return Err(format!(
+                    "inputs.integration.listen_unix_socket_path({}) not supported on this platform",
+                    self.listen_unix_socket_path
+                ));
+            }
+            if !path.is_absolute() {
+                return Err(format!(
+                    "inputs.integration.listen_unix_socket_path({}) is not an absolute path",
+                    self.listen_unix_socket_path
+                ));
+            }
+        } else if self.listen_port == 0 {
+            return Err(
+                "inputs.integration.listen_port(0) requires listen_unix_socket_path".to_owned(),
+            );
+        }
+        if self.otlp_grpc_listen_port != 0 && self.otlp_grpc_listen_port == self.listen_port {
+            return Err(format!(
+                "inputs.integration.otlp_grpc_listen_port({}) conflicts with listen_port",
+                self.otlp_grpc_listen_port
+            ));
+        }
+        self.statsd.validate()?;
+        self.compression.validate()?;
+        self.source_filter.validate()?;
+        self.prometheus_extra_labels.validate()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct Vector {
+    pub enabled: bool,
+    pub config: serde_yaml::Value,
+}
+
+impl Default for Vector {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            config: serde_yaml::Value::Null,
         }
     }
 }
@@ -1731,6 +3061,8 @@ impl Inputs {
         }
 
         self.ebpf.socket.preprocess.adjust();
+        self.ebpf.profile.memory.adjust();
+        self.ebpf.profile.preprocess.adjust();
     }
 }
 
@@ -1781,13 +3113,77 @@ where
     ));
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+// TCP header fields reported in packet sequence blocks, compiled to the flag of each block:
+// | OPT_TS | FLAG | SEQ | ACK | PAYLOAD_SIZE | WINDOW_SIZE | OPT_MSS | OPT_WS | OPT_SACK |
+//     8       7     6     5          4              3           2        1         0
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
 #[serde(default)]
+pub struct TcpHeaderFields {
+    pub enabled: bool,
+    pub flags: bool,
+    pub seq: bool,
+    pub ack: bool,
+    pub payload_size: bool,
+    pub window_size: bool,
+    pub mss: bool,
+    pub window_scale: bool,
+    pub sack: bool,
+    pub timestamps: bool,
+}
+
+impl TcpHeaderFields {
+    // the deprecated header_fields_flag has no bit for timestamps
+    pub fn from_flag(flag: u8) -> Self {
+        Self {
+            enabled: flag != 0,
+            flags: flag & 0b1000_0000 != 0,
+            seq: flag & 0b0100_0000 != 0,
+            ack: flag & 0b0010_0000 != 0,
+            payload_size: flag & 0b0001_0000 != 0,
+            window_size: flag & 0b0000_1000 != 0,
+            mss: flag & 0b0000_0100 != 0,
+            window_scale: flag & 0b0000_0010 != 0,
+            sack: flag & 0b0000_0001 != 0,
+            timestamps: false,
+        }
+    }
+
+    // 0 if disabled
+    pub fn flag(&self) -> u16 {
+        if !self.enabled {
+            return 0;
+        }
+        [
+            self.timestamps,
+            self.flags,
+            self.seq,
+            self.ack,
+            self.payload_size,
+            self.window_size,
+            self.mss,
+            self.window_scale,
+            self.sack,
+        ]
+        .into_iter()
+        .fold(0, |flag, on| flag << 1 | on as u16)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.flag() == 0 {
+            return Err(
+                "processors.packet.tcp_header.header_fields is enabled without any field"
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TcpHeader {
     pub block_size: usize,
     pub sender_queue_size: usize,
-    #[serde(deserialize_with = "parse_maybe_binary_u8")]
-    pub header_fields_flag: u8,
+    pub header_fields: TcpHeaderFields,
 }
 
 impl Default for TcpHeader {
@@ -1795,8 +3191,49 @@ impl Default for TcpHeader {
         Self {
             block_size: 256,
             sender_queue_size: 65536,
-            header_fields_flag: 0b0000_0000,
+            header_fields: TcpHeaderFields::default(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TcpHeader {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(default)]
+        struct Raw {
+            block_size: usize,
+            sender_queue_size: usize,
+            header_fields: TcpHeaderFields,
+            // deprecated, overrides header_fields if not zero
+            #[serde(deserialize_with = "parse_maybe_binary_u8")]
+            header_fields_flag: u8,
+        }
+
+        impl Default for Raw {
+            fn default() -> Self {
+                let d = TcpHeader::default();
+                Self {
+                    block_size: d.block_size,
+                    sender_queue_size: d.sender_queue_size,
+                    header_fields: d.header_fields,
+                    header_fields_flag: 0,
+                }
+            }
         }
+
+        let raw = Raw::deserialize(deserializer)?;
+        Ok(Self {
+            block_size: raw.block_size,
+            sender_queue_size: raw.sender_queue_size,
+            header_fields: if raw.header_fields_flag != 0 {
+                TcpHeaderFields::from_flag(raw.header_fields_flag)
+            } else {
+                raw.header_fields
+            },
+        })
     }
 }
 
@@ -1848,12 +3285,59 @@ pub struct Packet {
     pub toa: Toa,
 }
 
+#[derive(Clone, Copy, Default, Debug, Deserialize, PartialEq, Eq)]
+pub enum OracleCharset {
+    #[default]
+    Al32Utf8,
+    Utf8,
+    Zhs16Gbk,
+    We8Iso8859P1,
+    We8MsWin1252,
+    Us7Ascii,
+}
+
+impl OracleCharset {
+    // character set id in Oracle NLS, also used in TNS negotiation
+    pub fn id(&self) -> u16 {
+        match self {
+            Self::Al32Utf8 => 873,
+            Self::Utf8 => 871,
+            Self::Zhs16Gbk => 852,
+            Self::We8Iso8859P1 => 31,
+            Self::We8MsWin1252 => 178,
+            Self::Us7Ascii => 1,
+        }
+    }
+}
+
+fn to_oracle_charset<'de, D>(deserializer: D) -> Result<OracleCharset, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match String::deserialize(deserializer)?.to_uppercase().as_str() {
+        "AL32UTF8" => Ok(OracleCharset::Al32Utf8),
+        "UTF8" => Ok(OracleCharset::Utf8),
+        "ZHS16GBK" => Ok(OracleCharset::Zhs16Gbk),
+        "WE8ISO8859P1" => Ok(OracleCharset::We8Iso8859P1),
+        "WE8MSWIN1252" => Ok(OracleCharset::We8MsWin1252),
+        "US7ASCII" => Ok(OracleCharset::Us7Ascii),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"AL32UTF8|UTF8|ZHS16GBK|WE8ISO8859P1|WE8MSWIN1252|US7ASCII",
+        )),
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct OracleConfig {
     pub is_be: bool,
     pub int_compressed: bool,
     pub resp_0x04_extra_byte: bool,
+    #[serde(deserialize_with = "to_oracle_charset")]
+    pub charset: OracleCharset,
+    pub bundled_calls_enabled: bool,
+    pub fetch_calls_enabled: bool,
 }
 
 impl Default for OracleConfig {
@@ -1862,6 +3346,9 @@ impl Default for OracleConfig {
             is_be: true,
             int_compressed: true,
             resp_0x04_extra_byte: false,
+            charset: OracleCharset::default(),
+            bundled_calls_enabled: true,
+            fetch_calls_enabled: true,
         }
     }
 }
@@ -1920,6 +3407,7 @@ impl Default for NetSignConfig {
 #[serde(default)]
 pub struct MysqlConfig {
     pub decompress_payload: bool,
+    pub decompress_max_bytes: usize,
     pub endpoint_disabled: bool,
 }
 
@@ -1927,11 +3415,204 @@ impl Default for MysqlConfig {
     fn default() -> Self {
         Self {
             decompress_payload: true,
+            decompress_max_bytes: 1048576,
             endpoint_disabled: true,
         }
     }
 }
 
+impl MysqlConfig {
+    const DECOMPRESS_MAX_BYTES_RANGE: (usize, usize) = (65536, 16777216);
+
+    fn validate(&self) -> Result<(), String> {
+        if self.decompress_max_bytes < Self::DECOMPRESS_MAX_BYTES_RANGE.0
+            || self.decompress_max_bytes > Self::DECOMPRESS_MAX_BYTES_RANGE.1
+        {
+            return Err(format!(
+                "mysql decompress_max_bytes({}) not in {:?}",
+                self.decompress_max_bytes,
+                Self::DECOMPRESS_MAX_BYTES_RANGE
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct MongodbConfig {
+    pub decompress_max_bytes: usize,
+}
+
+impl Default for MongodbConfig {
+    fn default() -> Self {
+        Self {
+            decompress_max_bytes: 1048576,
+        }
+    }
+}
+
+impl MongodbConfig {
+    // 48MiB is the maximum message size of MongoDB
+    const DECOMPRESS_MAX_BYTES_RANGE: (usize, usize) = (65536, 50331648);
+
+    fn validate(&self) -> Result<(), String> {
+        if self.decompress_max_bytes < Self::DECOMPRESS_MAX_BYTES_RANGE.0
+            || self.decompress_max_bytes > Self::DECOMPRESS_MAX_BYTES_RANGE.1
+        {
+            return Err(format!(
+                "mongodb decompress_max_bytes({}) not in {:?}",
+                self.decompress_max_bytes,
+                Self::DECOMPRESS_MAX_BYTES_RANGE
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DubboConfig {
+    pub extract_attachments: Vec<String>,
+    pub extract_arguments: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomTextPairing {
+    #[default]
+    Sequential,
+    RequestId,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CustomTextConfig {
+    pub name: String,
+    pub request_delimiter: String,
+    pub response_delimiter: String,
+    #[serde(deserialize_with = "to_optional_regex")]
+    pub request_regex: Option<Regex>,
+    #[serde(deserialize_with = "to_optional_regex")]
+    pub response_regex: Option<Regex>,
+    pub success_status: Vec<String>,
+    pub pairing: CustomTextPairing,
+}
+
+impl Default for CustomTextConfig {
+    fn default() -> Self {
+        Self {
+            name: "CustomText".to_string(),
+            request_delimiter: "\n".to_string(),
+            response_delimiter: "\n".to_string(),
+            request_regex: None,
+            response_regex: None,
+            success_status: vec!["OK".to_string()],
+            pairing: CustomTextPairing::Sequential,
+        }
+    }
+}
+
+impl Eq for CustomTextConfig {}
+
+impl PartialEq for CustomTextConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.request_delimiter == other.request_delimiter
+            && self.response_delimiter == other.response_delimiter
+            && self.request_regex.as_ref().map(|r| r.as_str())
+                == other.request_regex.as_ref().map(|r| r.as_str())
+            && self.response_regex.as_ref().map(|r| r.as_str())
+                == other.response_regex.as_ref().map(|r| r.as_str())
+            && self.success_status == other.success_status
+            && self.pairing == other.pairing
+    }
+}
+
+impl CustomTextConfig {
+    const REQUEST_GROUPS: [&'static str; 3] = ["request_type", "resource", "request_id"];
+    const RESPONSE_GROUPS: [&'static str; 3] = ["status", "code", "request_id"];
+
+    fn validate(&self) -> Result<(), String> {
+        let Some(request_regex) = self.request_regex.as_ref() else {
+            return Ok(());
+        };
+        if self.request_delimiter.is_empty() || self.response_delimiter.is_empty() {
+            return Err("custom_text delimiters should not be empty".to_string());
+        }
+        let has_group = |r: &Regex, name: &str| r.capture_names().any(|n| n == Some(name));
+        if !Self::REQUEST_GROUPS
+            .iter()
+            .any(|g| has_group(request_regex, g))
+        {
+            return Err(format!(
+                "custom_text request_regex({}) should capture one of {:?}",
+                request_regex.as_str(),
+                Self::REQUEST_GROUPS
+            ));
+        }
+        if let Some(response_regex) = self.response_regex.as_ref() {
+            if !Self::RESPONSE_GROUPS
+                .iter()
+                .any(|g| has_group(response_regex, g))
+            {
+                return Err(format!(
+                    "custom_text response_regex({}) should capture one of {:?}",
+                    response_regex.as_str(),
+                    Self::RESPONSE_GROUPS
+                ));
+            }
+        }
+        if self.pairing == CustomTextPairing::RequestId {
+            let both_captured = has_group(request_regex, "request_id")
+                && self
+                    .response_regex
+                    .as_ref()
+                    .map(|r| has_group(r, "request_id"))
+                    .unwrap_or(false);
+            if !both_captured {
+                return Err(
+                    "custom_text pairing by request_id requires request_id in both regexes"
+                        .to_string(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn to_optional_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    match Regex::new(raw.as_str()) {
+        Ok(regex) => Ok(Some(regex)),
+        Err(_) => Err(de::Error::invalid_value(
+            Unexpected::Str(raw.as_str()),
+            &"See: https://regexr.com/",
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Http2Config {
+    pub hpack_max_table_size: usize,
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            hpack_max_table_size: 65536,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct GrpcConfig {
@@ -1946,6 +3627,39 @@ impl Default for GrpcConfig {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub fingerprint_as_endpoint: bool,
+    #[serde(with = "humantime_serde")]
+    pub certificate_expiry_warning: Duration,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            fingerprint_as_endpoint: false,
+            certificate_expiry_warning: Duration::from_secs(30 * 24 * 3600),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ElasticsearchConfig {
+    pub ports: String,
+    pub index_suffix_regex: String,
+}
+
+impl Default for ElasticsearchConfig {
+    fn default() -> Self {
+        Self {
+            ports: "9200".to_string(),
+            index_suffix_regex: r"[-_.]\d{4}([-_.]?\d{2}){1,2}$".to_string(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct InferenceWhitelist {
@@ -1996,7 +3710,13 @@ pub struct ProtocolSpecialConfig {
     pub web_sphere_mq: WebSphereMqConfig,
     pub net_sign: NetSignConfig,
     pub mysql: MysqlConfig,
+    pub mongodb: MongodbConfig,
+    pub dubbo: DubboConfig,
+    pub custom_text: CustomTextConfig,
+    pub http2: Http2Config,
     pub grpc: GrpcConfig,
+    pub elasticsearch: ElasticsearchConfig,
+    pub tls: TlsConfig,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -2043,10 +3763,21 @@ pub struct TagFilterOperator {
     pub value: String,
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PortPrefilterMode {
+    // only check protocols on their configured ports
+    #[default]
+    Strict,
+    // check protocols on their configured ports first, then the other enabled protocols
+    Prefer,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Filters {
     pub port_number_prefilters: HashMap<String, String>,
+    pub port_prefilter_mode: PortPrefilterMode,
     pub tag_filters: HashMap<String, Vec<TagFilterOperator>>,
     pub unconcerned_dns_nxdomain_response_suffixes: Vec<String>,
     pub cbpf_disabled: bool,
@@ -2085,9 +3816,12 @@ impl Default for Filters {
                 ("WebSphereMQ".to_string(), "1-65535".to_string()),
                 ("DNS".to_string(), "53,5353".to_string()),
                 ("TLS".to_string(), "443,6443".to_string()),
+                ("LDAP".to_string(), "389,636".to_string()),
+                ("SMTP".to_string(), "25,587".to_string()),
                 ("PING".to_string(), "1-65535".to_string()),
                 ("Custom".to_string(), "1-65535".to_string()),
             ]),
+            port_prefilter_mode: PortPrefilterMode::default(),
             tag_filters: HashMap::from([
                 ("HTTP".to_string(), vec![]),
                 ("HTTP2".to_string(), vec![]),
@@ -2119,6 +3853,9 @@ impl Default for Filters {
                 ("WebSphereMQ".to_string(), vec![]),
                 ("DNS".to_string(), vec![]),
                 ("TLS".to_string(), vec![]),
+                ("LDAP".to_string(), vec![]),
+                ("SMTP".to_string(), vec![]),
+                ("Elasticsearch".to_string(), vec![]),
                 ("PING".to_string(), vec![]),
                 ("Custom".to_string(), vec![]),
             ]),
@@ -2141,6 +3878,8 @@ impl SessionTimeout {
     pub const DEFAULT: Duration = Duration::from_secs(120);
     pub const DNS_DEFAULT: Duration = Duration::from_secs(15);
     pub const TLS_DEFAULT: Duration = Duration::from_secs(15);
+    // sessions are flushed with a granularity of one second
+    pub const MIN: Duration = Duration::from_secs(1);
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -2185,11 +3924,52 @@ impl Timeouts {
     }
 }
 
+// A plain string reads the same header in both directions, while the struct form reads
+// `request` from requests and `response` from responses.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum XRequestIdHeader {
+    Both(String),
+    Split { request: String, response: String },
+}
+
+impl XRequestIdHeader {
+    pub fn request(&self) -> &str {
+        match self {
+            Self::Both(s) => s,
+            Self::Split { request, .. } => request,
+        }
+    }
+
+    pub fn response(&self) -> &str {
+        match self {
+            Self::Both(s) => s,
+            Self::Split { response, .. } => response,
+        }
+    }
+
+    pub fn to_ascii_lowercase(&self) -> Self {
+        match self {
+            Self::Both(s) => Self::Both(s.to_ascii_lowercase()),
+            Self::Split { request, response } => Self::Split {
+                request: request.to_ascii_lowercase(),
+                response: response.to_ascii_lowercase(),
+            },
+        }
+    }
+}
+
+impl From<&str> for XRequestIdHeader {
+    fn from(s: &str) -> Self {
+        Self::Both(s.to_owned())
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct TracingTag {
     pub http_real_client: Vec<String>,
-    pub x_request_id: Vec<String>,
+    pub x_request_id: Vec<XRequestIdHeader>,
     pub multiple_trace_id_collection: bool,
     pub copy_apm_trace_id: bool,
     pub apm_trace_id: Vec<String>,
@@ -2200,7 +3980,7 @@ impl Default for TracingTag {
     fn default() -> Self {
         Self {
             http_real_client: vec!["X_Forwarded_For".to_string()],
-            x_request_id: vec!["X_Request_ID".to_string()],
+            x_request_id: vec!["X_Request_ID".into()],
             multiple_trace_id_collection: true,
             copy_apm_trace_id: false,
             apm_trace_id: vec!["traceparent".to_string(), "sw8".to_string()],
@@ -2225,11 +4005,34 @@ impl Default for HttpEndpointMatchRule {
     }
 }
 
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpEndpointRewriteRule {
+    pub url_regex: String,
+    // replaces all matches in the URL path, `$1` or `${name}` refers to capture groups
+    pub replacement: String,
+}
+
+impl HttpEndpointRewriteRule {
+    const REGEX_SIZE_LIMIT: usize = 1 << 20;
+    const REGEX_NEST_LIMIT: u32 = 32;
+
+    // limit the compiled program size and nesting so that complex patterns are rejected
+    pub fn compile(&self) -> Result<Regex, regex::Error> {
+        regex::RegexBuilder::new(&self.url_regex)
+            .size_limit(Self::REGEX_SIZE_LIMIT)
+            .dfa_size_limit(Self::REGEX_SIZE_LIMIT)
+            .nest_limit(Self::REGEX_NEST_LIMIT)
+            .build()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct HttpEndpoint {
     pub extraction_disabled: bool,
     pub match_rules: Vec<HttpEndpointMatchRule>,
+    pub rewrite_rules: Vec<HttpEndpointRewriteRule>,
 }
 
 impl Default for HttpEndpoint {
@@ -2237,14 +4040,30 @@ impl Default for HttpEndpoint {
         Self {
             extraction_disabled: false,
             match_rules: vec![HttpEndpointMatchRule::default()],
+            rewrite_rules: vec![],
         }
     }
 }
 
-#[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct CustomFields {
     pub field_name: String,
+    // concatenate values of repeated headers
+    pub multi: bool,
+    pub separator: String,
+    pub max_bytes: usize,
+}
+
+impl Default for CustomFields {
+    fn default() -> Self {
+        Self {
+            field_name: "".to_string(),
+            multi: false,
+            separator: ",".to_string(),
+            max_bytes: 1024,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -2267,12 +4086,23 @@ impl Default for RequestLogTagExtractionRaw {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpQueryObfuscation {
+    // drop the query string after `?`
+    #[default]
+    Truncate,
+    // keep the keys and replace the values with `?`
+    MaskValues,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct RequestLogTagExtraction {
     pub tracing_tag: TracingTag,
     pub http_endpoint: HttpEndpoint,
     pub obfuscate_protocols: Vec<String>,
+    pub obfuscate_http_query: HttpQueryObfuscation,
     pub custom_fields: HashMap<String, Vec<CustomFields>>,
     #[deprecated]
     #[cfg(feature = "enterprise")]
@@ -2290,6 +4120,7 @@ impl Default for RequestLogTagExtraction {
                 ("HTTP2".to_string(), vec![]),
             ]),
             obfuscate_protocols: vec!["Redis".to_string()],
+            obfuscate_http_query: HttpQueryObfuscation::Truncate,
             #[cfg(feature = "enterprise")]
             custom_field_policies: Default::default(),
             raw: RequestLogTagExtractionRaw::default(),
@@ -2536,8 +4367,8 @@ pub struct RelativeSysLoad {
 impl PartialEq for RelativeSysLoad {
     fn eq(&self, other: &Self) -> bool {
         self.trigger_threshold == other.trigger_threshold
-            || self.recovery_threshold == other.recovery_threshold
-            || self.metric == other.metric
+            && self.recovery_threshold == other.recovery_threshold
+            && self.metric == other.metric
     }
 }
 impl Eq for RelativeSysLoad {}
@@ -2552,19 +4383,58 @@ impl Default for RelativeSysLoad {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TxThroughputAction {
+    #[default]
+    Stop = 0,
+    Sample = 1,
+}
+
+fn to_tx_throughput_action<'de: 'a, 'a, D>(deserializer: D) -> Result<TxThroughputAction, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_uppercase()
+        .as_str()
+    {
+        "STOP" => Ok(TxThroughputAction::Stop),
+        "SAMPLE" => Ok(TxThroughputAction::Sample),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"STOP|SAMPLE",
+        )),
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct TxThroughput {
     #[serde(deserialize_with = "deser_u64_with_mega_unit")]
     pub trigger_threshold: u64,
+    #[serde(deserialize_with = "deser_u64_with_mega_unit")]
+    pub recovery_threshold: u64,
+    #[serde(with = "humantime_serde")]
+    pub min_hold_time: Duration,
+    #[serde(deserialize_with = "to_tx_throughput_action")]
+    pub over_limit_action: TxThroughputAction,
     #[serde(with = "humantime_serde")]
     pub throughput_monitoring_interval: Duration,
 }
 
+impl TxThroughput {
+    pub const MIN_THRESHOLD: u64 = 1 << 20;
+    pub const MAX_HOLD_TIME: Duration = Duration::from_secs(3600);
+}
+
 impl Default for TxThroughput {
     fn default() -> Self {
         Self {
             trigger_threshold: 0,
+            recovery_threshold: 0,
+            min_hold_time: Duration::from_secs(50),
+            over_limit_action: TxThroughputAction::Stop,
             throughput_monitoring_interval: Duration::from_secs(10),
         }
     }
@@ -2682,11 +4552,26 @@ pub struct Communication {
     pub max_escape_duration: Duration,
     pub ingester_ip: String,
     pub ingester_port: u16,
+    // CA file verifying the ingester with TLS data socket, certificate files of
+    // `controller_cert_file_prefix` are used if empty
+    pub ingester_tls_ca_file: String,
+    // server name sent in SNI and verified, ingester_ip if empty
+    pub ingester_tls_server_name: String,
     #[serde(skip)]
     pub grpc_buffer_size: usize,
     pub max_throughput_to_ingester: u64,
     #[serde(deserialize_with = "to_traffic_overflow_action")]
     pub ingester_traffic_overflow_action: TrafficOverflowAction,
+    // exponential backoff of reconnecting the data sockets to the ingester
+    #[serde(with = "humantime_serde")]
+    pub ingester_reconnect_backoff_base: Duration,
+    #[serde(with = "humantime_serde")]
+    pub ingester_reconnect_backoff_max: Duration,
+    // backup ingesters in priority order, failed over to when ingester_ip is unreachable
+    pub ingester_failover_ips: Vec<String>,
+    pub ingester_failover_threshold: u32,
+    #[serde(with = "humantime_serde")]
+    pub ingester_failback_hold_down: Duration,
     pub request_via_nat_ip: bool,
     pub proxy_controller_ip: String,
     pub proxy_controller_port: u16,
@@ -2694,7 +4579,13 @@ pub struct Communication {
 
 pub const GRPC_BUFFER_SIZE_MIN: usize = 1 << 20;
 
-impl Default for Communication {
+impl Communication {
+    pub const INGESTER_FAILOVER_THRESHOLD_RANGE: (u32, u32) = (1, 100);
+    pub const INGESTER_FAILBACK_HOLD_DOWN_RANGE: (Duration, Duration) =
+        (Duration::from_secs(10), Duration::from_secs(3600));
+}
+
+impl Default for Communication {
     fn default() -> Self {
         Self {
             proactive_request_interval: Duration::from_secs(60),
@@ -2703,9 +4594,16 @@ impl Default for Communication {
             proxy_controller_port: 30035,
             ingester_ip: "".to_string(),
             ingester_port: 30033,
+            ingester_tls_ca_file: "".to_string(),
+            ingester_tls_server_name: "".to_string(),
             grpc_buffer_size: GRPC_BUFFER_SIZE_MIN,
             max_throughput_to_ingester: 100,
             ingester_traffic_overflow_action: TrafficOverflowAction::Waiting,
+            ingester_reconnect_backoff_base: Duration::from_secs(2),
+            ingester_reconnect_backoff_max: Duration::from_secs(300),
+            ingester_failover_ips: vec![],
+            ingester_failover_threshold: 3,
+            ingester_failback_hold_down: Duration::from_secs(60),
             request_via_nat_ip: false,
         }
     }
@@ -2781,6 +4679,15 @@ pub struct StandaloneMode {
     #[serde(deserialize_with = "deser_u64_with_mega_unit")]
     pub max_data_file_size: u64,
     pub data_file_dir: String,
+    #[serde(with = "humantime_serde")]
+    pub data_file_rotation_interval: Duration,
+    pub max_data_files: usize,
+}
+
+impl StandaloneMode {
+    pub const DATA_FILE_ROTATION_INTERVAL_RANGE: (Duration, Duration) =
+        (Duration::from_secs(60), Duration::from_secs(7 * 24 * 3600));
+    pub const MAX_DATA_FILES_RANGE: (usize, usize) = (1, 1000);
 }
 
 impl Default for StandaloneMode {
@@ -2788,6 +4695,8 @@ impl Default for StandaloneMode {
         Self {
             max_data_file_size: 200 << 20,
             data_file_dir: "/var/log/deepflow-agent/".to_string(),
+            data_file_rotation_interval: Duration::ZERO,
+            max_data_files: 1,
         }
     }
 }
@@ -2872,10 +4781,62 @@ where
         "UDP" => Ok(agent::SocketType::Udp),
         "RAW_UDP" => Ok(agent::SocketType::RawUdp),
         "ZMQ" => Ok(agent::SocketType::Zmq),
+        "TLS" => Ok(agent::SocketType::Tls),
         "" => Ok(agent::SocketType::File),
         other => Err(de::Error::invalid_value(
             Unexpected::Str(other),
-            &"FILE|TCP|UDP|RAW_UDP|ZMQ",
+            &"FILE|TCP|UDP|RAW_UDP|ZMQ|TLS",
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SocketBalance {
+    // documents of the same flow are always sent through the same socket
+    #[default]
+    Hash,
+    RoundRobin,
+}
+
+fn to_socket_balance<'de: 'a, 'a, D>(deserializer: D) -> Result<SocketBalance, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_uppercase()
+        .as_str()
+    {
+        "HASH" => Ok(SocketBalance::Hash),
+        "ROUND_ROBIN" => Ok(SocketBalance::RoundRobin),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"HASH|ROUND_ROBIN",
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DataCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+fn to_data_compression<'de: 'a, 'a, D>(deserializer: D) -> Result<DataCompression, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_uppercase()
+        .as_str()
+    {
+        "NONE" => Ok(DataCompression::None),
+        "LZ4" => Ok(DataCompression::Lz4),
+        "ZSTD" => Ok(DataCompression::Zstd),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"NONE|LZ4|ZSTD",
         )),
     }
 }
@@ -2889,6 +4850,35 @@ pub struct Socket {
     pub npb_socket_type: agent::SocketType,
     pub raw_udp_qos_bypass: bool,
     pub multiple_sockets_to_ingester: bool,
+    pub sockets_per_sender: usize,
+    #[serde(deserialize_with = "to_socket_balance")]
+    pub multiple_sockets_balance: SocketBalance,
+    #[serde(deserialize_with = "to_data_compression")]
+    pub data_compression: DataCompression,
+    // only for zstd, 0 for the default level
+    pub data_compression_level: i32,
+    pub tunning: OutputsSocketTunning,
+}
+
+impl Socket {
+    pub const MAX_SOCKETS_PER_SENDER: usize = 16;
+    pub const MAX_ZSTD_LEVEL: i32 = 22;
+
+    // Compression of the flow logs and metrics sent to the ingester through the TCP data socket
+    pub fn effective_data_compression(&self) -> DataCompression {
+        match self.data_socket_type {
+            agent::SocketType::Udp | agent::SocketType::RawUdp
+                if self.data_compression != DataCompression::None =>
+            {
+                warn!(
+                    "data_compression {:?} ignored for data_socket_type {:?}",
+                    self.data_compression, self.data_socket_type
+                );
+                DataCompression::None
+            }
+            _ => self.data_compression,
+        }
+    }
 }
 
 impl Default for Socket {
@@ -2898,6 +4888,61 @@ impl Default for Socket {
             npb_socket_type: agent::SocketType::RawUdp,
             raw_udp_qos_bypass: false,
             multiple_sockets_to_ingester: false,
+            sockets_per_sender: 1,
+            multiple_sockets_balance: SocketBalance::Hash,
+            data_compression: DataCompression::None,
+            data_compression_level: 0,
+            tunning: OutputsSocketTunning::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputsSocketTunning {
+    pub batch_max_bytes: usize,
+    // 0 for unlimited
+    pub batch_max_documents: usize,
+    #[serde(with = "humantime_serde")]
+    pub linger: Duration,
+}
+
+impl OutputsSocketTunning {
+    pub const BATCH_MAX_BYTES_RANGE: (usize, usize) = (4 << 10, 8 << 20);
+    pub const BATCH_MAX_DOCUMENTS_MAX: usize = 1000000;
+    pub const LINGER_RANGE: (Duration, Duration) =
+        (Duration::from_millis(100), Duration::from_secs(60));
+}
+
+impl Default for OutputsSocketTunning {
+    fn default() -> Self {
+        Self {
+            batch_max_bytes: 256 << 10,
+            batch_max_documents: 0,
+            linger: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ObservationPointSampling {
+    pub observation_point: u16,
+    pub ratio: f32,
+}
+
+impl PartialEq for ObservationPointSampling {
+    fn eq(&self, other: &Self) -> bool {
+        self.observation_point == other.observation_point && self.ratio == other.ratio
+    }
+}
+impl Eq for ObservationPointSampling {}
+
+impl Default for ObservationPointSampling {
+    fn default() -> Self {
+        Self {
+            observation_point: 0,
+            ratio: 1.0,
         }
     }
 }
@@ -2909,6 +4954,7 @@ pub struct FlowLogFilters {
     pub l7_capture_network_types: Vec<i16>,
     pub l4_ignored_observation_points: Vec<u16>,
     pub l7_ignored_observation_points: Vec<u16>,
+    pub l4_observation_point_sampling_ratios: Vec<ObservationPointSampling>,
 }
 
 impl Default for FlowLogFilters {
@@ -2918,6 +4964,7 @@ impl Default for FlowLogFilters {
             l7_capture_network_types: vec![0],
             l4_ignored_observation_points: vec![],
             l7_ignored_observation_points: vec![],
+            l4_observation_point_sampling_ratios: vec![],
         }
     }
 }
@@ -2936,11 +4983,24 @@ impl Default for FlowLogAggregators {
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProtocolThrottle {
+    #[serde(deserialize_with = "deser_l7_protocol")]
+    pub protocol: L7Protocol,
+    pub throttle: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Throttles {
     pub l4_throttle: u64,
     pub l7_throttle: u64,
+    pub l7_throttle_overrides: Vec<ProtocolThrottle>,
+}
+
+impl Throttles {
+    pub const RANGE: (u64, u64) = (100, 1000000);
 }
 
 impl Default for Throttles {
@@ -2948,6 +5008,7 @@ impl Default for Throttles {
         Self {
             l4_throttle: 10000,
             l7_throttle: 10000,
+            l7_throttle_overrides: vec![],
         }
     }
 }
@@ -3003,16 +5064,30 @@ impl Default for FlowMetricsFilters {
 #[serde(default)]
 pub struct FlowMetricsTunning {
     pub sender_queue_size: usize,
+    // shed second metrics while the sender queue is congested
+    pub second_metrics_shedding_enabled: bool,
+    // occupancy of the sender queue in percent
+    pub second_metrics_shedding_high_watermark: u8,
+    pub second_metrics_shedding_low_watermark: u8,
+    pub second_metrics_shedding_intervals: u32,
 }
 
 impl Default for FlowMetricsTunning {
     fn default() -> Self {
         Self {
             sender_queue_size: 65536,
+            second_metrics_shedding_enabled: false,
+            second_metrics_shedding_high_watermark: 80,
+            second_metrics_shedding_low_watermark: 50,
+            second_metrics_shedding_intervals: 3,
         }
     }
 }
 
+impl FlowMetricsTunning {
+    pub const SECOND_METRICS_SHEDDING_INTERVALS_RANGE: (u32, u32) = (1, 60);
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct FlowMetrics {
@@ -3046,33 +5121,87 @@ where
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VxlanEncapsulation {
+    #[default]
+    Vxlan,
+    VxlanGpe,
+}
+
+fn to_vxlan_encapsulation<'de: 'a, 'a, D>(deserializer: D) -> Result<VxlanEncapsulation, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_lowercase()
+        .as_str()
+    {
+        "vxlan" => Ok(VxlanEncapsulation::Vxlan),
+        "vxlan-gpe" => Ok(VxlanEncapsulation::VxlanGpe),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"vxlan|vxlan-gpe",
+        )),
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Npb {
+    pub enabled: bool,
     pub max_mtu: u32,
     pub raw_udp_vlan_tag: u16,
     #[serde(deserialize_with = "to_vlan_mode")]
     pub extra_vlan_header: agent::VlanMode,
     pub traffic_global_dedup: bool,
+    // 0 means the default port of vxlan_encapsulation
     pub target_port: u16,
+    #[serde(deserialize_with = "to_vxlan_encapsulation")]
+    pub vxlan_encapsulation: VxlanEncapsulation,
     #[serde(deserialize_with = "parse_maybe_binary_u8")]
     pub custom_vxlan_flags: u8,
     pub overlay_vlan_header_trimming: bool,
     #[serde(deserialize_with = "deser_u64_with_mega_unit")]
     pub max_tx_throughput: u64,
+    pub underlay_hop_limit: u8,
+}
+
+impl Npb {
+    // 200 bytes for the compressor plus the larger IPv6 underlay header
+    pub const MIN_MTU: u32 = 200 + IPV6_HEADER_ADJUST as u32;
+
+    pub fn target_port(&self) -> u16 {
+        if self.target_port != 0 {
+            return self.target_port;
+        }
+        match self.vxlan_encapsulation {
+            VxlanEncapsulation::Vxlan => NPB_DEFAULT_PORT,
+            VxlanEncapsulation::VxlanGpe => NPB_GPE_DEFAULT_PORT,
+        }
+    }
+
+    pub fn vxlan_flags(&self) -> u8 {
+        match self.vxlan_encapsulation {
+            VxlanEncapsulation::Vxlan => self.custom_vxlan_flags,
+            VxlanEncapsulation::VxlanGpe => NPB_GPE_VXLAN_FLAGS,
+        }
+    }
 }
 
 impl Default for Npb {
     fn default() -> Self {
         Self {
+            enabled: true,
             max_mtu: 1500,
             raw_udp_vlan_tag: 0,
             extra_vlan_header: agent::VlanMode::None,
             traffic_global_dedup: true,
-            target_port: 4789,
+            target_port: 0,
+            vxlan_encapsulation: VxlanEncapsulation::Vxlan,
             custom_vxlan_flags: 0b1111_1111,
             overlay_vlan_header_trimming: false,
             max_tx_throughput: 1000 << 20,
+            underlay_hop_limit: 64,
         }
     }
 }
@@ -3097,6 +5226,176 @@ impl Default for OutputCompression {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KafkaSerialization {
+    #[default]
+    Json,
+    Protobuf,
+}
+
+fn to_kafka_serialization<'de: 'a, 'a, D>(deserializer: D) -> Result<KafkaSerialization, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_uppercase()
+        .as_str()
+    {
+        "JSON" => Ok(KafkaSerialization::Json),
+        "PROTOBUF" => Ok(KafkaSerialization::Protobuf),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"JSON|PROTOBUF",
+        )),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KafkaSaslMechanism {
+    #[default]
+    None,
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+impl KafkaSaslMechanism {
+    // value of the librdkafka property sasl.mechanisms
+    pub fn as_str(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Plain => Some("PLAIN"),
+            Self::ScramSha256 => Some("SCRAM-SHA-256"),
+            Self::ScramSha512 => Some("SCRAM-SHA-512"),
+        }
+    }
+}
+
+fn to_kafka_sasl_mechanism<'de: 'a, 'a, D>(deserializer: D) -> Result<KafkaSaslMechanism, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match <&'a str>::deserialize(deserializer)?
+        .to_uppercase()
+        .as_str()
+    {
+        "" | "NONE" => Ok(KafkaSaslMechanism::None),
+        "PLAIN" => Ok(KafkaSaslMechanism::Plain),
+        "SCRAM-SHA-256" => Ok(KafkaSaslMechanism::ScramSha256),
+        "SCRAM-SHA-512" => Ok(KafkaSaslMechanism::ScramSha512),
+        other => Err(de::Error::invalid_value(
+            Unexpected::Str(other),
+            &"NONE|PLAIN|SCRAM-SHA-256|SCRAM-SHA-512",
+        )),
+    }
+}
+
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputsKafka {
+    pub enabled: bool,
+    pub brokers: Vec<String>,
+    pub topic: String,
+    #[serde(deserialize_with = "to_kafka_serialization")]
+    pub serialization: KafkaSerialization,
+    pub buffer_size: usize,
+    #[serde(deserialize_with = "to_kafka_sasl_mechanism")]
+    pub sasl_mechanism: KafkaSaslMechanism,
+    pub sasl_username: String,
+    pub sasl_password: String,
+    pub tls_enabled: bool,
+    pub tls_ca_file: String,
+}
+
+impl OutputsKafka {
+    pub const BUFFER_SIZE_RANGE: (usize, usize) = (1024, 1 << 24);
+}
+
+impl Default for OutputsKafka {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brokers: vec![],
+            topic: "deepflow_l7_flow_log".to_string(),
+            serialization: KafkaSerialization::Json,
+            buffer_size: 65536,
+            sasl_mechanism: KafkaSaslMechanism::None,
+            sasl_username: String::new(),
+            sasl_password: String::new(),
+            tls_enabled: false,
+            tls_ca_file: String::new(),
+        }
+    }
+}
+
+// the password is not printed in logs
+impl fmt::Debug for OutputsKafka {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutputsKafka")
+            .field("enabled", &self.enabled)
+            .field("brokers", &self.brokers)
+            .field("topic", &self.topic)
+            .field("serialization", &self.serialization)
+            .field("buffer_size", &self.buffer_size)
+            .field("sasl_mechanism", &self.sasl_mechanism)
+            .field("sasl_username", &self.sasl_username)
+            .field(
+                "sasl_password",
+                &if self.sasl_password.is_empty() {
+                    ""
+                } else {
+                    "******"
+                },
+            )
+            .field("tls_enabled", &self.tls_enabled)
+            .field("tls_ca_file", &self.tls_ca_file)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputsOtlp {
+    pub enabled: bool,
+    pub endpoint: String,
+    pub buffer_size: usize,
+    pub batch_size: usize,
+    #[serde(with = "humantime_serde")]
+    pub batch_timeout: Duration,
+    #[serde(with = "humantime_serde")]
+    pub export_timeout: Duration,
+    pub max_retries: u32,
+    #[serde(with = "humantime_serde")]
+    pub retry_backoff: Duration,
+}
+
+impl OutputsOtlp {
+    pub const BUFFER_SIZE_RANGE: (usize, usize) = (1024, 1 << 24);
+    pub const BATCH_SIZE_RANGE: (usize, usize) = (1, 8192);
+    pub const BATCH_TIMEOUT_RANGE: (Duration, Duration) =
+        (Duration::from_millis(100), Duration::from_secs(60));
+    pub const EXPORT_TIMEOUT_RANGE: (Duration, Duration) =
+        (Duration::from_secs(1), Duration::from_secs(60));
+    pub const MAX_RETRIES_MAX: u32 = 20;
+    pub const RETRY_BACKOFF_RANGE: (Duration, Duration) =
+        (Duration::from_millis(100), Duration::from_secs(60));
+}
+
+impl Default for OutputsOtlp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            buffer_size: 65536,
+            batch_size: 512,
+            batch_timeout: Duration::from_secs(1),
+            export_timeout: Duration::from_secs(10),
+            max_retries: 5,
+            retry_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default)]
 pub struct Outputs {
@@ -3105,6 +5404,8 @@ pub struct Outputs {
     pub flow_metrics: FlowMetrics,
     pub npb: Npb,
     pub compression: OutputCompression,
+    pub kafka: OutputsKafka,
+    pub otlp: OutputsOtlp,
 }
 
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
@@ -3155,9 +5456,19 @@ impl UserConfig {
     const DEFAULT_ORACLE_PORTS: &'static str = "1521";
     const DEFAULT_DAMENG_PORTS: &'static str = "5236";
     const DEFAULT_MEMCACHED_PORTS: &'static str = "11211";
+    const DEFAULT_LDAP_PORTS: &'static str = "389,636";
+    const DEFAULT_SMTP_PORTS: &'static str = "25,587";
     const PACKET_FANOUT_MODE_MAX: u32 = 7;
 
     pub fn adjust(&mut self) {
+        // before inputs.adjust() which only recognizes canonical protocol names
+        self.inputs.ebpf.socket.preprocess.validate_protocols(
+            &self
+                .processors
+                .request_log
+                .application_protocol_inference
+                .enabled_protocols,
+        );
         self.inputs.adjust();
     }
 
@@ -3266,6 +5577,28 @@ impl UserConfig {
                 Self::DEFAULT_MEMCACHED_PORTS.to_string(),
             );
         }
+        let ldap_str = L7ProtocolParser::LDAP(LdapLog::default()).as_str();
+        // ldap default only parse 389,636 port. when l7_protocol_ports config without LDAP, need to reserve the ldap default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(ldap_str)
+        {
+            new.insert(ldap_str.to_string(), Self::DEFAULT_LDAP_PORTS.to_string());
+        }
+        let smtp_str = L7ProtocolParser::SMTP(SmtpLog::default()).as_str();
+        // smtp default only parse 25,587 port. when l7_protocol_ports config without SMTP, need to reserve the smtp default config.
+        if !self
+            .processors
+            .request_log
+            .filters
+            .port_number_prefilters
+            .contains_key(smtp_str)
+        {
+            new.insert(smtp_str.to_string(), Self::DEFAULT_SMTP_PORTS.to_string());
+        }
 
         #[cfg(feature = "enterprise")]
         {
@@ -3349,15 +5682,62 @@ impl UserConfig {
             )));
         }
 
+        let tx_throughput = &self.global.circuit_breakers.tx_throughput;
+        if tx_throughput.trigger_threshold != 0 {
+            if tx_throughput.trigger_threshold < TxThroughput::MIN_THRESHOLD {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "tx_throughput trigger_threshold({}Mbps) smaller than 1Mbps",
+                    tx_throughput.trigger_threshold >> 20
+                )));
+            }
+            // 0 means 90% of trigger_threshold
+            if tx_throughput.recovery_threshold != 0
+                && (tx_throughput.recovery_threshold < TxThroughput::MIN_THRESHOLD
+                    || tx_throughput.recovery_threshold > tx_throughput.trigger_threshold)
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "tx_throughput recovery_threshold({}Mbps) not in [1Mbps, trigger_threshold({}Mbps)]",
+                    tx_throughput.recovery_threshold >> 20,
+                    tx_throughput.trigger_threshold >> 20
+                )));
+            }
+        }
+        if tx_throughput.min_hold_time > TxThroughput::MAX_HOLD_TIME {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "tx_throughput min_hold_time {:?} not in [0s, 1h]",
+                tx_throughput.min_hold_time
+            )));
+        }
+
         // 虽然RFC 791里最低MTU是68，但是此时compressor会崩溃，
-        // 所以MTU最低限定到200以确保deepflow-agent能够成功运行
-        if self.outputs.npb.max_mtu < 200 {
+        // 所以MTU最低限定到200以确保deepflow-agent能够成功运行，
+        // 另外需要为IPv6隧道头额外预留20字节
+        if self.outputs.npb.max_mtu < Npb::MIN_MTU {
             return Err(ConfigError::RuntimeConfigInvalid(format!(
-                "MTU({}) specified smaller than 200",
-                self.outputs.npb.max_mtu
+                "MTU({}) specified smaller than {}",
+                self.outputs.npb.max_mtu,
+                Npb::MIN_MTU
             )));
         }
 
+        // VXLAN-GPE uses fixed flags, custom_vxlan_flags may only be left
+        // unchanged or set to the same flags
+        if self.outputs.npb.vxlan_encapsulation == VxlanEncapsulation::VxlanGpe
+            && self.outputs.npb.custom_vxlan_flags != NPB_VXLAN_FLAGS
+            && self.outputs.npb.custom_vxlan_flags != NPB_GPE_VXLAN_FLAGS
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "custom_vxlan_flags({:#010b}) conflicts with vxlan-gpe encapsulation, only {:#010b} is allowed",
+                self.outputs.npb.custom_vxlan_flags, NPB_GPE_VXLAN_FLAGS
+            )));
+        }
+
+        if self.outputs.npb.underlay_hop_limit == 0 {
+            return Err(ConfigError::RuntimeConfigInvalid(
+                "underlay_hop_limit(0) should be in [1, 255]".to_owned(),
+            ));
+        }
+
         if self.outputs.npb.raw_udp_vlan_tag > 4095 {
             return Err(ConfigError::RuntimeConfigInvalid(format!(
                 "raw_udp_vlan_tag({}) out of range (0-4095)",
@@ -3388,55 +5768,536 @@ impl UserConfig {
             )));
         }
 
-        if self.global.communication.max_escape_duration < Duration::from_secs(600)
-            || self.global.communication.max_escape_duration
-                > Duration::from_secs(30 * 24 * 60 * 60)
-        {
-            return Err(ConfigError::RuntimeConfigInvalid(format!(
-                "max_escape_duration {:?} not in [600s, 30d]",
-                self.global.communication.max_escape_duration
-            )));
+        if let Err(e) = self.inputs.cbpf.af_packet.validate() {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
         }
 
-        if self.global.communication.proxy_controller_port == 0 {
-            return Err(ConfigError::RuntimeConfigInvalid(format!(
-                "proxy_controller_port({}) invalid",
-                self.global.communication.proxy_controller_port
-            )));
+        if let Err(e) = self.inputs.cbpf.special_network.dpdk.validate() {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
         }
 
-        if !(128..=65535).contains(&self.inputs.cbpf.tunning.max_capture_packet_size) {
-            return Err(ConfigError::RuntimeConfigInvalid(format!(
-                "max_capture_packet_size {} not in [128, 65535]",
-                self.inputs.cbpf.tunning.max_capture_packet_size
-            )));
+        if let Err(e) = self.inputs.cbpf.validate(&self.outputs.npb) {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
         }
 
-        if self.outputs.socket.data_socket_type == agent::SocketType::RawUdp {
-            return Err(ConfigError::RuntimeConfigInvalid(format!(
-                "invalid data_socket_type {:?}",
-                self.outputs.socket.data_socket_type
-            )));
+        for interface in self.inputs.cbpf.special_network.libpcap.interfaces.iter() {
+            if let Err(e) = interface.validate() {
+                return Err(ConfigError::RuntimeConfigInvalid(e));
+            }
         }
 
-        for nic in &self.inputs.ebpf.network.nic_optimize {
-            nic.validate().map_err(ConfigError::RuntimeConfigInvalid)?;
+        if let Err(e) = check_extra_bpf_filter(&self.inputs.cbpf.af_packet.extra_bpf_filter) {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
         }
-        self.inputs
-            .ebpf
-            .tunning
-            .validate()
-            .map_err(ConfigError::RuntimeConfigInvalid)?;
 
-        Ok(())
-    }
+        if let Err(e) = self.processors.packet.tcp_header.header_fields.validate() {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
+        }
 
-    fn set_standalone(&mut self) {
-        self.global.common.enabled = true;
-        self.global.communication.ingester_ip = "127.0.0.1".to_string();
-        self.global.communication.ingester_port = 30033;
-        self.global.communication.proxy_controller_ip = "127.0.0.1".to_string();
-        self.global.communication.proxy_controller_port = 30035;
+        if let Err(e) = self.inputs.integration.validate() {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
+        }
+
+        if let Err(e) = self
+            .processors
+            .request_log
+            .application_protocol_inference
+            .protocol_special_config
+            .mysql
+            .validate()
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
+        }
+
+        if let Err(e) = self
+            .processors
+            .request_log
+            .application_protocol_inference
+            .protocol_special_config
+            .mongodb
+            .validate()
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(e));
+        }
+
+        if !self
+            .inputs
+            .cbpf
+            .af_packet
+            .interface_exclude_regex
+            .is_empty()
+            && regex::Regex::new(&self.inputs.cbpf.af_packet.interface_exclude_regex).is_err()
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "malformed interface_exclude_regex({})",
+                self.inputs.cbpf.af_packet.interface_exclude_regex
+            )));
+        }
+
+        let elasticsearch = &self
+            .processors
+            .request_log
+            .application_protocol_inference
+            .protocol_special_config
+            .elasticsearch;
+        if !elasticsearch.index_suffix_regex.is_empty()
+            && regex::Regex::new(&elasticsearch.index_suffix_regex).is_err()
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "malformed elasticsearch index_suffix_regex({})",
+                elasticsearch.index_suffix_regex
+            )));
+        }
+
+        for rule in self
+            .processors
+            .request_log
+            .tag_extraction
+            .http_endpoint
+            .rewrite_rules
+            .iter()
+        {
+            if let Err(e) = rule.compile() {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "invalid http endpoint rewrite rule {rule:?}: {e}"
+                )));
+            }
+        }
+
+        for (protocol, rules) in self.processors.request_log.filters.tag_filters.iter() {
+            for rule in rules {
+                if let Err(e) = BlacklistTrie::validate_rule(rule) {
+                    return Err(ConfigError::RuntimeConfigInvalid(format!(
+                        "invalid tag filter of {protocol}: {e}"
+                    )));
+                }
+            }
+        }
+
+        for app in self
+            .processors
+            .request_log
+            .timeouts
+            .session_aggregate
+            .iter()
+        {
+            if app.timeout < SessionTimeout::MIN {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "session_aggregate timeout {:?} of {:?} less than {:?}",
+                    app.timeout,
+                    app.protocol,
+                    SessionTimeout::MIN
+                )));
+            }
+        }
+
+        self.processors
+            .request_log
+            .application_protocol_inference
+            .protocol_special_config
+            .custom_text
+            .validate()
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+
+        if self.global.communication.max_escape_duration < Duration::from_secs(600)
+            || self.global.communication.max_escape_duration
+                > Duration::from_secs(30 * 24 * 60 * 60)
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "max_escape_duration {:?} not in [600s, 30d]",
+                self.global.communication.max_escape_duration
+            )));
+        }
+
+        for sampling in self
+            .outputs
+            .flow_log
+            .filters
+            .l4_observation_point_sampling_ratios
+            .iter()
+        {
+            if !(sampling.ratio > 0.0 && sampling.ratio <= 1.0) {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "l4_observation_point_sampling_ratios ratio {} of observation point {} not in (0, 1]",
+                    sampling.ratio, sampling.observation_point
+                )));
+            }
+            if u8::try_from(sampling.observation_point)
+                .ok()
+                .and_then(|p| TapSide::try_from(p).ok())
+                .is_none()
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "l4_observation_point_sampling_ratios observation point {} invalid",
+                    sampling.observation_point
+                )));
+            }
+        }
+
+        let communication = &self.global.communication;
+        if communication.ingester_reconnect_backoff_base < Duration::from_millis(100)
+            || communication.ingester_reconnect_backoff_base > Duration::from_secs(60)
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "ingester_reconnect_backoff_base {:?} not in [100ms, 1min]",
+                communication.ingester_reconnect_backoff_base
+            )));
+        }
+        // sockets are retried at least once within max_escape_duration
+        let backoff_max_limit = communication
+            .max_escape_duration
+            .min(Duration::from_secs(3600));
+        if communication.ingester_reconnect_backoff_max
+            < communication
+                .ingester_reconnect_backoff_base
+                .max(Duration::from_secs(1))
+            || communication.ingester_reconnect_backoff_max > backoff_max_limit
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "ingester_reconnect_backoff_max {:?} not in [max(1s, ingester_reconnect_backoff_base), min(1h, max_escape_duration)]",
+                communication.ingester_reconnect_backoff_max
+            )));
+        }
+
+        for (i, ip) in communication.ingester_failover_ips.iter().enumerate() {
+            if ip.is_empty() || *ip == communication.ingester_ip {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "ingester_failover_ips {:?} invalid, addresses should not be empty or the same as ingester_ip",
+                    communication.ingester_failover_ips
+                )));
+            }
+            if communication.ingester_failover_ips[..i].contains(ip) {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "ingester_failover_ips {:?} has duplicate address {}",
+                    communication.ingester_failover_ips, ip
+                )));
+            }
+        }
+        let (min, max) = Communication::INGESTER_FAILOVER_THRESHOLD_RANGE;
+        if !(min..=max).contains(&communication.ingester_failover_threshold) {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "ingester_failover_threshold {} not in [{}, {}]",
+                communication.ingester_failover_threshold, min, max
+            )));
+        }
+        let (min, max) = Communication::INGESTER_FAILBACK_HOLD_DOWN_RANGE;
+        if !(min..=max).contains(&communication.ingester_failback_hold_down) {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "ingester_failback_hold_down {:?} not in [{:?}, {:?}]",
+                communication.ingester_failback_hold_down, min, max
+            )));
+        }
+
+        if self.global.communication.proxy_controller_port == 0 {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "proxy_controller_port({}) invalid",
+                self.global.communication.proxy_controller_port
+            )));
+        }
+
+        if !(128..=65535).contains(&self.inputs.cbpf.tunning.max_capture_packet_size) {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "max_capture_packet_size {} not in [128, 65535]",
+                self.inputs.cbpf.tunning.max_capture_packet_size
+            )));
+        }
+
+        self.inputs
+            .cbpf
+            .tunning
+            .validate(&self.global.tunning.cpu_affinity)
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+
+        if self.outputs.socket.data_socket_type == agent::SocketType::RawUdp {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "invalid data_socket_type {:?}",
+                self.outputs.socket.data_socket_type
+            )));
+        }
+        if self.outputs.socket.sockets_per_sender < 1
+            || self.outputs.socket.sockets_per_sender > Socket::MAX_SOCKETS_PER_SENDER
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "sockets_per_sender {} not in [1, {}]",
+                self.outputs.socket.sockets_per_sender,
+                Socket::MAX_SOCKETS_PER_SENDER
+            )));
+        }
+        let throttles = &self.outputs.flow_log.throttles;
+        for (name, throttle) in [
+            ("l4_throttle", throttles.l4_throttle),
+            ("l7_throttle", throttles.l7_throttle),
+        ] {
+            if throttle < Throttles::RANGE.0 || throttle > Throttles::RANGE.1 {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "{name}({throttle}) not in {:?}",
+                    Throttles::RANGE
+                )));
+            }
+        }
+        for o in throttles.l7_throttle_overrides.iter() {
+            // logs sampled by the override still pass the global throttle
+            if o.throttle > throttles.l7_throttle {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "l7_throttle_overrides throttle({}) of protocol {:?} is larger than l7_throttle({})",
+                    o.throttle, o.protocol, throttles.l7_throttle
+                )));
+            }
+        }
+        if !(0..=Socket::MAX_ZSTD_LEVEL).contains(&self.outputs.socket.data_compression_level) {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "data_compression_level {} not in [0, {}]",
+                self.outputs.socket.data_compression_level,
+                Socket::MAX_ZSTD_LEVEL
+            )));
+        }
+        let tunning = &self.outputs.socket.tunning;
+        if tunning.batch_max_bytes < OutputsSocketTunning::BATCH_MAX_BYTES_RANGE.0
+            || tunning.batch_max_bytes > OutputsSocketTunning::BATCH_MAX_BYTES_RANGE.1
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "batch_max_bytes {} not in {:?}",
+                tunning.batch_max_bytes,
+                OutputsSocketTunning::BATCH_MAX_BYTES_RANGE
+            )));
+        }
+        if tunning.batch_max_documents > OutputsSocketTunning::BATCH_MAX_DOCUMENTS_MAX {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "batch_max_documents {} not in [0, {}]",
+                tunning.batch_max_documents,
+                OutputsSocketTunning::BATCH_MAX_DOCUMENTS_MAX
+            )));
+        }
+        if tunning.linger < OutputsSocketTunning::LINGER_RANGE.0
+            || tunning.linger > OutputsSocketTunning::LINGER_RANGE.1
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "linger {:?} not in {:?}",
+                tunning.linger,
+                OutputsSocketTunning::LINGER_RANGE
+            )));
+        }
+        let kafka = &self.outputs.kafka;
+        if kafka.enabled {
+            if kafka.brokers.is_empty() || kafka.brokers.iter().any(|b| b.trim().is_empty()) {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "kafka brokers {:?} invalid",
+                    kafka.brokers
+                )));
+            }
+            if kafka.topic.is_empty() {
+                return Err(ConfigError::RuntimeConfigInvalid(
+                    "kafka topic is empty".to_string(),
+                ));
+            }
+            if kafka.buffer_size < OutputsKafka::BUFFER_SIZE_RANGE.0
+                || kafka.buffer_size > OutputsKafka::BUFFER_SIZE_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "kafka buffer_size {} not in {:?}",
+                    kafka.buffer_size,
+                    OutputsKafka::BUFFER_SIZE_RANGE
+                )));
+            }
+            if kafka.sasl_mechanism != KafkaSaslMechanism::None && kafka.sasl_username.is_empty() {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "kafka sasl_username is empty with sasl_mechanism {:?}",
+                    kafka.sasl_mechanism
+                )));
+            }
+        }
+        let standalone_mode = &self.global.standalone_mode;
+        // zero disables the rotation by time
+        if !standalone_mode.data_file_rotation_interval.is_zero()
+            && (standalone_mode.data_file_rotation_interval
+                < StandaloneMode::DATA_FILE_ROTATION_INTERVAL_RANGE.0
+                || standalone_mode.data_file_rotation_interval
+                    > StandaloneMode::DATA_FILE_ROTATION_INTERVAL_RANGE.1)
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "data_file_rotation_interval {:?} not 0 or in {:?}",
+                standalone_mode.data_file_rotation_interval,
+                StandaloneMode::DATA_FILE_ROTATION_INTERVAL_RANGE
+            )));
+        }
+        if standalone_mode.max_data_files < StandaloneMode::MAX_DATA_FILES_RANGE.0
+            || standalone_mode.max_data_files > StandaloneMode::MAX_DATA_FILES_RANGE.1
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "max_data_files {} not in {:?}",
+                standalone_mode.max_data_files,
+                StandaloneMode::MAX_DATA_FILES_RANGE
+            )));
+        }
+        let tunning = &self.outputs.flow_metrics.tunning;
+        if tunning.second_metrics_shedding_high_watermark == 0
+            || tunning.second_metrics_shedding_high_watermark > 100
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "second_metrics_shedding_high_watermark {} not in [1, 100]",
+                tunning.second_metrics_shedding_high_watermark
+            )));
+        }
+        if tunning.second_metrics_shedding_low_watermark
+            >= tunning.second_metrics_shedding_high_watermark
+        {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "second_metrics_shedding_low_watermark {} not less than second_metrics_shedding_high_watermark {}",
+                tunning.second_metrics_shedding_low_watermark,
+                tunning.second_metrics_shedding_high_watermark
+            )));
+        }
+        let (min, max) = FlowMetricsTunning::SECOND_METRICS_SHEDDING_INTERVALS_RANGE;
+        if !(min..=max).contains(&tunning.second_metrics_shedding_intervals) {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "second_metrics_shedding_intervals {} not in [{}, {}]",
+                tunning.second_metrics_shedding_intervals, min, max
+            )));
+        }
+        let otlp = &self.outputs.otlp;
+        if otlp.enabled {
+            // tonic is built without tls
+            if !otlp.endpoint.starts_with("http://") {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp endpoint {:?} invalid, should be http://host:port",
+                    otlp.endpoint
+                )));
+            }
+            if otlp.buffer_size < OutputsOtlp::BUFFER_SIZE_RANGE.0
+                || otlp.buffer_size > OutputsOtlp::BUFFER_SIZE_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp buffer_size {} not in {:?}",
+                    otlp.buffer_size,
+                    OutputsOtlp::BUFFER_SIZE_RANGE
+                )));
+            }
+            if otlp.batch_size < OutputsOtlp::BATCH_SIZE_RANGE.0
+                || otlp.batch_size > OutputsOtlp::BATCH_SIZE_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp batch_size {} not in {:?}",
+                    otlp.batch_size,
+                    OutputsOtlp::BATCH_SIZE_RANGE
+                )));
+            }
+            if otlp.batch_timeout < OutputsOtlp::BATCH_TIMEOUT_RANGE.0
+                || otlp.batch_timeout > OutputsOtlp::BATCH_TIMEOUT_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp batch_timeout {:?} not in {:?}",
+                    otlp.batch_timeout,
+                    OutputsOtlp::BATCH_TIMEOUT_RANGE
+                )));
+            }
+            if otlp.export_timeout < OutputsOtlp::EXPORT_TIMEOUT_RANGE.0
+                || otlp.export_timeout > OutputsOtlp::EXPORT_TIMEOUT_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp export_timeout {:?} not in {:?}",
+                    otlp.export_timeout,
+                    OutputsOtlp::EXPORT_TIMEOUT_RANGE
+                )));
+            }
+            if otlp.max_retries > OutputsOtlp::MAX_RETRIES_MAX {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp max_retries {} exceeds {}",
+                    otlp.max_retries,
+                    OutputsOtlp::MAX_RETRIES_MAX
+                )));
+            }
+            if otlp.retry_backoff < OutputsOtlp::RETRY_BACKOFF_RANGE.0
+                || otlp.retry_backoff > OutputsOtlp::RETRY_BACKOFF_RANGE.1
+            {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "otlp retry_backoff {:?} not in {:?}",
+                    otlp.retry_backoff,
+                    OutputsOtlp::RETRY_BACKOFF_RANGE
+                )));
+            }
+        }
+        // npb packets are sent to the configured npb targets, not the ingester
+        if self.outputs.socket.npb_socket_type == agent::SocketType::Tls {
+            return Err(ConfigError::RuntimeConfigInvalid(format!(
+                "invalid npb_socket_type {:?}",
+                self.outputs.socket.npb_socket_type
+            )));
+        }
+
+        for matcher in &self.inputs.proc.process_matcher {
+            if let Some(t) = matcher.off_cpu_min_blocking_time {
+                if t > EbpfProfileOffCpu::MAX_MIN_BLOCKING_TIME {
+                    return Err(ConfigError::RuntimeConfigInvalid(format!(
+                        "off_cpu_min_blocking_time {:?} of process matcher {} exceeds {:?}",
+                        t,
+                        matcher.match_regex.as_str(),
+                        EbpfProfileOffCpu::MAX_MIN_BLOCKING_TIME
+                    )));
+                }
+            }
+        }
+
+        for group in &self.inputs.cbpf.af_packet.interface_groups {
+            if let Err(e) = Regex::new(&group.interface_regex) {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "invalid interface_regex {} of interface group: {e}",
+                    group.interface_regex
+                )));
+            }
+            if let Some(mode) = group.packet_fanout_mode {
+                if mode > Self::PACKET_FANOUT_MODE_MAX {
+                    return Err(ConfigError::RuntimeConfigInvalid(format!(
+                        "packet_fanout_mode {mode} of interface group {} exceeds {}",
+                        group.interface_regex,
+                        Self::PACKET_FANOUT_MODE_MAX
+                    )));
+                }
+            }
+            if group.packet_fanout_count == Some(0) {
+                return Err(ConfigError::RuntimeConfigInvalid(format!(
+                    "packet_fanout_count of interface group {} is 0",
+                    group.interface_regex
+                )));
+            }
+        }
+
+        for nic in &self.inputs.ebpf.network.nic_optimize {
+            nic.validate().map_err(ConfigError::RuntimeConfigInvalid)?;
+        }
+        self.inputs
+            .ebpf
+            .tunning
+            .validate()
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+        self.inputs
+            .ebpf
+            .profile
+            .unwinding
+            .validate()
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+        self.inputs
+            .ebpf
+            .file
+            .io_event
+            .validate()
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+        self.inputs
+            .ebpf
+            .socket
+            .uprobe
+            .golang
+            .validate()
+            .map_err(ConfigError::RuntimeConfigInvalid)?;
+
+        Ok(())
+    }
+
+    fn set_standalone(&mut self) {
+        self.global.common.enabled = true;
+        self.global.communication.ingester_ip = "127.0.0.1".to_string();
+        self.global.communication.ingester_port = 30033;
+        self.global.communication.proxy_controller_ip = "127.0.0.1".to_string();
+        self.global.communication.proxy_controller_port = 30035;
         self.global.ntp.enabled = false;
         self.outputs.flow_metrics.filters.apm_metrics = true;
         self.outputs.flow_metrics.filters.npm_metrics = true;
@@ -3664,12 +6525,18 @@ pub struct HttpEndpointExtraction {
 #[serde(default, rename_all = "kebab-case")]
 pub struct ExtraLogFieldsInfo {
     pub field_name: String,
+    pub multi: bool,
+    pub separator: String,
+    pub max_bytes: usize,
 }
 
 impl From<&CustomFields> for ExtraLogFieldsInfo {
     fn from(c: &CustomFields) -> Self {
         Self {
             field_name: c.field_name.clone(),
+            multi: c.multi,
+            separator: c.separator.clone(),
+            max_bytes: c.max_bytes,
         }
     }
 }
@@ -3679,6 +6546,8 @@ impl From<&CustomFields> for ExtraLogFieldsInfo {
 pub struct ExtraLogFields {
     pub http: Vec<ExtraLogFieldsInfo>,
     pub http2: Vec<ExtraLogFieldsInfo>,
+    pub mqtt: Vec<ExtraLogFieldsInfo>,
+    pub sofarpc: Vec<ExtraLogFieldsInfo>,
 }
 
 impl ExtraLogFields {
@@ -3687,12 +6556,21 @@ impl ExtraLogFields {
             fields
                 .iter_mut()
                 .for_each(|f| f.field_name.make_ascii_lowercase());
-            fields.sort_by(|a, b| a.field_name.cmp(&b.field_name));
-            fields.dedup_by(|a, b| a.field_name == b.field_name);
+            fields.sort_by(|a, b| {
+                (&a.field_name, a.multi, &a.separator, a.max_bytes).cmp(&(
+                    &b.field_name,
+                    b.multi,
+                    &b.separator,
+                    b.max_bytes,
+                ))
+            });
+            fields.dedup();
         }
 
         deduplicate_fields(&mut self.http);
         deduplicate_fields(&mut self.http2);
+        deduplicate_fields(&mut self.mqtt);
+        deduplicate_fields(&mut self.sofarpc);
     }
 }
 
@@ -3757,6 +6635,41 @@ pub struct NetSignParseConfig {
     pub extract_biz_data_enabled: bool,
 }
 
+#[derive(Clone, Debug)]
+pub struct ElasticsearchParseConfig {
+    pub ports: Bitmap,
+    pub index_suffix_regex: Option<Regex>,
+}
+
+impl Default for ElasticsearchParseConfig {
+    fn default() -> Self {
+        (&ElasticsearchConfig::default()).into()
+    }
+}
+
+impl From<&ElasticsearchConfig> for ElasticsearchParseConfig {
+    fn from(c: &ElasticsearchConfig) -> Self {
+        Self {
+            ports: parse_u16_range_list_to_bitmap(&c.ports, false).unwrap(),
+            index_suffix_regex: if c.index_suffix_regex.is_empty() {
+                None
+            } else {
+                Regex::new(&c.index_suffix_regex).ok()
+            },
+        }
+    }
+}
+
+impl Eq for ElasticsearchParseConfig {}
+
+impl PartialEq for ElasticsearchParseConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.ports == other.ports
+            && self.index_suffix_regex.as_ref().map(|r| r.as_str())
+                == other.index_suffix_regex.as_ref().map(|r| r.as_str())
+    }
+}
+
 #[derive(Clone, Default, Debug, Deserialize, PartialEq, Eq)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct BondGroup {
@@ -4059,23 +6972,45 @@ mod tests {
         let yaml = r#"
 block_size: 512
 sender_queue_size: 131072
-header_fields_flag: "0b1010_1010"
+header_fields:
+  enabled: true
+  seq: true
+  ack: true
+  sack: true
 "#;
         let tcp_header: TcpHeader = serde_yaml::from_str(yaml).unwrap();
 
         assert_eq!(tcp_header.block_size, 512);
         assert_eq!(tcp_header.sender_queue_size, 131072);
-        assert_eq!(tcp_header.header_fields_flag, 0b1010_1010);
+        assert!(tcp_header.header_fields.seq && tcp_header.header_fields.ack);
+        assert!(!tcp_header.header_fields.flags);
+        assert_eq!(tcp_header.header_fields.flag(), 0b0110_0001);
+
+        // Test with the deprecated binary header_fields_flag
+        let yaml = r#"
+block_size: 256
+sender_queue_size: 65536
+header_fields_flag: "0b1010_1010"
+"#;
+        let tcp_header: TcpHeader = serde_yaml::from_str(yaml).unwrap();
 
-        // Test with decimal input for header_fields_flag
+        assert!(tcp_header.header_fields.enabled);
+        assert!(tcp_header.header_fields.flags && tcp_header.header_fields.window_scale);
+        assert!(!tcp_header.header_fields.seq && !tcp_header.header_fields.sack);
+        assert_eq!(tcp_header.header_fields.flag(), 0b1010_1010);
+
+        // Test with decimal input for header_fields_flag, which overrides header_fields
         let yaml = r#"
 block_size: 256
 sender_queue_size: 65536
 header_fields_flag: "170"
+header_fields:
+  enabled: true
+  seq: true
 "#;
         let tcp_header: TcpHeader = serde_yaml::from_str(yaml).unwrap();
 
-        assert_eq!(tcp_header.header_fields_flag, 170); // 170 decimal == 0b1010_1010
+        assert_eq!(tcp_header.header_fields.flag(), 170); // 170 decimal == 0b1010_1010
 
         // Test with invalid input
         let yaml_invalid = r#"
@@ -4087,6 +7022,149 @@ header_fields_flag: "invalid"
         assert!(result.is_err());
     }
 
+    #[test]
+    fn tcp_header_fields_flag() {
+        assert_eq!(TcpHeaderFields::default().flag(), 0);
+        for flag in 0..=u8::MAX {
+            let mut fields = TcpHeaderFields::from_flag(flag);
+            assert_eq!(fields.flag(), flag as u16);
+            fields.enabled = false;
+            assert_eq!(fields.flag(), 0);
+        }
+    }
+
+    #[test]
+    fn tcp_header_each_field() {
+        let fields = [
+            ("flags", 0b1000_0000),
+            ("seq", 0b0100_0000),
+            ("ack", 0b0010_0000),
+            ("payload_size", 0b0001_0000),
+            ("window_size", 0b0000_1000),
+            ("mss", 0b0000_0100),
+            ("window_scale", 0b0000_0010),
+            ("sack", 0b0000_0001),
+            ("timestamps", 0b1_0000_0000),
+        ];
+        for (name, flag) in fields {
+            let yaml = format!("header_fields:\n  enabled: true\n  {}: true\n", name);
+            let tcp_header: TcpHeader = serde_yaml::from_str(&yaml).unwrap();
+            assert_eq!(tcp_header.header_fields.flag(), flag, "{}", name);
+            assert!(tcp_header.header_fields.validate().is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn validate_tcp_header_fields() {
+        let mut c = UserConfig::default();
+        c.processors.packet.tcp_header.header_fields.enabled = true;
+        assert!(matches!(
+            c.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("without any field")
+        ));
+
+        c.processors.packet.tcp_header.header_fields.seq = true;
+        assert!(c.validate().is_ok());
+
+        // fields are ignored if disabled
+        c.processors.packet.tcp_header.header_fields = TcpHeaderFields {
+            enabled: false,
+            ..TcpHeaderFields::from_flag(0xff)
+        };
+        assert!(c.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_mysql_decompress_max_bytes() {
+        let mut c = UserConfig::default();
+        for (max_bytes, valid) in [
+            (1024, false),
+            (65536, true),
+            (16777216, true),
+            (16777217, false),
+        ] {
+            c.processors
+                .request_log
+                .application_protocol_inference
+                .protocol_special_config
+                .mysql
+                .decompress_max_bytes = max_bytes;
+            if valid {
+                assert!(c.validate().is_ok());
+            } else {
+                assert!(matches!(
+                    c.validate(),
+                    Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains(&format!("decompress_max_bytes({max_bytes})"))
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn validate_mongodb_decompress_max_bytes() {
+        let mut c = UserConfig::default();
+        for (max_bytes, valid) in [
+            (1024, false),
+            (65536, true),
+            (50331648, true),
+            (50331649, false),
+        ] {
+            c.processors
+                .request_log
+                .application_protocol_inference
+                .protocol_special_config
+                .mongodb
+                .decompress_max_bytes = max_bytes;
+            if valid {
+                assert!(c.validate().is_ok());
+            } else {
+                assert!(matches!(
+                    c.validate(),
+                    Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains(&format!("mongodb decompress_max_bytes({max_bytes})"))
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn parse_x_request_id() {
+        let yaml = r#"
+x_request_id:
+- X-Request-ID
+"#;
+        let tag: TracingTag = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            tag.x_request_id,
+            vec![XRequestIdHeader::Both("X-Request-ID".to_string())]
+        );
+        assert_eq!(tag.x_request_id[0].request(), "X-Request-ID");
+        assert_eq!(tag.x_request_id[0].response(), "X-Request-ID");
+
+        let yaml = r#"
+x_request_id:
+- request: X-Request-ID
+  response: X-Correlation-ID
+- X_Request_ID
+"#;
+        let tag: TracingTag = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            tag.x_request_id[0],
+            XRequestIdHeader::Split {
+                request: "X-Request-ID".to_string(),
+                response: "X-Correlation-ID".to_string(),
+            }
+        );
+        assert_eq!(tag.x_request_id[0].request(), "X-Request-ID");
+        assert_eq!(tag.x_request_id[0].response(), "X-Correlation-ID");
+        assert_eq!(tag.x_request_id[1].response(), "X_Request_ID");
+
+        let yaml = r#"
+x_request_id:
+- request: X-Request-ID
+"#;
+        assert!(serde_yaml::from_str::<TracingTag>(yaml).is_err());
+    }
+
     #[test]
     fn parse_proc_config() {
         let yaml = r#"
@@ -4154,6 +7232,81 @@ process_matcher:
         assert_eq!(rewritten.name, "com.example.Main");
     }
 
+    #[test]
+    fn resolve_memory_profile_feature() {
+        let on_cpu = ProcessMatcher {
+            match_regex: Regex::new("^java").unwrap(),
+            enabled_features: vec![ProcessMatcher::FEATURE_PROFILE_ON_CPU.to_string()],
+            ..Default::default()
+        };
+        let memory = ProcessMatcher {
+            match_regex: Regex::new("^nginx").unwrap(),
+            enabled_features: vec!["eBPF.Profile.Memory".to_string()],
+            ..Default::default()
+        };
+
+        // memory follows on-cpu when no matcher lists it
+        let resolved = ProcessMatcher::resolve_features(&[on_cpu.clone()]);
+        assert!(resolved[0].has_feature(ProcessMatcher::FEATURE_PROFILE_MEMORY));
+
+        // otherwise only the matchers listing it enable memory profiling
+        let resolved = ProcessMatcher::resolve_features(&[on_cpu, memory]);
+        assert!(!resolved[0].has_feature(ProcessMatcher::FEATURE_PROFILE_MEMORY));
+        assert_eq!(
+            resolved[1].enabled_features,
+            vec![ProcessMatcher::FEATURE_PROFILE_MEMORY.to_string()]
+        );
+    }
+
+    #[test]
+    fn memory_profile_matcher_with_container_and_rewrite() {
+        let yaml = r#"
+process_matcher:
+- match_regex: \bjava( +\S+)* +-jar +(\S*/)*([^ /]+\.jar)
+  match_type: cmdline_with_args
+  only_in_container: true
+  rewrite_name: $3
+  enabled_features: [ebpf.profile.memory]
+"#;
+        let proc: Proc = serde_yaml::from_str(yaml).unwrap();
+        let matchers = ProcessMatcher::resolve_features(&proc.process_matcher);
+        let matcher = &matchers[0];
+        assert!(matcher.has_feature(ProcessMatcher::FEATURE_PROFILE_MEMORY));
+
+        let mut pdata = process_data_for_cmdline("/usr/bin/java -jar /app/demo.jar");
+        assert!(matcher.get_process_data(&pdata, &HashMap::new()).is_none());
+
+        // the match is done on the command line, so the rewritten name does not affect it
+        pdata.container_id = "abc".to_string();
+        let rewritten = matcher.get_process_data(&pdata, &HashMap::new()).unwrap();
+        assert_eq!(rewritten.name, "demo.jar");
+        assert_eq!(rewritten.pid, pdata.pid);
+    }
+
+    #[test]
+    fn off_cpu_min_blocking_time_override() {
+        let yaml = r#"
+inputs:
+  proc:
+    process_matcher:
+    - match_regex: ^trade-engine$
+      off_cpu_min_blocking_time: 1us
+    - match_regex: .*
+"#;
+        let mut cfg: UserConfig = serde_yaml::from_str(yaml).unwrap();
+        let matchers = &cfg.inputs.proc.process_matcher;
+        assert_eq!(
+            matchers[0].off_cpu_min_blocking_time,
+            Some(Duration::from_micros(1))
+        );
+        assert_eq!(matchers[1].off_cpu_min_blocking_time, None);
+        assert!(cfg.validate().is_ok());
+
+        cfg.inputs.proc.process_matcher[1].off_cpu_min_blocking_time =
+            Some(Duration::from_secs(7200));
+        assert!(cfg.validate().is_err());
+    }
+
     #[test]
     fn parse_timeouts_by_applications() {
         let yaml = r#"
@@ -4175,4 +7328,1069 @@ processors:
         assert_eq!(apps[1].protocol, L7Protocol::Grpc);
         assert_eq!(apps[1].timeout, Duration::from_secs(130));
     }
+
+    #[test]
+    fn validate_custom_text() {
+        let parse = |yaml: &str| serde_yaml::from_str::<CustomTextConfig>(yaml);
+
+        assert!(parse("request_regex: '^(?P<request_type>[A-Z]+'").is_err());
+
+        let c = parse("request_regex: '^(?P<request_type>[A-Z]+) (?P<resource>\\S+)'").unwrap();
+        assert!(c.validate().is_ok());
+
+        let c = parse(
+            r#"
+request_regex: '^(?P<request_type>[A-Z]+) (?P<request_id>\d+)'
+response_regex: '^(?P<status>[A-Z]+)'
+pairing: request_id
+"#,
+        )
+        .unwrap();
+        assert!(c.validate().is_err());
+
+        let c = parse("request_regex: '^[A-Z]+'").unwrap();
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn validate_unwinding() {
+        let yaml = r#"
+inputs:
+  ebpf:
+    profile:
+      unwinding:
+        dwarf_disabled: false
+        dwarf_regex: ^java$
+        dwarf_process_map_size: 2048
+        dwarf_shard_map_size: 256
+"#;
+        let c: UserConfig = serde_yaml::from_str(yaml).unwrap();
+        let unwinding = &c.inputs.ebpf.profile.unwinding;
+        assert!(!unwinding.dwarf_disabled);
+        assert_eq!(unwinding.dwarf_regex, "^java$");
+        assert_eq!(unwinding.dwarf_process_map_size, 2048);
+        assert_eq!(unwinding.dwarf_shard_map_size, 256);
+        assert!(unwinding.validate().is_ok());
+
+        let invalid = [
+            Unwinding {
+                dwarf_regex: "^(java".to_string(),
+                ..Default::default()
+            },
+            Unwinding {
+                dwarf_process_map_size: 0,
+                ..Default::default()
+            },
+            Unwinding {
+                dwarf_shard_map_size: 4097,
+                ..Default::default()
+            },
+        ];
+        for unwinding in invalid {
+            assert!(unwinding.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn go_version_range() {
+        for (s, expected) in [
+            ("go1.23", (1, 23)),
+            ("1.23", (1, 23)),
+            ("go1.23rc1", (1, 23)),
+            ("go1.23beta2", (1, 23)),
+            ("go1.23.4", (1, 23)),
+        ] {
+            let v: GoVersion = s.parse().unwrap();
+            assert_eq!((v.major, v.minor), expected, "{}", s);
+        }
+        for s in ["", "go", "go1", "gox.23", "go1.x", "go1.256"] {
+            assert!(s.parse::<GoVersion>().is_err(), "{}", s);
+        }
+
+        let yaml = r#"
+inputs:
+  ebpf:
+    socket:
+      uprobe:
+        golang:
+          offset_overrides:
+          - min_version: go1.23
+            max_version: go1.24
+            offsets:
+            - structure: runtime.g
+              field: goid
+              offset: 160
+"#;
+        let c: UserConfig = serde_yaml::from_str(yaml).unwrap();
+        let golang = &c.inputs.ebpf.socket.uprobe.golang;
+        assert!(golang.validate().is_ok());
+        let o = &golang.offset_overrides[0];
+        assert_eq!(o.offsets[0].offset, 160);
+        for (s, expected) in [
+            ("go1.22.9", false),
+            ("go1.23rc1", true),
+            ("go1.23.0", true),
+            ("go1.24.3", true),
+            ("go1.25rc1", false),
+        ] {
+            assert_eq!(o.contains(&s.parse().unwrap()), expected, "{}", s);
+        }
+        assert!(o.min_version.encode(0) <= (1 << 16) + (23 << 8));
+        assert!(o.max_version.encode(255) >= (1 << 16) + (24 << 8) + 3);
+
+        let mut invalid = o.clone();
+        invalid.min_version = "go1.25".parse().unwrap();
+        assert!(invalid.validate().is_err());
+        for (structure, field, offset) in [
+            ("runtime.g", "goid", 162),
+            ("runtime.g", "goid", 4096),
+            ("runtime.g", "m", 16),
+        ] {
+            let mut invalid = o.clone();
+            invalid.offsets[0] = GolangStructOffset {
+                structure: structure.to_string(),
+                field: field.to_string(),
+                offset,
+            };
+            assert!(invalid.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn deduplicate_extra_log_fields() {
+        let field = |name: &str, multi: bool, separator: &str| ExtraLogFieldsInfo {
+            field_name: name.to_string(),
+            multi,
+            separator: separator.to_string(),
+            max_bytes: 1024,
+        };
+        let mut fields = ExtraLogFields {
+            http: vec![
+                field("Via", true, ","),
+                field("via", true, ","),
+                field("via", false, ","),
+                field("via", true, ";"),
+            ],
+            ..Default::default()
+        };
+        fields.deduplicate();
+        assert_eq!(
+            fields.http,
+            vec![
+                field("via", false, ","),
+                field("via", true, ","),
+                field("via", true, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn canonicalize_reassembly_protocols() {
+        let mut protocols = vec![
+            "http2".to_string(),
+            "MYSQL".to_string(),
+            "Redis".to_string(),
+        ];
+        EbpfSocketPreprocess::canonicalize_protocols(&mut protocols, "test");
+        assert_eq!(protocols, vec!["HTTP2", "MySQL", "Redis"]);
+
+        let mut preprocess = EbpfSocketPreprocess {
+            out_of_order_reassembly_protocols: vec!["grpc".to_string()],
+            segmentation_reassembly_protocols: vec!["Grpc".to_string()],
+            ..Default::default()
+        };
+        preprocess.validate_protocols(&["HTTP2".to_string()]);
+        preprocess.adjust();
+        assert_eq!(
+            preprocess.out_of_order_reassembly_protocols,
+            vec!["gRPC", "HTTP2", "Triple"]
+        );
+        assert_eq!(
+            preprocess.segmentation_reassembly_protocols,
+            preprocess.out_of_order_reassembly_protocols
+        );
+    }
+
+    #[test]
+    fn drop_unknown_reassembly_protocols() {
+        let mut protocols = vec![
+            "HTTP2".to_string(),
+            "Postgres".to_string(),
+            "Ping".to_string(),
+            "PostgreSQL".to_string(),
+        ];
+        EbpfSocketPreprocess::canonicalize_protocols(&mut protocols, "test");
+        assert_eq!(protocols, vec!["HTTP2", "PostgreSQL"]);
+    }
+
+    #[test]
+    fn parse_vhost_socket_path() {
+        let parse = |yaml: &str| {
+            serde_yaml::from_str::<VhostUser>(yaml)
+                .unwrap()
+                .vhost_socket_path
+        };
+        assert!(parse("vhost_socket_path: \"\"").is_empty());
+        assert!(parse("{}").is_empty());
+        assert_eq!(
+            parse("vhost_socket_path: /tmp/vm1.sock"),
+            vec!["/tmp/vm1.sock"]
+        );
+        assert_eq!(
+            parse("vhost_socket_path: [/tmp/vm1.sock, \"\", /tmp/vm2.sock, /tmp/vm1.sock]"),
+            vec!["/tmp/vm1.sock", "/tmp/vm2.sock"]
+        );
+    }
+
+    #[test]
+    fn parse_vlan_pcp_mappings() {
+        let parse = |yaml: &str| {
+            let af_packet = serde_yaml::from_str::<AfPacket>(yaml).unwrap();
+            af_packet
+                .validate()
+                .map(|_| af_packet.vlan_pcp_in_physical_mirror_traffic)
+        };
+        let mapping = |pcp, capture_network_type| VlanPcpMapping {
+            pcp,
+            capture_network_type,
+        };
+        assert_eq!(parse("{}"), Ok(vec![mapping(0, None)]));
+        assert_eq!(
+            parse("vlan_pcp_in_physical_mirror_traffic: 9"),
+            Ok(vec![mapping(9, None)])
+        );
+        assert_eq!(
+            parse(
+                "vlan_pcp_in_physical_mirror_traffic: [{pcp: 1, capture_network_type: 4}, {pcp: 2}]"
+            ),
+            Ok(vec![mapping(1, Some(4)), mapping(2, None)])
+        );
+        assert!(parse("vlan_pcp_in_physical_mirror_traffic: 10").is_err());
+        assert!(parse("vlan_pcp_in_physical_mirror_traffic: [{pcp: 1}, {pcp: 8}]").is_err());
+        assert!(parse("vlan_pcp_in_physical_mirror_traffic: [{pcp: 1}, {pcp: 1}]").is_err());
+        assert!(parse(
+            "vlan_pcp_in_physical_mirror_traffic: [{pcp: 1, capture_network_type: 256}]"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_interface_dispatcher_assignments() {
+        let parse = |yaml: &str| {
+            let af_packet = serde_yaml::from_str::<AfPacket>(yaml).unwrap();
+            af_packet
+                .validate()
+                .map(|_| af_packet.interface_dispatcher_assignments)
+        };
+        let assignment = |interface_regex: &str, dispatcher| InterfaceDispatcherAssignment {
+            interface_regex: interface_regex.to_string(),
+            dispatcher,
+        };
+        assert_eq!(parse("{}"), Ok(vec![]));
+        assert_eq!(
+            parse(
+                r#"
+tunning:
+  packet_fanout_count: 2
+interface_dispatcher_assignments:
+- interface_regex: ^eth0$
+  dispatcher: 0
+- interface_regex: ^eth1$
+  dispatcher: 1
+"#
+            ),
+            Ok(vec![assignment("^eth0$", 0), assignment("^eth1$", 1)])
+        );
+        // out of range of packet_fanout_count
+        assert!(parse(
+            r#"
+tunning:
+  packet_fanout_count: 2
+interface_dispatcher_assignments:
+- interface_regex: ^eth0$
+  dispatcher: 2
+"#
+        )
+        .is_err());
+        assert!(parse(
+            r#"
+interface_dispatcher_assignments:
+- interface_regex: ^eth0$
+  dispatcher: 1
+"#
+        )
+        .is_err());
+        assert!(parse(
+            r#"
+tunning:
+  packet_fanout_count: 2
+interface_dispatcher_assignments:
+- interface_regex: "eth("
+  dispatcher: 0
+"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn validate_interface_promisc_overrides() {
+        let cbpf = |yaml: &str| serde_yaml::from_str::<Cbpf>(yaml).unwrap();
+        let overrides = r#"
+af_packet:
+  interface_promisc_overrides:
+  - interface_regex: ^storage\d+$
+    allmulti_enabled: true
+  - interface_regex: ^eth\d+$
+    promisc_enabled: true
+"#;
+        let c = cbpf(overrides);
+        assert_eq!(
+            c.af_packet.interface_promisc_overrides,
+            vec![
+                InterfacePromisc {
+                    interface_regex: r"^storage\d+$".to_string(),
+                    promisc_enabled: false,
+                    allmulti_enabled: true,
+                },
+                InterfacePromisc {
+                    interface_regex: r"^eth\d+$".to_string(),
+                    promisc_enabled: true,
+                    allmulti_enabled: false,
+                },
+            ]
+        );
+        assert!(c.af_packet.validate().is_ok());
+        assert!(c.validate(&Npb::default()).is_ok());
+
+        // nothing useful is captured without promiscuous mode in mirror mode
+        let mut mirror = c.clone();
+        mirror.common.capture_mode = agent::PacketCaptureType::Mirror;
+        assert!(mirror.validate(&Npb::default()).is_err());
+        mirror.af_packet.interface_promisc_overrides.remove(0);
+        assert!(mirror.validate(&Npb::default()).is_ok());
+
+        let invalid = cbpf(
+            r#"
+af_packet:
+  interface_promisc_overrides:
+  - interface_regex: (
+"#,
+        );
+        assert!(invalid.af_packet.validate().is_err());
+    }
+    #[test]
+    fn validate_decap_capture_mode() {
+        let npb = Npb {
+            enabled: false,
+            ..Default::default()
+        };
+        let mut cbpf = Cbpf::default();
+        cbpf.tunning.dispatcher_queue_enabled = true;
+        assert!(cbpf.validate(&npb).is_ok());
+
+        cbpf.common.capture_mode = agent::PacketCaptureType::Decap;
+        assert!(cbpf.validate(&npb).is_err());
+        cbpf.tunning.dispatcher_queue_enabled = false;
+        assert!(cbpf.validate(&npb).is_ok());
+
+        let mut invalid = cbpf.clone();
+        invalid.preprocess.tunnel_decap_protocols.clear();
+        assert!(invalid.validate(&npb).is_err());
+        let mut invalid = cbpf.clone();
+        invalid.special_network.libpcap.enabled = true;
+        assert!(invalid.validate(&npb).is_err());
+        let mut invalid = cbpf.clone();
+        invalid.special_network.vhost_user.vhost_socket_path = vec!["/tmp/vm1.sock".to_owned()];
+        assert!(invalid.validate(&npb).is_err());
+        let mut invalid = cbpf.clone();
+        invalid.special_network.dpdk.source = DpdkSource::PDump;
+        assert!(invalid.validate(&npb).is_err());
+        assert!(cbpf.validate(&Npb::default()).is_err());
+    }
+
+    #[test]
+    fn auto_raw_packet_buffer_block_size() {
+        let tunning = |max_capture_packet_size, raw_packet_buffer_block_size| CbpfTunning {
+            max_capture_packet_size,
+            raw_packet_buffer_block_size,
+            ..Default::default()
+        };
+        assert_eq!(CbpfTunning::default().raw_packet_buffer_block_size(), 65536);
+        assert_eq!(
+            tunning(65535, 1 << 20).raw_packet_buffer_block_size(),
+            1 << 20
+        );
+
+        // bounded by the minimum block size
+        assert_eq!(tunning(128, 0).raw_packet_buffer_block_size(), 65536);
+        // 9000 * 64 rounded up to page size
+        assert_eq!(tunning(9000, 0).raw_packet_buffer_block_size(), 141 * 4096);
+        assert_eq!(
+            tunning(65535, 0).raw_packet_buffer_block_size(),
+            1024 * 4096
+        );
+        for snaplen in [128, 1500, 9000, 65535] {
+            let size = tunning(snaplen, 0).raw_packet_buffer_block_size();
+            assert_eq!(size % 4096, 0);
+            assert!(size >= snaplen as usize * 64);
+        }
+    }
+
+    #[test]
+    fn parse_dispatcher_queue_cpu_pinning() {
+        let parse = |yaml: &str, cpu_affinity: &[usize]| {
+            let tunning = serde_yaml::from_str::<CbpfTunning>(yaml).unwrap();
+            tunning.validate(cpu_affinity).map(|_| tunning)
+        };
+        let tunning = parse(
+            "dispatcher_queue_cpu_pinning: [{queue: 0, cpu: 2}, {queue: 1, cpu: 3}]",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(tunning.dispatcher_queue_cpu(0), Some(2));
+        assert_eq!(tunning.dispatcher_queue_cpu(1), Some(3));
+        assert_eq!(tunning.dispatcher_queue_cpu(2), None);
+        assert!(parse(
+            "dispatcher_queue_cpu_pinning: [{queue: 0, cpu: 2}, {queue: 1, cpu: 3}]",
+            &[2, 3],
+        )
+        .is_ok());
+
+        // out of cpu_affinity
+        assert!(parse(
+            "dispatcher_queue_cpu_pinning: [{queue: 0, cpu: 2}, {queue: 1, cpu: 4}]",
+            &[2, 3],
+        )
+        .is_err());
+        assert!(parse("dispatcher_queue_cpu_pinning: [{queue: 0, cpu: 1024}]", &[]).is_err());
+        assert!(parse(
+            "dispatcher_queue_cpu_pinning: [{queue: 0, cpu: 2}, {queue: 0, cpu: 3}]",
+            &[],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_packet_dedup() {
+        let physical_mirror = PhysicalMirror::default();
+        assert_eq!(
+            physical_mirror.packet_dedup_config(),
+            PacketDedupConfig::default()
+        );
+        assert!(physical_mirror.validate().is_ok());
+
+        let physical_mirror = serde_yaml::from_str::<PhysicalMirror>(
+            "{packet_dedup_window: 50us, packet_dedup_table_size: 65536, packet_dedup_ignore_ttl_tos: true}",
+        )
+        .unwrap();
+        assert!(physical_mirror.validate().is_ok());
+        assert_eq!(
+            physical_mirror.packet_dedup_config(),
+            PacketDedupConfig {
+                window: Duration::from_micros(50),
+                table_size: 65536,
+                ignore_ttl_tos: true,
+            }
+        );
+
+        for yaml in [
+            "packet_dedup_window: 0s",
+            "packet_dedup_window: 1s",
+            "packet_dedup_table_size: 16",
+        ] {
+            let physical_mirror = serde_yaml::from_str::<PhysicalMirror>(yaml).unwrap();
+            assert!(physical_mirror.validate().is_err(), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn parse_geneve_options() {
+        let parse = |yaml: &str| {
+            let preprocess = serde_yaml::from_str::<PreProcess>(yaml).unwrap();
+            preprocess.validate().map(|_| preprocess)
+        };
+        let preprocess = parse("{}").unwrap();
+        assert!(preprocess.geneve_options.is_empty());
+        assert_eq!(
+            preprocess.geneve_metadata_max_bytes,
+            GENEVE_METADATA_SIZE_DEFAULT
+        );
+
+        let preprocess = parse(
+            "{geneve_options: [{class: 258, type: 128}, {class: 258, type: 1}], geneve_metadata_max_bytes: 16}",
+        )
+        .unwrap();
+        assert_eq!(
+            preprocess.geneve_options,
+            vec![
+                GeneveTlv {
+                    class: 0x0102,
+                    option_type: 0x80
+                },
+                GeneveTlv {
+                    class: 0x0102,
+                    option_type: 1
+                }
+            ]
+        );
+        assert_eq!(preprocess.geneve_metadata_max_bytes, 16);
+
+        assert!(parse("geneve_options: [{class: 1, type: 1}, {class: 1, type: 1}]").is_err());
+        assert!(parse("geneve_metadata_max_bytes: 0").is_err());
+        assert!(parse("geneve_metadata_max_bytes: 65").is_err());
+        assert!(parse(
+            "geneve_options: [{class: 1, type: 1}, {class: 1, type: 2}, {class: 1, type: 3}, {class: 1, type: 4}, {class: 1, type: 5}, {class: 1, type: 6}, {class: 1, type: 7}, {class: 1, type: 8}, {class: 1, type: 9}]"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_tls_socket_type() {
+        let socket =
+            serde_yaml::from_str::<Socket>("{data_socket_type: TLS, npb_socket_type: UDP}")
+                .unwrap();
+        assert_eq!(socket.data_socket_type, agent::SocketType::Tls);
+        assert!(serde_yaml::from_str::<Socket>("data_socket_type: SSL").is_err());
+
+        let mut config = UserConfig::default();
+        config.outputs.socket = socket;
+        config.outputs.socket.npb_socket_type = agent::SocketType::Tls;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn parse_multiple_sockets() {
+        let socket = serde_yaml::from_str::<Socket>(
+            "{multiple_sockets_to_ingester: true, sockets_per_sender: 4, multiple_sockets_balance: round_robin}",
+        )
+        .unwrap();
+        assert_eq!(socket.sockets_per_sender, 4);
+        assert_eq!(socket.multiple_sockets_balance, SocketBalance::RoundRobin);
+        assert_eq!(
+            serde_yaml::from_str::<Socket>("{}")
+                .unwrap()
+                .multiple_sockets_balance,
+            SocketBalance::Hash
+        );
+        assert!(serde_yaml::from_str::<Socket>("multiple_sockets_balance: RANDOM").is_err());
+
+        let sockets_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("sockets_per_sender"));
+        let mut config = UserConfig::default();
+        config.outputs.socket = socket;
+        assert!(!sockets_error(&config));
+        config.outputs.socket.sockets_per_sender = 0;
+        assert!(sockets_error(&config));
+        config.outputs.socket.sockets_per_sender = Socket::MAX_SOCKETS_PER_SENDER + 1;
+        assert!(sockets_error(&config));
+    }
+
+    #[test]
+    fn parse_data_compression() {
+        let socket =
+            serde_yaml::from_str::<Socket>("{data_compression: zstd, data_compression_level: 9}")
+                .unwrap();
+        assert_eq!(socket.data_compression, DataCompression::Zstd);
+        assert_eq!(socket.data_compression_level, 9);
+        assert_eq!(socket.effective_data_compression(), DataCompression::Zstd);
+        assert!(serde_yaml::from_str::<Socket>("data_compression: gzip").is_err());
+
+        let socket =
+            serde_yaml::from_str::<Socket>("{data_socket_type: UDP, data_compression: LZ4}")
+                .unwrap();
+        assert_eq!(socket.data_compression, DataCompression::Lz4);
+        assert_eq!(socket.effective_data_compression(), DataCompression::None);
+
+        let level_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("data_compression_level"));
+        let mut config = UserConfig::default();
+        config.outputs.socket.data_compression_level = Socket::MAX_ZSTD_LEVEL;
+        assert!(!level_error(&config));
+        config.outputs.socket.data_compression_level = -1;
+        assert!(level_error(&config));
+    }
+
+    #[test]
+    fn parse_l7_throttle_overrides() {
+        let throttles = serde_yaml::from_str::<Throttles>(
+            "{l7_throttle: 5000, l7_throttle_overrides: [{protocol: DNS, throttle: 1000}]}",
+        )
+        .unwrap();
+        assert_eq!(
+            throttles.l7_throttle_overrides,
+            vec![ProtocolThrottle {
+                protocol: L7Protocol::DNS,
+                throttle: 1000,
+            }]
+        );
+
+        let override_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("l7_throttle_overrides"));
+        let mut config = UserConfig::default();
+        config.outputs.flow_log.throttles = throttles;
+        assert!(!override_error(&config));
+        config.outputs.flow_log.throttles.l7_throttle_overrides[0].throttle = 5001;
+        assert!(override_error(&config));
+
+        // l4 and l7 throttles share the same range
+        let range_error = |c: &UserConfig, name: &str| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains(name));
+        let mut config = UserConfig::default();
+        config.outputs.flow_log.throttles.l4_throttle = Throttles::RANGE.1;
+        assert!(!range_error(&config, "l4_throttle"));
+        config.outputs.flow_log.throttles.l4_throttle = Throttles::RANGE.0 - 1;
+        assert!(range_error(&config, "l4_throttle"));
+        config.outputs.flow_log.throttles.l4_throttle = Throttles::RANGE.0;
+        config.outputs.flow_log.throttles.l7_throttle = Throttles::RANGE.1 + 1;
+        assert!(range_error(&config, "l7_throttle"));
+    }
+
+    #[test]
+    fn parse_npb_underlay() {
+        let npb = serde_yaml::from_str::<Npb>("{max_mtu: 1280, underlay_hop_limit: 255}").unwrap();
+        assert_eq!(npb.underlay_hop_limit, 255);
+
+        let npb_error = |c: &UserConfig, name: &str| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains(name));
+        let mut config = UserConfig::default();
+        config.outputs.npb = npb;
+        assert!(!npb_error(&config, "MTU"));
+        config.outputs.npb.max_mtu = Npb::MIN_MTU - 1;
+        assert!(npb_error(&config, "MTU"));
+        config.outputs.npb.max_mtu = Npb::MIN_MTU;
+        assert!(!npb_error(&config, "MTU"));
+        config.outputs.npb.underlay_hop_limit = 0;
+        assert!(npb_error(&config, "underlay_hop_limit"));
+    }
+
+    #[test]
+    fn parse_npb_vxlan_encapsulation() {
+        let npb = serde_yaml::from_str::<Npb>("{}").unwrap();
+        assert_eq!(npb.vxlan_encapsulation, VxlanEncapsulation::Vxlan);
+        assert_eq!(npb.target_port(), 4789);
+        let npb = serde_yaml::from_str::<Npb>("{vxlan_encapsulation: vxlan-gpe}").unwrap();
+        assert_eq!(npb.vxlan_encapsulation, VxlanEncapsulation::VxlanGpe);
+        assert_eq!(npb.target_port(), 4790);
+        let npb =
+            serde_yaml::from_str::<Npb>("{vxlan_encapsulation: VXLAN-GPE, target_port: 4789}")
+                .unwrap();
+        assert_eq!(npb.target_port(), 4789);
+        assert!(serde_yaml::from_str::<Npb>("{vxlan_encapsulation: geneve}").is_err());
+
+        let flags_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("custom_vxlan_flags"));
+        let mut config = UserConfig::default();
+        config.outputs.npb.custom_vxlan_flags = 0b0000_1000;
+        assert!(!flags_error(&config));
+        config.outputs.npb.vxlan_encapsulation = VxlanEncapsulation::VxlanGpe;
+        assert!(flags_error(&config));
+        config.outputs.npb.custom_vxlan_flags = 0b1111_1111;
+        assert!(!flags_error(&config));
+        config.outputs.npb.custom_vxlan_flags = 0b0000_1100;
+        assert!(!flags_error(&config));
+    }
+
+    #[test]
+    fn parse_tx_throughput() {
+        let tx_throughput = serde_yaml::from_str::<TxThroughput>(
+            "{trigger_threshold: 100, recovery_threshold: 80, min_hold_time: 30s, over_limit_action: SAMPLE}",
+        )
+        .unwrap();
+        assert_eq!(tx_throughput.trigger_threshold, 100 << 20);
+        assert_eq!(tx_throughput.recovery_threshold, 80 << 20);
+        assert_eq!(tx_throughput.min_hold_time, Duration::from_secs(30));
+        assert_eq!(tx_throughput.over_limit_action, TxThroughputAction::Sample);
+        assert!(serde_yaml::from_str::<TxThroughput>("{over_limit_action: PAUSE}").is_err());
+
+        let threshold_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("tx_throughput"));
+        let mut config = UserConfig::default();
+        config.global.circuit_breakers.tx_throughput = tx_throughput;
+        assert!(!threshold_error(&config));
+        config
+            .global
+            .circuit_breakers
+            .tx_throughput
+            .recovery_threshold = 101 << 20;
+        assert!(threshold_error(&config));
+        config
+            .global
+            .circuit_breakers
+            .tx_throughput
+            .recovery_threshold = 1 << 19;
+        assert!(threshold_error(&config));
+        config
+            .global
+            .circuit_breakers
+            .tx_throughput
+            .recovery_threshold = 0;
+        assert!(!threshold_error(&config));
+        config
+            .global
+            .circuit_breakers
+            .tx_throughput
+            .trigger_threshold = 1 << 19;
+        assert!(threshold_error(&config));
+        // 0 disables the limiter
+        config
+            .global
+            .circuit_breakers
+            .tx_throughput
+            .trigger_threshold = 0;
+        assert!(!threshold_error(&config));
+    }
+
+    #[test]
+    fn validate_ingester_reconnect_backoff() {
+        let backoff_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("ingester_reconnect_backoff"));
+        let mut config = UserConfig::default();
+        assert!(!backoff_error(&config));
+        config.global.communication.ingester_reconnect_backoff_base = Duration::from_millis(50);
+        assert!(backoff_error(&config));
+        config.global.communication.ingester_reconnect_backoff_base = Duration::from_secs(10);
+        config.global.communication.ingester_reconnect_backoff_max = Duration::from_secs(5);
+        assert!(backoff_error(&config));
+        config.global.communication.ingester_reconnect_backoff_max = Duration::from_secs(1800);
+        assert!(!backoff_error(&config));
+        // reconnection is not postponed beyond max_escape_duration
+        config.global.communication.max_escape_duration = Duration::from_secs(600);
+        assert!(backoff_error(&config));
+    }
+
+    #[test]
+    fn validate_ingester_failover() {
+        let failover_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("ingester_failover") || e.contains("ingester_failback"));
+        let mut config = UserConfig::default();
+        assert!(!failover_error(&config));
+        config.global.communication.ingester_ip = "10.1.1.1".to_string();
+        config.global.communication.ingester_failover_ips =
+            vec!["10.1.1.2".to_string(), "10.1.1.3".to_string()];
+        assert!(!failover_error(&config));
+        config.global.communication.ingester_failover_ips =
+            vec!["10.1.1.2".to_string(), "10.1.1.1".to_string()];
+        assert!(failover_error(&config));
+        config.global.communication.ingester_failover_ips =
+            vec!["10.1.1.2".to_string(), "10.1.1.2".to_string()];
+        assert!(failover_error(&config));
+        config.global.communication.ingester_failover_ips = vec!["".to_string()];
+        assert!(failover_error(&config));
+        config.global.communication.ingester_failover_ips = vec!["10.1.1.2".to_string()];
+        config.global.communication.ingester_failover_threshold = 0;
+        assert!(failover_error(&config));
+        config.global.communication.ingester_failover_threshold = 5;
+        config.global.communication.ingester_failback_hold_down = Duration::from_secs(1);
+        assert!(failover_error(&config));
+        config.global.communication.ingester_failback_hold_down = Duration::from_secs(300);
+        assert!(!failover_error(&config));
+    }
+
+    #[test]
+    fn validate_l4_observation_point_sampling_ratios() {
+        let filters = serde_yaml::from_str::<FlowLogFilters>(
+            "l4_observation_point_sampling_ratios: [{observation_point: 0, ratio: 0.1}, {observation_point: 1}]",
+        )
+        .unwrap();
+        assert_eq!(
+            filters.l4_observation_point_sampling_ratios,
+            vec![
+                ObservationPointSampling {
+                    observation_point: 0,
+                    ratio: 0.1,
+                },
+                ObservationPointSampling {
+                    observation_point: 1,
+                    ratio: 1.0,
+                },
+            ]
+        );
+
+        let ratio_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("l4_observation_point_sampling_ratios"));
+        let mut config = UserConfig::default();
+        config.outputs.flow_log.filters = filters;
+        assert!(!ratio_error(&config));
+        for (observation_point, ratio) in [(0, 0.0), (0, 1.5), (0, f32::NAN), (3, 0.5), (256, 0.5)]
+        {
+            config
+                .outputs
+                .flow_log
+                .filters
+                .l4_observation_point_sampling_ratios = vec![ObservationPointSampling {
+                observation_point,
+                ratio,
+            }];
+            assert!(ratio_error(&config));
+        }
+    }
+
+    #[test]
+    fn validate_outputs_socket_tunning() {
+        let tunning = serde_yaml::from_str::<OutputsSocketTunning>(
+            "{batch_max_bytes: 65536, batch_max_documents: 1000, linger: 500ms}",
+        )
+        .unwrap();
+        assert_eq!(tunning.batch_max_bytes, 65536);
+        assert_eq!(tunning.batch_max_documents, 1000);
+        assert_eq!(tunning.linger, Duration::from_millis(500));
+        // the defaults keep the batches of previous versions
+        let tunning = serde_yaml::from_str::<OutputsSocketTunning>("{}").unwrap();
+        assert_eq!(tunning.batch_max_bytes, 256 << 10);
+        assert_eq!(tunning.batch_max_documents, 0);
+        assert_eq!(tunning.linger, Duration::from_secs(10));
+
+        let tunning_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("batch_max_") || e.contains("linger"));
+        let mut config = UserConfig::default();
+        assert!(!tunning_error(&config));
+        config.outputs.socket.tunning.batch_max_bytes = 1024;
+        assert!(tunning_error(&config));
+        config.outputs.socket.tunning.batch_max_bytes = 16 << 20;
+        assert!(tunning_error(&config));
+        config.outputs.socket.tunning.batch_max_bytes = 1 << 20;
+        config.outputs.socket.tunning.batch_max_documents =
+            OutputsSocketTunning::BATCH_MAX_DOCUMENTS_MAX + 1;
+        assert!(tunning_error(&config));
+        config.outputs.socket.tunning.batch_max_documents = 100;
+        config.outputs.socket.tunning.linger = Duration::from_millis(10);
+        assert!(tunning_error(&config));
+        config.outputs.socket.tunning.linger = Duration::from_secs(120);
+        assert!(tunning_error(&config));
+        config.outputs.socket.tunning.linger = Duration::from_secs(1);
+        assert!(!tunning_error(&config));
+    }
+
+    #[test]
+    fn validate_outputs_kafka() {
+        let kafka = serde_yaml::from_str::<OutputsKafka>(
+            "{enabled: true, brokers: [10.1.1.1:9092], serialization: protobuf, sasl_mechanism: scram-sha-512, sasl_username: deepflow, sasl_password: secret}",
+        )
+        .unwrap();
+        assert_eq!(kafka.topic, "deepflow_l7_flow_log");
+        assert_eq!(kafka.serialization, KafkaSerialization::Protobuf);
+        assert_eq!(kafka.sasl_mechanism, KafkaSaslMechanism::ScramSha512);
+        assert_eq!(kafka.sasl_mechanism.as_str(), Some("SCRAM-SHA-512"));
+        assert!(!format!("{:?}", kafka).contains("secret"));
+        assert!(serde_yaml::from_str::<OutputsKafka>("serialization: AVRO").is_err());
+        assert!(serde_yaml::from_str::<OutputsKafka>("sasl_mechanism: GSSAPI").is_err());
+
+        let kafka_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("kafka"));
+        let mut config = UserConfig::default();
+        // not checked when disabled
+        config.outputs.kafka.brokers = vec![];
+        assert!(!kafka_error(&config));
+        config.outputs.kafka = kafka;
+        assert!(!kafka_error(&config));
+        config.outputs.kafka.brokers = vec!["".to_string()];
+        assert!(kafka_error(&config));
+        config.outputs.kafka.brokers = vec!["10.1.1.1:9092".to_string()];
+        config.outputs.kafka.topic = String::new();
+        assert!(kafka_error(&config));
+        config.outputs.kafka.topic = "l7".to_string();
+        config.outputs.kafka.buffer_size = 16;
+        assert!(kafka_error(&config));
+        config.outputs.kafka.buffer_size = 4096;
+        config.outputs.kafka.sasl_username = String::new();
+        assert!(kafka_error(&config));
+        config.outputs.kafka.sasl_mechanism = KafkaSaslMechanism::None;
+        assert!(!kafka_error(&config));
+    }
+
+    #[test]
+    fn validate_second_metrics_shedding() {
+        let shedding_error = |f: fn(&mut FlowMetricsTunning)| {
+            let mut config = UserConfig::default();
+            f(&mut config.outputs.flow_metrics.tunning);
+            matches!(config.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("second_metrics_shedding"))
+        };
+        assert!(!shedding_error(|_| {}));
+        assert!(shedding_error(|t| {
+            t.second_metrics_shedding_high_watermark = 0;
+        }));
+        assert!(shedding_error(|t| {
+            t.second_metrics_shedding_high_watermark = 101;
+        }));
+        assert!(shedding_error(|t| {
+            t.second_metrics_shedding_high_watermark = 50;
+        }));
+        assert!(!shedding_error(|t| {
+            t.second_metrics_shedding_high_watermark = 50;
+            t.second_metrics_shedding_low_watermark = 0;
+        }));
+        assert!(shedding_error(|t| t.second_metrics_shedding_intervals = 0));
+        assert!(shedding_error(|t| t.second_metrics_shedding_intervals = 61));
+    }
+
+    #[test]
+    fn validate_outputs_otlp() {
+        let otlp = serde_yaml::from_str::<OutputsOtlp>(
+            "{enabled: true, endpoint: http://tempo:4317, batch_timeout: 500ms, retry_backoff: 2s}",
+        )
+        .unwrap();
+        assert_eq!(otlp.batch_size, 512);
+        assert_eq!(otlp.batch_timeout, Duration::from_millis(500));
+        assert_eq!(otlp.retry_backoff, Duration::from_secs(2));
+        assert!(!OutputsOtlp::default().enabled);
+
+        let otlp_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("otlp"));
+        let mut config = UserConfig::default();
+        // not checked when disabled
+        assert!(!otlp_error(&config));
+        config.outputs.otlp = otlp;
+        assert!(!otlp_error(&config));
+        config.outputs.otlp.endpoint = "tempo:4317".to_string();
+        assert!(otlp_error(&config));
+        config.outputs.otlp.endpoint = "https://tempo:4317".to_string();
+        assert!(otlp_error(&config));
+        config.outputs.otlp.endpoint = "http://10.1.1.1:4317".to_string();
+        config.outputs.otlp.batch_size = 0;
+        assert!(otlp_error(&config));
+        config.outputs.otlp.batch_size = 1024;
+        config.outputs.otlp.export_timeout = Duration::from_millis(10);
+        assert!(otlp_error(&config));
+        config.outputs.otlp.export_timeout = Duration::from_secs(5);
+        config.outputs.otlp.max_retries = 100;
+        assert!(otlp_error(&config));
+        config.outputs.otlp.max_retries = 0;
+        assert!(!otlp_error(&config));
+    }
+
+    #[test]
+    fn validate_standalone_mode() {
+        let standalone_mode = serde_yaml::from_str::<StandaloneMode>(
+            "{max_data_file_size: 100, data_file_rotation_interval: 1h, max_data_files: 24}",
+        )
+        .unwrap();
+        assert_eq!(standalone_mode.max_data_file_size, 100 << 20);
+        assert_eq!(
+            standalone_mode.data_file_rotation_interval,
+            Duration::from_secs(3600)
+        );
+        assert_eq!(standalone_mode.max_data_files, 24);
+        // by size only and one file retained as before
+        let standalone_mode = StandaloneMode::default();
+        assert!(standalone_mode.data_file_rotation_interval.is_zero());
+        assert_eq!(standalone_mode.max_data_files, 1);
+
+        let standalone_error = |c: &UserConfig| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("data_file"));
+        let mut config = UserConfig::default();
+        assert!(!standalone_error(&config));
+        config.global.standalone_mode.data_file_rotation_interval = Duration::from_secs(1);
+        assert!(standalone_error(&config));
+        config.global.standalone_mode.data_file_rotation_interval = Duration::from_secs(30 * 86400);
+        assert!(standalone_error(&config));
+        config.global.standalone_mode.data_file_rotation_interval = Duration::from_secs(600);
+        config.global.standalone_mode.max_data_files = 0;
+        assert!(standalone_error(&config));
+        config.global.standalone_mode.max_data_files = 10;
+        assert!(!standalone_error(&config));
+    }
+
+    #[test]
+    fn validate_integration_listen() {
+        let integration_error = |c: &UserConfig, value: &str| matches!(c.validate(), Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains(value));
+        let mut config = UserConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.inputs.integration.listen_address(), None);
+
+        config.inputs.integration.listen_address = "10.1.2.300".to_string();
+        assert!(integration_error(&config, "listen_address(10.1.2.300)"));
+        config.inputs.integration.listen_address = "10.1.2.3".to_string();
+        assert!(config.validate().is_ok());
+        config.inputs.integration.listen_address = "fd00::1".to_string();
+        assert!(config.validate().is_ok());
+
+        config.inputs.integration.listen_port = 0;
+        assert!(integration_error(&config, "listen_port(0)"));
+        config.inputs.integration.listen_unix_socket_path = "run/otel.sock".to_string();
+        assert!(integration_error(
+            &config,
+            "listen_unix_socket_path(run/otel.sock)"
+        ));
+        config.inputs.integration.listen_unix_socket_path = "/run/otel.sock".to_string();
+        assert_eq!(config.validate().is_ok(), cfg!(unix));
+
+        let mut config = UserConfig::default();
+        config.inputs.integration.otlp_grpc_listen_port = config.inputs.integration.listen_port;
+        assert!(integration_error(&config, "otlp_grpc_listen_port(38086)"));
+        config.inputs.integration.otlp_grpc_listen_port = 4317;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_prometheus_extra_labels_rewrite_rules() {
+        let mut config = UserConfig::default();
+        let extra_labels = &mut config.inputs.integration.prometheus_extra_labels;
+        extra_labels.extra_labels = vec!["X-Pod-Name".to_string()];
+        extra_labels.rewrite_rules = vec![PrometheusLabelRewriteRule {
+            label: "x-pod-name".to_string(),
+            regex: "^(.+)-[0-9a-f]+$".to_string(),
+            replacement: "$1".to_string(),
+            ..Default::default()
+        }];
+        assert!(config.validate().is_ok());
+
+        let extra_labels = &mut config.inputs.integration.prometheus_extra_labels;
+        extra_labels.rewrite_rules[0].regex = "^(.+".to_string();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("label(x-pod-name) has invalid regex")
+        ));
+
+        let extra_labels = &mut config.inputs.integration.prometheus_extra_labels;
+        extra_labels.rewrite_rules[0].label = "x-build".to_string();
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("label(x-build) not in extra_labels")
+        ));
+    }
+
+    #[test]
+    fn validate_integration_statsd() {
+        let mut config = UserConfig::default();
+        assert!(!config.inputs.integration.statsd.enabled());
+        config.inputs.integration.statsd.listen_port = 8125;
+        assert!(config.validate().is_ok());
+
+        config.inputs.integration.statsd.flush_interval = Duration::from_millis(500);
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("statsd.flush_interval(500ms)")
+        ));
+        config.inputs.integration.statsd.flush_interval = Duration::from_secs(10);
+
+        config.inputs.integration.statsd.listen_unix_socket_path = "run/statsd.sock".to_string();
+        assert!(config.validate().is_err());
+        config.inputs.integration.statsd.listen_unix_socket_path = "/run/statsd.sock".to_string();
+        assert_eq!(config.validate().is_ok(), cfg!(unix));
+    }
+
+    #[test]
+    fn validate_integration_max_decompressed_size() {
+        let compression = serde_yaml::from_str::<Compression>("max_decompressed_size: 16").unwrap();
+        assert_eq!(compression.max_decompressed_size, 16 << 20);
+        assert!(compression.trace && compression.profile);
+
+        let mut config = UserConfig::default();
+        config.inputs.integration.compression.max_decompressed_size = 2 << 30;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("max_decompressed_size(2048MiB)")
+        ));
+        config.inputs.integration.compression.max_decompressed_size = 1 << 30;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_integration_source_filter() {
+        let mut config = UserConfig::default();
+        let source_filter = &mut config.inputs.integration.source_filter;
+        source_filter.trace.allow = vec!["10.0.0.0/8".to_string(), "2001:db8::/32".to_string()];
+        source_filter.trace.deny = vec![" 10.1.2.3 ".to_string(), "::1".to_string()];
+        assert!(config.validate().is_ok());
+        let source_filter = &config.inputs.integration.source_filter;
+        assert_eq!(
+            source_filter.trace.deny_networks().unwrap(),
+            vec![
+                "10.1.2.3/32".parse::<IpNet>().unwrap(),
+                "::1/128".parse::<IpNet>().unwrap()
+            ]
+        );
+
+        config.inputs.integration.source_filter.log.deny = vec!["10.0.0.0/33".to_string()];
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::RuntimeConfigInvalid(e)) if e.contains("source_filter.log.deny invalid CIDR(10.0.0.0/33)")
+        ));
+    }
 }
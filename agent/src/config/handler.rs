@@ -26,7 +26,10 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::PathBuf,
     str,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
     time::Duration,
 };
 
@@ -49,21 +52,25 @@ use nix::{
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use procfs::{process::Process, ProcError};
+use regex::Regex;
 use sysinfo::SystemExt;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use sysinfo::{CpuRefreshKind, RefreshKind, System};
 use tokio::runtime::Runtime;
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
-use super::config::{Ebpf, EbpfFileIoEvent, ProcessMatcher, SymbolTable};
+use super::config::{Ebpf, EbpfFileIoEvent, ProcessMatcher, RelativeSysLoad, SymbolTable};
 use super::{
     config::{
-        ApiResources, Config, DpdkSource, ExtraLogFields, ExtraLogFieldsInfo, HttpEndpoint,
-        HttpEndpointMatchRule, Iso8583ParseConfig, NetSignParseConfig, OracleConfig, PcapStream,
-        PortConfig, ProcessorsFlowLogTunning, RequestLogTunning, SessionTimeout, TagFilterOperator,
-        Timeouts, UserConfig, WebSphereMqParseConfig, GRPC_BUFFER_SIZE_MIN,
+        ApiResources, Config, CustomTextConfig, DpdkSource, DubboConfig, ElasticsearchParseConfig,
+        ExtraLogFields, ExtraLogFieldsInfo, Http2Config, HttpEndpoint, HttpEndpointMatchRule,
+        HttpEndpointRewriteRule, HttpQueryObfuscation, Iso8583ParseConfig, MongodbConfig,
+        MysqlConfig, NetSignParseConfig, OracleConfig, PcapStream, PortConfig, PortPrefilterMode,
+        ProcessorsFlowLogTunning, RequestLogTunning, SessionTimeout, TagFilterOperator, Timeouts,
+        UserConfig, WebSphereMqParseConfig, XRequestIdHeader, GRPC_BUFFER_SIZE_MIN,
     },
-    ConfigError, KubernetesPollerType, TrafficOverflowAction,
+    ConfigError, KubernetesPollerType, SocketBalance, SourceFilter, TrafficOverflowAction,
+    TxThroughputAction,
 };
 use crate::config::InferenceWhitelist;
 use crate::flow_generator::protocol_logs::decode_new_rpc_trace_context_with_type;
@@ -72,8 +79,10 @@ use crate::rpc::Session;
 use crate::utils::environment::{get_ctrl_ip_and_mac, is_tt_workload};
 use crate::{
     common::{
-        decapsulate::TunnelTypeBitmap, enums::CaptureNetworkType,
-        l7_protocol_log::L7ProtocolBitmap, Timestamp, DEFAULT_LOG_UNCOMPRESSED_FILE_COUNT,
+        decapsulate::{GeneveOptionSelector, TunnelTypeBitmap},
+        enums::CaptureNetworkType,
+        l7_protocol_log::L7ProtocolBitmap,
+        Timestamp, DEFAULT_LOG_UNCOMPRESSED_FILE_COUNT,
     },
     exception::ExceptionHandler,
     flow_generator::{protocol_logs::SOFA_NEW_RPC_TRACE_CTX_KEY, FlowTimeout, TcpTimeout},
@@ -156,7 +165,7 @@ pub type MetricServerAccess = Access<MetricServerConfig>;
 
 pub type PortAccess = Access<PortConfig>;
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq)]
 pub struct CollectorConfig {
     pub enabled: bool,
     pub inactive_server_port_aggregation: bool,
@@ -165,6 +174,8 @@ pub struct CollectorConfig {
     pub l4_log_collect_nps_threshold: u64,
     pub l4_log_store_tap_types: [bool; 256],
     pub l4_log_ignore_tap_sides: [bool; TapSide::MAX as usize + 1],
+    // ratio of l4 flow logs kept for each observation point
+    pub l4_log_sampling_ratios: [f32; TapSide::MAX as usize + 1],
     pub aggregate_health_check_l4_flow_log: bool,
     pub l7_metrics_enabled: bool,
     pub agent_type: AgentType,
@@ -172,6 +183,10 @@ pub struct CollectorConfig {
     pub cloud_gateway_traffic: bool,
     pub packet_delay: Duration,
     pub npm_metrics_concurrent: bool,
+    pub second_metrics_shedding_enabled: bool,
+    pub second_metrics_shedding_high_watermark: u8,
+    pub second_metrics_shedding_low_watermark: u8,
+    pub second_metrics_shedding_intervals: u32,
 }
 
 impl fmt::Debug for CollectorConfig {
@@ -208,6 +223,21 @@ impl fmt::Debug for CollectorConfig {
                     })
                     .collect::<Vec<_>>(),
             )
+            .field(
+                "l4_log_sampling_ratios",
+                &self
+                    .l4_log_sampling_ratios
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, r)| {
+                        if *r < 1.0 {
+                            TapSide::try_from(i as u8).ok().map(|t| (t, *r))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            )
             .field(
                 "aggregate_health_check_l4_flow_log",
                 &self.aggregate_health_check_l4_flow_log,
@@ -222,6 +252,22 @@ impl fmt::Debug for CollectorConfig {
             .field("cloud_gateway_traffic", &self.cloud_gateway_traffic)
             .field("packet_delay", &self.packet_delay)
             .field("npm_metrics_concurrent", &self.npm_metrics_concurrent)
+            .field(
+                "second_metrics_shedding_enabled",
+                &self.second_metrics_shedding_enabled,
+            )
+            .field(
+                "second_metrics_shedding_high_watermark",
+                &self.second_metrics_shedding_high_watermark,
+            )
+            .field(
+                "second_metrics_shedding_low_watermark",
+                &self.second_metrics_shedding_low_watermark,
+            )
+            .field(
+                "second_metrics_shedding_intervals",
+                &self.second_metrics_shedding_intervals,
+            )
             .finish()
     }
 }
@@ -265,16 +311,42 @@ pub struct SenderConfig {
     pub npb_bps_threshold: u64,
     pub npb_socket_type: agent::SocketType,
     pub multiple_sockets_to_ingester: bool,
+    pub sockets_per_sender: usize,
+    pub multiple_sockets_balance: SocketBalance,
     pub max_throughput_to_ingester: u64, // unit: Mbps
     pub ingester_traffic_overflow_action: TrafficOverflowAction,
+    pub reconnect_backoff_base: Duration,
+    pub reconnect_backoff_max: Duration,
+    // backup ingesters in priority order, dest_ip is the primary
+    pub failover_ips: Vec<String>,
+    pub failover_threshold: u32,
+    pub failback_hold_down: Duration,
+    pub batch_max_bytes: usize,
+    pub batch_max_documents: usize,
+    pub linger: Duration,
     pub collector_socket_type: agent::SocketType,
+    // set if collector_socket_type is TLS
+    pub ingester_tls: Option<IngesterTlsConfig>,
     pub standalone_data_file_size: u64,
     pub standalone_data_file_dir: String,
+    pub standalone_data_file_rotation_interval: Duration,
+    pub standalone_max_data_files: usize,
     pub server_tx_bandwidth_threshold: u64,
+    pub server_tx_bandwidth_recovery_threshold: u64,
+    pub server_tx_bandwidth_min_hold_time: Duration,
+    pub server_tx_bandwidth_over_limit_action: TxThroughputAction,
     pub bandwidth_probe_interval: Duration,
     pub enabled: bool,
 }
 
+// Server verification of the TLS data socket to the ingester
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IngesterTlsConfig {
+    // PEM files of the trusted CA certificates
+    pub ca_files: Vec<String>,
+    pub server_name: String,
+}
+
 impl Default for SenderConfig {
     fn default() -> Self {
         let module_config = ModuleConfig::default();
@@ -284,7 +356,6 @@ impl Default for SenderConfig {
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct NpbConfig {
-    pub underlay_is_ipv6: bool,
     pub vxlan_flags: u8,
     pub npb_port: u16,
     pub dedup_enabled: bool,
@@ -295,6 +366,8 @@ pub struct NpbConfig {
     pub socket_type: agent::SocketType,
     pub ignore_overlay_vlan: bool,
     pub queue_size: usize,
+    pub hop_limit: u8,
+    pub vxlan_encapsulation: VxlanEncapsulation,
 }
 
 impl Default for NpbConfig {
@@ -353,6 +426,7 @@ pub struct HandlerConfig {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct DispatcherConfig {
     pub global_pps_threshold: u64,
+    pub global_pps_burst: u64,
     pub capture_packet_size: u32,
     pub l7_log_packet_size: u32,
     pub tunnel_type_bitmap: TunnelTypeBitmap,
@@ -363,6 +437,7 @@ pub struct DispatcherConfig {
     #[cfg(target_os = "linux")]
     pub extra_netns_regex: String,
     pub tap_interface_regex: String,
+    pub tap_interface_exclude_regex: String,
     pub inner_interface_capture_enabled: bool,
     pub inner_tap_interface_regex: String,
     pub if_mac_source: agent::IfMacSource,
@@ -512,13 +587,14 @@ pub struct FlowConfig {
     pub l7_protocol_inference_whitelist: Vec<InferenceWhitelist>,
 
     // Enterprise Edition Feature: packet-sequence
-    pub packet_sequence_flag: u8,
+    pub packet_sequence_flag: u16,
     pub packet_sequence_block_size: usize,
 
     pub l7_protocol_enabled_bitmap: L7ProtocolBitmap,
 
     // vec<protocolName, port bitmap>
     pub l7_protocol_parse_port_bitmap: Arc<Vec<(String, Bitmap)>>,
+    pub l7_protocol_port_prefilter_mode: PortPrefilterMode,
 
     pub plugins: PluginConfig,
 
@@ -671,7 +747,7 @@ impl From<&UserConfig> for FlowConfig {
                 .application_protocol_inference
                 .inference_whitelist
                 .clone(),
-            packet_sequence_flag: conf.processors.packet.tcp_header.header_fields_flag, // Enterprise Edition Feature: packet-sequence
+            packet_sequence_flag: conf.processors.packet.tcp_header.header_fields.flag(), // Enterprise Edition Feature: packet-sequence
             packet_sequence_block_size: conf.processors.packet.tcp_header.block_size, // Enterprise Edition Feature: packet-sequence
             l7_protocol_enabled_bitmap: L7ProtocolBitmap::from(
                 conf.processors
@@ -681,6 +757,11 @@ impl From<&UserConfig> for FlowConfig {
                     .as_slice(),
             ),
             l7_protocol_parse_port_bitmap: Arc::new(conf.get_protocol_port_parse_bitmap()),
+            l7_protocol_port_prefilter_mode: conf
+                .processors
+                .request_log
+                .filters
+                .port_prefilter_mode,
             plugins: PluginConfig {
                 last_updated: conf.plugins.update_time.as_secs() as u32,
                 digest: {
@@ -885,6 +966,10 @@ impl fmt::Debug for FlowConfig {
             )
             // FIXME: this field is too long to log
             // .field("l7_protocol_parse_port_bitmap", &self.l7_protocol_parse_port_bitmap)
+            .field(
+                "l7_protocol_port_prefilter_mode",
+                &self.l7_protocol_port_prefilter_mode,
+            )
             .field("plugins", &self.plugins)
             .field("server_ports", &self.server_ports)
             .field(
@@ -910,18 +995,53 @@ impl TrieNode {
     }
 }
 
+#[derive(Clone)]
+struct EndpointRewriteRule {
+    regex: Regex,
+    replacement: String,
+}
+
+impl PartialEq for EndpointRewriteRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str() && self.replacement == other.replacement
+    }
+}
+
+impl Eq for EndpointRewriteRule {}
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct HttpEndpointTrie {
     root: TrieNode,
+    // checked in order before the prefix rules
+    rewrite_rules: Vec<EndpointRewriteRule>,
 }
 
 impl HttpEndpointTrie {
     pub fn new() -> Self {
         Self {
             root: TrieNode::new(),
+            rewrite_rules: vec![],
+        }
+    }
+
+    pub fn insert_rewrite_rule(&mut self, rule: &HttpEndpointRewriteRule) {
+        match rule.compile() {
+            Ok(regex) => self.rewrite_rules.push(EndpointRewriteRule {
+                regex,
+                replacement: rule.replacement.clone(),
+            }),
+            Err(e) => warn!("Invalid http endpoint rewrite rule {rule:?}: {e}"),
         }
     }
 
+    // the first matching rewrite rule wins
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        self.rewrite_rules
+            .iter()
+            .find(|r| r.regex.is_match(path))
+            .map(|r| r.regex.replace_all(path, &r.replacement).into_owned())
+    }
+
     pub fn insert(&mut self, rule: &HttpEndpointMatchRule) {
         let mut node = &mut self.root;
         for ch in rule.url_prefix.chars() {
@@ -961,6 +1081,9 @@ impl HttpEndpointTrie {
 impl From<&HttpEndpoint> for HttpEndpointTrie {
     fn from(v: &HttpEndpoint) -> Self {
         let mut t = Self::new();
+        v.rewrite_rules
+            .iter()
+            .for_each(|r| t.insert_rewrite_rule(r));
         v.match_rules
             .iter()
             .filter(|r| r.keep_segments > 0)
@@ -975,10 +1098,84 @@ enum Operator {
     Prefix,
 }
 
+#[derive(Debug, Default)]
+pub struct RuleHitCounter {
+    hits: AtomicU64,
+}
+
+impl RuleHitCounter {
+    fn hit(&self) -> bool {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+// counters are runtime states and do not take part in config comparison
+impl PartialEq for RuleHitCounter {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for RuleHitCounter {}
+
+impl stats::RefCountable for RuleHitCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![(
+            "hits",
+            stats::CounterType::Counted,
+            stats::CounterValue::Unsigned(self.hits.swap(0, Ordering::Relaxed)),
+        )]
+    }
+}
+
+pub struct TagFilterModule {
+    protocol: L7Protocol,
+    rule: TagFilterOperator,
+}
+
+impl stats::Module for TagFilterModule {
+    fn name(&self) -> &'static str {
+        "l7_log_tag_filter"
+    }
+
+    fn tags(&self) -> Vec<stats::StatsOption> {
+        vec![
+            stats::StatsOption::Tag("protocol", format!("{:?}", self.protocol)),
+            stats::StatsOption::Tag("field_name", self.rule.field_name.clone()),
+            stats::StatsOption::Tag("operator", self.rule.operator.clone()),
+            stats::StatsOption::Tag("value", self.rule.value.clone()),
+        ]
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RegexRule {
+    regex: Regex,
+    negated: bool,
+    counter: Arc<RuleHitCounter>,
+}
+
+impl PartialEq for RegexRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str() && self.negated == other.negated
+    }
+}
+
+impl Eq for RegexRule {}
+
+impl RegexRule {
+    fn is_match(&self, input: &str) -> bool {
+        self.regex.is_match(input) != self.negated && self.counter.hit()
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct BlacklistTrieNode {
     children: HashMap<char, Box<BlacklistTrieNode>>,
-    operator: Option<Operator>,
+    operator: Option<(Operator, Arc<RuleHitCounter>)>,
+    // only used in the root node, checked after the trie
+    regexes: Vec<RegexRule>,
 }
 
 impl BlacklistTrieNode {
@@ -986,36 +1183,100 @@ impl BlacklistTrieNode {
         if input.is_empty() {
             return false;
         }
+        self.trie_match(input) || self.regexes.iter().any(|r| r.is_match(input))
+    }
+
+    fn trie_match(&self, input: &str) -> bool {
+        if self.children.is_empty() {
+            return false;
+        }
         let mut node = self;
         for c in input.chars() {
             node = match node.children.get(&c) {
                 Some(child) => child,
                 None => return false,
             };
-            if let Some(op) = &node.operator {
-                if op == &Operator::Prefix {
-                    return true;
-                }
+            if let Some((Operator::Prefix, counter)) = &node.operator {
+                return counter.hit();
             }
         }
         // If we've reached the end of the input and the last node has an operator,
         // it must be because we matched a complete word, not a prefix.
-        if let Some(o) = node.operator {
-            o == Operator::Equal
-        } else {
-            false
+        match &node.operator {
+            Some((Operator::Equal, counter)) => counter.hit(),
+            _ => false,
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum NumericField {
+    ResponseCode,
+    ResponseDuration,
+    RequestLength,
+    ResponseLength,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum NumericOperator {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct NumericRule {
+    field: NumericField,
+    operator: NumericOperator,
+    value: i64,
+    counter: Arc<RuleHitCounter>,
+}
+
+impl NumericRule {
+    fn is_match(&self, tags: &NumericTags) -> bool {
+        let v = match self.field {
+            NumericField::ResponseCode => tags.response_code,
+            NumericField::ResponseDuration => tags.response_duration,
+            NumericField::RequestLength => tags.request_length,
+            NumericField::ResponseLength => tags.response_length,
+        };
+        let Some(v) = v else {
+            return false;
+        };
+        let matched = match self.operator {
+            NumericOperator::Greater => v > self.value,
+            NumericOperator::GreaterOrEqual => v >= self.value,
+            NumericOperator::Less => v < self.value,
+            NumericOperator::LessOrEqual => v <= self.value,
+        };
+        matched && self.counter.hit()
+    }
+}
+
+// numeric fields of a merged l7 log, response_duration is in microseconds
+#[derive(Debug, Default)]
+pub struct NumericTags {
+    pub response_code: Option<i64>,
+    pub response_duration: Option<i64>,
+    pub request_length: Option<i64>,
+    pub response_length: Option<i64>,
+}
+
 #[derive(Clone, Default, Eq, PartialEq)]
 pub struct BlacklistTrie {
     config: Vec<TagFilterOperator>,
+    // hit counters of the rules successfully inserted
+    counters: Vec<(TagFilterOperator, Arc<RuleHitCounter>)>,
 
     pub endpoint: BlacklistTrieNode,
     pub request_type: BlacklistTrieNode,
     pub request_domain: BlacklistTrieNode,
     pub request_resource: BlacklistTrieNode,
+    // protocol specific fields, see `ATTRIBUTES`
+    attributes: HashMap<String, BlacklistTrieNode>,
+    // rules on fields in `NUMERIC_FIELDS`
+    numeric: Vec<NumericRule>,
 }
 
 impl fmt::Debug for BlacklistTrie {
@@ -1032,10 +1293,102 @@ impl BlacklistTrie {
     const REQUEST_TYPE: &'static str = "request_type";
     const REQUEST_DOMAIN: &'static str = "request_domain";
     const REQUEST_RESOURCE: &'static str = "request_resource";
+    // and the following protocol specific fields:
+    const ATTRIBUTES: [&'static str; 6] = [
+        "server_cert_subject_cn",
+        "server_cert_issuer_cn",
+        "server_cert_expiring",
+        "took",
+        "errors",
+        "failed_items",
+    ];
 
     // Currently, the following matching operations are supported:
     const EQUAL: &'static str = "equal";
     const PREFIX: &'static str = "prefix";
+    const REGEX: &'static str = "regex";
+    const NOT_REGEX: &'static str = "not-regex";
+    // and the following operations on numeric fields:
+    const NUMERIC_OPERATORS: [(&'static str, NumericOperator); 4] = [
+        (">", NumericOperator::Greater),
+        (">=", NumericOperator::GreaterOrEqual),
+        ("<", NumericOperator::Less),
+        ("<=", NumericOperator::LessOrEqual),
+    ];
+    const NUMERIC_FIELDS: [(&'static str, NumericField); 4] = [
+        ("response_code", NumericField::ResponseCode),
+        ("response_duration", NumericField::ResponseDuration),
+        ("request_length", NumericField::RequestLength),
+        ("response_length", NumericField::ResponseLength),
+    ];
+
+    pub fn is_regex_operator(operator: &str) -> bool {
+        [Self::REGEX, Self::NOT_REGEX].contains(&operator.to_ascii_lowercase().as_str())
+    }
+
+    pub fn is_numeric_operator(operator: &str) -> bool {
+        Self::NUMERIC_OPERATORS
+            .iter()
+            .any(|(op, _)| *op == operator.trim())
+    }
+
+    fn parse_numeric_rule(
+        rule: &TagFilterOperator,
+    ) -> Result<(NumericField, NumericOperator, i64), String> {
+        let Some((_, operator)) = Self::NUMERIC_OPERATORS
+            .iter()
+            .find(|(op, _)| *op == rule.operator.trim())
+        else {
+            return Err(format!("{rule:?}: not a numeric operator"));
+        };
+        let field_name = rule.field_name.to_ascii_lowercase();
+        let Some((_, field)) = Self::NUMERIC_FIELDS
+            .iter()
+            .find(|(name, _)| *name == field_name)
+        else {
+            return Err(format!(
+                "{rule:?}: numeric operator {} is not supported on string field {}, only supports {}",
+                rule.operator,
+                rule.field_name,
+                Self::NUMERIC_FIELDS.map(|(name, _)| name).join(", ")
+            ));
+        };
+        let value = rule.value.trim();
+        let parsed = match field {
+            // plain numbers are microseconds
+            NumericField::ResponseDuration => value.parse::<i64>().or_else(|_| {
+                humantime::parse_duration(value)
+                    .map(|d| d.as_micros() as i64)
+                    .map_err(|e| e.to_string())
+            }),
+            _ => value.parse::<i64>().map_err(|e| e.to_string()),
+        };
+        match parsed {
+            Ok(v) => Ok((*field, *operator, v)),
+            Err(e) => Err(format!("{rule:?}: invalid value: {e}")),
+        }
+    }
+
+    // returns error describing the rule if it can not be applied
+    pub fn validate_rule(rule: &TagFilterOperator) -> Result<(), String> {
+        if Self::is_numeric_operator(&rule.operator) {
+            return Self::parse_numeric_rule(rule).map(|_| ());
+        }
+        if Self::is_regex_operator(&rule.operator) {
+            if let Err(e) = Regex::new(&rule.value) {
+                return Err(format!("{rule:?}: malformed regex: {e}"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_numeric_rules(&self) -> bool {
+        !self.numeric.is_empty()
+    }
+
+    pub fn numeric_is_on_blacklist(&self, tags: &NumericTags) -> bool {
+        self.numeric.iter().any(|r| r.is_match(tags))
+    }
 
     pub fn new(blacklists: Vec<TagFilterOperator>) -> Option<BlacklistTrie> {
         if blacklists.is_empty() {
@@ -1051,26 +1404,64 @@ impl BlacklistTrie {
     }
 
     pub fn insert(&mut self, rule: &TagFilterOperator) {
+        if Self::is_numeric_operator(&rule.operator) {
+            match Self::parse_numeric_rule(rule) {
+                Ok((field, operator, value)) => {
+                    let counter = Arc::new(RuleHitCounter::default());
+                    self.numeric.push(NumericRule {
+                        field,
+                        operator,
+                        value,
+                        counter: counter.clone(),
+                    });
+                    self.counters.push((rule.clone(), counter));
+                }
+                Err(e) => warn!("Invalid tag filter {e}"),
+            }
+            return;
+        }
+
         let mut node = match rule.field_name.to_ascii_lowercase().as_str() {
             Self::ENDPOINT => &mut self.endpoint,
             Self::REQUEST_TYPE => &mut self.request_type,
             Self::REQUEST_DOMAIN => &mut self.request_domain,
             Self::REQUEST_RESOURCE => &mut self.request_resource,
+            name if Self::ATTRIBUTES.contains(&name) => {
+                self.attributes.entry(name.to_string()).or_default()
+            }
             _ => {
                 warn!(
-                    "Unsupported field_name: {}, only supports endpoint, request_type, request_domain, request_resource.",
-                    rule.field_name.as_str()
+                    "Unsupported field_name: {}, only supports endpoint, request_type, request_domain, request_resource, {}.",
+                    rule.field_name.as_str(),
+                    Self::ATTRIBUTES.join(", ")
                 );
                 return;
             }
         };
 
+        let counter = Arc::new(RuleHitCounter::default());
         let operator = match rule.operator.to_ascii_lowercase().as_str() {
             Self::EQUAL => Operator::Equal,
             Self::PREFIX => Operator::Prefix,
+            op @ (Self::REGEX | Self::NOT_REGEX) => {
+                let regex = match Regex::new(&rule.value) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        warn!("Invalid regex {} in tag filter: {e}", rule.value);
+                        return;
+                    }
+                };
+                node.regexes.push(RegexRule {
+                    regex,
+                    negated: op == Self::NOT_REGEX,
+                    counter: counter.clone(),
+                });
+                self.counters.push((rule.clone(), counter));
+                return;
+            }
             _ => {
                 warn!(
-                    "Unsupported operator: {}, only supports equal, prefix.",
+                    "Unsupported operator: {}, only supports equal, prefix, regex, not-regex, >, >=, <, <=.",
                     rule.operator.as_str()
                 );
                 return;
@@ -1083,42 +1474,113 @@ impl BlacklistTrie {
                 .entry(ch)
                 .or_insert_with(|| Box::new(BlacklistTrieNode::default()));
         }
-        node.operator = Some(operator);
+        node.operator = Some((operator, counter.clone()));
+        self.counters.push((rule.clone(), counter));
+    }
+
+    pub fn counters(
+        &self,
+        protocol: L7Protocol,
+    ) -> impl Iterator<Item = (TagFilterModule, stats::Countable)> + '_ {
+        self.counters.iter().map(move |(rule, counter)| {
+            (
+                TagFilterModule {
+                    protocol,
+                    rule: rule.clone(),
+                },
+                stats::Countable::Ref(Arc::downgrade(counter) as Weak<dyn stats::RefCountable>),
+            )
+        })
+    }
+
+    pub fn attribute_is_on_blacklist(&self, name: &str, input: &str) -> bool {
+        self.attributes
+            .get(name)
+            .map(|node| node.is_on_blacklist(input))
+            .unwrap_or_default()
+    }
+}
+
+pub struct NxdomainSuffixModule {
+    pattern: String,
+}
+
+impl stats::Module for NxdomainSuffixModule {
+    fn name(&self) -> &'static str {
+        "l7_log_dns_nxdomain_suffix"
+    }
+
+    fn tags(&self) -> Vec<stats::StatsOption> {
+        vec![stats::StatsOption::Tag("pattern", self.pattern.clone())]
     }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 struct DomainNameTrieNode {
     children: HashMap<String, DomainNameTrieNode>,
-    unconcerned: bool,
+    // labels with `?`, or `*` matching any label
+    patterns: Vec<(String, DomainNameTrieNode)>,
+    // index of the matching rule
+    rule: Option<usize>,
 }
 
 impl DomainNameTrieNode {
-    pub fn insert(&mut self, segments: &mut iter::Rev<str::Split<'_, char>>) {
+    fn is_pattern(label: &str) -> bool {
+        label == "*" || label.contains('?')
+    }
+
+    fn label_matches(pattern: &str, label: &str) -> bool {
+        if pattern == "*" {
+            return !label.is_empty();
+        }
+        pattern.len() == label.len()
+            && pattern
+                .bytes()
+                .zip(label.bytes())
+                .all(|(p, l)| p == b'?' || p == l)
+    }
+
+    pub fn insert(&mut self, segments: &mut iter::Rev<str::Split<'_, char>>, rule: usize) {
         match segments.next() {
-            None => self.unconcerned = true,
+            None => {
+                self.rule.get_or_insert(rule);
+            }
+            Some(seg) if Self::is_pattern(seg) => {
+                match self.patterns.iter().position(|(p, _)| p == seg) {
+                    Some(i) => self.patterns[i].1.insert(segments, rule),
+                    None => {
+                        let mut child = DomainNameTrieNode::default();
+                        child.insert(segments, rule);
+                        self.patterns.push((seg.to_string(), child));
+                    }
+                }
+            }
             Some(seg) => {
                 let child = self
                     .children
                     .entry(seg.to_string())
                     .or_insert_with(|| DomainNameTrieNode::default());
-                child.insert(segments);
+                child.insert(segments, rule);
             }
         }
     }
 
-    pub fn search(&self, segments: &mut iter::Rev<str::Split<'_, char>>) -> bool {
-        if self.unconcerned {
-            return true;
+    pub fn search(&self, segments: &mut iter::Rev<str::Split<'_, char>>) -> Option<usize> {
+        if self.rule.is_some() {
+            return self.rule;
         }
-        match segments.next() {
-            None => self.unconcerned,
-            Some(seg) => self
-                .children
-                .get(seg)
-                .map(|child| child.search(segments))
-                .unwrap_or(false),
+        let seg = segments.next()?;
+        if let Some(rule) = self
+            .children
+            .get(seg)
+            .and_then(|child| child.search(&mut segments.clone()))
+        {
+            return Some(rule);
         }
+        self.patterns
+            .iter()
+            .filter(|(p, _)| Self::label_matches(p, seg))
+            .find_map(|(_, child)| child.search(&mut segments.clone()))
     }
 }
 
@@ -1126,6 +1588,8 @@ impl DomainNameTrieNode {
 pub struct DomainNameTrie {
     entries: HashSet<String>,
     root: DomainNameTrieNode,
+    // normalized rules with their hit counters
+    rules: Vec<(String, Arc<RuleHitCounter>)>,
 }
 
 impl fmt::Debug for DomainNameTrie {
@@ -1151,21 +1615,50 @@ impl DomainNameTrie {
         self.entries.is_empty()
     }
 
+    // Rules are matched on label boundaries and case-insensitively, a leading `*.` matches
+    // one or more labels and `?` matches any single character in a label
     pub fn insert(&mut self, rule: &str) {
         if self.entries.contains(rule) {
             return;
         }
         self.entries.insert(rule.to_string());
-        let mut segments = rule.split(Self::SEP).rev();
-        self.root.insert(&mut segments);
+        let normalized = rule.trim().trim_matches(Self::SEP).to_ascii_lowercase();
+        if normalized.is_empty() {
+            return;
+        }
+        let index = self.rules.len();
+        let mut segments = normalized.split(Self::SEP).rev();
+        self.root.insert(&mut segments, index);
+        self.rules
+            .push((normalized, Arc::new(RuleHitCounter::default())));
     }
 
     pub fn is_unconcerned(&self, input: &str) -> bool {
+        let input = input.trim_end_matches(Self::SEP);
         if input.is_empty() {
             return false;
         }
+        let input = if input.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(input.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(input)
+        };
         let mut segments = input.split(Self::SEP).rev();
-        self.root.search(&mut segments)
+        match self.root.search(&mut segments) {
+            Some(rule) => self.rules[rule].1.hit(),
+            None => false,
+        }
+    }
+
+    pub fn counters(&self) -> impl Iterator<Item = (NxdomainSuffixModule, stats::Countable)> + '_ {
+        self.rules.iter().map(|(pattern, counter)| {
+            (
+                NxdomainSuffixModule {
+                    pattern: pattern.clone(),
+                },
+                stats::Countable::Ref(Arc::downgrade(counter) as Weak<dyn stats::RefCountable>),
+            )
+        })
     }
 }
 
@@ -1197,6 +1690,7 @@ impl PartialEq for CustomAppConfig {
 #[derive(Clone, PartialEq)]
 pub struct LogParserConfig {
     pub l7_log_collect_nps_threshold: u64,
+    pub l7_log_protocol_nps_threshold: HashMap<L7Protocol, u64>,
     pub l7_log_session_aggr_max_entries: usize,
     pub l7_log_session_aggr_max_timeout: Duration,
     pub l7_log_session_aggr_timeout: HashMap<L7Protocol, Duration>,
@@ -1205,10 +1699,19 @@ pub struct LogParserConfig {
     pub http_endpoint_disabled: bool,
     pub http_endpoint_trie: HttpEndpointTrie,
     pub obfuscate_enabled_protocols: L7ProtocolBitmap,
+    pub obfuscate_http_query: HttpQueryObfuscation,
     pub l7_log_blacklist_trie: HashMap<L7Protocol, BlacklistTrie>,
     pub unconcerned_dns_nxdomain_trie: DomainNameTrie,
     pub mysql_decompress_payload: bool,
+    pub mysql_decompress_max_bytes: usize,
     pub mysql_endpoint_disabled: bool,
+    pub mongodb_decompress_max_bytes: usize,
+    pub dubbo: DubboConfig,
+    pub custom_text: CustomTextConfig,
+    pub http2_hpack_max_table_size: usize,
+    pub elasticsearch: ElasticsearchParseConfig,
+    pub tls_fingerprint_as_endpoint: bool,
+    pub tls_cert_expiry_warning: Duration,
     pub custom_app: CustomAppConfig,
 }
 
@@ -1216,6 +1719,7 @@ impl Default for LogParserConfig {
     fn default() -> Self {
         Self {
             l7_log_collect_nps_threshold: 0,
+            l7_log_protocol_nps_threshold: HashMap::new(),
             l7_log_session_aggr_max_entries: RequestLogTunning::default()
                 .session_aggregate_max_entries,
             l7_log_session_aggr_max_timeout: SessionTimeout::DEFAULT,
@@ -1225,10 +1729,19 @@ impl Default for LogParserConfig {
             http_endpoint_disabled: false,
             http_endpoint_trie: HttpEndpointTrie::new(),
             obfuscate_enabled_protocols: L7ProtocolBitmap::default(),
+            obfuscate_http_query: HttpQueryObfuscation::default(),
             l7_log_blacklist_trie: HashMap::new(),
             unconcerned_dns_nxdomain_trie: DomainNameTrie::default(),
             mysql_decompress_payload: true,
+            mysql_decompress_max_bytes: MysqlConfig::default().decompress_max_bytes,
             mysql_endpoint_disabled: true,
+            mongodb_decompress_max_bytes: MongodbConfig::default().decompress_max_bytes,
+            dubbo: DubboConfig::default(),
+            custom_text: CustomTextConfig::default(),
+            http2_hpack_max_table_size: Http2Config::default().hpack_max_table_size,
+            elasticsearch: ElasticsearchParseConfig::default(),
+            tls_fingerprint_as_endpoint: false,
+            tls_cert_expiry_warning: TlsConfig::default().certificate_expiry_warning,
             custom_app: CustomAppConfig::default(),
         }
     }
@@ -1241,6 +1754,10 @@ impl fmt::Debug for LogParserConfig {
                 "l7_log_collect_nps_threshold",
                 &self.l7_log_collect_nps_threshold,
             )
+            .field(
+                "l7_log_protocol_nps_threshold",
+                &self.l7_log_protocol_nps_threshold,
+            )
             .field(
                 "l7_log_session_aggr_max_entries",
                 &self.l7_log_session_aggr_max_entries,
@@ -1269,13 +1786,34 @@ impl fmt::Debug for LogParserConfig {
                     })
                     .collect::<Vec<_>>(),
             )
+            .field("obfuscate_http_query", &self.obfuscate_http_query)
             .field("l7_log_blacklist_trie", &self.l7_log_blacklist_trie)
             .field(
                 "unconcerned_dns_nxdomain_trie",
                 &self.unconcerned_dns_nxdomain_trie,
             )
             .field("mysql_decompress_payload", &self.mysql_decompress_payload)
+            .field(
+                "mysql_decompress_max_bytes",
+                &self.mysql_decompress_max_bytes,
+            )
             .field("mysql_endpoint_disabled", &self.mysql_endpoint_disabled)
+            .field(
+                "mongodb_decompress_max_bytes",
+                &self.mongodb_decompress_max_bytes,
+            )
+            .field("dubbo", &self.dubbo)
+            .field("custom_text", &self.custom_text)
+            .field(
+                "http2_hpack_max_table_size",
+                &self.http2_hpack_max_table_size,
+            )
+            .field("elasticsearch", &self.elasticsearch)
+            .field(
+                "tls_fingerprint_as_endpoint",
+                &self.tls_fingerprint_as_endpoint,
+            )
+            .field("tls_cert_expiry_warning", &self.tls_cert_expiry_warning)
             .field("custom_app", &self.custom_app)
             .finish()
     }
@@ -1359,11 +1897,14 @@ pub struct EbpfConfig {
     pub l7_protocol_parse_port_bitmap: Arc<Vec<(String, Bitmap)>>,
     pub l7_protocol_ports: std::collections::HashMap<String, String>,
     pub queue_size: usize,
+    pub queue_overflow_policy: public::queue::OverflowPolicy,
+    pub profile_batch_max_bytes: usize,
     pub ebpf: Ebpf,
     pub symbol_table: SymbolTable,
     pub process_matcher: Vec<ProcessMatcher>,
     pub io_event: EbpfFileIoEvent,
     pub dpdk_enabled: bool,
+    pub relative_sys_load: RelativeSysLoad,
 }
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -1395,9 +1936,12 @@ impl fmt::Debug for EbpfConfig {
                 &self.l7_protocol_enabled_bitmap,
             )
             .field("queue_size", &self.queue_size)
+            .field("queue_overflow_policy", &self.queue_overflow_policy)
+            .field("profile_batch_max_bytes", &self.profile_batch_max_bytes)
             .field("l7_protocol_ports", &self.l7_protocol_ports)
             .field("ebpf", &self.ebpf)
             .field("dpdk_enabled", &self.dpdk_enabled)
+            .field("relative_sys_load", &self.relative_sys_load)
             .finish()
     }
 }
@@ -1431,6 +1975,127 @@ pub enum TraceType {
     CloudWise,
     Customize(String),
     B3,
+    // header name with an explicit value format, configured as `header:format`
+    Formatted(String, TraceFormat),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceFormat {
+    // 00-TRACEID-SPANID-FLAGS
+    W3c,
+    // 1-TRACEID-SEGMENTID-SPANID-...
+    Sw8,
+    // TRACEID:SPANID:PARENTSPANID:FLAGS
+    Jaeger,
+    // Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1
+    XRay,
+    // the whole value is the id
+    Plain,
+}
+
+impl TraceFormat {
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "w3c" => Some(Self::W3c),
+            "sw8" => Some(Self::Sw8),
+            "jaeger" => Some(Self::Jaeger),
+            "xray" => Some(Self::XRay),
+            "plain" => Some(Self::Plain),
+            _ => None,
+        }
+    }
+
+    fn is_hex(s: &str, len: std::ops::RangeInclusive<usize>) -> bool {
+        len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    fn decode_w3c(value: &str, id_type: u8) -> Option<&str> {
+        let mut segs = value.split('-');
+        let (version, trace_id, span_id, flags) =
+            (segs.next()?, segs.next()?, segs.next()?, segs.next()?);
+        if !Self::is_hex(version, 2..=2)
+            || !Self::is_hex(trace_id, 32..=32)
+            || !Self::is_hex(span_id, 16..=16)
+            || !Self::is_hex(flags, 2..=2)
+        {
+            return None;
+        }
+        if id_type == TraceType::TRACE_ID {
+            Some(trace_id)
+        } else {
+            Some(span_id)
+        }
+    }
+
+    fn decode_jaeger(value: &str, id_type: u8) -> Option<&str> {
+        let mut segs = value.split(':');
+        let (trace_id, span_id, parent_id, flags) =
+            (segs.next()?, segs.next()?, segs.next()?, segs.next()?);
+        if segs.next().is_some()
+            || !Self::is_hex(trace_id, 1..=32)
+            || !Self::is_hex(span_id, 1..=16)
+            || !Self::is_hex(parent_id, 1..=16)
+            || !Self::is_hex(flags, 1..=2)
+        {
+            return None;
+        }
+        if id_type == TraceType::TRACE_ID {
+            Some(trace_id)
+        } else {
+            Some(span_id)
+        }
+    }
+
+    fn decode_xray(value: &str, id_type: u8) -> Option<&str> {
+        let key = if id_type == TraceType::TRACE_ID {
+            "Root"
+        } else {
+            "Parent"
+        };
+        let id = value.split(';').find_map(|kv| {
+            let (k, v) = kv.split_once('=')?;
+            k.trim().eq_ignore_ascii_case(key).then_some(v.trim())
+        })?;
+        let valid = if id_type == TraceType::TRACE_ID {
+            // 1-EPOCH-UNIQUEID
+            let mut segs = id.split('-');
+            segs.next() == Some("1")
+                && segs.next().map(|s| Self::is_hex(s, 8..=8)).unwrap_or(false)
+                && segs
+                    .next()
+                    .map(|s| Self::is_hex(s, 24..=24))
+                    .unwrap_or(false)
+                && segs.next().is_none()
+        } else {
+            Self::is_hex(id, 16..=16)
+        };
+        valid.then_some(id)
+    }
+
+    fn decode<'a>(&self, value: &'a str, id_type: u8) -> Option<Cow<'a, str>> {
+        let id = match self {
+            Self::W3c => Self::decode_w3c(value, id_type).map(|s| s.into()),
+            Self::Sw8 => {
+                // 8 fields at least
+                if value.split('-').count() < 8 {
+                    return None;
+                }
+                TraceType::decode_skywalking_id(value, id_type)
+            }
+            Self::Jaeger => {
+                // jaeger clients may url encode the value
+                if value.contains('%') {
+                    let decoded = value.replace("%3A", ":").replace("%3a", ":");
+                    return Self::decode_jaeger(&decoded, id_type).map(|s| s.to_owned().into());
+                }
+                Self::decode_jaeger(value, id_type).map(|s| s.into())
+            }
+            Self::XRay => Self::decode_xray(value, id_type).map(|s| s.into()),
+            Self::Plain => Some(value.into()),
+        };
+        // malformed values leave the field empty
+        id.filter(|id| !id.is_empty())
+    }
 }
 
 // The value here must be lower case
@@ -1453,6 +2118,11 @@ impl From<&str> for TraceType {
     // Example 2: "xxx.x"
     fn from(t: &str) -> TraceType {
         let tag_lowercase = t.trim().to_lowercase();
+        if let Some((header, format)) = tag_lowercase.rsplit_once(':') {
+            if let Some(format) = TraceFormat::from_suffix(format.trim()) {
+                return TraceType::Formatted(header.trim().to_string(), format);
+            }
+        }
         let (tag, sub_tag) = if let Some(i) = tag_lowercase.find('.') {
             (
                 tag_lowercase[..i].to_string(),
@@ -1496,6 +2166,7 @@ impl TraceType {
             TraceType::CloudWise => context.eq_ignore_ascii_case(TRACE_TYPE_CLOUD_WISE),
             TraceType::Customize(tag) => context.eq_ignore_ascii_case(&tag),
             TraceType::B3 => context.eq_ignore_ascii_case(TRACE_TYPE_B3),
+            TraceType::Formatted(header, _) => context.eq_ignore_ascii_case(&header),
             _ => false,
         }
     }
@@ -1514,6 +2185,7 @@ impl TraceType {
             TraceType::CloudWise => TRACE_TYPE_CLOUD_WISE_UPPER,
             TraceType::Customize(tag) => &tag,
             TraceType::B3 => TRACE_TYPE_B3,
+            TraceType::Formatted(header, _) => &header,
             _ => "",
         }
     }
@@ -1642,6 +2314,7 @@ impl TraceType {
             TraceType::XTingyun(sub_tag) => Self::decode_tingyun(value, sub_tag),
             TraceType::CloudWise => Self::decode_cloud_wise(value).map(|s| s.into()),
             TraceType::B3 => Self::decode_b3(value, id_type).map(|s| s.into()),
+            TraceType::Formatted(_, format) => format.decode(value, id_type),
         }
     }
 
@@ -1664,8 +2337,9 @@ impl Default for TraceType {
 pub struct L7LogDynamicConfig {
     // in lowercase
     pub proxy_client: Vec<String>,
-    // in lowercase
-    pub x_request_id: Vec<String>,
+    // in lowercase, headers read from requests and responses respectively
+    pub x_request_id_request: Vec<String>,
+    pub x_request_id_response: Vec<String>,
 
     pub multiple_trace_id_collection: bool,
     pub copy_apm_trace_id: bool,
@@ -1695,7 +2369,8 @@ impl fmt::Debug for L7LogDynamicConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("L7LogDynamicConfig")
             .field("proxy_client", &self.proxy_client)
-            .field("x_request_id", &self.x_request_id)
+            .field("x_request_id_request", &self.x_request_id_request)
+            .field("x_request_id_response", &self.x_request_id_response)
             .field("trace_types", &self.trace_types)
             .field("span_types", &self.span_types)
             .field(
@@ -1729,7 +2404,8 @@ impl fmt::Debug for L7LogDynamicConfig {
 impl PartialEq for L7LogDynamicConfig {
     fn eq(&self, other: &Self) -> bool {
         self.proxy_client == other.proxy_client
-            && self.x_request_id == other.x_request_id
+            && self.x_request_id_request == other.x_request_id_request
+            && self.x_request_id_response == other.x_request_id_response
             && self.multiple_trace_id_collection == other.multiple_trace_id_collection
             && self.copy_apm_trace_id == other.copy_apm_trace_id
             && self.trace_types == other.trace_types
@@ -1745,7 +2421,7 @@ impl PartialEq for L7LogDynamicConfig {
 
 pub struct L7LogDynamicConfigBuilder {
     pub proxy_client: Vec<String>,
-    pub x_request_id: Vec<String>,
+    pub x_request_id: Vec<XRequestIdHeader>,
     pub multiple_trace_id_collection: bool,
     pub copy_apm_trace_id: bool,
     pub trace_types: Vec<TraceType>,
@@ -1813,6 +2489,18 @@ impl From<&UserConfig> for L7LogDynamicConfigBuilder {
                     .get("HTTP2")
                     .map(|c| c.iter().map(|f| ExtraLogFieldsInfo::from(f)).collect())
                     .unwrap_or(vec![]),
+                mqtt: c
+                    .tag_extraction
+                    .custom_fields
+                    .get("MQTT")
+                    .map(|c| c.iter().map(|f| ExtraLogFieldsInfo::from(f)).collect())
+                    .unwrap_or(vec![]),
+                sofarpc: c
+                    .tag_extraction
+                    .custom_fields
+                    .get("SofaRPC")
+                    .map(|c| c.iter().map(|f| ExtraLogFieldsInfo::from(f)).collect())
+                    .unwrap_or(vec![]),
             },
             grpc_streaming_data_enabled: c
                 .application_protocol_inference
@@ -1833,7 +2521,7 @@ impl From<L7LogDynamicConfigBuilder> for L7LogDynamicConfig {
     fn from(builder: L7LogDynamicConfigBuilder) -> Self {
         let L7LogDynamicConfigBuilder {
             mut proxy_client,
-            mut x_request_id,
+            x_request_id,
             multiple_trace_id_collection,
             copy_apm_trace_id,
             trace_types,
@@ -1862,16 +2550,29 @@ impl From<L7LogDynamicConfigBuilder> for L7LogDynamicConfig {
             true
         });
 
+        let mut x_request_id_request = vec![];
         dup_checker.clear();
-        x_request_id.retain(|s| {
-            let s = s.trim();
-            if dup_checker.contains(s) {
-                return false;
+        for s in x_request_id.iter() {
+            let s = s.request().trim().to_owned();
+            if s.is_empty() || dup_checker.contains(&s) {
+                continue;
             }
-            dup_checker.insert(s.to_owned());
+            dup_checker.insert(s.clone());
             expected_headers_set.insert(s.as_bytes().to_vec());
-            true
-        });
+            x_request_id_request.push(s);
+        }
+
+        let mut x_request_id_response = vec![];
+        dup_checker.clear();
+        for s in x_request_id.iter() {
+            let s = s.response().trim().to_owned();
+            if s.is_empty() || dup_checker.contains(&s) {
+                continue;
+            }
+            dup_checker.insert(s.clone());
+            expected_headers_set.insert(s.as_bytes().to_vec());
+            x_request_id_response.push(s);
+        }
 
         let mut trace_set = HashSet::new();
         for t in trace_types.iter() {
@@ -1905,7 +2606,8 @@ impl From<L7LogDynamicConfigBuilder> for L7LogDynamicConfig {
 
         Self {
             proxy_client,
-            x_request_id,
+            x_request_id_request,
+            x_request_id_response,
             multiple_trace_id_collection,
             copy_apm_trace_id,
             trace_types,
@@ -1924,6 +2626,14 @@ impl From<L7LogDynamicConfigBuilder> for L7LogDynamicConfig {
 }
 
 impl L7LogDynamicConfig {
+    pub fn x_request_id(&self, direction: PacketDirection) -> &[String] {
+        if direction == PacketDirection::ClientToServer {
+            &self.x_request_id_request
+        } else {
+            &self.x_request_id_response
+        }
+    }
+
     pub fn is_trace_id(&self, context: &str) -> bool {
         self.trace_set.contains(context)
     }
@@ -1937,8 +2647,13 @@ impl L7LogDynamicConfig {
 pub struct MetricServerConfig {
     pub enabled: bool,
     pub port: u16,
+    pub listen_address: Option<IpAddr>,
+    pub unix_socket_path: Option<PathBuf>,
+    pub otlp_grpc_port: u16,
     pub compressed: bool,
     pub profile_compressed: bool,
+    pub max_decompressed_size: usize,
+    pub source_filter: SourceFilter,
     pub application_log_compressed: bool,
     pub l7_flow_log_compressed: bool,
     pub l4_flow_log_compressed: bool,
@@ -2086,6 +2801,7 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
             },
             dispatcher: DispatcherConfig {
                 global_pps_threshold: conf.inputs.cbpf.tunning.max_capture_pps,
+                global_pps_burst: conf.inputs.cbpf.tunning.max_capture_pps_burst(),
                 capture_packet_size: conf.inputs.cbpf.tunning.max_capture_packet_size,
                 dpdk_source: conf.inputs.cbpf.special_network.dpdk.source,
                 dispatcher_queue: conf.inputs.cbpf.tunning.dispatcher_queue_enabled,
@@ -2093,6 +2809,21 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 tunnel_type_bitmap: TunnelTypeBitmap::from_slices(
                     &conf.inputs.cbpf.preprocess.tunnel_decap_protocols,
                     &conf.inputs.cbpf.preprocess.tunnel_trim_protocols,
+                )
+                .with_mpls_label_limit(conf.inputs.cbpf.preprocess.mpls_label_limit)
+                .with_geneve_options(
+                    &conf
+                        .inputs
+                        .cbpf
+                        .preprocess
+                        .geneve_options
+                        .iter()
+                        .map(|o| GeneveOptionSelector {
+                            class: o.class,
+                            option_type: o.option_type,
+                        })
+                        .collect::<Vec<_>>(),
+                    conf.inputs.cbpf.preprocess.geneve_metadata_max_bytes,
                 ),
                 tunnel_type_trim_bitmap: TunnelTypeBitmap::from_strings(
                     &conf.inputs.cbpf.preprocess.tunnel_trim_protocols,
@@ -2103,6 +2834,12 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 #[cfg(target_os = "linux")]
                 extra_netns_regex: conf.inputs.cbpf.af_packet.extra_netns_regex.clone(),
                 tap_interface_regex: conf.inputs.cbpf.af_packet.interface_regex.clone(),
+                tap_interface_exclude_regex: conf
+                    .inputs
+                    .cbpf
+                    .af_packet
+                    .interface_exclude_regex
+                    .clone(),
                 inner_interface_capture_enabled: conf
                     .inputs
                     .cbpf
@@ -2134,7 +2871,11 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 },
                 #[cfg(any(target_os = "linux", target_os = "android"))]
                 cpu_set: CpuSet::new(),
-                raw_packet_buffer_block_size: conf.inputs.cbpf.tunning.raw_packet_buffer_block_size,
+                raw_packet_buffer_block_size: conf
+                    .inputs
+                    .cbpf
+                    .tunning
+                    .raw_packet_buffer_block_size(),
                 raw_packet_queue_size: conf.inputs.cbpf.tunning.raw_packet_queue_size,
             },
             sender: SenderConfig {
@@ -2143,8 +2884,8 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 team_id: conf.global.common.team_id,
                 organize_id: conf.global.common.organize_id,
                 dest_port: conf.global.communication.ingester_port,
-                npb_port: conf.outputs.npb.target_port,
-                vxlan_flags: conf.outputs.npb.custom_vxlan_flags,
+                npb_port: conf.outputs.npb.target_port(),
+                vxlan_flags: conf.outputs.npb.vxlan_flags(),
                 npb_enable_qos_bypass: conf.outputs.socket.raw_udp_qos_bypass,
                 npb_vlan: conf.outputs.npb.raw_udp_vlan_tag,
                 npb_vlan_mode: conf.outputs.npb.extra_vlan_header.into(),
@@ -2156,27 +2897,82 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                     .circuit_breakers
                     .tx_throughput
                     .trigger_threshold,
+                server_tx_bandwidth_recovery_threshold: conf
+                    .global
+                    .circuit_breakers
+                    .tx_throughput
+                    .recovery_threshold,
+                server_tx_bandwidth_min_hold_time: conf
+                    .global
+                    .circuit_breakers
+                    .tx_throughput
+                    .min_hold_time,
+                server_tx_bandwidth_over_limit_action: conf
+                    .global
+                    .circuit_breakers
+                    .tx_throughput
+                    .over_limit_action,
                 bandwidth_probe_interval: conf
                     .global
                     .circuit_breakers
                     .tx_throughput
                     .throughput_monitoring_interval,
                 multiple_sockets_to_ingester: conf.outputs.socket.multiple_sockets_to_ingester,
+                sockets_per_sender: conf.outputs.socket.sockets_per_sender,
+                multiple_sockets_balance: conf.outputs.socket.multiple_sockets_balance,
                 max_throughput_to_ingester: conf.global.communication.max_throughput_to_ingester,
                 ingester_traffic_overflow_action: conf
                     .global
                     .communication
                     .ingester_traffic_overflow_action,
+                reconnect_backoff_base: conf.global.communication.ingester_reconnect_backoff_base,
+                reconnect_backoff_max: conf.global.communication.ingester_reconnect_backoff_max,
+                failover_ips: conf.global.communication.ingester_failover_ips.clone(),
+                failover_threshold: conf.global.communication.ingester_failover_threshold,
+                failback_hold_down: conf.global.communication.ingester_failback_hold_down,
+                batch_max_bytes: conf.outputs.socket.tunning.batch_max_bytes,
+                batch_max_documents: conf.outputs.socket.tunning.batch_max_documents,
+                linger: conf.outputs.socket.tunning.linger,
                 collector_socket_type: conf.outputs.socket.data_socket_type,
+                ingester_tls: (conf.outputs.socket.data_socket_type == agent::SocketType::Tls)
+                    .then(|| {
+                        let communication = &conf.global.communication;
+                        let ca_files = if !communication.ingester_tls_ca_file.is_empty() {
+                            vec![communication.ingester_tls_ca_file.clone()]
+                        } else if !static_config.controller_cert_file_prefix.is_empty() {
+                            // certificate files are named prefix.controller-ip
+                            static_config
+                                .controller_ips
+                                .iter()
+                                .map(|ip| {
+                                    format!("{}.{}", static_config.controller_cert_file_prefix, ip)
+                                })
+                                .collect()
+                        } else {
+                            vec![]
+                        };
+                        IngesterTlsConfig {
+                            ca_files,
+                            server_name: if !communication.ingester_tls_server_name.is_empty() {
+                                communication.ingester_tls_server_name.clone()
+                            } else {
+                                dest_ip.clone()
+                            },
+                        }
+                    }),
                 standalone_data_file_size: conf.global.standalone_mode.max_data_file_size,
                 standalone_data_file_dir: conf.global.standalone_mode.data_file_dir.clone(),
+                standalone_data_file_rotation_interval: conf
+                    .global
+                    .standalone_mode
+                    .data_file_rotation_interval,
+                standalone_max_data_files: conf.global.standalone_mode.max_data_files,
                 enabled: conf.outputs.flow_metrics.enabled,
             },
             npb: NpbConfig {
                 mtu: conf.outputs.npb.max_mtu,
-                underlay_is_ipv6: controller_ip.is_ipv6(),
-                npb_port: conf.outputs.npb.target_port,
-                vxlan_flags: conf.outputs.npb.custom_vxlan_flags,
+                npb_port: conf.outputs.npb.target_port(),
+                vxlan_flags: conf.outputs.npb.vxlan_flags(),
                 ignore_overlay_vlan: conf.outputs.npb.overlay_vlan_header_trimming,
                 enable_qos_bypass: conf.outputs.socket.raw_udp_qos_bypass,
                 output_vlan: conf.outputs.npb.raw_udp_vlan_tag,
@@ -2184,6 +2980,8 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 dedup_enabled: conf.outputs.npb.traffic_global_dedup,
                 socket_type: conf.outputs.socket.npb_socket_type,
                 queue_size: conf.outputs.flow_metrics.tunning.sender_queue_size,
+                hop_limit: conf.outputs.npb.underlay_hop_limit,
+                vxlan_encapsulation: conf.outputs.npb.vxlan_encapsulation,
             },
             collector: CollectorConfig {
                 enabled: conf.outputs.flow_metrics.enabled,
@@ -2215,6 +3013,19 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                     }
                     tap_sides
                 },
+                l4_log_sampling_ratios: {
+                    let mut ratios = [1.0; TapSide::MAX as usize + 1];
+                    for s in conf
+                        .outputs
+                        .flow_log
+                        .filters
+                        .l4_observation_point_sampling_ratios
+                        .iter()
+                    {
+                        ratios[s.observation_point as usize] = s.ratio;
+                    }
+                    ratios
+                },
                 aggregate_health_check_l4_flow_log: conf
                     .outputs
                     .flow_log
@@ -2231,6 +3042,26 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                     .time_window
                     .max_tolerable_packet_delay,
                 npm_metrics_concurrent: conf.outputs.flow_metrics.filters.npm_metrics_concurrent,
+                second_metrics_shedding_enabled: conf
+                    .outputs
+                    .flow_metrics
+                    .tunning
+                    .second_metrics_shedding_enabled,
+                second_metrics_shedding_high_watermark: conf
+                    .outputs
+                    .flow_metrics
+                    .tunning
+                    .second_metrics_shedding_high_watermark,
+                second_metrics_shedding_low_watermark: conf
+                    .outputs
+                    .flow_metrics
+                    .tunning
+                    .second_metrics_shedding_low_watermark,
+                second_metrics_shedding_intervals: conf
+                    .outputs
+                    .flow_metrics
+                    .tunning
+                    .second_metrics_shedding_intervals,
             },
             handler: HandlerConfig {
                 npb_dedup_enabled: conf.outputs.npb.traffic_global_dedup,
@@ -2306,6 +3137,14 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
             flow: (&conf).into(),
             log_parser: LogParserConfig {
                 l7_log_collect_nps_threshold: conf.outputs.flow_log.throttles.l7_throttle,
+                l7_log_protocol_nps_threshold: conf
+                    .outputs
+                    .flow_log
+                    .throttles
+                    .l7_throttle_overrides
+                    .iter()
+                    .map(|o| (o.protocol, o.throttle))
+                    .collect(),
                 l7_log_session_aggr_max_timeout: conf.processors.request_log.timeouts.max(),
                 l7_log_session_aggr_timeout: conf
                     .processors
@@ -2351,6 +3190,11 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                         .obfuscate_protocols
                         .as_slice(),
                 ),
+                obfuscate_http_query: conf
+                    .processors
+                    .request_log
+                    .tag_extraction
+                    .obfuscate_http_query,
                 l7_log_blacklist_trie: {
                     let mut blacklist_trie = HashMap::new();
                     for (k, v) in conf.processors.request_log.filters.tag_filters.iter() {
@@ -2379,6 +3223,13 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                     .protocol_special_config
                     .mysql
                     .decompress_payload,
+                mysql_decompress_max_bytes: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .mysql
+                    .decompress_max_bytes,
                 mysql_endpoint_disabled: conf
                     .processors
                     .request_log
@@ -2386,6 +3237,55 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                     .protocol_special_config
                     .mysql
                     .endpoint_disabled,
+                mongodb_decompress_max_bytes: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .mongodb
+                    .decompress_max_bytes,
+                dubbo: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .dubbo
+                    .clone(),
+                custom_text: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .custom_text
+                    .clone(),
+                http2_hpack_max_table_size: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .http2
+                    .hpack_max_table_size,
+                elasticsearch: (&conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .elasticsearch)
+                    .into(),
+                tls_fingerprint_as_endpoint: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .tls
+                    .fingerprint_as_endpoint,
+                tls_cert_expiry_warning: conf
+                    .processors
+                    .request_log
+                    .application_protocol_inference
+                    .protocol_special_config
+                    .tls
+                    .certificate_expiry_warning,
                 #[cfg(not(feature = "enterprise"))]
                 custom_app: CustomAppConfig::default(),
                 #[cfg(feature = "enterprise")]
@@ -2501,17 +3401,30 @@ impl TryFrom<(Config, UserConfig)> for ModuleConfig {
                 l7_protocol_parse_port_bitmap: Arc::new(conf.get_protocol_port_parse_bitmap()),
                 l7_protocol_ports: conf.get_protocol_port(),
                 queue_size: conf.inputs.ebpf.tunning.collector_queue_size,
+                queue_overflow_policy: conf.inputs.ebpf.tunning.collector_queue_overflow_policy(),
+                profile_batch_max_bytes: conf
+                    .global
+                    .communication
+                    .grpc_buffer_size
+                    .max(GRPC_BUFFER_SIZE_MIN),
                 ebpf: conf.inputs.ebpf.clone(),
                 symbol_table: conf.inputs.proc.symbol_table,
                 process_matcher: conf.inputs.proc.process_matcher.clone(),
-                io_event: conf.inputs.ebpf.file.io_event,
+                io_event: conf.inputs.ebpf.file.io_event.clone(),
                 dpdk_enabled: conf.inputs.cbpf.special_network.dpdk.source == DpdkSource::Ebpf,
+                relative_sys_load: conf.global.circuit_breakers.relative_sys_load,
             },
             metric_server: MetricServerConfig {
                 enabled: conf.inputs.integration.enabled,
                 port: conf.inputs.integration.listen_port,
+                listen_address: conf.inputs.integration.listen_address(),
+                unix_socket_path: conf.inputs.integration.listen_unix_socket_path(),
+                otlp_grpc_port: conf.inputs.integration.otlp_grpc_listen_port,
                 compressed: conf.inputs.integration.compression.trace,
                 profile_compressed: conf.inputs.integration.compression.profile,
+                max_decompressed_size: conf.inputs.integration.compression.max_decompressed_size
+                    as usize,
+                source_filter: conf.inputs.integration.source_filter.clone(),
                 application_log_compressed: conf.outputs.compression.application_log,
                 l7_flow_log_compressed: conf.outputs.compression.l7_flow_log,
                 l4_flow_log_compressed: conf.outputs.compression.l4_flow_log,
@@ -2704,7 +3617,12 @@ impl ConfigHandler {
     #[cfg(any(target_os = "linux", target_os = "android"))]
     fn should_skip_cpu_affinity(thread_name: &str) -> bool {
         // `kick-kern.*` threads are self-managed eBPF per-CPU kickers.
+        // `dpdk-q*` threads are DPDK queue dispatchers pinned to a single CPU.
+        // `dispatcher-q*` threads are dispatcher queue consumers pinned by
+        // `inputs.cbpf.tunning.dispatcher_queue_cpu_pinning`.
         thread_name.starts_with("kick-kern.")
+            || thread_name.starts_with("dpdk-q")
+            || thread_name.starts_with("dispatcher-q")
     }
 
     #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -2914,19 +3832,23 @@ impl ConfigHandler {
         }
     }
 
-    fn leaky_bucket_callback(handler: &ConfigHandler, components: &mut AgentComponents) {
+    fn rx_token_bucket_callback(handler: &ConfigHandler, components: &mut AgentComponents) {
+        let dispatcher = &handler.candidate_config.dispatcher;
         match handler.candidate_config.capture_mode {
             PacketCaptureType::Analyzer => {
-                components.rx_leaky_bucket.set_rate(None);
+                components
+                    .rx_token_bucket
+                    .set(None, dispatcher.global_pps_burst);
                 info!("dispatcher.global pps set ulimit when capture_mode=analyzer");
             }
             _ => {
-                components.rx_leaky_bucket.set_rate(Some(
-                    handler.candidate_config.dispatcher.global_pps_threshold,
-                ));
+                components.rx_token_bucket.set(
+                    Some(dispatcher.global_pps_threshold),
+                    dispatcher.global_pps_burst,
+                );
                 info!(
-                    "dispatcher.global pps threshold change to {}",
-                    handler.candidate_config.dispatcher.global_pps_threshold
+                    "dispatcher.global pps threshold change to {} with burst {}",
+                    dispatcher.global_pps_threshold, dispatcher.global_pps_burst
                 );
             }
         }
@@ -3039,6 +3961,10 @@ impl ConfigHandler {
             .metrics_server_component
             .external_metrics_server
             .set_port(handler.candidate_config.metric_server.port);
+        components
+            .metrics_server_component
+            .external_metrics_server
+            .set_source_filter(&handler.candidate_config.metric_server.source_filter);
     }
 
     fn set_npb(handler: &ConfigHandler, components: &mut AgentComponents) {
@@ -3110,7 +4036,7 @@ impl ConfigHandler {
         &mut self,
         user_config: UserConfig,
         exception_handler: &ExceptionHandler,
-        #[allow(unused)] stats_collector: &stats::Collector, // used for custom_field_policies
+        stats_collector: &stats::Collector,
         mut components: Option<&mut AgentComponents>,
         #[cfg(target_os = "linux")] api_watcher: &Arc<ApiWatcher>,
         runtime: &Runtime,
@@ -3219,6 +4145,21 @@ impl ConfigHandler {
                     new_af_packet.bond_interfaces,
                     "inputs.cbpf.af_packet.bond_interfaces"
                 ),
+                (
+                    af_packet.interface_groups,
+                    new_af_packet.interface_groups,
+                    "inputs.cbpf.af_packet.interface_groups"
+                ),
+                (
+                    af_packet.interface_dispatcher_assignments,
+                    new_af_packet.interface_dispatcher_assignments,
+                    "inputs.cbpf.af_packet.interface_dispatcher_assignments"
+                ),
+                (
+                    af_packet.interface_promisc_overrides,
+                    new_af_packet.interface_promisc_overrides,
+                    "inputs.cbpf.af_packet.interface_promisc_overrides"
+                ),
                 (
                     af_packet.extra_bpf_filter,
                     new_af_packet.extra_bpf_filter,
@@ -3252,6 +4193,17 @@ impl ConfigHandler {
                 callbacks.push(Self::switch_recv_engine);
             }
         }
+        if af_packet.interface_exclude_regex != new_af_packet.interface_exclude_regex {
+            info!(
+                "Update inputs.cbpf.af_packet.interface_exclude_regex from {:?} to {:?}.",
+                af_packet.interface_exclude_regex, new_af_packet.interface_exclude_regex
+            );
+            af_packet.interface_exclude_regex = new_af_packet.interface_exclude_regex.clone();
+            #[cfg(target_os = "windows")]
+            if capture_mode == PacketCaptureType::Local {
+                callbacks.push(Self::switch_recv_engine);
+            }
+        }
         update_fields_with_restart_reason!(
             restart_agent,
             !first_run,
@@ -3391,6 +4343,21 @@ impl ConfigHandler {
                     new_physical_mirror.packet_dedup_disabled,
                     "inputs.cbpf.physical_mirror.packet_dedup_disabled"
                 ),
+                (
+                    physical_mirror.packet_dedup_window,
+                    new_physical_mirror.packet_dedup_window,
+                    "inputs.cbpf.physical_mirror.packet_dedup_window"
+                ),
+                (
+                    physical_mirror.packet_dedup_table_size,
+                    new_physical_mirror.packet_dedup_table_size,
+                    "inputs.cbpf.physical_mirror.packet_dedup_table_size"
+                ),
+                (
+                    physical_mirror.packet_dedup_ignore_ttl_tos,
+                    new_physical_mirror.packet_dedup_ignore_ttl_tos,
+                    "inputs.cbpf.physical_mirror.packet_dedup_ignore_ttl_tos"
+                ),
                 (
                     physical_mirror.private_cloud_gateway_traffic,
                     new_physical_mirror.private_cloud_gateway_traffic,
@@ -3418,10 +4385,31 @@ impl ConfigHandler {
         );
         if preprocess.tunnel_decap_protocols != new_preprocess.tunnel_decap_protocols {
             info!(
-                "Update inputs.cbpf.preprocess.tunnel_decap_protocols from {:?} to {:?}.",
-                preprocess.tunnel_decap_protocols, new_preprocess.tunnel_decap_protocols
+                "Update inputs.cbpf.preprocess.tunnel_decap_protocols from {:?} to {:?}.",
+                preprocess.tunnel_decap_protocols, new_preprocess.tunnel_decap_protocols
+            );
+            preprocess.tunnel_decap_protocols = new_preprocess.tunnel_decap_protocols.clone();
+        }
+        if preprocess.mpls_label_limit != new_preprocess.mpls_label_limit {
+            info!(
+                "Update inputs.cbpf.preprocess.mpls_label_limit from {:?} to {:?}.",
+                preprocess.mpls_label_limit, new_preprocess.mpls_label_limit
+            );
+            preprocess.mpls_label_limit = new_preprocess.mpls_label_limit;
+        }
+        if preprocess.geneve_options != new_preprocess.geneve_options {
+            info!(
+                "Update inputs.cbpf.preprocess.geneve_options from {:?} to {:?}.",
+                preprocess.geneve_options, new_preprocess.geneve_options
+            );
+            preprocess.geneve_options = new_preprocess.geneve_options.clone();
+        }
+        if preprocess.geneve_metadata_max_bytes != new_preprocess.geneve_metadata_max_bytes {
+            info!(
+                "Update inputs.cbpf.preprocess.geneve_metadata_max_bytes from {:?} to {:?}.",
+                preprocess.geneve_metadata_max_bytes, new_preprocess.geneve_metadata_max_bytes
             );
-            preprocess.tunnel_decap_protocols = new_preprocess.tunnel_decap_protocols.clone();
+            preprocess.geneve_metadata_max_bytes = new_preprocess.geneve_metadata_max_bytes;
         }
         update_fields_with_restart_reason!(
             restart_agent,
@@ -3451,11 +4439,26 @@ impl ConfigHandler {
                     new_special_network.dpdk.reorder_cache_window_size,
                     "inputs.cbpf.special_network.dpdk.reorder_cache_window_size"
                 ),
+                (
+                    special_network.dpdk.ports,
+                    new_special_network.dpdk.ports,
+                    "inputs.cbpf.special_network.dpdk.ports"
+                ),
+                (
+                    special_network.dpdk.rss,
+                    new_special_network.dpdk.rss,
+                    "inputs.cbpf.special_network.dpdk.rss"
+                ),
                 (
                     special_network.libpcap.enabled,
                     new_special_network.libpcap.enabled,
                     "inputs.cbpf.special_network.libpcap.enabled"
                 ),
+                (
+                    special_network.libpcap.interfaces,
+                    new_special_network.libpcap.interfaces,
+                    "inputs.cbpf.special_network.libpcap.interfaces"
+                ),
                 (
                     special_network.vhost_user.vhost_socket_path,
                     new_special_network.vhost_user.vhost_socket_path,
@@ -3489,11 +4492,18 @@ impl ConfigHandler {
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                tunning.dispatcher_queue_enabled,
-                new_tunning.dispatcher_queue_enabled,
-                "inputs.cbpf.tunning.dispatcher_queue_enabled"
-            )]
+            [
+                (
+                    tunning.dispatcher_queue_enabled,
+                    new_tunning.dispatcher_queue_enabled,
+                    "inputs.cbpf.tunning.dispatcher_queue_enabled"
+                ),
+                (
+                    tunning.dispatcher_queue_cpu_pinning,
+                    new_tunning.dispatcher_queue_cpu_pinning,
+                    "inputs.cbpf.tunning.dispatcher_queue_cpu_pinning"
+                )
+            ]
         );
         if tunning.max_capture_packet_size != new_tunning.max_capture_packet_size {
             info!(
@@ -3502,13 +4512,25 @@ impl ConfigHandler {
             );
             tunning.max_capture_packet_size = new_tunning.max_capture_packet_size;
         }
+        let mut rx_token_bucket_changed = false;
         if tunning.max_capture_pps != new_tunning.max_capture_pps {
             info!(
                 "Update inputs.cbpf.tunning.max_capture_pps from {:?} to {:?}.",
                 tunning.max_capture_pps, new_tunning.max_capture_pps
             );
             tunning.max_capture_pps = new_tunning.max_capture_pps;
-            callbacks.push(Self::leaky_bucket_callback);
+            rx_token_bucket_changed = true;
+        }
+        if tunning.max_capture_pps_burst != new_tunning.max_capture_pps_burst {
+            info!(
+                "Update inputs.cbpf.tunning.max_capture_pps_burst from {:?} to {:?}.",
+                tunning.max_capture_pps_burst, new_tunning.max_capture_pps_burst
+            );
+            tunning.max_capture_pps_burst = new_tunning.max_capture_pps_burst;
+            rx_token_bucket_changed = true;
+        }
+        if rx_token_bucket_changed {
+            callbacks.push(Self::rx_token_bucket_callback);
         }
         update_fields_with_restart_reason!(
             restart_agent,
@@ -3527,6 +4549,13 @@ impl ConfigHandler {
                 )
             ]
         );
+        if first_run && tunning.raw_packet_buffer_block_size == 0 {
+            info!(
+                "inputs.cbpf.tunning.raw_packet_buffer_block_size is auto sized to {} by max_capture_packet_size {}.",
+                tunning.raw_packet_buffer_block_size(),
+                tunning.max_capture_packet_size
+            );
+        }
 
         let ebpf = &mut config.inputs.ebpf;
         let new_ebpf = &mut new_config.user_config.inputs.ebpf;
@@ -3534,7 +4563,14 @@ impl ConfigHandler {
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(ebpf.disabled, new_ebpf.disabled, "inputs.ebpf.disabled")]
+            [
+                (ebpf.disabled, new_ebpf.disabled, "inputs.ebpf.disabled"),
+                (
+                    ebpf.excluded_namespaces,
+                    new_ebpf.excluded_namespaces,
+                    "inputs.ebpf.excluded_namespaces"
+                )
+            ]
         );
 
         let io_event = &mut ebpf.file.io_event;
@@ -3558,6 +4594,26 @@ impl ConfigHandler {
                     io_event.enable_virtual_file_collect,
                     new_io_event.enable_virtual_file_collect,
                     "inputs.ebpf.file.io_event.enable_virtual_file_collect"
+                ),
+                (
+                    io_event.include_mount_points,
+                    new_io_event.include_mount_points,
+                    "inputs.ebpf.file.io_event.include_mount_points"
+                ),
+                (
+                    io_event.exclude_mount_points,
+                    new_io_event.exclude_mount_points,
+                    "inputs.ebpf.file.io_event.exclude_mount_points"
+                ),
+                (
+                    io_event.include_devices,
+                    new_io_event.include_devices,
+                    "inputs.ebpf.file.io_event.include_devices"
+                ),
+                (
+                    io_event.exclude_devices,
+                    new_io_event.exclude_devices,
+                    "inputs.ebpf.file.io_event.exclude_devices"
                 )
             ]
         );
@@ -3630,6 +4686,16 @@ impl ConfigHandler {
                     new_off_cpu.aggregate_by_cpu,
                     "inputs.ebpf.profile.off_cpu.aggregate_by_cpu"
                 ),
+                (
+                    off_cpu.aggregate_by_thread,
+                    new_off_cpu.aggregate_by_thread,
+                    "inputs.ebpf.profile.off_cpu.aggregate_by_thread"
+                ),
+                (
+                    off_cpu.max_threads_per_process,
+                    new_off_cpu.max_threads_per_process,
+                    "inputs.ebpf.profile.off_cpu.max_threads_per_process"
+                ),
                 (
                     off_cpu.disabled,
                     new_off_cpu.disabled,
@@ -3647,11 +4713,18 @@ impl ConfigHandler {
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                ebpf.profile.preprocess.stack_compression,
-                new_ebpf.profile.preprocess.stack_compression,
-                "inputs.ebpf.profile.preprocess.stack_compression"
-            )]
+            [
+                (
+                    ebpf.profile.preprocess.stack_compression,
+                    new_ebpf.profile.preprocess.stack_compression,
+                    "inputs.ebpf.profile.preprocess.stack_compression"
+                ),
+                (
+                    ebpf.profile.preprocess.batch_interval,
+                    new_ebpf.profile.preprocess.batch_interval,
+                    "inputs.ebpf.profile.preprocess.batch_interval"
+                )
+            ]
         );
 
         let unwinding = &mut ebpf.profile.unwinding;
@@ -3720,19 +4793,24 @@ impl ConfigHandler {
                     kprobe.enable_unix_socket,
                     new_kprobe.enable_unix_socket,
                     "inputs.ebpf.socket.kprobe.enable_unix_socket"
-                ),
-                (
-                    kprobe.blacklist.ports,
-                    new_kprobe.blacklist.ports,
-                    "inputs.ebpf.socket.kprobe.blacklist.ports"
-                ),
-                (
-                    kprobe.whitelist.ports,
-                    new_kprobe.whitelist.ports,
-                    "inputs.ebpf.socket.kprobe.whitelist.ports"
                 )
             ]
         );
+        // The kprobe port lists are hot updated within the set_ebpf() callback.
+        if kprobe.blacklist != new_kprobe.blacklist {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.blacklist.ports from {:?} to {:?}.",
+                kprobe.blacklist.ports, new_kprobe.blacklist.ports
+            );
+            kprobe.blacklist = new_kprobe.blacklist.clone();
+        }
+        if kprobe.whitelist != new_kprobe.whitelist {
+            info!(
+                "Update inputs.ebpf.socket.kprobe.whitelist.ports from {:?} to {:?}.",
+                kprobe.whitelist.ports, new_kprobe.whitelist.ports
+            );
+            kprobe.whitelist = new_kprobe.whitelist.clone();
+        }
 
         let sock_ops = &mut ebpf.socket.sock_ops;
         let new_sock_ops = &mut new_ebpf.socket.sock_ops;
@@ -3761,11 +4839,23 @@ impl ConfigHandler {
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                uprobe.tls.enabled,
-                new_uprobe.tls.enabled,
-                "inputs.ebpf.socket.uprobe.tls.enabled"
-            )]
+            [
+                (
+                    uprobe.tls.enabled,
+                    new_uprobe.tls.enabled,
+                    "inputs.ebpf.socket.uprobe.tls.enabled"
+                ),
+                (
+                    uprobe.tls.library_paths,
+                    new_uprobe.tls.library_paths,
+                    "inputs.ebpf.socket.uprobe.tls.library_paths"
+                ),
+                (
+                    uprobe.tls.binary_names,
+                    new_uprobe.tls.binary_names,
+                    "inputs.ebpf.socket.uprobe.tls.binary_names"
+                )
+            ]
         );
         let golang_uprobe = &mut uprobe.golang;
         let new_golang_uprobe = &mut new_uprobe.golang;
@@ -3783,6 +4873,11 @@ impl ConfigHandler {
                     golang_uprobe.tracing_timeout,
                     new_golang_uprobe.tracing_timeout,
                     "inputs.ebpf.socket.uprobe.golang.tracing_timeout"
+                ),
+                (
+                    golang_uprobe.offset_overrides,
+                    new_golang_uprobe.offset_overrides,
+                    "inputs.ebpf.socket.uprobe.golang.offset_overrides"
                 )
             ]
         );
@@ -3864,6 +4959,11 @@ impl ConfigHandler {
                     new_tunning.syscall_trace_id_disabled,
                     "inputs.ebpf.socket.tunning.syscall_trace_id_disabled"
                 ),
+                (
+                    tunning.io_uring_enabled,
+                    new_tunning.io_uring_enabled,
+                    "inputs.ebpf.socket.tunning.io_uring_enabled"
+                ),
                 (
                     tunning.max_capture_rate,
                     new_tunning.max_capture_rate,
@@ -3884,6 +4984,16 @@ impl ConfigHandler {
                     new_tunning.collector_queue_size,
                     "inputs.ebpf.tunning.collector_queue_size"
                 ),
+                (
+                    tunning.collector_queue_overflow_policy,
+                    new_tunning.collector_queue_overflow_policy,
+                    "inputs.ebpf.tunning.collector_queue_overflow_policy"
+                ),
+                (
+                    tunning.collector_queue_block_timeout,
+                    new_tunning.collector_queue_block_timeout,
+                    "inputs.ebpf.tunning.collector_queue_block_timeout"
+                ),
                 (
                     tunning.kernel_ring_size,
                     new_tunning.kernel_ring_size,
@@ -3949,6 +5059,26 @@ impl ConfigHandler {
                     new_integration.listen_port,
                     "inputs.integration.listen_port"
                 ),
+                (
+                    integration.listen_address,
+                    new_integration.listen_address,
+                    "inputs.integration.listen_address"
+                ),
+                (
+                    integration.listen_unix_socket_path,
+                    new_integration.listen_unix_socket_path,
+                    "inputs.integration.listen_unix_socket_path"
+                ),
+                (
+                    integration.otlp_grpc_listen_port,
+                    new_integration.otlp_grpc_listen_port,
+                    "inputs.integration.otlp_grpc_listen_port"
+                ),
+                (
+                    integration.statsd,
+                    new_integration.statsd,
+                    "inputs.integration.statsd"
+                ),
                 (
                     integration.prometheus_extra_labels,
                     new_integration.prometheus_extra_labels,
@@ -4227,6 +5357,42 @@ impl ConfigHandler {
                     .set_nic_rate(new_tx_throughput.trigger_threshold);
             }
         }
+        if tx_throughput.recovery_threshold != new_tx_throughput.recovery_threshold {
+            info!(
+                "Update global.circuit_breakers.tx_throughput.recovery_threshold from {:?} to {:?}.",
+                tx_throughput.recovery_threshold, new_tx_throughput.recovery_threshold
+            );
+            tx_throughput.recovery_threshold = new_tx_throughput.recovery_threshold;
+            if let Some(components) = &components {
+                components
+                    .npb_bandwidth_watcher
+                    .set_nic_recovery_rate(new_tx_throughput.recovery_threshold);
+            }
+        }
+        if tx_throughput.min_hold_time != new_tx_throughput.min_hold_time {
+            info!(
+                "Update global.circuit_breakers.tx_throughput.min_hold_time from {:?} to {:?}.",
+                tx_throughput.min_hold_time, new_tx_throughput.min_hold_time
+            );
+            tx_throughput.min_hold_time = new_tx_throughput.min_hold_time;
+            if let Some(components) = &components {
+                components
+                    .npb_bandwidth_watcher
+                    .set_min_hold_time(new_tx_throughput.min_hold_time);
+            }
+        }
+        if tx_throughput.over_limit_action != new_tx_throughput.over_limit_action {
+            info!(
+                "Update global.circuit_breakers.tx_throughput.over_limit_action from {:?} to {:?}.",
+                tx_throughput.over_limit_action, new_tx_throughput.over_limit_action
+            );
+            tx_throughput.over_limit_action = new_tx_throughput.over_limit_action;
+            if let Some(components) = &components {
+                components
+                    .npb_bandwidth_watcher
+                    .set_over_limit_action(new_tx_throughput.over_limit_action);
+            }
+        }
         if tx_throughput.throughput_monitoring_interval
             != new_tx_throughput.throughput_monitoring_interval
         {
@@ -4379,6 +5545,28 @@ impl ConfigHandler {
             communication.ingester_traffic_overflow_action =
                 new_communication.ingester_traffic_overflow_action;
         }
+        if communication.ingester_reconnect_backoff_base
+            != new_communication.ingester_reconnect_backoff_base
+        {
+            info!(
+                "Update global.communication.ingester_reconnect_backoff_base from {:?} to {:?}.",
+                communication.ingester_reconnect_backoff_base,
+                new_communication.ingester_reconnect_backoff_base
+            );
+            communication.ingester_reconnect_backoff_base =
+                new_communication.ingester_reconnect_backoff_base;
+        }
+        if communication.ingester_reconnect_backoff_max
+            != new_communication.ingester_reconnect_backoff_max
+        {
+            info!(
+                "Update global.communication.ingester_reconnect_backoff_max from {:?} to {:?}.",
+                communication.ingester_reconnect_backoff_max,
+                new_communication.ingester_reconnect_backoff_max
+            );
+            communication.ingester_reconnect_backoff_max =
+                new_communication.ingester_reconnect_backoff_max;
+        }
         if communication.ingester_ip != new_communication.ingester_ip {
             info!(
                 "Update global.communication.ingester_ip from {:?} to {:?}.",
@@ -4386,6 +5574,35 @@ impl ConfigHandler {
             );
             communication.ingester_ip = new_communication.ingester_ip.clone();
         }
+        if communication.ingester_failover_ips != new_communication.ingester_failover_ips {
+            info!(
+                "Update global.communication.ingester_failover_ips from {:?} to {:?}.",
+                communication.ingester_failover_ips, new_communication.ingester_failover_ips
+            );
+            communication.ingester_failover_ips = new_communication.ingester_failover_ips.clone();
+        }
+        if communication.ingester_failover_threshold
+            != new_communication.ingester_failover_threshold
+        {
+            info!(
+                "Update global.communication.ingester_failover_threshold from {:?} to {:?}.",
+                communication.ingester_failover_threshold,
+                new_communication.ingester_failover_threshold
+            );
+            communication.ingester_failover_threshold =
+                new_communication.ingester_failover_threshold;
+        }
+        if communication.ingester_failback_hold_down
+            != new_communication.ingester_failback_hold_down
+        {
+            info!(
+                "Update global.communication.ingester_failback_hold_down from {:?} to {:?}.",
+                communication.ingester_failback_hold_down,
+                new_communication.ingester_failback_hold_down
+            );
+            communication.ingester_failback_hold_down =
+                new_communication.ingester_failback_hold_down;
+        }
         if communication.ingester_port != new_communication.ingester_port {
             info!(
                 "Update global.communication.ingester_port from {:?} to {:?}.",
@@ -4393,6 +5610,21 @@ impl ConfigHandler {
             );
             communication.ingester_port = new_communication.ingester_port;
         }
+        if communication.ingester_tls_ca_file != new_communication.ingester_tls_ca_file {
+            info!(
+                "Update global.communication.ingester_tls_ca_file from {:?} to {:?}.",
+                communication.ingester_tls_ca_file, new_communication.ingester_tls_ca_file
+            );
+            communication.ingester_tls_ca_file = new_communication.ingester_tls_ca_file.clone();
+        }
+        if communication.ingester_tls_server_name != new_communication.ingester_tls_server_name {
+            info!(
+                "Update global.communication.ingester_tls_server_name from {:?} to {:?}.",
+                communication.ingester_tls_server_name, new_communication.ingester_tls_server_name
+            );
+            communication.ingester_tls_server_name =
+                new_communication.ingester_tls_server_name.clone();
+        }
         if communication.max_escape_duration != new_communication.max_escape_duration {
             info!(
                 "Update global.communication.max_escape_duration from {:?} to {:?}.",
@@ -4623,6 +5855,16 @@ impl ConfigHandler {
                     standalone_mode.max_data_file_size,
                     new_standalone_mode.max_data_file_size,
                     "global.standalone_mode.max_data_file_size"
+                ),
+                (
+                    standalone_mode.data_file_rotation_interval,
+                    new_standalone_mode.data_file_rotation_interval,
+                    "global.standalone_mode.data_file_rotation_interval"
+                ),
+                (
+                    standalone_mode.max_data_files,
+                    new_standalone_mode.max_data_files,
+                    "global.standalone_mode.max_data_files"
                 )
             ]
         );
@@ -4743,15 +5985,48 @@ impl ConfigHandler {
             );
             socket.multiple_sockets_to_ingester = new_socket.multiple_sockets_to_ingester;
         }
+        if socket.sockets_per_sender != new_socket.sockets_per_sender {
+            info!(
+                "Update outputs.socket.sockets_per_sender from {:?} to {:?}.",
+                socket.sockets_per_sender, new_socket.sockets_per_sender
+            );
+            socket.sockets_per_sender = new_socket.sockets_per_sender;
+        }
+        if socket.multiple_sockets_balance != new_socket.multiple_sockets_balance {
+            info!(
+                "Update outputs.socket.multiple_sockets_balance from {:?} to {:?}.",
+                socket.multiple_sockets_balance, new_socket.multiple_sockets_balance
+            );
+            socket.multiple_sockets_balance = new_socket.multiple_sockets_balance;
+        }
+        if socket.tunning != new_socket.tunning {
+            info!(
+                "Update outputs.socket.tunning from {:?} to {:?}.",
+                socket.tunning, new_socket.tunning
+            );
+            socket.tunning = new_socket.tunning;
+        }
         update_fields_with_restart_reason!(
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                socket.raw_udp_qos_bypass,
-                new_socket.raw_udp_qos_bypass,
-                "outputs.socket.raw_udp_qos_bypass"
-            )]
+            [
+                (
+                    socket.raw_udp_qos_bypass,
+                    new_socket.raw_udp_qos_bypass,
+                    "outputs.socket.raw_udp_qos_bypass"
+                ),
+                (
+                    socket.data_compression,
+                    new_socket.data_compression,
+                    "outputs.socket.data_compression"
+                ),
+                (
+                    socket.data_compression_level,
+                    new_socket.data_compression_level,
+                    "outputs.socket.data_compression_level"
+                ),
+            ]
         );
         if socket.data_socket_type != new_socket.data_socket_type {
             info!(
@@ -4802,6 +6077,17 @@ impl ConfigHandler {
             filters.l7_ignored_observation_points =
                 new_filters.l7_ignored_observation_points.clone();
         }
+        if filters.l4_observation_point_sampling_ratios
+            != new_filters.l4_observation_point_sampling_ratios
+        {
+            info!(
+                "Update outputs.flow_log.filters.l4_observation_point_sampling_ratios from {:?} to {:?}.",
+                filters.l4_observation_point_sampling_ratios,
+                new_filters.l4_observation_point_sampling_ratios
+            );
+            filters.l4_observation_point_sampling_ratios =
+                new_filters.l4_observation_point_sampling_ratios.clone();
+        }
         let aggregators = &mut flow_log.aggregators;
         let new_aggregators = &mut new_flow_log.aggregators;
         if aggregators.aggregate_health_check_l4_flow_log
@@ -4832,6 +6118,13 @@ impl ConfigHandler {
             );
             throttles.l7_throttle = new_throttles.l7_throttle;
         }
+        if throttles.l7_throttle_overrides != new_throttles.l7_throttle_overrides {
+            info!(
+                "Update outputs.flow_log.throttles.l7_throttle_overrides from {:?} to {:?}.",
+                throttles.l7_throttle_overrides, new_throttles.l7_throttle_overrides
+            );
+            throttles.l7_throttle_overrides = new_throttles.l7_throttle_overrides.clone();
+        }
 
         let tunning = &mut flow_log.tunning;
         let new_tunning = &mut new_flow_log.tunning;
@@ -4916,6 +6209,46 @@ impl ConfigHandler {
                 "outputs.flow_metrics.tunning.sender_queue_size"
             )]
         );
+        if tunning.second_metrics_shedding_enabled != new_tunning.second_metrics_shedding_enabled {
+            info!(
+                "Update outputs.flow_metrics.tunning.second_metrics_shedding_enabled from {:?} to {:?}.",
+                tunning.second_metrics_shedding_enabled, new_tunning.second_metrics_shedding_enabled
+            );
+            tunning.second_metrics_shedding_enabled = new_tunning.second_metrics_shedding_enabled;
+        }
+        if tunning.second_metrics_shedding_high_watermark
+            != new_tunning.second_metrics_shedding_high_watermark
+        {
+            info!(
+                "Update outputs.flow_metrics.tunning.second_metrics_shedding_high_watermark from {:?} to {:?}.",
+                tunning.second_metrics_shedding_high_watermark,
+                new_tunning.second_metrics_shedding_high_watermark
+            );
+            tunning.second_metrics_shedding_high_watermark =
+                new_tunning.second_metrics_shedding_high_watermark;
+        }
+        if tunning.second_metrics_shedding_low_watermark
+            != new_tunning.second_metrics_shedding_low_watermark
+        {
+            info!(
+                "Update outputs.flow_metrics.tunning.second_metrics_shedding_low_watermark from {:?} to {:?}.",
+                tunning.second_metrics_shedding_low_watermark,
+                new_tunning.second_metrics_shedding_low_watermark
+            );
+            tunning.second_metrics_shedding_low_watermark =
+                new_tunning.second_metrics_shedding_low_watermark;
+        }
+        if tunning.second_metrics_shedding_intervals
+            != new_tunning.second_metrics_shedding_intervals
+        {
+            info!(
+                "Update outputs.flow_metrics.tunning.second_metrics_shedding_intervals from {:?} to {:?}.",
+                tunning.second_metrics_shedding_intervals,
+                new_tunning.second_metrics_shedding_intervals
+            );
+            tunning.second_metrics_shedding_intervals =
+                new_tunning.second_metrics_shedding_intervals;
+        }
 
         let npb = &mut outputs.npb;
         let new_npb = &mut new_outputs.npb;
@@ -4931,6 +6264,7 @@ impl ConfigHandler {
             !first_run,
             agent_restart_reasons,
             [
+                (npb.enabled, new_npb.enabled, "outputs.npb.enabled"),
                 (
                     npb.overlay_vlan_header_trimming,
                     new_npb.overlay_vlan_header_trimming,
@@ -4976,25 +6310,43 @@ impl ConfigHandler {
             );
             npb.raw_udp_vlan_tag = new_npb.raw_udp_vlan_tag;
         }
+        if npb.underlay_hop_limit != new_npb.underlay_hop_limit {
+            info!(
+                "Update outputs.npb.underlay_hop_limit from {:?} to {:?}.",
+                npb.underlay_hop_limit, new_npb.underlay_hop_limit
+            );
+            npb.underlay_hop_limit = new_npb.underlay_hop_limit;
+        }
         update_fields_with_restart_reason!(
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                npb.target_port,
-                new_npb.target_port,
-                "outputs.npb.target_port"
-            )]
+            [
+                (
+                    npb.target_port,
+                    new_npb.target_port,
+                    "outputs.npb.target_port"
+                ),
+                (
+                    npb.vxlan_encapsulation,
+                    new_npb.vxlan_encapsulation,
+                    "outputs.npb.vxlan_encapsulation"
+                )
+            ]
         );
         update_fields_with_restart_reason!(
             restart_agent,
             !first_run,
             agent_restart_reasons,
-            [(
-                outputs.compression,
-                new_outputs.compression,
-                "outputs.compression"
-            )]
+            [
+                (
+                    outputs.compression,
+                    new_outputs.compression,
+                    "outputs.compression"
+                ),
+                (outputs.kafka, new_outputs.kafka, "outputs.kafka"),
+                (outputs.otlp, new_outputs.otlp, "outputs.otlp"),
+            ]
         );
 
         // plugins
@@ -5100,9 +6452,9 @@ impl ConfigHandler {
                     "processors.packet.tcp_header.block_size"
                 ),
                 (
-                    tcp_header.header_fields_flag,
-                    new_tcp_header.header_fields_flag,
-                    "processors.packet.tcp_header.header_fields_flag"
+                    tcp_header.header_fields,
+                    new_tcp_header.header_fields,
+                    "processors.packet.tcp_header.header_fields"
                 ),
                 (
                     tcp_header.sender_queue_size,
@@ -5325,6 +6677,11 @@ impl ConfigHandler {
                     new_filters.port_number_prefilters,
                     "processors.request_log.filters.port_number_prefilters"
                 ),
+                (
+                    filters.port_prefilter_mode,
+                    new_filters.port_prefilter_mode,
+                    "processors.request_log.filters.port_prefilter_mode"
+                ),
                 (
                     filters.tag_filters,
                     new_filters.tag_filters,
@@ -5761,6 +7118,45 @@ impl ConfigHandler {
             candidate_config.handler = new_config.handler.clone();
         }
 
+        let old_tries = &candidate_config.log_parser.l7_log_blacklist_trie;
+        let new_tries = &mut new_config.log_parser.l7_log_blacklist_trie;
+        if first_run || old_tries != new_tries {
+            let old_counters = old_tries
+                .iter()
+                .flat_map(|(p, t)| t.counters(*p).map(|(m, _)| m))
+                .collect::<Vec<_>>();
+            stats_collector
+                .deregister_countables(old_counters.iter().map(|m| m as &dyn stats::Module));
+            let (modules, countables): (Vec<_>, Vec<_>) =
+                new_tries.iter().flat_map(|(p, t)| t.counters(*p)).unzip();
+            stats_collector.register_countables(
+                modules
+                    .iter()
+                    .map(|m| m as &dyn stats::Module)
+                    .zip(countables),
+            );
+        } else {
+            // keep the tries whose hit counters are registered
+            *new_tries = old_tries.clone();
+        }
+
+        let old_trie = &candidate_config.log_parser.unconcerned_dns_nxdomain_trie;
+        let new_trie = &mut new_config.log_parser.unconcerned_dns_nxdomain_trie;
+        if first_run || old_trie != new_trie {
+            let old_counters = old_trie.counters().map(|(m, _)| m).collect::<Vec<_>>();
+            stats_collector
+                .deregister_countables(old_counters.iter().map(|m| m as &dyn stats::Module));
+            let (modules, countables): (Vec<_>, Vec<_>) = new_trie.counters().unzip();
+            stats_collector.register_countables(
+                modules
+                    .iter()
+                    .map(|m| m as &dyn stats::Module)
+                    .zip(countables),
+            );
+        } else {
+            *new_trie = old_trie.clone();
+        }
+
         // this comparison does not include custom_protocol_config and custom_field_policies
         // do not copy these two fields when log parser config changed
         if candidate_config.log_parser != new_config.log_parser {
@@ -6010,6 +7406,32 @@ mod tests {
         thread,
     };
 
+    #[cfg(all(unix, feature = "libtrace"))]
+    #[test]
+    fn ebpf_unwinding_config() {
+        let yaml = r#"
+inputs:
+  ebpf:
+    profile:
+      unwinding:
+        dwarf_disabled: false
+        dwarf_regex: ^java$
+        dwarf_process_map_size: 2048
+        dwarf_shard_map_size: 256
+"#;
+        let user_config: UserConfig = serde_yaml::from_str(yaml).unwrap();
+        let static_config = Config {
+            controller_ips: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        };
+        let config = ModuleConfig::try_from((static_config, user_config)).unwrap();
+        let unwinding = &config.ebpf.ebpf.profile.unwinding;
+        assert!(!unwinding.dwarf_disabled);
+        assert_eq!(unwinding.dwarf_regex, "^java$");
+        assert_eq!(unwinding.dwarf_process_map_size, 2048);
+        assert_eq!(unwinding.dwarf_shard_map_size, 256);
+    }
+
     #[test]
     fn test_new_trie() {
         let trie = HttpEndpointTrie::new();
@@ -6111,6 +7533,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn trace_format_parse() {
+        assert_eq!(
+            TraceType::from("Uber-Trace-Id:jaeger"),
+            TraceType::Formatted("uber-trace-id".to_string(), TraceFormat::Jaeger)
+        );
+        assert_eq!(
+            TraceType::from("x-amzn-trace-id : xray"),
+            TraceType::Formatted("x-amzn-trace-id".to_string(), TraceFormat::XRay)
+        );
+        // unknown formats are treated as header names
+        assert_eq!(
+            TraceType::from("x-id:unknown"),
+            TraceType::Customize("x-id:unknown".to_string())
+        );
+        assert!(TraceType::from("X-Amzn-Trace-Id:xray").check("x-amzn-trace-id"));
+        assert_eq!(
+            TraceType::from("X-Amzn-Trace-Id:xray").as_str(),
+            "x-amzn-trace-id"
+        );
+    }
+
+    #[test]
+    fn trace_format_id_decode() {
+        let testcases = vec![
+            (
+                TraceFormat::W3c,
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                Some("4bf92f3577b34da6a3ce929d0e0e4736"),
+                Some("00f067aa0ba902b7"),
+            ),
+            (TraceFormat::W3c, "00-trace_id-span_id-01", None, None),
+            (
+                TraceFormat::Sw8,
+                "1-dHJhY2VfaWQ=-c3Bhbg==-3-x-x-x-x",
+                Some("trace_id"),
+                Some("span-3"),
+            ),
+            (TraceFormat::Sw8, "1-dHJhY2VfaWQ=", None, None),
+            (
+                TraceFormat::Jaeger,
+                "5c4e8a3d1b2f6a7e:a2fb4a1d1a96d312:0:1",
+                Some("5c4e8a3d1b2f6a7e"),
+                Some("a2fb4a1d1a96d312"),
+            ),
+            (
+                TraceFormat::Jaeger,
+                "5c4e8a3d1b2f6a7e%3Aa2fb4a1d1a96d312%3A0%3A1",
+                Some("5c4e8a3d1b2f6a7e"),
+                Some("a2fb4a1d1a96d312"),
+            ),
+            (TraceFormat::Jaeger, "trace:span", None, None),
+            (
+                TraceFormat::XRay,
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+                Some("1-5759e988-bd862e3fe1be46a994272793"),
+                Some("53995c3f42cd8ad8"),
+            ),
+            (
+                TraceFormat::XRay,
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1",
+                Some("1-5759e988-bd862e3fe1be46a994272793"),
+                None,
+            ),
+            (TraceFormat::XRay, "Root=abc;Parent=xyz", None, None),
+            (
+                TraceFormat::Plain,
+                " abc-123 ",
+                Some("abc-123"),
+                Some("abc-123"),
+            ),
+            (TraceFormat::Plain, "", None, None),
+        ];
+        for (format, value, tid, sid) in testcases {
+            let tt = TraceType::Formatted("x-trace".to_string(), format);
+            assert_eq!(
+                tt.decode_trace_id(value).as_ref().map(|s| s.as_ref()),
+                tid,
+                "{format:?} {value}"
+            );
+            assert_eq!(
+                tt.decode_span_id(value).as_ref().map(|s| s.as_ref()),
+                sid,
+                "{format:?} {value}"
+            );
+        }
+    }
+
     #[test]
     fn test_domain_name_trie() {
         let mut trie = DomainNameTrie::default();
@@ -6127,6 +7637,116 @@ mod tests {
         assert!(!trie.is_unconcerned("yyy.zzz"));
     }
 
+    #[test]
+    fn test_domain_name_trie_wildcard() {
+        let trie = DomainNameTrie::from(&vec![
+            "*.svc.cluster.local".to_string(),
+            "ns-??.corp.example".to_string(),
+            ".internal".to_string(),
+            "Example.COM.".to_string(),
+        ]);
+
+        assert!(trie.is_unconcerned("api.default.svc.cluster.local"));
+        assert!(trie.is_unconcerned("API.Default.SVC.cluster.local."));
+        assert!(!trie.is_unconcerned("svc.cluster.local"));
+        assert!(!trie.is_unconcerned("api.default.svc.cluster.localhost"));
+
+        assert!(trie.is_unconcerned("ns-01.corp.example"));
+        assert!(trie.is_unconcerned("www.ns-ab.corp.example"));
+        assert!(!trie.is_unconcerned("ns-1.corp.example"));
+        assert!(!trie.is_unconcerned("ns-001.corp.example"));
+
+        assert!(trie.is_unconcerned("internal"));
+        assert!(trie.is_unconcerned("db.internal"));
+        assert!(!trie.is_unconcerned("notinternal"));
+        assert!(!trie.is_unconcerned("db.notinternal"));
+
+        assert!(trie.is_unconcerned("www.example.com"));
+
+        let hits = trie
+            .rules
+            .iter()
+            .map(|(p, c)| (p.as_str(), c.hits.load(Ordering::Relaxed)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hits,
+            vec![
+                ("*.svc.cluster.local", 2),
+                ("ns-??.corp.example", 2),
+                ("internal", 2),
+                ("example.com", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_blacklist_trie_regex() {
+        let rule = |operator: &str, value: &str| TagFilterOperator {
+            field_name: "endpoint".to_string(),
+            operator: operator.to_string(),
+            value: value.to_string(),
+        };
+        let trie = BlacklistTrie::new(vec![
+            rule("regex", "^/(healthz|livez|readyz)"),
+            rule("prefix", "/metrics"),
+            rule("regex", "(invalid"),
+        ])
+        .unwrap();
+        assert_eq!(trie.counters.len(), 2);
+
+        assert!(trie.endpoint.is_on_blacklist("/healthz"));
+        assert!(trie.endpoint.is_on_blacklist("/readyz/ping"));
+        assert!(trie.endpoint.is_on_blacklist("/metrics/cpu"));
+        assert!(!trie.endpoint.is_on_blacklist("/api/healthz"));
+        assert!(!trie.endpoint.is_on_blacklist(""));
+        let hits = trie
+            .counters
+            .iter()
+            .map(|(_, c)| c.hits.load(Ordering::Relaxed))
+            .collect::<Vec<_>>();
+        assert_eq!(hits, vec![2, 1]);
+
+        let trie = BlacklistTrie::new(vec![rule("not-regex", "^/api/")]).unwrap();
+        assert!(trie.endpoint.is_on_blacklist("/healthz"));
+        assert!(!trie.endpoint.is_on_blacklist("/api/users"));
+    }
+
+    #[test]
+    fn test_blacklist_trie_numeric() {
+        let rule = |field_name: &str, operator: &str, value: &str| TagFilterOperator {
+            field_name: field_name.to_string(),
+            operator: operator.to_string(),
+            value: value.to_string(),
+        };
+        assert!(BlacklistTrie::validate_rule(&rule("response_code", ">=", "500")).is_ok());
+        assert!(BlacklistTrie::validate_rule(&rule("response_duration", "<", "200ms")).is_ok());
+        assert!(BlacklistTrie::validate_rule(&rule("endpoint", ">", "1")).is_err());
+        assert!(BlacklistTrie::validate_rule(&rule("response_code", "<", "5xx")).is_err());
+
+        // rules are ORed, logs matching any of them are dropped
+        let trie = BlacklistTrie::new(vec![
+            rule("response_code", "<", "500"),
+            rule("response_duration", "<=", "200ms"),
+        ])
+        .unwrap();
+        assert!(trie.has_numeric_rules());
+        let tags = |code: i64, duration: i64| NumericTags {
+            response_code: Some(code),
+            response_duration: Some(duration),
+            ..Default::default()
+        };
+        assert!(!trie.numeric_is_on_blacklist(&tags(502, 200_001)));
+        assert!(trie.numeric_is_on_blacklist(&tags(200, 300_000)));
+        assert!(trie.numeric_is_on_blacklist(&tags(502, 200_000)));
+        assert!(!trie.numeric_is_on_blacklist(&NumericTags::default()));
+
+        let trie = BlacklistTrie::new(vec![rule("request_length", ">", "1024")]).unwrap();
+        assert!(trie.numeric_is_on_blacklist(&NumericTags {
+            request_length: Some(1025),
+            ..Default::default()
+        }));
+    }
+
     #[cfg(any(target_os = "linux", target_os = "android"))]
     fn available_cpu_ids(cpu_set: &CpuSet) -> Vec<usize> {
         (0..libc::CPU_SETSIZE as usize)
@@ -18,9 +18,11 @@ pub mod config;
 pub mod handler;
 
 pub use config::{
-    AgentIdType, Config, ConfigError, DpdkSource, InferenceWhitelist, KubernetesPollerType,
-    OracleConfig, PcapStream, PrometheusExtraLabels, TrafficOverflowAction, UserConfig,
-    K8S_CA_CRT_PATH,
+    AgentIdType, Config, ConfigError, DataCompression, DpdkSource, InferenceWhitelist,
+    KubernetesPollerType, OracleConfig, PcapStream, PrometheusExtraLabels,
+    PrometheusLabelRewriteAction, PrometheusLabelRewriteRule, SignalSourceFilter, SocketBalance,
+    SourceFilter, Statsd, TrafficOverflowAction, TxThroughputAction, UserConfig, VlanPcpMapping,
+    VxlanEncapsulation, K8S_CA_CRT_PATH,
 };
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use config::{ApiResources, ProcessMatcher};
@@ -29,6 +31,6 @@ pub use handler::FlowAccess;
 pub use handler::{DispatcherConfig, FlowConfig, ModuleConfig, NpbConfig};
 
 #[cfg(test)]
-pub use config::{HttpEndpoint, HttpEndpointMatchRule};
+pub use config::{HttpEndpoint, HttpEndpointMatchRule, HttpEndpointRewriteRule};
 #[cfg(test)]
 pub use handler::HttpEndpointTrie;
@@ -0,0 +1,56 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Mutex;
+
+use bincode::{Decode, Encode};
+
+#[derive(Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub struct PcapInterfaceSettings {
+    pub name: String,
+    pub snap_len: u64,
+    pub buffer_size: u64,
+    pub immediate_mode: bool,
+    pub promisc: bool,
+}
+
+#[derive(PartialEq, Eq, Debug, Encode, Decode)]
+pub enum CaptureMessage {
+    PcapInterfaces(Option<Vec<PcapInterfaceSettings>>),
+    Fin,
+}
+
+#[derive(Default)]
+pub struct CaptureDebugger {
+    // effective settings of the opened pcap handles
+    pcap_interfaces: Mutex<Vec<PcapInterfaceSettings>>,
+}
+
+impl CaptureDebugger {
+    pub fn set_pcap_interfaces(&self, settings: Vec<PcapInterfaceSettings>) {
+        let mut pcap_interfaces = self.pcap_interfaces.lock().unwrap();
+        pcap_interfaces.retain(|s| settings.iter().all(|n| n.name != s.name));
+        pcap_interfaces.extend(settings);
+        pcap_interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub(super) fn pcap_interfaces(&self) -> Vec<CaptureMessage> {
+        vec![
+            CaptureMessage::PcapInterfaces(Some(self.pcap_interfaces.lock().unwrap().clone())),
+            CaptureMessage::Fin,
+        ]
+    }
+}
@@ -39,6 +39,7 @@ use super::ebpf::{EbpfDebugger, EbpfMessage};
 #[cfg(target_os = "linux")]
 use super::platform::{PlatformDebugger, PlatformMessage};
 use super::{
+    capture::{CaptureDebugger, CaptureMessage},
     policy::{PolicyDebugger, PolicyMessage},
     rpc::{RpcDebugger, RpcMessage},
     Beacon, Message, Module, BEACON_INTERVAL, BEACON_INTERVAL_MIN, DEEPFLOW_AGENT_BEACON,
@@ -52,6 +53,8 @@ use crate::{
     trident::AgentId,
     utils::command::get_hostname,
 };
+#[cfg(target_os = "linux")]
+use public::utils::net::Link;
 use public::{
     consts::DEFAULT_CONTROLLER_PORT,
     debug::{send_to, Error, QueueDebugger, QueueMessage, Result, MAX_BUF_SIZE},
@@ -65,6 +68,7 @@ struct ModuleDebuggers {
     pub policy: PolicyDebugger,
     #[cfg(all(target_os = "linux", feature = "libtrace"))]
     pub ebpf: EbpfDebugger,
+    pub capture: Arc<CaptureDebugger>,
 }
 
 pub struct Debugger {
@@ -465,6 +469,8 @@ impl Debugger {
                     PlatformMessage::Watcher(w) => debugger
                         .watcher(String::from_utf8(w).map_err(|e| Error::FromUtf8(e.to_string()))?),
                     PlatformMessage::MacMappings(_) => debugger.mac_mapping(),
+                    PlatformMessage::TapInterfaces(_) => debugger.tap_interfaces(),
+                    PlatformMessage::DispatcherInterfaces(_) => debugger.dispatcher_interfaces(),
                     _ => unreachable!(),
                 };
                 iter_send_to(conn.0, conn.1, resp.iter(), serialize_conf)?;
@@ -545,9 +551,28 @@ impl Debugger {
                     EbpfMessage::Cpdbg(_) => {
                         ebpf.cpdbg(conn.0, conn.1, serialize_conf, &req);
                     }
+                    EbpfMessage::RefreshJavaSymbols(_) => {
+                        ebpf.refresh_java_symbols(conn.0, conn.1, serialize_conf, &req);
+                    }
+                    EbpfMessage::Capability => {
+                        ebpf.capability(conn.0, conn.1, serialize_conf);
+                    }
+                    EbpfMessage::UprobeTargets => {
+                        ebpf.uprobe_targets(conn.0, conn.1, serialize_conf);
+                    }
                     _ => unreachable!(),
                 }
             }
+            Module::Capture => {
+                let req: Message<CaptureMessage> =
+                    decode_from_std_read(&mut payload, serialize_conf)?;
+                let debugger = &debuggers.capture;
+                let resp = match req.into_inner() {
+                    CaptureMessage::PcapInterfaces(_) => debugger.pcap_interfaces(),
+                    _ => unreachable!(),
+                };
+                iter_send_to(conn.0, conn.1, resp.iter(), serialize_conf)?;
+            }
             _ => warn!("invalid module or invalid request, skip it"),
         }
 
@@ -573,6 +598,7 @@ impl Debugger {
             policy: PolicyDebugger::new(context.policy_setter),
             #[cfg(all(target_os = "linux", feature = "libtrace"))]
             ebpf: EbpfDebugger::new(),
+            capture: Arc::new(CaptureDebugger::default()),
         };
 
         Self {
@@ -588,6 +614,22 @@ impl Debugger {
         self.debuggers.queue.clone()
     }
 
+    pub fn clone_capture(&self) -> Arc<CaptureDebugger> {
+        self.debuggers.capture.clone()
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn set_tap_interfaces(&self, links: &[Link]) {
+        self.debuggers.platform.set_tap_interfaces(links);
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn set_dispatcher_interfaces(&self, dispatchers: &[Vec<Link>]) {
+        self.debuggers
+            .platform
+            .set_dispatcher_interfaces(dispatchers);
+    }
+
     pub fn notify_stop(&self) -> Option<JoinHandle<()>> {
         if !self.running.swap(false, Ordering::Relaxed) {
             return None;
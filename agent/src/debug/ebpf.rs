@@ -24,12 +24,74 @@ use bincode::{Decode, Encode};
 use libc::{c_char, c_int};
 use log::warn;
 
-use crate::ebpf::{cpdbg_set_config, datadump_set_config};
+use crate::ebpf::{
+    cpdbg_set_config, datadump_set_config, get_java_pids, get_uprobe_targets, java_syms_refresh,
+    java_syms_refresh_result, uprobe_target_info, UPROBE_STATE_ATTACHED, UPROBE_STATE_RETRYING,
+    UPROBE_TARGETS_MAX, UPROBE_TARGET_GOLANG,
+};
+use crate::ebpf_dispatcher::capability::{self, CapabilityReport};
 use public::{
     debug::send_to,
     queue::{bounded, Receiver, Sender},
 };
 
+#[derive(PartialEq, Debug, Encode, Decode)]
+pub struct JavaSymbolsRefresh {
+    pub pid: u32,
+    pub bytes: u64,
+    pub duration_us: u64,
+    // waited for a refresh of the same process already in progress
+    pub coalesced: bool,
+    pub error: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Encode, Decode)]
+pub struct UprobeTarget {
+    pub pid: u32,
+    // golang or openssl
+    pub target: String,
+    // attached, retrying or gave-up
+    pub state: String,
+    pub attempts: u16,
+    pub inode: u64,
+    pub hash: u64,
+    // seconds to the next retry
+    pub next_retry_in: Option<u32>,
+    pub reason: String,
+    pub path: String,
+}
+
+impl UprobeTarget {
+    fn new(info: &uprobe_target_info, uptime: u32) -> Self {
+        let c_str = |b: &[u8]| {
+            let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+            String::from_utf8_lossy(&b[..end]).into_owned()
+        };
+        Self {
+            pid: info.pid as u32,
+            target: if info.target_type == UPROBE_TARGET_GOLANG {
+                "golang"
+            } else {
+                "openssl"
+            }
+            .to_owned(),
+            state: match info.state {
+                UPROBE_STATE_ATTACHED => "attached",
+                UPROBE_STATE_RETRYING => "retrying",
+                _ => "gave-up",
+            }
+            .to_owned(),
+            attempts: info.attempts,
+            inode: info.inode,
+            hash: info.hash,
+            next_retry_in: (info.state == UPROBE_STATE_RETRYING)
+                .then(|| info.next_retry.saturating_sub(uptime)),
+            reason: c_str(&info.reason),
+            path: c_str(&info.path),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Encode, Decode)]
 pub enum EbpfMessage {
     DataDump((u32, String, u8, u16)),
@@ -37,6 +99,14 @@ pub enum EbpfMessage {
     Context((u64, Vec<u8>)),
     Error(String),
     Done,
+    // pid 0 for all Java processes
+    RefreshJavaSymbols(u32),
+    JavaSymbolsRefreshed(JavaSymbolsRefresh),
+    Capability,
+    // None if the eBPF collector is not started
+    CapabilityReport(Option<CapabilityReport>),
+    UprobeTargets,
+    UprobeTarget(UprobeTarget),
 }
 
 pub struct EbpfDebugger {
@@ -48,6 +118,7 @@ static mut EBPF_DEBUG_SENDER: Option<Sender<Vec<u8>>> = None;
 
 impl EbpfDebugger {
     const QUEUE_RECV_TIMEOUT: Duration = Duration::from_secs(1);
+    const JAVA_PIDS_MAX: usize = 4096;
 
     extern "C" fn ebpf_debug(data: *mut c_char, len: c_int) {
         #[allow(static_mut_refs)]
@@ -140,4 +211,96 @@ impl EbpfDebugger {
             warn!("send ebpf item error: {}", e);
         }
     }
+
+    pub fn refresh_java_symbols(
+        &self,
+        sock: &UdpSocket,
+        conn: SocketAddr,
+        serialize_conf: Configuration,
+        msg: &EbpfMessage,
+    ) {
+        let EbpfMessage::RefreshJavaSymbols(pid) = msg else {
+            return;
+        };
+        let pids = if *pid == 0 {
+            let mut pids = vec![0 as c_int; Self::JAVA_PIDS_MAX];
+            let n = unsafe { get_java_pids(pids.as_mut_ptr(), pids.len() as c_int) };
+            pids.truncate(n.max(0) as usize);
+            pids
+        } else {
+            vec![*pid as c_int]
+        };
+        if pids.is_empty() {
+            let msg = EbpfMessage::Error("no java process found".to_owned());
+            if let Err(e) = send_to(&sock, conn, msg, serialize_conf) {
+                warn!("send ebpf item error: {}", e);
+            }
+            return;
+        }
+
+        for pid in pids {
+            let mut res = java_syms_refresh_result::default();
+            unsafe {
+                java_syms_refresh(pid, &mut res);
+            }
+            let error = match -res.error {
+                0 => None,
+                libc::ENOENT => Some("not a java process known to the profiler".to_owned()),
+                e => Some(std::io::Error::from_raw_os_error(e).to_string()),
+            };
+            let msg = EbpfMessage::JavaSymbolsRefreshed(JavaSymbolsRefresh {
+                pid: pid as u32,
+                bytes: res.file_size.max(0) as u64,
+                duration_us: res.cost_us,
+                coalesced: res.coalesced,
+                error,
+            });
+            if let Err(e) = send_to(&sock, conn, msg, serialize_conf) {
+                warn!("send ebpf item error: {}", e);
+            }
+        }
+        if let Err(e) = send_to(&sock, conn, EbpfMessage::Done, serialize_conf) {
+            warn!("send ebpf item error: {}", e);
+        }
+    }
+
+    pub fn uprobe_targets(
+        &self,
+        sock: &UdpSocket,
+        conn: SocketAddr,
+        serialize_conf: Configuration,
+    ) {
+        let mut infos = vec![uprobe_target_info::default(); UPROBE_TARGETS_MAX];
+        let n = unsafe { get_uprobe_targets(infos.as_mut_ptr(), infos.len() as c_int) };
+        infos.truncate(n.max(0) as usize);
+        let uptime = Self::uptime_secs();
+        for info in infos.iter() {
+            let msg = EbpfMessage::UprobeTarget(UprobeTarget::new(info, uptime));
+            if let Err(e) = send_to(&sock, conn, msg, serialize_conf) {
+                warn!("send ebpf item error: {}", e);
+            }
+        }
+        if let Err(e) = send_to(&sock, conn, EbpfMessage::Done, serialize_conf) {
+            warn!("send ebpf item error: {}", e);
+        }
+    }
+
+    // same as get_sys_uptime() in eBPF
+    fn uptime_secs() -> u32 {
+        let mut info: libc::sysinfo = unsafe { std::mem::zeroed() };
+        if unsafe { libc::sysinfo(&mut info) } != 0 {
+            return 0;
+        }
+        info.uptime as u32
+    }
+
+    pub fn capability(&self, sock: &UdpSocket, conn: SocketAddr, serialize_conf: Configuration) {
+        let msg = EbpfMessage::CapabilityReport(capability::report());
+        if let Err(e) = send_to(&sock, conn, msg, serialize_conf) {
+            warn!("send ebpf item error: {}", e);
+        }
+        if let Err(e) = send_to(&sock, conn, EbpfMessage::Done, serialize_conf) {
+            warn!("send ebpf item error: {}", e);
+        }
+    }
 }
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+mod capture;
 mod debugger;
 #[cfg(all(target_os = "linux", feature = "libtrace"))]
 mod ebpf;
@@ -23,6 +24,7 @@ mod policy;
 mod rpc;
 
 use bincode::{Decode, Encode};
+pub use capture::{CaptureDebugger, CaptureMessage, PcapInterfaceSettings};
 pub use debugger::{Client, ConstructDebugCtx, Debugger};
 #[cfg(all(target_os = "linux", feature = "libtrace"))]
 pub use ebpf::EbpfMessage;
@@ -54,6 +56,7 @@ pub enum Module {
     Policy,
     #[cfg(all(target_os = "linux", feature = "libtrace"))]
     Ebpf,
+    Capture,
 }
 
 impl Default for Module {
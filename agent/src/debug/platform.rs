@@ -14,29 +14,79 @@
  * limitations under the License.
  */
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use bincode::{Decode, Encode};
 
 use crate::platform::{ApiWatcher, GenericPoller, Poller};
+use public::utils::net::Link;
 
 #[derive(PartialEq, Eq, Debug, Encode, Decode)]
 pub enum PlatformMessage {
     Version(Option<String>),
     Watcher(Vec<u8>),
     MacMappings(Option<Vec<(u32, String)>>),
+    TapInterfaces(Option<Vec<(u32, String)>>),
     Fin,
     NotFound,
+    DispatcherInterfaces(Option<Vec<(u32, u32, String)>>),
 }
 
 pub struct PlatformDebugger {
     api: Arc<ApiWatcher>,
     poller: Arc<GenericPoller>,
+    // interfaces captured by the dispatchers after include and exclude regex
+    tap_interfaces: Mutex<Vec<(u32, String)>>,
+    // (dispatcher, if_index, name) of the explicit interface dispatcher assignment
+    dispatcher_interfaces: Mutex<Vec<(u32, u32, String)>>,
 }
 
 impl PlatformDebugger {
     pub(super) fn new(api: Arc<ApiWatcher>, poller: Arc<GenericPoller>) -> Self {
-        Self { api, poller }
+        Self {
+            api,
+            poller,
+            tap_interfaces: Mutex::new(vec![]),
+            dispatcher_interfaces: Mutex::new(vec![]),
+        }
+    }
+
+    pub(super) fn set_tap_interfaces(&self, links: &[Link]) {
+        let mut interfaces = links
+            .iter()
+            .map(|l| (l.if_index, l.name.clone()))
+            .collect::<Vec<_>>();
+        interfaces.sort();
+        interfaces.dedup();
+        *self.tap_interfaces.lock().unwrap() = interfaces;
+    }
+
+    pub(super) fn tap_interfaces(&self) -> Vec<PlatformMessage> {
+        vec![
+            PlatformMessage::TapInterfaces(Some(self.tap_interfaces.lock().unwrap().clone())),
+            PlatformMessage::Fin,
+        ]
+    }
+
+    pub(super) fn set_dispatcher_interfaces(&self, dispatchers: &[Vec<Link>]) {
+        *self.dispatcher_interfaces.lock().unwrap() = dispatchers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, links)| {
+                links
+                    .iter()
+                    .map(move |l| (i as u32, l.if_index, l.name.clone()))
+            })
+            .collect();
+    }
+
+    pub(super) fn dispatcher_interfaces(&self) -> Vec<PlatformMessage> {
+        vec![
+            PlatformMessage::DispatcherInterfaces(Some(
+                self.dispatcher_interfaces.lock().unwrap().clone(),
+            )),
+            PlatformMessage::Fin,
+        ]
     }
 
     pub(super) fn watcher(&self, resource: impl AsRef<str>) -> Vec<PlatformMessage> {
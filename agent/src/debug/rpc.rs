@@ -23,6 +23,10 @@ use tokio::runtime::Runtime;
 use crate::{
     exception::ExceptionHandler,
     rpc::{Session, StaticConfig, Status, Synchronizer},
+    sender::{
+        npb_sender::{self, QosBypass},
+        uniform_sender::{self, SocketHealth},
+    },
     trident::AgentId,
 };
 use public::debug::{Error, Result};
@@ -45,6 +49,10 @@ pub struct ConfigResp {
     revision: String,
     config: String,
     self_update_url: String,
+    // mechanism in effect of outputs.socket.raw_udp_qos_bypass
+    npb_qos_bypass: Option<QosBypass>,
+    // health of the sockets to the ingester
+    ingester_sockets: Vec<SocketHealth>,
 }
 
 #[derive(PartialEq, Debug, Encode, Decode)]
@@ -115,6 +123,8 @@ impl RpcDebugger {
             config: resp.user_config.take().unwrap(),
             version_acls: resp.version_acls(),
             self_update_url: resp.self_update_url.take().unwrap_or_default(),
+            npb_qos_bypass: npb_sender::qos_bypass(),
+            ingester_sockets: uniform_sender::socket_health(),
         };
 
         let c = format!("{:?}", config);
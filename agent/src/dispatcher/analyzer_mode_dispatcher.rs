@@ -18,7 +18,7 @@ use std::{
     collections::HashMap,
     mem::drop,
     ops::Add,
-    sync::{atomic::Ordering, Arc, RwLock},
+    sync::{atomic::Ordering, Arc, RwLock, Weak},
     thread::{self, JoinHandle},
     time::Duration,
 };
@@ -33,6 +33,8 @@ use nix::{
 use packet_dedup::PacketDedupMap;
 
 use super::base_dispatcher::BaseDispatcher;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::queue_cpu_set;
 use super::Packet;
 use crate::{
     common::{
@@ -44,14 +46,18 @@ use crate::{
     dispatcher::{
         base_dispatcher::{BaseDispatcherListener, CaptureNetworkTypeHandler},
         error::Result,
+        flow_generator_thread_name, DispatcherQueueCounter,
+    },
+    flow_generator::{
+        flow_map::{AllocatorStats, Config},
+        FlowMap,
     },
-    flow_generator::{flow_map::Config, FlowMap},
     handler::{MiniPacket, PacketHandler},
     liveness::{self, ComponentId, ComponentSpec, LivenessRegistry},
     rpc::get_timestamp,
     utils::{
         bytes::read_u32_be,
-        stats::{self, Countable, QueueStats},
+        stats::{self, Countable, DispatcherQueueStats, QueueStats},
     },
 };
 use public::{
@@ -257,7 +263,8 @@ impl AnalyzerModeDispatcher {
         let counter = base.counter.clone();
         let analyzer_dedup_disabled = base.analyzer_dedup_disabled;
         let vm_mac_addrs = self.vm_mac_addrs.clone();
-        let mut dedup = PacketDedupMap::new();
+        let mut dedup =
+            PacketDedupMap::new(base.packet_dedup_config, base.packet_dedup_counter.clone());
         let id = base.id;
         let pool_raw_size = self.pool_raw_size;
 
@@ -274,11 +281,12 @@ impl AnalyzerModeDispatcher {
         let stats = base.stats.clone();
         let liveness_registry = self.liveness_registry.clone();
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        let cpu_set = base.options.lock().unwrap().cpu_set;
+        let cpu_set = queue_cpu_set(&base.options, id);
+        let thread_name = flow_generator_thread_name(&base.options, id);
 
         self.flow_generator_thread_handler.replace(
             thread::Builder::new()
-                .name("dispatcher-packet-to-flow-generator".to_owned())
+                .name(thread_name)
                 .spawn(move || {
                     let liveness = liveness::register(
                         liveness_registry.as_ref(),
@@ -525,8 +533,15 @@ impl AnalyzerModeDispatcher {
         );
     }
 
-    fn setup_inner_thread_and_queue(&mut self) -> DebugSender<Packet> {
+    fn setup_inner_thread_and_queue(
+        &mut self,
+    ) -> (DebugSender<Packet>, Arc<DispatcherQueueCounter>) {
         let id = self.base.is.id;
+        let queue_counter = Arc::new(DispatcherQueueCounter::default());
+        self.stats_collector.register_countable(
+            &DispatcherQueueStats { index: id },
+            Countable::Ref(Arc::downgrade(&queue_counter) as Weak<dyn stats::RefCountable>),
+        );
         let name = "0.1-raw-packet-to-flow-generator";
         let (sender_to_parser, receiver_from_dispatcher, counter) =
             bounded_with_debug(self.inner_queue_size, name, &self.queue_debugger);
@@ -545,7 +560,7 @@ impl AnalyzerModeDispatcher {
 
         self.run_flow_generator(receiver_from_dispatcher, sender_to_pipeline);
         self.run_additional_packet_pipeline(receiver_from_flow);
-        return sender_to_parser;
+        return (sender_to_parser, queue_counter);
     }
 
     pub(super) fn run(&mut self) {
@@ -558,7 +573,7 @@ impl AnalyzerModeDispatcher {
                 ..Default::default()
             },
         );
-        let sender_to_parser = self.setup_inner_thread_and_queue();
+        let (sender_to_parser, queue_counter) = self.setup_inner_thread_and_queue();
         let base = &mut self.base.is;
         info!("Start analyzer dispatcher {}", base.log_id);
         let time_diff = base.ntp_diff.load(Ordering::Relaxed);
@@ -566,6 +581,13 @@ impl AnalyzerModeDispatcher {
         let id = base.id;
         let mut batch = Vec::with_capacity(HANDLER_BATCH_SIZE);
         let mut allocator = Allocator::new(self.raw_packet_block_size);
+        self.stats_collector.register_countable(
+            &AllocatorStats {
+                id: id as u32,
+                obj_type: "RawPacket",
+            },
+            Countable::Ref(allocator.counter()),
+        );
         let mut last_liveness = Duration::ZERO;
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = base.options.lock().unwrap().cpu_set;
@@ -584,7 +606,7 @@ impl AnalyzerModeDispatcher {
             let recved = unsafe {
                 BaseDispatcher::recv(
                     &mut self.base.engine,
-                    &base.leaky_bucket,
+                    &mut base.rx_token_bucket,
                     &base.exception_handler,
                     &mut prev_timestamp,
                     &base.counter,
@@ -592,7 +614,7 @@ impl AnalyzerModeDispatcher {
                 )
             };
             if recved.is_none() || batch.len() >= HANDLER_BATCH_SIZE {
-                if let Err(e) = sender_to_parser.send_all(&mut batch) {
+                if let Err(e) = queue_counter.send_all(&sender_to_parser, &mut batch) {
                     debug!("dispatcher {} sender failed: {:?}", id, e);
                     batch.clear();
                 }
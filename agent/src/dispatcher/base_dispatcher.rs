@@ -29,6 +29,8 @@ use std::time::Duration;
 use dns_lookup::lookup_host;
 use log::{error, info, warn};
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::promisc::PromiscState;
 use super::{
     error::{Error, Result},
     recv_engine::{self, bpf, RecvEngine},
@@ -37,6 +39,7 @@ use super::{
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use recv_engine::af_packet::{bpf::*, BpfSyntax};
 
+use packet_dedup::{PacketDedupConfig, PacketDedupCounter};
 use special_recv_engine::Libpcap;
 
 use crate::config::handler::{CollectorAccess, DispatcherAccess, LogParserAccess};
@@ -50,7 +53,7 @@ use crate::{
         DEFAULT_INGESTER_PORT, ETH_HEADER_SIZE, FIELD_OFFSET_ETH_TYPE, VLAN_HEADER_SIZE,
         VLAN_ID_MASK,
     },
-    config::{handler::FlowAccess, DispatcherConfig},
+    config::{handler::FlowAccess, DispatcherConfig, VlanPcpMapping},
     exception::ExceptionHandler,
     flow_generator::AppProto,
     handler::PacketHandlerBuilder,
@@ -66,7 +69,7 @@ use public::{
     proto::agent::{Exception, IfMacSource, PacketCaptureType},
     queue::DebugSender,
     utils::net::{self, get_route_src_ip, Link, MacAddr},
-    LeakyBucket,
+    TokenBucket,
 };
 
 pub(super) struct BaseDispatcher {
@@ -86,7 +89,7 @@ pub(super) struct InternalState {
     pub(super) options: Arc<Mutex<Options>>,
     pub(super) bpf_options: Arc<Mutex<BpfOptions>>,
 
-    pub(super) leaky_bucket: Arc<LeakyBucket>,
+    pub(super) rx_token_bucket: TokenBucket,
     pub(super) handler_builder: Arc<RwLock<Vec<PacketHandlerBuilder>>>,
     pub(super) pipelines: Arc<Mutex<HashMap<u64, Arc<Mutex<Pipeline>>>>>,
     pub(super) tap_interfaces: Arc<Mutex<Vec<Link>>>,
@@ -108,6 +111,8 @@ pub(super) struct InternalState {
     pub(super) tap_interface_whitelist: TapInterfaceWhitelist,
 
     pub(super) analyzer_dedup_disabled: bool,
+    pub(super) packet_dedup_config: PacketDedupConfig,
+    pub(super) packet_dedup_counter: Arc<PacketDedupCounter>,
 
     pub(super) flow_output_queue: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
     pub(super) l7_stats_output_queue: DebugSender<BatchedBox<L7Stats>>,
@@ -134,11 +139,12 @@ pub(super) struct InternalState {
     #[cfg(target_os = "linux")]
     pub(super) netns: public::netns::NsFile,
 
-    pub(super) bond_group_map: HashMap<u32, MacAddr>,
+    pub(super) bond_group_map: Arc<RwLock<HashMap<u32, MacAddr>>>,
 
     // dispatcher id for easy debugging
     pub log_id: String,
-    pub promisc_if_indices: Vec<i32>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) promisc_state: PromiscState,
 }
 
 impl BaseDispatcher {
@@ -207,7 +213,13 @@ impl BaseDispatcher {
         let pcap_interfaces = match public::netns::links_by_name_regex_in_netns(
             &config.tap_interface_regex,
             &self.is.netns,
-        ) {
+        )
+        .and_then(|links| {
+            Ok(net::exclude_links_by_name_regex(
+                links,
+                &config.tap_interface_exclude_regex,
+            )?)
+        }) {
             Err(e) => {
                 warn!("get interfaces by name regex failed: {}", e);
                 vec![]
@@ -215,13 +227,16 @@ impl BaseDispatcher {
             Ok(links) => links,
         };
         #[cfg(any(target_os = "windows", target_os = "android"))]
-        let pcap_interfaces = match net::links_by_name_regex(&config.tap_interface_regex) {
-            Err(e) => {
-                warn!("get interfaces by name regex failed: {}", e);
-                vec![]
-            }
-            Ok(links) => links,
-        };
+        let pcap_interfaces =
+            match net::links_by_name_regex(&config.tap_interface_regex).and_then(|links| {
+                net::exclude_links_by_name_regex(links, &config.tap_interface_exclude_regex)
+            }) {
+                Err(e) => {
+                    warn!("get interfaces by name regex failed: {}", e);
+                    vec![]
+                }
+                Ok(links) => links,
+            };
         let options = self.is.options.lock().unwrap();
         self.engine = if options.capture_mode == PacketCaptureType::Local && options.libpcap_enabled
         {
@@ -230,27 +245,14 @@ impl BaseDispatcher {
                     "libpcap capture must give interface to capture packet".into(),
                 ));
             }
-            #[cfg(target_os = "windows")]
-            let src_ifaces: Vec<_> = pcap_interfaces
-                .iter()
-                .map(|src_iface| (src_iface.device_name.as_str(), src_iface.if_index as isize))
-                .collect();
-            #[cfg(any(target_os = "linux", target_os = "android"))]
-            let src_ifaces: Vec<_> = pcap_interfaces
-                .iter()
-                .map(|src_iface| (src_iface.name.as_str(), src_iface.if_index as isize))
-                .collect();
-            let libpcap = Libpcap::new(
-                src_ifaces.clone(),
-                options.packet_blocks,
-                options.snap_len,
-                &self.is.queue_debugger,
-            )
-            .map_err(|e| Error::Libpcap(e.to_string()))?;
-            info!(
-                "libpcap init with {:?} block {} snap {}",
-                src_ifaces, options.packet_blocks, options.snap_len
+            let src_ifaces = options.pcap_resolver.resolve_links(
+                &pcap_interfaces,
+                &options.pcap_defaults(),
+                &options.promisc_resolver,
             );
+            info!("libpcap init with {:?}", src_ifaces);
+            let libpcap = Libpcap::new(src_ifaces, &self.is.queue_debugger)
+                .map_err(|e| Error::Libpcap(e.to_string()))?;
             self.is.need_update_bpf.store(true, Ordering::Relaxed);
             RecvEngine::Libpcap(Some(libpcap))
         } else {
@@ -262,7 +264,7 @@ impl BaseDispatcher {
 
     pub(super) unsafe fn recv<'a>(
         engine: &'a mut RecvEngine,
-        leaky_bucket: &LeakyBucket,
+        rx_token_bucket: &mut TokenBucket,
         exception_handler: &ExceptionHandler,
         prev_timestamp: &mut Duration,
         counter: &PacketCounter,
@@ -303,11 +305,16 @@ impl BaseDispatcher {
             }
             *prev_timestamp = timestamp;
         }
-        while !leaky_bucket.acquire(1) {
-            counter.get_token_failed.fetch_add(1, Ordering::Relaxed);
+        if !rx_token_bucket.acquire(timestamp, 1) {
+            counter
+                .token_exhausted_drops
+                .fetch_add(1, Ordering::Relaxed);
             exception_handler.set(Exception::RxPpsThresholdExceeded, None);
-            thread::sleep(Duration::from_millis(1));
+            return None;
         }
+        counter
+            .token_burst_high_watermark
+            .fetch_max(rx_token_bucket.used(), Ordering::Relaxed);
 
         counter.rx_all.fetch_add(1, Ordering::Relaxed);
         counter
@@ -348,6 +355,13 @@ impl BaseDispatcher {
             // 最外层隧道封装，可能是ERSPAN或VXLAN
             EthernetType::IPV4 => tunnel_info.decapsulate(packet, l2_len, bitmap),
             EthernetType::IPV6 => tunnel_info.decapsulate_v6(packet, l2_len, bitmap),
+            // MPLS标签紧跟l2层头，返回值即为解封装后的包开始位置
+            EthernetType::MPLS_UNICAST => {
+                return Ok((
+                    tunnel_info.decapsulate_mpls(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
             _ => 0,
         };
         if offset == 0 {
@@ -496,6 +510,13 @@ impl BaseDispatcher {
             // 最外层隧道封装，可能是ERSPAN或VXLAN
             EthernetType::IPV4 => tunnel_info.decapsulate(packet, l2_len, bitmap),
             EthernetType::IPV6 => tunnel_info.decapsulate_v6(packet, l2_len, bitmap),
+            // MPLS标签紧跟l2层头，返回值即为解封装后的包开始位置
+            EthernetType::MPLS_UNICAST => {
+                return Ok((
+                    tunnel_info.decapsulate_mpls(packet, l2_len, bitmap),
+                    tap_type,
+                ));
+            }
             _ => 0,
         };
         if offset == 0 {
@@ -576,30 +597,66 @@ impl InternalState {
             );
         }
 
-        let if_indices = tap_interfaces
-            .iter()
-            .map(|i| i.if_index as i32)
-            .collect::<Vec<i32>>();
         // When the configuration is changed, the deepflow-agent will restart,
         // and the NIC configured in promiscuous mode will be retired
-        if self.options.lock().unwrap().promisc && self.promisc_if_indices != if_indices {
-            if let Err(e) = engine.set_promisc(&self.promisc_if_indices, false) {
-                warn!(
-                    "set_promisc disabled failed with tap_interfaces count {}: {:?}",
-                    self.promisc_if_indices.len(),
-                    e
-                );
-            }
+        self.promisc_state.update(
+            &format!("Dispatcher{}", self.log_id),
+            engine,
+            &tap_interfaces,
+            &self.options.lock().unwrap().promisc_resolver,
+            &self.stats,
+        );
+    }
+}
 
-            if let Err(e) = engine.set_promisc(&if_indices, true) {
-                warn!(
-                    "set_promisc enabled failed with tap_interfaces count {}: {:?}",
-                    if_indices.len(),
-                    e
-                );
-            }
-            self.promisc_if_indices = if_indices;
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) enum PcpCaptureNetworkType {
+    #[default]
+    None,
+    // calculate from vlan id by tap_typer
+    FromVlan,
+    Fixed(CaptureNetworkType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum MirrorTrafficPcp {
+    OuterVlan,
+    InnerVlan,
+    // indexed by pcp of the outer vlan tag
+    Pcp([PcpCaptureNetworkType; VlanPcpMapping::MAX_PCP as usize + 1]),
+}
+
+impl Default for MirrorTrafficPcp {
+    fn default() -> Self {
+        Self::from(&[VlanPcpMapping::default()][..])
+    }
+}
+
+impl From<&[VlanPcpMapping]> for MirrorTrafficPcp {
+    fn from(mappings: &[VlanPcpMapping]) -> Self {
+        match mappings {
+            [VlanPcpMapping {
+                pcp: VlanPcpMapping::OUTER_VLAN,
+                capture_network_type: None,
+            }] => return Self::OuterVlan,
+            [VlanPcpMapping {
+                pcp: VlanPcpMapping::INNER_VLAN,
+                capture_network_type: None,
+            }] => return Self::InnerVlan,
+            _ => (),
+        }
+        let mut pcps = [PcpCaptureNetworkType::None; VlanPcpMapping::MAX_PCP as usize + 1];
+        for m in mappings {
+            let Some(p) = pcps.get_mut(m.pcp as usize) else {
+                continue;
+            };
+            *p = match m.capture_network_type.map(CaptureNetworkType::try_from) {
+                None => PcpCaptureNetworkType::FromVlan,
+                Some(Ok(t)) => PcpCaptureNetworkType::Fixed(t),
+                Some(Err(_)) => continue,
+            };
         }
+        Self::Pcp(pcps)
     }
 }
 
@@ -607,14 +664,11 @@ impl InternalState {
 pub(super) struct CaptureNetworkTypeHandler {
     pub(super) tap_typer: Arc<CaptureNetworkTyper>,
     pub(super) default_tap_type: CaptureNetworkType,
-    pub(super) mirror_traffic_pcp: u16,
+    pub(super) mirror_traffic_pcp: MirrorTrafficPcp,
     pub(super) capture_mode: PacketCaptureType,
 }
 
 impl CaptureNetworkTypeHandler {
-    const OUTER_VLAN: u16 = 8;
-    const INNER_VLAN: u16 = 9;
-
     // returns tap_type, ethernet_type and l2_len
     pub(super) fn get_l2_info(
         &self,
@@ -653,8 +707,8 @@ impl CaptureNetworkTypeHandler {
                     }
                 }
             } else {
-                match self.mirror_traffic_pcp {
-                    Self::OUTER_VLAN => {
+                match &self.mirror_traffic_pcp {
+                    MirrorTrafficPcp::OuterVlan => {
                         if let Some(t) = self
                             .tap_typer
                             .get_tap_type_by_vlan(outer_vlan_tag & VLAN_ID_MASK)
@@ -664,7 +718,7 @@ impl CaptureNetworkTypeHandler {
                             }
                         }
                     }
-                    Self::INNER_VLAN => {
+                    MirrorTrafficPcp::InnerVlan => {
                         if let Some(t) = self
                             .tap_typer
                             .get_tap_type_by_vlan(inner_vlan_tag & VLAN_ID_MASK)
@@ -674,16 +728,20 @@ impl CaptureNetworkTypeHandler {
                             }
                         }
                     }
-                    _ => {
-                        if (outer_vlan_tag >> 13) & 0x7 == self.mirror_traffic_pcp {
-                            if let Some(t) = self
-                                .tap_typer
-                                .get_tap_type_by_vlan(outer_vlan_tag & VLAN_ID_MASK)
-                            {
-                                if t != CaptureNetworkType::Unknown {
-                                    tap_type = t;
+                    MirrorTrafficPcp::Pcp(pcps) => {
+                        match pcps[(outer_vlan_tag >> 13) as usize & 0x7] {
+                            PcpCaptureNetworkType::FromVlan => {
+                                if let Some(t) = self
+                                    .tap_typer
+                                    .get_tap_type_by_vlan(outer_vlan_tag & VLAN_ID_MASK)
+                                {
+                                    if t != CaptureNetworkType::Unknown {
+                                        tap_type = t;
+                                    }
                                 }
                             }
+                            PcpCaptureNetworkType::Fixed(t) => tap_type = t,
+                            PcpCaptureNetworkType::None => (),
                         }
                     }
                 };
@@ -764,7 +822,7 @@ pub struct BaseDispatcherListener {
     pub npb_dedup_enabled: Arc<AtomicBool>,
     pub reset_whitelist: Arc<AtomicBool>,
     pub pause: Arc<AtomicBool>,
-    pub bond_group_map: HashMap<u32, MacAddr>,
+    pub bond_group_map: Arc<RwLock<HashMap<u32, MacAddr>>>,
     capture_bpf: String,
     proxy_controller_ip: String,
     analyzer_ip: String,
@@ -928,9 +986,11 @@ impl BaseDispatcherListener {
                 .collect();
             let bond_mac = self
                 .bond_group_map
+                .read()
+                .unwrap()
                 .get(&(*key as u32))
-                .unwrap_or_else(|| &vm_mac)
-                .clone();
+                .copied()
+                .unwrap_or(vm_mac);
             pipelines.insert(
                 *key,
                 Arc::new(Mutex::new(Pipeline {
@@ -974,6 +1034,83 @@ impl BaseDispatcherListener {
         *tap_interfaces = interfaces;
         self.need_update_bpf.store(true, Ordering::Release);
     }
+
+    // Only the pipelines of the interfaces joining or leaving the bond are updated, and the
+    // capture filter is reloaded if any captured interface is gone.
+    pub(super) fn on_bond_group_change(&self, bond_group: &[String]) {
+        #[cfg(target_os = "linux")]
+        let links = match public::netns::link_list_in_netns(&self.netns) {
+            Ok(links) => links,
+            Err(e) => {
+                warn!("Dispatcher{} link_list failed: {:?}", self.log_id, e);
+                return;
+            }
+        };
+        #[cfg(any(target_os = "windows", target_os = "android"))]
+        let links = match net::link_list() {
+            Ok(links) => links,
+            Err(e) => {
+                warn!("Dispatcher{} link_list failed: {:?}", self.log_id, e);
+                return;
+            }
+        };
+        let new_map = bond_group_map(bond_group, &links);
+        let mut bond_map = self.bond_group_map.write().unwrap();
+        if *bond_map == new_map {
+            return;
+        }
+        let affected = bond_map
+            .keys()
+            .chain(new_map.keys())
+            .filter(|i| bond_map.get(i) != new_map.get(i))
+            .copied()
+            .collect::<HashSet<_>>();
+        *bond_map = new_map;
+
+        let mut updated = 0;
+        for (key, pipeline) in self.pipelines.lock().unwrap().iter() {
+            if !affected.contains(&(*key as u32)) {
+                continue;
+            }
+            let mut pipeline = pipeline.lock().unwrap();
+            pipeline.bond_mac = bond_map
+                .get(&(*key as u32))
+                .copied()
+                .unwrap_or(pipeline.vm_mac);
+            updated += 1;
+        }
+        drop(bond_map);
+
+        let mut tap_interfaces = self.tap_interfaces.lock().unwrap();
+        let len = tap_interfaces.len();
+        tap_interfaces.retain(|i| links.contains(i));
+        if tap_interfaces.len() != len {
+            self.need_update_bpf.store(true, Ordering::Release);
+        }
+        info!(
+            "Dispatcher{} bond group changed to {:?}, {} pipelines updated, {} interfaces gone",
+            self.log_id,
+            bond_group,
+            updated,
+            len - tap_interfaces.len()
+        );
+    }
+}
+
+// Maps the if_index of the bond slaves to the mac of the first slave found
+pub(super) fn bond_group_map(bond_group: &[String], links: &[Link]) -> HashMap<u32, MacAddr> {
+    let mut bond_group_map = HashMap::new();
+    let mut bond_mac = None;
+    for sub_iface in bond_group {
+        for iface in links {
+            if sub_iface == &iface.name {
+                let mac = *bond_mac.get_or_insert(iface.mac_addr);
+                bond_group_map.insert(iface.if_index, mac);
+                break;
+            }
+        }
+    }
+    bond_group_map
 }
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -986,3 +1123,84 @@ impl BaseDispatcherListener {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::proto::agent;
+
+    fn tagged_packet(pcp: u16, vlan: u16) -> Vec<u8> {
+        let mut packet = vec![0; 12];
+        packet.extend_from_slice(&[0x81, 0x00]);
+        packet.extend_from_slice(&(pcp << 13 | vlan).to_be_bytes());
+        packet.extend_from_slice(&[0x08, 0x00]);
+        packet.extend_from_slice(&[0; 20]);
+        packet
+    }
+
+    fn new_handler(mappings: &[VlanPcpMapping]) -> CaptureNetworkTypeHandler {
+        let tap_typer = CaptureNetworkTyper::new();
+        tap_typer.on_tap_types_change(vec![agent::CaptureNetworkType {
+            capture_network_type: Some(10),
+            packet_type: Some(agent::PacketType::Packet as i32),
+            vlan: Some(100),
+            ..Default::default()
+        }]);
+        CaptureNetworkTypeHandler {
+            tap_typer: Arc::new(tap_typer),
+            default_tap_type: CaptureNetworkType::Cloud,
+            mirror_traffic_pcp: MirrorTrafficPcp::from(mappings),
+            capture_mode: PacketCaptureType::Analyzer,
+        }
+    }
+
+    #[test]
+    fn tap_type_by_vlan_pcp() {
+        let mapping = |pcp, capture_network_type| VlanPcpMapping {
+            pcp,
+            capture_network_type,
+        };
+        let handler = new_handler(&[
+            mapping(1, Some(4)),
+            mapping(2, Some(5)),
+            mapping(3, Some(6)),
+            mapping(5, None),
+        ]);
+        for (pcp, expected) in [
+            (1, CaptureNetworkType::Idc(4)),
+            (2, CaptureNetworkType::Idc(5)),
+            (3, CaptureNetworkType::Idc(6)),
+            // not mapped
+            (4, CaptureNetworkType::Cloud),
+            // calculated from vlan id
+            (5, CaptureNetworkType::Idc(10)),
+        ] {
+            let (tap_type, eth_type, l2_len) =
+                handler.get_l2_info(&tagged_packet(pcp, 100)).unwrap();
+            assert_eq!(tap_type, expected, "pcp {}", pcp);
+            assert_eq!(eth_type, EthernetType::IPV4);
+            assert_eq!(l2_len, ETH_HEADER_SIZE + VLAN_HEADER_SIZE);
+        }
+    }
+
+    #[test]
+    fn tap_type_by_single_pcp() {
+        let handler = new_handler(&[VlanPcpMapping {
+            pcp: 3,
+            capture_network_type: None,
+        }]);
+        let (tap_type, _, _) = handler.get_l2_info(&tagged_packet(3, 100)).unwrap();
+        assert_eq!(tap_type, CaptureNetworkType::Idc(10));
+        let (tap_type, _, _) = handler.get_l2_info(&tagged_packet(2, 100)).unwrap();
+        assert_eq!(tap_type, CaptureNetworkType::Cloud);
+
+        let handler = new_handler(&[VlanPcpMapping {
+            pcp: VlanPcpMapping::OUTER_VLAN,
+            capture_network_type: None,
+        }]);
+        assert_eq!(handler.mirror_traffic_pcp, MirrorTrafficPcp::OuterVlan);
+        let (tap_type, _, _) = handler.get_l2_info(&tagged_packet(2, 100)).unwrap();
+        assert_eq!(tap_type, CaptureNetworkType::Idc(10));
+    }
+}
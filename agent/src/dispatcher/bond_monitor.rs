@@ -0,0 +1,598 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    io,
+    os::unix::io::AsRawFd,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use libc::{ENOBUFS, NLMSG_DONE, RTNLGRP_LINK};
+use log::{debug, info, warn};
+use neli::{
+    consts::{nl::*, rtnl::*, socket::NlFamily},
+    err::{NlError, WrappedError},
+    nl::{NlPayload, Nlmsghdr},
+    rtnl::Ifinfomsg,
+    socket::NlSocketHandle,
+    types::RtBuffer,
+};
+use nix::sys::{
+    socket::{setsockopt, sockopt::ReceiveTimeout},
+    time::{TimeVal, TimeValLike},
+};
+
+use crate::trident::AgentState;
+
+use public::{
+    bytes::read_u32_le,
+    counter::{Counter, CounterType, CounterValue, RefCountable},
+};
+
+const RECV_TIMEOUT_SECS: i64 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkEvent {
+    pub if_index: u32,
+    pub name: String,
+    // if_index of the bond this link is enslaved to
+    pub master: Option<u32>,
+    pub removed: bool,
+}
+
+// Converts a RTM_NEWLINK or RTM_DELLINK message to a link event
+pub fn link_event(msg: &Nlmsghdr<Rtm, Ifinfomsg>) -> Option<LinkEvent> {
+    let removed = match msg.nl_type {
+        Rtm::Newlink => false,
+        Rtm::Dellink => true,
+        _ => return None,
+    };
+    let NlPayload::Payload(info) = &msg.nl_payload else {
+        return None;
+    };
+    let mut name = None;
+    let mut master = None;
+    for attr in info.rtattrs.iter() {
+        match attr.rta_type {
+            Ifla::Ifname => {
+                name = CStr::from_bytes_with_nul(attr.rta_payload.as_ref())
+                    .ok()
+                    .and_then(|c| c.to_str().ok())
+                    .map(String::from);
+            }
+            Ifla::Master => {
+                master = attr
+                    .rta_payload
+                    .as_ref()
+                    .get(..4)
+                    .map(read_u32_le)
+                    .filter(|m| *m != 0);
+            }
+            _ => (),
+        }
+    }
+    Some(LinkEvent {
+        if_index: info.ifi_index as u32,
+        name: name?,
+        master,
+        removed,
+    })
+}
+
+fn link_dump_request() -> Nlmsghdr<Rtm, Ifinfomsg> {
+    let msg = Ifinfomsg::new(
+        RtAddrFamily::Unspecified,
+        Arphrd::None,
+        0,
+        IffFlags::empty(),
+        IffFlags::empty(),
+        RtBuffer::new(),
+    );
+    Nlmsghdr::new(
+        None,
+        Rtm::Getlink,
+        NlmFFlags::new(&[NlmF::Request, NlmF::Dump]),
+        None,
+        None,
+        NlPayload::Payload(msg),
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BondChange {
+    // index in `inputs.cbpf.af_packet.bond_interfaces`
+    pub bond: usize,
+    pub master: Option<String>,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+struct Bond {
+    configured: Vec<String>,
+    master: Option<u32>,
+    slaves: Vec<String>,
+}
+
+struct LinkState {
+    name: String,
+    master: Option<u32>,
+}
+
+// Tracks the actual slaves of the configured bonds
+//
+// A configured bond is identified by the master of any of its configured slaves, all links
+// enslaved to that master are its slaves. Bonds with no enslaved link fall back to the
+// configured slaves.
+pub struct BondMembership {
+    bonds: Vec<Bond>,
+    links: HashMap<u32, LinkState>,
+}
+
+impl BondMembership {
+    pub fn new(bonds: Vec<Vec<String>>) -> Self {
+        Self {
+            bonds: bonds
+                .into_iter()
+                .map(|mut configured| {
+                    configured.sort();
+                    configured.dedup();
+                    Bond {
+                        slaves: configured.clone(),
+                        configured,
+                        master: None,
+                    }
+                })
+                .collect(),
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn slaves(&self) -> Vec<Vec<String>> {
+        self.bonds.iter().map(|b| b.slaves.clone()).collect()
+    }
+
+    pub fn apply<I: IntoIterator<Item = LinkEvent>>(&mut self, events: I) -> Vec<BondChange> {
+        for e in events {
+            if e.removed {
+                self.links.remove(&e.if_index);
+            } else {
+                self.links.insert(
+                    e.if_index,
+                    LinkState {
+                        name: e.name,
+                        master: e.master,
+                    },
+                );
+            }
+        }
+
+        let mut changes = vec![];
+        for (i, bond) in self.bonds.iter_mut().enumerate() {
+            if bond.master.is_some_and(|m| !self.links.contains_key(&m)) {
+                bond.master = None;
+            }
+            if bond.master.is_none() {
+                bond.master = self
+                    .links
+                    .values()
+                    .find(|l| l.master.is_some() && bond.configured.contains(&l.name))
+                    .and_then(|l| l.master);
+            }
+            let slaves = match bond.master {
+                Some(m) => {
+                    let mut slaves = self
+                        .links
+                        .values()
+                        .filter(|l| l.master == Some(m))
+                        .map(|l| l.name.clone())
+                        .collect::<Vec<_>>();
+                    slaves.sort();
+                    slaves
+                }
+                None => bond.configured.clone(),
+            };
+            if slaves == bond.slaves {
+                continue;
+            }
+            changes.push(BondChange {
+                bond: i,
+                master: bond
+                    .master
+                    .and_then(|m| self.links.get(&m))
+                    .map(|l| l.name.clone()),
+                old: std::mem::replace(&mut bond.slaves, slaves.clone()),
+                new: slaves,
+            });
+        }
+        changes
+    }
+}
+
+#[derive(Default)]
+pub struct BondMonitorCounter {
+    pub membership_changes: AtomicU64,
+    pub slaves_added: AtomicU64,
+    pub slaves_removed: AtomicU64,
+    pub netlink_errors: AtomicU64,
+}
+
+impl RefCountable for BondMonitorCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "membership_changes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.membership_changes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "slaves_added",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.slaves_added.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "slaves_removed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.slaves_removed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "netlink_errors",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.netlink_errors.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+// Watches rtnetlink link messages for membership changes of the configured bonds
+//
+// Changes are flagged until `take_changed()` is called, the agent main loop is woken up by
+// `AgentState::notify_bond_change()` to refresh the capture of the dispatchers.
+pub struct BondMonitor {
+    membership: Arc<Mutex<BondMembership>>,
+    changed: Arc<AtomicBool>,
+    counter: Arc<BondMonitorCounter>,
+    agent_state: Arc<AgentState>,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BondMonitor {
+    pub fn new(bonds: Vec<Vec<String>>, agent_state: Arc<AgentState>) -> Self {
+        Self {
+            membership: Arc::new(Mutex::new(BondMembership::new(bonds))),
+            changed: Default::default(),
+            counter: Default::default(),
+            agent_state,
+            running: Default::default(),
+            thread: Mutex::new(None),
+        }
+    }
+
+    pub fn counter(&self) -> &Arc<BondMonitorCounter> {
+        &self.counter
+    }
+
+    // Whether any of the bonds is changed since the last call
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn slaves(&self) -> Vec<Vec<String>> {
+        self.membership.lock().unwrap().slaves()
+    }
+
+    fn open_socket() -> io::Result<NlSocketHandle> {
+        let socket = NlSocketHandle::connect(NlFamily::Route, None, &[RTNLGRP_LINK])?;
+        setsockopt(
+            socket.as_raw_fd(),
+            ReceiveTimeout,
+            &TimeVal::seconds(RECV_TIMEOUT_SECS),
+        )?;
+        Ok(socket)
+    }
+
+    fn process(
+        membership: &Mutex<BondMembership>,
+        changed: &AtomicBool,
+        counter: &BondMonitorCounter,
+        agent_state: &AgentState,
+        events: Vec<LinkEvent>,
+    ) {
+        if events.is_empty() {
+            return;
+        }
+        debug!("bond monitor link events: {events:?}");
+        let changes = membership.lock().unwrap().apply(events);
+        for c in changes.iter() {
+            let added = c.new.iter().filter(|s| !c.old.contains(s)).count();
+            let removed = c.old.iter().filter(|s| !c.new.contains(s)).count();
+            info!(
+                "bond {} (master {}) slaves changed from {:?} to {:?}",
+                c.bond,
+                c.master.as_deref().unwrap_or("unknown"),
+                c.old,
+                c.new
+            );
+            counter.membership_changes.fetch_add(1, Ordering::Relaxed);
+            counter
+                .slaves_added
+                .fetch_add(added as u64, Ordering::Relaxed);
+            counter
+                .slaves_removed
+                .fetch_add(removed as u64, Ordering::Relaxed);
+        }
+        if !changes.is_empty() {
+            changed.store(true, Ordering::Relaxed);
+            agent_state.notify_bond_change();
+        }
+    }
+
+    fn run(
+        running: Arc<AtomicBool>,
+        membership: Arc<Mutex<BondMembership>>,
+        changed: Arc<AtomicBool>,
+        counter: Arc<BondMonitorCounter>,
+        agent_state: Arc<AgentState>,
+    ) {
+        let mut socket = None;
+        let mut need_dump = true;
+        // events of the link dump in progress, applied together on NLMSG_DONE
+        let mut dump: Option<Vec<LinkEvent>> = None;
+        while running.load(Ordering::Relaxed) {
+            let s = match socket.as_mut() {
+                Some(s) => s,
+                None => match Self::open_socket() {
+                    Ok(s) => {
+                        need_dump = true;
+                        socket.insert(s)
+                    }
+                    Err(e) => {
+                        warn!("bond monitor open netlink socket failed: {e}");
+                        counter.netlink_errors.fetch_add(1, Ordering::Relaxed);
+                        thread::sleep(Duration::from_secs(RECV_TIMEOUT_SECS as u64));
+                        continue;
+                    }
+                },
+            };
+            if need_dump {
+                if let Err(e) = s.send(link_dump_request()) {
+                    warn!("bond monitor request link dump failed: {e}");
+                    counter.netlink_errors.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(Duration::from_secs(RECV_TIMEOUT_SECS as u64));
+                    continue;
+                }
+                need_dump = false;
+                dump = Some(vec![]);
+            }
+            match s.recv::<Rtm, Ifinfomsg>() {
+                Ok(Some(msg)) => {
+                    if u16::from(msg.nl_type) == NLMSG_DONE as u16 {
+                        if let Some(events) = dump.take() {
+                            Self::process(&membership, &changed, &counter, &agent_state, events);
+                        }
+                    } else if let Some(event) = link_event(&msg) {
+                        match dump.as_mut() {
+                            Some(events) => events.push(event),
+                            None => Self::process(
+                                &membership,
+                                &changed,
+                                &counter,
+                                &agent_state,
+                                vec![event],
+                            ),
+                        }
+                    }
+                }
+                Ok(None) => (),
+                Err(NlError::Wrapped(WrappedError::IOError(e)))
+                    if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(NlError::Wrapped(WrappedError::IOError(e)))
+                    if e.raw_os_error() == Some(ENOBUFS) =>
+                {
+                    // messages dropped by the kernel, resync with a dump
+                    warn!("bond monitor netlink buffer overrun, dump links again");
+                    counter.netlink_errors.fetch_add(1, Ordering::Relaxed);
+                    need_dump = true;
+                }
+                Err(e) => {
+                    // the handle keeps undecodable messages buffered, start over with a new socket
+                    warn!("bond monitor receive failed: {e}");
+                    counter.netlink_errors.fetch_add(1, Ordering::Relaxed);
+                    socket = None;
+                    thread::sleep(Duration::from_secs(RECV_TIMEOUT_SECS as u64));
+                }
+            }
+        }
+    }
+
+    pub fn start(&self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let running = self.running.clone();
+        let membership = self.membership.clone();
+        let changed = self.changed.clone();
+        let counter = self.counter.clone();
+        let agent_state = self.agent_state.clone();
+        self.thread.lock().unwrap().replace(
+            thread::Builder::new()
+                .name("bond-monitor".to_owned())
+                .spawn(move || Self::run(running, membership, changed, counter, agent_state))
+                .unwrap(),
+        );
+        info!("bond monitor started");
+    }
+
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(t) = self.thread.lock().unwrap().take() {
+            let _ = t.join();
+        }
+        info!("bond monitor stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+
+    use neli::rtnl::Rtattr;
+
+    fn link_message(
+        nl_type: Rtm,
+        if_index: i32,
+        name: &str,
+        master: Option<u32>,
+    ) -> Nlmsghdr<Rtm, Ifinfomsg> {
+        let mut attrs = vec![Rtattr::new(
+            None,
+            Ifla::Ifname,
+            CString::new(name).unwrap().as_bytes_with_nul(),
+        )
+        .unwrap()];
+        if let Some(m) = master {
+            attrs.push(Rtattr::new(None, Ifla::Master, m).unwrap());
+        }
+        let msg = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::Ether,
+            if_index,
+            IffFlags::empty(),
+            IffFlags::empty(),
+            RtBuffer::from_iter(attrs),
+        );
+        Nlmsghdr::new(
+            None,
+            nl_type,
+            NlmFFlags::empty(),
+            None,
+            None,
+            NlPayload::Payload(msg),
+        )
+    }
+
+    fn events(messages: &[Nlmsghdr<Rtm, Ifinfomsg>]) -> Vec<LinkEvent> {
+        messages.iter().filter_map(link_event).collect()
+    }
+
+    #[test]
+    fn parse_messages() {
+        assert_eq!(
+            events(&[
+                link_message(Rtm::Newlink, 2, "eth0", Some(10)),
+                link_message(Rtm::Dellink, 3, "eth1", None),
+                link_message(Rtm::Newlink, 4, "eth2", Some(0)),
+            ]),
+            vec![
+                LinkEvent {
+                    if_index: 2,
+                    name: "eth0".to_string(),
+                    master: Some(10),
+                    removed: false,
+                },
+                LinkEvent {
+                    if_index: 3,
+                    name: "eth1".to_string(),
+                    master: None,
+                    removed: true,
+                },
+                LinkEvent {
+                    if_index: 4,
+                    name: "eth2".to_string(),
+                    master: None,
+                    removed: false,
+                },
+            ]
+        );
+        // not a link message
+        assert!(link_event(&link_message(Rtm::Getlink, 2, "eth0", None)).is_none());
+        // without name
+        let mut msg = link_message(Rtm::Newlink, 2, "eth0", None);
+        if let NlPayload::Payload(info) = &mut msg.nl_payload {
+            info.rtattrs = RtBuffer::new();
+        }
+        assert!(link_event(&msg).is_none());
+    }
+
+    #[test]
+    fn membership_changes() {
+        let mut m = BondMembership::new(vec![
+            vec!["eth1".to_string(), "eth0".to_string()],
+            vec!["eth4".to_string()],
+        ]);
+        // initial dump, eth2 is already enslaved to bond0 and eth4 not to any bond
+        let changes = m.apply(events(&[
+            link_message(Rtm::Newlink, 10, "bond0", None),
+            link_message(Rtm::Newlink, 2, "eth0", Some(10)),
+            link_message(Rtm::Newlink, 3, "eth1", Some(10)),
+            link_message(Rtm::Newlink, 4, "eth2", Some(10)),
+            link_message(Rtm::Newlink, 5, "eth4", None),
+        ]));
+        assert_eq!(
+            changes,
+            vec![BondChange {
+                bond: 0,
+                master: Some("bond0".to_string()),
+                old: vec!["eth0".to_string(), "eth1".to_string()],
+                new: vec!["eth0".to_string(), "eth1".to_string(), "eth2".to_string()],
+            }]
+        );
+
+        // unrelated link changes
+        assert!(m
+            .apply(events(&[link_message(Rtm::Newlink, 6, "eth5", None)]))
+            .is_empty());
+
+        // eth0 released from the bond and removed during maintenance
+        let changes = m.apply(events(&[link_message(Rtm::Newlink, 2, "eth0", None)]));
+        assert_eq!(changes[0].new, vec!["eth1", "eth2"]);
+        assert!(m
+            .apply(events(&[link_message(Rtm::Dellink, 2, "eth0", None)]))
+            .is_empty());
+
+        // replaced by eth3, still identified by the remaining configured slave
+        let changes = m.apply(events(&[link_message(Rtm::Newlink, 7, "eth3", Some(10))]));
+        assert_eq!(changes[0].old, vec!["eth1", "eth2"]);
+        assert_eq!(changes[0].new, vec!["eth1", "eth2", "eth3"]);
+
+        // bond removed, back to the configured slaves
+        let changes = m.apply(events(&[
+            link_message(Rtm::Newlink, 3, "eth1", None),
+            link_message(Rtm::Newlink, 4, "eth2", None),
+            link_message(Rtm::Newlink, 7, "eth3", None),
+            link_message(Rtm::Dellink, 10, "bond0", None),
+        ]));
+        assert_eq!(changes[0].new, vec!["eth0", "eth1"]);
+        assert_eq!(
+            m.slaves(),
+            vec![vec!["eth0", "eth1"], vec!["eth4"]]
+                .into_iter()
+                .map(|v| v.into_iter().map(String::from).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,387 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    mem::drop,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use nix::{
+    sched::{sched_setaffinity, CpuSet},
+    unistd::Pid,
+};
+
+use super::base_dispatcher::{BaseDispatcher, BaseDispatcherListener, CaptureNetworkTypeHandler};
+use crate::{
+    common::decapsulate::{TunnelInfo, TunnelType, TunnelTypeBitmap},
+    config::DispatcherConfig,
+    liveness::{self, ComponentId, ComponentSpec, LivenessRegistry},
+    rpc::get_timestamp,
+    utils::stats::{Counter, CounterType, CounterValue, RefCountable},
+};
+use public::{proto::agent::IfMacSource, utils::net::Link};
+
+const TUNNEL_TYPE_COUNT: usize = TunnelType::MplsGre as usize + 1;
+// counter names of packets and bytes for each tunnel type, indexed by TunnelType
+const TUNNEL_COUNTER_NAMES: [(&str, &str); TUNNEL_TYPE_COUNT] = [
+    ("", ""),
+    ("vxlan_packets", "vxlan_bytes"),
+    ("ipip_packets", "ipip_bytes"),
+    ("tencent_gre_packets", "tencent_gre_bytes"),
+    ("geneve_packets", "geneve_bytes"),
+    ("vxlan_nsh_packets", "vxlan_nsh_bytes"),
+    ("erspan_packets", "erspan_bytes"),
+    ("teb_packets", "teb_bytes"),
+    ("mpls_packets", "mpls_bytes"),
+    ("mpls_gre_packets", "mpls_gre_bytes"),
+];
+
+// Inner packets and bytes decapsulated from each tunnel type, the outermost tunnel
+// of a packet is used for attribution
+#[derive(Default)]
+pub struct DecapCounter {
+    packets: [AtomicU64; TUNNEL_TYPE_COUNT],
+    bytes: [AtomicU64; TUNNEL_TYPE_COUNT],
+    unknown_tunnel_drops: AtomicU64,
+    invalid_packets: AtomicU64,
+}
+
+impl DecapCounter {
+    fn add(&self, tunnel_type: TunnelType, bytes: u64) {
+        let index = tunnel_type as usize;
+        self.packets[index].fetch_add(1, Ordering::Relaxed);
+        self.bytes[index].fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl RefCountable for DecapCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        let mut counters = vec![];
+        for (i, (packets, bytes)) in TUNNEL_COUNTER_NAMES.iter().enumerate().skip(1) {
+            counters.push((
+                *packets,
+                CounterType::Counted,
+                CounterValue::Unsigned(self.packets[i].swap(0, Ordering::Relaxed)),
+            ));
+            counters.push((
+                *bytes,
+                CounterType::Counted,
+                CounterValue::Unsigned(self.bytes[i].swap(0, Ordering::Relaxed)),
+            ));
+        }
+        counters.push((
+            "unknown_tunnel_drops",
+            CounterType::Counted,
+            CounterValue::Unsigned(self.unknown_tunnel_drops.swap(0, Ordering::Relaxed)),
+        ));
+        counters.push((
+            "invalid_packets",
+            CounterType::Counted,
+            CounterValue::Unsigned(self.invalid_packets.swap(0, Ordering::Relaxed)),
+        ));
+        counters
+    }
+}
+
+// The decap mode only strips the tunnel headers configured by `tunnel_decap_protocols`
+// and counts the inner packets, the flow generator is not involved.
+pub(super) struct DecapModeDispatcher {
+    pub(super) base: BaseDispatcher,
+    pub(super) liveness_registry: Option<LivenessRegistry>,
+    pub(super) counter: Arc<DecapCounter>,
+}
+
+impl DecapModeDispatcher {
+    // Returns the offset of the inner packet, or None if the packet is dropped
+    fn decap_packet(
+        packet: &mut [u8],
+        capture_length: u64,
+        tap_type_handler: &CaptureNetworkTypeHandler,
+        tunnel_info: &mut TunnelInfo,
+        tunnel_type_bitmap: TunnelTypeBitmap,
+        counter: &DecapCounter,
+    ) -> Option<usize> {
+        // tunnels are not trimmed to keep the outermost tunnel type for attribution
+        let decap_length = match BaseDispatcher::decap_tunnel(
+            packet,
+            tap_type_handler,
+            tunnel_info,
+            tunnel_type_bitmap,
+            TunnelTypeBitmap::default(),
+        ) {
+            Ok((l, _)) => l,
+            Err(e) => {
+                counter.invalid_packets.fetch_add(1, Ordering::Relaxed);
+                warn!("decap_tunnel failed: {:?}", e);
+                return None;
+            }
+        };
+        if decap_length == 0 || tunnel_info.tunnel_type == TunnelType::None {
+            counter.unknown_tunnel_drops.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        counter.add(
+            tunnel_info.tunnel_type,
+            capture_length.saturating_sub(decap_length as u64),
+        );
+        Some(decap_length)
+    }
+
+    pub(super) fn run(&mut self) {
+        let liveness_handle = liveness::register(
+            self.liveness_registry.as_ref(),
+            ComponentSpec {
+                id: ComponentId::new("dispatcher", self.base.is.id as u32),
+                display_name: "dispatcher decap".into(),
+                timeout_ms: BaseDispatcher::LIVENESS_TIMEOUT_MS,
+                ..Default::default()
+            },
+        );
+        let base = &mut self.base.is;
+        info!("Start decap dispatcher {}", base.log_id);
+        let time_diff = base.ntp_diff.load(Ordering::Relaxed);
+        let mut prev_timestamp = get_timestamp(time_diff);
+        let mut last_liveness = Duration::ZERO;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let cpu_set = base.options.lock().unwrap().cpu_set;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if cpu_set != CpuSet::new() {
+            if let Err(e) = sched_setaffinity(Pid::from_raw(0), &cpu_set) {
+                warn!("CPU Affinity({:?}) bind error: {:?}.", &cpu_set, e);
+            }
+        }
+
+        while !base.terminated.load(Ordering::Relaxed) {
+            // The lifecycle of the recved will end before the next call to recv.
+            let recved = unsafe {
+                BaseDispatcher::recv(
+                    &mut self.base.engine,
+                    &mut base.rx_token_bucket,
+                    &base.exception_handler,
+                    &mut prev_timestamp,
+                    &base.counter,
+                    &base.ntp_diff,
+                )
+            };
+            if recved.is_none() {
+                liveness_handle.heartbeat();
+                drop(recved);
+                base.check_and_update_bpf(&mut self.base.engine);
+                continue;
+            }
+            if base.pause.load(Ordering::Relaxed) {
+                continue;
+            }
+            let (mut packet, timestamp) = recved.unwrap();
+            if timestamp >= last_liveness + BaseDispatcher::LIVENESS_HEARTBEAT_INTERVAL {
+                liveness_handle.heartbeat();
+                last_liveness = timestamp;
+            }
+
+            let tunnel_type_bitmap = *base.tunnel_type_bitmap.read().unwrap();
+            if Self::decap_packet(
+                &mut packet.data,
+                packet.capture_length as u64,
+                &base.tap_type_handler,
+                &mut base.tunnel_info,
+                tunnel_type_bitmap,
+                &self.counter,
+            )
+            .is_some()
+            {
+                base.counter.rx.fetch_add(1, Ordering::Relaxed);
+                base.counter
+                    .rx_bytes
+                    .fetch_add(packet.capture_length as u64, Ordering::Relaxed);
+            }
+            drop(packet);
+            base.check_and_update_bpf(&mut self.base.engine);
+        }
+
+        liveness_handle.pause();
+        self.base.terminate_handler();
+        info!("Stopped decap dispatcher {}", self.base.is.log_id);
+    }
+
+    pub(super) fn listener(&self) -> DecapModeDispatcherListener {
+        DecapModeDispatcherListener {
+            base: self.base.listener(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DecapModeDispatcherListener {
+    pub(super) base: BaseDispatcherListener,
+}
+
+impl DecapModeDispatcherListener {
+    #[cfg(target_os = "linux")]
+    pub fn netns(&self) -> &public::netns::NsFile {
+        &self.base.netns
+    }
+
+    pub fn on_tap_interface_change(&self, links: &[Link], _: IfMacSource) {
+        self.base
+            .on_tap_interface_change(links.to_vec(), IfMacSource::IfMac);
+    }
+
+    pub(super) fn on_config_change(&mut self, config: &DispatcherConfig) {
+        self.base.on_config_change(config)
+    }
+
+    pub fn id(&self) -> usize {
+        self.base.id
+    }
+
+    pub fn flow_acl_change(&self) {
+        // Start capturing traffic after resource information is distributed
+        self.base.pause.store(false, Ordering::Relaxed);
+        self.base.reset_whitelist.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common::enums::IpProtocol;
+
+    fn ipv4_header(protocol: IpProtocol, payload_len: usize) -> Vec<u8> {
+        let total_len = (20 + payload_len) as u16;
+        let mut bs = vec![0x45, 0];
+        bs.extend_from_slice(&total_len.to_be_bytes());
+        bs.extend_from_slice(&[0, 0, 0x40, 0, 64, u8::from(protocol), 0, 0]);
+        bs.extend_from_slice(&[172, 16, 0, 1, 172, 16, 0, 2]);
+        bs
+    }
+
+    fn ethernet(l3: &[u8]) -> Vec<u8> {
+        let mut bs = vec![0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02, 0x08, 0x00];
+        bs.extend_from_slice(l3);
+        bs
+    }
+
+    // a TCP segment of 100 bytes payload in an ethernet frame
+    fn inner_frame() -> Vec<u8> {
+        let mut l3 = ipv4_header(IpProtocol::TCP, 120);
+        l3.extend_from_slice(&[0x30, 0x39, 0, 80]);
+        l3.extend_from_slice(&[0; 8]);
+        l3.extend_from_slice(&[0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+        l3.extend_from_slice(&[0xab; 100]);
+        ethernet(&l3)
+    }
+
+    fn vxlan_frame(inner: &[u8]) -> Vec<u8> {
+        let mut udp = vec![0xc0, 0x00, 0x12, 0xb5];
+        udp.extend_from_slice(&((8 + 8 + inner.len()) as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        // flags and vni 100
+        udp.extend_from_slice(&[0x08, 0, 0, 0, 0, 0, 100, 0]);
+        udp.extend_from_slice(inner);
+        let mut l3 = ipv4_header(IpProtocol::UDP, udp.len());
+        l3.extend_from_slice(&udp);
+        ethernet(&l3)
+    }
+
+    fn ipip_frame(inner: &[u8]) -> Vec<u8> {
+        let inner_l3 = &inner[14..];
+        let mut l3 = ipv4_header(IpProtocol::IPV4, inner_l3.len());
+        l3.extend_from_slice(inner_l3);
+        ethernet(&l3)
+    }
+
+    fn gre_frame(inner: &[u8]) -> Vec<u8> {
+        // transparent ethernet bridging
+        let mut gre = vec![0, 0, 0x65, 0x58];
+        gre.extend_from_slice(inner);
+        let mut l3 = ipv4_header(IpProtocol::GRE, gre.len());
+        l3.extend_from_slice(&gre);
+        ethernet(&l3)
+    }
+
+    #[test]
+    fn counter_attribution() {
+        let handler = CaptureNetworkTypeHandler::default();
+        let mut tunnel_info = TunnelInfo::default();
+        let counter = DecapCounter::default();
+        // VXLAN and IPIP, GRE is not configured
+        let bitmap = TunnelTypeBitmap::new(&vec![TunnelType::Vxlan, TunnelType::Ipip]);
+        let inner = inner_frame();
+
+        let mut frames = vec![
+            vxlan_frame(&inner),
+            vxlan_frame(&inner),
+            ipip_frame(&inner),
+            gre_frame(&inner),
+            inner.clone(),
+        ];
+        let mut offsets = vec![];
+        for frame in frames.iter_mut() {
+            let len = frame.len() as u64;
+            offsets.push(DecapModeDispatcher::decap_packet(
+                frame,
+                len,
+                &handler,
+                &mut tunnel_info,
+                bitmap,
+                &counter,
+            ));
+        }
+        // ipip decapsulation moves the l2 header in front of the inner ip header
+        assert_eq!(offsets, vec![Some(50), Some(50), Some(20), None, None]);
+        assert_eq!(&frames[0][50..], &inner[..]);
+        assert_eq!(&frames[2][20..], &inner[..]);
+
+        let vxlan = TunnelType::Vxlan as usize;
+        let ipip = TunnelType::Ipip as usize;
+        let teb = TunnelType::Teb as usize;
+        assert_eq!(counter.packets[vxlan].load(Ordering::Relaxed), 2);
+        assert_eq!(
+            counter.bytes[vxlan].load(Ordering::Relaxed),
+            2 * inner.len() as u64
+        );
+        assert_eq!(counter.packets[ipip].load(Ordering::Relaxed), 1);
+        assert_eq!(
+            counter.bytes[ipip].load(Ordering::Relaxed),
+            inner.len() as u64
+        );
+        assert_eq!(counter.packets[teb].load(Ordering::Relaxed), 0);
+        assert_eq!(counter.unknown_tunnel_drops.load(Ordering::Relaxed), 2);
+
+        let counters = counter.get_counters();
+        let get = |name| {
+            counters
+                .iter()
+                .find(|c| c.0 == name)
+                .map(|c| match c.2 {
+                    CounterValue::Unsigned(v) => v,
+                    _ => unreachable!(),
+                })
+                .unwrap()
+        };
+        assert_eq!(get("vxlan_packets"), 2);
+        assert_eq!(get("ipip_packets"), 1);
+        assert_eq!(get("teb_packets"), 0);
+        assert_eq!(get("unknown_tunnel_drops"), 2);
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use log::{info, warn};
+use regex::Regex;
+
+use public::utils::net::Link;
+
+use crate::config::config::InterfaceDispatcherAssignment;
+
+// Splits the interfaces of local mode among dispatchers instead of packet fanout.
+//
+// Interfaces matching an assignment go to its dispatcher, the first matching assignment wins.
+// The others stay on the dispatcher they were assigned to in the last rescan, and new ones go to
+// the dispatcher with the least interfaces, so that flows are not moved between dispatchers.
+pub struct InterfaceAssigner {
+    rules: Vec<(Regex, usize)>,
+    dispatcher_count: usize,
+    // interface name to dispatcher index of the last assignment
+    assigned: HashMap<String, usize>,
+}
+
+impl InterfaceAssigner {
+    // Assignments with invalid regex or pointing at a dispatcher out of range are ignored
+    pub fn new(assignments: &[InterfaceDispatcherAssignment], dispatcher_count: usize) -> Self {
+        let rules = assignments
+            .iter()
+            .filter_map(|a| {
+                if a.dispatcher >= dispatcher_count {
+                    warn!(
+                        "dispatcher {} of interface dispatcher assignment {} not in [0, {}), ignored",
+                        a.dispatcher, a.interface_regex, dispatcher_count
+                    );
+                    return None;
+                }
+                match Regex::new(&a.interface_regex) {
+                    Ok(r) => Some((r, a.dispatcher)),
+                    Err(e) => {
+                        warn!(
+                            "invalid interface_regex {} of interface dispatcher assignment: {e}",
+                            a.interface_regex
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self {
+            rules,
+            dispatcher_count: dispatcher_count.max(1),
+            assigned: HashMap::new(),
+        }
+    }
+
+    pub fn dispatcher_count(&self) -> usize {
+        self.dispatcher_count
+    }
+
+    // Returns the interfaces of each dispatcher, indexed by dispatcher
+    pub fn assign(&mut self, links: &[Link]) -> Vec<Vec<Link>> {
+        let mut dispatchers = vec![vec![]; self.dispatcher_count];
+        let mut unassigned = vec![];
+        for link in links {
+            let index = self
+                .rules
+                .iter()
+                .find(|(r, _)| r.is_match(&link.name))
+                .map(|(_, i)| *i)
+                .or_else(|| self.assigned.get(&link.name).copied());
+            match index {
+                Some(i) if i < self.dispatcher_count => dispatchers[i].push(link.clone()),
+                _ => unassigned.push(link),
+            }
+        }
+        unassigned.sort_by(|a, b| a.name.cmp(&b.name));
+        for link in unassigned {
+            // the first one with the least interfaces
+            let (_, links) = dispatchers
+                .iter_mut()
+                .enumerate()
+                .min_by_key(|(i, links)| (links.len(), *i))
+                .unwrap();
+            links.push(link.clone());
+        }
+
+        let mut assigned = HashMap::new();
+        for (i, links) in dispatchers.iter_mut().enumerate() {
+            links.sort();
+            for link in links.iter() {
+                if self.assigned.get(&link.name) != Some(&i) {
+                    info!("interface {} assigned to dispatcher {}", link.name, i);
+                }
+                assigned.insert(link.name.clone(), i);
+            }
+        }
+        self.assigned = assigned;
+        dispatchers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(name: &str, if_index: u32) -> Link {
+        Link {
+            name: name.to_string(),
+            if_index,
+            ..Default::default()
+        }
+    }
+
+    fn names(dispatchers: &[Vec<Link>]) -> Vec<Vec<&str>> {
+        dispatchers
+            .iter()
+            .map(|links| links.iter().map(|l| l.name.as_str()).collect())
+            .collect()
+    }
+
+    fn assignment(interface_regex: &str, dispatcher: usize) -> InterfaceDispatcherAssignment {
+        InterfaceDispatcherAssignment {
+            interface_regex: interface_regex.to_string(),
+            dispatcher,
+        }
+    }
+
+    #[test]
+    fn assign_and_rebalance() {
+        let mut assigner =
+            InterfaceAssigner::new(&[assignment("^eth0$", 0), assignment("^eth1$", 1)], 2);
+        let mut links = vec![
+            link("eth0", 2),
+            link("eth1", 3),
+            link("veth-a", 10),
+            link("veth-b", 11),
+            link("veth-c", 12),
+        ];
+        assert_eq!(
+            names(&assigner.assign(&links)),
+            vec![vec!["eth0", "veth-a", "veth-c"], vec!["eth1", "veth-b"]]
+        );
+
+        // new veths land on the least loaded dispatcher, the existing ones stay
+        links.retain(|l| l.name != "veth-a" && l.name != "veth-c");
+        links.push(link("veth-d", 13));
+        links.push(link("veth-e", 14));
+        links.push(link("veth-f", 15));
+        assert_eq!(
+            names(&assigner.assign(&links)),
+            vec![
+                vec!["eth0", "veth-d", "veth-e"],
+                vec!["eth1", "veth-b", "veth-f"]
+            ]
+        );
+    }
+
+    #[test]
+    fn assign_without_rules() {
+        let mut assigner = InterfaceAssigner::new(&[], 3);
+        let links = (0..4)
+            .map(|i| link(&format!("veth{}", i), i + 10))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names(&assigner.assign(&links)),
+            vec![vec!["veth0", "veth3"], vec!["veth1"], vec!["veth2"]]
+        );
+        // nothing moves without interface changes
+        assert_eq!(
+            names(&assigner.assign(&links)),
+            vec![vec!["veth0", "veth3"], vec!["veth1"], vec!["veth2"]]
+        );
+    }
+
+    #[test]
+    fn out_of_range_assignment() {
+        let mut assigner = InterfaceAssigner::new(
+            &[
+                assignment("^eth0$", 2),
+                assignment("^eth.*", 1),
+                assignment("(", 0),
+            ],
+            2,
+        );
+        assert_eq!(assigner.rules.len(), 1);
+        // eth0 falls through to the next matching assignment
+        assert_eq!(
+            names(&assigner.assign(&[link("eth0", 2), link("eth1", 3), link("lo", 1)])),
+            vec![vec!["lo"], vec!["eth0", "eth1"]]
+        );
+    }
+}
@@ -261,7 +261,7 @@ impl LocalModeDispatcher {
             let recved = unsafe {
                 BaseDispatcher::recv(
                     &mut self.base.engine,
-                    &base.leaky_bucket,
+                    &mut base.rx_token_bucket,
                     &base.exception_handler,
                     &mut prev_timestamp,
                     &base.counter,
@@ -20,13 +20,14 @@ use public::{
     netns::{self, NsFile},
     proto::agent::{AgentType, IfMacSource},
     queue::{self, bounded_with_debug, DebugSender},
-    utils::net::{links_by_name_regex, Link, MacAddr},
-    LeakyBucket,
+    utils::net::{exclude_links_by_name_regex, links_by_name_regex, Link, MacAddr},
+    TokenBucket,
 };
 use regex::Regex;
 
 use super::{
     base_dispatcher::{BaseDispatcherListener, TapInterfaceWhitelist},
+    promisc::PromiscState,
     BaseDispatcher, BpfOptions, DispatcherBuilder, DispatcherConfig, LocalModeDispatcher, Options,
     Packet, PacketCaptureType, PacketCounter, RecvEngine,
 };
@@ -36,7 +37,7 @@ use crate::{
     flow_generator::{flow_map::Config, FlowMap},
     liveness::{self, ComponentId, ComponentSpec, LivenessRegistry},
     rpc::get_timestamp,
-    utils::stats::QueueStats,
+    utils::stats::{Collector, QueueStats},
 };
 
 const PACKET_BATCH_SIZE: usize = 64;
@@ -84,13 +85,14 @@ impl LocalMultinsModeDispatcher {
             options: base.options.clone(),
             bpf_options: base.bpf_options.clone(),
             queue_debugger: base.queue_debugger.clone(),
-            leaky_bucket: base.leaky_bucket.clone(),
+            rx_token_bucket: base.rx_token_bucket.clone(),
             exception_handler: base.exception_handler.clone(),
             counter: base.counter.clone(),
             ntp_diff: base.ntp_diff.clone(),
             bpf_controls: bpf_controls.clone(),
             liveness_registry: self.liveness_registry.clone(),
             liveness_id: id as u32,
+            stats: base.stats.clone(),
             output: packet_input,
         };
         self.receiver_manager.replace(
@@ -277,11 +279,12 @@ struct PktReceiver {
     bpf_options: Arc<Mutex<BpfOptions>>,
     queue_debugger: Arc<QueueDebugger>,
 
-    leaky_bucket: Arc<LeakyBucket>,
+    rx_token_bucket: TokenBucket,
     exception_handler: ExceptionHandler,
     counter: Arc<PacketCounter>,
     ntp_diff: Arc<AtomicI64>,
     liveness_registry: Option<LivenessRegistry>,
+    stats: Arc<Collector>,
 
     bpf_control: Arc<BpfControl>,
 
@@ -311,7 +314,8 @@ impl PktReceiver {
         config: &DispatcherConfig,
         options: &Mutex<Options>,
         bpf_options: &Mutex<BpfOptions>,
-        promisc_if_indices: &mut Vec<i32>,
+        promisc_state: &mut PromiscState,
+        stats: &Collector,
     ) -> Option<ExitStatus> {
         debug!("{log_prefix} updating bpf");
         let if_regex = if is_root {
@@ -319,7 +323,10 @@ impl PktReceiver {
         } else {
             &config.inner_tap_interface_regex
         };
-        let links = match links_by_name_regex(if_regex) {
+        let links = match links_by_name_regex(if_regex).and_then(|links| match is_root {
+            true => exclude_links_by_name_regex(links, &config.tap_interface_exclude_regex),
+            false => Ok(links),
+        }) {
             Ok(links) => links,
             Err(e) => {
                 warn!("{log_prefix} failed to get links: {e}");
@@ -349,33 +356,14 @@ impl PktReceiver {
             return Some(ExitStatus::UpdateFailed);
         }
 
-        let if_indices = links
-            .iter()
-            .map(|i| i.if_index as i32)
-            .collect::<Vec<i32>>();
         // When the configuration is changed, the deepflow-agent will restart,
         // and the NIC configured in promiscuous mode will be retired
-        if options.promisc && *promisc_if_indices != if_indices {
-            if let Err(e) = engine.set_promisc(promisc_if_indices, false) {
-                warn!(
-                    "{log_prefix} set_promisc disabled failed with tap_interfaces count {}: {e}",
-                    promisc_if_indices.len()
-                );
-            }
-
-            if let Err(e) = engine.set_promisc(&if_indices, true) {
-                warn!(
-                    "{log_prefix} set_promisc enabled failed with tap_interfaces count {}: {e}",
-                    if_indices.len()
-                );
-            }
-            *promisc_if_indices = if_indices;
-        }
+        promisc_state.update(log_prefix, engine, &links, &options.promisc_resolver, stats);
 
         None
     }
 
-    fn run(self) -> impl FnOnce() -> ExitStatus {
+    fn run(mut self) -> impl FnOnce() -> ExitStatus {
         move || {
             super::set_cpu_affinity(&self.options);
 
@@ -420,7 +408,12 @@ impl PktReceiver {
                 NsFile::Root => &cfg.tap_interface_regex,
                 _ => &cfg.inner_tap_interface_regex,
             };
-            let links = match links_by_name_regex(if_regex) {
+            let links = match links_by_name_regex(if_regex).and_then(|links| match self.netns {
+                NsFile::Root => {
+                    exclude_links_by_name_regex(links, &cfg.tap_interface_exclude_regex)
+                }
+                _ => Ok(links),
+            }) {
                 Ok(links) => links,
                 Err(e) => {
                     warn!("{log_prefix} failed to get links: {e}");
@@ -451,7 +444,7 @@ impl PktReceiver {
             }
 
             let mut prev_timestamp = get_timestamp(self.ntp_diff.load(Ordering::Relaxed));
-            let mut promisc_if_indices = vec![];
+            let mut promisc_state = PromiscState::default();
 
             let mut batch = Vec::with_capacity(PACKET_BATCH_SIZE);
             let mut allocator = Allocator::new(cfg.raw_packet_buffer_block_size);
@@ -467,7 +460,7 @@ impl PktReceiver {
                     //     It will be copied before sending into the queue.
                     let recved = BaseDispatcher::recv(
                         &mut engine,
-                        &self.leaky_bucket,
+                        &mut self.rx_token_bucket,
                         &self.exception_handler,
                         &mut prev_timestamp,
                         &self.counter,
@@ -492,7 +485,8 @@ impl PktReceiver {
                                 &self.config.load(),
                                 &self.options,
                                 &self.bpf_options,
-                                &mut promisc_if_indices,
+                                &mut promisc_state,
+                                &self.stats,
                             );
                         }
                         continue;
@@ -527,7 +521,8 @@ impl PktReceiver {
                             &self.config.load(),
                             &self.options,
                             &self.bpf_options,
-                            &mut promisc_if_indices,
+                            &mut promisc_state,
+                            &self.stats,
                         );
                     }
                 }
@@ -555,12 +550,13 @@ struct ReceiverManager {
 
     bpf_controls: Arc<Mutex<HashMap<NsFile, Arc<BpfControl>>>>,
 
-    leaky_bucket: Arc<LeakyBucket>,
+    rx_token_bucket: TokenBucket,
     exception_handler: ExceptionHandler,
     counter: Arc<PacketCounter>,
     ntp_diff: Arc<AtomicI64>,
     liveness_registry: Option<LivenessRegistry>,
     liveness_id: u32,
+    stats: Arc<Collector>,
 
     output: DebugSender<Packet>,
 }
@@ -713,12 +709,13 @@ impl ReceiverManager {
                         options: self.options.clone(),
                         bpf_options: self.bpf_options.clone(),
                         queue_debugger: self.queue_debugger.clone(),
-                        leaky_bucket: self.leaky_bucket.clone(),
+                        rx_token_bucket: self.rx_token_bucket.clone(),
                         exception_handler: self.exception_handler.clone(),
                         counter: self.counter.clone(),
                         ntp_diff: self.ntp_diff.clone(),
                         liveness_registry: self.liveness_registry.clone(),
                         dispatcher_id: self.liveness_id,
+                        stats: self.stats.clone(),
                         bpf_control: bpf_control.clone(),
                         output: self.output.clone(),
                     };
@@ -881,7 +878,13 @@ impl LocalMultinsModeDispatcherListener {
 
         let mut new_interface_indices = vec![];
 
-        match netns::links_by_name_regex_in_netns(&config.tap_interface_regex, &NsFile::Root) {
+        match netns::links_by_name_regex_in_netns(&config.tap_interface_regex, &NsFile::Root)
+            .and_then(|links| {
+                Ok(exclude_links_by_name_regex(
+                    links,
+                    &config.tap_interface_exclude_regex,
+                )?)
+            }) {
             Err(e) => {
                 warn!(
                     "get interfaces by name regex in {:?} failed: {e}",
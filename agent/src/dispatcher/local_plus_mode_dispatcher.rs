@@ -21,7 +21,7 @@ use std::mem::drop;
 use std::process::Command;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use std::str;
-use std::sync::{atomic::Ordering, Arc};
+use std::sync::{atomic::Ordering, Arc, Weak};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
@@ -36,7 +36,9 @@ use nix::{
 use super::base_dispatcher::{BaseDispatcher, BaseDispatcherListener};
 use super::error::Result;
 use super::local_mode_dispatcher::{skip_by_blacklist, LocalModeDispatcherListener, MacRewriter};
-use super::Packet;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::queue_cpu_set;
+use super::{flow_generator_thread_name, DispatcherQueueCounter, Packet};
 
 #[cfg(target_os = "linux")]
 use crate::platform::LibvirtXmlExtractor;
@@ -47,13 +49,16 @@ use crate::{
         MetaPacket, TapPort, FIELD_OFFSET_ETH_TYPE, MAC_ADDR_LEN, VLAN_HEADER_SIZE,
     },
     config::DispatcherConfig,
-    flow_generator::{flow_map::Config, FlowMap},
+    flow_generator::{
+        flow_map::{AllocatorStats, Config},
+        FlowMap,
+    },
     handler::MiniPacket,
     liveness::{self, ComponentId, ComponentSpec, LivenessRegistry},
     rpc::get_timestamp,
     utils::{
         bytes::read_u16_be,
-        stats::{self, Countable, QueueStats},
+        stats::{self, Countable, DispatcherQueueStats, QueueStats},
     },
 };
 use public::{
@@ -117,11 +122,12 @@ impl LocalPlusModeDispatcher {
         let tunnel_type_trim_bitmap = base.tunnel_type_trim_bitmap.clone();
         let liveness_registry = self.liveness_registry.clone();
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        let cpu_set = base.options.lock().unwrap().cpu_set;
+        let cpu_set = queue_cpu_set(&base.options, id);
+        let thread_name = flow_generator_thread_name(&base.options, id);
 
         self.flow_generator_thread_handler.replace(
             thread::Builder::new()
-                .name("dispatcher-packet-to-flow-generator".to_owned())
+                .name(thread_name)
                 .spawn(move || {
                     let liveness = liveness::register(
                         liveness_registry.as_ref(),
@@ -367,8 +373,15 @@ impl LocalPlusModeDispatcher {
         );
     }
 
-    fn setup_inner_thread_and_queue(&mut self) -> DebugSender<Packet> {
+    fn setup_inner_thread_and_queue(
+        &mut self,
+    ) -> (DebugSender<Packet>, Arc<DispatcherQueueCounter>) {
         let id = self.base.is.id;
+        let queue_counter = Arc::new(DispatcherQueueCounter::default());
+        self.stats_collector.register_countable(
+            &DispatcherQueueStats { index: id },
+            Countable::Ref(Arc::downgrade(&queue_counter) as Weak<dyn stats::RefCountable>),
+        );
         let name = "0.1-raw-packet-to-flow-generator";
         let (sender_to_parser, receiver_from_dispatcher, counter) =
             bounded_with_debug(self.inner_queue_size, name, &self.queue_debugger);
@@ -387,7 +400,7 @@ impl LocalPlusModeDispatcher {
 
         self.run_flow_generator(receiver_from_dispatcher, sender_to_pipeline);
         self.run_additional_packet_pipeline(receiver_from_flow);
-        return sender_to_parser;
+        return (sender_to_parser, queue_counter);
     }
 
     pub(super) fn run(&mut self) {
@@ -400,7 +413,7 @@ impl LocalPlusModeDispatcher {
                 ..Default::default()
             },
         );
-        let sender_to_parser = self.setup_inner_thread_and_queue();
+        let (sender_to_parser, queue_counter) = self.setup_inner_thread_and_queue();
         let base = &mut self.base.is;
         info!("Start local plus dispatcher {}", base.log_id);
         let time_diff = base.ntp_diff.load(Ordering::Relaxed);
@@ -408,6 +421,13 @@ impl LocalPlusModeDispatcher {
         let id = base.id;
         let mut batch = Vec::with_capacity(HANDLER_BATCH_SIZE);
         let mut allocator = Allocator::new(self.raw_packet_block_size);
+        self.stats_collector.register_countable(
+            &AllocatorStats {
+                id: id as u32,
+                obj_type: "RawPacket",
+            },
+            Countable::Ref(allocator.counter()),
+        );
         let mut last_liveness = Duration::ZERO;
         while !base.terminated.load(Ordering::Relaxed) {
             if base.reset_whitelist.swap(false, Ordering::Relaxed) {
@@ -417,7 +437,7 @@ impl LocalPlusModeDispatcher {
             let recved = unsafe {
                 BaseDispatcher::recv(
                     &mut self.base.engine,
-                    &base.leaky_bucket,
+                    &mut base.rx_token_bucket,
                     &base.exception_handler,
                     &mut prev_timestamp,
                     &base.counter,
@@ -425,7 +445,7 @@ impl LocalPlusModeDispatcher {
                 )
             };
             if recved.is_none() || batch.len() >= HANDLER_BATCH_SIZE {
-                if let Err(e) = sender_to_parser.send_all(&mut batch) {
+                if let Err(e) = queue_counter.send_all(&sender_to_parser, &mut batch) {
                     debug!("dispatcher {} sender failed: {:?}", id, e);
                     batch.clear();
                 }
@@ -19,7 +19,7 @@ use std::{
     mem::drop,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, RwLock,
+        Arc, RwLock, Weak,
     },
     thread::{self, JoinHandle},
     time::Duration,
@@ -36,6 +36,8 @@ use nix::{
 use super::mirror_mode_dispatcher::{
     get_key as mirror_get_key, handler as mirror_handler, swap_last_timestamp,
 };
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::queue_cpu_set;
 use super::{CaptureNetworkTypeHandler, Packet};
 #[cfg(target_os = "linux")]
 use crate::platform::{GenericPoller, Poller};
@@ -45,12 +47,15 @@ use crate::{
     dispatcher::{
         base_dispatcher::{BaseDispatcher, BaseDispatcherListener},
         error::Result,
-        PacketCounter,
+        flow_generator_thread_name, DispatcherQueueCounter, PacketCounter,
+    },
+    flow_generator::{
+        flow_map::{AllocatorStats, Config},
+        FlowMap,
     },
-    flow_generator::{flow_map::Config, FlowMap},
     liveness::{self, ComponentId, ComponentSpec, LivenessRegistry},
     rpc::get_timestamp,
-    utils::stats::{self, Countable, QueueStats},
+    utils::stats::{self, Countable, DispatcherQueueStats, QueueStats},
 };
 use public::{
     buffer::Allocator,
@@ -245,8 +250,15 @@ impl MirrorPlusModeDispatcher {
         }
     }
 
-    fn setup_inner_thread_and_queue(&mut self) -> DebugSender<Packet> {
+    fn setup_inner_thread_and_queue(
+        &mut self,
+    ) -> (DebugSender<Packet>, Arc<DispatcherQueueCounter>) {
         let id = self.base.is.id;
+        let queue_counter = Arc::new(DispatcherQueueCounter::default());
+        self.stats_collector.register_countable(
+            &DispatcherQueueStats { index: id },
+            Countable::Ref(Arc::downgrade(&queue_counter) as Weak<dyn stats::RefCountable>),
+        );
         let name = "0.1-raw-packet-to-flow-generator-and-pipeline";
         let (sender_to_parser, receiver_from_dispatcher, counter) =
             bounded_with_debug(self.inner_queue_size, name, &self.queue_debugger);
@@ -256,7 +268,7 @@ impl MirrorPlusModeDispatcher {
         );
 
         self.run_flow_generator_and_pipeline(receiver_from_dispatcher);
-        return sender_to_parser;
+        return (sender_to_parser, queue_counter);
     }
 
     // This thread implements the following functions:
@@ -293,13 +305,17 @@ impl MirrorPlusModeDispatcher {
         let local_vm_mac_set = self.local_vm_mac_set.clone();
         let liveness_registry = self.liveness_registry.clone();
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        let cpu_set = base.options.lock().unwrap().cpu_set;
+        let cpu_set = queue_cpu_set(&base.options, id);
+        let thread_name = flow_generator_thread_name(&base.options, id);
         #[cfg(any(target_os = "linux", target_os = "android"))]
-        let mut dedup = packet_dedup::PacketDedupMap::new();
+        let mut dedup = packet_dedup::PacketDedupMap::new(
+            base.packet_dedup_config,
+            base.packet_dedup_counter.clone(),
+        );
 
         self.flow_generator_thread_handler.replace(
             thread::Builder::new()
-                .name("dispatcher-packet-to-flow-generator".to_owned())
+                .name(thread_name)
                 .spawn(move || {
                     let liveness = liveness::register(
                         liveness_registry.as_ref(),
@@ -491,13 +507,20 @@ impl MirrorPlusModeDispatcher {
             },
         );
         info!("Start mirror plus dispatcher {}", self.base.is.log_id);
-        let sender_to_parser = self.setup_inner_thread_and_queue();
+        let (sender_to_parser, queue_counter) = self.setup_inner_thread_and_queue();
         let base = &mut self.base.is;
         let time_diff = base.ntp_diff.load(Ordering::Relaxed);
         let mut prev_timestamp = get_timestamp(time_diff);
         let mut batch = Vec::with_capacity(HANDLER_BATCH_SIZE);
         let id = base.id;
         let mut allocator = Allocator::new(self.raw_packet_block_size);
+        self.stats_collector.register_countable(
+            &AllocatorStats {
+                id: id as u32,
+                obj_type: "RawPacket",
+            },
+            Countable::Ref(allocator.counter()),
+        );
         let mut last_liveness = Duration::ZERO;
         #[cfg(any(target_os = "linux", target_os = "android"))]
         let cpu_set = base.options.lock().unwrap().cpu_set;
@@ -513,7 +536,7 @@ impl MirrorPlusModeDispatcher {
             let recved = unsafe {
                 BaseDispatcher::recv(
                     &mut self.base.engine,
-                    &base.leaky_bucket,
+                    &mut base.rx_token_bucket,
                     &base.exception_handler,
                     &mut prev_timestamp,
                     &base.counter,
@@ -521,7 +544,7 @@ impl MirrorPlusModeDispatcher {
                 )
             };
             if recved.is_none() || batch.len() >= HANDLER_BATCH_SIZE {
-                if let Err(e) = sender_to_parser.send_all(&mut batch) {
+                if let Err(e) = queue_counter.send_all(&sender_to_parser, &mut batch) {
                     debug!("dispatcher {} sender failed: {:?}", id, e);
                     batch.clear();
                 }
@@ -18,8 +18,14 @@ mod error;
 pub(crate) mod recv_engine;
 
 mod base_dispatcher;
+#[cfg(target_os = "linux")]
+pub mod bond_monitor;
+#[cfg(target_os = "linux")]
+pub mod interface_assigner;
+pub mod promisc;
 
 mod analyzer_mode_dispatcher;
+mod decap_mode_dispatcher;
 mod local_mode_dispatcher;
 #[cfg(target_os = "linux")]
 mod local_multins_mode_dispatcher;
@@ -33,6 +39,7 @@ use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use std::{
     collections::HashMap,
+    fmt::Debug,
     sync::{
         atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
         Arc, Mutex, RwLock, Weak,
@@ -46,14 +53,15 @@ use log::error;
 use log::{debug, info, warn};
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use nix::sched::CpuSet;
-use packet_dedup::PacketDedupMap;
+use packet_dedup::{PacketDedupConfig, PacketDedupMap};
 use public::debug::QueueDebugger;
-use special_recv_engine::Libpcap;
 #[cfg(target_os = "linux")]
 use special_recv_engine::{Dpdk, VhostUser};
+use special_recv_engine::{Libpcap, PcapHandleOptions};
 
 use analyzer_mode_dispatcher::{AnalyzerModeDispatcher, AnalyzerModeDispatcherListener}; // Enterprise Edition Feature: analyzer_mode
-use base_dispatcher::{BaseDispatcher, CaptureNetworkTypeHandler, InternalState};
+use base_dispatcher::{BaseDispatcher, CaptureNetworkTypeHandler, InternalState, MirrorTrafficPcp};
+use decap_mode_dispatcher::{DecapCounter, DecapModeDispatcher, DecapModeDispatcherListener};
 use error::{Error, Result};
 use local_mode_dispatcher::{LocalModeDispatcher, LocalModeDispatcherListener};
 #[cfg(target_os = "linux")]
@@ -63,6 +71,10 @@ use local_multins_mode_dispatcher::{
 use local_plus_mode_dispatcher::{LocalPlusModeDispatcher, LocalPlusModeDispatcherListener};
 use mirror_mode_dispatcher::{MirrorModeDispatcher, MirrorModeDispatcherListener};
 use mirror_plus_mode_dispatcher::{MirrorPlusModeDispatcher, MirrorPlusModeDispatcherListener};
+use promisc::PromiscResolver;
+#[cfg(target_os = "linux")]
+use recv_engine::dpdk::DpdkQueue;
+use recv_engine::libpcap::PcapInterfaceResolver;
 pub use recv_engine::RecvEngine;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub use recv_engine::{
@@ -82,7 +94,7 @@ use crate::{
     },
     config::{
         handler::{CollectorAccess, DispatcherAccess, FlowAccess, LogParserAccess},
-        DispatcherConfig, DpdkSource,
+        DispatcherConfig, DpdkSource, VlanPcpMapping,
     },
     exception::ExceptionHandler,
     flow_generator::AppProto,
@@ -101,9 +113,9 @@ use public::{
     buffer::{BatchedBox, BatchedBuffer},
     packet,
     proto::agent::{AgentType, IfMacSource, PacketCaptureType},
-    queue::{DebugSender, Receiver},
+    queue::{self, DebugSender, Receiver},
     utils::net::{Link, MacAddr},
-    LeakyBucket,
+    TokenBucket, TokenBucketConfig,
 };
 
 #[derive(Debug)]
@@ -118,6 +130,7 @@ pub struct Packet {
 
 enum DispatcherFlavor {
     Analyzer(AnalyzerModeDispatcher), // Enterprise Edition Feature: analyzer_mode
+    Decap(DecapModeDispatcher),
     Local(LocalModeDispatcher),
     #[cfg(target_os = "linux")]
     LocalMultins(LocalMultinsModeDispatcher),
@@ -136,6 +149,7 @@ impl DispatcherFlavor {
             DispatcherFlavor::Mirror(d) => d.init(),
             DispatcherFlavor::MirrorPlus(d) => d.init(),
             DispatcherFlavor::Analyzer(d) => d.base.init(), // Enterprise Edition Feature: analyzer_mode
+            DispatcherFlavor::Decap(d) => d.base.init(),
         }
     }
 
@@ -148,6 +162,7 @@ impl DispatcherFlavor {
             DispatcherFlavor::Mirror(d) => d.run(),
             DispatcherFlavor::MirrorPlus(d) => d.run(),
             DispatcherFlavor::Analyzer(d) => d.run(), // Enterprise Edition Feature: analyzer_mode
+            DispatcherFlavor::Decap(d) => d.run(),
         }
     }
 
@@ -161,6 +176,7 @@ impl DispatcherFlavor {
             DispatcherFlavor::MirrorPlus(d) => DispatcherListener::MirrorPlus(d.listener()),
             // Enterprise Edition Feature: analyzer_mode
             DispatcherFlavor::Analyzer(d) => DispatcherListener::Analyzer(d.listener()),
+            DispatcherFlavor::Decap(d) => DispatcherListener::Decap(d.listener()),
         }
     }
 
@@ -173,6 +189,7 @@ impl DispatcherFlavor {
 }
 
 pub struct Dispatcher {
+    thread_name: String,
     flavor: Mutex<Option<DispatcherFlavor>>,
     terminated: Arc<AtomicBool>,
     running: AtomicBool,
@@ -197,7 +214,7 @@ impl Dispatcher {
         let mut flavor = self.flavor.lock().unwrap().take().unwrap();
         self.handle.lock().unwrap().replace(
             thread::Builder::new()
-                .name("dispatcher".to_owned())
+                .name(self.thread_name.clone())
                 .spawn(move || {
                     flavor.run();
                     flavor
@@ -238,6 +255,7 @@ impl Dispatcher {
 #[derive(Clone)]
 pub enum DispatcherListener {
     Analyzer(AnalyzerModeDispatcherListener), // Enterprise Edition Feature: analyzer_mode
+    Decap(DecapModeDispatcherListener),
     Local(LocalModeDispatcherListener),
     #[cfg(target_os = "linux")]
     LocalMultins(LocalMultinsModeDispatcherListener),
@@ -267,6 +285,7 @@ impl FlowAclListener for DispatcherListener {
             DispatcherListener::Mirror(a) => a.flow_acl_change(),
             DispatcherListener::MirrorPlus(a) => a.flow_acl_change(),
             DispatcherListener::Analyzer(a) => a.flow_acl_change(),
+            DispatcherListener::Decap(a) => a.flow_acl_change(),
         }
         Ok(())
     }
@@ -280,6 +299,7 @@ impl FlowAclListener for DispatcherListener {
             DispatcherListener::Mirror(a) => a.id(),
             DispatcherListener::MirrorPlus(a) => a.id(),
             DispatcherListener::Analyzer(a) => a.id(),
+            DispatcherListener::Decap(a) => a.id(),
         };
         u16::from(FlowAclListenerId::Dispatcher) as usize + id
     }
@@ -296,6 +316,7 @@ impl DispatcherListener {
             Self::Mirror(a) => a.netns(),
             Self::MirrorPlus(a) => a.netns(),
             Self::Analyzer(a) => a.netns(),
+            Self::Decap(a) => a.netns(),
         }
     }
 
@@ -308,6 +329,7 @@ impl DispatcherListener {
             Self::Mirror(a) => a.id(),
             Self::MirrorPlus(a) => a.id(),
             Self::Analyzer(a) => a.id(),
+            Self::Decap(a) => a.id(),
         }
     }
 
@@ -318,6 +340,7 @@ impl DispatcherListener {
             Self::LocalMultins(l) => l.base.on_config_change(config),
             Self::LocalPlus(l) => l.on_config_change(config),
             Self::Analyzer(l) => l.on_config_change(config), // Enterprise Edition Feature: analyzer_mode
+            Self::Decap(l) => l.on_config_change(config),
             Self::Mirror(l) => l.on_config_change(config),
             Self::MirrorPlus(l) => l.on_config_change(config),
         }
@@ -331,11 +354,25 @@ impl DispatcherListener {
             Self::LocalMultins(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
             Self::LocalPlus(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
             Self::Analyzer(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
+            Self::Decap(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
             Self::Mirror(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
             Self::MirrorPlus(l) => l.base.need_reload_config.store(true, Ordering::Relaxed),
         }
     }
 
+    pub fn on_bond_group_change(&self, bond_group: &[String]) {
+        match self {
+            Self::Local(l) => l.base.on_bond_group_change(bond_group),
+            #[cfg(target_os = "linux")]
+            Self::LocalMultins(l) => l.base.on_bond_group_change(bond_group),
+            Self::LocalPlus(l) => l.base.on_bond_group_change(bond_group),
+            Self::Analyzer(l) => l.base.on_bond_group_change(bond_group),
+            Self::Decap(l) => l.base.on_bond_group_change(bond_group),
+            Self::Mirror(l) => l.base.on_bond_group_change(bond_group),
+            Self::MirrorPlus(l) => l.base.on_bond_group_change(bond_group),
+        }
+    }
+
     pub fn on_vm_change(&self, vm_mac_addrs: &[MacAddr], gateway_vmac_addrs: &[MacAddr]) {
         match self {
             // Enterprise Edition Feature: analyzer_mode
@@ -374,6 +411,9 @@ impl DispatcherListener {
             Self::Analyzer(l) => {
                 l.on_tap_interface_change(interfaces, if_mac_source);
             }
+            Self::Decap(l) => {
+                l.on_tap_interface_change(interfaces, if_mac_source);
+            }
             Self::Mirror(l) => {
                 l.on_tap_interface_change(interfaces, if_mac_source, agent_type);
             }
@@ -523,7 +563,7 @@ impl BpfOptions {
         snap_len: usize,
     ) -> Vec<RawInstruction> {
         let mut syntaxs = vec![];
-        debug!("Capture bpf set to:");
+        debug!("Capture bpf set to: {}", self.merged_syntax());
         if self.capture_bpf.len() != 0 {
             let prog = self.to_pcap_bpf_prog();
             if !prog.is_none() && prog.unwrap().bf_len > 0 {
@@ -585,14 +625,17 @@ impl BpfOptions {
         return syntaxs;
     }
 
-    pub fn get_bpf_syntax(&self) -> String {
+    fn merged_syntax(&self) -> String {
         if self.capture_bpf.len() > 0 {
-            let syntax = format!("({}) and ({})", self.capture_bpf, self.bpf_syntax_str);
-            debug!("Capture bpf set to: {}", syntax);
-            return syntax;
+            return format!("({}) and ({})", self.capture_bpf, self.bpf_syntax_str);
         }
-        debug!("Capture bpf set to: {}", self.bpf_syntax_str);
-        return self.bpf_syntax_str.clone();
+        self.bpf_syntax_str.clone()
+    }
+
+    pub fn get_bpf_syntax(&self) -> String {
+        let syntax = self.merged_syntax();
+        debug!("Capture bpf set to: {}", syntax);
+        syntax
     }
 }
 
@@ -614,15 +657,23 @@ pub struct Options {
     pub controller_port: u16,
     pub controller_tls_port: u16,
     pub vhost_socket_path: String,
+    #[cfg(target_os = "linux")]
+    pub vhost_if_index: isize,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub cpu_set: CpuSet,
+    // CPU of the dispatcher queue consumer thread
+    pub queue_cpu: Option<usize>,
     pub dpdk_ebpf_receiver: Option<Receiver<Box<packet::Packet<'static>>>>,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub dpdk_ebpf_windows: Duration,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub fanout_enabled: bool,
     #[cfg(any(target_os = "linux", target_os = "android"))]
-    pub promisc: bool,
+    pub packet_fanout_group_id: Option<u16>,
+    #[cfg(target_os = "linux")]
+    pub dpdk_queue: Option<DpdkQueue>,
+    pub pcap_resolver: PcapInterfaceResolver,
+    pub promisc_resolver: PromiscResolver,
     pub skip_npb_bpf: bool,
 }
 
@@ -631,6 +682,16 @@ impl Options {
         // The unit of packet_blocks is M, and the buffer size of the queue is 4096 bytes
         self.packet_blocks * 1024 * 1024 / 4096
     }
+
+    // pcap handle settings for interfaces without libpcap overrides
+    fn pcap_defaults(&self) -> PcapHandleOptions {
+        PcapHandleOptions {
+            snap_len: self.snap_len,
+            buffer_size: self.packet_blocks * recv_engine::DEFAULT_BLOCK_SIZE,
+            immediate_mode: false,
+            promisc: false,
+        }
+    }
 }
 
 pub struct Pipeline {
@@ -650,7 +711,9 @@ pub struct PacketCounter {
     err: AtomicU64,
 
     invalid_packets: AtomicU64,
-    get_token_failed: AtomicU64,
+    token_exhausted_drops: AtomicU64,
+    // max tokens used from the burst of max_capture_pps
+    token_burst_high_watermark: AtomicU64,
 
     retired: AtomicU64,
     kernel_counter: Arc<dyn stats::RefCountable>,
@@ -668,7 +731,8 @@ impl PacketCounter {
             err: AtomicU64::new(0),
 
             invalid_packets: AtomicU64::new(0),
-            get_token_failed: AtomicU64::new(0),
+            token_exhausted_drops: AtomicU64::new(0),
+            token_burst_high_watermark: AtomicU64::new(0),
 
             retired: AtomicU64::new(0),
             kernel_counter,
@@ -679,9 +743,12 @@ impl PacketCounter {
 impl stats::RefCountable for PacketCounter {
     fn get_counters(&self) -> Vec<stats::Counter> {
         let mut counters = self.kernel_counter.get_counters();
-        let get_token_failed = self.get_token_failed.swap(0, Ordering::Relaxed);
-        if get_token_failed > 0 {
-            warn!("rx rate limit hit {}", get_token_failed);
+        let token_exhausted_drops = self.token_exhausted_drops.swap(0, Ordering::Relaxed);
+        if token_exhausted_drops > 0 {
+            warn!(
+                "rx rate limit hit, {} packets dropped",
+                token_exhausted_drops
+            );
         }
         counters.extend(vec![
             (
@@ -715,9 +782,16 @@ impl stats::RefCountable for PacketCounter {
                 stats::CounterValue::Unsigned(self.invalid_packets.swap(0, Ordering::Relaxed)),
             ),
             (
-                "get_token_failed",
+                "token_exhausted_drops",
                 stats::CounterType::Counted,
-                stats::CounterValue::Unsigned(get_token_failed),
+                stats::CounterValue::Unsigned(token_exhausted_drops),
+            ),
+            (
+                "token_burst_high_watermark",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(
+                    self.token_burst_high_watermark.swap(0, Ordering::Relaxed),
+                ),
             ),
             (
                 "retired",
@@ -729,20 +803,77 @@ impl stats::RefCountable for PacketCounter {
     }
 }
 
+// Packets sent from dispatcher to flow generator when dispatcher queue is enabled
+#[derive(Default)]
+pub struct DispatcherQueueCounter {
+    packets_in: AtomicU64,
+    drops: AtomicU64,
+    high_watermark: AtomicU64,
+
+    // total overwritten count of the queue when last sent
+    overwritten: AtomicU64,
+}
+
+impl DispatcherQueueCounter {
+    pub(crate) fn send_all<T: Debug>(
+        &self,
+        sender: &DebugSender<T>,
+        batch: &mut Vec<T>,
+    ) -> std::result::Result<(), queue::Error<T>> {
+        let count = batch.len() as u64;
+        let result = sender.send_all(batch);
+        if result.is_ok() {
+            self.packets_in.fetch_add(count, Ordering::Relaxed);
+        } else {
+            self.drops.fetch_add(count, Ordering::Relaxed);
+        }
+        let overwritten = sender.total_overwritten_count();
+        let last = self.overwritten.swap(overwritten, Ordering::Relaxed);
+        self.drops
+            .fetch_add(overwritten.saturating_sub(last), Ordering::Relaxed);
+        self.high_watermark
+            .fetch_max(sender.len() as u64, Ordering::Relaxed);
+        result
+    }
+}
+
+impl stats::RefCountable for DispatcherQueueCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![
+            (
+                "packets_in",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.packets_in.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "drops",
+                stats::CounterType::Counted,
+                stats::CounterValue::Unsigned(self.drops.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "high_watermark",
+                stats::CounterType::Gauged,
+                stats::CounterValue::Unsigned(self.high_watermark.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
 #[derive(Default)]
 pub struct DispatcherBuilder {
     id: Option<usize>,
     pause: Option<bool>,
     src_interface: Option<String>,
     ctrl_mac: Option<MacAddr>,
-    leaky_bucket: Option<Arc<LeakyBucket>>,
+    rx_token_bucket: Option<Arc<TokenBucketConfig>>,
     options: Option<Arc<Mutex<Options>>>,
     handler_builders: Arc<RwLock<Vec<PacketHandlerBuilder>>>,
     bpf_options: Option<Arc<Mutex<BpfOptions>>>,
     default_tap_type: Option<CaptureNetworkType>,
-    mirror_traffic_pcp: Option<u16>,
+    mirror_traffic_pcp: Option<MirrorTrafficPcp>,
     tap_typer: Option<Arc<CaptureNetworkTyper>>,
     analyzer_dedup_disabled: Option<bool>,
+    packet_dedup_config: Option<PacketDedupConfig>,
     #[cfg(target_os = "linux")]
     libvirt_xml_extractor: Option<Arc<LibvirtXmlExtractor>>,
     flow_output_queue: Option<DebugSender<Arc<BatchedBox<TaggedFlow>>>>,
@@ -802,8 +933,8 @@ impl DispatcherBuilder {
         self
     }
 
-    pub fn leaky_bucket(mut self, v: Arc<LeakyBucket>) -> Self {
-        self.leaky_bucket = Some(v);
+    pub fn rx_token_bucket(mut self, v: Arc<TokenBucketConfig>) -> Self {
+        self.rx_token_bucket = Some(v);
         self
     }
 
@@ -822,8 +953,8 @@ impl DispatcherBuilder {
         self
     }
 
-    pub fn mirror_traffic_pcp(mut self, v: u16) -> Self {
-        self.mirror_traffic_pcp = Some(v);
+    pub fn mirror_traffic_pcp(mut self, v: &[VlanPcpMapping]) -> Self {
+        self.mirror_traffic_pcp = Some(MirrorTrafficPcp::from(v));
         self
     }
 
@@ -837,6 +968,11 @@ impl DispatcherBuilder {
         self
     }
 
+    pub fn packet_dedup_config(mut self, v: PacketDedupConfig) -> Self {
+        self.packet_dedup_config = Some(v);
+        self
+    }
+
     #[cfg(target_os = "linux")]
     pub fn libvirt_xml_extractor(mut self, v: Arc<LibvirtXmlExtractor>) -> Self {
         self.libvirt_xml_extractor = Some(v);
@@ -968,6 +1104,13 @@ impl DispatcherBuilder {
             .queue_debugger
             .ok_or(Error::ConfigIncomplete("no queue debugger".into()))?;
         let dispatcher_queue = options.lock().unwrap().dispatcher_queue;
+        #[cfg(target_os = "linux")]
+        let dpdk_queue = options
+            .lock()
+            .unwrap()
+            .dpdk_queue
+            .as_ref()
+            .map(|q| (q.port.port_id(), q.queue_id));
         let engine = Self::get_engine(
             &self.pcap_interfaces,
             &self.src_interface,
@@ -975,6 +1118,29 @@ impl DispatcherBuilder {
             &options,
             &queue_debugger,
         )?;
+        // dpdk queue threads are pinned by themselves, see `Tunning::cpu_affinity`
+        #[cfg(target_os = "linux")]
+        let thread_name = match dpdk_queue {
+            Some((port_id, queue_id)) => format!("dpdk-q{port_id}-{queue_id}"),
+            None => "dispatcher".to_owned(),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let thread_name = "dispatcher".to_owned();
+        #[cfg(target_os = "linux")]
+        let dpdk_queue_counter = match (&engine, dpdk_queue) {
+            (RecvEngine::Dpdk(d), Some((port_id, queue_id))) => d
+                .queue_counter()
+                .map(|c| (stats::DpdkQueueStats { port_id, queue_id }, c)),
+            _ => None,
+        };
+        #[cfg(target_os = "linux")]
+        let vhost_user_counter = match &engine {
+            RecvEngine::VhostUser(v) => Some((
+                options.lock().unwrap().vhost_socket_path.clone(),
+                v.socket_counter(),
+            )),
+            _ => None,
+        };
 
         let kernel_counter = engine.get_counter_handle();
         let id = self.id.ok_or(Error::ConfigIncomplete("no id".into()))?;
@@ -997,19 +1163,7 @@ impl DispatcherBuilder {
             .bond_group
             .take()
             .ok_or(Error::ConfigIncomplete("no bond group".into()))?;
-        let mut bond_group_map = HashMap::new();
-        let mut bond_mac = None;
-        for sub_iface in &bond_group {
-            for iface in &local_tap_interfaces {
-                if sub_iface == &iface.name {
-                    if bond_mac.is_none() {
-                        bond_mac = Some(iface.mac_addr);
-                    }
-                    bond_group_map.insert(iface.if_index, bond_mac.as_ref().unwrap().clone());
-                    break;
-                }
-            }
-        }
+        let bond_group_map = base_dispatcher::bond_group_map(&bond_group, &local_tap_interfaces);
 
         #[cfg(target_os = "linux")]
         let platform_poller = self
@@ -1047,9 +1201,10 @@ impl DispatcherBuilder {
             options,
             bpf_options: self.bpf_options.unwrap_or_default(),
 
-            leaky_bucket: self
-                .leaky_bucket
-                .ok_or(Error::ConfigIncomplete("no leaky_bucket".into()))?,
+            rx_token_bucket: TokenBucket::new(
+                self.rx_token_bucket
+                    .ok_or(Error::ConfigIncomplete("no rx_token_bucket".into()))?,
+            ),
             handler_builder: self.handler_builders.clone(),
             pipelines: Default::default(),
             tap_interfaces: Default::default(),
@@ -1070,6 +1225,7 @@ impl DispatcherBuilder {
                     .ok_or(Error::ConfigIncomplete("no default_tap_type".into()))?,
                 mirror_traffic_pcp: self
                     .mirror_traffic_pcp
+                    .take()
                     .ok_or(Error::ConfigIncomplete("no mirror_traffic_pcp".into()))?,
                 capture_mode,
             },
@@ -1077,6 +1233,10 @@ impl DispatcherBuilder {
             analyzer_dedup_disabled: self
                 .analyzer_dedup_disabled
                 .ok_or(Error::ConfigIncomplete("no analyzer_dedup_disabled".into()))?,
+            packet_dedup_config: self
+                .packet_dedup_config
+                .ok_or(Error::ConfigIncomplete("no packet_dedup_config".into()))?,
+            packet_dedup_counter: Default::default(),
 
             flow_output_queue: self
                 .flow_output_queue
@@ -1134,14 +1294,29 @@ impl DispatcherBuilder {
                 .tunnel_type_trim_bitmap
                 .take()
                 .ok_or(Error::ConfigIncomplete("no trim tunnel type".into()))?,
-            bond_group_map,
-            promisc_if_indices: vec![],
+            bond_group_map: Arc::new(RwLock::new(bond_group_map)),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            promisc_state: Default::default(),
         };
         let base = BaseDispatcher { engine, is };
         collector.register_countable(
             &stats::SingleTagModule("dispatcher", "id", base.is.id),
             stats::Countable::Ref(Arc::downgrade(&stat_counter) as Weak<dyn stats::RefCountable>),
         );
+        #[cfg(target_os = "linux")]
+        if let Some((module, counter)) = dpdk_queue_counter {
+            collector.register_countable(
+                &module,
+                stats::Countable::Ref(Arc::downgrade(&counter) as Weak<dyn stats::RefCountable>),
+            );
+        }
+        #[cfg(target_os = "linux")]
+        if let Some((socket, counter)) = vhost_user_counter {
+            collector.register_countable(
+                &stats::SingleTagModule("vhost_user", "socket", socket),
+                stats::Countable::Ref(Arc::downgrade(&counter) as Weak<dyn stats::RefCountable>),
+            );
+        }
         let mut dispatcher = match capture_mode {
             PacketCaptureType::Local => {
                 #[cfg(target_os = "linux")]
@@ -1190,6 +1365,11 @@ impl DispatcherBuilder {
                 }
             }
             PacketCaptureType::Mirror => {
+                collector.register_countable(
+                    &stats::SingleTagModule("packet_dedup", "id", base.is.id),
+                    stats::Countable::Ref(Arc::downgrade(&base.is.packet_dedup_counter)
+                        as Weak<dyn stats::RefCountable>),
+                );
                 if dispatcher_queue {
                     DispatcherFlavor::MirrorPlus(MirrorPlusModeDispatcher {
                         base,
@@ -1220,7 +1400,10 @@ impl DispatcherBuilder {
                     DispatcherFlavor::Mirror(MirrorModeDispatcher {
                         base,
                         liveness_registry: self.liveness_registry.clone(),
-                        dedup: PacketDedupMap::new(),
+                        dedup: PacketDedupMap::new(
+                            base.is.packet_dedup_config,
+                            base.is.packet_dedup_counter.clone(),
+                        ),
                         local_vm_mac_set: Arc::new(RwLock::new(HashMap::new())),
                         local_segment_macs: vec![],
                         tap_bridge_macs: vec![],
@@ -1248,6 +1431,11 @@ impl DispatcherBuilder {
                     // TODO fill bpf_syntax_str
                 }
 
+                collector.register_countable(
+                    &stats::SingleTagModule("packet_dedup", "id", base.is.id),
+                    stats::Countable::Ref(Arc::downgrade(&base.is.packet_dedup_counter)
+                        as Weak<dyn stats::RefCountable>),
+                );
                 DispatcherFlavor::Analyzer(AnalyzerModeDispatcher {
                     base,
                     vm_mac_addrs: Arc::new(RwLock::new(Default::default())),
@@ -1266,6 +1454,24 @@ impl DispatcherBuilder {
                     )?,
                 })
             }
+            PacketCaptureType::Decap => {
+                #[cfg(target_os = "linux")]
+                {
+                    // Do not capture tx direction traffic
+                    base.add_skip_outgoing();
+                }
+
+                let counter = Arc::new(DecapCounter::default());
+                collector.register_countable(
+                    &stats::SingleTagModule("decap", "id", base.is.id),
+                    stats::Countable::Ref(Arc::downgrade(&counter) as Weak<dyn stats::RefCountable>),
+                );
+                DispatcherFlavor::Decap(DecapModeDispatcher {
+                    base,
+                    liveness_registry: self.liveness_registry.clone(),
+                    counter,
+                })
+            }
             _ => {
                 return Err(Error::ConfigInvalid(format!(
                     "invalid capture mode {:?}",
@@ -1277,6 +1483,7 @@ impl DispatcherBuilder {
         #[cfg(target_os = "linux")]
         let _ = public::netns::reset_netns()?;
         Ok(Dispatcher {
+            thread_name,
             flavor: Mutex::new(Some(dispatcher)),
             terminated,
             running: AtomicBool::new(false),
@@ -1300,41 +1507,26 @@ impl DispatcherBuilder {
             #[cfg(target_os = "linux")]
             PacketCaptureType::Mirror if !options.vhost_socket_path.is_empty() => {
                 info!(
-                    "Vhostuser init with: {} {}",
+                    "Vhostuser init with: {} if_index {} {}",
                     options.vhost_socket_path,
+                    options.vhost_if_index,
                     options.vhost_queue_size()
                 );
                 Ok(RecvEngine::VhostUser(VhostUser::new(
                     options.vhost_socket_path.clone(),
+                    options.vhost_if_index,
                     options.vhost_queue_size(),
                 )))
             }
             PacketCaptureType::Mirror | PacketCaptureType::Local if options.libpcap_enabled => {
-                #[cfg(target_os = "windows")]
-                let src_ifaces = pcap_interfaces
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .map(|src_iface| (src_iface.device_name.as_str(), src_iface.if_index as isize))
-                    .collect();
-                #[cfg(any(target_os = "linux", target_os = "android"))]
-                let src_ifaces = pcap_interfaces
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .map(|src_iface| (src_iface.name.as_str(), src_iface.if_index as isize))
-                    .collect();
-                info!(
-                    "Libpcap init with: {:?} {} {}",
-                    &src_ifaces, options.packet_blocks, options.snap_len
+                let src_ifaces = options.pcap_resolver.resolve_links(
+                    pcap_interfaces.as_ref().unwrap(),
+                    &options.pcap_defaults(),
+                    &options.promisc_resolver,
                 );
-                let libpcap = Libpcap::new(
-                    src_ifaces,
-                    options.packet_blocks,
-                    options.snap_len,
-                    queue_debugger,
-                )
-                .map_err(|e| error::Error::Libpcap(e.to_string()))?;
+                info!("Libpcap init with: {:?}", &src_ifaces);
+                let libpcap = Libpcap::new(src_ifaces, queue_debugger)
+                    .map_err(|e| error::Error::Libpcap(e.to_string()))?;
                 Ok(RecvEngine::Libpcap(Some(libpcap)))
             }
             #[cfg(target_os = "linux")]
@@ -1344,13 +1536,28 @@ impl DispatcherBuilder {
                     "cpu arch s390x does not support DPDK!".into(),
                 ));
                 #[cfg(not(target_arch = "s390x"))]
-                {
-                    info!("Dpdk init with: {:?}", options.dpdk_source);
-                    Ok(RecvEngine::Dpdk(Dpdk::new(None, None, options.snap_len)))
+                match options.dpdk_queue.as_ref() {
+                    Some(q) => {
+                        info!(
+                            "Dpdk init with: {:?} port {} queue {}",
+                            options.dpdk_source,
+                            q.port.port_id(),
+                            q.queue_id
+                        );
+                        Ok(RecvEngine::Dpdk(Dpdk::new_queue(
+                            q.port.clone(),
+                            q.queue_id,
+                            options.snap_len,
+                        )))
+                    }
+                    None => {
+                        info!("Dpdk init with: {:?}", options.dpdk_source);
+                        Ok(RecvEngine::Dpdk(Dpdk::new(None, None, options.snap_len)))
+                    }
                 }
             }
             #[cfg(target_os = "linux")]
-            PacketCaptureType::Mirror | PacketCaptureType::Analyzer
+            PacketCaptureType::Mirror | PacketCaptureType::Analyzer | PacketCaptureType::Decap
                 if options.dpdk_source == DpdkSource::Ebpf =>
             {
                 #[cfg(target_arch = "s390x")]
@@ -1378,7 +1585,10 @@ impl DispatcherBuilder {
                 }
             }
             #[cfg(any(target_os = "linux", target_os = "android"))]
-            PacketCaptureType::Local | PacketCaptureType::Mirror | PacketCaptureType::Analyzer => {
+            PacketCaptureType::Local
+            | PacketCaptureType::Mirror
+            | PacketCaptureType::Analyzer
+            | PacketCaptureType::Decap => {
                 let afp = af_packet::Options {
                     frame_size: if options.capture_mode == PacketCaptureType::Analyzer {
                         FRAME_SIZE_MIN as u32
@@ -1395,6 +1605,7 @@ impl DispatcherBuilder {
                     } else {
                         None
                     },
+                    packet_fanout_group_id: options.packet_fanout_group_id,
                     ..Default::default()
                 };
                 info!("Afpacket init with {:?}", afp);
@@ -1417,6 +1628,39 @@ impl DispatcherBuilder {
 
 const L2_MAC_ADDR_OFFSET: usize = 12;
 
+// The consumer thread of a pinned dispatcher queue is named `dispatcher-q{id}`
+// so that it keeps its own CPU when global cpu affinity is updated
+pub(crate) fn flow_generator_thread_name(options: &Mutex<Options>, id: usize) -> String {
+    if options.lock().unwrap().queue_cpu.is_some() {
+        format!("dispatcher-q{}", id)
+    } else {
+        "dispatcher-packet-to-flow-generator".to_owned()
+    }
+}
+
+// Falls back to the cpu set of dispatchers if the pinned CPU is invalid
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn queue_cpu_set(options: &Mutex<Options>, id: usize) -> CpuSet {
+    let options = options.lock().unwrap();
+    let Some(cpu) = options.queue_cpu else {
+        return options.cpu_set;
+    };
+    let mut cpu_set = CpuSet::new();
+    match cpu_set.set(cpu) {
+        Ok(_) => {
+            info!("Dispatcher queue {} is pinned to CPU {}.", id, cpu);
+            cpu_set
+        }
+        Err(e) => {
+            warn!(
+                "Dispatcher queue {} CPU({}) is invalid: {:?}, use {:?} instead.",
+                id, cpu, e, options.cpu_set
+            );
+            options.cpu_set
+        }
+    }
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub(crate) fn set_cpu_affinity(options: &Mutex<Options>) {
     let cpu_set = options.lock().unwrap().cpu_set;
@@ -1426,3 +1670,50 @@ pub(crate) fn set_cpu_affinity(options: &Mutex<Options>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::queue::bounded_with_debug;
+
+    use crate::utils::stats::{DispatcherQueueStats, Module, RefCountable, StatsOption};
+
+    fn queue_tags(index: usize) -> Vec<(&'static str, String)> {
+        DispatcherQueueStats { index }
+            .tags()
+            .into_iter()
+            .map(|t| match t {
+                StatsOption::Tag(k, v) => (k, v),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dispatcher_queue_stats_tags() {
+        assert_eq!(queue_tags(0), vec![("queue", "0".to_owned())]);
+        assert_ne!(queue_tags(0), queue_tags(1));
+    }
+
+    #[test]
+    fn dispatcher_queue_counter() {
+        let counter = DispatcherQueueCounter::default();
+        let (sender, receiver, _) = bounded_with_debug(4, "test", &QueueDebugger::new());
+
+        counter.send_all(&sender, &mut vec![0, 1, 2]).unwrap();
+        counter.send_all(&sender, &mut vec![3, 4, 5]).unwrap();
+        let counters = counter.get_counters();
+        assert_eq!(counters[0].2, stats::CounterValue::Unsigned(6));
+        // overwritten by the second batch
+        assert_eq!(counters[1].2, stats::CounterValue::Unsigned(2));
+        assert_eq!(counters[2].2, stats::CounterValue::Unsigned(4));
+
+        receiver.recv_n(4, None).unwrap();
+        counter.send_all(&sender, &mut vec![6]).unwrap();
+        let counters = counter.get_counters();
+        assert_eq!(counters[0].2, stats::CounterValue::Unsigned(1));
+        assert_eq!(counters[1].2, stats::CounterValue::Unsigned(0));
+        assert_eq!(counters[2].2, stats::CounterValue::Unsigned(1));
+    }
+}
@@ -0,0 +1,266 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
+};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use log::info;
+use log::warn;
+use regex::Regex;
+
+use crate::config::config::InterfacePromisc;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::{
+    dispatcher::RecvEngine,
+    utils::stats::{
+        self, Collector, Countable, CounterType, CounterValue, InterfacePromiscStats, RefCountable,
+    },
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use public::utils::net::Link;
+
+// Without promiscuous mode the NIC only passes up packets to its own MAC addresses, broadcast and
+// subscribed multicast, which is all the traffic of the host itself in local capture mode.
+// The BPF filter does not match destination MAC, so nothing more is needed to capture them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PromiscMode {
+    #[default]
+    NonPromisc,
+    // non promiscuous, but receive all multicast packets
+    AllMulti,
+    Promisc,
+}
+
+impl PromiscMode {
+    pub fn new(promisc_enabled: bool, allmulti_enabled: bool) -> Self {
+        match (promisc_enabled, allmulti_enabled) {
+            (true, _) => Self::Promisc,
+            (false, true) => Self::AllMulti,
+            (false, false) => Self::NonPromisc,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NonPromisc => "non-promisc",
+            Self::AllMulti => "allmulti",
+            Self::Promisc => "promisc",
+        }
+    }
+}
+
+impl fmt::Display for PromiscMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// Resolves the promiscuous mode of an interface from the first matching override,
+// or the global default if none matches
+#[derive(Clone, Debug, Default)]
+pub struct PromiscResolver {
+    default: PromiscMode,
+    overrides: Vec<(Regex, PromiscMode)>,
+}
+
+impl PromiscResolver {
+    pub fn new(promisc_enabled: bool, overrides: &[InterfacePromisc]) -> Self {
+        let overrides = overrides
+            .iter()
+            .filter_map(|o| match Regex::new(&o.interface_regex) {
+                Ok(r) => Some((r, PromiscMode::new(o.promisc_enabled, o.allmulti_enabled))),
+                Err(e) => {
+                    warn!(
+                        "invalid interface_regex {} of interface promisc override: {e}",
+                        o.interface_regex
+                    );
+                    None
+                }
+            })
+            .collect();
+        Self {
+            default: PromiscMode::new(promisc_enabled, false),
+            overrides,
+        }
+    }
+
+    pub fn resolve(&self, name: &str) -> PromiscMode {
+        self.overrides
+            .iter()
+            .find(|(r, _)| r.is_match(name))
+            .map(|(_, m)| *m)
+            .unwrap_or(self.default)
+    }
+}
+
+// Exported with the interface and promiscuous mode as tags
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Default)]
+pub struct InterfacePromiscCounter {
+    // whether the mode was set on the interface successfully
+    applied: AtomicBool,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RefCountable for InterfacePromiscCounter {
+    fn get_counters(&self) -> Vec<stats::Counter> {
+        vec![(
+            "applied",
+            CounterType::Gauged,
+            CounterValue::Unsigned(self.applied.load(Ordering::Relaxed) as u64),
+        )]
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+struct AppliedPromisc {
+    name: String,
+    mode: PromiscMode,
+    // unregistered from stats on drop
+    _counter: Arc<InterfacePromiscCounter>,
+}
+
+// Promiscuous mode applied to the capture interfaces of a receive engine
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Default)]
+pub struct PromiscState {
+    applied: HashMap<i32, AppliedPromisc>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl PromiscState {
+    // Reverts the mode of interfaces gone or changed, and applies the mode of new ones
+    pub fn update(
+        &mut self,
+        log_prefix: &str,
+        engine: &mut RecvEngine,
+        links: &[Link],
+        resolver: &PromiscResolver,
+        stats: &Collector,
+    ) {
+        let wanted = links
+            .iter()
+            .map(|l| {
+                (
+                    l.if_index as i32,
+                    (l.name.as_str(), resolver.resolve(&l.name)),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.applied.retain(|if_index, a| {
+            if wanted.get(if_index) == Some(&(a.name.as_str(), a.mode)) {
+                return true;
+            }
+            if let Err(e) = engine.set_promisc(*if_index, a.mode, false) {
+                warn!(
+                    "{log_prefix} revert {} of interface {} ({if_index}) failed: {e}",
+                    a.mode, a.name
+                );
+            }
+            false
+        });
+
+        for link in links {
+            let if_index = link.if_index as i32;
+            if self.applied.contains_key(&if_index) {
+                continue;
+            }
+            let (name, mode) = (link.name.as_str(), resolver.resolve(&link.name));
+            let applied = match engine.set_promisc(if_index, mode, true) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("{log_prefix} set {mode} on interface {name} ({if_index}) failed: {e}");
+                    false
+                }
+            };
+            info!("{log_prefix} interface {name} ({if_index}) captured in {mode} mode");
+
+            let counter = Arc::new(InterfacePromiscCounter {
+                applied: AtomicBool::new(applied),
+            });
+            stats.register_countable(
+                &InterfacePromiscStats {
+                    interface: name.to_owned(),
+                    if_index: if_index as u32,
+                    promisc_mode: mode.as_str(),
+                },
+                Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+            );
+            self.applied.insert(
+                if_index,
+                AppliedPromisc {
+                    name: name.to_owned(),
+                    mode,
+                    _counter: counter,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn promisc(regex: &str, promisc_enabled: bool, allmulti_enabled: bool) -> InterfacePromisc {
+        InterfacePromisc {
+            interface_regex: regex.to_owned(),
+            promisc_enabled,
+            allmulti_enabled,
+        }
+    }
+
+    #[test]
+    fn resolve_order() {
+        let resolver = PromiscResolver::new(
+            true,
+            &[
+                promisc("^storage0$", true, false),
+                promisc("^storage", false, true),
+                promisc("^mgmt", false, false),
+                promisc("(", false, false),
+            ],
+        );
+        assert_eq!(resolver.overrides.len(), 3);
+        assert_eq!(resolver.resolve("storage0"), PromiscMode::Promisc);
+        assert_eq!(resolver.resolve("storage1"), PromiscMode::AllMulti);
+        assert_eq!(resolver.resolve("mgmt0"), PromiscMode::NonPromisc);
+        assert_eq!(resolver.resolve("eth0"), PromiscMode::Promisc);
+
+        let resolver = PromiscResolver::new(false, &[promisc("^eth", false, true)]);
+        assert_eq!(resolver.resolve("eth0"), PromiscMode::AllMulti);
+        assert_eq!(resolver.resolve("veth0"), PromiscMode::NonPromisc);
+        assert_eq!(
+            PromiscResolver::default().resolve("eth0"),
+            PromiscMode::NonPromisc
+        );
+    }
+
+    #[test]
+    fn promisc_over_allmulti() {
+        assert_eq!(PromiscMode::new(true, true), PromiscMode::Promisc);
+        assert_eq!(PromiscMode::new(false, true).as_str(), "allmulti");
+    }
+}
@@ -0,0 +1,201 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::process;
+
+use log::{info, warn};
+use regex::Regex;
+
+use public::utils::net::Link;
+
+use crate::config::config::{AfPacketTunning, InterfaceGroup};
+
+// Fanout settings of the sockets of a dispatcher
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fanout {
+    pub mode: u32,
+    pub group_id: u16,
+    // bound to the interface of the dispatcher, otherwise capturing on all interfaces
+    pub bound: bool,
+}
+
+// Fanout group ids are unique in a network namespace, the sockets capturing on all interfaces
+// use the base id derived from the pid and each interface group gets the ones after it.
+pub struct FanoutGroupIds {
+    base: u16,
+    allocated: u16,
+}
+
+impl Default for FanoutGroupIds {
+    fn default() -> Self {
+        Self::new((process::id() & 0xffff) as u16)
+    }
+}
+
+impl FanoutGroupIds {
+    pub fn new(base: u16) -> Self {
+        Self { base, allocated: 0 }
+    }
+
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    pub fn allocate(&mut self) -> u16 {
+        self.allocated = self.allocated.wrapping_add(1);
+        self.base.wrapping_add(self.allocated)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupedInterface {
+    pub link: Link,
+    // number of dispatchers capturing on the interface
+    pub count: usize,
+    pub fanout: Option<Fanout>,
+}
+
+// Takes the links matching an interface group out of `links`, the first matching group wins.
+//
+// The sockets of a fanout group must be bound to the same interface, so each grouped interface
+// gets its own fanout group with the mode and count of its interface group.
+pub fn split_by_group(
+    links: &mut Vec<Link>,
+    groups: &[InterfaceGroup],
+    tunning: &AfPacketTunning,
+    ids: &mut FanoutGroupIds,
+) -> Vec<GroupedInterface> {
+    let regexes = groups
+        .iter()
+        .filter_map(|g| match Regex::new(&g.interface_regex) {
+            Ok(r) => Some((r, g)),
+            Err(e) => {
+                warn!(
+                    "invalid interface_regex {} of interface group: {e}",
+                    g.interface_regex
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut grouped = vec![];
+    links.retain(|link| {
+        let Some((_, group)) = regexes.iter().find(|(r, _)| r.is_match(&link.name)) else {
+            return true;
+        };
+        let count = group.packet_fanout_count(tunning).max(1);
+        let fanout = if count > 1 {
+            Some(Fanout {
+                mode: group.packet_fanout_mode(tunning),
+                group_id: ids.allocate(),
+                bound: true,
+            })
+        } else {
+            None
+        };
+        info!(
+            "interface {} in group {} captured by {} dispatchers with fanout {:?}",
+            link.name, group.interface_regex, count, fanout
+        );
+        grouped.push(GroupedInterface {
+            link: link.clone(),
+            count,
+            fanout,
+        });
+        false
+    });
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(name: &str) -> Link {
+        Link {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn group_id_allocation() {
+        let mut ids = FanoutGroupIds::new(0xfffe);
+        let allocated = (0..4).map(|_| ids.allocate()).collect::<Vec<_>>();
+        // wraps around and never reuses the base
+        assert_eq!(allocated, vec![0xffff, 0, 1, 2]);
+        assert!(!allocated.contains(&ids.base()));
+    }
+
+    #[test]
+    fn split_interfaces_by_group() {
+        let tunning = AfPacketTunning {
+            packet_fanout_count: 4,
+            packet_fanout_mode: 0,
+            ..Default::default()
+        };
+        let groups = vec![
+            InterfaceGroup {
+                interface_regex: "^eth[01]$".to_string(),
+                ..Default::default()
+            },
+            InterfaceGroup {
+                interface_regex: "^cali.*".to_string(),
+                packet_fanout_mode: Some(2),
+                packet_fanout_count: Some(2),
+            },
+            InterfaceGroup {
+                interface_regex: "^eth.*".to_string(),
+                packet_fanout_count: Some(1),
+                ..Default::default()
+            },
+        ];
+        let mut links = ["eth0", "eth1", "cali1", "cali2", "eth2", "lo"]
+            .iter()
+            .map(|n| link(n))
+            .collect::<Vec<_>>();
+        let mut ids = FanoutGroupIds::new(100);
+        let grouped = split_by_group(&mut links, &groups, &tunning, &mut ids);
+
+        assert_eq!(
+            links.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(),
+            vec!["lo"]
+        );
+        let summary = grouped
+            .iter()
+            .map(|g| (g.link.name.as_str(), g.count, g.fanout))
+            .collect::<Vec<_>>();
+        let fanout = |mode, group_id| {
+            Some(Fanout {
+                mode,
+                group_id,
+                bound: true,
+            })
+        };
+        assert_eq!(
+            summary,
+            vec![
+                // falls back to the global mode and count
+                ("eth0", 4, fanout(0, 101)),
+                ("eth1", 4, fanout(0, 102)),
+                ("cali1", 2, fanout(2, 103)),
+                ("cali2", 2, fanout(2, 104)),
+                // a single dispatcher needs no fanout
+                ("eth2", 1, None),
+            ]
+        );
+    }
+}
@@ -15,6 +15,8 @@
  */
 pub mod bpf;
 #[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod fanout;
+#[cfg(any(target_os = "linux", target_os = "android"))]
 mod header;
 pub mod options;
 #[cfg(any(target_os = "linux", target_os = "android"))]
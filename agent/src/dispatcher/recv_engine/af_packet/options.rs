@@ -81,6 +81,8 @@ pub struct Options {
     pub socket_type: OptSocketType,
     pub iface: String,
     pub packet_fanout_mode: Option<u32>,
+    // derived from the pid if not set
+    pub packet_fanout_group_id: Option<u16>,
 }
 
 impl Default for Options {
@@ -96,6 +98,7 @@ impl Default for Options {
             socket_type: OptSocketType::SocketTypeRaw,
             iface: "".to_string(),
             packet_fanout_mode: None,
+            packet_fanout_group_id: None,
         }
     }
 }
@@ -24,8 +24,8 @@ use std::process;
 use libc::{
     c_int, c_uint, c_void, getsockopt, mmap, munmap, off_t, packet_mreq, poll, pollfd, setsockopt,
     size_t, sockaddr, sockaddr_ll, socket, socklen_t, write, AF_PACKET, ETH_P_ALL, MAP_LOCKED,
-    MAP_NORESERVE, MAP_SHARED, PACKET_ADD_MEMBERSHIP, PACKET_DROP_MEMBERSHIP, PACKET_MR_PROMISC,
-    POLLERR, POLLIN, PROT_READ, PROT_WRITE, SOL_PACKET, SOL_SOCKET, SO_ATTACH_FILTER,
+    MAP_NORESERVE, MAP_SHARED, PACKET_ADD_MEMBERSHIP, PACKET_DROP_MEMBERSHIP, POLLERR, POLLIN,
+    PROT_READ, PROT_WRITE, SOL_PACKET, SOL_SOCKET, SO_ATTACH_FILTER,
 };
 use log::{info, warn};
 use public::error::*;
@@ -44,6 +44,7 @@ const PACKET_VERSION: c_int = 10;
 const PACKET_RX_RING: c_int = 5;
 const PACKET_FANOUT: c_int = 18;
 const PACKET_STATISTICS: c_int = 6;
+const PACKET_QDISC_BYPASS: c_int = 20;
 const MILLI_SECONDS: u32 = 1000000;
 const MIN_KERNEL_VERSION_SUPPORT_PACKET_FANOUT: &'static str = "3.1";
 #[cfg(feature = "extended_observability")]
@@ -134,24 +135,30 @@ impl Tpacket {
         Ok(())
     }
 
-    pub fn set_promisc(&self, if_indices: &Vec<i32>, enabled: bool) -> Result<()> {
-        for i in if_indices {
-            let mreq = packet_mreq {
-                mr_type: PACKET_MR_PROMISC as u16,
-                mr_ifindex: *i,
-                mr_alen: 0,
-                mr_address: [0, 0, 0, 0, 0, 0, 0, 0],
-            };
-            let err = if enabled {
-                self.setsockopt(SOL_PACKET, PACKET_ADD_MEMBERSHIP, mreq)
-            } else {
-                self.setsockopt(SOL_PACKET, PACKET_DROP_MEMBERSHIP, mreq)
-            };
-            if err.is_err() {
-                warn!("Ifindex {} set promisc {} error: {:?}", i, enabled, err);
-            }
+    // Adds or drops PACKET_MR_PROMISC or PACKET_MR_ALLMULTI membership of the interface,
+    // the kernel reverts it when the socket is closed
+    pub fn set_membership(
+        &self,
+        if_index: i32,
+        mr_type: c_int,
+        enabled: bool,
+    ) -> af_packet::Result<()> {
+        let mreq = packet_mreq {
+            mr_type: mr_type as u16,
+            mr_ifindex: if_index,
+            mr_alen: 0,
+            mr_address: [0, 0, 0, 0, 0, 0, 0, 0],
+        };
+        if enabled {
+            self.setsockopt(SOL_PACKET, PACKET_ADD_MEMBERSHIP, mreq)
+        } else {
+            self.setsockopt(SOL_PACKET, PACKET_DROP_MEMBERSHIP, mreq)
         }
-        Ok(())
+    }
+
+    // Sends packets without the qdisc layer of the kernel, requires Linux kernel >= 3.14
+    pub fn set_qdisc_bypass(&self) -> af_packet::Result<()> {
+        self.setsockopt(SOL_PACKET, PACKET_QDISC_BYPASS, 1 as c_int)
     }
 
     fn setsockopt<T>(&self, level: i32, name: i32, value: T) -> af_packet::Result<()> {
@@ -232,7 +239,10 @@ impl Tpacket {
             return Ok(());
         };
         // The first 16 bits encode the fanout group ID, and the second set of 16 bits encode the fanout mode and options.
-        let fanout_group_id = process::id() & 0xffff;
+        let fanout_group_id = match self.opts.packet_fanout_group_id {
+            Some(id) => id as u32,
+            None => process::id() & 0xffff,
+        };
         let fanout_arg: c_uint = fanout_group_id | (packet_fanout_mode << 16);
         self.setsockopt(SOL_PACKET, PACKET_FANOUT, fanout_arg)?;
         #[cfg(feature = "extended_observability")]
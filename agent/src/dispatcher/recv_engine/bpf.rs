@@ -811,11 +811,63 @@ impl Builder {
     }
 }
 
+pub const MAX_EXTRA_BPF_FILTER_LEN: usize = 512;
+
+// 用和 dispatcher 相同的 libpcap 编译器在 dummy 以太网链路上编译 extra_bpf_filter 与
+// 默认过滤条件的组合表达式，以便在下发配置时即可发现语法错误
+pub fn check_extra_bpf_filter(filter: &str) -> Result<(), String> {
+    if filter.is_empty() {
+        return Ok(());
+    }
+    if filter.len() > MAX_EXTRA_BPF_FILTER_LEN {
+        return Err(format!(
+            "extra_bpf_filter({filter}) is too long: {} > {MAX_EXTRA_BPF_FILTER_LEN}",
+            filter.len()
+        ));
+    }
+    // 默认过滤条件只依赖端口与地址的取值，这里用占位值生成同样结构的表达式
+    let builder = Builder {
+        is_ipv6: false,
+        vxlan_flags: 0xff,
+        npb_port: 4789,
+        controller_port: 30035,
+        controller_tls_port: 30135,
+        proxy_controller_port: 30035,
+        analyzer_port: 30033,
+        analyzer_source_ip: IpAddr::from([127, 0, 0, 1]),
+        skip_npb_bpf: false,
+    };
+    let syntax = format!("({filter}) and ({})", builder.build_pcap_syntax_to_str());
+    let capture = pcap::Capture::dead(pcap::Linktype::ETHERNET)
+        .map_err(|e| format!("open dummy capture for extra_bpf_filter({filter}) failed: {e}"))?;
+    capture
+        .compile(&syntax, true)
+        .map(|_| ())
+        .map_err(|e| format!("malformed extra_bpf_filter({filter}): {e}"))
+}
+
 #[cfg(any(target_os = "linux", target_os = "android"))]
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn extra_bpf_filter() {
+        assert!(check_extra_bpf_filter("").is_ok());
+        assert!(check_extra_bpf_filter("tcp port 80 or (udp and not port 53)").is_ok());
+
+        let e = check_extra_bpf_filter("tcp prot 80").unwrap_err();
+        assert!(e.contains("tcp prot 80"));
+        assert!(check_extra_bpf_filter("tcp port 80 and (").is_err());
+        assert!(check_extra_bpf_filter("port 80\0").is_err());
+
+        let long = vec!["port 80"; 80].join(" or ");
+        assert!(long.len() > MAX_EXTRA_BPF_FILTER_LEN);
+        assert!(check_extra_bpf_filter(&long)
+            .unwrap_err()
+            .contains("too long"));
+    }
+
     #[test]
     fn ipv4_bpf_syntax() {
         let builder = Builder {
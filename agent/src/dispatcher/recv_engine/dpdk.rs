@@ -0,0 +1,217 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use log::info;
+use nix::sched::CpuSet;
+use special_recv_engine::{
+    DpdkPort, DpdkRssConf, RSS_HASH_IPV4, RSS_HASH_IPV4_SCTP, RSS_HASH_IPV4_TCP, RSS_HASH_IPV4_UDP,
+    RSS_HASH_IPV6, RSS_HASH_IPV6_SCTP, RSS_HASH_IPV6_TCP, RSS_HASH_IPV6_UDP,
+};
+
+use super::{Error, Result};
+use crate::config::config::{DpdkPortQueues, DpdkRss};
+
+// One RSS queue of a DPDK port, polled by its own dispatcher
+#[derive(Clone)]
+pub struct DpdkQueue {
+    pub port: Arc<DpdkPort>,
+    pub queue_id: u16,
+    pub cpu_set: Option<CpuSet>,
+}
+
+fn rss_hash_fields(fields: &[String]) -> u64 {
+    let fields = fields.iter().map(|f| f.to_lowercase()).collect::<Vec<_>>();
+    let has = |f: &str| fields.iter().any(|x| x == f);
+    // l4 fields are hashed for the enabled l3 protocols, ipv4 and ipv6 if none specified
+    let (ipv4, ipv6) = match (has("ipv4"), has("ipv6")) {
+        (false, false) => (true, true),
+        other => other,
+    };
+    let mut hash_fields = 0;
+    for (enabled, l3, tcp, udp, sctp) in [
+        (
+            ipv4,
+            RSS_HASH_IPV4,
+            RSS_HASH_IPV4_TCP,
+            RSS_HASH_IPV4_UDP,
+            RSS_HASH_IPV4_SCTP,
+        ),
+        (
+            ipv6,
+            RSS_HASH_IPV6,
+            RSS_HASH_IPV6_TCP,
+            RSS_HASH_IPV6_UDP,
+            RSS_HASH_IPV6_SCTP,
+        ),
+    ] {
+        if !enabled {
+            continue;
+        }
+        hash_fields |= l3;
+        if has("tcp") {
+            hash_fields |= tcp;
+        }
+        if has("udp") {
+            hash_fields |= udp;
+        }
+        if has("sctp") {
+            hash_fields |= sctp;
+        }
+    }
+    hash_fields
+}
+
+pub fn rss_conf(rss: &DpdkRss) -> Result<DpdkRssConf> {
+    Ok(DpdkRssConf {
+        key: rss.parse_hash_key().map_err(Error::DpdkError)?,
+        hash_fields: rss_hash_fields(&rss.hash_fields),
+    })
+}
+
+// Returns (port_id, queue_id, cpu_id) of all queues, queues are pinned to the cpus in
+// `cpu_affinity` round robin, or not pinned if `cpu_affinity` is empty
+fn queue_layout(
+    ports: &[DpdkPortQueues],
+    cpu_affinity: &[usize],
+) -> Vec<(u16, u16, Option<usize>)> {
+    ports
+        .iter()
+        .flat_map(|p| (0..p.queue_count).map(move |q| (p.port_id, q)))
+        .enumerate()
+        .map(|(i, (port_id, queue_id))| {
+            let cpu_id = if cpu_affinity.is_empty() {
+                None
+            } else {
+                Some(cpu_affinity[i % cpu_affinity.len()])
+            };
+            (port_id, queue_id, cpu_id)
+        })
+        .collect()
+}
+
+pub fn open_queues(
+    ports: &[DpdkPortQueues],
+    rss: &DpdkRss,
+    cpu_affinity: &[usize],
+) -> Result<Vec<DpdkQueue>> {
+    let rss = rss_conf(rss)?;
+    let mut opened: Vec<Arc<DpdkPort>> = vec![];
+    for p in ports.iter() {
+        info!(
+            "Dpdk port {} init with {} queues, rss: {:?}",
+            p.port_id, p.queue_count, rss
+        );
+        opened.push(Arc::new(DpdkPort::open(p.port_id, p.queue_count, &rss)?));
+    }
+    let mut queues = vec![];
+    for (port_id, queue_id, cpu_id) in queue_layout(ports, cpu_affinity) {
+        let port = opened.iter().find(|p| p.port_id() == port_id).unwrap();
+        let cpu_set = match cpu_id {
+            Some(id) => {
+                let mut cpu_set = CpuSet::new();
+                cpu_set
+                    .set(id)
+                    .map_err(|e| Error::DpdkError(format!("invalid cpu {id}: {e}")))?;
+                Some(cpu_set)
+            }
+            None => None,
+        };
+        queues.push(DpdkQueue {
+            port: port.clone(),
+            queue_id,
+            cpu_set,
+        });
+    }
+    Ok(queues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(port_id: u16, queue_count: u16) -> DpdkPortQueues {
+        DpdkPortQueues {
+            port_id,
+            queue_count,
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let ports = [port(0, 3), port(2, 2)];
+        assert_eq!(
+            queue_layout(&ports, &[]),
+            vec![
+                (0, 0, None),
+                (0, 1, None),
+                (0, 2, None),
+                (2, 0, None),
+                (2, 1, None)
+            ]
+        );
+        assert_eq!(
+            queue_layout(&ports, &[4, 6]),
+            vec![
+                (0, 0, Some(4)),
+                (0, 1, Some(6)),
+                (0, 2, Some(4)),
+                (2, 0, Some(6)),
+                (2, 1, Some(4))
+            ]
+        );
+        assert!(queue_layout(&[], &[1]).is_empty());
+    }
+
+    #[test]
+    fn hash_fields() {
+        let fields = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            rss_hash_fields(&fields(&["ipv4", "ipv6", "tcp", "udp"])),
+            RSS_HASH_IPV4
+                | RSS_HASH_IPV4_TCP
+                | RSS_HASH_IPV4_UDP
+                | RSS_HASH_IPV6
+                | RSS_HASH_IPV6_TCP
+                | RSS_HASH_IPV6_UDP
+        );
+        assert_eq!(
+            rss_hash_fields(&fields(&["IPv4", "tcp"])),
+            RSS_HASH_IPV4 | RSS_HASH_IPV4_TCP
+        );
+        assert_eq!(
+            rss_hash_fields(&fields(&["sctp"])),
+            RSS_HASH_IPV4 | RSS_HASH_IPV4_SCTP | RSS_HASH_IPV6 | RSS_HASH_IPV6_SCTP
+        );
+    }
+
+    #[test]
+    fn hash_key() {
+        let mut rss = DpdkRss::default();
+        assert!(rss_conf(&rss).unwrap().key.is_empty());
+
+        rss.hash_key = "6d5a".repeat(20);
+        assert_eq!(rss_conf(&rss).unwrap().key.len(), 40);
+        rss.hash_key = vec!["6d"; 52].join(":");
+        assert_eq!(rss_conf(&rss).unwrap().key.len(), 52);
+
+        rss.hash_key = "6d5a".repeat(10);
+        assert!(rss_conf(&rss).is_err());
+        rss.hash_key = "zz".repeat(40);
+        assert!(rss_conf(&rss).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use log::{info, warn};
+use regex::Regex;
+use special_recv_engine::PcapHandleOptions;
+
+use crate::config::config::LibpcapInterface;
+use crate::debug::{CaptureDebugger, PcapInterfaceSettings};
+use crate::dispatcher::promisc::{PromiscMode, PromiscResolver};
+use public::utils::net::Link;
+
+// Resolves the pcap handle settings of an interface, each setting is taken from the first
+// matching override that sets it, or the global default if none does
+#[derive(Clone, Default)]
+pub struct PcapInterfaceResolver {
+    overrides: Vec<(Regex, LibpcapInterface)>,
+    debugger: Option<Arc<CaptureDebugger>>,
+}
+
+impl PcapInterfaceResolver {
+    pub fn new(overrides: &[LibpcapInterface], debugger: Option<Arc<CaptureDebugger>>) -> Self {
+        let overrides = overrides
+            .iter()
+            .filter_map(
+                // interface_regex matches the whole name, so plain names match exactly
+                |o| match Regex::new(&format!("^(?:{})$", o.interface_regex)) {
+                    Ok(r) => Some((r, o.clone())),
+                    Err(e) => {
+                        warn!("Invalid libpcap interface_regex {}: {e}", o.interface_regex);
+                        None
+                    }
+                },
+            )
+            .collect();
+        Self {
+            overrides,
+            debugger,
+        }
+    }
+
+    pub fn resolve(&self, name: &str, default: &PcapHandleOptions) -> PcapHandleOptions {
+        let matched = self
+            .overrides
+            .iter()
+            .filter(|(r, _)| r.is_match(name))
+            .map(|(_, o)| o)
+            .collect::<Vec<_>>();
+        PcapHandleOptions {
+            snap_len: matched
+                .iter()
+                .find_map(|o| o.snap_len)
+                .map(|s| s as usize)
+                .unwrap_or(default.snap_len),
+            buffer_size: matched
+                .iter()
+                .find_map(|o| o.buffer_size)
+                .unwrap_or(default.buffer_size),
+            immediate_mode: matched
+                .iter()
+                .find_map(|o| o.immediate_mode)
+                .unwrap_or(default.immediate_mode),
+            promisc: default.promisc,
+        }
+    }
+
+    // Returns the handle settings of `links` with the device name used to open them,
+    // the effective settings are logged and recorded for debugging.
+    // Pcap handles have no allmulti mode, which is the same as non promiscuous.
+    pub fn resolve_links<'a>(
+        &self,
+        links: &'a [Link],
+        default: &PcapHandleOptions,
+        promisc: &PromiscResolver,
+    ) -> Vec<(&'a str, isize, PcapHandleOptions)> {
+        let mut settings = vec![];
+        let resolved = links
+            .iter()
+            .map(|l| {
+                let mut options = self.resolve(&l.name, default);
+                options.promisc = promisc.resolve(&l.name) == PromiscMode::Promisc;
+                info!(
+                    "Libpcap interface {} snaplen {} buffer {} immediate {} promisc {}",
+                    l.name,
+                    options.snap_len,
+                    options.buffer_size,
+                    options.immediate_mode,
+                    options.promisc
+                );
+                settings.push(PcapInterfaceSettings {
+                    name: l.name.clone(),
+                    snap_len: options.snap_len as u64,
+                    buffer_size: options.buffer_size as u64,
+                    immediate_mode: options.immediate_mode,
+                    promisc: options.promisc,
+                });
+                #[cfg(target_os = "windows")]
+                let device_name = l.device_name.as_str();
+                #[cfg(not(target_os = "windows"))]
+                let device_name = l.name.as_str();
+                (device_name, l.if_index as isize, options)
+            })
+            .collect();
+        if let Some(d) = self.debugger.as_ref() {
+            d.set_pcap_interfaces(settings);
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::config::InterfacePromisc;
+
+    fn interface(
+        regex: &str,
+        snap_len: Option<u32>,
+        buffer_size: Option<usize>,
+        immediate_mode: Option<bool>,
+    ) -> LibpcapInterface {
+        LibpcapInterface {
+            interface_regex: regex.to_owned(),
+            snap_len,
+            buffer_size,
+            immediate_mode,
+        }
+    }
+
+    const DEFAULT: PcapHandleOptions = PcapHandleOptions {
+        snap_len: 65535,
+        buffer_size: 64 << 20,
+        immediate_mode: false,
+        promisc: false,
+    };
+
+    #[test]
+    fn resolve_order() {
+        let resolver = PcapInterfaceResolver::new(
+            &[
+                interface("Ethernet 2", Some(128), None, None),
+                interface("Ethernet.*", Some(256), Some(16 << 20), None),
+                interface(".*", None, None, Some(true)),
+            ],
+            None,
+        );
+
+        // first match wins for each setting, unset ones fall through to later matches
+        assert_eq!(
+            resolver.resolve("Ethernet 2", &DEFAULT),
+            PcapHandleOptions {
+                snap_len: 128,
+                buffer_size: 16 << 20,
+                immediate_mode: true,
+                promisc: false,
+            }
+        );
+        assert_eq!(
+            resolver.resolve("Ethernet 3", &DEFAULT),
+            PcapHandleOptions {
+                snap_len: 256,
+                buffer_size: 16 << 20,
+                immediate_mode: true,
+                promisc: false,
+            }
+        );
+        assert_eq!(
+            resolver.resolve("Management", &DEFAULT),
+            PcapHandleOptions {
+                immediate_mode: true,
+                ..DEFAULT
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_whole_name() {
+        let resolver = PcapInterfaceResolver::new(
+            &[
+                interface("eth0", Some(128), None, None),
+                interface("[", Some(64), None, None),
+            ],
+            None,
+        );
+        assert_eq!(resolver.resolve("eth0", &DEFAULT).snap_len, 128);
+        assert_eq!(resolver.resolve("veth0", &DEFAULT), DEFAULT);
+        assert_eq!(resolver.resolve("eth01", &DEFAULT), DEFAULT);
+        assert_eq!(
+            PcapInterfaceResolver::default().resolve("eth0", &DEFAULT),
+            DEFAULT
+        );
+    }
+
+    #[test]
+    fn resolve_links_promisc() {
+        let links = ["eth0", "eth1", "lo"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Link {
+                name: name.to_string(),
+                if_index: i as u32 + 1,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let promisc = PromiscResolver::new(
+            true,
+            &[
+                InterfacePromisc {
+                    interface_regex: "^eth1$".to_owned(),
+                    allmulti_enabled: true,
+                    ..Default::default()
+                },
+                InterfacePromisc {
+                    interface_regex: "^lo$".to_owned(),
+                    ..Default::default()
+                },
+            ],
+        );
+        let resolved = PcapInterfaceResolver::default().resolve_links(&links, &DEFAULT, &promisc);
+        // allmulti is not promiscuous for pcap handles
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|(_, _, o)| o.promisc)
+                .collect::<Vec<_>>(),
+            vec![true, false, false]
+        );
+    }
+}
@@ -16,6 +16,9 @@
 
 pub mod af_packet;
 pub(crate) mod bpf;
+#[cfg(target_os = "linux")]
+pub mod dpdk;
+pub mod libpcap;
 
 use std::ffi::CStr;
 use std::sync::{atomic::AtomicU64, Arc};
@@ -26,6 +29,8 @@ use af_packet::{options::Options, tpacket::Tpacket};
 pub use public::error::{Error, Result};
 use public::packet;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::promisc::PromiscMode;
 use crate::utils::stats;
 
 #[cfg(target_os = "linux")]
@@ -36,6 +41,10 @@ pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
 pub const FRAME_SIZE_MAX: usize = 1 << 16; // local and mirror
 pub const FRAME_SIZE_MIN: usize = 1 << 11; // analyzer
 pub const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+// vhost-user sockets have no interface, packets of the n-th socket are tagged with
+// VHOST_USER_IF_INDEX_BASE + n as if_index
+#[cfg(target_os = "linux")]
+pub const VHOST_USER_IF_INDEX_BASE: u32 = 0x7000_0000;
 
 pub enum RecvEngine {
     #[cfg(any(target_os = "linux", target_os = "android"))]
@@ -123,12 +132,18 @@ impl RecvEngine {
         }
     }
 
-    #[allow(unused_variables)]
-    pub fn set_promisc(&mut self, if_index: &Vec<i32>, enabled: bool) -> Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_promisc(&mut self, if_index: i32, mode: PromiscMode, enabled: bool) -> Result<()> {
+        let mr_type = match mode {
+            PromiscMode::NonPromisc => return Ok(()),
+            PromiscMode::AllMulti => libc::PACKET_MR_ALLMULTI,
+            PromiscMode::Promisc => libc::PACKET_MR_PROMISC,
+        };
         match self {
-            #[cfg(any(target_os = "linux", target_os = "android"))]
-            Self::AfPacket(e) => e.set_promisc(if_index, enabled).map_err(|e| e.into()),
-            _ => return Ok(()),
+            Self::AfPacket(e) => e
+                .set_membership(if_index, mr_type, enabled)
+                .map_err(|e| e.into()),
+            _ => Ok(()),
         }
     }
 
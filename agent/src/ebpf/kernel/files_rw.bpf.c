@@ -91,6 +91,65 @@ static __inline bool is_readable_file(int fd,
 	return S_ISREG(i_mode);
 }
 
+/*
+ * Check the device of the file system against io_event_dev_filter_map,
+ * events dropped here are counted in trace_stats.io_event_dev_filtered.
+ */
+static __inline bool is_dev_filtered(int fd,
+				     struct member_fields_offset *off_ptr)
+{
+	__u32 k0 = 0;
+	struct io_event_dev_filter *filter = io_event_dev_filter_map__lookup(&k0);
+	if (filter == NULL || filter->mode == IO_EVENT_DEV_FILTER_NONE)
+		return false;
+
+	struct member_fields_offset *offset = off_ptr;
+	if (offset == NULL) {
+		offset = members_offset__lookup(&k0);
+		if (offset == NULL)
+			return false;
+	}
+	void *file = fd_to_file(fd, offset);
+	if (file == NULL)
+		return false;
+
+	// file -> f_inode -> i_sb -> s_dev
+	void *ptr = NULL;
+	__u32 s_dev = 0;
+	bpf_probe_read_kernel(&ptr, sizeof(ptr),
+			      file + offset->struct_file_f_inode_offset);
+	if (!ptr)
+		return false;
+	bpf_probe_read_kernel(&ptr, sizeof(ptr),
+			      ptr + offset->struct_inode_i_sb_offset);
+	if (!ptr)
+		return false;
+	bpf_probe_read_kernel(&s_dev, sizeof(s_dev),
+			      ptr + offset->struct_super_block_s_dev_offset);
+
+	bool found = false;
+#pragma unroll
+	for (int i = 0; i < IO_EVENT_DEV_FILTER_MAX; i++) {
+		if (i >= filter->count)
+			break;
+		if (filter->devs[i] == s_dev) {
+			found = true;
+			break;
+		}
+	}
+
+	bool filtered = (filter->mode == IO_EVENT_DEV_FILTER_INCLUDE) ?
+	    !found : found;
+	if (filtered) {
+		struct trace_stats *trace_stats = trace_stats_map__lookup(&k0);
+		if (trace_stats)
+			__sync_fetch_and_add(&trace_stats->io_event_dev_filtered,
+					     1);
+	}
+
+	return filtered;
+}
+
 static __inline void *get_mount_ptr(void *file,
 				    struct member_fields_offset *off_ptr)
 {
@@ -374,6 +433,10 @@ static __inline int trace_io_event_common(void *ctx,
 		return -1;
 	}
 
+	if (is_dev_filtered(data_args->fd, offset)) {
+		return -1;
+	}
+
 	struct __io_event_buffer *buffer = io_event_buffer__lookup(&k0);
 	if (!buffer) {
 		return -1;
@@ -154,6 +154,7 @@ struct trace_stats {
 	__u64 period_event_max_delay; /**< The maximum latency for periodic data push. */
 	__u64 period_event_total_time; /**< The total elapsed time for periodic event. */
 	__u64 period_event_count; /**< The number of occurrences of periodic events. */
+	__u64 io_event_dev_filtered; /**< IO events dropped by io_event_dev_filter_map. */
 };
 
 struct socket_info_s {
@@ -318,8 +319,24 @@ struct user_io_event_buffer {
 	char file_dir[FILE_PATH_SZ];
 	int mnt_id;
 	__u32 mntns_id;
+	// device of the file system, kernel internal encoding (major << 20 | minor)
+	__u32 s_dev;
 } __attribute__ ((packed));
 
+#define IO_EVENT_DEV_FILTER_MAX 32
+
+enum io_event_dev_filter_mode {
+	IO_EVENT_DEV_FILTER_NONE,
+	IO_EVENT_DEV_FILTER_INCLUDE,	// only devices in the list are collected
+	IO_EVENT_DEV_FILTER_EXCLUDE,	// devices in the list are dropped
+};
+
+struct io_event_dev_filter {
+	__u32 mode;
+	__u32 count;
+	__u32 devs[IO_EVENT_DEV_FILTER_MAX];	// kernel internal encoding
+};
+
 // struct ebpf_proc_info -> offsets[]  arrays index.
 enum offsets_index {
 	OFFSET_IDX_GOID_RUNTIME_G,
@@ -404,7 +421,8 @@ struct member_fields_offset {
 	__u8 enable_unix_socket:1;		// Enable flag for Unix socket tracing
 	__u8 files_infer_done:1;		// 0: file-related structure offset inference not completed
 						// 1: file-related structure offset inference completed
-	__u8 reserved:5;
+	__u8 enable_io_uring:1;			// Enable flag for io_uring send/recv tracing
+	__u8 reserved:4;
 	__u16 struct_dentry_d_parent_offset;    // offsetof(struct dentry, d_parent)
 	__u32 task__files_offset;
 	__u32 sock__flags_offset;
@@ -442,6 +460,13 @@ struct member_fields_offset {
 	__u16 struct_mnt_namespace_ns_offset; // offsetof(struct mnt_namespace, ns)
 	__u16 struct_ns_common_inum_offset;   // offsetof(struct mnt_common, inum)
 	__u16 struct_mount_mnt_id_offset;     // offsetof(struct mount, mnt_id)
+
+	/*
+	 * io_uring related offsets, only obtained from BTF
+	 */
+	__u16 struct_io_kiocb_fd_offset;      // offsetof(struct io_kiocb, cqe) + offsetof(struct io_cqe, fd)
+	__u16 struct_io_kiocb_res_offset;     // offsetof(struct io_kiocb, cqe) + offsetof(struct io_cqe, res)
+	__u16 struct_io_sr_msg_buf_offset;    // offsetof(struct io_sr_msg, buf)
 };
 
 typedef struct member_fields_offset bpf_offset_param_t;
@@ -0,0 +1,132 @@
+/*
+ * This code runs using bpf in the Linux kernel.
+ * Copyright 2025- The Yunshan Networks Authors.
+ *
+ * This program is free software; you can redistribute it and/or
+ * modify it under the terms of the GNU General Public License
+ * as published by the Free Software Foundation; either version 2
+ * of the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301, USA.
+ *
+ * SPDX-License-Identifier: GPL-2.0
+ */
+
+/*
+ * io_uring IORING_OP_SEND/IORING_OP_RECV tracing.
+ *
+ * Data sent or received through io_uring does not pass through the
+ * read()/write() family of syscalls. io_send()/io_recv() are called
+ * either inline from io_uring_enter() or from task_work of the submitting
+ * thread, so the data is handed to process_data() in the same way as the
+ * syscall path and thread trace IDs are associated in the same way too.
+ * Requests executed by SQPOLL or io-wq kernel threads are attributed to
+ * those threads.
+ *
+ * The op-specific data (struct io_sr_msg) overlays the beginning of
+ * struct io_kiocb, so its fields are read relative to the request.
+ */
+#if defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS)
+
+struct io_uring_args_t {
+	void *req;
+	struct data_args_t args;
+};
+
+BPF_HASH(io_uring_args_map, __u64, struct io_uring_args_t, MAP_MAX_ENTRIES_DEF, FEATURE_FLAG_SOCKET_TRACER)
+
+static __inline int io_uring_sr_enter(void *req, bool is_send)
+{
+	__u32 k0 = 0;
+	struct member_fields_offset *offset = members_offset__lookup(&k0);
+	if (!offset || !offset->ready || !offset->enable_io_uring)
+		return 0;
+
+	int fd = -1;
+	const char *buf = NULL;
+	bpf_probe_read_kernel(&fd, sizeof(fd),
+			      req + offset->struct_io_kiocb_fd_offset);
+	bpf_probe_read_kernel(&buf, sizeof(buf),
+			      req + offset->struct_io_sr_msg_buf_offset);
+	// Registered (fixed) files are not supported.
+	if (fd < 0 || buf == NULL)
+		return 0;
+
+	__u64 id = bpf_get_current_pid_tgid();
+	struct io_uring_args_t io_args = {};
+	io_args.req = req;
+	io_args.args.source_fn = is_send ? SYSCALL_FUNC_SEND : SYSCALL_FUNC_RECV;
+	io_args.args.fd = fd;
+	io_args.args.buf = buf;
+	io_args.args.enter_ts = bpf_ktime_get_ns();
+	__u64 conn_key = gen_conn_key_id((__u64) (id >> 32), (__u64) fd);
+	struct socket_info_s *socket_info_ptr =
+	    socket_info_map__lookup(&conn_key);
+	if (is_send)
+		io_args.args.tcp_seq =
+		    get_tcp_write_seq(fd, &io_args.args.sk, socket_info_ptr);
+	else
+		io_args.args.tcp_seq =
+		    get_tcp_read_seq(fd, &io_args.args.sk, socket_info_ptr);
+	io_uring_args_map__update(&id, &io_args);
+	return 0;
+}
+
+static __inline int io_uring_sr_exit(struct pt_regs *ctx, bool is_send)
+{
+	__u64 id = bpf_get_current_pid_tgid();
+	struct io_uring_args_t *io_args = io_uring_args_map__lookup(&id);
+	if (io_args == NULL)
+		return 0;
+
+	__u32 k0 = 0;
+	struct member_fields_offset *offset = members_offset__lookup(&k0);
+	// A negative return value (e.g. -EAGAIN) means the request is retried later.
+	int ret = (int)PT_REGS_RC(ctx);
+	if (offset && ret >= 0) {
+		int res = 0;
+		bpf_probe_read_kernel(&res, sizeof(res),
+				      io_args->req +
+				      offset->struct_io_kiocb_res_offset);
+		if (res > 0) {
+			struct process_data_extra extra = {
+				.vecs = false,
+				.source = DATA_SOURCE_SYSCALL,
+				.is_go_process = is_current_go_process(),
+			};
+			io_args->args.bytes_count = res;
+			process_data(ctx, id, is_send ? T_EGRESS : T_INGRESS,
+				     &io_args->args, res, &extra);
+		}
+	}
+
+	io_uring_args_map__delete(&id);
+	return 0;
+}
+
+// int io_send(struct io_kiocb *req, unsigned int issue_flags)
+KPROG(io_send) (struct pt_regs *ctx) {
+	return io_uring_sr_enter((void *)PT_REGS_PARM1(ctx), true);
+}
+
+KRETPROG(io_send) (struct pt_regs *ctx) {
+	return io_uring_sr_exit(ctx, true);
+}
+
+// int io_recv(struct io_kiocb *req, unsigned int issue_flags)
+KPROG(io_recv) (struct pt_regs *ctx) {
+	return io_uring_sr_enter((void *)PT_REGS_PARM1(ctx), false);
+}
+
+KRETPROG(io_recv) (struct pt_regs *ctx) {
+	return io_uring_sr_exit(ctx, false);
+}
+
+#endif /* defined(LINUX_VER_KFUNC) || defined(LINUX_VER_5_2_PLUS) */
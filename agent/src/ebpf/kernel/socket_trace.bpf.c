@@ -114,8 +114,17 @@ MAP_ARRAY(protocol_filter, int, int, PROTO_NUM, FEATURE_FLAG_SOCKET_TRACER)
  */
 MAP_ARRAY(allow_reasm_protos_map, int, bool, PROTO_NUM, FEATURE_FLAG_SOCKET_TRACER)
 
-// 0: allow bitmap; 1: bypass bitmap
-MAP_ARRAY(kprobe_port_bitmap, __u32, struct kprobe_port_bitmap, 2, FEATURE_FLAG_SOCKET_TRACER)
+/*
+ * Double buffered port bitmaps, each slot holds a pair of bitmaps:
+ * slot 0: key 0 allow bitmap, key 1 bypass bitmap
+ * slot 1: key 2 allow bitmap, key 3 bypass bitmap
+ *
+ * User space fills the inactive slot and then switches `kprobe_port_slot`,
+ * so the datapath never sees a partially updated bitmap.
+ */
+MAP_ARRAY(kprobe_port_bitmap, __u32, struct kprobe_port_bitmap, 4, FEATURE_FLAG_SOCKET_TRACER)
+// key 0: the active slot of kprobe_port_bitmap
+MAP_ARRAY(kprobe_port_slot, __u32, __u32, 1, FEATURE_FLAG_SOCKET_TRACER)
 
 /*
  * l7-protocol-ports
@@ -163,6 +172,8 @@ MAP_ARRAY(proto_infer_cache_map, __u32, struct proto_infer_cache_t, PROTO_INFER_
 #endif
 // Store IO event information
 MAP_PERARRAY(io_event_buffer, __u32, struct __io_event_buffer, 1, FEATURE_FLAG_SOCKET_TRACER)
+// key 0: device filter of IO events, set by user space
+MAP_ARRAY(io_event_dev_filter_map, __u32, struct io_event_dev_filter, 1, FEATURE_FLAG_SOCKET_TRACER)
 /* *INDENT-ON* */
 
 static __inline bool is_protocol_enabled(int protocol)
@@ -1775,7 +1786,7 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 	 * Here you can filter the pid according to the configuration.
 	 */
 
-	__u32 k0 = 0, k1 = 1;
+	__u32 k0 = 0;
 	struct member_fields_offset *offset = members_offset__lookup(&k0);
 	if (!offset)
 		return -1;
@@ -1841,7 +1852,13 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 	if (!ctx_map)
 		return -1;
 
-	struct kprobe_port_bitmap *bypass = kprobe_port_bitmap__lookup(&k1);
+	__u32 k_allow = 0, k_bypass;
+	__u32 *port_slot = kprobe_port_slot__lookup(&k0);
+	if (port_slot && *port_slot)
+		k_allow = 2;
+	k_bypass = k_allow + 1;
+
+	struct kprobe_port_bitmap *bypass = kprobe_port_bitmap__lookup(&k_bypass);
 	if (bypass) {
 		if (is_set_bitmap(bypass->bitmap, conn_info->tuple.dport) ||
 		    is_set_bitmap(bypass->bitmap, conn_info->tuple.num)) {
@@ -1849,7 +1866,7 @@ static __inline int process_data(struct pt_regs *ctx, __u64 id,
 		}
 	}
 
-	struct kprobe_port_bitmap *allow = kprobe_port_bitmap__lookup(&k0);
+	struct kprobe_port_bitmap *allow = kprobe_port_bitmap__lookup(&k_allow);
 	if (allow) {
 		if (is_set_bitmap(allow->bitmap, conn_info->tuple.dport) ||
 		    is_set_bitmap(allow->bitmap, conn_info->tuple.num)) {
@@ -3770,3 +3787,4 @@ TP_SYSCALL_PROG(enter_getppid) (struct syscall_comm_enter_ctx *ctx) {
 #include "go_tls.bpf.c"
 #include "go_http2.bpf.c"
 #include "openssl.bpf.c"
+#include "io_uring.bpf.c"
@@ -166,6 +166,24 @@ pub const DATA_SOURCE_IO_EVENT: u8 = 4;
 pub const DATA_SOURCE_GO_HTTP2_DATAFRAME_UPROBE: u8 = 5;
 #[allow(dead_code)]
 pub const DATA_SOURCE_UNIX_SOCKET: u8 = 8;
+
+// enum io_uring_trace_status
+#[allow(dead_code)]
+pub const IO_URING_TRACE_DISABLED: u8 = 0;
+#[allow(dead_code)]
+pub const IO_URING_TRACE_RUNNING: u8 = 1;
+#[allow(dead_code)]
+pub const IO_URING_TRACE_KERNEL_UNSUPPORTED: u8 = 2;
+#[allow(dead_code)]
+pub const IO_URING_TRACE_SYMBOL_MISSING: u8 = 3;
+#[allow(dead_code)]
+pub const IO_URING_TRACE_OFFSETS_UNAVAILABLE: u8 = 4;
+
+// enum io_event_dev_filter_mode
+pub const IO_EVENT_DEV_FILTER_NONE: c_int = 0;
+pub const IO_EVENT_DEV_FILTER_INCLUDE: c_int = 1;
+pub const IO_EVENT_DEV_FILTER_EXCLUDE: c_int = 2;
+pub const IO_EVENT_DEV_FILTER_MAX: usize = 32;
 cfg_if::cfg_if! {
     if #[cfg(feature = "extended_observability")] {
         #[allow(dead_code)]
@@ -439,6 +457,11 @@ impl fmt::Display for SK_BPF_DATA {
     }
 }
 
+// Upper bounds in seconds of the idle time buckets of the reclaimed socket entries, the last
+// bucket takes the rest. Must be kept in sync with SOCKET_RECLAIM_AGE_BUCKETS in config.h.
+pub const SOCKET_RECLAIM_AGE_BUCKETS: [u32; 3] = [30, 120, 600];
+pub const SOCKET_RECLAIM_AGE_BUCKET_NUM: usize = SOCKET_RECLAIM_AGE_BUCKETS.len() + 1;
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct SK_TRACE_STATS {
@@ -509,6 +532,29 @@ pub struct SK_TRACE_STATS {
     pub dropped_packets: u64,
     pub kern_missed_packets: u64,
     pub invalid_packets: u64,
+
+    // Processes selected for openssl uprobes with SSL_read/SSL_write resolved / not resolved
+    pub ssl_uprobe_targets: u64,
+    pub ssl_uprobe_target_failed: u64,
+
+    // Retries of failed golang/openssl uprobe attachments, targets given up and targets retrying
+    pub uprobe_attach_retries: u64,
+    pub uprobe_attach_gave_up: u64,
+    pub uprobe_targets_retrying: u64,
+
+    // Socket map reclaims, entries reclaimed, average and maximum reclaim latency in microseconds,
+    // and idle time of the reclaimed entries bucketed by SOCKET_RECLAIM_AGE_BUCKETS
+    pub socket_map_reclaims: u64,
+    pub socket_map_reclaimed: u64,
+    pub socket_map_reclaim_avg_us: u64,
+    pub socket_map_reclaim_max_us: u64,
+    pub socket_map_reclaim_ages: [u64; SOCKET_RECLAIM_AGE_BUCKET_NUM],
+
+    // IO events dropped by the device filter in eBPF
+    pub io_event_dev_filtered: u64,
+
+    // enum io_uring_trace_status
+    pub io_uring_trace_status: u8,
 }
 
 #[repr(C)]
@@ -564,6 +610,61 @@ pub struct stack_profile_data {
     pub stack_data: *mut c_char,
 }
 
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct java_syms_refresh_result {
+    pub pid: c_int,
+    pub error: c_int, // 0 on success, negative errno otherwise
+    pub coalesced: bool,
+    pub file_size: i64,
+    pub cost_us: u64,
+}
+
+pub const UPROBE_TARGET_GOLANG: u8 = 0;
+pub const UPROBE_TARGET_OPENSSL: u8 = 1;
+
+pub const UPROBE_STATE_ATTACHED: u8 = 0;
+pub const UPROBE_STATE_RETRYING: u8 = 1;
+pub const UPROBE_STATE_GAVE_UP: u8 = 2;
+
+pub const UPROBE_TARGETS_MAX: usize = 4096;
+
+// Attachment state of a uprobe target, see user/uprobe_retry.h
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct uprobe_target_info {
+    pub pid: i32,
+    pub target_type: u8,
+    pub state: u8,
+    pub attempts: u16,
+    pub inode: u64,
+    pub hash: u64,
+    pub next_retry: u32, // system uptime in seconds, 0 if not retrying
+    pub reason: [u8; 64],
+    pub path: [u8; 256],
+}
+
+impl Default for uprobe_target_info {
+    fn default() -> Self {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+// eBPF features supported by the running kernel, see user/capability.h
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ebpf_capability {
+    pub btf_vmlinux: bool,
+    pub perf_event_array: bool,
+    pub ring_buffer: bool,
+    pub probe_read_kernel: bool,
+    pub kprobe: bool,
+    pub uprobe: bool,
+    pub tracepoint: bool,
+    pub perf_event: bool,
+    pub fentry: bool,
+}
+
 extern "C" {
     /*
      * Set maximum amount of data passed to the agent by eBPF program.
@@ -577,8 +678,24 @@ extern "C" {
     pub fn set_go_tracing_timeout(timeout: c_int) -> c_int;
     pub fn set_io_event_collect_mode(mode: c_int) -> c_int;
     pub fn set_io_event_minimal_duration(duration: c_ulonglong) -> c_int;
+    /*
+     * Filter IO events by the device of the file system in eBPF.
+     * @mode IO_EVENT_DEV_FILTER_NONE/INCLUDE/EXCLUDE
+     * @devs devices in kernel internal encoding (major << 20 | minor)
+     * @count number of devices, at most IO_EVENT_DEV_FILTER_MAX
+     *
+     * @return 0 on success, < 0 on failure.
+     */
+    pub fn set_io_event_dev_filter(mode: c_int, devs: *const u32, count: c_int) -> c_int;
     pub fn set_allow_port_bitmap(bitmap: *const c_uchar) -> c_int;
     pub fn set_bypass_port_bitmap(bitmap: *const c_uchar) -> c_int;
+    /*
+     * Apply the port bitmaps to a running socket tracer, the datapath
+     * switches to the new whitelist and blacklist at once.
+     *
+     * @return 0 on success, ETR_NOTEXIST if the socket tracer is not running.
+     */
+    pub fn update_kprobe_port_bitmaps() -> c_int;
     pub fn enable_ebpf_protocol(protocol: c_int) -> c_int;
     pub fn enable_ebpf_seg_reasm_protocol(protocol: c_int) -> c_int;
     pub fn set_feature_regex(idx: c_int, pattern: *const c_char) -> c_int;
@@ -636,6 +753,31 @@ extern "C" {
 
     pub fn set_uprobe_golang_enabled(enabled: bool) -> c_void;
     pub fn set_uprobe_openssl_enabled(enabled: bool) -> c_void;
+    /*
+     * Override Go struct member offsets for a range of Go versions, the
+     * overrides take precedence over the built-in offsets.
+     * @ver_min, @ver_max inclusive, encoded as GO_VERSION()
+     * @structure, @field_name e.g.: "runtime.g" and "goid"
+     *
+     * @return 0 on success, ETR_NOTEXIST for unknown fields, ETR_NOROOM if full
+     */
+    pub fn add_go_offset_override(
+        ver_min: u32,
+        ver_max: u32,
+        structure: *const c_char,
+        field_name: *const c_char,
+        offset: c_int,
+    ) -> c_int;
+    pub fn clear_go_offset_overrides() -> c_void;
+    /*
+     * Set extra SSL objects to probe, both are comma separated lists.
+     * @library_paths glob patterns matching the path of mapped libraries
+     * @binary_names base names of mapped executables or libraries
+     */
+    pub fn set_uprobe_openssl_targets(
+        library_paths: *const c_char,
+        binary_names: *const c_char,
+    ) -> c_int;
 
     // 获取socket_tracer的这种统计数据的接口
     pub fn socket_tracer_stats() -> SK_TRACE_STATS;
@@ -675,6 +817,11 @@ extern "C" {
         socket_map_max_reclaim: c_uint,
     ) -> c_int;
 
+    // Update the socket map reclaim threshold at runtime, capped at max_socket_entries.
+    pub fn set_socket_map_reclaim_threshold(threshold: c_uint);
+    // The current number of entries in the socket map
+    pub fn get_socket_map_used() -> c_uint;
+
     // 停止tracer运行
     // 返回值：成功返回0，否则返回非0
     pub fn socket_tracer_stop() -> c_int;
@@ -740,6 +887,16 @@ extern "C" {
      */
     pub fn set_profiler_cpu_aggregation(flag: c_int) -> c_int;
 
+    /*
+     * Change the sampling frequency of a running on-cpu profiler without
+     * re-attaching the perf events.
+     *
+     * @freq sample frequency, Hertz.
+     *
+     * @returns 0 on success, < 0 on error
+     */
+    pub fn set_profiler_sample_freq(freq: c_int) -> c_int;
+
     /*
      * profile data release
      */
@@ -801,10 +958,43 @@ extern "C" {
         callback: extern "C" fn(data: *mut c_char, len: c_int),
     ) -> c_int;
 
+    /*
+     * Regenerate the symbol file of a Java process immediately, and have its
+     * symbol cache rebuilt at the next symbolization. A refresh of the same
+     * process already in progress is waited for instead of started again.
+     *
+     * @pid Process ID of a Java process
+     * @res Refresh outcome
+     * @return 0 on success, -1 on failure (see res.error).
+     */
+    pub fn java_syms_refresh(pid: c_int, res: *mut java_syms_refresh_result) -> c_int;
+
+    /*
+     * Get the PIDs of Java processes known to the profiler.
+     *
+     * @return the number of PIDs written to `pids`.
+     */
+    pub fn get_java_pids(pids: *mut c_int, max: c_int) -> c_int;
+
+    /*
+     * Java symbol file refreshes since the last call, done periodically and
+     * by java_syms_refresh() respectively.
+     */
+    pub fn get_java_syms_refresh_counts(scheduled: *mut u64, manual: *mut u64);
+
+    /*
+     * Get the attachment states of the golang/openssl uprobe targets
+     * attached after retries, retrying or given up.
+     *
+     * @return the number of targets written to `infos`.
+     */
+    pub fn get_uprobe_targets(infos: *mut uprobe_target_info, max: c_int) -> c_int;
+
     pub fn enable_oncpu_profiler() -> c_int;
     pub fn disable_oncpu_profiler() -> c_int;
     pub fn show_collect_pool();
     pub fn disable_syscall_trace_id() -> c_int;
+    pub fn enable_io_uring_trace();
 
     pub fn dwarf_available() -> bool;
     /*
@@ -842,6 +1032,13 @@ extern "C" {
     pub fn enable_unix_socket_feature();
     pub fn disable_fentry();
     pub fn enable_fentry();
+    /*
+     * Probe the eBPF capabilities of the running kernel, must be called
+     * after bpf_tracer_init(). The result is probed once and cached.
+     *
+     * @returns 0 on success, < 0 on error
+     */
+    pub fn ebpf_capability_probe(cap: *mut ebpf_capability) -> c_int;
     pub fn set_virtual_file_collect(enabled: bool) -> c_int;
 
     cfg_if::cfg_if! {
@@ -0,0 +1,114 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#include <unistd.h>
+#include <pthread.h>
+#include <bcc/linux/bpf.h>
+#include <bcc/libbpf.h>
+#include "config.h"
+#include "utils.h"
+#include "log.h"
+#include "load.h"
+#include "socket.h"
+#include "capability.h"
+
+static struct ebpf_capability capability;
+static pthread_once_t capability_once = PTHREAD_ONCE_INIT;
+
+static bool prog_type_supported(enum bpf_prog_type type)
+{
+	struct bpf_insn insns[] = {
+		BPF_ALU64_IMM(BPF_MOV, BPF_REG_0, 0),	/* r0 = 0 */
+		BPF_EXIT_INSN(),
+	};
+
+	int stderr_fd = suspend_stderr();
+	int fd = df_prog_load(type, NULL, insns, sizeof(insns));
+	if (stderr_fd >= 0)
+		resume_stderr(stderr_fd);
+	if (fd < 0)
+		return false;
+
+	close(fd);
+	return true;
+}
+
+static bool map_type_supported(enum bpf_map_type type, int key_size,
+			       int value_size, int max_entries)
+{
+	int fd = bcc_create_map(type, NULL, key_size, value_size, max_entries,
+				0);
+	if (fd < 0)
+		return false;
+
+	close(fd);
+	return true;
+}
+
+// A PMU or a tracefs interface is needed to create the probe events
+static bool probe_events_available(const char *pmu, const char *events)
+{
+	char path[128];
+	snprintf(path, sizeof(path), "/sys/bus/event_source/devices/%s/type",
+		 pmu);
+	if (access(path, R_OK) == 0)
+		return true;
+
+	snprintf(path, sizeof(path), "/sys/kernel/debug/tracing/%s", events);
+	if (access(path, W_OK) == 0)
+		return true;
+
+	snprintf(path, sizeof(path), "/sys/kernel/tracing/%s", events);
+	return access(path, W_OK) == 0;
+}
+
+static void capability_probe_once(void)
+{
+	struct ebpf_capability *c = &capability;
+	bool kprobe_prog = prog_type_supported(BPF_PROG_TYPE_KPROBE);
+
+	c->btf_vmlinux = access("/sys/kernel/btf/vmlinux", R_OK) == 0;
+	c->perf_event_array =
+	    map_type_supported(BPF_MAP_TYPE_PERF_EVENT_ARRAY, sizeof(int),
+			       sizeof(int), 1);
+	c->ring_buffer =
+	    map_type_supported(BPF_MAP_TYPE_RINGBUF, 0, 0, getpagesize());
+	c->probe_read_kernel = probe_read_kernel_supported();
+	c->kprobe = kprobe_prog
+	    && probe_events_available("kprobe", "kprobe_events");
+	c->uprobe = kprobe_prog
+	    && probe_events_available("uprobe", "uprobe_events");
+	c->tracepoint = prog_type_supported(BPF_PROG_TYPE_TRACEPOINT);
+	c->perf_event = prog_type_supported(BPF_PROG_TYPE_PERF_EVENT);
+	c->fentry = c->btf_vmlinux && fentry_supported();
+
+	ebpf_info("eBPF capability: btf_vmlinux %d perf_event_array %d "
+		  "ring_buffer %d probe_read_kernel %d kprobe %d uprobe %d "
+		  "tracepoint %d perf_event %d fentry %d\n",
+		  c->btf_vmlinux, c->perf_event_array, c->ring_buffer,
+		  c->probe_read_kernel, c->kprobe, c->uprobe, c->tracepoint,
+		  c->perf_event, c->fentry);
+}
+
+int ebpf_capability_probe(struct ebpf_capability *cap)
+{
+	if (cap == NULL)
+		return ETR_INVAL;
+
+	pthread_once(&capability_once, capability_probe_once);
+	*cap = capability;
+	return ETR_OK;
+}
@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef DF_USER_CAPABILITY_H
+#define DF_USER_CAPABILITY_H
+
+#include <stdbool.h>
+
+/*
+ * eBPF features supported by the running kernel.
+ *
+ * Must be kept in sync with `ebpf_capability` in mod.rs.
+ */
+struct ebpf_capability {
+	bool btf_vmlinux;	// /sys/kernel/btf/vmlinux is readable
+	bool perf_event_array;	// BPF_MAP_TYPE_PERF_EVENT_ARRAY
+	bool ring_buffer;	// BPF_MAP_TYPE_RINGBUF
+	bool probe_read_kernel;	// bpf_probe_read_{kernel,user}[_str]
+	bool kprobe;
+	bool uprobe;
+	bool tracepoint;
+	bool perf_event;	// BPF_PROG_TYPE_PERF_EVENT
+	bool fentry;		// fentry/fexit usable for socket tracing
+};
+
+/*
+ * Probe the eBPF capabilities of the running kernel, must be called
+ * after bpf_tracer_init(). The result is probed once and cached.
+ *
+ * @returns 0 on success, < 0 on error
+ */
+int ebpf_capability_probe(struct ebpf_capability *cap);
+#endif /* DF_USER_CAPABILITY_H */
@@ -43,10 +43,12 @@
 #define MAP_TRACE_STATS_NAME            "__trace_stats_map"
 #define MAP_PROTO_FILTER_NAME		"__protocol_filter"
 #define MAP_KPROBE_PORT_BITMAP_NAME	"__kprobe_port_bitmap"
+#define MAP_KPROBE_PORT_SLOT_NAME	"__kprobe_port_slot"
 #define MAP_ADAPT_KERN_DATA_NAME	"__adapt_kern_data_map"
 #define MAP_PROTO_PORTS_BITMAPS_NAME	"__proto_ports_bitmap"
 #define MAP_ALLOW_REASM_PROTOS_NAME     "__allow_reasm_protos_map"
 #define MAP_PKTS_STATES_NAME		"__pkts_stats_map"
+#define MAP_IO_EVENT_DEV_FILTER_NAME	"__io_event_dev_filter_map"
 
 //Program jmp tables
 #define MAP_PROGS_JMP_KP_NAME		"__progs_jmp_kp_map"
@@ -210,6 +212,13 @@ enum cfg_feature_idx {
  */
 #define SOCKET_RECLAIM_TIMEOUT_DEF	10
 
+/*
+ * Upper bounds (in seconds, inclusive) of the idle time buckets of the
+ * reclaimed socket entries, the last bucket takes the rest.
+ */
+#define SOCKET_RECLAIM_AGE_BUCKETS	{ 30, 120, 600 }
+#define SOCKET_RECLAIM_AGE_BUCKET_NUM	4
+
 /*
  * When the trace map is recycled, each trace information is recycled without a matching
  * action for more than 10 seconds.
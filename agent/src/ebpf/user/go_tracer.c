@@ -44,6 +44,7 @@
 #include "symbol.h"
 #include "tracer.h"
 #include "go_tracer.h"
+#include "uprobe_retry.h"
 #include "offset.h"
 #include "table.h"
 #include "symbol.h"
@@ -179,6 +180,21 @@ static struct data_members offsets[] = {
 	}
 };
 
+/*
+ * User supplied offsets for Go versions whose structures changed, they
+ * take precedence over the offsets analyzed from DWARF and the defaults.
+ */
+struct go_offset_override {
+	uint32_t ver_min;	// GO_VERSION(), inclusive
+	uint32_t ver_max;	// GO_VERSION(), inclusive
+	enum offsets_index idx;
+	int offset;
+};
+
+#define GO_OFFSET_OVERRIDES_MAX 256
+static struct go_offset_override go_offset_overrides[GO_OFFSET_OVERRIDES_MAX];
+static int go_offset_overrides_count;
+
 static struct symbol syms[] = {
 	{
 		.type = GO_UPROBE,
@@ -466,6 +482,65 @@ exit:
 
 }
 
+/*
+ * Look up the override of an offset for the Go version.
+ * @version_matched set to true if any override covers the version
+ *
+ * @return the offset, or ETR_NOTEXIST if not overridden.
+ */
+static int find_go_offset_override(uint32_t version, enum offsets_index idx,
+				   bool *version_matched)
+{
+	int i;
+	struct go_offset_override *o;
+	for (i = 0; i < go_offset_overrides_count; i++) {
+		o = &go_offset_overrides[i];
+		if (version < o->ver_min || version > o->ver_max)
+			continue;
+		*version_matched = true;
+		if (o->idx == idx)
+			return o->offset;
+	}
+
+	return ETR_NOTEXIST;
+}
+
+int add_go_offset_override(uint32_t ver_min, uint32_t ver_max,
+			   const char *structure, const char *field_name,
+			   int offset)
+{
+	int k;
+	struct go_offset_override *o;
+	if (go_offset_overrides_count >= GO_OFFSET_OVERRIDES_MAX) {
+		ebpf_warning("Too many go offset overrides, the maximum is %d\n",
+			     GO_OFFSET_OVERRIDES_MAX);
+		return ETR_NOROOM;
+	}
+
+	for (k = 0; k < NELEMS(offsets); k++) {
+		if (strcmp(offsets[k].structure, structure) ||
+		    strcmp(offsets[k].field_name, field_name))
+			continue;
+
+		o = &go_offset_overrides[go_offset_overrides_count++];
+		o->ver_min = ver_min;
+		o->ver_max = ver_max;
+		o->idx = offsets[k].idx;
+		o->offset = offset;
+		ebpf_info("Go offset override, version:[0x%x, 0x%x] %s.%s:%d\n",
+			  ver_min, ver_max, structure, field_name, offset);
+		return ETR_OK;
+	}
+
+	ebpf_warning("Unknown go offset %s.%s\n", structure, field_name);
+	return ETR_NOTEXIST;
+}
+
+void clear_go_offset_overrides(void)
+{
+	go_offset_overrides_count = 0;
+}
+
 static struct proc_info *find_proc_info_by_pid(int pid)
 {
 	struct proc_info *p_info;
@@ -594,18 +669,28 @@ static int resolve_bin_file(const char *path, int pid,
 			goto offset_failed;
 		}
 		// resolve all offsets.
+		bool override_matched = false;
 		for (int k = 0; k < NELEMS(offsets); k++) {
 			off = &offsets[k];
-			int offset = struct_member_offset_analyze(binary_path,
-								  off->
-								  structure,
-								  off->
-								  field_name);
+			int offset =
+			    find_go_offset_override(p_info->info.version,
+						    off->idx,
+						    &override_matched);
+			if (offset < 0)
+				offset = struct_member_offset_analyze(binary_path,
+								      off->
+								      structure,
+								      off->
+								      field_name);
 			if (offset == ETR_INVAL)
 				offset = off->default_offset;
 
 			p_info->info.offsets[off->idx] = offset;
 		}
+		if (go_offset_overrides_count > 0 && !override_matched)
+			ebpf_warning("No offset overrides for go%d.%d "
+				     "(pid %d), use the built-in offsets.\n",
+				     go_ver->major, go_ver->minor, pid);
 
 		const char *tcp_conn_sym, *tls_conn_sym, *syscall_conn_sym;
 		if (p_info->info.version < GO_VERSION(1, 20, 0)) {
@@ -685,13 +770,20 @@ static int proc_parse_and_register(int pid, struct tracer_probes_conf *conf)
 	if (!is_feature_matched(FEATURE_UPROBE_GOLANG, pid, path))
 		goto out;
 
+	/*
+	 * The binary may still be written or its final layer not mounted yet
+	 * during rolling deploys, failures are retried with backoff.
+	 */
 	struct version_info go_version;
 	memset(&go_version, 0, sizeof(go_version));
-	if (fetch_go_elf_version(path, &go_version)) {
-		resolve_bin_file(path, pid, &go_version, conf, &syms_count);
-	} else {
-		free((void *)path);
-		return syms_count;
+	if (!fetch_go_elf_version(path, &go_version)) {
+		uprobe_target_update(UPROBE_TARGET_GOLANG, pid, path,
+				     "go version not found");
+	} else if (resolve_bin_file(path, pid, &go_version, conf,
+				    &syms_count) != ETR_OK) {
+		uprobe_target_update(UPROBE_TARGET_GOLANG, pid, path,
+				     syms_count == 0 ? "symbols not found" :
+				     "offsets not resolved");
 	}
 
 out:
@@ -955,11 +1047,18 @@ static void process_execute_handle(int pid, struct bpf_tracer *tracer)
 		tracer_uprobes_update(tracer);
 		// Attach probes
 		int count = 0;
+		char *path = get_elf_path_by_pid(pid);
 		if (tracer_hooks_process(tracer, HOOK_ATTACH, &count) == ETR_OK) {
 			if (count > 0)
 				// Update offsets map
 				update_proc_info_to_map(tracer);
+			uprobe_target_update(UPROBE_TARGET_GOLANG, pid, path,
+					     NULL);
+		} else {
+			uprobe_target_update(UPROBE_TARGET_GOLANG, pid, path,
+					     "attach failed");
 		}
+		free(path);
 	}
 	pthread_mutex_unlock(&tracer->mutex_probes_lock);
 }
@@ -1054,6 +1153,7 @@ void go_process_exit(int pid)
 	if (!is_feature_enabled(FEATURE_UPROBE_GOLANG))
 		return;
 
+	uprobe_target_remove(UPROBE_TARGET_GOLANG, pid);
 	tracer = find_bpf_tracer(SK_TRACER_NAME);
 	if (tracer == NULL)
 		return;
@@ -1061,6 +1161,24 @@ void go_process_exit(int pid)
 	process_exit_handle(pid, tracer);
 }
 
+// Retry the failed attachments due, see uprobe_retry.h
+static void go_process_retry_handle(void)
+{
+	int pids[16];
+	int i, n;
+	struct bpf_tracer *tracer;
+	if (!is_feature_enabled(FEATURE_UPROBE_GOLANG))
+		return;
+
+	tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL || tracer->probes_count > OPEN_FILES_MAX)
+		return;
+
+	n = uprobe_targets_due(UPROBE_TARGET_GOLANG, pids, NELEMS(pids));
+	for (i = 0; i < n; i++)
+		process_execute_handle(pids[i], tracer);
+}
+
 /**
  * go_process_events_handle - process exec/exit events handle, called by process_events_handle_main().
  */
@@ -1109,6 +1227,8 @@ void go_process_events_handle(void)
 			break;
 		}
 	} while (true);
+
+	go_process_retry_handle();
 }
 
 void golang_trace_handle(int pid, enum match_pids_act act)
@@ -51,4 +51,12 @@ void golang_trace_handle(int pid, enum match_pids_act act);
 void golang_trace_init(void);
 void set_uprobe_golang_enabled(bool enabled);
 bool is_golang_trace_enabled(void);
+/*
+ * Override the offset of a structure field for Go versions in
+ * [ver_min, ver_max], the versions are encoded by GO_VERSION().
+ */
+int add_go_offset_override(uint32_t ver_min, uint32_t ver_max,
+			   const char *structure, const char *field_name,
+			   int offset);
+void clear_go_offset_overrides(void);
 #endif
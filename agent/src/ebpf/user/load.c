@@ -318,6 +318,11 @@ static bool feat_probe_read_kernel(unsigned kern_version)
 	return false;
 }
 
+bool probe_read_kernel_supported(void)
+{
+	return feat_probe_read_kernel(fetch_kernel_version_code());
+}
+
 static bool is_helper_call_insn(struct bpf_insn *insn, int32_t * func_id)
 {
 	if (BPF_CLASS(insn->code) == BPF_JMP &&
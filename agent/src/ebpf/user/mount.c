@@ -653,7 +653,7 @@ static int replace_suffix_prefix(const char *str1, const char *str2,
 }
 
 u32 copy_file_metrics(int pid, void *dst, void *src, int len,
-		      u32 mntns_id, const char *mount_point,
+		      u32 mntns_id, kern_dev_t s_dev, const char *mount_point,
 		      const char *mount_source, const char *root,
 		      fs_type_t file_type)
 {
@@ -792,6 +792,7 @@ copy_event:
 	u_event->file_type = file_type;
 	u_event->mnt_id = event->mnt_id;
 	u_event->mntns_id = event->mntns_id;
+	u_event->s_dev = s_dev;
 	strcpy_s_inline(u_event->mount_source, sizeof(u_event->mount_source),
 			mount_source, strlen(mount_source));
 	fast_strncat_trunc(mntns_str, mount_point, u_event->mount_point,
@@ -179,6 +179,7 @@ void get_mount_info(pid_t pid, int mnt_id, u32 mntns_id,
  * @param[in]  src           Source buffer (raw eBPF event)
  * @param[in]  len           Length of destination buffer
  * @param[in]  mntns_id      The mount namespace ID of the file
+ * @param[in]  s_dev         The device of the file system
  * @param[in]  mount_point   Mount point path
  * @param[in]  mount_source  Mount source path
  * @param[in]  root	     Mount root path
@@ -186,7 +187,7 @@ void get_mount_info(pid_t pid, int mnt_id, u32 mntns_id,
  * @return Number of bytes written to dst
  */
 uint32_t copy_file_metrics(int pid, void *dst, void *src, int len,
-			   u32 mntns_id, const char *mount_point,
+			   u32 mntns_id, kern_dev_t s_dev, const char *mount_point,
 			   const char *mount_source, const char *root,
 			   fs_type_t file_type);
 /**
@@ -464,6 +464,7 @@ static int config_symbolizer_proc_info(struct symbolizer_proc_info *p, int pid)
 	p->cache_need_update = false;
 	p->gen_java_syms_file_err = false;
 	p->need_new_symbol_collector = true;
+	p->java_syms_refreshing = 0;
 	p->lock = 0;
 	pthread_mutex_init(&p->mutex, NULL);
 	p->syms_cache = 0;
@@ -1006,7 +1007,116 @@ int creat_ksyms_cache(void)
 	return 0;
 }
 
+/*
+ * Regenerate the symbol file of a Java process on demand, and have its
+ * symbol cache rebuilt at the next symbolization without waiting for
+ * 'java_syms_update_delay'.
+ *
+ * @pid Process ID of a Java process known to the symbolizer
+ * @res Refresh outcome
+ * @returns 0 on success, -1 on error (see res->error)
+ */
+int java_syms_refresh(int pid, struct java_syms_refresh_result *res)
+{
+	memset(res, 0, sizeof(*res));
+	res->pid = pid;
+
+	symbol_caches_hash_t *h = &syms_cache_hash;
+	struct symbolizer_cache_kvp kv;
+	kv.k.pid = (u64) pid;
+	kv.v.proc_info_p = 0;
+	if (symbol_caches_hash_search(h, (symbol_caches_hash_kv *) & kv,
+				      (symbol_caches_hash_kv *) & kv) != 0
+	    || kv.v.proc_info_p == 0) {
+		res->error = -ENOENT;
+		return -1;
+	}
+
+	struct symbolizer_proc_info *p =
+	    (struct symbolizer_proc_info *)kv.v.proc_info_p;
+	AO_INC(&p->use);
+	if (!p->verified || !p->is_java || AO_GET(&p->is_exit)) {
+		AO_DEC(&p->use);
+		res->error = -ENOENT;
+		return -1;
+	}
+
+	java_syms_manual_refresh(p, res);
+	if (res->error == 0) {
+		symbolizer_proc_lock(p);
+		p->update_syms_table_time = current_sys_time_secs();
+		symbolizer_proc_unlock(p);
+	}
+	AO_DEC(&p->use);
+
+	ebpf_info("Manual refresh of JAVA symbol file, PID %d, size %ld, "
+		  "cost %lu us, coalesced %d, error %d\n", pid,
+		  res->file_size, res->cost_us, res->coalesced, res->error);
+	return res->error == 0 ? 0 : -1;
+}
+
+struct java_pids_ctx {
+	int *pids;
+	int max;
+	int count;
+};
+
+static int java_pids_kvp_cb(symbol_caches_hash_kv * kvp, void *arg)
+{
+	struct symbolizer_cache_kvp *kv = (struct symbolizer_cache_kvp *)kvp;
+	struct java_pids_ctx *ctx = arg;
+	struct symbolizer_proc_info *p =
+	    (struct symbolizer_proc_info *)kv->v.proc_info_p;
+	if (p == NULL)
+		return BIHASH_WALK_CONTINUE;
+
+	AO_INC(&p->use);
+	if (p->verified && p->is_java && !AO_GET(&p->is_exit)
+	    && ctx->count < ctx->max)
+		ctx->pids[ctx->count++] = p->pid;
+	AO_DEC(&p->use);
+
+	return ctx->count < ctx->max ?
+	    BIHASH_WALK_CONTINUE : BIHASH_WALK_STOP;
+}
+
+/*
+ * Get the PIDs of Java processes known to the symbolizer.
+ *
+ * @pids Buffer for the PIDs
+ * @max Size of the buffer
+ * @returns the number of PIDs written
+ */
+int get_java_pids(int *pids, int max)
+{
+	struct java_pids_ctx ctx = {
+		.pids = pids,
+		.max = max,
+		.count = 0,
+	};
+	if (max <= 0)
+		return 0;
+
+	symbol_caches_hash_foreach_key_value_pair(&syms_cache_hash,
+						  java_pids_kvp_cb,
+						  (void *)&ctx);
+	return ctx.count;
+}
+
 #else /* defined AARCH64_MUSL */
+int java_syms_refresh(int pid, struct java_syms_refresh_result *res)
+{
+	memset(res, 0, sizeof(*res));
+	res->pid = pid;
+	res->error = -ENOTSUP;
+	return -1;
+}
+
+int get_java_pids(int *pids, int max)
+{
+	return 0;
+}
+
 /* pid : The process ID (PID) that occurs when a process exits. */
 void update_proc_info_cache(pid_t pid, enum proc_act_type type)
 {
@@ -81,6 +81,8 @@ struct symbolizer_proc_info {
 	bool unknown_syms_found;
 	/* Is it necessary to create a new Java symbol collector? */
 	bool need_new_symbol_collector;
+	/* Set while the Java symbol file is being regenerated */
+	u64 java_syms_refreshing;
 	/* Expiration time (in seconds) for updating the Java symbol table */
 	u64 update_syms_table_time;
 	/* process name */
@@ -190,6 +192,9 @@ void symbolizer_kernel_unlock(void);
 #endif
 void exec_proc_info_cache_update(void);
 int create_and_init_proc_info_caches(void);
+struct java_syms_refresh_result;
+int java_syms_refresh(int pid, struct java_syms_refresh_result *res);
+int get_java_pids(int *pids, int max);
 /**
  * @brief Retrieve container ID, process name, and mount point from the cache based on a PID.
  *
@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+#include <errno.h>
 #include <sys/stat.h>
 #include <bcc/perf_reader.h>
 #include "../../config.h"
@@ -42,6 +43,10 @@ static pthread_mutex_t list_lock;
 static struct list_head java_syms_update_tasks_head;
 static u64 tasks_list_init_done;
 
+/* Symbol file refreshes done by 'java_syms_update_main' and on demand. */
+static u64 java_syms_scheduled_refresh_count;
+static u64 java_syms_manual_refresh_count;
+
 /** Collect Java symbols.
  *
  * @pid Process ID
@@ -127,6 +132,79 @@ void add_java_syms_update_task(struct symbolizer_proc_info *p_info)
 	pthread_mutex_unlock(&list_lock);
 }
 
+static void java_syms_update_state(struct symbolizer_proc_info *p, int ret)
+{
+	if (ret != JAVA_SYMS_COLLECT_ERR && ret != JAVA_CREATE_COLLECTOR_ERR) {
+		if (ret == JAVA_SYMS_NEED_UPDATE
+		    || ret == JAVA_SYMS_NEW_COLLECTOR)
+			p->cache_need_update = true;
+		else
+			p->cache_need_update = false;
+
+		if (ret != JAVA_SYMS_NEW_COLLECTOR) {
+			p->need_new_symbol_collector = false;
+		}
+
+		p->gen_java_syms_file_err = false;
+	} else {
+		/*
+		 * Mark an error occurred when creating collector,
+		 * no further symbol collection for this process.
+		 */
+		if (ret == JAVA_CREATE_COLLECTOR_ERR)
+			p->gen_java_syms_file_err = true;
+
+		p->cache_need_update = false;
+	}
+
+	AO_SET(&p->new_java_syms_file, true);
+}
+
+/*
+ * Regenerate the symbol file of a Java process right away, regardless of
+ * earlier collection errors. If the process is already being refreshed,
+ * wait for that refresh to finish and report its result instead of
+ * starting another one.
+ *
+ * The caller holds a reference to 'p' and rebuilds the symbol cache.
+ */
+void java_syms_manual_refresh(struct symbolizer_proc_info *p,
+			      struct java_syms_refresh_result *res)
+{
+	u64 start_time = gettime(CLOCK_MONOTONIC, TIME_TYPE_NAN);
+	bool ok;
+	if (AO_CASB(&p->java_syms_refreshing, 0, 1)) {
+		int ret;
+		collect_java_symbols(p->pid, &ret, false);
+		java_syms_update_state(p, ret);
+		AO_INC(&java_syms_manual_refresh_count);
+		AO_SET(&p->java_syms_refreshing, 0);
+		ok = (ret == JAVA_SYMS_NEED_UPDATE
+		      || ret == JAVA_SYMS_NEW_COLLECTOR);
+	} else {
+		res->coalesced = true;
+		while (AO_GET(&p->java_syms_refreshing))
+			usleep(LOOP_DELAY_US);
+		ok = !p->gen_java_syms_file_err;
+	}
+	res->cost_us =
+	    (gettime(CLOCK_MONOTONIC, TIME_TYPE_NAN) - start_time) / 1000ULL;
+
+	res->file_size = ok ? get_local_symbol_file_sz(p->pid) : 0;
+	if (res->file_size <= 0) {
+		res->file_size = 0;
+		res->error = -EIO;
+	}
+}
+
+void get_java_syms_refresh_counts(u64 *scheduled, u64 *manual)
+{
+	*scheduled = AO_GET(&java_syms_scheduled_refresh_count);
+	*manual = AO_GET(&java_syms_manual_refresh_count);
+	AO_SUB(&java_syms_scheduled_refresh_count, *scheduled);
+	AO_SUB(&java_syms_manual_refresh_count, *manual);
+}
+
 void java_syms_update_main(void *arg)
 {
 	// Ensure the profiler is initialized and currently running
@@ -156,36 +234,18 @@ void java_syms_update_main(void *arg)
 			struct symbolizer_proc_info *p = task->p;
 			/* JAVA process has not exited. */
 			if (AO_GET(&p->use) > 1) {
-				int ret;
-				collect_java_symbols(p->pid, &ret,
-						     p->gen_java_syms_file_err);
-				if (ret != JAVA_SYMS_COLLECT_ERR
-				    && ret != JAVA_CREATE_COLLECTOR_ERR) {
-					if (ret == JAVA_SYMS_NEED_UPDATE
-					    || ret == JAVA_SYMS_NEW_COLLECTOR)
-						p->cache_need_update = true;
-					else
-						p->cache_need_update = false;
-
-					if (ret != JAVA_SYMS_NEW_COLLECTOR) {
-						p->need_new_symbol_collector =
-						    false;
-					}
-
-					p->gen_java_syms_file_err = false;
-				} else {
-					/*
-					 * Mark an error occurred when creating collector,
-					 * no further symbol collection for this process.
-					 */
-					if (ret == JAVA_CREATE_COLLECTOR_ERR)
-						p->gen_java_syms_file_err =
-						    true;
-
-					p->cache_need_update = false;
+				/*
+				 * Skip if a manual refresh of the process is in
+				 * progress, it updates the state when done.
+				 */
+				if (AO_CASB(&p->java_syms_refreshing, 0, 1)) {
+					int ret;
+					collect_java_symbols(p->pid, &ret,
+							     p->gen_java_syms_file_err);
+					java_syms_update_state(p, ret);
+					AO_INC(&java_syms_scheduled_refresh_count);
+					AO_SET(&p->java_syms_refreshing, 0);
 				}
-
-				AO_SET(&p->new_java_syms_file, true);
 			}
 
 			AO_DEC(&p->use);
@@ -28,8 +28,21 @@ struct java_syms_update_task {
 	struct symbolizer_proc_info *p;
 };
 
+/* Outcome of a Java symbol file refresh requested through java_syms_refresh() */
+struct java_syms_refresh_result {
+	int pid;
+	int error;		/* 0 on success, negative errno otherwise */
+	bool coalesced;		/* Waited for a refresh already in progress */
+	i64 file_size;		/* Size of the symbol file after refresh */
+	u64 cost_us;
+};
+
 void gen_java_symbols_file(int pid, int *ret_val, bool error_occurred);
+void collect_java_symbols(int pid, int *ret_val, bool error_occurred);
 void clean_local_java_symbols_files(int pid);
 void add_java_syms_update_task(struct symbolizer_proc_info *p_info);
+void java_syms_manual_refresh(struct symbolizer_proc_info *p,
+			      struct java_syms_refresh_result *res);
+void get_java_syms_refresh_counts(u64 *scheduled, u64 *manual);
 void java_syms_update_main(void *arg);
 #endif /* COLLECT_SYMS_FILE_H */
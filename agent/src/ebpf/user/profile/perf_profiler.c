@@ -24,6 +24,7 @@
 #include <sys/stat.h>
 #include <math.h>
 #include <signal.h>		/* kill() */
+#include <sys/ioctl.h>
 #include <bcc/perf_reader.h>
 #include "../config.h"
 #include "../common_utils.h"
@@ -971,6 +972,47 @@ int set_profiler_cpu_aggregation(int flag)
 	return (0);
 }
 
+/*
+ * Change the on-cpu sampling frequency of a running profiler.
+ *
+ * The perf events are opened in frequency mode, so PERF_EVENT_IOC_PERIOD
+ * updates attr.sample_freq in place and no re-attach is needed.
+ *
+ * @freq sample frequency, Hertz.
+ *
+ * @returns 0 on success, < 0 on error
+ */
+int set_profiler_sample_freq(int freq)
+{
+	struct bpf_tracer *t = profiler_tracer;
+	if (t == NULL || freq <= 0)
+		return (-1);
+
+	if (t->sample_freq == freq)
+		return (0);
+
+	int i, ret = 0;
+	u64 val = (u64) freq;
+	for (i = 0; i < ARRAY_SIZE(t->per_cpu_fds); i++) {
+		if (t->per_cpu_fds[i] <= 0)
+			continue;
+		if (ioctl(t->per_cpu_fds[i], PERF_EVENT_IOC_PERIOD, &val) < 0) {
+			ebpf_warning(LOG_CP_TAG
+				     "Set sample freq %d on cpu %d failed (%s).\n",
+				     freq, i, strerror(errno));
+			ret = -1;
+		}
+	}
+
+	if (ret == 0) {
+		ebpf_info(LOG_CP_TAG "Set sample freq from %d to %d.\n",
+			  t->sample_freq, freq);
+		t->sample_freq = freq;
+	}
+
+	return ret;
+}
+
 struct bpf_tracer *get_profiler_tracer(void)
 {
 	return profiler_tracer;
@@ -1083,6 +1125,11 @@ int set_profiler_cpu_aggregation(int flag)
 	return (-1);
 }
 
+int set_profiler_sample_freq(int freq)
+{
+	return (-1);
+}
+
 struct bpf_tracer *get_profiler_tracer(void)
 {
 	return NULL;
@@ -36,6 +36,7 @@
 #include "log.h"
 #include "go_tracer.h"
 #include "ssl_tracer.h"
+#include "uprobe_retry.h"
 #include "unwind_tracer.h"
 #include "load.h"
 #include "btf_core.h"
@@ -67,6 +68,10 @@ static bool virtual_file_collect_enable; // Whether to enable virtual file colle
  */
 static bool g_disable_syscall_tracing;
 
+// Set by enable_io_uring_trace()
+static bool io_uring_trace_enable;
+static enum io_uring_trace_status io_uring_trace_status;
+
 /*
  * tracer_hooks_detach() and tracer_hooks_attach() will become terrible
  * when the number of probes is very large. Because we have to spend a
@@ -112,6 +117,10 @@ static char datadump_file_path[DATADUMP_FILE_PATH_SIZE];
 static FILE *datadump_file;
 static pthread_mutex_t datadump_mutex;
 
+// The active slot of the double buffered kprobe port bitmaps
+static __u32 kprobe_port_slot;
+static pthread_mutex_t kprobe_port_mutex = PTHREAD_MUTEX_INITIALIZER;
+
 /*
  * The maximum amount of data passed to the agent by eBPF programe.
  * Set by set_data_limit_max()
@@ -123,6 +132,8 @@ static uint32_t go_tracing_timeout = GO_TRACING_TIMEOUT_DEFAULT;
 // 0: disable 1: during request 2: all
 static uint32_t io_event_collect_mode = 1;
 static uint64_t io_event_minimal_duration = 1000000;
+// Set by set_io_event_dev_filter()
+static struct io_event_dev_filter io_event_dev_filter;
 
 /*
  * The maximum threshold for socket map reclamation, with map
@@ -130,6 +141,14 @@ static uint64_t io_event_minimal_duration = 1000000;
  */
 static uint32_t conf_socket_map_max_reclaim;
 
+// Socket map reclaim statistics, reset on read in socket_tracer_stats()
+static uint64_t socket_map_reclaims;
+static uint64_t socket_map_reclaimed;
+static uint64_t socket_map_reclaim_total_us;
+static uint64_t socket_map_reclaim_max_us;
+static uint64_t socket_map_reclaim_ages[SOCKET_RECLAIM_AGE_BUCKET_NUM];
+static const uint32_t socket_reclaim_age_bounds[] = SOCKET_RECLAIM_AGE_BUCKETS;
+
 struct bpf_tracer *g_tracer;
 bpf_offset_param_t g_kern_offsets;
 
@@ -221,6 +240,12 @@ static bool fentry_can_attach(const char *name)
 	return fentry_try_attach(name);
 }
 
+bool fentry_supported(void)
+{
+	return fentry_can_attach(TEST_KFUNC_NAME) &&
+	    get_kfunc_params_num(TEST_KFUNC_NAME) == TEST_KFUNC_PARAMS_NUM;
+}
+
 static inline void
 kfunc_set_sym_for_entry_and_exit(struct tracer_probes_conf *tps, const char *fn)
 {
@@ -477,6 +502,40 @@ static void config_probes_for_kprobe(struct tracer_probes_conf *tps)
 	probes_set_enter_symbol(tps, "__sys_connect");
 }
 
+static void config_probes_for_io_uring(struct tracer_probes_conf *tps)
+{
+	if (!io_uring_trace_enable) {
+		io_uring_trace_status = IO_URING_TRACE_DISABLED;
+		return;
+	}
+
+	/*
+	 * The io_uring programs are only built into the kfunc and 5.2+
+	 * eBPF binaries, IORING_OP_SEND/IORING_OP_RECV tracing requires
+	 * Linux 5.10+.
+	 */
+	if (k_version < KERNEL_VERSION(5, 10, 0) ||
+	    (g_k_type != K_TYPE_KFUNC && g_k_type != K_TYPE_VER_5_2_PLUS)) {
+		io_uring_trace_status = IO_URING_TRACE_KERNEL_UNSUPPORTED;
+		ebpf_warning("io_uring tracing is not supported on linux %s,"
+			     " disabled.\n", linux_release);
+		return;
+	}
+
+	if (kallsyms_lookup_name("io_send") <= 0 ||
+	    kallsyms_lookup_name("io_recv") <= 0) {
+		io_uring_trace_status = IO_URING_TRACE_SYMBOL_MISSING;
+		ebpf_warning("io_send()/io_recv() not found, io_uring tracing"
+			     " disabled.\n");
+		return;
+	}
+
+	probes_set_symbol(tps, "io_send");
+	probes_set_symbol(tps, "io_recv");
+	// Confirmed after the offsets are obtained from BTF.
+	io_uring_trace_status = IO_URING_TRACE_OFFSETS_UNAVAILABLE;
+}
+
 static void socket_tracer_set_probes(struct tracer_probes_conf *tps)
 {
 	if (g_k_type == K_TYPE_KFUNC)
@@ -485,6 +544,8 @@ static void socket_tracer_set_probes(struct tracer_probes_conf *tps)
 		config_probes_for_kprobe(tps);
 	else
 		config_probes_for_kprobe_and_tracepoint(tps);
+
+	config_probes_for_io_uring(tps);
 }
 
 /* ==========================================================
@@ -1372,7 +1433,7 @@ static void reader_raw_cb(void *cookie, void *raw, int raw_size)
 				len =
 				    copy_file_metrics(sd->tgid, submit_data->cap_data
 						      + offset, sd->data, len,
-						      display_mntns_id, mount_point,
+						      display_mntns_id, s_dev, mount_point,
 						      mount_source, root, file_type);
 			} else {
 				memcpy_fast(submit_data->cap_data + offset,
@@ -1494,19 +1555,28 @@ static void reclaim_socket_map(struct bpf_tracer *tracer, uint32_t timeout)
 	conn_key = 0;
 	uint32_t uptime = get_sys_uptime();
 	uint32_t curr_socket_count = 0;
+	uint64_t ages[SOCKET_RECLAIM_AGE_BUCKET_NUM] = { 0 };
+	uint64_t start_us = gettime(CLOCK_MONOTONIC, TIME_TYPE_NAN) / 1000;
+	uint32_t age, max_reclaim = conf_socket_map_max_reclaim;
+	int i;
 	struct list_head clear_elem_head;
 	init_list_head(&clear_elem_head);
 
 	while (bpf_get_next_key(map_fd, &conn_key, &next_conn_key) == 0) {
 		if (bpf_lookup_elem(map_fd, &next_conn_key, &value) == 0) {
 			curr_socket_count++;
-			if ((uptime - value.update_time > timeout) &&
-			    (sockets_reclaim_count <
-			     conf_socket_map_max_reclaim)) {
+			age = uptime - value.update_time;
+			if ((age > timeout) &&
+			    (sockets_reclaim_count < max_reclaim)) {
 				if (insert_list(&next_conn_key,
 						sizeof(next_conn_key),
 						&clear_elem_head)) {
 					sockets_reclaim_count++;
+					i = 0;
+					while (i < SOCKET_RECLAIM_AGE_BUCKET_NUM - 1
+					       && age > socket_reclaim_age_bounds[i])
+						i++;
+					ages[i]++;
 				}
 			}
 		}
@@ -1515,13 +1585,52 @@ static void reclaim_socket_map(struct bpf_tracer *tracer, uint32_t timeout)
 
 	sockets_reclaim_count = __reclaim_map(map_fd, &clear_elem_head);
 	curr_socket_count -= sockets_reclaim_count;
+
+	uint64_t cost_us =
+	    gettime(CLOCK_MONOTONIC, TIME_TYPE_NAN) / 1000 - start_us;
+	__atomic_add_fetch(&socket_map_reclaims, 1, __ATOMIC_RELAXED);
+	__atomic_add_fetch(&socket_map_reclaimed, sockets_reclaim_count,
+			   __ATOMIC_RELAXED);
+	__atomic_add_fetch(&socket_map_reclaim_total_us, cost_us,
+			   __ATOMIC_RELAXED);
+	if (cost_us > __atomic_load_n(&socket_map_reclaim_max_us,
+				      __ATOMIC_RELAXED))
+		__atomic_store_n(&socket_map_reclaim_max_us, cost_us,
+				 __ATOMIC_RELAXED);
+	for (i = 0; i < SOCKET_RECLAIM_AGE_BUCKET_NUM; i++)
+		__atomic_add_fetch(&socket_map_reclaim_ages[i], ages[i],
+				   __ATOMIC_RELAXED);
 	if (!bpf_stats_map_update
 	    (tracer, curr_socket_count, -1, -1, -1, -1, -1)) {
 		ebpf_warning("Update trace statistics failed.\n");
 	}
 
-	ebpf_info("[%s] curr_socket_count %u sockets_reclaim_count :%u\n",
-		  __func__, curr_socket_count, sockets_reclaim_count);
+	ebpf_info("[%s] curr_socket_count %u sockets_reclaim_count :%u"
+		  " cost %luus\n", __func__, curr_socket_count,
+		  sockets_reclaim_count, cost_us);
+}
+
+uint32_t get_socket_map_used(void)
+{
+	struct trace_stats stats_total;
+	struct bpf_tracer *t = find_bpf_tracer(SK_TRACER_NAME);
+	if (t == NULL || !bpf_stats_map_collect(t, &stats_total))
+		return 0;
+
+	return stats_total.socket_map_count;
+}
+
+void set_socket_map_reclaim_threshold(uint32_t threshold)
+{
+	if (conf_max_socket_entries > 0 && threshold > conf_max_socket_entries)
+		threshold = conf_max_socket_entries;
+	if (threshold == __atomic_load_n(&conf_socket_map_max_reclaim,
+					 __ATOMIC_RELAXED))
+		return;
+	ebpf_info("socket map reclaim threshold changed from %u to %u\n",
+		  conf_socket_map_max_reclaim, threshold);
+	__atomic_store_n(&conf_socket_map_max_reclaim, threshold,
+			 __ATOMIC_RELAXED);
 }
 
 static int check_map_exceeded(void)
@@ -1539,10 +1648,12 @@ static int check_map_exceeded(void)
 		kern_trace_map_used = stats_total.trace_map_count;
 	}
 
-	if (kern_socket_map_used >= conf_socket_map_max_reclaim) {
+	uint32_t socket_map_max_reclaim =
+	    __atomic_load_n(&conf_socket_map_max_reclaim, __ATOMIC_RELAXED);
+	if (kern_socket_map_used >= socket_map_max_reclaim) {
 		ebpf_info("Current socket map used %u exceed"
 			  " conf_socket_map_max_reclaim %u,reclaim map\n",
-			  kern_socket_map_used, conf_socket_map_max_reclaim);
+			  kern_socket_map_used, socket_map_max_reclaim);
 		reclaim_socket_map(t, SOCKET_RECLAIM_TIMEOUT_DEF);
 	}
 
@@ -1819,6 +1930,40 @@ static int update_offset_map_default(struct bpf_tracer *t,
 	return ETR_OK;
 }
 
+/*
+ * io_uring offsets are optional, if any of them is missing only io_uring
+ * tracing is disabled.
+ */
+static void set_io_uring_offsets(struct ebpf_object *obj,
+				 bpf_offset_param_t * offset)
+{
+	if (io_uring_trace_status != IO_URING_TRACE_OFFSETS_UNAVAILABLE)
+		return;
+
+	int cqe_offs = kernel_struct_field_offset(obj, "io_kiocb", "cqe");
+	int fd_offs = kernel_struct_field_offset(obj, "io_cqe", "fd");
+	int res_offs = kernel_struct_field_offset(obj, "io_cqe", "res");
+	int buf_offs = kernel_struct_field_offset(obj, "io_sr_msg", "buf");
+	if (cqe_offs < 0 || fd_offs < 0 || res_offs < 0 || buf_offs < 0) {
+		ebpf_warning("io_uring offsets not found in BTF, io_uring"
+			     " tracing disabled.\n");
+		return;
+	}
+
+	offset->enable_io_uring = 1;
+	offset->struct_io_kiocb_fd_offset = cqe_offs + fd_offs;
+	offset->struct_io_kiocb_res_offset = cqe_offs + res_offs;
+	offset->struct_io_sr_msg_buf_offset = buf_offs;
+	io_uring_trace_status = IO_URING_TRACE_RUNNING;
+
+	ebpf_info("    struct_io_kiocb_fd_offset: 0x%x\n",
+		  offset->struct_io_kiocb_fd_offset);
+	ebpf_info("    struct_io_kiocb_res_offset: 0x%x\n",
+		  offset->struct_io_kiocb_res_offset);
+	ebpf_info("    struct_io_sr_msg_buf_offset: 0x%x\n",
+		  offset->struct_io_sr_msg_buf_offset);
+}
+
 static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 {
 	struct ebpf_object *obj = t->obj;
@@ -2062,6 +2207,8 @@ static int update_offset_map_from_btf_vmlinux(struct bpf_tracer *t)
 	offset.struct_ns_common_inum_offset   = struct_ns_common_inum_offset;
 	offset.struct_mount_mnt_id_offset     = struct_mount_mnt_id_offset;
 
+	set_io_uring_offsets(obj, &offset);
+
 	if (update_offsets_table(t, &offset) != ETR_OK) {
 		ebpf_warning("Update offsets map failed.\n");
 		return ETR_UPDATE_MAP_FAILD;
@@ -2081,6 +2228,7 @@ static void display_kern_offsets(bpf_offset_param_t * offset)
 	ebpf_info("\tkprobe_invalid: 0x%x\n", offset->kprobe_invalid);
 	ebpf_info("\tenable_unix_socket: 0x%x\n", offset->enable_unix_socket);
 	ebpf_info("\tfiles_infer_done: 0x%x\n", offset->files_infer_done);
+	ebpf_info("\tenable_io_uring: 0x%x\n", offset->enable_io_uring);
 	ebpf_info("\treserved: 0x%x\n", offset->reserved);
 
 	ebpf_info("\tstruct_dentry_d_parent_offset: 0x%x\n",
@@ -2147,6 +2295,13 @@ static void display_kern_offsets(bpf_offset_param_t * offset)
 		  offset->struct_ns_common_inum_offset);
 	ebpf_info("\tstruct_mount_mnt_id_offset: 0x%x\n",
 		  offset->struct_mount_mnt_id_offset);
+
+	ebpf_info("\tstruct_io_kiocb_fd_offset: 0x%x\n",
+		  offset->struct_io_kiocb_fd_offset);
+	ebpf_info("\tstruct_io_kiocb_res_offset: 0x%x\n",
+		  offset->struct_io_kiocb_res_offset);
+	ebpf_info("\tstruct_io_sr_msg_buf_offset: 0x%x\n",
+		  offset->struct_io_sr_msg_buf_offset);
 }
 
 static void save_kern_offsets(struct bpf_tracer *t)
@@ -2244,14 +2399,55 @@ static inline void print_ports_bitmap(struct kprobe_port_bitmap *bmap,
 	free(ports);
 }
 
-static void update_kprobe_port_bitmap(struct bpf_tracer *tracer)
+/*
+ * The kprobe port bitmaps are double buffered, fill the inactive slot
+ * and then switch the active slot, so that eBPF programs always see a
+ * consistent pair of whitelist and blacklist.
+ */
+static int update_kprobe_port_bitmap(struct bpf_tracer *tracer)
 {
-	bpf_table_set_value(tracer, MAP_KPROBE_PORT_BITMAP_NAME, 0,
-			    &allow_port_bitmap);
+	int ret = ETR_OK;
+	pthread_mutex_lock(&kprobe_port_mutex);
+	__u32 next_slot = kprobe_port_slot ^ 1;
+	__u32 k_allow = next_slot * 2, k_bypass = k_allow + 1, k0 = 0;
+	if (!bpf_table_set_value(tracer, MAP_KPROBE_PORT_BITMAP_NAME, k_allow,
+				 &allow_port_bitmap) ||
+	    !bpf_table_set_value(tracer, MAP_KPROBE_PORT_BITMAP_NAME, k_bypass,
+				 &bypass_port_bitmap)) {
+		ebpf_warning("Set '%s' failed\n", MAP_KPROBE_PORT_BITMAP_NAME);
+		ret = ETR_UPDATE_MAP_FAILD;
+		goto out;
+	}
+
+	if (!bpf_table_set_value(tracer, MAP_KPROBE_PORT_SLOT_NAME, k0,
+				 &next_slot)) {
+		ebpf_warning("Set '%s' failed\n", MAP_KPROBE_PORT_SLOT_NAME);
+		ret = ETR_UPDATE_MAP_FAILD;
+		goto out;
+	}
+
+	kprobe_port_slot = next_slot;
 	print_ports_bitmap(&allow_port_bitmap, "Whitelist");
-	bpf_table_set_value(tracer, MAP_KPROBE_PORT_BITMAP_NAME, 1,
-			    &bypass_port_bitmap);
 	print_ports_bitmap(&bypass_port_bitmap, "Blacklist");
+out:
+	pthread_mutex_unlock(&kprobe_port_mutex);
+	return ret;
+}
+
+/**
+ * Apply the bitmaps set by set_allow_port_bitmap() and
+ * set_bypass_port_bitmap() to a running socket tracer.
+ *
+ * @return 0 on success, ETR_NOTEXIST if the socket tracer is not running,
+ *         < 0 on other failures.
+ */
+int update_kprobe_port_bitmaps(void)
+{
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL)
+		return ETR_NOTEXIST;
+
+	return update_kprobe_port_bitmap(tracer);
 }
 
 static void config_proto_ports_bitmap(struct bpf_tracer *tracer)
@@ -2453,6 +2649,43 @@ int set_io_event_minimal_duration(uint64_t duration)
 	return 0;
 }
 
+static int update_io_event_dev_filter(struct bpf_tracer *tracer)
+{
+	if (!bpf_table_set_value(tracer, MAP_IO_EVENT_DEV_FILTER_NAME, 0,
+				 &io_event_dev_filter)) {
+		ebpf_warning("Set '%s' failed\n", MAP_IO_EVENT_DEV_FILTER_NAME);
+		return ETR_UPDATE_MAP_FAILD;
+	}
+
+	ebpf_info("Set io event device filter mode %u, %u devices\n",
+		  io_event_dev_filter.mode, io_event_dev_filter.count);
+	return 0;
+}
+
+int set_io_event_dev_filter(int mode, const uint32_t *devs, int count)
+{
+	if (mode < IO_EVENT_DEV_FILTER_NONE ||
+	    mode > IO_EVENT_DEV_FILTER_EXCLUDE || count < 0 ||
+	    count > IO_EVENT_DEV_FILTER_MAX || (count > 0 && devs == NULL)) {
+		ebpf_warning("Invalid io event device filter, mode %d count %d\n",
+			     mode, count);
+		return ETR_INVAL;
+	}
+
+	memset(&io_event_dev_filter, 0, sizeof(io_event_dev_filter));
+	io_event_dev_filter.mode = mode;
+	io_event_dev_filter.count = count;
+	if (count > 0)
+		memcpy(io_event_dev_filter.devs, devs, count * sizeof(*devs));
+
+	struct bpf_tracer *tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL) {
+		return 0;
+	}
+
+	return update_io_event_dev_filter(tracer);
+}
+
 int set_virtual_file_collect(bool enabled)
 {
 	virtual_file_collect_enable = enabled;
@@ -2938,6 +3171,7 @@ int running_socket_tracer(tracer_callback_t handle,
 	memset(tps, 0, sizeof(*tps));
 	init_list_head(&tps->uprobe_syms_head);
 	socket_tracer_set_probes(tps);
+	uprobe_retry_init();
 	golang_trace_init();
 	openssl_trace_init();
 	create_and_init_proc_info_caches();
@@ -3089,6 +3323,8 @@ int running_socket_tracer(tracer_callback_t handle,
 
 	update_kprobe_port_bitmap(tracer);
 
+	update_io_event_dev_filter(tracer);
+
 	// Configure l7 protocol ports
 	config_proto_ports_bitmap(tracer);
 
@@ -3232,6 +3468,7 @@ static bool bpf_stats_map_collect(struct bpf_tracer *tracer,
 	stats_total->period_event_max_delay = value.period_event_max_delay;
 	stats_total->period_event_total_time = value.period_event_total_time;
 	stats_total->period_event_count = value.period_event_count;
+	stats_total->io_event_dev_filtered = value.io_event_dev_filtered;
 	return true;
 }
 
@@ -3426,6 +3663,13 @@ struct socket_trace_stats socket_tracer_stats(void)
 			stats.period_push_avg_delay = 0;
 		}
 
+		// io_event_dev_filtered is never reset in kernel, report the increment
+		static uint64_t last_io_event_dev_filtered;
+		stats.io_event_dev_filtered =
+		    stats_total.io_event_dev_filtered -
+		    last_io_event_dev_filtered;
+		last_io_event_dev_filtered = stats_total.io_event_dev_filtered;
+
 		if (!bpf_stats_map_update(t, -1, -1, 0, 0, 0, 0)) {
 			ebpf_warning("Update trace statistics failed.\n");
 		}
@@ -3461,6 +3705,30 @@ struct socket_trace_stats socket_tracer_stats(void)
 	clear_proc_exec_event_count();
 	clear_proc_exit_event_count();
 
+	stats.ssl_uprobe_targets = get_ssl_uprobe_targets();
+	stats.ssl_uprobe_target_failed = get_ssl_uprobe_target_failed();
+	stats.uprobe_attach_retries = get_uprobe_attach_retries();
+	stats.uprobe_attach_gave_up = get_uprobe_attach_gave_up();
+	stats.uprobe_targets_retrying = get_uprobe_targets_retrying();
+	stats.socket_map_reclaims =
+	    __atomic_exchange_n(&socket_map_reclaims, 0, __ATOMIC_RELAXED);
+	stats.socket_map_reclaimed =
+	    __atomic_exchange_n(&socket_map_reclaimed, 0, __ATOMIC_RELAXED);
+	uint64_t reclaim_total_us =
+	    __atomic_exchange_n(&socket_map_reclaim_total_us, 0,
+				__ATOMIC_RELAXED);
+	if (stats.socket_map_reclaims > 0)
+		stats.socket_map_reclaim_avg_us =
+		    reclaim_total_us / stats.socket_map_reclaims;
+	stats.socket_map_reclaim_max_us =
+	    __atomic_exchange_n(&socket_map_reclaim_max_us, 0,
+				__ATOMIC_RELAXED);
+	for (int i = 0; i < SOCKET_RECLAIM_AGE_BUCKET_NUM; i++)
+		stats.socket_map_reclaim_ages[i] =
+		    __atomic_exchange_n(&socket_map_reclaim_ages[i], 0,
+					__ATOMIC_RELAXED);
+	stats.io_uring_trace_status = io_uring_trace_status;
+
 	return stats;
 }
 
@@ -4007,6 +4275,12 @@ int disable_syscall_trace_id(void)
 	return 0;
 }
 
+void enable_io_uring_trace(void)
+{
+	io_uring_trace_enable = true;
+	ebpf_info("Enable io_uring tracing feature.\n");
+}
+
 void uprobe_match_pid_handle(int feat, int pid, enum match_pids_act act)
 {
 	if (feat == FEATURE_UPROBE_GOLANG)
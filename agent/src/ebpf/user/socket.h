@@ -59,6 +59,17 @@ enum linux_kernel_type {
 	K_TYPE_KPROBE,
 };
 
+/*
+ * io_uring send/recv tracing capability, reported in socket_trace_stats.
+ */
+enum io_uring_trace_status {
+	IO_URING_TRACE_DISABLED,	// Not enabled by configuration
+	IO_URING_TRACE_RUNNING,
+	IO_URING_TRACE_KERNEL_UNSUPPORTED,	// Linux < 5.10 or no suitable eBPF binary
+	IO_URING_TRACE_SYMBOL_MISSING,	// io_send()/io_recv() not found in kallsyms
+	IO_URING_TRACE_OFFSETS_UNAVAILABLE,	// Required offsets not found in BTF
+};
+
 enum probes_act_type {
 	ACT_NONE,
 	ACT_ATTACH,
@@ -199,6 +210,38 @@ struct socket_trace_stats {
 	uint64_t dropped_packets;
 	uint64_t kern_missed_packets;
 	uint64_t invalid_packets;
+
+	/*
+	 * Processes selected for openssl uprobes with SSL_read/SSL_write
+	 * resolved / not resolved.
+	 */
+	uint64_t ssl_uprobe_targets;
+	uint64_t ssl_uprobe_target_failed;
+
+	/*
+	 * Retries of failed golang/openssl uprobe attachments, targets given
+	 * up after the maximum attempts and targets waiting for a retry.
+	 */
+	uint64_t uprobe_attach_retries;
+	uint64_t uprobe_attach_gave_up;
+	uint64_t uprobe_targets_retrying;
+
+	/*
+	 * Socket map reclaims, entries reclaimed, the average and maximum
+	 * latency of a reclaim in microseconds and the idle time of the
+	 * reclaimed entries, bucketed by SOCKET_RECLAIM_AGE_BUCKETS.
+	 */
+	uint64_t socket_map_reclaims;
+	uint64_t socket_map_reclaimed;
+	uint64_t socket_map_reclaim_avg_us;
+	uint64_t socket_map_reclaim_max_us;
+	uint64_t socket_map_reclaim_ages[SOCKET_RECLAIM_AGE_BUCKET_NUM];
+
+	// IO events dropped by the device filter in eBPF
+	uint64_t io_event_dev_filtered;
+
+	// enum io_uring_trace_status
+	uint8_t io_uring_trace_status;
 };
 
 struct bpf_offset_param_array {
@@ -416,9 +459,20 @@ prefetch_and_process_data(struct bpf_tracer *t, int id, int nb_rx, void **datas_
 }
 
 int set_data_limit_max(int limit_size);
+int update_kprobe_port_bitmaps(void);
 int set_go_tracing_timeout(int timeout);
 int set_io_event_collect_mode(uint32_t mode);
 int set_io_event_minimal_duration(uint64_t duration);
+/**
+ * @brief Filter IO events by the device of the file system in eBPF.
+ *
+ * @param mode   IO_EVENT_DEV_FILTER_NONE/INCLUDE/EXCLUDE
+ * @param devs   Devices in kernel internal encoding (major << 20 | minor)
+ * @param count  Number of devices, at most IO_EVENT_DEV_FILTER_MAX
+ *
+ * @return 0 on success, or a negative error code on failure.
+ */
+int set_io_event_dev_filter(int mode, const uint32_t *devs, int count);
 struct socket_trace_stats socket_tracer_stats(void);
 int running_socket_tracer(tracer_callback_t handle,
 			  int thread_nr,
@@ -427,6 +481,16 @@ int running_socket_tracer(tracer_callback_t handle,
 			  uint32_t max_socket_entries,
 			  uint32_t max_trace_entries,
 			  uint32_t socket_map_max_reclaim);
+/*
+ * Update the socket map reclaim threshold at runtime, used by the
+ * adaptive reclaim of the agent.
+ *
+ * @threshold Reclaim when the entries of the socket map reach it,
+ *   capped at the maximum entries of the socket map.
+ */
+void set_socket_map_reclaim_threshold(uint32_t threshold);
+// The current number of entries in the socket map
+uint32_t get_socket_map_used(void);
 int register_event_handle(uint32_t type, void (*fn)(void *));
 int socket_tracer_stop(void);
 int socket_tracer_start(void);
@@ -434,6 +498,15 @@ enum tracer_state get_socket_tracer_state(void);
 int set_protocol_ports_bitmap(int proto_type, const char *ports);
 int disable_syscall_trace_id(void);
 
+/**
+ * @brief Enable io_uring IORING_OP_SEND/IORING_OP_RECV tracing.
+ *
+ * Must be called before running_socket_tracer(). Whether the feature
+ * actually works depends on the kernel, see socket_trace_stats
+ * io_uring_trace_status.
+ */
+void enable_io_uring_trace(void);
+
 /**
  * eBPF Probe Point Configuration
  *
@@ -508,4 +581,5 @@ void insert_adapt_kern_data_to_map(struct bpf_tracer *tracer,
  */
 int set_virtual_file_collect(bool enabled);
 bool is_pure_kprobe_ebpf(void);
+bool fentry_supported(void);
 #endif /* DF_USER_SOCKET_H */
@@ -20,7 +20,10 @@
 #include "socket.h"
 #include "utils.h"
 #include "log.h"
+#include "uprobe_retry.h"
+#include <ctype.h>
 #include <dirent.h>
+#include <fnmatch.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <unistd.h>
@@ -31,6 +34,22 @@
 static proc_event_list_t proc_events;
 static bool ssl_trace_enabled;
 
+/*
+ * Extra SSL objects to probe, such as BoringSSL statically linked into
+ * the executable or OpenSSL bundled under a non-default path.
+ * - ssl_library_paths: glob patterns matching the full path of mapped files.
+ * - ssl_binary_names: base names of mapped executables or libraries.
+ */
+#define SSL_EXTRA_TARGETS_MAX 32
+static char *ssl_library_paths[SSL_EXTRA_TARGETS_MAX];
+static int ssl_library_paths_count;
+static char *ssl_binary_names[SSL_EXTRA_TARGETS_MAX];
+static int ssl_binary_names_count;
+
+// Per process statistics of SSL symbol resolving
+static uint64_t ssl_uprobe_targets;
+static uint64_t ssl_uprobe_target_failed;
+
 /* *INDENT-OFF* */
 static struct symbol symbols[] = {
 	{
@@ -60,29 +79,144 @@ static struct symbol symbols[] = {
 };
 /* *INDENT-ON* */
 
-static void openssl_parse_and_register(int pid, struct tracer_probes_conf *conf)
+static bool is_extra_ssl_object(const char *path)
+{
+	int i;
+	const char *base = strrchr(path, '/');
+	base = base ? base + 1 : path;
+
+	for (i = 0; i < ssl_binary_names_count; i++) {
+		if (strcmp(base, ssl_binary_names[i]) == 0)
+			return true;
+	}
+
+	for (i = 0; i < ssl_library_paths_count; i++) {
+		if (fnmatch(ssl_library_paths[i], path, 0) == 0)
+			return true;
+	}
+
+	return false;
+}
+
+/*
+ * Find the first object mapped into the process that matches the
+ * configured library paths or binary names.
+ *
+ * @return the path under /proc/<pid>/root, needs to be freed by the caller.
+ */
+static char *get_extra_ssl_object_path(int pid)
+{
+	char line[PATH_MAX + 256], maps_file[64], *mapname, *newline;
+	char *path = NULL;
+	FILE *fp;
+
+	if (ssl_library_paths_count == 0 && ssl_binary_names_count == 0)
+		return NULL;
+
+	snprintf(maps_file, sizeof(maps_file), "/proc/%d/maps", pid);
+	fp = fopen(maps_file, "r");
+	if (!fp)
+		return NULL;
+
+	while (fgets(line, sizeof(line), fp)) {
+		newline = strchr(line, '\n');
+		if (newline)
+			newline[0] = '\0';
+		// address perms offset dev inode pathname
+		mapname = strchr(line, '/');
+		if (!mapname || !is_extra_ssl_object(mapname))
+			continue;
+
+		char so_path[PATH_MAX];
+		int n = snprintf(so_path, sizeof(so_path), "/proc/%d/root%s",
+				 pid, mapname);
+		if (n > 0 && n < sizeof(so_path))
+			path = strdup(so_path);
+		break;
+	}
+
+	fclose(fp);
+	return path;
+}
+
+/*
+ * SSL objects are looked up in the order:
+ * 1. objects matching the configured library paths or binary names,
+ * 2. libssl.so mapped into the process,
+ * 3. the executable itself, for statically linked OpenSSL or BoringSSL.
+ *
+ * Processes are selected by the process matcher, so the executable is
+ * always tried as the last resort even if nothing is detected.
+ *
+ * Failures are retried with backoff, as libraries may not be mapped or
+ * the binary may not be complete yet right after the process starts.
+ *
+ * @returns the path of the SSL object if the symbols are resolved, which
+ *          needs to be freed by the caller, otherwise NULL
+ */
+static char *openssl_parse_and_register(int pid,
+					struct tracer_probes_conf *conf)
 {
 	char *path = NULL;
 	int count = 0;
 
 	if (pid <= 1)
-		return;
+		return NULL;
 
 	if (!is_user_process(pid))
-		return;
+		return NULL;
 
-	path = get_so_path_by_pid_and_name(pid, "ssl");
+	path = get_extra_ssl_object_path(pid);
+	if (!path)
+		path = get_so_path_by_pid_and_name(pid, "ssl");
 	if (!path) {
 		path = get_elf_path_by_pid(pid);
-		if (!path)
-			return;
+		if (!path) {
+			__atomic_add_fetch(&ssl_uprobe_target_failed, 1,
+					   __ATOMIC_RELAXED);
+			uprobe_target_update(UPROBE_TARGET_OPENSSL, pid, NULL,
+					     "binary not found");
+			return NULL;
+		}
 	}
 
 	count = add_probe_sym_to_tracer_probes(pid, path, conf,
 					       symbols, NELEMS(symbols));
+	if (count < NELEMS(symbols)) {
+		__atomic_add_fetch(&ssl_uprobe_target_failed, 1,
+				   __ATOMIC_RELAXED);
+		ebpf_warning("openssl uprobes: pid:%d, path:%s, "
+			     "SSL_read/SSL_write symbols not found, "
+			     "probes_count:%d\n", pid, path, count);
+		uprobe_target_update(UPROBE_TARGET_OPENSSL, pid, path,
+				     "symbols not found");
+		free(path);
+		return NULL;
+	}
+
+	__atomic_add_fetch(&ssl_uprobe_targets, 1, __ATOMIC_RELAXED);
 	ebpf_info("openssl uprobes: pid:%d, path:%s, probes_count:%d\n",
 		  pid, path, count);
-	
+	return path;
+}
+
+// The caller needs 'tracer->mutex_probes_lock' for protection
+static void openssl_attach(struct bpf_tracer *tracer, int pid)
+{
+	int count = 0, ret;
+	char *path = openssl_parse_and_register(pid, tracer->tps);
+
+	// the symbols partially resolved are attached as well
+	tracer_uprobes_update(tracer);
+	ret = tracer_hooks_process(tracer, HOOK_ATTACH, &count);
+	if (path == NULL)
+		return;
+
+	if (ret == ETR_OK)
+		uprobe_target_update(UPROBE_TARGET_OPENSSL, pid, path, NULL);
+	else
+		uprobe_target_update(UPROBE_TARGET_OPENSSL, pid, path,
+				     "attach failed");
 	free(path);
 }
 
@@ -142,7 +276,7 @@ int collect_ssl_uprobe_syms_from_procfs(struct tracer_probes_conf *conf)
 			continue;
 		path = get_elf_path_by_pid(pid);
 		if (is_feature_matched(FEATURE_UPROBE_OPENSSL, pid, path)) {
-			openssl_parse_and_register(pid, conf);
+			free(openssl_parse_and_register(pid, conf));
 		}
 		free(path);
 	}
@@ -187,6 +321,7 @@ void ssl_process_exit(int pid)
 	if (!kernel_version_check())
 		return;
 
+	uprobe_target_remove(UPROBE_TARGET_OPENSSL, pid);
 	tracer = find_bpf_tracer(SK_TRACER_NAME);
 	if (tracer == NULL)
 		return;
@@ -196,11 +331,32 @@ void ssl_process_exit(int pid)
 	pthread_mutex_unlock(&tracer->mutex_probes_lock);
 }
 
+// Retry the failed attachments due, see uprobe_retry.h
+static void ssl_retry_handle(void)
+{
+	int pids[16];
+	int i, n;
+	struct bpf_tracer *tracer;
+	if (!is_feature_enabled(FEATURE_UPROBE_OPENSSL))
+		return;
+
+	tracer = find_bpf_tracer(SK_TRACER_NAME);
+	if (tracer == NULL || tracer->probes_count > OPEN_FILES_MAX)
+		return;
+
+	n = uprobe_targets_due(UPROBE_TARGET_OPENSSL, pids, NELEMS(pids));
+	for (i = 0; i < n; i++) {
+		pthread_mutex_lock(&tracer->mutex_probes_lock);
+		clear_ssl_probes_by_pid(tracer, pids[i]);
+		openssl_attach(tracer, pids[i]);
+		pthread_mutex_unlock(&tracer->mutex_probes_lock);
+	}
+}
+
 void ssl_events_handle(void)
 {
 	struct process_create_event *event = NULL;
 	struct bpf_tracer *tracer = NULL;
-	int count = 0;
 	do {
 		event = get_first_event(&proc_events);
 		if (!event)
@@ -215,9 +371,7 @@ void ssl_events_handle(void)
 		tracer = event->tracer;
 		if (tracer) {
 			pthread_mutex_lock(&tracer->mutex_probes_lock);
-			openssl_parse_and_register(event->pid, tracer->tps);
-			tracer_uprobes_update(tracer);
-			tracer_hooks_process(tracer, HOOK_ATTACH, &count);
+			openssl_attach(tracer, event->pid);
 			pthread_mutex_unlock(&tracer->mutex_probes_lock);
 		}
 
@@ -226,6 +380,8 @@ void ssl_events_handle(void)
 		process_event_free(event);
 
 	} while (true);
+
+	ssl_retry_handle();
 }
 
 void openssl_trace_handle(int pid, enum match_pids_act act)
@@ -252,3 +408,67 @@ bool is_openssl_trace_enabled(void)
 {
 	return ssl_trace_enabled;
 }
+
+static int split_ssl_targets(const char *list, char **targets)
+{
+	int count = 0;
+	char *dup, *token, *saveptr = NULL;
+
+	if (list == NULL)
+		return 0;
+
+	dup = strdup(list);
+	if (dup == NULL)
+		return 0;
+
+	for (token = strtok_r(dup, ",", &saveptr); token != NULL;
+	     token = strtok_r(NULL, ",", &saveptr)) {
+		while (isspace(*token))
+			token++;
+		char *end = token + strlen(token);
+		while (end > token && isspace(end[-1]))
+			*--end = '\0';
+		if (*token == '\0')
+			continue;
+		if (count >= SSL_EXTRA_TARGETS_MAX) {
+			ebpf_warning("Too many openssl uprobe targets, "
+				     "the maximum is %d, ignore '%s'\n",
+				     SSL_EXTRA_TARGETS_MAX, token);
+			continue;
+		}
+		targets[count++] = strdup(token);
+	}
+
+	free(dup);
+	return count;
+}
+
+int set_uprobe_openssl_targets(const char *library_paths,
+			       const char *binary_names)
+{
+	int i;
+	for (i = 0; i < ssl_library_paths_count; i++)
+		free(ssl_library_paths[i]);
+	for (i = 0; i < ssl_binary_names_count; i++)
+		free(ssl_binary_names[i]);
+
+	ssl_library_paths_count =
+	    split_ssl_targets(library_paths, ssl_library_paths);
+	ssl_binary_names_count =
+	    split_ssl_targets(binary_names, ssl_binary_names);
+	ebpf_info("openssl uprobe targets, library paths: %s, "
+		  "binary names: %s\n", library_paths ? library_paths : "",
+		  binary_names ? binary_names : "");
+	return 0;
+}
+
+uint64_t get_ssl_uprobe_targets(void)
+{
+	return __atomic_exchange_n(&ssl_uprobe_targets, 0, __ATOMIC_RELAXED);
+}
+
+uint64_t get_ssl_uprobe_target_failed(void)
+{
+	return __atomic_exchange_n(&ssl_uprobe_target_failed, 0,
+				   __ATOMIC_RELAXED);
+}
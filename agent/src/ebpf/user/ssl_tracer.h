@@ -34,4 +34,14 @@ void openssl_trace_handle(int pid, enum match_pids_act act);
 void openssl_trace_init(void);
 void set_uprobe_openssl_enabled(bool enabled);
 bool is_openssl_trace_enabled(void);
+/*
+ * Set extra SSL objects to probe, both are comma separated lists.
+ * @library_paths glob patterns matching the path of mapped libraries
+ * @binary_names base names of mapped executables or libraries
+ */
+int set_uprobe_openssl_targets(const char *library_paths,
+			       const char *binary_names);
+// Processes with / without SSL symbols resolved since the last call
+uint64_t get_ssl_uprobe_targets(void);
+uint64_t get_ssl_uprobe_target_failed(void);
 #endif
@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <pthread.h>
+#include <sys/stat.h>
+#include "config.h"
+#include "utils.h"
+#include "list.h"
+#include "log.h"
+#include "uprobe_retry.h"
+
+// Bytes hashed at the head and the tail of a binary
+#define FINGERPRINT_CHUNK_SIZE 4096
+
+struct uprobe_target {
+	struct list_head list;
+	struct uprobe_target_info info;
+	u64 stime;		// process start time, to detect pid reuse
+};
+
+static struct list_head targets_head;
+static int targets_count;
+static pthread_mutex_t targets_lock;
+
+static uint64_t attach_retries;
+static uint64_t attach_gave_up;
+
+static const char *type_names[] = {
+	[UPROBE_TARGET_GOLANG] = "golang",
+	[UPROBE_TARGET_OPENSSL] = "openssl",
+};
+
+static inline uint64_t fnv1a(uint64_t h, const void *data, size_t len)
+{
+	const uint8_t *p = data;
+	for (size_t i = 0; i < len; i++) {
+		h ^= p[i];
+		h *= 0x100000001b3ULL;
+	}
+	return h;
+}
+
+/*
+ * Hashing the whole binary is too expensive for large executables, the
+ * size, mtime and both ends are hashed instead. A binary still being
+ * written changes its size and tail, where the ELF section headers are.
+ */
+static uint64_t binary_fingerprint(const char *path, uint64_t * inode)
+{
+	struct stat st;
+	char buf[FINGERPRINT_CHUNK_SIZE];
+	uint64_t h = 0xcbf29ce484222325ULL;
+	ssize_t n;
+	int fd;
+
+	*inode = 0;
+	if (path == NULL)
+		return 0;
+
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return 0;
+
+	if (fstat(fd, &st) != 0) {
+		close(fd);
+		return 0;
+	}
+
+	*inode = st.st_ino;
+	h = fnv1a(h, &st.st_size, sizeof(st.st_size));
+	h = fnv1a(h, &st.st_mtim, sizeof(st.st_mtim));
+	n = pread(fd, buf, sizeof(buf), 0);
+	if (n > 0)
+		h = fnv1a(h, buf, n);
+	if (st.st_size > FINGERPRINT_CHUNK_SIZE) {
+		n = pread(fd, buf, sizeof(buf),
+			  st.st_size - FINGERPRINT_CHUNK_SIZE);
+		if (n > 0)
+			h = fnv1a(h, buf, n);
+	}
+
+	close(fd);
+	return h;
+}
+
+static uint32_t backoff_interval(int attempts)
+{
+	uint32_t interval = UPROBE_RETRY_BASE_INTERVAL;
+	while (--attempts > 0 && interval < UPROBE_RETRY_MAX_INTERVAL)
+		interval <<= 1;
+	return interval < UPROBE_RETRY_MAX_INTERVAL ?
+	    interval : UPROBE_RETRY_MAX_INTERVAL;
+}
+
+// The caller needs 'targets_lock' for protection
+static struct uprobe_target *find_target(enum uprobe_target_type type,
+					 int pid)
+{
+	struct uprobe_target *t;
+	list_for_each_entry(t, &targets_head, list) {
+		if (t->info.type == type && t->info.pid == pid)
+			return t;
+	}
+
+	return NULL;
+}
+
+// The caller needs 'targets_lock' for protection
+static void free_target(struct uprobe_target *t)
+{
+	list_head_del(&t->list);
+	free(t);
+	targets_count--;
+}
+
+// The caller needs 'targets_lock' for protection
+static void evict_target(void)
+{
+	struct uprobe_target *t, *victim = NULL;
+	list_for_each_entry(t, &targets_head, list) {
+		if (t->info.state == UPROBE_STATE_ATTACHED) {
+			victim = t;
+			break;
+		}
+	}
+
+	if (victim == NULL && !list_empty(&targets_head))
+		victim = list_first_entry(&targets_head, struct uprobe_target,
+					  list);
+	if (victim)
+		free_target(victim);
+}
+
+// The caller needs 'targets_lock' for protection
+static void schedule_retry(struct uprobe_target *t, uint32_t now)
+{
+	struct uprobe_target_info *info = &t->info;
+	if (++info->attempts >= UPROBE_RETRY_MAX_ATTEMPTS) {
+		info->state = UPROBE_STATE_GAVE_UP;
+		info->next_retry = 0;
+		__atomic_add_fetch(&attach_gave_up, 1, __ATOMIC_RELAXED);
+		ebpf_warning("%s uprobe: pid:%d, path:%s, gave up after %d "
+			     "attempts, reason:%s\n", type_names[info->type],
+			     info->pid, info->path, info->attempts,
+			     info->reason);
+		return;
+	}
+
+	info->state = UPROBE_STATE_RETRYING;
+	info->next_retry = now + backoff_interval(info->attempts);
+	ebpf_info("%s uprobe: pid:%d, path:%s, attempt %d failed (%s), "
+		  "retry in %us\n", type_names[info->type], info->pid,
+		  info->path, info->attempts, info->reason,
+		  info->next_retry - now);
+}
+
+void uprobe_target_update(enum uprobe_target_type type, int pid,
+			  const char *path, const char *reason)
+{
+	uint64_t inode;
+	uint64_t hash = binary_fingerprint(path, &inode);
+
+	pthread_mutex_lock(&targets_lock);
+	struct uprobe_target *t = find_target(type, pid);
+	// a new binary starts over
+	if (t && t->info.inode != inode) {
+		free_target(t);
+		t = NULL;
+	}
+
+	if (t == NULL) {
+		// nothing to track for targets attached at the first attempt
+		if (reason == NULL)
+			goto out;
+		if (targets_count >= UPROBE_TARGETS_MAX)
+			evict_target();
+		t = calloc(1, sizeof(*t));
+		if (t == NULL) {
+			ebpf_warning("calloc() failed.\n");
+			goto out;
+		}
+		t->info.pid = pid;
+		t->info.type = type;
+		t->info.inode = inode;
+		t->stime = get_process_starttime(pid);
+		if (path)
+			snprintf(t->info.path, sizeof(t->info.path), "%s",
+				 path);
+		list_add_tail(&t->list, &targets_head);
+		targets_count++;
+	}
+
+	t->info.hash = hash;
+	if (reason == NULL) {
+		if (t->info.state != UPROBE_STATE_ATTACHED)
+			ebpf_info("%s uprobe: pid:%d, path:%s, attached after "
+				  "%d failed attempts\n", type_names[type],
+				  pid, t->info.path, t->info.attempts);
+		t->info.state = UPROBE_STATE_ATTACHED;
+		t->info.next_retry = 0;
+		goto out;
+	}
+
+	snprintf(t->info.reason, sizeof(t->info.reason), "%s", reason);
+	schedule_retry(t, get_sys_uptime());
+
+out:
+	pthread_mutex_unlock(&targets_lock);
+}
+
+void uprobe_target_remove(enum uprobe_target_type type, int pid)
+{
+	pthread_mutex_lock(&targets_lock);
+	struct uprobe_target *t = find_target(type, pid);
+	if (t)
+		free_target(t);
+	pthread_mutex_unlock(&targets_lock);
+}
+
+int uprobe_targets_due(enum uprobe_target_type type, int *pids, int max)
+{
+	struct uprobe_target *t, *n;
+	uint32_t now = get_sys_uptime();
+	uint64_t inode, hash;
+	int count = 0;
+
+	pthread_mutex_lock(&targets_lock);
+	list_for_each_entry_safe(t, n, &targets_head, list) {
+		if (count >= max)
+			break;
+		if (t->info.type != type
+		    || t->info.state != UPROBE_STATE_RETRYING
+		    || now < t->info.next_retry)
+			continue;
+
+		// the pid may be reused by another process
+		if (get_process_starttime(t->info.pid) != t->stime) {
+			free_target(t);
+			continue;
+		}
+
+		if (t->info.path[0] != '\0') {
+			hash = binary_fingerprint(t->info.path, &inode);
+			// replaced, the exec event of the new binary takes over
+			if (inode != t->info.inode) {
+				free_target(t);
+				continue;
+			}
+			// still being written, wait for it to settle
+			if (hash != t->info.hash) {
+				t->info.hash = hash;
+				snprintf(t->info.reason,
+					 sizeof(t->info.reason),
+					 "binary is changing");
+				schedule_retry(t, now);
+				continue;
+			}
+		}
+
+		pids[count++] = t->info.pid;
+		__atomic_add_fetch(&attach_retries, 1, __ATOMIC_RELAXED);
+	}
+	pthread_mutex_unlock(&targets_lock);
+
+	return count;
+}
+
+int get_uprobe_targets(struct uprobe_target_info *infos, int max)
+{
+	struct uprobe_target *t;
+	int count = 0;
+
+	pthread_mutex_lock(&targets_lock);
+	list_for_each_entry(t, &targets_head, list) {
+		if (count >= max)
+			break;
+		infos[count++] = t->info;
+	}
+	pthread_mutex_unlock(&targets_lock);
+
+	return count;
+}
+
+uint64_t get_uprobe_attach_retries(void)
+{
+	return __atomic_exchange_n(&attach_retries, 0, __ATOMIC_RELAXED);
+}
+
+uint64_t get_uprobe_attach_gave_up(void)
+{
+	return __atomic_exchange_n(&attach_gave_up, 0, __ATOMIC_RELAXED);
+}
+
+uint64_t get_uprobe_targets_retrying(void)
+{
+	struct uprobe_target *t;
+	uint64_t count = 0;
+
+	pthread_mutex_lock(&targets_lock);
+	list_for_each_entry(t, &targets_head, list) {
+		if (t->info.state == UPROBE_STATE_RETRYING)
+			count++;
+	}
+	pthread_mutex_unlock(&targets_lock);
+
+	return count;
+}
+
+void uprobe_retry_init(void)
+{
+	init_list_head(&targets_head);
+	pthread_mutex_init(&targets_lock, NULL);
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef DF_USER_UPROBE_RETRY_H
+#define DF_USER_UPROBE_RETRY_H
+
+#include <stdint.h>
+
+/*
+ * Failed uprobe attachments are retried with exponential backoff:
+ * 5s, 10s, 20s, ... up to UPROBE_RETRY_MAX_INTERVAL, and the target
+ * is given up after UPROBE_RETRY_MAX_ATTEMPTS failures.
+ */
+#define UPROBE_RETRY_BASE_INTERVAL 5	// seconds
+#define UPROBE_RETRY_MAX_INTERVAL 300	// seconds
+#define UPROBE_RETRY_MAX_ATTEMPTS 6
+// Maximum number of targets tracked, attached targets are evicted first
+#define UPROBE_TARGETS_MAX 4096
+
+#define UPROBE_TARGET_REASON_LEN 64
+#define UPROBE_TARGET_PATH_LEN 256
+
+enum uprobe_target_type {
+	UPROBE_TARGET_GOLANG = 0,
+	UPROBE_TARGET_OPENSSL,
+};
+
+enum uprobe_attach_state {
+	UPROBE_STATE_ATTACHED = 0,
+	UPROBE_STATE_RETRYING,
+	UPROBE_STATE_GAVE_UP,
+};
+
+/*
+ * Attachment state of a uprobe target, keyed by pid and binary inode.
+ *
+ * Must be kept in sync with `uprobe_target_info` in mod.rs.
+ */
+struct uprobe_target_info {
+	int32_t pid;
+	uint8_t type;		// enum uprobe_target_type
+	uint8_t state;		// enum uprobe_attach_state
+	uint16_t attempts;	// failed attempts
+	uint64_t inode;		// inode of the binary or library
+	uint64_t hash;		// binary fingerprint when last failed
+	uint32_t next_retry;	// system uptime in seconds, 0 if not retrying
+	char reason[UPROBE_TARGET_REASON_LEN];	// reason of the last failure
+	char path[UPROBE_TARGET_PATH_LEN];
+};
+
+/*
+ * Record the result of an attachment.
+ *
+ * @type enum uprobe_target_type
+ * @pid Process ID
+ * @path Binary or library the uprobes attach to, can be NULL if unknown
+ * @reason NULL if attached, otherwise the reason of the failure
+ */
+void uprobe_target_update(enum uprobe_target_type type, int pid,
+			  const char *path, const char *reason);
+
+// Forget the target, called when the process exits
+void uprobe_target_remove(enum uprobe_target_type type, int pid);
+
+/*
+ * Fetch the pids due for a retry.
+ *
+ * The start time of the process is checked to exclude reused pids, and
+ * the binary fingerprint is checked to wait for binaries still being
+ * written, both are done before the pid is returned.
+ *
+ * @returns the number of pids filled in
+ */
+int uprobe_targets_due(enum uprobe_target_type type, int *pids, int max);
+
+/*
+ * Copy the state of all targets, for the debug interface.
+ *
+ * @returns the number of targets filled in
+ */
+int get_uprobe_targets(struct uprobe_target_info *infos, int max);
+
+// Counters, the ones of events are reset on read
+uint64_t get_uprobe_attach_retries(void);
+uint64_t get_uprobe_attach_gave_up(void);
+uint64_t get_uprobe_targets_retrying(void);
+
+void uprobe_retry_init(void);
+#endif /* DF_USER_UPROBE_RETRY_H */
@@ -37,14 +37,25 @@ fn main() {
 
  */
 
+pub mod capability;
+mod capture_rate;
+mod io_event_filter;
 #[cfg(feature = "extended_observability")]
 pub mod memory_profile;
+mod namespace_filter;
+#[cfg(feature = "extended_observability")]
+mod off_cpu_override;
+mod profile_batch;
+mod reclaim_threshold;
+mod sampling_throttle;
+#[cfg(feature = "extended_observability")]
+mod thread_name;
 
 use std::ffi::{CStr, CString};
 use std::ptr::{self, null_mut};
 use std::slice;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
@@ -52,8 +63,8 @@ use ahash::HashSet;
 use arc_swap::access::Access;
 use libc::{c_int, c_ulonglong, c_void};
 use log::{debug, error, info, warn};
+use sysinfo::{CpuRefreshKind, RefreshKind, System, SystemExt};
 use thiserror::Error;
-use zstd::bulk::compress;
 
 use crate::common::ebpf::EbpfType;
 use crate::common::flow::L7Stats;
@@ -64,7 +75,10 @@ use crate::common::meta_packet::{MetaPacket, SegmentFlags};
 use crate::common::proc_event::{BoxedProcEvents, EventType, ProcEvent};
 use crate::common::{FlowAclListener, FlowAclListenerId};
 use crate::config::handler::{CollectorAccess, EbpfAccess, EbpfConfig, LogParserAccess};
-use crate::config::FlowAccess;
+use crate::config::{
+    config::{EbpfSocketKprobe, SocketMapReclaimMode},
+    FlowAccess, ProcessMatcher,
+};
 use crate::ebpf;
 use crate::exception::ExceptionHandler;
 use crate::flow_generator::{flow_map::Config, AppProto, FlowMap};
@@ -74,23 +88,26 @@ use crate::policy::PolicyGetter;
 use crate::rpc::get_timestamp;
 use crate::utils::{process::ProcessListener, stats};
 
+use capture_rate::ProcessRateLimiter;
 #[cfg(feature = "extended_observability")]
 use public::queue::Error::Terminated;
 use public::{
     buffer::BatchedBox,
-    counter::{Countable, Counter, CounterType, CounterValue, OwnedCountable},
+    counter::{Countable, Counter, CounterType, CounterValue, OwnedCountable, RefCountable},
     debug::QueueDebugger,
     l7_protocol::{L7Protocol, L7ProtocolChecker},
     leaky_bucket::LeakyBucket,
     packet,
     proto::{
-        agent::{AgentType, Exception},
+        agent::{AgentType, Exception, SystemLoadMetric},
         metric,
     },
-    queue::{bounded_with_debug, DebugSender, Receiver},
+    queue::{bounded_with_debug_and_policy, DebugSender, Receiver},
     utils::bitmap::parse_u16_range_list_to_bitmap,
 };
+use reclaim_threshold::{ReclaimThresholdController, ReclaimThresholdCounter};
 use reorder::{Reorder, ReorderCounter, StatsReorderCounter};
+use sampling_throttle::{SamplingThrottle, SamplingThrottleCounter};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -112,6 +129,7 @@ pub struct EbpfCounter {
     rx: AtomicU64,
     time_backtrack_max: AtomicU64,
     get_token_failed: AtomicU64,
+    kprobe_ports_hot_update: AtomicU64,
 }
 
 pub struct SyncEbpfCounter {
@@ -123,7 +141,20 @@ impl OwnedCountable for SyncEbpfCounter {
         let rx = self.counter.rx.swap(0, Ordering::Relaxed);
         let get_token_failed = self.counter.get_token_failed.swap(0, Ordering::Relaxed);
         let time_backtrack_max = self.counter.time_backtrack_max.swap(0, Ordering::Relaxed);
+        let kprobe_ports_hot_update = self
+            .counter
+            .kprobe_ports_hot_update
+            .swap(0, Ordering::Relaxed);
+        let io_event_filtered = IO_EVENT_FILTERED.swap(0, Ordering::Relaxed);
+        let namespace_filtered = NAMESPACE_FILTERED.swap(0, Ordering::Relaxed);
         let ebpf_counter = unsafe { ebpf::socket_tracer_stats() };
+        let (mut java_syms_scheduled_refresh, mut java_syms_manual_refresh) = (0, 0);
+        unsafe {
+            ebpf::get_java_syms_refresh_counts(
+                &mut java_syms_scheduled_refresh,
+                &mut java_syms_manual_refresh,
+            );
+        }
 
         vec![
             (
@@ -141,6 +172,11 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(time_backtrack_max),
             ),
+            (
+                "kprobe_ports_hot_update",
+                CounterType::Counted,
+                CounterValue::Unsigned(kprobe_ports_hot_update),
+            ),
             (
                 "perf_pages_count",
                 CounterType::Counted,
@@ -286,6 +322,102 @@ impl OwnedCountable for SyncEbpfCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(ebpf_counter.invalid_packets as u64),
             ),
+            (
+                "tls_uprobe_targets",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.ssl_uprobe_targets),
+            ),
+            (
+                "tls_uprobe_target_failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.ssl_uprobe_target_failed),
+            ),
+            (
+                "uprobe_attach_retries",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.uprobe_attach_retries),
+            ),
+            (
+                "uprobe_attach_gave_up",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.uprobe_attach_gave_up),
+            ),
+            (
+                "uprobe_targets_retrying",
+                CounterType::Gauged,
+                CounterValue::Unsigned(ebpf_counter.uprobe_targets_retrying),
+            ),
+            (
+                "socket_map_reclaims",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaims),
+            ),
+            (
+                "socket_map_reclaimed",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaimed),
+            ),
+            (
+                "socket_map_reclaim_avg_us",
+                CounterType::Gauged,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_avg_us),
+            ),
+            (
+                "socket_map_reclaim_max_us",
+                CounterType::Gauged,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_max_us),
+            ),
+            // idle time of the reclaimed entries, see SOCKET_RECLAIM_AGE_BUCKETS
+            (
+                "socket_map_reclaimed_idle_le_30s",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_ages[0]),
+            ),
+            (
+                "socket_map_reclaimed_idle_le_120s",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_ages[1]),
+            ),
+            (
+                "socket_map_reclaimed_idle_le_600s",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_ages[2]),
+            ),
+            (
+                "socket_map_reclaimed_idle_gt_600s",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.socket_map_reclaim_ages[3]),
+            ),
+            (
+                "io_event_filtered",
+                CounterType::Counted,
+                CounterValue::Unsigned(io_event_filtered),
+            ),
+            (
+                "namespace_filtered",
+                CounterType::Counted,
+                CounterValue::Unsigned(namespace_filtered),
+            ),
+            (
+                "io_event_dev_filtered",
+                CounterType::Counted,
+                CounterValue::Unsigned(ebpf_counter.io_event_dev_filtered),
+            ),
+            (
+                "io_uring_trace_status",
+                CounterType::Gauged,
+                CounterValue::Unsigned(ebpf_counter.io_uring_trace_status as u64),
+            ),
+            (
+                "java_syms_scheduled_refresh",
+                CounterType::Counted,
+                CounterValue::Unsigned(java_syms_scheduled_refresh),
+            ),
+            (
+                "java_syms_manual_refresh",
+                CounterType::Counted,
+                CounterValue::Unsigned(java_syms_manual_refresh),
+            ),
         ]
     }
     // EbpfCollector不会重复创建，这里都是false
@@ -437,6 +569,11 @@ impl EbpfDispatcher {
             true, // from_ebpf
         );
         let leaky_bucket = LeakyBucket::new(Some(ebpf_config.ebpf.socket.tunning.max_capture_rate));
+        // applied before the global max_capture_rate, which still caps all processes
+        let mut process_limiter = ProcessRateLimiter::new(
+            ebpf_config.ebpf.socket.tunning.process_max_capture_rate,
+            self.stats_collector.clone(),
+        );
         const QUEUE_BATCH_SIZE: usize = 1024;
         let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
 
@@ -446,6 +583,31 @@ impl EbpfDispatcher {
         let mut ebpf_config = self.config.load().clone();
         let mut last_packet_timestamp = 0;
 
+        // sampling_frequency requires restart, so the throttle is created only once
+        let mut sampling_throttle =
+            SamplingThrottle::new(ebpf_config.ebpf.profile.on_cpu.sampling_frequency as u32);
+        let sampling_counter = Arc::new(SamplingThrottleCounter::default());
+        sampling_counter
+            .frequency
+            .store(sampling_throttle.frequency(), Ordering::Relaxed);
+        self.stats_collector.register_countable(
+            &stats::NoTagModule("ebpf-profile-on-cpu-sampling"),
+            Countable::Ref(Arc::downgrade(&sampling_counter) as Weak<dyn RefCountable>),
+        );
+        let system = System::new_with_specifics(RefreshKind::new().with_cpu(CpuRefreshKind::new()));
+        let mut last_load_check = Duration::ZERO;
+
+        let mut reclaim_controller = None;
+        let reclaim_counter = Arc::new(ReclaimThresholdCounter::default());
+        reclaim_counter.threshold.store(
+            ebpf_config.ebpf.tunning.socket_map_reclaim_threshold,
+            Ordering::Relaxed,
+        );
+        self.stats_collector.register_countable(
+            &stats::NoTagModule("ebpf-socket-map-reclaim"),
+            Countable::Ref(Arc::downgrade(&reclaim_counter) as Weak<dyn RefCountable>),
+        );
+
         while unsafe { SWITCH } {
             if need_reload_config.swap(false, Ordering::Relaxed) {
                 info!("ebpf dispatcher reload config");
@@ -453,6 +615,8 @@ impl EbpfDispatcher {
                 log_parser_config = self.log_parser_config.load().clone();
                 collector_config = self.collector_config.load().clone();
                 ebpf_config = self.config.load().clone();
+                process_limiter
+                    .set_default_rate(ebpf_config.ebpf.socket.tunning.process_max_capture_rate);
             }
             let config = Config {
                 flow: &flow_config,
@@ -461,6 +625,26 @@ impl EbpfDispatcher {
                 ebpf: Some(&ebpf_config),
             };
 
+            // profiles are batched in the profiler callback, which is not called when idle
+            Self::flush_profile_batch(false);
+
+            let now = get_timestamp(0);
+            if now >= last_load_check + LOAD_CHECK_INTERVAL {
+                last_load_check = now;
+                Self::throttle_sampling(
+                    &mut sampling_throttle,
+                    &system,
+                    &ebpf_config,
+                    &sampling_counter,
+                );
+                Self::adapt_reclaim_threshold(
+                    &mut reclaim_controller,
+                    &ebpf_config,
+                    &reclaim_counter,
+                );
+                Self::refresh_event_filters();
+            }
+
             if self
                 .receiver
                 .recv_all(&mut batch, Some(Duration::from_secs(1)))
@@ -479,6 +663,8 @@ impl EbpfDispatcher {
                 continue;
             }
 
+            process_limiter.check_update();
+            let now = get_timestamp(0);
             for mut packet in batch.drain(..) {
                 if packet.lookup_key.timestamp.as_nanos() < last_packet_timestamp {
                     counter.time_backtrack_max.fetch_max(
@@ -488,6 +674,10 @@ impl EbpfDispatcher {
                 }
                 last_packet_timestamp = packet.lookup_key.timestamp.as_nanos();
 
+                if !process_limiter.acquire(packet.process_id, &packet.process_kname, now) {
+                    continue;
+                }
+
                 if !leaky_bucket.acquire(1) {
                     counter.get_token_failed.fetch_add(1, Ordering::Relaxed);
                     exception_handler.set(Exception::RxPpsThresholdExceeded, None);
@@ -500,6 +690,120 @@ impl EbpfDispatcher {
                 Self::inject_meta_packet(packet, &mut flow_map, &config, &mut reorder);
             }
         }
+        Self::flush_profile_batch(true);
+    }
+
+    fn throttle_sampling(
+        throttle: &mut SamplingThrottle,
+        system: &System,
+        config: &EbpfConfig,
+        counter: &SamplingThrottleCounter,
+    ) {
+        if config.ebpf.profile.on_cpu.disabled || !unsafe { ebpf::continuous_profiler_running() } {
+            return;
+        }
+        let relative_sys_load = &config.relative_sys_load;
+        let load = system.load_average();
+        let load = match relative_sys_load.metric {
+            SystemLoadMetric::Load1 => load.one,
+            SystemLoadMetric::Load5 => load.five,
+            SystemLoadMetric::Load15 => load.fifteen,
+        } as f32;
+        let cpu_count = system.cpus().len().max(1) as f32;
+
+        let now = get_timestamp(0);
+        let frequency = throttle.update(now, load / cpu_count, relative_sys_load);
+        counter
+            .overloaded
+            .store(throttle.overloaded(), Ordering::Relaxed);
+        let Some(frequency) = frequency else {
+            return;
+        };
+        if unsafe { ebpf::set_profiler_sample_freq(frequency as c_int) } != 0 {
+            warn!("ebpf set on-cpu sampling frequency to {frequency}Hz failed.");
+            throttle.commit(now, false);
+            return;
+        }
+        throttle.commit(now, true);
+        counter.transitions.fetch_add(1, Ordering::Relaxed);
+        // reported with the profiles so that samples can be converted to cpu time
+        unsafe {
+            ON_CPU_PROFILE_FREQUENCY = frequency;
+        }
+        counter.frequency.store(frequency, Ordering::Relaxed);
+    }
+
+    fn adapt_reclaim_threshold(
+        controller: &mut Option<ReclaimThresholdController>,
+        config: &EbpfConfig,
+        counter: &ReclaimThresholdCounter,
+    ) {
+        let tunning = &config.ebpf.tunning;
+        if tunning.socket_map_reclaim_mode == SocketMapReclaimMode::Static {
+            if controller.take().is_some() {
+                // back to the configured threshold
+                unsafe {
+                    ebpf::set_socket_map_reclaim_threshold(tunning.socket_map_reclaim_threshold)
+                };
+                counter
+                    .threshold
+                    .store(tunning.socket_map_reclaim_threshold, Ordering::Relaxed);
+                counter.slope.store(0, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        let controller = controller.get_or_insert_with(|| {
+            ReclaimThresholdController::new(
+                tunning.max_socket_entries,
+                tunning.socket_map_reclaim_threshold,
+                tunning.socket_map_reclaim_threshold_min,
+                tunning.socket_map_reclaim_threshold_max,
+            )
+        });
+        controller.set_bounds(
+            tunning.socket_map_reclaim_threshold_min,
+            tunning.socket_map_reclaim_threshold_max,
+        );
+        let used = unsafe { ebpf::get_socket_map_used() };
+        let threshold = controller.update(get_timestamp(0), used);
+        counter
+            .slope
+            .store(controller.slope() as i64, Ordering::Relaxed);
+        let threshold = threshold.unwrap_or(controller.threshold());
+        if threshold != counter.threshold.swap(threshold, Ordering::Relaxed) {
+            counter.adjustments.fetch_add(1, Ordering::Relaxed);
+            unsafe { ebpf::set_socket_map_reclaim_threshold(threshold) };
+        }
+    }
+
+    // the filters are refreshed here rather than in the eBPF callbacks, which
+    // only load the refreshed results
+    fn refresh_event_filters() {
+        #[allow(static_mut_refs)]
+        if let Some(filter) = unsafe { NAMESPACE_FILTER.as_ref() } {
+            filter.refresh();
+        }
+        #[allow(static_mut_refs)]
+        if let Some(filter) = unsafe { IO_EVENT_FILTER.as_ref() } {
+            if filter.refresh() {
+                unsafe { EbpfCollector::ebpf_set_io_event_dev_filter(filter) };
+            }
+        }
+    }
+
+    fn flush_profile_batch(force: bool) {
+        #[allow(static_mut_refs)]
+        let Some(batcher) = (unsafe { PROFILE_BATCHER.as_ref() }) else {
+            return;
+        };
+        if let Ok(mut batcher) = batcher.lock() {
+            if force {
+                batcher.flush();
+            } else {
+                batcher.flush_expired();
+            }
+        }
     }
 }
 
@@ -537,6 +841,8 @@ pub struct EbpfCollector {
     thread_handle: Option<JoinHandle<()>>,
 
     config_handle: ConfigHandle,
+    // kprobe port lists applied to the socket tracer
+    kprobe_ports: EbpfSocketKprobe,
 
     counter: Arc<EbpfCounter>,
     stats_collector: Arc<stats::Collector>,
@@ -550,6 +856,9 @@ pub struct EbpfCollector {
 }
 
 const BATCH_SIZE: usize = 64;
+// relative_sys_load and the event filters are checked every 10s, the same as the
+// default guard interval
+const LOAD_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 #[allow(static_mut_refs)]
 static mut SWITCH: bool = false;
@@ -562,15 +871,23 @@ static mut DPDK_SENDER_BUFFERS: Vec<Vec<Box<packet::Packet>>> = vec![];
 #[allow(static_mut_refs)]
 static mut PROC_EVENT_SENDER: Option<DebugSender<BoxedProcEvents>> = None;
 #[allow(static_mut_refs)]
-static mut EBPF_PROFILE_SENDER: Option<DebugSender<Profile>> = None;
+static mut PROFILE_BATCHER: Option<Mutex<profile_batch::ProfileBatcher>> = None;
 #[allow(static_mut_refs)]
 static mut POLICY_GETTER: Option<PolicyGetter> = None;
 #[allow(static_mut_refs)]
 static mut ON_CPU_PROFILE_FREQUENCY: u32 = 0;
 #[allow(static_mut_refs)]
-static mut PROFILE_STACK_COMPRESSION: bool = true;
-#[allow(static_mut_refs)]
 static mut TIME_DIFF: Option<Arc<AtomicI64>> = None;
+#[cfg(feature = "extended_observability")]
+static mut OFF_CPU_THREAD_NAMES: Option<thread_name::ThreadNameCache> = None;
+#[allow(static_mut_refs)]
+static mut IO_EVENT_FILTER: Option<io_event_filter::IoEventFilter> = None;
+// IO events dropped in user space by IO_EVENT_FILTER
+static IO_EVENT_FILTERED: AtomicU64 = AtomicU64::new(0);
+#[allow(static_mut_refs)]
+static mut NAMESPACE_FILTER: Option<namespace_filter::NamespaceFilter> = None;
+// events dropped because their containers are in excluded namespaces
+static NAMESPACE_FILTERED: AtomicU64 = AtomicU64::new(0);
 
 pub unsafe fn string_from_null_terminated_c_str(ptr: *const u8) -> String {
     CStr::from_ptr(ptr as *const libc::c_char)
@@ -632,6 +949,12 @@ impl EbpfCollector {
 
             let container_id =
                 CStr::from_ptr(sd.container_id.as_ptr() as *const libc::c_char).to_string_lossy();
+            if let Some(filter) = NAMESPACE_FILTER.as_ref() {
+                if filter.is_filtered(&container_id) {
+                    NAMESPACE_FILTERED.fetch_add(1, Ordering::Relaxed);
+                    return 0;
+                }
+            }
             let event_type = EventType::from(sd.source);
             if event_type != EventType::OtherEvent {
                 // EbpfType like TracePoint, TlsUprobe, GoHttp2Uprobe belong to other events
@@ -641,6 +964,18 @@ impl EbpfCollector {
                     return 0;
                 }
                 let mut event = event.unwrap();
+                if let (Some(filter), Some(io_event)) =
+                    (IO_EVENT_FILTER.as_ref(), event.0.io_event_data())
+                {
+                    if filter.is_filtered(
+                        io_event.mount_point(),
+                        io_event.file_dir(),
+                        io_event.s_dev(),
+                    ) {
+                        IO_EVENT_FILTERED.fetch_add(1, Ordering::Relaxed);
+                        return 0;
+                    }
+                }
                 if let Some(policy) = POLICY_GETTER.as_ref() {
                     event.0.pod_id = policy.lookup_pod_id(&container_id);
                 }
@@ -673,7 +1008,7 @@ impl EbpfCollector {
     ) -> c_int {
         #[allow(static_mut_refs)]
         unsafe {
-            if !SWITCH || EBPF_PROFILE_SENDER.is_none() {
+            if !SWITCH || PROFILE_BATCHER.is_none() {
                 return 0;
             }
 
@@ -710,6 +1045,16 @@ impl EbpfCollector {
             profile.tid = data.tid;
             profile.thread_name = string_from_null_terminated_c_str(data.comm.as_ptr());
             profile.process_name = string_from_null_terminated_c_str(data.process_name.as_ptr());
+            #[cfg(feature = "extended_observability")]
+            if data.profiler_type == ebpf::PROFILER_TYPE_OFFCPU {
+                if !off_cpu_override::keep(data.pid, data.count) {
+                    return 0;
+                }
+                if let Some(cache) = OFF_CPU_THREAD_NAMES.as_mut() {
+                    (profile.tid, profile.thread_name) =
+                        cache.lookup(data.pid, data.tid, &profile.thread_name, get_timestamp(0));
+                }
+            }
             profile.u_stack_id = data.u_stack_id;
             profile.k_stack_id = data.k_stack_id;
             profile.cpu = data.cpu;
@@ -717,27 +1062,13 @@ impl EbpfCollector {
             profile.wide_count = data.count;
             let profile_data =
                 slice::from_raw_parts(data.stack_data as *mut u8, data.stack_data_len as usize);
-            if PROFILE_STACK_COMPRESSION {
-                match compress(&profile_data, 0) {
-                    Ok(compressed_data) => {
-                        profile.data_compressed = true;
-                        profile.data = compressed_data;
-                    }
-                    Err(e) => {
-                        profile.data = profile_data.to_vec();
-                        debug!("failed to compress ebpf profile: {:?}", e);
-                    }
-                }
-            } else {
-                profile.data = profile_data.to_vec();
-            }
             let container_id =
                 CStr::from_ptr(data.container_id.as_ptr() as *const libc::c_char).to_string_lossy();
             if let Some(policy_getter) = POLICY_GETTER.as_ref() {
                 profile.pod_id = policy_getter.lookup_pod_id(&container_id);
             }
-            if let Err(e) = EBPF_PROFILE_SENDER.as_mut().unwrap().send(Profile(profile)) {
-                warn!("ebpf profile send error: {:?}", e);
+            if let Ok(mut batcher) = PROFILE_BATCHER.as_ref().unwrap().lock() {
+                batcher.push(profile, profile_data);
             }
         }
 
@@ -776,15 +1107,60 @@ impl EbpfCollector {
                 DPDK_SENDER_BUFFERS.push(Vec::with_capacity(BATCH_SIZE));
             }
             PROC_EVENT_SENDER = Some(proc_event_sender);
-            EBPF_PROFILE_SENDER = Some(ebpf_profile_sender);
+            capture_rate::set_matchers(&config.process_matcher);
+            process_listener.register(
+                ProcessMatcher::FEATURE_SOCKET_CAPTURE_RATE,
+                capture_rate::set_feature_capture_rate,
+            );
+            stats_collector.register_countable(
+                &stats::NoTagModule("ebpf-capability"),
+                Countable::Owned(Box::new(capability::CapabilityCounter)),
+            );
+            let batch_counter = Arc::new(profile_batch::ProfileBatchCounter::default());
+            stats_collector.register_countable(
+                &stats::NoTagModule("ebpf-profile-batcher"),
+                Countable::Ref(Arc::downgrade(&batch_counter) as Weak<dyn RefCountable>),
+            );
+            PROFILE_BATCHER = Some(Mutex::new(profile_batch::ProfileBatcher::new(
+                ebpf_profile_sender,
+                config.ebpf.profile.preprocess.stack_compression,
+                config.ebpf.profile.preprocess.batch_interval,
+                config.profile_batch_max_bytes,
+                batch_counter,
+            )));
             POLICY_GETTER = Some(policy_getter);
             ON_CPU_PROFILE_FREQUENCY = config.ebpf.profile.on_cpu.sampling_frequency as u32;
-            PROFILE_STACK_COMPRESSION = config.ebpf.profile.preprocess.stack_compression;
             TIME_DIFF = Some(time_diff);
+            #[cfg(feature = "extended_observability")]
+            {
+                let off_cpu = &config.ebpf.profile.off_cpu;
+                OFF_CPU_THREAD_NAMES = if off_cpu.aggregate_by_thread {
+                    Some(thread_name::ThreadNameCache::new(
+                        off_cpu.max_threads_per_process,
+                    ))
+                } else {
+                    None
+                };
+            }
             handle
         }
     }
 
+    // push the part of the io event filter that can be checked by device into eBPF
+    unsafe fn ebpf_set_io_event_dev_filter(filter: &io_event_filter::IoEventFilter) {
+        let (mode, devices) = match filter.kernel_filter(ebpf::IO_EVENT_DEV_FILTER_MAX) {
+            Some((true, devices)) => (ebpf::IO_EVENT_DEV_FILTER_INCLUDE, devices),
+            Some((false, devices)) => (ebpf::IO_EVENT_DEV_FILTER_EXCLUDE, devices),
+            None => (ebpf::IO_EVENT_DEV_FILTER_NONE, vec![]),
+        };
+        if ebpf::set_io_event_dev_filter(mode, devices.as_ptr(), devices.len() as c_int) != 0 {
+            warn!(
+                "ebpf set_io_event_dev_filter error, mode: {} devices: {:?}",
+                mode, devices
+            );
+        }
+    }
+
     #[allow(unused)]
     unsafe fn ebpf_core_init(
         process_listener: &ProcessListener,
@@ -814,6 +1190,36 @@ impl EbpfCollector {
                 .map(|p| p.match_regex.as_str())
                 .unwrap_or_default();
             info!("ebpf set golang uprobe proc regexp: {}", uprobe_proc_regexp);
+            ebpf::clear_go_offset_overrides();
+            for o in config.ebpf.socket.uprobe.golang.offset_overrides.iter() {
+                for offset in o.offsets.iter() {
+                    let (Ok(structure), Ok(field)) = (
+                        CString::new(offset.structure.as_bytes()),
+                        CString::new(offset.field.as_bytes()),
+                    ) else {
+                        continue;
+                    };
+                    let ret = ebpf::add_go_offset_override(
+                        o.min_version.encode(0),
+                        o.max_version.encode(255),
+                        structure.as_ptr(),
+                        field.as_ptr(),
+                        offset.offset as c_int,
+                    );
+                    if ret != 0 {
+                        warn!(
+                            "ebpf add go offset override {}.{} for go{}.{}-go{}.{} failed: {}",
+                            offset.structure,
+                            offset.field,
+                            o.min_version.major,
+                            o.min_version.minor,
+                            o.max_version.major,
+                            o.max_version.minor,
+                            ret
+                        );
+                    }
+                }
+            }
             ebpf::set_feature_regex(
                 ebpf::FEATURE_UPROBE_GOLANG,
                 CString::new(uprobe_proc_regexp.as_bytes())
@@ -847,6 +1253,17 @@ impl EbpfCollector {
                 "ebpf set openssl uprobe proc regexp: {}",
                 uprobe_proc_regexp
             );
+            let tls = &config.ebpf.socket.uprobe.tls;
+            ebpf::set_uprobe_openssl_targets(
+                CString::new(tls.library_paths.join(",").as_bytes())
+                    .unwrap()
+                    .as_c_str()
+                    .as_ptr(),
+                CString::new(tls.binary_names.join(",").as_bytes())
+                    .unwrap()
+                    .as_c_str()
+                    .as_ptr(),
+            );
             ebpf::set_feature_regex(
                 ebpf::FEATURE_UPROBE_OPENSSL,
                 CString::new(uprobe_proc_regexp.as_bytes())
@@ -929,19 +1346,7 @@ impl EbpfCollector {
             ebpf::disable_unix_socket_feature();
         }
 
-        let white_list = &config.ebpf.socket.kprobe.whitelist;
-        if !white_list.ports.is_empty() {
-            if let Some(b) = parse_u16_range_list_to_bitmap(&white_list.ports, false) {
-                ebpf::set_allow_port_bitmap(b.get_raw_ptr());
-            }
-        }
-
-        let black_list = &config.ebpf.socket.kprobe.blacklist;
-        if !black_list.ports.is_empty() {
-            if let Some(b) = parse_u16_range_list_to_bitmap(&black_list.ports, false) {
-                ebpf::set_bypass_port_bitmap(b.get_raw_ptr());
-            }
-        }
+        Self::ebpf_set_kprobe_ports(&config.ebpf.socket.kprobe);
 
         if let Err(e) = config.ebpf.tunning.validate() {
             warn!(
@@ -960,6 +1365,21 @@ impl EbpfCollector {
             return Err(Error::EbpfInitError);
         }
 
+        // turn off the sub-features the kernel can not run instead of failing them all
+        let mut degraded_config = config.clone();
+        for feature in capability::probe_and_degrade(&mut degraded_config.ebpf) {
+            match feature {
+                capability::Feature::UprobeGolang => {
+                    ebpf::set_uprobe_golang_enabled(false);
+                }
+                capability::Feature::UprobeTls => {
+                    ebpf::set_uprobe_openssl_enabled(false);
+                }
+                _ => (),
+            }
+        }
+        let config = &degraded_config;
+
         if ebpf::set_go_tracing_timeout(
             config.ebpf.socket.uprobe.golang.tracing_timeout.as_secs() as c_int
         ) != 0
@@ -996,6 +1416,12 @@ impl EbpfCollector {
             return Err(Error::EbpfInitError);
         }
 
+        IO_EVENT_FILTER = io_event_filter::IoEventFilter::new(&config.ebpf.file.io_event);
+        if let Some(filter) = IO_EVENT_FILTER.as_ref() {
+            Self::ebpf_set_io_event_dev_filter(filter);
+        }
+        NAMESPACE_FILTER = namespace_filter::NamespaceFilter::new(&config.ebpf.excluded_namespaces);
+
         let mut all_proto_map = get_all_protocol()
             .iter()
             .map(|p| p.as_str().to_lowercase())
@@ -1029,6 +1455,10 @@ impl EbpfCollector {
             ebpf::disable_syscall_trace_id();
         }
 
+        if config.ebpf.socket.tunning.io_uring_enabled {
+            ebpf::enable_io_uring_trace();
+        }
+
         ebpf::set_bpf_map_prealloc(!config.ebpf.socket.tunning.map_prealloc_disabled);
 
         if config.ebpf.socket.tunning.fentry_enabled {
@@ -1236,27 +1666,24 @@ impl EbpfCollector {
                     );
 
                     ebpf::set_offcpu_cpuid_aggregation(off_cpu.aggregate_by_cpu as i32);
-                    ebpf::set_offcpu_minblock_time(off_cpu.min_blocking_time.as_micros() as u32);
+                    let min_block = off_cpu_override::set_config(&config.process_matcher, off_cpu);
+                    ebpf::set_offcpu_minblock_time(min_block.as_micros() as u32);
                 }
 
+                // global switch wins, the matchers only narrow down the processes
                 if !is_uprobe_meltdown && !memory.disabled {
-                    let feature = "ebpf.profile.memory";
+                    let feature = ProcessMatcher::FEATURE_PROFILE_MEMORY;
                     process_listener.register(feature, set_feature_memory);
 
-                    let memory_cpu_regexp = config
-                        .process_matcher
+                    let matchers = ProcessMatcher::resolve_features(&config.process_matcher);
+                    let memory_regexp = matchers
                         .iter()
-                        .find(|p| {
-                            p.enabled_features
-                                .iter()
-                                .find(|f| f.eq_ignore_ascii_case("ebpf.profile.memory"))
-                                .is_some()
-                        })
+                        .find(|p| p.has_feature(feature))
                         .map(|p| p.match_regex.as_str())
                         .unwrap_or_default();
                     ebpf::set_feature_regex(
                         ebpf::FEATURE_PROFILE_MEMORY,
-                        CString::new(memory_cpu_regexp.as_bytes())
+                        CString::new(memory_regexp.as_bytes())
                             .unwrap()
                             .as_c_str()
                             .as_ptr(),
@@ -1312,6 +1739,47 @@ impl EbpfCollector {
         Ok(handle)
     }
 
+    unsafe fn ebpf_set_kprobe_ports(kprobe: &EbpfSocketKprobe) {
+        if let Some(b) = parse_u16_range_list_to_bitmap(&kprobe.whitelist.ports, false) {
+            ebpf::set_allow_port_bitmap(b.get_raw_ptr());
+        }
+        if let Some(b) = parse_u16_range_list_to_bitmap(&kprobe.blacklist.ports, false) {
+            ebpf::set_bypass_port_bitmap(b.get_raw_ptr());
+        }
+    }
+
+    // Port lists are applied to the running socket tracer directly, the datapath
+    // switches to the new bitmaps at once without restarting the eBPF collector
+    fn ebpf_update_kprobe_ports(&mut self, kprobe: &EbpfSocketKprobe) {
+        if self.kprobe_ports.whitelist == kprobe.whitelist
+            && self.kprobe_ports.blacklist == kprobe.blacklist
+        {
+            return;
+        }
+        let n = unsafe {
+            Self::ebpf_set_kprobe_ports(kprobe);
+            ebpf::update_kprobe_port_bitmaps()
+        };
+        if n != 0 {
+            warn!(
+                "ebpf update kprobe ports (whitelist: {:?}, blacklist: {:?}) failed: {}",
+                kprobe.whitelist.ports, kprobe.blacklist.ports, n
+            );
+            return;
+        }
+        info!(
+            "ebpf kprobe ports updated, whitelist: {:?} -> {:?}, blacklist: {:?} -> {:?}",
+            self.kprobe_ports.whitelist.ports,
+            kprobe.whitelist.ports,
+            self.kprobe_ports.blacklist.ports,
+            kprobe.blacklist.ports
+        );
+        self.counter
+            .kprobe_ports_hot_update
+            .fetch_add(1, Ordering::Relaxed);
+        self.kprobe_ports = kprobe.clone();
+    }
+
     fn ebpf_on_config_change(l7_log_packet_size: usize) {
         unsafe {
             let n = ebpf::set_data_limit_max(l7_log_packet_size as c_int);
@@ -1404,8 +1872,12 @@ impl EbpfCollector {
             is_uprobe_meltdown
         );
         let queue_name = "0-ebpf-to-ebpf-collector";
-        let (sender, receiver, counter) =
-            bounded_with_debug(ebpf_config.queue_size, queue_name, queue_debugger);
+        let (sender, receiver, counter) = bounded_with_debug_and_policy(
+            ebpf_config.queue_size,
+            queue_name,
+            queue_debugger,
+            ebpf_config.queue_overflow_policy,
+        );
         stats_collector.register_countable(
             &stats::QueueStats {
                 id: 0,
@@ -1456,10 +1928,12 @@ impl EbpfCollector {
             },
             thread_handle: None,
             config_handle,
+            kprobe_ports: ebpf_config.ebpf.socket.kprobe.clone(),
             counter: Arc::new(EbpfCounter {
                 rx: AtomicU64::new(0),
                 time_backtrack_max: AtomicU64::new(0),
                 get_token_failed: AtomicU64::new(0),
+                kprobe_ports_hot_update: AtomicU64::new(0),
             }),
             need_reload_config: Default::default(),
             stats_collector,
@@ -1487,6 +1961,7 @@ impl EbpfCollector {
     }
 
     pub fn on_config_change(&mut self, config: &EbpfConfig) {
+        capture_rate::set_matchers(&config.process_matcher);
         unsafe {
             let ecfg = &config.ebpf.profile;
             let is_uprobe_meltdown = crate::utils::guard::is_kernel_ebpf_uprobe_meltdown();
@@ -1535,6 +2010,7 @@ impl EbpfCollector {
             }
 
             Self::ebpf_on_config_change(config.l7_log_packet_size);
+            self.ebpf_update_kprobe_ports(&config.ebpf.socket.kprobe);
 
             #[cfg(feature = "extended_observability")]
             {
@@ -1568,6 +2044,13 @@ impl EbpfCollector {
                 for nic in &config.ebpf.network.nic_optimize {
                     nic.apply();
                 }
+
+                // process matchers are hot updated, their min_blocking_time overrides too
+                let off_cpu = &config.ebpf.profile.off_cpu;
+                if !restart_cprofiler && !off_cpu.disabled && ebpf::continuous_profiler_running() {
+                    let min_block = off_cpu_override::set_config(&config.process_matcher, off_cpu);
+                    ebpf::set_offcpu_minblock_time(min_block.as_micros() as u32);
+                }
             }
         }
 
@@ -1690,7 +2173,11 @@ pub fn set_feature_on_cpu(pids: &Vec<u32>, _: &Vec<ProcessData>) {
     }
 }
 
-pub fn set_feature_off_cpu(pids: &Vec<u32>, _: &Vec<ProcessData>) {
+pub fn set_feature_off_cpu(pids: &Vec<u32>, #[allow(unused)] process_datas: &Vec<ProcessData>) {
+    #[cfg(feature = "extended_observability")]
+    if let Some(min_block) = off_cpu_override::set_processes(process_datas) {
+        unsafe { ebpf::set_offcpu_minblock_time(min_block.as_micros() as u32) };
+    }
     unsafe {
         ebpf::set_feature_pids(
             ebpf::FEATURE_PROFILE_OFFCPU,
@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::RwLock;
+
+use bincode::{Decode, Encode};
+use log::warn;
+
+use public::{
+    counter::{Counter, CounterType, CounterValue, OwnedCountable},
+    proto::agent,
+};
+
+use crate::config::config::Ebpf;
+use crate::ebpf::ebpf_capability;
+
+static REPORT: RwLock<Option<CapabilityReport>> = RwLock::new(None);
+
+// Sub-features turned off because the kernel does not support them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    // falls back to kprobe
+    Fentry,
+    UprobeGolang,
+    UprobeTls,
+    OnCpuProfile,
+    OffCpuProfile,
+    MemoryProfile,
+}
+
+impl Feature {
+    const ALL: [Feature; 6] = [
+        Feature::Fentry,
+        Feature::UprobeGolang,
+        Feature::UprobeTls,
+        Feature::OnCpuProfile,
+        Feature::OffCpuProfile,
+        Feature::MemoryProfile,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::Fentry => "fentry",
+            Feature::UprobeGolang => "uprobe_golang",
+            Feature::UprobeTls => "uprobe_tls",
+            Feature::OnCpuProfile => "on_cpu_profile",
+            Feature::OffCpuProfile => "off_cpu_profile",
+            Feature::MemoryProfile => "memory_profile",
+        }
+    }
+
+    fn supported(&self, cap: &ebpf_capability) -> bool {
+        match self {
+            Feature::Fentry => cap.fentry,
+            Feature::UprobeGolang | Feature::UprobeTls | Feature::MemoryProfile => cap.uprobe,
+            Feature::OnCpuProfile => cap.perf_event,
+            Feature::OffCpuProfile => cap.kprobe,
+        }
+    }
+
+    fn enabled(&self, config: &Ebpf) -> bool {
+        match self {
+            Feature::Fentry => config.socket.tunning.fentry_enabled,
+            Feature::UprobeGolang => config.socket.uprobe.golang.enabled,
+            Feature::UprobeTls => config.socket.uprobe.tls.enabled,
+            Feature::OnCpuProfile => !config.profile.on_cpu.disabled,
+            Feature::OffCpuProfile => !config.profile.off_cpu.disabled,
+            Feature::MemoryProfile => !config.profile.memory.disabled,
+        }
+    }
+
+    fn disable(&self, config: &mut Ebpf) {
+        match self {
+            Feature::Fentry => config.socket.tunning.fentry_enabled = false,
+            Feature::UprobeGolang => config.socket.uprobe.golang.enabled = false,
+            Feature::UprobeTls => config.socket.uprobe.tls.enabled = false,
+            Feature::OnCpuProfile => config.profile.on_cpu.disabled = true,
+            Feature::OffCpuProfile => config.profile.off_cpu.disabled = true,
+            Feature::MemoryProfile => config.profile.memory.disabled = true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Encode, Decode)]
+pub struct CapabilityReport {
+    pub btf_vmlinux: bool,
+    pub perf_event_array: bool,
+    pub ring_buffer: bool,
+    pub probe_read_kernel: bool,
+    pub kprobe: bool,
+    pub uprobe: bool,
+    pub tracepoint: bool,
+    pub perf_event: bool,
+    pub fentry: bool,
+    pub degraded_features: Vec<String>,
+}
+
+impl CapabilityReport {
+    fn new(cap: &ebpf_capability, degraded: &[Feature]) -> Self {
+        Self {
+            btf_vmlinux: cap.btf_vmlinux,
+            perf_event_array: cap.perf_event_array,
+            ring_buffer: cap.ring_buffer,
+            probe_read_kernel: cap.probe_read_kernel,
+            kprobe: cap.kprobe,
+            uprobe: cap.uprobe,
+            tracepoint: cap.tracepoint,
+            perf_event: cap.perf_event,
+            fentry: cap.fentry,
+            degraded_features: degraded.iter().map(|f| f.as_str().to_owned()).collect(),
+        }
+    }
+
+    fn capabilities(&self) -> [(&'static str, bool); 9] {
+        [
+            ("btf_vmlinux", self.btf_vmlinux),
+            ("perf_event_array", self.perf_event_array),
+            ("ring_buffer", self.ring_buffer),
+            ("probe_read_kernel", self.probe_read_kernel),
+            ("kprobe", self.kprobe),
+            ("uprobe", self.uprobe),
+            ("tracepoint", self.tracepoint),
+            ("perf_event", self.perf_event),
+            ("fentry", self.fentry),
+        ]
+    }
+}
+
+impl From<&CapabilityReport> for agent::EbpfCapability {
+    fn from(r: &CapabilityReport) -> Self {
+        Self {
+            btf_vmlinux: Some(r.btf_vmlinux),
+            perf_event_array: Some(r.perf_event_array),
+            ring_buffer: Some(r.ring_buffer),
+            probe_read_kernel: Some(r.probe_read_kernel),
+            kprobe: Some(r.kprobe),
+            uprobe: Some(r.uprobe),
+            tracepoint: Some(r.tracepoint),
+            perf_event: Some(r.perf_event),
+            fentry: Some(r.fentry),
+            degraded_features: r.degraded_features.clone(),
+        }
+    }
+}
+
+// Turns off the enabled sub-features the kernel does not support
+fn degrade(cap: &ebpf_capability, config: &mut Ebpf) -> Vec<Feature> {
+    let mut degraded = vec![];
+    for feature in Feature::ALL {
+        if feature.enabled(config) && !feature.supported(cap) {
+            warn!(
+                "ebpf feature {} is not supported by the kernel, disabled",
+                feature.as_str()
+            );
+            feature.disable(config);
+            degraded.push(feature);
+        }
+    }
+    degraded
+}
+
+// Probes the kernel, disables the unsupported sub-features in `config` and saves the report.
+// Must be called after bpf_tracer_init().
+pub fn probe_and_degrade(config: &mut Ebpf) -> Vec<Feature> {
+    let mut cap = ebpf_capability::default();
+    if unsafe { crate::ebpf::ebpf_capability_probe(&mut cap) } != 0 {
+        warn!("ebpf capability probe failed");
+        return vec![];
+    }
+    let degraded = degrade(&cap, config);
+    *REPORT.write().unwrap() = Some(CapabilityReport::new(&cap, &degraded));
+    degraded
+}
+
+pub fn report() -> Option<CapabilityReport> {
+    REPORT.read().unwrap().clone()
+}
+
+pub struct CapabilityCounter;
+
+impl OwnedCountable for CapabilityCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        let Some(report) = report() else {
+            return vec![];
+        };
+        let mut counters = report
+            .capabilities()
+            .into_iter()
+            .map(|(name, supported)| {
+                (
+                    name,
+                    CounterType::Gauged,
+                    CounterValue::Unsigned(supported as u64),
+                )
+            })
+            .collect::<Vec<_>>();
+        for feature in Feature::ALL {
+            let degraded = report
+                .degraded_features
+                .iter()
+                .any(|f| f == feature.as_str());
+            counters.push((
+                match feature {
+                    Feature::Fentry => "degraded_fentry",
+                    Feature::UprobeGolang => "degraded_uprobe_golang",
+                    Feature::UprobeTls => "degraded_uprobe_tls",
+                    Feature::OnCpuProfile => "degraded_on_cpu_profile",
+                    Feature::OffCpuProfile => "degraded_off_cpu_profile",
+                    Feature::MemoryProfile => "degraded_memory_profile",
+                },
+                CounterType::Gauged,
+                CounterValue::Unsigned(degraded as u64),
+            ));
+        }
+        counters
+    }
+
+    fn closed(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrade_unsupported_features() {
+        let mut config = Ebpf::default();
+        config.socket.tunning.fentry_enabled = true;
+        config.socket.uprobe.golang.enabled = true;
+        config.profile.on_cpu.disabled = false;
+        config.profile.memory.disabled = false;
+
+        // 5.4 without BTF
+        let cap = ebpf_capability {
+            perf_event_array: true,
+            probe_read_kernel: true,
+            kprobe: true,
+            uprobe: true,
+            tracepoint: true,
+            perf_event: true,
+            ..Default::default()
+        };
+        let degraded = degrade(&cap, &mut config.clone());
+        assert_eq!(degraded, vec![Feature::Fentry]);
+
+        // uprobe unavailable
+        let cap = ebpf_capability {
+            uprobe: false,
+            fentry: true,
+            ..cap
+        };
+        let degraded = degrade(&cap, &mut config);
+        assert_eq!(
+            degraded,
+            vec![Feature::UprobeGolang, Feature::MemoryProfile]
+        );
+        assert!(!config.socket.uprobe.golang.enabled);
+        assert!(config.profile.memory.disabled);
+        assert!(!config.profile.on_cpu.disabled);
+        assert!(config.socket.tunning.fentry_enabled);
+
+        // nothing changes once degraded
+        assert!(degrade(&cap, &mut config).is_empty());
+    }
+}
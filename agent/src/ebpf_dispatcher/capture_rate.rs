@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock, Weak,
+    },
+    time::Duration,
+};
+
+use log::{debug, info};
+use lru::LruCache;
+
+use public::counter::{Countable, Counter, CounterType, CounterValue, RefCountable};
+
+use crate::config::ProcessMatcher;
+use crate::platform::ProcessData;
+use crate::utils::stats;
+
+const BUCKET_LRU_SIZE: usize = 4096;
+// process names beyond this are counted as OTHER_PROCESSES
+const MAX_PROCESS_NAMES: usize = 256;
+const OTHER_PROCESSES: &str = "__others__";
+
+// Matchers with a max_capture_rate and the rates resolved for the pids reported by the process
+// listener, the generation is bumped on every change so that the dispatcher can pick them up.
+static MATCHERS: RwLock<Vec<ProcessMatcher>> = RwLock::new(vec![]);
+static PID_RATES: RwLock<Option<HashMap<u32, u64>>> = RwLock::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_matchers(matchers: &[ProcessMatcher]) {
+    let matchers = matchers
+        .iter()
+        .filter(|m| m.max_capture_rate > 0 && !m.ignore)
+        .map(|m| ProcessMatcher {
+            // tags are checked by the process listener already
+            only_with_tag: false,
+            ..m.clone()
+        })
+        .collect::<Vec<_>>();
+    let mut current = MATCHERS.write().unwrap();
+    if *current != matchers {
+        *current = matchers;
+        GENERATION.fetch_add(1, Ordering::Release);
+    }
+}
+
+fn resolve_rates(matchers: &[ProcessMatcher], process_datas: &[ProcessData]) -> HashMap<u32, u64> {
+    let tags = HashMap::new();
+    process_datas
+        .iter()
+        .filter_map(|pdata| {
+            // the first matching rule wins, the same as the process listener
+            matchers
+                .iter()
+                .find(|m| m.get_process_data(pdata, &tags).is_some())
+                .map(|m| (pdata.pid as u32, m.max_capture_rate))
+        })
+        .collect()
+}
+
+// ProcessListener callback of the `ebpf.socket.capture_rate` feature
+pub fn set_feature_capture_rate(pids: &Vec<u32>, process_datas: &Vec<ProcessData>) {
+    let rates = resolve_rates(&MATCHERS.read().unwrap(), process_datas);
+    debug!(
+        "ebpf capture rate overrides for {} of {} pids",
+        rates.len(),
+        pids.len()
+    );
+    *PID_RATES.write().unwrap() = Some(rates);
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+// Events are counted per second with a burst of one second
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    tokens: u64,
+    last_refill: Duration,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Duration) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: now,
+        }
+    }
+
+    fn acquire(&mut self, now: Duration) -> bool {
+        if now > self.last_refill {
+            let refill = (self.rate as u128 * (now - self.last_refill).as_nanos()
+                / Duration::from_secs(1).as_nanos()) as u64;
+            // keep the fraction for low rates by not moving last_refill
+            if refill > 0 {
+                self.tokens = (self.tokens + refill).min(self.rate);
+                self.last_refill = now;
+            }
+        }
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct ProcessDropCounter {
+    dropped: AtomicU64,
+}
+
+impl RefCountable for ProcessDropCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![(
+            "dropped",
+            CounterType::Counted,
+            CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+        )]
+    }
+}
+
+// Limits eBPF socket data per process before the global max_capture_rate
+//
+// The rate of a process is the max_capture_rate of the first process matcher matching it, or
+// process_max_capture_rate if none does. Token buckets are keyed by pid and evicted in LRU
+// order, 0 means no limitation.
+pub struct ProcessRateLimiter {
+    default_rate: u64,
+    generation: u64,
+    pid_rates: HashMap<u32, u64>,
+    buckets: LruCache<u32, TokenBucket>,
+
+    drop_counters: HashMap<String, Arc<ProcessDropCounter>>,
+    stats_collector: Arc<stats::Collector>,
+}
+
+impl ProcessRateLimiter {
+    pub fn new(default_rate: u64, stats_collector: Arc<stats::Collector>) -> Self {
+        Self {
+            default_rate,
+            generation: 0,
+            pid_rates: HashMap::new(),
+            buckets: LruCache::new(NonZeroUsize::new(BUCKET_LRU_SIZE).unwrap()),
+            drop_counters: HashMap::new(),
+            stats_collector,
+        }
+    }
+
+    pub fn set_default_rate(&mut self, rate: u64) {
+        if self.default_rate != rate {
+            info!(
+                "ebpf process max capture rate changed from {} to {}",
+                self.default_rate, rate
+            );
+            self.default_rate = rate;
+            self.buckets.clear();
+        }
+    }
+
+    // Called once per batch to pick up the pids updated by the process listener
+    pub fn check_update(&mut self) {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation == self.generation {
+            return;
+        }
+        self.generation = generation;
+        self.pid_rates = PID_RATES.read().unwrap().clone().unwrap_or_default();
+        self.buckets.clear();
+    }
+
+    fn rate(&self, pid: u32) -> u64 {
+        self.pid_rates
+            .get(&pid)
+            .copied()
+            .unwrap_or(self.default_rate)
+    }
+
+    // Returns false if the event should be dropped
+    pub fn acquire(&mut self, pid: u32, process_name: &[u8], now: Duration) -> bool {
+        let rate = self.rate(pid);
+        if rate == 0 {
+            return true;
+        }
+        match self.buckets.get_mut(&pid) {
+            Some(bucket) if bucket.acquire(now) => return true,
+            Some(_) => (),
+            None => {
+                let mut bucket = TokenBucket::new(rate, now);
+                bucket.acquire(now);
+                self.buckets.put(pid, bucket);
+                return true;
+            }
+        }
+        self.count_drop(process_name);
+        false
+    }
+
+    fn count_drop(&mut self, process_name: &[u8]) {
+        let end = process_name
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(process_name.len());
+        let name = String::from_utf8_lossy(&process_name[..end]);
+        if let Some(counter) = self.drop_counters.get(name.as_ref()) {
+            counter.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let name = if self.drop_counters.len() >= MAX_PROCESS_NAMES {
+            OTHER_PROCESSES
+        } else {
+            name.as_ref()
+        };
+        let stats_collector = &self.stats_collector;
+        let counter = self
+            .drop_counters
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                let counter = Arc::new(ProcessDropCounter::default());
+                stats_collector.register_countable(
+                    &stats::SingleTagModule("ebpf-process-capture-rate", "process_name", name),
+                    Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+                );
+                counter
+            });
+        counter.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let ms = Duration::from_millis;
+        let mut bucket = TokenBucket::new(2, ms(0));
+        assert!(bucket.acquire(ms(0)));
+        assert!(bucket.acquire(ms(100)));
+        assert!(!bucket.acquire(ms(200)));
+        // 1 token every 500ms, the fraction is kept across calls
+        assert!(!bucket.acquire(ms(400)));
+        assert!(bucket.acquire(ms(550)));
+        assert!(!bucket.acquire(ms(600)));
+        // burst is capped at one second
+        assert!(bucket.acquire(ms(10000)));
+        assert!(bucket.acquire(ms(10000)));
+        assert!(!bucket.acquire(ms(10000)));
+    }
+
+    #[test]
+    fn resolve_process_rates() {
+        let matchers = vec![
+            ProcessMatcher {
+                match_regex: Regex::new("^nginx").unwrap(),
+                only_in_container: false,
+                max_capture_rate: 100,
+                ..Default::default()
+            },
+            ProcessMatcher {
+                match_regex: Regex::new(".*").unwrap(),
+                only_in_container: false,
+                max_capture_rate: 10,
+                ..Default::default()
+            },
+        ];
+        let resolved = ProcessMatcher::resolve_features(&matchers);
+        assert!(resolved
+            .iter()
+            .all(|m| m.has_feature(ProcessMatcher::FEATURE_SOCKET_CAPTURE_RATE)));
+
+        let pdata = |pid: u64, name: &str| ProcessData {
+            name: name.to_string(),
+            pid,
+            ppid: 0,
+            process_name: name.to_string(),
+            cmd: format!("/usr/bin/{name}"),
+            cmd_with_args: vec![format!("/usr/bin/{name}")],
+            user_id: 0,
+            user: "root".to_string(),
+            start_time: Duration::ZERO,
+            os_app_tags: vec![],
+            netns_id: 0,
+            container_id: "".to_string(),
+        };
+        let rates = resolve_rates(&matchers, &[pdata(1, "nginx"), pdata(2, "java")]);
+        assert_eq!(rates, [(1, 100), (2, 10)].into());
+    }
+}
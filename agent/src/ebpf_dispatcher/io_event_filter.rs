@@ -0,0 +1,348 @@
+/*
+* Copyright (c) 2024 Yunshan Networks
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+*     http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*/
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use arc_swap::ArcSwap;
+use log::{info, warn};
+
+use crate::config::config::EbpfFileIoEvent;
+
+// (major, minor) as in /proc/<pid>/mountinfo
+pub type Device = (u32, u32);
+
+// kernel internal dev_t, see MINORBITS in include/linux/kdev_t.h
+pub fn from_kernel_dev(dev: u32) -> Device {
+    (dev >> 20, dev & 0xfffff)
+}
+
+pub fn to_kernel_dev((major, minor): Device) -> u32 {
+    (major << 20) | minor
+}
+
+// "/data" matches "/data" and "/data/db" but not "/database"
+fn has_path_prefix(path: &[u8], prefix: &[u8]) -> bool {
+    let prefix = match prefix.iter().rposition(|b| *b != b'/') {
+        Some(i) => &prefix[..=i],
+        None => return path.starts_with(b"/"),
+    };
+    path.starts_with(prefix) && (path.len() == prefix.len() || path[prefix.len()] == b'/')
+}
+
+// paths of files in other mount namespaces are prefixed with "<mntns_id>:"
+fn strip_mntns_id(path: &[u8]) -> &[u8] {
+    match path.iter().position(|b| *b == b':') {
+        Some(i) if i > 0 && path[..i].iter().all(u8::is_ascii_digit) => &path[i + 1..],
+        _ => path,
+    }
+}
+
+// mountinfo escapes space, tab, newline and backslash as \ooo
+fn unescape_mount_point(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if let Some(c) = bytes
+                .get(i + 1..i + 4)
+                .and_then(|o| std::str::from_utf8(o).ok())
+                .and_then(|o| u8::from_str_radix(o, 8).ok())
+            {
+                out.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct MountDevices {
+    include: HashSet<Device>,
+    exclude: HashSet<Device>,
+}
+
+// Filters file IO events by mount path prefixes and devices
+//
+// A mount path prefix also covers the devices of all mounts under it in the
+// host mount table, so that files opened through a container mount namespace
+// (e.g. a PVC mounted at /data in the container) are matched by the host path.
+// The mount table is checked for changes by `refresh` off the event path.
+pub struct IoEventFilter {
+    mountinfo: PathBuf,
+    include_mount_points: Vec<String>,
+    exclude_mount_points: Vec<String>,
+    include_devices: HashSet<Device>,
+    exclude_devices: HashSet<Device>,
+
+    mount_devices: ArcSwap<MountDevices>,
+    mountinfo_hash: AtomicU64,
+}
+
+impl IoEventFilter {
+    // Returns None if no filter is configured
+    pub fn new(config: &EbpfFileIoEvent) -> Option<Self> {
+        Self::with_mountinfo("/proc/1/mountinfo", config)
+    }
+
+    fn with_mountinfo<P: AsRef<Path>>(mountinfo: P, config: &EbpfFileIoEvent) -> Option<Self> {
+        if config.include_mount_points.is_empty()
+            && config.exclude_mount_points.is_empty()
+            && config.include_devices.is_empty()
+            && config.exclude_devices.is_empty()
+        {
+            return None;
+        }
+        let parse_devices = |devices: &Vec<String>| {
+            devices
+                .iter()
+                .filter_map(|d| EbpfFileIoEvent::parse_device(d))
+                .collect::<HashSet<_>>()
+        };
+        let filter = Self {
+            mountinfo: mountinfo.as_ref().to_path_buf(),
+            include_mount_points: config.include_mount_points.clone(),
+            exclude_mount_points: config.exclude_mount_points.clone(),
+            include_devices: parse_devices(&config.include_devices),
+            exclude_devices: parse_devices(&config.exclude_devices),
+            mount_devices: Default::default(),
+            mountinfo_hash: AtomicU64::new(0),
+        };
+        filter.refresh();
+        Some(filter)
+    }
+
+    fn resolve(&self, mountinfo: &str) -> MountDevices {
+        let mut devices = MountDevices::default();
+        for line in mountinfo.lines() {
+            // 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+            let mut fields = line.split_whitespace().skip(2);
+            let (Some(device), Some(mount_point)) = (fields.next(), fields.nth(1)) else {
+                continue;
+            };
+            let Some(device) = EbpfFileIoEvent::parse_device(device) else {
+                continue;
+            };
+            let mount_point = unescape_mount_point(mount_point);
+            let under = |prefixes: &Vec<String>| {
+                prefixes
+                    .iter()
+                    .any(|p| has_path_prefix(mount_point.as_bytes(), p.as_bytes()))
+            };
+            if under(&self.include_mount_points) {
+                devices.include.insert(device);
+            }
+            if under(&self.exclude_mount_points) {
+                devices.exclude.insert(device);
+            }
+        }
+        devices
+    }
+
+    // Returns true if devices of the mount path prefixes changed, blocking and
+    // called periodically by the ebpf collector thread
+    pub fn refresh(&self) -> bool {
+        if self.include_mount_points.is_empty() && self.exclude_mount_points.is_empty() {
+            return false;
+        }
+        let mountinfo = match fs::read_to_string(&self.mountinfo) {
+            Ok(m) => m,
+            Err(e) => {
+                warn!("read {} failed: {}", self.mountinfo.display(), e);
+                return false;
+            }
+        };
+        let mut hasher = DefaultHasher::new();
+        mountinfo.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.mountinfo_hash.swap(hash, Ordering::Relaxed) == hash {
+            return false;
+        }
+
+        let devices = self.resolve(&mountinfo);
+        if devices == **self.mount_devices.load() {
+            return false;
+        }
+        info!(
+            "io event filter mount devices updated, include: {:?} exclude: {:?}",
+            devices.include, devices.exclude
+        );
+        self.mount_devices.store(Arc::new(devices));
+        true
+    }
+
+    // Returns the filter mode and devices (kernel internal encoding) that can
+    // be applied in eBPF without changing the result of `is_filtered`
+    pub fn kernel_filter(&self, max_devices: usize) -> Option<(bool, Vec<u32>)> {
+        let mount_devices = self.mount_devices.load();
+        if self.include_mount_points.is_empty() && !self.include_devices.is_empty() {
+            // path prefixes can only be checked in user space
+            if self.include_devices.len() <= max_devices {
+                let mut devices: Vec<u32> = self
+                    .include_devices
+                    .iter()
+                    .map(|d| to_kernel_dev(*d))
+                    .collect();
+                devices.sort_unstable();
+                return Some((true, devices));
+            }
+            return None;
+        }
+        let mut devices: Vec<u32> = self
+            .exclude_devices
+            .union(&mount_devices.exclude)
+            .map(|d| to_kernel_dev(*d))
+            .collect();
+        if devices.is_empty() {
+            return None;
+        }
+        // the rest are dropped in user space
+        devices.sort_unstable();
+        devices.truncate(max_devices);
+        Some((false, devices))
+    }
+
+    pub fn is_filtered(&self, mount_point: &[u8], file_dir: &[u8], s_dev: Option<u32>) -> bool {
+        let path = strip_mntns_id(if file_dir.is_empty() {
+            mount_point
+        } else {
+            file_dir
+        });
+        let device = s_dev.map(from_kernel_dev);
+        let mount_devices = self.mount_devices.load();
+
+        let under =
+            |prefixes: &Vec<String>| prefixes.iter().any(|p| has_path_prefix(path, p.as_bytes()));
+        let has_device = |devices: &HashSet<Device>, mount_devices: &HashSet<Device>| {
+            device
+                .map(|d| devices.contains(&d) || mount_devices.contains(&d))
+                .unwrap_or(false)
+        };
+
+        if under(&self.exclude_mount_points)
+            || has_device(&self.exclude_devices, &mount_devices.exclude)
+        {
+            return true;
+        }
+        if self.include_mount_points.is_empty() && self.include_devices.is_empty() {
+            return false;
+        }
+        !(under(&self.include_mount_points)
+            || has_device(&self.include_devices, &mount_devices.include))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:5 - proc proc rw
+24 22 0:48 / /var/lib/docker/overlay2/abc/merged rw,relatime - overlay overlay rw
+25 22 8:16 / /var/lib/kubelet/pods/p1/volumes/pvc-1/mount rw,relatime - ext4 /dev/sdb rw
+26 22 8:32 / /mnt/my\\040disk rw,relatime - xfs /dev/sdc rw
+";
+
+    fn filter(config: EbpfFileIoEvent) -> (IoEventFilter, PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "io_event_filter_{}_{}",
+            std::process::id(),
+            config.include_mount_points.len() + 2 * config.exclude_mount_points.len()
+        ));
+        fs::write(&path, MOUNTINFO).unwrap();
+        (IoEventFilter::with_mountinfo(&path, &config).unwrap(), path)
+    }
+
+    #[test]
+    fn path_prefix() {
+        assert!(has_path_prefix(b"/data", b"/data"));
+        assert!(has_path_prefix(b"/data/db", b"/data/"));
+        assert!(!has_path_prefix(b"/database", b"/data"));
+        assert!(has_path_prefix(b"/proc/1/stat", b"/"));
+        assert_eq!(strip_mntns_id(b"4026532281:/data/a"), b"/data/a");
+        assert_eq!(strip_mntns_id(b"/data:a"), b"/data:a");
+        assert_eq!(unescape_mount_point("/mnt/my\\040disk"), "/mnt/my disk");
+        assert_eq!(from_kernel_dev(to_kernel_dev((8, 16))), (8, 16));
+    }
+
+    #[test]
+    fn io_event_filter() {
+        assert!(IoEventFilter::new(&EbpfFileIoEvent::default()).is_none());
+
+        let (f, path) = filter(EbpfFileIoEvent {
+            include_mount_points: vec!["/data".to_string(), "/var/lib/kubelet/pods".to_string()],
+            exclude_mount_points: vec!["/proc".to_string(), "/var/lib/docker".to_string()],
+            ..Default::default()
+        });
+        let pvc = Some(to_kernel_dev((8, 16)));
+        let overlay = Some(to_kernel_dev((0, 48)));
+        // (mount_point, file_dir, s_dev, filtered)
+        let events: [(&[u8], &[u8], Option<u32>, bool); 8] = [
+            (b"/", b"/data/db", None, false),
+            (b"/", b"/database", None, true),
+            (b"/proc", b"/proc/1", Some(to_kernel_dev((0, 21))), true),
+            // PVC mounted at /pvc in a container
+            (b"4026532281:/pvc", b"4026532281:/pvc/db", pvc, false),
+            (b"4026532281:/", b"4026532281:/data/tmp", overlay, true),
+            (b"/", b"/etc", Some(to_kernel_dev((8, 1))), true),
+            (b"/data", b"", None, false),
+            (b"/mnt/other", b"/mnt/other", None, true),
+        ];
+        for (mount_point, file_dir, s_dev, filtered) in events {
+            assert_eq!(
+                f.is_filtered(mount_point, file_dir, s_dev),
+                filtered,
+                "{}",
+                String::from_utf8_lossy(file_dir)
+            );
+        }
+        // include path prefixes can't be pushed into eBPF, excludes can
+        assert_eq!(
+            f.kernel_filter(32),
+            Some((false, vec![to_kernel_dev((0, 21)), to_kernel_dev((0, 48))]))
+        );
+        fs::remove_file(&path).unwrap();
+
+        let (f, path) = filter(EbpfFileIoEvent {
+            include_devices: vec!["8:16".to_string(), "8:32".to_string()],
+            exclude_devices: vec!["8:32".to_string()],
+            ..Default::default()
+        });
+        assert!(!f.is_filtered(b"/", b"/pvc/db", pvc));
+        assert!(f.is_filtered(b"/", b"/mnt/my disk", Some(to_kernel_dev((8, 32)))));
+        assert!(f.is_filtered(b"/", b"/etc", None));
+        assert_eq!(
+            f.kernel_filter(32),
+            Some((true, vec![to_kernel_dev((8, 16)), to_kernel_dev((8, 32))]))
+        );
+        assert_eq!(f.kernel_filter(1), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -70,6 +70,35 @@ struct StackKey {
     stack_id: u32,
 }
 
+// Allocation counts bucketed by size
+//
+// Bucket 0 holds sizes up to 16 bytes, bucket i holds sizes in (2^(i+3), 2^(i+4)],
+// and the last bucket also holds everything larger.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SizeHistogram(Vec<u64>);
+
+impl SizeHistogram {
+    const MIN_SHIFT: u32 = 4;
+
+    fn new(bucket_count: usize) -> Self {
+        Self(vec![0; bucket_count])
+    }
+
+    fn bucket(size: u64, bucket_count: usize) -> usize {
+        if size <= 1 << Self::MIN_SHIFT {
+            return 0;
+        }
+        // ceil(log2(size)) - MIN_SHIFT
+        let index = (u64::BITS - (size - 1).leading_zeros() - Self::MIN_SHIFT) as usize;
+        index.min(bucket_count - 1)
+    }
+
+    fn add(&mut self, size: u64) {
+        let index = Self::bucket(size, self.0.len());
+        self.0[index] += 1;
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 struct AllocInfo {
     stack_id: u32,
@@ -84,6 +113,10 @@ struct Processor {
     allocs: HashMap<StackKey, Rc<RefCell<Data>>>,
     in_use: HashMap<StackKey, Rc<RefCell<Data>>>,
 
+    // 0 if allocation size histogram is disabled
+    histogram_buckets: usize,
+    histograms: HashMap<StackKey, SizeHistogram>,
+
     latest_epoch: u64, // latest epoch of input data in nanos
 
     counter: Arc<MemoryCounter>,
@@ -105,11 +138,32 @@ impl Processor {
             },
             allocs: Default::default(),
             in_use: Default::default(),
+            histogram_buckets: Self::histogram_buckets(config),
+            histograms: Default::default(),
             latest_epoch: 0,
             counter,
         }
     }
 
+    fn histogram_buckets(config: &EbpfProfileMemory) -> usize {
+        if config.allocation_size_histogram {
+            config.histogram_bucket_count
+        } else {
+            0
+        }
+    }
+
+    fn update_histogram(&mut self, key: StackKey, size: u64) {
+        if self.histogram_buckets == 0 {
+            return;
+        }
+        let buckets = self.histogram_buckets;
+        self.histograms
+            .entry(key)
+            .or_insert_with(|| SizeHistogram::new(buckets))
+            .add(size);
+    }
+
     fn update_allocs(stacks: &mut HashMap<StackKey, Rc<RefCell<Data>>>, data: Rc<RefCell<Data>>) {
         let borrowed = data.borrow();
         let profile = borrowed.as_ref();
@@ -148,6 +202,12 @@ impl Processor {
     }
 
     pub fn update_config(&mut self, config: &EbpfProfileMemory) {
+        let histogram_buckets = Self::histogram_buckets(config);
+        if histogram_buckets != self.histogram_buckets {
+            self.histogram_buckets = histogram_buckets;
+            self.histograms.clear();
+        }
+
         let lru_len = (config.allocated_addresses_lru_len as usize)
             .clamp(ADDRESS_LRU_LEN_MIN, ADDRESS_LRU_LEN_MAX);
 
@@ -198,6 +258,14 @@ impl Processor {
                         .saturating_sub((profile.count as i64).abs() as u64);
                 }
             } else {
+                self.update_histogram(
+                    StackKey {
+                        pid: profile.pid,
+                        stack_id: profile.u_stack_id,
+                    },
+                    profile.count,
+                );
+
                 // data will reside in both allocs and in_use hashmaps
                 let rc_data = Rc::new(RefCell::new(datum));
 
@@ -379,7 +447,7 @@ impl Interior {
         let doc_timestamp = (timestamp.as_nanos() as i64 - config.report_interval.as_nanos() as i64
             + self.time_diff.load(Ordering::Relaxed)) as u64;
 
-        for (_, data) in processor.allocs.drain() {
+        for (key, data) in processor.allocs.drain() {
             let data_ref = data.borrow();
             let data_ref = data_ref.as_ref();
             let flags = data_ref.flags;
@@ -392,6 +460,11 @@ impl Interior {
             let profile = metric::Profile {
                 timestamp: doc_timestamp,
                 event_type: event_type.into(),
+                alloc_size_histogram: processor
+                    .histograms
+                    .remove(&key)
+                    .map(|h| h.0)
+                    .unwrap_or_default(),
                 ..Self::generate_profile(compress, &self.policy_getter, data.borrow().as_ref())
             };
             if let Err(e) = self.output.send(Profile(profile)) {
@@ -399,6 +472,7 @@ impl Interior {
                 self.output.clear();
             }
         }
+        processor.histograms.clear();
 
         let mut process_addrs_and_stime: HashMap<u32, (Vec<u64>, Option<u64>)> = HashMap::new();
         let mut dead_pids = HashSet::new();
@@ -736,3 +810,39 @@ impl RefCountable for MemoryCounter {
         counters
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_histogram_buckets() {
+        let mut histogram = SizeHistogram::new(16);
+        for size in [
+            1,
+            16,
+            17,
+            32,
+            33,
+            4095,
+            4096,
+            4097,
+            1 << 19,
+            1 << 20,
+            u64::MAX,
+        ] {
+            histogram.add(size);
+        }
+        let mut expected = vec![0; 16];
+        expected[0] = 2; // 1, 16
+        expected[1] = 2; // 17, 32
+        expected[2] = 1; // 33
+        expected[8] = 2; // 4095, 4096
+        expected[9] = 1; // 4097
+        expected[15] = 3; // 512K, 1M, u64::MAX
+        assert_eq!(histogram.0, expected);
+
+        assert_eq!(SizeHistogram::bucket(4096, 2), 1);
+        assert_eq!(SizeHistogram::bucket(16, 2), 0);
+    }
+}
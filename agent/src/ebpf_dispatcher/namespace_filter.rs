@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use arc_swap::ArcSwap;
+use log::{info, warn};
+
+const CONTAINER_LOG_DIRS: [&str; 2] = ["/proc/1/root/var/log/containers", "/var/log/containers"];
+const CONTAINER_ID_LEN: usize = 64;
+
+// A container log link created by kubelet, e.g.
+//   /var/log/containers/<pod>_<namespace>_<container>-<container id>.log
+#[derive(Debug, PartialEq)]
+struct ContainerLog<'a> {
+    namespace: &'a str,
+    container_id: &'a str,
+}
+
+impl<'a> ContainerLog<'a> {
+    fn parse(name: &'a str) -> Option<Self> {
+        let (prefix, container_id) = name.strip_suffix(".log")?.rsplit_once('-')?;
+        if container_id.len() != CONTAINER_ID_LEN {
+            return None;
+        }
+        // kubernetes object names can not contain '_'
+        let mut fields = prefix.splitn(3, '_');
+        let (Some(_), Some(namespace), Some(_)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return None;
+        };
+        Some(Self {
+            namespace,
+            container_id,
+        })
+    }
+}
+
+// Drops eBPF events of containers in the excluded kubernetes namespaces
+//
+// The platform data synced from the server maps containers to pod ids only, so
+// containers are resolved to namespaces by the container log links kubelet
+// maintains on each node. The links are rescanned by `refresh` off the event
+// path, and events only load the resolved container ids.
+pub struct NamespaceFilter {
+    log_dir: PathBuf,
+    namespaces: HashSet<String>,
+
+    container_ids: ArcSwap<HashSet<String>>,
+}
+
+impl NamespaceFilter {
+    // Returns None if no namespace is excluded
+    pub fn new(namespaces: &[String]) -> Option<Self> {
+        let log_dir = CONTAINER_LOG_DIRS
+            .iter()
+            .find(|d| Path::new(d).is_dir())
+            .unwrap_or(&CONTAINER_LOG_DIRS[1]);
+        Self::with_log_dir(log_dir, namespaces)
+    }
+
+    fn with_log_dir<P: AsRef<Path>>(log_dir: P, namespaces: &[String]) -> Option<Self> {
+        if namespaces.is_empty() {
+            return None;
+        }
+        let filter = Self {
+            log_dir: log_dir.as_ref().to_path_buf(),
+            namespaces: namespaces.iter().cloned().collect(),
+            container_ids: Default::default(),
+        };
+        filter.refresh();
+        Some(filter)
+    }
+
+    fn resolve<'a, I: Iterator<Item = &'a str>>(&self, names: I) -> HashSet<String> {
+        names
+            .filter_map(ContainerLog::parse)
+            .filter(|log| self.namespaces.contains(log.namespace))
+            .map(|log| log.container_id.to_string())
+            .collect()
+    }
+
+    // Blocking, called periodically by the ebpf collector thread
+    pub fn refresh(&self) {
+        let entries = match fs::read_dir(&self.log_dir) {
+            Ok(e) => e,
+            Err(e) => {
+                warn!("read {} failed: {}", self.log_dir.display(), e);
+                return;
+            }
+        };
+        let names = entries
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .collect::<Vec<_>>();
+        let container_ids = self.resolve(names.iter().map(|n| n.as_str()));
+        if container_ids == **self.container_ids.load() {
+            return;
+        }
+        info!(
+            "ebpf namespace filter updated, {} containers excluded",
+            container_ids.len()
+        );
+        self.container_ids.store(Arc::new(container_ids));
+    }
+
+    pub fn is_filtered(&self, container_id: &str) -> bool {
+        !container_id.is_empty() && self.container_ids.load().contains(container_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cid(c: char) -> String {
+        c.to_string().repeat(CONTAINER_ID_LEN)
+    }
+
+    #[test]
+    fn parse_container_log() {
+        let name = format!("web-0_prod_nginx-{}.log", cid('a'));
+        let a = cid('a');
+        assert_eq!(
+            ContainerLog::parse(&name),
+            Some(ContainerLog {
+                namespace: "prod",
+                container_id: &a,
+            })
+        );
+        assert_eq!(ContainerLog::parse("web-0_prod_nginx-abc.log"), None);
+        assert_eq!(
+            ContainerLog::parse(&format!("web-0-{}.log", cid('a'))),
+            None
+        );
+    }
+
+    #[test]
+    fn container_id_to_decision() {
+        let filter = NamespaceFilter {
+            log_dir: PathBuf::new(),
+            namespaces: ["kube-system".to_string(), "tenant-a".to_string()].into(),
+            container_ids: Default::default(),
+        };
+        let names = [
+            format!("coredns-1_kube-system_coredns-{}.log", cid('1')),
+            format!("app-1_tenant-a_app-{}.log", cid('2')),
+            format!("app-1_tenant-a_sidecar-{}.log", cid('3')),
+            format!("app-2_tenant-b_app-{}.log", cid('4')),
+        ];
+        filter
+            .container_ids
+            .store(Arc::new(filter.resolve(names.iter().map(|n| n.as_str()))));
+
+        assert!(filter.is_filtered(&cid('1')));
+        assert!(filter.is_filtered(&cid('2')));
+        assert!(filter.is_filtered(&cid('3')));
+        assert!(!filter.is_filtered(&cid('4')));
+        // processes not in containers are never filtered
+        assert!(!filter.is_filtered(""));
+    }
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+use log::debug;
+
+use crate::config::{config::EbpfProfileOffCpu, ProcessMatcher};
+use crate::platform::ProcessData;
+
+// Matchers with an off_cpu_min_blocking_time, the processes reported by the process listener
+// and the overrides resolved for them, in microseconds.
+static MATCHERS: RwLock<Vec<ProcessMatcher>> = RwLock::new(vec![]);
+static PROCESSES: RwLock<Vec<ProcessData>> = RwLock::new(vec![]);
+static PID_MIN_BLOCK: RwLock<Option<HashMap<u32, u64>>> = RwLock::new(None);
+static DEFAULT_MIN_BLOCK: AtomicU64 = AtomicU64::new(0);
+static KERNEL_MIN_BLOCK: AtomicU64 = AtomicU64::new(0);
+// set if the kernel filters with a lower value than some processes require
+static FILTER_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// The first matching rule wins, the same as `max_capture_rate`
+fn resolve_min_block(
+    matchers: &[ProcessMatcher],
+    process_datas: &[ProcessData],
+) -> HashMap<u32, u64> {
+    let tags = HashMap::new();
+    process_datas
+        .iter()
+        .filter_map(|pdata| {
+            matchers
+                .iter()
+                .find(|m| m.get_process_data(pdata, &tags).is_some())
+                .and_then(|m| m.off_cpu_min_blocking_time)
+                .map(|t| (pdata.pid as u32, t.as_micros() as u64))
+        })
+        .collect()
+}
+
+// Resolves the overrides and returns the min_blocking_time for the kernel, which is lowered
+// only for the overrides of running processes.
+fn update() -> u64 {
+    let min_block = resolve_min_block(&MATCHERS.read().unwrap(), &PROCESSES.read().unwrap());
+    let default_min_block = DEFAULT_MIN_BLOCK.load(Ordering::Relaxed);
+    let kernel_min_block = min_block
+        .values()
+        .copied()
+        .fold(default_min_block, u64::min);
+    debug!(
+        "off-cpu min_blocking_time overrides for {} processes, {}us in kernel",
+        min_block.len(),
+        kernel_min_block
+    );
+    FILTER_ENABLED.store(kernel_min_block < default_min_block, Ordering::Relaxed);
+    *PID_MIN_BLOCK.write().unwrap() = Some(min_block);
+    KERNEL_MIN_BLOCK.store(kernel_min_block, Ordering::Relaxed);
+    kernel_min_block
+}
+
+// Returns the min_blocking_time to be programmed into eBPF, overrides higher than it are
+// applied in user space by `keep()`.
+pub fn set_config(matchers: &[ProcessMatcher], off_cpu: &EbpfProfileOffCpu) -> Duration {
+    let matchers = matchers
+        .iter()
+        .filter(|m| m.off_cpu_min_blocking_time.is_some() && !m.ignore)
+        .map(|m| ProcessMatcher {
+            // tags are checked by the process listener already
+            only_with_tag: false,
+            ..m.clone()
+        })
+        .collect::<Vec<_>>();
+    DEFAULT_MIN_BLOCK.store(
+        off_cpu.min_blocking_time.as_micros() as u64,
+        Ordering::Relaxed,
+    );
+    *MATCHERS.write().unwrap() = matchers;
+    Duration::from_micros(update())
+}
+
+// Called with the processes of the `ebpf.profile.off_cpu` feature, returns the new
+// min_blocking_time to be programmed into eBPF if it is changed.
+pub fn set_processes(process_datas: &[ProcessData]) -> Option<Duration> {
+    *PROCESSES.write().unwrap() = process_datas.to_vec();
+    let last = KERNEL_MIN_BLOCK.load(Ordering::Relaxed);
+    let kernel_min_block = update();
+    (kernel_min_block != last).then(|| Duration::from_micros(kernel_min_block))
+}
+
+// Whether an off-cpu sample of `count` microseconds of blocking should be kept
+//
+// The kernel aggregates blocking events by stack before they are filtered here, so
+// a process with a higher min_blocking_time than the one programmed into eBPF is
+// filtered by the aggregated blocking time of each stack, not by single events.
+pub fn keep(pid: u32, count: u64) -> bool {
+    if !FILTER_ENABLED.load(Ordering::Relaxed) {
+        return true;
+    }
+    let min_block = PID_MIN_BLOCK
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|m| m.get(&pid).copied())
+        .unwrap_or_else(|| DEFAULT_MIN_BLOCK.load(Ordering::Relaxed));
+    count >= min_block
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn pdata(pid: u64, name: &str, container_id: &str) -> ProcessData {
+        ProcessData {
+            name: name.to_string(),
+            pid,
+            ppid: 0,
+            process_name: name.to_string(),
+            cmd: format!("/usr/bin/{name}"),
+            cmd_with_args: vec![format!("/usr/bin/{name}")],
+            user_id: 0,
+            user: "root".to_string(),
+            start_time: Duration::ZERO,
+            os_app_tags: vec![],
+            netns_id: 0,
+            container_id: container_id.to_string(),
+        }
+    }
+
+    fn matcher(regex: &str, in_container: bool, min_block: u64) -> ProcessMatcher {
+        ProcessMatcher {
+            match_regex: Regex::new(regex).unwrap(),
+            only_in_container: in_container,
+            off_cpu_min_blocking_time: Some(Duration::from_micros(min_block)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn first_matcher_wins() {
+        let matchers = vec![
+            matcher("^trade-.*", true, 10),
+            matcher("^trade-engine$", false, 1),
+            matcher(".*", false, 100),
+        ];
+        let processes = [
+            pdata(1, "nginx", ""),
+            pdata(2, "trade-engine", ""),
+            pdata(3, "trade-engine", "c0ffee"),
+        ];
+        assert_eq!(
+            resolve_min_block(&matchers, &processes),
+            [(1, 100), (2, 1), (3, 10)].into()
+        );
+    }
+
+    #[test]
+    fn user_space_filter() {
+        let off_cpu = EbpfProfileOffCpu {
+            min_blocking_time: Duration::from_micros(50),
+            ..Default::default()
+        };
+        let matchers = vec![matcher("^trade-engine$", false, 1)];
+        // the kernel is not lowered before a matched process runs
+        assert_eq!(set_config(&matchers, &off_cpu), Duration::from_micros(50));
+        assert!(keep(2, 2));
+        assert_eq!(set_processes(&[pdata(2, "nginx", "")]), None);
+
+        assert_eq!(
+            set_processes(&[pdata(1, "trade-engine", ""), pdata(2, "nginx", "")]),
+            Some(Duration::from_micros(1))
+        );
+        assert!(keep(1, 2));
+        assert!(!keep(2, 2));
+        assert!(keep(2, 50));
+        // not reported by the process listener yet
+        assert!(!keep(3, 10));
+
+        assert_eq!(set_config(&[], &off_cpu), Duration::from_micros(50));
+        assert!(keep(2, 2));
+        assert_eq!(set_config(&matchers, &off_cpu), Duration::from_micros(1));
+        assert_eq!(
+            set_processes(&[pdata(2, "nginx", "")]),
+            Some(Duration::from_micros(50))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+/*
+* Copyright (c) 2025 Yunshan Networks
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+*     http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*/
+
+use std::{
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use prost::Message;
+use zstd::bulk::Compressor;
+
+use public::{
+    counter::{Counter, CounterType, CounterValue, RefCountable},
+    proto::metric,
+    queue::DebugSender,
+};
+
+use crate::integration_collector::Profile;
+
+// Accumulates profiles and hands them to the sender queue together, so that the uniform
+// sender packs a whole batch into one message.
+//
+// Stacks are still compressed one by one since the server stores them as they are, but a
+// single zstd context is reused across the batch instead of creating one per record.
+pub struct ProfileBatcher {
+    output: DebugSender<Profile>,
+    compressor: Option<Compressor<'static>>,
+
+    interval: Duration,
+    max_bytes: usize,
+
+    batch: Vec<Profile>,
+    batch_bytes: usize,
+    batch_start: Instant,
+
+    counter: Arc<ProfileBatchCounter>,
+}
+
+impl ProfileBatcher {
+    pub fn new(
+        output: DebugSender<Profile>,
+        stack_compression: bool,
+        interval: Duration,
+        max_bytes: usize,
+        counter: Arc<ProfileBatchCounter>,
+    ) -> Self {
+        let compressor = if stack_compression {
+            match Compressor::new(0) {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    warn!("failed to create ebpf profile compressor: {:?}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Self {
+            output,
+            compressor,
+            interval,
+            max_bytes,
+            batch: vec![],
+            batch_bytes: 0,
+            batch_start: Instant::now(),
+            counter,
+        }
+    }
+
+    pub fn push(&mut self, mut profile: metric::Profile, stack: &[u8]) {
+        self.counter
+            .raw_bytes
+            .fetch_add(stack.len() as u64, Ordering::Relaxed);
+        match self.compressor.as_mut().map(|c| c.compress(stack)) {
+            Some(Ok(compressed)) => {
+                profile.data_compressed = true;
+                profile.data = compressed;
+            }
+            Some(Err(e)) => {
+                profile.data = stack.to_vec();
+                debug!("failed to compress ebpf profile: {:?}", e);
+            }
+            None => profile.data = stack.to_vec(),
+        }
+        self.counter
+            .compressed_bytes
+            .fetch_add(profile.data.len() as u64, Ordering::Relaxed);
+
+        let size = profile.encoded_len();
+        if !self.batch.is_empty() && self.batch_bytes + size > self.max_bytes {
+            self.flush();
+        }
+        if self.batch.is_empty() {
+            self.batch_start = Instant::now();
+        }
+        self.batch.push(Profile(profile));
+        self.batch_bytes += size;
+        self.counter.records.fetch_add(1, Ordering::Relaxed);
+
+        if self.batch_bytes >= self.max_bytes || self.batch_start.elapsed() >= self.interval {
+            self.flush();
+        }
+    }
+
+    // called periodically so that a batch is not held back when no more profiles come in
+    pub fn flush_expired(&mut self) {
+        if !self.batch.is_empty() && self.batch_start.elapsed() >= self.interval {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        self.batch_bytes = 0;
+        self.counter.batches.fetch_add(1, Ordering::Relaxed);
+        let mut batch = mem::take(&mut self.batch);
+        if let Err(e) = self.output.send_all(&mut batch) {
+            warn!("ebpf profile send error: {:?}", e);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ProfileBatchCounter {
+    records: AtomicU64,
+    batches: AtomicU64,
+    raw_bytes: AtomicU64,
+    compressed_bytes: AtomicU64,
+}
+
+impl RefCountable for ProfileBatchCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "records",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.records.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "batches",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.batches.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "raw_bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.raw_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "compressed_bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.compressed_bytes.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::{debug::QueueDebugger, queue::bounded_with_debug};
+
+    fn drain(r: &public::queue::Receiver<Profile>) -> Vec<Profile> {
+        let mut out = vec![];
+        while let Ok(p) = r.recv(Some(Duration::from_millis(10))) {
+            out.push(p);
+        }
+        out
+    }
+
+    fn batcher(
+        interval: Duration,
+        max_bytes: usize,
+    ) -> (ProfileBatcher, public::queue::Receiver<Profile>) {
+        let (s, r, _) = bounded_with_debug(1024, "test", &QueueDebugger::new());
+        (
+            ProfileBatcher::new(
+                s,
+                true,
+                interval,
+                max_bytes,
+                Arc::new(ProfileBatchCounter::default()),
+            ),
+            r,
+        )
+    }
+
+    #[test]
+    fn flush_by_interval() {
+        let (mut b, r) = batcher(Duration::from_secs(60), 1 << 20);
+        for _ in 0..10 {
+            b.push(metric::Profile::default(), b"main;foo;bar");
+        }
+        assert!(r.recv(Some(Duration::from_millis(10))).is_err());
+        b.flush_expired();
+        assert!(r.recv(Some(Duration::from_millis(10))).is_err());
+
+        b.interval = Duration::ZERO;
+        b.flush_expired();
+        let out = drain(&r);
+        assert_eq!(out.len(), 10);
+        assert!(out.iter().all(|p| p.0.data_compressed));
+        assert_eq!(b.counter.batches.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn flush_by_bytes() {
+        let (mut b, r) = batcher(Duration::from_secs(60), 256);
+        let stack = vec![b'a'; 100];
+        for _ in 0..10 {
+            b.push(metric::Profile::default(), &stack);
+        }
+        assert!(b.counter.batches.load(Ordering::Relaxed) > 1);
+        b.flush();
+        let out = drain(&r);
+        assert_eq!(out.len(), 10);
+        let stack_out = zstd::bulk::decompress(&out[0].0.data, stack.len()).unwrap();
+        assert_eq!(stack_out, stack);
+    }
+}
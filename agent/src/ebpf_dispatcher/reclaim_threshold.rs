@@ -0,0 +1,247 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::info;
+
+use public::counter::{Counter, CounterType, CounterValue, RefCountable};
+
+// Occupancy samples used to estimate the growth of the socket map
+const SAMPLE_WINDOW: usize = 6;
+// The socket map should not fill up within this time after the threshold is reached at the
+// current growth, giving the reclaim time to catch up.
+const HEADROOM_DURATION: Duration = Duration::from_secs(30);
+// The threshold is raised by at most 1/RAISE_STEPS of the bounds range per update
+const RAISE_STEPS: u32 = 8;
+// Changes smaller than 1/HYSTERESIS of the socket map capacity are ignored
+const HYSTERESIS: u32 = 100;
+
+// Adjusts the socket map reclaim threshold between the bounds by the occupancy slope
+//
+// A growing map lowers the threshold at once so that reclaim starts while there is still room
+// for `HEADROOM_DURATION` of growth. A stable or shrinking map raises it stepwise back to the
+// upper bound to avoid needless reclaims.
+pub struct ReclaimThresholdController {
+    capacity: u32,
+    min: u32,
+    max: u32,
+    threshold: u32,
+    slope: f64,
+    samples: VecDeque<(Duration, u32)>,
+}
+
+impl ReclaimThresholdController {
+    pub fn new(capacity: u32, threshold: u32, min: u32, max: u32) -> Self {
+        let mut c = Self {
+            capacity,
+            min: 0,
+            max: 0,
+            threshold,
+            slope: 0.0,
+            samples: VecDeque::with_capacity(SAMPLE_WINDOW),
+        };
+        c.set_bounds(min, max);
+        c
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    // Entries per second
+    pub fn slope(&self) -> f64 {
+        self.slope
+    }
+
+    pub fn set_bounds(&mut self, min: u32, max: u32) {
+        self.max = max.min(self.capacity);
+        self.min = min.min(self.max);
+        self.threshold = self.threshold.clamp(self.min, self.max);
+    }
+
+    // Least squares slope of the samples
+    fn estimate_slope(&self) -> f64 {
+        let n = self.samples.len() as f64;
+        if self.samples.len() < 2 {
+            return 0.0;
+        }
+        let t0 = self.samples[0].0;
+        let (mut sum_t, mut sum_u) = (0.0, 0.0);
+        for (t, u) in self.samples.iter() {
+            sum_t += (*t - t0).as_secs_f64();
+            sum_u += *u as f64;
+        }
+        let (mean_t, mean_u) = (sum_t / n, sum_u / n);
+        let (mut cov, mut var) = (0.0, 0.0);
+        for (t, u) in self.samples.iter() {
+            let dt = (*t - t0).as_secs_f64() - mean_t;
+            cov += dt * (*u as f64 - mean_u);
+            var += dt * dt;
+        }
+        if var == 0.0 {
+            0.0
+        } else {
+            cov / var
+        }
+    }
+
+    // Returns the new threshold if it is changed
+    pub fn update(&mut self, now: Duration, used: u32) -> Option<u32> {
+        if self.samples.len() == SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, used));
+        self.slope = self.estimate_slope();
+
+        let headroom = (self.slope.max(0.0) * HEADROOM_DURATION.as_secs_f64()) as u32;
+        let target = self
+            .capacity
+            .saturating_sub(headroom)
+            .clamp(self.min, self.max);
+        let new = if target < self.threshold {
+            target
+        } else {
+            let step = ((self.max - self.min) / RAISE_STEPS).max(1);
+            target.min(self.threshold.saturating_add(step))
+        };
+        // always allowed to settle on the bounds
+        if new == self.threshold
+            || (new.abs_diff(self.threshold) < self.capacity / HYSTERESIS
+                && new != self.min
+                && new != self.max)
+        {
+            return None;
+        }
+        info!(
+            "socket map reclaim threshold changed from {} to {}, used {} slope {:.1}/s",
+            self.threshold, new, used, self.slope
+        );
+        self.threshold = new;
+        Some(new)
+    }
+}
+
+#[derive(Default)]
+pub struct ReclaimThresholdCounter {
+    pub threshold: AtomicU32,
+    pub slope: AtomicI64,
+    pub adjustments: AtomicU64,
+}
+
+impl RefCountable for ReclaimThresholdCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "reclaim_threshold",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.threshold.load(Ordering::Relaxed) as u64),
+            ),
+            (
+                "occupancy_slope",
+                CounterType::Gauged,
+                CounterValue::Signed(self.slope.load(Ordering::Relaxed)),
+            ),
+            (
+                "adjustments",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.adjustments.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: u32 = 131072;
+    const MIN: u32 = 65536;
+    const MAX: u32 = 126976;
+
+    // Replays an occupancy curve sampled every 10 seconds, returns the threshold after each sample
+    fn replay(c: &mut ReclaimThresholdController, curve: &[u32], start: u64) -> Vec<u32> {
+        curve
+            .iter()
+            .enumerate()
+            .map(|(i, used)| {
+                c.update(Duration::from_secs(start + i as u64 * 10), *used);
+                c.threshold()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn steady_occupancy() {
+        let mut c = ReclaimThresholdController::new(CAPACITY, 120000, MIN, MAX);
+        let curve = [
+            80120, 80340, 79980, 80210, 80050, 80400, 80160, 79900, 80230, 80080,
+        ];
+        let thresholds = replay(&mut c, &curve, 0);
+        // noise is absorbed, the threshold settles on the upper bound
+        assert!(thresholds.iter().all(|t| *t >= 120000));
+        assert_eq!(c.threshold(), MAX);
+    }
+
+    #[test]
+    fn connection_burst_and_drain() {
+        let mut c = ReclaimThresholdController::new(CAPACITY, MAX, MIN, MAX);
+        // a burst of short connections adding ~2000 entries per second
+        let burst = [
+            40000, 40300, 40500, 60500, 80400, 100600, 118000, 121000, 122000,
+        ];
+        let thresholds = replay(&mut c, &burst, 0);
+        assert!(c.slope() > 1000.0);
+        // lowered as soon as the growth shows up
+        assert!(thresholds[3] < MAX);
+        assert!(thresholds.windows(2).take(6).all(|w| w[1] <= w[0]));
+        assert!(thresholds[6] < 90000);
+        assert!(thresholds.iter().all(|t| *t >= MIN));
+
+        // reclaimed back and stable
+        let drain = [
+            90000, 70000, 60000, 60100, 59900, 60000, 60050, 60000, 59950, 60000, 60100, 60000,
+            60000, 59900, 60000, 60050, 60000, 60000,
+        ];
+        let thresholds = replay(&mut c, &drain, 100);
+        // raised stepwise, never by more than a step at a time
+        let step = (MAX - MIN) / RAISE_STEPS;
+        let mut prev = c.threshold();
+        for t in thresholds.iter().rev().skip(1) {
+            assert!(prev <= *t + step);
+            prev = *t;
+        }
+        assert_eq!(c.threshold(), MAX);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut c = ReclaimThresholdController::new(CAPACITY, 120000, MIN, MAX);
+        // extreme growth is capped by the lower bound
+        let thresholds = replay(&mut c, &[0, 50000, 100000], 0);
+        assert_eq!(thresholds[2], MIN);
+
+        // bounds are capped by the capacity and clamp the current threshold
+        c.set_bounds(100000, 200000);
+        assert_eq!(c.threshold(), 100000);
+        let thresholds = replay(&mut c, &[100000; 20], 100);
+        assert_eq!(*thresholds.last().unwrap(), CAPACITY);
+    }
+}
@@ -0,0 +1,246 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use public::counter::{Counter, CounterType, CounterValue, RefCountable};
+
+use crate::config::config::RelativeSysLoad;
+
+// Frequencies to step down to while the system is overloaded, only the ones lower
+// than the configured frequency are used.
+const BACKOFF_FREQUENCIES: [u32; 2] = [49, 19];
+// Minimum time to stay on a frequency before stepping again
+pub const STEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Lowers the on-cpu sampling frequency stepwise while the relative system load is over
+// the trigger threshold of the relative_sys_load circuit breaker, and restores it
+// stepwise after the load falls below the recovery threshold.
+//
+// A step returned by `update` only takes effect after it is applied to the kernel and
+// committed, a step failed to apply is retried on the next update.
+pub struct SamplingThrottle {
+    frequencies: Vec<u32>,
+    level: usize,
+    overloaded: bool,
+    last_step: Duration,
+    pending: Option<usize>,
+    retry: bool,
+}
+
+impl SamplingThrottle {
+    pub fn new(frequency: u32) -> Self {
+        let mut frequencies = vec![frequency];
+        frequencies.extend(BACKOFF_FREQUENCIES.iter().filter(|f| **f < frequency));
+        Self {
+            frequencies,
+            level: 0,
+            overloaded: false,
+            last_step: Duration::ZERO,
+            pending: None,
+            retry: false,
+        }
+    }
+
+    pub fn frequency(&self) -> u32 {
+        self.frequencies[self.level]
+    }
+
+    pub fn overloaded(&self) -> bool {
+        self.overloaded
+    }
+
+    // Returns the frequency to step to if it is changed, see `commit`
+    pub fn update(
+        &mut self,
+        now: Duration,
+        relative_load: f32,
+        config: &RelativeSysLoad,
+    ) -> Option<u32> {
+        if config.trigger_threshold == 0.0 || config.recovery_threshold == 0.0 {
+            // circuit breaker disabled, restore at once
+            self.overloaded = false;
+            if self.level == 0 {
+                return None;
+            }
+            return self.step(0);
+        }
+
+        let mut triggered = false;
+        if !self.overloaded && relative_load >= config.trigger_threshold {
+            warn!(
+                "Relative system load {:?} {} exceeds the threshold {}, backing off on-cpu sampling.",
+                config.metric, relative_load, config.trigger_threshold
+            );
+            self.overloaded = true;
+            triggered = true;
+        } else if self.overloaded && relative_load < config.recovery_threshold {
+            info!(
+                "Relative system load {:?} {} is below the recover threshold {}, restoring on-cpu sampling.",
+                config.metric, relative_load, config.recovery_threshold
+            );
+            self.overloaded = false;
+        }
+
+        if !triggered && !self.retry && now < self.last_step + STEP_INTERVAL {
+            return None;
+        }
+        if self.overloaded && self.level + 1 < self.frequencies.len() {
+            self.step(self.level + 1)
+        } else if !self.overloaded && self.level > 0 {
+            self.step(self.level - 1)
+        } else {
+            None
+        }
+    }
+
+    fn step(&mut self, level: usize) -> Option<u32> {
+        self.pending = Some(level);
+        Some(self.frequencies[level])
+    }
+
+    // Commits the step returned by the last `update` if it is applied to the kernel
+    pub fn commit(&mut self, now: Duration, applied: bool) {
+        let Some(level) = self.pending.take() else {
+            return;
+        };
+        if !applied {
+            self.retry = true;
+            return;
+        }
+        info!(
+            "On-cpu sampling frequency changed from {}Hz to {}Hz.",
+            self.frequency(),
+            self.frequencies[level]
+        );
+        self.level = level;
+        self.last_step = now;
+        self.retry = false;
+    }
+}
+
+#[derive(Default)]
+pub struct SamplingThrottleCounter {
+    pub frequency: AtomicU32,
+    pub overloaded: AtomicBool,
+    pub transitions: AtomicU64,
+}
+
+impl RefCountable for SamplingThrottleCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "sampling_frequency",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.frequency.load(Ordering::Relaxed) as u64),
+            ),
+            (
+                "overloaded",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.overloaded.load(Ordering::Relaxed) as u64),
+            ),
+            (
+                "transitions",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.transitions.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // updates and commits the step as applied
+    fn update(
+        t: &mut SamplingThrottle,
+        now: Duration,
+        relative_load: f32,
+        config: &RelativeSysLoad,
+    ) -> Option<u32> {
+        let frequency = t.update(now, relative_load, config);
+        t.commit(now, true);
+        frequency
+    }
+
+    #[test]
+    fn backoff_and_restore() {
+        let config = RelativeSysLoad::default();
+        let mut t = SamplingThrottle::new(99);
+        let secs = Duration::from_secs;
+
+        assert_eq!(update(&mut t, secs(10), 0.5, &config), None);
+        // steps down at once when triggered, then once per interval
+        assert_eq!(update(&mut t, secs(20), 1.2, &config), Some(49));
+        assert_eq!(update(&mut t, secs(30), 1.2, &config), None);
+        assert_eq!(update(&mut t, secs(80), 1.2, &config), Some(19));
+        assert_eq!(update(&mut t, secs(200), 1.5, &config), None);
+        assert!(t.overloaded());
+
+        // between the thresholds nothing changes
+        assert_eq!(update(&mut t, secs(300), 0.95, &config), None);
+        assert!(t.overloaded());
+
+        // restores stepwise after recovery
+        assert_eq!(update(&mut t, secs(310), 0.5, &config), Some(49));
+        assert!(!t.overloaded());
+        assert_eq!(update(&mut t, secs(320), 0.5, &config), None);
+        assert_eq!(update(&mut t, secs(370), 0.5, &config), Some(99));
+        assert_eq!(update(&mut t, secs(500), 0.5, &config), None);
+    }
+
+    #[test]
+    fn low_frequency_and_disabled_breaker() {
+        let mut config = RelativeSysLoad::default();
+        let secs = Duration::from_secs;
+
+        let mut t = SamplingThrottle::new(29);
+        assert_eq!(update(&mut t, secs(10), 2.0, &config), Some(19));
+        assert_eq!(update(&mut t, secs(100), 2.0, &config), None);
+
+        config.trigger_threshold = 0.0;
+        assert_eq!(update(&mut t, secs(110), 2.0, &config), Some(29));
+        assert!(!t.overloaded());
+
+        let mut t = SamplingThrottle::new(10);
+        config.trigger_threshold = 1.0;
+        assert_eq!(update(&mut t, secs(10), 2.0, &config), None);
+        assert_eq!(t.frequency(), 10);
+    }
+
+    #[test]
+    fn retry_failed_step() {
+        let config = RelativeSysLoad::default();
+        let mut t = SamplingThrottle::new(99);
+        let secs = Duration::from_secs;
+
+        assert_eq!(t.update(secs(10), 1.2, &config), Some(49));
+        t.commit(secs(10), false);
+        assert_eq!(t.frequency(), 99);
+        // retried without waiting for STEP_INTERVAL
+        assert_eq!(t.update(secs(20), 1.2, &config), Some(49));
+        t.commit(secs(20), true);
+        assert_eq!(t.frequency(), 49);
+        assert_eq!(t.update(secs(30), 1.2, &config), None);
+        assert_eq!(update(&mut t, secs(80), 1.2, &config), Some(19));
+    }
+}
@@ -0,0 +1,214 @@
+/*
+* Copyright (c) 2024 Yunshan Networks
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+*     http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*/
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const OTHER_THREADS_TID: u32 = 0;
+pub const OTHER_THREADS_NAME: &str = "other-threads";
+
+// sweep exited processes when this many processes are cached
+const PROCESS_SWEEP_THRESHOLD: usize = 4096;
+// cached entries are trusted for this long before the thread start time is checked again
+const REVALIDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+struct ThreadInfo {
+    start_time: u64,
+    name: String,
+    validated_at: Duration,
+}
+
+// Resolves off-cpu thread names from /proc/<pid>/task/<tid>/comm
+//
+// Names are cached with the thread start time, which is checked again on a
+// lookup at most every REVALIDATE_INTERVAL, so a recycled tid gets its name
+// refreshed instead of inheriting the old one. At most `max_threads` threads
+// are tracked per process, samples from other threads are folded into the
+// "other-threads" bucket.
+pub struct ThreadNameCache {
+    proc_root: PathBuf,
+    max_threads: usize,
+    processes: HashMap<u32, HashMap<u32, ThreadInfo>>,
+}
+
+impl ThreadNameCache {
+    pub fn new(max_threads: usize) -> Self {
+        Self::with_proc_root("/proc", max_threads)
+    }
+
+    fn with_proc_root<P: AsRef<Path>>(proc_root: P, max_threads: usize) -> Self {
+        Self {
+            proc_root: proc_root.as_ref().to_path_buf(),
+            max_threads: max_threads.max(1),
+            processes: HashMap::new(),
+        }
+    }
+
+    fn task_path(&self, pid: u32, tid: u32) -> PathBuf {
+        self.proc_root
+            .join(pid.to_string())
+            .join("task")
+            .join(tid.to_string())
+    }
+
+    // field 22 of /proc/<pid>/task/<tid>/stat, comm may contain spaces and parentheses
+    fn start_time(&self, pid: u32, tid: u32) -> Option<u64> {
+        let stat = fs::read_to_string(self.task_path(pid, tid).join("stat")).ok()?;
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(19)?.parse().ok()
+    }
+
+    fn comm(&self, pid: u32, tid: u32) -> Option<String> {
+        let comm = fs::read_to_string(self.task_path(pid, tid).join("comm")).ok()?;
+        Some(comm.trim_end_matches('\n').to_owned())
+    }
+
+    fn sweep_processes(&mut self) {
+        let proc_root = &self.proc_root;
+        self.processes
+            .retain(|pid, _| proc_root.join(pid.to_string()).exists());
+    }
+
+    // Returns the tid and thread name to report, `default_name` is used if the
+    // comm file cannot be read
+    pub fn lookup(
+        &mut self,
+        pid: u32,
+        tid: u32,
+        default_name: &str,
+        now: Duration,
+    ) -> (u32, String) {
+        if let Some(info) = self.processes.get(&pid).and_then(|t| t.get(&tid)) {
+            if now < info.validated_at + REVALIDATE_INTERVAL {
+                return (tid, info.name.clone());
+            }
+        }
+
+        let Some(start_time) = self.start_time(pid, tid) else {
+            // thread exited before the sample is handled
+            if let Some(threads) = self.processes.get_mut(&pid) {
+                threads.remove(&tid);
+            }
+            return (tid, default_name.to_owned());
+        };
+
+        if !self.processes.contains_key(&pid) && self.processes.len() >= PROCESS_SWEEP_THRESHOLD {
+            self.sweep_processes();
+        }
+        let mut threads = self.processes.remove(&pid).unwrap_or_default();
+        if !threads.contains_key(&tid) && threads.len() >= self.max_threads {
+            threads.retain(|t, _| self.task_path(pid, *t).exists());
+        }
+        let full = threads.len() >= self.max_threads;
+        let result = match threads.entry(tid) {
+            Entry::Occupied(mut o) => {
+                if o.get().start_time != start_time {
+                    // tid reused by a new thread
+                    o.get_mut().name = self
+                        .comm(pid, tid)
+                        .unwrap_or_else(|| default_name.to_owned());
+                    o.get_mut().start_time = start_time;
+                }
+                o.get_mut().validated_at = now;
+                (tid, o.get().name.clone())
+            }
+            Entry::Vacant(_) if full => (OTHER_THREADS_TID, OTHER_THREADS_NAME.to_owned()),
+            Entry::Vacant(v) => {
+                let name = self
+                    .comm(pid, tid)
+                    .unwrap_or_else(|| default_name.to_owned());
+                let info = ThreadInfo {
+                    start_time,
+                    name,
+                    validated_at: now,
+                };
+                (tid, v.insert(info).name.clone())
+            }
+        };
+        self.processes.insert(pid, threads);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_task(root: &Path, pid: u32, tid: u32, comm: &str, start_time: u64) {
+        let dir = root
+            .join(pid.to_string())
+            .join("task")
+            .join(tid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(
+            dir.join("stat"),
+            format!(
+                "{} ({}) S 1 1 1 0 -1 4194560 100 0 0 0 1 1 0 0 20 0 8 0 {} 0 0",
+                tid, comm, start_time
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn thread_name_cache() {
+        let root = std::env::temp_dir().join(format!("thread_name_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        write_task(&root, 100, 100, "main", 10);
+        write_task(&root, 100, 101, "worker (1)", 11);
+        write_task(&root, 100, 102, "worker-2", 12);
+
+        let secs = Duration::from_secs;
+        let mut cache = ThreadNameCache::with_proc_root(&root, 2);
+        assert_eq!(
+            cache.lookup(100, 100, "proc", secs(1)),
+            (100, "main".to_owned())
+        );
+        assert_eq!(
+            cache.lookup(100, 101, "proc", secs(1)),
+            (101, "worker (1)".to_owned())
+        );
+        assert_eq!(
+            cache.lookup(100, 102, "proc", secs(1)),
+            (OTHER_THREADS_TID, OTHER_THREADS_NAME.to_owned())
+        );
+
+        // tid reused with a different start time, found after REVALIDATE_INTERVAL
+        write_task(&root, 100, 101, "reused", 20);
+        assert_eq!(
+            cache.lookup(100, 101, "proc", secs(2)),
+            (101, "worker (1)".to_owned())
+        );
+        assert_eq!(
+            cache.lookup(100, 101, "proc", secs(11)),
+            (101, "reused".to_owned())
+        );
+
+        // exited threads release their slots
+        fs::remove_dir_all(root.join("100/task/100")).unwrap();
+        assert_eq!(
+            cache.lookup(100, 102, "proc", secs(11)),
+            (102, "worker-2".to_owned())
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -128,9 +128,9 @@ impl stats::Module for PluginStats<'_> {
     }
 }
 
-struct AllocatorStats {
-    id: u32,
-    obj_type: &'static str,
+pub(crate) struct AllocatorStats {
+    pub id: u32,
+    pub obj_type: &'static str,
 }
 
 impl stats::Module for AllocatorStats {
@@ -891,6 +891,7 @@ impl FlowMap {
                 tcp_data.win_scale,
                 tcp_data.sack_permitted,
                 &tcp_data.sack,
+                tcp_data.timestamps,
             );
             node.packet_sequence_block
                 .as_mut()
@@ -1262,6 +1263,7 @@ impl FlowMap {
         } else {
             TcpFlags::default()
         };
+        let weight = meta_packet.sample_weight();
         let flow = Flow {
             flow_key: FlowKey {
                 agent_id: flow_config.agent_id,
@@ -1285,6 +1287,7 @@ impl FlowMap {
                     tier: tunnel.tier,
                     tunnel_type: tunnel.tunnel_type,
                     is_ipv6: tunnel.is_ipv6,
+                    tx_options: tunnel.geneve_metadata,
                     ..Default::default()
                 }
             } else {
@@ -1305,12 +1308,12 @@ impl FlowMap {
             // 统计量
             flow_metrics_peers: [
                 FlowMetricsPeer {
-                    total_packet_count: 1,
-                    packet_count: 1,
-                    total_byte_count: meta_packet.packet_len as u64,
-                    byte_count: meta_packet.packet_len as u64,
-                    l3_byte_count: meta_packet.l3_payload_len() as u64,
-                    l4_byte_count: meta_packet.l4_payload_len() as u64,
+                    total_packet_count: weight,
+                    packet_count: weight,
+                    total_byte_count: meta_packet.packet_len as u64 * weight,
+                    byte_count: meta_packet.packet_len as u64 * weight,
+                    l3_byte_count: meta_packet.l3_payload_len() as u64 * weight,
+                    l4_byte_count: meta_packet.l4_payload_len() as u64 * weight,
                     first: lookup_key.timestamp.into(),
                     last: lookup_key.timestamp.into(),
                     tcp_flags: flags,
@@ -1322,6 +1325,7 @@ impl FlowMap {
             signal_source: meta_packet.signal_source,
             is_active_service,
             init_ipid: meta_packet.ip_id as u32,
+            sampling_rate: meta_packet.sampling_rate,
             ..Default::default()
         };
         tagged_flow.flow = flow;
@@ -1609,14 +1613,18 @@ impl FlowMap {
             return;
         }
 
+        // sampled xFlow packets are scaled by their own sampling rate, which may differ
+        // between packets of the same flow
+        let weight = meta_packet.sample_weight();
+        flow.sampling_rate = flow.sampling_rate.max(meta_packet.sampling_rate);
         let flow_metrics_peer =
             &mut flow.flow_metrics_peers[meta_packet.lookup_key.direction as usize];
-        flow_metrics_peer.packet_count += 1;
-        flow_metrics_peer.total_packet_count += 1;
-        flow_metrics_peer.byte_count += meta_packet.packet_len as u64;
-        flow_metrics_peer.l3_byte_count += meta_packet.l3_payload_len() as u64;
-        flow_metrics_peer.l4_byte_count += meta_packet.l4_payload_len() as u64;
-        flow_metrics_peer.total_byte_count += meta_packet.packet_len as u64;
+        flow_metrics_peer.packet_count += weight;
+        flow_metrics_peer.total_packet_count += weight;
+        flow_metrics_peer.byte_count += meta_packet.packet_len as u64 * weight;
+        flow_metrics_peer.l3_byte_count += meta_packet.l3_payload_len() as u64 * weight;
+        flow_metrics_peer.l4_byte_count += meta_packet.l4_payload_len() as u64 * weight;
+        flow_metrics_peer.total_byte_count += meta_packet.packet_len as u64 * weight;
         flow_metrics_peer.last = pkt_timestamp.into();
         if flow_metrics_peer.first.is_zero() {
             flow_metrics_peer.first = pkt_timestamp.into();
@@ -1633,6 +1641,7 @@ impl FlowMap {
                     flow.tunnel.tx_mac0 = tunnel.mac_src;
                     flow.tunnel.tx_mac1 = tunnel.mac_dst;
                     flow.tunnel.tx_id = tunnel.id;
+                    flow.tunnel.tx_options = tunnel.geneve_metadata;
                 }
                 PacketDirection::ServerToClient => {
                     flow.tunnel.rx_ip0 = tunnel.src;
@@ -1640,6 +1649,7 @@ impl FlowMap {
                     flow.tunnel.rx_mac0 = tunnel.mac_src;
                     flow.tunnel.rx_mac1 = tunnel.mac_dst;
                     flow.tunnel.rx_id = tunnel.id;
+                    flow.tunnel.rx_options = tunnel.geneve_metadata;
                 }
             }
             flow.tunnel.tier = tunnel.tier;
@@ -1660,6 +1670,19 @@ impl FlowMap {
         }
     }
 
+    // Seconds between the capture time and the request time of a log, the metrics of responses and
+    // merged sessions are accounted to the second of their requests with it. eBPF merges request
+    // and response into sessions in some cases, which should be aligned the same way as cBPF responses.
+    fn l7_stats_time_span(msg_type: LogMessageType, rrt: u64, time_in_micros: u64) -> u64 {
+        match msg_type {
+            LogMessageType::Response | LogMessageType::Session if rrt != 0 => {
+                time_in_micros / Self::MICROS_IN_SECONDS
+                    - time_in_micros.saturating_sub(rrt) / Self::MICROS_IN_SECONDS
+            }
+            _ => 0,
+        }
+    }
+
     fn collect_l7_stats(
         &mut self,
         node: &mut FlowNode,
@@ -1680,14 +1703,16 @@ impl FlowMap {
 
         let flow = &node.tagged_flow.flow;
         let app_proto_head = l7_info.app_proto_head().unwrap();
-        let time_span = if consistent_timestamp_in_l7_metrics
-            && app_proto_head.msg_type == LogMessageType::Response
-            && app_proto_head.rrt != 0
-        {
-            time_in_micros / Self::MICROS_IN_SECONDS
-                - ((time_in_micros - app_proto_head.rrt) / Self::MICROS_IN_SECONDS)
+        let capture_second = time_in_micros / Self::MICROS_IN_SECONDS;
+        let time_in_second = if consistent_timestamp_in_l7_metrics {
+            capture_second
+                - Self::l7_stats_time_span(
+                    app_proto_head.msg_type,
+                    app_proto_head.rrt,
+                    time_in_micros,
+                )
         } else {
-            0
+            capture_second
         };
 
         let mut l7_stats = L7Stats::default();
@@ -1696,9 +1721,9 @@ impl FlowMap {
         l7_stats.endpoint = l7_info.get_endpoint();
         l7_stats.flow_id = flow.flow_id;
         l7_stats.signal_source = flow.signal_source;
-        l7_stats.time_in_second = Duration::from_secs(time_in_micros / Self::MICROS_IN_SECONDS);
+        l7_stats.time_in_second = Duration::from_secs(time_in_second);
+        l7_stats.capture_time = Duration::from_micros(time_in_micros);
         l7_stats.l7_protocol = l7_protocol;
-        l7_stats.time_span = time_span as u32;
         l7_stats.biz_type = l7_info.get_biz_type();
         l7_stats.mini_flow = MiniFlow::from(flow);
 
@@ -2070,6 +2095,7 @@ impl FlowMap {
                     flow_id: flow.flow_id,
                     signal_source: flow.signal_source,
                     time_in_second: self.start_time,
+                    capture_time: self.start_time,
                     l7_protocol: flow_perf.l7_protocol,
                     mini_flow: MiniFlow::from(flow),
                     is_reversed: false,
@@ -2090,6 +2116,7 @@ impl FlowMap {
                     flow_id: flow.flow_id,
                     signal_source: flow.signal_source,
                     time_in_second: self.start_time,
+                    capture_time: self.start_time,
                     l7_protocol: flow_perf.l7_protocol,
                     mini_flow: MiniFlow::from(flow),
                     is_reversed: true,
@@ -2614,6 +2641,10 @@ pub struct FlowMapCounter {
     slot_max_depth: AtomicU64,           // the max length of Vec<FlowNode>
     total_scan: AtomicU64,               // the total number of iteration to scan over Vec<FlowNode>
     time_set_shrinks: AtomicU64,         // the total number of time_set HashSet shrinks
+    pub(crate) http2_hpack_max_bytes: AtomicU64, // the max size of HPACK dynamic tables of one flow
+    pub(crate) http2_partial_header_decode: AtomicU64, // the number of HTTP2 header blocks decoded partially
+    pub(crate) fastcgi_request_evictions: AtomicU64, // the number of in-flight FastCGI requests evicted by the per flow limit
+    pub(crate) mongodb_unsupported_compressed: AtomicU64, // the number of MongoDB OP_COMPRESSED messages with unsupported compressors
     l7_perf_cache_counters: L7PerfCacheCounter,
 }
 
@@ -2633,6 +2664,10 @@ impl FlowMapCounter {
             slot_max_depth: AtomicU64::new(0),
             total_scan: AtomicU64::new(0),
             time_set_shrinks: AtomicU64::new(0),
+            http2_hpack_max_bytes: AtomicU64::new(0),
+            http2_partial_header_decode: AtomicU64::new(0),
+            fastcgi_request_evictions: AtomicU64::new(0),
+            mongodb_unsupported_compressed: AtomicU64::new(0),
             l7_perf_cache_counters,
         }
     }
@@ -2705,6 +2740,26 @@ impl RefCountable for FlowMapCounter {
                 CounterType::Gauged,
                 CounterValue::Unsigned(self.time_set_shrinks.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "http2_hpack_max_bytes",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.http2_hpack_max_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http2_partial_header_decode",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.http2_partial_header_decode.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "fastcgi_request_evictions",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.fastcgi_request_evictions.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "mongodb_unsupported_compressed",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.mongodb_unsupported_compressed.swap(0, Ordering::Relaxed)),
+            ),
             (
                 "l7_perf_cache_len",
                 CounterType::Gauged,
@@ -2845,7 +2900,7 @@ pub fn _new_meta_packet<'a>() -> MetaPacket<'a> {
 // 对应 flow_generator_test.go
 #[cfg(test)]
 mod tests {
-    use std::{net::IpAddr, ops::Add, time};
+    use std::{net::IpAddr, ops::Add, path::Path, time};
 
     use super::*;
 
@@ -2857,7 +2912,7 @@ mod tests {
             tap_port::TapPort,
         },
         flow_generator::protocol_logs::L7ResponseStatus,
-        utils::test_utils::Capture,
+        utils::test_utils::{load_packets, Capture, FlowMapTesterBuilder},
     };
     use npb_pcap_policy::{DirectionType, NpbAction, NpbTunnelType, PolicyData, TapSide};
     use public::utils::net::MacAddr;
@@ -3741,6 +3796,94 @@ mod tests {
         assert_eq!(tagged_flow.flow.close_type, CloseType::TcpClientRst);
     }
 
+    #[test]
+    fn l7_stats_time_span() {
+        // request sent at 10.9s and response captured at 11.2s
+        let (rrt, response_time) = (300_000, 11_200_000);
+        let time_in_second = response_time / 1_000_000;
+
+        let cbpf = FlowMap::l7_stats_time_span(LogMessageType::Response, rrt, response_time);
+        let ebpf = FlowMap::l7_stats_time_span(LogMessageType::Session, rrt, response_time);
+        assert_eq!(cbpf, 1);
+        assert_eq!(time_in_second - cbpf, time_in_second - ebpf);
+
+        assert_eq!(
+            FlowMap::l7_stats_time_span(LogMessageType::Request, rrt, response_time),
+            0
+        );
+        assert_eq!(
+            FlowMap::l7_stats_time_span(LogMessageType::Session, 0, response_time),
+            0
+        );
+        // no underflow if rrt is larger than the capture time
+        assert_eq!(
+            FlowMap::l7_stats_time_span(LogMessageType::Response, 20_000_000, response_time),
+            11
+        );
+    }
+
+    fn collect_l7_stats_from(file: &str) -> Vec<(Duration, Duration, L7PerfStats)> {
+        let mut builder = FlowMapTesterBuilder::new(AgentType::TtProcess);
+        builder.config.consistent_timestamp_in_l7_metrics = true;
+        let module_config = ModuleConfig {
+            flow: builder.config.clone(),
+            ..Default::default()
+        };
+        let mut tester = builder.build();
+        let mut packets = load_packets(Path::new("resources/test/flow_generator/http").join(file));
+        tester
+            .flow_map
+            .reset_start_time(packets[0].lookup_key.timestamp.into());
+
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        for packet in packets.iter_mut() {
+            tester.flow_map.inject_meta_packet(&config, packet);
+        }
+        let last_timestamp =
+            packets.last().unwrap().lookup_key.timestamp + Duration::from_secs(600);
+        tester
+            .flow_map
+            .inject_flush_ticker(&config, last_timestamp.into());
+
+        let output = tester.l7_stats_output.take().unwrap();
+        mem::drop(tester);
+
+        output
+            .filter(|l7_stats| l7_stats.stats != L7PerfStats::default())
+            .map(|l7_stats| {
+                (
+                    l7_stats.time_in_second,
+                    l7_stats.capture_time,
+                    l7_stats.stats.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consistent_l7_stats_time() {
+        // the same request sent at 37.9s and responded at 38.2s, captured by cBPF and eBPF
+        let cbpf = collect_l7_stats_from("consistent-timestamp.pcap");
+        let ebpf = collect_l7_stats_from("consistent-timestamp.log");
+        assert!(!cbpf.is_empty());
+        assert_eq!(cbpf, ebpf);
+
+        let request_second = cbpf[0].0;
+        assert!(cbpf
+            .iter()
+            .all(|(time_in_second, _, _)| *time_in_second == request_second));
+        assert!(cbpf
+            .iter()
+            .any(|(_, capture_time, stats)| stats.rrt_count > 0
+                && capture_time.as_secs() == request_second.as_secs() + 1));
+    }
+
     #[test]
     fn sort_nodes_by_timeout() {
         let mut nodes = vec![
@@ -25,6 +25,7 @@ pub mod perf;
 mod pool;
 pub mod protocol_logs;
 mod service_table;
+pub mod xflow;
 
 pub use error::{Error, Result};
 pub use flow_config::{FlowTimeout, TcpTimeout};
@@ -32,7 +33,9 @@ pub use flow_map::FlowMap;
 use flow_node::{FlowMapKey, FlowNode};
 pub use flow_state::FlowState;
 pub use packet_sequence::PacketSequenceParser; // Enterprise Edition Feature: packet-sequence
-pub use protocol_logs::{AppProto, AppProtoHead, DnsLog, HttpLog, MemcachedLog, MetaAppProto};
+pub use protocol_logs::{
+    AppProto, AppProtoHead, DnsLog, HttpLog, LdapLog, MemcachedLog, MetaAppProto, SmtpLog,
+};
 
 use std::time::Duration;
 
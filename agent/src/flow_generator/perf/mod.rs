@@ -60,7 +60,7 @@ use crate::{
         meta_packet::MetaPacket,
         Timestamp,
     },
-    config::{handler::LogParserConfig, FlowConfig},
+    config::{config::PortPrefilterMode, handler::LogParserConfig, FlowConfig},
 };
 
 use {icmp::IcmpPerf, tcp::TcpPerf, udp::UdpPerf};
@@ -127,6 +127,8 @@ pub struct L7ProtocolChecker {
     tcp: Vec<L7ProtocolTuple>,
     udp: Vec<L7ProtocolTuple>,
     other: Vec<L7ProtocolTuple>,
+    // in prefer mode, protocols not configured on the port are checked after the configured ones
+    prefer: bool,
 }
 
 impl From<&FlowConfig> for L7ProtocolChecker {
@@ -143,6 +145,7 @@ impl From<&FlowConfig> for L7ProtocolChecker {
                 })
                 .collect(),
         )
+        .with_prefilter_mode(config.l7_protocol_port_prefilter_mode)
     }
 }
 
@@ -171,7 +174,17 @@ impl L7ProtocolChecker {
             }
         }
 
-        L7ProtocolChecker { tcp, udp, other }
+        L7ProtocolChecker {
+            tcp,
+            udp,
+            other,
+            prefer: false,
+        }
+    }
+
+    pub fn with_prefilter_mode(mut self, mode: PortPrefilterMode) -> Self {
+        self.prefer = mode == PortPrefilterMode::Prefer;
+        self
     }
 
     pub fn possible_protocols(
@@ -179,11 +192,17 @@ impl L7ProtocolChecker {
         l4_protocol: L4Protocol,
         port: u16,
     ) -> L7ProtocolCheckerIterator<'_> {
+        let protocols = match l4_protocol {
+            L4Protocol::Tcp => &self.tcp,
+            L4Protocol::Udp => &self.udp,
+            _ => &self.other,
+        };
         L7ProtocolCheckerIterator {
-            iter: match l4_protocol {
-                L4Protocol::Tcp => self.tcp.iter(),
-                L4Protocol::Udp => self.udp.iter(),
-                _ => self.other.iter(),
+            iter: protocols.iter(),
+            fallback: if self.prefer {
+                Some(protocols.iter())
+            } else {
+                None
             },
             port,
         }
@@ -192,6 +211,8 @@ impl L7ProtocolChecker {
 
 pub struct L7ProtocolCheckerIterator<'a> {
     iter: slice::Iter<'a, L7ProtocolTuple>,
+    // protocols skipped by port in the first pass, only in prefer mode
+    fallback: Option<slice::Iter<'a, L7ProtocolTuple>>,
     port: u16,
 }
 
@@ -206,6 +227,13 @@ impl<'a> Iterator for L7ProtocolCheckerIterator<'a> {
                 _ => return Some(proto),
             }
         }
+        let fallback = self.fallback.as_mut()?;
+        while let Some((proto, bitmap)) = fallback.next() {
+            match bitmap {
+                Some(b) if !b.get(self.port as usize).unwrap_or_default() => return Some(proto),
+                _ => continue,
+            }
+        }
         None
     }
 }
@@ -330,6 +358,7 @@ impl FlowLog {
             } else {
                 false
             };
+            let upgrade_protocol = parser.upgrade_protocol();
             parser.reset();
 
             if !self.l7_protocol_inference_succeed {
@@ -345,6 +374,14 @@ impl FlowLog {
                     }
                 }
             }
+            // e.g. SMTP STARTTLS, the rest of the flow is parsed by another parser. the protocol
+            // is not cached in app table because new connections still start with the old one.
+            if let Some(proto) = upgrade_protocol {
+                if let Some(parser) = get_parser(proto.clone()) {
+                    self.l7_protocol_log_parser = Some(Box::new(parser));
+                    self.l7_protocol_enum = proto;
+                }
+            }
             return ret;
         }
 
@@ -705,3 +742,63 @@ impl FlowLog {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::utils::bitmap::parse_u16_range_list_to_bitmap;
+
+    use crate::config::config::ApplicationProtocolInference;
+
+    fn new_checker(mode: PortPrefilterMode) -> L7ProtocolChecker {
+        let enabled = L7ProtocolBitmap::from(
+            ApplicationProtocolInference::default()
+                .enabled_protocols
+                .as_slice(),
+        );
+        let ports = HashMap::from(
+            [
+                (L7Protocol::Http1, "80,8080"),
+                (L7Protocol::MySQL, "3306"),
+                (L7Protocol::Redis, "6379"),
+            ]
+            .map(|(p, r)| (p, parse_u16_range_list_to_bitmap(r, false).unwrap())),
+        );
+        L7ProtocolChecker::new(&enabled, &ports).with_prefilter_mode(mode)
+    }
+
+    #[test]
+    fn strict_port_prefilter() {
+        let checker = new_checker(PortPrefilterMode::Strict);
+        let protocols = checker
+            .possible_protocols(L4Protocol::Tcp, 3306)
+            .collect::<Vec<_>>();
+        assert!(protocols.contains(&&L7Protocol::MySQL));
+        assert!(!protocols.contains(&&L7Protocol::Http1));
+        assert!(!protocols.contains(&&L7Protocol::Redis));
+    }
+
+    #[test]
+    fn prefer_port_prefilter() {
+        let strict = new_checker(PortPrefilterMode::Strict);
+        let prefer = new_checker(PortPrefilterMode::Prefer);
+        for port in [80, 3306, 6379, 18080] {
+            let configured = strict
+                .possible_protocols(L4Protocol::Tcp, port)
+                .collect::<Vec<_>>();
+            let protocols = prefer
+                .possible_protocols(L4Protocol::Tcp, port)
+                .collect::<Vec<_>>();
+            // protocols configured on the port come first in the same order as strict mode
+            assert_eq!(&protocols[..configured.len()], &configured[..]);
+            // followed by the rest of the enabled protocols without duplicates
+            let mut all = strict.tcp.iter().map(|(p, _)| p).collect::<Vec<_>>();
+            assert_eq!(protocols.len(), all.len());
+            let mut sorted = protocols.clone();
+            sorted.sort();
+            all.sort();
+            assert_eq!(sorted, all);
+        }
+    }
+}
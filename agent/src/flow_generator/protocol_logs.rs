@@ -16,20 +16,24 @@
 
 pub mod consts;
 pub(crate) mod dns;
+pub(crate) mod elasticsearch;
 pub(crate) mod fastcgi;
 pub(crate) mod http;
+pub(crate) mod ldap;
 pub(crate) mod mq;
 mod parser;
 pub mod pb_adapter;
 pub(crate) mod ping;
 pub mod plugin;
 pub(crate) mod rpc;
+pub(crate) mod smtp;
 pub(crate) mod sql;
 
 pub use self::http::{check_http_method, parse_v1_headers, HttpInfo, HttpLog};
 use self::pb_adapter::L7ProtocolSendLog;
 
 pub use dns::{DnsInfo, DnsLog};
+pub use ldap::{LdapInfo, LdapLog};
 pub use mq::{
     AmqpInfo, AmqpLog, KafkaInfo, KafkaLog, MqttInfo, MqttLog, NatsInfo, NatsLog, OpenWireInfo,
     OpenWireLog, PulsarInfo, PulsarLog, RocketmqInfo, RocketmqLog, ZmtpInfo, ZmtpLog,
@@ -40,6 +44,7 @@ pub use rpc::{
     decode_new_rpc_trace_context_with_type, BrpcInfo, BrpcLog, DubboInfo, DubboLog, SofaRpcInfo,
     SofaRpcLog, TarsInfo, TarsLog, SOFA_NEW_RPC_TRACE_CTX_KEY,
 };
+pub use smtp::{SmtpInfo, SmtpLog};
 pub use sql::{
     MemcachedInfo, MemcachedLog, MongoDBInfo, MongoDBLog, MysqlInfo, MysqlLog, PostgreInfo,
     PostgresqlLog, RedisInfo, RedisLog,
@@ -456,7 +461,7 @@ impl AppProtoLogsBaseInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BoxAppProtoLogsData {
     pub data: Box<MetaAppProto>,
     pub override_resp_status: Option<L7ResponseStatus>,
@@ -502,6 +507,10 @@ impl Sendable for BoxAppProtoLogsData {
         kv_string.push_str(&json);
         kv_string.push('\n');
     }
+
+    fn hash_key(&self) -> Option<u64> {
+        Some(self.data.base_info.flow_id)
+    }
 }
 
 impl fmt::Display for AppProtoLogsBaseInfo {
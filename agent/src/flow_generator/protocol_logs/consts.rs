@@ -47,6 +47,11 @@ pub const HTTPV2_MAGIC_PREFIX: [u8; 10] =
 
 pub const HTTPV2_FRAME_DATA_TYPE: u8 = 0x00;
 pub const HTTPV2_FRAME_HEADERS_TYPE: u8 = 0x01;
+pub const HTTPV2_FRAME_SETTINGS_TYPE: u8 = 0x04;
+
+pub const FLAG_SETTINGS_ACK: u8 = 0x1;
+pub const HTTPV2_SETTINGS_HEADER_TABLE_SIZE: u16 = 0x1;
+pub const HPACK_DEFAULT_TABLE_SIZE: usize = 4096;
 
 pub const HTTPV2_FRAME_TYPE_MIN: u8 = 0x00;
 pub const HTTPV2_FRAME_TYPE_MAX: u8 = 0x09;
@@ -86,3 +91,7 @@ pub const SERVER_THREADPOOL_EXHAUSTED_ERROR: u8 = 100;
 
 pub const APM_TRACE_ID_ATTR: &str = "apm_trace_id";
 pub const APM_SPAN_ID_ATTR: &str = "apm_span_id";
+
+pub const HTTP_BODY_TRUNCATED_ATTR: &str = "http_body_truncated";
+pub const HTTP_BODY_ENCODING_ATTR: &str = "http_body_encoding";
+pub const HTTP2_PARTIAL_HEADER_DECODE_ATTR: &str = "http2_partial_header_decode";
@@ -20,7 +20,9 @@ use std::net::{Ipv4Addr, Ipv6Addr};
 
 use log::debug;
 use serde::{ser::SerializeStruct, Serialize, Serializer};
-use simple_dns::{rdata::RData, Packet, PacketFlag, SimpleDnsError, OPCODE, QTYPE, RCODE, TYPE};
+use simple_dns::{
+    rdata::RData, Name, Packet, PacketFlag, SimpleDnsError, OPCODE, QTYPE, RCODE, TYPE,
+};
 
 use super::{
     pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
@@ -46,6 +48,8 @@ use public::l7_protocol::{L7Protocol, LogMessageType};
 const TCP_PAYLOAD_OFFSET: usize = 2;
 const DNS_HEADER_LEN: usize = 12;
 const ANSWER_SPLIT: &str = "; ";
+// answers (including authority records) kept in the log, the rest are only checked for nxdomain
+const MAX_ANSWER_COUNT: usize = 16;
 
 impl From<SimpleDnsError> for Error {
     fn from(e: SimpleDnsError) -> Self {
@@ -73,7 +77,10 @@ pub struct DnsInfo {
     pub answers: Vec<(TYPE, String, u32)>,
 
     pub is_unconcerned: bool,
+    // extended RCODE from the OPT record is already merged by simple-dns
     pub status_code: Option<u8>,
+    // EDNS0 requestor's UDP payload size from the OPT pseudo-record
+    pub edns_udp_payload_size: Option<u16>,
 
     msg_type: LogMessageType,
 
@@ -109,6 +116,9 @@ impl Serialize for DnsInfo {
         if self.status_code.is_some() {
             field_count += 1;
         }
+        if self.edns_udp_payload_size.is_some() {
+            field_count += 1;
+        }
 
         let mut state = serializer.serialize_struct("DnsInfo", field_count)?;
         if self.trans_id != 0 {
@@ -130,6 +140,9 @@ impl Serialize for DnsInfo {
         if let Some(status_code) = self.status_code {
             state.serialize_field("response_code", &status_code)?;
         }
+        if let Some(size) = self.edns_udp_payload_size {
+            state.serialize_field("edns_udp_payload_size", &size)?;
+        }
         state.serialize_field("msg_type", &self.msg_type)?;
         state.serialize_field("captured_request_byte", &self.captured_request_byte)?;
         state.serialize_field("captured_response_byte", &self.captured_response_byte)?;
@@ -198,6 +211,9 @@ impl DnsInfo {
             }
             _ => (),
         }
+        if other.edns_udp_payload_size.is_some() {
+            self.edns_udp_payload_size = other.edns_udp_payload_size;
+        }
         self.captured_response_byte = other.captured_response_byte;
         if other.is_on_blacklist {
             self.is_on_blacklist = other.is_on_blacklist;
@@ -241,6 +257,7 @@ impl DnsInfo {
         info.query_name = question.qname.to_string();
         info.query_type = Some(question.qtype);
         info.opcode = Some(p.opcode());
+        info.edns_udp_payload_size = p.opt().map(|opt| opt.udp_packet_size);
         Ok(info)
     }
 
@@ -261,14 +278,20 @@ impl DnsInfo {
         info.query_name = question.qname.to_string();
         info.query_type = Some(question.qtype);
         info.opcode = Some(p.opcode());
+        info.edns_udp_payload_size = p.opt().map(|opt| opt.udp_packet_size);
         for rr in p.answers.iter().chain(p.name_servers.iter()) {
             let answer = match &rr.rdata {
                 RData::A(d) => Ipv4Addr::from(d.address).to_string(),
                 RData::AAAA(d) => Ipv6Addr::from(d.address).to_string(),
+                RData::CNAME(d) => d.0.to_string(),
                 RData::NS(d) => d.0.to_string(),
                 RData::SOA(d) => d.mname.to_string(),
                 RData::WKS(d) => Ipv4Addr::from(d.address).to_string(),
                 RData::PTR(d) => d.0.to_string(),
+                RData::SVCB(d) => Self::svcb_target(&rr.name, &d.target),
+                RData::HTTPS(d) => Self::svcb_target(&rr.name, &d.0.target),
+                // OPT is a pseudo-record and should not be counted as an answer
+                RData::OPT(_) => continue,
                 // TODO: DNAME
                 // simple-dns do not have dname support, perhaps this is not often used
                 _ => String::new(),
@@ -277,7 +300,9 @@ impl DnsInfo {
                 Some(trie) => info.is_unconcerned |= trie.is_unconcerned(&answer),
                 _ => (),
             }
-            info.answers.push((rr.rdata.type_code(), answer, rr.ttl));
+            if info.answers.len() < MAX_ANSWER_COUNT {
+                info.answers.push((rr.rdata.type_code(), answer, rr.ttl));
+            }
         }
         // nxdomain responses may not have any answers, need to check qname in question.
         if let Some(trie) = nxdomain_trie {
@@ -286,6 +311,16 @@ impl DnsInfo {
         Ok(info)
     }
 
+    // target "." in SVCB/HTTPS service mode means the owner name itself
+    fn svcb_target(owner: &Name, target: &Name) -> String {
+        let target = target.to_string();
+        if target.is_empty() || target == "." {
+            owner.to_string()
+        } else {
+            target
+        }
+    }
+
     // parse a UDP DNS packet
     fn parse(params: &ParseParam, payload: &[u8]) -> Result<Self> {
         let p = Packet::parse(payload)?;
@@ -369,10 +404,19 @@ impl From<DnsInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 request_id: Some(f.trans_id as u32),
-                attributes: Some(vec![KeyVal {
-                    key: "opcode".to_string(),
-                    val: format!("{:?}", f.opcode.unwrap_or_else(|| OPCODE::Reserved)),
-                }]),
+                attributes: {
+                    let mut attributes = vec![KeyVal {
+                        key: "opcode".to_string(),
+                        val: format!("{:?}", f.opcode.unwrap_or_else(|| OPCODE::Reserved)),
+                    }];
+                    if let Some(size) = f.edns_udp_payload_size {
+                        attributes.push(KeyVal {
+                            key: "edns_udp_payload_size".to_string(),
+                            val: size.to_string(),
+                        });
+                    }
+                    Some(attributes)
+                },
                 ..Default::default()
             }),
             flags,
@@ -799,6 +843,202 @@ mod tests {
         perf_stats
     }
 
+    fn encode_name(name: &str) -> Vec<u8> {
+        let mut buf = vec![];
+        for label in name.split('.').filter(|l| !l.is_empty()) {
+            buf.push(label.len() as u8);
+            buf.extend_from_slice(label.as_bytes());
+        }
+        buf.push(0);
+        buf
+    }
+
+    fn encode_rr(owner: &str, rtype: u16, ttl: u32, rdata: &[u8]) -> Vec<u8> {
+        let mut buf = encode_name(owner);
+        buf.extend_from_slice(&rtype.to_be_bytes());
+        buf.extend_from_slice(&1u16.to_be_bytes()); // IN
+        buf.extend_from_slice(&ttl.to_be_bytes());
+        buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        buf.extend_from_slice(rdata);
+        buf
+    }
+
+    fn encode_opt(udp_payload_size: u16, extended_rcode: u8) -> Vec<u8> {
+        let mut buf = vec![0]; // root
+        buf.extend_from_slice(&41u16.to_be_bytes());
+        buf.extend_from_slice(&udp_payload_size.to_be_bytes());
+        buf.extend_from_slice(&[extended_rcode, 0, 0, 0, 0, 0]);
+        buf
+    }
+
+    fn encode_message(
+        id: u16,
+        flags: u16,
+        question: (&str, u16),
+        answers: &[Vec<u8>],
+        additionals: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut buf = vec![];
+        for v in [
+            id,
+            flags,
+            1,
+            answers.len() as u16,
+            0,
+            additionals.len() as u16,
+        ] {
+            buf.extend_from_slice(&v.to_be_bytes());
+        }
+        buf.extend_from_slice(&encode_name(question.0));
+        buf.extend_from_slice(&question.1.to_be_bytes());
+        buf.extend_from_slice(&1u16.to_be_bytes());
+        for rr in answers.iter().chain(additionals.iter()) {
+            buf.extend_from_slice(rr);
+        }
+        buf
+    }
+
+    fn with_length_prefix(message: Vec<u8>) -> Vec<u8> {
+        let mut buf = (message.len() as u16).to_be_bytes().to_vec();
+        buf.extend(message);
+        buf
+    }
+
+    #[test]
+    fn edns_and_svcb() {
+        const HTTPS: u16 = 65;
+        const QUERY: u16 = 0x0100;
+        const RESPONSE: u16 = 0x8180;
+        const TRUNCATED: u16 = 0x0200;
+
+        let config = LogParserConfig {
+            unconcerned_dns_nxdomain_trie: DomainNameTrie::from(&vec![
+                "svc.cluster.local".to_string()
+            ]),
+            ..Default::default()
+        };
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let mut dns = DnsLog::default();
+
+        // truncated UDP response, client retries over TCP
+        param.l4_protocol = IpProtocol::UDP;
+        let request = encode_message(
+            0x1234,
+            QUERY,
+            ("www.example.com", HTTPS),
+            &[],
+            &[encode_opt(1232, 0)],
+        );
+        let info = dns.parse(&request, &param, false).unwrap().remove(0);
+        assert_eq!(info.msg_type, LogMessageType::Request);
+        assert_eq!(info.edns_udp_payload_size, Some(1232));
+
+        let response = encode_message(
+            0x1234,
+            RESPONSE | TRUNCATED,
+            ("www.example.com", HTTPS),
+            &[],
+            &[encode_opt(1232, 0)],
+        );
+        let info = dns.parse(&response, &param, false).unwrap().remove(0);
+        assert_eq!(info.status(), L7ResponseStatus::Ok);
+        assert!(info.answers.is_empty());
+
+        param.l4_protocol = IpProtocol::TCP;
+        let request = with_length_prefix(encode_message(
+            0x5678,
+            QUERY,
+            ("www.example.com", HTTPS),
+            &[],
+            &[encode_opt(1232, 0)],
+        ));
+        let infos = dns.parse(&request, &param, false).unwrap();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].query_type, Some(QTYPE::TYPE(TYPE::HTTPS)));
+
+        let answers = vec![
+            encode_rr(
+                "www.example.com",
+                5, // CNAME
+                300,
+                &encode_name("cdn.example.net"),
+            ),
+            encode_rr(
+                "cdn.example.net",
+                HTTPS,
+                60,
+                &[0, 1, 0], // priority 1, target "."
+            ),
+            encode_rr("cdn.example.net", 1, 60, &[10, 0, 0, 1]),
+            encode_rr("cdn.example.net", 1, 60, &[10, 0, 0, 2]),
+        ];
+        let response = with_length_prefix(encode_message(
+            0x5678,
+            RESPONSE,
+            ("www.example.com", HTTPS),
+            &answers,
+            &[encode_opt(4096, 0)],
+        ));
+        let infos = dns.parse(&response, &param, false).unwrap();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].edns_udp_payload_size, Some(4096));
+        assert_eq!(infos[0].status(), L7ResponseStatus::Ok);
+        assert_eq!(
+            infos[0].answers_to_string(),
+            "CNAME=cdn.example.net TTL=300; HTTPS=cdn.example.net TTL=60; A=10.0.0.1 TTL=60; A=10.0.0.2 TTL=60"
+        );
+
+        // extended RCODE BADVERS(16) is carried in the upper bits of OPT TTL
+        param.l4_protocol = IpProtocol::UDP;
+        let response = encode_message(
+            0x1235,
+            RESPONSE,
+            ("www.example.com", HTTPS),
+            &[],
+            &[encode_opt(1232, 1)],
+        );
+        let info = dns.parse(&response, &param, false).unwrap().remove(0);
+        assert_eq!(info.status_code, Some(16));
+        assert_eq!(info.status(), L7ResponseStatus::ServerError);
+
+        // answers beyond MAX_ANSWER_COUNT are not kept in the log
+        let answers = (0..MAX_ANSWER_COUNT + 4)
+            .map(|i| encode_rr("www.example.com", 1, 60, &[10, 0, 1, i as u8]))
+            .collect::<Vec<_>>();
+        let response = encode_message(0x1236, RESPONSE, ("www.example.com", 1), &answers, &[]);
+        let info = dns.parse(&response, &param, false).unwrap().remove(0);
+        assert_eq!(info.answers.len(), MAX_ANSWER_COUNT);
+
+        // nxdomain suffixes still apply to names found in CNAME chains
+        param.set_log_parser_config(&config);
+        let answers = vec![encode_rr(
+            "www.example.com",
+            5, // CNAME
+            300,
+            &encode_name("www.default.svc.cluster.local"),
+        )];
+        let response = encode_message(
+            0x1237,
+            RESPONSE | 3, // NXDOMAIN
+            ("www.example.com", 1),
+            &answers,
+            &[encode_opt(1232, 0)],
+        );
+        let info = dns.parse(&response, &param, false).unwrap().remove(0);
+        assert_eq!(info.status_code, Some(3));
+        assert!(info.is_unconcerned);
+        assert_eq!(info.status(), L7ResponseStatus::Ok);
+    }
+
     #[test]
     fn malformed_packet() {
         let packet = MetaPacket::empty();
@@ -0,0 +1,274 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Elasticsearch REST API is carried by HTTP, this module extracts its operation and index
+// from HTTP requests and a few fields from JSON responses.
+
+use std::borrow::Cow;
+
+use regex::Regex;
+use serde::Serialize;
+
+use super::pb_adapter::KeyVal;
+
+pub const ELASTIC_PRODUCT_HEADER: &str = "x-elastic-product";
+pub const ELASTIC_PRODUCT: &[u8] = b"Elasticsearch";
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ElasticsearchInfo {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub operation: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub index: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub took: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<bool>,
+    // failed items of a bulk request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_items: Option<u32>,
+
+    // kept for parsing the request after merging, if elasticsearch is only recognized by the response
+    #[serde(skip)]
+    pub index_suffix_regex: Option<Regex>,
+}
+
+impl PartialEq for ElasticsearchInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.operation == other.operation
+            && self.index == other.index
+            && self.took == other.took
+            && self.errors == other.errors
+            && self.failed_items == other.failed_items
+    }
+}
+
+impl ElasticsearchInfo {
+    // path: /my-index-2024.06.01/_search?q=user:kimchy
+    pub fn parse_request(method: &str, path: &str, index_suffix_regex: Option<&Regex>) -> Self {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
+        let (operation, index) = match segments.iter().position(|s| s.starts_with('_')) {
+            Some(i) => {
+                let operation = match segments[i] {
+                    "_doc" => match method {
+                        "GET" => "get",
+                        "HEAD" => "exists",
+                        "DELETE" => "delete",
+                        _ => "index",
+                    },
+                    "_create" => "create",
+                    "_update" => "update",
+                    "_source" => "get_source",
+                    api => api,
+                };
+                (operation, if i > 0 { segments[0] } else { "" })
+            }
+            None if segments.is_empty() => ("info", ""),
+            None => {
+                let operation = match method {
+                    "PUT" => "create_index",
+                    "DELETE" => "delete_index",
+                    "HEAD" => "exists_index",
+                    _ => "get_index",
+                };
+                (operation, segments[0])
+            }
+        };
+
+        Self {
+            operation: operation.to_owned(),
+            index: normalize_index(index, index_suffix_regex),
+            ..Default::default()
+        }
+    }
+
+    // The payload may be truncated by l7_log_packet_size, so fields are searched in the raw JSON
+    // instead of deserializing it. `took` and `errors` are at the beginning of the response.
+    //
+    // {"took":30,"errors":true,"items":[{"index":{"_index":"test","status":201}},
+    //  {"create":{"_index":"test","status":409,"error":{"type":"version_conflict_engine_exception"}}}]}
+    pub fn parse_response(&mut self, body: &[u8]) {
+        let Ok(body) = std::str::from_utf8(body) else {
+            return;
+        };
+        if !body.trim_start().starts_with('{') {
+            return;
+        }
+        if let Some(took) = json_value(body, "took") {
+            self.took = took
+                .split(|c: char| !c.is_ascii_digit())
+                .next()
+                .and_then(|s| s.parse().ok());
+        }
+        if let Some(errors) = json_value(body, "errors") {
+            self.errors = if errors.starts_with("true") {
+                Some(true)
+            } else if errors.starts_with("false") {
+                Some(false)
+            } else {
+                None
+            };
+            // only bulk responses have `errors`, each failed item has an `error` object
+            self.failed_items = Some(if self.errors == Some(true) {
+                body.matches("\"error\":").count() as u32
+            } else {
+                0
+            });
+        }
+    }
+
+    pub fn merge(&mut self, other: &mut Self) {
+        super::swap_if!(self, operation, is_empty, other);
+        super::swap_if!(self, index, is_empty, other);
+        super::swap_if!(self, took, is_none, other);
+        super::swap_if!(self, errors, is_none, other);
+        super::swap_if!(self, failed_items, is_none, other);
+    }
+
+    // fill operation and index from the request if they are not parsed yet
+    pub fn merge_request(&mut self, method: &str, path: &str) {
+        if !self.operation.is_empty() {
+            return;
+        }
+        let request = Self::parse_request(method, path, self.index_suffix_regex.as_ref());
+        self.operation = request.operation;
+        self.index = request.index;
+    }
+
+    pub fn endpoint(&self) -> String {
+        if self.index.is_empty() {
+            self.operation.clone()
+        } else {
+            format!("{}/{}", self.index, self.operation)
+        }
+    }
+
+    pub fn attributes(&self) -> Vec<KeyVal> {
+        let mut attributes = vec![];
+        if let Some(took) = self.took {
+            attributes.push(KeyVal {
+                key: "took".to_string(),
+                val: took.to_string(),
+            });
+        }
+        if let Some(errors) = self.errors {
+            attributes.push(KeyVal {
+                key: "errors".to_string(),
+                val: errors.to_string(),
+            });
+        }
+        if let Some(failed_items) = self.failed_items {
+            attributes.push(KeyVal {
+                key: "failed_items".to_string(),
+                val: failed_items.to_string(),
+            });
+        }
+        attributes
+    }
+}
+
+// index may be a comma separated list, e.g. "logs-2024.06.01,logs-2024.06.02" => "logs"
+fn normalize_index(index: &str, index_suffix_regex: Option<&Regex>) -> String {
+    let mut names: Vec<Cow<'_, str>> = vec![];
+    for name in index.split(',').filter(|s| !s.is_empty()) {
+        let name = match index_suffix_regex {
+            Some(r) => r.replace(name, ""),
+            None => Cow::Borrowed(name),
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.join(",")
+}
+
+// returns the text after `"key":` with leading whitespaces trimmed
+fn json_value<'a>(body: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("\"{key}\"");
+    let start = body.find(&pattern)? + pattern.len();
+    let rest = body[start..].trim_start().strip_prefix(':')?;
+    Some(rest.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::config::ElasticsearchConfig;
+
+    #[test]
+    fn check_request() {
+        let regex = Regex::new(&ElasticsearchConfig::default().index_suffix_regex).unwrap();
+        let cases = [
+            (
+                "POST",
+                "/my-index-2024.06.01/_search?size=10",
+                "_search",
+                "my-index",
+            ),
+            ("POST", "/_bulk", "_bulk", ""),
+            ("PUT", "/logs-2024-06-01/_doc/1", "index", "logs"),
+            ("GET", "/logs_20240601/_doc/1", "get", "logs"),
+            ("DELETE", "/users/_doc/1", "delete", "users"),
+            ("POST", "/users/_update/1", "update", "users"),
+            ("PUT", "/metrics-2024.06", "create_index", "metrics"),
+            ("GET", "/", "info", ""),
+            (
+                "GET",
+                "/a-2024.06.01,a-2024.06.02,b/_count",
+                "_count",
+                "a,b",
+            ),
+        ];
+        for (method, path, operation, index) in cases {
+            let info = ElasticsearchInfo::parse_request(method, path, Some(&regex));
+            assert_eq!(info.operation, operation, "{method} {path}");
+            assert_eq!(info.index, index, "{method} {path}");
+        }
+
+        let info = ElasticsearchInfo::parse_request("POST", "/logs-2024.06.01/_search", None);
+        assert_eq!(info.index, "logs-2024.06.01");
+        assert_eq!(info.endpoint(), "logs-2024.06.01/_search");
+    }
+
+    #[test]
+    fn check_response() {
+        let mut info = ElasticsearchInfo::default();
+        info.parse_response(br#"{"took": 5, "timed_out":false,"hits":{"total":{"value":1}}}"#);
+        assert_eq!(info.took, Some(5));
+        assert_eq!(info.errors, None);
+        assert_eq!(info.failed_items, None);
+
+        let mut info = ElasticsearchInfo::default();
+        info.parse_response(
+            br#"{"took":30,"errors":true,"items":[{"index":{"_index":"test","status":201}},{"create":{"_index":"test","status":409,"error":{"type":"version_conflict_engine_exception","reason":"conflict"}}},{"delete":{"_index":"test","status":404,"error":{"type":"#,
+        );
+        assert_eq!(info.took, Some(30));
+        assert_eq!(info.errors, Some(true));
+        assert_eq!(info.failed_items, Some(2));
+
+        let mut info = ElasticsearchInfo::default();
+        info.parse_response(br#"{"took":3,"errors":false,"items":[]}"#);
+        assert_eq!(info.errors, Some(false));
+        assert_eq!(info.failed_items, Some(0));
+    }
+}
@@ -14,8 +14,11 @@
  * limitations under the License.
  */
 
-use public::bytes::read_u16_be;
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
 
+use lru::LruCache;
+use public::bytes::{read_u16_be, read_u32_be};
 use public::l7_protocol::{L7Protocol, LogMessageType};
 use serde::Serialize;
 
@@ -56,6 +59,17 @@ const FCGI_GET_VALUES_RESULT: u8 = 10;
 const FCGI_UNKNOWN_TYPE: u8 = 11;
 const FCGI_MAXTYPE: u8 = FCGI_UNKNOWN_TYPE;
 
+// protocolStatus of FCGI_END_REQUEST
+const FCGI_REQUEST_COMPLETE: u8 = 0;
+const FCGI_CANT_MPX_CONN: u8 = 1;
+const FCGI_OVERLOADED: u8 = 2;
+const FCGI_UNKNOWN_ROLE: u8 = 3;
+
+// in-flight requests tracked per flow, the least recently used one is evicted beyond this
+const FCGI_MAX_INFLIGHT_REQUESTS: usize = 32;
+// PARAMS stream of one request is accumulated up to this size
+const FCGI_MAX_PARAMS_LEN: usize = 16384;
+
 #[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct FastCGIInfo {
     version: u8,
@@ -76,6 +90,8 @@ pub struct FastCGIInfo {
     pub status_code: Option<i32>,
     #[serde(rename = "response_status")]
     status: L7ResponseStatus,
+    #[serde(rename = "response_exception", skip_serializing_if = "Option::is_none")]
+    pub exception: Option<String>,
 
     #[serde(rename = "request_length", skip_serializing_if = "Option::is_none")]
     pub req_content_length: Option<u32>,
@@ -117,6 +133,7 @@ impl L7ProtocolInfoInterface for FastCGIInfo {
         if let L7ProtocolInfo::FastCGIInfo(info) = other {
             self.status = info.status;
             self.status_code = info.status_code;
+            self.exception = info.exception.take();
             self.captured_response_byte = info.captured_response_byte;
             let other_trace_ids = std::mem::take(&mut info.trace_ids);
             self.trace_ids.merge(other_trace_ids);
@@ -269,7 +286,7 @@ impl FastCGIInfo {
                 } else {
                     &mut self.x_request_id_1
                 };
-                for (i, req_id) in config.x_request_id.iter().enumerate() {
+                for (i, req_id) in config.x_request_id(direction).iter().enumerate() {
                     let prio = i as u8 + BASE_FIELD_PRIORITY;
                     if x_req_id.prio() <= prio {
                         break;
@@ -285,6 +302,95 @@ impl FastCGIInfo {
         Ok(())
     }
 
+    fn set_status(&mut self, status_code: u16) {
+        if status_code >= HTTP_STATUS_CLIENT_ERROR_MIN
+            && status_code <= HTTP_STATUS_CLIENT_ERROR_MAX
+        {
+            // http客户端请求存在错误
+            self.status = L7ResponseStatus::ClientError;
+        } else if status_code >= HTTP_STATUS_SERVER_ERROR_MIN
+            && status_code <= HTTP_STATUS_SERVER_ERROR_MAX
+        {
+            self.status = L7ResponseStatus::ServerError;
+        } else {
+            self.status = L7ResponseStatus::Ok;
+        }
+    }
+
+    // parse the CGI response headers at the beginning of the STDOUT stream,
+    // return false if there are none
+    fn fill_from_stdout(
+        &mut self,
+        stdout_payload: &[u8],
+        config: Option<&L7LogDynamicConfig>,
+    ) -> Result<bool> {
+        let mut is_hdr = false;
+        for i in parse_v1_headers(stdout_payload) {
+            let Some(col_index) = i.find(':') else {
+                break;
+            };
+
+            if col_index + 1 >= i.len() {
+                break;
+            }
+
+            is_hdr = true;
+            let key = &i[..col_index];
+            let value = &i[col_index + 1..];
+            self.on_param(
+                key.as_bytes(),
+                value.as_bytes(),
+                PacketDirection::ServerToClient,
+                config,
+            )?;
+
+            if key == "Status" {
+                if value.len() < 4 {
+                    break;
+                }
+                if let Ok(status_code) = &value[1..4].parse::<u16>() {
+                    self.status_code = Some(*status_code as i32);
+                    self.set_status(*status_code);
+                };
+                break;
+            }
+        }
+
+        if is_hdr && self.status_code.is_none() {
+            self.status_code = Some(200);
+            self.set_status(200);
+        }
+        Ok(is_hdr)
+    }
+
+    // reference https://www.mit.edu/~yandros/doc/specs/fcgi-spec.html#S5.5
+    fn on_end_request(&mut self, end_payload: &[u8]) {
+        if end_payload.len() < 5 {
+            return;
+        }
+        let app_status = read_u32_be(end_payload);
+        let exception = match end_payload[4] {
+            FCGI_REQUEST_COMPLETE if app_status == 0 => {
+                if self.status == L7ResponseStatus::Unknown {
+                    self.status = L7ResponseStatus::Ok;
+                }
+                return;
+            }
+            FCGI_REQUEST_COMPLETE => format!("app status {app_status}"),
+            FCGI_CANT_MPX_CONN => "FCGI_CANT_MPX_CONN".to_owned(),
+            FCGI_OVERLOADED => "FCGI_OVERLOADED".to_owned(),
+            FCGI_UNKNOWN_ROLE => "FCGI_UNKNOWN_ROLE".to_owned(),
+            s => format!("protocol status {s}"),
+        };
+        // keep the client error reported by http status
+        if self.status != L7ResponseStatus::ClientError {
+            self.status = L7ResponseStatus::ServerError;
+        }
+        if self.exception.is_none() {
+            self.exception = Some(exception);
+        }
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::FastCGI) {
             self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.path)
@@ -318,6 +424,7 @@ impl From<FastCGIInfo> for L7ProtocolSendLog {
             resp: L7Response {
                 status: f.status,
                 code: f.status_code,
+                exception: f.exception.unwrap_or_default(),
                 ..Default::default()
             },
             version: Some(f.version.to_string()),
@@ -382,25 +489,166 @@ impl FastCGIRecord {
     }
 }
 
+// state of one request id on the connection, requests are multiplexed by request id
 #[derive(Default)]
+struct RequestState {
+    version: u8,
+    // PARAMS stream accumulated until the empty PARAMS record
+    params: Vec<u8>,
+    // offset of the first PARAMS record in current payload
+    params_off: Option<u32>,
+    request_done: bool,
+    // only the first STDOUT record carries the response headers
+    stdout_seen: bool,
+    // response parsed from STDOUT headers, held until FCGI_END_REQUEST with the app status
+    response: Option<FastCGIInfo>,
+}
+
 pub struct FastCGILog {
     perf_stats: Vec<L7PerfStats>,
+    requests: LruCache<u16, RequestState>,
+}
+
+impl Default for FastCGILog {
+    fn default() -> Self {
+        Self {
+            perf_stats: vec![],
+            requests: LruCache::new(NonZeroUsize::new(FCGI_MAX_INFLIGHT_REQUESTS).unwrap()),
+        }
+    }
 }
 
 impl FastCGILog {
-    fn set_status(&mut self, status_code: u16, info: &mut FastCGIInfo) {
-        if status_code >= HTTP_STATUS_CLIENT_ERROR_MIN
-            && status_code <= HTTP_STATUS_CLIENT_ERROR_MAX
-        {
-            // http客户端请求存在错误
-            info.status = L7ResponseStatus::ClientError;
-        } else if status_code >= HTTP_STATUS_SERVER_ERROR_MIN
-            && status_code <= HTTP_STATUS_SERVER_ERROR_MAX
-        {
-            info.status = L7ResponseStatus::ServerError;
-        } else {
-            info.status = L7ResponseStatus::Ok;
+    fn insert_request(&mut self, request_id: u16, state: RequestState, param: &ParseParam) {
+        if let Some((id, _)) = self.requests.push(request_id, state) {
+            if id != request_id {
+                if let Some(counter) = param.stats_counter.as_ref() {
+                    counter
+                        .fastcgi_request_evictions
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn request_state(&mut self, request_id: u16, param: &ParseParam) -> &mut RequestState {
+        if !self.requests.contains(&request_id) {
+            self.insert_request(request_id, RequestState::default(), param);
+        }
+        self.requests.get_mut(&request_id).unwrap()
+    }
+
+    fn parse_request(
+        &mut self,
+        payload: &[u8],
+        param: &ParseParam,
+        config: Option<&L7LogDynamicConfig>,
+    ) -> Vec<FastCGIInfo> {
+        let mut infos = vec![];
+        for (record, record_payload, off) in RecordIter::new(payload) {
+            match record.record_type {
+                FCGI_BEGIN_REQUEST => {
+                    let state = RequestState {
+                        version: record.version,
+                        ..Default::default()
+                    };
+                    self.insert_request(record.request_id, state, param);
+                }
+                FCGI_ABORT_REQUEST => {
+                    self.requests.pop(&record.request_id);
+                }
+                FCGI_PARAMS => {
+                    let state = self.request_state(record.request_id, param);
+                    if state.request_done {
+                        continue;
+                    }
+                    state.version = record.version;
+                    if record.content_len > 0 {
+                        if state.params_off.is_none() {
+                            state.params_off = Some(off as u32);
+                        }
+                        let remain = FCGI_MAX_PARAMS_LEN.saturating_sub(state.params.len());
+                        state
+                            .params
+                            .extend_from_slice(&record_payload[..record_payload.len().min(remain)]);
+                        // the rest of a truncated record can not be followed in next payload
+                        if record_payload.len() == record.content_len as usize {
+                            continue;
+                        }
+                    }
+
+                    state.request_done = true;
+                    let mut info = FastCGIInfo {
+                        version: state.version,
+                        request_id: record.request_id as u32,
+                        msg_type: LogMessageType::Request,
+                        seq_off: state.params_off.unwrap_or_default(),
+                        ..Default::default()
+                    };
+                    let params = std::mem::take(&mut state.params);
+                    if info
+                        .fill_from_param(&params, PacketDirection::ClientToServer, config)
+                        .is_ok()
+                        && !info.method.is_empty()
+                    {
+                        infos.push(info);
+                    }
+                }
+                _ => {}
+            }
         }
+        for (_, state) in self.requests.iter_mut() {
+            state.params_off = None;
+        }
+        infos
+    }
+
+    fn parse_response(
+        &mut self,
+        payload: &[u8],
+        param: &ParseParam,
+        config: Option<&L7LogDynamicConfig>,
+    ) -> Vec<FastCGIInfo> {
+        let mut infos: Vec<FastCGIInfo> = vec![];
+        for (record, record_payload, off) in RecordIter::new(payload) {
+            match record.record_type {
+                FCGI_STDOUT if record.content_len > 0 => {
+                    let state = self.request_state(record.request_id, param);
+                    if state.stdout_seen {
+                        continue;
+                    }
+                    state.stdout_seen = true;
+                    let mut info = FastCGIInfo {
+                        version: record.version,
+                        request_id: record.request_id as u32,
+                        msg_type: LogMessageType::Response,
+                        seq_off: off as u32,
+                        ..Default::default()
+                    };
+                    if let Ok(true) = info.fill_from_stdout(record_payload, config) {
+                        state.response = Some(info);
+                    }
+                }
+                FCGI_END_REQUEST => {
+                    let state = self.requests.pop(&record.request_id).unwrap_or_default();
+                    // no response headers, e.g. rejected by FCGI_OVERLOADED
+                    let mut info = state.response.unwrap_or_else(|| FastCGIInfo {
+                        version: record.version,
+                        request_id: record.request_id as u32,
+                        msg_type: LogMessageType::Response,
+                        ..Default::default()
+                    });
+                    // the response may be held from previous payloads
+                    info.seq_off = off as u32;
+                    info.on_end_request(record_payload);
+                    if info.status != L7ResponseStatus::Unknown {
+                        infos.push(info);
+                    }
+                }
+                _ => {}
+            }
+        }
+        infos
     }
 }
 
@@ -475,106 +723,54 @@ impl L7ProtocolParserInterface for FastCGILog {
 
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
         let config = param.parse_config.and_then(|c| Some(&c.l7_log_dynamic));
-        let mut info = FastCGIInfo::default();
 
         self.perf_stats.clear();
-        match param.direction {
-            PacketDirection::ClientToServer => {
-                info.msg_type = LogMessageType::Request;
-                for (record, record_payload, off) in RecordIter::new(payload) {
-                    if record.record_type == FCGI_PARAMS {
-                        info.request_id = record.request_id as u32;
-                        info.version = record.version;
-                        if record.content_len > 0 {
-                            info.fill_from_param(record_payload, param.direction, config)?;
-                            info.seq_off = off as u32;
-                            break;
-                        }
-                    }
-                }
-
-                if info.method.is_empty() {
-                    return Err(Error::L7ProtocolUnknown);
-                }
+        let mut infos = match param.direction {
+            PacketDirection::ClientToServer => self.parse_request(payload, param, config),
+            PacketDirection::ServerToClient => self.parse_response(payload, param, config),
+        };
+        if infos.is_empty() {
+            // PARAMS stream or response continues in next payload
+            if self
+                .requests
+                .iter()
+                .any(|(_, s)| !s.params.is_empty() || s.response.is_some())
+            {
+                return Ok(L7ParseResult::None);
             }
-            PacketDirection::ServerToClient => {
-                info.msg_type = LogMessageType::Response;
-
-                'l: for (record, record_payload, off) in RecordIter::new(payload) {
-                    info.seq_off = off as u32;
-                    if record.record_type == FCGI_STDOUT {
-                        info.request_id = record.request_id as u32;
-                        info.version = record.version;
-                        let mut is_hdr = false;
-
-                        for i in parse_v1_headers(record_payload) {
-                            let Some(col_index) = i.find(':') else {
-                                break;
-                            };
-
-                            if col_index + 1 >= i.len() {
-                                break;
-                            }
-
-                            is_hdr = true;
-                            let key = &i[..col_index];
-                            let value = &i[col_index + 1..];
-                            info.on_param(
-                                key.as_bytes(),
-                                value.as_bytes(),
-                                PacketDirection::ServerToClient,
-                                config,
-                            )?;
-
-                            if key == "Status" {
-                                if value.len() < 4 {
-                                    break 'l;
-                                }
-                                if let Ok(status_code) = &value[1..4].parse::<u16>() {
-                                    info.status_code = Some(*status_code as i32);
-                                    self.set_status(*status_code, &mut info);
-                                };
-                                break 'l;
-                            }
-                        }
+            return Err(Error::L7ProtocolUnknown);
+        }
 
-                        if !is_hdr {
-                            continue;
-                        }
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            set_captured_byte!(info, param);
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
 
-                        if info.status_code.is_none() {
-                            info.status_code = Some(200);
-                            self.set_status(200, &mut info);
-                        }
-                        break 'l;
+            if param.parse_perf {
+                let mut perf_stat = L7PerfStats::default();
+                if info.msg_type == LogMessageType::Response {
+                    if let Some(endpoint) = info.load_endpoint_from_cache(param, false) {
+                        info.endpoint = Some(endpoint.to_string());
                     }
                 }
-
-                if info.status_code.is_none() {
-                    return Err(Error::L7ProtocolUnknown);
+                if let Some(stats) = info.perf_stats(param) {
+                    info.rrt = stats.rrt_sum;
+                    perf_stat.sequential_merge(&stats);
                 }
+                self.perf_stats.push(perf_stat);
             }
         }
-        info.is_tls = param.is_tls();
-        set_captured_byte!(info, param);
-        if let Some(config) = param.parse_config {
-            info.set_is_on_blacklist(config);
-        }
 
-        if param.parse_perf {
-            let mut perf_stat = L7PerfStats::default();
-            if info.msg_type == LogMessageType::Response {
-                if let Some(endpoint) = info.load_endpoint_from_cache(param, false) {
-                    info.endpoint = Some(endpoint.to_string());
-                }
-            }
-            if let Some(stats) = info.perf_stats(param) {
-                info.rrt = stats.rrt_sum;
-                perf_stat.sequential_merge(&stats);
-            }
-            self.perf_stats.push(perf_stat);
+        if infos.len() == 1 {
+            return Ok(L7ParseResult::Single(L7ProtocolInfo::FastCGIInfo(
+                infos.pop().unwrap(),
+            )));
         }
-        Ok(L7ParseResult::Single(L7ProtocolInfo::FastCGIInfo(info)))
+        Ok(L7ParseResult::Multi(
+            infos.into_iter().map(L7ProtocolInfo::FastCGIInfo).collect(),
+        ))
     }
 
     fn protocol(&self) -> L7Protocol {
@@ -668,12 +864,17 @@ mod test {
 
     use crate::common::flow::{L7PerfStats, PacketDirection};
     use crate::common::l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface};
-    use crate::common::l7_protocol_log::{L7PerfCache, L7ProtocolParserInterface, ParseParam};
+    use crate::common::l7_protocol_log::{
+        L7ParseResult, L7PerfCache, L7ProtocolParserInterface, ParseParam,
+    };
+    use crate::common::{enums::IpProtocol, MetaPacket};
     use crate::flow_generator::protocol_logs::fastcgi::FastCGILog;
     use crate::flow_generator::protocol_logs::L7ResponseStatus;
     use crate::{flow_generator::L7_RRT_CACHE_CAPACITY, utils::test_utils::Capture};
 
-    use super::FastCGIInfo;
+    use super::{
+        FastCGIInfo, FCGI_BEGIN_REQUEST, FCGI_END_REQUEST, FCGI_PARAMS, FCGI_STDIN, FCGI_STDOUT,
+    };
 
     const FILE_DIR: &str = "resources/test/flow_generator/fastcgi";
 
@@ -717,6 +918,143 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_fastcgi_multiplexed() {
+        let pcap_file = Path::new(FILE_DIR).join("fastcgi-multiplexed.pcap");
+        let capture = Capture::load_pcap(pcap_file);
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut p = capture.collect::<Vec<_>>();
+        p[0].lookup_key.direction = PacketDirection::ClientToServer;
+        p[1].lookup_key.direction = PacketDirection::ServerToClient;
+
+        let mut parser = FastCGILog::default();
+        let mut logs = vec![];
+        for packet in p.iter() {
+            let param = &mut ParseParam::new(
+                packet,
+                Some(log_cache.clone()),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            let payload = packet.get_l4_payload().unwrap();
+            param.set_captured_byte(payload.len());
+            match parser.parse_payload(payload, param).unwrap() {
+                L7ParseResult::Multi(infos) => logs.push(infos),
+                _ => unreachable!(),
+            }
+            assert_eq!(parser.perf_stats().len(), 2);
+        }
+
+        let (mut reqs, mut resps) = (logs.remove(0), logs.remove(0));
+        // responses are emitted in the order of FCGI_END_REQUEST, the headers of request 1 come first
+        assert_eq!(resps[0].session_id(), Some(2));
+        for req in reqs.iter_mut() {
+            let resp = resps
+                .iter_mut()
+                .find(|r| r.session_id() == req.session_id())
+                .unwrap();
+            req.merge_log(resp).unwrap();
+        }
+        let infos = reqs
+            .into_iter()
+            .map(|i| match i {
+                L7ProtocolInfo::FastCGIInfo(info) => info,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(infos[0].request_id, 2);
+        assert_eq!(infos[0].path.as_str(), "/orders/2");
+        assert_eq!(infos[0].status, L7ResponseStatus::ServerError);
+        assert_eq!(infos[0].status_code, None);
+        assert_eq!(infos[0].exception.as_deref(), Some("FCGI_OVERLOADED"));
+
+        assert_eq!(infos[1].request_id, 1);
+        assert_eq!(infos[1].path.as_str(), "/users/1");
+        assert_eq!(infos[1].status, L7ResponseStatus::Ok);
+        assert_eq!(infos[1].status_code, Some(200));
+        assert_eq!(infos[1].exception, None);
+    }
+
+    fn record(record_type: u8, request_id: u16, content: &[u8]) -> Vec<u8> {
+        let mut r = vec![1, record_type];
+        r.extend_from_slice(&request_id.to_be_bytes());
+        r.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        r.extend_from_slice(&[0, 0]);
+        r.extend_from_slice(content);
+        r
+    }
+
+    #[test]
+    fn test_fastcgi_end_request_in_next_payload() {
+        let packet = MetaPacket::empty();
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut param = ParseParam::new(
+            &packet,
+            Some(log_cache.clone()),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        let mut parser = FastCGILog::default();
+
+        let mut params = vec![];
+        for (k, v) in [
+            ("REQUEST_METHOD", "POST"),
+            ("SCRIPT_NAME", "/index.php"),
+            ("REQUEST_URI", "/orders"),
+        ] {
+            params.extend_from_slice(&[k.len() as u8, v.len() as u8]);
+            params.extend_from_slice(k.as_bytes());
+            params.extend_from_slice(v.as_bytes());
+        }
+        let request = [
+            record(FCGI_BEGIN_REQUEST, 1, &[0, 1, 0, 0, 0, 0, 0, 0]),
+            record(FCGI_PARAMS, 1, &params),
+            record(FCGI_PARAMS, 1, &[]),
+            record(FCGI_STDIN, 1, &[]),
+        ]
+        .concat();
+        param.direction = PacketDirection::ClientToServer;
+        let mut req = parser
+            .parse_payload(&request, &param)
+            .unwrap()
+            .unwrap_single();
+
+        // response headers and FCGI_END_REQUEST in separate payloads
+        param.direction = PacketDirection::ServerToClient;
+        let stdout = [
+            record(
+                FCGI_STDOUT,
+                1,
+                b"Status: 200 OK\r\nContent-type: text/html\r\n\r\nok",
+            ),
+            record(FCGI_STDOUT, 1, &[]),
+        ]
+        .concat();
+        assert!(matches!(
+            parser.parse_payload(&stdout, &param),
+            Ok(L7ParseResult::None)
+        ));
+        let end = record(FCGI_END_REQUEST, 1, &[0, 0, 0, 255, 0, 0, 0, 0]);
+        let mut resp = parser.parse_payload(&end, &param).unwrap().unwrap_single();
+
+        req.merge_log(&mut resp).unwrap();
+        let L7ProtocolInfo::FastCGIInfo(info) = req else {
+            unreachable!();
+        };
+        assert_eq!(info.path.as_str(), "/orders");
+        assert_eq!(info.status_code, Some(200));
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+        assert_eq!(info.exception.as_deref(), Some("app status 255"));
+    }
+
     fn check_and_parse(file_name: &str) -> (FastCGIInfo, L7PerfStats) {
         let pcap_file = Path::new(FILE_DIR).join(file_name);
         let capture = Capture::load_pcap(pcap_file);
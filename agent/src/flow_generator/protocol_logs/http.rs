@@ -17,12 +17,12 @@
 use std::{
     borrow::{Borrow, Cow},
     cell::OnceCell,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     mem, str,
-    sync::Arc,
+    sync::{atomic::Ordering, Arc},
 };
 
-use hpack::Decoder;
+use hpack::{decoder::DecoderError, Decoder};
 use nom::{AsBytes, ParseTo};
 use serde::Serialize;
 
@@ -33,6 +33,7 @@ use public_derive::L7Log;
 
 use super::{
     consts::*,
+    elasticsearch::{ElasticsearchInfo, ELASTIC_PRODUCT, ELASTIC_PRODUCT_HEADER},
     pb_adapter::{
         ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, MetricKeyVal, TraceInfo,
     },
@@ -52,13 +53,16 @@ use crate::{
         },
         meta_packet::ApplicationFlags,
     },
-    config::handler::{L7LogDynamicConfig, LogParserConfig},
+    config::{
+        config::HttpQueryObfuscation,
+        handler::{L7LogDynamicConfig, LogParserConfig},
+    },
     flow_generator::error::{Error, Result},
     flow_generator::protocol_logs::{
         set_captured_byte, L7ProtoRawDataType, BASE_FIELD_PRIORITY, PLUGIN_FIELD_PRIORITY,
     },
     plugin::CustomInfo,
-    utils::bytes::read_u32_be,
+    utils::bytes::{read_u16_be, read_u32_be},
 };
 
 cfg_if::cfg_if! {
@@ -335,6 +339,12 @@ pub struct HttpInfo {
         skip_serializing_if = "Vec::is_empty"
     )]
     attributes: Vec<KeyVal>,
+    // custom fields with `multi` enabled, reported as attributes
+    #[serde(skip)]
+    multi_value_attributes: Vec<MultiValueAttribute>,
+    // HPACK dynamic table entries referenced by the headers are unknown
+    #[serde(skip)]
+    partial_header_decode: bool,
 
     #[serde(skip)]
     metrics: Vec<MetricKeyVal>,
@@ -352,6 +362,45 @@ pub struct HttpInfo {
 
     #[serde(skip)]
     dubbo_service_version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elasticsearch: Option<Box<ElasticsearchInfo>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct MultiValueAttribute {
+    key: String,
+    val: String,
+    separator: String,
+    max_bytes: usize,
+}
+
+impl MultiValueAttribute {
+    // values are kept in order, those exceeding `max_bytes` are dropped
+    fn push(&mut self, val: &str) {
+        let separator_len = if self.val.is_empty() {
+            0
+        } else {
+            self.separator.len()
+        };
+        if self.val.len() + separator_len + val.len() > self.max_bytes {
+            return;
+        }
+        if separator_len > 0 {
+            self.val.push_str(&self.separator);
+        }
+        self.val.push_str(val);
+    }
+
+    fn merge_into(self, attributes: &mut Vec<MultiValueAttribute>) {
+        match attributes
+            .iter_mut()
+            .find(|a| a.key == self.key && a.separator == self.separator)
+        {
+            Some(a) => a.push(&self.val),
+            None => attributes.push(self),
+        }
+    }
 }
 
 impl L7LogAttribute for HttpInfo {
@@ -623,6 +672,22 @@ impl HttpInfo {
         if other.proto == L7Protocol::Triple {
             self.proto = L7Protocol::Triple;
         }
+        if other.proto == L7Protocol::Elasticsearch {
+            self.proto = L7Protocol::Elasticsearch;
+        }
+        match (self.elasticsearch.as_mut(), other.elasticsearch.as_mut()) {
+            (Some(es), Some(other_es)) => es.merge(other_es),
+            (None, Some(_)) => self.elasticsearch = other.elasticsearch.take(),
+            _ => (),
+        }
+        // elasticsearch recognized by the response header only,
+        // operation and index are parsed from the request side method and path
+        if let Some(es) = self.elasticsearch.as_mut() {
+            if es.operation.is_empty() && !self.method.is_none() {
+                es.merge_request(self.method.as_str(), &self.path);
+                self.endpoint = Some(es.endpoint());
+            }
+        }
         if other.is_reversed {
             self.is_reversed = other.is_reversed;
         }
@@ -641,6 +706,11 @@ impl HttpInfo {
         super::swap_if!(self, x_request_id_0, is_default, other);
         super::swap_if!(self, x_request_id_1, is_default, other);
         self.attributes.append(&mut other.attributes);
+        // repeated headers may be in different HEADERS frames of one stream
+        for a in other.multi_value_attributes.drain(..) {
+            a.merge_into(&mut self.multi_value_attributes);
+        }
+        self.partial_header_decode |= other.partial_header_decode;
         self.metrics.append(&mut other.metrics);
         Ok(())
     }
@@ -684,20 +754,44 @@ impl HttpInfo {
             return false;
         };
 
-        if self.is_grpc() {
-            if let Some(name) = self.service_name.as_ref() {
-                if blacklist.request_resource.is_on_blacklist(name) {
+        if let Some(es) = self.elasticsearch.as_ref() {
+            // request_type and request_resource are the operation and index of elasticsearch
+            if blacklist.request_resource.is_on_blacklist(&es.index)
+                || blacklist.request_type.is_on_blacklist(&es.operation)
+            {
+                return true;
+            }
+            if let Some(took) = es.took {
+                if blacklist.attribute_is_on_blacklist("took", &took.to_string()) {
+                    return true;
+                }
+            }
+            if let Some(errors) = es.errors {
+                if blacklist.attribute_is_on_blacklist("errors", &errors.to_string()) {
+                    return true;
+                }
+            }
+            if let Some(failed_items) = es.failed_items {
+                if blacklist.attribute_is_on_blacklist("failed_items", &failed_items.to_string()) {
                     return true;
                 }
             }
         } else {
-            if blacklist.request_resource.is_on_blacklist(&self.path) {
-                return true;
+            if self.is_grpc() {
+                if let Some(name) = self.service_name.as_ref() {
+                    if blacklist.request_resource.is_on_blacklist(name) {
+                        return true;
+                    }
+                }
+            } else {
+                if blacklist.request_resource.is_on_blacklist(&self.path) {
+                    return true;
+                }
             }
-        }
 
-        if blacklist.request_type.is_on_blacklist(self.method.as_str()) {
-            return true;
+            if blacklist.request_type.is_on_blacklist(self.method.as_str()) {
+                return true;
+            }
         }
 
         if blacklist.request_domain.is_on_blacklist(&self.host) {
@@ -811,6 +905,14 @@ impl From<HttpInfo> for L7ProtocolSendLog {
                 f.host,
                 f.path,
             )
+        } else if let Some(es) = f.elasticsearch.as_mut() {
+            f.attributes.append(&mut es.attributes());
+            (
+                mem::take(&mut es.operation),
+                mem::take(&mut es.index),
+                f.host,
+                f.endpoint.unwrap_or_default(),
+            )
         } else {
             (
                 f.method.as_str().to_owned(),
@@ -830,6 +932,18 @@ impl From<HttpInfo> for L7ProtocolSendLog {
             flags = flags | ApplicationFlags::REVERSED;
         }
 
+        f.attributes
+            .extend(f.multi_value_attributes.into_iter().map(|a| KeyVal {
+                key: a.key,
+                val: a.val,
+            }));
+        if f.partial_header_decode {
+            f.attributes.push(KeyVal {
+                key: HTTP2_PARTIAL_HEADER_DECODE_ATTR.to_string(),
+                val: "true".to_string(),
+            });
+        }
+
         if f.status != L7ResponseStatus::Ok {
             if let Some(request_header) = f.request_header {
                 f.attributes.push(KeyVal {
@@ -963,8 +1077,8 @@ impl From<&HttpInfo> for LogCache {
 pub struct HttpLog {
     proto: L7Protocol,
     perf_stats: Vec<L7PerfStats>,
-    http2_req_decoder: Option<Decoder<'static>>,
-    http2_resp_decoder: Option<Decoder<'static>>,
+    http2_req_decoder: Option<Http2HeaderDecoder>,
+    http2_resp_decoder: Option<Http2HeaderDecoder>,
 
     #[cfg(feature = "enterprise")]
     custom_field_store: Store,
@@ -985,9 +1099,6 @@ impl L7ProtocolParserInterface for HttpLog {
                 let Some(config) = param.parse_config else {
                     return None;
                 };
-                if self.http2_req_decoder.is_none() {
-                    self.set_header_decoder(config.l7_log_dynamic.expected_headers_set.clone());
-                }
                 match param.ebpf_type {
                     #[cfg(feature = "libtrace")]
                     EbpfType::GoHttp2Uprobe
@@ -1013,17 +1124,22 @@ impl L7ProtocolParserInterface for HttpLog {
                         }
                     }
                     _ => {
-                        if self
-                            .check_http_v2(
-                                payload,
-                                param,
-                                &mut info,
-                                #[cfg(feature = "enterprise")]
-                                None,
-                            )
-                            .is_ok()
-                            && info.msg_type != LogMessageType::Other
-                        {
+                        // the payload is parsed again after checking, decode it with a separate
+                        // HPACK state so that entries are not inserted into the dynamic table twice
+                        let decoders = (
+                            self.http2_req_decoder.take(),
+                            self.http2_resp_decoder.take(),
+                        );
+                        self.set_header_decoder(config);
+                        let result = self.check_http_v2(
+                            payload,
+                            param,
+                            &mut info,
+                            #[cfg(feature = "enterprise")]
+                            None,
+                        );
+                        (self.http2_req_decoder, self.http2_resp_decoder) = decoders;
+                        if result.is_ok() && info.msg_type != LogMessageType::Other {
                             Some(LogMessageType::Request)
                         } else {
                             None
@@ -1067,7 +1183,7 @@ impl L7ProtocolParserInterface for HttpLog {
                     param,
                     config,
                     payload,
-                    Some(l7_payload),
+                    Some(&l7_payload),
                     &mut info,
                     #[cfg(feature = "enterprise")]
                     custom_policies,
@@ -1085,7 +1201,7 @@ impl L7ProtocolParserInterface for HttpLog {
                 let mut last_error = Err(Error::HttpHeaderParseFailed);
 
                 if self.http2_req_decoder.is_none() {
-                    self.set_header_decoder(config.l7_log_dynamic.expected_headers_set.clone());
+                    self.set_header_decoder(config);
                 }
 
                 loop {
@@ -1125,6 +1241,13 @@ impl L7ProtocolParserInterface for HttpLog {
                         }
                         Ok(n) => n,
                     };
+                    if info.partial_header_decode {
+                        if let Some(counter) = param.stats_counter.as_ref() {
+                            counter
+                                .http2_partial_header_decode
+                                .fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
                     self.set_info_by_config(
                         param,
                         config,
@@ -1148,6 +1271,12 @@ impl L7ProtocolParserInterface for HttpLog {
                     offset += n;
                 }
 
+                if let Some(counter) = param.stats_counter.as_ref() {
+                    counter
+                        .http2_hpack_max_bytes
+                        .fetch_max(self.hpack_table_size() as u64, Ordering::Relaxed);
+                }
+
                 if param.parse_log {
                     if !infos.is_empty() {
                         Ok(L7ParseResult::Multi(infos))
@@ -1241,9 +1370,7 @@ impl HttpLog {
                 .obfuscate_enabled_protocols
                 .is_enabled(L7Protocol::Http2)
         {
-            if let Some(index) = info.path.find('?') {
-                info.path.truncate(index + 1); // retain `?`
-            }
+            obfuscate_query(&mut info.path, config.obfuscate_http_query);
         }
         info.service_name = info.grpc_package_service_name();
         if !config.http_endpoint_disabled && info.path.len() > 0 {
@@ -1254,6 +1381,7 @@ impl HttpLog {
             };
             info.endpoint = Some(handle_endpoint(config, path));
         }
+        Self::set_elasticsearch_info(param, config, l7_payload, info);
 
         let l7_dynamic_config = &config.l7_log_dynamic;
         if param.direction == PacketDirection::ServerToClient {
@@ -1269,8 +1397,10 @@ impl HttpLog {
             if let Some(l7_payload) = l7_payload {
                 if info.status != L7ResponseStatus::Ok {
                     if l7_dynamic_config.error_response_header > 0 {
-                        let error_response_header = (payload.len() - l7_payload.len())
-                            .min(l7_dynamic_config.error_response_header);
+                        // body may be decoded from chunks, the header length is calculated from payload
+                        let header_len = payload.len() - V1Structure::new(payload).body.len();
+                        let error_response_header =
+                            header_len.min(l7_dynamic_config.error_response_header);
                         if error_response_header > 0 {
                             info.response_header = Some(payload[..error_response_header].to_vec());
                         }
@@ -1289,8 +1419,9 @@ impl HttpLog {
         } else {
             if let Some(l7_payload) = l7_payload {
                 if l7_dynamic_config.error_request_header > 0 {
-                    let error_request_header = (payload.len() - l7_payload.len())
-                        .min(l7_dynamic_config.error_request_header);
+                    let header_len = payload.len() - V1Structure::new(payload).body.len();
+                    let error_request_header =
+                        header_len.min(l7_dynamic_config.error_request_header);
                     if error_request_header > 0 {
                         info.request_header = Some(payload[..error_request_header].to_vec());
                     }
@@ -1339,11 +1470,98 @@ impl HttpLog {
         }
     }
 
-    fn set_header_decoder(&mut self, expected_headers_set: Arc<HashSet<Vec<u8>>>) {
-        self.http2_req_decoder = Some(Decoder::new_with_expected_headers(
+    // Elasticsearch is recognized by server port, or by the product header in response
+    fn set_elasticsearch_info(
+        param: &ParseParam,
+        config: &LogParserConfig,
+        l7_payload: Option<&[u8]>,
+        info: &mut HttpInfo,
+    ) {
+        let server_port = match param.direction {
+            PacketDirection::ClientToServer => param.port_dst,
+            PacketDirection::ServerToClient => param.port_src,
+        };
+        match info.proto {
+            L7Protocol::Elasticsearch => (),
+            L7Protocol::Http1 | L7Protocol::Http2
+                if config
+                    .elasticsearch
+                    .ports
+                    .get(server_port as usize)
+                    .unwrap_or_default() =>
+            {
+                info.proto = L7Protocol::Elasticsearch
+            }
+            _ => return,
+        }
+
+        let mut es = if info.msg_type == LogMessageType::Request {
+            let es = ElasticsearchInfo::parse_request(
+                info.method.as_str(),
+                &info.path,
+                config.elasticsearch.index_suffix_regex.as_ref(),
+            );
+            // the original endpoint contains unnormalized index
+            info.endpoint = Some(es.endpoint());
+            es
+        } else {
+            ElasticsearchInfo::default()
+        };
+        es.index_suffix_regex = config.elasticsearch.index_suffix_regex.clone();
+        if info.msg_type == LogMessageType::Response {
+            if let Some(body) = l7_payload {
+                es.parse_response(body);
+            }
+        }
+        info.elasticsearch = Some(Box::new(es));
+    }
+
+    fn set_header_decoder(&mut self, config: &LogParserConfig) {
+        let expected_headers_set = &config.l7_log_dynamic.expected_headers_set;
+        self.http2_req_decoder = Some(Http2HeaderDecoder::new(
+            expected_headers_set.clone(),
+            config.http2_hpack_max_table_size,
+        ));
+        self.http2_resp_decoder = Some(Http2HeaderDecoder::new(
             expected_headers_set.clone(),
+            config.http2_hpack_max_table_size,
         ));
-        self.http2_resp_decoder = Some(Decoder::new_with_expected_headers(expected_headers_set));
+    }
+
+    // memory used by the HPACK dynamic tables of the connection
+    fn hpack_table_size(&self) -> usize {
+        [&self.http2_req_decoder, &self.http2_resp_decoder]
+            .into_iter()
+            .filter_map(|d| d.as_ref().map(|d| d.table_size))
+            .sum()
+    }
+
+    fn reset_header_decoder(&mut self, direction: PacketDirection) {
+        let decoder = if direction == PacketDirection::ClientToServer {
+            self.http2_req_decoder.as_mut()
+        } else {
+            self.http2_resp_decoder.as_mut()
+        };
+        if let Some(decoder) = decoder {
+            decoder.reset();
+        }
+    }
+
+    // SETTINGS_HEADER_TABLE_SIZE limits the table used by the encoder of the peer
+    fn on_settings(&mut self, payload: &[u8], direction: PacketDirection) {
+        let decoder = if direction == PacketDirection::ClientToServer {
+            self.http2_resp_decoder.as_mut()
+        } else {
+            self.http2_req_decoder.as_mut()
+        };
+        let Some(decoder) = decoder else {
+            return;
+        };
+        for setting in payload.chunks_exact(6) {
+            if read_u16_be(setting) == HTTPV2_SETTINGS_HEADER_TABLE_SIZE {
+                decoder.set_table_size_limit(read_u32_be(&setting[2..]) as usize);
+            }
+        }
     }
 
     fn http1_check_protocol(&mut self, payload: &[u8]) -> Option<LogMessageType> {
@@ -1531,7 +1749,7 @@ impl HttpLog {
         param: &ParseParam,
         info: &mut HttpInfo,
         #[cfg(feature = "enterprise")] custom_policies: Option<PolicySlice>,
-    ) -> Result<&'a [u8]> {
+    ) -> Result<Cow<'a, [u8]>> {
         let (direction, config) = (
             param.direction,
             &param.parse_config.as_ref().unwrap().l7_log_dynamic,
@@ -1586,45 +1804,69 @@ impl HttpLog {
         }
 
         let mut content_length: Option<u32> = None;
+        let mut chunked = false;
+        let mut content_encoding = None;
         for body_line in headers.by_ref() {
-            let col_index = body_line.find(':');
-            if col_index.is_none() {
-                continue;
-            }
-            let col_index = col_index.unwrap();
-            if col_index + 1 >= body_line.len() {
-                continue;
-            }
-
-            let key = &body_line[..col_index];
-            let value = &body_line[col_index + 1..];
-
-            let lower_key = key.to_ascii_lowercase();
-            let trim_value = value.trim();
-            self.on_header(
+            let Some((lower_key, value)) = self.on_v1_header_line(
+                body_line,
                 config,
-                lower_key.as_bytes(),
-                trim_value.as_bytes(),
                 direction,
                 info,
-            )?;
+                #[cfg(feature = "enterprise")]
+                custom_policies,
+            )?
+            else {
+                continue;
+            };
 
-            if &lower_key == "content-length" {
-                content_length = Some(value.trim_start().parse::<u32>().unwrap_or_default());
+            match lower_key.as_str() {
+                "content-length" => {
+                    content_length = Some(value.trim_start().parse::<u32>().unwrap_or_default());
+                }
+                "transfer-encoding" => {
+                    chunked = value.to_ascii_lowercase().trim_end().ends_with("chunked");
+                }
+                "content-encoding" if !value.trim().eq_ignore_ascii_case("identity") => {
+                    content_encoding = Some(value.trim());
+                }
+                _ => (),
             }
+        }
 
-            #[cfg(feature = "enterprise")]
-            if let Some(policies) = custom_policies {
-                policies.apply(
-                    &mut self.custom_field_store,
+        let raw_body = V1Structure::new(payload).body;
+        let mut l7_payload = Cow::Borrowed(raw_body);
+        if chunked {
+            let limit = match param.buf_size {
+                0 => usize::MAX,
+                size => size as usize,
+            };
+            let body = ChunkedBody::decode(raw_body, limit);
+            // trace ids and x-request-id may be sent as trailers
+            for trailer in parse_v1_headers(body.trailers) {
+                self.on_v1_header_line(
+                    trailer,
+                    config,
+                    direction,
                     info,
-                    direction.into(),
-                    Source::Header(key, trim_value),
-                );
+                    #[cfg(feature = "enterprise")]
+                    custom_policies,
+                )?;
+            }
+            if body.truncated {
+                info.attributes.push(KeyVal {
+                    key: HTTP_BODY_TRUNCATED_ATTR.to_string(),
+                    val: "true".to_string(),
+                });
             }
+            l7_payload = Cow::Owned(body.data);
+        }
+        // compressed body is not supported
+        if let Some(encoding) = content_encoding {
+            info.attributes.push(KeyVal {
+                key: HTTP_BODY_ENCODING_ATTR.to_string(),
+                val: encoding.to_string(),
+            });
         }
-
-        let l7_payload = V1Structure::new(payload).body;
 
         set_captured_byte!(info, param);
         // 当解析完所有Header仍未找到Content-Length，则认为该字段值为0
@@ -1635,18 +1877,59 @@ impl HttpLog {
         }
 
         #[cfg(feature = "enterprise")]
-        if let Some(policies) = custom_policies {
+        if let Some(policies) = custom_policies.filter(|_| content_encoding.is_none()) {
             policies.apply(
                 &mut self.custom_field_store,
                 info,
                 direction.into(),
-                Source::Payload(PayloadType::JSON | PayloadType::XML, l7_payload),
+                Source::Payload(PayloadType::JSON | PayloadType::XML, &l7_payload),
             );
         }
 
         Ok(l7_payload)
     }
 
+    // returns the lowercase key and the value of a valid header line
+    fn on_v1_header_line<'b>(
+        &mut self,
+        line: &'b str,
+        config: &L7LogDynamicConfig,
+        direction: PacketDirection,
+        info: &mut HttpInfo,
+        #[cfg(feature = "enterprise")] custom_policies: Option<PolicySlice>,
+    ) -> Result<Option<(String, &'b str)>> {
+        let Some(col_index) = line.find(':') else {
+            return Ok(None);
+        };
+        if col_index + 1 >= line.len() {
+            return Ok(None);
+        }
+
+        let key = &line[..col_index];
+        let value = &line[col_index + 1..];
+
+        let lower_key = key.to_ascii_lowercase();
+        let trim_value = value.trim();
+        self.on_header(
+            config,
+            lower_key.as_bytes(),
+            trim_value.as_bytes(),
+            direction,
+            info,
+        )?;
+
+        #[cfg(feature = "enterprise")]
+        if let Some(policies) = custom_policies {
+            policies.apply(
+                &mut self.custom_field_store,
+                info,
+                direction.into(),
+                Source::Header(key, trim_value),
+            );
+        }
+        Ok(Some((lower_key, value)))
+    }
+
     fn has_magic(payload: &[u8]) -> bool {
         if payload.len() < HTTPV2_MAGIC_LENGTH {
             return false;
@@ -1768,6 +2051,13 @@ impl HttpLog {
             offset += httpv2_header.frame_length as usize + HTTPV2_FRAME_HEADER_LENGTH;
 
             if httpv2_header.stream_id == 0 {
+                if httpv2_header.frame_type == HTTPV2_FRAME_SETTINGS_TYPE
+                    && httpv2_header.flags & FLAG_SETTINGS_ACK == 0
+                {
+                    let end = (httpv2_header.frame_length as usize + HTTPV2_FRAME_HEADER_LENGTH)
+                        .min(frame_payload.len());
+                    self.on_settings(&frame_payload[HTTPV2_FRAME_HEADER_LENGTH..end], direction);
+                }
                 // Headers和Data帧的StreamId不为0
                 // 参考协议：https://tools.ietf.org/html/rfc7540#section-6.2
                 if httpv2_header.frame_length as usize + HTTPV2_FRAME_HEADER_LENGTH
@@ -1804,6 +2094,10 @@ impl HttpLog {
                 if l_offset >= httpv2_header.frame_length
                     || httpv2_header.frame_length > frame_payload.len() as u32
                 {
+                    if httpv2_header.frame_length > frame_payload.len() as u32 {
+                        // entries inserted by the truncated header block are lost
+                        self.reset_header_decoder(direction);
+                    }
                     break;
                 }
 
@@ -1816,9 +2110,7 @@ impl HttpLog {
                     self.http2_resp_decoder.take().unwrap()
                 };
 
-                let result = decoder.decode_with_cb(header_frame_payload, |key, val| {
-                    let key: &[u8] = &key;
-                    let val: &[u8] = &val;
+                let result = decoder.decode(header_frame_payload, |key, val| {
                     let _ = self.on_header(config, key, val, direction, info);
                     if key == b"content-length" {
                         content_length = Some(val.parse_to().unwrap_or_default())
@@ -1851,8 +2143,8 @@ impl HttpLog {
                     self.http2_resp_decoder.replace(decoder);
                 }
 
-                if result.is_err() {
-                    return Err(Error::HttpHeaderParseFailed);
+                if !result? {
+                    info.partial_header_decode = true;
                 }
 
                 header_frame_parsed = true;
@@ -1869,6 +2161,24 @@ impl HttpLog {
                     is_httpv2 = true;
                     break;
                 }
+            } else if httpv2_header.frame_type == HTTPV2_FRAME_HEADERS_TYPE {
+                // e.g. trailers right after headers, only decoded to keep the dynamic table in sync
+                match httpv2_header.header_block(frame_payload) {
+                    Some(block) => {
+                        let decoder = if direction == PacketDirection::ClientToServer {
+                            self.http2_req_decoder.as_mut()
+                        } else {
+                            self.http2_resp_decoder.as_mut()
+                        };
+                        if let Some(decoder) = decoder {
+                            let _ = decoder.decode(block, |_, _| ());
+                        }
+                    }
+                    None if httpv2_header.frame_length as usize > frame_payload.len() => {
+                        self.reset_header_decoder(direction)
+                    }
+                    None => (),
+                }
             } else if (header_frame_parsed
                 || self.proto == L7Protocol::Grpc
                 || self.proto == L7Protocol::Triple)
@@ -2002,6 +2312,12 @@ impl HttpLog {
                     info.proto = L7Protocol::Grpc;
                 }
             }
+            ELASTIC_PRODUCT_HEADER
+                if matches!(info.proto, L7Protocol::Http1 | L7Protocol::Http2)
+                    && val.eq_ignore_ascii_case(ELASTIC_PRODUCT) =>
+            {
+                info.proto = L7Protocol::Elasticsearch;
+            }
             _ => {}
         }
 
@@ -2056,7 +2372,7 @@ impl HttpLog {
         } else {
             &mut info.x_request_id_1
         };
-        for (i, req_id) in config.x_request_id.iter().enumerate() {
+        for (i, req_id) in config.x_request_id(direction).iter().enumerate() {
             let prio = i as u8 + BASE_FIELD_PRIORITY;
             if x_req_id.prio() <= prio {
                 break;
@@ -2092,19 +2408,33 @@ impl HttpLog {
                 L7Protocol::Http2 | L7Protocol::Grpc | L7Protocol::Triple => {
                     config.extra_log_fields.http2.iter()
                 }
+                L7Protocol::Elasticsearch if info.version == Version::V2 => {
+                    config.extra_log_fields.http2.iter()
+                }
+                L7Protocol::Elasticsearch => config.extra_log_fields.http.iter(),
                 _ => return,
             };
 
-            info.attributes.extend(field_iter.filter_map(|f| {
-                if f.field_name.eq_ignore_ascii_case(key) {
-                    Some(KeyVal {
+            for f in field_iter {
+                if !f.field_name.eq_ignore_ascii_case(key) {
+                    continue;
+                }
+                if !f.multi {
+                    info.attributes.push(KeyVal {
                         key: key.replace("-", "_"),
                         val: val.to_owned(),
-                    })
-                } else {
-                    None
+                    });
+                    continue;
                 }
-            }));
+                let mut attribute = MultiValueAttribute {
+                    key: key.replace("-", "_"),
+                    separator: f.separator.clone(),
+                    max_bytes: f.max_bytes,
+                    ..Default::default()
+                };
+                attribute.push(val);
+                attribute.merge_into(&mut info.multi_value_attributes);
+            }
         }
 
         process_attributes(config, info, key, val);
@@ -2204,6 +2534,219 @@ impl HttpLog {
     }
 }
 
+// name lengths of the HPACK static table, https://www.rfc-editor.org/rfc/rfc7541#appendix-A
+const HPACK_STATIC_NAME_LENS: [u8; 61] = [
+    10, 7, 7, 5, 5, 7, 7, 7, 7, 7, 7, 7, 7, 7, 14, 15, 15, 13, 6, 27, 3, 5, 13, 13, 19, 16, 16, 14,
+    16, 13, 12, 6, 4, 4, 6, 7, 4, 4, 8, 17, 13, 8, 19, 13, 4, 8, 12, 18, 19, 5, 7, 7, 11, 6, 10,
+    25, 17, 10, 4, 3, 16,
+];
+const HPACK_ENTRY_OVERHEAD: usize = 32;
+// number of codes of each bit length in the HPACK huffman code, https://www.rfc-editor.org/rfc/rfc7541#appendix-B
+// the code is canonical, so decoded lengths are counted without the symbol table
+const HPACK_HUFFMAN_CODE_COUNTS: [u16; 31] = [
+    0, 0, 0, 0, 0, 10, 26, 32, 6, 0, 5, 3, 2, 6, 2, 3, 0, 0, 0, 3, 8, 13, 26, 29, 12, 4, 15, 19,
+    29, 0, 4,
+];
+
+// returns the value and the number of bytes consumed
+fn decode_hpack_integer(buf: &[u8], prefix: u8) -> Option<(usize, usize)> {
+    let mask = ((1u16 << prefix) - 1) as u8;
+    let mut value = (*buf.first()? & mask) as usize;
+    if value < mask as usize {
+        return Some((value, 1));
+    }
+    for (i, b) in buf[1..].iter().enumerate() {
+        if i >= 4 {
+            return None;
+        }
+        value += ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 2));
+        }
+    }
+    None
+}
+
+// returns the number of symbols in a huffman coded string
+fn hpack_huffman_decoded_len(buf: &[u8]) -> Option<usize> {
+    let mut decoded = 0;
+    // the current code, its bit length and the first code of this length
+    let (mut code, mut len, mut first) = (0u32, 0usize, 0u32);
+    for b in buf {
+        for i in (0..8).rev() {
+            code = (code << 1) | ((b >> i) & 1) as u32;
+            first <<= 1;
+            len += 1;
+            let count = *HPACK_HUFFMAN_CODE_COUNTS.get(len)? as u32;
+            if code < first + count {
+                decoded += 1;
+                (code, len, first) = (0, 0, 0);
+            } else {
+                first += count;
+            }
+        }
+    }
+    // at most 7 bits of EOS prefix are used as padding
+    if len > 7 {
+        return None;
+    }
+    Some(decoded)
+}
+
+// returns the decoded length of the string and the number of bytes consumed,
+// entry sizes in the dynamic table are calculated with decoded lengths
+fn skip_hpack_string(buf: &[u8]) -> Option<(usize, usize)> {
+    let (len, n) = decode_hpack_integer(buf, 7)?;
+    if buf.len() < n + len {
+        return None;
+    }
+    let decoded_len = if buf[0] & 0x80 != 0 {
+        hpack_huffman_decoded_len(&buf[n..n + len])?
+    } else {
+        len
+    };
+    Some((decoded_len, n + len))
+}
+
+// HPACK decoding state of one direction of an HTTP2 connection
+//
+// Entries inserted into the dynamic table are referenced by index in later header blocks,
+// so the state lives as long as the flow. Entry sizes are tracked alongside the decoder
+// to limit and report the memory of the table.
+struct Http2HeaderDecoder {
+    decoder: Decoder<'static>,
+    expected_headers_set: Arc<HashSet<Vec<u8>>>,
+    // (name length, entry size) of the dynamic table, the newest first
+    entries: VecDeque<(usize, usize)>,
+    table_size: usize,
+    max_table_size: usize,
+    // SETTINGS_HEADER_TABLE_SIZE of the peer, limited by `hpack_max_table_size`
+    table_size_limit: usize,
+    hpack_max_table_size: usize,
+}
+
+impl Http2HeaderDecoder {
+    fn new(expected_headers_set: Arc<HashSet<Vec<u8>>>, hpack_max_table_size: usize) -> Self {
+        Self {
+            decoder: Decoder::new_with_expected_headers(expected_headers_set.clone()),
+            expected_headers_set,
+            entries: VecDeque::new(),
+            table_size: 0,
+            max_table_size: HPACK_DEFAULT_TABLE_SIZE,
+            // SETTINGS may have been sent before capture started
+            table_size_limit: hpack_max_table_size,
+            hpack_max_table_size,
+        }
+    }
+
+    fn set_table_size_limit(&mut self, size: usize) {
+        self.table_size_limit = size.min(self.hpack_max_table_size);
+    }
+
+    // Drop the dynamic table, entries referenced later are reported as unknown
+    fn reset(&mut self) {
+        self.decoder = Decoder::new_with_expected_headers(self.expected_headers_set.clone());
+        self.entries.clear();
+        self.table_size = 0;
+        self.max_table_size = HPACK_DEFAULT_TABLE_SIZE;
+    }
+
+    // Returns false if the header block is only partially decoded
+    fn decode<F: FnMut(&[u8], &[u8])>(&mut self, block: &[u8], mut cb: F) -> Result<bool> {
+        let result = self
+            .decoder
+            .decode_with_cb(block, |key, val| cb(&key, &val));
+        let complete = match result {
+            Ok(_) => true,
+            // referenced entries are inserted before capture started or have been dropped
+            Err(DecoderError::HeaderIndexOutOfBounds) => false,
+            Err(_) => {
+                self.reset();
+                return Err(Error::HttpHeaderParseFailed);
+            }
+        };
+        if !self.update_table(block) {
+            self.reset();
+        }
+        Ok(complete)
+    }
+
+    // Returns false if the dynamic table grows beyond the limit
+    fn update_table(&mut self, block: &[u8]) -> bool {
+        let mut buf = block;
+        while let Some(&b) = buf.first() {
+            let n = if b & 0x80 != 0 {
+                // indexed header field
+                decode_hpack_integer(buf, 7).map(|(_, n)| n)
+            } else if b & 0xc0 == 0x40 {
+                // literal header field with incremental indexing
+                self.skip_literal(buf, 6).map(|(name_len, value_len, n)| {
+                    self.insert(name_len, name_len + value_len + HPACK_ENTRY_OVERHEAD);
+                    n
+                })
+            } else if b & 0xe0 == 0x20 {
+                // dynamic table size update
+                match decode_hpack_integer(buf, 5) {
+                    Some((size, _)) if size > self.table_size_limit => return false,
+                    Some((size, n)) => {
+                        self.max_table_size = size;
+                        self.evict(0);
+                        Some(n)
+                    }
+                    None => None,
+                }
+            } else {
+                // literal header field without indexing or never indexed
+                self.skip_literal(buf, 4).map(|(_, _, n)| n)
+            };
+            let Some(n) = n else {
+                break;
+            };
+            buf = &buf[n..];
+        }
+        self.table_size <= self.table_size_limit
+    }
+
+    // returns the name length, the value length and the number of bytes consumed
+    fn skip_literal(&self, buf: &[u8], prefix: u8) -> Option<(usize, usize, usize)> {
+        let (index, mut n) = decode_hpack_integer(buf, prefix)?;
+        let name_len = if index == 0 {
+            let (len, m) = skip_hpack_string(&buf[n..])?;
+            n += m;
+            len
+        } else if index <= HPACK_STATIC_NAME_LENS.len() {
+            HPACK_STATIC_NAME_LENS[index - 1] as usize
+        } else {
+            self.entries
+                .get(index - HPACK_STATIC_NAME_LENS.len() - 1)
+                .map(|(name_len, _)| *name_len)
+                .unwrap_or_default()
+        };
+        let (value_len, m) = skip_hpack_string(&buf[n..])?;
+        Some((name_len, value_len, n + m))
+    }
+
+    fn insert(&mut self, name_len: usize, size: usize) {
+        if size > self.max_table_size {
+            self.entries.clear();
+            self.table_size = 0;
+            return;
+        }
+        self.evict(size);
+        self.entries.push_front((name_len, size));
+        self.table_size += size;
+    }
+
+    fn evict(&mut self, reserved: usize) {
+        while self.table_size + reserved > self.max_table_size {
+            let Some((_, size)) = self.entries.pop_back() else {
+                break;
+            };
+            self.table_size -= size;
+        }
+    }
+}
+
 #[derive(Debug, Default, PartialEq)]
 pub struct Httpv2Headers {
     pub frame_length: u32,
@@ -2278,6 +2821,26 @@ impl Httpv2Headers {
     fn is_stream_end(&self) -> bool {
         self.flags & Self::FLAGS_STREAM_END == Self::FLAGS_STREAM_END
     }
+
+    // header block fragment of a HEADERS frame without padding and priority
+    fn header_block<'a>(&self, frame_payload: &'a [u8]) -> Option<&'a [u8]> {
+        let mut start = 0;
+        let mut end = self.frame_length as usize;
+        if end > frame_payload.len() {
+            return None;
+        }
+        if self.flags & FLAG_HEADERS_PADDED != 0 {
+            start += 1;
+            end = end.checked_sub(*frame_payload.first()? as usize)?;
+        }
+        if self.flags & FLAG_HEADERS_PRIORITY != 0 {
+            start += 5;
+        }
+        if start >= end {
+            return None;
+        }
+        Some(&frame_payload[start..end])
+    }
 }
 
 const HTTP_METHODS: [&'static str; 15] = [
@@ -2463,12 +3026,94 @@ impl<'a> V1Structure<'a> {
     }
 }
 
+// Body of `Transfer-Encoding: chunked`
+// ref: https://www.rfc-editor.org/rfc/rfc9112#section-7.1
+struct ChunkedBody<'a> {
+    data: Vec<u8>,
+    trailers: &'a [u8],
+    // ends in the middle of a chunk, or exceeds the limit
+    truncated: bool,
+}
+
+impl<'a> ChunkedBody<'a> {
+    // at most `limit` bytes of chunk data are decoded
+    fn decode(mut body: &'a [u8], limit: usize) -> Self {
+        let mut decoded = ChunkedBody {
+            data: vec![],
+            trailers: &[],
+            truncated: false,
+        };
+        // more chunks may follow in the next payload if it ends at a chunk boundary
+        while !body.is_empty() {
+            let Some(line_end) = body.windows(2).position(|w| w == b"\r\n") else {
+                decoded.truncated = true;
+                break;
+            };
+            // chunk extensions are ignored
+            let size = body[..line_end]
+                .split(|b| *b == b';')
+                .next()
+                .and_then(|s| str::from_utf8(s).ok())
+                .and_then(|s| usize::from_str_radix(s.trim(), 16).ok());
+            let Some(size) = size else {
+                break;
+            };
+            body = &body[line_end + 2..];
+            if size == 0 {
+                decoded.trailers = body;
+                break;
+            }
+            let n = size.min(body.len()).min(limit - decoded.data.len());
+            decoded.data.extend_from_slice(&body[..n]);
+            if n < size {
+                decoded.truncated = true;
+                break;
+            }
+            body = &body[size..];
+            body = body.strip_prefix(b"\r\n").unwrap_or(body);
+        }
+        decoded
+    }
+}
+
+// Truncate mode drops everything after `?`, MaskValues mode keeps the keys and replaces values with `?`
+fn obfuscate_query(path: &mut String, mode: HttpQueryObfuscation) {
+    let Some(index) = path.find('?') else {
+        return;
+    };
+    match mode {
+        HttpQueryObfuscation::Truncate => path.truncate(index + 1), // retain `?`
+        HttpQueryObfuscation::MaskValues => {
+            let mut masked = String::with_capacity(path.len());
+            masked.push_str(&path[..index + 1]);
+            let query = path[index + 1..].split('#').next().unwrap_or_default();
+            for (i, pair) in query.split('&').enumerate() {
+                if i > 0 {
+                    masked.push('&');
+                }
+                match pair.split_once('=') {
+                    Some((key, _)) => {
+                        masked.push_str(key);
+                        masked.push_str("=?");
+                    }
+                    None if pair.is_empty() => (),
+                    None => masked.push('?'),
+                }
+            }
+            *path = masked;
+        }
+    }
+}
+
 pub fn handle_endpoint(config: &LogParserConfig, path: &String) -> String {
+    let output = path.split('?').next().unwrap();
+    if let Some(endpoint) = config.http_endpoint_trie.rewrite(output) {
+        return endpoint;
+    }
     let keep_segments = config.http_endpoint_trie.find_matching_rule(path);
     if keep_segments <= 0 {
         return "".to_string();
     }
-    let output = path.split('?').next().unwrap();
     let cleaned_output = output.split('/').collect::<Vec<&str>>();
     let mut start = 0;
     let mut end = 0;
@@ -2509,9 +3154,9 @@ mod tests {
     use crate::{
         common::{l7_protocol_log::L7PerfCache, MetaPacket},
         config::{
-            config::TagFilterOperator,
+            config::{ExtraLogFields, ExtraLogFieldsInfo, TagFilterOperator, XRequestIdHeader},
             handler::{BlacklistTrie, L7LogDynamicConfigBuilder, LogParserConfig, TraceType},
-            HttpEndpoint, HttpEndpointMatchRule, HttpEndpointTrie,
+            HttpEndpoint, HttpEndpointMatchRule, HttpEndpointRewriteRule, HttpEndpointTrie,
         },
         flow_generator::L7_RRT_CACHE_CAPACITY,
         utils::test_utils::Capture,
@@ -2588,8 +3233,8 @@ mod tests {
         let mut http1 = HttpLog::new_v1();
         let mut http2 = HttpLog::new_v2(false);
         let mut protocol = L7Protocol::Unknown;
-        http1.set_header_decoder(config.expected_headers_set.clone());
-        http2.set_header_decoder(config.expected_headers_set.clone());
+        http1.set_header_decoder(parse_config);
+        http2.set_header_decoder(parse_config);
         for packet in packets.iter_mut() {
             packet.lookup_key.direction = if packet.lookup_key.dst_port == first_dst_port {
                 PacketDirection::ClientToServer
@@ -2943,6 +3588,387 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chunked_body() {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let parse_config = LogParserConfig {
+            l7_log_dynamic: L7LogDynamicConfigBuilder {
+                x_request_id: vec!["x-request-id".into()],
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        };
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = PacketDirection::ServerToClient;
+        param.set_log_parser_config(&parse_config);
+
+        // each chunk is written by a separate syscall
+        let reads: [&[u8]; 4] = [
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+            b"7\r\n{\"a\":1,\r\n",
+            b"9;ext=1\r\n\"b\":\"xy\"}\r\n",
+            b"0\r\nx-request-id: 789\r\n\r\n",
+        ];
+        let payload = reads.concat();
+        let truncated = |info: &HttpInfo| {
+            info.attributes
+                .iter()
+                .any(|a| a.key == HTTP_BODY_TRUNCATED_ATTR && a.val == "true")
+        };
+
+        let mut info = HttpInfo::default();
+        let body = HttpLog::new_v1()
+            .parse_http_v1(
+                &payload,
+                &param,
+                &mut info,
+                #[cfg(feature = "enterprise")]
+                None,
+            )
+            .unwrap();
+        assert_eq!(body.as_ref(), br#"{"a":1,"b":"xy"}"#);
+        assert_eq!(info.x_request_id_1.get(), "789");
+        assert!(!truncated(&info));
+
+        // ends at a chunk boundary, or in the middle of a chunk
+        for (len, expected, is_truncated) in [
+            (reads[..2].concat().len(), &br#"{"a":1,"#[..], false),
+            (reads[..2].concat().len() + 13, &br#"{"a":1,"b":"#[..], true),
+            (reads[..2].concat().len() + 3, &br#"{"a":1,"#[..], true),
+        ] {
+            let mut info = HttpInfo::default();
+            let body = HttpLog::new_v1()
+                .parse_http_v1(
+                    &payload[..len],
+                    &param,
+                    &mut info,
+                    #[cfg(feature = "enterprise")]
+                    None,
+                )
+                .unwrap();
+            assert_eq!(body.as_ref(), expected);
+            assert_eq!(truncated(&info), is_truncated);
+            assert!(info.x_request_id_1.is_default());
+        }
+
+        // limited by payload_truncation
+        param.set_buf_size(10);
+        let mut info = HttpInfo::default();
+        let body = HttpLog::new_v1()
+            .parse_http_v1(
+                &payload,
+                &param,
+                &mut info,
+                #[cfg(feature = "enterprise")]
+                None,
+            )
+            .unwrap();
+        assert_eq!(body.as_ref(), br#"{"a":1,"b""#);
+        assert!(truncated(&info));
+
+        // compressed body is flagged
+        let payload = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 4\r\n\r\n\x1f\x8b\x08\x00";
+        let mut info = HttpInfo::default();
+        let _ = HttpLog::new_v1().parse_http_v1(
+            payload,
+            &param,
+            &mut info,
+            #[cfg(feature = "enterprise")]
+            None,
+        );
+        assert!(info
+            .attributes
+            .iter()
+            .any(|a| a.key == HTTP_BODY_ENCODING_ATTR && a.val == "gzip"));
+        assert!(!truncated(&info));
+    }
+
+    fn multi_value_field(name: &str, separator: &str, max_bytes: usize) -> ExtraLogFieldsInfo {
+        ExtraLogFieldsInfo {
+            field_name: name.to_string(),
+            multi: true,
+            separator: separator.to_string(),
+            max_bytes,
+        }
+    }
+
+    #[test]
+    fn repeated_headers() {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let parse_config = LogParserConfig {
+            l7_log_dynamic: L7LogDynamicConfigBuilder {
+                extra_log_fields: ExtraLogFields {
+                    http: vec![
+                        multi_value_field("Via", ",", 1024),
+                        multi_value_field("set-cookie", "; ", 12),
+                        ExtraLogFieldsInfo {
+                            field_name: "x-cache".to_string(),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        };
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = PacketDirection::ServerToClient;
+        param.set_log_parser_config(&parse_config);
+
+        let payload = b"HTTP/1.1 200 OK\r\n\
+            Via: 1.1 a\r\n\
+            Set-Cookie: a=1\r\n\
+            X-Cache: HIT\r\n\
+            via: 1.1 b\r\n\
+            Set-Cookie: bb=22\r\n\
+            Set-Cookie: c=3\r\n\
+            Via: 1.1 c\r\n\
+            Content-Length: 0\r\n\r\n";
+        let mut info = HttpInfo::default();
+        let _ = HttpLog::new_v1()
+            .parse_http_v1(
+                payload,
+                &param,
+                &mut info,
+                #[cfg(feature = "enterprise")]
+                None,
+            )
+            .unwrap();
+        let log: L7ProtocolSendLog = info.into();
+        let attributes = log.ext_info.unwrap().attributes.unwrap();
+        let get = |key: &str| {
+            attributes
+                .iter()
+                .filter(|a| a.key == key)
+                .map(|a| a.val.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(get("via"), vec!["1.1 a,1.1 b,1.1 c"]);
+        // "c=3" still fits after "bb=22" is dropped for exceeding max_bytes
+        assert_eq!(get("set_cookie"), vec!["a=1; c=3"]);
+        assert_eq!(get("x_cache"), vec!["HIT"]);
+    }
+
+    #[test]
+    fn repeated_headers_in_http2_frames() {
+        let mut parser = HttpLog::new_v2(false);
+        let config: L7LogDynamicConfig = L7LogDynamicConfigBuilder {
+            extra_log_fields: ExtraLogFields {
+                http2: vec![multi_value_field("via", ",", 1024)],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .into();
+
+        // response headers and trailers of one stream
+        let mut infos = vec![];
+        for headers in [
+            &[
+                (&b":status"[..], &b"200"[..]),
+                (b"via", b"1.1 a"),
+                (b"via", b"1.1 b"),
+            ][..],
+            &[(b"via", b"1.1 c")][..],
+        ] {
+            let mut info = HttpInfo {
+                proto: L7Protocol::Http2,
+                version: Version::V2,
+                stream_id: Some(1),
+                ..Default::default()
+            };
+            for (key, val) in headers {
+                parser
+                    .on_header(
+                        &config,
+                        key,
+                        val,
+                        PacketDirection::ServerToClient,
+                        &mut info,
+                    )
+                    .unwrap();
+            }
+            infos.push(info);
+        }
+        let mut trailer = L7ProtocolInfo::HttpInfo(infos.pop().unwrap());
+        let mut info = infos.pop().unwrap();
+        info.merge_log(&mut trailer).unwrap();
+
+        assert_eq!(
+            info.multi_value_attributes,
+            vec![MultiValueAttribute {
+                key: "via".to_string(),
+                val: "1.1 a,1.1 b,1.1 c".to_string(),
+                separator: ",".to_string(),
+                max_bytes: 1024,
+            }]
+        );
+    }
+
+    fn run_hpack_state(
+        skip_packets: usize,
+        hpack_max_table_size: usize,
+    ) -> (Vec<HttpInfo>, HttpLog) {
+        let capture = Capture::load_pcap(Path::new(FILE_DIR).join("grpc-hpack-long-lived.pcap"));
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packets = capture.collect::<Vec<_>>();
+        let server_port = packets[0].lookup_key.dst_port;
+        let parse_config = LogParserConfig {
+            l7_log_dynamic: L7LogDynamicConfigBuilder {
+                x_request_id: vec!["x-request-id".into()],
+                extra_log_fields: ExtraLogFields {
+                    http2: vec![ExtraLogFieldsInfo {
+                        field_name: "x-tenant".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .into(),
+            http2_hpack_max_table_size: hpack_max_table_size,
+            ..Default::default()
+        };
+
+        let mut http2 = HttpLog::new_v2(false);
+        let mut infos = vec![];
+        for packet in packets.iter_mut().skip(skip_packets) {
+            packet.lookup_key.direction = if packet.lookup_key.dst_port == server_port {
+                PacketDirection::ClientToServer
+            } else {
+                PacketDirection::ServerToClient
+            };
+            let payload = packet.get_l4_payload().unwrap();
+            let mut param = ParseParam::new(
+                packet as &MetaPacket,
+                Some(log_cache.clone()),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.set_captured_byte(payload.len());
+            param.set_log_parser_config(&parse_config);
+
+            if let Ok(L7ParseResult::Multi(m)) = http2.parse_payload(payload, &param) {
+                for i in m {
+                    if let L7ProtocolInfo::HttpInfo(h) = i {
+                        infos.push(h);
+                    }
+                }
+            }
+        }
+        (infos, http2)
+    }
+
+    #[test]
+    fn hpack_state_across_requests() {
+        // the 50th request on the connection only references indexed headers
+        let find = |infos: &[HttpInfo], msg_type| {
+            infos
+                .iter()
+                .find(|i| i.stream_id == Some(99) && i.msg_type == msg_type)
+                .cloned()
+                .unwrap()
+        };
+        let tenant = |info: &HttpInfo| {
+            info.attributes
+                .iter()
+                .find(|a| a.key == "x_tenant")
+                .map(|a| a.val.clone())
+        };
+
+        let (infos, http2) = run_hpack_state(0, 65536);
+        let request = find(&infos, LogMessageType::Request);
+        assert_eq!(request.proto, L7Protocol::Grpc);
+        assert_eq!(request.path, "/demo.Greeter/SayHello");
+        assert_eq!(request.host, "demo:50051");
+        assert_eq!(request.x_request_id_0.get(), "50");
+        assert_eq!(tenant(&request).as_deref(), Some("acme"));
+        assert!(!request.partial_header_decode);
+        let response = find(&infos, LogMessageType::Response);
+        assert_eq!(response.status_code, Some(200));
+        assert_eq!(response.grpc_status_code, Some(0));
+        assert!(!response.partial_header_decode);
+        // x-tenant, content-type, :authority and :path for requests,
+        // grpc-status and content-type for responses
+        assert_eq!(http2.hpack_table_size(), 44 + 60 + 52 + 59 + 44 + 60);
+
+        // capture started after the first 10 requests
+        let (infos, _) = run_hpack_state(20, 65536);
+        let request = find(&infos, LogMessageType::Request);
+        assert!(request.path.is_empty());
+        assert_eq!(tenant(&request), None);
+        assert!(request.partial_header_decode);
+        let log: L7ProtocolSendLog = request.into();
+        assert!(log
+            .ext_info
+            .unwrap()
+            .attributes
+            .unwrap()
+            .iter()
+            .any(|a| a.key == HTTP2_PARTIAL_HEADER_DECODE_ATTR && a.val == "true"));
+
+        // state dropped when exceeding hpack_max_table_size
+        let (infos, http2) = run_hpack_state(0, 200);
+        let request = find(&infos, LogMessageType::Request);
+        assert!(request.path.is_empty());
+        assert!(request.partial_header_decode);
+        assert_eq!(http2.hpack_table_size(), 44 + 60);
+    }
+
+    #[test]
+    fn hpack_huffman_entry_size() {
+        // https://www.rfc-editor.org/rfc/rfc7541#appendix-C.4
+        let blocks = [
+            "828684418cf1e3c2e5f23a6ba0ab90f4ff",
+            "828684be5886a8eb10649cbf",
+            "828785bf408825a849e95ba97d7f8925a849e95bb8e8b4bf",
+        ];
+        let mut decoder = Http2HeaderDecoder::new(Arc::new(HashSet::new()), 4096);
+        let mut sizes = vec![];
+        for block in blocks {
+            let block = hex::decode(block).unwrap();
+            assert!(decoder.decode(&block, |_, _| ()).unwrap());
+            sizes.push(decoder.table_size);
+        }
+        assert_eq!(sizes, vec![57, 110, 164]);
+
+        for (coded, len) in [
+            ("f1e3c2e5f23a6ba0ab90f4ff", 15),
+            ("a8eb10649cbf", 8),
+            ("6402", 3),
+            ("d07abe941054d444a8200595040b8166e082a62d1bff", 29),
+        ] {
+            let coded = hex::decode(coded).unwrap();
+            assert_eq!(hpack_huffman_decoded_len(&coded), Some(len));
+        }
+        // padding longer than 7 bits
+        assert_eq!(hpack_huffman_decoded_len(&[0xf1, 0xff]), None);
+    }
+
     #[test]
     fn panic_caused_by_invalid_header() {
         let packet = MetaPacket::empty();
@@ -3070,7 +4096,7 @@ mod tests {
         let mut config = LogParserConfig::default();
         config.l7_log_dynamic.grpc_streaming_data_enabled = true;
         if http.protocol() == L7Protocol::Http2 || http.protocol() == L7Protocol::Grpc {
-            http.set_header_decoder(config.l7_log_dynamic.expected_headers_set.clone());
+            http.set_header_decoder(&config);
         }
         let mut perf_stats = vec![];
         for packet in packets.iter_mut() {
@@ -3170,6 +4196,32 @@ mod tests {
         assert_eq!(stats, Default::default());
     }
 
+    #[test]
+    fn test_obfuscate_query() {
+        for (path, truncated, masked) in [
+            ("/api/v1", "/api/v1", "/api/v1"),
+            ("/api?", "/api?", "/api?"),
+            (
+                "/api?user=alice&token=abc%3D&flag",
+                "/api?",
+                "/api?user=?&token=?&?",
+            ),
+            (
+                "/搜索?关键字=值&page=2#top",
+                "/搜索?",
+                "/搜索?关键字=?&page=?",
+            ),
+            ("/api?a=&&b=1", "/api?", "/api?a=?&&b=?"),
+        ] {
+            let mut p = path.to_string();
+            obfuscate_query(&mut p, HttpQueryObfuscation::Truncate);
+            assert_eq!(p, truncated);
+            let mut p = path.to_string();
+            obfuscate_query(&mut p, HttpQueryObfuscation::MaskValues);
+            assert_eq!(p, masked);
+        }
+    }
+
     #[test]
     fn test_handle_endpoint() {
         let mut config = LogParserConfig::default();
@@ -3190,6 +4242,7 @@ mod tests {
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
         let trie = HttpEndpointTrie::from(&HttpEndpoint {
             extraction_disabled: false,
+            rewrite_rules: vec![],
             match_rules: vec![HttpEndpointMatchRule {
                 url_prefix: "/api".to_string(),
                 keep_segments: 1,
@@ -3204,6 +4257,7 @@ mod tests {
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
         let trie = HttpEndpointTrie::from(&HttpEndpoint {
             extraction_disabled: false,
+            rewrite_rules: vec![],
             match_rules: vec![
                 HttpEndpointMatchRule {
                     url_prefix: "/api".to_string(),
@@ -3227,6 +4281,7 @@ mod tests {
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
         let trie = HttpEndpointTrie::from(&HttpEndpoint {
             extraction_disabled: false,
+            rewrite_rules: vec![],
             match_rules: vec![HttpEndpointMatchRule {
                 url_prefix: "".to_string(),
                 keep_segments: 3,
@@ -3238,6 +4293,7 @@ mod tests {
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
         let trie = HttpEndpointTrie::from(&HttpEndpoint {
             extraction_disabled: false,
+            rewrite_rules: vec![],
             match_rules: vec![HttpEndpointMatchRule {
                 url_prefix: "/api/v1".to_string(),
                 keep_segments: 0,
@@ -3249,6 +4305,98 @@ mod tests {
         assert_eq!(handle_endpoint(&config, &path), expected_output.to_string());
     }
 
+    #[test]
+    fn test_handle_endpoint_rewrite() {
+        let rule = |url_regex: &str, replacement: &str| HttpEndpointRewriteRule {
+            url_regex: url_regex.to_string(),
+            replacement: replacement.to_string(),
+        };
+        let mut config = LogParserConfig::default();
+        config.http_endpoint_trie = HttpEndpointTrie::from(&HttpEndpoint {
+            extraction_disabled: false,
+            rewrite_rules: vec![
+                rule(r"^/api/(v\d+)/users/\d+$", "/api/$1/users/{uid}"),
+                // overlaps with the rule above
+                rule(r"/\d+(/|$)", "/{id}$1"),
+                rule(r"^/static/.*", "/static"),
+                rule(r"(invalid", ""),
+            ],
+            match_rules: vec![HttpEndpointMatchRule {
+                url_prefix: "/api".to_string(),
+                keep_segments: 1,
+            }],
+        });
+
+        for (path, expected) in [
+            (
+                "/api/v1/users/123/orders/456",
+                "/api/v1/users/{id}/orders/{id}",
+            ),
+            ("/api/v2/users/123?query=1", "/api/v2/users/{uid}"),
+            (
+                "/api/v1/users/abc/orders/7",
+                "/api/v1/users/abc/orders/{id}",
+            ),
+            ("/static/js/app.js", "/static"),
+            // no rewrite rule matches, falls back to prefix rules
+            ("/api/v1/users/abc", "/api"),
+            ("/app/v1/users/abc", ""),
+        ] {
+            assert_eq!(
+                handle_endpoint(&config, &path.to_string()),
+                expected,
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn x_request_id_per_direction() {
+        let mut parser = HttpLog::new_v1();
+        let config = L7LogDynamicConfigBuilder {
+            x_request_id: vec![
+                XRequestIdHeader::Split {
+                    request: "x-request-id".into(),
+                    response: "x-correlation-id".into(),
+                },
+                "x-trace-req".into(),
+            ],
+            ..Default::default()
+        }
+        .into();
+
+        let mut info = HttpInfo::default();
+        for (key, val, direction) in [
+            (
+                &b"x-correlation-id"[..],
+                &b"req-c"[..],
+                PacketDirection::ClientToServer,
+            ),
+            (b"x-request-id", b"req-a", PacketDirection::ClientToServer),
+            (b"x-request-id", b"resp-a", PacketDirection::ServerToClient),
+            (
+                b"x-correlation-id",
+                b"resp-c",
+                PacketDirection::ServerToClient,
+            ),
+        ] {
+            let _ = parser.on_header(&config, key, val, direction, &mut info);
+        }
+        assert_eq!(info.x_request_id_0.get(), "req-a");
+        assert_eq!(info.x_request_id_1.get(), "resp-c");
+
+        // legacy single header is read in both directions
+        let mut info = HttpInfo::default();
+        for direction in [
+            PacketDirection::ClientToServer,
+            PacketDirection::ServerToClient,
+        ] {
+            let _ = parser.on_header(&config, b"x-trace-req", b"t", direction, &mut info);
+        }
+        assert_eq!(info.x_request_id_0.get(), "t");
+        assert_eq!(info.x_request_id_1.get(), "t");
+    }
+
     #[test]
     fn header_priority() {
         let mut parser = HttpLog::new_v1();
@@ -3375,4 +4523,106 @@ mod tests {
             .check_payload("GET / HTTP/1.1\r\n\r\n".as_bytes(), &param)
             .is_some());
     }
+
+    #[test]
+    fn elasticsearch_recognized_by_response_header() {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            Some(Rc::new(RefCell::new(L7PerfCache::new(
+                L7_RRT_CACHE_CAPACITY,
+            )))),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        let config = LogParserConfig::default();
+        let blacklist_cases = [
+            ("errors", "true", true),
+            ("errors", "false", false),
+            ("took", "30", true),
+            ("failed_items", "1", true),
+            ("failed_items", "0", false),
+        ]
+        .map(|(field_name, value, expected)| {
+            let config = LogParserConfig {
+                l7_log_blacklist_trie: HashMap::from([(
+                    L7Protocol::Elasticsearch,
+                    BlacklistTrie::new(vec![TagFilterOperator {
+                        field_name: field_name.to_string(),
+                        operator: "equal".to_string(),
+                        value: value.to_string(),
+                    }])
+                    .unwrap(),
+                )]),
+                ..Default::default()
+            };
+            (field_name, value, expected, config)
+        });
+        param.l4_protocol = IpProtocol::TCP;
+        param.port_src = 50000;
+        param.port_dst = 8080;
+        param.set_log_parser_config(&config);
+
+        let parse = |parser: &mut HttpLog, payload: &[u8], param: &ParseParam| match parser
+            .parse_payload(payload, param)
+            .unwrap()
+        {
+            L7ParseResult::Single(L7ProtocolInfo::HttpInfo(info)) => info,
+            _ => unreachable!(),
+        };
+        let request = b"POST /logs-2024.06.01/_bulk HTTP/1.1\r\n\
+            Host: es\r\n\
+            Content-Length: 0\r\n\r\n";
+        let body = br#"{"took":30,"errors":true,"items":[{"create":{"status":409,"error":{}}}]}"#;
+        let response = [
+            format!(
+                "HTTP/1.1 200 OK\r\n\
+                X-elastic-product: Elasticsearch\r\n\
+                Content-Type: application/json\r\n\
+                Content-Length: {}\r\n\r\n",
+                body.len()
+            )
+            .as_bytes(),
+            body,
+        ]
+        .concat();
+
+        let mut parser = HttpLog::new_v1();
+        param.direction = PacketDirection::ClientToServer;
+        let mut req = parse(&mut parser, request, &param);
+        assert_eq!(req.proto, L7Protocol::Http1);
+        assert!(req.elasticsearch.is_none());
+
+        param.direction = PacketDirection::ServerToClient;
+        let mut resp = parse(&mut parser, &response, &param);
+        assert_eq!(resp.proto, L7Protocol::Elasticsearch);
+
+        req.merge(&mut resp).unwrap();
+        assert_eq!(req.proto, L7Protocol::Elasticsearch);
+        assert_eq!(req.method, Method::Post);
+        assert_eq!(req.path, "/logs-2024.06.01/_bulk");
+        let log: L7ProtocolSendLog = req.into();
+        assert_eq!(log.req.req_type, "_bulk");
+        assert_eq!(log.req.resource, "logs");
+        assert_eq!(log.req.endpoint, "logs/_bulk");
+        let attributes = log.ext_info.unwrap().attributes.unwrap();
+        let get = |key: &str| {
+            attributes
+                .iter()
+                .find(|a| a.key == key)
+                .map(|a| a.val.as_str())
+        };
+        assert_eq!(get("took"), Some("30"));
+        assert_eq!(get("errors"), Some("true"));
+        assert_eq!(get("failed_items"), Some("1"));
+
+        for (field_name, value, expected, config) in blacklist_cases.iter() {
+            param.set_log_parser_config(config);
+            let resp = parse(&mut parser, &response, &param);
+            assert_eq!(resp.is_on_blacklist, *expected, "{field_name} = {value}");
+        }
+    }
 }
@@ -0,0 +1,995 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::HashMap, fmt, mem};
+
+use serde::Serialize;
+
+use super::{
+    pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
+    value_is_default, AppProtoHead, L7ResponseStatus,
+};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
+        meta_packet::ApplicationFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+};
+use public::l7_protocol::{L7Protocol, LogMessageType};
+
+pub const LDAPS_PORT: u16 = 636;
+
+// BER identifiers used by LDAPv3 (RFC 4511)
+const BER_SEQUENCE: u8 = 0x30;
+const BER_INTEGER: u8 = 0x02;
+const BER_OCTET_STRING: u8 = 0x04;
+const BER_ENUMERATED: u8 = 0x0a;
+const BER_CONTEXT_0: u8 = 0x80;
+// protocolOp is an [APPLICATION n] tag, the low 5 bits are the operation number
+const BER_CLASS_MASK: u8 = 0xc0;
+const BER_CLASS_APPLICATION: u8 = 0x40;
+const BER_TAG_NUMBER_MASK: u8 = 0x1f;
+
+// limits the number of outstanding searches whose searchResEntry are being counted
+const MAX_PENDING_SEARCHES: usize = 64;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    BindRequest,
+    BindResponse,
+    UnbindRequest,
+    SearchRequest,
+    SearchResultEntry,
+    SearchResultDone,
+    ModifyRequest,
+    ModifyResponse,
+    AddRequest,
+    AddResponse,
+    DelRequest,
+    DelResponse,
+    ModifyDnRequest,
+    ModifyDnResponse,
+    CompareRequest,
+    CompareResponse,
+    AbandonRequest,
+    SearchResultReference,
+    ExtendedRequest,
+    ExtendedResponse,
+    IntermediateResponse,
+}
+
+impl TryFrom<u8> for Operation {
+    type Error = Error;
+
+    fn try_from(op: u8) -> Result<Self> {
+        match op {
+            0 => Ok(Self::BindRequest),
+            1 => Ok(Self::BindResponse),
+            2 => Ok(Self::UnbindRequest),
+            3 => Ok(Self::SearchRequest),
+            4 => Ok(Self::SearchResultEntry),
+            5 => Ok(Self::SearchResultDone),
+            6 => Ok(Self::ModifyRequest),
+            7 => Ok(Self::ModifyResponse),
+            8 => Ok(Self::AddRequest),
+            9 => Ok(Self::AddResponse),
+            10 => Ok(Self::DelRequest),
+            11 => Ok(Self::DelResponse),
+            12 => Ok(Self::ModifyDnRequest),
+            13 => Ok(Self::ModifyDnResponse),
+            14 => Ok(Self::CompareRequest),
+            15 => Ok(Self::CompareResponse),
+            16 => Ok(Self::AbandonRequest),
+            19 => Ok(Self::SearchResultReference),
+            23 => Ok(Self::ExtendedRequest),
+            24 => Ok(Self::ExtendedResponse),
+            25 => Ok(Self::IntermediateResponse),
+            _ => Err(Error::L7LogParseFailed {
+                proto: L7Protocol::LDAP,
+                reason: format!("unknown protocolOp {op}").into(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // names from the ASN.1 definition of LDAPMessage
+        let s = match self {
+            Self::BindRequest => "bindRequest",
+            Self::BindResponse => "bindResponse",
+            Self::UnbindRequest => "unbindRequest",
+            Self::SearchRequest => "searchRequest",
+            Self::SearchResultEntry => "searchResEntry",
+            Self::SearchResultDone => "searchResDone",
+            Self::ModifyRequest => "modifyRequest",
+            Self::ModifyResponse => "modifyResponse",
+            Self::AddRequest => "addRequest",
+            Self::AddResponse => "addResponse",
+            Self::DelRequest => "delRequest",
+            Self::DelResponse => "delResponse",
+            Self::ModifyDnRequest => "modDNRequest",
+            Self::ModifyDnResponse => "modDNResponse",
+            Self::CompareRequest => "compareRequest",
+            Self::CompareResponse => "compareResponse",
+            Self::AbandonRequest => "abandonRequest",
+            Self::SearchResultReference => "searchResRef",
+            Self::ExtendedRequest => "extendedReq",
+            Self::ExtendedResponse => "extendedResp",
+            Self::IntermediateResponse => "intermediateResponse",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl Operation {
+    fn is_request(&self) -> bool {
+        match self {
+            Self::BindRequest
+            | Self::UnbindRequest
+            | Self::SearchRequest
+            | Self::ModifyRequest
+            | Self::AddRequest
+            | Self::DelRequest
+            | Self::ModifyDnRequest
+            | Self::CompareRequest
+            | Self::AbandonRequest
+            | Self::ExtendedRequest => true,
+            _ => false,
+        }
+    }
+
+    // unbindRequest and abandonRequest are never answered by the server
+    fn has_response(&self) -> bool {
+        match self {
+            Self::UnbindRequest | Self::AbandonRequest => false,
+            _ => true,
+        }
+    }
+
+    // searchResEntry, searchResRef and intermediateResponse are followed by
+    // another response with the same message ID
+    fn is_partial_response(&self) -> bool {
+        match self {
+            Self::SearchResultEntry | Self::SearchResultReference | Self::IntermediateResponse => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn result_code_name(code: u8) -> &'static str {
+    match code {
+        0 => "success",
+        1 => "operationsError",
+        2 => "protocolError",
+        3 => "timeLimitExceeded",
+        4 => "sizeLimitExceeded",
+        5 => "compareFalse",
+        6 => "compareTrue",
+        7 => "authMethodNotSupported",
+        8 => "strongerAuthRequired",
+        10 => "referral",
+        11 => "adminLimitExceeded",
+        12 => "unavailableCriticalExtension",
+        13 => "confidentialityRequired",
+        14 => "saslBindInProgress",
+        16 => "noSuchAttribute",
+        17 => "undefinedAttributeType",
+        18 => "inappropriateMatching",
+        19 => "constraintViolation",
+        20 => "attributeOrValueExists",
+        21 => "invalidAttributeSyntax",
+        32 => "noSuchObject",
+        33 => "aliasProblem",
+        34 => "invalidDNSyntax",
+        36 => "aliasDereferencingProblem",
+        48 => "inappropriateAuthentication",
+        49 => "invalidCredentials",
+        50 => "insufficientAccessRights",
+        51 => "busy",
+        52 => "unavailable",
+        53 => "unwillingToPerform",
+        54 => "loopDetect",
+        64 => "namingViolation",
+        65 => "objectClassViolation",
+        66 => "notAllowedOnNonLeaf",
+        67 => "notAllowedOnRDN",
+        68 => "entryAlreadyExists",
+        69 => "objectClassModsProhibited",
+        71 => "affectsMultipleDSAs",
+        80 => "other",
+        _ => "unknown",
+    }
+}
+
+fn result_code_status(code: u8) -> L7ResponseStatus {
+    match code {
+        // success, compareFalse, compareTrue, referral and saslBindInProgress are not errors
+        0 | 5 | 6 | 10 | 14 => L7ResponseStatus::Ok,
+        // failures caused by server state or server side limits
+        1 | 3 | 4 | 11 | 51 | 52 | 53 | 54 | 80 => L7ResponseStatus::ServerError,
+        _ => L7ResponseStatus::ClientError,
+    }
+}
+
+// replace every attribute value in a DN with '?', e.g. "cn=admin,dc=example" => "cn=?,dc=?"
+fn obfuscate_dn(dn: &str) -> String {
+    let mut output = String::with_capacity(dn.len());
+    let mut in_value = false;
+    let mut escaped = false;
+    for c in dn.chars() {
+        if in_value {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' => escaped = true,
+                ',' | '+' => {
+                    output.push(c);
+                    in_value = false;
+                }
+                _ => (),
+            }
+            continue;
+        }
+        output.push(c);
+        if c == '=' {
+            output.push('?');
+            in_value = true;
+        }
+    }
+    output
+}
+
+struct Tlv<'a> {
+    tag: u8,
+    // may be shorter than the encoded length if the payload is truncated
+    value: &'a [u8],
+    // header length + encoded length
+    total_len: usize,
+}
+
+fn read_tlv(buf: &[u8]) -> Option<Tlv<'_>> {
+    let tag = *buf.get(0)?;
+    // multi-byte tags are not used by LDAP
+    if tag & BER_TAG_NUMBER_MASK == BER_TAG_NUMBER_MASK {
+        return None;
+    }
+    let first = *buf.get(1)?;
+    let (len, header_len) = if first & 0x80 == 0 {
+        (first as usize, 2)
+    } else {
+        let n = (first & 0x7f) as usize;
+        // indefinite length is not allowed in LDAP, and 4 bytes is more than enough
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = buf.get(2..2 + n)?;
+        let len = bytes.iter().fold(0usize, |acc, b| acc << 8 | *b as usize);
+        (len, 2 + n)
+    };
+    let end = buf.len().min(header_len + len);
+    Some(Tlv {
+        tag,
+        value: &buf[header_len..end],
+        total_len: header_len + len,
+    })
+}
+
+fn read_integer(value: &[u8]) -> Option<u32> {
+    if value.is_empty() || value.len() > 4 {
+        return None;
+    }
+    // message ID is defined as INTEGER (0 .. maxInt), so negative values are invalid
+    if value[0] & 0x80 != 0 {
+        return None;
+    }
+    Some(value.iter().fold(0u32, |acc, b| acc << 8 | *b as u32))
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct LdapInfo {
+    pub msg_type: LogMessageType,
+
+    #[serde(rename = "request_id")]
+    pub message_id: u32,
+    #[serde(rename = "request_type", skip_serializing_if = "Option::is_none")]
+    pub op: Option<Operation>,
+    #[serde(rename = "request_resource", skip_serializing_if = "String::is_empty")]
+    pub dn: String,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub result_code: Option<u8>,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub diagnostic_message: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub search_entries: u32,
+
+    pub captured_request_byte: u32,
+    pub captured_response_byte: u32,
+
+    rrt: u64,
+
+    #[serde(skip)]
+    is_tls: bool,
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for LdapInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.message_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::LdapInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::LDAP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn get_request_resource_length(&self) -> usize {
+        self.dn.len()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl LdapInfo {
+    fn merge(&mut self, other: &mut Self) {
+        if other.captured_request_byte != 0 {
+            self.captured_request_byte = other.captured_request_byte;
+        }
+        if other.captured_response_byte != 0 {
+            self.captured_response_byte = other.captured_response_byte;
+        }
+        if self.op.is_none() {
+            self.op = other.op;
+        }
+        if self.dn.is_empty() {
+            mem::swap(&mut self.dn, &mut other.dn);
+        }
+        if other.result_code.is_some() {
+            self.result_code = other.result_code;
+            mem::swap(&mut self.diagnostic_message, &mut other.diagnostic_message);
+        }
+        self.search_entries += other.search_entries;
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::LDAP) else {
+            return;
+        };
+        self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.dn)
+            || self
+                .op
+                .map(|op| t.request_type.is_on_blacklist(&op.to_string()))
+                .unwrap_or(false);
+    }
+
+    fn status(&self) -> L7ResponseStatus {
+        match self.result_code {
+            Some(code) => result_code_status(code),
+            None if self.msg_type == LogMessageType::Session => L7ResponseStatus::Ok,
+            None => L7ResponseStatus::Unknown,
+        }
+    }
+}
+
+impl From<LdapInfo> for L7ProtocolSendLog {
+    fn from(f: LdapInfo) -> Self {
+        let flags = if f.is_tls {
+            ApplicationFlags::TLS.bits()
+        } else {
+            ApplicationFlags::NONE.bits()
+        };
+        let status = f.status();
+        let exception = match (status, f.result_code) {
+            (L7ResponseStatus::ClientError | L7ResponseStatus::ServerError, Some(code)) => {
+                if f.diagnostic_message.is_empty() {
+                    result_code_name(code).to_string()
+                } else {
+                    format!("{}: {}", result_code_name(code), f.diagnostic_message)
+                }
+            }
+            _ => String::new(),
+        };
+        let attributes = if f.search_entries > 0 {
+            Some(vec![KeyVal {
+                key: "search_entries".to_string(),
+                val: f.search_entries.to_string(),
+            }])
+        } else {
+            None
+        };
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req: L7Request {
+                req_type: f.op.map(|op| op.to_string()).unwrap_or_default(),
+                resource: f.dn,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status,
+                code: f.result_code.map(|c| c as i32),
+                exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                request_id: Some(f.message_id),
+                attributes,
+                ..Default::default()
+            }),
+            flags,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&LdapInfo> for LogCache {
+    fn from(info: &LdapInfo) -> Self {
+        LogCache {
+            msg_type: info.msg_type,
+            resp_status: info.status(),
+            on_blacklist: info.is_on_blacklist,
+            ..Default::default()
+        }
+    }
+}
+
+struct Message<'a> {
+    message_id: u32,
+    op: Operation,
+    // content of protocolOp
+    body: &'a [u8],
+    len: usize,
+}
+
+#[derive(Default)]
+struct PendingSearch {
+    entries: u32,
+    bytes: u32,
+}
+
+#[derive(Default)]
+pub struct LdapLog {
+    // searchResEntry/searchResRef received for each message ID, reported with searchResDone
+    pending_searches: HashMap<u32, PendingSearch>,
+    obfuscate: bool,
+    perf_stats: Vec<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for LdapLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return None;
+        }
+        // LDAPS is only readable after TLS has been removed, e.g. by uprobe
+        if (param.port_dst == LDAPS_PORT || param.port_src == LDAPS_PORT) && !param.is_tls() {
+            return None;
+        }
+        let msg = Self::parse_message(payload).ok()?;
+        if msg.message_id == 0 || !msg.op.is_request() {
+            return None;
+        }
+        Some(LogMessageType::Request)
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        self.obfuscate = param.obfuscate_cache.is_some();
+        let mut infos = self.parse(payload, param.direction)?;
+
+        self.perf_stats.clear();
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if param.parse_perf {
+                let mut perf_stat = L7PerfStats::default();
+                if let Some(stats) = info.perf_stats(param) {
+                    info.rrt = stats.rrt_sum;
+                    perf_stat.sequential_merge(&stats);
+                }
+                self.perf_stats.push(perf_stat);
+            }
+        }
+
+        if !param.parse_log || infos.is_empty() {
+            return Ok(L7ParseResult::None);
+        }
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::LdapInfo(
+                infos.pop().unwrap(),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos.into_iter().map(L7ProtocolInfo::LdapInfo).collect(),
+            ))
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::LDAP
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn perf_stats(&mut self) -> Vec<L7PerfStats> {
+        mem::take(&mut self.perf_stats)
+    }
+}
+
+impl LdapLog {
+    // LDAPMessage ::= SEQUENCE {
+    //      messageID       MessageID,
+    //      protocolOp      CHOICE { ... },
+    //      controls        [0] Controls OPTIONAL }
+    fn parse_message(payload: &[u8]) -> Result<Message<'_>> {
+        let err = |reason: &'static str| Error::L7LogParseFailed {
+            proto: L7Protocol::LDAP,
+            reason: reason.into(),
+        };
+        let envelope = read_tlv(payload).ok_or_else(|| err("invalid LDAPMessage"))?;
+        if envelope.tag != BER_SEQUENCE {
+            return Err(err("LDAPMessage is not a SEQUENCE"));
+        }
+        let id = read_tlv(envelope.value).ok_or_else(|| err("invalid messageID"))?;
+        if id.tag != BER_INTEGER {
+            return Err(err("messageID is not an INTEGER"));
+        }
+        let message_id = read_integer(id.value).ok_or_else(|| err("invalid messageID"))?;
+        let rest = envelope
+            .value
+            .get(id.total_len..)
+            .ok_or_else(|| err("protocolOp truncated"))?;
+        let op = read_tlv(rest).ok_or_else(|| err("invalid protocolOp"))?;
+        if op.tag & BER_CLASS_MASK != BER_CLASS_APPLICATION {
+            return Err(err("protocolOp is not an APPLICATION tag"));
+        }
+        Ok(Message {
+            message_id,
+            op: Operation::try_from(op.tag & BER_TAG_NUMBER_MASK)?,
+            body: op.value,
+            len: envelope.total_len,
+        })
+    }
+
+    fn parse(&mut self, mut payload: &[u8], direction: PacketDirection) -> Result<Vec<LdapInfo>> {
+        let mut infos = vec![];
+        let mut parsed = false;
+        while !payload.is_empty() {
+            let msg = match Self::parse_message(payload) {
+                Ok(msg) => msg,
+                Err(e) if !parsed => return Err(e),
+                // ignore anything that can not be decoded after the first message
+                Err(_) => break,
+            };
+            parsed = true;
+            let len = msg.len;
+            match direction {
+                PacketDirection::ClientToServer if msg.op.is_request() => {
+                    infos.push(self.parse_request(msg));
+                }
+                PacketDirection::ServerToClient if !msg.op.is_request() => {
+                    if let Some(info) = self.parse_response(msg) {
+                        infos.push(info);
+                    }
+                }
+                _ => {
+                    return Err(Error::L7LogParseFailed {
+                        proto: L7Protocol::LDAP,
+                        reason: "unexpected protocolOp direction".into(),
+                    })
+                }
+            }
+            if len >= payload.len() {
+                break;
+            }
+            payload = &payload[len..];
+        }
+        Ok(infos)
+    }
+
+    fn parse_request(&self, msg: Message) -> LdapInfo {
+        let dn = match msg.op {
+            // BindRequest ::= [APPLICATION 0] SEQUENCE {
+            //      version         INTEGER (1 ..  127),
+            //      name            LDAPDN,
+            //      authentication  AuthenticationChoice }
+            Operation::BindRequest => read_tlv(msg.body)
+                .and_then(|version| msg.body.get(version.total_len..))
+                .and_then(|rest| read_tlv(rest))
+                .filter(|name| name.tag == BER_OCTET_STRING)
+                .map(|name| name.value),
+            // DelRequest ::= [APPLICATION 10] LDAPDN
+            Operation::DelRequest => Some(msg.body),
+            // ExtendedRequest ::= [APPLICATION 23] SEQUENCE {
+            //      requestName      [0] LDAPOID,
+            //      requestValue     [1] OCTET STRING OPTIONAL }
+            Operation::ExtendedRequest => read_tlv(msg.body)
+                .filter(|name| name.tag == BER_CONTEXT_0)
+                .map(|name| name.value),
+            // baseObject/object/entry is the first field of these requests
+            Operation::SearchRequest
+            | Operation::ModifyRequest
+            | Operation::AddRequest
+            | Operation::ModifyDnRequest
+            | Operation::CompareRequest => read_tlv(msg.body)
+                .filter(|dn| dn.tag == BER_OCTET_STRING)
+                .map(|dn| dn.value),
+            _ => None,
+        };
+        let dn = dn
+            .map(|dn| String::from_utf8_lossy(dn).into_owned())
+            .unwrap_or_default();
+        LdapInfo {
+            msg_type: if msg.op.has_response() {
+                LogMessageType::Request
+            } else {
+                LogMessageType::Session
+            },
+            message_id: msg.message_id,
+            op: Some(msg.op),
+            dn: if self.obfuscate && msg.op != Operation::ExtendedRequest {
+                obfuscate_dn(&dn)
+            } else {
+                dn
+            },
+            captured_request_byte: msg.len as u32,
+            ..Default::default()
+        }
+    }
+
+    fn parse_response(&mut self, msg: Message) -> Option<LdapInfo> {
+        if msg.op.is_partial_response() {
+            if !self.pending_searches.contains_key(&msg.message_id)
+                && self.pending_searches.len() >= MAX_PENDING_SEARCHES
+            {
+                self.pending_searches.clear();
+            }
+            let pending = self.pending_searches.entry(msg.message_id).or_default();
+            if msg.op != Operation::IntermediateResponse {
+                pending.entries += 1;
+            }
+            pending.bytes += msg.len as u32;
+            return None;
+        }
+
+        // LDAPResult ::= SEQUENCE {
+        //      resultCode         ENUMERATED { ... },
+        //      matchedDN          LDAPDN,
+        //      diagnosticMessage  LDAPString,
+        //      referral           [3] Referral OPTIONAL }
+        let mut info = LdapInfo {
+            msg_type: LogMessageType::Response,
+            message_id: msg.message_id,
+            captured_response_byte: msg.len as u32,
+            ..Default::default()
+        };
+        if let Some(code) = read_tlv(msg.body).filter(|code| code.tag == BER_ENUMERATED) {
+            info.result_code = code.value.last().copied();
+            let diagnostic = msg
+                .body
+                .get(code.total_len..)
+                .and_then(|rest| read_tlv(rest))
+                .and_then(|matched_dn| msg.body.get(code.total_len + matched_dn.total_len..))
+                .and_then(|rest| read_tlv(rest))
+                .filter(|message| message.tag == BER_OCTET_STRING);
+            if let Some(message) = diagnostic {
+                info.diagnostic_message = String::from_utf8_lossy(message.value).into_owned();
+            }
+        }
+        if let Some(pending) = self.pending_searches.remove(&msg.message_id) {
+            info.search_entries = pending.entries;
+            info.captured_response_byte += pending.bytes;
+        }
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    // build an LDAPMessage with short form lengths
+    fn message(message_id: u8, op: u8, constructed: bool, body: &[u8]) -> Vec<u8> {
+        let tag = BER_CLASS_APPLICATION | if constructed { 0x20 } else { 0 } | op;
+        let mut op = vec![tag, body.len() as u8];
+        op.extend_from_slice(body);
+        let mut msg = vec![
+            BER_SEQUENCE,
+            (op.len() + 3) as u8,
+            BER_INTEGER,
+            1,
+            message_id,
+        ];
+        msg.extend_from_slice(&op);
+        msg
+    }
+
+    fn octet_string(s: &str) -> Vec<u8> {
+        let mut v = vec![BER_OCTET_STRING, s.len() as u8];
+        v.extend_from_slice(s.as_bytes());
+        v
+    }
+
+    fn ldap_result(code: u8, diagnostic: &str) -> Vec<u8> {
+        let mut v = vec![BER_ENUMERATED, 1, code];
+        v.extend(octet_string(""));
+        v.extend(octet_string(diagnostic));
+        v
+    }
+
+    fn bind_request(message_id: u8, dn: &str) -> Vec<u8> {
+        let mut body = vec![BER_INTEGER, 1, 3];
+        body.extend(octet_string(dn));
+        // simple authentication
+        body.extend_from_slice(&[0x80, 6]);
+        body.extend_from_slice(b"secret");
+        message(message_id, 0, true, &body)
+    }
+
+    fn search_request(message_id: u8, base: &str) -> Vec<u8> {
+        let mut body = octet_string(base);
+        // scope, derefAliases, sizeLimit, timeLimit, typesOnly
+        body.extend_from_slice(&[0x0a, 1, 2, 0x0a, 1, 0, 0x02, 1, 0, 0x02, 1, 0, 0x01, 1, 0]);
+        // (objectClass=*)
+        body.extend_from_slice(&[0x87, 11]);
+        body.extend_from_slice(b"objectClass");
+        // attributes
+        body.extend_from_slice(&[0x30, 0]);
+        message(message_id, 3, true, &body)
+    }
+
+    fn search_entry(message_id: u8, dn: &str) -> Vec<u8> {
+        let mut body = octet_string(dn);
+        body.extend_from_slice(&[0x30, 0]);
+        message(message_id, 4, true, &body)
+    }
+
+    fn parse(
+        ldap: &mut LdapLog,
+        payload: &[u8],
+        direction: PacketDirection,
+        time: u64,
+        cache: &Rc<RefCell<L7PerfCache>>,
+    ) -> Vec<LdapInfo> {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            Some(cache.clone()),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = direction;
+        param.time = time;
+        match ldap.parse_payload(payload, &param).unwrap() {
+            L7ParseResult::Single(L7ProtocolInfo::LdapInfo(info)) => vec![info],
+            L7ParseResult::Multi(m) => m
+                .into_iter()
+                .filter_map(|i| match i {
+                    L7ProtocolInfo::LdapInfo(info) => Some(info),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn check_bind() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut ldap = LdapLog::default();
+
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        let request = bind_request(1, "cn=admin,dc=example,dc=org");
+        assert_eq!(
+            ldap.check_payload(&request, &param),
+            Some(LogMessageType::Request)
+        );
+        // plaintext is not expected on LDAPS port without TLS uprobe
+        param.port_dst = LDAPS_PORT;
+        assert_eq!(ldap.check_payload(&request, &param), None);
+
+        let infos = parse(
+            &mut ldap,
+            &request,
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].message_id, 1);
+        assert_eq!(infos[0].op, Some(Operation::BindRequest));
+        assert_eq!(infos[0].dn, "cn=admin,dc=example,dc=org");
+
+        let response = message(1, 1, true, &ldap_result(49, "invalid credentials"));
+        let infos = parse(
+            &mut ldap,
+            &response,
+            PacketDirection::ServerToClient,
+            300,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].result_code, Some(49));
+        assert_eq!(infos[0].diagnostic_message, "invalid credentials");
+        assert_eq!(infos[0].status(), L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].rrt, 200);
+    }
+
+    #[test]
+    fn check_search_entries() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut ldap = LdapLog::default();
+
+        let infos = parse(
+            &mut ldap,
+            &search_request(2, "ou=people,dc=example,dc=org"),
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].dn, "ou=people,dc=example,dc=org");
+
+        // entries split across segments, the final one carries searchResDone
+        let mut first = vec![];
+        for uid in ["alice", "bob", "carol"] {
+            first.extend(search_entry(2, &format!("uid={uid},ou=people")));
+        }
+        let infos = parse(
+            &mut ldap,
+            &first,
+            PacketDirection::ServerToClient,
+            200,
+            &cache,
+        );
+        assert!(infos.is_empty());
+        assert_eq!(ldap.perf_stats(), vec![]);
+
+        let mut second = search_entry(2, "uid=dave,ou=people");
+        second.extend(message(2, 5, true, &ldap_result(0, "")));
+        let infos = parse(
+            &mut ldap,
+            &second,
+            PacketDirection::ServerToClient,
+            400,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].search_entries, 4);
+        assert_eq!(infos[0].status(), L7ResponseStatus::Ok);
+        assert_eq!(infos[0].rrt, 300);
+        assert_eq!(
+            infos[0].captured_response_byte as usize,
+            first.len() + second.len()
+        );
+        assert!(ldap.pending_searches.is_empty());
+
+        let stats = ldap.perf_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].response_count, 1);
+    }
+
+    #[test]
+    fn check_pipelined_searches() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut ldap = LdapLog::default();
+
+        let mut requests = search_request(3, "dc=a");
+        requests.extend(search_request(4, "dc=b"));
+        let infos = parse(
+            &mut ldap,
+            &requests,
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(infos.len(), 2);
+
+        let mut responses = search_entry(4, "cn=x,dc=b");
+        responses.extend(search_entry(3, "cn=y,dc=a"));
+        responses.extend(search_entry(4, "cn=z,dc=b"));
+        responses.extend(message(4, 5, true, &ldap_result(0, "")));
+        responses.extend(message(3, 5, true, &ldap_result(32, "")));
+        let infos = parse(
+            &mut ldap,
+            &responses,
+            PacketDirection::ServerToClient,
+            200,
+            &cache,
+        );
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].message_id, 4);
+        assert_eq!(infos[0].search_entries, 2);
+        assert_eq!(infos[1].message_id, 3);
+        assert_eq!(infos[1].search_entries, 1);
+        assert_eq!(infos[1].status(), L7ResponseStatus::ClientError);
+    }
+
+    #[test]
+    fn check_unbind_and_obfuscate() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut ldap = LdapLog::default();
+
+        let infos = parse(
+            &mut ldap,
+            &message(5, 2, false, &[]),
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(infos[0].msg_type, LogMessageType::Session);
+        assert_eq!(infos[0].status(), L7ResponseStatus::Ok);
+
+        assert_eq!(
+            obfuscate_dn("cn=John\\, Doe+uid=jd,dc=example,dc=org"),
+            "cn=?+uid=?,dc=?,dc=?"
+        );
+    }
+}
@@ -14,18 +14,20 @@
  * limitations under the License.
  */
 
-use std::{mem, str};
+use std::{mem, num::NonZeroUsize, str};
 
+use lru::LruCache;
 use serde::Serialize;
 use serde_json::{value::Value, Map, Number};
 
 const AMQPHEADER: &[u8] = b"AMQP\x00\x00\x09\x01";
-const AMQPVERSION: &[u8] = b"v0.9.1";
+const AMQP1_HEADER: &[u8] = b"AMQP\x00\x01\x00\x00";
+const AMQP1_SASL_HEADER: &[u8] = b"AMQP\x03\x01\x00\x00";
 
 use crate::{
     common::{
         enums::IpProtocol,
-        flow::{L7PerfStats, L7Protocol},
+        flow::{L7PerfStats, L7Protocol, PacketDirection},
         l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
         meta_packet::ApplicationFlags,
@@ -110,6 +112,76 @@ impl From<ReplyCode> for i32 {
     }
 }
 
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AmqpVersion {
+    #[default]
+    #[serde(rename = "v0.9.1")]
+    V0_9_1,
+    #[serde(rename = "v1.0")]
+    V1_0,
+}
+
+impl AmqpVersion {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::V0_9_1 => "v0.9.1",
+            Self::V1_0 => "v1.0",
+        }
+    }
+
+    // routes a connection by the first 8 bytes, either a protocol header or a frame header
+    fn detect(payload: &[u8]) -> Option<Self> {
+        if payload.starts_with(AMQPHEADER) {
+            return Some(Self::V0_9_1);
+        }
+        if payload.starts_with(AMQP1_HEADER) || payload.starts_with(AMQP1_SASL_HEADER) {
+            return Some(Self::V1_0);
+        }
+        if payload.len() < 8 {
+            return None;
+        }
+        // frame type of 0-9-1 is never 0, while the size of 1.0 frame rarely exceeds 16MB
+        if payload[0] == 0 {
+            Some(Self::V1_0)
+        } else {
+            Some(Self::V0_9_1)
+        }
+    }
+}
+
+// AMQP 1.0 performatives, ref: https://docs.oasis-open.org/amqp/core/v1.0/os/amqp-core-transport-v1.0-os.html#section-performatives
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Performative {
+    Open,
+    Begin,
+    Attach,
+    Flow,
+    Transfer,
+    Disposition,
+    Detach,
+    End,
+    Close,
+}
+
+impl TryFrom<u64> for Performative {
+    type Error = u64;
+
+    fn try_from(descriptor: u64) -> std::result::Result<Self, Self::Error> {
+        match descriptor {
+            0x10 => Ok(Self::Open),
+            0x11 => Ok(Self::Begin),
+            0x12 => Ok(Self::Attach),
+            0x13 => Ok(Self::Flow),
+            0x14 => Ok(Self::Transfer),
+            0x15 => Ok(Self::Disposition),
+            0x16 => Ok(Self::Detach),
+            0x17 => Ok(Self::End),
+            0x18 => Ok(Self::Close),
+            _ => Err(descriptor),
+        }
+    }
+}
+
 #[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum FrameType {
     #[default]
@@ -297,6 +369,8 @@ pub struct AmqpInfo {
 
     rtt: u64,
 
+    version: AmqpVersion,
+
     vhost: Option<String>,
 
     is_protcol_header: bool,
@@ -329,6 +403,15 @@ pub struct AmqpInfo {
     #[serde(rename = "routing_key", skip_serializing_if = "Option::is_none")]
     routing_key: Option<String>,
 
+    // AMQP 1.0
+    #[serde(skip_serializing_if = "Option::is_none")]
+    performative: Option<Performative>,
+    // source or target address of the link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivery_id: Option<u32>,
+
     #[serde(rename = "trace_ids", skip_serializing_if = "value_is_default")]
     trace_ids: PrioFields,
     #[serde(rename = "span_id", skip_serializing_if = "Option::is_none")]
@@ -479,6 +562,9 @@ fn read_table(payload: &[u8]) -> Option<(&[u8], Value)> {
 
 impl AmqpInfo {
     fn generate_endpoint(&self) -> Option<String> {
+        if self.version == AmqpVersion::V1_0 {
+            return self.address.clone();
+        }
         let (exchange, exchange_len) = self
             .exchange
             .as_ref()
@@ -499,6 +585,9 @@ impl AmqpInfo {
         if self.is_protcol_header {
             return "Protocol-Header".to_string();
         }
+        if let Some(p) = self.performative {
+            return format!("{:?}", p);
+        }
         match self.frame_type {
             FrameType::Method => format!("{:?}.{:?}", self.class_id, self.method_id),
             FrameType::Header => "Content-Header".to_string(),
@@ -783,6 +872,22 @@ impl AmqpInfo {
         ))
     }
 
+    fn set_amqp1_error(&mut self, error: &types::Value) {
+        let Some((condition, text)) = AmqpLog::amqp1_error(error) else {
+            return;
+        };
+        // ref: https://docs.oasis-open.org/amqp/core/v1.0/os/amqp-core-transport-v1.0-os.html#type-amqp-error
+        self.resp_status = match condition.as_str() {
+            "amqp:internal-error"
+            | "amqp:resource-limit-exceeded"
+            | "amqp:not-implemented"
+            | "amqp:connection:forced"
+            | "amqp:link:detach-forced" => L7ResponseStatus::ServerError,
+            _ => L7ResponseStatus::ClientError,
+        };
+        self.resp_text = Some(text);
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::AMQP) {
             self.is_on_blacklist = self
@@ -804,10 +909,34 @@ impl AmqpInfo {
     }
 }
 
-#[derive(Default)]
+// AMQP 1.0 multi-frame delivery waiting for its last transfer frame
+#[derive(Clone, Copy, Debug)]
+struct PendingDelivery {
+    delivery_id: u32,
+    settled: bool,
+    size: u32,
+}
+
 pub struct AmqpLog {
     perf_stats: Vec<L7PerfStats>,
     vhost: Option<String>,
+    version: Option<AmqpVersion>,
+    // AMQP 1.0 link addresses by (is from client, channel, handle)
+    links: LruCache<(bool, u16, u32), String>,
+    // AMQP 1.0 unfinished deliveries by (is from client, channel, handle)
+    deliveries: LruCache<(bool, u16, u32), PendingDelivery>,
+}
+
+impl Default for AmqpLog {
+    fn default() -> Self {
+        Self {
+            perf_stats: vec![],
+            vhost: None,
+            version: None,
+            links: LruCache::new(NonZeroUsize::new(Self::MAX_LINKS_PER_FLOW).unwrap()),
+            deliveries: LruCache::new(NonZeroUsize::new(Self::MAX_LINKS_PER_FLOW).unwrap()),
+        }
+    }
 }
 
 impl From<AmqpInfo> for L7ProtocolSendLog {
@@ -819,7 +948,7 @@ impl From<AmqpInfo> for L7ProtocolSendLog {
         let log = L7ProtocolSendLog {
             captured_request_byte: info.captured_request_byte,
             captured_response_byte: info.captured_response_byte,
-            version: Some(std::str::from_utf8(AMQPVERSION).unwrap().to_string()),
+            version: Some(info.version.as_str().to_string()),
             flags,
             req_len: info.req_len,
             resp_len: info.resp_len,
@@ -872,7 +1001,8 @@ impl L7ProtocolInfoInterface for AmqpInfo {
     }
 
     fn session_id(&self) -> Option<u32> {
-        None
+        // AMQP 1.0 transfer is paired with disposition by delivery-id
+        self.delivery_id
     }
 
     fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
@@ -933,6 +1063,433 @@ impl L7ProtocolInfoInterface for AmqpInfo {
     }
 }
 
+// AMQP 1.0 type system, only types appearing in performatives are decoded
+// ref: https://docs.oasis-open.org/amqp/core/v1.0/os/amqp-core-types-v1.0-os.html
+mod types {
+    use std::str;
+
+    use crate::utils::bytes::{read_u16_be, read_u32_be, read_u64_be};
+
+    const MAX_DEPTH: usize = 8;
+
+    #[derive(Debug, PartialEq)]
+    pub enum Value<'a> {
+        Null,
+        Bool(bool),
+        Ulong(u64),
+        Long(i64),
+        // string and symbol
+        Str(&'a str),
+        Binary(&'a [u8]),
+        List(Vec<Value<'a>>),
+        Described(Box<Value<'a>>, Box<Value<'a>>),
+        // value skipped
+        Other,
+    }
+
+    impl<'a> Value<'a> {
+        pub fn as_u64(&self) -> Option<u64> {
+            match self {
+                Self::Ulong(v) => Some(*v),
+                Self::Long(v) if *v >= 0 => Some(*v as u64),
+                _ => None,
+            }
+        }
+
+        pub fn as_bool(&self) -> Option<bool> {
+            match self {
+                Self::Bool(v) => Some(*v),
+                _ => None,
+            }
+        }
+
+        pub fn as_str(&self) -> Option<&'a str> {
+            match self {
+                Self::Str(s) => Some(*s),
+                _ => None,
+            }
+        }
+
+        pub fn as_list(&self) -> Option<&[Value<'a>]> {
+            match self {
+                Self::List(l) => Some(l.as_slice()),
+                _ => None,
+            }
+        }
+
+        // numeric descriptor and the described value
+        pub fn as_described(&self) -> Option<(u64, &Value<'a>)> {
+            match self {
+                Self::Described(descriptor, value) => Some((descriptor.as_u64()?, value.as_ref())),
+                _ => None,
+            }
+        }
+
+        // field of a described list, missing trailing fields are null
+        pub fn field(&self, index: usize) -> &Value<'a> {
+            self.as_described()
+                .and_then(|(_, v)| v.as_list())
+                .unwrap_or_default()
+                .get(index)
+                .unwrap_or(&Value::Null)
+        }
+    }
+
+    fn split(buf: &[u8], n: usize) -> Option<(&[u8], &[u8])> {
+        if buf.len() < n {
+            return None;
+        }
+        Some(buf.split_at(n))
+    }
+
+    fn variable(buf: &[u8], wide: bool) -> Option<(&[u8], &[u8])> {
+        let (size, buf) = if wide {
+            let (size, buf) = split(buf, 4)?;
+            (read_u32_be(size) as usize, buf)
+        } else {
+            let (size, buf) = split(buf, 1)?;
+            (size[0] as usize, buf)
+        };
+        let (data, buf) = split(buf, size)?;
+        Some((buf, data))
+    }
+
+    // elements beyond the captured payload are dropped so that truncated performatives can still be read
+    fn list(buf: &[u8], wide: bool, depth: usize) -> Option<(&[u8], Value)> {
+        let width = if wide { 4 } else { 1 };
+        let (size, buf) = split(buf, width)?;
+        let size = if wide {
+            read_u32_be(size) as usize
+        } else {
+            size[0] as usize
+        };
+        let (body, rest) = buf.split_at(size.min(buf.len()));
+        let (count, mut body) = split(body, width)?;
+        let count = if wide {
+            read_u32_be(count) as usize
+        } else {
+            count[0] as usize
+        };
+        let mut values = vec![];
+        for _ in 0..count {
+            let Some((b, v)) = decode_value(body, depth + 1) else {
+                break;
+            };
+            values.push(v);
+            body = b;
+        }
+        Some((rest, Value::List(values)))
+    }
+
+    fn decode_value(buf: &[u8], depth: usize) -> Option<(&[u8], Value)> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let (code, buf) = split(buf, 1)?;
+        let rest = match code[0] {
+            0x00 => {
+                let (buf, descriptor) = decode_value(buf, depth + 1)?;
+                let (buf, value) = decode_value(buf, depth + 1)?;
+                return Some((buf, Value::Described(Box::new(descriptor), Box::new(value))));
+            }
+            0x40 => return Some((buf, Value::Null)),
+            0x41 => return Some((buf, Value::Bool(true))),
+            0x42 => return Some((buf, Value::Bool(false))),
+            0x43 | 0x44 => return Some((buf, Value::Ulong(0))),
+            0x45 => return Some((buf, Value::List(vec![]))),
+            0x56 => {
+                let (v, buf) = split(buf, 1)?;
+                return Some((buf, Value::Bool(v[0] != 0)));
+            }
+            // ubyte, smalluint, smallulong
+            0x50 | 0x52 | 0x53 => {
+                let (v, buf) = split(buf, 1)?;
+                return Some((buf, Value::Ulong(v[0] as u64)));
+            }
+            0x60 => {
+                let (v, buf) = split(buf, 2)?;
+                return Some((buf, Value::Ulong(read_u16_be(v) as u64)));
+            }
+            0x70 => {
+                let (v, buf) = split(buf, 4)?;
+                return Some((buf, Value::Ulong(read_u32_be(v) as u64)));
+            }
+            0x80 => {
+                let (v, buf) = split(buf, 8)?;
+                return Some((buf, Value::Ulong(read_u64_be(v))));
+            }
+            // byte, smallint, smalllong
+            0x51 | 0x54 | 0x55 => {
+                let (v, buf) = split(buf, 1)?;
+                return Some((buf, Value::Long(v[0] as i8 as i64)));
+            }
+            0x61 => {
+                let (v, buf) = split(buf, 2)?;
+                return Some((buf, Value::Long(read_u16_be(v) as i16 as i64)));
+            }
+            0x71 => {
+                let (v, buf) = split(buf, 4)?;
+                return Some((buf, Value::Long(read_u32_be(v) as i32 as i64)));
+            }
+            0x81 => {
+                let (v, buf) = split(buf, 8)?;
+                return Some((buf, Value::Long(read_u64_be(v) as i64)));
+            }
+            0xa0 | 0xb0 => {
+                let (buf, data) = variable(buf, code[0] == 0xb0)?;
+                return Some((buf, Value::Binary(data)));
+            }
+            0xa1 | 0xa3 | 0xb1 | 0xb3 => {
+                let (buf, data) = variable(buf, code[0] & 0xf0 == 0xb0)?;
+                let value = str::from_utf8(data).map(Value::Str).unwrap_or(Value::Other);
+                return Some((buf, value));
+            }
+            0xc0 | 0xd0 => return list(buf, code[0] == 0xd0, depth),
+            // maps and arrays are skipped
+            0xc1 | 0xe0 => variable(buf, false)?.0,
+            0xd1 | 0xf0 => variable(buf, true)?.0,
+            // float, decimal32, char
+            0x72 | 0x73 | 0x74 => split(buf, 4)?.1,
+            // double, decimal64, timestamp
+            0x82 | 0x83 | 0x84 => split(buf, 8)?.1,
+            // decimal128, uuid
+            0x94 | 0x98 => split(buf, 16)?.1,
+            _ => return None,
+        };
+        Some((rest, Value::Other))
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<(&[u8], Value)> {
+        decode_value(buf, 0)
+    }
+}
+
+impl AmqpLog {
+    const MAX_LINKS_PER_FLOW: usize = 64;
+    // deliveries logged for a disposition with a range of delivery-ids
+    const AMQP1_MAX_DISPOSITION_RANGE: u32 = 256;
+
+    // ref: https://docs.oasis-open.org/amqp/core/v1.0/os/amqp-core-transport-v1.0-os.html#section-amqp-frames
+    const AMQP1_FRAME_TYPE_AMQP: u8 = 0;
+    const AMQP1_FRAME_HEADER_SIZE: usize = 8;
+
+    // descriptors of described types in performatives
+    const AMQP1_ERROR: u64 = 0x1d;
+    const AMQP1_REJECTED: u64 = 0x25;
+    const AMQP1_RELEASED: u64 = 0x26;
+    const AMQP1_MODIFIED: u64 = 0x27;
+
+    fn parse_v1_0(
+        &mut self,
+        mut payload: &[u8],
+        param: &ParseParam,
+        vec: &mut Vec<L7ProtocolInfo>,
+    ) {
+        let from_client = param.direction == PacketDirection::ClientToServer;
+        loop {
+            // protocol headers are exchanged for both sasl and amqp layers
+            if payload.starts_with(AMQP1_HEADER) || payload.starts_with(AMQP1_SASL_HEADER) {
+                if from_client {
+                    vec.push(L7ProtocolInfo::AmqpInfo(AmqpInfo {
+                        is_protcol_header: true,
+                        version: AmqpVersion::V1_0,
+                        msg_type: LogMessageType::Session,
+                        resp_status: L7ResponseStatus::Ok,
+                        ..Default::default()
+                    }));
+                }
+                payload = &payload[AMQP1_HEADER.len()..];
+                continue;
+            }
+            if payload.len() < Self::AMQP1_FRAME_HEADER_SIZE {
+                break;
+            }
+            let size = read_u32_be(&payload[0..4]) as usize;
+            let data_offset = payload[4] as usize * 4;
+            let frame_type = payload[5];
+            let channel = read_u16_be(&payload[6..8]);
+            if data_offset < Self::AMQP1_FRAME_HEADER_SIZE || data_offset > size {
+                break;
+            }
+            let (frame, rest) = payload.split_at(size.min(payload.len()));
+            payload = rest;
+            // sasl frames are not logged
+            if frame_type != Self::AMQP1_FRAME_TYPE_AMQP {
+                continue;
+            }
+            // empty frames are used as heartbeats
+            let Some(body) = frame.get(data_offset..).filter(|b| !b.is_empty()) else {
+                continue;
+            };
+            self.parse_performative(body, from_client, channel, size as u32, vec);
+        }
+    }
+
+    fn parse_performative(
+        &mut self,
+        body: &[u8],
+        from_client: bool,
+        channel: u16,
+        mut size: u32,
+        vec: &mut Vec<L7ProtocolInfo>,
+    ) -> Option<()> {
+        let (_, value) = types::decode(body)?;
+        let (descriptor, _) = value.as_described()?;
+        let performative = Performative::try_from(descriptor).ok()?;
+
+        let mut info = AmqpInfo {
+            version: AmqpVersion::V1_0,
+            performative: Some(performative),
+            channel_id: channel,
+            payload_size: size,
+            msg_type: if from_client {
+                LogMessageType::Request
+            } else {
+                LogMessageType::Response
+            },
+            ..Default::default()
+        };
+        let mut last_delivery_id = None;
+        match performative {
+            Performative::Open => {
+                // hostname requested by client is the virtual host of the connection
+                if let Some(hostname) = value.field(1).as_str().filter(|_| from_client) {
+                    self.vhost = Some(hostname.to_string());
+                }
+            }
+            Performative::Attach => {
+                let handle = value.field(1).as_u64()? as u32;
+                // role: false for sender and true for receiver
+                let is_receiver = value.field(2).as_bool().unwrap_or_default();
+                let source = value.field(5).field(0).as_str();
+                let target = value.field(6).field(0).as_str();
+                // messages flow from server to client on a client receiver or a server sender
+                let address = if is_receiver == from_client {
+                    source.or(target)
+                } else {
+                    target.or(source)
+                };
+                if let Some(address) = address {
+                    self.links
+                        .put((from_client, channel, handle), address.to_string());
+                    info.address = Some(address.to_string());
+                }
+            }
+            // flow control is frequent and not logged
+            Performative::Flow => return None,
+            Performative::Transfer => {
+                let link = (from_client, channel, value.field(0).as_u64()? as u32);
+                info.address = self.links.get(&link).cloned();
+                // continuation frames of a multi-frame delivery omit delivery-id,
+                // the delivery is logged once on its last frame
+                let mut delivery = match (value.field(1).as_u64(), self.deliveries.pop(&link)) {
+                    (Some(id), _) => PendingDelivery {
+                        delivery_id: id as u32,
+                        settled: false,
+                        size: 0,
+                    },
+                    (None, Some(pending)) => pending,
+                    (None, None) => return None,
+                };
+                delivery.settled |= value.field(4).as_bool().unwrap_or_default();
+                delivery.size = delivery.size.saturating_add(size);
+                // aborted delivery is discarded by the receiver
+                if value.field(9).as_bool().unwrap_or_default() {
+                    return None;
+                }
+                if value.field(5).as_bool().unwrap_or_default() {
+                    self.deliveries.put(link, delivery);
+                    return None;
+                }
+                size = delivery.size;
+                info.payload_size = size;
+                info.delivery_id = Some(delivery.delivery_id);
+                info.msg_type = if delivery.settled {
+                    LogMessageType::Session
+                } else {
+                    LogMessageType::Request
+                };
+            }
+            Performative::Disposition => {
+                info.msg_type = LogMessageType::Response;
+                info.delivery_id = value.field(1).as_u64().map(|id| id as u32);
+                last_delivery_id = value.field(2).as_u64().map(|id| id as u32);
+                match value.field(4).as_described() {
+                    Some((Self::AMQP1_REJECTED, _)) => {
+                        info.resp_status = L7ResponseStatus::ClientError;
+                        let state = value.field(4);
+                        info.resp_text = Some(
+                            Self::amqp1_error(state.field(0))
+                                .map(|(_, text)| text)
+                                .unwrap_or_else(|| "rejected".to_string()),
+                        );
+                    }
+                    Some((Self::AMQP1_MODIFIED, _)) => {
+                        info.resp_status = L7ResponseStatus::ServerError;
+                        info.resp_text = Some("modified".to_string());
+                    }
+                    Some((Self::AMQP1_RELEASED, _)) => {
+                        info.resp_text = Some("released".to_string());
+                    }
+                    // accepted or received
+                    _ => (),
+                }
+            }
+            Performative::Detach => {
+                if let Some(handle) = value.field(0).as_u64() {
+                    self.links.pop(&(from_client, channel, handle as u32));
+                }
+                info.set_amqp1_error(value.field(2));
+            }
+            Performative::End | Performative::Close => info.set_amqp1_error(value.field(0)),
+            Performative::Begin => (),
+        }
+        if info.msg_type == LogMessageType::Response
+            && info.resp_status == L7ResponseStatus::Unknown
+        {
+            info.resp_status = L7ResponseStatus::Ok;
+        }
+        match info.msg_type {
+            LogMessageType::Response => info.resp_len = Some(size),
+            _ => {
+                info.req_len = Some(size);
+                info.req_type = Some(info.get_packet_type());
+            }
+        }
+        info.vhost = self.vhost.clone();
+        // a disposition settles deliveries from first to last, each is paired with its transfer
+        if let (Some(first), Some(last)) = (info.delivery_id, last_delivery_id) {
+            let count = last
+                .wrapping_sub(first)
+                .min(Self::AMQP1_MAX_DISPOSITION_RANGE - 1);
+            for i in 0..count {
+                vec.push(L7ProtocolInfo::AmqpInfo(AmqpInfo {
+                    delivery_id: Some(first.wrapping_add(i)),
+                    ..info.clone()
+                }));
+            }
+            info.delivery_id = Some(first.wrapping_add(count));
+        }
+        vec.push(L7ProtocolInfo::AmqpInfo(info));
+        Some(())
+    }
+
+    // condition and text of an error
+    fn amqp1_error(error: &types::Value) -> Option<(String, String)> {
+        match error.as_described() {
+            Some((Self::AMQP1_ERROR, _)) => {
+                let condition = error.field(0).as_str()?;
+                let text = match error.field(1).as_str() {
+                    Some(description) => format!("{}: {}", condition, description),
+                    None => condition.to_string(),
+                };
+                Some((condition.to_string(), text))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl L7ProtocolParserInterface for AmqpLog {
     fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
         if !param.ebpf_type.is_raw_protocol() {
@@ -942,6 +1499,10 @@ impl L7ProtocolParserInterface for AmqpLog {
             return None;
         }
         if payload.starts_with(AMQPHEADER) {
+            self.version = Some(AmqpVersion::V0_9_1);
+            Some(LogMessageType::Request)
+        } else if payload.starts_with(AMQP1_HEADER) || payload.starts_with(AMQP1_SASL_HEADER) {
+            self.version = Some(AmqpVersion::V1_0);
             Some(LogMessageType::Request)
         } else {
             None
@@ -953,7 +1514,12 @@ impl L7ProtocolParserInterface for AmqpLog {
 
         let mut offset = 0;
         let mut vec = Vec::new();
-        if payload.starts_with(AMQPHEADER) {
+        if self.version.is_none() {
+            self.version = AmqpVersion::detect(payload);
+        }
+        if self.version == Some(AmqpVersion::V1_0) {
+            self.parse_v1_0(payload, param, &mut vec);
+        } else if payload.starts_with(AMQPHEADER) {
             offset += AMQPHEADER.len();
             let info = AmqpInfo {
                 is_protcol_header: true,
@@ -964,7 +1530,7 @@ impl L7ProtocolParserInterface for AmqpLog {
             };
             vec.push(L7ProtocolInfo::AmqpInfo(info));
         }
-        loop {
+        while self.version != Some(AmqpVersion::V1_0) {
             let offset_begin = offset;
             let mut info = AmqpInfo::default();
             info.is_tls = param.is_tls();
@@ -1152,6 +1718,8 @@ impl L7ProtocolParserInterface for AmqpLog {
     fn reset(&mut self) {
         let mut s = Self::default();
         s.vhost = self.vhost.take();
+        s.version = self.version.take();
+        mem::swap(&mut s.links, &mut self.links);
         s.perf_stats = self.perf_stats();
         *self = s;
     }
@@ -1219,6 +1787,10 @@ mod tests {
                 .field_skip_default("queue", &info.queue)
                 .field_skip_default("exchange", &info.exchange)
                 .field_skip_default("routing_key", &info.routing_key)
+                .field_skip_default("version", &info.version)
+                .field_skip_default("performative", &info.performative)
+                .field_skip_default("address", &info.address)
+                .field_skip_default("delivery_id", &info.delivery_id)
                 .field_skip_default("trace_ids", &info.trace_ids)
                 .field_skip_default("span_id", &info.span_id)
                 .field_skip_default("req_type", &info.req_type)
@@ -1314,4 +1886,339 @@ mod tests {
             }
         }
     }
+
+    fn amqp1_frame(channel: u16, descriptor: u8, fields: &[Vec<u8>]) -> Vec<u8> {
+        let body = [&[0x00, 0x53, descriptor][..], &list8(fields)].concat();
+        let mut frame = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&[2, 0]);
+        frame.extend_from_slice(&channel.to_be_bytes());
+        frame.extend_from_slice(&body);
+        frame
+    }
+
+    fn list8(fields: &[Vec<u8>]) -> Vec<u8> {
+        let items = fields.concat();
+        [
+            &[0xc0, items.len() as u8 + 1, fields.len() as u8][..],
+            &items,
+        ]
+        .concat()
+    }
+
+    fn described(descriptor: u8, fields: &[Vec<u8>]) -> Vec<u8> {
+        [&[0x00, 0x53, descriptor][..], &list8(fields)].concat()
+    }
+
+    fn str8(code: u8, s: &str) -> Vec<u8> {
+        [&[code, s.len() as u8][..], s.as_bytes()].concat()
+    }
+
+    fn uint(v: u8) -> Vec<u8> {
+        vec![0x52, v]
+    }
+
+    const NULL: [u8; 1] = [0x40];
+    const TRUE: [u8; 1] = [0x41];
+    const FALSE: [u8; 1] = [0x42];
+
+    fn parse_amqp1(
+        amqp: &mut AmqpLog,
+        payload: &[u8],
+        direction: PacketDirection,
+    ) -> Vec<AmqpInfo> {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = direction;
+        match amqp.parse_payload(payload, &param).unwrap() {
+            L7ParseResult::Single(L7ProtocolInfo::AmqpInfo(info)) => vec![info],
+            L7ParseResult::Multi(m) => m
+                .into_iter()
+                .map(|i| match i {
+                    L7ProtocolInfo::AmqpInfo(info) => info,
+                    _ => unreachable!(),
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn check_amqp1() {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        let mut amqp = AmqpLog::default();
+        assert!(amqp.check_payload(AMQP1_SASL_HEADER, &param).is_some());
+        assert_eq!(amqp.version, Some(AmqpVersion::V1_0));
+        let mut amqp = AmqpLog::default();
+        assert!(amqp.check_payload(AMQPHEADER, &param).is_some());
+        assert_eq!(amqp.version, Some(AmqpVersion::V0_9_1));
+
+        let c2s = PacketDirection::ClientToServer;
+        let s2c = PacketDirection::ServerToClient;
+        let mut amqp = AmqpLog::default();
+
+        // protocol header and open in the same segment
+        let mut payload = AMQP1_HEADER.to_vec();
+        payload.extend(amqp1_frame(
+            0,
+            0x10,
+            &[str8(0xa1, "client-1"), str8(0xa1, "sb.example.com")],
+        ));
+        let infos = parse_amqp1(&mut amqp, &payload, c2s);
+        assert_eq!(infos.len(), 2);
+        assert!(infos[0].is_protcol_header);
+        assert_eq!(infos[1].performative, Some(Performative::Open));
+        assert_eq!(infos[1].msg_type, LogMessageType::Request);
+        assert_eq!(infos[1].vhost.as_deref(), Some("sb.example.com"));
+        let log = L7ProtocolSendLog::from(infos[1].clone());
+        assert_eq!(log.version.as_deref(), Some("v1.0"));
+        assert_eq!(log.req.req_type, "Open");
+
+        let mut payload = AMQP1_HEADER.to_vec();
+        payload.extend(amqp1_frame(0, 0x10, &[str8(0xa1, "broker")]));
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+        assert_eq!(infos[0].resp_status, L7ResponseStatus::Ok);
+
+        // sender link on handle 1, heartbeat frame is ignored
+        let mut payload = vec![0, 0, 0, 8, 2, 0, 0, 0];
+        payload.extend(amqp1_frame(
+            0,
+            0x12,
+            &[
+                str8(0xa1, "sender-link"),
+                uint(1),
+                FALSE.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                described(0x28, &[str8(0xa1, "client-1")]),
+                described(0x29, &[str8(0xa1, "orders")]),
+            ],
+        ));
+        let infos = parse_amqp1(&mut amqp, &payload, c2s);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].performative, Some(Performative::Attach));
+        assert_eq!(infos[0].endpoint.as_deref(), Some("orders"));
+
+        // unsettled transfer is paired with disposition by delivery-id
+        let mut payload = amqp1_frame(
+            0,
+            0x14,
+            &[
+                uint(1),
+                uint(7),
+                str8(0xa0, "\x00"),
+                uint(0),
+                FALSE.to_vec(),
+            ],
+        );
+        // message sections following the performative
+        payload.extend_from_slice(&[0x00, 0x53, 0x75, 0xa0, 0x02, b'h', b'i']);
+        let size = payload.len() as u32;
+        payload[..4].copy_from_slice(&size.to_be_bytes());
+        let infos = parse_amqp1(&mut amqp, &payload, c2s);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].session_id(), Some(7));
+        assert_eq!(infos[0].endpoint.as_deref(), Some("orders"));
+
+        let rejected = described(
+            0x25,
+            &[described(
+                0x1d,
+                &[
+                    str8(0xa3, "amqp:not-allowed"),
+                    str8(0xa1, "message too old"),
+                ],
+            )],
+        );
+        let payload = amqp1_frame(
+            0,
+            0x15,
+            &[TRUE.to_vec(), uint(7), uint(7), TRUE.to_vec(), rejected],
+        );
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Response);
+        assert_eq!(infos[0].session_id(), Some(7));
+        assert_eq!(infos[0].resp_status, L7ResponseStatus::ClientError);
+        assert_eq!(
+            infos[0].resp_text.as_deref(),
+            Some("amqp:not-allowed: message too old")
+        );
+
+        let payload = amqp1_frame(
+            0,
+            0x15,
+            &[
+                TRUE.to_vec(),
+                uint(8),
+                NULL.to_vec(),
+                TRUE.to_vec(),
+                described(0x27, &[TRUE.to_vec()]),
+            ],
+        );
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(infos[0].resp_status, L7ResponseStatus::ServerError);
+
+        // detach with error
+        let payload = amqp1_frame(
+            0,
+            0x16,
+            &[
+                uint(1),
+                TRUE.to_vec(),
+                described(0x1d, &[str8(0xa3, "amqp:internal-error")]),
+            ],
+        );
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(infos[0].performative, Some(Performative::Detach));
+        assert_eq!(infos[0].resp_status, L7ResponseStatus::ServerError);
+
+        // close without error
+        let infos = parse_amqp1(&mut amqp, &amqp1_frame(0, 0x18, &[]), s2c);
+        assert_eq!(infos[0].performative, Some(Performative::Close));
+        assert_eq!(infos[0].resp_status, L7ResponseStatus::Ok);
+    }
+
+    #[test]
+    fn check_amqp1_multi_frame_delivery() {
+        let c2s = PacketDirection::ClientToServer;
+        let s2c = PacketDirection::ServerToClient;
+        let mut amqp = AmqpLog::default();
+        amqp.version = Some(AmqpVersion::V1_0);
+
+        let payload = amqp1_frame(
+            0,
+            0x12,
+            &[
+                str8(0xa1, "sender-link"),
+                uint(1),
+                FALSE.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                described(0x29, &[str8(0xa1, "orders")]),
+            ],
+        );
+        assert_eq!(parse_amqp1(&mut amqp, &payload, c2s).len(), 1);
+
+        // continuation frames omit delivery-id, the delivery is logged on its last frame
+        let first = amqp1_frame(
+            0,
+            0x14,
+            &[
+                uint(1),
+                uint(9),
+                str8(0xa0, "\x01"),
+                uint(0),
+                FALSE.to_vec(),
+                TRUE.to_vec(),
+            ],
+        );
+        let middle = amqp1_frame(
+            0,
+            0x14,
+            &[
+                uint(1),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                TRUE.to_vec(),
+            ],
+        );
+        let last = amqp1_frame(0, 0x14, &[uint(1)]);
+        assert!(parse_amqp1(&mut amqp, &first, c2s).is_empty());
+        assert!(parse_amqp1(&mut amqp, &middle, c2s).is_empty());
+        let infos = parse_amqp1(&mut amqp, &last, c2s);
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].session_id(), Some(9));
+        assert_eq!(infos[0].endpoint.as_deref(), Some("orders"));
+        assert_eq!(
+            infos[0].req_len,
+            Some((first.len() + middle.len() + last.len()) as u32)
+        );
+
+        // continuation of an unknown delivery is ignored
+        assert!(parse_amqp1(&mut amqp, &last, c2s).is_empty());
+
+        // aborted delivery is not logged
+        let aborted = amqp1_frame(
+            0,
+            0x14,
+            &[
+                uint(1),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                FALSE.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                NULL.to_vec(),
+                TRUE.to_vec(),
+            ],
+        );
+        assert!(parse_amqp1(&mut amqp, &first, c2s).is_empty());
+        assert!(parse_amqp1(&mut amqp, &aborted, c2s).is_empty());
+
+        // one disposition settles deliveries 9 to 11
+        let payload = amqp1_frame(
+            0,
+            0x15,
+            &[
+                TRUE.to_vec(),
+                uint(9),
+                uint(11),
+                TRUE.to_vec(),
+                described(0x24, &[]),
+            ],
+        );
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(
+            infos.iter().map(|i| i.session_id()).collect::<Vec<_>>(),
+            vec![Some(9), Some(10), Some(11)]
+        );
+        assert!(infos.iter().all(
+            |i| i.msg_type == LogMessageType::Response && i.resp_status == L7ResponseStatus::Ok
+        ));
+
+        // delivery-ids are serial numbers and the range may wrap
+        let payload = amqp1_frame(
+            0,
+            0x15,
+            &[
+                TRUE.to_vec(),
+                vec![0x70, 0xff, 0xff, 0xff, 0xff],
+                uint(0),
+                TRUE.to_vec(),
+            ],
+        );
+        let infos = parse_amqp1(&mut amqp, &payload, s2c);
+        assert_eq!(
+            infos.iter().map(|i| i.session_id()).collect::<Vec<_>>(),
+            vec![Some(u32::MAX), Some(0)]
+        );
+    }
 }
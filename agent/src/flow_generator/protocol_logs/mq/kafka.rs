@@ -20,7 +20,9 @@ use log::debug;
 use lru::LruCache;
 use nom::{
     bytes::complete::take,
+    error::ErrorKind,
     number::complete::{be_i16, be_i32, be_i64, be_u16, be_u32},
+    IResult,
 };
 use num_enum::FromPrimitive;
 use serde::{Serialize, Serializer};
@@ -152,6 +154,68 @@ pub enum ApiKey {
     Unknown(u16),
 }
 
+impl ApiKey {
+    // first version of each api that uses request header v2 and response header v1 with tagged fields
+    // from `flexibleVersions` in:
+    //     https://github.com/apache/kafka/tree/trunk/clients/src/main/resources/common/message
+    fn first_flexible_version(&self) -> Option<u16> {
+        match self {
+            Self::Produce => Some(9),
+            Self::Fetch => Some(12),
+            Self::ListOffsets => Some(6),
+            Self::Metadata => Some(9),
+            Self::LeaderAndIsr => Some(4),
+            Self::StopReplica => Some(2),
+            Self::UpdateMetadata => Some(6),
+            Self::ControlledShutdown => Some(3),
+            Self::OffsetCommit => Some(8),
+            Self::OffsetFetch => Some(6),
+            Self::FindCoordinator => Some(3),
+            Self::JoinGroup => Some(6),
+            Self::Heartbeat => Some(4),
+            Self::LeaveGroup => Some(4),
+            Self::SyncGroup => Some(4),
+            Self::DescribeGroups => Some(5),
+            Self::ListGroups => Some(3),
+            Self::SaslHandshake => None,
+            Self::ApiVersions => Some(3),
+            Self::CreateTopics => Some(5),
+            Self::DeleteTopics => Some(4),
+            Self::DeleteRecords => Some(2),
+            Self::InitProducerId => Some(2),
+            Self::OffsetForLeaderEpoch => Some(4),
+            Self::AddPartitionsToTxn => Some(3),
+            Self::AddOffsetsToTxn => Some(3),
+            Self::EndTxn => Some(3),
+            Self::WriteTxnMarkers => Some(1),
+            Self::TxnOffsetCommit => Some(3),
+            Self::DescribeAcls => Some(2),
+            Self::CreateAcls => Some(2),
+            Self::DeleteAcls => Some(2),
+            Self::DescribeConfigs => Some(4),
+            Self::AlterConfigs => Some(2),
+            Self::AlterReplicaLogDirs => Some(2),
+            Self::DescribeLogDirs => Some(2),
+            Self::SaslAuthenticate => Some(2),
+            Self::CreatePartitions => Some(2),
+            Self::CreateDelegationToken => Some(2),
+            Self::RenewDelegationToken => Some(2),
+            Self::ExpireDelegationToken => Some(2),
+            Self::DescribeDelegationToken => Some(2),
+            Self::DeleteGroups => Some(2),
+            Self::ElectLeaders => Some(2),
+            Self::IncrementalAlterConfigs => Some(1),
+            Self::OffsetDelete => None,
+            Self::DescribeClientQuotas => Some(1),
+            Self::AlterClientQuotas => Some(1),
+            Self::BeginQuorumEpoch | Self::EndQuorumEpoch => Some(1),
+            Self::Unknown(_) => None,
+            // apis added after KIP-482 are flexible from the first version
+            _ => Some(0),
+        }
+    }
+}
+
 impl Default for ApiKey {
     fn default() -> Self {
         Self::Unknown(u16::MAX)
@@ -172,20 +236,21 @@ impl fmt::Display for Api {
 
 impl Api {
     fn header_versions(&self) -> Result<(u8, u8)> {
+        if matches!(self.key, ApiKey::Unknown(_)) {
+            return Err(Error::UnsupportedApi(*self));
+        }
+        let flexible = self
+            .key
+            .first_flexible_version()
+            .map(|v| self.version >= v)
+            .unwrap_or(false);
         match (self.key, self.version) {
-            (ApiKey::Produce, 9..) => Ok((2, 1)),
-            (ApiKey::Produce, 0..) => Ok((1, 0)),
-            (ApiKey::Fetch, 12..) => Ok((2, 1)),
-            (ApiKey::Fetch, 0..) => Ok((1, 0)),
-            (ApiKey::JoinGroup, 6..) => Ok((2, 1)),
-            (ApiKey::JoinGroup, 0..) => Ok((1, 0)),
-            (ApiKey::LeaveGroup, 4..) => Ok((2, 1)),
-            (ApiKey::LeaveGroup, 0..) => Ok((1, 0)),
-            (ApiKey::SyncGroup, 4..) => Ok((2, 1)),
-            (ApiKey::SyncGroup, 0..) => Ok((1, 0)),
-            (ApiKey::ApiVersions, 3..) => Ok((2, 0)),
-            (ApiKey::ApiVersions, 0..) => Ok((1, 0)),
-            _ => Err(Error::UnsupportedApi(*self)),
+            // the only request using header v0 without client_id
+            (ApiKey::ControlledShutdown, 0) => Ok((0, 0)),
+            // ApiVersions response always uses header v0 so that clients can parse it before negotiation
+            (ApiKey::ApiVersions, _) if flexible => Ok((2, 0)),
+            _ if flexible => Ok((2, 1)),
+            _ => Ok((1, 0)),
         }
     }
 
@@ -242,6 +307,9 @@ mod decoder {
     use prost::encoding::decode_varint;
 
     pub fn unsigned_varint(mut input: &[u8]) -> IResult<&[u8], u32> {
+        if input.is_empty() {
+            return Err(NomErr::Error(NomError::new(input, ErrorKind::Eof)));
+        }
         let data = &mut input;
         match decode_varint(data) {
             Ok(v) => {
@@ -446,14 +514,21 @@ mod decoder {
         }
     }
 
+    pub fn array_length(input: &[u8]) -> IResult<&[u8], u32> {
+        let (input, length) = be_i32(input)?;
+        Ok((input, length.max(0) as u32))
+    }
+
+    pub fn compact_array_length(input: &[u8]) -> IResult<&[u8], u32> {
+        let (input, length) = unsigned_varint(input)?;
+        Ok((input, length.saturating_sub(1)))
+    }
+
     pub fn array(
         input: &[u8],
         mut obj_decoder: impl FnMut(&[u8]) -> IResult<&[u8], ()>,
     ) -> IResult<&[u8], ()> {
-        let (mut input, length) = be_i32(input)?;
-        if length <= 0 {
-            return Ok((input, ()));
-        }
+        let (mut input, length) = array_length(input)?;
         for _ in 0..length {
             let (sub_offset, _) = obj_decoder(input)?;
             input = sub_offset;
@@ -465,11 +540,8 @@ mod decoder {
         input: &[u8],
         mut obj_decoder: impl FnMut(&[u8]) -> IResult<&[u8], ()>,
     ) -> IResult<&[u8], ()> {
-        let (mut input, length) = unsigned_varint(input)?;
-        if length <= 1 {
-            return Ok((input, ()));
-        }
-        for _ in 0..(length - 1) {
+        let (mut input, length) = compact_array_length(input)?;
+        for _ in 0..length {
             let (sub_offset, _) = obj_decoder(input)?;
             input = sub_offset;
         }
@@ -568,6 +640,12 @@ mod decoder {
     }
 }
 
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct TopicInfo {
+    pub name: String,
+    pub partition_count: u32,
+}
+
 #[derive(Serialize, Debug, Default, Clone)]
 pub struct KafkaInfo {
     msg_type: LogMessageType,
@@ -591,6 +669,9 @@ pub struct KafkaInfo {
     // Extract only from KAFKA_PRODUCE and KAFKA_FETCH
     #[serde(rename = "request_resource", skip_serializing_if = "value_is_default")]
     pub topic_name: String,
+    // all topics in KAFKA_PRODUCE and KAFKA_FETCH, at most `KafkaLog::MAX_TOPICS_PER_LOG`
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub topics: Vec<TopicInfo>,
     pub partition: i32,
     pub offset: i64,
     pub group_id: String,
@@ -691,6 +772,7 @@ impl KafkaInfo {
         self.msg_type = LogMessageType::Session;
         self.captured_response_byte = other.captured_response_byte;
         swap_if!(self, topic_name, is_empty, other);
+        swap_if!(self, topics, is_empty, other);
         swap_if!(self, resource, is_none, other);
         swap_if!(self, endpoint, is_none, other);
         swap_if!(self, command, is_none, other);
@@ -727,6 +809,26 @@ impl KafkaInfo {
         }
     }
 
+    fn add_topic(&mut self, name: &str, partition_count: u32) {
+        if self.topic_name.is_empty() {
+            self.topic_name = name.to_string();
+        }
+        if self.topics.len() < KafkaLog::MAX_TOPICS_PER_LOG {
+            self.topics.push(TopicInfo {
+                name: name.to_string(),
+                partition_count,
+            });
+        }
+    }
+
+    // the first non-zero partition error code is kept for error classification
+    fn set_partition_error_code(&mut self, error_code: i16) {
+        match self.status_code.as_ref() {
+            Some(c) if *c != 0 => (),
+            _ => self.status_code = Some(error_code as i32),
+        }
+    }
+
     fn has_trace_info(&self) -> bool {
         !self.trace_ids.is_empty() && !self.span_id.is_empty()
     }
@@ -746,6 +848,17 @@ impl From<KafkaInfo> for L7ProtocolSendLog {
                 val: f.group_id,
             });
         }
+        if !f.topics.is_empty() {
+            attributes.push(KeyVal {
+                key: "topics".to_string(),
+                val: f
+                    .topics
+                    .iter()
+                    .map(|t| format!("{}:{}", t.name, t.partition_count))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            });
+        }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
@@ -936,6 +1049,7 @@ impl L7ProtocolParserInterface for KafkaLog {
 
 impl KafkaLog {
     const MAX_SESSION_PER_FLOW: usize = 32;
+    const MAX_TOPICS_PER_LOG: usize = 16;
     const CODE_APIKEY_NOT_SUPPORTED: i32 = -2;
     const EXCEPTION_APIKEY_NOT_SUPPORTED: &str = "Type not yet inspected by DeepFlow";
 
@@ -961,12 +1075,11 @@ impl KafkaLog {
 
         if info.api.version >= 9 {
             // topic data
-            let _ = decoder::compact_array(buffer, |input| {
+            let result = decoder::compact_array(buffer, |input| {
                 // topic data -> name
                 let (input, name) = decoder::compact_string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = name.to_string();
-                }
+                let partition_count = decoder::compact_array_length(input)?.1;
+                info.add_topic(name, partition_count);
 
                 // topic_data -> partition_data (p_data)
                 let input = decoder::compact_array(input, |input| {
@@ -990,17 +1103,17 @@ impl KafkaLog {
                 let input = decoder::tagged_fields(input)?.0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
 
             // _tagged_fields ignored
         } else {
             // topic data
-            let _ = decoder::array(buffer, |input| {
+            let result = decoder::array(buffer, |input| {
                 // topic data -> name
                 let (input, name) = decoder::string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = name.to_string();
-                }
+                let partition_count = decoder::array_length(input)?.1;
+                info.add_topic(name, partition_count);
 
                 // topic_data -> partition_data (p_data)
                 let input = decoder::array(input, |input| {
@@ -1019,7 +1132,8 @@ impl KafkaLog {
                 .0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
         };
 
         Ok(())
@@ -1030,12 +1144,11 @@ impl KafkaLog {
 
         if info.api.version >= 9 {
             // responses
-            let _ = decoder::compact_array(buffer, |input| {
+            let result = decoder::compact_array(buffer, |input| {
                 // responses -> name
                 let (input, name) = decoder::compact_string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = name.to_string();
-                }
+                let partition_count = decoder::compact_array_length(input)?.1;
+                info.add_topic(name, partition_count);
 
                 // responses -> partition_responses (pr)
                 let input = decoder::compact_array(input, |mut input| {
@@ -1044,7 +1157,7 @@ impl KafkaLog {
                     // pr -> error_code
                     let error_code = be_i16(input)?;
                     input = error_code.0;
-                    info.status_code = Some(error_code.1 as i32);
+                    info.set_partition_error_code(error_code.1);
                     // pr -> base_offset
                     (input, info.offset) = be_i64(input)?;
                     // pr -> log_append_time_ms
@@ -1076,17 +1189,17 @@ impl KafkaLog {
                 let input = decoder::tagged_fields(input)?.0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
 
             // throttle_time_ms, _tagged_fields ignored
         } else {
             // responses
-            let _ = decoder::array(buffer, |input| {
+            let result = decoder::array(buffer, |input| {
                 // responses -> name
                 let (input, name) = decoder::string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = name.to_string();
-                }
+                let partition_count = decoder::array_length(input)?.1;
+                info.add_topic(name, partition_count);
 
                 // responses -> partition_responses (pr)
                 let input = decoder::array(input, |mut input| {
@@ -1095,7 +1208,7 @@ impl KafkaLog {
                     // pr -> error_code
                     let error_code = be_i16(input)?;
                     input = error_code.0;
-                    info.status_code = Some(error_code.1 as i32);
+                    info.set_partition_error_code(error_code.1);
                     // pr -> base_offset
                     (input, info.offset) = be_i64(input)?;
 
@@ -1129,7 +1242,8 @@ impl KafkaLog {
                 .0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
 
             // throttle_time_ms ignored
         }
@@ -1156,20 +1270,18 @@ impl KafkaLog {
 
         if info.api.version >= 12 {
             // topics
-            let _ = decoder::compact_array(buffer, |input| {
+            let result = decoder::compact_array(buffer, |input| {
                 let input = if info.api.version >= 13 {
                     // topic -> topic_id
                     let (input, topic_id) = decoder::uuid(input)?;
-                    if info.topic_name.is_empty() {
-                        info.topic_name = topic_id.hyphenated().to_string();
-                    }
+                    let partition_count = decoder::compact_array_length(input)?.1;
+                    info.add_topic(&topic_id.hyphenated().to_string(), partition_count);
                     input
                 } else {
                     // topic -> topic
                     let (input, topic) = decoder::compact_string(input)?;
-                    if info.topic_name.is_empty() {
-                        info.topic_name = topic.to_string();
-                    }
+                    let partition_count = decoder::compact_array_length(input)?.1;
+                    info.add_topic(topic, partition_count);
                     input
                 };
 
@@ -1202,16 +1314,16 @@ impl KafkaLog {
 
                 Ok((input, ()))
             });
+            Self::stop_on_truncation(result)?;
 
             // [forgotten_topics_data], rack_id, _tagged_fields ignored
         } else {
             // topics
-            let _ = decoder::array(buffer, |input| {
+            let result = decoder::array(buffer, |input| {
                 // topic -> topic
                 let (input, topic) = decoder::string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = topic.to_string();
-                }
+                let partition_count = decoder::array_length(input)?.1;
+                info.add_topic(topic, partition_count);
 
                 // topic -> partitions
                 let input = decoder::array(input, |input| {
@@ -1242,6 +1354,7 @@ impl KafkaLog {
 
                 Ok((input, ()))
             });
+            Self::stop_on_truncation(result)?;
 
             // [forgotten_topics_data], rack_id ignored
         }
@@ -1267,20 +1380,18 @@ impl KafkaLog {
 
         // responses
         if info.api.version >= 12 {
-            let _ = decoder::compact_array(buffer, |input| {
+            let result = decoder::compact_array(buffer, |input| {
                 let input = if info.api.version >= 13 {
                     // response -> topic_id
                     let (input, topic_id) = decoder::uuid(input)?;
-                    if info.topic_name.is_empty() {
-                        info.topic_name = topic_id.hyphenated().to_string();
-                    }
+                    let partition_count = decoder::compact_array_length(input)?.1;
+                    info.add_topic(&topic_id.hyphenated().to_string(), partition_count);
                     input
                 } else {
                     // response -> topic
                     let (input, topic) = decoder::compact_string(input)?;
-                    if info.topic_name.is_empty() {
-                        info.topic_name = topic.to_string();
-                    }
+                    let partition_count = decoder::compact_array_length(input)?.1;
+                    info.add_topic(topic, partition_count);
                     input
                 };
 
@@ -1292,10 +1403,7 @@ impl KafkaLog {
 
                     // partition -> error_code
                     let (input, error_code) = be_i16(input)?;
-                    match info.status_code.as_ref() {
-                        Some(c) if *c != 0 => (),
-                        _ => info.status_code = Some(error_code as i32),
-                    }
+                    info.set_partition_error_code(error_code);
 
                     // high_watermark, last_stable_offset, log_start_offset
                     let skip = 8 + 8 + 8;
@@ -1331,16 +1439,16 @@ impl KafkaLog {
                 let input = decoder::tagged_fields(input)?.0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
 
             // _tagged_fields ignored
         } else {
-            let _ = decoder::array(buffer, |input| {
+            let result = decoder::array(buffer, |input| {
                 // response -> topic
                 let (input, topic) = decoder::string(input)?;
-                if info.topic_name.is_empty() {
-                    info.topic_name = topic.to_string();
-                }
+                let partition_count = decoder::array_length(input)?.1;
+                info.add_topic(topic, partition_count);
 
                 // response -> partitions
                 let input = decoder::array(input, |input| {
@@ -1350,10 +1458,7 @@ impl KafkaLog {
 
                     // partition -> error_code
                     let (input, error_code) = be_i16(input)?;
-                    match info.status_code.as_ref() {
-                        Some(c) if *c != 0 => (),
-                        _ => info.status_code = Some(error_code as i32),
-                    }
+                    info.set_partition_error_code(error_code);
 
                     // partition -> high_watermark
                     let mut input = be_i64(input)?.0;
@@ -1393,7 +1498,8 @@ impl KafkaLog {
                 .0;
 
                 Ok((input, ()))
-            })?;
+            });
+            Self::stop_on_truncation(result)?;
         }
 
         Ok(())
@@ -1515,6 +1621,15 @@ impl KafkaLog {
         Ok(())
     }
 
+    // topics are decoded until the captured payload ends, which may be cut short by `payload_truncation`
+    fn stop_on_truncation<T>(result: IResult<&[u8], T>) -> Result<()> {
+        match result {
+            Ok(_) => Ok(()),
+            Err(nom::Err::Error(e)) if e.code == ErrorKind::Eof => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     fn decode_trace_info(headers: &[(&str, &str)], info: &mut KafkaInfo) {
         for (k, v) in headers {
             let tp = TraceType::from(*k);
@@ -1558,15 +1673,21 @@ impl KafkaLog {
             self.sessions.push(info.correlation_id.unwrap(), info.api);
         }
 
-        let (payload, client_id) = decoder::nullable_string(payload)?;
-        if !client_id.is_ascii() {
-            return Err(Error::ParseFailed(
-                format!("client id {} is not ascii", client_id).into(),
-            ));
-        }
-        info.client_id = client_id.to_string();
+        let header_version = info.api.request_header_version()?;
+        let payload = if header_version >= 1 {
+            let (payload, client_id) = decoder::nullable_string(payload)?;
+            if !client_id.is_ascii() {
+                return Err(Error::ParseFailed(
+                    format!("client id {} is not ascii", client_id).into(),
+                ));
+            }
+            info.client_id = client_id.to_string();
+            payload
+        } else {
+            payload
+        };
 
-        let payload = if info.api.request_header_version()? == 2 {
+        let payload = if header_version == 2 {
             decoder::tagged_fields(payload)?.0
         } else {
             payload
@@ -1790,6 +1911,245 @@ mod tests {
         }
     }
 
+    fn frame(header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut payload = ((header.len() + body.len()) as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(header);
+        payload.extend_from_slice(body);
+        payload
+    }
+
+    fn request_header_v2(api_key: u16, version: u16, correlation_id: i32) -> Vec<u8> {
+        let mut header = api_key.to_be_bytes().to_vec();
+        header.extend_from_slice(&version.to_be_bytes());
+        header.extend_from_slice(&correlation_id.to_be_bytes());
+        header.extend_from_slice(&4u16.to_be_bytes());
+        header.extend_from_slice(b"test");
+        // _tagged_fields
+        header.push(0);
+        header
+    }
+
+    fn compact_string(s: &str) -> Vec<u8> {
+        let mut buf = vec![s.len() as u8 + 1];
+        buf.extend_from_slice(s.as_bytes());
+        buf
+    }
+
+    fn produce_v9_request_body(topics: &[(&str, &[i32])]) -> Vec<u8> {
+        // transactional_id, acks, timeout_ms
+        let mut body = vec![0, 0xff, 0xff, 0, 0, 0x75, 0x30];
+        body.push(topics.len() as u8 + 1);
+        for (name, partitions) in topics {
+            body.extend_from_slice(&compact_string(name));
+            body.push(partitions.len() as u8 + 1);
+            for p in partitions.iter() {
+                body.extend_from_slice(&p.to_be_bytes());
+                // records, _tagged_fields
+                body.extend_from_slice(&[0, 0]);
+            }
+            // _tagged_fields
+            body.push(0);
+        }
+        // _tagged_fields
+        body.push(0);
+        body
+    }
+
+    #[test]
+    fn check_produce_v9_topics() {
+        let mut kafka = KafkaLog::default();
+        let payload = frame(
+            &request_header_v2(0, 9, 7),
+            &produce_v9_request_body(&[("orders", &[0, 1, 2]), ("payments", &[3])]),
+        );
+
+        let mut info = KafkaInfo::default();
+        kafka
+            .parse(&payload, PacketDirection::ClientToServer, &mut info)
+            .unwrap();
+        assert_eq!(info.topic_name, "orders");
+        assert_eq!(
+            info.topics,
+            vec![
+                TopicInfo {
+                    name: "orders".to_string(),
+                    partition_count: 3,
+                },
+                TopicInfo {
+                    name: "payments".to_string(),
+                    partition_count: 1,
+                },
+            ]
+        );
+
+        // topic decoding stops where the captured payload ends
+        let truncated = &payload[..payload.len() - 14];
+        let mut info = KafkaInfo::default();
+        kafka
+            .parse(truncated, PacketDirection::ClientToServer, &mut info)
+            .unwrap();
+        assert_eq!(info.topic_name, "orders");
+        assert_eq!(info.topics.len(), 1);
+
+        // error code of any partition marks the response as failed
+        let mut body = vec![3];
+        for (name, partitions) in [
+            ("orders", [(0i32, 0i16), (1, 6)]),
+            ("payments", [(3, 0), (4, 0)]),
+        ] {
+            body.extend_from_slice(&compact_string(name));
+            body.push(partitions.len() as u8 + 1);
+            for (index, error_code) in partitions {
+                body.extend_from_slice(&index.to_be_bytes());
+                body.extend_from_slice(&error_code.to_be_bytes());
+                // base_offset, log_append_time_ms, log_start_offset
+                body.extend_from_slice(&[0; 24]);
+                // record_errors, error_message, _tagged_fields
+                body.extend_from_slice(&[1, 0, 0]);
+            }
+            // _tagged_fields
+            body.push(0);
+        }
+        // throttle_time_ms, _tagged_fields
+        body.extend_from_slice(&[0, 0, 0, 0, 0]);
+        let payload = frame(&[0, 0, 0, 7, 0], &body);
+        let mut info = KafkaInfo::default();
+        kafka
+            .parse(&payload, PacketDirection::ServerToClient, &mut info)
+            .unwrap();
+        assert_eq!(info.topics.len(), 2);
+        assert_eq!(info.status_code, Some(6));
+        assert_eq!(info.status, L7ResponseStatus::ServerError);
+    }
+
+    #[test]
+    fn check_fetch_v13_topics() {
+        let topic_ids = [
+            uuid::Uuid::from_u128(0x0123456789abcdef0123456789abcdef),
+            uuid::Uuid::from_u128(0xfedcba9876543210fedcba9876543210),
+        ];
+        // replica_id, max_wait_ms, min_bytes, max_bytes, isolation_level, session_id, session_epoch
+        let mut body = vec![0xff, 0xff, 0xff, 0xff, 0, 0, 0x01, 0xf4, 0, 0, 0, 1];
+        body.extend_from_slice(&[0x03, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        body.push(topic_ids.len() as u8 + 1);
+        for (i, topic_id) in topic_ids.iter().enumerate() {
+            body.extend_from_slice(topic_id.as_bytes());
+            body.push(i as u8 + 2);
+            for p in 0..=i as i32 {
+                // partition, current_leader_epoch
+                body.extend_from_slice(&p.to_be_bytes());
+                body.extend_from_slice(&0i32.to_be_bytes());
+                // fetch_offset
+                body.extend_from_slice(&42i64.to_be_bytes());
+                // last_fetched_epoch, log_start_offset, partition_max_bytes, _tagged_fields
+                body.extend_from_slice(&[0; 16]);
+                body.push(0);
+            }
+            // _tagged_fields
+            body.push(0);
+        }
+        // forgotten_topics_data, rack_id, _tagged_fields
+        body.extend_from_slice(&[1, 1, 0]);
+        let payload = frame(&request_header_v2(1, 13, 9), &body);
+
+        let mut kafka = KafkaLog::default();
+        let mut info = KafkaInfo::default();
+        kafka.request(&payload, true, &mut info).unwrap();
+        assert!(info.check());
+        assert_eq!(info.topic_name, topic_ids[0].hyphenated().to_string());
+        assert_eq!(info.offset, 42);
+        let topics = info
+            .topics
+            .iter()
+            .map(|t| (t.name.clone(), t.partition_count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            topics,
+            vec![
+                (topic_ids[0].hyphenated().to_string(), 1),
+                (topic_ids[1].hyphenated().to_string(), 2),
+            ]
+        );
+
+        // truncated in the middle of the second topic id
+        let truncated = &payload[..payload.len() - 80];
+        let mut info = KafkaInfo::default();
+        kafka
+            .parse(truncated, PacketDirection::ClientToServer, &mut info)
+            .unwrap();
+        assert_eq!(info.topics.len(), 1);
+        assert_eq!(info.topics[0].partition_count, 1);
+    }
+
+    #[test]
+    fn check_flexible_headers() {
+        // header layout of kafka-clients 3.6 with a tagged field appended to request header v2
+        let mut header = request_header_v2(11, 9, 21);
+        *header.last_mut().unwrap() = 1;
+        header.extend_from_slice(&[0, 3, b'a', b'b', b'c']);
+        let mut body = compact_string("group-1");
+        // session_timeout_ms, rebalance_timeout_ms
+        body.extend_from_slice(&[0, 0, 0x27, 0x10, 0, 0x04, 0x93, 0xe0]);
+        let payload = frame(&header, &body);
+
+        let mut kafka = KafkaLog::default();
+        let mut info = KafkaInfo::default();
+        kafka
+            .parse(&payload, PacketDirection::ClientToServer, &mut info)
+            .unwrap();
+        assert_eq!(info.client_id, "test");
+        assert_eq!(info.group_id, "group-1");
+
+        // heartbeat body is not decoded, but header is parsed for correlation
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = PacketDirection::ClientToServer;
+        let payload = frame(&request_header_v2(12, 4, 22), &compact_string("group-1"));
+        let info = kafka.parse_payload(&payload, &param).unwrap();
+        let L7ProtocolInfo::KafkaInfo(info) = info.unwrap_single() else {
+            unreachable!()
+        };
+        assert_eq!(info.correlation_id, Some(22));
+        assert_eq!(info.command.as_deref(), Some("Heartbeat"));
+
+        // response header v1 with a tagged field
+        param.direction = PacketDirection::ServerToClient;
+        let payload = frame(&[0, 0, 0, 22, 1, 0, 1, 0xff], &[0, 0, 0, 0, 0, 0, 0]);
+        let info = kafka.parse_payload(&payload, &param).unwrap();
+        let L7ProtocolInfo::KafkaInfo(info) = info.unwrap_single() else {
+            unreachable!()
+        };
+        assert_eq!(info.correlation_id, Some(22));
+        assert_eq!(info.command.as_deref(), Some("Heartbeat"));
+        let log = L7ProtocolSendLog::from(info);
+        assert_eq!(log.version.as_deref(), Some("4"));
+
+        // legacy header v1 is kept for old versions
+        let mut header = vec![0, 12, 0, 3, 0, 0, 0, 23, 0, 4];
+        header.extend_from_slice(b"test");
+        let mut kafka = KafkaLog::default();
+        let mut info = KafkaInfo::default();
+        let _ = kafka.parse(
+            &frame(&header, &[0, 7]),
+            PacketDirection::ClientToServer,
+            &mut info,
+        );
+        assert_eq!(info.client_id, "test");
+        assert_eq!(
+            kafka.sessions.peek(&23).map(|api| api.to_string()),
+            Some("Heartbeat v3".to_string())
+        );
+    }
+
     fn run_perf(pcap: &str) -> L7PerfStats {
         let rrt_cache = Rc::new(RefCell::new(L7PerfCache::new(100)));
         let mut kafka = KafkaLog::default();
@@ -14,9 +14,13 @@
  * limitations under the License.
  */
 
-use std::fmt::{self, Write};
+use std::{
+    fmt::{self, Write},
+    num::NonZeroUsize,
+};
 
 use log::{debug, warn};
+use lru::LruCache;
 use nom::{
     bits, bytes,
     combinator::map_res,
@@ -29,18 +33,18 @@ use serde::{Serialize, Serializer};
 use crate::{
     common::{
         enums::IpProtocol,
-        flow::{L7PerfStats, L7Protocol},
+        flow::{L7PerfStats, L7Protocol, PacketDirection},
         l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
         meta_packet::ApplicationFlags,
     },
-    config::handler::LogParserConfig,
+    config::{config::ExtraLogFieldsInfo, handler::LogParserConfig},
     flow_generator::{
         error::{Error, Result},
         protocol_logs::{
-            pb_adapter::{L7ProtocolSendLog, L7Request, L7Response},
-            set_captured_byte, swap_if, value_is_default, value_is_negative, AppProtoHead,
-            L7ResponseStatus,
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
+            serialize_attributes, set_captured_byte, swap_if, value_is_default, value_is_negative,
+            AppProtoHead, L7ResponseStatus,
         },
     },
 };
@@ -84,6 +88,15 @@ pub struct MqttInfo {
     is_on_blacklist: bool,
     #[serde(skip)]
     endpoint: Option<String>,
+
+    // v5 only
+    #[serde(rename = "response_exception", skip_serializing_if = "Option::is_none")]
+    pub reason_string: Option<String>,
+    #[serde(
+        serialize_with = "serialize_attributes",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    attributes: Vec<KeyVal>,
 }
 
 impl L7ProtocolInfoInterface for MqttInfo {
@@ -151,6 +164,8 @@ impl Default for MqttInfo {
             captured_response_byte: 0,
             is_on_blacklist: false,
             endpoint: None,
+            reason_string: None,
+            attributes: vec![],
         }
     }
 }
@@ -204,6 +219,8 @@ impl MqttInfo {
             _ => (),
         }
         swap_if!(self, endpoint, is_none, other);
+        swap_if!(self, reason_string, is_none, other);
+        self.attributes.append(&mut other.attributes);
         if other.is_on_blacklist {
             self.is_on_blacklist = other.is_on_blacklist;
         }
@@ -218,6 +235,34 @@ impl MqttInfo {
         }
     }
 
+    fn set_properties(&mut self, props: &Properties, fields: Option<&[ExtraLogFieldsInfo]>) {
+        if let Some(reason) = props.reason_string {
+            self.reason_string = Some(reason.to_string());
+        }
+        let Some(fields) = fields else {
+            return;
+        };
+        for f in fields {
+            let val = match f.field_name.as_str() {
+                "response_topic" => props.response_topic.map(str::to_string),
+                "correlation_data" => props.correlation_data.map(|data| {
+                    // correlation data is binary, keep it readable when it is printable text
+                    match std::str::from_utf8(data) {
+                        Ok(s) if !s.contains(char::is_control) => s.to_string(),
+                        _ => hex::encode(data),
+                    }
+                }),
+                _ => None,
+            };
+            if let Some(val) = val {
+                self.attributes.push(KeyVal {
+                    key: f.field_name.clone(),
+                    val,
+                });
+            }
+        }
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::MQTT) {
             self.is_on_blacklist = t.request_type.is_on_blacklist(self.pkt_type.as_str())
@@ -260,8 +305,17 @@ impl From<MqttInfo> for L7ProtocolSendLog {
             resp: L7Response {
                 status: f.status,
                 code: f.code,
+                exception: f.reason_string.unwrap_or_default(),
                 ..Default::default()
             },
+            ext_info: if f.attributes.is_empty() {
+                None
+            } else {
+                Some(ExtendedInfo {
+                    attributes: Some(f.attributes),
+                    ..Default::default()
+                })
+            },
             flags,
             ..Default::default()
         }
@@ -280,12 +334,27 @@ impl From<&MqttInfo> for LogCache {
     }
 }
 
-#[derive(Default)]
 pub struct MqttLog {
     msg_type: LogMessageType,
     status: L7ResponseStatus,
     version: u8,
     perf_stats: Vec<L7PerfStats>,
+    // v5 topic aliases are assigned separately by client and server, key is (from_client, alias)
+    topic_aliases: LruCache<(bool, u16), String>,
+}
+
+impl Default for MqttLog {
+    fn default() -> Self {
+        Self {
+            msg_type: Default::default(),
+            status: Default::default(),
+            version: 0,
+            perf_stats: vec![],
+            topic_aliases: LruCache::new(
+                NonZeroUsize::new(Self::MAX_TOPIC_ALIASES_PER_FLOW).unwrap(),
+            ),
+        }
+    }
 }
 
 impl L7ProtocolParserInterface for MqttLog {
@@ -349,6 +418,7 @@ impl L7ProtocolParserInterface for MqttLog {
     fn reset(&mut self) {
         let mut s = Self::default();
         s.version = self.version;
+        std::mem::swap(&mut s.topic_aliases, &mut self.topic_aliases);
         s.perf_stats = self.perf_stats();
         *self = s;
     }
@@ -359,17 +429,27 @@ impl L7ProtocolParserInterface for MqttLog {
 }
 
 impl MqttLog {
+    const PROTOCOL_LEVEL_V5: u8 = 5;
+    const MAX_TOPIC_ALIASES_PER_FLOW: usize = 64;
+
     fn parse_mqtt_info(
         &mut self,
         mut payload: &[u8],
-        parse_log: bool,
+        param: &ParseParam,
     ) -> Result<Vec<L7ProtocolInfo>> {
-        // 现在只支持MQTT 3.1.1解析，不支持v5.0
-        // Now only supports MQTT 3.1.1 parsing, not support v5.0
-        if self.version != 0 && self.version != 4 {
-            warn!("cannot parse packet, log parser only support to parse MQTT V3.1.1 packet");
+        // 现在只支持MQTT 3.1.1和v5.0解析，不支持v3.1
+        // Now only supports MQTT 3.1.1 and v5.0 parsing, not support v3.1
+        if self.version != 0 && self.version != 4 && self.version != Self::PROTOCOL_LEVEL_V5 {
+            warn!(
+                "cannot parse packet, log parser only support to parse MQTT V3.1.1 and V5.0 packet"
+            );
             return Err(Error::MqttLogParseFailed);
         }
+        let parse_log = param.parse_log;
+        let from_client = param.direction == PacketDirection::ClientToServer;
+        let extra_fields = param
+            .parse_config
+            .map(|c| c.l7_log_dynamic.extra_log_fields.mqtt.as_slice());
         let mut infos = vec![];
         /*
            FIXME
@@ -396,19 +476,28 @@ impl MqttLog {
                     info.req_msg_size = Some(header.remaining_length as u32);
                     info.pkt_type = header.kind;
                     self.version = version;
+                    // topic aliases only live as long as the network connection
+                    self.topic_aliases.clear();
                 }
                 PacketKind::Connack => {
-                    let (_, return_code) =
+                    let (rest, return_code) =
                         parse_connack_packet(input).map_err(|_| Error::MqttLogParseFailed)?;
                     info.code = Some(return_code as i32);
                     info.version = self.version;
                     self.msg_type = LogMessageType::Response;
                     info.res_msg_size = Some(header.remaining_length as u32);
                     info.pkt_type = header.kind;
-                    self.status = self.parse_status_code(return_code);
+                    if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        self.status = self.parse_reason_code(return_code);
+                        if let Ok((_, props)) = mqtt_properties(rest) {
+                            info.set_properties(&props, extra_fields);
+                        }
+                    } else {
+                        self.status = self.parse_status_code(return_code);
+                    }
                 }
                 PacketKind::Publish { dup, qos, .. } => {
-                    let (_, topic_name) =
+                    let (rest, topic_name) =
                         mqtt_string(input).map_err(|_| Error::MqttLogParseFailed)?;
                     if dup && qos == QualityOfService::AtMostOnce {
                         debug!("mqtt publish packet has invalid dup flags={}", dup);
@@ -424,17 +513,45 @@ impl MqttLog {
                         self.msg_type = LogMessageType::Response;
                         info.res_msg_size = Some(header.remaining_length as u32);
                     };
-                    info.publish_topic.replace(topic_name.to_string());
+                    let mut topic_name = topic_name.to_string();
+                    if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        let rest = if qos == QualityOfService::AtMostOnce {
+                            rest
+                        } else {
+                            rest.get(2..).unwrap_or_default()
+                        };
+                        if let Ok((_, props)) = mqtt_properties(rest) {
+                            if let Some(alias) = props.topic_alias {
+                                self.resolve_topic_alias(&mut topic_name, from_client, alias);
+                            }
+                            info.set_properties(&props, extra_fields);
+                        }
+                    }
+                    info.publish_topic.replace(topic_name);
                     info.pkt_type = header.kind;
                     info.version = self.version;
                 }
                 PacketKind::Subscribe => {
                     // 跳过解析报文标识符
                     // skip parsing packet identifier
-                    let (_, (_, result)) = mqtt_packet_identifier
-                        .and(mqtt_subscription_requests)
-                        .parse(input)
-                        .map_err(|_| Error::MqttLogParseFailed)?;
+                    let result = if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        let body = input
+                            .get(..header.remaining_length as usize)
+                            .unwrap_or(input);
+                        let (_, ((_, props), result)) = mqtt_packet_identifier
+                            .and(mqtt_properties)
+                            .and(mqtt_subscription_requests_v5)
+                            .parse(body)
+                            .map_err(|_| Error::MqttLogParseFailed)?;
+                        info.set_properties(&props, extra_fields);
+                        result
+                    } else {
+                        let (_, (_, result)) = mqtt_packet_identifier
+                            .and(mqtt_subscription_requests)
+                            .parse(input)
+                            .map_err(|_| Error::MqttLogParseFailed)?;
+                        result
+                    };
                     self.msg_type = LogMessageType::Request;
                     info.req_msg_size = Some(header.remaining_length as u32);
                     info.pkt_type = header.kind;
@@ -450,10 +567,24 @@ impl MqttLog {
                     );
                 }
                 PacketKind::Unsubscribe => {
-                    let (_, (_, reqs)) = mqtt_packet_identifier
-                        .and(mqtt_unsubscription_requests)
-                        .parse(input)
-                        .map_err(|_| Error::MqttLogParseFailed)?;
+                    let reqs = if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        let body = input
+                            .get(..header.remaining_length as usize)
+                            .unwrap_or(input);
+                        let (_, ((_, props), reqs)) = mqtt_packet_identifier
+                            .and(mqtt_properties)
+                            .and(mqtt_unsubscription_requests)
+                            .parse(body)
+                            .map_err(|_| Error::MqttLogParseFailed)?;
+                        info.set_properties(&props, extra_fields);
+                        reqs
+                    } else {
+                        let (_, (_, reqs)) = mqtt_packet_identifier
+                            .and(mqtt_unsubscription_requests)
+                            .parse(input)
+                            .map_err(|_| Error::MqttLogParseFailed)?;
+                        reqs
+                    };
                     self.msg_type = LogMessageType::Request;
                     info.req_msg_size = Some(header.remaining_length as u32);
                     info.pkt_type = header.kind;
@@ -472,6 +603,11 @@ impl MqttLog {
                     info.version = self.version;
                     info.req_msg_size = Some(header.remaining_length as u32);
                     self.msg_type = LogMessageType::Request;
+                    if self.version == Self::PROTOCOL_LEVEL_V5 && header.kind == PacketKind::Pubrel
+                    {
+                        let body = input.get(2..header.remaining_length as usize);
+                        self.parse_v5_reason(body.unwrap_or_default(), &mut info, extra_fields);
+                    }
                 }
                 PacketKind::Suback
                 | PacketKind::Pingresp
@@ -483,12 +619,43 @@ impl MqttLog {
                     info.version = self.version;
                     self.msg_type = LogMessageType::Response;
                     info.res_msg_size = Some(header.remaining_length as u32);
+                    if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        // skip packet identifier
+                        let body = input
+                            .get(2..header.remaining_length as usize)
+                            .unwrap_or_default();
+                        match header.kind {
+                            PacketKind::Suback | PacketKind::Unsuback => {
+                                self.parse_v5_reason_list(body, &mut info, extra_fields)
+                            }
+                            PacketKind::Pingresp => (),
+                            _ => self.parse_v5_reason(body, &mut info, extra_fields),
+                        }
+                    }
                 }
                 PacketKind::Disconnect => {
                     info.pkt_type = header.kind;
                     self.msg_type = LogMessageType::Session;
                     info.res_msg_size = Some(header.remaining_length as u32);
                     info.version = self.version;
+                    if self.version == Self::PROTOCOL_LEVEL_V5 {
+                        let body = input.get(..header.remaining_length as usize);
+                        self.parse_v5_reason(body.unwrap_or_default(), &mut info, extra_fields);
+                    }
+                }
+                PacketKind::Auth => {
+                    // AUTH may be sent by both sides during enhanced authentication
+                    info.pkt_type = header.kind;
+                    info.version = self.version;
+                    if from_client {
+                        self.msg_type = LogMessageType::Request;
+                        info.req_msg_size = Some(header.remaining_length as u32);
+                    } else {
+                        self.msg_type = LogMessageType::Response;
+                        info.res_msg_size = Some(header.remaining_length as u32);
+                    }
+                    let body = input.get(..header.remaining_length as usize);
+                    self.parse_v5_reason(body.unwrap_or_default(), &mut info, extra_fields);
                 }
             }
 
@@ -546,7 +713,55 @@ impl MqttLog {
         }
         self.status = L7ResponseStatus::Ok;
 
-        self.parse_mqtt_info(payload, param.parse_log)
+        self.parse_mqtt_info(payload, param)
+    }
+
+    fn resolve_topic_alias(&mut self, topic: &mut String, from_client: bool, alias: u16) {
+        // an empty topic name refers to a previously established alias, otherwise the alias
+        // is (re)assigned to the topic name
+        if topic.is_empty() {
+            if let Some(t) = self.topic_aliases.get(&(from_client, alias)) {
+                *topic = t.clone();
+            }
+        } else {
+            self.topic_aliases.put((from_client, alias), topic.clone());
+        }
+    }
+
+    // PUBACK, PUBREC, PUBREL, PUBCOMP, DISCONNECT and AUTH carry an optional reason code
+    // followed by properties, a missing reason code means success
+    fn parse_v5_reason(
+        &mut self,
+        input: &[u8],
+        info: &mut MqttInfo,
+        extra_fields: Option<&[ExtraLogFieldsInfo]>,
+    ) {
+        let Some((&code, rest)) = input.split_first() else {
+            return;
+        };
+        info.code = Some(code as i32);
+        self.status = self.parse_reason_code(code);
+        if let Ok((_, props)) = mqtt_properties(rest) {
+            info.set_properties(&props, extra_fields);
+        }
+    }
+
+    // SUBACK and UNSUBACK carry properties followed by one reason code per topic filter
+    fn parse_v5_reason_list(
+        &mut self,
+        input: &[u8],
+        info: &mut MqttInfo,
+        extra_fields: Option<&[ExtraLogFieldsInfo]>,
+    ) {
+        let Ok((codes, props)) = mqtt_properties(input) else {
+            return;
+        };
+        info.set_properties(&props, extra_fields);
+        // report the first failed topic filter if any
+        if let Some(&code) = codes.iter().find(|c| **c >= 0x80).or(codes.first()) {
+            info.code = Some(code as i32);
+            self.status = self.parse_reason_code(code);
+        }
     }
 
     fn parse_status_code(&mut self, code: u8) -> L7ResponseStatus {
@@ -565,6 +780,28 @@ impl MqttLog {
             _ => L7ResponseStatus::ParseFailed,
         }
     }
+
+    // ref: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901031
+    fn parse_reason_code(&self, code: u8) -> L7ResponseStatus {
+        match code {
+            0x00..=0x7F => L7ResponseStatus::Ok,
+            /*
+            UnspecifiedError = 0x80,
+            ImplementationSpecificError = 0x83,
+            ServerUnavailable = 0x88,
+            ServerBusy = 0x89,
+            ServerShuttingDown = 0x8B,
+            QuotaExceeded = 0x97,
+            AdministrativeAction = 0x98,
+            UseAnotherServer = 0x9C,
+            ServerMoved = 0x9D,
+            */
+            0x80 | 0x83 | 0x88 | 0x89 | 0x8B | 0x97 | 0x98 | 0x9C | 0x9D => {
+                L7ResponseStatus::ServerError
+            }
+            _ => L7ResponseStatus::ClientError,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -593,6 +830,7 @@ pub enum PacketKind {
     Pingreq,
     Pingresp,
     Disconnect,
+    Auth,
 }
 
 impl fmt::Display for PacketKind {
@@ -612,6 +850,7 @@ impl fmt::Display for PacketKind {
             Self::Pingreq => write!(f, "PINGREQ"),
             Self::Pingresp => write!(f, "PINGRESP"),
             Self::Disconnect => write!(f, "DISCONNECT"),
+            Self::Auth => write!(f, "AUTH"),
         }
     }
 }
@@ -639,6 +878,7 @@ impl PacketKind {
             Self::Pingreq => "PINGREQ",
             Self::Pingresp => "PINGRESP",
             Self::Disconnect => "DISCONNECT",
+            Self::Auth => "AUTH",
         }
     }
 }
@@ -697,6 +937,7 @@ fn mqtt_packet_kind(input: &[u8]) -> IResult<&[u8], PacketKind> {
         (12, 0b0000) => (input, PacketKind::Pingreq),
         (13, 0b0000) => (input, PacketKind::Pingresp),
         (14, 0b0000) => (input, PacketKind::Disconnect),
+        (15, 0b0000) => (input, PacketKind::Auth),
         (inv_type, _) => {
             debug!(
                 "parse mqtt packet failed because get invalid type={}",
@@ -795,6 +1036,72 @@ fn mqtt_string(input: &[u8]) -> IResult<&[u8], &str> {
     .parse(input)
 }
 
+fn mqtt_binary(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    number::complete::be_u16
+        .flat_map(bytes::complete::take)
+        .parse(input)
+}
+
+#[derive(Debug, Default)]
+struct Properties<'a> {
+    topic_alias: Option<u16>,
+    response_topic: Option<&'a str>,
+    correlation_data: Option<&'a [u8]>,
+    reason_string: Option<&'a str>,
+}
+
+// ref: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901027
+fn mqtt_properties(input: &[u8]) -> IResult<&[u8], Properties> {
+    let (input, length) = decode_variable_length(input)?;
+    let (input, mut data) = bytes::complete::take(length)(input)?;
+    let mut props = Properties::default();
+    while !data.is_empty() {
+        let (rest, id) = decode_variable_length(data)?;
+        data = match id {
+            // byte
+            0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2A => number::complete::u8(rest)?.0,
+            // two byte integer
+            0x13 | 0x21 | 0x22 => number::complete::be_u16(rest)?.0,
+            0x23 => {
+                let (rest, alias) = number::complete::be_u16(rest)?;
+                props.topic_alias = Some(alias);
+                rest
+            }
+            // four byte integer
+            0x02 | 0x11 | 0x18 | 0x27 => number::complete::be_u32(rest)?.0,
+            // variable byte integer
+            0x0B => decode_variable_length(rest)?.0,
+            // utf-8 string or binary data
+            0x03 | 0x12 | 0x15 | 0x16 | 0x1A | 0x1C => mqtt_binary(rest)?.0,
+            0x08 => {
+                let (rest, topic) = mqtt_string(rest)?;
+                props.response_topic = Some(topic);
+                rest
+            }
+            0x09 => {
+                let (rest, data) = mqtt_binary(rest)?;
+                props.correlation_data = Some(data);
+                rest
+            }
+            0x1F => {
+                let (rest, reason) = map_res(mqtt_binary, std::str::from_utf8).parse(rest)?;
+                props.reason_string = Some(reason);
+                rest
+            }
+            // user property, utf-8 string pair
+            0x26 => mqtt_binary.and(mqtt_binary).parse(rest)?.0,
+            _ => {
+                debug!("invalid mqtt property identifier: {}", id);
+                return Err(nom::Err::Error(error::Error::new(
+                    rest,
+                    error::ErrorKind::MapRes,
+                )));
+            }
+        };
+    }
+    Ok((input, props))
+}
+
 pub fn parse_connect_packet(input: &[u8]) -> IResult<&[u8], (u8, &str)> {
     let (input, protocol_name) = mqtt_string(input)?;
     if protocol_name != "MQTT" {
@@ -807,6 +1114,11 @@ pub fn parse_connect_packet(input: &[u8]) -> IResult<&[u8], (u8, &str)> {
 
     let (input, protocol_level) = number::complete::u8(input)?;
     let (input, _) = number::complete::be_u16(&input[1..])?;
+    let input = if protocol_level == MqttLog::PROTOCOL_LEVEL_V5 {
+        mqtt_properties(input)?.0
+    } else {
+        input
+    };
     // Payload
     let (input, client_id) = mqtt_string(input)?;
     Ok((input, (protocol_level, client_id)))
@@ -842,6 +1154,20 @@ fn mqtt_subscription_requests(input: &[u8]) -> IResult<&[u8], Vec<(&str, Quality
     Ok((input, count))
 }
 
+// v5 subscription options carry retain handling, RAP and NL flags above the QoS bits
+fn mqtt_subscription_requests_v5(input: &[u8]) -> IResult<&[u8], Vec<(&str, QualityOfService)>> {
+    fn subscription_request(input: &[u8]) -> IResult<&[u8], (&str, QualityOfService)> {
+        let (input, topic) = mqtt_string(input)?;
+        let (input, qos) = map_res(number::complete::u8, |options| {
+            mqtt_quality_of_service(options & 0b11)
+        })
+        .parse(input)?;
+        Ok((input, (topic, qos)))
+    }
+
+    many1(subscription_request)(input)
+}
+
 fn mqtt_quality_of_service(lower: u8) -> Result<QualityOfService, u8> {
     match lower {
         0b00 => Ok(QualityOfService::AtMostOnce),
@@ -1112,6 +1438,258 @@ mod tests {
         }
     }
 
+    fn parse_packets(
+        mqtt: &mut MqttLog,
+        packets: &[(&[u8], PacketDirection)],
+        config: Option<&LogParserConfig>,
+    ) -> Vec<MqttInfo> {
+        let packet = MetaPacket::empty();
+        let mut infos = vec![];
+        for (payload, direction) in packets {
+            let mut param = ParseParam::new(
+                &packet,
+                None,
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.l4_protocol = IpProtocol::TCP;
+            param.direction = *direction;
+            param.parse_config = config;
+            for info in mqtt.parse(payload, &param).unwrap() {
+                if let L7ProtocolInfo::MqttInfo(info) = info {
+                    infos.push(info);
+                }
+            }
+            mqtt.reset();
+        }
+        infos
+    }
+
+    const V5_CONNECT: &[u8] = &[
+        0x10, 21, // CONNECT
+        0x00, 0x04, b'M', b'Q', b'T', b'T', // Protocol name
+        0x05, // Level
+        0x02, // Connect flags
+        0x00, 0x3c, // Keep alive
+        0x06, // Properties length
+        0x22, 0x00, 0x0a, // Topic alias maximum
+        0x21, 0x00, 0x10, // Receive maximum
+        0x00, 0x02, b'c', b'1', // Client identifier
+    ];
+
+    const V5_CONNACK: &[u8] = &[
+        0x20, 6,    // CONNACK
+        0x00, // Flags
+        0x00, // Reason code
+        0x03, // Properties length
+        0x22, 0x00, 0x0a, // Topic alias maximum
+    ];
+
+    #[test]
+    fn check_v5_topic_alias() {
+        let publish_with_alias: &[u8] = &[
+            0x32, 26, // PUBLISH, QoS 1
+            0x00, 0x03, b'a', b'/', b'b', // Topic name
+            0x00, 0x01, // Packet identifier
+            0x10, // Properties length
+            0x23, 0x00, 0x01, // Topic alias
+            0x08, 0x00, 0x03, b'r', b'/', b'1', // Response topic
+            0x09, 0x00, 0x04, b'i', b'd', b'-', b'1', // Correlation data
+            b'h', b'i', // Payload
+        ];
+        let publish_by_alias: &[u8] = &[
+            0x32, 10, // PUBLISH, QoS 1
+            0x00, 0x00, // Empty topic name
+            0x00, 0x02, // Packet identifier
+            0x03, // Properties length
+            0x23, 0x00, 0x01, // Topic alias
+            b'h', b'i', // Payload
+        ];
+        // aliases of the server are independent of the client ones
+        let server_publish_by_alias: &[u8] = &[
+            0x30, 7, // PUBLISH, QoS 0
+            0x00, 0x00, // Empty topic name
+            0x03, // Properties length
+            0x23, 0x00, 0x01, // Topic alias
+            b'x', // Payload
+        ];
+
+        let mut config = LogParserConfig::default();
+        config.l7_log_dynamic.extra_log_fields.mqtt = vec![
+            ExtraLogFieldsInfo {
+                field_name: "correlation_data".to_string(),
+                ..Default::default()
+            },
+            ExtraLogFieldsInfo {
+                field_name: "response_topic".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let mut mqtt = MqttLog::default();
+        let infos = parse_packets(
+            &mut mqtt,
+            &[
+                (V5_CONNECT, PacketDirection::ClientToServer),
+                (V5_CONNACK, PacketDirection::ServerToClient),
+                (publish_with_alias, PacketDirection::ClientToServer),
+                (publish_by_alias, PacketDirection::ClientToServer),
+                (server_publish_by_alias, PacketDirection::ServerToClient),
+            ],
+            Some(&config),
+        );
+        assert_eq!(infos.len(), 5);
+
+        assert_eq!(infos[0].version, 5);
+        assert_eq!(infos[0].client_id.as_deref(), Some("c1"));
+        assert_eq!(infos[1].code, Some(0));
+        assert_eq!(infos[1].status, L7ResponseStatus::Ok);
+
+        assert_eq!(infos[2].publish_topic.as_deref(), Some("a/b"));
+        assert_eq!(
+            infos[2].attributes,
+            vec![
+                KeyVal {
+                    key: "correlation_data".to_string(),
+                    val: "id-1".to_string(),
+                },
+                KeyVal {
+                    key: "response_topic".to_string(),
+                    val: "r/1".to_string(),
+                },
+            ]
+        );
+        assert_eq!(infos[3].publish_topic.as_deref(), Some("a/b"));
+        assert_eq!(infos[3].endpoint.as_deref(), Some("a/b"));
+        assert!(infos[3].attributes.is_empty());
+        assert_eq!(infos[4].publish_topic.as_deref(), Some(""));
+
+        // properties are not exported without configuration
+        let mut mqtt = MqttLog::default();
+        let infos = parse_packets(
+            &mut mqtt,
+            &[
+                (V5_CONNECT, PacketDirection::ClientToServer),
+                (publish_with_alias, PacketDirection::ClientToServer),
+            ],
+            None,
+        );
+        assert_eq!(infos[1].publish_topic.as_deref(), Some("a/b"));
+        assert!(infos[1].attributes.is_empty());
+    }
+
+    #[test]
+    fn check_v5_reason_codes() {
+        let puback: &[u8] = &[
+            0x40, 13, // PUBACK
+            0x00, 0x01, // Packet identifier
+            0x87, // Not authorized
+            0x09, // Properties length
+            0x1f, 0x00, 0x06, b'd', b'e', b'n', b'i', b'e', b'd', // Reason string
+        ];
+        let subscribe: &[u8] = &[
+            0x82, 15, // SUBSCRIBE
+            0x00, 0x03, // Packet identifier
+            0x00, // Properties length
+            0x00, 0x03, b'c', b'/', b'd', // Topic filter
+            0x2d, // Retain handling 2, RAP, NL, QoS 1
+            0x00, 0x03, b'e', b'/', b'f', // Topic filter
+            0x02, // QoS 2
+        ];
+        let suback: &[u8] = &[
+            0x90, 5, // SUBACK
+            0x00, 0x03, // Packet identifier
+            0x00, // Properties length
+            0x01, // Granted QoS 1
+            0x97, // Quota exceeded
+        ];
+        let disconnect: &[u8] = &[
+            0xe0, 2,    // DISCONNECT
+            0x8e, // Session taken over
+            0x00, // Properties length
+        ];
+
+        let mut mqtt = MqttLog::default();
+        let infos = parse_packets(
+            &mut mqtt,
+            &[
+                (V5_CONNECT, PacketDirection::ClientToServer),
+                (V5_CONNACK, PacketDirection::ServerToClient),
+                (puback, PacketDirection::ServerToClient),
+                (subscribe, PacketDirection::ClientToServer),
+                (suback, PacketDirection::ServerToClient),
+                (disconnect, PacketDirection::ServerToClient),
+            ],
+            None,
+        );
+        assert_eq!(infos.len(), 6);
+
+        assert_eq!(infos[2].pkt_type, PacketKind::Puback);
+        assert_eq!(infos[2].code, Some(0x87));
+        assert_eq!(infos[2].status, L7ResponseStatus::ClientError);
+        assert_eq!(infos[2].reason_string.as_deref(), Some("denied"));
+
+        assert_eq!(infos[3].endpoint.as_deref(), Some("c/d,e/f"));
+        let qos = infos[3]
+            .subscribe_topics
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|t| t.qos)
+            .collect::<Vec<_>>();
+        assert_eq!(qos, vec![1, 2]);
+        assert_eq!(infos[3].status, L7ResponseStatus::Ok);
+
+        assert_eq!(infos[4].code, Some(0x97));
+        assert_eq!(infos[4].status, L7ResponseStatus::ServerError);
+
+        assert_eq!(infos[5].pkt_type, PacketKind::Disconnect);
+        assert_eq!(infos[5].code, Some(0x8e));
+        assert_eq!(infos[5].status, L7ResponseStatus::ClientError);
+    }
+
+    #[test]
+    fn check_v5_auth() {
+        let client_auth: &[u8] = &[
+            0xf0, 10,   // AUTH
+            0x19, // Re-authenticate
+            0x08, // Properties length
+            0x15, 0x00, 0x05, b'S', b'C', b'R', b'A', b'M', // Authentication method
+        ];
+        let server_auth: &[u8] = &[
+            0xf0, 10,   // AUTH
+            0x18, // Continue authentication
+            0x08, // Properties length
+            0x15, 0x00, 0x05, b'S', b'C', b'R', b'A', b'M', // Authentication method
+        ];
+
+        let mut mqtt = MqttLog::default();
+        let infos = parse_packets(
+            &mut mqtt,
+            &[
+                (V5_CONNECT, PacketDirection::ClientToServer),
+                (client_auth, PacketDirection::ClientToServer),
+                (server_auth, PacketDirection::ServerToClient),
+            ],
+            None,
+        );
+        assert_eq!(infos.len(), 3);
+
+        assert_eq!(infos[1].pkt_type, PacketKind::Auth);
+        assert_eq!(infos[1].pkt_type.to_string(), "AUTH");
+        assert_eq!(infos[1].req_msg_size, Some(10));
+        assert_eq!(infos[1].code, Some(0x19));
+        assert_eq!(infos[1].status, L7ResponseStatus::Ok);
+
+        assert_eq!(infos[2].pkt_type, PacketKind::Auth);
+        assert_eq!(infos[2].res_msg_size, Some(10));
+        assert_eq!(infos[2].code, Some(0x18));
+        assert_eq!(infos[2].status, L7ResponseStatus::Ok);
+    }
+
     #[test]
     fn check_simple_string() {
         let input = [0x00, 0x05, 0x41, 0xF0, 0xAA, 0x9B, 0x94];
@@ -15,10 +15,11 @@
  */
 
 use std::{
+    collections::HashMap,
     fmt,
     sync::{
         atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, Weak,
     },
     thread,
     thread::JoinHandle,
@@ -29,7 +30,9 @@ use arc_swap::access::Access;
 use log::{debug, info};
 use serde::Serialize;
 
-use super::{AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData};
+use super::{
+    pb_adapter::L7ProtocolSendLog, AppProtoHead, AppProtoLogsBaseInfo, BoxAppProtoLogsData,
+};
 
 use crate::{
     common::{
@@ -39,19 +42,23 @@ use crate::{
         meta_packet::ProtocolData,
         MetaPacket, TaggedFlow, Timestamp,
     },
-    config::handler::{LogParserAccess, LogParserConfig},
+    config::handler::{LogParserAccess, LogParserConfig, NumericTags},
     flow_generator::{
-        error::Result, protocol_logs::L7ResponseStatus, FLOW_METRICS_PEER_DST,
-        FLOW_METRICS_PEER_SRC,
+        error::Result,
+        protocol_logs::{http::Version, L7ResponseStatus},
+        FLOW_METRICS_PEER_DST, FLOW_METRICS_PEER_SRC,
     },
     rpc::get_timestamp,
-    utils::stats::{Counter, CounterType, CounterValue, RefCountable},
+    utils::stats::{
+        self, Countable, Counter, CounterType, CounterValue, FlowLogThrottleStats, RefCountable,
+        StatsOption,
+    },
 };
 use public::{
     chrono_map::ChronoMap,
     l7_protocol::LogMessageType,
     queue::{self, DebugSender, Receiver},
-    throttle::Throttle,
+    throttle::{Throttle, ThrottleCounter, UniformThrottle},
     utils::net::MacAddr,
 };
 
@@ -256,10 +263,19 @@ impl MetaAppProto {
         if self.base_info.head.msg_type == LogMessageType::Request {
             cap_seq += 1;
         }
+        let mut proto = self.base_info.head.proto;
+        // elasticsearch request may be recognized only by its response,
+        // so the key must be the same as its underlying http version
+        if proto == L7Protocol::Elasticsearch {
+            proto = match &self.l7_info {
+                L7ProtocolInfo::HttpInfo(info) if info.version == Version::V2 => L7Protocol::Http2,
+                _ => L7Protocol::Http1,
+            };
+        }
         Self::session_key(
             self.base_info.signal_source,
             self.base_info.flow_id,
-            self.base_info.head.proto,
+            proto,
             self.l7_info.session_id(),
             cap_seq,
         )
@@ -346,22 +362,173 @@ impl RefCountable for SessionAggrCounter {
     }
 }
 
+#[derive(Default)]
+pub struct SessionAggrProtocolCounter {
+    merge: AtomicU64,
+    timeout: AtomicU64,
+    throttle_drop: AtomicU64, // dropped by the throttle of the protocol, not the global one
+}
+
+impl RefCountable for SessionAggrProtocolCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "merge",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.merge.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "timeout",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.timeout.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "throttle-drop",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.throttle_drop.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct SessionAggrProtocolModule {
+    index: u32,
+    protocol: L7Protocol,
+}
+
+impl stats::Module for SessionAggrProtocolModule {
+    fn name(&self) -> &'static str {
+        "l7_session_aggr_protocol"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("index", self.index.to_string()),
+            StatsOption::Tag("protocol", format!("{:?}", self.protocol)),
+        ]
+    }
+}
+
+// per protocol merged and timed out sessions, for tuning session_aggregate timeouts,
+// and logs dropped by the throttle overrides
+// counters are registered when the protocol is first seen
+struct SessionAggrProtocolCounters {
+    index: u32,
+    stats_collector: Arc<stats::Collector>,
+    counters: HashMap<L7Protocol, Arc<SessionAggrProtocolCounter>>,
+}
+
+impl SessionAggrProtocolCounters {
+    fn get(&mut self, protocol: L7Protocol) -> &Arc<SessionAggrProtocolCounter> {
+        self.counters.entry(protocol).or_insert_with(|| {
+            let counter = Arc::new(SessionAggrProtocolCounter::default());
+            self.stats_collector.register_countable(
+                &SessionAggrProtocolModule {
+                    index: self.index,
+                    protocol,
+                },
+                Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+            );
+            counter
+        })
+    }
+}
+
+struct ProtocolThrottle {
+    throttle: UniformThrottle<BoxAppProtoLogsData>,
+    counter: Arc<SessionAggrProtocolCounter>,
+}
+
 struct ThrottleSender {
     throttle: Throttle<BoxAppProtoLogsData>,
+    // logs of these protocols are sampled before the global throttle,
+    // so that one protocol does not use up the global throttle
+    protocol_throttles: HashMap<L7Protocol, ProtocolThrottle>,
+    // sampled by protocol throttles, to be sent to the global throttle
+    sampled: Vec<BoxAppProtoLogsData>,
     counter: Arc<SessionAggrCounter>,
+    config: LogParserAccess,
 }
 
 impl ThrottleSender {
+    // numeric tag filters are checked on merged logs so that dropped logs do not consume the throttle
+    fn is_on_numeric_blacklist(&self, data: &MetaAppProto) -> bool {
+        let config = self.config.load();
+        let Some(trie) = config.l7_log_blacklist_trie.get(&data.base_info.head.proto) else {
+            return false;
+        };
+        if !trie.has_numeric_rules() {
+            return false;
+        }
+        let log = L7ProtocolSendLog::from(data.l7_info.clone());
+        trie.numeric_is_on_blacklist(&NumericTags {
+            response_code: log.resp.code.map(|c| c as i64),
+            response_duration: (data.base_info.head.msg_type == LogMessageType::Session)
+                .then_some(data.base_info.head.rrt as i64),
+            request_length: log.req_len.map(|l| l as i64),
+            response_length: log.resp_len.map(|l| l as i64),
+        })
+    }
+
     fn send(&mut self, data: Box<MetaAppProto>, override_resp_status: Option<L7ResponseStatus>) {
-        if data.l7_info.skip_send() || data.l7_info.is_on_blacklist() {
+        self.send_at(get_timestamp(0), data, override_resp_status);
+    }
+
+    fn send_at(
+        &mut self,
+        now: Duration,
+        data: Box<MetaAppProto>,
+        override_resp_status: Option<L7ResponseStatus>,
+    ) {
+        if data.l7_info.skip_send()
+            || data.l7_info.is_on_blacklist()
+            || self.is_on_numeric_blacklist(&data)
+        {
             return;
         }
-        if !self
-            .throttle
-            .send(BoxAppProtoLogsData::new(data, override_resp_status))
+        let item = BoxAppProtoLogsData::new(data, override_resp_status);
+        match self
+            .protocol_throttles
+            .get_mut(&item.data.base_info.head.proto)
         {
-            self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
+            Some(t) => {
+                if !t.throttle.send(now.as_secs(), item, &mut self.sampled) {
+                    t.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
+                }
+                self.send_sampled(now);
+            }
+            None => {
+                if !self.throttle.send(now, item) {
+                    self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    fn send_sampled(&mut self, now: Duration) {
+        for item in self.sampled.drain(..) {
+            if !self.throttle.send(now, item) {
+                self.counter.throttle_drop.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // logs sampled by protocol throttles are sent when their second ends
+    fn flush(&mut self) {
+        let now = get_timestamp(0);
+        for t in self.protocol_throttles.values_mut() {
+            t.throttle.expire(now.as_secs(), &mut self.sampled);
         }
+        self.send_sampled(now);
+        self.throttle.flush();
+    }
+
+    fn flush_all(&mut self) {
+        for t in self.protocol_throttles.values_mut() {
+            t.throttle.flush(&mut self.sampled);
+        }
+        self.send_sampled(get_timestamp(0));
+        self.throttle.flush();
     }
 }
 
@@ -374,21 +541,33 @@ struct SessionQueue {
     entries: ChronoMap<Timestamp, u128, Box<MetaAppProto>>,
 
     counter: Arc<SessionAggrCounter>,
+    protocol_counters: SessionAggrProtocolCounters,
 
     throttle_sender: ThrottleSender,
     l7_log_collect_nps_threshold: u64,
+    l7_log_protocol_nps_threshold: HashMap<L7Protocol, u64>,
 }
 
 impl SessionQueue {
     fn new(
+        index: u32,
         counter: Arc<SessionAggrCounter>,
+        stats_collector: Arc<stats::Collector>,
         output_queue: DebugSender<BoxAppProtoLogsData>,
         config: LogParserAccess,
     ) -> Self {
         let conf = config.load();
         let max_timelines = conf.l7_log_session_aggr_max_timeout.as_secs() as usize;
         let max_entries = conf.l7_log_session_aggr_max_entries;
-        Self {
+        let throttle_counter = Arc::new(ThrottleCounter::default());
+        stats_collector.register_countable(
+            &FlowLogThrottleStats {
+                kind: "l7_flow_log",
+                index: index as usize,
+            },
+            Countable::Ref(Arc::downgrade(&throttle_counter) as Weak<dyn RefCountable>),
+        );
+        let mut queue = Self {
             config: config.clone(),
 
             window_start: Timestamp::ZERO,
@@ -397,13 +576,56 @@ impl SessionQueue {
             entries: ChronoMap::with_capacity(max_entries, max_timelines),
 
             counter: counter.clone(),
+            protocol_counters: SessionAggrProtocolCounters {
+                index,
+                stats_collector,
+                counters: HashMap::new(),
+            },
 
             throttle_sender: ThrottleSender {
-                throttle: Throttle::new(conf.l7_log_collect_nps_threshold, output_queue),
+                throttle: Throttle::new(
+                    conf.l7_log_collect_nps_threshold,
+                    output_queue,
+                    throttle_counter,
+                ),
+                protocol_throttles: HashMap::new(),
+                sampled: vec![],
                 counter: counter.clone(),
+                config: config.clone(),
             },
             l7_log_collect_nps_threshold: conf.l7_log_collect_nps_threshold,
+            l7_log_protocol_nps_threshold: HashMap::new(),
+        };
+        queue.set_protocol_nps_threshold(&conf.l7_log_protocol_nps_threshold);
+        queue
+    }
+
+    fn set_protocol_nps_threshold(&mut self, thresholds: &HashMap<L7Protocol, u64>) {
+        let sender = &mut self.throttle_sender;
+        // logs sampled by removed throttles still pass the global throttle
+        sender.protocol_throttles.retain(|protocol, t| {
+            if thresholds.contains_key(protocol) {
+                return true;
+            }
+            t.throttle.flush(&mut sender.sampled);
+            false
+        });
+        sender.send_sampled(get_timestamp(0));
+        for (protocol, rate) in thresholds.iter() {
+            match sender.protocol_throttles.get_mut(protocol) {
+                Some(t) => t.throttle.set_rate(*rate),
+                None => {
+                    sender.protocol_throttles.insert(
+                        *protocol,
+                        ProtocolThrottle {
+                            throttle: UniformThrottle::new(*rate),
+                            counter: self.protocol_counters.get(*protocol).clone(),
+                        },
+                    );
+                }
+            }
         }
+        self.l7_log_protocol_nps_threshold = thresholds.clone();
     }
 
     fn aggregate_session_and_send(&mut self, config: &LogParserConfig, item: AppProto) {
@@ -488,6 +710,10 @@ impl SessionQueue {
                             Ordering::Relaxed,
                         );
                         self.counter.merge.fetch_add(1, Ordering::Relaxed);
+                        self.protocol_counters
+                            .get(item.base_info.head.proto)
+                            .merge
+                            .fetch_add(1, Ordering::Relaxed);
                         self.throttle_sender
                             .send(self.entries.remove(&key).unwrap(), None);
                     }
@@ -507,6 +733,10 @@ impl SessionQueue {
                         }
                         self.counter.cached.fetch_sub(1, Ordering::Relaxed);
                         self.counter.merge.fetch_add(1, Ordering::Relaxed);
+                        self.protocol_counters
+                            .get(item.base_info.head.proto)
+                            .merge
+                            .fetch_add(1, Ordering::Relaxed);
                         self.throttle_sender.send(item, None);
                     }
                     // if entry and item cannot merge, send the early one and cache the other
@@ -566,7 +796,7 @@ impl SessionQueue {
         for item in self.entries.drain(..) {
             self.throttle_sender.send(item, None);
         }
-        self.throttle_sender.throttle.flush();
+        self.throttle_sender.flush_all();
         self.counter.cached.store(0, Ordering::Relaxed);
         self.counter
             .cached_request_resource
@@ -582,12 +812,16 @@ impl SessionQueue {
                 item.l7_info.get_request_resource_length() as u64,
                 Ordering::Relaxed,
             );
+            self.protocol_counters
+                .get(item.base_info.head.proto)
+                .timeout
+                .fetch_add(1, Ordering::Relaxed);
 
             self.throttle_sender
                 .send(item.clone(), Some(L7ResponseStatus::Timeout));
             None
         });
-        self.throttle_sender.throttle.flush();
+        self.throttle_sender.flush();
         // update timestamp
         self.window_start = time;
     }
@@ -600,6 +834,7 @@ pub struct SessionAggregator {
     running: Arc<AtomicBool>,
     thread: Mutex<Option<JoinHandle<()>>>,
     counter: Arc<SessionAggrCounter>,
+    stats_collector: Arc<stats::Collector>,
     config: LogParserAccess,
     ntp_diff: Arc<AtomicI64>,
 }
@@ -611,6 +846,7 @@ impl SessionAggregator {
         id: u32,
         config: LogParserAccess,
         ntp_diff: Arc<AtomicI64>,
+        stats_collector: Arc<stats::Collector>,
     ) -> (Self, Arc<SessionAggrCounter>) {
         let counter: Arc<SessionAggrCounter> = Default::default();
         (
@@ -621,6 +857,7 @@ impl SessionAggregator {
                 running: Default::default(),
                 thread: Mutex::new(None),
                 counter: counter.clone(),
+                stats_collector,
                 config,
                 ntp_diff,
             },
@@ -640,11 +877,14 @@ impl SessionAggregator {
 
         let config = self.config.clone();
         let ntp_diff = self.ntp_diff.clone();
+        let stats_collector = self.stats_collector.clone();
+        let id = self.id;
 
         let thread = thread::Builder::new()
             .name("protocol-logs-parser".to_owned())
             .spawn(move || {
-                let mut session_queue = SessionQueue::new(counter, output_queue, config.clone());
+                let mut session_queue =
+                    SessionQueue::new(id, counter, stats_collector, output_queue, config.clone());
                 let mut batch_buffer = Vec::with_capacity(QUEUE_BATCH_SIZE);
                 // estimated lag from app proto start time to current time
                 let mut lag_second = 0i64;
@@ -711,6 +951,17 @@ impl SessionAggregator {
                             .throttle
                             .set_rate(config.l7_log_collect_nps_threshold);
                     }
+                    if config.l7_log_protocol_nps_threshold
+                        != session_queue.l7_log_protocol_nps_threshold
+                    {
+                        info!(
+                            "update l7_log_protocol_nps_threshold from {:?} to {:?}",
+                            session_queue.l7_log_protocol_nps_threshold,
+                            config.l7_log_protocol_nps_threshold
+                        );
+                        session_queue
+                            .set_protocol_nps_threshold(&config.l7_log_protocol_nps_threshold);
+                    }
                 }
                 session_queue.flush();
             })
@@ -737,3 +988,225 @@ impl SessionAggregator {
         info!("app protocol logs parser (id={}) stopped", self.id);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arc_swap::{access::Map, ArcSwap};
+    use public::debug::QueueDebugger;
+
+    use crate::{
+        config::ModuleConfig,
+        flow_generator::protocol_logs::{DnsInfo, KafkaInfo},
+    };
+
+    fn new_log(
+        l7_info: L7ProtocolInfo,
+        proto: L7Protocol,
+        msg_type: LogMessageType,
+        flow_id: u64,
+        secs: u64,
+    ) -> AppProto {
+        let mut log = MetaAppProto::new(
+            &TaggedFlow::default(),
+            &MetaPacket::empty(),
+            l7_info,
+            AppProtoHead {
+                proto,
+                msg_type,
+                rrt: 0,
+            },
+        )
+        .unwrap();
+        log.base_info.signal_source = SignalSource::Packet;
+        log.base_info.flow_id = flow_id;
+        log.base_info.start_time = Timestamp::from_secs(secs);
+        log.base_info.end_time = log.base_info.start_time;
+        AppProto::MetaAppProto(Box::new(log))
+    }
+
+    #[test]
+    fn session_timeout_by_protocol() {
+        let mut module_config = ModuleConfig::default();
+        module_config.log_parser.l7_log_session_aggr_max_timeout = Duration::from_secs(300);
+        module_config.log_parser.l7_log_session_aggr_timeout = HashMap::from([
+            (L7Protocol::DNS, Duration::from_secs(5)),
+            (L7Protocol::Kafka, Duration::from_secs(300)),
+        ]);
+        let current_config = Arc::new(ArcSwap::from_pointee(module_config));
+        let access: LogParserAccess = Map::new(current_config, |config| &config.log_parser);
+        let config = access.load();
+
+        let queue_debugger = QueueDebugger::new();
+        let (output_sender, output_receiver, _) =
+            queue::bounded_with_debug(1024, "", &queue_debugger);
+        let mut queue = SessionQueue::new(
+            0,
+            Default::default(),
+            Arc::new(stats::Collector::new("", Arc::new(AtomicI64::new(0)))),
+            output_sender,
+            access.clone(),
+        );
+
+        let dns = |msg_type, flow_id, secs| {
+            new_log(
+                L7ProtocolInfo::DnsInfo(DnsInfo::default()),
+                L7Protocol::DNS,
+                msg_type,
+                flow_id,
+                secs,
+            )
+        };
+        let kafka = |msg_type, flow_id, secs| {
+            new_log(
+                L7ProtocolInfo::KafkaInfo(KafkaInfo::default()),
+                L7Protocol::Kafka,
+                msg_type,
+                flow_id,
+                secs,
+            )
+        };
+
+        // dns request and response within 5s are merged
+        queue.aggregate_session_and_send(&config, dns(LogMessageType::Request, 1, 100));
+        queue.aggregate_session_and_send(&config, dns(LogMessageType::Response, 1, 101));
+        // an unanswered dns request and a kafka request waiting for a slow response
+        queue.aggregate_session_and_send(&config, dns(LogMessageType::Request, 2, 100));
+        queue.aggregate_session_and_send(&config, kafka(LogMessageType::Request, 3, 100));
+        assert_eq!(queue.entries.len(), 2);
+
+        // dns times out after 5s, kafka is still waiting
+        queue.flush_till(Timestamp::from_secs(110));
+        assert_eq!(queue.entries.len(), 1);
+        queue.aggregate_session_and_send(&config, kafka(LogMessageType::Response, 3, 250));
+        assert_eq!(queue.entries.len(), 0);
+
+        let mut counter = |protocol| {
+            let c = queue.protocol_counters.get(protocol);
+            (
+                c.merge.load(Ordering::Relaxed),
+                c.timeout.load(Ordering::Relaxed),
+            )
+        };
+        assert_eq!(counter(L7Protocol::DNS), (1, 1));
+        assert_eq!(counter(L7Protocol::Kafka), (1, 0));
+
+        queue.throttle_sender.throttle.flush();
+        let mut output = Vec::with_capacity(1024);
+        let _ = output_receiver.recv_all(&mut output, Some(Duration::from_secs(1)));
+        assert_eq!(output.len(), 3);
+    }
+
+    #[test]
+    fn throttle_rate() {
+        let mut module_config = ModuleConfig::default();
+        module_config.log_parser.l7_log_collect_nps_threshold = 1000;
+        let current_config = Arc::new(ArcSwap::from_pointee(module_config));
+        let access: LogParserAccess = Map::new(current_config, |config| &config.log_parser);
+
+        let queue_debugger = QueueDebugger::new();
+        let (output_sender, output_receiver, _) =
+            queue::bounded_with_debug(1 << 14, "", &queue_debugger);
+        let mut queue = SessionQueue::new(
+            0,
+            Default::default(),
+            Arc::new(stats::Collector::new("", Arc::new(AtomicI64::new(0)))),
+            output_sender,
+            access.clone(),
+        );
+
+        // twice the rate for 10 seconds, the same load and drops as l4 flow logs
+        for i in 0..20000 {
+            let log = match new_log(
+                L7ProtocolInfo::DnsInfo(DnsInfo::default()),
+                L7Protocol::DNS,
+                LogMessageType::Session,
+                i,
+                100,
+            ) {
+                AppProto::MetaAppProto(m) => m,
+                _ => unreachable!(),
+            };
+            queue
+                .throttle_sender
+                .send_at(Duration::from_micros(i * 500), log, None);
+        }
+        queue.throttle_sender.throttle.flush();
+        let dropped = queue.counter.throttle_drop.load(Ordering::Relaxed) as usize;
+        assert_eq!(dropped, 9001);
+
+        let mut output = Vec::with_capacity(1 << 14);
+        let _ = output_receiver.recv_all(&mut output, Some(Duration::from_secs(1)));
+        assert_eq!(output.len(), 20000 - dropped);
+    }
+
+    #[test]
+    fn throttle_by_protocol() {
+        let mut module_config = ModuleConfig::default();
+        module_config.log_parser.l7_log_protocol_nps_threshold =
+            HashMap::from([(L7Protocol::DNS, 2)]);
+        let current_config = Arc::new(ArcSwap::from_pointee(module_config));
+        let access: LogParserAccess = Map::new(current_config, |config| &config.log_parser);
+
+        let queue_debugger = QueueDebugger::new();
+        let (output_sender, output_receiver, _) =
+            queue::bounded_with_debug(1024, "", &queue_debugger);
+        let mut queue = SessionQueue::new(
+            0,
+            Default::default(),
+            Arc::new(stats::Collector::new("", Arc::new(AtomicI64::new(0)))),
+            output_sender,
+            access.clone(),
+        );
+
+        let session = |l7_info, proto, flow_id| match new_log(
+            l7_info,
+            proto,
+            LogMessageType::Session,
+            flow_id,
+            100,
+        ) {
+            AppProto::MetaAppProto(m) => m,
+            _ => unreachable!(),
+        };
+        for i in 0..10 {
+            queue.throttle_sender.send(
+                session(
+                    L7ProtocolInfo::DnsInfo(DnsInfo::default()),
+                    L7Protocol::DNS,
+                    i,
+                ),
+                None,
+            );
+        }
+        for i in 10..13 {
+            queue.throttle_sender.send(
+                session(
+                    L7ProtocolInfo::KafkaInfo(KafkaInfo::default()),
+                    L7Protocol::Kafka,
+                    i,
+                ),
+                None,
+            );
+        }
+        queue.throttle_sender.flush_all();
+
+        let mut output = Vec::with_capacity(1024);
+        let _ = output_receiver.recv_all(&mut output, Some(Duration::from_secs(1)));
+        let dns_sent = output
+            .iter()
+            .filter(|l| l.data.base_info.head.proto == L7Protocol::DNS)
+            .count() as u64;
+        let dns_dropped = queue
+            .protocol_counters
+            .get(L7Protocol::DNS)
+            .throttle_drop
+            .load(Ordering::Relaxed);
+        // a second may end while sending
+        assert!(dns_sent >= 2 && dns_sent < 10);
+        assert_eq!(dns_sent + dns_dropped, 10);
+        assert_eq!(output.len() as u64 - dns_sent, 3);
+        assert_eq!(queue.counter.throttle_drop.load(Ordering::Relaxed), 0);
+    }
+}
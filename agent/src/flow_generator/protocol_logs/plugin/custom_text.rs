@@ -0,0 +1,351 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use public::l7_protocol::{CustomProtocol, L7Protocol, LogMessageType};
+use regex::Captures;
+
+use crate::{
+    common::{
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, ParseParam},
+    },
+    config::config::{CustomTextConfig, CustomTextPairing},
+    flow_generator::{
+        protocol_logs::{set_captured_byte, IpProtocol, L7ResponseStatus},
+        Error, Result,
+    },
+    plugin::CustomInfo,
+};
+
+// delimited lines of a payload, the trailing incomplete line is only returned if the payload is truncated
+struct Lines<'a> {
+    p: &'a [u8],
+    delimiter: &'a [u8],
+    truncated: bool,
+}
+
+impl<'a> Lines<'a> {
+    fn new(payload: &'a [u8], delimiter: &'a [u8], param: &ParseParam) -> Self {
+        let buf_size = param.buf_size as usize;
+        let (p, truncated) = if buf_size > 0 && payload.len() > buf_size {
+            (&payload[..buf_size], true)
+        } else {
+            (payload, false)
+        };
+        Self {
+            p,
+            delimiter,
+            truncated,
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.p.is_empty() && !self.delimiter.is_empty() {
+            let line = match self
+                .p
+                .windows(self.delimiter.len())
+                .position(|w| w == self.delimiter)
+            {
+                Some(pos) => {
+                    let line = &self.p[..pos];
+                    self.p = &self.p[pos + self.delimiter.len()..];
+                    line
+                }
+                None if self.truncated => std::mem::take(&mut self.p),
+                None => return None,
+            };
+            if line.is_empty() {
+                continue;
+            }
+            return std::str::from_utf8(line).ok();
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+pub struct CustomTextLog {
+    perf_stats: Vec<L7PerfStats>,
+    name: Option<Arc<String>>,
+}
+
+impl CustomTextLog {
+    pub fn get(name: Arc<String>) -> CustomTextLog {
+        CustomTextLog {
+            name: Some(name),
+            ..Default::default()
+        }
+    }
+
+    fn fill_request(info: &mut CustomInfo, caps: &Captures) {
+        if let Some(m) = caps.name("request_type") {
+            info.req.req_type = m.as_str().to_string();
+        }
+        if let Some(m) = caps.name("resource") {
+            info.req.resource = m.as_str().to_string();
+        }
+    }
+
+    fn fill_response(info: &mut CustomInfo, caps: Option<&Captures>, config: &CustomTextConfig) {
+        info.resp.status = L7ResponseStatus::Ok;
+        let Some(caps) = caps else {
+            return;
+        };
+        if let Some(m) = caps.name("code") {
+            info.resp.code = m.as_str().parse::<i32>().ok();
+        }
+        if let Some(m) = caps.name("status") {
+            let status = m.as_str();
+            if !config
+                .success_status
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(status))
+            {
+                info.resp.status = L7ResponseStatus::ServerError;
+                info.resp.exception = status.to_string();
+            }
+        }
+    }
+}
+
+impl L7ProtocolParserInterface for CustomTextLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
+        if param.l4_protocol != IpProtocol::TCP
+            || param.direction != PacketDirection::ClientToServer
+        {
+            return None;
+        }
+        let config = &param.parse_config?.custom_text;
+        let regex = config.request_regex.as_ref()?;
+
+        let line = Lines::new(payload, config.request_delimiter.as_bytes(), param).next()?;
+        if !regex.is_match(line) {
+            return None;
+        }
+        self.name = Some(Arc::new(config.name.clone()));
+        Some(LogMessageType::Request)
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        let Some(config) = param.parse_config else {
+            return Err(Error::NoParseConfig);
+        };
+        let Some(name) = self.name.as_ref() else {
+            return Ok(L7ParseResult::None);
+        };
+        let text_config = &config.custom_text;
+
+        self.perf_stats.clear();
+        let (delimiter, regex) = match param.direction {
+            PacketDirection::ClientToServer => (
+                text_config.request_delimiter.as_bytes(),
+                text_config.request_regex.as_ref(),
+            ),
+            PacketDirection::ServerToClient => (
+                text_config.response_delimiter.as_bytes(),
+                text_config.response_regex.as_ref(),
+            ),
+        };
+
+        let mut infos = vec![];
+        for line in Lines::new(payload, delimiter, param) {
+            let caps = regex.and_then(|r| r.captures(line));
+            if regex.is_some() && caps.is_none() {
+                continue;
+            }
+
+            let mut info = CustomInfo::default();
+            info.msg_type = param.direction.into();
+            info.proto_str = name.to_string();
+            match param.direction {
+                PacketDirection::ClientToServer => {
+                    info.req_len = Some(line.len() as u32);
+                    if let Some(caps) = caps.as_ref() {
+                        Self::fill_request(&mut info, caps);
+                    }
+                }
+                PacketDirection::ServerToClient => {
+                    info.resp_len = Some(line.len() as u32);
+                    Self::fill_response(&mut info, caps.as_ref(), text_config);
+                }
+            }
+            if text_config.pairing == CustomTextPairing::RequestId {
+                info.request_id = caps
+                    .as_ref()
+                    .and_then(|c| c.name("request_id"))
+                    .and_then(|m| m.as_str().parse::<u32>().ok());
+            }
+            info.set_is_on_blacklist(config);
+            set_captured_byte!(info, param);
+
+            if param.parse_perf {
+                let mut perf_stat = L7PerfStats::default();
+                if let Some(stats) = info.perf_stats(param) {
+                    info.rrt = stats.rrt_sum;
+                    perf_stat.sequential_merge(&stats);
+                }
+                self.perf_stats.push(perf_stat);
+            }
+            infos.push(L7ProtocolInfo::CustomInfo(info));
+        }
+
+        if infos.is_empty() {
+            return Err(Error::L7ProtocolUnknown);
+        }
+        if param.parse_log {
+            Ok(L7ParseResult::Multi(infos))
+        } else {
+            Ok(L7ParseResult::None)
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::Custom
+    }
+
+    fn perf_stats(&mut self) -> Vec<L7PerfStats> {
+        std::mem::take(&mut self.perf_stats)
+    }
+
+    fn custom_protocol(&self) -> Option<CustomProtocol> {
+        match self.name.as_ref() {
+            Some(p) => Some(CustomProtocol::CustomText(p.clone())),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, rc::Rc};
+
+    use regex::Regex;
+
+    use super::*;
+
+    use crate::{
+        common::l7_protocol_log::L7PerfCache, config::handler::LogParserConfig,
+        flow_generator::L7_RRT_CACHE_CAPACITY, utils::test_utils::Capture,
+    };
+
+    const FILE_DIR: &str = "resources/test/flow_generator/custom_text";
+
+    fn parse(config: &LogParserConfig) -> (Vec<CustomInfo>, Vec<CustomInfo>) {
+        let capture = Capture::load_pcap(Path::new(FILE_DIR).join("info-ok.pcap"));
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut packets = capture.collect::<Vec<_>>();
+        packets[0].lookup_key.direction = PacketDirection::ClientToServer;
+        packets[1].lookup_key.direction = PacketDirection::ServerToClient;
+
+        let mut parser = CustomTextLog::default();
+        let mut logs = vec![];
+        for (i, packet) in packets.iter().enumerate() {
+            let mut param = ParseParam::new(
+                packet,
+                Some(log_cache.clone()),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.set_log_parser_config(config);
+            let payload = packet.get_l4_payload().unwrap();
+            if i == 0 {
+                assert_eq!(
+                    parser.check_payload(payload, &param),
+                    Some(LogMessageType::Request)
+                );
+            }
+            let L7ParseResult::Multi(infos) = parser.parse_payload(payload, &param).unwrap() else {
+                unreachable!()
+            };
+            logs.push(
+                infos
+                    .into_iter()
+                    .map(|i| match i {
+                        L7ProtocolInfo::CustomInfo(info) => info,
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(
+            parser.custom_protocol(),
+            Some(CustomProtocol::CustomText(Arc::new("InfoOk".to_string())))
+        );
+        let resps = logs.pop().unwrap();
+        (logs.pop().unwrap(), resps)
+    }
+
+    fn config(pairing: CustomTextPairing) -> LogParserConfig {
+        LogParserConfig {
+            custom_text: CustomTextConfig {
+                name: "InfoOk".to_string(),
+                request_regex: Some(
+                    Regex::new(r"^(?P<request_type>[A-Z]+) (?P<request_id>\d+) (?P<resource>\S+)$")
+                        .unwrap(),
+                ),
+                response_regex: Some(
+                    Regex::new(r"^(?P<status>[A-Z]+) (?P<request_id>\d+) (?P<code>\d+)$").unwrap(),
+                ),
+                pairing,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_by_request_id() {
+        let (reqs, resps) = parse(&config(CustomTextPairing::RequestId));
+
+        assert_eq!(reqs.len(), 2);
+        assert_eq!(reqs[0].request_id, Some(1));
+        assert_eq!(reqs[0].req.req_type, "INFO");
+        assert_eq!(reqs[0].req.resource, "/users/1");
+        assert_eq!(reqs[1].request_id, Some(2));
+        assert_eq!(reqs[1].req.resource, "/orders/9");
+
+        // responses are out of order
+        assert_eq!(resps.len(), 2);
+        assert_eq!(resps[0].request_id, Some(2));
+        assert_eq!(resps[0].resp.status, L7ResponseStatus::Ok);
+        assert_eq!(resps[0].resp.code, Some(200));
+        assert_eq!(resps[1].request_id, Some(1));
+        assert_eq!(resps[1].resp.status, L7ResponseStatus::ServerError);
+        assert_eq!(resps[1].resp.code, Some(404));
+        assert_eq!(resps[1].resp.exception, "ERR");
+    }
+
+    #[test]
+    fn parse_sequentially() {
+        let (reqs, resps) = parse(&config(CustomTextPairing::Sequential));
+
+        assert!(reqs.iter().all(|i| i.request_id.is_none()));
+        assert!(resps.iter().all(|i| i.request_id.is_none()));
+        assert_eq!(reqs[0].req.resource, "/users/1");
+        assert_eq!(resps[0].resp.status, L7ResponseStatus::Ok);
+        assert_eq!(resps[1].resp.status, L7ResponseStatus::ServerError);
+    }
+}
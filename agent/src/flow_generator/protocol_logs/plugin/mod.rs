@@ -28,8 +28,9 @@ use crate::{
 
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use self::shared_obj::{get_so_parser, SoLog};
-use self::{custom_wrap::CustomWrapLog, wasm::get_wasm_parser};
+use self::{custom_text::CustomTextLog, custom_wrap::CustomWrapLog, wasm::get_wasm_parser};
 
+pub mod custom_text;
 pub mod custom_wrap;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub mod shared_obj;
@@ -49,6 +50,7 @@ pub enum CustomLog {
     SoLog(SoLog),
     #[cfg(feature = "enterprise")]
     CustomPolicyLog(CustomPolicyLog),
+    CustomTextLog(CustomTextLog),
 }
 
 pub fn get_custom_log_parser(proto: CustomProtocol) -> L7ProtocolParser {
@@ -63,6 +65,7 @@ pub fn get_custom_log_parser(proto: CustomProtocol) -> L7ProtocolParser {
             CustomProtocol::CustomPolicy(s) => CustomLog::CustomPolicyLog(CustomPolicyLog::get(s)),
             #[cfg(not(feature = "enterprise"))]
             CustomProtocol::CustomPolicy(_) => unimplemented!(),
+            CustomProtocol::CustomText(s) => CustomLog::CustomTextLog(CustomTextLog::get(s)),
         }),
     })
 }
@@ -75,5 +78,6 @@ fn all_plugin_log_parser() -> Vec<CustomLog> {
         CustomLog::SoLog(SoLog::default()),
         #[cfg(feature = "enterprise")]
         CustomLog::CustomPolicyLog(CustomPolicyLog::default()),
+        CustomLog::CustomTextLog(CustomTextLog::default()),
     ]
 }
@@ -33,7 +33,10 @@ use crate::{
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
         meta_packet::ApplicationFlags,
     },
-    config::handler::{L7LogDynamicConfig, LogParserConfig, TraceType},
+    config::{
+        config::DubboConfig,
+        handler::{L7LogDynamicConfig, LogParserConfig, TraceType},
+    },
     flow_generator::{
         error::{Error, Result},
         protocol_logs::{
@@ -64,6 +67,8 @@ use self::consts::*;
 
 const TRACE_ID_MAX_LEN: usize = 1024;
 
+const HESSIAN2_SKIPPED_FIELDS_METRIC: &str = "hessian2_skipped_fields";
+
 const HESSIAN2_SERIALIZATION_ID: u8 = 2;
 const JAVA_SERIALIZATION_ID: u8 = 3;
 const COMPACTED_JAVA_SERIALIZATION_ID: u8 = 4;
@@ -258,12 +263,11 @@ impl DubboInfo {
     }
 
     fn set_span_id(&mut self, span_id: String, trace_type: &TraceType) {
-        self.span_id.set_with(BASE_FIELD_PRIORITY, || {
-            match trace_type.decode_span_id(&span_id) {
-                Some(id) => id.to_string(),
-                None => span_id,
-            }
-        });
+        // malformed values leave the field empty
+        if let Some(id) = trace_type.decode_span_id(&span_id) {
+            self.span_id
+                .set_with(BASE_FIELD_PRIORITY, || id.to_string());
+        }
     }
 
     pub fn merge_custom_info(&mut self, custom: CustomInfo) {
@@ -513,6 +517,8 @@ impl From<&DubboInfo> for LogCache {
 #[derive(Default)]
 pub struct DubboLog {
     perf_stats: Vec<L7PerfStats>,
+    // 当前流中无法解析而跳过的 hessian2 字段数
+    hessian2_skipped_fields: u32,
 
     #[cfg(feature = "enterprise")]
     custom_field_store: Store,
@@ -569,6 +575,9 @@ impl L7ProtocolParserInterface for DubboLog {
             #[cfg(feature = "enterprise")]
             custom_policies,
         )?;
+        if info.msg_type == LogMessageType::Request && info.serial_id == HESSIAN2_SERIALIZATION_ID {
+            self.extract_hessian2_fields(&config.dubbo, payload, param.buf_size, &mut info);
+        }
         info.is_tls = param.is_tls();
         set_captured_byte!(info, param);
         info.endpoint = info.generate_endpoint();
@@ -636,9 +645,11 @@ mod kryo {
 
     fn lookup_str(payload: &[u8], trace_type: &TraceType) -> Option<String> {
         let tag = match trace_type {
-            TraceType::Sw3 | TraceType::Sw8 | TraceType::CloudWise | TraceType::Customize(_) => {
-                trace_type.as_str()
-            }
+            TraceType::Sw3
+            | TraceType::Sw8
+            | TraceType::CloudWise
+            | TraceType::Customize(_)
+            | TraceType::Formatted(..) => trace_type.as_str(),
             _ => return None,
         };
         if tag.len() <= 1 {
@@ -846,9 +857,11 @@ mod fastjson2 {
 
     fn lookup_str(payload: &[u8], trace_type: &TraceType) -> Option<String> {
         let tag = match trace_type {
-            TraceType::Sw3 | TraceType::Sw8 | TraceType::CloudWise | TraceType::Customize(_) => {
-                trace_type.as_str()
-            }
+            TraceType::Sw3
+            | TraceType::Sw8
+            | TraceType::CloudWise
+            | TraceType::Customize(_)
+            | TraceType::Formatted(..) => trace_type.as_str(),
             _ => return None,
         };
         if tag.len() <= 1 {
@@ -990,6 +1003,34 @@ impl DubboLog {
         );
     }
 
+    fn extract_hessian2_fields(
+        &mut self,
+        config: &DubboConfig,
+        payload: &[u8],
+        buf_size: u16,
+        info: &mut DubboInfo,
+    ) {
+        if config.extract_attachments.is_empty() && config.extract_arguments.is_empty() {
+            return;
+        }
+        // 解码范围不超过 payload_truncation
+        let limit = match buf_size {
+            0 => payload.len(),
+            size => payload.len().min(size as usize),
+        };
+        if limit <= DUBBO_HEADER_LEN {
+            return;
+        }
+        self.hessian2_skipped_fields +=
+            hessian2::get_req_body_fields(config, &payload[DUBBO_HEADER_LEN..limit], info);
+        if self.hessian2_skipped_fields > 0 {
+            info.metrics.push(MetricKeyVal {
+                key: HESSIAN2_SKIPPED_FIELDS_METRIC.to_string(),
+                val: self.hessian2_skipped_fields as f32,
+            });
+        }
+    }
+
     fn set_status(&mut self, status_code: u8, info: &mut DubboInfo) {
         info.resp_status = match status_code {
             20 => L7ResponseStatus::Ok,
@@ -1529,6 +1570,86 @@ mod tests {
         perf_stat
     }
 
+    #[test]
+    fn extract_hessian2_fields() {
+        let config = LogParserConfig {
+            dubbo: DubboConfig {
+                extract_attachments: vec!["timeout".to_string(), "x-biz-region".to_string()],
+                extract_arguments: vec![
+                    "tenantId".to_string(),
+                    "orderNo".to_string(),
+                    "amount".to_string(),
+                    "items".to_string(),
+                ],
+            },
+            ..Default::default()
+        };
+        let expected = [
+            (
+                "dubbo-2.7-hessian2-args.pcap",
+                vec![
+                    ("tenantId", "t-1001"),
+                    ("orderNo", "A20231114001"),
+                    ("amount", "1999"),
+                    ("x-biz-region", "cn-east"),
+                ],
+            ),
+            // dubbo 3.x 从 triple 回退到 dubbo 协议，服务版本为 null，参数为 object
+            (
+                "dubbo-3-tri-fallback-hessian2-args.pcap",
+                vec![
+                    ("tenantId", "t-2002"),
+                    ("orderNo", "B20231114002"),
+                    ("amount", "42"),
+                    ("timeout", "3000"),
+                    ("x-biz-region", "cn-north"),
+                ],
+            ),
+        ];
+
+        for (name, attributes) in expected {
+            let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+            let mut packets =
+                Capture::load_pcap(Path::new(FILE_DIR).join(name)).collect::<Vec<_>>();
+            packets[0].lookup_key.direction = PacketDirection::ClientToServer;
+            let payload = packets[0].get_l4_payload().unwrap();
+            let mut dubbo = DubboLog::default();
+            let param = &mut ParseParam::new(
+                &packets[0],
+                Some(log_cache),
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                true,
+                true,
+            );
+            param.set_log_parser_config(&config);
+            assert!(dubbo.check_payload(payload, param).is_some(), "{name}");
+
+            let L7ProtocolInfo::DubboInfo(info) =
+                dubbo.parse_payload(payload, param).unwrap().unwrap_single()
+            else {
+                unreachable!()
+            };
+            assert_eq!(info.method_name, "createOrder", "{name}");
+            let actual = info
+                .attributes
+                .iter()
+                .map(|kv| (kv.key.as_str(), kv.val.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(actual, attributes, "{name}");
+            // `items` 是嵌套 map，`String[]` 参数是 list
+            assert_eq!(
+                info.metrics,
+                vec![MetricKeyVal {
+                    key: HESSIAN2_SKIPPED_FIELDS_METRIC.to_string(),
+                    val: 1.0,
+                }],
+                "{name}"
+            );
+        }
+    }
+
     #[test]
     fn decode_java_trace_id() {
         let payload = [
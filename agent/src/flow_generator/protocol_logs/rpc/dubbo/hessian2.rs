@@ -14,14 +14,22 @@
  * limitations under the License.
  */
 
+use std::{borrow::Cow, collections::HashMap};
+
 use nom::FindSubstring;
 
-use public::codecs::hessian2::{consts::*, Hessian2Decoder};
+use public::{
+    codecs::hessian2::{consts::*, Hessian2Decoder, HessianValue},
+    l7_protocol::L7LogAttribute,
+};
 
 use super::consts::*;
 use super::{DubboInfo, BODY_PARAM_MAX, BODY_PARAM_MIN};
 
-use crate::config::handler::{L7LogDynamicConfig, TraceType};
+use crate::config::{
+    config::DubboConfig,
+    handler::{L7LogDynamicConfig, TraceType},
+};
 
 cfg_if::cfg_if! {
 if #[cfg(feature = "enterprise")] {
@@ -34,9 +42,11 @@ if #[cfg(feature = "enterprise")] {
 
 fn lookup_str(payload: &[u8], trace_type: &TraceType) -> Option<String> {
     let tag = match trace_type {
-        TraceType::Sw3 | TraceType::Sw8 | TraceType::CloudWise | TraceType::Customize(_) => {
-            trace_type.as_str()
-        }
+        TraceType::Sw3
+        | TraceType::Sw8
+        | TraceType::CloudWise
+        | TraceType::Customize(_)
+        | TraceType::Formatted(..) => trace_type.as_str(),
         _ => return None,
     };
 
@@ -83,6 +93,8 @@ fn decode_span_id(payload: &[u8], trace_type: &TraceType, info: &mut DubboInfo)
 fn get_req_param_len(payload: &[u8]) -> (usize, usize) {
     let tag = payload[0];
     match tag {
+        // dubbo 3.x 未设置服务版本时写入 null
+        BC_NULL => (1, 0),
         BC_STRING_DIRECT..=STRING_DIRECT_MAX => (1, tag as usize),
         BC_STRING_SHORT..=BC_STRING_SHORT_MAX if payload.len() > 2 => {
             (2, ((tag as usize - 0x30) << 8) + payload[1] as usize)
@@ -109,7 +121,7 @@ pub fn get_req_body_info(
     while n < BODY_PARAM_MAX && para_index < payload_len {
         let (offset, para_len) = get_req_param_len(&payload[para_index..]);
         para_index += offset;
-        if para_len == 0 || para_len + para_index > payload_len {
+        if offset == 0 || para_len + para_index > payload_len {
             return;
         }
 
@@ -173,6 +185,88 @@ pub fn get_req_body_info(
     apply_custom_field_policies(payload, info, cf_ctx);
 }
 
+// 根据参数类型描述计算参数个数，如 `Ljava/lang/String;[II` 表示 3 个参数
+fn get_param_count(desc: &str) -> usize {
+    let mut count = 0;
+    let mut chars = desc.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => continue,
+            'L' => {
+                if chars.find(|c| *c == ';').is_none() {
+                    break;
+                }
+            }
+            _ => (),
+        }
+        count += 1;
+    }
+    count
+}
+
+// 只提取字符串和整数类型的值，返回跳过的字段数
+fn add_attributes(
+    keys: &[String],
+    mut fields: HashMap<String, HessianValue>,
+    info: &mut DubboInfo,
+) -> u32 {
+    let mut skipped = 0;
+    for key in keys {
+        match fields.remove(key) {
+            Some(
+                value @ (HessianValue::String(_) | HessianValue::Int(_) | HessianValue::Long(_)),
+            ) => info.add_attribute(Cow::Borrowed(key), Cow::Owned(value.to_string())),
+            Some(HessianValue::Null) | None => (),
+            Some(_) => skipped += 1,
+        }
+    }
+    skipped
+}
+
+// 提取 Dubbo 请求 body 中参数的顶层字段和 attachments，返回无法解析而跳过的字段数
+// body 格式：dubbo version, service name, service version, method name, 参数类型描述, 各参数, attachments
+// 参考：https://github.com/apache/dubbo/blob/dubbo-2.7.23/dubbo-rpc/dubbo-rpc-dubbo/src/main/java/org/apache/dubbo/rpc/protocol/dubbo/DubboCodec.java#L204
+pub fn get_req_body_fields(config: &DubboConfig, payload: &[u8], info: &mut DubboInfo) -> u32 {
+    let mut decoder = Hessian2Decoder::default();
+    let mut offset = 0;
+    for _ in BODY_PARAM_MIN..BODY_PARAM_MAX {
+        match decoder.decode_field(payload, offset) {
+            (Some(HessianValue::String(_) | HessianValue::Null), len) => offset += len,
+            _ => return 0,
+        }
+    }
+    let (Some(HessianValue::String(desc)), len) = decoder.decode_field(payload, offset) else {
+        return 0;
+    };
+    offset += len;
+
+    let mut skipped = 0;
+    for _ in 0..get_param_count(&desc) {
+        if offset >= payload.len() {
+            return skipped;
+        }
+        let (value, len) = decoder.decode_field(payload, offset);
+        offset += len;
+        match value {
+            // object 参数也被解析为 map
+            Some(HessianValue::Map(fields)) => {
+                skipped += add_attributes(&config.extract_arguments, fields, info)
+            }
+            // 基本类型参数没有字段名，不提取
+            Some(_) => (),
+            // list、引用及未知类型
+            None => skipped += 1,
+        }
+    }
+
+    if offset < payload.len() {
+        if let (Some(HessianValue::Map(attachments)), _) = decoder.decode_field(payload, offset) {
+            skipped += add_attributes(&config.extract_attachments, attachments, info);
+        }
+    }
+    skipped
+}
+
 #[allow(unused_variables)]
 pub fn get_resp_body_info(
     config: &L7LogDynamicConfig,
@@ -40,9 +40,11 @@ fn decode_tc_string(payload: &[u8]) -> Option<String> {
 
 fn lookup_str(payload: &[u8], trace_type: &TraceType) -> Option<String> {
     let tag = match trace_type {
-        TraceType::Sw3 | TraceType::Sw8 | TraceType::CloudWise | TraceType::Customize(_) => {
-            trace_type.as_str()
-        }
+        TraceType::Sw3
+        | TraceType::Sw8
+        | TraceType::CloudWise
+        | TraceType::Customize(_)
+        | TraceType::Formatted(..) => trace_type.as_str(),
         _ => return None,
     };
     if tag.len() <= 1 {
@@ -31,10 +31,15 @@ use crate::{
         l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
         meta_packet::ApplicationFlags,
     },
-    config::handler::{LogParserConfig, TraceType},
+    config::{
+        config::ExtraLogFieldsInfo,
+        handler::{L7LogDynamicConfig, LogParserConfig, TraceType},
+    },
     flow_generator::{
         protocol_logs::{
-            pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response, TraceInfo},
+            pb_adapter::{
+                ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response, TraceInfo,
+            },
             set_captured_byte, swap_if, L7ResponseStatus, PrioFields, BASE_FIELD_PRIORITY,
         },
         AppProtoHead, Error, Result,
@@ -45,8 +50,11 @@ use self::hessian::{FieldEnum, HessianObjIterator};
 
 const REQ_HDR_LEN: usize = 22;
 const RESP_HDR_LEN: usize = 20;
+// bolt v2 has extra ver1 and switch fields
+const V2_EXTRA_HDR_LEN: usize = 2;
 
 const PROTO_BOLT_V1: u8 = 1;
+const PROTO_BOLT_V2: u8 = 2;
 
 const TYPE_REQ: u8 = 1;
 const TYPE_RESP: u8 = 0;
@@ -63,6 +71,13 @@ const HDR_METHOD_KEY: &'static str = "sofa_head_method_name";
 const METHOD_KEY: &'static str = "methodName";
 const HDR_TRACE_ID_KEY: &'static str = "rpc_trace_context.sofaTraceId";
 const TRACE_ID_KEY: &'static str = "sofaTraceId";
+const HDR_APP_KEY: &'static str = "app";
+const HDR_CALLER_APP_KEY: &'static str = "rpc_trace_context.sofaCallerApp";
+const HDR_BAGGAGE_KEY: &'static str = "rpc_trace_context.sofaPenAttrs";
+const HDR_SYS_BAGGAGE_KEY: &'static str = "rpc_trace_context.sysPenAttrs";
+
+const CALLER_APP_ATTR: &'static str = "caller_app";
+const SERVICE_VERSION_ATTR: &'static str = "service_version";
 
 pub const SOFA_NEW_RPC_TRACE_CTX_KEY: &'static str = "new_rpc_trace_context";
 
@@ -76,6 +91,8 @@ struct Hdr {
     resp_code: u16,
     hdr_len: u16,
     content_len: u32,
+    // length of the fixed header, depends on protocol version
+    fixed_len: usize,
 }
 
 impl TryFrom<&[u8]> for Hdr {
@@ -94,6 +111,18 @@ impl TryFrom<&[u8]> for Hdr {
         * |                               ... ...                                                         |
         * +-----------------------------------------------------------------------------------------------+
 
+        * Request command protocol for v2
+        * 0     1     2           4           6           8          10     11     12          14         16
+        * +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+
+        * |proto| ver1|type | cmdcode   |ver2 |   requestId           |codec|switch|   timeout             |
+        * +-----------+-----------+-----------+-----------+-----------+-----------+-----------+-----------+
+        * |classLen   |headerLen  |contentLen             |           ... ...                             |
+        * +-----------+-----------+-----------+-----------+                                               +
+        * |               className + header  + content  bytes                                            |
+        * +                                                                                               +
+        * |                               ... ...                               | CRC32(optional)         |
+        * +-----------------------------------------------------------------------------------------------+
+
         * Response command protocol for v1
         * 0     1     2     3     4           6           8          10           12          14         16
         * +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+
@@ -105,25 +134,46 @@ impl TryFrom<&[u8]> for Hdr {
         * +                                                                                               +
         * |                               ... ...                                                         |
         * +-----------------------------------------------------------------------------------------------+
+
+        * Response command protocol for v2
+        * 0     1     2           4           6           8          10     11     12          14         16
+        * +-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+-----+
+        * |proto| ver1| type| cmdcode   |ver2 |   requestId           |codec|switch|respstatus |  classLen |
+        * +-----------+-----------+-----------+-----------+-----------+-----------+-----------+-----------+
+        * |headerLen  | contentLen            |                      ... ...                              |
+        * +-----------------------------------+                                                           +
+        * |                         className + header  + content  bytes                                  |
+        * +                                                                                               +
+        * |                               ... ...                               | CRC32(optional)         |
+        * +-----------------------------------------------------------------------------------------------+
+
+        * CRC32 is appended when the crc bit of switch is set, it is not counted in contentLen
     */
     fn try_from(payload: &[u8]) -> Result<Self, Self::Error> {
         if payload.len() < 10 {
             return Err(Error::L7ProtocolUnknown);
         }
         let proto = payload[0];
-        let typ = payload[1];
-        let code_c = payload[9];
+        // offset of fields before codec and after codec
+        let (pre, post) = match proto {
+            PROTO_BOLT_V1 => (0, 0),
+            PROTO_BOLT_V2 if payload.len() >= 12 => (1, V2_EXTRA_HDR_LEN),
+            _ => return Err(Error::L7ProtocolUnknown),
+        };
+        let typ = payload[1 + pre];
+        let code_c = payload[9 + pre];
         match typ {
             TYPE_REQ => {
-                if payload.len() < REQ_HDR_LEN {
+                let fixed_len = REQ_HDR_LEN + post;
+                if payload.len() < fixed_len {
                     return Err(Error::L7ProtocolUnknown);
                 }
-                let cmd_code = read_u16_be(&payload[2..4]);
-                let req_id = read_u32_be(&payload[5..9]);
+                let cmd_code = read_u16_be(&payload[2 + pre..]);
+                let req_id = read_u32_be(&payload[5 + pre..]);
                 let (class_len, hdr_len, content_len) = (
-                    read_u16_be(&payload[14..16]),
-                    read_u16_be(&payload[16..18]),
-                    read_u32_be(&payload[18..22]),
+                    read_u16_be(&payload[14 + post..]),
+                    read_u16_be(&payload[16 + post..]),
+                    read_u32_be(&payload[18 + post..]),
                 );
                 Ok(Self {
                     proto,
@@ -135,19 +185,21 @@ impl TryFrom<&[u8]> for Hdr {
                     class_len,
                     hdr_len,
                     content_len,
+                    fixed_len,
                 })
             }
             TYPE_RESP => {
-                if payload.len() < RESP_HDR_LEN {
+                let fixed_len = RESP_HDR_LEN + post;
+                if payload.len() < fixed_len {
                     return Err(Error::L7ProtocolUnknown);
                 }
-                let cmd_code = read_u16_be(&payload[2..4]);
-                let req_id = read_u32_be(&payload[5..9]);
-                let resp_code = read_u16_be(&payload[10..12]);
+                let cmd_code = read_u16_be(&payload[2 + pre..]);
+                let req_id = read_u32_be(&payload[5 + pre..]);
+                let resp_code = read_u16_be(&payload[10 + post..]);
                 let (class_len, hdr_len, content_len) = (
-                    read_u16_be(&payload[12..14]),
-                    read_u16_be(&payload[14..16]),
-                    read_u32_be(&payload[16..20]),
+                    read_u16_be(&payload[12 + post..]),
+                    read_u16_be(&payload[14 + post..]),
+                    read_u32_be(&payload[16 + post..]),
                 );
                 Ok(Self {
                     proto,
@@ -159,6 +211,7 @@ impl TryFrom<&[u8]> for Hdr {
                     class_len,
                     hdr_len,
                     content_len,
+                    fixed_len,
                 })
             }
             _ => return Err(Error::L7ProtocolUnknown),
@@ -177,6 +230,8 @@ pub struct SofaRpcInfo {
 
     target_serv: String,
     method: String,
+    service_version: String,
+    caller_app: String,
     cmd_code: u16,
     trace_ids: PrioFields,
     span_id: String,
@@ -190,6 +245,9 @@ pub struct SofaRpcInfo {
     resp_code: u16,
     status: L7ResponseStatus,
 
+    #[serde(skip)]
+    attributes: Vec<KeyVal>,
+
     #[serde(skip)]
     is_on_blacklist: bool,
     #[serde(skip)]
@@ -219,6 +277,41 @@ impl SofaRpcInfo {
         }
     }
 
+    // only trace types with explicit formats are looked up in the header map,
+    // ids from the native sofa trace context take precedence
+    fn fill_with_trace_headers(
+        &mut self,
+        config: &L7LogDynamicConfig,
+        headers: &[(String, String)],
+    ) {
+        for (key, val) in headers {
+            for (i, t) in config.trace_types.iter().enumerate() {
+                if !matches!(t, TraceType::Formatted(..)) || !t.check(key) {
+                    continue;
+                }
+                if !config.multiple_trace_id_collection && !self.trace_ids.is_empty() {
+                    break;
+                }
+                if let Some(id) = t.decode_trace_id(val) {
+                    self.trace_ids
+                        .merge_field(BASE_FIELD_PRIORITY + 2 + i as u8, id.to_string());
+                }
+            }
+            if !self.span_id.is_empty() {
+                continue;
+            }
+            for t in config.span_types.iter() {
+                if !matches!(t, TraceType::Formatted(..)) || !t.check(key) {
+                    continue;
+                }
+                if let Some(id) = t.decode_span_id(val) {
+                    self.span_id = id.to_string();
+                    break;
+                }
+            }
+        }
+    }
+
     fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::SofaRPC) {
             self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.target_serv)
@@ -279,6 +372,20 @@ impl From<SofaRpcInfo> for L7ProtocolSendLog {
         } else {
             ApplicationFlags::NONE.bits()
         };
+        let mut attributes = vec![];
+        if !s.caller_app.is_empty() {
+            attributes.push(KeyVal {
+                key: CALLER_APP_ATTR.to_string(),
+                val: s.caller_app,
+            });
+        }
+        if !s.service_version.is_empty() {
+            attributes.push(KeyVal {
+                key: SERVICE_VERSION_ATTR.to_string(),
+                val: s.service_version,
+            });
+        }
+        attributes.extend(s.attributes);
         Self {
             captured_request_byte: s.captured_request_byte,
             captured_response_byte: s.captured_response_byte,
@@ -304,6 +411,7 @@ impl From<SofaRpcInfo> for L7ProtocolSendLog {
             ext_info: Some(ExtendedInfo {
                 rpc_service: Some(s.target_serv),
                 request_id: Some(s.req_id),
+                attributes: Some(attributes),
                 ..Default::default()
             }),
             flags,
@@ -403,10 +511,6 @@ impl SofaRpcLog {
     ) -> Result<bool> {
         let hdr = Hdr::try_from(payload)?;
         info.proto = hdr.proto;
-        // now only support bolt v1
-        if info.proto != PROTO_BOLT_V1 {
-            return Err(Error::L7ProtocolUnknown);
-        }
 
         info.cmd_code = hdr.cmd_code;
         if info.cmd_code == CMD_CODE_HEARTBEAT {
@@ -420,12 +524,12 @@ impl SofaRpcLog {
         info.req_id = hdr.req_id;
         info.msg_type = match hdr.typ {
             TYPE_REQ => {
-                payload = &payload[REQ_HDR_LEN..];
+                payload = &payload[hdr.fixed_len..];
                 info.req_len = hdr.content_len + (hdr.hdr_len as u32) + (hdr.class_len as u32);
                 LogMessageType::Request
             }
             TYPE_RESP => {
-                payload = &payload[RESP_HDR_LEN..];
+                payload = &payload[hdr.fixed_len..];
                 info.resp_code = hdr.resp_code;
                 info.resp_len = hdr.content_len + (hdr.hdr_len as u32) + (hdr.class_len as u32);
                 info.status = match info.resp_code {
@@ -459,10 +563,14 @@ impl SofaRpcLog {
         }
 
         payload = &payload[hdr.class_len as usize..];
-        let multiple_trace_id_collection = if let Some(config) = param.parse_config {
-            config.l7_log_dynamic.multiple_trace_id_collection
+        let trace_config = param.parse_config.map(|c| &c.l7_log_dynamic);
+        let (multiple_trace_id_collection, custom_fields) = if let Some(config) = trace_config {
+            (
+                config.multiple_trace_id_collection,
+                config.extra_log_fields.sofarpc.as_slice(),
+            )
         } else {
-            true
+            (true, &[][..])
         };
 
         if hdr.hdr_len != 0 {
@@ -479,9 +587,17 @@ impl SofaRpcLog {
                 hdr_payload
             };
 
-            let sofa_hdr = SofaHdr::from(hdr_payload);
+            let formatted_trace_config = trace_config.filter(|c| {
+                c.trace_types
+                    .iter()
+                    .chain(c.span_types.iter())
+                    .any(|t| matches!(t, TraceType::Formatted(..)))
+            });
+            let sofa_hdr = SofaHdr::new(hdr_payload, custom_fields, formatted_trace_config);
             info.target_serv = sofa_hdr.service;
             info.method = sofa_hdr.method;
+            info.caller_app = sofa_hdr.caller_app;
+            info.attributes = sofa_hdr.attributes;
             if !sofa_hdr.trace_id.is_empty() {
                 info.trace_ids
                     .merge_field(BASE_FIELD_PRIORITY, sofa_hdr.trace_id);
@@ -490,6 +606,10 @@ impl SofaRpcLog {
             if !sofa_hdr.new_rpc_trace_context.is_empty() {
                 info.fill_with_trace_ctx(sofa_hdr.new_rpc_trace_context);
             }
+
+            if let Some(config) = formatted_trace_config {
+                info.fill_with_trace_headers(config, &sofa_hdr.trace_headers);
+            }
         }
         // parse req hessian2 obj
         if hdr.code_c == CODE_C_HESSIAN && payload.len() != 0 && hdr.typ == TYPE_REQ {
@@ -527,6 +647,10 @@ impl SofaRpcLog {
         if check && (info.target_serv.is_empty() || info.method.is_empty()) {
             return Err(Error::L7ProtocolUnknown);
         }
+        // targetServiceUniqueName is formatted as `interface:version[:uniqueId]`
+        if let Some(version) = info.target_serv.split(':').nth(1) {
+            info.service_version = version.to_string();
+        }
 
         if check {
             Ok(false)
@@ -536,12 +660,17 @@ impl SofaRpcLog {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct SofaHdr {
     service: String,
     method: String,
     trace_id: String,
     new_rpc_trace_context: String,
+    caller_app: String,
+    // configured custom fields found in header or baggage
+    attributes: Vec<KeyVal>,
+    // headers configured as apm_trace_id or apm_span_id, keys are in lowercase
+    trace_headers: Vec<(String, String)>,
 }
 
 /*
@@ -555,19 +684,28 @@ struct SofaHdr {
        rpc_trace_context.sofaTraceId: ${trace_id}
 
     the const var define in source: https://github.com/sofastack/sofa-rpc/blob/7931102255d6ea95ee75676d368aad37c56b57ee/core/api/src/main/java/com/alipay/sofa/rpc/common/RemotingConstants.java
+
+    baggage is carried in `rpc_trace_context.sofaPenAttrs` and `rpc_trace_context.sysPenAttrs` like
+       rpc_trace_context.sofaPenAttrs: tenant=acme&zone=gz00a&
 */
-impl From<&[u8]> for SofaHdr {
-    fn from(mut payload: &[u8]) -> Self {
-        let mut ret = Self {
-            service: "".to_string(),
-            method: "".to_string(),
-            trace_id: "".to_string(),
-            new_rpc_trace_context: "".to_string(),
-        };
+impl SofaHdr {
+    fn new(
+        mut payload: &[u8],
+        custom_fields: &[ExtraLogFieldsInfo],
+        trace_config: Option<&L7LogDynamicConfig>,
+    ) -> Self {
+        let mut ret = Self::default();
         while let Some((key, val)) = read_b32_kv(&mut payload) {
             let Ok(key_str) = std::str::from_utf8(key) else {
                 return ret;
             };
+            if let Some(config) = trace_config {
+                let key_lower = key_str.to_ascii_lowercase();
+                if config.is_trace_id(&key_lower) || config.is_span_id(&key_lower) {
+                    ret.trace_headers
+                        .push((key_lower, String::from_utf8_lossy(val).to_string()));
+                }
+            }
             match key_str {
                 HDR_SERVICE_KEY => ret.service = String::from_utf8_lossy(val).to_string(),
                 HDR_METHOD_KEY => ret.method = String::from_utf8_lossy(val).to_string(),
@@ -575,11 +713,38 @@ impl From<&[u8]> for SofaHdr {
                 SOFA_NEW_RPC_TRACE_CTX_KEY => {
                     ret.new_rpc_trace_context = String::from_utf8_lossy(val).to_string()
                 }
+                HDR_APP_KEY => ret.caller_app = String::from_utf8_lossy(val).to_string(),
+                HDR_CALLER_APP_KEY if ret.caller_app.is_empty() => {
+                    ret.caller_app = String::from_utf8_lossy(val).to_string()
+                }
                 _ => {}
             }
+            if custom_fields.is_empty() {
+                continue;
+            }
+            if key_str == HDR_BAGGAGE_KEY || key_str == HDR_SYS_BAGGAGE_KEY {
+                let mut baggage = val;
+                while let Some((k, v)) = read_url_param_kv(&mut baggage) {
+                    ret.add_custom_field(custom_fields, k, v);
+                }
+            } else {
+                ret.add_custom_field(custom_fields, key, val);
+            }
         }
         ret
     }
+
+    fn add_custom_field(&mut self, custom_fields: &[ExtraLogFieldsInfo], key: &[u8], val: &[u8]) {
+        if custom_fields
+            .iter()
+            .any(|f| f.field_name.as_bytes().eq_ignore_ascii_case(key))
+        {
+            self.attributes.push(KeyVal {
+                key: String::from_utf8_lossy(key).to_string(),
+                val: String::from_utf8_lossy(val).to_string(),
+            });
+        }
+    }
 }
 
 fn read_b32_kv<'a>(payload: &mut &'a [u8]) -> Option<(&'a [u8], &'a [u8])> {
@@ -677,9 +842,13 @@ mod test {
             l7_protocol_info::L7ProtocolInfo,
             l7_protocol_log::{L7PerfCache, L7ProtocolParserInterface, ParseParam},
         },
+        config::{
+            config::{ExtraLogFields, ExtraLogFieldsInfo},
+            handler::{L7LogDynamicConfigBuilder, LogParserConfig},
+        },
         flow_generator::{
             protocol_logs::{
-                rpc::sofa_rpc::{CMD_CODE_REQ, CMD_CODE_RESP, PROTO_BOLT_V1},
+                rpc::sofa_rpc::{CMD_CODE_REQ, CMD_CODE_RESP, PROTO_BOLT_V1, PROTO_BOLT_V2},
                 L7ResponseStatus,
             },
             L7_RRT_CACHE_CAPACITY,
@@ -913,4 +1082,111 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_sofarpc_bolt_v2_crc() {
+        let pcap_file = Path::new("resources/test/flow_generator/sofarpc/sofa-bolt-v2-crc.pcap");
+        let log_cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let capture = Capture::load_pcap(pcap_file);
+        let mut p = capture.collect::<Vec<_>>();
+        p[0].lookup_key.direction = PacketDirection::ClientToServer;
+        p[1].lookup_key.direction = PacketDirection::ServerToClient;
+        let config = LogParserConfig {
+            l7_log_dynamic: L7LogDynamicConfigBuilder {
+                extra_log_fields: ExtraLogFields {
+                    sofarpc: vec![
+                        ExtraLogFieldsInfo {
+                            field_name: "tenant".to_string(),
+                            ..Default::default()
+                        },
+                        ExtraLogFieldsInfo {
+                            field_name: "rpc_trace_context.sofaRpcId".to_string(),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        };
+        let mut parser = SofaRpcLog::default();
+
+        let req_param = &mut ParseParam::new(
+            &p[0],
+            Some(log_cache.clone()),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        req_param.set_log_parser_config(&config);
+        let req_payload = p[0].get_l4_payload().unwrap();
+        req_param.set_captured_byte(req_payload.len());
+        assert_eq!(
+            parser.check_payload(req_payload, req_param),
+            Some(LogMessageType::Request)
+        );
+        let req_info = parser
+            .parse_payload(req_payload, req_param)
+            .unwrap()
+            .unwrap_single();
+        if let L7ProtocolInfo::SofaRpcInfo(k) = &req_info {
+            assert_eq!(k.msg_type, LogMessageType::Request);
+            assert_eq!(k.proto, PROTO_BOLT_V2);
+            assert_eq!(k.cmd_code, CMD_CODE_REQ);
+            assert_eq!(k.req_id, 7);
+            assert_eq!(k.req_len, 462);
+            assert_eq!(k.target_serv, "com.example.HelloService:2.0");
+            assert_eq!(k.method, "sayHello");
+            assert_eq!(k.service_version, "2.0");
+            assert_eq!(k.caller_app, "hello-client");
+            assert_eq!(k.trace_ids.highest(), "0a0fe8a01700000000001100112345");
+            assert_eq!(
+                k.endpoint.as_deref(),
+                Some("com.example.HelloService:2.0/sayHello")
+            );
+            assert_eq!(
+                k.attributes
+                    .iter()
+                    .map(|kv| (kv.key.as_str(), kv.val.as_str()))
+                    .collect::<Vec<_>>(),
+                vec![("rpc_trace_context.sofaRpcId", "0.1"), ("tenant", "acme")]
+            );
+        } else {
+            unreachable!()
+        }
+
+        parser.reset();
+
+        let resp_param = &mut ParseParam::new(
+            &p[1],
+            Some(log_cache.clone()),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        resp_param.set_log_parser_config(&config);
+        let resp_payload = p[1].get_l4_payload().unwrap();
+        resp_param.set_captured_byte(resp_payload.len());
+        let resp_info = parser
+            .parse_payload(resp_payload, resp_param)
+            .unwrap()
+            .unwrap_single();
+        if let L7ProtocolInfo::SofaRpcInfo(k) = &resp_info {
+            assert_eq!(k.msg_type, LogMessageType::Response);
+            assert_eq!(k.proto, PROTO_BOLT_V2);
+            assert_eq!(k.cmd_code, CMD_CODE_RESP);
+            assert_eq!(k.req_id, 7);
+            assert_eq!(k.resp_code, 0);
+            assert_eq!(k.status, L7ResponseStatus::Ok);
+            assert_eq!(k.resp_len, 47);
+        } else {
+            unreachable!()
+        }
+    }
 }
@@ -0,0 +1,868 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::VecDeque, mem};
+
+use serde::Serialize;
+
+use super::{
+    pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
+    AppProtoHead, L7ResponseStatus,
+};
+use crate::{
+    common::{
+        enums::IpProtocol,
+        flow::{L7PerfStats, PacketDirection},
+        l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
+        l7_protocol_log::{L7ParseResult, L7ProtocolParserInterface, LogCache, ParseParam},
+        meta_packet::ApplicationFlags,
+    },
+    config::handler::LogParserConfig,
+    flow_generator::error::{Error, Result},
+};
+use public::l7_protocol::{L7Protocol, L7ProtocolEnum, LogMessageType};
+
+const CRLF: &[u8] = b"\r\n";
+// the message content of DATA is terminated by a line containing only a period
+const END_OF_DATA: &[u8] = b"\r\n.\r\n";
+
+// start mail input reply of DATA
+const REPLY_START_MAIL_INPUT: u16 = 354;
+// server challenge during AUTH, the next client line is a credential
+const REPLY_AUTH_CONTINUE: u16 = 334;
+// ready to start TLS reply of STARTTLS
+const REPLY_SERVICE_READY: u16 = 220;
+
+// limits the number of commands waiting for a reply
+const MAX_PENDING_COMMANDS: usize = 64;
+const MAX_RCPT_DOMAINS: usize = 16;
+
+const COMMANDS: [&str; 13] = [
+    "HELO", "EHLO", "MAIL", "RCPT", "DATA", "RSET", "VRFY", "EXPN", "HELP", "NOOP", "QUIT",
+    "STARTTLS", "AUTH",
+];
+
+// returns the command verb in upper case and its argument
+fn parse_command(line: &[u8]) -> Option<(&'static str, &[u8])> {
+    let (verb, arg) = match line.iter().position(|b| *b == b' ') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => (line, &line[line.len()..]),
+    };
+    COMMANDS
+        .iter()
+        .find(|c| c.as_bytes().eq_ignore_ascii_case(verb))
+        .map(|c| (*c, arg))
+}
+
+// reply line is "ddd text" or "ddd-text" for all but the last line of a multiline reply
+fn parse_reply(line: &[u8]) -> Option<(u16, bool, &[u8])> {
+    if line.len() < 3
+        || !(b'2'..=b'5').contains(&line[0])
+        || !line[1].is_ascii_digit()
+        || !line[2].is_ascii_digit()
+    {
+        return None;
+    }
+    let code =
+        (line[0] - b'0') as u16 * 100 + (line[1] - b'0') as u16 * 10 + (line[2] - b'0') as u16;
+    match line.get(3) {
+        None => Some((code, true, &line[3..])),
+        Some(b' ') => Some((code, true, &line[4..])),
+        Some(b'-') => Some((code, false, &line[4..])),
+        _ => None,
+    }
+}
+
+// only the domain part of the mailbox is kept, e.g. "FROM:<Alice@Example.com> SIZE=100" => "example.com"
+fn mailbox_domain(arg: &[u8]) -> String {
+    let Some(start) = arg.iter().position(|b| *b == b'<') else {
+        return String::new();
+    };
+    let mailbox = &arg[start + 1..];
+    let mailbox = match mailbox.iter().position(|b| *b == b'>') {
+        Some(end) => &mailbox[..end],
+        None => mailbox,
+    };
+    match mailbox.iter().rposition(|b| *b == b'@') {
+        Some(at) => String::from_utf8_lossy(&mailbox[at + 1..]).to_ascii_lowercase(),
+        None => String::new(),
+    }
+}
+
+fn reply_status(code: u16) -> L7ResponseStatus {
+    match code / 100 {
+        2 | 3 => L7ResponseStatus::Ok,
+        4 => L7ResponseStatus::ClientError,
+        _ => L7ResponseStatus::ServerError,
+    }
+}
+
+// returns lines without CRLF and their length on wire, a trailing partial line is included
+fn lines(mut payload: &[u8]) -> impl Iterator<Item = (&[u8], u32)> {
+    std::iter::from_fn(move || {
+        if payload.is_empty() {
+            return None;
+        }
+        let (line, len) = match payload.windows(CRLF.len()).position(|w| w == CRLF) {
+            Some(i) => (&payload[..i], i + CRLF.len()),
+            None => (payload, payload.len()),
+        };
+        payload = &payload[len..];
+        Some((line, len as u32))
+    })
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct SmtpInfo {
+    pub msg_type: LogMessageType,
+
+    #[serde(skip)]
+    session_id: u32,
+    // MAIL for a mail transaction, otherwise the command verb
+    #[serde(rename = "request_type", skip_serializing_if = "String::is_empty")]
+    pub command: String,
+    // sender domain for mail transactions, client identity for HELO/EHLO
+    #[serde(rename = "request_domain", skip_serializing_if = "String::is_empty")]
+    pub domain: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rcpt_domains: Vec<String>,
+
+    #[serde(rename = "response_code", skip_serializing_if = "Option::is_none")]
+    pub reply_code: Option<u16>,
+    #[serde(
+        rename = "response_exception",
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub reply_text: String,
+
+    pub captured_request_byte: u32,
+    pub captured_response_byte: u32,
+
+    rrt: u64,
+
+    #[serde(skip)]
+    is_tls: bool,
+    #[serde(skip)]
+    is_on_blacklist: bool,
+}
+
+impl L7ProtocolInfoInterface for SmtpInfo {
+    fn session_id(&self) -> Option<u32> {
+        Some(self.session_id)
+    }
+
+    fn merge_log(&mut self, other: &mut L7ProtocolInfo) -> Result<()> {
+        if let L7ProtocolInfo::SmtpInfo(other) = other {
+            self.merge(other);
+        }
+        Ok(())
+    }
+
+    fn app_proto_head(&self) -> Option<AppProtoHead> {
+        Some(AppProtoHead {
+            proto: L7Protocol::SMTP,
+            msg_type: self.msg_type,
+            rrt: self.rrt,
+        })
+    }
+
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn get_request_domain(&self) -> String {
+        self.domain.clone()
+    }
+
+    fn is_on_blacklist(&self) -> bool {
+        self.is_on_blacklist
+    }
+}
+
+impl SmtpInfo {
+    fn merge(&mut self, other: &mut Self) {
+        // request bytes of RCPT, DATA and the message content are carried by the final reply
+        self.captured_request_byte += other.captured_request_byte;
+        self.captured_response_byte += other.captured_response_byte;
+        if self.command.is_empty() {
+            mem::swap(&mut self.command, &mut other.command);
+        }
+        if self.domain.is_empty() {
+            mem::swap(&mut self.domain, &mut other.domain);
+        }
+        if self.rcpt_domains.is_empty() {
+            mem::swap(&mut self.rcpt_domains, &mut other.rcpt_domains);
+        }
+        if other.reply_code.is_some() {
+            self.reply_code = other.reply_code;
+            mem::swap(&mut self.reply_text, &mut other.reply_text);
+        }
+        self.is_on_blacklist |= other.is_on_blacklist;
+    }
+
+    fn set_is_on_blacklist(&mut self, config: &LogParserConfig) {
+        let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::SMTP) else {
+            return;
+        };
+        self.is_on_blacklist = t.request_type.is_on_blacklist(&self.command)
+            || t.request_domain.is_on_blacklist(&self.domain);
+    }
+
+    fn status(&self) -> L7ResponseStatus {
+        match self.reply_code {
+            Some(code) => reply_status(code),
+            None => L7ResponseStatus::Unknown,
+        }
+    }
+}
+
+impl From<SmtpInfo> for L7ProtocolSendLog {
+    fn from(f: SmtpInfo) -> Self {
+        let flags = if f.is_tls {
+            ApplicationFlags::TLS.bits()
+        } else {
+            ApplicationFlags::NONE.bits()
+        };
+        let status = f.status();
+        let exception = match status {
+            L7ResponseStatus::ClientError | L7ResponseStatus::ServerError => f.reply_text,
+            _ => String::new(),
+        };
+        let attributes = if !f.rcpt_domains.is_empty() {
+            Some(vec![KeyVal {
+                key: "rcpt_domains".to_string(),
+                val: f.rcpt_domains.join(","),
+            }])
+        } else {
+            None
+        };
+        L7ProtocolSendLog {
+            captured_request_byte: f.captured_request_byte,
+            captured_response_byte: f.captured_response_byte,
+            req: L7Request {
+                req_type: f.command,
+                domain: f.domain,
+                ..Default::default()
+            },
+            resp: L7Response {
+                status,
+                code: f.reply_code.map(|c| c as i32),
+                exception,
+                ..Default::default()
+            },
+            ext_info: Some(ExtendedInfo {
+                attributes,
+                ..Default::default()
+            }),
+            flags,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&SmtpInfo> for LogCache {
+    fn from(info: &SmtpInfo) -> Self {
+        LogCache {
+            msg_type: info.msg_type,
+            resp_status: info.status(),
+            on_blacklist: info.is_on_blacklist,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pending {
+    // commands logged by themselves, e.g. EHLO, QUIT
+    Command(u32, &'static str),
+    // commands belonging to the mail transaction
+    Mail,
+    Rcpt,
+    Data,
+    EndOfData,
+}
+
+// MAIL FROM, RCPT TO, DATA and the message content are reported as one log
+#[derive(Default)]
+struct Transaction {
+    session_id: u32,
+    rcpt_domains: Vec<String>,
+    // the last failed RCPT reply, reported if the transaction is aborted
+    rejected: Option<(u16, String)>,
+    request_bytes: u32,
+    response_bytes: u32,
+}
+
+#[derive(Default)]
+pub struct SmtpLog {
+    pending: VecDeque<Pending>,
+    transaction: Option<Transaction>,
+    // client is sending message content after DATA is accepted
+    in_data: bool,
+    // the last message content segment ended with CRLF
+    data_line_start: bool,
+    // the next client line answers a AUTH challenge and should not be parsed
+    in_auth: bool,
+    // STARTTLS is accepted, the remaining of the connection is TLS
+    starttls: bool,
+    next_session_id: u32,
+    perf_stats: Vec<L7PerfStats>,
+}
+
+impl L7ProtocolParserInterface for SmtpLog {
+    fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
+        if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
+            return None;
+        }
+        let (line, _) = lines(payload).next()?;
+        // the server speaks first with a greeting, which usually names the protocol
+        if let Some((code, _, text)) = parse_reply(line) {
+            let text = String::from_utf8_lossy(text).to_ascii_uppercase();
+            if code == REPLY_SERVICE_READY && text.contains("SMTP") {
+                return Some(LogMessageType::Response);
+            }
+            return None;
+        }
+        match parse_command(line) {
+            Some(("HELO" | "EHLO", arg)) if !arg.is_empty() => Some(LogMessageType::Request),
+            _ => None,
+        }
+    }
+
+    fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
+        // the flow is kept by this parser when no TLS parser is available
+        if self.starttls {
+            return Ok(L7ParseResult::None);
+        }
+        let mut infos = match param.direction {
+            PacketDirection::ClientToServer => self.parse_commands(payload)?,
+            PacketDirection::ServerToClient => self.parse_replies(payload, param.is_tls())?,
+        };
+
+        self.perf_stats.clear();
+        for info in infos.iter_mut() {
+            info.is_tls = param.is_tls();
+            if let Some(config) = param.parse_config {
+                info.set_is_on_blacklist(config);
+            }
+            if param.parse_perf {
+                let mut perf_stat = L7PerfStats::default();
+                if let Some(stats) = info.perf_stats(param) {
+                    info.rrt = stats.rrt_sum;
+                    perf_stat.sequential_merge(&stats);
+                }
+                self.perf_stats.push(perf_stat);
+            }
+        }
+
+        if !param.parse_log || infos.is_empty() {
+            return Ok(L7ParseResult::None);
+        }
+        if infos.len() == 1 {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::SmtpInfo(
+                infos.pop().unwrap(),
+            )))
+        } else {
+            Ok(L7ParseResult::Multi(
+                infos.into_iter().map(L7ProtocolInfo::SmtpInfo).collect(),
+            ))
+        }
+    }
+
+    fn protocol(&self) -> L7Protocol {
+        L7Protocol::SMTP
+    }
+
+    fn parsable_on_udp(&self) -> bool {
+        false
+    }
+
+    fn upgrade_protocol(&self) -> Option<L7ProtocolEnum> {
+        if self.starttls {
+            Some(L7ProtocolEnum::L7Protocol(L7Protocol::TLS))
+        } else {
+            None
+        }
+    }
+
+    fn perf_stats(&mut self) -> Vec<L7PerfStats> {
+        mem::take(&mut self.perf_stats)
+    }
+}
+
+impl SmtpLog {
+    fn new_session_id(&mut self) -> u32 {
+        let id = self.next_session_id;
+        self.next_session_id = self.next_session_id.wrapping_add(1);
+        id
+    }
+
+    fn push_pending(&mut self, pending: Pending) {
+        if self.pending.len() >= MAX_PENDING_COMMANDS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(pending);
+    }
+
+    // returns the length of the message content in the payload including the terminating line
+    fn parse_data(&mut self, payload: &[u8]) -> usize {
+        let end = if self.data_line_start && payload.starts_with(&END_OF_DATA[2..]) {
+            Some(END_OF_DATA.len() - 2)
+        } else {
+            payload
+                .windows(END_OF_DATA.len())
+                .position(|w| w == END_OF_DATA)
+                .map(|i| i + END_OF_DATA.len())
+        };
+        match end {
+            Some(end) => {
+                self.in_data = false;
+                self.push_pending(Pending::EndOfData);
+                end
+            }
+            None => {
+                self.data_line_start = payload.ends_with(CRLF);
+                payload.len()
+            }
+        }
+    }
+
+    fn parse_commands(&mut self, mut payload: &[u8]) -> Result<Vec<SmtpInfo>> {
+        let mut infos = vec![];
+        if self.in_data {
+            let len = self.parse_data(payload);
+            if let Some(t) = self.transaction.as_mut() {
+                t.request_bytes += len as u32;
+            }
+            payload = &payload[len..];
+        }
+
+        let mut parsed = false;
+        for (line, line_len) in lines(payload) {
+            if self.in_auth {
+                self.in_auth = false;
+                parsed = true;
+                continue;
+            }
+            let Some((command, arg)) = parse_command(line) else {
+                continue;
+            };
+            parsed = true;
+            match command {
+                "MAIL" => {
+                    let session_id = self.new_session_id();
+                    self.transaction = Some(Transaction {
+                        session_id,
+                        ..Default::default()
+                    });
+                    self.push_pending(Pending::Mail);
+                    infos.push(SmtpInfo {
+                        msg_type: LogMessageType::Request,
+                        session_id,
+                        command: command.to_string(),
+                        domain: mailbox_domain(arg),
+                        captured_request_byte: line_len,
+                        ..Default::default()
+                    });
+                }
+                "RCPT" | "DATA" => {
+                    if let Some(t) = self.transaction.as_mut() {
+                        t.request_bytes += line_len;
+                        if command == "RCPT" {
+                            let domain = mailbox_domain(arg);
+                            if !domain.is_empty()
+                                && t.rcpt_domains.len() < MAX_RCPT_DOMAINS
+                                && !t.rcpt_domains.contains(&domain)
+                            {
+                                t.rcpt_domains.push(domain);
+                            }
+                        }
+                    }
+                    self.push_pending(if command == "RCPT" {
+                        Pending::Rcpt
+                    } else {
+                        Pending::Data
+                    });
+                }
+                _ => {
+                    let session_id = self.new_session_id();
+                    self.push_pending(Pending::Command(session_id, command));
+                    infos.push(SmtpInfo {
+                        msg_type: LogMessageType::Request,
+                        session_id,
+                        command: command.to_string(),
+                        domain: match command {
+                            "HELO" | "EHLO" => String::from_utf8_lossy(arg).into_owned(),
+                            _ => String::new(),
+                        },
+                        captured_request_byte: line_len,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        if !parsed && !payload.is_empty() {
+            return Err(Error::L7LogParseFailed {
+                proto: L7Protocol::SMTP,
+                reason: "no SMTP command found".into(),
+            });
+        }
+        Ok(infos)
+    }
+
+    fn finish_transaction(&mut self, code: u16, text: String, bytes: u32) -> Option<SmtpInfo> {
+        let t = self.transaction.take()?;
+        Some(SmtpInfo {
+            msg_type: LogMessageType::Response,
+            session_id: t.session_id,
+            rcpt_domains: t.rcpt_domains,
+            reply_code: Some(code),
+            reply_text: text,
+            captured_request_byte: t.request_bytes,
+            captured_response_byte: t.response_bytes + bytes,
+            ..Default::default()
+        })
+    }
+
+    fn parse_replies(&mut self, payload: &[u8], is_tls: bool) -> Result<Vec<SmtpInfo>> {
+        let mut infos = vec![];
+        let mut parsed = false;
+        // bytes of a multiline reply
+        let mut reply_bytes = 0;
+        for (line, line_len) in lines(payload) {
+            let Some((code, last, text)) = parse_reply(line) else {
+                if !parsed {
+                    return Err(Error::L7LogParseFailed {
+                        proto: L7Protocol::SMTP,
+                        reason: "invalid SMTP reply".into(),
+                    });
+                }
+                break;
+            };
+            parsed = true;
+            reply_bytes += line_len;
+            if !last {
+                continue;
+            }
+            let bytes = mem::take(&mut reply_bytes);
+            let text = String::from_utf8_lossy(text).into_owned();
+
+            if code == REPLY_AUTH_CONTINUE {
+                self.in_auth = true;
+                continue;
+            }
+            // the greeting or a reply to a command not captured
+            let Some(pending) = self.pending.pop_front() else {
+                continue;
+            };
+            match pending {
+                Pending::Mail if code / 100 != 2 => {
+                    infos.extend(self.finish_transaction(code, text, bytes));
+                }
+                Pending::Rcpt if code / 100 != 2 => {
+                    if let Some(t) = self.transaction.as_mut() {
+                        t.response_bytes += bytes;
+                        t.rejected = Some((code, text));
+                    }
+                }
+                Pending::Data if code == REPLY_START_MAIL_INPUT => {
+                    if let Some(t) = self.transaction.as_mut() {
+                        t.response_bytes += bytes;
+                    }
+                    self.in_data = true;
+                    self.data_line_start = true;
+                }
+                Pending::Data | Pending::EndOfData => {
+                    infos.extend(self.finish_transaction(code, text, bytes));
+                }
+                Pending::Mail | Pending::Rcpt => {
+                    if let Some(t) = self.transaction.as_mut() {
+                        t.response_bytes += bytes;
+                    }
+                }
+                Pending::Command(session_id, command) => {
+                    // the transaction is aborted if it is not completed before these commands
+                    if matches!(command, "RSET" | "HELO" | "EHLO" | "QUIT" | "STARTTLS") {
+                        if let Some(t) = self.transaction.as_mut() {
+                            let (code, text) = t.rejected.take().unwrap_or((code, text.clone()));
+                            infos.extend(self.finish_transaction(code, text, 0));
+                        }
+                    }
+                    infos.push(SmtpInfo {
+                        msg_type: LogMessageType::Response,
+                        session_id,
+                        reply_code: Some(code),
+                        reply_text: text,
+                        captured_response_byte: bytes,
+                        ..Default::default()
+                    });
+                    // TLS handshake starts right after the reply, the remaining of the connection
+                    // is not readable unless it is collected by TLS uprobe
+                    if command == "STARTTLS" && code == REPLY_SERVICE_READY && !is_tls {
+                        self.starttls = true;
+                        self.pending.clear();
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(infos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        common::{l7_protocol_log::L7PerfCache, MetaPacket},
+        flow_generator::L7_RRT_CACHE_CAPACITY,
+    };
+
+    fn parse(
+        smtp: &mut SmtpLog,
+        payload: &[u8],
+        direction: PacketDirection,
+        time: u64,
+        cache: &Rc<RefCell<L7PerfCache>>,
+    ) -> Vec<SmtpInfo> {
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            Some(cache.clone()),
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = direction;
+        param.time = time;
+        match smtp.parse_payload(payload, &param).unwrap() {
+            L7ParseResult::Single(L7ProtocolInfo::SmtpInfo(info)) => vec![info],
+            L7ParseResult::Multi(m) => m
+                .into_iter()
+                .filter_map(|i| match i {
+                    L7ProtocolInfo::SmtpInfo(info) => Some(info),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn check_greeting() {
+        let mut smtp = SmtpLog::default();
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            true,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        assert_eq!(
+            smtp.check_payload(b"220 mx.example.com ESMTP Postfix\r\n", &param),
+            Some(LogMessageType::Response)
+        );
+        assert_eq!(
+            smtp.check_payload(b"EHLO client.example.org\r\n", &param),
+            Some(LogMessageType::Request)
+        );
+        // FTP greeting
+        assert_eq!(smtp.check_payload(b"220 (vsFTPd 3.0.3)\r\n", &param), None);
+    }
+
+    #[test]
+    fn check_pipelined_transaction() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut smtp = SmtpLog::default();
+
+        assert!(parse(
+            &mut smtp,
+            b"220 mx.example.com ESMTP\r\n",
+            PacketDirection::ServerToClient,
+            10,
+            &cache,
+        )
+        .is_empty());
+
+        let infos = parse(
+            &mut smtp,
+            b"EHLO client.example.org\r\n",
+            PacketDirection::ClientToServer,
+            20,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].command, "EHLO");
+        assert_eq!(infos[0].domain, "client.example.org");
+
+        let infos = parse(
+            &mut smtp,
+            b"250-mx.example.com\r\n250-PIPELINING\r\n250 8BITMIME\r\n",
+            PacketDirection::ServerToClient,
+            30,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].reply_code, Some(250));
+        assert_eq!(infos[0].rrt, 10);
+
+        // RFC 2920 pipelining, DATA is the last command of the group
+        let infos = parse(
+            &mut smtp,
+            b"MAIL FROM:<alice@Example.ORG> SIZE=100\r\nRCPT TO:<bob@example.com>\r\nRCPT TO:<carol@example.net>\r\nRCPT TO:<dave@example.com>\r\nDATA\r\n",
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].msg_type, LogMessageType::Request);
+        assert_eq!(infos[0].command, "MAIL");
+        assert_eq!(infos[0].domain, "example.org");
+
+        assert!(parse(
+            &mut smtp,
+            b"250 2.1.0 Ok\r\n250 2.1.5 Ok\r\n550 5.1.1 User unknown\r\n250 2.1.5 Ok\r\n354 End data with <CR><LF>.<CR><LF>\r\n",
+            PacketDirection::ServerToClient,
+            110,
+            &cache,
+        )
+        .is_empty());
+
+        assert!(parse(
+            &mut smtp,
+            b"Subject: test\r\n\r\nhello\r\n",
+            PacketDirection::ClientToServer,
+            120,
+            &cache,
+        )
+        .is_empty());
+        // end of data and QUIT pipelined in one segment
+        let infos = parse(
+            &mut smtp,
+            b".\r\nQUIT\r\n",
+            PacketDirection::ClientToServer,
+            130,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].command, "QUIT");
+
+        let infos = parse(
+            &mut smtp,
+            b"250 2.0.0 Ok: queued as 12345\r\n221 2.0.0 Bye\r\n",
+            PacketDirection::ServerToClient,
+            400,
+            &cache,
+        );
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].reply_code, Some(250));
+        assert_eq!(infos[0].rcpt_domains, vec!["example.com", "example.net"]);
+        assert_eq!(infos[0].status(), L7ResponseStatus::Ok);
+        // from MAIL FROM to the final reply after DATA
+        assert_eq!(infos[0].rrt, 300);
+        assert_eq!(infos[1].reply_code, Some(221));
+        assert_eq!(infos[1].rrt, 270);
+    }
+
+    #[test]
+    fn check_rejected() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut smtp = SmtpLog::default();
+
+        parse(
+            &mut smtp,
+            b"MAIL FROM:<alice@example.org>\r\n",
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        let infos = parse(
+            &mut smtp,
+            b"451 4.7.1 Try again later\r\n",
+            PacketDirection::ServerToClient,
+            200,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].status(), L7ResponseStatus::ClientError);
+        assert_eq!(infos[0].reply_text, "4.7.1 Try again later");
+
+        parse(
+            &mut smtp,
+            b"MAIL FROM:<alice@example.org>\r\nRCPT TO:<bob@example.com>\r\nDATA\r\n",
+            PacketDirection::ClientToServer,
+            300,
+            &cache,
+        );
+        let infos = parse(
+            &mut smtp,
+            b"250 Ok\r\n550 5.7.1 Relay denied\r\n554 5.5.1 No valid recipients\r\n",
+            PacketDirection::ServerToClient,
+            400,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].reply_code, Some(554));
+        assert_eq!(infos[0].status(), L7ResponseStatus::ServerError);
+        assert_eq!(infos[0].rrt, 100);
+    }
+
+    #[test]
+    fn check_starttls() {
+        let cache = Rc::new(RefCell::new(L7PerfCache::new(L7_RRT_CACHE_CAPACITY)));
+        let mut smtp = SmtpLog::default();
+
+        parse(
+            &mut smtp,
+            b"STARTTLS\r\n",
+            PacketDirection::ClientToServer,
+            100,
+            &cache,
+        );
+        assert_eq!(smtp.upgrade_protocol(), None);
+        let infos = parse(
+            &mut smtp,
+            b"220 2.0.0 Ready to start TLS\r\n",
+            PacketDirection::ServerToClient,
+            200,
+            &cache,
+        );
+        assert_eq!(infos.len(), 1);
+        assert_eq!(
+            smtp.upgrade_protocol(),
+            Some(L7ProtocolEnum::L7Protocol(L7Protocol::TLS))
+        );
+        // TLS records are not parsed as SMTP
+        assert!(parse(
+            &mut smtp,
+            &[0x16, 0x03, 0x01, 0x00, 0x05, 0x01, 0x00, 0x00, 0x01, 0x00],
+            PacketDirection::ClientToServer,
+            300,
+            &cache,
+        )
+        .is_empty());
+    }
+}
@@ -14,9 +14,15 @@
  * limitations under the License.
  */
 
-use std::ffi::CStr;
+use std::{
+    ffi::CStr,
+    io::{self, Read},
+    sync::atomic::Ordering,
+};
 
 use bson::{self, Document};
+use flate2::read::ZlibDecoder;
+use log::{debug, warn};
 use serde::Serialize;
 
 use public::l7_protocol::LogMessageType;
@@ -36,6 +42,7 @@ use crate::{
         meta_packet::ApplicationFlags,
     },
     flow_generator::{
+        flow_map::FlowMapCounter,
         protocol_logs::{
             pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
             value_is_default, L7ResponseStatus,
@@ -231,6 +238,10 @@ impl From<&MongoDBInfo> for LogCache {
 pub struct MongoDBLog {
     info: MongoDBInfo,
     perf_stats: Vec<L7PerfStats>,
+
+    // number of OP_COMPRESSED messages with unsupported compressor in this flow, only to warn once
+    // per flow, the total is reported by FlowMapCounter.mongodb_unsupported_compressed
+    unsupported_compressed_count: u32,
 }
 
 impl L7ProtocolParserInterface for MongoDBLog {
@@ -258,7 +269,18 @@ impl L7ProtocolParserInterface for MongoDBLog {
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
         let mut info = MongoDBInfo::default();
 
-        self.parse(payload, param.l4_protocol, param.direction, &mut info)?;
+        let decompress_max_bytes = param
+            .parse_config
+            .map(|c| c.mongodb_decompress_max_bytes)
+            .unwrap_or_else(|| LogParserConfig::default().mongodb_decompress_max_bytes);
+        self.parse(
+            payload,
+            param.l4_protocol,
+            param.direction,
+            decompress_max_bytes,
+            param.stats_counter.as_deref(),
+            &mut info,
+        )?;
         info.is_tls = param.is_tls();
         set_captured_byte!(info, param);
         if let Some(config) = param.parse_config {
@@ -317,6 +339,7 @@ const _EXCEPTION_OFFSET: usize = 20;
 const _COLLECTION_NAME_OFFSET: usize = 20;
 const _QUERY_DOC_OFFSET: usize = _COLLECTION_NAME_OFFSET + 8; // 8 is sizeof(Number to skip + Number to Return)
 const _MSG_DOC_SECTION_OFFSET: usize = _HEADER_SIZE + 4; // 4 is sizeof(Message Flags)
+const _OP_CODE_OFFSET: usize = 12;
 
 impl MongoDBLog {
     // TODO: tracing
@@ -325,6 +348,8 @@ impl MongoDBLog {
         payload: &[u8],
         proto: IpProtocol,
         _direction: PacketDirection,
+        decompress_max_bytes: usize,
+        stats_counter: Option<&FlowMapCounter>,
         info: &mut MongoDBInfo,
     ) -> Result<bool> {
         if proto != IpProtocol::TCP {
@@ -350,6 +375,42 @@ impl MongoDBLog {
             info.response_id = header.request_id;
         }
 
+        if info.op_code == _OP_COMPRESSED && payload.len() > _HEADER_SIZE {
+            let mut compressed = MongoOpCompressed::default();
+            compressed.decode(&payload[_HEADER_SIZE..])?;
+            match compressed.decompress(&payload[_HEADER_SIZE..], decompress_max_bytes) {
+                Ok(message) => {
+                    // replace with the original message and parse as usual
+                    let mut original = Vec::with_capacity(_HEADER_SIZE + message.len());
+                    original.extend_from_slice(&payload[.._OP_CODE_OFFSET]);
+                    original.extend_from_slice(&compressed.original_op_code.to_le_bytes());
+                    original.extend_from_slice(&message);
+                    header.op_code = compressed.original_op_code;
+                    info.op_code = header.op_code;
+                    info.op_code_name = header.get_op_str().to_string();
+                    return self.decode_command(&original, info);
+                }
+                Err(CompressError::UnsupportedCompressor(id)) => {
+                    if self.unsupported_compressed_count == 0 {
+                        warn!("mongodb OP_COMPRESSED with unsupported compressor id {id}");
+                    }
+                    self.unsupported_compressed_count =
+                        self.unsupported_compressed_count.saturating_add(1);
+                    if let Some(counter) = stats_counter {
+                        counter
+                            .mongodb_unsupported_compressed
+                            .fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                Err(e) => debug!("decompress mongodb OP_COMPRESSED failed: {e}"),
+            }
+            return Ok(false);
+        }
+
+        self.decode_command(payload, info)
+    }
+
+    fn decode_command(&self, payload: &[u8], info: &mut MongoDBInfo) -> Result<bool> {
         // command decode
         match info.op_code {
             _OP_MSG if payload.len() > _MSG_DOC_SECTION_OFFSET => {
@@ -498,13 +559,179 @@ impl MongoDBHeader {
     }
 }
 
-// TODO: support compressed
+#[derive(Debug, thiserror::Error)]
+enum CompressError {
+    #[error("unsupported compressor id {0}")]
+    UnsupportedCompressor(u8),
+    #[error("uncompressed size {0} exceeds limit {1}")]
+    TooLarge(u64, usize),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("invalid snappy data: {0}")]
+    Snappy(&'static str),
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct MongoOpCompressed {
     original_op_code: u32,
     uncompressed_size: u32,
     compressor_id: u8,
-    //char: String,
+}
+
+impl MongoOpCompressed {
+    const _ORIGINAL_OP_CODE_OFFSET: usize = 0;
+    const _UNCOMPRESSED_SIZE_OFFSET: usize = 4;
+    const _COMPRESSOR_ID_OFFSET: usize = 8;
+    const _COMPRESSED_MESSAGE_OFFSET: usize = 9;
+
+    const _COMPRESSOR_NOOP: u8 = 0;
+    const _COMPRESSOR_SNAPPY: u8 = 1;
+    const _COMPRESSOR_ZLIB: u8 = 2;
+    const _COMPRESSOR_ZSTD: u8 = 3;
+
+    fn decode(&mut self, payload: &[u8]) -> Result<bool> {
+        if payload.len() < Self::_COMPRESSED_MESSAGE_OFFSET {
+            return Err(Error::MongoDBLogParseFailed);
+        }
+        self.original_op_code = bytes::read_u32_le(&payload[Self::_ORIGINAL_OP_CODE_OFFSET..]);
+        self.uncompressed_size = bytes::read_u32_le(&payload[Self::_UNCOMPRESSED_SIZE_OFFSET..]);
+        self.compressor_id = payload[Self::_COMPRESSOR_ID_OFFSET];
+        Ok(true)
+    }
+
+    // decompress at most `max_bytes`, the output can be partial if the message is truncated
+    fn decompress(&self, payload: &[u8], max_bytes: usize) -> Result<Vec<u8>, CompressError> {
+        if self.uncompressed_size as usize > max_bytes {
+            return Err(CompressError::TooLarge(
+                self.uncompressed_size as u64,
+                max_bytes,
+            ));
+        }
+        let data = &payload[Self::_COMPRESSED_MESSAGE_OFFSET..];
+        let limit = self.uncompressed_size as u64;
+        let mut output = vec![];
+        let result = match self.compressor_id {
+            Self::_COMPRESSOR_NOOP => {
+                output.extend_from_slice(&data[..data.len().min(limit as usize)]);
+                Ok(())
+            }
+            Self::_COMPRESSOR_SNAPPY => snappy::decompress(data, max_bytes, &mut output),
+            Self::_COMPRESSOR_ZLIB => ZlibDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output)
+                .map(|_| ())
+                .map_err(CompressError::from),
+            Self::_COMPRESSOR_ZSTD => zstd::stream::read::Decoder::new(data)
+                .and_then(|d| d.take(limit).read_to_end(&mut output))
+                .map(|_| ())
+                .map_err(CompressError::from),
+            id => return Err(CompressError::UnsupportedCompressor(id)),
+        };
+        match result {
+            // truncated message
+            Err(_) if !output.is_empty() => Ok(output),
+            Err(e) => Err(e),
+            Ok(_) => Ok(output),
+        }
+    }
+}
+
+// snappy block format: https://github.com/google/snappy/blob/main/format_description.txt
+mod snappy {
+    use super::CompressError;
+
+    const TAG_LITERAL: u8 = 0;
+    const TAG_COPY_1: u8 = 1;
+    const TAG_COPY_2: u8 = 2;
+
+    fn read_varint(input: &[u8]) -> Option<(u64, usize)> {
+        let mut value = 0u64;
+        for (i, b) in input.iter().take(5).enumerate() {
+            value |= ((b & 0x7f) as u64) << (7 * i);
+            if b & 0x80 == 0 {
+                return Some((value, i + 1));
+            }
+        }
+        None
+    }
+
+    fn read_le(input: &[u8], n: usize) -> Option<usize> {
+        let bytes = input.get(..n)?;
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0usize, |acc, b| (acc << 8) | *b as usize),
+        )
+    }
+
+    // decompress into output, stops at the end of input for truncated data
+    pub fn decompress(
+        input: &[u8],
+        max_bytes: usize,
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressError> {
+        let Some((length, mut offset)) = read_varint(input) else {
+            return Err(CompressError::Snappy("bad uncompressed length"));
+        };
+        if length > max_bytes as u64 {
+            return Err(CompressError::TooLarge(length, max_bytes));
+        }
+        let length = length as usize;
+        output.reserve(length.min(input.len() * 4));
+
+        while offset < input.len() && output.len() < length {
+            let tag = input[offset];
+            offset += 1;
+            match tag & 0x3 {
+                TAG_LITERAL => {
+                    let mut len = (tag >> 2) as usize;
+                    if len >= 60 {
+                        let n = len - 59;
+                        let Some(l) = read_le(&input[offset..], n) else {
+                            return Ok(());
+                        };
+                        len = l;
+                        offset += n;
+                    }
+                    let len = (len + 1).min(length - output.len());
+                    let end = (offset + len).min(input.len());
+                    output.extend_from_slice(&input[offset..end]);
+                    offset = end;
+                }
+                t => {
+                    let (len, copy_offset, n) = match t {
+                        TAG_COPY_1 => {
+                            let Some(&b) = input.get(offset) else {
+                                return Ok(());
+                            };
+                            (
+                                4 + ((tag >> 2) & 0x7) as usize,
+                                ((tag as usize >> 5) << 8) | b as usize,
+                                1,
+                            )
+                        }
+                        _ => {
+                            let n = if t == TAG_COPY_2 { 2 } else { 4 };
+                            let Some(o) = read_le(&input[offset..], n) else {
+                                return Ok(());
+                            };
+                            (1 + (tag >> 2) as usize, o, n)
+                        }
+                    };
+                    offset += n;
+                    if copy_offset == 0 || copy_offset > output.len() {
+                        return Err(CompressError::Snappy("bad copy offset"));
+                    }
+                    let start = output.len() - copy_offset;
+                    for i in 0..len.min(length - output.len()) {
+                        output.push(output[start + i]);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 // TODO: support op msg
@@ -684,6 +911,9 @@ mod tests {
 
     use super::*;
 
+    use bson::doc;
+    use flate2::{write::ZlibEncoder, Compression};
+
     use crate::{
         common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
         flow_generator::L7_RRT_CACHE_CAPACITY,
@@ -768,4 +998,127 @@ mod tests {
             }
         }
     }
+
+    // OP_MSG body with flags and a kind 0 section
+    fn op_msg_body() -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 0, 0];
+        doc! { "find": "users", "$db": "test" }
+            .to_writer(&mut body)
+            .unwrap();
+        body
+    }
+
+    fn op_compressed(compressor_id: u8, uncompressed_size: u32, compressed: &[u8]) -> Vec<u8> {
+        let length = _HEADER_SIZE + 9 + compressed.len();
+        let mut payload = vec![];
+        payload.extend_from_slice(&(length as u32).to_le_bytes());
+        payload.extend_from_slice(&1u32.to_le_bytes());
+        payload.extend_from_slice(&0u32.to_le_bytes());
+        payload.extend_from_slice(&_OP_COMPRESSED.to_le_bytes());
+        payload.extend_from_slice(&_OP_MSG.to_le_bytes());
+        payload.extend_from_slice(&uncompressed_size.to_le_bytes());
+        payload.push(compressor_id);
+        payload.extend_from_slice(compressed);
+        payload
+    }
+
+    // snappy stream with literals only
+    fn snappy_literal(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![];
+        let mut length = data.len();
+        while length >= 0x80 {
+            output.push(length as u8 | 0x80);
+            length >>= 7;
+        }
+        output.push(length as u8);
+        output.push(61 << 2);
+        output.extend_from_slice(&((data.len() - 1) as u16).to_le_bytes());
+        output.extend_from_slice(data);
+        output
+    }
+
+    fn parse_compressed(mongo: &mut MongoDBLog, payload: &[u8], max_bytes: usize) -> MongoDBInfo {
+        let mut info = MongoDBInfo::default();
+        mongo
+            .parse(
+                payload,
+                IpProtocol::TCP,
+                PacketDirection::ClientToServer,
+                max_bytes,
+                None,
+                &mut info,
+            )
+            .unwrap();
+        info
+    }
+
+    #[test]
+    fn check_op_compressed() {
+        let body = op_msg_body();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        std::io::Write::write_all(&mut zlib, &body).unwrap();
+        let fixtures = [
+            (0, body.clone()),
+            (1, snappy_literal(&body)),
+            (2, zlib.finish().unwrap()),
+            (3, zstd::bulk::compress(&body, 0).unwrap()),
+        ];
+
+        for (compressor_id, compressed) in fixtures {
+            let payload = op_compressed(compressor_id, body.len() as u32, &compressed);
+            let info = parse_compressed(&mut MongoDBLog::default(), &payload, 1 << 20);
+            assert_eq!(info.op_code, _OP_MSG, "compressor {compressor_id}");
+            assert_eq!(info.op_code_name, "OP_MSG");
+            assert!(
+                info.request.contains("\"find\": \"users\""),
+                "compressor {compressor_id}: {}",
+                info.request
+            );
+        }
+    }
+
+    #[test]
+    fn check_op_compressed_oversized() {
+        let body = op_msg_body();
+        let mut mongo = MongoDBLog::default();
+
+        // bail out on uncompressed size in header
+        let payload = op_compressed(3, u32::MAX, &zstd::bulk::compress(&body, 0).unwrap());
+        let info = parse_compressed(&mut mongo, &payload, 1 << 20);
+        assert_eq!(info.op_code, _OP_COMPRESSED);
+        assert!(info.request.is_empty());
+
+        // bail out on uncompressed length in snappy stream
+        let mut compressed = vec![0xff, 0xff, 0xff, 0xff, 0x0f];
+        compressed.extend_from_slice(&snappy_literal(&body)[1..]);
+        let payload = op_compressed(1, body.len() as u32, &compressed);
+        let info = parse_compressed(&mut mongo, &payload, 1 << 20);
+        assert_eq!(info.op_code, _OP_COMPRESSED);
+        assert!(info.request.is_empty());
+
+        // unsupported compressor is counted
+        let payload = op_compressed(9, body.len() as u32, &body);
+        for _ in 0..2 {
+            let info = parse_compressed(&mut mongo, &payload, 1 << 20);
+            assert_eq!(info.op_code, _OP_COMPRESSED);
+        }
+        assert_eq!(mongo.unsupported_compressed_count, 2);
+    }
+
+    #[test]
+    fn check_snappy() {
+        let mut output = vec![];
+        // literal "abc" and copy with 2 bytes offset
+        snappy::decompress(b"\x0c\x08abc\x22\x03\x00", 1024, &mut output).unwrap();
+        assert_eq!(output, b"abcabcabcabc");
+
+        output.clear();
+        // literal "ab" and copy with 1 byte offset
+        snappy::decompress(b"\x08\x04ab\x09\x02", 1024, &mut output).unwrap();
+        assert_eq!(output, b"abababab");
+
+        output.clear();
+        assert!(snappy::decompress(b"\x08\x04ab\x09\x05", 1024, &mut output).is_err());
+        assert!(snappy::decompress(b"\x80\x10", 1024, &mut output).is_err());
+    }
 }
@@ -20,17 +20,19 @@ mod consts;
 use std::{
     borrow::Cow,
     cell::Cell,
-    io::Read,
+    io::{Read, Take},
+    num::NonZeroUsize,
     str::{self, SplitWhitespace},
 };
 
 use flate2::bufread::ZlibDecoder;
 use log::{debug, trace};
+use lru::LruCache;
 use serde::Serialize;
 
 use super::{
     sql_check::{is_mysql, is_valid_sql, trim_head_comment_and_get_first_word},
-    sql_obfuscate::CachedObfuscator,
+    sql_obfuscate::{CachedObfuscator, SqlDialect},
 };
 
 use self::consts::*;
@@ -76,6 +78,9 @@ const SERVER_STATUS_CODE_MIN: u16 = 1000;
 const CLIENT_STATUS_CODE_MIN: u16 = 2000;
 const CLIENT_STATUS_CODE_MAX: u16 = 2999;
 
+const PARAMETERS_ATTR: &str = "parameters";
+const PREPARE_NOT_CAPTURED_ATTR: &str = "prepare_not_captured";
+
 #[derive(Debug)]
 enum TruncationType {
     Packet,
@@ -198,6 +203,11 @@ pub struct MysqlInfo {
 
     #[serde(skip_serializing_if = "value_is_default")]
     biz_response_code: String,
+
+    // COM_STMT_EXECUTE of a statement prepared before the flow was observed,
+    // so the sql is missing in request_resource
+    #[serde(skip_serializing_if = "value_is_default")]
+    prepare_not_captured: bool,
 }
 
 impl L7LogAttribute for MysqlInfo {
@@ -266,6 +276,7 @@ impl MysqlInfo {
             LogMessageType::Request => {
                 self.command = other.command;
                 std::mem::swap(&mut self.context, &mut other.context);
+                self.prepare_not_captured = other.prepare_not_captured;
                 self.captured_request_byte = other.captured_request_byte;
             }
             LogMessageType::Response => {
@@ -487,7 +498,8 @@ impl MysqlInfo {
         if let Some(c) = config {
             self.extract_trace_and_span_id(&c.l7_log_dynamic, sql_string);
         }
-        let obfuscator = CachedObfuscator::new(param.obfuscate_cache.clone());
+        let obfuscator =
+            CachedObfuscator::new(param.obfuscate_cache.clone()).with_dialect(SqlDialect::MySql);
         self.context = match obfuscator.apply(sql_string) {
             Ok(obfuscated) => obfuscated.into_owned(),
             _ => sql_string.to_string(),
@@ -581,6 +593,13 @@ impl From<MysqlInfo> for L7ProtocolSendLog {
         } else {
             ApplicationFlags::NONE.bits()
         };
+        let mut attributes = f.attributes;
+        if f.prepare_not_captured {
+            attributes.push(KeyVal {
+                key: PREPARE_NOT_CAPTURED_ATTR.to_string(),
+                val: "true".to_string(),
+            });
+        }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
             captured_response_byte: f.captured_response_byte,
@@ -621,10 +640,10 @@ impl From<MysqlInfo> for L7ProtocolSendLog {
             },
             ext_info: Some(ExtendedInfo {
                 attributes: {
-                    if f.attributes.is_empty() {
+                    if attributes.is_empty() {
                         None
                     } else {
-                        Some(f.attributes)
+                        Some(attributes)
                     }
                 },
                 request_id: f.statement_id.into(),
@@ -671,23 +690,50 @@ fn give_buffer(buffer: Vec<u8>) {
     DECODE_BUFFER.with(|c| c.replace(Some(buffer)));
 }
 
-#[derive(Default)]
+struct PreparedStatement {
+    // obfuscated at prepare time
+    sql: String,
+    // calculated based on the sql statement, only when obfuscation is disabled
+    pc: ParameterCounter,
+}
+
 pub struct MysqlLog {
     pub protocol_version: u8,
     perf_stats: Vec<L7PerfStats>,
 
-    // This field is extracted in the COM_STMT_PREPARE request and calculate based on SQL statements
-    pc: ParameterCounter,
+    // statement id -> prepared statement, the id is returned in COM_STMT_PREPARE response
+    statements: LruCache<u32, PreparedStatement>,
+    // COM_STMT_PREPARE request waiting for the statement id in response
+    preparing: Option<PreparedStatement>,
     has_request: bool,
     has_login: bool,
 
     // if compression is enabled, both requests and responses will have compression header
     has_compressed_header: Option<bool>,
+    // compression is negotiated in login request, and enabled after login succeeded
+    compress_after_login: bool,
 
     #[cfg(feature = "enterprise")]
     custom_field_store: Store,
 }
 
+impl Default for MysqlLog {
+    fn default() -> Self {
+        Self {
+            protocol_version: 0,
+            perf_stats: vec![],
+            statements: LruCache::new(NonZeroUsize::new(Self::MAX_STATEMENT_PER_FLOW).unwrap()),
+            preparing: None,
+            has_request: false,
+            has_login: false,
+            has_compressed_header: None,
+            compress_after_login: false,
+            #[cfg(feature = "enterprise")]
+            custom_field_store: Store::default(),
+        }
+    }
+}
+
 impl L7ProtocolParserInterface for MysqlLog {
     fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
         if !param.ebpf_type.is_raw_protocol() || param.l4_protocol != IpProtocol::TCP {
@@ -695,14 +741,14 @@ impl L7ProtocolParserInterface for MysqlLog {
         }
 
         if self.has_compressed_header.is_none() {
-            if let Err(_) = self.check_compressed_header(payload) {
+            if let Err(_) = self.check_compressed_header(payload, param) {
                 return None;
             }
         }
 
         let mut decompress_buffer = take_buffer();
         let ret = Self::check(
-            param.parse_config,
+            param,
             &mut decompress_buffer,
             self.has_compressed_header.unwrap(),
             payload,
@@ -749,7 +795,7 @@ impl L7ProtocolParserInterface for MysqlLog {
         };
 
         if self.has_compressed_header.is_none() {
-            let _ = self.check_compressed_header(payload)?;
+            let _ = self.check_compressed_header(payload, param)?;
         }
 
         #[cfg(feature = "enterprise")]
@@ -883,10 +929,6 @@ enum SqlState {
 struct ParameterCounter(u32);
 
 impl ParameterCounter {
-    fn reset(&mut self) {
-        self.0 = 0;
-    }
-
     fn set(&mut self, sql: &[u8]) {
         let mut counter = 0;
         let mut state = SqlState::None;
@@ -948,9 +990,9 @@ impl ParameterCounter {
         self.0 = counter;
     }
 
-    fn get(&mut self, payload: &[u8], info: &mut MysqlInfo) {
+    fn get(&self, payload: &[u8]) -> Option<String> {
         if self.0 == 0 {
-            return;
+            return None;
         }
         let mut params = vec![];
         let mut offset = 0;
@@ -963,7 +1005,7 @@ impl ParameterCounter {
         }
         for _ in 0..self.0 as usize {
             if offset + PARAMETER_TYPE_LEN > payload.len() {
-                return;
+                return None;
             }
             params.push((FieldType::from(payload[offset]), payload[offset + 1]));
             offset += PARAMETER_TYPE_LEN;
@@ -983,13 +1025,17 @@ impl ParameterCounter {
             }
         }
 
-        info.context = context;
+        Some(context)
     }
 }
 
 impl MysqlLog {
-    fn check_compressed_header(&mut self, payload: &[u8]) -> Result<()> {
-        self.has_compressed_header = Some(PayloadParser::is_compressed(payload)?);
+    const MAX_STATEMENT_PER_FLOW: usize = 64;
+
+    fn check_compressed_header(&mut self, payload: &[u8], param: &ParseParam) -> Result<()> {
+        // payloads of large mysql packets are truncated by `payload_truncation`
+        let truncated = param.buf_size > 0 && payload.len() >= param.buf_size as usize;
+        self.has_compressed_header = Some(PayloadParser::is_compressed(payload, truncated)?);
         Ok(())
     }
 
@@ -1051,11 +1097,21 @@ impl MysqlLog {
         }
         let config = param.parse_config.as_ref();
         info.command = payload[COMMAND_OFFSET];
+        self.preparing = None;
         let mut msg_type = LogMessageType::Request;
         match info.command {
-            COM_QUIT | COM_STMT_CLOSE => {
+            COM_QUIT => {
+                msg_type = LogMessageType::Session;
+                info.status = L7ResponseStatus::Ok;
+                self.statements.clear();
+            }
+            COM_STMT_CLOSE => {
                 msg_type = LogMessageType::Session;
                 info.status = L7ResponseStatus::Ok;
+                if payload.len() >= STATEMENT_ID_OFFSET + STATEMENT_ID_LEN {
+                    self.statements
+                        .pop(&bytes::read_u32_le(&payload[STATEMENT_ID_OFFSET..]));
+                }
             }
             COM_FIELD_LIST | COM_STMT_FETCH => (),
             COM_INIT_DB | COM_QUERY => {
@@ -1075,22 +1131,39 @@ impl MysqlLog {
                     #[cfg(feature = "enterprise")]
                     custom_policies,
                 )?;
+                let mut pc = ParameterCounter::default();
                 if let Some(config) = config {
                     if config
                         .obfuscate_enabled_protocols
                         .is_disabled(L7Protocol::MySQL)
                     {
-                        self.pc.set(info.context.as_bytes());
+                        pc.set(info.context.as_bytes());
                     }
                 }
+                self.preparing = Some(PreparedStatement {
+                    sql: info.context.clone(),
+                    pc,
+                });
             }
             COM_STMT_EXECUTE => {
                 info.statement_id(&payload[STATEMENT_ID_OFFSET..]);
-                if payload.len() > EXECUTE_STATEMENT_PARAMS_OFFSET {
-                    self.pc
-                        .get(&payload[EXECUTE_STATEMENT_PARAMS_OFFSET..], info);
+                let params = payload.get(EXECUTE_STATEMENT_PARAMS_OFFSET..);
+                match self.statements.get(&info.statement_id) {
+                    Some(stmt) => {
+                        info.context = stmt.sql.clone();
+                        info.generate_endpoint();
+                        if let Some(params) = params.and_then(|p| stmt.pc.get(p)) {
+                            info.attributes.push(KeyVal {
+                                key: PARAMETERS_ATTR.to_string(),
+                                val: params,
+                            });
+                        }
+                    }
+                    None => {
+                        // prepared before the flow was observed or evicted
+                        info.prepare_not_captured = true;
+                    }
                 }
-                self.pc.reset();
             }
             COM_PING => {}
             _ => return Err(Error::CommandNotSupported(info.command)),
@@ -1194,7 +1267,8 @@ impl MysqlLog {
         str::from_utf8(&payload[..n]).ok()
     }
 
-    fn login(payload: &[u8], mut info: Option<&mut MysqlInfo>) -> Result<()> {
+    // return client capabilities flags
+    fn login(payload: &[u8], mut info: Option<&mut MysqlInfo>) -> Result<u16> {
         if payload.len() < LOGIN_USERNAME_OFFSET {
             return Err(Error::Truncated(TruncationType::Login));
         }
@@ -1247,7 +1321,7 @@ impl MysqlLog {
             }
         }
 
-        Ok(())
+        Ok(client_capabilities_flags)
     }
 
     fn is_interested_response(code: u8) -> bool {
@@ -1257,16 +1331,12 @@ impl MysqlLog {
     }
 
     fn check(
-        config: Option<&LogParserConfig>,
+        param: &ParseParam,
         decompress_buffer: &mut Vec<u8>,
         has_compressed_header: bool,
         payload: &[u8],
     ) -> bool {
-        let decompress = config
-            .map(|c| c.mysql_decompress_payload)
-            .unwrap_or_else(|| LogParserConfig::default().mysql_decompress_payload);
-
-        let mut parser = match PayloadParser::new(decompress, has_compressed_header, payload) {
+        let mut parser = match PayloadParser::new(param, has_compressed_header, payload) {
             Ok(parser) => parser,
             Err(e) => {
                 debug!("create payload parser failed: {e}");
@@ -1339,13 +1409,8 @@ impl MysqlLog {
         info: &mut MysqlInfo,
         #[cfg(feature = "enterprise")] custom_policies: Option<PolicySlice>,
     ) -> Result<bool> {
-        let (config, direction) = (param.parse_config.as_ref(), param.direction);
-        let decompress = config
-            .map(|c| c.mysql_decompress_payload)
-            .unwrap_or_else(|| LogParserConfig::default().mysql_decompress_payload);
-
-        let mut parser =
-            PayloadParser::new(decompress, self.has_compressed_header.unwrap(), payload)?;
+        let direction = param.direction;
+        let mut parser = PayloadParser::new(param, self.has_compressed_header.unwrap(), payload)?;
         // interested packets:
         // - the first packet in request
         // - greetings packet in response
@@ -1397,19 +1462,33 @@ impl MysqlLog {
             LogMessageType::Request
                 if direction == PacketDirection::ClientToServer && header.seq_id == 1 =>
             {
-                Self::login(payload, Some(info))?;
+                let flags = Self::login(payload, Some(info))?;
                 self.has_login = true;
+                self.compress_after_login = flags & CLIENT_COMPRESS == CLIENT_COMPRESS;
             }
             LogMessageType::Response
                 if self.has_login && payload[RESPONSE_CODE_OFFSET] == MYSQL_RESPONSE_CODE_OK
                     || payload[RESPONSE_CODE_OFFSET] == MYSQL_RESPONSE_CODE_ERR =>
             {
                 Self::response(payload, info)?;
+                if self.has_login
+                    && self.compress_after_login
+                    && info.response_code == MYSQL_RESPONSE_CODE_OK
+                {
+                    // packets after login OK packet have compression header
+                    self.has_compressed_header = Some(true);
+                }
                 self.has_login = false;
+                self.compress_after_login = false;
             }
             LogMessageType::Response if self.has_request => {
                 Self::response(payload, info)?;
                 self.has_request = false;
+                if let Some(stmt) = self.preparing.take() {
+                    if info.response_code == MYSQL_RESPONSE_CODE_OK && info.statement_id > 0 {
+                        self.statements.put(info.statement_id, stmt);
+                    }
+                }
             }
             LogMessageType::Other => {
                 self.protocol_version = Self::greeting(payload, info)?;
@@ -1444,15 +1523,24 @@ impl MysqlLog {
 // If mysql decompress payload is enabled, agent will try to decompress the payload before parsing.
 //
 // ref: https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_compression.html
+//
+// Payloads of 16MB or larger are split into several mysql packets, all but the last one have
+// payload length 0xffffff. They are reassembled into one payload no longer than `payload_truncation`,
+// the rest of the payload is skipped.
+//
+// ref: https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_packets.html
 struct PayloadParser<'a> {
     payload: &'a [u8],
-    decoder: Option<ZlibDecoder<&'a [u8]>>,
+    // the decompressed bytes are limited by `mysql_decompress_max_bytes`
+    decoder: Option<Take<ZlibDecoder<&'a [u8]>>>,
+    // 0 for not reassembling split packets
+    max_payload_len: usize,
 }
 
 impl<'a> PayloadParser<'a> {
     // check if the payload has a compression header
-    // only work if the payload is not truncated
-    fn is_compressed(payload: &[u8]) -> Result<bool> {
+    // only work if the payload is not truncated, or the compressed payload is a zlib stream
+    fn is_compressed(payload: &[u8], truncated: bool) -> Result<bool> {
         if payload.len() >= COMPRESS_HEADER_LEN {
             let compressed_len = (bytes::read_u32_le(&payload[..]) & 0xffffff) as usize;
             let uncompressed_len = bytes::read_u16_le(&payload[COMPRESS_HEADER_UNCOMPRESS_OFFSET..])
@@ -1464,12 +1552,22 @@ impl<'a> PayloadParser<'a> {
             {
                 return Ok(true);
             }
+            if truncated
+                && COMPRESS_HEADER_LEN + compressed_len > payload.len()
+                && uncompressed_len >= compressed_len
+                && payload.get(COMPRESS_HEADER_LEN) == Some(&ZLIB_CMF)
+            {
+                return Ok(true);
+            }
         }
 
         let mut offset = 0;
         while offset + HEADER_LEN < payload.len() {
             let header = MysqlHeader::new(&payload[offset..]);
             if offset + HEADER_LEN + header.length as usize > payload.len() {
+                if truncated {
+                    return Ok(false);
+                }
                 return Err(Error::Truncated(TruncationType::PacketPayload(header)));
             }
             offset += HEADER_LEN + header.length as usize;
@@ -1480,7 +1578,16 @@ impl<'a> PayloadParser<'a> {
         Err(Error::Truncated(TruncationType::PacketHeader))
     }
 
-    fn new(decompress: bool, has_compressed_header: bool, payload: &'a [u8]) -> Result<Self> {
+    fn new(param: &ParseParam, has_compressed_header: bool, payload: &'a [u8]) -> Result<Self> {
+        let (decompress, max_decompressed_len) = param
+            .parse_config
+            .map(|c| (c.mysql_decompress_payload, c.mysql_decompress_max_bytes))
+            .unwrap_or_else(|| {
+                let c = LogParserConfig::default();
+                (c.mysql_decompress_payload, c.mysql_decompress_max_bytes)
+            });
+        let max_payload_len = param.buf_size as usize;
+
         let compressed = if has_compressed_header {
             if payload.len() < COMPRESS_HEADER_LEN {
                 return Err(Error::Truncated(TruncationType::CompressedHeader));
@@ -1501,7 +1608,11 @@ impl<'a> PayloadParser<'a> {
             } else {
                 Ok(Self {
                     payload,
-                    decoder: Some(ZlibDecoder::new(&payload[COMPRESS_HEADER_LEN..])),
+                    decoder: Some(
+                        ZlibDecoder::new(&payload[COMPRESS_HEADER_LEN..])
+                            .take(max_decompressed_len as u64),
+                    ),
+                    max_payload_len,
                 })
             }
         } else {
@@ -1512,6 +1623,7 @@ impl<'a> PayloadParser<'a> {
                     payload
                 },
                 decoder: None,
+                max_payload_len,
             })
         }
     }
@@ -1535,7 +1647,11 @@ impl<'a> PayloadParser<'a> {
                     return Err(Error::Truncated(TruncationType::CompressedPacketHeader));
                 }
                 let header = MysqlHeader::new(&hb);
-                Self::fill_buffer(decoder, buffer, header.length as usize);
+                buffer.clear();
+                // a compressed packet is no larger than 16MB, so there's no split packet in it
+                // do not allocate beyond the decompression budget
+                let length = (header.length as u64).min(decoder.limit()) as usize;
+                Self::fill_buffer(&mut *decoder, buffer, length);
                 Ok(Some((header, &buffer[..])))
             }
             None => {
@@ -1543,32 +1659,52 @@ impl<'a> PayloadParser<'a> {
                     self.payload = &self.payload[self.payload.len()..];
                     return Err(Error::Truncated(TruncationType::PacketHeader));
                 }
-                let header = MysqlHeader::new(&self.payload[..HEADER_LEN]);
+                let mut header = MysqlHeader::new(&self.payload[..HEADER_LEN]);
                 let end_of_frame = self.payload.len().min(HEADER_LEN + header.length as usize);
                 let frame = &self.payload[HEADER_LEN..end_of_frame];
                 self.payload = &self.payload[end_of_frame..];
-                Ok(Some((header, frame)))
+                if header.length != MAX_PACKET_PAYLOAD_LEN
+                    || frame.len() != header.length as usize
+                    || self.max_payload_len == 0
+                    || self.payload.len() < HEADER_LEN
+                {
+                    return Ok(Some((header, frame)));
+                }
+
+                buffer.clear();
+                buffer.extend_from_slice(&frame[..frame.len().min(self.max_payload_len)]);
+                let mut length = header.length;
+                while length == MAX_PACKET_PAYLOAD_LEN && self.payload.len() >= HEADER_LEN {
+                    length = MysqlHeader::new(&self.payload[..HEADER_LEN]).length;
+                    let end_of_frame = self.payload.len().min(HEADER_LEN + length as usize);
+                    let n = (end_of_frame - HEADER_LEN).min(self.max_payload_len - buffer.len());
+                    buffer.extend_from_slice(&self.payload[HEADER_LEN..HEADER_LEN + n]);
+                    self.payload = &self.payload[end_of_frame..];
+                }
+                header.length = buffer.len() as u32;
+                Ok(Some((header, &buffer[..])))
             }
         }
     }
 
+    // append `length` bytes to buffer
     // do not use read_exact because on failure, it will consume decoder data without putting successful reads into buffer
     fn fill_buffer<R: Read>(mut decoder: R, buffer: &mut Vec<u8>, length: usize) {
-        buffer.clear();
-        buffer.resize(length, 0);
+        let mut offset = buffer.len();
+        let end = offset + length;
+        buffer.resize(end, 0);
 
-        let mut offset = 0;
         loop {
-            match decoder.read(&mut buffer[offset..]) {
+            match decoder.read(&mut buffer[offset..end]) {
                 Ok(n) if n == 0 => break,
-                Ok(n) if n + offset == length => return,
+                Ok(n) if n + offset == end => return,
                 Ok(n) => offset += n,
                 Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
                 _ => break,
             }
         }
 
-        buffer.resize(offset, 0);
+        buffer.truncate(offset);
     }
 }
 
@@ -1691,8 +1827,10 @@ mod tests {
 
     use super::*;
 
+    use flate2::{write::ZlibEncoder, Compression};
+
     use crate::{
-        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache},
+        common::{flow::PacketDirection, l7_protocol_log::L7PerfCache, MetaPacket},
         config::handler::{L7LogDynamicConfigBuilder, TraceType},
         flow_generator::{protocol_logs::PrioStrings, L7_RRT_CACHE_CAPACITY},
         utils::test_utils::Capture,
@@ -2155,4 +2293,206 @@ mod tests {
             );
         }
     }
+
+    fn mysql_packet(seq_id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq_id);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn compressed_packet(seq_id: u8, packets: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        std::io::Write::write_all(&mut encoder, packets).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut packet = (compressed.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(seq_id);
+        packet.extend_from_slice(&(packets.len() as u32).to_le_bytes()[..3]);
+        packet.extend_from_slice(&compressed);
+        packet
+    }
+
+    fn parse_packet(
+        mysql: &mut MysqlLog,
+        payload: &[u8],
+        direction: PacketDirection,
+        buf_size: usize,
+    ) -> Option<MysqlInfo> {
+        let log_config = LogParserConfig::default();
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.l4_protocol = IpProtocol::TCP;
+        param.direction = direction;
+        param.parse_config = Some(&log_config);
+        param.set_buf_size(buf_size);
+        match mysql.parse_payload(payload, &param) {
+            Ok(L7ParseResult::Single(L7ProtocolInfo::MysqlInfo(info))) => Some(info),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn check_compressed_session() {
+        let mut mysql = MysqlLog::default();
+
+        let mut greeting = vec![PROTOCOL_VERSION];
+        greeting.extend_from_slice(b"8.0.36\0");
+        greeting.extend_from_slice(&[1, 0, 0, 0]);
+        greeting.extend_from_slice(&[0x2a; 9]);
+        assert!(parse_packet(
+            &mut mysql,
+            &mysql_packet(0, &greeting),
+            PacketDirection::ServerToClient,
+            0
+        )
+        .is_none());
+
+        // compression is negotiated in login request
+        let mut login = vec![];
+        login.extend_from_slice(&(CLIENT_PROTOCOL_41 | CLIENT_COMPRESS).to_le_bytes());
+        login.extend_from_slice(&[0, 0, 0, 0, 0, 1, 0x21]);
+        login.extend_from_slice(&[0; FILTER_SIZE]);
+        login.extend_from_slice(b"root\0\0");
+        let info = parse_packet(
+            &mut mysql,
+            &mysql_packet(1, &login),
+            PacketDirection::ClientToServer,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.context, "Login username: root");
+        let info = parse_packet(
+            &mut mysql,
+            &mysql_packet(2, &[0, 0, 0, 2, 0, 0, 0]),
+            PacketDirection::ServerToClient,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+
+        let info = parse_packet(
+            &mut mysql,
+            &compressed_packet(0, &mysql_packet(0, b"\x03SELECT * FROM t WHERE id = 1")),
+            PacketDirection::ClientToServer,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.command, COM_QUERY);
+        assert_eq!(info.context, "SELECT * FROM t WHERE id = 1");
+        let info = parse_packet(
+            &mut mysql,
+            &compressed_packet(0, &mysql_packet(1, &[0, 1, 0, 2, 0, 0, 0])),
+            PacketDirection::ServerToClient,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.status, L7ResponseStatus::Ok);
+        assert_eq!(info.affected_rows, 1);
+    }
+
+    #[test]
+    fn check_large_packet() {
+        let mut query = vec![COM_QUERY];
+        query.extend_from_slice(b"SELECT '");
+        query.resize(MAX_PACKET_PAYLOAD_LEN as usize, b'a');
+        let mut payload = mysql_packet(0, &query);
+        payload.extend(mysql_packet(1, b"aaaa'"));
+        payload.extend(mysql_packet(2, b"hello"));
+
+        // split packets are reassembled and truncated
+        let log_config = LogParserConfig::default();
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.parse_config = Some(&log_config);
+        param.set_buf_size(1024);
+        let mut buffer = vec![];
+        let mut parser = PayloadParser::new(&param, false, &payload).unwrap();
+        let (header, frame) = parser.try_next(&mut buffer).unwrap().unwrap();
+        assert_eq!(header.length, 1024);
+        assert!(frame.starts_with(b"\x03SELECT 'aaaa"));
+        let (header, frame) = parser.try_next(&mut buffer).unwrap().unwrap();
+        assert_eq!(header.seq_id, 2);
+        assert_eq!(frame, b"hello");
+
+        // the payload of a large packet is truncated by payload_truncation
+        let mut mysql = MysqlLog::default();
+        let info = parse_packet(
+            &mut mysql,
+            &payload[..1024],
+            PacketDirection::ClientToServer,
+            1024,
+        )
+        .unwrap();
+        assert_eq!(mysql.has_compressed_header, Some(false));
+        assert_eq!(info.command, COM_QUERY);
+        assert!(info.context.starts_with("SELECT 'aaaa"));
+    }
+
+    #[test]
+    fn check_prepared_statement() {
+        let mut mysql = MysqlLog::default();
+        let execute = |id: u32| {
+            let mut execute = vec![COM_STMT_EXECUTE];
+            execute.extend_from_slice(&id.to_le_bytes());
+            execute.extend_from_slice(&[0, 1, 0, 0, 0]);
+            mysql_packet(0, &execute)
+        };
+
+        let info = parse_packet(
+            &mut mysql,
+            &mysql_packet(0, b"\x16SELECT name FROM user WHERE id = ?"),
+            PacketDirection::ClientToServer,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.command, COM_STMT_PREPARE);
+        // PREPARE_OK with statement id 7
+        let info = parse_packet(
+            &mut mysql,
+            &mysql_packet(1, &[0, 7, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0]),
+            PacketDirection::ServerToClient,
+            0,
+        )
+        .unwrap();
+        assert_eq!(info.statement_id, 7);
+
+        let info =
+            parse_packet(&mut mysql, &execute(7), PacketDirection::ClientToServer, 0).unwrap();
+        assert_eq!(info.statement_id, 7);
+        assert_eq!(info.context, "SELECT name FROM user WHERE id = ?");
+        assert!(!info.prepare_not_captured);
+
+        let mut close = vec![COM_STMT_CLOSE];
+        close.extend_from_slice(&7u32.to_le_bytes());
+        parse_packet(
+            &mut mysql,
+            &mysql_packet(0, &close),
+            PacketDirection::ClientToServer,
+            0,
+        );
+        assert!(mysql.statements.is_empty());
+
+        // statement closed or prepared before the flow was observed
+        let info =
+            parse_packet(&mut mysql, &execute(7), PacketDirection::ClientToServer, 0).unwrap();
+        assert_eq!(info.statement_id, 7);
+        assert!(info.context.is_empty());
+        assert!(info.prepare_not_captured);
+    }
 }
@@ -25,12 +25,16 @@ pub const PROTOCOL_VERSION: u8 = 10;
 // Compressed Header
 pub const COMPRESS_HEADER_LEN: usize = 7;
 pub const COMPRESS_HEADER_UNCOMPRESS_OFFSET: usize = 4;
+// the first byte of zlib stream, deflate with 32K window size
+pub const ZLIB_CMF: u8 = 0x78;
 
 // Header
 pub const HEADER_LEN: usize = 4;
 
 pub const HEADER_OFFSET: usize = 0;
 pub const NUMBER_OFFSET: usize = 3;
+// payload of 16MB or larger is split into packets with this payload length
+pub const MAX_PACKET_PAYLOAD_LEN: u32 = 0xffffff;
 
 // Greeting
 pub const SERVER_VERSION_EOF: u8 = 0;
@@ -312,6 +316,7 @@ pub const EXECUTE_STATEMENT_PARAMS_OFFSET: usize = STATEMENT_ID_OFFSET + STATEME
 // Client Capabilities
 pub const CLIENT_PROTOCOL_41: u16 = 512;
 pub const CONNECT_WITH_DB: u16 = 1 << 3;
+pub const CLIENT_COMPRESS: u16 = 1 << 5;
 // Extended Client Capabilities
 pub const CLIENT_PLUGIN_AUTH_LENENC_CLIENT_DATA: u16 = 1 << 5;
 
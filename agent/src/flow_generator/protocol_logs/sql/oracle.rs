@@ -19,7 +19,7 @@ use std::borrow::Cow;
 use serde::Serialize;
 
 use super::super::value_is_default;
-use crate::config::handler::LogParserConfig;
+use crate::config::{handler::LogParserConfig, OracleConfig};
 use crate::flow_generator::{
     protocol_logs::{
         auto_merge_custom_field,
@@ -87,6 +87,8 @@ pub struct OracleInfo {
 
     // response
     pub ret_code: u16,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub error_code: Option<u32>,
     #[serde(rename = "sql_affected_rows", skip_serializing_if = "value_is_default")]
     pub affected_rows: Option<u32>,
     #[l7_log(response_exception)]
@@ -143,6 +145,7 @@ impl OracleInfo {
         if other.ret_code > 0 {
             self.ret_code = other.ret_code;
         }
+        swap_if!(self, error_code, is_none, other);
         swap_if!(self, affected_rows, is_none, other);
         swap_if!(self, error_message, is_empty, other);
         if other.status != L7ResponseStatus::default() {
@@ -265,7 +268,8 @@ impl From<OracleInfo> for L7ProtocolSendLog {
             },
             resp: L7Response {
                 status: f.status,
-                code: Some(f.ret_code.into()),
+                // ORA error code takes precedence over the return code in TNS
+                code: Some(f.error_code.map(|c| c as i32).unwrap_or(f.ret_code.into())),
                 exception: f.error_message,
                 ..Default::default()
             },
@@ -290,6 +294,17 @@ impl From<&OracleInfo> for LogCache {
     }
 }
 
+fn parse_config(conf: &OracleConfig) -> OracleParseConfig {
+    OracleParseConfig {
+        is_be: conf.is_be,
+        int_compress: conf.int_compressed,
+        resp_0x04_extra_byte: conf.resp_0x04_extra_byte,
+        charset_id: conf.charset.id(),
+        bundled_calls: conf.bundled_calls_enabled,
+        fetch_calls: conf.fetch_calls_enabled,
+    }
+}
+
 #[derive(Default)]
 pub struct OracleLog {
     perf_stats: Vec<L7PerfStats>,
@@ -298,25 +313,14 @@ pub struct OracleLog {
 
 impl L7ProtocolParserInterface for OracleLog {
     fn check_payload(&mut self, payload: &[u8], param: &ParseParam) -> Option<LogMessageType> {
-        OracleParser::check_payload(
-            payload,
-            &OracleParseConfig {
-                is_be: param.oracle_parse_conf.is_be,
-                int_compress: param.oracle_parse_conf.int_compressed,
-                resp_0x04_extra_byte: param.oracle_parse_conf.resp_0x04_extra_byte,
-            },
-        )
+        OracleParser::check_payload(payload, &parse_config(&param.oracle_parse_conf))
     }
 
     fn parse_payload(&mut self, payload: &[u8], param: &ParseParam) -> Result<L7ParseResult> {
         let frames = OracleParser::parse_payload(
             payload,
             param.direction == PacketDirection::ClientToServer,
-            &OracleParseConfig {
-                is_be: param.oracle_parse_conf.is_be,
-                int_compress: param.oracle_parse_conf.int_compressed,
-                resp_0x04_extra_byte: param.oracle_parse_conf.resp_0x04_extra_byte,
-            },
+            &parse_config(&param.oracle_parse_conf),
         );
         if frames.is_empty() {
             return Err(Error::L7ProtocolUnknown);
@@ -351,10 +355,11 @@ impl L7ProtocolParserInterface for OracleLog {
                     msg_type: param.direction.into(),
                     packet_type: frame.packet_type,
                     ret_code: resp.ret_code,
+                    error_code: resp.error_code,
                     affected_rows: resp.affected_rows,
                     error_message: resp.error_message,
-                    status: match resp.ret_code {
-                        0 => L7ResponseStatus::Ok,
+                    status: match (resp.ret_code, resp.error_code) {
+                        (0, None | Some(0)) => L7ResponseStatus::Ok,
                         // TODO: Error code needs to be referenced: https://docs.oracle.com/cd/E11882_01/server.112/e17766/e29250.htm. Currently, simple processing is considered to be a client error
                         _ => L7ResponseStatus::ClientError,
                     },
@@ -412,3 +417,33 @@ impl L7ProtocolParserInterface for OracleLog {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::config::OracleCharset;
+
+    #[test]
+    fn check_parse_config() {
+        for is_be in [true, false] {
+            let conf = OracleConfig {
+                is_be,
+                charset: OracleCharset::Zhs16Gbk,
+                bundled_calls_enabled: false,
+                ..Default::default()
+            };
+            let c = parse_config(&conf);
+            assert_eq!(c.is_be, is_be);
+            assert!(c.int_compress);
+            assert!(!c.resp_0x04_extra_byte);
+            assert_eq!(c.charset_id, 852);
+            assert!(!c.bundled_calls);
+            assert!(c.fetch_calls);
+        }
+
+        let c = parse_config(&OracleConfig::default());
+        assert_eq!(c.charset_id, 873);
+        assert!(c.bundled_calls && c.fetch_calls);
+    }
+}
@@ -569,3 +569,18 @@ pub(super) fn get_code_desc(code: &str) -> (&'static str, L7ResponseStatus) {
         _ => ("", L7ResponseStatus::Unknown),
     }
 }
+
+// status by the class (the first 2 characters) of the code, for the codes not listed above
+pub(super) fn get_status_by_class(code: &str) -> L7ResponseStatus {
+    let Some(class) = code.get(..2) else {
+        return L7ResponseStatus::Unknown;
+    };
+    match class {
+        "03" | "0A" | "0B" | "0F" | "0L" | "0P" | "20" | "22" | "23" | "26" | "2F" | "34"
+        | "3D" | "3F" | "42" => L7ResponseStatus::ClientError,
+        "08" | "09" | "0Z" | "21" | "24" | "25" | "27" | "28" | "2B" | "2D" | "38" | "39"
+        | "3B" | "40" | "44" | "53" | "54" | "55" | "57" | "58" | "72" | "F0" | "HV" | "P0"
+        | "XX" => L7ResponseStatus::ServerError,
+        _ => L7ResponseStatus::Unknown,
+    }
+}
@@ -14,11 +14,13 @@
  * limitations under the License.
  */
 
+use std::num::NonZeroUsize;
+
+use lru::LruCache;
 use public::{
     bytes::{read_u32_be, read_u64_be},
     l7_protocol::{L7Protocol, LogMessageType},
 };
-
 use serde::Serialize;
 
 use crate::{
@@ -31,7 +33,7 @@ use crate::{
     config::handler::LogParserConfig,
     flow_generator::{
         protocol_logs::{
-            pb_adapter::{ExtendedInfo, L7ProtocolSendLog, L7Request, L7Response},
+            pb_adapter::{ExtendedInfo, KeyVal, L7ProtocolSendLog, L7Request, L7Response},
             set_captured_byte, L7ResponseStatus,
         },
         AppProtoHead, Error, Result,
@@ -40,9 +42,9 @@ use crate::{
 
 use super::{
     super::value_is_default,
-    postgre_convert::{get_code_desc, get_request_str},
+    postgre_convert::{get_code_desc, get_request_str, get_status_by_class},
     sql_check::is_postgresql,
-    sql_obfuscate::CachedObfuscator,
+    sql_obfuscate::{CachedObfuscator, SqlDialect},
 };
 
 const SSL_REQ: u64 = 34440615471; // 00000008(len) 04d2162f(const 80877103)
@@ -59,6 +61,7 @@ pub struct PostgreInfo {
         with request, parse:
             simple query ('Q')
             prepare statment ('P')
+            execute ('E') without prepare statment, the sql is from the previous prepare statment
 
         with response parse
             command complete('C')
@@ -91,6 +94,9 @@ pub struct PostgreInfo {
         skip_serializing_if = "value_is_default"
     )]
     pub error_message: String,
+    // severity of error response, such as ERROR, FATAL and PANIC
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub severity: String,
     pub status: L7ResponseStatus,
 
     captured_request_byte: u32,
@@ -132,6 +138,7 @@ impl L7ProtocolInfoInterface for PostgreInfo {
                     self.resp_type = pg.resp_type;
                     std::mem::swap(&mut self.result, &mut pg.result);
                     std::mem::swap(&mut self.error_message, &mut pg.error_message);
+                    std::mem::swap(&mut self.severity, &mut pg.severity);
                     self.status = pg.status;
                     self.affected_rows = pg.affected_rows;
                     self.captured_response_byte = pg.captured_response_byte;
@@ -166,6 +173,14 @@ impl From<PostgreInfo> for L7ProtocolSendLog {
         } else {
             ApplicationFlags::NONE.bits()
         };
+        let attributes = if p.severity.is_empty() {
+            None
+        } else {
+            Some(vec![KeyVal {
+                key: "severity".to_string(),
+                val: p.severity,
+            }])
+        };
         L7ProtocolSendLog {
             captured_request_byte: p.captured_request_byte,
             captured_response_byte: p.captured_response_byte,
@@ -184,6 +199,7 @@ impl From<PostgreInfo> for L7ProtocolSendLog {
                 ..Default::default()
             },
             ext_info: Some(ExtendedInfo {
+                attributes,
                 ..Default::default()
             }),
             flags,
@@ -203,11 +219,26 @@ impl From<&PostgreInfo> for LogCache {
     }
 }
 
-#[derive(Default)]
 pub struct PostgresqlLog {
     perf_stats: Vec<L7PerfStats>,
 
     has_request: bool,
+
+    // extended query protocol, statement name -> sql and portal name -> sql,
+    // the unnamed statement and portal use empty name
+    statements: LruCache<String, String>,
+    portals: LruCache<String, String>,
+}
+
+impl Default for PostgresqlLog {
+    fn default() -> Self {
+        Self {
+            perf_stats: vec![],
+            has_request: false,
+            statements: LruCache::new(NonZeroUsize::new(Self::MAX_STATEMENT_PER_FLOW).unwrap()),
+            portals: LruCache::new(NonZeroUsize::new(Self::MAX_PORTAL_PER_FLOW).unwrap()),
+        }
+    }
 }
 
 impl L7ProtocolParserInterface for PostgresqlLog {
@@ -271,6 +302,9 @@ impl L7ProtocolParserInterface for PostgresqlLog {
 }
 
 impl PostgresqlLog {
+    const MAX_STATEMENT_PER_FLOW: usize = 64;
+    const MAX_PORTAL_PER_FLOW: usize = 8;
+
     fn set_msg_type(&mut self, direction: PacketDirection, info: &mut PostgreInfo) {
         match direction {
             PacketDirection::ClientToServer => info.msg_type = LogMessageType::Request,
@@ -286,6 +320,8 @@ impl PostgresqlLog {
         info: &mut PostgreInfo,
     ) -> Result<()> {
         let mut offset = 0;
+        // sql of the executed portals in a pipelined batch
+        let mut executed = vec![];
         loop {
             if offset >= payload.len() {
                 break;
@@ -294,9 +330,14 @@ impl PostgresqlLog {
             if let Some((tag, len)) = read_block(sub_payload) {
                 offset += len + 5; // len(data) + len 4B + tag 1B
                 let parsed = match info.msg_type {
-                    LogMessageType::Request => {
-                        self.on_req_block(param, tag, &sub_payload[5..5 + len], strict, info)?
-                    }
+                    LogMessageType::Request => self.on_req_block(
+                        param,
+                        tag,
+                        &sub_payload[5..5 + len],
+                        strict,
+                        &mut executed,
+                        info,
+                    )?,
                     LogMessageType::Response => {
                         self.on_resp_block(tag, &sub_payload[5..5 + len], info)?
                     }
@@ -311,6 +352,9 @@ impl PostgresqlLog {
                 break;
             }
         }
+        if !executed.is_empty() {
+            info.context = executed.join("; ");
+        }
         if info.at_lease_one_block {
             if info.msg_type == LogMessageType::Request {
                 self.has_request = true;
@@ -334,9 +378,11 @@ impl PostgresqlLog {
         tag: char,
         data: &[u8],
         strict: bool,
+        executed: &mut Vec<String>,
         info: &mut PostgreInfo,
     ) -> Result<bool> {
-        let obfuscator = CachedObfuscator::new(param.obfuscate_cache.clone());
+        let obfuscator = CachedObfuscator::new(param.obfuscate_cache.clone())
+            .with_dialect(SqlDialect::PostgreSql);
         match tag {
             'Q' => {
                 info.req_type = tag;
@@ -358,7 +404,7 @@ impl PostgresqlLog {
 
                 // | statement str, end with 0x0 | query str, end with 0x0 | param |
                 if let Some(idx) = data.iter().position(|x| *x == 0x0) {
-                    // skip statement
+                    let statement = &data[..idx];
                     data = &data[idx + 1..];
 
                     // parse query
@@ -371,18 +417,71 @@ impl PostgresqlLog {
                             Err(_) => context.to_string(),
                         };
                         if postgresql {
+                            // do not update statements on check_payload
+                            if !strict {
+                                self.statements.put(
+                                    String::from_utf8_lossy(statement).into_owned(),
+                                    info.context.clone(),
+                                );
+                            }
                             return Ok(true);
                         }
                     }
                 }
                 Err(Error::L7ProtocolUnknown)
             }
-            'E' if !strict && info.req_type == '\0' => {
+            // | portal str, end with 0x0 | statement str, end with 0x0 | param |
+            'B' if !strict => {
+                let mut names = data.splitn(3, |x| *x == 0x0);
+                if let (Some(portal), Some(statement)) = (names.next(), names.next()) {
+                    let portal = String::from_utf8_lossy(portal).into_owned();
+                    match self.statements.get(&*String::from_utf8_lossy(statement)) {
+                        Some(sql) => {
+                            let sql = sql.clone();
+                            self.portals.put(portal, sql);
+                        }
+                        // the statement is prepared before capture started
+                        None => {
+                            self.portals.pop(&portal);
+                        }
+                    }
+                }
+                Ok(false)
+            }
+            // | portal str, end with 0x0 | max rows |
+            'E' if !strict => {
+                if let Some(idx) = data.iter().position(|x| *x == 0x0) {
+                    let portal = String::from_utf8_lossy(&data[..idx]);
+                    if let Some(sql) = self.portals.get(&*portal) {
+                        if !executed.contains(sql) {
+                            executed.push(sql.clone());
+                        }
+                    }
+                }
+                if info.req_type != '\0' {
+                    return Ok(false);
+                }
                 info.req_type = tag;
                 info.ignore = false;
 
                 Ok(true)
             }
+            // | 'S' or 'P' | name str, end with 0x0 |
+            'C' if !strict => {
+                if data.len() > 1 && data.ends_with(&[0]) {
+                    let name = String::from_utf8_lossy(&data[1..data.len() - 1]);
+                    match data[0] {
+                        b'S' => {
+                            self.statements.pop(&*name);
+                        }
+                        b'P' => {
+                            self.portals.pop(&*name);
+                        }
+                        _ => (),
+                    }
+                }
+                Ok(false)
+            }
             'B' | 'F' | 'C' | 'D' | 'H' | 'S' | 'X' | 'd' | 'c' | 'f' | 'E' => Ok(false),
             _ => Err(Error::L7ProtocolUnknown),
         }
@@ -400,6 +499,7 @@ impl PostgresqlLog {
                 info.ignore = false;
                 info.resp_type = tag;
 
+                // the affected rows of all commands in a pipelined batch are summed up
                 // reference https://www.postgresql.org/docs/16/protocol-message-formats.html#PROTOCOL-MESSAGE-FORMATS-COMMANDCOMPLETE
                 // INSERT oid rows0x0, where rows is the number of rows inserted.
                 // DELETE | UPDATE | SELECT | MERGE | MOVE | FETCH | COPY rows0x0
@@ -412,7 +512,7 @@ impl PostgresqlLog {
                             data = &data[idx + 1..];
                             if let Some(idx) = data.iter().position(|x| *x == 0x0) {
                                 let row_eff = String::from_utf8_lossy(&data[..idx]).to_string();
-                                info.affected_rows = row_eff.parse().unwrap_or(0);
+                                info.affected_rows += row_eff.parse().unwrap_or(0);
                             }
                         } else {
                             return Ok(true);
@@ -427,7 +527,7 @@ impl PostgresqlLog {
                     {
                         if let Some(idx) = data.iter().position(|x| *x == 0x0) {
                             let row_eff = String::from_utf8_lossy(&data[..idx]).to_string();
-                            info.affected_rows = row_eff.parse().unwrap_or(0);
+                            info.affected_rows += row_eff.parse().unwrap_or(0);
                         }
                     }
                 }
@@ -439,32 +539,47 @@ impl PostgresqlLog {
                 info.resp_type = tag;
                 info.ignore = false;
                 /*
-                Severity: string end with 0x0
-                Text:     string end with 0x0
-                code:     string end with 0x0
-                message:  string end with 0x0
+                | field type 1B | field value, end with 0x0 | ... | 0x0 |
+
+                S: severity (localized)
+                V: severity (never localized, since 9.6)
+                C: code
+                M: message
                 ...
 
+                reference https://www.postgresql.org/docs/16/protocol-error-fields.html
                 */
-                for _ in 0..2 {
-                    if let Some(idx) = data.iter().position(|x| *x == 0) {
-                        data = &data[idx + 1..];
-                    } else {
-                        return Ok(true);
+                let mut code = None;
+                while let Some(idx) = data.iter().position(|x| *x == 0) {
+                    if idx == 0 {
+                        break;
                     }
-                }
-                // code, such as `C42601`
-                if let Some(idx) = data.iter().position(|x| *x == 0) {
-                    if data[0] != b'C' {
-                        return Err(Error::L7ProtocolUnknown);
+                    let value = &data[1..idx];
+                    match data[0] {
+                        b'S' if info.severity.is_empty() => {
+                            info.severity = String::from_utf8_lossy(value).into_owned()
+                        }
+                        b'V' => info.severity = String::from_utf8_lossy(value).into_owned(),
+                        // such as `C42601`
+                        b'C' => code = Some(value),
+                        _ => (),
                     }
-                    info.result = String::from_utf8_lossy(&data[1..idx]).to_string();
-                    let (err_desc, status) = get_code_desc(info.result.as_str());
-                    info.error_message = String::from(err_desc);
-                    info.status = status;
-                    return Ok(true);
+                    data = &data[idx + 1..];
                 }
-                Err(Error::L7ProtocolUnknown)
+                let Some(code) = code else {
+                    return Err(Error::L7ProtocolUnknown);
+                };
+                info.result = String::from_utf8_lossy(code).into_owned();
+                let (err_desc, mut status) = get_code_desc(info.result.as_str());
+                if status == L7ResponseStatus::Unknown {
+                    status = get_status_by_class(info.result.as_str());
+                }
+                if info.severity == "FATAL" || info.severity == "PANIC" {
+                    status = L7ResponseStatus::ServerError;
+                }
+                info.error_message = String::from(err_desc);
+                info.status = status;
+                Ok(true)
             }
 
             'Z' | 'I' | '1' | '2' | '3' | 'S' | 'K' | 'T' | 'n' | 'N' | 't' | 'D' | 'G' | 'H'
@@ -513,9 +628,10 @@ mod test {
             l7_protocol_info::{L7ProtocolInfo, L7ProtocolInfoInterface},
             l7_protocol_log::ParseParam,
             l7_protocol_log::{L7PerfCache, L7ProtocolParserInterface},
+            MetaPacket,
         },
         config::handler::LogParserConfig,
-        flow_generator::protocol_logs::PostgreInfo,
+        flow_generator::protocol_logs::{L7ResponseStatus, PostgreInfo},
         flow_generator::{protocol_logs::PostgresqlLog, L7_RRT_CACHE_CAPACITY},
         utils::test_utils::Capture,
     };
@@ -586,6 +702,7 @@ mod test {
         assert_eq!(info.resp_type, 'E');
         assert_eq!(info.result.as_str(), "42601");
         assert_eq!(info.error_message.as_str(), "syntax_error",);
+        assert_eq!(info.severity.as_str(), "ERROR");
         assert_eq!(info.captured_request_byte, 16);
         assert_eq!(info.captured_response_byte, 98);
 
@@ -664,6 +781,165 @@ mod test {
         unreachable!()
     }
 
+    fn message(tag: u8, fields: &[&[u8]]) -> Vec<u8> {
+        let mut body = vec![];
+        for f in fields {
+            body.extend_from_slice(f);
+        }
+        let mut m = vec![tag];
+        m.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        m.extend_from_slice(&body);
+        m
+    }
+
+    fn parse_message(
+        parser: &mut PostgresqlLog,
+        messages: &[Vec<u8>],
+        direction: PacketDirection,
+    ) -> Option<PostgreInfo> {
+        let payload = messages.concat();
+        let packet = MetaPacket::empty();
+        let mut param = ParseParam::new(
+            &packet,
+            None,
+            Default::default(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Default::default(),
+            false,
+            true,
+        );
+        param.direction = direction;
+        match parser.parse_payload(&payload, &param) {
+            Ok(info) if !info.is_none() => match info.unwrap_single() {
+                L7ProtocolInfo::PostgreInfo(i) => Some(i),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_extended_query_pipeline() {
+        let mut parser = PostgresqlLog::default();
+        let req = parse_message(
+            &mut parser,
+            &[
+                message(b'P', &[b"s1\0insert into t values($1)\0", &[0, 0]]),
+                message(b'B', &[b"\0s1\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"\0", &[0, 0, 0, 0]]),
+                message(b'B', &[b"\0s1\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"\0", &[0, 0, 0, 0]]),
+                message(b'P', &[b"s2\0update t set a=1 where id=$1\0", &[0, 0]]),
+                message(b'B', &[b"\0s2\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"\0", &[0, 0, 0, 0]]),
+                message(b'S', &[]),
+            ],
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(req.req_type, 'P');
+        assert_eq!(
+            req.context.as_str(),
+            "insert into t values($1); update t set a=1 where id=$1"
+        );
+
+        let resp = parse_message(
+            &mut parser,
+            &[
+                message(b'1', &[]),
+                message(b'2', &[]),
+                message(b'C', &[b"INSERT 0 1\0"]),
+                message(b'2', &[]),
+                message(b'C', &[b"INSERT 0 1\0"]),
+                message(b'1', &[]),
+                message(b'2', &[]),
+                message(b'C', &[b"UPDATE 3\0"]),
+                message(b'Z', &[b"I"]),
+            ],
+            PacketDirection::ServerToClient,
+        )
+        .unwrap();
+        assert_eq!(resp.status, L7ResponseStatus::Ok);
+        assert_eq!(resp.affected_rows, 5);
+    }
+
+    #[test]
+    fn test_extended_query_across_segments() {
+        let mut parser = PostgresqlLog::default();
+        let req = parse_message(
+            &mut parser,
+            &[
+                message(b'P', &[b"s3\0select * from t where id=$1\0", &[0, 0]]),
+                message(b'S', &[]),
+            ],
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(req.req_type, 'P');
+        assert!(parse_message(
+            &mut parser,
+            &[message(b'1', &[]), message(b'Z', &[b"I"])],
+            PacketDirection::ServerToClient,
+        )
+        .is_none());
+
+        // bind and execute in another segment
+        let req = parse_message(
+            &mut parser,
+            &[
+                message(b'B', &[b"p1\0s3\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"p1\0", &[0, 0, 0, 0]]),
+                message(b'S', &[]),
+            ],
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(req.req_type, 'E');
+        assert_eq!(req.context.as_str(), "select * from t where id=$1");
+
+        // the portal is reused
+        let req = parse_message(
+            &mut parser,
+            &[message(b'E', &[b"p1\0", &[0, 0, 0, 0]]), message(b'S', &[])],
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(req.context.as_str(), "select * from t where id=$1");
+
+        // the statement is closed or prepared before capture started
+        let req = parse_message(
+            &mut parser,
+            &[
+                message(b'C', &[b"Ss3\0"]),
+                message(b'B', &[b"\0s3\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"\0", &[0, 0, 0, 0]]),
+                message(b'B', &[b"\0S_1\0", &[0, 0, 0, 0, 0, 0]]),
+                message(b'E', &[b"\0", &[0, 0, 0, 0]]),
+                message(b'S', &[]),
+            ],
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(req.req_type, 'E');
+        assert!(req.context.is_empty());
+
+        let resp = parse_message(
+            &mut parser,
+            &[
+                message(
+                    b'E',
+                    &[b"SFATAL\0VFATAL\0C3D999\0Mdatabase does not exist\0\0"],
+                ),
+                message(b'Z', &[b"I"]),
+            ],
+            PacketDirection::ServerToClient,
+        )
+        .unwrap();
+        assert_eq!(resp.result.as_str(), "3D999");
+        assert_eq!(resp.severity.as_str(), "FATAL");
+        assert_eq!(resp.status, L7ResponseStatus::ServerError);
+    }
+
     fn run(name: &str, truncate: Option<usize>) -> String {
         let pcap_file = Path::new(FILE_DIR).join(name);
         let capture = Capture::load_pcap(pcap_file);
@@ -372,6 +372,10 @@ pub struct RedisLog {
     has_request: bool,
     perf_stats: Vec<L7PerfStats>,
     obfuscate: bool,
+    // protocol version requested in HELLO, applied after a successful reply
+    hello_version: Option<u8>,
+    // RESP3 negotiated with HELLO, out-of-band push frames are expected from server
+    resp3: bool,
     #[cfg(feature = "enterprise")]
     custom_field_store: Store,
 }
@@ -401,6 +405,17 @@ impl L7ProtocolParserInterface for RedisLog {
             .parse_config
             .and_then(|config| config.get_custom_field_policies(L7Protocol::Redis.into(), param));
 
+        // push frames in RESP3 are not replies to any request, skip them
+        let payload = if self.resp3 && param.direction == PacketDirection::ServerToClient {
+            let reply = stringifier::skip_pushes(payload);
+            if reply.is_empty() {
+                return Ok(L7ParseResult::None);
+            }
+            reply
+        } else {
+            payload
+        };
+
         let mut info = RedisInfo {
             is_tls: param.is_tls(),
             ..Default::default()
@@ -472,6 +487,16 @@ impl RedisLog {
         info.msg_type = LogMessageType::Request;
         info.request = request.stringify(self.obfuscate);
         self.has_request = true;
+        // HELLO [protover [AUTH username password] [SETNAME clientname]]
+        self.hello_version = if request.cmd_upper == "HELLO" {
+            request
+                .iter()
+                .nth(1)
+                .and_then(|v| str::from_utf8(v).ok())
+                .and_then(|v| v.parse().ok())
+        } else {
+            None
+        };
     }
 
     fn fill_response(&mut self, context: (Vec<u8>, ResponseType), info: &mut RedisInfo) {
@@ -480,17 +505,19 @@ impl RedisLog {
         let (context, response_type) = context;
         info.resp_status = L7ResponseStatus::Ok;
         info.response_result = response_type;
-        if context.is_empty() {
-            return;
-        }
-        match context[0] {
-            b'+' => info.status = context,
-            b'-' | b'!' => {
+        match context.first() {
+            Some(b'+') => info.status = context,
+            Some(b'-' | b'!') => {
                 info.error = context;
                 info.resp_status = L7ResponseStatus::ServerError;
             }
             _ => {}
         }
+        if let Some(version) = self.hello_version.take() {
+            if info.resp_status == L7ResponseStatus::Ok {
+                self.resp3 = version == 3;
+            }
+        }
     }
 
     fn parse(
@@ -512,6 +539,13 @@ impl RedisLog {
             PacketDirection::ClientToServer if payload.get(0) == Some(&b'*') => {
                 self.fill_request(CommandLine::new(payload)?, info)
             }
+            // inline commands are not used in protocol inference, only parsed on redis flows
+            PacketDirection::ClientToServer => {
+                let Ok(request) = CommandLine::new_inline(payload) else {
+                    return Err(Error::L7ProtocolUnknown);
+                };
+                self.fill_request(request, info)
+            }
             PacketDirection::ServerToClient if self.has_request => {
                 self.fill_response(stringifier::decode(payload, false)?, info)
             }
@@ -734,10 +768,8 @@ mod stringifier {
         validate_bulk_type(payload)
     }
 
-    // %<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
-    fn validate_map(payload: &[u8]) -> Result<&[u8]> {
-        assert_eq!(payload[0], b'%');
-
+    // decode maps and attributes
+    fn validate_map_type(payload: &[u8]) -> Result<&[u8]> {
         let (mut payload, length) = read_length(&payload[1..])?;
 
         // actually only -1 is valid
@@ -759,6 +791,12 @@ mod stringifier {
         Ok(payload)
     }
 
+    // %<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
+    fn validate_map(payload: &[u8]) -> Result<&[u8]> {
+        assert_eq!(payload[0], b'%');
+        validate_map_type(payload)
+    }
+
     // ~<number-of-elements>\r\n<element-1>...<element-n>
     fn validate_set(payload: &[u8]) -> Result<&[u8]> {
         assert_eq!(payload[0], b'~');
@@ -771,6 +809,25 @@ mod stringifier {
         validate_array_type(payload)
     }
 
+    // |<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
+    // attributes are auxiliary data preceding the actual reply
+    fn validate_attribute(payload: &[u8]) -> Result<&[u8]> {
+        assert_eq!(payload[0], b'|');
+        validate_map_type(payload)
+    }
+
+    // skip leading push frames, return the rest of payload
+    pub fn skip_pushes(mut payload: &[u8]) -> &[u8] {
+        while payload.first() == Some(&b'>') {
+            match validate_push(payload) {
+                Ok(p) => payload = p,
+                // truncated push frame
+                Err(_) => return &[],
+            }
+        }
+        payload
+    }
+
     fn decode_resp_type<'a>(output: Option<&mut Vec<u8>>, payload: &'a [u8]) -> Result<&'a [u8]> {
         if payload.is_empty() {
             // happens when compound RESP types are truncated between valid segments
@@ -794,6 +851,7 @@ mod stringifier {
             b'%' => validate_map(payload),
             b'~' => validate_set(payload),
             b'>' => validate_push(payload),
+            b'|' => decode_resp_type(output, validate_attribute(payload)?),
             _ => Err(Error::RedisLogParseFailed),
         }
     }
@@ -802,15 +860,30 @@ mod stringifier {
         if payload.is_empty() {
             return Err(Error::RedisLogParseFailed);
         }
-        let mut output = match payload[0] {
-            b'+' | b'-' | b'!' => Vec::with_capacity(payload.len()),
+        // the type of reply after attributes
+        let mut reply = payload;
+        while reply.first() == Some(&b'|') {
+            match validate_attribute(reply) {
+                Ok(p) => reply = p,
+                Err(Error::RedisLogParsePartial) if !strict => break,
+                Err(_) => return Err(Error::RedisLogParseFailed),
+            }
+        }
+        let response_type = reply
+            .first()
+            .map(|t| ResponseType::from(*t))
+            .unwrap_or_default();
+        let mut output = match response_type {
+            ResponseType::String | ResponseType::Error | ResponseType::BulkError => {
+                Vec::with_capacity(reply.len())
+            }
             _ => Vec::new(),
         };
         match (strict, decode_resp_type(Some(&mut output), payload)) {
             (_, Err(Error::RedisLogParseFailed)) | (true, Err(Error::RedisLogParsePartial)) => {
                 Err(Error::RedisLogParseFailed)
             }
-            _ => Ok((output, ResponseType::from(payload[0]))),
+            _ => Ok((output, response_type)),
         }
     }
 }
@@ -879,6 +952,8 @@ struct CommandLine<'a> {
     payload: &'a [u8],
     cmd_upper: String,
     length: usize,
+    // telnet-style command, space separated arguments in one line
+    inline: bool,
 }
 
 impl<'a> CommandLine<'a> {
@@ -919,27 +994,74 @@ impl<'a> CommandLine<'a> {
             payload,
             cmd_upper,
             length: length as usize,
+            inline: false,
+        })
+    }
+
+    // inline command: <command> [argument ...]\r\n
+    fn new_inline(payload: &'a [u8]) -> Result<Self> {
+        let Some(end) = payload.iter().position(|&b| b == b'\n') else {
+            return Err(Error::RedisLogParseFailed);
+        };
+        let line = payload[..end]
+            .strip_suffix(b"\r")
+            .unwrap_or(&payload[..end]);
+        if line
+            .iter()
+            .any(|b| !b.is_ascii() || (b.is_ascii_control() && *b != b'\t'))
+        {
+            return Err(Error::RedisLogParseFailed);
+        }
+
+        let mut iter = CommandIterator {
+            payload: line,
+            index: 0,
+            size: usize::MAX,
+            inline: true,
+        };
+        let Some(command) = iter.next() else {
+            return Err(Error::RedisLogParseFailed);
+        };
+        if command.len() > max_command_length() {
+            return Err(Error::RedisLogParseFailed);
+        }
+        // SAFTY: checked ascii string
+        let cmd_upper = unsafe { str::from_utf8_unchecked(command).to_ascii_uppercase() };
+        if !Self::check_command_word(&cmd_upper, iter.next()) {
+            return Err(Error::RedisLogParseFailed);
+        }
+
+        Ok(Self {
+            payload: line,
+            cmd_upper,
+            length: line
+                .split(u8::is_ascii_whitespace)
+                .filter(|s| !s.is_empty())
+                .count(),
+            inline: true,
         })
     }
 
     fn check_command(cmd_upper: &str, next_cmds: Option<&[u8]>) -> bool {
+        let next_cmd = match next_cmds.map(Self::decode_bulk_string) {
+            Some(Ok((_, next_cmd))) => Some(next_cmd),
+            Some(Err(_)) => return false,
+            None => None,
+        };
+        Self::check_command_word(cmd_upper, next_cmd)
+    }
+
+    fn check_command_word(cmd_upper: &str, next_cmd: Option<&[u8]>) -> bool {
         ALL_COMMANDS.with(|cell| {
             let cmds = cell.get_or_init(all_commands);
             match cmds.binary_search_by_key(&cmd_upper, |cmd| &cmd.cmd) {
                 Ok(id) if cmds[id].sub.is_empty() => true,
                 Ok(id) => {
-                    if let Some(next) = next_cmds {
-                        let Ok((_, next_cmd)) = Self::decode_bulk_string(next) else {
-                            return false;
-                        };
-                        let Ok(next_cmd) = str::from_utf8(next_cmd) else {
-                            return false;
-                        };
-                        let next_cmd_upper = next_cmd.to_ascii_uppercase();
-                        cmds[id].sub.binary_search(&next_cmd_upper).is_ok()
-                    } else {
-                        false
-                    }
+                    let Some(Ok(next_cmd)) = next_cmd.map(str::from_utf8) else {
+                        return false;
+                    };
+                    let next_cmd_upper = next_cmd.to_ascii_uppercase();
+                    cmds[id].sub.binary_search(&next_cmd_upper).is_ok()
                 }
                 Err(_) => false,
             }
@@ -971,12 +1093,13 @@ impl<'a> CommandLine<'a> {
             payload: self.payload,
             index: 0,
             size: self.length,
+            inline: self.inline,
         }
     }
 
     fn command(&self) -> &[u8] {
-        // unwrap safe because checked in Self::new()
-        Self::decode_bulk_string(self.payload).unwrap().1
+        // unwrap safe because checked in Self::new() or Self::new_inline()
+        self.iter().next().unwrap()
     }
 
     fn stringify(&self, obfuscate: bool) -> Vec<u8> {
@@ -1168,11 +1291,12 @@ impl<'a> CommandLine<'a> {
     }
 }
 
-// redis command is defined as 'an array of bulk strings'
+// redis command is defined as 'an array of bulk strings', or space separated words for inline commands
 struct CommandIterator<'a> {
     payload: &'a [u8],
     index: usize,
     size: usize,
+    inline: bool,
 }
 
 impl CommandIterator<'_> {
@@ -1220,6 +1344,18 @@ impl<'a> Iterator for CommandIterator<'a> {
             return None;
         }
 
+        if self.inline {
+            let start = self.payload.iter().position(|b| !b.is_ascii_whitespace())?;
+            let payload = &self.payload[start..];
+            let end = payload
+                .iter()
+                .position(u8::is_ascii_whitespace)
+                .unwrap_or(payload.len());
+            self.payload = &payload[end..];
+            self.index += 1;
+            return Some(&payload[..end]);
+        }
+
         // unwrap safe because checked in CommandLine::new()
         let (payload, s) = CommandLine::decode_bulk_string(self.payload).unwrap();
         self.payload = payload;
@@ -1359,6 +1495,9 @@ mod tests {
             // ~<number-of-elements>\r\n<element-1>...<element-n>
             // ><number-of-elements>\r\n<element-1>...<element-n>
             (("~2\r\n+key\r\n:123\r\n", true), Some("")),
+            // |<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>
+            (("|1\r\n+ttl\r\n:3600\r\n+OK\r\n", true), Some("+OK")),
+            (("|1\r\n+ttl\r\n:3600\r\n", true), None),
         ];
         for (input, expected) in testcases.iter() {
             let output = stringifier::decode(&input.0.as_bytes(), input.1);
@@ -1606,4 +1745,127 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn check_inline_command() {
+        let testcases = [
+            ("PING\r\n", "PING"),
+            ("GET key\r\n", "GET key"),
+            ("SET  key   value\r\n", "SET key ?"),
+            ("AUTH my-secret-password\n", "AUTH ?"),
+            ("CONFIG SET parameter value\r\n", "CONFIG SET parameter ?"),
+            (
+                "HSET key field value field1 value1\r\n",
+                "HSET key field ? field1 ?",
+            ),
+        ];
+        for (input, expected) in testcases.iter() {
+            let Ok(cmdline) = CommandLine::new_inline(input.as_bytes()) else {
+                panic!("parse inline cmdline failed at: {input:?}");
+            };
+            let output = cmdline.stringify(true);
+            assert_eq!(str::from_utf8(&output).unwrap(), *expected);
+
+            // obfuscation result is the same as RESP encoded command
+            let redis_str = encode_redis_command(
+                input
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .as_str(),
+            );
+            let resp = CommandLine::new(&redis_str).unwrap().stringify(true);
+            assert_eq!(output, resp, "testcase {input:?} failed");
+        }
+
+        assert!(CommandLine::new_inline(b"GET key").is_err());
+        assert!(CommandLine::new_inline(b"\r\n").is_err());
+        assert!(CommandLine::new_inline(b"NOTACOMMAND key\r\n").is_err());
+        assert!(CommandLine::new_inline(b"ACL INVALID\r\n").is_err());
+        assert!(CommandLine::new_inline(b"GET \x01\x02\r\n").is_err());
+    }
+
+    #[test]
+    fn check_resp3_upgrade() {
+        let mut redis = RedisLog::default();
+        let packet = MetaPacket::empty();
+        let mut parse = |payload: &[u8], direction: PacketDirection| {
+            let mut param = ParseParam::new(
+                &packet,
+                None,
+                Default::default(),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                Default::default(),
+                false,
+                true,
+            );
+            param.l4_protocol = IpProtocol::TCP;
+            param.direction = direction;
+            match redis.parse_payload(payload, &param) {
+                Ok(L7ParseResult::Single(L7ProtocolInfo::RedisInfo(info))) => Some(info),
+                _ => None,
+            }
+        };
+        let push = b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n";
+
+        // RESP2 before HELLO, push frame is handled as before
+        parse(
+            &encode_redis_command("GET key"),
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        let info = parse(push, PacketDirection::ServerToClient).unwrap();
+        assert!(matches!(info.response_result, ResponseType::Push));
+
+        // switch to RESP3 mid-connection
+        parse(
+            &encode_redis_command("HELLO 3"),
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        let info = parse(
+            b"%2\r\n$6\r\nserver\r\n$5\r\nredis\r\n$5\r\nproto\r\n:3\r\n",
+            PacketDirection::ServerToClient,
+        )
+        .unwrap();
+        assert!(matches!(info.response_result, ResponseType::Map));
+        assert_eq!(info.resp_status, L7ResponseStatus::Ok);
+
+        // out-of-band push frame is not a response
+        assert!(parse(push, PacketDirection::ServerToClient).is_none());
+
+        let info = parse(
+            &encode_redis_command("HGETALL key"),
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        assert_eq!(info.request, b"HGETALL key");
+        let mut payload = push.to_vec();
+        payload.extend_from_slice(b"%1\r\n$5\r\nfield\r\n,3.14\r\n");
+        let info = parse(&payload, PacketDirection::ServerToClient).unwrap();
+        assert_eq!(info.msg_type, LogMessageType::Response);
+        assert!(matches!(info.response_result, ResponseType::Map));
+        assert_eq!(info.resp_status, L7ResponseStatus::Ok);
+
+        parse(b"SMEMBERS key\r\n", PacketDirection::ClientToServer).unwrap();
+        let info = parse(
+            b"~2\r\n(3492890328409238509324850943850943825024385\r\n#t\r\n",
+            PacketDirection::ServerToClient,
+        )
+        .unwrap();
+        assert!(matches!(info.response_result, ResponseType::Set));
+
+        parse(
+            &encode_redis_command("GET key"),
+            PacketDirection::ClientToServer,
+        )
+        .unwrap();
+        let info = parse(
+            b"!21\r\nSYNTAX invalid syntax\r\n",
+            PacketDirection::ServerToClient,
+        )
+        .unwrap();
+        assert_eq!(info.resp_status, L7ResponseStatus::ServerError);
+        assert_eq!(info.error, b"!SYNTAX invalid syntax");
+    }
 }
@@ -17,9 +17,9 @@
 use std::{borrow::Cow, fmt::Write, str};
 
 use sqlparser::{
-    dialect::GenericDialect,
+    dialect::{Dialect, GenericDialect, MySqlDialect, PostgreSqlDialect},
     keywords::Keyword,
-    tokenizer::{Token, Tokenizer, TokenizerError, Word},
+    tokenizer::{Token, Tokenizer, TokenizerError, Whitespace, Word},
 };
 
 use public::utils::hash::hash_to_u64;
@@ -34,6 +34,79 @@ pub enum Error {
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SqlDialect {
+    #[default]
+    Generic,
+    // backslash escapes in string literals
+    MySql,
+    // E'' escaped strings and $$ quoted strings
+    PostgreSql,
+}
+
+impl SqlDialect {
+    fn dialect(&self) -> &'static dyn Dialect {
+        match self {
+            Self::Generic => &GenericDialect,
+            Self::MySql => &MySqlDialect {},
+            Self::PostgreSql => &PostgreSqlDialect {},
+        }
+    }
+}
+
+// output of obfuscation, comments are stripped
+#[derive(Default)]
+struct Output {
+    s: String,
+    // a comment is stripped right before the next token
+    stripped: bool,
+    has_stripped: bool,
+}
+
+impl Output {
+    fn with_tokens(expected_len: usize, tokens: &[Token]) -> Self {
+        let mut output = Self {
+            s: String::with_capacity(expected_len),
+            ..Default::default()
+        };
+        for token in tokens {
+            output.push_token(token);
+        }
+        output
+    }
+
+    fn push_str(&mut self, s: &str) {
+        // keep words separated where a comment is removed, e.g. `SELECT/**/1`
+        if self.stripped {
+            let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+            if self.s.ends_with(is_word_char) && s.starts_with(is_word_char) {
+                self.s.push(' ');
+            }
+            self.stripped = false;
+        }
+        self.s.push_str(s);
+    }
+
+    fn push_token(&mut self, token: &Token) {
+        if Obfuscator::is_comment(token) {
+            self.stripped = true;
+            self.has_stripped = true;
+            return;
+        }
+        let mut s = String::new();
+        let _ = write!(s, "{token}");
+        self.push_str(&s);
+    }
+
+    fn into_string(self) -> String {
+        if self.has_stripped {
+            self.s.trim().to_string()
+        } else {
+            self.s
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Obfuscator;
 
@@ -70,32 +143,52 @@ impl Obfuscator {
         }
     }
 
-    // write all tokens before the first obfuscated token to the output
-    fn prepare_obfuscated(expected_len: usize, tokens: &[Token]) -> String {
-        let mut obfuscated = String::with_capacity(expected_len);
-        for token in tokens {
-            let _ = write!(obfuscated, "{token}");
-        }
-        obfuscated
+    fn is_literal(token: &Token) -> bool {
+        matches!(
+            token,
+            Token::SingleQuotedString(_)
+                | Token::DoubleQuotedString(_)
+                | Token::NationalStringLiteral(_)
+                | Token::EscapedStringLiteral(_)
+                | Token::HexStringLiteral(_)
+                | Token::SingleQuotedByteStringLiteral(_)
+                | Token::DollarQuotedString(_)
+                | Token::Number(_, _)
+        )
+    }
+
+    fn is_comment(token: &Token) -> bool {
+        matches!(
+            token,
+            Token::Whitespace(Whitespace::SingleLineComment { .. })
+                | Token::Whitespace(Whitespace::MultiLineComment(_))
+        )
     }
 
     pub fn apply(sql: &str) -> Result<Cow<'_, str>> {
-        let dialect = GenericDialect;
-        let tokens = match Tokenizer::new(&dialect, sql)
-            .with_unescape(false)
-            .tokenize()
-        {
+        Self::apply_with_dialect(SqlDialect::Generic, sql)
+    }
+
+    pub fn apply_with_dialect(dialect: SqlDialect, sql: &str) -> Result<Cow<'_, str>> {
+        let dialect = dialect.dialect();
+        let tokens = match Tokenizer::new(dialect, sql).with_unescape(false).tokenize() {
             Ok(tokens) => tokens,
             Err(TokenizerError { location, .. }) => {
                 // if sql is truncated, try again before error location, and append a phantom word token to the end
-                let byte_offset = sql
+                // column of the location is counted in chars
+                let line_offset = sql
                     .split_inclusive('\n')
                     .take(location.line.saturating_sub(1) as usize)
                     .map(|l| l.len())
                     .sum::<usize>()
-                    + (location.column as usize).saturating_sub(1);
-                let truncated = &sql[..byte_offset.min(sql.len())];
-                let mut tokens = Tokenizer::new(&dialect, truncated)
+                    .min(sql.len());
+                let byte_offset = sql[line_offset..]
+                    .char_indices()
+                    .nth((location.column as usize).saturating_sub(1))
+                    .map(|(i, _)| line_offset + i)
+                    .unwrap_or(sql.len());
+                let truncated = &sql[..byte_offset];
+                let mut tokens = Tokenizer::new(dialect, truncated)
                     .with_unescape(false)
                     .tokenize()?;
                 tokens.push(Token::Word(Word {
@@ -106,28 +199,32 @@ impl Obfuscator {
                 tokens
             }
         };
-        let mut obfuscated = None;
+        let mut obfuscated: Option<Output> = None;
 
         let mut iter = tokens.iter().enumerate().peekable();
         while let Some((index, token)) = iter.next() {
             match token {
-                Token::SingleQuotedString(_)
-                | Token::DoubleQuotedString(_)
-                | Token::Number(_, _) => {
+                _ if Self::is_literal(token) => {
+                    let obfuscated = obfuscated.get_or_insert_with(|| {
+                        Output::with_tokens(tokens.len() / 2, &tokens[..index])
+                    });
+                    obfuscated.push_str("?");
+                }
+                _ if Self::is_comment(token) => {
                     let obfuscated = obfuscated.get_or_insert_with(|| {
-                        Self::prepare_obfuscated(tokens.len() / 2, &tokens[..index])
+                        Output::with_tokens(tokens.len() / 2, &tokens[..index])
                     });
-                    let _ = write!(obfuscated, "?");
+                    obfuscated.push_token(token);
                 }
                 _ if Self::is_before_obfuscated(token) => {
                     let obfuscated = obfuscated.get_or_insert_with(|| {
-                        Self::prepare_obfuscated(tokens.len() / 2, &tokens[..index])
+                        Output::with_tokens(tokens.len() / 2, &tokens[..index])
                     });
-                    let _ = write!(obfuscated, "{token}");
+                    obfuscated.push_token(token);
                     // consume any whitespaces
                     while let Some((_, token)) = iter.peek() {
                         if matches!(token, Token::Whitespace(_)) {
-                            let _ = write!(obfuscated, "{token}");
+                            obfuscated.push_token(token);
                             iter.next();
                         } else {
                             break;
@@ -151,7 +248,7 @@ impl Obfuscator {
                                     break;
                                 }
                             }
-                            let _ = write!(obfuscated, "(?)");
+                            obfuscated.push_str("(?)");
                         }
                         _ => {
                             // consume all valid tokens
@@ -176,20 +273,20 @@ impl Obfuscator {
                                     _ => break,
                                 }
                             }
-                            let _ = write!(obfuscated, "?");
+                            obfuscated.push_str("?");
                         }
                     }
                 }
                 _ => {
                     if let Some(obfuscated) = obfuscated.as_mut() {
-                        let _ = write!(obfuscated, "{token}");
+                        obfuscated.push_token(token);
                     }
                 }
             }
         }
 
         match obfuscated {
-            Some(obfuscated) => Ok(Cow::Owned(obfuscated)),
+            Some(obfuscated) => Ok(Cow::Owned(obfuscated.into_string())),
             None => Ok(Cow::Borrowed(sql)),
         }
     }
@@ -199,11 +296,20 @@ impl Obfuscator {
 pub struct CachedObfuscator {
     // if cache is not set, do not apply obfuscation
     cache: Option<ObfuscateCache>,
+    dialect: SqlDialect,
 }
 
 impl CachedObfuscator {
     pub fn new(cache: Option<ObfuscateCache>) -> Self {
-        Self { cache }
+        Self {
+            cache,
+            dialect: SqlDialect::Generic,
+        }
+    }
+
+    pub fn with_dialect(mut self, dialect: SqlDialect) -> Self {
+        self.dialect = dialect;
+        self
     }
 
     pub fn apply<'a>(&self, sql: &'a str) -> Result<Cow<'a, str>> {
@@ -211,11 +317,11 @@ impl CachedObfuscator {
             return Ok(Cow::Borrowed(sql));
         };
 
-        let key = hash_to_u64(&sql);
+        let key = hash_to_u64(&(self.dialect, sql));
         if let Some(s) = cache.borrow_mut().get(&key) {
             return Ok(Cow::Owned(s.clone()));
         }
-        let obfuscated = Obfuscator::apply(sql)?;
+        let obfuscated = Obfuscator::apply_with_dialect(self.dialect, sql)?;
         if obfuscated != sql {
             cache.borrow_mut().put(key, obfuscated.to_string());
         }
@@ -286,7 +392,7 @@ mod tests {
                 ),
                 (
                     "SELECT * FROM table where id = 1;-- some comment, 一些注释",
-                    Some("SELECT * FROM table where id = ?;-- some comment, 一些注释"),
+                    Some("SELECT * FROM table where id = ?;"),
                 ),
                 (
                     "SELECT * FROM table where id = 1;// some comment, 一些注释",
@@ -294,7 +400,7 @@ mod tests {
                 ),
                 (
                     r#"/* 这是一个多行注释*/ SELECT count(*) FROM table WHERE id = 100;"#,
-                    Some(r#"/* 这是一个多行注释*/ SELECT count(*) FROM table WHERE id = ?;"#),
+                    Some("SELECT count(*) FROM table WHERE id = ?;"),
                 ),
                 (
                     "MERGE INTO Employees AS target USING EmployeeUpdates AS source ON (target.EmployeeID = source.EmployeeID) WHEN MATCHED THEN UPDATE SET target.Name = source.Name WHEN NOT MATCHED BY TARGET THEN INSERT (EmployeeID, Name) VALUES (source.EmployeeID, source.Name) WHEN NOT MATCHED BY SOURCE THEN DELETE OUTPUT $action, inserted.*, deleted.*;",
@@ -322,7 +428,7 @@ mod tests {
                 ),
                 (
                     "select * from vm where (/*hello*/name = '40.211-sxdl-1060a');",
-                    Some("select * from vm where (/*hello*/name = ?);"),
+                    Some("select * from vm where (name = ?);"),
                 ),
                 (
                     "SELECT * FROM `process` WHERE `process`.`deleted_at` IS NULL",
@@ -347,6 +453,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn obfuscate_with_dialect() {
+        let test_cases = [
+            (
+                SqlDialect::MySql,
+                r"SELECT * FROM users WHERE name = 'O\'Brien' AND id = 1",
+                "SELECT * FROM users WHERE name = ? AND id = ?",
+            ),
+            (
+                SqlDialect::MySql,
+                "SELECT HEX(X'4D7953514C'), CONCAT('a', 0x41) FROM t",
+                "SELECT HEX(?), CONCAT(?, ?) FROM t",
+            ),
+            (
+                SqlDialect::MySql,
+                "SELECT id FROM t # password is 'secret'\nWHERE flag",
+                "SELECT id FROM t WHERE flag",
+            ),
+            (
+                SqlDialect::MySql,
+                "SELECT/* hint */id FROM t",
+                "SELECT id FROM t",
+            ),
+            (
+                SqlDialect::PostgreSql,
+                r"SELECT upper(E'it\'s'), 'O''Brien'",
+                "SELECT upper(?), ?",
+            ),
+            (
+                SqlDialect::PostgreSql,
+                "SELECT concat('张三', '😊') FROM users",
+                "SELECT concat(?, ?) FROM users",
+            ),
+        ];
+        for (dialect, input, expected) in test_cases.iter() {
+            let result = Obfuscator::apply_with_dialect(*dialect, input).unwrap();
+            assert_eq!(result.as_ref(), *expected, "testcase failed: `{input}`");
+        }
+    }
+
+    #[test]
+    fn multi_byte_truncated() {
+        let input = "SELECT * FROM users WHERE name = '张三' AND city = '北京";
+        let expected = "SELECT * FROM users WHERE name = ? AND city = ?";
+        let result = Obfuscator::apply_with_dialect(SqlDialect::MySql, input).unwrap();
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn multiple_line_truncated() {
         let input = r#"
@@ -14,9 +14,11 @@
  * limitations under the License.
  */
 
-use std::fmt::Display;
+use std::fmt::{Display, Write};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use md5::{Digest, Md5};
+use ring::digest;
 use serde::Serialize;
 
 use super::pb_adapter::{
@@ -34,6 +36,7 @@ use crate::{
         Timestamp,
     },
     flow_generator::error::{Error, Result},
+    utils::bytes::read_u16_be,
 };
 use l7::tls::TlsHeader;
 use public::l7_protocol::{L7Protocol, LogMessageType};
@@ -252,6 +255,20 @@ pub struct TlsInfo {
     pub client_cert_not_before: Timestamp,
     #[serde(skip)]
     pub client_cert_not_after: Timestamp,
+    #[serde(skip)]
+    pub server_cert_subject_cn: String,
+    #[serde(skip)]
+    pub server_cert_issuer_cn: String,
+    #[serde(skip)]
+    pub server_cert_san_count: u32,
+    #[serde(skip)]
+    pub server_cert_expiring: bool,
+    #[serde(skip)]
+    pub handshake_completed: bool,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub ja3: String,
+    #[serde(skip_serializing_if = "value_is_default")]
+    pub ja4: String,
 
     captured_request_byte: u32,
     captured_response_byte: u32,
@@ -263,6 +280,8 @@ pub struct TlsInfo {
 
     #[serde(skip)]
     is_on_blacklist: bool,
+    #[serde(skip)]
+    endpoint: Option<String>,
 }
 
 impl L7ProtocolInfoInterface for TlsInfo {
@@ -306,6 +325,10 @@ impl L7ProtocolInfoInterface for TlsInfo {
         self.request_resource.len()
     }
 
+    fn get_endpoint(&self) -> Option<String> {
+        self.endpoint.clone()
+    }
+
     fn is_on_blacklist(&self) -> bool {
         self.is_on_blacklist
     }
@@ -316,6 +339,9 @@ impl TlsInfo {
         if other.is_on_blacklist {
             self.is_on_blacklist = other.is_on_blacklist;
         }
+        if other.handshake_completed {
+            self.handshake_completed = true;
+        }
         match other.msg_type {
             LogMessageType::Request => {
                 std::mem::swap(&mut self.handshake_protocol, &mut other.handshake_protocol);
@@ -330,6 +356,11 @@ impl TlsInfo {
                     &mut self.client_cert_not_before,
                     &mut other.client_cert_not_before,
                 );
+                std::mem::swap(&mut self.ja3, &mut other.ja3);
+                std::mem::swap(&mut self.ja4, &mut other.ja4);
+                if self.endpoint.is_none() {
+                    self.endpoint = other.endpoint.take();
+                }
                 self.captured_request_byte = other.captured_request_byte;
             }
             LogMessageType::Response => {
@@ -347,6 +378,18 @@ impl TlsInfo {
                     &mut self.server_cert_not_before,
                     &mut other.server_cert_not_before,
                 );
+                if !other.server_cert_subject_cn.is_empty() || other.server_cert_san_count > 0 {
+                    std::mem::swap(
+                        &mut self.server_cert_subject_cn,
+                        &mut other.server_cert_subject_cn,
+                    );
+                    std::mem::swap(
+                        &mut self.server_cert_issuer_cn,
+                        &mut other.server_cert_issuer_cn,
+                    );
+                    self.server_cert_san_count = other.server_cert_san_count;
+                }
+                self.server_cert_expiring = other.server_cert_expiring;
                 self.captured_response_byte = other.captured_response_byte;
             }
             _ => {}
@@ -357,7 +400,21 @@ impl TlsInfo {
         if let Some(t) = config.l7_log_blacklist_trie.get(&L7Protocol::TLS) {
             self.is_on_blacklist = t.request_resource.is_on_blacklist(&self.request_resource)
                 || t.request_type.is_on_blacklist(&self.request_type)
-                || t.request_domain.is_on_blacklist(&self.request_domain);
+                || t.request_domain.is_on_blacklist(&self.request_domain)
+                || t.attribute_is_on_blacklist(
+                    "server_cert_subject_cn",
+                    &self.server_cert_subject_cn,
+                )
+                || t.attribute_is_on_blacklist(
+                    "server_cert_issuer_cn",
+                    &self.server_cert_issuer_cn,
+                );
+            if !self.server_cert_not_after.is_zero() {
+                self.is_on_blacklist |= t.attribute_is_on_blacklist(
+                    "server_cert_expiring",
+                    &self.server_cert_expiring.to_string(),
+                );
+            }
         }
     }
 }
@@ -373,6 +430,18 @@ impl From<TlsInfo> for L7ProtocolSendLog {
                 val: cipher_suite.to_string(),
             });
         }
+        if !f.ja3.is_empty() {
+            attributes.push(KeyVal {
+                key: "ja3".to_string(),
+                val: f.ja3,
+            });
+        }
+        if !f.ja4.is_empty() {
+            attributes.push(KeyVal {
+                key: "ja4".to_string(),
+                val: f.ja4,
+            });
+        }
         if !f.client_cert_not_before.is_zero() {
             attributes.push(KeyVal {
                 key: "client_cert_not_before".to_string(),
@@ -416,6 +485,34 @@ impl From<TlsInfo> for L7ProtocolSendLog {
                 key: "server_cert_valid_days".to_string(),
                 val: valid_days,
             });
+            attributes.push(KeyVal {
+                key: "server_cert_expiring".to_string(),
+                val: f.server_cert_expiring.to_string(),
+            });
+        }
+        if !f.server_cert_subject_cn.is_empty() {
+            attributes.push(KeyVal {
+                key: "server_cert_subject_cn".to_string(),
+                val: f.server_cert_subject_cn,
+            });
+        }
+        if f.server_cert_san_count > 0 {
+            attributes.push(KeyVal {
+                key: "server_cert_san_count".to_string(),
+                val: f.server_cert_san_count.to_string(),
+            });
+        }
+        if !f.server_cert_issuer_cn.is_empty() {
+            attributes.push(KeyVal {
+                key: "server_cert_issuer_cn".to_string(),
+                val: f.server_cert_issuer_cn,
+            });
+        }
+        if f.handshake_completed {
+            attributes.push(KeyVal {
+                key: "handshake_completed".to_string(),
+                val: "true".to_string(),
+            });
         }
         let log = L7ProtocolSendLog {
             captured_request_byte: f.captured_request_byte,
@@ -428,6 +525,7 @@ impl From<TlsInfo> for L7ProtocolSendLog {
                 } else {
                     f.request_type
                 },
+                endpoint: f.endpoint.unwrap_or_default(),
                 ..Default::default()
             },
             resp: L7Response {
@@ -471,6 +569,7 @@ impl From<&TlsInfo> for LogCache {
             msg_type: info.msg_type,
             resp_status: info.status,
             on_blacklist: info.is_on_blacklist,
+            endpoint: info.endpoint.clone(),
             ..Default::default()
         }
     }
@@ -480,6 +579,7 @@ impl From<&TlsInfo> for LogCache {
 pub struct TlsLog {
     change_cipher_spec_count: u8,
     is_change_cipher_spec: bool,
+    certificate_reader: CertificateReader,
     perf_stats: Vec<L7PerfStats>,
 }
 
@@ -507,10 +607,22 @@ impl L7ProtocolParserInterface for TlsLog {
         self.parse(payload, &mut info, param)?;
 
         if let Some(config) = param.parse_config {
+            if config.tls_fingerprint_as_endpoint && !info.ja4.is_empty() {
+                info.endpoint = Some(info.ja4.clone());
+            }
+            if !info.server_cert_not_after.is_zero() {
+                info.server_cert_expiring = info.server_cert_not_after
+                    < Timestamp::from_micros(param.time) + config.tls_cert_expiry_warning;
+            }
             info.set_is_on_blacklist(config);
         }
         if param.parse_perf {
             let mut perf_stat = L7PerfStats::default();
+            if info.msg_type == LogMessageType::Response {
+                if let Some(endpoint) = info.load_endpoint_from_cache(param, false) {
+                    info.endpoint = Some(endpoint.to_string());
+                }
+            }
             // Triggered by Client Hello and the last Change cipher spec
             if info.cal_tls_rtt {
                 if let Some(stats) = info.perf_stats(param) {
@@ -554,6 +666,13 @@ impl TlsLog {
     const CHNAGE_CIPHER_SPEC_LIMIT: u8 = 2;
 
     fn parse(&mut self, payload: &[u8], info: &mut TlsInfo, param: &ParseParam) -> Result<()> {
+        // Segments in the middle of a record are not recognized by `TlsHeader`,
+        // so the server certificate is read before the headers are checked
+        if param.direction == PacketDirection::ServerToClient {
+            self.certificate_reader
+                .read(payload, param.buf_size as usize);
+        }
+
         let mut tls_headers = vec![];
         let mut offset = 0;
         while offset + TlsHeader::HEADER_LEN <= payload.len() {
@@ -602,6 +721,7 @@ impl TlsLog {
                         if self.change_cipher_spec_count >= Self::CHNAGE_CIPHER_SPEC_LIMIT {
                             self.change_cipher_spec_count = 0;
                             info.cal_tls_rtt = true;
+                            info.handshake_completed = true;
                         }
                     } else {
                         self.is_change_cipher_spec = false;
@@ -624,6 +744,12 @@ impl TlsLog {
                     }
                 });
 
+                // fingerprints are only computed for records starting with a ClientHello
+                if let Some(hello) = ClientHello::parse(payload) {
+                    info.ja3 = hello.ja3();
+                    info.ja4 = hello.ja4();
+                }
+
                 info.request_type = tls_headers
                     .iter()
                     .map(|i| i.to_string())
@@ -663,6 +789,7 @@ impl TlsLog {
                         if self.change_cipher_spec_count >= Self::CHNAGE_CIPHER_SPEC_LIMIT {
                             self.change_cipher_spec_count = 0;
                             info.cal_tls_rtt = true;
+                            info.handshake_completed = true;
                         }
                     } else {
                         self.is_change_cipher_spec = false;
@@ -680,6 +807,18 @@ impl TlsLog {
                     }
                 });
 
+                // The certificate may be completed by a previous segment that is not a log itself,
+                // it's reported with the next response in this case
+                if let Some(cert) = self.certificate_reader.certificate.take() {
+                    if info.server_cert_not_after.is_zero() {
+                        info.server_cert_not_before = cert.not_before;
+                        info.server_cert_not_after = cert.not_after;
+                    }
+                    info.server_cert_subject_cn = cert.subject_cn;
+                    info.server_cert_issuer_cn = cert.issuer_cn;
+                    info.server_cert_san_count = cert.san_count;
+                }
+
                 if let Version::Unknown(v) = info.version {
                     return Err(Error::TlsLogParseFailed(format!(
                         "Unknown tls version 0x{:x}",
@@ -699,3 +838,689 @@ impl TlsLog {
         Ok(())
     }
 }
+
+fn read_u24_be(bs: &[u8]) -> usize {
+    (bs[0] as usize) << 16 | (bs[1] as usize) << 8 | bs[2] as usize
+}
+
+// Reads the leaf certificate from the server Certificate message, which is only in cleartext
+// before TLS 1.3. The message usually spans several records and TCP segments.
+#[derive(Default)]
+struct CertificateReader {
+    done: bool,
+    // bytes of the current handshake record in the following segments
+    record_remain: usize,
+    // handshake messages stripped from records
+    buffer: Vec<u8>,
+    certificate: Option<ServerCertificate>,
+}
+
+impl CertificateReader {
+    const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+    const HANDSHAKE_TYPE_CERTIFICATE: u8 = 11;
+    const HANDSHAKE_HEADER_LEN: usize = 4;
+    // certificate_list length and the first certificate length
+    const CERTIFICATE_LIST_HEADER_LEN: usize = 6;
+
+    // `budget` limits the size of handshake messages to be buffered
+    fn read(&mut self, payload: &[u8], budget: usize) {
+        if self.done {
+            return;
+        }
+        let mut data = payload;
+        while !data.is_empty() {
+            if self.record_remain > 0 {
+                let n = self.record_remain.min(data.len());
+                self.buffer.extend_from_slice(&data[..n]);
+                self.record_remain -= n;
+                data = &data[n..];
+                continue;
+            }
+            // Records after ChangeCipherSpec are encrypted, and TLS 1.3 encrypts everything after
+            // ServerHello. Record headers split across segments are not supported either.
+            if data.len() < TlsHeader::HEADER_LEN
+                || data[0] != Self::CONTENT_TYPE_HANDSHAKE
+                || data[1] != 3
+            {
+                self.done = true;
+                break;
+            }
+            self.record_remain = read_u16_be(&data[3..]) as usize;
+            data = &data[TlsHeader::HEADER_LEN..];
+        }
+        if !self.done {
+            self.read_messages(budget);
+        }
+        if self.done {
+            self.buffer = vec![];
+        }
+    }
+
+    fn read_messages(&mut self, budget: usize) {
+        let mut offset = 0;
+        while let Some(header) = self.buffer.get(offset..offset + Self::HANDSHAKE_HEADER_LEN) {
+            let message_end = offset + Self::HANDSHAKE_HEADER_LEN + read_u24_be(&header[1..]);
+            if header[0] != Self::HANDSHAKE_TYPE_CERTIFICATE {
+                if message_end > self.buffer.len() {
+                    if message_end - offset > budget {
+                        self.done = true;
+                    }
+                    break;
+                }
+                offset = message_end;
+                continue;
+            }
+
+            // only the leaf certificate is needed
+            let leaf_start =
+                offset + Self::HANDSHAKE_HEADER_LEN + Self::CERTIFICATE_LIST_HEADER_LEN;
+            let Some(lengths) = self
+                .buffer
+                .get(offset + Self::HANDSHAKE_HEADER_LEN..leaf_start)
+            else {
+                break;
+            };
+            let leaf_end = leaf_start + read_u24_be(&lengths[3..]);
+            if let Some(leaf) = self.buffer.get(leaf_start..leaf_end) {
+                self.certificate = ServerCertificate::parse(leaf);
+                self.done = true;
+            } else if leaf_end - offset > budget {
+                self.done = true;
+            }
+            break;
+        }
+        self.buffer.drain(..offset);
+    }
+}
+
+// A minimal DER reader for the X.509 fields in request logs
+// ref: https://www.rfc-editor.org/rfc/rfc5280#section-4.1
+#[derive(Debug, Default, PartialEq)]
+struct ServerCertificate {
+    not_before: Timestamp,
+    not_after: Timestamp,
+    subject_cn: String,
+    issuer_cn: String,
+    san_count: u32,
+}
+
+impl ServerCertificate {
+    const TAG_BOOLEAN: u8 = 0x01;
+    const TAG_UTC_TIME: u8 = 0x17;
+    const TAG_GENERALIZED_TIME: u8 = 0x18;
+    const TAG_VERSION: u8 = 0xa0;
+    const TAG_EXTENSIONS: u8 = 0xa3;
+
+    // 2.5.4.3
+    const OID_COMMON_NAME: [u8; 3] = [0x55, 0x04, 0x03];
+    // 2.5.29.17
+    const OID_SUBJECT_ALT_NAME: [u8; 3] = [0x55, 0x1d, 0x11];
+
+    // returns tag, value and the remaining bytes
+    fn next_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+        let tag = *data.first()?;
+        let first = *data.get(1)? as usize;
+        let (len, offset) = if first & 0x80 == 0 {
+            (first, 2)
+        } else {
+            let n = first & 0x7f;
+            if n == 0 || n > 4 {
+                return None;
+            }
+            let len = data
+                .get(2..2 + n)?
+                .iter()
+                .fold(0, |len, b| len << 8 | *b as usize);
+            (len, 2 + n)
+        };
+        let value = data.get(offset..offset.checked_add(len)?)?;
+        Some((tag, value, &data[offset + len..]))
+    }
+
+    fn common_name(mut name: &[u8]) -> Option<String> {
+        while !name.is_empty() {
+            let (_, mut rdn, rest) = Self::next_tlv(name)?;
+            name = rest;
+            while !rdn.is_empty() {
+                let (_, attribute, rest) = Self::next_tlv(rdn)?;
+                rdn = rest;
+                let (_, oid, value) = Self::next_tlv(attribute)?;
+                if oid == Self::OID_COMMON_NAME {
+                    let (_, value, _) = Self::next_tlv(value)?;
+                    return Some(String::from_utf8_lossy(value).into_owned());
+                }
+            }
+        }
+        None
+    }
+
+    fn time(tag: u8, value: &[u8]) -> Option<Timestamp> {
+        let value = std::str::from_utf8(value).ok()?;
+        let (year, value) = match tag {
+            Self::TAG_UTC_TIME => {
+                let year: i32 = value.get(..2)?.parse().ok()?;
+                (if year >= 50 { 1900 } else { 2000 } + year, value.get(2..)?)
+            }
+            Self::TAG_GENERALIZED_TIME => (value.get(..4)?.parse().ok()?, value.get(4..)?),
+            _ => return None,
+        };
+        let field = |i: usize| value.get(i..i + 2)?.parse::<u32>().ok();
+        let time = NaiveDate::from_ymd_opt(year, field(0)?, field(2)?)?.and_hms_opt(
+            field(4)?,
+            field(6)?,
+            field(8)?,
+        )?;
+        Some(Timestamp::from_secs(
+            u64::try_from(time.and_utc().timestamp()).ok()?,
+        ))
+    }
+
+    fn parse(der: &[u8]) -> Option<Self> {
+        let (_, certificate, _) = Self::next_tlv(der)?;
+        let (_, tbs_certificate, _) = Self::next_tlv(certificate)?;
+        let (tag, _, mut data) = Self::next_tlv(tbs_certificate)?;
+        if tag == Self::TAG_VERSION {
+            data = Self::next_tlv(data)?.2; // serialNumber
+        }
+        let (_, _, data) = Self::next_tlv(data)?; // signature
+        let (_, issuer, data) = Self::next_tlv(data)?;
+        let (_, validity, data) = Self::next_tlv(data)?;
+        let (_, subject, mut data) = Self::next_tlv(data)?;
+
+        let (tag, value, validity) = Self::next_tlv(validity)?;
+        let not_before = Self::time(tag, value)?;
+        let (tag, value, _) = Self::next_tlv(validity)?;
+        let not_after = Self::time(tag, value)?;
+
+        let mut certificate = ServerCertificate {
+            not_before,
+            not_after,
+            subject_cn: Self::common_name(subject).unwrap_or_default(),
+            issuer_cn: Self::common_name(issuer).unwrap_or_default(),
+            san_count: 0,
+        };
+
+        // subjectPublicKeyInfo and optional unique identifiers are skipped
+        while !data.is_empty() {
+            let (tag, value, rest) = Self::next_tlv(data)?;
+            data = rest;
+            if tag != Self::TAG_EXTENSIONS {
+                continue;
+            }
+            let (_, mut extensions, _) = Self::next_tlv(value)?;
+            while !extensions.is_empty() {
+                let (_, extension, rest) = Self::next_tlv(extensions)?;
+                extensions = rest;
+                let (_, oid, extension) = Self::next_tlv(extension)?;
+                if oid != Self::OID_SUBJECT_ALT_NAME {
+                    continue;
+                }
+                let (tag, value, rest) = Self::next_tlv(extension)?;
+                // skips the optional critical flag
+                let extn_value = if tag == Self::TAG_BOOLEAN {
+                    Self::next_tlv(rest)?.1
+                } else {
+                    value
+                };
+                let (_, mut names, _) = Self::next_tlv(extn_value)?;
+                while !names.is_empty() {
+                    names = Self::next_tlv(names)?.2;
+                    certificate.san_count += 1;
+                }
+            }
+        }
+        Some(certificate)
+    }
+}
+
+// ref: https://github.com/salesforce/ja3
+//      https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md
+#[derive(Debug, Default)]
+struct ClientHello<'a> {
+    legacy_version: u16,
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    groups: Vec<u16>,
+    point_formats: &'a [u8],
+    signature_algorithms: Vec<u16>,
+    supported_versions: Vec<u16>,
+    alpn: Option<&'a [u8]>,
+}
+
+impl<'a> ClientHello<'a> {
+    const CONTENT_TYPE_HANDSHAKE: u8 = 22;
+    const HANDSHAKE_TYPE_CLIENT_HELLO: u8 = 1;
+    const RANDOM_LEN: usize = 32;
+
+    const EXT_SERVER_NAME: u16 = 0x0000;
+    const EXT_SUPPORTED_GROUPS: u16 = 0x000a;
+    const EXT_EC_POINT_FORMATS: u16 = 0x000b;
+    const EXT_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+    const EXT_ALPN: u16 = 0x0010;
+    const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+    // RFC 8701: 0x0a0a, 0x1a1a, ..., 0xfafa
+    fn is_grease(v: u16) -> bool {
+        v & 0x0f0f == 0x0a0a && v >> 8 == v & 0xff
+    }
+
+    fn u16_list(data: &[u8]) -> Vec<u16> {
+        data.chunks_exact(2)
+            .map(read_u16_be)
+            .filter(|v| !Self::is_grease(*v))
+            .collect()
+    }
+
+    // takes a vector prefixed with a `len_size` bytes length
+    fn take_vec(data: &'a [u8], len_size: usize) -> Option<(&'a [u8], &'a [u8])> {
+        let len = match len_size {
+            1 => *data.first()? as usize,
+            _ => read_u16_be(data.get(..2)?) as usize,
+        };
+        let end = len_size + len;
+        Some((data.get(len_size..end)?, &data[end..]))
+    }
+
+    // ClientHello fragmented across multiple records is not supported
+    fn parse(payload: &'a [u8]) -> Option<Self> {
+        if payload.len() < TlsHeader::HEADER_LEN + 4
+            || payload[0] != Self::CONTENT_TYPE_HANDSHAKE
+            || payload[TlsHeader::HEADER_LEN] != Self::HANDSHAKE_TYPE_CLIENT_HELLO
+        {
+            return None;
+        }
+        let record_len = read_u16_be(&payload[3..]) as usize;
+        let record = payload.get(TlsHeader::HEADER_LEN..TlsHeader::HEADER_LEN + record_len)?;
+        let header = record.get(..4)?;
+        let hello_len = (header[1] as usize) << 16 | (header[2] as usize) << 8 | header[3] as usize;
+        let hello = record.get(4..4 + hello_len)?;
+
+        let mut info = ClientHello {
+            legacy_version: read_u16_be(hello.get(..2)?),
+            ..Default::default()
+        };
+        let data = hello.get(2 + Self::RANDOM_LEN..)?;
+        let (_, data) = Self::take_vec(data, 1)?; // session id
+        let (ciphers, data) = Self::take_vec(data, 2)?;
+        info.ciphers = Self::u16_list(ciphers);
+        let (_, data) = Self::take_vec(data, 1)?; // compression methods
+        if data.is_empty() {
+            // no extensions
+            return Some(info);
+        }
+        let (mut extensions, _) = Self::take_vec(data, 2)?;
+        while !extensions.is_empty() {
+            let ext_type = read_u16_be(extensions.get(..2)?);
+            let (ext, rest) = Self::take_vec(&extensions[2..], 2)?;
+            extensions = rest;
+            if Self::is_grease(ext_type) {
+                continue;
+            }
+            info.extensions.push(ext_type);
+            match ext_type {
+                Self::EXT_SUPPORTED_GROUPS => {
+                    info.groups = Self::u16_list(Self::take_vec(ext, 2)?.0);
+                }
+                Self::EXT_EC_POINT_FORMATS => info.point_formats = Self::take_vec(ext, 1)?.0,
+                Self::EXT_SIGNATURE_ALGORITHMS => {
+                    // order matters and GREASE is kept as sent
+                    info.signature_algorithms = Self::take_vec(ext, 2)?
+                        .0
+                        .chunks_exact(2)
+                        .map(read_u16_be)
+                        .collect();
+                }
+                Self::EXT_ALPN => {
+                    let (protocols, _) = Self::take_vec(ext, 2)?;
+                    info.alpn = Self::take_vec(protocols, 1).map(|(first, _)| first);
+                }
+                Self::EXT_SUPPORTED_VERSIONS => {
+                    info.supported_versions = Self::u16_list(Self::take_vec(ext, 1)?.0);
+                }
+                _ => (),
+            }
+        }
+        Some(info)
+    }
+
+    fn join<T: Display>(values: impl Iterator<Item = T>, sep: &str) -> String {
+        let mut s = String::new();
+        for (i, v) in values.enumerate() {
+            if i > 0 {
+                s.push_str(sep);
+            }
+            let _ = write!(&mut s, "{v}");
+        }
+        s
+    }
+
+    fn ja3_string(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            Self::join(self.ciphers.iter(), "-"),
+            Self::join(self.extensions.iter(), "-"),
+            Self::join(self.groups.iter(), "-"),
+            Self::join(self.point_formats.iter(), "-"),
+        )
+    }
+
+    fn ja3(&self) -> String {
+        hex::encode(Md5::digest(self.ja3_string().as_bytes()))
+    }
+
+    fn ja4_hash(s: &str) -> String {
+        if s.is_empty() {
+            return "000000000000".to_string();
+        }
+        let mut h = hex::encode(digest::digest(&digest::SHA256, s.as_bytes()));
+        h.truncate(12);
+        h
+    }
+
+    fn ja4(&self) -> String {
+        let version = self
+            .supported_versions
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or(self.legacy_version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            0x0200 => "s2",
+            _ => "00",
+        };
+        let sni = if self.extensions.contains(&Self::EXT_SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+        let alpn = match self.alpn.and_then(|a| Some((*a.first()?, *a.last()?))) {
+            Some((first, last))
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() =>
+            {
+                format!("{}{}", first as char, last as char)
+            }
+            // first and last characters of the hex representation
+            Some((first, last)) => format!("{:x}{:x}", first >> 4, last & 0xf),
+            None => "00".to_string(),
+        };
+
+        let mut ciphers = self.ciphers.clone();
+        ciphers.sort_unstable();
+        let mut extensions = self
+            .extensions
+            .iter()
+            .filter(|e| **e != Self::EXT_SERVER_NAME && **e != Self::EXT_ALPN)
+            .copied()
+            .collect::<Vec<_>>();
+        extensions.sort_unstable();
+        let mut ext_string = Self::join(extensions.iter().map(|e| format!("{e:04x}")), ",");
+        if !ext_string.is_empty() && !self.signature_algorithms.is_empty() {
+            ext_string.push('_');
+            ext_string.push_str(&Self::join(
+                self.signature_algorithms.iter().map(|s| format!("{s:04x}")),
+                ",",
+            ));
+        }
+
+        format!(
+            "t{version}{sni}{:02}{:02}{alpn}_{}_{}",
+            self.ciphers.len().min(99),
+            self.extensions.len().min(99),
+            Self::ja4_hash(&Self::join(ciphers.iter().map(|c| format!("{c:04x}")), ",")),
+            Self::ja4_hash(&ext_string),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use super::*;
+
+    use crate::config::{config::TagFilterOperator, handler::BlacklistTrie};
+    use crate::utils::test_utils::Capture;
+
+    const FILE_DIR: &str = "resources/test/flow_generator/tls";
+
+    #[test]
+    fn check_fingerprints() {
+        let expected = [
+            (
+                // curl 7.88.1 with OpenSSL 3.0
+                "curl-client-hello.pcap",
+                "771,4866-4867-4865-49196-49200-159-52393-52392-52394-49195-49199-158-49188-49192-107-49187-49191-103-49162-49172-57-49161-49171-51-157-156-61-60-53-47-255,0-11-10-16-22-23-49-13-43-45-51-21,29-23-30-25-24-256-257-258-259-260,0-1-2",
+                "0149f47eabf9a20d0893e2a44e5a6323",
+                "t13d3112h2_e8f1e7e78f70_b26ce05bbdd6",
+            ),
+            (
+                // python ssl with OpenSSL 3.0, without SNI
+                "python-client-hello.pcap",
+                "771,4866-4867-4865-49196-49200-49195-49199-52393-52392-49188-49192-49187-49191-159-158-107-103-255,11-10-35-16-22-23-13-43-45-51-21,29-23-30-25-24-256-257-258-259-260,0-1-2",
+                "b0020a04d796a92096d3928b119a51f6",
+                "t13i1811h2_85036bcba153_d41ae481755e",
+            ),
+        ];
+        for (pcap, ja3_string, ja3, ja4) in expected {
+            let packets = Capture::load_pcap(Path::new(FILE_DIR).join(pcap)).collect::<Vec<_>>();
+            let payload = packets[0].get_l4_payload().unwrap();
+            let hello = ClientHello::parse(payload).unwrap();
+            assert_eq!(hello.ja3_string(), ja3_string, "{pcap}");
+            assert_eq!(hello.ja3(), ja3, "{pcap}");
+            assert_eq!(hello.ja4(), ja4, "{pcap}");
+        }
+    }
+
+    fn client_hello(grease: bool) -> Vec<u8> {
+        fn with_len(data: &[u8], len_size: usize) -> Vec<u8> {
+            let mut v = (data.len() as u32).to_be_bytes()[4 - len_size..].to_vec();
+            v.extend_from_slice(data);
+            v
+        }
+        fn u16s(values: &[u16]) -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_be_bytes()).collect()
+        }
+        fn ext(ext_type: u16, data: &[u8]) -> Vec<u8> {
+            let mut v = ext_type.to_be_bytes().to_vec();
+            v.extend(with_len(data, 2));
+            v
+        }
+        let with_grease = |g: u16, values: &[u16]| {
+            let mut v = if grease { vec![g] } else { vec![] };
+            v.extend_from_slice(values);
+            u16s(&v)
+        };
+
+        let mut exts = vec![];
+        if grease {
+            exts.extend(ext(0x2a2a, &[]));
+        }
+        let mut server_name = vec![0];
+        server_name.extend(with_len(b"a.com", 2));
+        exts.extend(ext(0x0000, &with_len(&server_name, 2)));
+        exts.extend(ext(0x000a, &with_len(&with_grease(0x3a3a, &[0x001d]), 2)));
+        exts.extend(ext(0x000b, &with_len(&[0], 1)));
+        exts.extend(ext(0x000d, &with_len(&u16s(&[0x0403]), 2)));
+        exts.extend(ext(0x0010, &with_len(&with_len(b"h2", 1), 2)));
+        exts.extend(ext(
+            0x002b,
+            &with_len(&with_grease(0x4a4a, &[0x0304, 0x0303]), 1),
+        ));
+        if grease {
+            exts.extend(ext(0xfafa, &[0]));
+        }
+
+        let mut body = 0x0303u16.to_be_bytes().to_vec();
+        body.extend([0; 32]); // random
+        body.extend(with_len(&[], 1)); // session id
+        body.extend(with_len(&with_grease(0x1a1a, &[0x1301, 0xc02b]), 2));
+        body.extend(with_len(&[0], 1)); // compression methods
+        body.extend(with_len(&exts, 2));
+
+        let mut handshake = vec![1];
+        handshake.extend(with_len(&body, 3));
+        let mut record = vec![22, 3, 1];
+        record.extend(with_len(&handshake, 2));
+        record
+    }
+
+    #[test]
+    fn check_fingerprints_skip_grease() {
+        for grease in [false, true] {
+            let payload = client_hello(grease);
+            let hello = ClientHello::parse(&payload).unwrap();
+            assert_eq!(hello.ja3_string(), "771,4865-49195,0-10-11-13-16-43,29,0");
+            assert_eq!(hello.ja3(), "6436593d91c01223ebc88a27a28b54af");
+            assert_eq!(hello.ja4(), "t13d0206h2_777cda164f4b_5e519ef2b8a0");
+        }
+
+        // application data and truncated hellos are ignored
+        assert!(ClientHello::parse(&[23, 3, 3, 0, 4, 1, 0, 0, 0]).is_none());
+        let payload = client_hello(true);
+        assert!(ClientHello::parse(&payload[..payload.len() - 1]).is_none());
+    }
+
+    fn record(content_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut v = vec![content_type, 3, 3];
+        v.extend((data.len() as u16).to_be_bytes());
+        v.extend_from_slice(data);
+        v
+    }
+
+    fn handshake(handshake_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut v = vec![handshake_type];
+        v.extend(&(data.len() as u32).to_be_bytes()[1..]);
+        v.extend_from_slice(data);
+        v
+    }
+
+    fn certificate_message() -> Vec<u8> {
+        let mut list = vec![];
+        for file in ["server-cert.der", "ca-cert.der"] {
+            let der = fs::read(Path::new(FILE_DIR).join(file)).unwrap();
+            list.extend(&(der.len() as u32).to_be_bytes()[1..]);
+            list.extend(der);
+        }
+        let mut body = (list.len() as u32).to_be_bytes()[1..].to_vec();
+        body.extend(list);
+        handshake(11, &body)
+    }
+
+    fn expected_certificate() -> ServerCertificate {
+        ServerCertificate {
+            not_before: Timestamp::from_secs(1792112069), // 2026-10-16 00:54:29
+            not_after: Timestamp::from_secs(1823648069),  // 2027-10-16 00:54:29
+            subject_cn: "test.deepflow.io".to_string(),
+            issuer_cn: "DeepFlow Test CA".to_string(),
+            san_count: 3,
+        }
+    }
+
+    #[test]
+    fn check_server_certificate() {
+        let der = fs::read(Path::new(FILE_DIR).join("server-cert.der")).unwrap();
+        assert_eq!(ServerCertificate::parse(&der), Some(expected_certificate()));
+
+        // self signed without SAN
+        let der = fs::read(Path::new(FILE_DIR).join("ca-cert.der")).unwrap();
+        let ca = ServerCertificate::parse(&der).unwrap();
+        assert_eq!(ca.subject_cn, "DeepFlow Test CA");
+        assert_eq!(ca.issuer_cn, "DeepFlow Test CA");
+        assert_eq!(ca.san_count, 0);
+
+        assert!(ServerCertificate::parse(&der[..der.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn check_certificate_across_segments() {
+        let server_hello = record(22, &handshake(2, &[0; 70]));
+        let certificate = certificate_message();
+        let (first, second) = certificate.split_at(certificate.len() / 2);
+        let first = record(22, first);
+        let mut second = second.to_vec();
+        second.extend(handshake(14, &[])); // ServerHelloDone
+        let second = record(22, &second);
+
+        // segments are split in the middle of records
+        let mut segments = vec![server_hello];
+        segments[0].extend_from_slice(&first[..100]);
+        segments.push(first[100..].to_vec());
+        segments
+            .last_mut()
+            .unwrap()
+            .extend_from_slice(&second[..20]);
+        segments.push(second[20..].to_vec());
+
+        let mut reader = CertificateReader::default();
+        for segment in &segments[..2] {
+            reader.read(segment, 1024);
+            assert!(!reader.done);
+            assert!(reader.certificate.is_none());
+        }
+        reader.read(&segments[2], 1024);
+        assert!(reader.done);
+        assert!(reader.buffer.is_empty());
+        assert_eq!(reader.certificate, Some(expected_certificate()));
+
+        // leaf certificate exceeding the budget is given up
+        let mut reader = CertificateReader::default();
+        reader.read(&segments[0], 256);
+        assert!(reader.done);
+        reader.read(&segments[1], 256);
+        assert!(reader.certificate.is_none());
+    }
+
+    #[test]
+    fn check_certificate_tls13() {
+        // ServerHello, ChangeCipherSpec for middlebox compatibility and encrypted extensions
+        let mut payload = record(22, &handshake(2, &[0; 118]));
+        payload.extend(record(20, &[1]));
+        payload.extend(record(23, &[0; 64]));
+        let mut reader = CertificateReader::default();
+        reader.read(&payload, 1024);
+        assert!(reader.done);
+        assert!(reader.buffer.is_empty());
+        assert!(reader.certificate.is_none());
+    }
+
+    #[test]
+    fn check_certificate_blacklist() {
+        let cert = expected_certificate();
+        let mut info = TlsInfo {
+            server_cert_not_before: cert.not_before,
+            server_cert_not_after: cert.not_after,
+            server_cert_subject_cn: cert.subject_cn,
+            server_cert_issuer_cn: cert.issuer_cn,
+            server_cert_san_count: cert.san_count,
+            ..Default::default()
+        };
+        let config_with = |field_name: &str, value: &str| LogParserConfig {
+            l7_log_blacklist_trie: HashMap::from([(
+                L7Protocol::TLS,
+                BlacklistTrie::new(vec![TagFilterOperator {
+                    field_name: field_name.to_string(),
+                    operator: "equal".to_string(),
+                    value: value.to_string(),
+                }])
+                .unwrap(),
+            )]),
+            ..Default::default()
+        };
+
+        info.set_is_on_blacklist(&config_with("server_cert_issuer_cn", "DeepFlow Test CA"));
+        assert!(info.is_on_blacklist);
+        info.set_is_on_blacklist(&config_with("server_cert_subject_cn", "deepflow.io"));
+        assert!(!info.is_on_blacklist);
+        info.set_is_on_blacklist(&config_with("server_cert_expiring", "false"));
+        assert!(info.is_on_blacklist);
+        info.server_cert_expiring = true;
+        info.set_is_on_blacklist(&config_with("server_cert_expiring", "false"));
+        assert!(!info.is_on_blacklist);
+    }
+}
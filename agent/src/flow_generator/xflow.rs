@@ -0,0 +1,751 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Receives xFlow datagrams exported by physical switches, sFlow packet samples are
+// injected into a flow map just like captured packets, scaled by their sampling rate,
+// while NetFlow and IPFIX records are already flows and sent to the collector directly
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, SyncSender},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use arc_swap::access::Access;
+use log::{debug, info, warn};
+use tokio::{net::UdpSocket, runtime::Runtime, task::JoinHandle as TaskHandle, time};
+
+use super::{
+    flow_map::Config, AppProto, FlowMap, COUNTER_FLOW_ID_MASK, FLOW_METRICS_PEER_SRC,
+    STATISTICAL_INTERVAL, THREAD_FLOW_ID_MASK, TIMER_FLOW_ID_MASK,
+};
+use crate::{
+    common::{
+        enums::{CaptureNetworkType, EthernetType, IpProtocol, TcpFlags},
+        flow::{CloseType, Flow, FlowKey, L7Stats, SignalSource},
+        meta_packet::MetaPacket,
+        netflow::{NetflowCounter, NetflowDecoder, NetflowRecord},
+        sflow::{parse_sflow_v5, HEADER_PROTOCOL_ETHERNET},
+        tagged_flow::TaggedFlow,
+        tap_port::TapPort,
+        Timestamp,
+    },
+    config::handler::{CollectorAccess, FlowAccess, LogParserAccess},
+    policy::PolicyGetter,
+    rpc::get_timestamp,
+    utils::stats::{self, Countable},
+};
+use public::{
+    buffer::{Allocator, BatchedBox},
+    counter::{Counter, CounterType, CounterValue, RefCountable},
+    queue::DebugSender,
+};
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+const DATAGRAM_QUEUE_SIZE: usize = 4096;
+const RCV_TIMEOUT: Duration = Duration::from_secs(1);
+const TAGGED_FLOW_BATCH_SIZE: usize = 64;
+
+#[derive(Default)]
+pub struct XflowCounter {
+    pub rx_datagrams: AtomicU64,
+    pub drop_datagrams: AtomicU64,
+    pub invalid_datagrams: AtomicU64,
+    pub sflow_samples: AtomicU64,
+    pub invalid_samples: AtomicU64,
+}
+
+impl RefCountable for XflowCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx_datagrams",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx_datagrams.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "drop_datagrams",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.drop_datagrams.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "invalid_datagrams",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.invalid_datagrams.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "sflow_samples",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.sflow_samples.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "invalid_samples",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.invalid_samples.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XflowSource {
+    Sflow,
+    Netflow,
+}
+
+pub struct Datagram {
+    pub source: XflowSource,
+    pub exporter: IpAddr,
+    pub data: Vec<u8>,
+    pub timestamp: Duration,
+}
+
+// Switches are distinguished by the lower 32 bits of their addresses
+fn exporter_id(ip: IpAddr) -> u32 {
+    match ip {
+        IpAddr::V4(ip) => u32::from(ip),
+        IpAddr::V6(ip) => u128::from(ip) as u32,
+    }
+}
+
+pub struct XflowHandler {
+    id: u32,
+    flow_map: FlowMap,
+    netflow_decoder: NetflowDecoder,
+    flow_output: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
+    tagged_flow_allocator: Allocator<TaggedFlow>,
+    total_flow: u64,
+    tap_type: CaptureNetworkType,
+    counter: Arc<XflowCounter>,
+}
+
+impl XflowHandler {
+    pub fn new(
+        id: u32,
+        flow_map: FlowMap,
+        flow_output: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
+        tap_type: CaptureNetworkType,
+        counter: Arc<XflowCounter>,
+        netflow_counter: Arc<NetflowCounter>,
+    ) -> Self {
+        Self {
+            id,
+            flow_map,
+            netflow_decoder: NetflowDecoder::new(netflow_counter),
+            flow_output,
+            tagged_flow_allocator: Allocator::new(TAGGED_FLOW_BATCH_SIZE),
+            total_flow: 0,
+            tap_type,
+            counter,
+        }
+    }
+
+    pub fn handle(&mut self, config: &Config, datagram: &Datagram) {
+        match datagram.source {
+            XflowSource::Sflow => self.handle_sflow(config, datagram),
+            XflowSource::Netflow => self.handle_netflow(config, datagram),
+        }
+    }
+
+    fn handle_sflow(&mut self, config: &Config, datagram: &Datagram) {
+        let samples = match parse_sflow_v5(&datagram.data) {
+            Ok(samples) => samples,
+            Err(e) => {
+                self.counter
+                    .invalid_datagrams
+                    .fetch_add(1, Ordering::Relaxed);
+                debug!("invalid sflow datagram from {}: {}", datagram.exporter, e);
+                return;
+            }
+        };
+        for sample in samples {
+            if sample.header_protocol != HEADER_PROTOCOL_ETHERNET {
+                self.counter.invalid_samples.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let mut meta_packet = MetaPacket::empty();
+            if meta_packet
+                .update(
+                    sample.header,
+                    false,
+                    false,
+                    datagram.timestamp,
+                    sample.frame_length as usize,
+                )
+                .is_err()
+            {
+                self.counter.invalid_samples.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            self.counter.sflow_samples.fetch_add(1, Ordering::Relaxed);
+            meta_packet.sampling_rate = sample.weight();
+            meta_packet.signal_source = SignalSource::XFlow;
+            meta_packet.tap_port = TapPort::from_sflow(exporter_id(sample.agent));
+            meta_packet.lookup_key.tap_type = self.tap_type;
+            self.flow_map.inject_meta_packet(config, &mut meta_packet);
+        }
+    }
+
+    fn handle_netflow(&mut self, config: &Config, datagram: &Datagram) {
+        let decoded =
+            self.netflow_decoder
+                .decode(datagram.exporter, &datagram.data, datagram.timestamp);
+        let records = match decoded {
+            Ok(records) => records,
+            Err(e) => {
+                self.counter
+                    .invalid_datagrams
+                    .fetch_add(1, Ordering::Relaxed);
+                debug!("invalid netflow datagram from {}: {}", datagram.exporter, e);
+                return;
+            }
+        };
+        for record in records {
+            let tagged_flow = TaggedFlow {
+                flow: self.netflow_flow(config, &record, datagram.timestamp),
+                ..Default::default()
+            };
+            let _ = self.flow_output.send(Arc::new(
+                self.tagged_flow_allocator.allocate_one_with(tagged_flow),
+            ));
+        }
+    }
+
+    // A NetFlow record is a finished flow counted by the switch, its counters are
+    // accounted to the second it is received in, as records may be exported minutes
+    // after the flow started
+    fn netflow_flow(
+        &mut self,
+        config: &Config,
+        record: &NetflowRecord,
+        received: Duration,
+    ) -> Flow {
+        let received = Timestamp::from(received);
+        self.total_flow += 1;
+        let mut flow = Flow {
+            flow_key: FlowKey {
+                agent_id: config.flow.agent_id,
+                tap_type: self.tap_type,
+                tap_port: TapPort::from_netflow(exporter_id(record.exporter)),
+                ip_src: record.src_ip,
+                ip_dst: record.dst_ip,
+                port_src: record.src_port,
+                port_dst: record.dst_port,
+                proto: IpProtocol::from(record.protocol),
+                ..Default::default()
+            },
+            flow_id: (received.as_nanos() >> 30 & TIMER_FLOW_ID_MASK) << 32
+                | (self.id as u64 & THREAD_FLOW_ID_MASK) << 24
+                | self.total_flow & COUNTER_FLOW_ID_MASK,
+            start_time: record.start.into(),
+            end_time: record.end.into(),
+            duration: record.end.saturating_sub(record.start).into(),
+            flow_stat_time: received.round_to(STATISTICAL_INTERVAL.into()),
+            eth_type: if record.src_ip.is_ipv6() {
+                EthernetType::IPV6
+            } else {
+                EthernetType::IPV4
+            },
+            close_type: CloseType::Timeout,
+            signal_source: SignalSource::XFlow,
+            is_new_flow: true,
+            ..Default::default()
+        };
+        let tcp_flags = TcpFlags::from_bits_truncate(record.tcp_flags);
+        let peer = &mut flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
+        peer.byte_count = record.byte_count;
+        peer.total_byte_count = record.byte_count;
+        peer.packet_count = record.packet_count;
+        peer.total_packet_count = record.packet_count;
+        peer.tcp_flags = tcp_flags;
+        peer.total_tcp_flags = tcp_flags;
+        peer.first = record.start.into();
+        peer.last = record.end.into();
+        flow.set_tap_side(config.flow.agent_type, config.flow.cloud_gateway_traffic);
+        flow
+    }
+
+    // `timestamp` is Duration::ZERO to flush by the current time
+    pub fn flush(&mut self, config: &Config, timestamp: Duration) {
+        self.flow_map.inject_flush_ticker(config, timestamp);
+    }
+}
+
+// Listens on the sFlow and NetFlow ports in the agent runtime, datagrams of all ports are
+// handled by a single thread so that they share one flow map and template cache
+pub struct XflowReceiver {
+    id: usize,
+    sflow_ports: Vec<u16>,
+    netflow_ports: Vec<u16>,
+    tap_type: CaptureNetworkType,
+
+    flow_output: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
+    l7_stats_output: DebugSender<BatchedBox<L7Stats>>,
+    log_output: DebugSender<AppProto>,
+    policy_getter: PolicyGetter,
+
+    flow_config: FlowAccess,
+    log_parser_config: LogParserAccess,
+    collector_config: CollectorAccess,
+
+    ntp_diff: Arc<AtomicI64>,
+    runtime: Arc<Runtime>,
+    stats_collector: Arc<stats::Collector>,
+    counter: Arc<XflowCounter>,
+    netflow_counter: Arc<NetflowCounter>,
+
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    tasks: Mutex<Vec<TaskHandle<()>>>,
+}
+
+impl XflowReceiver {
+    pub fn new(
+        id: usize,
+        sflow_ports: Vec<u16>,
+        netflow_ports: Vec<u16>,
+        tap_type: CaptureNetworkType,
+        flow_output: DebugSender<Arc<BatchedBox<TaggedFlow>>>,
+        l7_stats_output: DebugSender<BatchedBox<L7Stats>>,
+        log_output: DebugSender<AppProto>,
+        policy_getter: PolicyGetter,
+        flow_config: FlowAccess,
+        log_parser_config: LogParserAccess,
+        collector_config: CollectorAccess,
+        ntp_diff: Arc<AtomicI64>,
+        runtime: Arc<Runtime>,
+        stats_collector: Arc<stats::Collector>,
+    ) -> Self {
+        let counter = Arc::new(XflowCounter::default());
+        stats_collector.register_countable(
+            &stats::NoTagModule("xflow-receiver"),
+            Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+        );
+        let netflow_counter = Arc::new(NetflowCounter::default());
+        stats_collector.register_countable(
+            &stats::NoTagModule("netflow-decoder"),
+            Countable::Ref(Arc::downgrade(&netflow_counter) as Weak<dyn RefCountable>),
+        );
+        Self {
+            id,
+            sflow_ports,
+            netflow_ports,
+            tap_type,
+            flow_output,
+            l7_stats_output,
+            log_output,
+            policy_getter,
+            flow_config,
+            log_parser_config,
+            collector_config,
+            ntp_diff,
+            runtime,
+            stats_collector,
+            counter,
+            netflow_counter,
+            running: Arc::new(AtomicBool::new(false)),
+            thread: Mutex::new(None),
+            tasks: Mutex::new(vec![]),
+        }
+    }
+
+    async fn receive(
+        source: XflowSource,
+        port: u16,
+        sender: SyncSender<Datagram>,
+        ntp_diff: Arc<AtomicI64>,
+        counter: Arc<XflowCounter>,
+    ) {
+        let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                warn!(
+                    "xflow receiver bind {:?} port {} failed: {}",
+                    source, port, e
+                );
+                return;
+            }
+        };
+        info!("xflow receiver listening {:?} on port {}", source, port);
+        let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (n, addr) = match socket.recv_from(&mut buffer).await {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!("xflow receiver port {} recv failed: {}", port, e);
+                    time::sleep(RCV_TIMEOUT).await;
+                    continue;
+                }
+            };
+            counter.rx_datagrams.fetch_add(1, Ordering::Relaxed);
+            let datagram = Datagram {
+                source,
+                exporter: addr.ip(),
+                data: buffer[..n].to_vec(),
+                timestamp: get_timestamp(ntp_diff.load(Ordering::Relaxed)),
+            };
+            if sender.try_send(datagram).is_err() {
+                counter.drop_datagrams.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn start(&self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(DATAGRAM_QUEUE_SIZE);
+        let mut tasks = self.tasks.lock().unwrap();
+        let ports = self
+            .sflow_ports
+            .iter()
+            .map(|p| (XflowSource::Sflow, *p))
+            .chain(
+                self.netflow_ports
+                    .iter()
+                    .map(|p| (XflowSource::Netflow, *p)),
+            );
+        for (source, port) in ports {
+            tasks.push(self.runtime.spawn(Self::receive(
+                source,
+                port,
+                sender.clone(),
+                self.ntp_diff.clone(),
+                self.counter.clone(),
+            )));
+        }
+        drop(sender);
+
+        let id = self.id as u32;
+        let tap_type = self.tap_type;
+        let flow_output = self.flow_output.clone();
+        let l7_stats_output = self.l7_stats_output.clone();
+        let log_output = self.log_output.clone();
+        let policy_getter = self.policy_getter;
+        let ntp_diff = self.ntp_diff.clone();
+        let stats_collector = self.stats_collector.clone();
+        let counter = self.counter.clone();
+        let netflow_counter = self.netflow_counter.clone();
+        let running = self.running.clone();
+        let flow_config = self.flow_config.clone();
+        let log_parser_config = self.log_parser_config.clone();
+        let collector_config = self.collector_config.clone();
+        let thread = thread::Builder::new()
+            .name("xflow-receiver".to_owned())
+            .spawn(move || {
+                let flow_map = FlowMap::new(
+                    id,
+                    Some(flow_output.clone()),
+                    l7_stats_output,
+                    policy_getter,
+                    log_output,
+                    ntp_diff,
+                    &flow_config.load(),
+                    None, // Enterprise Edition Feature: packet-sequence
+                    stats_collector,
+                    false,
+                );
+                let mut handler = XflowHandler::new(
+                    id,
+                    flow_map,
+                    flow_output,
+                    tap_type,
+                    counter,
+                    netflow_counter,
+                );
+                while running.load(Ordering::Relaxed) {
+                    let result = receiver.recv_timeout(RCV_TIMEOUT);
+                    let flow_config = flow_config.load();
+                    let log_parser_config = log_parser_config.load();
+                    let collector_config = collector_config.load();
+                    let config = Config {
+                        flow: &flow_config,
+                        log_parser: &log_parser_config,
+                        collector: &collector_config,
+                        #[cfg(any(target_os = "linux", target_os = "android"))]
+                        ebpf: None,
+                    };
+                    match result {
+                        Ok(datagram) => handler.handle(&config, &datagram),
+                        Err(RecvTimeoutError::Timeout) => handler.flush(&config, Duration::ZERO),
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+            })
+            .unwrap();
+        self.thread.lock().unwrap().replace(thread);
+        info!("xflow receiver (id={}) started", self.id);
+    }
+
+    pub fn stop(&self) {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        // aborting the tasks drops the senders, which also wakes up the thread
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+        info!("xflow receiver (id={}) stopped", self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    use public::{
+        debug::QueueDebugger,
+        proto::agent::AgentType,
+        queue::{self, Receiver},
+    };
+
+    use crate::flow_generator::{flow_map::_new_flow_map_and_receiver, TIME_UNIT};
+
+    const FRAME_LENGTH: u32 = 1000;
+    const EXPORTER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    fn new_handler(
+        flow_map: FlowMap,
+    ) -> (
+        XflowHandler,
+        Receiver<Arc<BatchedBox<TaggedFlow>>>,
+        Arc<XflowCounter>,
+    ) {
+        let (flow_output, flow_receiver, _) =
+            queue::bounded_with_debug(256, "", &QueueDebugger::new());
+        let counter = Arc::new(XflowCounter::default());
+        let handler = XflowHandler::new(
+            0,
+            flow_map,
+            flow_output,
+            CaptureNetworkType::Cloud,
+            counter.clone(),
+            Arc::new(NetflowCounter::default()),
+        );
+        (handler, flow_receiver, counter)
+    }
+
+    // an ethernet, IPv4 and UDP header of a 10.1.1.1:40000 -> 10.1.1.2:53 packet,
+    // the payload is not sampled
+    fn udp_header() -> Vec<u8> {
+        let mut bs = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        bs.extend_from_slice(&[0x45, 0]);
+        bs.extend_from_slice(&(FRAME_LENGTH as u16 - 14).to_be_bytes());
+        bs.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0, 10, 1, 1, 1, 10, 1, 1, 2]);
+        for v in [40000u16, 53, FRAME_LENGTH as u16 - 34, 0] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs
+    }
+
+    fn flow_sample(sampling_rate: u32, header: &[u8]) -> Vec<u8> {
+        let mut record = vec![];
+        for v in [
+            HEADER_PROTOCOL_ETHERNET,
+            FRAME_LENGTH,
+            4,
+            header.len() as u32,
+        ] {
+            record.extend_from_slice(&v.to_be_bytes());
+        }
+        record.extend_from_slice(header);
+        record.resize((record.len() + 3) / 4 * 4, 0);
+
+        let mut sample = vec![];
+        for v in [1, 0, sampling_rate, 0, 0, 1, 0, 1, 1, record.len() as u32] {
+            sample.extend_from_slice(&v.to_be_bytes());
+        }
+        sample.extend_from_slice(&record);
+
+        let mut bs = vec![];
+        for v in [1, sample.len() as u32] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&sample);
+        bs
+    }
+
+    fn sflow_datagram(samples: &[Vec<u8>]) -> Vec<u8> {
+        let mut bs = vec![];
+        for v in [5u32, 1] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&[10, 0, 0, 1]);
+        for v in [0, 1, 1000, samples.len() as u32] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        samples.iter().for_each(|s| bs.extend_from_slice(s));
+        bs
+    }
+
+    #[test]
+    fn sflow_sampling_rates() {
+        let (module_config, flow_map, output_queue_receiver) =
+            _new_flow_map_and_receiver(AgentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let (mut handler, _, counter) = new_handler(flow_map);
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let header = udp_header();
+        let datagram = Datagram {
+            source: XflowSource::Sflow,
+            exporter: EXPORTER,
+            data: sflow_datagram(&[flow_sample(100, &header), flow_sample(400, &header)]),
+            timestamp,
+        };
+        handler.handle(&config, &datagram);
+        assert_eq!(counter.sflow_samples.load(Ordering::Relaxed), 2);
+
+        handler.flush(&config, timestamp + Duration::from_secs(120));
+        let tagged_flow = output_queue_receiver.recv(Some(TIME_UNIT)).unwrap();
+        let flow = &tagged_flow.flow;
+        assert_eq!(flow.flow_key.proto, IpProtocol::UDP);
+        assert_eq!(flow.signal_source, SignalSource::XFlow);
+        assert_eq!(
+            flow.flow_key.tap_port,
+            TapPort::from_sflow(exporter_id(EXPORTER))
+        );
+        assert_eq!(flow.sampling_rate, 400);
+        let peer_src = &flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
+        assert_eq!(peer_src.packet_count, 500);
+        assert_eq!(peer_src.byte_count, 500 * FRAME_LENGTH as u64);
+    }
+
+    // a NetFlow v9 datagram with a template and a data set of one
+    // 10.1.1.1:40000 -> 10.1.1.2:80 TCP record
+    fn netflow_v9_datagram(bytes: u32, packets: u32) -> Vec<u8> {
+        let fields: [(u16, u16); 8] = [
+            (8, 4),  // IPV4_SRC_ADDR
+            (12, 4), // IPV4_DST_ADDR
+            (7, 2),  // L4_SRC_PORT
+            (11, 2), // L4_DST_PORT
+            (4, 1),  // PROTOCOL
+            (6, 1),  // TCP_FLAGS
+            (1, 4),  // IN_BYTES
+            (2, 4),  // IN_PKTS
+        ];
+        let mut template = vec![];
+        for v in [0, 4 + 4 + fields.len() as u16 * 4, 256, fields.len() as u16] {
+            template.extend_from_slice(&v.to_be_bytes());
+        }
+        for (id, length) in fields {
+            template.extend_from_slice(&id.to_be_bytes());
+            template.extend_from_slice(&length.to_be_bytes());
+        }
+
+        let mut record = vec![10, 1, 1, 1, 10, 1, 1, 2];
+        for v in [40000u16, 80] {
+            record.extend_from_slice(&v.to_be_bytes());
+        }
+        record.extend_from_slice(&[6, 0x1b]);
+        for v in [bytes, packets] {
+            record.extend_from_slice(&v.to_be_bytes());
+        }
+        let mut data = vec![];
+        for v in [256u16, 4 + record.len() as u16] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        data.extend_from_slice(&record);
+
+        let mut bs = vec![];
+        for v in [9u16, 2] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        for v in [10000u32, 1_700_000_000, 1, 0] {
+            bs.extend_from_slice(&v.to_be_bytes());
+        }
+        bs.extend_from_slice(&template);
+        bs.extend_from_slice(&data);
+        bs
+    }
+
+    #[test]
+    fn netflow_records() {
+        let (module_config, flow_map, _) =
+            _new_flow_map_and_receiver(AgentType::TtProcess, None, false);
+        let config = Config {
+            flow: &module_config.flow,
+            log_parser: &module_config.log_parser,
+            collector: &module_config.collector,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ebpf: None,
+        };
+        let (mut handler, flow_receiver, counter) = new_handler(flow_map);
+
+        let timestamp = Duration::from_millis(1_700_000_010_400);
+        let mut datagram = Datagram {
+            source: XflowSource::Netflow,
+            exporter: EXPORTER,
+            data: netflow_v9_datagram(150000, 100),
+            timestamp,
+        };
+        handler.handle(&config, &datagram);
+        let tagged_flow = flow_receiver.recv(Some(TIME_UNIT)).unwrap();
+        let flow = &tagged_flow.flow;
+        assert_eq!(flow.flow_key.proto, IpProtocol::TCP);
+        assert_eq!(
+            (flow.flow_key.ip_src, flow.flow_key.ip_dst),
+            (
+                IpAddr::from(Ipv4Addr::new(10, 1, 1, 1)),
+                IpAddr::from(Ipv4Addr::new(10, 1, 1, 2))
+            )
+        );
+        assert_eq!(
+            (flow.flow_key.port_src, flow.flow_key.port_dst),
+            (40000, 80)
+        );
+        assert_eq!(
+            flow.flow_key.tap_port,
+            TapPort::from_netflow(exporter_id(EXPORTER))
+        );
+        assert_eq!(flow.signal_source, SignalSource::XFlow);
+        assert_eq!(flow.flow_stat_time, Timestamp::from_secs(1_700_000_010));
+        let peer_src = &flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC];
+        assert_eq!((peer_src.packet_count, peer_src.byte_count), (100, 150000));
+        assert_eq!(peer_src.tcp_flags, TcpFlags::from_bits_truncate(0x1b));
+
+        // records of the next datagram are new flows
+        datagram.data = netflow_v9_datagram(60, 1);
+        handler.handle(&config, &datagram);
+        let next = flow_receiver.recv(Some(TIME_UNIT)).unwrap();
+        assert_ne!(next.flow.flow_id, flow.flow_id);
+        assert_eq!(
+            next.flow.flow_metrics_peers[FLOW_METRICS_PEER_SRC].byte_count,
+            60
+        );
+
+        datagram.data.truncate(10);
+        handler.handle(&config, &datagram);
+        assert_eq!(counter.invalid_datagrams.load(Ordering::Relaxed), 1);
+    }
+}
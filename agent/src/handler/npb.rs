@@ -32,11 +32,12 @@ use pnet::packet::{
 use public::enums::IpProtocol;
 
 use crate::common::{
-    erspan, vxlan, ERSPAN_HEADER_SIZE, ETH_HEADER_SIZE, GRE_HEADER_SIZE, IPV4_HEADER_SIZE,
-    IPV6_HEADER_SIZE, TCP6_PACKET_SIZE, TCP_PACKET_SIZE, UDP_HEADER_SIZE, VLAN_HEADER_SIZE,
-    VXLAN_HEADER_SIZE,
+    erspan, vxlan, ERSPAN_HEADER_SIZE, ETH_HEADER_SIZE, GPE_VXLAN_FLAGS_OFFSET,
+    GPE_VXLAN_HEADER_SIZE, GPE_VXLAN_NEXT_PROTOCOL_ETHERNET, GPE_VXLAN_NEXT_PROTOCOL_OFFSET,
+    GRE_HEADER_SIZE, IPV4_HEADER_SIZE, IPV6_HEADER_SIZE, NPB_GPE_VXLAN_FLAGS, TCP6_PACKET_SIZE,
+    TCP_PACKET_SIZE, UDP_HEADER_SIZE, VLAN_HEADER_SIZE, VXLAN_HEADER_SIZE,
 };
-use crate::config::NpbConfig;
+use crate::config::{NpbConfig, VxlanEncapsulation};
 use crate::sender::npb_sender::{NpbArpTable, NpbPacketSender};
 use crate::utils::stats::{self, QueueStats, StatsOption};
 use npb_handler::{NpbHandler, NpbHandlerCounter, NpbHeader, StatsNpbHandlerCounter, NOT_SUPPORT};
@@ -74,7 +75,6 @@ pub struct NpbBuilder {
     mtu: usize,
 
     enable_qos_bypass: bool,
-    underlay_is_ipv6: bool,
     underlay_has_vlan: bool,
     ignore_overlay_vlan: bool,
     overlay_vlan_mode: VlanMode,
@@ -84,7 +84,8 @@ pub struct NpbBuilder {
     npb_packet_sender: Option<Arc<NpbPacketSender>>,
     arp: Arc<NpbArpTable>,
 
-    pseudo_tunnel_header: [Vec<u8>; NpbTunnelType::Max as usize],
+    // Indexed by the address family of the tunnel ip, 0 for IPv4 and 1 for IPv6
+    pseudo_tunnel_header: [[Vec<u8>; NpbTunnelType::Max as usize]; 2],
 
     thread_handle: Mutex<Option<JoinHandle<()>>>,
 
@@ -93,10 +94,10 @@ pub struct NpbBuilder {
 }
 
 impl NpbBuilder {
-    fn create_pseudo_ether_header(config: &NpbConfig) -> Vec<u8> {
+    fn create_pseudo_ether_header(config: &NpbConfig, is_ipv6: bool) -> Vec<u8> {
         let mut buffer = [0u8; ETH_HEADER_SIZE + VLAN_HEADER_SIZE];
         let mut ethernet_header = MutableEthernetPacket::new(&mut buffer[..]).unwrap();
-        let ether_type = if config.underlay_is_ipv6 {
+        let ether_type = if is_ipv6 {
             EtherTypes::Ipv6
         } else {
             EtherTypes::Ipv4
@@ -114,20 +115,24 @@ impl NpbBuilder {
         }
     }
 
-    fn create_pseudo_ip_header(config: &NpbConfig, protocol: IpNextHeaderProtocol) -> Vec<u8> {
-        if !config.underlay_is_ipv6 {
+    fn create_pseudo_ip_header(
+        config: &NpbConfig,
+        is_ipv6: bool,
+        protocol: IpNextHeaderProtocol,
+    ) -> Vec<u8> {
+        if !is_ipv6 {
             let mut buffer = [0u8; IPV4_HEADER_SIZE];
             let mut ip_header = MutableIpv4Packet::new(&mut buffer).unwrap();
             ip_header.set_header_length(5);
             ip_header.set_next_level_protocol(protocol);
-            ip_header.set_ttl(64);
+            ip_header.set_ttl(config.hop_limit);
             ip_header.set_version(4);
             return buffer.to_vec();
         } else {
             let mut buffer = [0u8; IPV6_HEADER_SIZE];
             let mut ip_header = MutableIpv6Packet::new(&mut buffer).unwrap();
             ip_header.set_next_header(protocol);
-            ip_header.set_hop_limit(64);
+            ip_header.set_hop_limit(config.hop_limit);
             ip_header.set_version(6);
             return buffer.to_vec();
         }
@@ -142,14 +147,24 @@ impl NpbBuilder {
     }
 
     fn create_pseudo_vxlan_header(config: &NpbConfig) -> Vec<u8> {
-        let mut buffer = vec![0u8; VXLAN_HEADER_SIZE];
-        buffer[vxlan::FLAGS_OFFSET] = config.vxlan_flags;
-        return buffer;
+        match config.vxlan_encapsulation {
+            VxlanEncapsulation::Vxlan => {
+                let mut buffer = vec![0u8; VXLAN_HEADER_SIZE];
+                buffer[vxlan::FLAGS_OFFSET] = config.vxlan_flags;
+                return buffer;
+            }
+            VxlanEncapsulation::VxlanGpe => {
+                let mut buffer = vec![0u8; GPE_VXLAN_HEADER_SIZE];
+                buffer[GPE_VXLAN_FLAGS_OFFSET] = NPB_GPE_VXLAN_FLAGS;
+                buffer[GPE_VXLAN_NEXT_PROTOCOL_OFFSET] = GPE_VXLAN_NEXT_PROTOCOL_ETHERNET;
+                return buffer;
+            }
+        }
     }
 
-    fn create_pseudo_vxlan_packet(config: &NpbConfig) -> Vec<u8> {
-        let mut l2 = Self::create_pseudo_ether_header(config);
-        let mut l3 = Self::create_pseudo_ip_header(config, IpNextHeaderProtocols::Udp);
+    fn create_pseudo_vxlan_packet(config: &NpbConfig, is_ipv6: bool) -> Vec<u8> {
+        let mut l2 = Self::create_pseudo_ether_header(config, is_ipv6);
+        let mut l3 = Self::create_pseudo_ip_header(config, is_ipv6, IpNextHeaderProtocols::Udp);
         let mut l4 = Self::create_pseudo_udp_header(config);
         let mut vxlan = Self::create_pseudo_vxlan_header(config);
 
@@ -175,9 +190,9 @@ impl NpbBuilder {
         return buffer.to_vec();
     }
 
-    fn create_pseudo_erspan_packet(config: &NpbConfig) -> Vec<u8> {
-        let mut l2 = Self::create_pseudo_ether_header(config);
-        let mut l3 = Self::create_pseudo_ip_header(config, IpNextHeaderProtocols::Gre);
+    fn create_pseudo_erspan_packet(config: &NpbConfig, is_ipv6: bool) -> Vec<u8> {
+        let mut l2 = Self::create_pseudo_ether_header(config, is_ipv6);
+        let mut l3 = Self::create_pseudo_ip_header(config, is_ipv6, IpNextHeaderProtocols::Gre);
         let mut gre = Self::create_pseudo_gre_packet();
         let mut erspan = Self::create_pseudo_erspan_header();
 
@@ -187,8 +202,8 @@ impl NpbBuilder {
         return l2;
     }
 
-    fn create_pseudo_tcp_packet(config: &NpbConfig) -> Vec<u8> {
-        let mut packet_size = if !config.underlay_is_ipv6 {
+    fn create_pseudo_tcp_packet(config: &NpbConfig, is_ipv6: bool) -> Vec<u8> {
+        let mut packet_size = if !is_ipv6 {
             TCP_PACKET_SIZE + NpbHeader::SIZEOF
         } else {
             TCP6_PACKET_SIZE + NpbHeader::SIZEOF
@@ -200,6 +215,28 @@ impl NpbBuilder {
         vec![u8::from(IpProtocol::TCP); packet_size]
     }
 
+    fn create_pseudo_tunnel_header(
+        config: &NpbConfig,
+        is_ipv6: bool,
+    ) -> [Vec<u8>; NpbTunnelType::Max as usize] {
+        [
+            Self::create_pseudo_vxlan_packet(config, is_ipv6),
+            Self::create_pseudo_erspan_packet(config, is_ipv6),
+            vec![],
+            vec![],
+            Self::create_pseudo_tcp_packet(config, is_ipv6),
+        ]
+    }
+
+    fn create_pseudo_tunnel_headers(
+        config: &NpbConfig,
+    ) -> [[Vec<u8>; NpbTunnelType::Max as usize]; 2] {
+        [
+            Self::create_pseudo_tunnel_header(config, false),
+            Self::create_pseudo_tunnel_header(config, true),
+        ]
+    }
+
     pub fn on_config_change(&mut self, config: &NpbConfig, queue_debugger: &QueueDebugger) {
         if self.npb_packet_sender.is_none() {
             return;
@@ -228,16 +265,9 @@ impl NpbBuilder {
         ));
 
         self.mtu = config.mtu as usize;
-        self.underlay_is_ipv6 = config.underlay_is_ipv6;
         self.underlay_has_vlan = config.output_vlan > 0;
         self.overlay_vlan_mode = config.vlan_mode;
-        self.pseudo_tunnel_header = [
-            Self::create_pseudo_vxlan_packet(config),
-            Self::create_pseudo_erspan_packet(config),
-            vec![],
-            vec![],
-            Self::create_pseudo_tcp_packet(config),
-        ];
+        self.pseudo_tunnel_header = Self::create_pseudo_tunnel_headers(config);
         self.npb_packet_sender = Some(npb_packet_sender);
         self.sender = sender;
 
@@ -269,7 +299,6 @@ impl NpbBuilder {
             id,
             mtu: config.mtu as usize,
             enable_qos_bypass: config.enable_qos_bypass, // TODO
-            underlay_is_ipv6: config.underlay_is_ipv6,
             underlay_has_vlan: config.output_vlan > 0,
             overlay_vlan_mode: config.vlan_mode,
             ignore_overlay_vlan: config.ignore_overlay_vlan,
@@ -281,13 +310,7 @@ impl NpbBuilder {
                 arp.clone(),
                 stats_collector.clone(),
             ))),
-            pseudo_tunnel_header: [
-                Self::create_pseudo_vxlan_packet(config),
-                Self::create_pseudo_erspan_packet(config),
-                vec![],
-                vec![],
-                Self::create_pseudo_tcp_packet(config),
-            ],
+            pseudo_tunnel_header: Self::create_pseudo_tunnel_headers(config),
             thread_handle: Mutex::new(None),
             arp,
             stats_collector,
@@ -378,7 +401,7 @@ impl NpbBuilder {
 #[cfg(test)]
 mod test {
     use super::*;
-    use public::consts::NPB_DEFAULT_PORT;
+    use public::consts::{NPB_DEFAULT_PORT, NPB_GPE_DEFAULT_PORT};
 
     #[test]
     fn test_pseudo_vxlan() {
@@ -387,10 +410,9 @@ mod test {
             vxlan_flags: 0x08,
             output_vlan: 0,
             enable_qos_bypass: false,
-            underlay_is_ipv6: false,
             ..Default::default()
         };
-        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config);
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, false);
         assert_eq!(vxlan_packet.len(), 50);
         assert_eq!(
             vxlan_packet,
@@ -405,10 +427,9 @@ mod test {
             npb_port: NPB_DEFAULT_PORT,
             vxlan_flags: 0xff,
             enable_qos_bypass: false,
-            underlay_is_ipv6: false,
             ..Default::default()
         };
-        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config);
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, false);
         assert_eq!(vxlan_packet.len(), 54);
         assert_eq!(
             vxlan_packet,
@@ -423,11 +444,10 @@ mod test {
             output_vlan: 4097,
             npb_port: NPB_DEFAULT_PORT,
             vxlan_flags: 0xff,
-            underlay_is_ipv6: true,
             enable_qos_bypass: false,
             ..Default::default()
         };
-        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config);
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, true);
         assert_eq!(vxlan_packet.len(), 74);
         assert_eq!(
             vxlan_packet,
@@ -438,4 +458,92 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_pseudo_vxlan_gpe() {
+        let config = NpbConfig {
+            npb_port: NPB_GPE_DEFAULT_PORT,
+            vxlan_flags: 0xff,
+            vxlan_encapsulation: VxlanEncapsulation::VxlanGpe,
+            output_vlan: 0,
+            ..Default::default()
+        };
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, false);
+        assert_eq!(vxlan_packet.len(), 50);
+        assert_eq!(
+            vxlan_packet,
+            vec![
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 69, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 192, 0, 18, 182, 0, 0, 0, 0, 12, 0, 0, 3, 0, 0, 0, 0
+            ]
+        );
+
+        let config = NpbConfig {
+            output_vlan: 4097,
+            ..config
+        };
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, true);
+        assert_eq!(vxlan_packet.len(), 74);
+        assert_eq!(
+            vxlan_packet,
+            vec![
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 129, 0, 0, 1, 134, 221, 96, 0, 0, 0, 0, 0, 17,
+                64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 192, 0, 18, 182, 0, 0, 0, 0, 12, 0, 0, 3, 0, 0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pseudo_hop_limit() {
+        let config = NpbConfig {
+            npb_port: NPB_DEFAULT_PORT,
+            vxlan_flags: 0x08,
+            hop_limit: 255,
+            ..Default::default()
+        };
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, false);
+        assert_eq!(vxlan_packet[22], 255);
+        let vxlan_packet = NpbBuilder::create_pseudo_vxlan_packet(&config, true);
+        assert_eq!(vxlan_packet[21], 255);
+    }
+
+    #[test]
+    fn test_pseudo_erspan() {
+        let config = NpbConfig {
+            output_vlan: 0,
+            ..Default::default()
+        };
+        let erspan_packet = NpbBuilder::create_pseudo_erspan_packet(&config, false);
+        assert_eq!(erspan_packet.len(), 58);
+        assert_eq!(
+            erspan_packet,
+            vec![
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 69, 0, 0, 0, 0, 0, 0, 0, 64, 47, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 48, 0, 34, 235, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 6
+            ]
+        );
+
+        let erspan_packet = NpbBuilder::create_pseudo_erspan_packet(&config, true);
+        assert_eq!(erspan_packet.len(), 78);
+        assert_eq!(
+            erspan_packet,
+            vec![
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 134, 221, 96, 0, 0, 0, 0, 0, 47, 64, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 48, 0, 34, 235, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 6
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pseudo_tunnel_headers() {
+        let config = NpbConfig::default();
+        let headers = NpbBuilder::create_pseudo_tunnel_headers(&config);
+        assert_eq!(headers[0][NpbTunnelType::VxLan as usize].len(), 50);
+        assert_eq!(headers[1][NpbTunnelType::VxLan as usize].len(), 70);
+        assert_eq!(headers[0][NpbTunnelType::GreErspan as usize].len(), 58);
+        assert_eq!(headers[1][NpbTunnelType::GreErspan as usize].len(), 78);
+    }
 }
@@ -18,34 +18,47 @@ use std::collections::HashMap;
 use std::fmt::{self, Debug, Formatter};
 use std::io::{Read, Write};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU16, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 
-use flate2::{read::GzDecoder, write::ZlibEncoder, Compression};
+use arc_swap::ArcSwap;
+use flate2::{
+    read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+    Compression,
+};
+use futures::future;
 use http::header::{CONTENT_ENCODING, CONTENT_TYPE};
 use http::HeaderMap;
 use hyper::{
     body::{aggregate, Buf},
-    server::conn::AddrStream,
+    server::{accept, conn::AddrStream},
     service::{make_service_fn, service_fn},
     Body, Method, Request, Response, Server, StatusCode,
 };
+use ipnet::IpNet;
 use log::{debug, error, info, log_enabled, warn, Level};
 use prost::Message;
 use public::{
     buffer::{Allocator, BatchedBox},
     sender::{SendMessageType, Sendable},
 };
+use regex::Regex;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
 use tokio::{
     runtime::Runtime,
     select,
-    sync::{mpsc, oneshot},
+    sync::{mpsc, oneshot, watch},
     task::JoinHandle,
     time,
 };
-use zstd::bulk::compress;
+use zstd::{bulk::compress, stream::read::Decoder as ZstdDecoder};
 
 use crate::{
     collector::types::MiniFlow,
@@ -54,7 +67,10 @@ use crate::{
         lookup_key::LookupKey,
         TaggedFlow, Timestamp,
     },
-    config::{handler::LogParserConfig, PrometheusExtraLabels},
+    config::{
+        handler::LogParserConfig, PrometheusExtraLabels, PrometheusLabelRewriteAction,
+        SignalSourceFilter, SourceFilter, Statsd,
+    },
     exception::ExceptionHandler,
     flow_generator::protocol_logs::{http::handle_endpoint, L7ResponseStatus},
     metric::document::{Direction, TapSide},
@@ -85,10 +101,18 @@ use public::{
     utils::net::ipv6_enabled,
 };
 
+mod datadog;
+mod otlp_grpc;
+mod statsd;
+
+use otlp_grpc::OtlpReceiver;
+
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
 
 const NOT_FOUND: &[u8] = b"Not Found";
 const GZIP: &str = "gzip";
+const ZSTD: &str = "zstd";
+const DEFLATE: &str = "deflate";
 
 // Otel的protobuf数据
 // ingester使用该proto https://github.com/open-telemetry/opentelemetry-proto/blob/main/opentelemetry/proto/trace/v1/trace.proto进行解析
@@ -188,23 +212,193 @@ impl Sendable for Profile {
     }
 }
 
-fn decode_metric(mut whole_body: impl Buf, headers: &HeaderMap) -> Result<Vec<u8>, GenericError> {
-    let metric = if headers
-        .get(CONTENT_ENCODING)
-        .filter(|&v| v == GZIP)
-        .is_some()
-    {
-        let mut metric = vec![];
-        let mut gz = GzDecoder::new(whole_body.reader());
-        gz.read_to_end(&mut metric)?;
-        metric
-    } else {
-        let mut metric = vec![0u8; whole_body.remaining()];
-        whole_body.copy_to_slice(metric.as_mut_slice());
-        metric
-    };
+#[derive(Debug)]
+struct BodyTooLarge(usize);
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "decompressed request body exceeds {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
 
-    Ok(metric)
+// Decodes request bodies by Content-Encoding, other encodings such as snappy of prometheus
+// remote write are forwarded as is
+struct BodyDecoder {
+    // limit of decompressed bodies, in case of decompression bombs
+    max_size: usize,
+    counter: Arc<ContentEncodingMetric>,
+}
+
+impl BodyDecoder {
+    fn read_limited(&self, decoder: impl Read) -> Result<Vec<u8>, GenericError> {
+        let mut data = vec![];
+        decoder
+            .take(self.max_size as u64 + 1)
+            .read_to_end(&mut data)?;
+        if data.len() > self.max_size {
+            self.counter.too_large.fetch_add(1, Ordering::Relaxed);
+            return Err(BodyTooLarge(self.max_size).into());
+        }
+        Ok(data)
+    }
+
+    fn decode(
+        &self,
+        mut whole_body: impl Buf,
+        headers: &HeaderMap,
+    ) -> Result<Vec<u8>, GenericError> {
+        let encoding = headers
+            .get(CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim())
+            .unwrap_or_default();
+        if encoding.eq_ignore_ascii_case(GZIP) {
+            self.counter.gzip.fetch_add(1, Ordering::Relaxed);
+            self.read_limited(GzDecoder::new(whole_body.reader()))
+        } else if encoding.eq_ignore_ascii_case(ZSTD) {
+            self.counter.zstd.fetch_add(1, Ordering::Relaxed);
+            self.read_limited(ZstdDecoder::new(whole_body.reader())?)
+        } else if encoding.eq_ignore_ascii_case(DEFLATE) {
+            self.counter.deflate.fetch_add(1, Ordering::Relaxed);
+            let data = whole_body.copy_to_bytes(whole_body.remaining());
+            // deflate in HTTP is zlib wrapped, but some clients send raw deflate streams
+            if is_zlib_header(&data) {
+                self.read_limited(ZlibDecoder::new(data.as_ref()))
+            } else {
+                self.read_limited(DeflateDecoder::new(data.as_ref()))
+            }
+        } else {
+            self.counter.identity.fetch_add(1, Ordering::Relaxed);
+            let mut data = vec![0u8; whole_body.remaining()];
+            whole_body.copy_to_slice(data.as_mut_slice());
+            Ok(data)
+        }
+    }
+}
+
+// RFC 1950: CM is 8 (deflate) and CMF * 256 + FLG is a multiple of 31
+fn is_zlib_header(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] & 0x0f == 8 && u16::from_be_bytes([data[0], data[1]]) % 31 == 0
+}
+
+// Bodies exceeding the limit after decompression are rejected instead of resetting the connection
+fn reject_too_large(
+    response: Result<Response<Body>, GenericError>,
+) -> Result<Response<Body>, GenericError> {
+    match response {
+        Err(e) if e.is::<BodyTooLarge>() => Ok(Response::builder()
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .body(e.to_string().into())
+            .unwrap()),
+        response => response,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Signal {
+    Trace,
+    Metric,
+    Log,
+    Profile,
+}
+
+// Signal of a route for the source filter, None for routes not carrying data
+fn route_signal(path: &str) -> Option<Signal> {
+    match path {
+        "/api/v1/otel/trace"
+        | "/v3/segments"
+        | "/skywalking.v3.TraceSegmentReportService/collectInSync"
+        | "/TraceSegmentReportService/collectInSync"
+        | "/skywalking.v3.TraceSegmentReportService/collect"
+        | "/TraceSegmentReportService/collect"
+        | "/api/v0.2/traces"
+        | "/v0.3/traces"
+        | "/v0.4/traces"
+        | "/v0.5/traces"
+        | "/v0.7/traces" => Some(Signal::Trace),
+        "/api/v1/prometheus" | "/api/v1/telegraf" => Some(Signal::Metric),
+        "/api/v1/log" => Some(Signal::Log),
+        "/api/v1/profile/ingest" => Some(Signal::Profile),
+        _ => None,
+    }
+}
+
+// Source networks of a signal, deny entries win over overlapping allow entries
+#[derive(Default)]
+struct SignalSourceAcl {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl From<&SignalSourceFilter> for SignalSourceAcl {
+    fn from(c: &SignalSourceFilter) -> Self {
+        // validated in the config
+        Self {
+            allow: c.allow_networks().unwrap_or_default(),
+            deny: c.deny_networks().unwrap_or_default(),
+        }
+    }
+}
+
+impl SignalSourceAcl {
+    fn permits(&self, ip: &IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+    }
+}
+
+#[derive(Default)]
+struct SourceAcls {
+    trace: SignalSourceAcl,
+    metric: SignalSourceAcl,
+    log: SignalSourceAcl,
+    profile: SignalSourceAcl,
+}
+
+impl From<&SourceFilter> for SourceAcls {
+    fn from(c: &SourceFilter) -> Self {
+        Self {
+            trace: (&c.trace).into(),
+            metric: (&c.metric).into(),
+            log: (&c.log).into(),
+            profile: (&c.profile).into(),
+        }
+    }
+}
+
+// Checks peers of the integration listeners before request bodies are read, the filter is
+// swapped on config changes without restarting the listeners
+#[derive(Default)]
+struct SourceGuard {
+    acls: ArcSwap<SourceAcls>,
+    rejected: Arc<RejectedRequestMetric>,
+}
+
+impl SourceGuard {
+    fn update(&self, c: &SourceFilter) {
+        self.acls.store(Arc::new(c.into()));
+    }
+
+    // Returns false and counts the rejection if the peer is not allowed to send the signal
+    fn permits(&self, signal: Signal, ip: IpAddr) -> bool {
+        // IPv4 peers of dual stack listeners are IPv4-mapped
+        let ip = ip.to_canonical();
+        let acls = self.acls.load();
+        let (acl, rejected) = match signal {
+            Signal::Trace => (&acls.trace, &self.rejected.trace),
+            Signal::Metric => (&acls.metric, &self.rejected.metric),
+            Signal::Log => (&acls.log, &self.rejected.log),
+            Signal::Profile => (&acls.profile, &self.rejected.profile),
+        };
+        if acl.permits(&ip) {
+            true
+        } else {
+            rejected.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
 }
 
 async fn aggregate_with_catch_exception(
@@ -267,8 +461,29 @@ fn decode_otel_trace_data(
     flow_id: Arc<AtomicU64>,
     log_parser_config: Arc<LogParserConfig>,
 ) -> Result<(Vec<u8>, Vec<BatchedBox<L7Stats>>), GenericError> {
+    let d = TracesData::decode(data.as_slice())?;
+    Ok(fill_otel_trace_data(
+        peer_addr,
+        d,
+        local_epc_id,
+        policy_getter,
+        time_diff,
+        flow_id,
+        log_parser_config,
+    ))
+}
+
+// Adds the peer IP to traces without "app.host.ip", and collects L7 stats from spans
+fn fill_otel_trace_data(
+    peer_addr: SocketAddr,
+    mut d: TracesData,
+    local_epc_id: u32,
+    policy_getter: Arc<PolicyGetter>,
+    time_diff: i64,
+    flow_id: Arc<AtomicU64>,
+    log_parser_config: Arc<LogParserConfig>,
+) -> (Vec<u8>, Vec<BatchedBox<L7Stats>>) {
     let mut l7_stats: Vec<BatchedBox<L7Stats>> = vec![];
-    let mut d = TracesData::decode(data.as_slice())?;
     // 因为collector传过来traceData的全部resource都有"app.host.ip"的属性，所以只检查第一个resource有没有“app.host.ip”即可，
     // sdk传过来的traceData因没有该属性则要补上(key: “app.host.ip”, value: 对端IP)属性值
     // =======================================================================
@@ -350,7 +565,42 @@ fn decode_otel_trace_data(
     }
     let sdk_data = d.encode_to_vec();
     debug!("send otel sdk traces_data to sender: {:?}", d);
-    return Ok((sdk_data, l7_stats));
+    (sdk_data, l7_stats)
+}
+
+fn send_otel_trace_data(
+    (data, mut l7_stats): (Vec<u8>, Vec<BatchedBox<L7Stats>>),
+    compressed: bool,
+    counter: &CompressedMetric,
+    otel_sender: &DebugSender<OpenTelemetry>,
+    compressed_otel_sender: &DebugSender<OpenTelemetryCompressed>,
+    otel_l7_stats_sender: &DebugSender<BatchedBox<L7Stats>>,
+) -> std::io::Result<()> {
+    if !l7_stats.is_empty() {
+        if let Err(e) = otel_l7_stats_sender.send_all(&mut l7_stats) {
+            warn!("otel_l7_stats_sender failed to send data, because {:?}", e);
+        }
+    }
+    if compressed {
+        counter
+            .uncompressed
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        let compressed_data = compress_data(data)?;
+        counter
+            .compressed
+            .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
+        if let Err(e) = compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data)) {
+            warn!(
+                "compressed_otel_sender failed to send data, because {:?}",
+                e
+            );
+        }
+    } else {
+        if let Err(e) = otel_sender.send(OpenTelemetry(data)) {
+            warn!("otel_sender failed to send data, because {:?}", e);
+        }
+    }
+    Ok(())
 }
 
 fn fill_l7_stats(
@@ -570,8 +820,8 @@ fn fill_l7_stats(
         l7_protocol,
         signal_source: SignalSource::OTel,
         time_in_second: flow_stat_time.into(),
+        capture_time: flow_stat_time.into(),
         biz_type: 0,
-        time_span: 0,
         is_reversed: false,
     }
 }
@@ -597,12 +847,115 @@ fn http_code_to_response_status(status_code: i64) -> L7ResponseStatus {
     }
 }
 
+// The ingester only decodes zlib for OpenTelemetryCompressed and does not advertise other codecs,
+// so the codec is fixed instead of negotiated, whatever the Content-Encoding of the request was
 fn compress_data(input: Vec<u8>) -> std::io::Result<Vec<u8>> {
     let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
     e.write_all(input.as_slice())?;
     e.finish()
 }
 
+struct LabelRewriteRule {
+    regex: Regex,
+    replacement: String,
+    action: PrometheusLabelRewriteAction,
+}
+
+// PrometheusExtraLabels with the rewrite rules compiled
+struct PrometheusExtraLabelsConfig {
+    enabled: bool,
+    extra_labels: Vec<String>,
+    label_length: usize,
+    value_length: usize,
+    // keyed by lowercase label, rules are validated in the config
+    rewrite_rules: HashMap<String, Vec<LabelRewriteRule>>,
+}
+
+impl From<&PrometheusExtraLabels> for PrometheusExtraLabelsConfig {
+    fn from(c: &PrometheusExtraLabels) -> Self {
+        let mut rewrite_rules: HashMap<String, Vec<LabelRewriteRule>> = HashMap::new();
+        for rule in c.rewrite_rules.iter() {
+            match rule.compile() {
+                Ok(regex) => rewrite_rules
+                    .entry(rule.label.to_ascii_lowercase())
+                    .or_default()
+                    .push(LabelRewriteRule {
+                        regex,
+                        replacement: rule.replacement.clone(),
+                        action: rule.action,
+                    }),
+                Err(e) => warn!("Invalid prometheus extra label rewrite rule {rule:?}: {e}"),
+            }
+        }
+        Self {
+            enabled: c.enabled,
+            extra_labels: c.extra_labels.clone(),
+            label_length: c.label_length,
+            value_length: c.value_length,
+            rewrite_rules,
+        }
+    }
+}
+
+impl PrometheusExtraLabelsConfig {
+    // None if the label is dropped
+    fn rewrite(&self, label: &str, mut value: String) -> Option<String> {
+        let Some(rules) = self.rewrite_rules.get(&label.to_ascii_lowercase()) else {
+            return Some(value);
+        };
+        for rule in rules {
+            let matched = rule.regex.is_match(&value);
+            match rule.action {
+                PrometheusLabelRewriteAction::Keep if !matched => return None,
+                PrometheusLabelRewriteAction::Drop if matched => return None,
+                _ => (),
+            }
+            if matched && !rule.replacement.is_empty() {
+                value = rule
+                    .regex
+                    .replace_all(&value, &rule.replacement)
+                    .into_owned();
+            }
+        }
+        Some(value)
+    }
+
+    // Returns names and values of the extra labels found in headers, rewritten values are
+    // counted in label_length and value_length
+    fn extract(&self, headers: &HeaderMap) -> (Vec<String>, Vec<String>) {
+        let mut labels_count = 0;
+        let mut values_count = 0;
+        let mut extra_label_names = vec![];
+        let mut extra_label_values = vec![];
+
+        if !self.enabled {
+            return (extra_label_names, extra_label_values);
+        }
+        for label in self.extra_labels.iter() {
+            if headers.contains_key(label) {
+                let value = headers
+                    .get(label)
+                    .unwrap()
+                    .to_str()
+                    .unwrap_or_default()
+                    .to_string();
+                let Some(value) = self.rewrite(label, value) else {
+                    continue;
+                };
+                labels_count += label.len();
+                values_count += value.len();
+                if labels_count > self.label_length || values_count > self.value_length {
+                    debug!("labels_count exceeds the labels limit:{} or values_count exceeds the values limit:{} ", self.label_length, self.value_length);
+                    break;
+                }
+                extra_label_names.push(label.to_string());
+                extra_label_values.push(value);
+            }
+        }
+        (extra_label_names, extra_label_values)
+    }
+}
+
 /// 接收metric server发送的请求，根据路由处理分发
 async fn handler(
     peer_addr: SocketAddr,
@@ -620,10 +973,13 @@ async fn handler(
     compressed: bool,
     profile_compressed: bool,
     counter: Arc<CompressedMetric>,
+    otlp_requests: Arc<OtlpRequestMetric>,
+    body_decoder: Arc<BodyDecoder>,
+    source_guard: Arc<SourceGuard>,
     local_epc_id: u32,
     policy_getter: Arc<PolicyGetter>,
     time_diff: Arc<AtomicI64>,
-    prometheus_extra_config: Arc<PrometheusExtraLabels>,
+    prometheus_extra_config: Arc<PrometheusExtraLabelsConfig>,
     log_parser_config: Arc<LogParserConfig>,
     flow_id: Arc<AtomicU64>,
     external_profile_integration_disabled: bool,
@@ -631,6 +987,14 @@ async fn handler(
     external_metric_integration_disabled: bool,
     external_log_integration_disabled: bool,
 ) -> Result<Response<Body>, GenericError> {
+    if let Some(signal) = route_signal(req.uri().path()) {
+        if !source_guard.permits(signal, peer_addr.ip()) {
+            return Ok(Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(Body::empty())
+                .unwrap());
+        }
+    }
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/") => {
             let doc_bytes = include_bytes!("../resources/doc/integration_collector.pdf");
@@ -641,6 +1005,7 @@ async fn handler(
         }
         // OpenTelemetry trace integration
         (&Method::POST, "/api/v1/otel/trace") => {
+            otlp_requests.http_trace.fetch_add(1, Ordering::Relaxed);
             if external_trace_integration_disabled {
                 return Ok(Response::builder().body(Body::empty()).unwrap());
             }
@@ -651,9 +1016,9 @@ async fn handler(
                     return Ok(e);
                 }
             };
-            let tracing_data = decode_metric(whole_body, &part.headers)?;
+            let tracing_data = body_decoder.decode(whole_body, &part.headers)?;
             let time_diff = time_diff.load(Ordering::Relaxed);
-            let mut decode_data = decode_otel_trace_data(
+            let decode_data = decode_otel_trace_data(
                 peer_addr,
                 tracing_data,
                 local_epc_id,
@@ -666,32 +1031,14 @@ async fn handler(
                 debug!("decode otel trace data error: {}", e);
                 e
             })?;
-            if !decode_data.1.is_empty() {
-                if let Err(e) = otel_l7_stats_sender.send_all(&mut decode_data.1) {
-                    warn!("otel_l7_stats_sender failed to send data, because {:?}", e);
-                }
-            }
-            if compressed {
-                counter
-                    .uncompressed
-                    .fetch_add(decode_data.0.len() as u64, Ordering::Relaxed);
-                let compressed_data = compress_data(decode_data.0)?;
-                counter
-                    .compressed
-                    .fetch_add(compressed_data.len() as u64, Ordering::Relaxed);
-                if let Err(e) =
-                    compressed_otel_sender.send(OpenTelemetryCompressed(compressed_data))
-                {
-                    warn!(
-                        "compressed_otel_sender failed to send data, because {:?}",
-                        e
-                    );
-                }
-            } else {
-                if let Err(e) = otel_sender.send(OpenTelemetry(decode_data.0)) {
-                    warn!("otel_sender failed to send data, because {:?}", e);
-                }
-            }
+            send_otel_trace_data(
+                decode_data,
+                compressed,
+                &counter,
+                &otel_sender,
+                &compressed_otel_sender,
+                &otel_l7_stats_sender,
+            )?;
 
             Ok(Response::builder().body(Body::empty()).unwrap())
         }
@@ -700,35 +1047,8 @@ async fn handler(
             if external_metric_integration_disabled {
                 return Ok(Response::builder().body(Body::empty()).unwrap());
             }
-            let headers = req.headers();
-            let labels = &prometheus_extra_config.extra_labels;
-            let labels_limit = prometheus_extra_config.label_length;
-            let values_limit = prometheus_extra_config.value_length;
-            let mut labels_count = 0;
-            let mut values_count = 0;
-            let mut extra_label_names = vec![];
-            let mut extra_label_values = vec![];
-
-            if prometheus_extra_config.enabled {
-                for label in labels {
-                    if headers.contains_key(label) {
-                        let value = headers
-                            .get(label)
-                            .unwrap()
-                            .to_str()
-                            .unwrap_or_default()
-                            .to_string();
-                        labels_count += label.len();
-                        values_count += value.len();
-                        if labels_count > labels_limit || values_count > values_limit {
-                            debug!("labels_count exceeds the labels limit:{} or values_count exceeds the values limit:{} ", labels_limit, values_limit);
-                            break;
-                        }
-                        extra_label_names.push(label.to_string());
-                        extra_label_values.push(value);
-                    }
-                }
-            }
+            let (extra_label_names, extra_label_values) =
+                prometheus_extra_config.extract(req.headers());
 
             let mut whole_body =
                 match aggregate_with_catch_exception(req.into_body(), &exception_handler).await {
@@ -765,7 +1085,7 @@ async fn handler(
                     return Ok(e);
                 }
             };
-            let metric = decode_metric(whole_body, &part.headers)?;
+            let metric = body_decoder.decode(whole_body, &part.headers)?;
             if log_enabled!(Level::Debug) {
                 if let Ok(r) = String::from_utf8(metric.clone()) {
                     debug!("telegraf metric: {}", r)
@@ -792,7 +1112,7 @@ async fn handler(
                     return Ok(e);
                 }
             };
-            profile.data = decode_metric(whole_body, &part.headers)?;
+            profile.data = body_decoder.decode(whole_body, &part.headers)?;
             if profile_compressed {
                 match compress(&profile.data, 0) {
                     Ok(compressed_data) => {
@@ -836,7 +1156,7 @@ async fn handler(
                     return Ok(e);
                 }
             };
-            let log_data = decode_metric(whole_body, &part.headers)?;
+            let log_data = body_decoder.decode(whole_body, &part.headers)?;
             if let Err(e) = application_log_sender.send(ApplicationLog(log_data)) {
                 warn!(
                     "application_log_sender failed to send data, because {:?}",
@@ -863,7 +1183,7 @@ async fn handler(
                     return Ok(e);
                 }
             };
-            let data = decode_metric(whole_body, &part.headers)?;
+            let data = body_decoder.decode(whole_body, &part.headers)?;
             Ok(
                 handle_skywalking_request(peer_addr, data, part.uri.path(), skywalking_sender)
                     .await,
@@ -887,10 +1207,48 @@ async fn handler(
             )
             .await)
         }
+        // Datadog traces converted to OpenTelemetry
+        (&Method::POST, "/v0.4/traces" | "/v0.7/traces") => {
+            if external_trace_integration_disabled {
+                return Ok(datadog_response(datadog::RATE_BY_SERVICE));
+            }
+            let (part, body) = req.into_parts();
+            let whole_body = match aggregate_with_catch_exception(body, &exception_handler).await {
+                Ok(b) => b,
+                Err(e) => {
+                    return Ok(e);
+                }
+            };
+            let data = body_decoder.decode(whole_body, &part.headers)?;
+            let traces =
+                datadog::decode_traces(part.uri.path(), &data, &part.headers).map_err(|e| {
+                    debug!("decode datadog trace data error: {}", e);
+                    e
+                })?;
+            let decode_data = fill_otel_trace_data(
+                peer_addr,
+                traces,
+                local_epc_id,
+                policy_getter,
+                time_diff.load(Ordering::Relaxed),
+                flow_id.clone(),
+                log_parser_config.clone(),
+            );
+            send_otel_trace_data(
+                decode_data,
+                compressed,
+                &counter,
+                &otel_sender,
+                &compressed_otel_sender,
+                &otel_l7_stats_sender,
+            )?;
+
+            Ok(datadog_response(datadog::RATE_BY_SERVICE))
+        }
         (
             &Method::POST,
             // https://github.com/open-telemetry/opentelemetry-collector-contrib/blob/v0.114.0/receiver/datadogreceiver/README.md?plain=1#L65
-            "/api/v0.2/traces" | "/v0.3/traces" | "/v0.4/traces" | "/v0.5/traces" | "/v0.7/traces",
+            "/api/v0.2/traces" | "/v0.3/traces" | "/v0.5/traces",
         ) => {
             if external_trace_integration_disabled {
                 return Ok(Response::builder().body(Body::empty()).unwrap());
@@ -905,7 +1263,7 @@ async fn handler(
 
             let mut third_party_data = flow_log::ThirdPartyTrace::default();
             parse_dd_headers(&part.headers, &mut third_party_data);
-            third_party_data.data = decode_metric(whole_body, &part.headers)?;
+            third_party_data.data = body_decoder.decode(whole_body, &part.headers)?;
             third_party_data.uri = part.uri.path().to_string();
             third_party_data.peer_ip = match peer_addr.ip() {
                 IpAddr::V4(ip4) => ip4.octets().to_vec(),
@@ -918,6 +1276,8 @@ async fn handler(
 
             Ok(Response::builder().body(Body::empty()).unwrap())
         }
+        // Other APIs of the Datadog agent
+        (_, path) if datadog::is_unsupported_endpoint(path) => Ok(datadog_response("{}")),
         // Return the 404 Not Found for other routes.
         _ => Ok(Response::builder()
             .status(StatusCode::NOT_FOUND)
@@ -970,6 +1330,13 @@ fn parse_profile_query(query: &str, profile: &mut metric::Profile) {
     };
 }
 
+fn datadog_response(body: &'static str) -> Response<Body> {
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+
 fn parse_dd_headers(headers: &HeaderMap, third_party_data: &mut flow_log::ThirdPartyTrace) {
     for key in vec![
         "Datadog-Meta-Lang",           // headers.lang
@@ -993,9 +1360,51 @@ struct CompressedMetric {
     uncompressed: AtomicU64, // unit (bytes)
 }
 
+// OTLP requests by transport and signal
+#[derive(Default)]
+struct OtlpRequestMetric {
+    http_trace: AtomicU64,
+    grpc_trace: AtomicU64,
+    grpc_metric: AtomicU64,
+    grpc_log: AtomicU64,
+}
+
+// HTTP requests by Content-Encoding, and requests rejected for exceeding the decompressed size limit
+#[derive(Default)]
+struct ContentEncodingMetric {
+    identity: AtomicU64,
+    gzip: AtomicU64,
+    zstd: AtomicU64,
+    deflate: AtomicU64,
+    too_large: AtomicU64,
+}
+
+// Requests rejected by the source filter by signal
+#[derive(Default)]
+struct RejectedRequestMetric {
+    trace: AtomicU64,
+    metric: AtomicU64,
+    log: AtomicU64,
+    profile: AtomicU64,
+}
+
+// StatsD lines received, malformed lines and lines dropped for too many series are counted,
+// as well as datagrams rejected by the source filter
+#[derive(Default)]
+struct StatsdMetric {
+    lines: AtomicU64,
+    malformed_lines: AtomicU64,
+    dropped_lines: AtomicU64,
+    rejected_datagrams: AtomicU64,
+}
+
 #[derive(Default)]
 pub struct IntegrationCounter {
     metrics: Arc<CompressedMetric>,
+    otlp_requests: Arc<OtlpRequestMetric>,
+    content_encodings: Arc<ContentEncodingMetric>,
+    rejected: Arc<RejectedRequestMetric>,
+    statsd: Arc<StatsdMetric>,
 }
 
 impl OwnedCountable for IntegrationCounter {
@@ -1025,6 +1434,91 @@ impl OwnedCountable for IntegrationCounter {
                     uncomressed as f64 / compressed as f64
                 }),
             ),
+            (
+                "otlp_http_trace_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.otlp_requests.http_trace.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_grpc_trace_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.otlp_requests.grpc_trace.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_grpc_metric_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.otlp_requests.grpc_metric.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "otlp_grpc_log_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.otlp_requests.grpc_log.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http_identity_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.content_encodings.identity.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http_gzip_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.content_encodings.gzip.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http_zstd_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.content_encodings.zstd.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http_deflate_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.content_encodings.deflate.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "http_too_large_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.content_encodings.too_large.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rejected_trace_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rejected.trace.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rejected_metric_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rejected.metric.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rejected_log_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rejected.log.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "rejected_profile_requests",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rejected.profile.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "statsd_lines",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.statsd.lines.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "statsd_malformed_lines",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.statsd.malformed_lines.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "statsd_dropped_lines",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.statsd.dropped_lines.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "statsd_rejected_datagrams",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.statsd.rejected_datagrams.swap(0, Ordering::Relaxed)),
+            ),
         ]
     }
 
@@ -1049,15 +1543,25 @@ pub struct MetricServer {
     skywalking_sender: DebugSender<SkyWalkingExtra>,
     datadog_sender: DebugSender<Datadog>,
     port: Arc<AtomicU16>,
+    listen_address: Option<IpAddr>,
+    unix_socket_path: Option<PathBuf>,
     exception_handler: ExceptionHandler,
     server_shutdown_tx: Mutex<Option<mpsc::Sender<()>>>,
+    otlp_grpc_port: u16,
+    otlp_grpc_thread: Mutex<Option<JoinHandle<()>>>,
+    statsd: Statsd,
+    statsd_thread: Mutex<Option<JoinHandle<()>>>,
+    statsd_counter: Arc<StatsdMetric>,
     counter: Arc<CompressedMetric>,
+    otlp_requests: Arc<OtlpRequestMetric>,
+    body_decoder: Arc<BodyDecoder>,
+    source_guard: Arc<SourceGuard>,
     compressed: Arc<AtomicBool>,
     profile_compressed: Arc<AtomicBool>,
     local_epc_id: u32,
     policy_getter: Arc<PolicyGetter>,
     time_diff: Arc<AtomicI64>,
-    prometheus_extra_config: Arc<PrometheusExtraLabels>,
+    prometheus_extra_config: Arc<PrometheusExtraLabelsConfig>,
     log_parser_config: Arc<LogParserConfig>,
     external_profile_integration_disabled: bool,
     external_trace_integration_disabled: bool,
@@ -1078,9 +1582,15 @@ impl MetricServer {
         #[cfg(feature = "enterprise-integration")] skywalking_sender: DebugSender<SkyWalkingExtra>,
         datadog_sender: DebugSender<Datadog>,
         port: u16,
+        listen_address: Option<IpAddr>,
+        unix_socket_path: Option<PathBuf>,
+        otlp_grpc_port: u16,
+        statsd: Statsd,
         exception_handler: ExceptionHandler,
         compressed: bool,
         profile_compressed: bool,
+        max_decompressed_size: usize,
+        source_filter: SourceFilter,
         local_epc_id: u32,
         policy_getter: PolicyGetter,
         time_diff: Arc<AtomicI64>,
@@ -1109,13 +1619,29 @@ impl MetricServer {
                 skywalking_sender,
                 datadog_sender,
                 port: Arc::new(AtomicU16::new(port)),
+                listen_address,
+                unix_socket_path,
                 exception_handler,
                 server_shutdown_tx: Default::default(),
+                otlp_grpc_port,
+                otlp_grpc_thread: Default::default(),
+                statsd,
+                statsd_thread: Default::default(),
+                statsd_counter: counter.statsd.clone(),
                 counter: counter.metrics.clone(),
+                otlp_requests: counter.otlp_requests.clone(),
+                body_decoder: Arc::new(BodyDecoder {
+                    max_size: max_decompressed_size,
+                    counter: counter.content_encodings.clone(),
+                }),
+                source_guard: Arc::new(SourceGuard {
+                    acls: ArcSwap::from_pointee((&source_filter).into()),
+                    rejected: counter.rejected.clone(),
+                }),
                 local_epc_id,
                 policy_getter: Arc::new(policy_getter),
                 time_diff,
-                prometheus_extra_config: Arc::new(prometheus_extra_config),
+                prometheus_extra_config: Arc::new((&prometheus_extra_config).into()),
                 log_parser_config: Arc::new(log_parser_config),
                 otel_l7_stats_sender,
                 external_profile_integration_disabled,
@@ -1135,6 +1661,32 @@ impl MetricServer {
         self.profile_compressed.store(enable, Ordering::Relaxed);
     }
 
+    // Endpoints of the listeners, such as tcp://0.0.0.0:38086, unix:///run/otel.sock
+    // and grpc://0.0.0.0:4317
+    pub fn endpoints(&self) -> Vec<String> {
+        let mut endpoints = listen_endpoints(
+            self.listen_address,
+            self.port.load(Ordering::Acquire),
+            self.unix_socket_path.as_deref(),
+        );
+        if self.otlp_grpc_port != 0 {
+            endpoints.push(format!(
+                "grpc://{}",
+                tcp_listen_addr(self.listen_address, self.otlp_grpc_port)
+            ));
+        }
+        if self.statsd.listen_port != 0 {
+            endpoints.push(format!(
+                "udp://{}",
+                tcp_listen_addr(self.listen_address, self.statsd.listen_port)
+            ));
+        }
+        if let Some(path) = self.statsd.listen_unix_socket_path() {
+            endpoints.push(format!("unixgram://{}", path.display()));
+        }
+        endpoints
+    }
+
     pub fn set_port(&self, port: u16) {
         if self.port.swap(port, Ordering::Release) != port {
             // port changes, resets server
@@ -1145,6 +1697,10 @@ impl MetricServer {
         }
     }
 
+    pub fn set_source_filter(&self, source_filter: &SourceFilter) {
+        self.source_guard.update(source_filter);
+    }
+
     pub fn start(&self) {
         if self.running.swap(true, Ordering::Relaxed) {
             return;
@@ -1161,11 +1717,16 @@ impl MetricServer {
         let skywalking_sender = self.skywalking_sender.clone();
         let datadog_sender = self.datadog_sender.clone();
         let port = self.port.clone();
+        let listen_address = self.listen_address;
+        let unix_socket_path = self.unix_socket_path.clone();
         let monitor_port = Arc::new(AtomicU16::new(port.load(Ordering::Acquire)));
         let (mon_tx, mon_rx) = oneshot::channel();
         let exception_handler = self.exception_handler.clone();
         let running = self.running.clone();
         let counter = self.counter.clone();
+        let otlp_requests = self.otlp_requests.clone();
+        let body_decoder = self.body_decoder.clone();
+        let source_guard = self.source_guard.clone();
         let compressed = self.compressed.clone();
         let profile_compressed = self.profile_compressed.clone();
         let local_epc_id = self.local_epc_id.clone();
@@ -1178,10 +1739,82 @@ impl MetricServer {
         let external_metric_integration_disabled = self.external_metric_integration_disabled;
         let external_log_integration_disabled = self.external_log_integration_disabled;
         let (tx, mut rx) = mpsc::channel(8);
-        self.runtime
-            .spawn(Self::alive_check(monitor_port.clone(), tx.clone(), mon_rx));
+        self.runtime.spawn(Self::alive_check(
+            listen_address,
+            monitor_port.clone(),
+            tx.clone(),
+            mon_rx,
+        ));
         self.server_shutdown_tx.lock().unwrap().replace(tx);
 
+        if self.otlp_grpc_port != 0 {
+            let receiver = OtlpReceiver {
+                otel_sender: self.otel_sender.clone(),
+                compressed_otel_sender: self.compressed_otel_sender.clone(),
+                otel_l7_stats_sender: self.otel_l7_stats_sender.clone(),
+                telegraf_sender: self.telegraf_sender.clone(),
+                application_log_sender: self.application_log_sender.clone(),
+                compressed: self.compressed.clone(),
+                counter: self.counter.clone(),
+                requests: self.otlp_requests.clone(),
+                source_guard: self.source_guard.clone(),
+                local_epc_id: self.local_epc_id,
+                policy_getter: self.policy_getter.clone(),
+                time_diff: self.time_diff.clone(),
+                log_parser_config: self.log_parser_config.clone(),
+                flow_id: Default::default(),
+                trace_disabled: self.external_trace_integration_disabled,
+                metric_disabled: self.external_metric_integration_disabled,
+                log_disabled: self.external_log_integration_disabled,
+            };
+            self.otlp_grpc_thread
+                .lock()
+                .unwrap()
+                .replace(self.runtime.spawn(otlp_grpc::serve(
+                    tcp_listen_addr(listen_address, self.otlp_grpc_port),
+                    Arc::new(receiver),
+                    running.clone(),
+                    exception_handler.clone(),
+                )));
+        }
+
+        if self.statsd.enabled() && !self.external_metric_integration_disabled {
+            let statsd = self.statsd.clone();
+            let aggregator = statsd::Aggregator::new(
+                self.prometheus_extra_config.label_length,
+                self.prometheus_extra_config.value_length,
+            );
+            let telegraf_sender = self.telegraf_sender.clone();
+            let counter = self.statsd_counter.clone();
+            let source_guard = self.source_guard.clone();
+            let exception_handler = exception_handler.clone();
+            self.statsd_thread
+                .lock()
+                .unwrap()
+                .replace(self.runtime.spawn(async move {
+                    let udp_addr = if statsd.listen_port != 0 {
+                        Some(tcp_listen_addr(listen_address, statsd.listen_port))
+                    } else {
+                        None
+                    };
+                    if let Err(e) = statsd::serve(
+                        udp_addr,
+                        statsd.listen_unix_socket_path(),
+                        statsd.flush_interval,
+                        aggregator,
+                        telegraf_sender,
+                        counter,
+                        source_guard,
+                    )
+                    .await
+                    {
+                        let error_msg = format!("statsd receiver error: {}", e);
+                        error!("{}", error_msg);
+                        exception_handler.set(Exception::IntegrationSocketError, Some(error_msg));
+                    }
+                }));
+        }
+
         self.thread
             .lock()
             .unwrap()
@@ -1189,23 +1822,37 @@ impl MetricServer {
                 info!("integration collector starting");
                 while running.load(Ordering::Relaxed) {
                     let mut max_tries = 0;
-                    let (server_builder, addr) = loop {
+                    let (tcp_builder, unix_listener, endpoints) = loop {
                         if !running.load(Ordering::Relaxed) {
                             return;
                         }
                         while let Ok(_) = rx.try_recv() {} // drain useless messages
                         let port = port.load(Ordering::Acquire);
-                        let addr = if ipv6_enabled() {
-                            (Ipv6Addr::UNSPECIFIED, port).into()
+                        let tcp_builder = if port != 0 {
+                            let addr = tcp_listen_addr(listen_address, port);
+                            Server::try_bind(&addr)
+                                .map(Some)
+                                .map_err(|e| format!("{} with addr={}", e, addr))
                         } else {
-                            (Ipv4Addr::UNSPECIFIED, port).into()
+                            Ok(None)
                         };
-                        match Server::try_bind(&addr) {
-                            Ok(s) => {
+                        let unix_listener = match unix_socket_path.as_ref() {
+                            Some(path) => bind_unix(path)
+                                .map(Some)
+                                .map_err(|e| format!("{} with path={}", e, path.display())),
+                            None => Ok(None),
+                        };
+                        match (tcp_builder, unix_listener) {
+                            (Ok(tcp_builder), Ok(unix_listener)) => {
                                 monitor_port.store(port, Ordering::Release);
-                                break (s, addr);
+                                let endpoints = listen_endpoints(
+                                    listen_address,
+                                    port,
+                                    unix_socket_path.as_deref(),
+                                );
+                                break (tcp_builder, unix_listener, endpoints);
                             }
-                            Err(e) => {
+                            (Err(e), _) | (_, Err(e)) => {
                                 // 因为有场景是停止server之后立刻开启server，Server::stop采用丢弃线程的方法会直接返回，而操作系统回收监听端口资源需要时间，
                                 // 为了没有spurious error log，需要睡眠一会等待操作系统完成回收资源。
                                 // =================================================================================================
@@ -1217,10 +1864,7 @@ impl MetricServer {
                                     sleep(Duration::from_secs(1));
                                     continue;
                                 }
-                                let error_msg = format!(
-                                    "integration collector error: {} with addr={}",
-                                    e, addr
-                                );
+                                let error_msg = format!("integration collector error: {}", e);
                                 error!("{}", error_msg);
                                 exception_handler
                                     .set(Exception::IntegrationSocketError, Some(error_msg));
@@ -1242,6 +1886,9 @@ impl MetricServer {
                     let datadog_sender = datadog_sender.clone();
                     let exception_handler_inner = exception_handler.clone();
                     let counter = counter.clone();
+                    let otlp_requests = otlp_requests.clone();
+                    let body_decoder = body_decoder.clone();
+                    let source_guard = source_guard.clone();
                     let compressed = compressed.clone();
                     let profile_compressed = profile_compressed.clone();
                     let local_epc_id = local_epc_id.clone();
@@ -1249,7 +1896,7 @@ impl MetricServer {
                     let time_diff = time_diff.clone();
                     let prometheus_extra_config = prometheus_extra_config.clone();
                     let log_parser_config = log_parser_config.clone();
-                    let service = make_service_fn(move |conn: &AddrStream| {
+                    let new_service = move |peer_addr: SocketAddr| {
                         let otel_sender = otel_sender.clone();
                         let compressed_otel_sender = compressed_otel_sender.clone();
                         let otel_l7_stats_sender = otel_l7_stats_sender.clone();
@@ -1261,8 +1908,10 @@ impl MetricServer {
                         let skywalking_sender = skywalking_sender.clone();
                         let datadog_sender = datadog_sender.clone();
                         let exception_handler = exception_handler_inner.clone();
-                        let peer_addr = conn.remote_addr();
                         let counter = counter.clone();
+                        let otlp_requests = otlp_requests.clone();
+                        let body_decoder = body_decoder.clone();
+                        let source_guard = source_guard.clone();
                         let compressed = compressed.clone();
                         let profile_compressed = profile_compressed.clone();
                         let local_epc_id = local_epc_id.clone();
@@ -1271,47 +1920,106 @@ impl MetricServer {
                         let prometheus_extra_config = prometheus_extra_config.clone();
                         let log_parser_config = log_parser_config.clone();
                         let flow_id = Arc::new(AtomicU64::new(0));
-                        async move {
-                            Ok::<_, GenericError>(service_fn(move |req| {
-                                handler(
-                                    peer_addr,
-                                    req,
-                                    otel_sender.clone(),
-                                    compressed_otel_sender.clone(),
-                                    otel_l7_stats_sender.clone(),
-                                    prometheus_sender.clone(),
-                                    telegraf_sender.clone(),
-                                    profile_sender.clone(),
-                                    application_log_sender.clone(),
-                                    #[cfg(feature = "enterprise-integration")]
-                                    skywalking_sender.clone(),
-                                    datadog_sender.clone(),
-                                    exception_handler.clone(),
-                                    compressed.load(Ordering::Relaxed),
-                                    profile_compressed.load(Ordering::Relaxed),
-                                    counter.clone(),
-                                    local_epc_id,
-                                    policy_getter.clone(),
-                                    time_diff.clone(),
-                                    prometheus_extra_config.clone(),
-                                    log_parser_config.clone(),
-                                    flow_id.clone(),
-                                    external_profile_integration_disabled,
-                                    external_trace_integration_disabled,
-                                    external_metric_integration_disabled,
-                                    external_log_integration_disabled,
-                                )
-                            }))
-                        }
-                    });
+                        service_fn(move |req| {
+                            let response = handler(
+                                peer_addr,
+                                req,
+                                otel_sender.clone(),
+                                compressed_otel_sender.clone(),
+                                otel_l7_stats_sender.clone(),
+                                prometheus_sender.clone(),
+                                telegraf_sender.clone(),
+                                profile_sender.clone(),
+                                application_log_sender.clone(),
+                                #[cfg(feature = "enterprise-integration")]
+                                skywalking_sender.clone(),
+                                datadog_sender.clone(),
+                                exception_handler.clone(),
+                                compressed.load(Ordering::Relaxed),
+                                profile_compressed.load(Ordering::Relaxed),
+                                counter.clone(),
+                                otlp_requests.clone(),
+                                body_decoder.clone(),
+                                source_guard.clone(),
+                                local_epc_id,
+                                policy_getter.clone(),
+                                time_diff.clone(),
+                                prometheus_extra_config.clone(),
+                                log_parser_config.clone(),
+                                flow_id.clone(),
+                                external_profile_integration_disabled,
+                                external_trace_integration_disabled,
+                                external_metric_integration_disabled,
+                                external_log_integration_disabled,
+                            );
+                            async move { reject_too_large(response.await) }
+                        })
+                    };
 
-                    let server = server_builder.serve(service).with_graceful_shutdown(async {
-                        let _ = rx.recv().await;
-                    });
+                    let (stop_tx, stop_rx) = watch::channel(());
+                    let mut servers = vec![];
+                    if let Some(builder) = tcp_builder {
+                        let new_service = new_service.clone();
+                        let mut stop_rx = stop_rx.clone();
+                        let service = make_service_fn(move |conn: &AddrStream| {
+                            let service = new_service(conn.remote_addr());
+                            async move { Ok::<_, GenericError>(service) }
+                        });
+                        servers.push(tokio::spawn(builder.serve(service).with_graceful_shutdown(
+                            async move {
+                                let _ = stop_rx.changed().await;
+                            },
+                        )));
+                    }
+                    #[cfg(unix)]
+                    if let Some(listener) = unix_listener {
+                        let mut stop_rx = stop_rx.clone();
+                        let incoming = accept::poll_fn(move |cx| {
+                            listener
+                                .poll_accept(cx)
+                                .map(|r| Some(r.map(|(stream, _)| stream)))
+                        });
+                        // peers connecting through the unix domain socket are local
+                        let peer_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+                        let service = make_service_fn(move |_: &UnixStream| {
+                            let service = new_service(peer_addr);
+                            async move { Ok::<_, GenericError>(service) }
+                        });
+                        servers.push(tokio::spawn(
+                            Server::builder(incoming)
+                                .serve(service)
+                                .with_graceful_shutdown(async move {
+                                    let _ = stop_rx.changed().await;
+                                }),
+                        ));
+                    }
+                    #[cfg(not(unix))]
+                    let _ = unix_listener;
 
                     info!("integration collector started");
-                    info!("integration collector listening on http://{}", addr);
-                    if let Err(e) = server.await {
+                    info!(
+                        "integration collector listening on {}",
+                        endpoints.join(", ")
+                    );
+                    // a server exiting on error restarts all listeners
+                    let error = if servers.is_empty() {
+                        let _ = rx.recv().await;
+                        None
+                    } else {
+                        select! {
+                            _ = rx.recv() => None,
+                            (r, _, _) = future::select_all(servers.iter_mut()) => match r {
+                                Ok(Ok(_)) => None,
+                                Ok(Err(e)) => Some(e.to_string()),
+                                Err(e) => Some(e.to_string()),
+                            },
+                        }
+                    };
+                    let _ = stop_tx.send(());
+                    for server in servers {
+                        let _ = server.await;
+                    }
+                    if let Some(e) = error {
                         let error_msg = format!("external metric collector error: {}", e);
                         error!("{}", error_msg);
                         exception_handler.set(Exception::IntegrationSocketError, Some(error_msg));
@@ -1334,6 +2042,12 @@ impl MetricServer {
         if let Some(t) = self.thread.lock().unwrap().take() {
             t.abort();
         }
+        if let Some(t) = self.otlp_grpc_thread.lock().unwrap().take() {
+            t.abort();
+        }
+        if let Some(t) = self.statsd_thread.lock().unwrap().take() {
+            t.abort();
+        }
 
         info!("integration collector stopped");
     }
@@ -1345,6 +2059,7 @@ impl MetricServer {
     // lose monitoring after a period of time. So first detect whether the issued port is listening,
     // If not listening, restart the collector and listen again. After finding the root cause, remove the following code
     async fn alive_check(
+        listen_address: Option<IpAddr>,
         port: Arc<AtomicU16>,
         server_shutdown_tx: mpsc::Sender<()>,
         mut mon_rx: oneshot::Receiver<()>,
@@ -1354,7 +2069,12 @@ impl MetricServer {
             select! {
                 _ = ticker.tick() => {
                     let p = port.load(Ordering::Relaxed);
-                    if let Err(_) = TcpStream::connect(("localhost", p)) {
+                    // port 0 means the TCP listener is disabled
+                    let lost = p != 0 && match listen_address {
+                        Some(ip) if !ip.is_unspecified() => TcpStream::connect((ip, p)).is_err(),
+                        _ => TcpStream::connect(("localhost", p)).is_err(),
+                    };
+                    if lost {
                         warn!(
                             "the port=({}) listen by the integration collector lost, restart the collector",
                             p
@@ -1367,3 +2087,368 @@ impl MetricServer {
         }
     }
 }
+
+// Escapes measurements, tag keys, tag values and field keys of the influx line protocol
+fn escape_line_protocol(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn tcp_listen_addr(listen_address: Option<IpAddr>, port: u16) -> SocketAddr {
+    match listen_address {
+        Some(ip) => (ip, port).into(),
+        None if ipv6_enabled() => (Ipv6Addr::UNSPECIFIED, port).into(),
+        None => (Ipv4Addr::UNSPECIFIED, port).into(),
+    }
+}
+
+fn listen_endpoints(
+    listen_address: Option<IpAddr>,
+    port: u16,
+    unix_socket_path: Option<&Path>,
+) -> Vec<String> {
+    let mut endpoints = vec![];
+    if port != 0 {
+        endpoints.push(format!("tcp://{}", tcp_listen_addr(listen_address, port)));
+    }
+    if let Some(path) = unix_socket_path {
+        endpoints.push(format!("unix://{}", path.display()));
+    }
+    endpoints
+}
+
+// The socket file left by the last run is removed before binding, other files are kept
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &Path) -> std::io::Result<UnixListener> {
+    remove_stale_socket(path)?;
+    UnixListener::bind(path)
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::PrometheusLabelRewriteRule;
+
+    #[tokio::test]
+    async fn bind_to_address() {
+        let loopback = Some(IpAddr::from(Ipv4Addr::LOCALHOST));
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = tcp_listen_addr(loopback, port);
+        assert_eq!(addr, SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+
+        let _builder = Server::try_bind(&addr).unwrap();
+        assert!(TcpStream::connect(addr).is_ok());
+        assert_eq!(
+            listen_endpoints(loopback, port, None),
+            vec![format!("tcp://127.0.0.1:{}", port)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_to_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("integration.sock");
+
+        let listener = bind_unix(&path).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+        // the socket file left by the last listener is replaced
+        drop(listener);
+        let _listener = bind_unix(&path).unwrap();
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+
+        // other files are not removed
+        let file_path = dir.path().join("integration.txt");
+        std::fs::write(&file_path, b"data").unwrap();
+        assert!(bind_unix(&file_path).is_err());
+        assert!(file_path.exists());
+
+        assert_eq!(
+            listen_endpoints(None, 0, Some(&path)),
+            vec![format!("unix://{}", path.display())]
+        );
+    }
+
+    fn extra_labels_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("pod", "web-7d9f8b6c5-x2x4z".parse().unwrap());
+        headers.insert("build", "3f2a9c1e7b".parse().unwrap());
+        headers.insert("env", "staging".parse().unwrap());
+        headers
+    }
+
+    fn extra_labels_config(
+        rewrite_rules: Vec<PrometheusLabelRewriteRule>,
+    ) -> PrometheusExtraLabels {
+        PrometheusExtraLabels {
+            enabled: true,
+            extra_labels: vec!["pod".to_owned(), "Build".to_owned(), "env".to_owned()],
+            rewrite_rules,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn extract_extra_labels_without_rules() {
+        let headers = extra_labels_headers();
+        let config = PrometheusExtraLabelsConfig::from(&extra_labels_config(vec![]));
+        assert_eq!(
+            config.extract(&headers),
+            (
+                vec!["pod".to_owned(), "Build".to_owned(), "env".to_owned()],
+                vec![
+                    "web-7d9f8b6c5-x2x4z".to_owned(),
+                    "3f2a9c1e7b".to_owned(),
+                    "staging".to_owned()
+                ]
+            )
+        );
+
+        let config = PrometheusExtraLabelsConfig::from(&PrometheusExtraLabels {
+            value_length: 20,
+            ..extra_labels_config(vec![])
+        });
+        assert_eq!(
+            config.extract(&headers),
+            (
+                vec!["pod".to_owned()],
+                vec!["web-7d9f8b6c5-x2x4z".to_owned()]
+            )
+        );
+    }
+
+    #[test]
+    fn extract_extra_labels_with_rules() {
+        let headers = extra_labels_headers();
+        let rule =
+            |label: &str, regex: &str, replacement: &str, action| PrometheusLabelRewriteRule {
+                label: label.to_owned(),
+                regex: regex.to_owned(),
+                replacement: replacement.to_owned(),
+                action,
+            };
+        let extra_labels = extra_labels_config(vec![
+            // strips the replica set and pod hashes
+            rule(
+                "pod",
+                r"^(.+)-[0-9a-f]+-[0-9a-z]{5}$",
+                "$1",
+                PrometheusLabelRewriteAction::Replace,
+            ),
+            rule(
+                "build",
+                r"^([0-9a-f]{7})[0-9a-f]*$",
+                "$1",
+                PrometheusLabelRewriteAction::Keep,
+            ),
+            rule("env", "^staging$", "", PrometheusLabelRewriteAction::Drop),
+        ]);
+        let config = PrometheusExtraLabelsConfig::from(&extra_labels);
+        assert_eq!(
+            config.extract(&headers),
+            (
+                vec!["pod".to_owned(), "Build".to_owned()],
+                vec!["web".to_owned(), "3f2a9c1".to_owned()]
+            )
+        );
+
+        // dropped if not in the allowlist
+        let mut headers = headers;
+        headers.insert("build", "dev".parse().unwrap());
+        headers.insert("env", "prod".parse().unwrap());
+        assert_eq!(
+            config.extract(&headers),
+            (
+                vec!["pod".to_owned(), "env".to_owned()],
+                vec!["web".to_owned(), "prod".to_owned()]
+            )
+        );
+
+        // rewritten values are counted in value_length
+        let config = PrometheusExtraLabelsConfig::from(&PrometheusExtraLabels {
+            value_length: 7,
+            ..extra_labels
+        });
+        assert_eq!(
+            config.extract(&headers),
+            (
+                vec!["pod".to_owned(), "env".to_owned()],
+                vec!["web".to_owned(), "prod".to_owned()]
+            )
+        );
+    }
+
+    fn encoded_headers(encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, encoding.parse().unwrap());
+        headers
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut e = flate2::write::GzEncoder::new(vec![], Compression::default());
+        e.write_all(data).unwrap();
+        e.finish().unwrap()
+    }
+
+    #[test]
+    fn decode_content_encodings() {
+        let decoder = BodyDecoder {
+            max_size: 1 << 20,
+            counter: Default::default(),
+        };
+        let data = b"cpu,host=a usage_idle=99.5 1700000000000000000\n".repeat(100);
+        let mut raw_deflate = flate2::write::DeflateEncoder::new(vec![], Compression::default());
+        raw_deflate.write_all(&data).unwrap();
+        for (encoding, body) in [
+            ("", data.clone()),
+            // forwarded as is
+            ("snappy", data.clone()),
+            ("gzip", gzip(&data)),
+            ("ZSTD", compress(&data, 0).unwrap()),
+            ("deflate", compress_data(data.clone()).unwrap()),
+            ("deflate", raw_deflate.finish().unwrap()),
+        ] {
+            assert_eq!(
+                decoder
+                    .decode(body.as_slice(), &encoded_headers(encoding))
+                    .unwrap(),
+                data,
+                "{}",
+                encoding
+            );
+        }
+        assert!(decoder
+            .decode(b"not zstd".as_slice(), &encoded_headers("zstd"))
+            .is_err());
+
+        let counter = &decoder.counter;
+        assert_eq!(counter.identity.load(Ordering::Relaxed), 2);
+        assert_eq!(counter.gzip.load(Ordering::Relaxed), 1);
+        assert_eq!(counter.zstd.load(Ordering::Relaxed), 2);
+        assert_eq!(counter.deflate.load(Ordering::Relaxed), 2);
+        assert_eq!(counter.too_large.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn reject_decompression_bomb() {
+        let decoder = BodyDecoder {
+            max_size: 1 << 20,
+            counter: Default::default(),
+        };
+        // 16MiB of zeros are compressed to a few KiB
+        let zeros = vec![0u8; 16 << 20];
+        let zstd_bomb = compress(&zeros, 19).unwrap();
+        let gzip_bomb = gzip(&zeros);
+        assert!(zstd_bomb.len() < 64 << 10 && gzip_bomb.len() < 64 << 10);
+        for (encoding, body) in [("zstd", zstd_bomb), ("gzip", gzip_bomb)] {
+            let e = decoder
+                .decode(body.as_slice(), &encoded_headers(encoding))
+                .unwrap_err();
+            assert!(e.is::<BodyTooLarge>(), "{}", encoding);
+        }
+        assert_eq!(decoder.counter.too_large.load(Ordering::Relaxed), 2);
+
+        // accepted at the limit
+        let body = compress(&zeros[..1 << 20], 0).unwrap();
+        assert_eq!(
+            decoder
+                .decode(body.as_slice(), &encoded_headers("zstd"))
+                .unwrap()
+                .len(),
+            1 << 20
+        );
+        assert_eq!(decoder.counter.too_large.load(Ordering::Relaxed), 2);
+
+        let response = reject_too_large(Err(BodyTooLarge(1 << 20).into())).unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn source_filter_deny_wins() {
+        let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut source_filter = SourceFilter {
+            trace: SignalSourceFilter {
+                allow: strings(&["10.0.0.0/8", "2001:db8::/32"]),
+                deny: strings(&["10.1.0.0/16", "2001:db8:1::/48"]),
+            },
+            metric: SignalSourceFilter {
+                allow: vec![],
+                deny: strings(&["192.168.1.10"]),
+            },
+            ..Default::default()
+        };
+        let guard = SourceGuard::default();
+        guard.update(&source_filter);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert!(guard.permits(Signal::Trace, ip("10.2.3.4")));
+        // denied in the overlapping range
+        assert!(!guard.permits(Signal::Trace, ip("10.1.2.3")));
+        assert!(!guard.permits(Signal::Trace, ip("172.16.0.1")));
+        assert!(guard.permits(Signal::Trace, ip("2001:db8:2::1")));
+        assert!(!guard.permits(Signal::Trace, ip("2001:db8:1::1")));
+        // IPv4-mapped peers are matched as IPv4
+        assert!(!guard.permits(Signal::Trace, ip("::ffff:10.1.2.3")));
+        assert!(guard.permits(Signal::Trace, ip("::ffff:10.2.3.4")));
+
+        // all sources not denied are allowed without allow entries
+        assert!(guard.permits(Signal::Metric, ip("192.168.1.11")));
+        assert!(!guard.permits(Signal::Metric, ip("192.168.1.10")));
+        assert!(guard.permits(Signal::Log, ip("172.16.0.1")));
+        assert!(guard.permits(Signal::Profile, ip("172.16.0.1")));
+
+        let rejected = &guard.rejected;
+        assert_eq!(rejected.trace.load(Ordering::Relaxed), 4);
+        assert_eq!(rejected.metric.load(Ordering::Relaxed), 1);
+        assert_eq!(rejected.log.load(Ordering::Relaxed), 0);
+
+        // applied without restarting
+        source_filter.profile.deny = strings(&["0.0.0.0/0", "::/0"]);
+        guard.update(&source_filter);
+        assert!(!guard.permits(Signal::Profile, ip("172.16.0.1")));
+        assert!(!guard.permits(Signal::Profile, ip("fe80::1")));
+        assert_eq!(rejected.profile.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn route_signals() {
+        assert_eq!(route_signal("/api/v1/otel/trace"), Some(Signal::Trace));
+        assert_eq!(route_signal("/v0.4/traces"), Some(Signal::Trace));
+        assert_eq!(route_signal("/api/v1/telegraf"), Some(Signal::Metric));
+        assert_eq!(route_signal("/api/v1/log"), Some(Signal::Log));
+        assert_eq!(
+            route_signal("/api/v1/profile/ingest"),
+            Some(Signal::Profile)
+        );
+        assert_eq!(route_signal("/"), None);
+        assert_eq!(route_signal("/info"), None);
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Converts traces of the Datadog trace intake API to OpenTelemetry, so that dd-trace SDKs
+// can send traces to deepflow-agent by DD_AGENT_HOST.
+// https://github.com/DataDog/datadog-agent/blob/main/pkg/proto/datadog/trace/span.proto
+
+use std::collections::{BTreeMap, HashMap};
+
+use http::HeaderMap;
+use public::proto::integration::opentelemetry::proto::{
+    common::v1::{
+        any_value::Value::{DoubleValue, IntValue, StringValue},
+        AnyValue, KeyValue,
+    },
+    resource::v1::Resource,
+    trace::v1::{
+        span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span, Status, TracesData,
+    },
+};
+use serde::{Deserialize, Deserializer};
+
+// The response of trace intake without sampling rates, dd-trace SDKs keep their own rates
+pub(super) const RATE_BY_SERVICE: &str = r#"{"rate_by_service":{}}"#;
+
+// Intake APIs of the Datadog agent other than traces, an empty JSON object is returned so that
+// SDKs do not log errors
+pub(super) fn is_unsupported_endpoint(path: &str) -> bool {
+    matches!(
+        path,
+        "/info"
+            | "/v0.6/stats"
+            | "/v0.1/pipeline_stats"
+            | "/v0.7/config"
+            | "/profiling/v1/input"
+            | "/debugger/v1/input"
+            | "/debugger/v1/diagnostics"
+            | "/dogstatsd/v2/proxy"
+            | "/tracer_flare/v1"
+    ) || path.starts_with("/telemetry/proxy/")
+        || path.starts_with("/evp_proxy/")
+}
+
+// msgpack nil is decoded as the default value
+fn nullable<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DdSpan {
+    #[serde(deserialize_with = "nullable")]
+    service: String,
+    #[serde(deserialize_with = "nullable")]
+    name: String,
+    #[serde(deserialize_with = "nullable")]
+    resource: String,
+    trace_id: u64,
+    span_id: u64,
+    parent_id: u64,
+    start: i64,
+    duration: i64,
+    error: i32,
+    #[serde(deserialize_with = "nullable")]
+    meta: HashMap<String, String>,
+    #[serde(deserialize_with = "nullable")]
+    metrics: HashMap<String, f64>,
+    #[serde(rename = "type", deserialize_with = "nullable")]
+    span_type: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DdTraceChunk {
+    #[serde(deserialize_with = "nullable")]
+    spans: Vec<DdSpan>,
+}
+
+// The payload of /v0.7/traces, payloads of /v0.4/traces are converted to it
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DdTracerPayload {
+    #[serde(deserialize_with = "nullable")]
+    container_id: String,
+    #[serde(deserialize_with = "nullable")]
+    language_name: String,
+    #[serde(deserialize_with = "nullable")]
+    tracer_version: String,
+    #[serde(deserialize_with = "nullable")]
+    chunks: Vec<DdTraceChunk>,
+    #[serde(deserialize_with = "nullable")]
+    env: String,
+    #[serde(deserialize_with = "nullable")]
+    hostname: String,
+    #[serde(deserialize_with = "nullable")]
+    app_version: String,
+}
+
+impl DdTracerPayload {
+    // /v0.4/traces is an array of traces, and each trace is an array of spans
+    fn from_v04(data: &[u8], headers: &HeaderMap) -> Result<Self, rmp_serde::decode::Error> {
+        let traces: Vec<Vec<DdSpan>> = rmp_serde::from_slice(data)?;
+        let header = |key: &str| {
+            headers
+                .get(key)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+        Ok(Self {
+            container_id: header("Datadog-Container-Id"),
+            language_name: header("Datadog-Meta-Lang"),
+            tracer_version: header("Datadog-Meta-Tracer-Version"),
+            chunks: traces
+                .into_iter()
+                .map(|spans| DdTraceChunk { spans })
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+// Decodes traces of /v0.4/traces or /v0.7/traces
+pub(super) fn decode_traces(
+    path: &str,
+    data: &[u8],
+    headers: &HeaderMap,
+) -> Result<TracesData, rmp_serde::decode::Error> {
+    let payload = if path == "/v0.7/traces" {
+        rmp_serde::from_slice(data)?
+    } else {
+        DdTracerPayload::from_v04(data, headers)?
+    };
+    Ok(to_traces_data(payload))
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(StringValue(value)),
+        }),
+    }
+}
+
+fn int_attribute(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(IntValue(value)),
+        }),
+    }
+}
+
+// Spans are grouped by service, each service is a resource
+fn to_traces_data(payload: DdTracerPayload) -> TracesData {
+    let mut resource_attributes = vec![];
+    for (key, value) in [
+        ("container.id", &payload.container_id),
+        ("telemetry.sdk.language", &payload.language_name),
+        ("telemetry.sdk.version", &payload.tracer_version),
+        ("deployment.environment", &payload.env),
+        ("host.name", &payload.hostname),
+        ("service.version", &payload.app_version),
+    ] {
+        if !value.is_empty() {
+            resource_attributes.push(string_attribute(key, value.clone()));
+        }
+    }
+
+    let mut services: BTreeMap<String, Vec<Span>> = BTreeMap::new();
+    for span in payload.chunks.into_iter().flat_map(|c| c.spans.into_iter()) {
+        let service = span.service.clone();
+        services.entry(service).or_default().push(to_span(span));
+    }
+    TracesData {
+        resource_spans: services
+            .into_iter()
+            .map(|(service, spans)| {
+                let mut attributes = vec![string_attribute("service.name", service)];
+                attributes.extend(resource_attributes.iter().cloned());
+                ResourceSpans {
+                    resource: Some(Resource {
+                        attributes,
+                        ..Default::default()
+                    }),
+                    scope_spans: vec![ScopeSpans {
+                        spans,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }
+            })
+            .collect(),
+    }
+}
+
+// https://github.com/DataDog/dd-trace-go/blob/main/ddtrace/ext/app_types.go
+fn span_kind(span: &DdSpan) -> SpanKind {
+    match span.meta.get("span.kind").map(|s| s.as_str()) {
+        Some("server") => return SpanKind::Server,
+        Some("client") => return SpanKind::Client,
+        Some("producer") => return SpanKind::Producer,
+        Some("consumer") => return SpanKind::Consumer,
+        Some("internal") => return SpanKind::Internal,
+        _ => (),
+    }
+    match span.span_type.as_str() {
+        "web" => SpanKind::Server,
+        "http" | "grpc" | "sql" | "db" | "cache" | "redis" | "memcached" | "mongodb"
+        | "elasticsearch" | "cassandra" => SpanKind::Client,
+        "queue" => SpanKind::Producer,
+        _ => SpanKind::Internal,
+    }
+}
+
+// db.system of OpenTelemetry from db.type of Datadog or the span type
+fn db_system(span: &DdSpan) -> Option<String> {
+    let db_type = span
+        .meta
+        .get("db.system")
+        .or_else(|| span.meta.get("db.type"))
+        .map(|s| s.as_str())
+        .or_else(|| match span.span_type.as_str() {
+            t @ ("redis" | "memcached" | "mongodb" | "elasticsearch" | "cassandra") => Some(t),
+            _ => None,
+        })?;
+    Some(match db_type {
+        "postgres" => "postgresql".to_owned(),
+        t => t.to_owned(),
+    })
+}
+
+// The higher 64 bits of 128-bit trace ids are in the `_dd.p.tid` tag as hex
+fn trace_id(span: &DdSpan) -> Vec<u8> {
+    let high = span
+        .meta
+        .get("_dd.p.tid")
+        .and_then(|s| u64::from_str_radix(s, 16).ok())
+        .unwrap_or_default();
+    let mut id = high.to_be_bytes().to_vec();
+    id.extend_from_slice(&span.trace_id.to_be_bytes());
+    id
+}
+
+fn to_span(span: DdSpan) -> Span {
+    let kind = span_kind(&span);
+    let mut attributes = vec![];
+    if !span.name.is_empty() {
+        attributes.push(string_attribute("dd.span.name", span.name.clone()));
+    }
+    if !span.span_type.is_empty() {
+        attributes.push(string_attribute("dd.span.type", span.span_type.clone()));
+    }
+
+    let is_http =
+        matches!(span.span_type.as_str(), "web" | "http") || span.meta.contains_key("http.method");
+    if is_http {
+        let scheme = span
+            .meta
+            .get("http.url")
+            .and_then(|url| url.split_once("://"))
+            .map(|(scheme, _)| scheme.to_owned())
+            .unwrap_or_else(|| "http".to_owned());
+        attributes.push(string_attribute("http.scheme", scheme));
+    } else if let Some(system) = db_system(&span) {
+        attributes.push(string_attribute("db.system", system));
+        if !span.meta.contains_key("db.statement") && !span.resource.is_empty() {
+            attributes.push(string_attribute("db.statement", span.resource.clone()));
+        }
+    }
+    if let Some(host) = span
+        .meta
+        .get("network.destination.ip")
+        .or_else(|| span.meta.get("out.host"))
+    {
+        if host.parse::<std::net::IpAddr>().is_ok() {
+            attributes.push(string_attribute("net.peer.ip", host.clone()));
+        } else {
+            attributes.push(string_attribute("net.peer.name", host.clone()));
+        }
+    }
+    let port = span
+        .meta
+        .get("network.destination.port")
+        .or_else(|| span.meta.get("out.port"))
+        .and_then(|p| p.parse::<i64>().ok())
+        .or_else(|| span.metrics.get("out.port").map(|p| *p as i64));
+    if let Some(port) = port {
+        attributes.push(int_attribute("net.peer.port", port));
+    }
+
+    let mut meta = span.meta.iter().collect::<Vec<_>>();
+    meta.sort_unstable();
+    for (key, value) in meta {
+        match key.as_str() {
+            "_dd.p.tid" => (),
+            // integer as OpenTelemetry
+            "http.status_code" => match value.parse::<i64>() {
+                Ok(code) => attributes.push(int_attribute(key, code)),
+                Err(_) => attributes.push(string_attribute(key, value.clone())),
+            },
+            _ => attributes.push(string_attribute(key, value.clone())),
+        }
+    }
+    let mut metrics = span.metrics.iter().collect::<Vec<_>>();
+    metrics.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in metrics {
+        attributes.push(KeyValue {
+            key: key.clone(),
+            value: Some(AnyValue {
+                value: Some(DoubleValue(*value)),
+            }),
+        });
+    }
+
+    let status = if span.error != 0 {
+        Some(Status {
+            code: StatusCode::Error as i32,
+            message: span
+                .meta
+                .get("error.message")
+                .or_else(|| span.meta.get("error.msg"))
+                .cloned()
+                .unwrap_or_default(),
+        })
+    } else {
+        None
+    };
+    let start = span.start.max(0) as u64;
+    Span {
+        trace_id: trace_id(&span),
+        span_id: span.span_id.to_be_bytes().to_vec(),
+        parent_span_id: if span.parent_id != 0 {
+            span.parent_id.to_be_bytes().to_vec()
+        } else {
+            vec![]
+        },
+        name: if span.resource.is_empty() {
+            span.name
+        } else {
+            span.resource
+        },
+        kind: kind as i32,
+        start_time_unix_nano: start,
+        end_time_unix_nano: start + span.duration.max(0) as u64,
+        attributes,
+        status,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use public::proto::integration::opentelemetry::proto::common::v1::any_value::Value;
+    use serde_json::json;
+
+    fn attribute<'a>(span: &'a Span, key: &str) -> Option<&'a Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.as_ref()?.value.as_ref())
+    }
+
+    fn http_span() -> serde_json::Value {
+        json!({
+            "service": "web",
+            "name": "servlet.request",
+            "resource": "GET /users/{id}",
+            "trace_id": 0x8000_0000_0000_0001u64,
+            "span_id": 2,
+            "parent_id": 0,
+            "start": 1_700_000_000_000_000_000i64,
+            "duration": 5_000_000,
+            "error": 1,
+            "meta": {
+                "_dd.p.tid": "6543210000000000",
+                "http.method": "GET",
+                "http.url": "https://example.com/users/1",
+                "http.status_code": "500",
+                "error.message": "internal error",
+            },
+            "metrics": {"_sampling_priority_v1": 1},
+            "type": "web",
+        })
+    }
+
+    fn db_span() -> serde_json::Value {
+        json!({
+            "service": "web-postgres",
+            "name": "postgres.query",
+            "resource": "SELECT * FROM users WHERE id = ?",
+            "trace_id": 0x8000_0000_0000_0001u64,
+            "span_id": 3,
+            "parent_id": 2,
+            "start": 1_700_000_000_001_000_000i64,
+            "duration": 1_000_000,
+            "error": 0,
+            "meta": {"db.type": "postgres", "out.host": "10.0.0.2"},
+            "metrics": {"out.port": 5432},
+            "type": "sql",
+        })
+    }
+
+    fn find_span<'a>(traces: &'a TracesData, service: &str) -> &'a Span {
+        let rs = traces
+            .resource_spans
+            .iter()
+            .find(|rs| {
+                rs.resource.as_ref().unwrap().attributes[0]
+                    == string_attribute("service.name", service.to_owned())
+            })
+            .unwrap();
+        &rs.scope_spans[0].spans[0]
+    }
+
+    #[test]
+    fn convert_http_span() {
+        let data = rmp_serde::to_vec_named(&json!([[http_span()]])).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("Datadog-Meta-Lang", "java".parse().unwrap());
+        let traces = decode_traces("/v0.4/traces", &data, &headers).unwrap();
+        assert_eq!(traces.resource_spans.len(), 1);
+        assert_eq!(
+            traces.resource_spans[0]
+                .resource
+                .as_ref()
+                .unwrap()
+                .attributes,
+            vec![
+                string_attribute("service.name", "web".to_owned()),
+                string_attribute("telemetry.sdk.language", "java".to_owned()),
+            ]
+        );
+
+        let span = find_span(&traces, "web");
+        assert_eq!(span.name, "GET /users/{id}");
+        assert_eq!(span.kind, SpanKind::Server as i32);
+        assert_eq!(
+            span.trace_id,
+            vec![0x65, 0x43, 0x21, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(span.span_id, vec![0, 0, 0, 0, 0, 0, 0, 2]);
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.start_time_unix_nano, 1_700_000_000_000_000_000);
+        assert_eq!(span.end_time_unix_nano, 1_700_000_000_005_000_000);
+        assert_eq!(
+            span.status,
+            Some(Status {
+                code: StatusCode::Error as i32,
+                message: "internal error".to_owned(),
+            })
+        );
+        assert_eq!(
+            attribute(span, "http.scheme"),
+            Some(&StringValue("https".to_owned()))
+        );
+        assert_eq!(attribute(span, "http.status_code"), Some(&IntValue(500)));
+        assert_eq!(
+            attribute(span, "dd.span.name"),
+            Some(&StringValue("servlet.request".to_owned()))
+        );
+        assert_eq!(
+            attribute(span, "_sampling_priority_v1"),
+            Some(&DoubleValue(1.0))
+        );
+        assert_eq!(attribute(span, "_dd.p.tid"), None);
+    }
+
+    #[test]
+    fn convert_db_span() {
+        let payload = json!({
+            "container_id": "abc",
+            "language_name": "python",
+            "tracer_version": "2.9.0",
+            "env": "prod",
+            "chunks": [{"priority": 1, "spans": [http_span(), db_span()]}],
+        });
+        let data = rmp_serde::to_vec_named(&payload).unwrap();
+        let traces = decode_traces("/v0.7/traces", &data, &HeaderMap::new()).unwrap();
+        // grouped by service
+        assert_eq!(traces.resource_spans.len(), 2);
+        assert!(traces.resource_spans[1]
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes
+            .contains(&string_attribute(
+                "deployment.environment",
+                "prod".to_owned()
+            )));
+
+        let span = find_span(&traces, "web-postgres");
+        assert_eq!(span.name, "SELECT * FROM users WHERE id = ?");
+        assert_eq!(span.kind, SpanKind::Client as i32);
+        assert_eq!(span.parent_span_id, vec![0, 0, 0, 0, 0, 0, 0, 2]);
+        // no `_dd.p.tid`
+        assert_eq!(span.trace_id[..8], [0; 8]);
+        assert_eq!(span.status, None);
+        assert_eq!(
+            attribute(span, "db.system"),
+            Some(&StringValue("postgresql".to_owned()))
+        );
+        assert_eq!(
+            attribute(span, "db.statement"),
+            Some(&StringValue("SELECT * FROM users WHERE id = ?".to_owned()))
+        );
+        assert_eq!(
+            attribute(span, "net.peer.ip"),
+            Some(&StringValue("10.0.0.2".to_owned()))
+        );
+        assert_eq!(attribute(span, "net.peer.port"), Some(&IntValue(5432)));
+    }
+
+    #[test]
+    fn decode_nil_and_invalid() {
+        let data = rmp_serde::to_vec_named(&json!([[{
+            "service": "web",
+            "trace_id": 1,
+            "span_id": 1,
+            "meta": null,
+            "metrics": null,
+        }]]))
+        .unwrap();
+        let traces = decode_traces("/v0.4/traces", &data, &HeaderMap::new()).unwrap();
+        assert_eq!(find_span(&traces, "web").kind, SpanKind::Internal as i32);
+
+        assert!(decode_traces("/v0.4/traces", b"{}", &HeaderMap::new()).is_err());
+    }
+
+    #[test]
+    fn unsupported_endpoints() {
+        assert!(is_unsupported_endpoint("/info"));
+        assert!(is_unsupported_endpoint("/v0.6/stats"));
+        assert!(is_unsupported_endpoint(
+            "/telemetry/proxy/api/v2/apmtelemetry"
+        ));
+        assert!(is_unsupported_endpoint("/evp_proxy/v2/api/v2/exposures"));
+        assert!(!is_unsupported_endpoint("/v0.4/traces"));
+        assert!(!is_unsupported_endpoint("/api/v1/otel/trace"));
+    }
+}
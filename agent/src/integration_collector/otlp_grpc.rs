@@ -0,0 +1,706 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use log::{debug, error, info, warn};
+use public::{
+    buffer::BatchedBox,
+    proto::{
+        agent::Exception,
+        integration::opentelemetry::proto::{
+            collector::{
+                logs::v1::{
+                    logs_service_server::{LogsService, LogsServiceServer},
+                    ExportLogsServiceRequest, ExportLogsServiceResponse,
+                },
+                metrics::v1::{
+                    metrics_service_server::{MetricsService, MetricsServiceServer},
+                    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+                },
+                trace::v1::{
+                    trace_service_server::{TraceService, TraceServiceServer},
+                    ExportTraceServiceRequest, ExportTraceServiceResponse,
+                },
+            },
+            common::v1::{any_value::Value, AnyValue, KeyValue},
+            logs::v1::{LogRecord, ResourceLogs},
+            metrics::v1::{metric::Data, number_data_point, NumberDataPoint, ResourceMetrics},
+            trace::v1::TracesData,
+        },
+    },
+    queue::DebugSender,
+};
+use serde_json::{json, Map};
+use tokio::time;
+use tonic::{codec::CompressionEncoding, transport::Server, Request, Response, Status};
+
+use super::{
+    escape_line_protocol, fill_otel_trace_data, send_otel_trace_data, ApplicationLog,
+    CompressedMetric, OpenTelemetry, OpenTelemetryCompressed, OtlpRequestMetric, Signal,
+    SourceGuard, TelegrafMetric,
+};
+use crate::{
+    common::flow::L7Stats, config::handler::LogParserConfig, exception::ExceptionHandler,
+    policy::PolicyGetter,
+};
+
+// Receives OTLP over gRPC and feeds the same senders as the HTTP integration:
+// - traces are sent as OpenTelemetry, the same as /api/v1/otel/trace
+// - metrics are converted to the influx line protocol and sent as Telegraf metrics
+// - logs are converted to the JSON of application logs, the same as /api/v1/log
+pub(super) struct OtlpReceiver {
+    pub(super) otel_sender: DebugSender<OpenTelemetry>,
+    pub(super) compressed_otel_sender: DebugSender<OpenTelemetryCompressed>,
+    pub(super) otel_l7_stats_sender: DebugSender<BatchedBox<L7Stats>>,
+    pub(super) telegraf_sender: DebugSender<TelegrafMetric>,
+    pub(super) application_log_sender: DebugSender<ApplicationLog>,
+    pub(super) compressed: Arc<AtomicBool>,
+    pub(super) counter: Arc<CompressedMetric>,
+    pub(super) requests: Arc<OtlpRequestMetric>,
+    pub(super) source_guard: Arc<SourceGuard>,
+    pub(super) local_epc_id: u32,
+    pub(super) policy_getter: Arc<PolicyGetter>,
+    pub(super) time_diff: Arc<AtomicI64>,
+    pub(super) log_parser_config: Arc<LogParserConfig>,
+    pub(super) flow_id: Arc<AtomicU64>,
+    pub(super) trace_disabled: bool,
+    pub(super) metric_disabled: bool,
+    pub(super) log_disabled: bool,
+}
+
+#[tonic::async_trait]
+impl TraceService for OtlpReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        if !self
+            .source_guard
+            .permits(Signal::Trace, peer_addr(&request).ip())
+        {
+            return Err(Status::permission_denied("source address not allowed"));
+        }
+        self.requests.grpc_trace.fetch_add(1, Ordering::Relaxed);
+        if self.trace_disabled {
+            return Ok(Response::new(ExportTraceServiceResponse::default()));
+        }
+        let peer_addr = peer_addr(&request);
+        let traces = TracesData {
+            resource_spans: request.into_inner().resource_spans,
+        };
+        let data = fill_otel_trace_data(
+            peer_addr,
+            traces,
+            self.local_epc_id,
+            self.policy_getter.clone(),
+            self.time_diff.load(Ordering::Relaxed),
+            self.flow_id.clone(),
+            self.log_parser_config.clone(),
+        );
+        send_otel_trace_data(
+            data,
+            self.compressed.load(Ordering::Relaxed),
+            &self.counter,
+            &self.otel_sender,
+            &self.compressed_otel_sender,
+            &self.otel_l7_stats_sender,
+        )
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for OtlpReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        if !self
+            .source_guard
+            .permits(Signal::Metric, peer_addr(&request).ip())
+        {
+            return Err(Status::permission_denied("source address not allowed"));
+        }
+        self.requests.grpc_metric.fetch_add(1, Ordering::Relaxed);
+        if self.metric_disabled {
+            return Ok(Response::new(ExportMetricsServiceResponse::default()));
+        }
+        let lines = metrics_to_line_protocol(&request.into_inner().resource_metrics);
+        if !lines.is_empty() {
+            if let Err(e) = self
+                .telegraf_sender
+                .send(TelegrafMetric(lines.into_bytes()))
+            {
+                warn!("telegraf_sender failed to send data, because {:?}", e);
+            }
+        }
+        Ok(Response::new(ExportMetricsServiceResponse::default()))
+    }
+}
+
+#[tonic::async_trait]
+impl LogsService for OtlpReceiver {
+    async fn export(
+        &self,
+        request: Request<ExportLogsServiceRequest>,
+    ) -> Result<Response<ExportLogsServiceResponse>, Status> {
+        if !self
+            .source_guard
+            .permits(Signal::Log, peer_addr(&request).ip())
+        {
+            return Err(Status::permission_denied("source address not allowed"));
+        }
+        self.requests.grpc_log.fetch_add(1, Ordering::Relaxed);
+        if self.log_disabled {
+            return Ok(Response::new(ExportLogsServiceResponse::default()));
+        }
+        let logs = logs_to_application_logs(&request.into_inner().resource_logs);
+        if !logs.is_empty() {
+            let data = serde_json::to_vec(&logs).map_err(|e| Status::internal(e.to_string()))?;
+            if let Err(e) = self.application_log_sender.send(ApplicationLog(data)) {
+                warn!(
+                    "application_log_sender failed to send data, because {:?}",
+                    e
+                );
+            }
+        }
+        Ok(Response::new(ExportLogsServiceResponse::default()))
+    }
+}
+
+fn peer_addr<T>(request: &Request<T>) -> SocketAddr {
+    request
+        .remote_addr()
+        .unwrap_or_else(|| (Ipv4Addr::UNSPECIFIED, 0).into())
+}
+
+pub(super) async fn serve(
+    addr: SocketAddr,
+    receiver: Arc<OtlpReceiver>,
+    running: Arc<AtomicBool>,
+    exception_handler: ExceptionHandler,
+) {
+    while running.load(Ordering::Relaxed) {
+        info!("otlp grpc receiver listening on grpc://{}", addr);
+        let result = Server::builder()
+            .add_service(
+                TraceServiceServer::from_arc(receiver.clone())
+                    .accept_compressed(CompressionEncoding::Gzip),
+            )
+            .add_service(
+                MetricsServiceServer::from_arc(receiver.clone())
+                    .accept_compressed(CompressionEncoding::Gzip),
+            )
+            .add_service(
+                LogsServiceServer::from_arc(receiver.clone())
+                    .accept_compressed(CompressionEncoding::Gzip),
+            )
+            .serve(addr)
+            .await;
+        if let Err(e) = result {
+            let error_msg = format!("otlp grpc receiver error: {} with addr={}", e, addr);
+            error!("{}", error_msg);
+            exception_handler.set(Exception::IntegrationSocketError, Some(error_msg));
+            time::sleep(Duration::from_secs(60)).await;
+        }
+    }
+}
+
+fn any_value_to_string(value: &AnyValue) -> String {
+    match value.value.as_ref() {
+        Some(Value::StringValue(s)) => s.clone(),
+        Some(Value::BoolValue(b)) => b.to_string(),
+        Some(Value::IntValue(i)) => i.to_string(),
+        Some(Value::DoubleValue(d)) => d.to_string(),
+        Some(Value::BytesValue(b)) => hex::encode(b),
+        Some(Value::ArrayValue(a)) => format!(
+            "[{}]",
+            a.values
+                .iter()
+                .map(any_value_to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Some(Value::KvlistValue(kv)) => format!(
+            "{{{}}}",
+            kv.values
+                .iter()
+                .map(|kv| format!(
+                    "{}={}",
+                    kv.key,
+                    kv.value
+                        .as_ref()
+                        .map(any_value_to_string)
+                        .unwrap_or_default()
+                ))
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        None => String::new(),
+    }
+}
+
+fn attribute_strings(attributes: &[KeyValue]) -> impl Iterator<Item = (&str, String)> {
+    attributes.iter().filter_map(|kv| {
+        let value = any_value_to_string(kv.value.as_ref()?);
+        Some((kv.key.as_str(), value))
+    })
+}
+
+// One line per data point as `name,tag=value field=value timestamp`. Resource and data
+// point attributes are tags. Gauges and sums have a `value` field, histograms and
+// summaries have `count` and `sum` fields.
+fn metrics_to_line_protocol(resource_metrics: &[ResourceMetrics]) -> String {
+    let mut out = String::new();
+    for rm in resource_metrics {
+        let resource_tags = rm
+            .resource
+            .as_ref()
+            .map(|r| attribute_strings(&r.attributes).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for metric in rm.scope_metrics.iter().flat_map(|sm| sm.metrics.iter()) {
+            let mut points = vec![];
+            match metric.data.as_ref() {
+                Some(Data::Gauge(g)) => {
+                    for p in g.data_points.iter() {
+                        if let Some(field) = number_field(p) {
+                            points.push((&p.attributes, p.time_unix_nano, vec![field]));
+                        }
+                    }
+                }
+                Some(Data::Sum(s)) => {
+                    for p in s.data_points.iter() {
+                        if let Some(field) = number_field(p) {
+                            points.push((&p.attributes, p.time_unix_nano, vec![field]));
+                        }
+                    }
+                }
+                Some(Data::Histogram(h)) => {
+                    for p in h.data_points.iter() {
+                        points.push((
+                            &p.attributes,
+                            p.time_unix_nano,
+                            count_and_sum_fields(p.count, p.sum),
+                        ));
+                    }
+                }
+                Some(Data::ExponentialHistogram(h)) => {
+                    for p in h.data_points.iter() {
+                        points.push((
+                            &p.attributes,
+                            p.time_unix_nano,
+                            count_and_sum_fields(p.count, p.sum),
+                        ));
+                    }
+                }
+                Some(Data::Summary(s)) => {
+                    for p in s.data_points.iter() {
+                        points.push((
+                            &p.attributes,
+                            p.time_unix_nano,
+                            count_and_sum_fields(p.count, Some(p.sum)),
+                        ));
+                    }
+                }
+                None => (),
+            }
+            for (attributes, timestamp, fields) in points {
+                escape_line_protocol(&metric.name, &mut out);
+                // empty tag values are not allowed
+                for (key, value) in resource_tags
+                    .iter()
+                    .map(|(k, v)| (*k, v.clone()))
+                    .chain(attribute_strings(attributes))
+                    .filter(|(k, v)| !k.is_empty() && !v.is_empty())
+                {
+                    out.push(',');
+                    escape_line_protocol(key, &mut out);
+                    out.push('=');
+                    escape_line_protocol(&value, &mut out);
+                }
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push(if i == 0 { ' ' } else { ',' });
+                    let _ = write!(out, "{}={}", key, value);
+                }
+                if timestamp != 0 {
+                    let _ = write!(out, " {}", timestamp);
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn number_field(point: &NumberDataPoint) -> Option<(&'static str, String)> {
+    match point.value? {
+        number_data_point::Value::AsDouble(d) if d.is_finite() => Some(("value", d.to_string())),
+        number_data_point::Value::AsInt(i) => Some(("value", format!("{}i", i))),
+        _ => None,
+    }
+}
+
+fn count_and_sum_fields(count: u64, sum: Option<f64>) -> Vec<(&'static str, String)> {
+    let mut fields = vec![("count", format!("{}i", count))];
+    if let Some(sum) = sum.filter(|s| s.is_finite()) {
+        fields.push(("sum", sum.to_string()));
+    }
+    fields
+}
+
+fn severity_level(record: &LogRecord) -> &str {
+    if !record.severity_text.is_empty() {
+        return &record.severity_text;
+    }
+    // https://opentelemetry.io/docs/specs/otel/logs/data-model/#field-severitynumber
+    match record.severity_number {
+        1..=4 => "TRACE",
+        5..=8 => "DEBUG",
+        9..=12 => "INFO",
+        13..=16 => "WARN",
+        17..=20 => "ERROR",
+        21..=24 => "FATAL",
+        _ => "",
+    }
+}
+
+// Converts to the JSON of application logs, resource and log attributes are put into `json`
+fn logs_to_application_logs(resource_logs: &[ResourceLogs]) -> Vec<serde_json::Value> {
+    let mut logs = vec![];
+    for rl in resource_logs {
+        let resource_attributes = rl
+            .resource
+            .as_ref()
+            .map(|r| attribute_strings(&r.attributes).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let resource_attribute = |key: &str| {
+            resource_attributes
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+                .unwrap_or_default()
+        };
+        for record in rl.scope_logs.iter().flat_map(|sl| sl.log_records.iter()) {
+            let message = record
+                .body
+                .as_ref()
+                .map(any_value_to_string)
+                .unwrap_or_default();
+            if message.is_empty() {
+                debug!("otlp log record without body dropped: {:?}", record);
+                continue;
+            }
+            let timestamp = match (record.time_unix_nano, record.observed_time_unix_nano) {
+                (0, 0) => Utc::now(),
+                (0, t) | (t, _) => Utc.timestamp_nanos(t as i64),
+            };
+            let attributes = resource_attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone().into()))
+                .chain(
+                    attribute_strings(&record.attributes).map(|(k, v)| (k.to_string(), v.into())),
+                )
+                .collect::<Map<_, _>>();
+            logs.push(json!({
+                "message": message,
+                "level": severity_level(record),
+                "timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Nanos, true),
+                "app_service": resource_attribute("service.name"),
+                "trace_id": hex::encode(&record.trace_id),
+                "span_id": hex::encode(&record.span_id),
+                "kubernetes": {
+                    "pod_name": resource_attribute("k8s.pod.name"),
+                    "pod_ip": resource_attribute("k8s.pod.ip"),
+                },
+                "json": attributes,
+            }));
+        }
+    }
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::Message;
+    use public::{
+        debug::QueueDebugger,
+        proto::integration::opentelemetry::proto::{
+            collector::{
+                logs::v1::logs_service_client::LogsServiceClient,
+                metrics::v1::metrics_service_client::MetricsServiceClient,
+                trace::v1::trace_service_client::TraceServiceClient,
+            },
+            logs::v1::ScopeLogs,
+            metrics::v1::{Gauge, Histogram, HistogramDataPoint, Metric, ScopeMetrics},
+            resource::v1::Resource,
+            trace::v1::{span::SpanKind, ResourceSpans, ScopeSpans, Span},
+        },
+        queue::{self, Receiver},
+    };
+    use tonic::transport::Channel;
+
+    use crate::policy::Policy;
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.to_owned())),
+            }),
+        }
+    }
+
+    fn resource(service: &str) -> Option<Resource> {
+        Some(Resource {
+            attributes: vec![string_attribute("service.name", service)],
+            ..Default::default()
+        })
+    }
+
+    struct Receivers {
+        otel: Receiver<OpenTelemetry>,
+        telegraf: Receiver<TelegrafMetric>,
+        application_log: Receiver<ApplicationLog>,
+    }
+
+    fn new_receiver(trace_disabled: bool) -> (OtlpReceiver, Receivers) {
+        let (_, mut policy_getter) = Policy::new(1, 0, 1 << 10, 1 << 14, false, false);
+        policy_getter.disable();
+        let queue_debugger = QueueDebugger::new();
+        let (otel_sender, otel, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let (compressed_otel_sender, _, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let (otel_l7_stats_sender, _, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let (telegraf_sender, telegraf, _) = queue::bounded_with_debug(16, "", &queue_debugger);
+        let (application_log_sender, application_log, _) =
+            queue::bounded_with_debug(16, "", &queue_debugger);
+        let receiver = OtlpReceiver {
+            otel_sender,
+            compressed_otel_sender,
+            otel_l7_stats_sender,
+            telegraf_sender,
+            application_log_sender,
+            compressed: Default::default(),
+            counter: Default::default(),
+            requests: Default::default(),
+            source_guard: Default::default(),
+            local_epc_id: 0,
+            policy_getter: Arc::new(policy_getter),
+            time_diff: Default::default(),
+            log_parser_config: Default::default(),
+            flow_id: Default::default(),
+            trace_disabled,
+            metric_disabled: false,
+            log_disabled: false,
+        };
+        (
+            receiver,
+            Receivers {
+                otel,
+                telegraf,
+                application_log,
+            },
+        )
+    }
+
+    // Starts the receiver on a free port of 127.0.0.1 and connects to it
+    async fn start(receiver: Arc<OtlpReceiver>) -> Channel {
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(serve(
+            addr,
+            receiver,
+            Arc::new(AtomicBool::new(true)),
+            ExceptionHandler::default(),
+        ));
+        for _ in 0..50 {
+            if let Ok(channel) = Channel::from_shared(format!("http://{}", addr))
+                .unwrap()
+                .connect()
+                .await
+            {
+                return channel;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("otlp grpc receiver not started on {}", addr);
+    }
+
+    fn export_traces() -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: resource("svc-a"),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        name: "GET /".to_owned(),
+                        kind: SpanKind::Server as i32,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn export_metrics() -> ExportMetricsServiceRequest {
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: resource("svc-a"),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![
+                        Metric {
+                            name: "cpu usage".to_owned(),
+                            data: Some(Data::Gauge(Gauge {
+                                data_points: vec![NumberDataPoint {
+                                    attributes: vec![string_attribute("core", "0")],
+                                    time_unix_nano: 1_700_000_000_000_000_000,
+                                    value: Some(number_data_point::Value::AsDouble(0.5)),
+                                    ..Default::default()
+                                }],
+                            })),
+                            ..Default::default()
+                        },
+                        Metric {
+                            name: "latency".to_owned(),
+                            data: Some(Data::Histogram(Histogram {
+                                data_points: vec![HistogramDataPoint {
+                                    time_unix_nano: 1_700_000_000_000_000_000,
+                                    count: 3,
+                                    sum: Some(1.5),
+                                    ..Default::default()
+                                }],
+                                ..Default::default()
+                            })),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn export_logs() -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: resource("svc-a"),
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![
+                        LogRecord {
+                            time_unix_nano: 1_700_000_000_123_456_789,
+                            severity_number: 17,
+                            body: Some(AnyValue {
+                                value: Some(Value::StringValue("connection refused".to_owned())),
+                            }),
+                            attributes: vec![string_attribute("thread", "main")],
+                            trace_id: vec![1; 16],
+                            span_id: vec![2; 8],
+                            ..Default::default()
+                        },
+                        // dropped without body
+                        LogRecord::default(),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn export_with_grpc_clients() {
+        let (receiver, receivers) = new_receiver(false);
+        let receiver = Arc::new(receiver);
+        let channel = start(receiver.clone()).await;
+        let timeout = Some(Duration::from_secs(1));
+
+        let mut client =
+            TraceServiceClient::new(channel.clone()).send_compressed(CompressionEncoding::Gzip);
+        client.export(export_traces()).await.unwrap();
+        let traces =
+            TracesData::decode(receivers.otel.recv(timeout).unwrap().0.as_slice()).unwrap();
+        let attributes = &traces.resource_spans[0]
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes;
+        assert!(attributes.contains(&string_attribute("app.host.ip", "127.0.0.1")));
+
+        let mut client =
+            MetricsServiceClient::new(channel.clone()).send_compressed(CompressionEncoding::Gzip);
+        client.export(export_metrics()).await.unwrap();
+        let metrics = receivers.telegraf.recv(timeout).unwrap().0;
+        assert_eq!(
+            String::from_utf8(metrics).unwrap(),
+            "cpu\\ usage,service.name=svc-a,core=0 value=0.5 1700000000000000000\n\
+             latency,service.name=svc-a count=3i,sum=1.5 1700000000000000000\n"
+        );
+
+        let mut client = LogsServiceClient::new(channel).send_compressed(CompressionEncoding::Gzip);
+        client.export(export_logs()).await.unwrap();
+        let logs: serde_json::Value =
+            serde_json::from_slice(&receivers.application_log.recv(timeout).unwrap().0).unwrap();
+        assert_eq!(
+            logs,
+            json!([{
+                "message": "connection refused",
+                "level": "ERROR",
+                "timestamp": "2023-11-14T22:13:20.123456789Z",
+                "app_service": "svc-a",
+                "trace_id": "01".repeat(16),
+                "span_id": "02".repeat(8),
+                "kubernetes": {"pod_name": "", "pod_ip": ""},
+                "json": {"service.name": "svc-a", "thread": "main"},
+            }])
+        );
+
+        let requests = &receiver.requests;
+        assert_eq!(requests.grpc_trace.load(Ordering::Relaxed), 1);
+        assert_eq!(requests.grpc_metric.load(Ordering::Relaxed), 1);
+        assert_eq!(requests.grpc_log.load(Ordering::Relaxed), 1);
+        assert_eq!(requests.http_trace.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn export_disabled_signal() {
+        let (receiver, receivers) = new_receiver(true);
+        let receiver = Arc::new(receiver);
+        let channel = start(receiver.clone()).await;
+
+        let mut client = TraceServiceClient::new(channel);
+        client.export(export_traces()).await.unwrap();
+        assert!(receivers
+            .otel
+            .recv(Some(Duration::from_millis(100)))
+            .is_err());
+        // requests are counted even if the signal is disabled
+        assert_eq!(receiver.requests.grpc_trace.load(Ordering::Relaxed), 1);
+    }
+}
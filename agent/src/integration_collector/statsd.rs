@@ -0,0 +1,581 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// StatsD and DogStatsD receiver, metrics are aggregated over the flush interval and sent
+// as Telegraf metrics in the influx line protocol.
+// https://github.com/statsd/statsd/blob/master/docs/metric_types.md
+// https://docs.datadoghq.com/developers/dogstatsd/datagram_shell
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use public::queue::DebugSender;
+#[cfg(unix)]
+use tokio::net::UnixDatagram;
+use tokio::{net::UdpSocket, select, time};
+
+use super::{escape_line_protocol, Signal, SourceGuard, StatsdMetric, TelegrafMetric};
+
+// Series exceeding the limit are dropped until the next flush
+const MAX_SERIES: usize = 1 << 16;
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum MetricType {
+    Counter,
+    Gauge,
+    Timing,
+    Set,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Timing => "timing",
+            Self::Set => "set",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Value<'a> {
+    Number(f64),
+    // gauges prefixed with `+` or `-` are changes of the last value
+    GaugeDelta(f64),
+    Member(&'a str),
+}
+
+#[derive(Debug, PartialEq)]
+struct Sample<'a> {
+    name: &'a str,
+    metric_type: MetricType,
+    values: Vec<Value<'a>>,
+    sample_rate: f64,
+    tags: Vec<(&'a str, &'a str)>,
+}
+
+// <name>:<value>[:<value>...]|<type>[|@<sample_rate>][|#<tag>:<value>,<tag>][|c:<container>][|T<timestamp>]
+// Returns None if the line is malformed
+fn parse_line(line: &str) -> Option<Sample> {
+    let (name, rest) = line.split_once(':')?;
+    if name.is_empty() || name.contains('|') {
+        return None;
+    }
+    let mut sections = rest.split('|');
+    let values = sections.next()?;
+    let metric_type = match sections.next()? {
+        "c" => MetricType::Counter,
+        "g" => MetricType::Gauge,
+        "ms" | "h" | "d" => MetricType::Timing,
+        "s" => MetricType::Set,
+        _ => return None,
+    };
+    let mut sample_rate = 1.0;
+    let mut tags = vec![];
+    for section in sections {
+        if let Some(rate) = section.strip_prefix('@') {
+            sample_rate = rate.parse::<f64>().ok()?;
+            if !(sample_rate > 0.0 && sample_rate <= 1.0) {
+                return None;
+            }
+        } else if let Some(t) = section.strip_prefix('#') {
+            // only the first colon separates the key, values such as URLs can contain colons
+            for tag in t.split(',').filter(|t| !t.is_empty()) {
+                tags.push(tag.split_once(':').unwrap_or((tag, "true")));
+            }
+        }
+        // container ids and timestamps are ignored
+    }
+    let values = values
+        .split(':')
+        .map(|v| match metric_type {
+            MetricType::Set if !v.is_empty() => Some(Value::Member(v)),
+            MetricType::Set => None,
+            MetricType::Gauge if v.starts_with(['+', '-']) => {
+                v.parse::<f64>().ok().map(Value::GaugeDelta)
+            }
+            _ => v.parse::<f64>().ok().map(Value::Number),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if values.iter().any(|v| match v {
+        Value::Number(n) | Value::GaugeDelta(n) => !n.is_finite(),
+        _ => false,
+    }) {
+        return None;
+    }
+    Some(Sample {
+        name,
+        metric_type,
+        values,
+        sample_rate,
+        tags,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct SeriesKey {
+    name: String,
+    metric_type: MetricType,
+    tags: Vec<(String, String)>,
+}
+
+enum Series {
+    Counter(f64),
+    Gauge(f64),
+    // (count adjusted by sample rates, values)
+    Timing(f64, Vec<f64>),
+    Set(HashSet<String>),
+}
+
+// Counters, timings and sets are reset on each flush, gauges keep the last value and are
+// flushed every time, the same as StatsD
+pub(super) struct Aggregator {
+    label_length: usize,
+    value_length: usize,
+    series: HashMap<SeriesKey, Series>,
+}
+
+impl Aggregator {
+    // label_length and value_length limit the total length of tag keys and values of a series,
+    // the same as prometheus extra labels
+    pub(super) fn new(label_length: usize, value_length: usize) -> Self {
+        Self {
+            label_length,
+            value_length,
+            series: HashMap::new(),
+        }
+    }
+
+    fn key(&self, sample: &Sample) -> SeriesKey {
+        let (mut labels_count, mut values_count) = (0, 0);
+        let mut tags = vec![];
+        for (k, v) in sample.tags.iter() {
+            labels_count += k.len();
+            values_count += v.len();
+            if labels_count > self.label_length || values_count > self.value_length {
+                break;
+            }
+            tags.push((k.to_string(), v.to_string()));
+        }
+        tags.sort_unstable();
+        tags.dedup_by(|a, b| a.0 == b.0);
+        SeriesKey {
+            name: sample.name.to_owned(),
+            metric_type: sample.metric_type,
+            tags,
+        }
+    }
+
+    // Returns false if the sample is dropped for too many series
+    fn add(&mut self, sample: Sample) -> bool {
+        let key = self.key(&sample);
+        if self.series.len() >= MAX_SERIES && !self.series.contains_key(&key) {
+            return false;
+        }
+        let series = self
+            .series
+            .entry(key)
+            .or_insert_with(|| match sample.metric_type {
+                MetricType::Counter => Series::Counter(0.0),
+                MetricType::Gauge => Series::Gauge(0.0),
+                MetricType::Timing => Series::Timing(0.0, vec![]),
+                MetricType::Set => Series::Set(HashSet::new()),
+            });
+        for value in sample.values {
+            match (&mut *series, value) {
+                (Series::Counter(c), Value::Number(n)) => *c += n / sample.sample_rate,
+                (Series::Gauge(g), Value::Number(n)) => *g = n,
+                (Series::Gauge(g), Value::GaugeDelta(n)) => *g += n,
+                (Series::Timing(count, values), Value::Number(n)) => {
+                    *count += 1.0 / sample.sample_rate;
+                    values.push(n);
+                }
+                (Series::Set(s), Value::Member(m)) => {
+                    s.insert(m.to_owned());
+                }
+                _ => (),
+            }
+        }
+        true
+    }
+
+    // Returns metrics in the influx line protocol
+    fn flush(&mut self, timestamp: u64) -> String {
+        let mut out = String::new();
+        for (key, series) in self.series.iter_mut() {
+            escape_line_protocol(&key.name, &mut out);
+            let _ = write!(out, ",metric_type={}", key.metric_type.as_str());
+            for (k, v) in key
+                .tags
+                .iter()
+                .filter(|(k, v)| !k.is_empty() && !v.is_empty())
+            {
+                out.push(',');
+                escape_line_protocol(k, &mut out);
+                out.push('=');
+                escape_line_protocol(v, &mut out);
+            }
+            match series {
+                Series::Counter(c) | Series::Gauge(c) => {
+                    let _ = write!(out, " value={}", c);
+                }
+                Series::Timing(count, values) => {
+                    values.sort_unstable_by(|a, b| a.total_cmp(b));
+                    let sum = values.iter().sum::<f64>();
+                    // nearest rank
+                    let percentile = |p: f64| {
+                        values
+                            [((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1]
+                    };
+                    let _ = write!(
+                        out,
+                        " count={},sum={},min={},max={},mean={},p50={},p90={},p99={}",
+                        count,
+                        sum,
+                        values[0],
+                        values[values.len() - 1],
+                        sum / values.len() as f64,
+                        percentile(0.5),
+                        percentile(0.9),
+                        percentile(0.99),
+                    );
+                }
+                Series::Set(s) => {
+                    let _ = write!(out, " value={}i", s.len());
+                }
+            }
+            let _ = writeln!(out, " {}", timestamp);
+        }
+        self.series
+            .retain(|_, series| matches!(series, Series::Gauge(_)));
+        out
+    }
+
+    fn handle_datagram(&mut self, data: &[u8], counter: &StatsdMetric) {
+        for line in String::from_utf8_lossy(data).lines() {
+            let line = line.trim();
+            // events and service checks of DogStatsD are not supported
+            if line.is_empty() || line.starts_with("_e{") || line.starts_with("_sc|") {
+                continue;
+            }
+            counter.lines.fetch_add(1, Ordering::Relaxed);
+            match parse_line(line) {
+                Some(sample) => {
+                    if !self.add(sample) {
+                        counter.dropped_lines.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                None => {
+                    counter.malformed_lines.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn recv_unix(socket: &Option<UnixDatagram>, buf: &mut [u8]) -> std::io::Result<usize> {
+    match socket {
+        Some(s) => s.recv(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn recv_unix(_: &Option<()>, _: &mut [u8]) -> std::io::Result<usize> {
+    std::future::pending().await
+}
+
+async fn recv_udp(
+    socket: &Option<UdpSocket>,
+    buf: &mut [u8],
+) -> std::io::Result<(usize, SocketAddr)> {
+    match socket {
+        Some(s) => s.recv_from(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+pub(super) async fn serve(
+    udp_addr: Option<SocketAddr>,
+    unix_socket_path: Option<PathBuf>,
+    flush_interval: Duration,
+    mut aggregator: Aggregator,
+    telegraf_sender: DebugSender<TelegrafMetric>,
+    counter: Arc<StatsdMetric>,
+    source_guard: Arc<SourceGuard>,
+) -> std::io::Result<()> {
+    let udp_socket = match udp_addr {
+        Some(addr) => Some(UdpSocket::bind(addr).await?),
+        None => None,
+    };
+    #[cfg(unix)]
+    let unix_socket = match unix_socket_path.as_ref() {
+        Some(path) => {
+            super::remove_stale_socket(path)?;
+            Some(UnixDatagram::bind(path)?)
+        }
+        None => None,
+    };
+    #[cfg(not(unix))]
+    let unix_socket = unix_socket_path.map(|_| ());
+    info!(
+        "statsd receiver listening on udp {:?} and unix socket {:?}",
+        udp_addr, unix_socket_path
+    );
+
+    let (mut udp_buf, mut unix_buf) = (vec![0; MAX_DATAGRAM_SIZE], vec![0; MAX_DATAGRAM_SIZE]);
+    let mut ticker = time::interval(flush_interval);
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    loop {
+        select! {
+            r = recv_udp(&udp_socket, &mut udp_buf) => {
+                let (n, peer_addr) = r?;
+                if !source_guard.permits(Signal::Metric, peer_addr.ip()) {
+                    counter.rejected_datagrams.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                aggregator.handle_datagram(&udp_buf[..n], &counter);
+            }
+            r = recv_unix(&unix_socket, &mut unix_buf) => {
+                let n = r?;
+                // peers of the unix socket are taken as localhost like the HTTP listeners
+                if !source_guard.permits(Signal::Metric, Ipv4Addr::LOCALHOST.into()) {
+                    counter.rejected_datagrams.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+                aggregator.handle_datagram(&unix_buf[..n], &counter);
+            }
+            _ = ticker.tick() => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64;
+                let lines = aggregator.flush(timestamp);
+                if !lines.is_empty() {
+                    if let Err(e) = telegraf_sender.send(TelegrafMetric(lines.into_bytes())) {
+                        warn!("telegraf_sender failed to send data, because {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::{SignalSourceFilter, SourceFilter};
+    use public::{debug::QueueDebugger, queue};
+
+    fn flush_lines(aggregator: &mut Aggregator) -> Vec<String> {
+        let mut lines = aggregator
+            .flush(1)
+            .lines()
+            .map(|l| l.to_owned())
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+        lines
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            parse_line("page.views:1|c|@0.5|#env:prod,url:http://a.b:8080/x,canary|c:abc|T1"),
+            Some(Sample {
+                name: "page.views",
+                metric_type: MetricType::Counter,
+                values: vec![Value::Number(1.0)],
+                sample_rate: 0.5,
+                tags: vec![
+                    ("env", "prod"),
+                    ("url", "http://a.b:8080/x"),
+                    ("canary", "true")
+                ],
+            })
+        );
+        assert_eq!(
+            parse_line("temp:-3|g").map(|s| s.values),
+            Some(vec![Value::GaugeDelta(-3.0)])
+        );
+        assert_eq!(
+            parse_line("latency:1:2.5|ms").map(|s| s.values),
+            Some(vec![Value::Number(1.0), Value::Number(2.5)])
+        );
+        assert_eq!(
+            parse_line("users:alice|s").map(|s| s.values),
+            Some(vec![Value::Member("alice")])
+        );
+        for malformed in [
+            "page.views",
+            "page.views:1",
+            ":1|c",
+            "page.views:x|c",
+            "page.views:1|x",
+            "page.views:1|c|@0",
+            "page.views:1|c|@2",
+            "page.views:NaN|c",
+            "users:|s",
+        ] {
+            assert_eq!(parse_line(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn flush_counters_and_gauges() {
+        let counter = StatsdMetric::default();
+        let mut aggregator = Aggregator::new(1024, 4096);
+        aggregator.handle_datagram(
+            b"hits:1|c\nhits:2|c|@0.5\nconn:10|g\nconn:+5|g\nmalformed\n_e{1,1}:a|b",
+            &counter,
+        );
+        assert_eq!(
+            flush_lines(&mut aggregator),
+            vec![
+                "conn,metric_type=gauge value=15 1",
+                "hits,metric_type=counter value=5 1",
+            ]
+        );
+        assert_eq!(counter.lines.load(Ordering::Relaxed), 5);
+        assert_eq!(counter.malformed_lines.load(Ordering::Relaxed), 1);
+
+        // counters are reset, gauges keep the last value
+        assert_eq!(
+            flush_lines(&mut aggregator),
+            vec!["conn,metric_type=gauge value=15 1"]
+        );
+        aggregator.handle_datagram(b"conn:-20|g\nhits:1|c", &counter);
+        assert_eq!(
+            flush_lines(&mut aggregator),
+            vec![
+                "conn,metric_type=gauge value=-5 1",
+                "hits,metric_type=counter value=1 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn flush_timings_and_sets() {
+        let counter = StatsdMetric::default();
+        let mut aggregator = Aggregator::new(1024, 4096);
+        aggregator.handle_datagram(
+            b"rt:1:2:3:4|ms|@0.5|#path:/a\nusers:a|s\nusers:b|s\nusers:a|s",
+            &counter,
+        );
+        assert_eq!(
+            flush_lines(&mut aggregator),
+            vec![
+                "rt,metric_type=timing,path=/a count=8,sum=10,min=1,max=4,mean=2.5,p50=2,p90=4,p99=4 1",
+                "users,metric_type=set value=2i 1",
+            ]
+        );
+        assert!(flush_lines(&mut aggregator).is_empty());
+    }
+
+    #[test]
+    fn limit_tags() {
+        let counter = StatsdMetric::default();
+        // tags are kept in order until the total length of values exceeds 8
+        let mut aggregator = Aggregator::new(1024, 8);
+        aggregator.handle_datagram(b"hits:1|c|#a:1234,b:5678,c:9", &counter);
+        aggregator.handle_datagram(b"hits:1|c|#b:5678,a:1234", &counter);
+        assert_eq!(
+            flush_lines(&mut aggregator),
+            vec!["hits,metric_type=counter,a=1234,b=5678 value=2 1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn receive_from_udp() {
+        let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (sender, receiver, _) = queue::bounded_with_debug(16, "", &QueueDebugger::new());
+        let counter = Arc::new(StatsdMetric::default());
+        let server = tokio::spawn(serve(
+            Some(addr),
+            None,
+            Duration::from_millis(100),
+            Aggregator::new(1024, 4096),
+            sender,
+            counter.clone(),
+            Arc::new(SourceGuard::default()),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for _ in 0..50 {
+            client.send_to(b"hits:1|c|#env:prod", addr).await.unwrap();
+            time::sleep(Duration::from_millis(10)).await;
+            if counter.lines.load(Ordering::Relaxed) > 0 {
+                break;
+            }
+        }
+        let metrics = receiver.recv(Some(Duration::from_secs(1))).unwrap().0;
+        assert!(String::from_utf8(metrics)
+            .unwrap()
+            .starts_with("hits,metric_type=counter,env=prod value="));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn reject_by_source_filter() {
+        let addr = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (sender, _receiver, _) = queue::bounded_with_debug(16, "", &QueueDebugger::new());
+        let counter = Arc::new(StatsdMetric::default());
+        let source_guard = Arc::new(SourceGuard::default());
+        source_guard.update(&SourceFilter {
+            metric: SignalSourceFilter {
+                allow: vec![],
+                deny: vec!["127.0.0.0/8".to_owned()],
+            },
+            ..Default::default()
+        });
+        let server = tokio::spawn(serve(
+            Some(addr),
+            None,
+            Duration::from_millis(100),
+            Aggregator::new(1024, 4096),
+            sender,
+            counter.clone(),
+            source_guard,
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for _ in 0..50 {
+            client.send_to(b"hits:1|c", addr).await.unwrap();
+            time::sleep(Duration::from_millis(10)).await;
+            if counter.rejected_datagrams.load(Ordering::Relaxed) > 0 {
+                break;
+            }
+        }
+        assert!(counter.rejected_datagrams.load(Ordering::Relaxed) > 0);
+        assert_eq!(counter.lines.load(Ordering::Relaxed), 0);
+        server.abort();
+    }
+}
@@ -14,7 +14,11 @@
  * limitations under the License.
  */
 
-use std::net::{IpAddr, Ipv4Addr};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    net::{IpAddr, Ipv4Addr},
+};
 
 use bitflags::bitflags;
 use num_enum::TryFromPrimitive;
@@ -40,6 +44,8 @@ pub struct Document {
     pub tagger: Tagger,
     pub meter: Meter,
     pub flags: DocumentFlag,
+    // the largest xFlow sampling rate of the merged meters, 0 if not sampled
+    pub sampling_rate: u32,
 }
 
 impl Document {
@@ -49,11 +55,13 @@ impl Document {
             tagger: Tagger::default(),
             meter: m,
             flags: DocumentFlag::default(),
+            sampling_rate: 0,
         }
     }
 
     pub fn sequential_merge(&mut self, other: &Document) {
-        self.meter.sequential_merge(&other.meter)
+        self.meter.sequential_merge(&other.meter);
+        self.sampling_rate = self.sampling_rate.max(other.sampling_rate);
     }
 
     pub fn reverse(&mut self) {
@@ -79,6 +87,7 @@ impl From<Document> for metric::Document {
             tag: Some(d.tagger.into()),
             meter: Some(d.meter.into()),
             flags: d.flags.bits(),
+            sampling_rate: d.sampling_rate,
         }
     }
 }
@@ -105,6 +114,18 @@ impl Sendable for BoxedDocument {
     fn file_name(&self) -> &str {
         "flow_metrics"
     }
+
+    fn hash_key(&self) -> Option<u64> {
+        let tagger = &self.0.tagger;
+        let mut hasher = DefaultHasher::new();
+        tagger.ip.hash(&mut hasher);
+        tagger.ip1.hash(&mut hasher);
+        tagger.l3_epc_id.hash(&mut hasher);
+        tagger.l3_epc_id1.hash(&mut hasher);
+        u8::from(tagger.protocol).hash(&mut hasher);
+        tagger.server_port.hash(&mut hasher);
+        Some(hasher.finish())
+    }
 }
 
 bitflags! {
@@ -112,6 +133,7 @@ bitflags! {
     pub struct DocumentFlag: u32 {
         const NONE = 0; // PER_MINUTE_METRICS
         const PER_SECOND_METRICS = 1<<0;
+        const SAMPLED = 1<<1; // counters are extrapolated from sampled xFlow packets
    }
 }
 
@@ -852,6 +852,10 @@ impl Synchronizer {
                 static_config.agent_unique_identifier,
             ) as i32),
             current_grpc_buffer_size: Some(grpc_buffer_size),
+            #[cfg(all(unix, feature = "libtrace"))]
+            ebpf_capability: crate::ebpf_dispatcher::capability::report()
+                .as_ref()
+                .map(pb::EbpfCapability::from),
             custom_app_config: Some(pb::CustomAppConfig {
                 version: Some(status.custom_app.version),
                 ..Default::default()
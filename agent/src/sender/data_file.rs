@@ -0,0 +1,299 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{create_dir_all, read_dir, remove_file, rename, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use log::{info, warn};
+
+// Returns the rotation timestamp of files named as {name}.{agent_id}.{timestamp}
+fn rotation_timestamp<'a>(file_name: &'a str, name: &str) -> Option<&'a str> {
+    let (agent_id, timestamp) = file_name
+        .strip_prefix(name)?
+        .strip_prefix('.')?
+        .split_once('.')?;
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if is_number(agent_id) && is_number(timestamp) {
+        Some(timestamp)
+    } else {
+        None
+    }
+}
+
+// The data file of the standalone mode
+//
+// Data is written to {name}, which is renamed to {name}.{agent_id}.{timestamp} when it exceeds
+// max_size or is older than rotation_interval, so that a file appearing under the rotated name is
+// never written again. Only the latest max_files rotated files are retained.
+pub(crate) struct RotatingFile {
+    dir: PathBuf,
+    name: String,
+    agent_id: u16,
+    max_size: u64,
+    // rotation by time is disabled if zero
+    rotation_interval: Duration,
+    max_files: usize,
+
+    writer: Option<BufWriter<File>>,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        name: &str,
+        agent_id: u16,
+        max_size: u64,
+        rotation_interval: Duration,
+        max_files: usize,
+    ) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            name: name.to_owned(),
+            agent_id,
+            max_size,
+            rotation_interval,
+            max_files,
+            writer: None,
+            size: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    fn active_path(&self) -> PathBuf {
+        self.dir.join(&self.name)
+    }
+
+    // Size of the file being written
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        create_dir_all(&self.dir)?;
+        // data left by the last run is kept
+        let f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.active_path())?;
+        self.size = f.metadata()?.len();
+        self.opened_at = Instant::now();
+        self.writer = Some(BufWriter::new(f));
+        Ok(())
+    }
+
+    // Returns true if the file is rotated after writing
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<bool> {
+        if self.writer.is_none() {
+            self.open()?;
+        }
+        self.writer.as_mut().unwrap().write_all(buf)?;
+        self.size += buf.len() as u64;
+        if self.size >= self.max_size {
+            self.rotate()?;
+            return Ok(true);
+        }
+        self.rotate_if_expired()
+    }
+
+    // Returns true if the file is rotated for rotation_interval
+    pub fn rotate_if_expired(&mut self) -> io::Result<bool> {
+        if self.writer.is_none()
+            || self.size == 0
+            || self.rotation_interval.is_zero()
+            || self.opened_at.elapsed() < self.rotation_interval
+        {
+            return Ok(false);
+        }
+        self.rotate()?;
+        Ok(true)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(w) => w.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // The file is reopened on the next write
+    pub fn close(&mut self) {
+        if let Some(mut w) = self.writer.take() {
+            let _ = w.flush();
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut w) = self.writer.take() {
+            w.flush()?;
+        }
+        let rotated = self.dir.join(format!(
+            "{}.{}.{}",
+            self.name,
+            self.agent_id,
+            Utc::now().format("%Y%m%d%H%M%S%9f")
+        ));
+        rename(self.active_path(), &rotated)?;
+        self.size = 0;
+        info!("data file rotated to {}", rotated.display());
+        self.prune()
+    }
+
+    // Deletes the oldest rotated files exceeding max_files
+    fn prune(&self) -> io::Result<()> {
+        let mut rotated = read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|file_name| {
+                let timestamp = rotation_timestamp(&file_name, &self.name)?.to_owned();
+                Some((timestamp, file_name))
+            })
+            .collect::<Vec<_>>();
+        if rotated.len() <= self.max_files {
+            return Ok(());
+        }
+        rotated.sort_unstable();
+        for (_, file_name) in rotated.iter().take(rotated.len() - self.max_files) {
+            let path = self.dir.join(file_name);
+            if let Err(e) = remove_file(&path) {
+                warn!("remove data file {} failed: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{read_to_string, remove_dir_all};
+    use std::thread;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "deepflow-data-file-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = remove_dir_all(&dir);
+        dir
+    }
+
+    fn rotated_files(dir: &Path, name: &str) -> Vec<String> {
+        let mut files = read_dir(dir)
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter(|file_name| rotation_timestamp(file_name, name).is_some())
+            .collect::<Vec<_>>();
+        files
+            .sort_unstable_by(|a, b| rotation_timestamp(a, name).cmp(&rotation_timestamp(b, name)));
+        files
+    }
+
+    #[test]
+    fn parse_rotation_timestamp() {
+        assert_eq!(
+            rotation_timestamp("l7_flow_log.3.20240102030405000000001", "l7_flow_log"),
+            Some("20240102030405000000001")
+        );
+        assert_eq!(rotation_timestamp("l7_flow_log", "l7_flow_log"), None);
+        assert_eq!(rotation_timestamp("l7_flow_log.pre", "l7_flow_log"), None);
+        assert_eq!(rotation_timestamp("l7_flow_log.3.", "l7_flow_log"), None);
+        assert_eq!(
+            rotation_timestamp("l4_flow_log.3.2024", "l7_flow_log"),
+            None
+        );
+    }
+
+    #[test]
+    fn rotate_at_size() {
+        let dir = test_dir("size");
+        let mut file = RotatingFile::new(&dir, "l7_flow_log", 3, 10, Duration::ZERO, 10);
+        assert!(!file.write(b"12345").unwrap());
+        assert_eq!(file.size(), 5);
+        assert!(dir.join("l7_flow_log").exists());
+        assert!(rotated_files(&dir, "l7_flow_log").is_empty());
+
+        // rotated when reaching the threshold
+        assert!(file.write(b"67890").unwrap());
+        assert_eq!(file.size(), 0);
+        assert!(!dir.join("l7_flow_log").exists());
+        let rotated = rotated_files(&dir, "l7_flow_log");
+        assert_eq!(rotated.len(), 1);
+        assert!(rotated[0].starts_with("l7_flow_log.3."));
+        assert_eq!(read_to_string(dir.join(&rotated[0])).unwrap(), "1234567890");
+
+        // data left in the active file is appended
+        assert!(!file.write(b"abc").unwrap());
+        drop(file);
+        let mut file = RotatingFile::new(&dir, "l7_flow_log", 3, 10, Duration::ZERO, 10);
+        assert!(!file.write(b"def").unwrap());
+        assert_eq!(file.size(), 6);
+        file.flush().unwrap();
+        assert_eq!(read_to_string(dir.join("l7_flow_log")).unwrap(), "abcdef");
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_at_interval() {
+        let dir = test_dir("interval");
+        let mut file =
+            RotatingFile::new(&dir, "metrics", 0, 1 << 20, Duration::from_millis(50), 10);
+        // not rotated before anything is written
+        assert!(!file.rotate_if_expired().unwrap());
+        assert!(!file.write(b"123").unwrap());
+        assert!(!file.rotate_if_expired().unwrap());
+        thread::sleep(Duration::from_millis(60));
+        assert!(file.rotate_if_expired().unwrap());
+        assert_eq!(rotated_files(&dir, "metrics").len(), 1);
+
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_oldest() {
+        let dir = test_dir("prune");
+        create_dir_all(&dir).unwrap();
+        // rotated by an agent with another id, and files of other names
+        File::create(dir.join("l4_flow_log.1.10000000000000000000000")).unwrap();
+        File::create(dir.join("l4_flow_log.2.10000000000000000000001")).unwrap();
+        File::create(dir.join("l7_flow_log.2.10000000000000000000001")).unwrap();
+
+        let mut file = RotatingFile::new(&dir, "l4_flow_log", 3, 4, Duration::ZERO, 3);
+        for _ in 0..4 {
+            assert!(file.write(b"1234").unwrap());
+        }
+        let rotated = rotated_files(&dir, "l4_flow_log");
+        assert_eq!(rotated.len(), 3);
+        // the oldest are deleted first, regardless of the agent id
+        assert!(rotated.iter().all(|f| f.starts_with("l4_flow_log.3.")));
+        assert!(!dir.join("l4_flow_log.1.10000000000000000000000").exists());
+        assert!(dir.join("l7_flow_log.2.10000000000000000000001").exists());
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
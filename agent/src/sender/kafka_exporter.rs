@@ -0,0 +1,454 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use rdkafka::{
+    producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext},
+    ClientConfig, ClientContext,
+};
+
+use super::{uniform_sender::Tee, QUEUE_BATCH_SIZE};
+use crate::config::{KafkaSerialization, OutputsKafka};
+use crate::utils::stats::{
+    self, Collector, Countable, Counter, CounterType, CounterValue, QueueStats, RefCountable,
+};
+use public::{
+    queue::{self, Error, Receiver},
+    sender::Sendable,
+};
+
+#[derive(Debug, Default)]
+pub struct KafkaExporterCounter {
+    // copies received from the tee
+    rx: AtomicU64,
+    // acknowledged by the brokers
+    tx: AtomicU64,
+    tx_bytes: AtomicU64,
+    encode_failed: AtomicU64,
+    // rejected by the local queue of the producer
+    produce_failed: AtomicU64,
+    // not acknowledged by the brokers within message.timeout.ms
+    delivery_failed: AtomicU64,
+    // copies dropped by the tee when the buffer is full
+    buffer_dropped: Arc<AtomicU64>,
+}
+
+impl RefCountable for KafkaExporterCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "encode-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.encode_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "produce-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.produce_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "delivery-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.delivery_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "buffer-dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.buffer_dropped.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+struct ExporterContext {
+    counter: Arc<KafkaExporterCounter>,
+}
+
+impl ClientContext for ExporterContext {}
+
+impl ProducerContext for ExporterContext {
+    // size of the payload
+    type DeliveryOpaque = usize;
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, size: usize) {
+        match delivery_result {
+            Ok(_) => {
+                self.counter.tx.fetch_add(1, Ordering::Relaxed);
+                self.counter
+                    .tx_bytes
+                    .fetch_add(size as u64, Ordering::Relaxed);
+            }
+            Err((e, _)) => {
+                debug!("kafka exporter delivery failed: {}", e);
+                self.counter.delivery_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn client_config(config: &OutputsKafka) -> ClientConfig {
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set("bootstrap.servers", config.brokers.join(","))
+        // messages are batched for at most 100ms
+        .set("queue.buffering.max.ms", "100")
+        .set(
+            "queue.buffering.max.messages",
+            config.buffer_size.to_string(),
+        )
+        .set("message.timeout.ms", "60000")
+        .set("reconnect.backoff.ms", "100")
+        .set("reconnect.backoff.max.ms", "10000");
+    let security_protocol = match (config.tls_enabled, config.sasl_mechanism.as_str()) {
+        (false, None) => "plaintext",
+        (true, None) => "ssl",
+        (false, Some(_)) => "sasl_plaintext",
+        (true, Some(_)) => "sasl_ssl",
+    };
+    client_config.set("security.protocol", security_protocol);
+    if let Some(mechanism) = config.sasl_mechanism.as_str() {
+        client_config
+            .set("sasl.mechanisms", mechanism)
+            .set("sasl.username", &config.sasl_username)
+            .set("sasl.password", &config.sasl_password);
+    }
+    if config.tls_enabled && !config.tls_ca_file.is_empty() {
+        client_config.set("ssl.ca.location", &config.tls_ca_file);
+    }
+    client_config
+}
+
+// Sends copies of the data of a uniform sender to kafka, the copies are buffered in a queue of
+// their own, so that a slow kafka never blocks the uniform sender
+pub struct KafkaExporter<T> {
+    name: &'static str,
+    config: OutputsKafka,
+    input: Arc<Receiver<T>>,
+    tee: Tee<T>,
+    counter: Arc<KafkaExporterCounter>,
+
+    running: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Sendable + Clone> KafkaExporter<T> {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+    pub fn new(name: &'static str, config: &OutputsKafka, stats: &Collector) -> Self {
+        let (sender, receiver, queue_counter) = queue::bounded(config.buffer_size);
+        stats.register_countable(
+            &QueueStats {
+                module: name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(queue_counter)),
+        );
+        let counter = Arc::new(KafkaExporterCounter::default());
+        stats.register_countable(
+            &stats::SingleTagModule("kafka_exporter", "topic", config.topic.clone()),
+            Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+        );
+        Self {
+            name,
+            config: config.clone(),
+            input: Arc::new(receiver),
+            tee: Tee::new(sender, config.buffer_size, counter.buffer_dropped.clone()),
+            counter,
+            running: Arc::new(AtomicBool::new(false)),
+            thread_handle: None,
+        }
+    }
+
+    pub fn tee(&self) -> Tee<T> {
+        self.tee.clone()
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("{} kafka exporter already started, do nothing.", self.name);
+            return;
+        }
+        let config = self.config.clone();
+        let input = self.input.clone();
+        let counter = self.counter.clone();
+        let running = self.running.clone();
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("kafka-exporter".to_owned())
+                .spawn(move || Self::process(config, input, counter, running))
+                .unwrap(),
+        );
+        info!("{} kafka exporter started", self.name);
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("{} kafka exporter already stopped, do nothing.", self.name);
+            return None;
+        }
+        info!("notified {} kafka exporter stopping", self.name);
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.notify_stop() {
+            let _ = handle.join();
+            info!("{} kafka exporter stopped", self.name);
+        }
+    }
+
+    fn process(
+        config: OutputsKafka,
+        input: Arc<Receiver<T>>,
+        counter: Arc<KafkaExporterCounter>,
+        running: Arc<AtomicBool>,
+    ) {
+        let context = ExporterContext {
+            counter: counter.clone(),
+        };
+        let producer: BaseProducer<ExporterContext> =
+            match client_config(&config).create_with_context(context) {
+                Ok(p) => p,
+                Err(e) => {
+                    error!("kafka exporter create producer failed: {}", e);
+                    return;
+                }
+            };
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        let mut kv_string = String::new();
+        let mut payload = vec![];
+        while running.load(Ordering::Relaxed) {
+            match input.recv_all(&mut batch, Some(Self::QUEUE_READ_TIMEOUT)) {
+                Ok(_) => {
+                    for item in batch.drain(..) {
+                        counter.rx.fetch_add(1, Ordering::Relaxed);
+                        // documents of the same flow are sent to the same partition
+                        let key = item.hash_key().map(u64::to_be_bytes);
+                        payload.clear();
+                        match config.serialization {
+                            KafkaSerialization::Json => {
+                                kv_string.clear();
+                                item.to_kv_string(&mut kv_string);
+                                payload.extend_from_slice(kv_string.trim_end().as_bytes());
+                            }
+                            KafkaSerialization::Protobuf => {
+                                if let Err(e) = item.encode(&mut payload) {
+                                    debug!("kafka exporter encode failed: {}", e);
+                                    counter.encode_failed.fetch_add(1, Ordering::Relaxed);
+                                    continue;
+                                }
+                            }
+                        }
+                        let mut record = BaseRecord::with_opaque_to(&config.topic, payload.len())
+                            .payload(&payload[..]);
+                        if let Some(key) = key.as_ref() {
+                            record = record.key(&key[..]);
+                        }
+                        if let Err((e, _)) = producer.send(record) {
+                            debug!("kafka exporter produce failed: {}", e);
+                            counter.produce_failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    // serve the delivery callbacks
+                    producer.poll(Duration::ZERO);
+                }
+                Err(Error::Timeout) => producer.poll(Duration::ZERO),
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+        }
+        if let Err(e) = producer.flush(Self::FLUSH_TIMEOUT) {
+            warn!("kafka exporter flush failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicI64;
+    use std::time::Instant;
+
+    use prost::Message;
+    use rdkafka::{
+        consumer::{BaseConsumer, Consumer},
+        mocking::MockCluster,
+        Message as KafkaMessage,
+    };
+
+    use crate::config::KafkaSaslMechanism;
+    use public::sender::SendMessageType;
+
+    #[derive(Clone, Debug)]
+    struct TestLog(u64);
+
+    impl Sendable for TestLog {
+        fn encode(self, buf: &mut Vec<u8>) -> Result<usize, prost::EncodeError> {
+            self.0.encode(buf)?;
+            Ok(self.0.encoded_len())
+        }
+
+        fn message_type(&self) -> SendMessageType {
+            SendMessageType::ProtocolLog
+        }
+
+        fn to_kv_string(&self, kv_string: &mut String) {
+            kv_string.push_str(&format!("{{\"flow_id\":{}}}\n", self.0));
+        }
+
+        fn hash_key(&self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    fn wait_for(timeout: Duration, f: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if f() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+
+    fn consume(brokers: &str, topic: &str, count: usize) -> Vec<Vec<u8>> {
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", "test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&[topic]).unwrap();
+        let mut payloads = vec![];
+        let start = Instant::now();
+        while payloads.len() < count && start.elapsed() < Duration::from_secs(30) {
+            if let Some(Ok(m)) = consumer.poll(Duration::from_millis(100)) {
+                payloads.push(m.payload().unwrap_or_default().to_vec());
+            }
+        }
+        payloads
+    }
+
+    #[test]
+    fn security_protocol() {
+        let mut config = OutputsKafka::default();
+        assert_eq!(
+            client_config(&config).get("security.protocol"),
+            Some("plaintext")
+        );
+        config.tls_enabled = true;
+        config.tls_ca_file = "/etc/ca.pem".to_string();
+        config.sasl_mechanism = KafkaSaslMechanism::ScramSha256;
+        let client_config = client_config(&config);
+        assert_eq!(client_config.get("security.protocol"), Some("sasl_ssl"));
+        assert_eq!(client_config.get("sasl.mechanisms"), Some("SCRAM-SHA-256"));
+        assert_eq!(client_config.get("ssl.ca.location"), Some("/etc/ca.pem"));
+    }
+
+    #[test]
+    fn export_to_mock_broker() {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("l7", 1, 1).unwrap();
+        let config = OutputsKafka {
+            enabled: true,
+            brokers: vec![cluster.bootstrap_servers()],
+            topic: "l7".to_string(),
+            ..Default::default()
+        };
+        let stats = Collector::new("", Arc::new(AtomicI64::new(0)));
+        let mut exporter = KafkaExporter::<TestLog>::new("test", &config, &stats);
+        let tee = exporter.tee();
+        exporter.start();
+
+        // batched in a few requests
+        for i in 0..1000 {
+            tee.send(&TestLog(i));
+        }
+        let counter = exporter.counter.clone();
+        assert!(wait_for(Duration::from_secs(30), || counter
+            .tx
+            .load(Ordering::Relaxed)
+            == 1000));
+        let payloads = consume(&cluster.bootstrap_servers(), "l7", 1000);
+        assert_eq!(payloads.len(), 1000);
+        assert_eq!(payloads[0], b"{\"flow_id\":0}");
+
+        // messages are sent after the broker is back
+        cluster.broker_down(1).unwrap();
+        for i in 1000..1100 {
+            tee.send(&TestLog(i));
+        }
+        thread::sleep(Duration::from_secs(1));
+        assert_eq!(counter.tx.load(Ordering::Relaxed), 1000);
+        cluster.broker_up(1).unwrap();
+        assert!(wait_for(Duration::from_secs(30), || counter
+            .tx
+            .load(Ordering::Relaxed)
+            == 1100));
+        assert_eq!(counter.delivery_failed.load(Ordering::Relaxed), 0);
+
+        exporter.stop();
+    }
+
+    #[test]
+    fn drop_when_buffer_full() {
+        let config = OutputsKafka {
+            enabled: true,
+            brokers: vec!["127.0.0.1:1".to_string()],
+            buffer_size: 1024,
+            ..Default::default()
+        };
+        let stats = Collector::new("", Arc::new(AtomicI64::new(0)));
+        // not started, nothing is consumed from the buffer
+        let exporter = KafkaExporter::<TestLog>::new("test", &config, &stats);
+        let tee = exporter.tee();
+        for i in 0..1500 {
+            tee.send(&TestLog(i));
+        }
+        let mut batch = Vec::with_capacity(2048);
+        exporter
+            .input
+            .recv_all(&mut batch, Some(Duration::ZERO))
+            .unwrap();
+        assert_eq!(batch.len(), 1024);
+        assert_eq!(exporter.counter.buffer_dropped.load(Ordering::Relaxed), 476);
+    }
+}
@@ -16,9 +16,14 @@
 
 use std::sync::atomic::{AtomicU8, Ordering};
 
+mod data_file;
+#[cfg(feature = "kafka")]
+pub mod kafka_exporter;
 // NpbBandwidthWatcher NewFragmenterBuilder NewCompressorBuilder NewPCapBuilder NewUniformCollectSender
 pub mod npb_sender;
+pub mod otlp_exporter;
 mod tcp_packet;
+mod tls;
 pub(crate) mod uniform_sender;
 
 static ID_COUNTER: AtomicU8 = AtomicU8::new(0);
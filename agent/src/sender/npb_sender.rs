@@ -17,9 +17,11 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+#[cfg(unix)]
+use std::mem;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddrV4, SocketAddrV6};
 #[cfg(unix)]
-use std::os::unix::io::FromRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 #[cfg(windows)]
 use std::os::windows::io::{FromRawSocket, RawSocket};
 use std::sync::atomic::AtomicU64;
@@ -31,7 +33,10 @@ use std::thread::{self, JoinHandle};
 use std::time::{Duration, SystemTime};
 
 #[cfg(unix)]
-use libc::{c_int, socket, AF_INET, AF_INET6, SOCK_RAW};
+use libc::{
+    c_int, c_void, close, setsockopt, socket, socklen_t, AF_INET, AF_INET6, AF_PACKET,
+    IPPROTO_IPV6, IPV6_CHECKSUM, IPV6_TCLASS, SOCK_RAW,
+};
 use log::{info, warn};
 use socket2::{Domain, SockAddr, Socket, Type};
 #[cfg(windows)]
@@ -40,16 +45,19 @@ use windows::Win32::Networking::WinSock::socket;
 use super::QUEUE_BATCH_SIZE;
 
 use crate::common::{
-    enums::IpProtocol, erspan, vxlan, IPV4_ADDR_LEN, IPV4_DST_OFFSET, IPV4_PACKET_SIZE,
-    IPV4_PROTO_OFFSET, IPV6_ADDR_LEN, IPV6_DST_OFFSET, IPV6_PACKET_SIZE, IPV6_PROTO_OFFSET,
-    TCP6_PACKET_SIZE, TCP_PACKET_SIZE, UDP6_PACKET_SIZE, UDP_PACKET_SIZE,
+    enums::{EthernetType, IpProtocol},
+    erspan, vxlan, ERSPAN_HEADER_SIZE, ETH_HEADER_SIZE, ETH_TYPE_OFFSET, GRE_HEADER_SIZE,
+    IPV4_ADDR_LEN, IPV4_DST_OFFSET, IPV4_PACKET_SIZE, IPV4_PROTO_OFFSET, IPV6_ADDR_LEN,
+    IPV6_DST_OFFSET, IPV6_PACKET_SIZE, IPV6_PROTO_OFFSET, TCP6_PACKET_SIZE, TCP_PACKET_SIZE,
+    UDP6_PACKET_SIZE, UDP_HEADER_SIZE, UDP_PACKET_SIZE, VLAN_ID_MASK, VXLAN_HEADER_SIZE,
 };
 #[cfg(unix)]
 use crate::common::{
-    ETH_HEADER_SIZE, IPV4_CSUM_OFFSET, IPV4_HEADER_SIZE, IPV4_SRC_OFFSET, IPV6_SRC_OFFSET,
-    UDP6_CHKSUM_OFFSET,
+    ipv4, ipv6, IPV4_CSUM_OFFSET, IPV4_FRAG_DONT_FRAGMENT, IPV4_HEADER_SIZE, IPV4_SRC_OFFSET,
+    IPV6_FLOW_LABEL_OFFSET, IPV6_HEADER_SIZE, IPV6_PAYLOAD_LENGTH_OFFSET, IPV6_SRC_OFFSET,
+    UDP6_CHKSUM_OFFSET, UDP6_LENGTH_OFFSET,
 };
-use crate::config::NpbConfig;
+use crate::config::{NpbConfig, VxlanEncapsulation};
 #[cfg(unix)]
 use crate::dispatcher::af_packet::{Options, Tpacket};
 use crate::exception::ExceptionHandler;
@@ -57,7 +65,7 @@ use crate::utils::stats;
 use npb_handler::{NpbHeader, NOT_SUPPORT};
 use npb_sender::ZmqSender;
 use public::counter::{Countable, CounterType, CounterValue, OwnedCountable};
-use public::proto::agent::{Exception, SocketType};
+use public::proto::agent::{Exception, SocketType, VlanMode};
 use public::queue::Receiver;
 #[cfg(unix)]
 use public::utils::net::MAC_ADDR_LEN;
@@ -72,26 +80,79 @@ const AF_INET6: i32 = 23;
 #[cfg(windows)]
 const SOCK_RAW: i32 = 3;
 
+// The underlay address family is chosen by the tunnel ip of each packet
+fn underlay_is_ipv6(packet: &[u8], underlay_l2_opt_size: usize) -> bool {
+    packet[ETH_HEADER_SIZE + underlay_l2_opt_size] >> 4 == 6
+}
+
+// Offset of the overlay packet in VXLAN and ERSPAN tunnels
+fn overlay_offset(packet: &[u8], underlay_l2_opt_size: usize) -> Option<usize> {
+    let (protocol, ip_packet_size) = if underlay_is_ipv6(packet, underlay_l2_opt_size) {
+        (
+            packet[IPV6_PROTO_OFFSET + underlay_l2_opt_size],
+            IPV6_PACKET_SIZE,
+        )
+    } else {
+        (
+            packet[IPV4_PROTO_OFFSET + underlay_l2_opt_size],
+            IPV4_PACKET_SIZE,
+        )
+    };
+    let header_size = if protocol == IpProtocol::UDP {
+        UDP_HEADER_SIZE + VXLAN_HEADER_SIZE
+    } else if protocol == IpProtocol::GRE {
+        GRE_HEADER_SIZE + ERSPAN_HEADER_SIZE
+    } else {
+        return None;
+    };
+    Some(ip_packet_size + header_size + underlay_l2_opt_size)
+}
+
+// VLAN id of the outermost tag of the overlay packet, which is the extra VLAN
+// header when outputs.npb.extra_vlan_header is enabled
+fn overlay_vlan_id(packet: &[u8], underlay_l2_opt_size: usize) -> Option<u16> {
+    let offset = overlay_offset(packet, underlay_l2_opt_size)? + ETH_TYPE_OFFSET;
+    if packet.len() < offset + 4 {
+        return None;
+    }
+    let eth_type = u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+    if eth_type != EthernetType::DOT1Q && eth_type != EthernetType::QINQ {
+        return None;
+    }
+    Some(u16::from_be_bytes([packet[offset + 2], packet[offset + 3]]) & VLAN_ID_MASK)
+}
+
+// The sequence takes the 24 bits reserved field of the VXLAN header, but only
+// the lower 16 bits with VXLAN-GPE whose last reserved byte is the Next Protocol.
+fn serialize_vxlan_seq(packet: &mut [u8], offset: usize, seq: u32, encap: VxlanEncapsulation) {
+    let offset = offset + vxlan::SEQUENCE_OFFSET;
+    match encap {
+        VxlanEncapsulation::Vxlan => {
+            packet[offset] = (seq >> 16) as u8;
+            packet[offset + 1..offset + 3].copy_from_slice(&(seq as u16).to_be_bytes());
+        }
+        VxlanEncapsulation::VxlanGpe => {
+            packet[offset..offset + 2].copy_from_slice(&(seq as u16).to_be_bytes());
+        }
+    }
+}
+
 fn serialize_seq(
     packet: &mut Vec<u8>,
     seq: u32,
     underlay_l2_opt_size: usize,
-    underlay_is_ipv6: bool,
+    encap: VxlanEncapsulation,
 ) {
-    if !underlay_is_ipv6 {
+    if !underlay_is_ipv6(packet, underlay_l2_opt_size) {
         if packet[underlay_l2_opt_size + IPV4_PROTO_OFFSET] == IpProtocol::UDP {
-            let offset = UDP_PACKET_SIZE + underlay_l2_opt_size + vxlan::SEQUENCE_OFFSET;
-            packet[offset] = (seq >> 16) as u8;
-            packet[offset + 1..offset + 3].copy_from_slice(&(seq as u16).to_be_bytes());
+            serialize_vxlan_seq(packet, UDP_PACKET_SIZE + underlay_l2_opt_size, seq, encap);
         } else {
             let offset = IPV4_PACKET_SIZE + underlay_l2_opt_size + erspan::GRE_SEQUENCE_OFFSET;
             packet[offset..offset + 4].copy_from_slice(&seq.to_be_bytes());
         }
     } else {
-        if packet[underlay_l2_opt_size + IPV4_PROTO_OFFSET] == IpProtocol::UDP {
-            let offset = UDP6_PACKET_SIZE + underlay_l2_opt_size + vxlan::SEQUENCE_OFFSET;
-            packet[offset] = (seq >> 16) as u8;
-            packet[offset + 1..offset + 3].copy_from_slice(&(seq as u16).to_be_bytes());
+        if packet[underlay_l2_opt_size + IPV6_PROTO_OFFSET] == IpProtocol::UDP {
+            serialize_vxlan_seq(packet, UDP6_PACKET_SIZE + underlay_l2_opt_size, seq, encap);
         } else {
             let offset = IPV6_PACKET_SIZE + underlay_l2_opt_size + erspan::GRE_SEQUENCE_OFFSET;
             packet[offset..offset + 4].copy_from_slice(&seq.to_be_bytes());
@@ -99,6 +160,188 @@ fn serialize_seq(
     }
 }
 
+#[cfg(unix)]
+const FLOW_LABEL_MASK: u32 = 0xfffff;
+
+// Hash the addresses, protocol and ports of the inner packet into an IPv6 flow
+// label, so that the underlay keeps packets of the same inner flow on one path.
+#[cfg(unix)]
+fn inner_flow_label(inner: &[u8]) -> u32 {
+    let mut offset = ETH_TYPE_OFFSET;
+    let mut eth_type = 0;
+    while offset + 2 <= inner.len() {
+        eth_type = u16::from_be_bytes([inner[offset], inner[offset + 1]]);
+        offset += 2;
+        if eth_type != EthernetType::DOT1Q && eth_type != EthernetType::QINQ {
+            break;
+        }
+        // skip the vlan tag
+        offset += 2;
+    }
+
+    let (addresses, protocol, l4_offset) = if eth_type == EthernetType::IPV4 {
+        if inner.len() < offset + IPV4_HEADER_SIZE {
+            return 0;
+        }
+        let header_length = (inner[offset] & 0xf) as usize * 4;
+        let flags_offset = offset + ipv4::FLAGS_OFFSET;
+        let fragment = u16::from_be_bytes([inner[flags_offset], inner[flags_offset + 1]]);
+        let l4_offset = if fragment & !IPV4_FRAG_DONT_FRAGMENT == 0 {
+            Some(offset + header_length)
+        } else {
+            None
+        };
+        (
+            &inner[offset + ipv4::SRC_OFFSET..offset + ipv4::DST_OFFSET + IPV4_ADDR_LEN],
+            inner[offset + ipv4::PROTO_OFFSET],
+            l4_offset,
+        )
+    } else if eth_type == EthernetType::IPV6 {
+        if inner.len() < offset + IPV6_HEADER_SIZE {
+            return 0;
+        }
+        (
+            &inner[offset + ipv6::SRC_OFFSET..offset + ipv6::DST_OFFSET + IPV6_ADDR_LEN],
+            inner[offset + ipv6::PROTO_OFFSET],
+            Some(offset + IPV6_HEADER_SIZE),
+        )
+    } else {
+        return 0;
+    };
+    let ports: &[u8] = match l4_offset {
+        Some(o)
+            if (protocol == IpProtocol::TCP || protocol == IpProtocol::UDP)
+                && inner.len() >= o + 4 =>
+        {
+            &inner[o..o + 4]
+        }
+        _ => &[],
+    };
+
+    // FNV-1a
+    let mut hash = 0x811c9dc5u32;
+    for b in addresses.iter().chain(&[protocol]).chain(ports) {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    (hash ^ (hash >> 20)) & FLOW_LABEL_MASK
+}
+
+// DSCP of the packets sent by the fallback of outputs.socket.raw_udp_qos_bypass,
+// Expedited Forwarding
+const QOS_BYPASS_DSCP: u8 = 46;
+
+// Mechanism in effect of outputs.socket.raw_udp_qos_bypass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QosBypass {
+    Disabled,
+    // PACKET_QDISC_BYPASS of the AF_PACKET socket
+    QdiscBypass,
+    // DSCP or Traffic Class set with setsockopt on the ip socket
+    SocketDscp,
+}
+
+static QOS_BYPASS: RwLock<Option<QosBypass>> = RwLock::new(None);
+
+pub fn qos_bypass() -> Option<QosBypass> {
+    *QOS_BYPASS.read().unwrap()
+}
+
+impl QosBypass {
+    fn select(enabled: bool, socket_type: SocketType, afpacket_permitted: bool) -> Self {
+        match socket_type {
+            _ if !enabled => QosBypass::Disabled,
+            SocketType::RawUdp if afpacket_permitted => QosBypass::QdiscBypass,
+            SocketType::RawUdp | SocketType::Udp => QosBypass::SocketDscp,
+            _ => QosBypass::Disabled,
+        }
+    }
+
+    // Logs once when the mechanism in effect changes
+    fn store(self) {
+        let mut current = QOS_BYPASS.write().unwrap();
+        if *current == Some(self) {
+            return;
+        }
+        *current = Some(self);
+        match self {
+            QosBypass::Disabled => info!("Npb raw_udp_qos_bypass is disabled."),
+            QosBypass::QdiscBypass => {
+                info!("Npb raw_udp_qos_bypass uses PACKET_QDISC_BYPASS of the AF_PACKET socket.")
+            }
+            QosBypass::SocketDscp => info!(
+                "Npb raw_udp_qos_bypass falls back to DSCP {} with IP_TOS/IPV6_TCLASS of the ip socket.",
+                QOS_BYPASS_DSCP
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DscpSockopt {
+    // IP_TOS
+    Tos(u32),
+    // IPV6_TCLASS
+    TrafficClass(u32),
+}
+
+impl DscpSockopt {
+    fn new(is_ipv6: bool, dscp: u8) -> Self {
+        // DSCP takes the upper 6 bits of the TOS and Traffic Class, leaving ECN unset
+        let value = (dscp as u32 & 0x3f) << 2;
+        if is_ipv6 {
+            DscpSockopt::TrafficClass(value)
+        } else {
+            DscpSockopt::Tos(value)
+        }
+    }
+
+    fn apply(&self, socket: &Socket) -> IOResult<()> {
+        match self {
+            DscpSockopt::Tos(tos) => socket.set_tos(*tos),
+            #[cfg(unix)]
+            DscpSockopt::TrafficClass(tclass) => {
+                let tclass = *tclass as c_int;
+                let ret = unsafe {
+                    setsockopt(
+                        socket.as_raw_fd(),
+                        IPPROTO_IPV6,
+                        IPV6_TCLASS,
+                        &tclass as *const c_int as *const c_void,
+                        mem::size_of::<c_int>() as socklen_t,
+                    )
+                };
+                if ret < 0 {
+                    return Err(IOError::last_os_error());
+                }
+                Ok(())
+            }
+            #[cfg(windows)]
+            DscpSockopt::TrafficClass(_) => Err(IOError::new(
+                ErrorKind::Unsupported,
+                "IPV6_TCLASS is not supported",
+            )),
+        }
+    }
+}
+
+// AF_PACKET sockets are denied without CAP_NET_RAW
+#[cfg(unix)]
+fn afpacket_permitted() -> bool {
+    let fd = unsafe { socket(AF_PACKET, SOCK_RAW, 0) };
+    if fd < 0 {
+        warn!(
+            "Npb AF_PACKET socket unavailable: {}.",
+            IOError::last_os_error()
+        );
+        return false;
+    }
+    unsafe {
+        close(fd);
+    }
+    true
+}
+
 #[cfg(unix)]
 #[derive(Debug)]
 struct AfpacketSender {
@@ -108,6 +351,8 @@ struct AfpacketSender {
     underlay_src_ip: IpAddr,
     if_name: String,
     remote: IpAddr,
+    vxlan_encapsulation: VxlanEncapsulation,
+    qdisc_bypass: bool,
 
     last_arp_update: u64,
 }
@@ -115,7 +360,11 @@ struct AfpacketSender {
 #[cfg(unix)]
 impl AfpacketSender {
     const ARP_UPDATE_INTERVAL: u64 = 300 * 1000000000;
-    fn new(remote: &IpAddr) -> AfpacketSender {
+    fn new(
+        remote: &IpAddr,
+        vxlan_encapsulation: VxlanEncapsulation,
+        qdisc_bypass: bool,
+    ) -> AfpacketSender {
         Self {
             af_packet: None,
             underlay_dst_mac: MacAddr::ZERO,
@@ -123,12 +372,13 @@ impl AfpacketSender {
             underlay_src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             if_name: String::new(),
             remote: remote.clone(),
+            vxlan_encapsulation,
+            qdisc_bypass,
             last_arp_update: 0,
         }
     }
 
-    fn checksum(data: &[u8]) -> u16 {
-        let mut checksum = 0;
+    fn sum(mut checksum: u32, data: &[u8]) -> u32 {
         let mut i = 0;
 
         while i + 1 < data.len() {
@@ -142,7 +392,23 @@ impl AfpacketSender {
         while checksum > u16::MAX as u32 {
             checksum = (checksum >> 16) + (checksum & 0xffff);
         }
-        return !(checksum as u16);
+        checksum
+    }
+
+    fn checksum(data: &[u8]) -> u16 {
+        !(Self::sum(0, data) as u16)
+    }
+
+    // The UDP checksum is mandatory for IPv6 and covers the pseudo header of
+    // source address, destination address, UDP length and next header.
+    fn udp6_checksum(addresses: &[u8], udp: &[u8]) -> u16 {
+        let mut checksum = Self::sum(0, addresses);
+        checksum += (udp.len() >> 16) as u32 + (udp.len() & 0xffff) as u32;
+        checksum += u8::from(IpProtocol::UDP) as u32;
+        match !(Self::sum(checksum, udp) as u16) {
+            0 => 0xffff,
+            c => c,
+        }
     }
 
     fn serialize_underlay(&self, underlay_l2_opt_size: usize, packet: &mut Vec<u8>) {
@@ -168,11 +434,38 @@ impl AfpacketSender {
                 packet[src_ip_offset..src_ip_offset + IPV6_ADDR_LEN]
                     .copy_from_slice(&addr.octets());
 
+                let payload_offset = IPV6_PACKET_SIZE + underlay_l2_opt_size;
+                let payload_length = (packet.len() - payload_offset) as u16;
+                let payload_length_offset = IPV6_PAYLOAD_LENGTH_OFFSET + underlay_l2_opt_size;
+                packet[payload_length_offset..payload_length_offset + 2]
+                    .copy_from_slice(&payload_length.to_be_bytes());
+
                 let protocol_offset = IPV6_PROTO_OFFSET + underlay_l2_opt_size;
-                if packet[protocol_offset] == IpProtocol::UDP {
-                    let udp_header_offset = IPV6_PACKET_SIZE + underlay_l2_opt_size;
+                let is_udp = packet[protocol_offset] == IpProtocol::UDP;
+                let flow_label = match overlay_offset(packet, underlay_l2_opt_size) {
+                    Some(offset) => inner_flow_label(packet.get(offset..).unwrap_or_default()),
+                    None => 0,
+                };
+                let flow_label_offset = IPV6_FLOW_LABEL_OFFSET + underlay_l2_opt_size;
+                let word = u32::from_be_bytes(
+                    packet[flow_label_offset..flow_label_offset + 4]
+                        .try_into()
+                        .unwrap(),
+                ) & !FLOW_LABEL_MASK
+                    | flow_label;
+                packet[flow_label_offset..flow_label_offset + 4]
+                    .copy_from_slice(&word.to_be_bytes());
+
+                if is_udp {
+                    let length_offset = UDP6_LENGTH_OFFSET + underlay_l2_opt_size;
+                    packet[length_offset..length_offset + 2]
+                        .copy_from_slice(&payload_length.to_be_bytes());
                     let checksum_offset = UDP6_CHKSUM_OFFSET + underlay_l2_opt_size;
-                    let checksum = Self::checksum(&packet[udp_header_offset..]);
+                    packet[checksum_offset..checksum_offset + 2].copy_from_slice(&[0, 0]);
+                    let checksum = Self::udp6_checksum(
+                        &packet[src_ip_offset..payload_offset],
+                        &packet[payload_offset..],
+                    );
                     packet[checksum_offset..checksum_offset + 2]
                         .copy_from_slice(&checksum.to_be_bytes());
                 }
@@ -208,7 +501,13 @@ impl AfpacketSender {
                 ));
             }
             info!("Npb Afpacket sender init with: {:?}.", options);
-            self.af_packet.replace(af_packet.unwrap());
+            let af_packet = af_packet.unwrap();
+            if self.qdisc_bypass {
+                if let Err(e) = af_packet.set_qdisc_bypass() {
+                    warn!("Npb Afpacket sender set qdisc bypass failed: {:?}.", e);
+                }
+            }
+            self.af_packet.replace(af_packet);
             self.if_name = if_name;
         }
         self.underlay_dst_mac = dst_mac;
@@ -229,7 +528,7 @@ impl AfpacketSender {
             &mut packet,
             self.check_arp(timestamp, arp)?,
             underlay_l2_opt_size,
-            self.underlay_src_ip.is_ipv6(),
+            self.vxlan_encapsulation,
         );
         self.serialize_underlay(underlay_l2_opt_size, &mut packet);
         let n = self.af_packet.as_mut().unwrap().write(&packet.as_slice());
@@ -245,8 +544,8 @@ impl AfpacketSender {
 #[derive(Debug)]
 struct IpSender {
     socket: Socket,
-    underlay_is_ipv6: bool,
     underlay_header_size: usize,
+    vxlan_encapsulation: VxlanEncapsulation,
 
     dst_ip: IpAddr,
     remote: SockAddr,
@@ -254,7 +553,13 @@ struct IpSender {
 
 impl IpSender {
     #[cfg(windows)]
-    fn new(remote: &IpAddr, protocol: u8) -> IOResult<Self> {
+    fn new(
+        remote: &IpAddr,
+        protocol: u8,
+        hop_limit: u8,
+        vxlan_encapsulation: VxlanEncapsulation,
+        qos_bypass: QosBypass,
+    ) -> IOResult<Self> {
         let socket = unsafe {
             if remote.is_ipv6() {
                 socket(AF_INET6, SOCK_RAW as i32, protocol as i32)
@@ -267,16 +572,26 @@ impl IpSender {
         }
         let socket = unsafe { Socket::from_raw_socket(socket.0 as RawSocket) };
         socket.set_send_buffer_size(30 << 20)?;
+        if remote.is_ipv6() {
+            socket.set_unicast_hops_v6(hop_limit as u32)?;
+        } else {
+            socket.set_ttl(hop_limit as u32)?;
+        }
+        if qos_bypass == QosBypass::SocketDscp {
+            if let Err(e) = DscpSockopt::new(remote.is_ipv6(), QOS_BYPASS_DSCP).apply(&socket) {
+                warn!("Npb IpSender set dscp failed: {:?}.", e);
+            }
+        }
 
         info!("Npb IpSender init with {} {}.", remote, protocol);
         Ok(Self {
             socket,
-            underlay_is_ipv6: remote.is_ipv6(),
             underlay_header_size: if remote.is_ipv6() {
                 IPV6_PACKET_SIZE
             } else {
                 IPV4_PACKET_SIZE
             },
+            vxlan_encapsulation,
             remote: match remote {
                 IpAddr::V4(ip) => SockAddr::from(SocketAddrV4::new(ip.clone(), 0)),
                 IpAddr::V6(ip) => SockAddr::from(SocketAddrV6::new(ip.clone(), 0, 0, 0)),
@@ -286,7 +601,13 @@ impl IpSender {
     }
 
     #[cfg(unix)]
-    fn new(remote: &IpAddr, protocol: u8) -> IOResult<Self> {
+    fn new(
+        remote: &IpAddr,
+        protocol: u8,
+        hop_limit: u8,
+        vxlan_encapsulation: VxlanEncapsulation,
+        qos_bypass: QosBypass,
+    ) -> IOResult<Self> {
         let fd = unsafe {
             if remote.is_ipv6() {
                 socket(AF_INET6, SOCK_RAW, protocol as c_int)
@@ -299,16 +620,43 @@ impl IpSender {
         }
         let socket = unsafe { Socket::from_raw_fd(fd) };
         socket.set_send_buffer_size(30 << 20)?;
+        if remote.is_ipv6() {
+            socket.set_unicast_hops_v6(hop_limit as u32)?;
+            // The kernel does not fill the UDP checksum of raw IPv6 sockets, which
+            // is mandatory for IPv6, let it compute the checksum at offset 6.
+            if protocol == IpProtocol::UDP {
+                let offset: c_int = 6;
+                let ret = unsafe {
+                    setsockopt(
+                        fd,
+                        IPPROTO_IPV6,
+                        IPV6_CHECKSUM,
+                        &offset as *const c_int as *const c_void,
+                        mem::size_of::<c_int>() as socklen_t,
+                    )
+                };
+                if ret < 0 {
+                    return Err(IOError::last_os_error());
+                }
+            }
+        } else {
+            socket.set_ttl(hop_limit as u32)?;
+        }
+        if qos_bypass == QosBypass::SocketDscp {
+            if let Err(e) = DscpSockopt::new(remote.is_ipv6(), QOS_BYPASS_DSCP).apply(&socket) {
+                warn!("Npb IpSender set dscp failed: {:?}.", e);
+            }
+        }
 
         info!("Npb IpSender init with {} {}.", remote, protocol);
         Ok(Self {
             socket,
-            underlay_is_ipv6: remote.is_ipv6(),
             underlay_header_size: if remote.is_ipv6() {
                 IPV6_PACKET_SIZE
             } else {
                 IPV4_PACKET_SIZE
             },
+            vxlan_encapsulation,
             dst_ip: remote.clone(),
             remote: match remote {
                 IpAddr::V4(ip) => SockAddr::from(SocketAddrV4::new(ip.clone(), 0)),
@@ -329,7 +677,7 @@ impl IpSender {
             &mut packet,
             seq,
             underlay_l2_opt_size,
-            self.underlay_is_ipv6,
+            self.vxlan_encapsulation,
         );
         self.socket
             .send_to(&packet.as_slice()[header_size..], &self.remote)
@@ -780,9 +1128,17 @@ pub struct NpbConnectionPool {
     connections: HashMap<(u128, u8), NpbSender>,
     socket_type: SocketType,
     npb_port: u16,
-    underlay_is_ipv6: bool,
+    hop_limit: u8,
+    vlan_mode: VlanMode,
+    vxlan_encapsulation: VxlanEncapsulation,
+    qos_bypass: QosBypass,
+    #[cfg(unix)]
+    afpacket_permitted: bool,
 
+    id: usize,
     counter: Arc<NpbSenderCounter>,
+    vlan_counters: HashMap<u16, Arc<NpbSenderCounter>>,
+    stats_collector: Arc<stats::Collector>,
 
     arp: Arc<NpbArpTable>,
 }
@@ -790,9 +1146,7 @@ pub struct NpbConnectionPool {
 impl NpbConnectionPool {
     pub fn new(
         id: usize,
-        underlay_is_ipv6: bool,
-        socket_type: SocketType,
-        npb_port: u16,
+        config: &NpbConfig,
         arp: Arc<NpbArpTable>,
         stats_collector: Arc<stats::Collector>,
     ) -> Self {
@@ -803,7 +1157,9 @@ impl NpbConnectionPool {
         );
 
         #[cfg(windows)]
-        let mut socket_type = socket_type;
+        let mut socket_type = config.socket_type;
+        #[cfg(not(windows))]
+        let socket_type = config.socket_type;
         #[cfg(windows)]
         {
             if socket_type == SocketType::RawUdp {
@@ -812,12 +1168,28 @@ impl NpbConnectionPool {
             }
         }
 
+        #[cfg(unix)]
+        let afpacket_permitted = socket_type != SocketType::RawUdp || afpacket_permitted();
+        #[cfg(windows)]
+        let afpacket_permitted = false;
+        let qos_bypass =
+            QosBypass::select(config.enable_qos_bypass, socket_type, afpacket_permitted);
+        qos_bypass.store();
+
         Self {
             connections: HashMap::new(),
             socket_type,
-            npb_port,
-            underlay_is_ipv6,
+            npb_port: config.npb_port,
+            hop_limit: config.hop_limit,
+            vlan_mode: config.vlan_mode,
+            vxlan_encapsulation: config.vxlan_encapsulation,
+            qos_bypass,
+            #[cfg(unix)]
+            afpacket_permitted,
+            id,
             counter,
+            vlan_counters: HashMap::new(),
+            stats_collector,
             arp,
         }
     }
@@ -827,11 +1199,21 @@ impl NpbConnectionPool {
         self.arp.add(remote);
         match self.socket_type {
             #[cfg(unix)]
-            SocketType::RawUdp if protocol != IpProtocol::TCP => {
-                Ok(NpbSender::RawSender(AfpacketSender::new(remote)))
+            SocketType::RawUdp if protocol != IpProtocol::TCP && self.afpacket_permitted => {
+                Ok(NpbSender::RawSender(AfpacketSender::new(
+                    remote,
+                    self.vxlan_encapsulation,
+                    self.qos_bypass == QosBypass::QdiscBypass,
+                )))
             }
             _ if protocol != IpProtocol::TCP => {
-                let sender = IpSender::new(remote, protocol);
+                let sender = IpSender::new(
+                    remote,
+                    protocol,
+                    self.hop_limit,
+                    self.vxlan_encapsulation,
+                    self.qos_bypass,
+                );
                 if sender.is_err() {
                     return Err(format!("IpSender error: {:?}.", sender.unwrap_err()));
                 }
@@ -856,7 +1238,7 @@ impl NpbConnectionPool {
         underlay_l2_opt_size: usize,
         packet: Vec<u8>,
     ) -> IOResult<usize> {
-        let (remote, key) = if self.underlay_is_ipv6 {
+        let (remote, key) = if underlay_is_ipv6(&packet, underlay_l2_opt_size) {
             let offset = IPV6_DST_OFFSET + underlay_l2_opt_size;
             let ip = Ipv6Addr::from(
                 *<&[u8; 16]>::try_from(&packet[offset..offset + IPV6_ADDR_LEN]).unwrap(),
@@ -900,6 +1282,18 @@ impl NpbConnectionPool {
         return ret;
     }
 
+    fn vlan_counter(&mut self, vlan_id: u16) -> &Arc<NpbSenderCounter> {
+        let (id, stats_collector) = (self.id, &self.stats_collector);
+        self.vlan_counters.entry(vlan_id).or_insert_with(|| {
+            let counter = Arc::new(NpbSenderCounter::default());
+            stats_collector.register_countable(
+                &stats::NpbVlanStats { id, vlan_id },
+                Countable::Owned(Box::new(StatsNpbSenderCounter(Arc::downgrade(&counter)))),
+            );
+            counter
+        })
+    }
+
     pub fn send(
         &mut self,
         timestamp: u64,
@@ -907,13 +1301,27 @@ impl NpbConnectionPool {
         packet: Vec<u8>,
     ) -> IOResult<usize> {
         let bytes = packet.len();
+        let vlan_id = if self.vlan_mode != VlanMode::None {
+            overlay_vlan_id(&packet, underlay_l2_opt_size)
+        } else {
+            None
+        };
         let ret = self.send_to(timestamp, underlay_l2_opt_size, packet);
         if ret.is_err() {
             self.counter.tx_dropped.fetch_add(1, Ordering::Relaxed);
+            if let Some(vlan_id) = vlan_id {
+                let counter = self.vlan_counter(vlan_id);
+                counter.tx_dropped.fetch_add(1, Ordering::Relaxed);
+            }
             return ret;
         }
         self.counter.tx.fetch_add(1, Ordering::Relaxed);
         self.counter.tx_bytes.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(vlan_id) = vlan_id {
+            let counter = self.vlan_counter(vlan_id);
+            counter.tx.fetch_add(1, Ordering::Relaxed);
+            counter.tx_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
         return ret;
     }
 
@@ -940,9 +1348,7 @@ impl NpbPacketSender {
         NpbPacketSender {
             connections: Mutex::new(NpbConnectionPool::new(
                 id,
-                config.underlay_is_ipv6,
-                config.socket_type,
-                config.npb_port,
+                config,
                 arp.clone(),
                 stats_collector,
             )),
@@ -989,3 +1395,220 @@ impl NpbPacketSender {
         self.disable.store(true, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ethernet, ipv4 and udp headers of 10.0.0.1:1234 -> 10.0.0.2:80
+    const INNER_PACKET: [u8; 42] = [
+        2, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 2, 8, 0, 69, 0, 0, 28, 0, 0, 64, 0, 64, 17, 0, 0, 10, 0,
+        0, 1, 10, 0, 0, 2, 4, 210, 0, 80, 0, 8, 0, 0,
+    ];
+
+    #[test]
+    fn serialize_seq_by_family() {
+        let vxlan_v6 = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 134, 221, 96, 0, 0, 0, 0, 0, 17, 64, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 1, 13, 184, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+            192, 0, 18, 181, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut packet = vxlan_v6.to_vec();
+        serialize_seq(&mut packet, 0x010203, 0, VxlanEncapsulation::Vxlan);
+        assert_eq!(
+            &packet[UDP6_PACKET_SIZE..UDP6_PACKET_SIZE + 4],
+            &[8, 1, 2, 3]
+        );
+
+        let mut packet = vec![0; IPV6_PACKET_SIZE + 24];
+        packet[ETH_HEADER_SIZE] = 0x60;
+        packet[IPV6_PROTO_OFFSET] = u8::from(IpProtocol::GRE);
+        serialize_seq(&mut packet, 0x01020304, 0, VxlanEncapsulation::Vxlan);
+        let offset = IPV6_PACKET_SIZE + erspan::GRE_SEQUENCE_OFFSET;
+        assert_eq!(&packet[offset..offset + 4], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn serialize_seq_vxlan_gpe() {
+        let vxlan_gpe_v4 = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 69, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0, 0, 0,
+            0, 0, 10, 1, 1, 2, 192, 0, 18, 182, 0, 0, 0, 0, 12, 0, 0, 3, 0, 0, 0, 0,
+        ];
+        let mut packet = vxlan_gpe_v4.to_vec();
+        serialize_seq(&mut packet, 0x010203, 0, VxlanEncapsulation::VxlanGpe);
+        // the next protocol is kept as ethernet
+        assert_eq!(
+            &packet[UDP_PACKET_SIZE..UDP_PACKET_SIZE + 4],
+            &[12, 2, 3, 3]
+        );
+
+        let mut packet = vxlan_gpe_v4.to_vec();
+        serialize_seq(&mut packet, 0x010203, 0, VxlanEncapsulation::Vxlan);
+        assert_eq!(
+            &packet[UDP_PACKET_SIZE..UDP_PACKET_SIZE + 4],
+            &[12, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn qos_bypass_dscp_sockopt() {
+        assert_eq!(
+            DscpSockopt::new(false, QOS_BYPASS_DSCP),
+            DscpSockopt::Tos(0xb8)
+        );
+        assert_eq!(
+            DscpSockopt::new(true, QOS_BYPASS_DSCP),
+            DscpSockopt::TrafficClass(0xb8)
+        );
+        assert_eq!(DscpSockopt::new(false, 0xff), DscpSockopt::Tos(0xfc));
+    }
+
+    #[test]
+    fn qos_bypass_selection() {
+        assert_eq!(
+            QosBypass::select(false, SocketType::RawUdp, true),
+            QosBypass::Disabled
+        );
+        assert_eq!(
+            QosBypass::select(true, SocketType::RawUdp, true),
+            QosBypass::QdiscBypass
+        );
+        // AF_PACKET denied by capability
+        assert_eq!(
+            QosBypass::select(true, SocketType::RawUdp, false),
+            QosBypass::SocketDscp
+        );
+        assert_eq!(
+            QosBypass::select(true, SocketType::Udp, true),
+            QosBypass::SocketDscp
+        );
+        assert_eq!(
+            QosBypass::select(true, SocketType::Tcp, true),
+            QosBypass::Disabled
+        );
+    }
+
+    #[test]
+    fn overlay_vlan() {
+        let vxlan_v4 = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 69, 0, 0, 78, 0, 0, 0, 0, 64, 17, 0, 0, 0, 0,
+            0, 0, 10, 1, 1, 2, 192, 0, 18, 181, 0, 58, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let packet = [&vxlan_v4[..], &INNER_PACKET[..]].concat();
+        assert_eq!(overlay_offset(&packet, 0), Some(vxlan_v4.len()));
+        assert_eq!(overlay_vlan_id(&packet, 0), None);
+
+        // 802.1Q extra header with VLAN 100
+        let tagged = [
+            &vxlan_v4[..],
+            &INNER_PACKET[..ETH_TYPE_OFFSET],
+            &[0x81, 0, 0, 100],
+            &INNER_PACKET[ETH_TYPE_OFFSET..],
+        ]
+        .concat();
+        assert_eq!(overlay_vlan_id(&tagged, 0), Some(100));
+
+        // QinQ extra header with outer VLAN 200 and inner VLAN 100
+        let tagged = [
+            &vxlan_v4[..],
+            &INNER_PACKET[..ETH_TYPE_OFFSET],
+            &[0x88, 0xa8, 0, 200, 0x81, 0, 0, 100],
+            &INNER_PACKET[ETH_TYPE_OFFSET..],
+        ]
+        .concat();
+        assert_eq!(overlay_vlan_id(&tagged, 0), Some(200));
+    }
+
+    #[cfg(unix)]
+    fn afpacket_sender(src_ip: IpAddr, remote: IpAddr) -> AfpacketSender {
+        let mut sender = AfpacketSender::new(&remote, VxlanEncapsulation::Vxlan, false);
+        sender.underlay_dst_mac = MacAddr::from([2, 0, 0, 0, 0, 10]);
+        sender.underlay_src_mac = MacAddr::from([2, 0, 0, 0, 0, 11]);
+        sender.underlay_src_ip = src_ip;
+        sender
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serialize_ipv4_underlay() {
+        let sender = afpacket_sender("10.1.1.1".parse().unwrap(), "10.1.1.2".parse().unwrap());
+        let header = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 0, 69, 0, 0, 78, 0, 0, 0, 0, 64, 17, 0, 0, 0, 0,
+            0, 0, 10, 1, 1, 2, 192, 0, 18, 181, 0, 58, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut packet = [&header[..], &INNER_PACKET[..]].concat();
+        sender.serialize_underlay(0, &mut packet);
+        assert_eq!(
+            &packet[..header.len()],
+            &[
+                2, 0, 0, 0, 0, 10, 2, 0, 0, 0, 0, 11, 8, 0, 69, 0, 0, 78, 0, 0, 0, 0, 64, 17, 100,
+                155, 10, 1, 1, 1, 10, 1, 1, 2, 192, 0, 18, 181, 0, 58, 0, 0, 8, 0, 0, 0, 0, 0, 0,
+                0
+            ]
+        );
+        assert_eq!(&packet[header.len()..], &INNER_PACKET[..]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serialize_ipv6_underlay() {
+        let sender = afpacket_sender(
+            "2001:db8::1".parse().unwrap(),
+            "2001:db8::2".parse().unwrap(),
+        );
+        let header = [
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 134, 221, 96, 0, 0, 0, 0, 0, 17, 64, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 1, 13, 184, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+            192, 0, 18, 181, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut packet = [&header[..], &INNER_PACKET[..]].concat();
+        sender.serialize_underlay(0, &mut packet);
+        // payload length and udp length are 58, flow label is 0xd8a89
+        assert_eq!(
+            &packet[..header.len()],
+            &[
+                2, 0, 0, 0, 0, 10, 2, 0, 0, 0, 0, 11, 134, 221, 96, 13, 138, 137, 0, 58, 17, 64,
+                32, 1, 13, 184, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 32, 1, 13, 184, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 2, 192, 0, 18, 181, 0, 58, 222, 241, 8, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+        assert_eq!(&packet[header.len()..], &INNER_PACKET[..]);
+
+        // the checksum over the pseudo header and udp datagram adds up to 0xffff
+        let mut sum = AfpacketSender::sum(0, &packet[IPV6_SRC_OFFSET..IPV6_PACKET_SIZE]);
+        sum += (packet.len() - IPV6_PACKET_SIZE) as u32 + u8::from(IpProtocol::UDP) as u32;
+        assert_eq!(
+            AfpacketSender::sum(sum, &packet[IPV6_PACKET_SIZE..]),
+            0xffff
+        );
+
+        // serializing again with a stale checksum keeps the same result
+        let serialized = packet.clone();
+        sender.serialize_underlay(0, &mut packet);
+        assert_eq!(packet, serialized);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn flow_label_by_inner_flow() {
+        let label = inner_flow_label(&INNER_PACKET);
+        assert_eq!(label, 0xd8a89);
+        assert_eq!(label & !FLOW_LABEL_MASK, 0);
+
+        let mut other = INNER_PACKET;
+        // another source port
+        other[35] = 211;
+        assert_ne!(inner_flow_label(&other), label);
+
+        // vlan tagged inner packet hashes to the same label
+        let tagged = [
+            &INNER_PACKET[..ETH_TYPE_OFFSET],
+            &[0x81, 0, 0, 100],
+            &INNER_PACKET[ETH_TYPE_OFFSET..],
+        ]
+        .concat();
+        assert_eq!(inner_flow_label(&tagged), label);
+
+        assert_eq!(inner_flow_label(&INNER_PACKET[..20]), 0);
+    }
+}
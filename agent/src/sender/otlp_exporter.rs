@@ -0,0 +1,772 @@
+/*
+ * Copyright (c) 2024 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Weak,
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{debug, error, info, warn};
+use md5::{Digest, Md5};
+use rand::{thread_rng, RngCore};
+use tokio::runtime::Runtime;
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+use super::{uniform_sender::Tee, QUEUE_BATCH_SIZE};
+use crate::config::OutputsOtlp;
+use crate::flow_generator::protocol_logs::{
+    pb_adapter::{L7ProtocolSendLog, L7Request},
+    BoxAppProtoLogsData, L7ResponseStatus, MetaAppProto,
+};
+use crate::metric::document::TapSide;
+use crate::utils::stats::{
+    self, Collector, Countable, Counter, CounterType, CounterValue, QueueStats, RefCountable,
+};
+use public::{
+    l7_protocol::L7Protocol,
+    proto::integration::opentelemetry::proto::{
+        collector::trace::v1::{
+            trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+        },
+        common::v1::{any_value::Value, AnyValue, InstrumentationScope, KeyValue},
+        resource::v1::Resource,
+        trace::v1::{span::SpanKind, status::StatusCode, ResourceSpans, ScopeSpans, Span, Status},
+    },
+    queue::{self, Error, Receiver},
+};
+
+const SCOPE_NAME: &str = "deepflow-agent";
+// according to https://opentelemetry.io/docs/specs/semconv/resource/#service
+const UNKNOWN_SERVICE_NAME: &str = "unknown_service:deepflow-agent";
+
+#[derive(Debug, Default)]
+pub struct OtlpExporterCounter {
+    // spans accepted by the collector
+    exported: AtomicU64,
+    // spans dropped by the tee when the buffer is full, or after all retries failed
+    dropped: Arc<AtomicU64>,
+    // failed export requests, retries included
+    export_errors: AtomicU64,
+}
+
+impl RefCountable for OtlpExporterCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "exported",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.exported.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "export-errors",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.export_errors.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(Value::StringValue(value)),
+        }),
+    }
+}
+
+fn int_attribute(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_owned(),
+        value: Some(AnyValue {
+            value: Some(Value::IntValue(value)),
+        }),
+    }
+}
+
+fn is_sql(protocol: L7Protocol) -> bool {
+    matches!(
+        protocol,
+        L7Protocol::MySQL | L7Protocol::PostgreSQL | L7Protocol::Oracle | L7Protocol::Dameng
+    )
+}
+
+fn span_name(protocol: L7Protocol, req: &L7Request) -> String {
+    let name = match protocol {
+        L7Protocol::Http1 | L7Protocol::Http2 => {
+            if req.req_type.is_empty() || req.endpoint.is_empty() {
+                format!("{}{}", req.req_type, req.endpoint)
+            } else {
+                format!("{} {}", req.req_type, req.endpoint)
+            }
+        }
+        // the endpoint of grpc is the path, such as /helloworld.Greeter/SayHello
+        L7Protocol::Grpc => req.endpoint.trim_start_matches('/').to_owned(),
+        _ if is_sql(protocol) && req.endpoint.is_empty() => {
+            // the operation of the statement, or the command if there is no statement
+            match req.resource.split_whitespace().next() {
+                Some(operation) => operation.to_ascii_uppercase(),
+                None => req.req_type.clone(),
+            }
+        }
+        _ if !req.endpoint.is_empty() => req.endpoint.clone(),
+        _ => req.req_type.clone(),
+    };
+    if name.is_empty() {
+        format!("{:?}", protocol)
+    } else {
+        name
+    }
+}
+
+fn decode_id<const N: usize>(id: &str) -> Option<[u8; N]> {
+    let id = id.trim();
+    if id.is_empty() {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    if id.len() <= N * 2 && id.len() % 2 == 0 {
+        // shorter hex ids, such as the 64-bit trace ids of jaeger, are padded with zeros
+        if hex::decode_to_slice(id, &mut bytes[N - id.len() / 2..]).is_ok() {
+            return if bytes.iter().all(|b| *b == 0) {
+                None
+            } else {
+                Some(bytes)
+            };
+        }
+    }
+    // ids not in hex, such as those of skywalking, are hashed
+    let digest = Md5::digest(id.as_bytes());
+    bytes.copy_from_slice(&digest[..N]);
+    Some(bytes)
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    while bytes.iter().all(|b| *b == 0) {
+        thread_rng().fill_bytes(&mut bytes);
+    }
+    bytes
+}
+
+fn span_kind(tap_side: TapSide) -> SpanKind {
+    if tap_side as u8 & TapSide::Client as u8 != 0 {
+        SpanKind::Client
+    } else if tap_side as u8 & TapSide::Server as u8 != 0 {
+        SpanKind::Server
+    } else {
+        SpanKind::Internal
+    }
+}
+
+// Returns the service name and the span of the log
+pub fn to_span(log: BoxAppProtoLogsData) -> (String, Span) {
+    let MetaAppProto {
+        base_info, l7_info, ..
+    } = *log.data;
+    let protocol = base_info.head.proto;
+    let mut send_log: L7ProtocolSendLog = l7_info.into();
+    if let Some(status) = log.override_resp_status {
+        send_log.resp.status = status;
+    }
+
+    let mut span = Span {
+        name: span_name(protocol, &send_log.req),
+        kind: span_kind(base_info.tap_side) as i32,
+        start_time_unix_nano: base_info.start_time.as_nanos(),
+        end_time_unix_nano: base_info.end_time.max(base_info.start_time).as_nanos(),
+        ..Default::default()
+    };
+
+    let trace_info = send_log.trace_info.unwrap_or_default();
+    let trace_id = trace_info
+        .trace_ids
+        .iter()
+        .find_map(|id| decode_id::<16>(id))
+        .unwrap_or_else(random_id);
+    span.trace_id = trace_id.to_vec();
+    span.span_id = trace_info
+        .span_id
+        .as_deref()
+        .and_then(decode_id::<8>)
+        .unwrap_or_else(random_id)
+        .to_vec();
+    if let Some(parent_span_id) = trace_info
+        .parent_span_id
+        .as_deref()
+        .and_then(decode_id::<8>)
+    {
+        span.parent_span_id = parent_span_id.to_vec();
+    }
+
+    let resp = &send_log.resp;
+    span.status = Some(match resp.status {
+        L7ResponseStatus::Ok => Status {
+            code: StatusCode::Ok as i32,
+            ..Default::default()
+        },
+        L7ResponseStatus::ClientError
+        | L7ResponseStatus::ServerError
+        | L7ResponseStatus::Timeout => Status {
+            code: StatusCode::Error as i32,
+            message: if resp.exception.is_empty() {
+                resp.status.to_string()
+            } else {
+                resp.exception.clone()
+            },
+        },
+        _ => Status::default(),
+    });
+
+    let attributes = &mut span.attributes;
+    attributes.push(string_attribute(
+        "df.l7_protocol",
+        format!("{:?}", protocol),
+    ));
+    attributes.push(int_attribute("df.flow_id", base_info.flow_id as i64));
+    attributes.push(string_attribute(
+        "df.tap_side",
+        format!("{:?}", base_info.tap_side),
+    ));
+    attributes.push(string_attribute(
+        "df.response_status",
+        resp.status.to_string(),
+    ));
+    attributes.push(string_attribute(
+        "client.address",
+        base_info.ip_src.to_string(),
+    ));
+    attributes.push(int_attribute("client.port", base_info.port_src as i64));
+    attributes.push(string_attribute(
+        "server.address",
+        base_info.ip_dst.to_string(),
+    ));
+    attributes.push(int_attribute("server.port", base_info.port_dst as i64));
+    let req = &send_log.req;
+    for (key, value) in [
+        ("df.request_type", &req.req_type),
+        ("df.request_domain", &req.domain),
+        ("df.request_resource", &req.resource),
+        ("df.endpoint", &req.endpoint),
+        ("df.response_exception", &resp.exception),
+        ("df.response_result", &resp.result),
+    ] {
+        if !value.is_empty() {
+            attributes.push(string_attribute(key, value.clone()));
+        }
+    }
+    if let Some(code) = resp.code {
+        attributes.push(int_attribute("df.response_code", code as i64));
+    }
+    // semantic conventions of https://opentelemetry.io/docs/specs/semconv/
+    match protocol {
+        L7Protocol::Http1 | L7Protocol::Http2 => {
+            attributes.push(string_attribute(
+                "http.request.method",
+                req.req_type.clone(),
+            ));
+            attributes.push(string_attribute("url.path", req.resource.clone()));
+            if let Some(code) = resp.code {
+                attributes.push(int_attribute("http.response.status_code", code as i64));
+            }
+        }
+        L7Protocol::Grpc => {
+            attributes.push(string_attribute("rpc.system", "grpc".to_owned()));
+            if let Some(code) = resp.code {
+                attributes.push(int_attribute("rpc.grpc.status_code", code as i64));
+            }
+        }
+        _ if is_sql(protocol) => {
+            attributes.push(string_attribute(
+                "db.system",
+                format!("{:?}", protocol).to_lowercase(),
+            ));
+            attributes.push(string_attribute("db.statement", req.resource.clone()));
+        }
+        _ => (),
+    }
+
+    let mut service_name = None;
+    if let Some(ext_info) = send_log.ext_info {
+        service_name = ext_info.service_name.filter(|s| !s.is_empty());
+        for kv in ext_info.attributes.unwrap_or_default() {
+            attributes.push(string_attribute(&kv.key, kv.val));
+        }
+    }
+
+    (
+        service_name.unwrap_or_else(|| UNKNOWN_SERVICE_NAME.to_owned()),
+        span,
+    )
+}
+
+fn export_request(spans: impl Iterator<Item = (String, Span)>) -> ExportTraceServiceRequest {
+    let mut services: HashMap<String, Vec<Span>> = HashMap::new();
+    for (service_name, span) in spans {
+        services.entry(service_name).or_default().push(span);
+    }
+    ExportTraceServiceRequest {
+        resource_spans: services
+            .into_iter()
+            .map(|(service_name, spans)| ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![string_attribute("service.name", service_name)],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: SCOPE_NAME.to_owned(),
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                        ..Default::default()
+                    }),
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+// according to https://opentelemetry.io/docs/specs/otlp/#failures
+fn is_retryable(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Cancelled
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::OutOfRange
+            | Code::Unavailable
+            | Code::DataLoss
+    )
+}
+
+// Exports copies of the l7 flow logs as spans to an OTLP/gRPC collector, the copies are buffered
+// in a queue of their own, so that a slow collector never blocks the uniform sender
+pub struct OtlpExporter {
+    name: &'static str,
+    config: OutputsOtlp,
+    input: Arc<Receiver<BoxAppProtoLogsData>>,
+    tee: Tee<BoxAppProtoLogsData>,
+    counter: Arc<OtlpExporterCounter>,
+    runtime: Arc<Runtime>,
+
+    running: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl OtlpExporter {
+    const QUEUE_READ_TIMEOUT: Duration = Duration::from_secs(1);
+    const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+    pub fn new(
+        name: &'static str,
+        config: &OutputsOtlp,
+        stats: &Collector,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        let (sender, receiver, queue_counter) = queue::bounded(config.buffer_size);
+        stats.register_countable(
+            &QueueStats {
+                module: name,
+                ..Default::default()
+            },
+            Countable::Owned(Box::new(queue_counter)),
+        );
+        let counter = Arc::new(OtlpExporterCounter::default());
+        stats.register_countable(
+            &stats::SingleTagModule("otlp_exporter", "endpoint", config.endpoint.clone()),
+            Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+        );
+        Self {
+            name,
+            config: config.clone(),
+            input: Arc::new(receiver),
+            tee: Tee::new(sender, config.buffer_size, counter.dropped.clone()),
+            counter,
+            runtime,
+            running: Arc::new(AtomicBool::new(false)),
+            thread_handle: None,
+        }
+    }
+
+    pub fn tee(&self) -> Tee<BoxAppProtoLogsData> {
+        self.tee.clone()
+    }
+
+    pub fn start(&mut self) {
+        if self.running.swap(true, Ordering::Relaxed) {
+            warn!("{} otlp exporter already started, do nothing.", self.name);
+            return;
+        }
+        let mut processor = Processor {
+            config: self.config.clone(),
+            counter: self.counter.clone(),
+            runtime: self.runtime.clone(),
+            running: self.running.clone(),
+            failing: false,
+        };
+        let input = self.input.clone();
+        self.thread_handle = Some(
+            thread::Builder::new()
+                .name("otlp-exporter".to_owned())
+                .spawn(move || processor.process(input))
+                .unwrap(),
+        );
+        info!("{} otlp exporter started", self.name);
+    }
+
+    pub fn notify_stop(&mut self) -> Option<JoinHandle<()>> {
+        if !self.running.swap(false, Ordering::Relaxed) {
+            warn!("{} otlp exporter already stopped, do nothing.", self.name);
+            return None;
+        }
+        info!("notified {} otlp exporter stopping", self.name);
+        self.thread_handle.take()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.notify_stop() {
+            let _ = handle.join();
+            info!("{} otlp exporter stopped", self.name);
+        }
+    }
+}
+
+struct Processor {
+    config: OutputsOtlp,
+    counter: Arc<OtlpExporterCounter>,
+    runtime: Arc<Runtime>,
+    running: Arc<AtomicBool>,
+    // only the first failure and the recovery are logged
+    failing: bool,
+}
+
+impl Processor {
+    fn process(&mut self, input: Arc<Receiver<BoxAppProtoLogsData>>) {
+        let channel = match Endpoint::from_shared(self.config.endpoint.clone()) {
+            Ok(endpoint) => {
+                // the connection is driven by the runtime
+                let _guard = self.runtime.enter();
+                endpoint
+                    .connect_timeout(self.config.export_timeout)
+                    .timeout(self.config.export_timeout)
+                    .connect_lazy()
+            }
+            Err(e) => {
+                error!(
+                    "otlp exporter endpoint {} invalid: {}",
+                    self.config.endpoint, e
+                );
+                return;
+            }
+        };
+        let mut client = TraceServiceClient::new(channel);
+        let read_timeout = self
+            .config
+            .batch_timeout
+            .min(OtlpExporter::QUEUE_READ_TIMEOUT);
+        let mut batch = Vec::with_capacity(QUEUE_BATCH_SIZE);
+        let mut spans = Vec::with_capacity(self.config.batch_size);
+        let mut batch_start = Instant::now();
+        while self.running.load(Ordering::Relaxed) {
+            match input.recv_all(&mut batch, Some(read_timeout)) {
+                Ok(_) => {
+                    for item in batch.drain(..) {
+                        if spans.is_empty() {
+                            batch_start = Instant::now();
+                        }
+                        spans.push(to_span(item));
+                        if spans.len() >= self.config.batch_size {
+                            self.export(&mut client, &mut spans);
+                        }
+                    }
+                }
+                Err(Error::Timeout) => (),
+                Err(Error::Terminated(..)) => break,
+                Err(Error::BatchTooLarge(_)) => unreachable!(),
+            }
+            if !spans.is_empty() && batch_start.elapsed() >= self.config.batch_timeout {
+                self.export(&mut client, &mut spans);
+            }
+        }
+        if !spans.is_empty() {
+            self.export(&mut client, &mut spans);
+        }
+    }
+
+    // Spans are dropped after all retries failed, the backoff doubles on every retry
+    fn export(
+        &mut self,
+        client: &mut TraceServiceClient<Channel>,
+        spans: &mut Vec<(String, Span)>,
+    ) {
+        let count = spans.len() as u64;
+        let request = export_request(spans.drain(..));
+        let mut backoff = self.config.retry_backoff;
+        let mut retries = 0;
+        loop {
+            let e = match self.runtime.block_on(client.export(request.clone())) {
+                Ok(_) => {
+                    self.counter.exported.fetch_add(count, Ordering::Relaxed);
+                    if self.failing {
+                        self.failing = false;
+                        info!("otlp exporter recovered");
+                    }
+                    return;
+                }
+                Err(e) => e,
+            };
+            self.counter.export_errors.fetch_add(1, Ordering::Relaxed);
+            if !self.failing {
+                self.failing = true;
+                warn!("otlp exporter export failed: {}", e);
+            } else {
+                debug!("otlp exporter export failed: {}", e);
+            }
+            if !is_retryable(e.code()) || retries >= self.config.max_retries || !self.sleep(backoff)
+            {
+                break;
+            }
+            retries += 1;
+            backoff = (backoff * 2).min(OtlpExporter::MAX_RETRY_BACKOFF);
+        }
+        self.counter.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    // Returns false if stopped during the sleep
+    fn sleep(&self, duration: Duration) -> bool {
+        const STEP: Duration = Duration::from_millis(100);
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            if !self.running.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(STEP.min(duration.saturating_sub(start.elapsed())));
+        }
+        self.running.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::common::{flow::PacketDirection, l7_protocol_info::L7ProtocolInfo, Timestamp};
+    use crate::flow_generator::protocol_logs::{
+        http::Method, AppProtoHead, AppProtoLogsBaseInfo, HttpInfo, MysqlInfo, PrioField,
+        PrioFields,
+    };
+
+    fn app_proto_log(
+        tap_side: TapSide,
+        proto: L7Protocol,
+        l7_info: L7ProtocolInfo,
+    ) -> BoxAppProtoLogsData {
+        let base_info = AppProtoLogsBaseInfo {
+            start_time: Timestamp::from_micros(1_700_000_000_000_000),
+            end_time: Timestamp::from_micros(1_700_000_000_002_000),
+            flow_id: 42,
+            tap_side,
+            head: AppProtoHead {
+                proto,
+                ..Default::default()
+            },
+            ip_src: IpAddr::V4(Ipv4Addr::new(10, 1, 1, 1)),
+            ip_dst: IpAddr::V4(Ipv4Addr::new(10, 1, 1, 2)),
+            port_src: 51234,
+            port_dst: 8080,
+            ..Default::default()
+        };
+        BoxAppProtoLogsData::new(
+            Box::new(MetaAppProto {
+                base_info,
+                direction: PacketDirection::ClientToServer,
+                direction_score: 0,
+                l7_info,
+            }),
+            None,
+        )
+    }
+
+    fn attribute<'a>(span: &'a Span, key: &str) -> Option<&'a Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.as_ref())
+            .and_then(|v| v.value.as_ref())
+    }
+
+    #[test]
+    fn http_to_span() {
+        let mut info = HttpInfo::default();
+        info.proto = L7Protocol::Http1;
+        info.method = Method::Get;
+        info.path = "/api/v1/users/1001".to_owned();
+        info.host = "user.svc".to_owned();
+        info.endpoint = Some("/api/v1/users".to_owned());
+        info.status_code = Some(200);
+        info.status = L7ResponseStatus::Ok;
+        info.trace_ids = PrioFields(vec![PrioField::new(
+            0,
+            "4bf92f3577b34da6a3ce929d0e0e4736".to_owned(),
+        )]);
+        info.span_id = PrioField::new(0, "00f067aa0ba902b7".to_owned());
+
+        let (service_name, span) = to_span(app_proto_log(
+            TapSide::ServerProcess,
+            L7Protocol::Http1,
+            L7ProtocolInfo::HttpInfo(info),
+        ));
+        assert_eq!(service_name, UNKNOWN_SERVICE_NAME);
+        assert_eq!(span.name, "GET /api/v1/users");
+        assert_eq!(span.kind, SpanKind::Server as i32);
+        assert_eq!(
+            hex::encode(&span.trace_id),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(hex::encode(&span.span_id), "00f067aa0ba902b7");
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.start_time_unix_nano, 1_700_000_000_000_000_000);
+        assert_eq!(span.end_time_unix_nano, 1_700_000_000_002_000_000);
+        assert_eq!(span.status.as_ref().unwrap().code, StatusCode::Ok as i32);
+        assert_eq!(
+            attribute(&span, "http.request.method"),
+            Some(&Value::StringValue("GET".to_owned()))
+        );
+        assert_eq!(
+            attribute(&span, "url.path"),
+            Some(&Value::StringValue("/api/v1/users/1001".to_owned()))
+        );
+        assert_eq!(
+            attribute(&span, "http.response.status_code"),
+            Some(&Value::IntValue(200))
+        );
+        assert_eq!(
+            attribute(&span, "server.port"),
+            Some(&Value::IntValue(8080))
+        );
+
+        // 64-bit jaeger trace ids are padded, other ids are hashed
+        assert_eq!(
+            hex::encode(decode_id::<16>("a3ce929d0e0e4736").unwrap()),
+            "0000000000000000a3ce929d0e0e4736"
+        );
+        assert!(decode_id::<16>("00000000000000000000000000000000").is_none());
+        let hashed = decode_id::<16>("2.63.16588826153550001").unwrap();
+        assert_eq!(decode_id::<16>("2.63.16588826153550001"), Some(hashed));
+    }
+
+    #[test]
+    fn mysql_to_span() {
+        let mut info = MysqlInfo::default();
+        // COM_QUERY
+        info.command = 3;
+        info.context = "select * from users where id = 1001".to_owned();
+        info.status = L7ResponseStatus::ServerError;
+        info.error_code = Some(1146);
+        info.error_message = "Table 'test.users' doesn't exist".to_owned();
+
+        let (_, span) = to_span(app_proto_log(
+            TapSide::ClientProcess,
+            L7Protocol::MySQL,
+            L7ProtocolInfo::MysqlInfo(info),
+        ));
+        assert_eq!(span.name, "SELECT");
+        assert_eq!(span.kind, SpanKind::Client as i32);
+        // random ids without tracing tags
+        assert_eq!(span.trace_id.len(), 16);
+        assert_eq!(span.span_id.len(), 8);
+        let status = span.status.as_ref().unwrap();
+        assert_eq!(status.code, StatusCode::Error as i32);
+        assert_eq!(status.message, "Table 'test.users' doesn't exist");
+        assert_eq!(
+            attribute(&span, "db.system"),
+            Some(&Value::StringValue("mysql".to_owned()))
+        );
+        assert_eq!(
+            attribute(&span, "db.statement"),
+            Some(&Value::StringValue(
+                "select * from users where id = 1001".to_owned()
+            ))
+        );
+        assert_eq!(
+            attribute(&span, "df.request_type"),
+            Some(&Value::StringValue("COM_QUERY".to_owned()))
+        );
+        assert_eq!(
+            attribute(&span, "df.response_code"),
+            Some(&Value::IntValue(1146))
+        );
+
+        // the status is overridden as in the uniform sender
+        let mut log = app_proto_log(
+            TapSide::ClientProcess,
+            L7Protocol::MySQL,
+            L7ProtocolInfo::MysqlInfo(MysqlInfo::default()),
+        );
+        log.override_resp_status = Some(L7ResponseStatus::Timeout);
+        let (_, span) = to_span(log);
+        assert_eq!(span.name, "MySQL");
+        let status = span.status.as_ref().unwrap();
+        assert_eq!(status.code, StatusCode::Error as i32);
+        assert_eq!(status.message, L7ResponseStatus::Timeout.to_string());
+    }
+
+    #[test]
+    fn group_by_service() {
+        let span = |name: &str| Span {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let request = export_request(
+            vec![
+                ("a".to_owned(), span("1")),
+                ("b".to_owned(), span("2")),
+                ("a".to_owned(), span("3")),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(request.resource_spans.len(), 2);
+        let a = request
+            .resource_spans
+            .iter()
+            .find(|r| {
+                r.resource.as_ref().unwrap().attributes[0].value
+                    == Some(AnyValue {
+                        value: Some(Value::StringValue("a".to_owned())),
+                    })
+            })
+            .unwrap();
+        assert_eq!(a.scope_spans[0].spans.len(), 2);
+        assert!(is_retryable(Code::Unavailable));
+        assert!(!is_retryable(Code::InvalidArgument));
+    }
+}
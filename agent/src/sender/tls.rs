@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2025 Yunshan Networks
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info};
+use rustls::{
+    crypto::ring, pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+};
+
+use crate::config::handler::IngesterTlsConfig;
+
+pub type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Connects the TLS data socket to the ingester.
+//
+// The client config is kept across reconnections, so that the sessions cached in it are resumed
+// instead of doing full handshakes. It is loaded again on the next connection if loading failed.
+pub struct TlsConnector {
+    settings: IngesterTlsConfig,
+    config: Option<Arc<ClientConfig>>,
+}
+
+impl TlsConnector {
+    pub fn new(settings: IngesterTlsConfig) -> Self {
+        Self {
+            settings,
+            config: None,
+        }
+    }
+
+    pub fn settings(&self) -> &IngesterTlsConfig {
+        &self.settings
+    }
+
+    fn load_client_config(&self) -> io::Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        for path in self.settings.ca_files.iter() {
+            let file = match File::open(path) {
+                Ok(f) => f,
+                // not all controller ips have certificate files
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    debug!("tls ca file {} not found", path);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            for cert in rustls_pemfile::certs(&mut BufReader::new(file)) {
+                roots
+                    .add(cert?)
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+            }
+        }
+        if roots.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("no ca certificate found in {:?}", self.settings.ca_files),
+            ));
+        }
+        info!(
+            "tls loaded {} ca certificates from {:?}",
+            roots.len(),
+            self.settings.ca_files
+        );
+        Ok(
+            ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    }
+
+    // Completes the handshake on the connected tcp stream
+    pub fn connect(&mut self, tcp_stream: TcpStream) -> io::Result<TlsStream> {
+        let config = match self.config.as_ref() {
+            Some(c) => c.clone(),
+            None => {
+                let c = Arc::new(self.load_client_config()?);
+                self.config = Some(c.clone());
+                c
+            }
+        };
+        let server_name = ServerName::try_from(self.settings.server_name.clone())
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+        let mut conn = ClientConnection::new(config, server_name)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+
+        let mut tcp_stream = tcp_stream;
+        let read_timeout = tcp_stream.read_timeout()?;
+        tcp_stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut tcp_stream)?;
+        }
+        tcp_stream.set_read_timeout(read_timeout)?;
+        Ok(StreamOwned::new(conn, tcp_stream))
+    }
+}
+
+// Whether the handshake failed because the certificate of the ingester was not verified
+pub fn is_certificate_error(e: &io::Error) -> bool {
+    matches!(
+        e.get_ref().and_then(|e| e.downcast_ref::<rustls::Error>()),
+        Some(rustls::Error::InvalidCertificate(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use rustls::CertificateError;
+
+    fn connector(ca_files: Vec<String>) -> TlsConnector {
+        TlsConnector::new(IngesterTlsConfig {
+            ca_files,
+            server_name: "127.0.0.1".to_owned(),
+        })
+    }
+
+    #[test]
+    fn load_ca_files() {
+        let no_ca = connector(vec![
+            "/nonexistent/deepflow-server.cert.127.0.0.1".to_owned()
+        ]);
+        assert_eq!(
+            no_ca.load_client_config().unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let path = std::env::temp_dir().join(format!("deepflow-tls-test-{}", std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(b"not a certificate\n")
+            .unwrap();
+        let invalid = connector(vec![path.to_str().unwrap().to_owned()]);
+        assert!(invalid.load_client_config().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn certificate_error() {
+        let e = io::Error::new(
+            ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer),
+        );
+        assert!(is_certificate_error(&e));
+        let e = io::Error::new(ErrorKind::InvalidData, rustls::Error::DecryptError);
+        assert!(!is_certificate_error(&e));
+        assert!(!is_certificate_error(&io::Error::from(
+            ErrorKind::ConnectionRefused
+        )));
+    }
+}
@@ -14,33 +14,38 @@
  * limitations under the License.
  */
 
-use std::fs::{create_dir_all, rename, File, OpenOptions};
-use std::io::{BufWriter, ErrorKind, Write};
+use std::io::{self, ErrorKind, Write};
+use std::iter;
 use std::marker::PhantomData;
-use std::net::{Shutdown, TcpStream};
-use std::path::Path;
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::slice;
 use std::sync::Mutex;
 use std::sync::{
-    atomic::{AtomicBool, AtomicU64, Ordering},
-    Arc, Weak,
+    atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+    mpsc, Arc, Weak,
 };
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant, SystemTime};
 
 use arc_swap::access::Access;
 use lazy_static::lazy_static;
-use log::{debug, error, info, warn};
+use log::{debug, error, info, log, warn, Level};
 use public::{
     leaky_bucket::LeakyBucket,
     sender::{SendMessageType, Sendable},
 };
 use rand::{thread_rng, RngCore};
 
-use super::{get_sender_id, QUEUE_BATCH_SIZE};
+use super::{
+    data_file::RotatingFile,
+    get_sender_id,
+    tls::{self, TlsConnector, TlsStream},
+    QUEUE_BATCH_SIZE,
+};
 
 use crate::config::{
-    handler::{SenderAccess, SenderConfig},
-    TrafficOverflowAction,
+    handler::{IngesterTlsConfig, SenderAccess, SenderConfig},
+    SocketBalance, TrafficOverflowAction,
 };
 use crate::exception::ExceptionHandler;
 use crate::trident::SenderEncoder;
@@ -48,9 +53,8 @@ use crate::utils::stats::{
     self, Collector, Countable, Counter, CounterType, CounterValue, RefCountable,
 };
 use public::proto::agent::{Exception, SocketType};
-use public::queue::{Error, Receiver};
+use public::queue::{self, Error, Receiver};
 
-const PRE_FILE_SUFFIX: &str = ".pre";
 const MAX_WAIT_TIMES: u32 = 100;
 
 #[derive(Debug, Default)]
@@ -61,6 +65,21 @@ pub struct SenderCounter {
     pub tx_bytes: AtomicU64,
     pub dropped: AtomicU64,
     pub waited: AtomicU64,
+    pub tls_cert_verify_failed: AtomicU64,
+    // bytes before compression and time spent compressing them
+    compress_raw_bytes: AtomicU64,
+    compress_time: AtomicU64, // ns
+    // batches sent and the documents in them
+    batches: AtomicU64,
+    batch_documents: AtomicU64,
+    flush_by_bytes: AtomicU64,
+    flush_by_documents: AtomicU64,
+    flush_by_linger: AtomicU64,
+    flush_by_idle: AtomicU64,
+    flush_by_other: AtomicU64,
+    // size of the data file being written and the rotations of it, only for the FILE socket type
+    pub file_size: AtomicU64,
+    pub file_rotations: AtomicU64,
 }
 
 impl RefCountable for SenderCounter {
@@ -71,6 +90,21 @@ impl RefCountable for SenderCounter {
         if raw_bytes > 0 {
             compression_ratio = ((1.0 - tx_bytes as f64 / raw_bytes as f64) * 100.0).round() as u64;
         }
+        let mut compression_cpu_time = 0; // us per MiB
+        let compress_raw_bytes = self.compress_raw_bytes.swap(0, Ordering::Relaxed);
+        let compress_time = self.compress_time.swap(0, Ordering::Relaxed);
+        if compress_raw_bytes > 0 {
+            compression_cpu_time = (compress_time as f64 / 1000.0 * (1 << 20) as f64
+                / compress_raw_bytes as f64)
+                .round() as u64;
+        }
+        let (mut avg_batch_bytes, mut avg_batch_documents) = (0, 0);
+        let batches = self.batches.swap(0, Ordering::Relaxed);
+        let batch_documents = self.batch_documents.swap(0, Ordering::Relaxed);
+        if batches > 0 {
+            avg_batch_bytes = raw_bytes / batches;
+            avg_batch_documents = batch_documents / batches;
+        }
         vec![
             (
                 "rx",
@@ -89,9 +123,14 @@ impl RefCountable for SenderCounter {
             ),
             (
                 "compression-ratio",
-                CounterType::Counted,
+                CounterType::Gauged,
                 CounterValue::Unsigned(compression_ratio),
             ),
+            (
+                "compression-cpu-us-per-mb",
+                CounterType::Gauged,
+                CounterValue::Unsigned(compression_cpu_time),
+            ),
             (
                 "dropped",
                 CounterType::Counted,
@@ -102,6 +141,213 @@ impl RefCountable for SenderCounter {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.waited.swap(0, Ordering::Relaxed)),
             ),
+            (
+                "tls-cert-verify-failed",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tls_cert_verify_failed.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "avg-batch-bytes",
+                CounterType::Gauged,
+                CounterValue::Unsigned(avg_batch_bytes),
+            ),
+            (
+                "avg-batch-documents",
+                CounterType::Gauged,
+                CounterValue::Unsigned(avg_batch_documents),
+            ),
+            (
+                "flush-by-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flush_by_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "flush-by-documents",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flush_by_documents.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "flush-by-linger",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flush_by_linger.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "flush-by-idle",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flush_by_idle.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "flush-by-other",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.flush_by_other.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "file-size",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.file_size.load(Ordering::Relaxed)),
+            ),
+            (
+                "file-rotations",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.file_rotations.swap(0, Ordering::Relaxed)),
+            ),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SocketState {
+    #[default]
+    Disconnected = 0,
+    Connected = 1,
+    // waiting to reconnect after consecutive failures
+    Backoff = 2,
+}
+
+impl From<u8> for SocketState {
+    fn from(v: u8) -> Self {
+        match v {
+            1 => Self::Connected,
+            2 => Self::Backoff,
+            _ => Self::Disconnected,
+        }
+    }
+}
+
+// Health of a socket to the ingester, shown by `deepflow-agent-ctl rpc --get config`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketHealth {
+    pub sender: &'static str,
+    pub sender_id: usize,
+    pub socket: usize,
+    // the active ingester of the socket
+    pub ingester: String,
+    pub state: SocketState,
+    pub consecutive_failures: u64,
+    // unix timestamp in seconds, 0 if never connected
+    pub last_success: u64,
+    pub queued_bytes: u64,
+}
+
+lazy_static! {
+    static ref SOCKET_HEALTH: Mutex<Vec<(&'static str, usize, usize, Weak<SocketCounter>)>> =
+        Mutex::new(vec![]);
+}
+
+pub fn socket_health() -> Vec<SocketHealth> {
+    SOCKET_HEALTH
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(sender, sender_id, socket, counter)| {
+            counter.upgrade().map(|c| SocketHealth {
+                sender,
+                sender_id: *sender_id,
+                socket: *socket,
+                ingester: c.ingester.lock().unwrap().clone(),
+                state: c.state.load(Ordering::Relaxed).into(),
+                consecutive_failures: c.consecutive_failures.load(Ordering::Relaxed),
+                last_success: c.last_success.load(Ordering::Relaxed),
+                queued_bytes: c.queued_bytes.load(Ordering::Relaxed),
+            })
+        })
+        .collect()
+}
+
+// Counters and health of each socket to the ingester, to observe the skew and stale sockets
+#[derive(Debug, Default)]
+pub struct SocketCounter {
+    // data assigned to the socket
+    pub rx: AtomicU64,
+    pub tx: AtomicU64,
+    pub tx_bytes: AtomicU64,
+    pub dropped: AtomicU64,
+    pub switchovers: AtomicU64,
+
+    ingester: Mutex<String>,
+    // index of the active ingester in the failover list, 0 for the primary
+    active_ingester: AtomicU64,
+    state: AtomicU8,
+    consecutive_failures: AtomicU64,
+    last_success: AtomicU64, // s
+    // encoded and waiting to be sent
+    queued_bytes: AtomicU64,
+}
+
+impl SocketCounter {
+    fn update_health(&self, conn: &Connection) {
+        let state = if conn.tcp_stream.is_some() && !conn.reconnect {
+            SocketState::Connected
+        } else if conn.backoff.failures > 0 {
+            SocketState::Backoff
+        } else {
+            SocketState::Disconnected
+        };
+        self.state.store(state as u8, Ordering::Relaxed);
+        *self.ingester.lock().unwrap() = conn.dest_ip.clone();
+        self.active_ingester
+            .store(conn.failover.active as u64, Ordering::Relaxed);
+        self.consecutive_failures
+            .store(conn.backoff.failures as u64, Ordering::Relaxed);
+        self.last_success
+            .store(conn.last_connected.as_secs(), Ordering::Relaxed);
+    }
+}
+
+impl RefCountable for SocketCounter {
+    fn get_counters(&self) -> Vec<Counter> {
+        vec![
+            (
+                "rx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.rx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "tx-bytes",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.tx_bytes.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "dropped",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.dropped.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "ingester-switchovers",
+                CounterType::Counted,
+                CounterValue::Unsigned(self.switchovers.swap(0, Ordering::Relaxed)),
+            ),
+            (
+                "active-ingester",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.active_ingester.load(Ordering::Relaxed)),
+            ),
+            (
+                "state",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.state.load(Ordering::Relaxed) as u64),
+            ),
+            (
+                "consecutive-failures",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.consecutive_failures.load(Ordering::Relaxed)),
+            ),
+            (
+                "last-success-timestamp",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.last_success.load(Ordering::Relaxed)),
+            ),
+            (
+                "queued-bytes",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.queued_bytes.load(Ordering::Relaxed)),
+            ),
         ]
     }
 }
@@ -146,10 +392,38 @@ impl Header {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FlushReason {
+    // batch_max_bytes reached
+    Bytes,
+    // batch_max_documents reached
+    Documents,
+    // data cached longer than linger
+    Linger,
+    // no data received from the queue
+    Idle,
+    // socket rebalancing or sender stopping
+    Other,
+}
+
+impl FlushReason {
+    fn counter(self, counter: &SenderCounter) -> &AtomicU64 {
+        match self {
+            Self::Bytes => &counter.flush_by_bytes,
+            Self::Documents => &counter.flush_by_documents,
+            Self::Linger => &counter.flush_by_linger,
+            Self::Idle => &counter.flush_by_idle,
+            Self::Other => &counter.flush_by_other,
+        }
+    }
+}
+
 struct Encoder<T> {
     id: usize,
     header: Header,
+    compression_level: i32,
 
+    documents: usize,
     buffer: Vec<u8>,
     compressed_buffer: Vec<u8>,
     _marker: PhantomData<T>,
@@ -157,9 +431,17 @@ struct Encoder<T> {
 
 impl<T: Sendable> Encoder<T> {
     const BUFFER_LEN: usize = 256 << 10;
-    pub fn new(id: usize, msg_type: SendMessageType, agent_id: u16, encoder: u8) -> Self {
+    pub fn new(
+        id: usize,
+        msg_type: SendMessageType,
+        agent_id: u16,
+        encoder: u8,
+        compression_level: i32,
+    ) -> Self {
         Self {
             id,
+            compression_level,
+            documents: 0,
             buffer: Vec::with_capacity(Self::BUFFER_LEN),
             compressed_buffer: Vec::with_capacity(Self::BUFFER_LEN),
             header: Header {
@@ -191,12 +473,26 @@ impl<T: Sendable> Encoder<T> {
         let offset = self.buffer.len();
         self.buffer.extend_from_slice([0u8; 4].as_slice());
         match s.encode(&mut self.buffer) {
-            Ok(size) => self.buffer[offset..offset + 4]
-                .copy_from_slice((size as u32).to_le_bytes().as_slice()),
+            Ok(size) => {
+                self.buffer[offset..offset + 4]
+                    .copy_from_slice((size as u32).to_le_bytes().as_slice());
+                self.documents += 1;
+            }
             Err(e) => debug!("encode failed {}", e),
         };
     }
 
+    // Returns the reason if the batch is full
+    fn batch_full(&self, config: &SenderConfig) -> Option<FlushReason> {
+        if self.buffer_len() > config.batch_max_bytes {
+            Some(FlushReason::Bytes)
+        } else if config.batch_max_documents > 0 && self.documents >= config.batch_max_documents {
+            Some(FlushReason::Documents)
+        } else {
+            None
+        }
+    }
+
     fn add_header(&mut self) {
         self.header.encode(&mut self.buffer);
     }
@@ -230,6 +526,7 @@ impl<T: Sendable> Encoder<T> {
         match SenderEncoder::from(self.header.encoder).encode(
             &self.buffer[Header::HEADER_LEN..],
             &mut self.compressed_buffer,
+            self.compression_level,
         ) {
             Ok(_) => {
                 self.buffer.truncate(Header::HEADER_LEN);
@@ -256,6 +553,49 @@ impl<T: Sendable> Encoder<T> {
 
     pub fn reset_buffer(&mut self) {
         self.buffer.clear();
+        self.documents = 0;
+    }
+}
+
+// Copies the data of a sender into another bounded queue, the copies are dropped when the queue
+// is full, so that their consumer never blocks the sender
+pub struct Tee<T> {
+    output: queue::Sender<T>,
+    capacity: usize,
+    clone: fn(&T) -> T,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<T: Clone> Tee<T> {
+    pub fn new(output: queue::Sender<T>, capacity: usize, dropped: Arc<AtomicU64>) -> Self {
+        Self {
+            output,
+            capacity,
+            clone: T::clone,
+            dropped,
+        }
+    }
+}
+
+impl<T> Tee<T> {
+    pub fn send(&self, item: &T) {
+        // the sender is the only producer, the queue can not be filled by others after the check
+        if self.output.len() >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let _ = self.output.send((self.clone)(item));
+    }
+}
+
+impl<T> Clone for Tee<T> {
+    fn clone(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+            capacity: self.capacity,
+            clone: self.clone,
+            dropped: self.dropped.clone(),
+        }
     }
 }
 
@@ -273,7 +613,9 @@ pub struct UniformSenderThread<T> {
 
     private_shared_conn: Option<Arc<Mutex<Connection>>>,
     sender_encoder: SenderEncoder,
+    compression_level: i32,
     leaky_bucket: Arc<LeakyBucket>,
+    tees: Vec<Tee<T>>,
 }
 
 impl<T: Sendable> UniformSenderThread<T> {
@@ -299,10 +641,24 @@ impl<T: Sendable> UniformSenderThread<T> {
             exception_handler,
             private_shared_conn,
             sender_encoder,
+            compression_level: 0,
             leaky_bucket,
+            tees: vec![],
         }
     }
 
+    // Only for zstd, the default level is used if not set
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    // Copies of the data received are also sent to the tee, can be called more than once
+    pub fn with_tee(mut self, tee: Tee<T>) -> Self {
+        self.tees.push(tee);
+        self
+    }
+
     pub fn start(&mut self) {
         if self.running.swap(true, Ordering::Relaxed) {
             warn!(
@@ -322,8 +678,10 @@ impl<T: Sendable> UniformSenderThread<T> {
             self.exception_handler.clone(),
             self.private_shared_conn.clone(),
             self.sender_encoder,
+            self.compression_level,
             self.leaky_bucket.clone(),
         );
+        uniform_sender.tees = self.tees.clone();
         self.thread_handle = Some(
             thread::Builder::new()
                 .name("uniform-sender".to_owned())
@@ -370,29 +728,342 @@ enum ConnectionType {
     Private,
 }
 
+pub enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream>),
+}
+
+impl Stream {
+    fn shutdown(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.shutdown(Shutdown::Both),
+            Self::Tls(s) => {
+                // let the ingester know that the connection is closed on purpose
+                s.conn.send_close_notify();
+                let _ = s.flush();
+                s.sock.shutdown(Shutdown::Both)
+            }
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(s) => s.write(buf),
+            Self::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(s) => s.flush(),
+            Self::Tls(s) => s.flush(),
+        }
+    }
+}
+
+// Exponential backoff with jitter of reconnecting a socket to the ingester
+//
+// The n-th consecutive failure waits for a random interval in [d / 2, d], where
+// d = min(base * 2^(n - 1), max), so that agents do not reconnect together when the ingester restarts.
+struct ReconnectBackoff {
+    base: Duration,
+    max: Duration,
+    failures: u32,
+    interval: Duration,
+    last_attempt: Duration,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(2),
+            max: Duration::from_secs(300),
+            failures: 0,
+            interval: Duration::ZERO,
+            last_attempt: Duration::ZERO,
+        }
+    }
+}
+
+impl ReconnectBackoff {
+    fn set_bounds(&mut self, base: Duration, max: Duration) {
+        self.base = base;
+        self.max = max.max(base);
+    }
+
+    fn ready(&mut self, now: Duration) -> bool {
+        // If the local timestamp adjustment requires recalculating the interval
+        if self.last_attempt > now {
+            self.last_attempt = now;
+        }
+        self.last_attempt + self.interval <= now
+    }
+
+    // returns the interval before the next attempt
+    fn on_failure<R: RngCore>(&mut self, now: Duration, rng: &mut R) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let ceiling = self
+            .base
+            .saturating_mul(1u32.checked_shl(self.failures - 1).unwrap_or(u32::MAX))
+            .min(self.max);
+        let jitter = rng.next_u64() % (ceiling.as_millis() as u64 / 2 + 1);
+        self.interval = ceiling - Duration::from_millis(jitter);
+        self.last_attempt = now;
+        self.interval
+    }
+
+    fn on_success(&mut self) {
+        self.failures = 0;
+        self.interval = Duration::ZERO;
+    }
+
+    // reconnect immediately, e.g. to a new destination
+    fn reset(&mut self) {
+        self.on_success();
+        self.last_attempt = Duration::ZERO;
+    }
+}
+
+// Selects the ingester of a connection from the prioritized list
+//
+// The connection fails over to the next ingester after `threshold` consecutive failures of the
+// active one. While a backup is active the primary is probed, and it is failed back to only after
+// being reachable for `hold_down` in a row, to avoid flapping between ingesters.
+struct IngesterFailover {
+    // the primary first, then the backups
+    ips: Vec<String>,
+    threshold: u32,
+    hold_down: Duration,
+
+    active: usize,
+    failures: u32,
+    // since when the primary has been reachable while a backup is active
+    primary_up_since: Option<Duration>,
+    last_probe: Duration,
+}
+
+impl Default for IngesterFailover {
+    fn default() -> Self {
+        Self {
+            ips: vec![],
+            threshold: 3,
+            hold_down: Duration::from_secs(60),
+            active: 0,
+            failures: 0,
+            primary_up_since: None,
+            last_probe: Duration::ZERO,
+        }
+    }
+}
+
+impl IngesterFailover {
+    const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+    fn set_bounds(&mut self, threshold: u32, hold_down: Duration) {
+        self.threshold = threshold.max(1);
+        self.hold_down = hold_down;
+    }
+
+    fn reset(&mut self, ips: &[String]) {
+        self.ips = ips.to_vec();
+        self.switch_to(0);
+        self.last_probe = Duration::ZERO;
+    }
+
+    fn primary(&self) -> &str {
+        &self.ips[0]
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        self.active = index;
+        self.failures = 0;
+        self.primary_up_since = None;
+    }
+
+    // returns the ingester switched to, if any
+    fn on_failure(&mut self) -> Option<&str> {
+        if self.ips.len() <= 1 {
+            return None;
+        }
+        self.failures = self.failures.saturating_add(1);
+        if self.failures < self.threshold {
+            return None;
+        }
+        // the primary is retried after all the backups
+        self.switch_to((self.active + 1) % self.ips.len());
+        Some(&self.ips[self.active])
+    }
+
+    fn on_success(&mut self) {
+        self.failures = 0;
+    }
+
+    fn probe_due(&mut self, now: Duration) -> bool {
+        if self.active == 0 {
+            return false;
+        }
+        // If the local timestamp adjustment requires recalculating the interval
+        if self.last_probe > now {
+            self.last_probe = now;
+        }
+        self.last_probe + Self::PROBE_INTERVAL <= now
+    }
+
+    // returns true if failing back to the primary
+    fn on_probe(&mut self, now: Duration, reachable: bool) -> bool {
+        self.last_probe = now;
+        if self.active == 0 {
+            return false;
+        }
+        if !reachable {
+            self.primary_up_since = None;
+            return false;
+        }
+        // restarted if the local timestamp is adjusted backwards
+        let up_since = match self.primary_up_since {
+            Some(t) if t <= now => t,
+            _ => *self.primary_up_since.insert(now),
+        };
+        if now < up_since + self.hold_down {
+            return false;
+        }
+        self.switch_to(0);
+        true
+    }
+}
+
 pub struct Connection {
-    tcp_stream: Option<TcpStream>,
+    tcp_stream: Option<Stream>,
+    // set for TLS data socket
+    tls: Option<TlsConnector>,
 
-    reconnect_interval: u8,
+    backoff: ReconnectBackoff,
+    last_connected: Duration,
 
+    failover: IngesterFailover,
+    // result of the probe to the primary ingester running in the background
+    probe: Option<mpsc::Receiver<bool>>,
+    // the active ingester
     dest_ip: String,
     dest_port: u16,
 
     reconnect: bool,
-    last_reconnect: Duration,
 }
 
 impl Connection {
     pub fn new() -> Self {
         Self {
             tcp_stream: None,
-            reconnect_interval: 10,
+            tls: None,
+            backoff: ReconnectBackoff::default(),
+            last_connected: Duration::ZERO,
+            failover: IngesterFailover::default(),
+            probe: None,
             dest_ip: "127.0.0.1".to_string(),
             dest_port: 30033,
             reconnect: false,
-            last_reconnect: Duration::ZERO,
         }
     }
+
+    fn update_destination(
+        &mut self,
+        name: &str,
+        dest_ips: &[String],
+        dest_port: u16,
+        ingester_tls: Option<&IngesterTlsConfig>,
+    ) {
+        if self.failover.ips != dest_ips || self.dest_port != dest_port {
+            info!(
+                "{} sender update dest address from {}:{} to {}:{}, failover ingesters {:?}",
+                name,
+                self.dest_ip,
+                self.dest_port,
+                dest_ips[0],
+                dest_port,
+                &dest_ips[1..]
+            );
+            self.reconnect = true;
+            self.failover.reset(dest_ips);
+            // the result of a running probe is for the previous primary
+            self.probe = None;
+            self.dest_ip = dest_ips[0].clone();
+            self.dest_port = dest_port;
+            self.backoff.reset();
+        }
+        if self.tls.as_ref().map(|t| t.settings()) != ingester_tls {
+            info!(
+                "{} sender update tls of connection to {}:{} to {:?}",
+                name, self.dest_ip, dest_port, ingester_tls
+            );
+            self.reconnect = true;
+            self.tls = ingester_tls.cloned().map(TlsConnector::new);
+            self.backoff.reset();
+        }
+    }
+}
+
+// Maps hash keys to the multiple sockets of a sender through a fixed number of slots
+//
+// The home socket of a slot only changes when the number of sockets changes. The slots of a socket
+// down are failed over to the sockets alive, and are restored once it is reconnected.
+struct SocketSlots {
+    name: &'static str,
+    down: Vec<bool>,
+    alive: Vec<usize>,
+}
+
+impl SocketSlots {
+    const SLOT_COUNT: u64 = 256;
+
+    fn new(name: &'static str, sockets: usize) -> Self {
+        let mut slots = Self {
+            name,
+            down: vec![],
+            alive: vec![],
+        };
+        slots.resize(sockets);
+        slots
+    }
+
+    fn resize(&mut self, sockets: usize) {
+        self.down = vec![false; sockets.max(1)];
+        self.alive = (0..self.down.len()).collect();
+    }
+
+    fn is_down(&self, index: usize) -> bool {
+        self.down[index]
+    }
+
+    fn set_down(&mut self, index: usize, down: bool) {
+        if self.down.len() <= 1 || self.down[index] == down {
+            return;
+        }
+        self.down[index] = down;
+        self.alive = (0..self.down.len()).filter(|i| !self.down[*i]).collect();
+        if down {
+            info!(
+                "{} sender socket {} is down, fail over its slots to sockets {:?}",
+                self.name, index, self.alive
+            );
+        } else {
+            info!(
+                "{} sender socket {} is reconnected, restore its slots",
+                self.name, index
+            );
+        }
+    }
+
+    fn lookup(&self, key: u64) -> usize {
+        let slot = (key % Self::SLOT_COUNT) as usize;
+        let home = slot % self.down.len();
+        // keep the home socket if all are down, it will be reconnected on sending
+        if !self.down[home] || self.alive.is_empty() {
+            return home;
+        }
+        self.alive[slot % self.alive.len()]
+    }
 }
 
 pub struct UniformSender<T> {
@@ -403,17 +1074,28 @@ pub struct UniformSender<T> {
     counter: Arc<SenderCounter>,
     overwritten_count: u64,
 
-    encoder: Encoder<T>,
-    private_conn: Mutex<Connection>,
+    // one for each socket
+    encoders: Vec<Encoder<T>>,
+    sender_encoder: SenderEncoder,
+    compression_level: i32,
+    private_conns: Vec<Mutex<Connection>>,
     private_shared_conn: Option<Arc<Mutex<Connection>>>,
     global_shared_conn: Arc<Mutex<Connection>>,
     connection_type: ConnectionType,
     multiple_sockets_to_ingester: bool,
+    multiple_sockets_balance: SocketBalance,
+    socket_counters: Vec<Arc<SocketCounter>>,
+    slots: SocketSlots,
+    round_robin: u64,
     dest_ip: String,
+    failover_ips: Vec<String>,
     dest_port: u16,
+    ingester_tls: Option<IngesterTlsConfig>,
     max_throughput_mbps: u64,
     leaky_bucket: Arc<LeakyBucket>,
     last_traffic_overflow: Duration,
+    last_reconnect_error: Duration,
+    suppressed_reconnect_errors: u64,
 
     config: SenderAccess,
 
@@ -421,18 +1103,17 @@ pub struct UniformSender<T> {
     stats: Arc<Collector>,
     stats_registered: bool,
     exception_handler: ExceptionHandler,
-    buf_writer: Option<BufWriter<File>>,
-    file_path: String,
-    pre_file_path: String,
-    written_size: u64,
+    data_file: Option<RotatingFile>,
 
     cached: bool,
+    tees: Vec<Tee<T>>,
 }
 
 impl<T: Sendable> UniformSender<T> {
     const TCP_WRITE_TIMEOUT: u64 = 3; // s
     const QUEUE_READ_TIMEOUT: u64 = 3; // s
-    const DEFAULT_RECONNECT_INTERVAL: u8 = 10; // s
+    const RECONNECT_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
 
     pub fn new(
         id: usize,
@@ -444,6 +1125,7 @@ impl<T: Sendable> UniformSender<T> {
         exception_handler: ExceptionHandler,
         private_shared_conn: Option<Arc<Mutex<Connection>>>,
         sender_encoder: SenderEncoder,
+        compression_level: i32,
         leaky_bucket: Arc<LeakyBucket>,
     ) -> Self {
         let cfg = config.load();
@@ -453,44 +1135,65 @@ impl<T: Sendable> UniformSender<T> {
             input,
             counter: Arc::new(SenderCounter::default()),
             overwritten_count: 0,
-            encoder: Encoder::new(
+            encoders: vec![Encoder::new(
                 0,
                 SendMessageType::TaggedFlow,
                 cfg.agent_id,
                 u8::from(sender_encoder),
-            ),
+                compression_level,
+            )],
+            sender_encoder,
+            compression_level,
             config,
-            private_conn: Mutex::new(Connection::new()),
+            private_conns: vec![Mutex::new(Connection::new())],
             private_shared_conn,
             global_shared_conn: GLOBAL_CONNECTION.clone(),
             connection_type: ConnectionType::Global,
             multiple_sockets_to_ingester: false,
+            multiple_sockets_balance: SocketBalance::Hash,
+            socket_counters: vec![Arc::new(SocketCounter::default())],
+            slots: SocketSlots::new(name, 1),
+            round_robin: 0,
             dest_ip: "127.0.0.1".to_string(),
+            failover_ips: vec![],
             dest_port: cfg.dest_port,
+            ingester_tls: None,
             max_throughput_mbps: 0,
             leaky_bucket,
             last_traffic_overflow: Duration::ZERO,
+            last_reconnect_error: Duration::ZERO,
+            suppressed_reconnect_errors: 0,
 
             running,
             stats,
             stats_registered: false,
             exception_handler,
-            buf_writer: None,
-            file_path: String::new(),
-            pre_file_path: String::new(),
-            written_size: 0,
+            data_file: None,
             cached: true,
+            tees: vec![],
         }
     }
 
     fn update_connection(&mut self, cfg: &SenderConfig) {
+        self.multiple_sockets_balance = cfg.multiple_sockets_balance;
+        // shared connections are always single sockets
+        let sockets = if cfg.multiple_sockets_to_ingester && self.private_shared_conn.is_none() {
+            cfg.sockets_per_sender.max(1)
+        } else {
+            1
+        };
         if self.multiple_sockets_to_ingester != cfg.multiple_sockets_to_ingester
             || self.dest_ip != cfg.dest_ip
+            || self.failover_ips != cfg.failover_ips
             || self.dest_port != cfg.dest_port
+            || self.ingester_tls != cfg.ingester_tls
+            || self.encoders.len() != sockets
         {
             self.multiple_sockets_to_ingester = cfg.multiple_sockets_to_ingester;
             self.dest_ip = cfg.dest_ip.clone();
+            self.failover_ips = cfg.failover_ips.clone();
             self.dest_port = cfg.dest_port;
+            self.ingester_tls = cfg.ingester_tls.clone();
 
             let old_connnection_type = self.connection_type;
             // update connection type
@@ -503,7 +1206,9 @@ impl<T: Sendable> UniformSender<T> {
                 self.global_shared_conn.lock().unwrap().tcp_stream.take();
             } else {
                 self.connection_type = ConnectionType::Global;
-                self.private_conn.lock().unwrap().tcp_stream.take();
+                for conn in self.private_conns.iter() {
+                    conn.lock().unwrap().tcp_stream.take();
+                }
                 if let Some(conn) = self.private_shared_conn.as_ref() {
                     conn.lock().unwrap().tcp_stream.take();
                 }
@@ -515,77 +1220,195 @@ impl<T: Sendable> UniformSender<T> {
                 );
             }
 
-            let mut new_conn = match self.connection_type {
-                ConnectionType::Global => self.global_shared_conn.lock().unwrap(),
+            self.resize_sockets(cfg, sockets);
+
+            let new_conns = match self.connection_type {
+                ConnectionType::Global => slice::from_ref(&*self.global_shared_conn),
                 ConnectionType::PrivateShared => {
-                    self.private_shared_conn.as_mut().unwrap().lock().unwrap()
+                    slice::from_ref(&**self.private_shared_conn.as_ref().unwrap())
                 }
-                ConnectionType::Private => self.private_conn.lock().unwrap(),
+                ConnectionType::Private => &self.private_conns[..],
             };
-
-            if new_conn.dest_ip != self.dest_ip || new_conn.dest_port != self.dest_port {
-                info!(
-                    "{} sender update dest address from {}:{} to {}:{}",
-                    self.name, new_conn.dest_ip, new_conn.dest_port, self.dest_ip, self.dest_port
+            let dest_ips = iter::once(self.dest_ip.clone())
+                .chain(self.failover_ips.iter().cloned())
+                .collect::<Vec<_>>();
+            for conn in new_conns {
+                conn.lock().unwrap().update_destination(
+                    self.name,
+                    &dest_ips,
+                    self.dest_port,
+                    self.ingester_tls.as_ref(),
                 );
-                new_conn.reconnect = true;
-                new_conn.dest_ip = self.dest_ip.clone();
-                new_conn.dest_port = self.dest_port;
-                new_conn.last_reconnect = Duration::ZERO;
             }
         }
     }
 
-    fn flush_encoder(&mut self, config: &SenderConfig) {
+    // Hash slots are only rebalanced here, when the number of sockets changes
+    fn resize_sockets(&mut self, config: &SenderConfig, sockets: usize) {
+        let old_sockets = self.encoders.len();
+        if old_sockets == sockets {
+            return;
+        }
+        info!(
+            "{} sender update sockets from {} to {}",
+            self.name, old_sockets, sockets
+        );
+        // send the data cached before rebalancing, so that data of the same key cached before and
+        // after the change are not sent through different sockets at the same time
+        for index in 0..old_sockets {
+            self.flush_encoder(config, index, FlushReason::Other);
+        }
+        self.encoders.truncate(sockets);
+        self.private_conns.truncate(sockets);
+        let msg_type = self.encoders[0].header.msg_type;
+        for index in old_sockets..sockets {
+            self.encoders.push(Encoder::new(
+                index,
+                msg_type,
+                config.agent_id,
+                u8::from(self.sender_encoder),
+                self.compression_level,
+            ));
+            self.private_conns.push(Mutex::new(Connection::new()));
+        }
+        self.socket_counters = (0..sockets)
+            .map(|_| Arc::new(SocketCounter::default()))
+            .collect();
+        self.slots.resize(sockets);
+        if self.stats_registered {
+            self.register_socket_counters(msg_type);
+        }
+    }
+
+    fn select_socket(&mut self, send_item: &T) -> usize {
+        if self.encoders.len() == 1 {
+            return 0;
+        }
+        let key = match (self.multiple_sockets_balance, send_item.hash_key()) {
+            (SocketBalance::Hash, Some(key)) => key,
+            _ => {
+                self.round_robin = self.round_robin.wrapping_add(1);
+                self.round_robin
+            }
+        };
+        self.slots.lookup(key)
+    }
+
+    fn update_headers(&mut self, config: &SenderConfig) {
+        for encoder in self.encoders.iter_mut() {
+            encoder.update_header(self.name, self.id, config);
+        }
+    }
+
+    fn flush_encoders(&mut self, config: &SenderConfig, reason: FlushReason) {
+        self.cached = true;
+        for index in 0..self.encoders.len() {
+            if self.encoders[index].buffer_len() > 0 {
+                self.flush_encoder(config, index, reason);
+            } else if self.slots.is_down(index) {
+                // nothing is sent to a socket down, reconnect it here to restore its slots
+                self.check_connection(config, index);
+            }
+        }
+    }
+
+    fn flush_encoder(&mut self, config: &SenderConfig, index: usize, reason: FlushReason) {
         self.cached = true;
-        if self.encoder.buffer_len() > 0 {
+        let encoder = &mut self.encoders[index];
+        if encoder.buffer_len() > 0 {
             self.counter
                 .raw_bytes
-                .fetch_add(self.encoder.buffer_len() as u64, Ordering::Relaxed);
-            if SenderEncoder::from(self.encoder.header.encoder) != SenderEncoder::Raw {
-                self.encoder.compress_buffer();
+                .fetch_add(encoder.buffer_len() as u64, Ordering::Relaxed);
+            self.counter.batches.fetch_add(1, Ordering::Relaxed);
+            self.counter
+                .batch_documents
+                .fetch_add(encoder.documents as u64, Ordering::Relaxed);
+            reason
+                .counter(&self.counter)
+                .fetch_add(1, Ordering::Relaxed);
+            if SenderEncoder::from(encoder.header.encoder) != SenderEncoder::Raw {
+                let raw_bytes = encoder.buffer_len() as u64;
+                let start = Instant::now();
+                encoder.compress_buffer();
+                self.counter
+                    .compress_time
+                    .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                self.counter
+                    .compress_raw_bytes
+                    .fetch_add(raw_bytes, Ordering::Relaxed);
             }
-            self.encoder.set_header_frame_size();
-            self.send_buffer(config);
-            self.encoder.reset_buffer();
+            encoder.set_header_frame_size();
+            self.send_buffer(config, index);
+            self.encoders[index].reset_buffer();
+            self.socket_counters[index]
+                .queued_bytes
+                .store(0, Ordering::Relaxed);
         }
     }
 
-    fn send_buffer(&mut self, config: &SenderConfig) {
-        if self.is_traffic_overflow(config) {
-            return;
-        }
+    // Connects the socket if not connected, returns whether it is ready for sending
+    fn check_connection(&mut self, config: &SenderConfig, index: usize) -> bool {
         let mut conn = match self.connection_type {
             ConnectionType::Global => self.global_shared_conn.lock().unwrap(),
             ConnectionType::PrivateShared => {
-                self.private_shared_conn.as_mut().unwrap().lock().unwrap()
+                self.private_shared_conn.as_ref().unwrap().lock().unwrap()
             }
-            ConnectionType::Private => self.private_conn.lock().unwrap(),
+            ConnectionType::Private => self.private_conns[index].lock().unwrap(),
         };
 
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        conn.failover
+            .set_bounds(config.failover_threshold, config.failback_hold_down);
+        if conn.tcp_stream.is_some() && conn.probe.is_none() && conn.failover.probe_due(now) {
+            conn.probe = Self::probe(&self.name, conn.failover.primary(), conn.dest_port);
+            if conn.probe.is_none() {
+                // retried after PROBE_INTERVAL
+                conn.failover.on_probe(now, false);
+            }
+        }
+        let probe_result = match conn.probe.as_ref().map(|r| r.try_recv()) {
+            Some(Ok(reachable)) => Some(reachable),
+            Some(Err(mpsc::TryRecvError::Empty)) => None,
+            Some(Err(mpsc::TryRecvError::Disconnected)) => Some(false),
+            None => None,
+        };
+        if let Some(reachable) = probe_result {
+            conn.probe = None;
+            if conn.failover.on_probe(now, reachable) {
+                info!(
+                    "{} sender fail back from ingester {} to primary ingester {}",
+                    self.name,
+                    conn.dest_ip,
+                    conn.failover.primary()
+                );
+                conn.dest_ip = conn.failover.primary().to_owned();
+                conn.reconnect = true;
+                conn.backoff.reset();
+                self.socket_counters[index]
+                    .switchovers
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
         if conn.reconnect || conn.tcp_stream.is_none() {
             if !self.running.load(Ordering::Relaxed) {
-                return;
+                return false;
             }
-            if let Some(t) = conn.tcp_stream.take() {
-                if let Err(e) = t.shutdown(Shutdown::Both) {
+            if let Some(mut t) = conn.tcp_stream.take() {
+                if let Err(e) = t.shutdown() {
                     debug!("{} sender tcp stream shutdown failed {}", self.name, e);
                 }
             }
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap();
-            // If the local timestamp adjustment requires recalculating the interval
-            if conn.last_reconnect > now {
-                conn.last_reconnect = now;
-            }
-            if conn.last_reconnect + Duration::from_secs(conn.reconnect_interval as u64) > now {
-                return;
+            conn.backoff
+                .set_bounds(config.reconnect_backoff_base, config.reconnect_backoff_max);
+            if !conn.backoff.ready(now) {
+                return false;
             }
 
-            conn.last_reconnect = now;
-            conn.tcp_stream = TcpStream::connect((conn.dest_ip.clone(), conn.dest_port)).ok();
-            if let Some(tcp_stream) = conn.tcp_stream.as_mut() {
+            let tcp_stream = TcpStream::connect((conn.dest_ip.clone(), conn.dest_port)).ok();
+            let error = if let Some(tcp_stream) = tcp_stream {
                 if let Err(e) =
                     tcp_stream.set_write_timeout(Some(Duration::from_secs(Self::TCP_WRITE_TIMEOUT)))
                 {
@@ -593,42 +1416,180 @@ impl<T: Sendable> UniformSender<T> {
                         "{} sender tcp stream set write timeout failed {}",
                         self.name, e
                     );
-                    conn.tcp_stream.take();
-                    return;
+                    return false;
                 }
+                let stream = match conn.tls.as_mut() {
+                    Some(tls) => tls.connect(tcp_stream).map(|s| Stream::Tls(Box::new(s))),
+                    None => Ok(Stream::Tcp(tcp_stream)),
+                };
+                match stream {
+                    Ok(s) => {
+                        conn.tcp_stream = Some(s);
+                        None
+                    }
+                    Err(e) => {
+                        let cert_error = tls::is_certificate_error(&e);
+                        if cert_error {
+                            self.counter
+                                .tls_cert_verify_failed
+                                .fetch_add(1, Ordering::Relaxed);
+                            Some((
+                                Level::Error,
+                                format!(
+                                    "{} sender tls certificate verification of {}:{} failed: {}",
+                                    self.name, conn.dest_ip, conn.dest_port, e
+                                ),
+                            ))
+                        } else {
+                            Some((
+                                Level::Error,
+                                format!(
+                                    "{} sender tls connection to {}:{} failed: {}",
+                                    self.name, conn.dest_ip, conn.dest_port, e
+                                ),
+                            ))
+                        }
+                    }
+                }
+            } else if conn.dest_ip.is_empty() || conn.dest_ip == "0.0.0.0" {
+                Some((Level::Warn, "'analyzer_ip' is not assigned, please check whether the Agent is successfully registered".to_owned()))
+            } else {
+                Some((
+                    Level::Error,
+                    format!(
+                        "{} sender tcp connection to {}:{} failed",
+                        self.name, conn.dest_ip, conn.dest_port
+                    ),
+                ))
+            };
+
+            if let Some((level, error_msg)) = error {
+                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                self.socket_counters[index]
+                    .dropped
+                    .fetch_add(1, Ordering::Relaxed);
+                self.slots.set_down(index, true);
+                let interval = conn.backoff.on_failure(now, &mut thread_rng());
+                let failures = conn.backoff.failures;
+                if let Some(ingester) = conn.failover.on_failure().map(str::to_owned) {
+                    warn!(
+                        "{} sender fail over from ingester {} to {} after {} consecutive failures",
+                        self.name, conn.dest_ip, ingester, failures
+                    );
+                    conn.dest_ip = ingester;
+                    // connect to the new ingester immediately
+                    conn.backoff.reset();
+                    self.socket_counters[index]
+                        .switchovers
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                self.socket_counters[index].update_health(&conn);
+                drop(conn);
+                self.log_reconnect_error(
+                    now,
+                    level,
+                    format!(
+                        "{}, retry in {:?} after {} consecutive failures",
+                        error_msg, interval, failures
+                    ),
+                );
+                return false;
+            }
+
+            if conn.backoff.failures > 0 {
+                info!(
+                    "{} sender tcp connection to {}:{} succeed after {} failures.",
+                    self.name, conn.dest_ip, conn.dest_port, conn.backoff.failures
+                );
+            } else {
                 info!(
                     "{} sender tcp connection to {}:{} succeed.",
                     self.name, conn.dest_ip, conn.dest_port
                 );
-                conn.reconnect = false;
-                conn.reconnect_interval = 0;
-            } else {
-                if self.counter.dropped.load(Ordering::Relaxed) == 0 {
-                    if conn.dest_ip.is_empty() || conn.dest_ip == "0.0.0.0" {
-                        let error_msg = "'analyzer_ip' is not assigned, please check whether the Agent is successfully registered".into();
-                        warn!("{}", error_msg);
-                        self.exception_handler
-                            .set(Exception::AnalyzerSocketError, Some(error_msg));
-                    } else {
-                        let error_msg = format!(
-                            "{} sender tcp connection to {}:{} failed",
-                            self.name, conn.dest_ip, conn.dest_port
-                        );
-                        error!("{}", error_msg);
-                        self.exception_handler
-                            .set(Exception::AnalyzerSocketError, Some(error_msg));
-                    }
-                }
-                self.counter.dropped.fetch_add(1, Ordering::Relaxed);
-                // reconnect after waiting 10 seconds + random 5 seconds to prevent frequent reconnection
-                conn.reconnect_interval =
-                    Self::DEFAULT_RECONNECT_INTERVAL + (thread_rng().next_u64() % 5) as u8;
-                return;
+            }
+            conn.reconnect = false;
+            conn.backoff.on_success();
+            conn.failover.on_success();
+            conn.last_connected = now;
+            self.suppressed_reconnect_errors = 0;
+            self.slots.set_down(index, false);
+            self.socket_counters[index].update_health(&conn);
+        }
+        true
+    }
+
+    // Checks whether the primary ingester accepts connections, while failed over to a backup
+    //
+    // Resolving and connecting may block for seconds, so it runs in a separate thread and the
+    // result is polled on the following sends.
+    fn probe(name: &str, ip: &str, port: u16) -> Option<mpsc::Receiver<bool>> {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let ip = ip.to_owned();
+        let timeout = Self::PROBE_TIMEOUT;
+        let spawned = thread::Builder::new()
+            .name("ingester-probe".to_owned())
+            .spawn(move || {
+                let reachable = match (ip.as_str(), port).to_socket_addrs() {
+                    Ok(addrs) => addrs.into_iter().any(|addr| {
+                        TcpStream::connect_timeout(&addr, timeout)
+                            .map(|s| s.shutdown(Shutdown::Both))
+                            .is_ok()
+                    }),
+                    Err(_) => false,
+                };
+                let _ = sender.send(reachable);
+            });
+        match spawned {
+            Ok(_) => Some(receiver),
+            Err(e) => {
+                warn!("{} sender spawn ingester probe failed: {}", name, e);
+                None
             }
         }
+    }
+
+    // reconnection fails on every attempt while the ingester is down, log at most once a minute
+    fn log_reconnect_error(&mut self, now: Duration, level: Level, error_msg: String) {
+        if now >= self.last_reconnect_error
+            && now < self.last_reconnect_error + Self::RECONNECT_ERROR_LOG_INTERVAL
+        {
+            self.suppressed_reconnect_errors += 1;
+            return;
+        }
+        if self.suppressed_reconnect_errors > 0 {
+            log!(
+                level,
+                "{} ({} similar errors suppressed)",
+                error_msg,
+                self.suppressed_reconnect_errors
+            );
+        } else {
+            log!(level, "{}", error_msg);
+        }
+        self.exception_handler
+            .set(Exception::AnalyzerSocketError, Some(error_msg));
+        self.last_reconnect_error = now;
+        self.suppressed_reconnect_errors = 0;
+    }
+
+    fn send_buffer(&mut self, config: &SenderConfig, index: usize) {
+        if self.is_traffic_overflow(config, index) {
+            return;
+        }
+        if !self.check_connection(config, index) {
+            return;
+        }
+        let mut conn = match self.connection_type {
+            ConnectionType::Global => self.global_shared_conn.lock().unwrap(),
+            ConnectionType::PrivateShared => {
+                self.private_shared_conn.as_ref().unwrap().lock().unwrap()
+            }
+            ConnectionType::Private => self.private_conns[index].lock().unwrap(),
+        };
 
         let tcp_stream = conn.tcp_stream.as_mut().unwrap();
-        let buffer = &self.encoder.get_buffer();
+        let buffer = &self.encoders[index].get_buffer();
+        let socket_counter = &self.socket_counters[index];
         let mut write_offset = 0usize;
         while self.running.load(Ordering::Relaxed) {
             let result = tcp_stream.write(&buffer[write_offset..]);
@@ -640,6 +1601,10 @@ impl<T: Sendable> UniformSender<T> {
                         self.counter
                             .tx_bytes
                             .fetch_add(buffer.len() as u64, Ordering::Relaxed);
+                        socket_counter.tx.fetch_add(1, Ordering::Relaxed);
+                        socket_counter
+                            .tx_bytes
+                            .fetch_add(buffer.len() as u64, Ordering::Relaxed);
                         break;
                     }
                 }
@@ -658,7 +1623,10 @@ impl<T: Sendable> UniformSender<T> {
                             .set(Exception::AnalyzerSocketError, Some(error_msg));
                     }
                     self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+                    socket_counter.dropped.fetch_add(1, Ordering::Relaxed);
                     conn.tcp_stream.take();
+                    socket_counter.update_health(&conn);
+                    self.slots.set_down(index, true);
                     break;
                 }
             };
@@ -679,18 +1647,17 @@ impl<T: Sendable> UniformSender<T> {
         }
     }
 
-    fn is_traffic_overflow(&mut self, config: &SenderConfig) -> bool {
+    fn is_traffic_overflow(&mut self, config: &SenderConfig, index: usize) -> bool {
         if self.max_throughput_mbps == 0 {
             return false;
         }
+        let buffer_len = self.encoders[index].buffer_len() as u64;
         let mut overflow = false;
         if config.ingester_traffic_overflow_action == TrafficOverflowAction::Waiting {
             // When stopped, at least one acquire() is successfully triggered every 100ms, and the
             // loop can be exited quickly without getting stuck here.
             let mut wait_times = 0;
-            while !self.leaky_bucket.acquire(self.encoder.buffer_len() as u64)
-                && wait_times < MAX_WAIT_TIMES
-            {
+            while !self.leaky_bucket.acquire(buffer_len) && wait_times < MAX_WAIT_TIMES {
                 wait_times += 1;
                 // LeakyBucket token is updated every 100ms by default,
                 // wait 20ms each time until the token is acquired
@@ -701,7 +1668,7 @@ impl<T: Sendable> UniformSender<T> {
                 overflow = true;
             }
         } else {
-            if !self.leaky_bucket.acquire(self.encoder.buffer_len() as u64) {
+            if !self.leaky_bucket.acquire(buffer_len) {
                 overflow = true;
                 self.counter.dropped.fetch_add(1, Ordering::Relaxed);
             }
@@ -724,9 +1691,26 @@ impl<T: Sendable> UniformSender<T> {
             &stats::SingleTagModule("collect_sender", "type", message_type),
             Countable::Ref(Arc::downgrade(&self.counter) as Weak<dyn RefCountable>),
         );
+        self.register_socket_counters(message_type);
         self.stats_registered = true;
     }
 
+    fn register_socket_counters(&self, message_type: SendMessageType) {
+        let mut socket_health = SOCKET_HEALTH.lock().unwrap();
+        // counters of the sockets removed are dropped
+        socket_health.retain(|(_, _, _, counter)| counter.strong_count() > 0);
+        for (index, counter) in self.socket_counters.iter().enumerate() {
+            socket_health.push((self.name, self.id, index, Arc::downgrade(counter)));
+            self.stats.register_countable(
+                &stats::SenderSocketStats {
+                    message_type,
+                    index,
+                },
+                Countable::Ref(Arc::downgrade(counter) as Weak<dyn RefCountable>),
+            );
+        }
+    }
+
     pub fn process(&mut self) {
         let mut start_cached = Instant::now();
         let mut kv_string = String::with_capacity(2048);
@@ -739,13 +1723,14 @@ impl<T: Sendable> UniformSender<T> {
                 self.leaky_bucket.set_rate(Some(max_throughput_mbps << 17)); // Mbit -> byte
                 self.max_throughput_mbps = max_throughput_mbps;
             }
+            // cached data are sent when idle, no later than linger
             match self.input.recv_all(
                 &mut batch,
-                Some(Duration::from_secs(Self::QUEUE_READ_TIMEOUT)),
+                Some(Duration::from_secs(Self::QUEUE_READ_TIMEOUT).min(config.linger)),
             ) {
                 Ok(_) => {
-                    // guaranteed to be sent every 10 seconds
-                    if start_cached.elapsed() >= Duration::from_secs(10) {
+                    // guaranteed to be sent every linger
+                    if start_cached.elapsed() >= config.linger {
                         start_cached = Instant::now();
                         self.cached = false;
                     }
@@ -755,6 +1740,9 @@ impl<T: Sendable> UniformSender<T> {
                         }
                         let message_type = send_item.message_type();
                         self.counter.rx.fetch_add(1, Ordering::Relaxed);
+                        for tee in self.tees.iter() {
+                            tee.send(&send_item);
+                        }
                         debug!(
                             "{} sender send item {}: {:?}",
                             self.name, message_type, send_item
@@ -772,8 +1760,10 @@ impl<T: Sendable> UniformSender<T> {
                                     "{} sender send item {} failed {}",
                                     self.name, message_type, e
                                 );
-                                // reopen write file and overwritten
-                                let _ = self.buf_writer.take();
+                                // reopen the data file
+                                if let Some(f) = self.data_file.as_mut() {
+                                    f.close();
+                                }
                             }
                             self.counter.dropped.fetch_add(1, Ordering::Relaxed);
                         }
@@ -783,14 +1773,14 @@ impl<T: Sendable> UniformSender<T> {
                     SocketType::File => self.flush_writer(),
                     _ => {
                         self.update_connection(&config);
-                        self.encoder.update_header(self.name, self.id, &config);
-                        self.flush_encoder(&config);
+                        self.update_headers(&config);
+                        self.flush_encoders(&config, FlushReason::Idle);
                     }
                 },
                 Err(Error::Terminated(..)) => {
                     match socket_type {
                         SocketType::File => self.flush_writer(),
-                        _ => self.flush_encoder(&config),
+                        _ => self.flush_encoders(&config, FlushReason::Other),
                     }
                     break;
                 }
@@ -800,8 +1790,18 @@ impl<T: Sendable> UniformSender<T> {
     }
 
     pub fn flush_writer(&mut self) {
-        if let Some(buf_writer) = self.buf_writer.as_mut() {
-            _ = buf_writer.flush();
+        if let Some(data_file) = self.data_file.as_mut() {
+            _ = data_file.flush();
+            match data_file.rotate_if_expired() {
+                Ok(true) => {
+                    self.counter.file_rotations.fetch_add(1, Ordering::Relaxed);
+                    self.counter
+                        .file_size
+                        .store(data_file.size(), Ordering::Relaxed);
+                }
+                Ok(false) => (),
+                Err(e) => warn!("{} sender rotate data file failed: {}", self.name, e),
+            }
         }
     }
 
@@ -815,38 +1815,27 @@ impl<T: Sendable> UniformSender<T> {
         if kv_string.is_empty() {
             return Ok(());
         }
-        if self.file_path.is_empty() {
-            create_dir_all(&self.config.load().standalone_data_file_dir)?;
-            self.file_path = Path::new(&self.config.load().standalone_data_file_dir)
-                .join(send_item.file_name())
-                .to_str()
-                .unwrap()
-                .into();
-            self.pre_file_path = format!("{}{}", &self.file_path, PRE_FILE_SUFFIX);
+        if self.data_file.is_none() {
+            self.check_or_register_counterable(send_item.message_type());
+            self.data_file = Some(RotatingFile::new(
+                &config.standalone_data_file_dir,
+                send_item.file_name(),
+                config.agent_id,
+                config.standalone_data_file_size,
+                config.standalone_data_file_rotation_interval,
+                config.standalone_max_data_files,
+            ));
         }
 
-        if self.buf_writer.is_none() {
-            self.check_or_register_counterable(send_item.message_type());
-            let f = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(&self.file_path)?;
-            self.buf_writer = Some(BufWriter::new(f));
-        }
-
-        self.buf_writer
-            .as_mut()
-            .unwrap()
-            .write_all(kv_string.as_bytes())?;
-        self.written_size += kv_string.len() as u64;
+        let data_file = self.data_file.as_mut().unwrap();
+        let result = data_file.write(kv_string.as_bytes());
         kv_string.truncate(0);
-
-        if self.written_size > config.standalone_data_file_size {
-            self.buf_writer.as_mut().unwrap().flush()?;
-            self.buf_writer.take();
-            rename(&self.file_path, &self.pre_file_path)?;
-            self.written_size = 0;
+        if result? {
+            self.counter.file_rotations.fetch_add(1, Ordering::Relaxed);
         }
+        self.counter
+            .file_size
+            .store(data_file.size(), Ordering::Relaxed);
 
         Ok(())
     }
@@ -856,13 +1845,303 @@ impl<T: Sendable> UniformSender<T> {
         send_item: T,
         config: &SenderConfig,
     ) -> std::io::Result<()> {
-        self.encoder.cache_to_sender(send_item);
-        if !self.cached || self.encoder.buffer_len() > Encoder::<T>::BUFFER_LEN {
-            self.check_or_register_counterable(self.encoder.header.msg_type);
+        let index = self.select_socket(&send_item);
+        let encoder = &mut self.encoders[index];
+        encoder.cache_to_sender(send_item);
+        self.socket_counters[index]
+            .rx
+            .fetch_add(1, Ordering::Relaxed);
+        self.socket_counters[index]
+            .queued_bytes
+            .store(encoder.buffer_len() as u64, Ordering::Relaxed);
+        // data cached too long in all the sockets are sent together
+        let reason = if !self.cached {
+            Some(FlushReason::Linger)
+        } else {
+            encoder.batch_full(config)
+        };
+        if let Some(reason) = reason {
+            let msg_type = encoder.header.msg_type;
+            self.check_or_register_counterable(msg_type);
             self.update_connection(config);
-            self.encoder.update_header(self.name, self.id, config);
-            self.flush_encoder(config);
+            self.update_headers(config);
+            if reason == FlushReason::Linger {
+                self.flush_encoders(config, reason);
+            } else if index < self.encoders.len() {
+                // flushed already if the socket is removed
+                self.flush_encoder(config, index, reason);
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::mock::StepRng;
+
+    use crate::metric::{
+        document::{BoxedDocument, Document},
+        meter::{FlowMeter, Meter, UsageMeter},
+    };
+
+    fn assignment(slots: &SocketSlots) -> Vec<usize> {
+        (0..SocketSlots::SLOT_COUNT)
+            .map(|k| slots.lookup(k))
+            .collect()
+    }
+
+    #[test]
+    fn slots_stable_across_reconnects() {
+        let mut slots = SocketSlots::new("test", 4);
+        let origin = assignment(&slots);
+        for socket in 0..4 {
+            assert_eq!(origin.iter().filter(|s| **s == socket).count(), 64);
+        }
+        assert_eq!(slots.lookup(1), slots.lookup(1 + SocketSlots::SLOT_COUNT));
+
+        slots.set_down(1, true);
+        let failed_over = assignment(&slots);
+        for (slot, socket) in failed_over.iter().enumerate() {
+            if origin[slot] == 1 {
+                assert_ne!(*socket, 1);
+            } else {
+                assert_eq!(*socket, origin[slot]);
+            }
+        }
+        // slots of socket 1 are spread over all the sockets alive
+        for socket in [0, 2, 3] {
+            assert!(failed_over
+                .iter()
+                .zip(origin.iter())
+                .any(|(s, o)| *o == 1 && *s == socket));
+        }
+
+        slots.set_down(2, true);
+        assert!(assignment(&slots).iter().all(|s| *s == 0 || *s == 3));
+        slots.set_down(1, false);
+        slots.set_down(2, false);
+        assert_eq!(assignment(&slots), origin);
+
+        for socket in 0..4 {
+            slots.set_down(socket, true);
+        }
+        assert_eq!(assignment(&slots), origin);
+
+        slots.resize(4);
+        assert_eq!(assignment(&slots), origin);
+        slots.resize(1);
+        assert!(assignment(&slots).iter().all(|s| *s == 0));
+        slots.set_down(0, true);
+        assert!(!slots.is_down(0));
+    }
+
+    #[test]
+    fn same_tags_same_socket() {
+        let slots = SocketSlots::new("test", 4);
+        let document = |ip: [u8; 4], meter: Meter, timestamp: u32| {
+            let mut doc = Document::new(meter);
+            doc.timestamp = timestamp;
+            doc.tagger.ip = ip.into();
+            doc.tagger.server_port = 80;
+            BoxedDocument(Box::new(doc))
+        };
+        let mut sockets = vec![];
+        for i in 0..64 {
+            let key = document([10, 0, 0, i], Meter::Flow(FlowMeter::default()), 60)
+                .hash_key()
+                .unwrap();
+            for (meter, timestamp) in [
+                (Meter::Flow(FlowMeter::default()), 120),
+                (Meter::Usage(UsageMeter::default()), 60),
+            ] {
+                let other = document([10, 0, 0, i], meter, timestamp)
+                    .hash_key()
+                    .unwrap();
+                assert_eq!(key, other);
+                assert_eq!(slots.lookup(key), slots.lookup(other));
+            }
+            sockets.push(slots.lookup(key));
+        }
+        // different tags are spread over the sockets
+        for socket in 0..4 {
+            assert!(sockets.contains(&socket));
+        }
+    }
+
+    #[test]
+    fn batch_limits() {
+        let document = || BoxedDocument(Box::new(Document::new(Meter::Flow(FlowMeter::default()))));
+        let mut config = SenderConfig::default();
+        config.batch_max_bytes = 4 << 10;
+        config.batch_max_documents = 4;
+        let mut encoder = Encoder::<BoxedDocument>::new(0, SendMessageType::Metrics, 0, 0, 0);
+        for _ in 0..3 {
+            encoder.cache_to_sender(document());
+            assert_eq!(encoder.batch_full(&config), None);
+        }
+        encoder.cache_to_sender(document());
+        assert_eq!(encoder.batch_full(&config), Some(FlushReason::Documents));
+
+        // no limit of documents
+        config.batch_max_documents = 0;
+        assert_eq!(encoder.batch_full(&config), None);
+        while encoder.buffer_len() <= config.batch_max_bytes {
+            assert_eq!(encoder.batch_full(&config), None);
+            encoder.cache_to_sender(document());
+        }
+        assert_eq!(encoder.batch_full(&config), Some(FlushReason::Bytes));
+
+        encoder.reset_buffer();
+        config.batch_max_documents = 1;
+        assert_eq!(encoder.batch_full(&config), None);
+    }
+
+    #[test]
+    fn reconnect_backoff_schedule() {
+        // no jitter
+        let mut rng = StepRng::new(0, 0);
+        let mut backoff = ReconnectBackoff::default();
+        backoff.set_bounds(Duration::from_secs(1), Duration::from_secs(8));
+        let mut now = Duration::from_secs(1000);
+        assert!(backoff.ready(now));
+        let mut intervals = vec![];
+        for _ in 0..6 {
+            let interval = backoff.on_failure(now, &mut rng);
+            assert!(!backoff.ready(now + interval - Duration::from_millis(1)));
+            now += interval;
+            assert!(backoff.ready(now));
+            intervals.push(interval.as_secs());
+        }
+        assert_eq!(intervals, vec![1, 2, 4, 8, 8, 8]);
+        assert_eq!(backoff.failures, 6);
+
+        backoff.on_success();
+        assert_eq!(backoff.failures, 0);
+        assert!(backoff.ready(now));
+        assert_eq!(backoff.on_failure(now, &mut rng), Duration::from_secs(1));
+
+        // the local clock is adjusted backwards
+        now -= Duration::from_secs(100);
+        assert!(!backoff.ready(now));
+        assert!(backoff.ready(now + Duration::from_secs(1)));
+
+        backoff.reset();
+        assert!(backoff.ready(now));
+    }
+
+    #[test]
+    fn reconnect_backoff_jitter() {
+        // jitter of 500ms subtracted, in [d / 2, d]
+        let mut rng = StepRng::new(500, 0);
+        let mut backoff = ReconnectBackoff::default();
+        backoff.set_bounds(Duration::from_secs(1), Duration::from_secs(4));
+        let now = Duration::from_secs(1000);
+        let intervals = (0..4)
+            .map(|_| backoff.on_failure(now, &mut rng).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(intervals, vec![500, 1500, 3500, 3500]);
+
+        // the largest jitter halves the interval
+        let mut rng = StepRng::new(u64::MAX, 0);
+        let mut backoff = ReconnectBackoff::default();
+        backoff.set_bounds(Duration::from_secs(2), Duration::from_secs(1));
+        assert_eq!(backoff.max, Duration::from_secs(2));
+        let interval = backoff.on_failure(now, &mut rng);
+        assert!(interval >= Duration::from_secs(1) && interval <= Duration::from_secs(2));
+
+        // no overflow after lots of failures
+        backoff.set_bounds(Duration::from_secs(60), Duration::from_secs(3600));
+        for _ in 0..100 {
+            assert!(backoff.on_failure(now, &mut rng) <= Duration::from_secs(3600));
+        }
+    }
+
+    fn failover(ips: &[&str]) -> IngesterFailover {
+        let mut failover = IngesterFailover::default();
+        failover.set_bounds(3, Duration::from_secs(60));
+        failover.reset(&ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>());
+        failover
+    }
+
+    #[test]
+    fn ingester_failover_sequence() {
+        let mut failover = failover(&["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        // failures below the threshold are tolerated, and reset by a success
+        assert_eq!(failover.on_failure(), None);
+        assert_eq!(failover.on_failure(), None);
+        failover.on_success();
+        assert_eq!(failover.on_failure(), None);
+        assert_eq!(failover.on_failure(), None);
+        assert_eq!(failover.on_failure(), Some("10.0.0.2"));
+        assert_eq!(failover.active, 1);
+
+        // the next backup if the active one fails too
+        for _ in 0..2 {
+            assert_eq!(failover.on_failure(), None);
+        }
+        assert_eq!(failover.on_failure(), Some("10.0.0.3"));
+        // back to the primary after all the backups
+        for _ in 0..2 {
+            assert_eq!(failover.on_failure(), None);
+        }
+        assert_eq!(failover.on_failure(), Some("10.0.0.1"));
+        assert_eq!(failover.active, 0);
+        assert!(!failover.probe_due(Duration::from_secs(3600)));
+
+        // never switched without backups
+        let mut failover = self::failover(&["10.0.0.1"]);
+        for _ in 0..10 {
+            assert_eq!(failover.on_failure(), None);
+        }
+    }
+
+    #[test]
+    fn ingester_failback_hold_down() {
+        let mut failover = failover(&["10.0.0.1", "10.0.0.2"]);
+        let mut now = Duration::from_secs(1000);
+        for _ in 0..2 {
+            failover.on_failure();
+        }
+        assert_eq!(failover.on_failure(), Some("10.0.0.2"));
+        failover.on_success();
+
+        // probed at most once every PROBE_INTERVAL
+        assert!(failover.probe_due(now));
+        assert!(!failover.on_probe(now, false));
+        assert!(!failover.probe_due(now + Duration::from_secs(5)));
+        now += IngesterFailover::PROBE_INTERVAL;
+        assert!(failover.probe_due(now));
+
+        // the primary recovers, but flaps before the hold-down
+        assert!(!failover.on_probe(now, true));
+        now += Duration::from_secs(50);
+        assert!(!failover.on_probe(now, true));
+        now += Duration::from_secs(5);
+        assert!(!failover.on_probe(now, false));
+        now += Duration::from_secs(10);
+        assert!(!failover.on_probe(now, true));
+        now += Duration::from_secs(50);
+        assert!(!failover.on_probe(now, true));
+        assert_eq!(failover.active, 1);
+        // fail back once reachable for the hold-down
+        now += Duration::from_secs(10);
+        assert!(failover.on_probe(now, true));
+        assert_eq!(failover.active, 0);
+        assert!(!failover.probe_due(now + Duration::from_secs(3600)));
+
+        // the hold-down restarts on every switchover
+        for _ in 0..2 {
+            failover.on_failure();
+        }
+        assert_eq!(failover.on_failure(), Some("10.0.0.2"));
+        failover.set_bounds(3, Duration::from_secs(120));
+        assert!(!failover.on_probe(now, true));
+        now += Duration::from_secs(60);
+        assert!(!failover.on_probe(now, true));
+        now += Duration::from_secs(60);
+        assert!(failover.on_probe(now, true));
+    }
+}
@@ -14,6 +14,8 @@
  * limitations under the License.
  */
 
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
 use std::env;
 use std::fmt;
 use std::fs;
@@ -37,6 +39,7 @@ use flexi_logger::{
     colored_opt_format, writers::LogWriter, Age, Cleanup, Criterion, FileSpec, Logger, Naming,
 };
 use log::{debug, error, info, warn};
+use lz4_flex::frame::FrameEncoder as Lz4Encoder;
 use num_enum::{FromPrimitive, IntoPrimitive};
 use serde::Serialize;
 use tokio::runtime::{Builder, Runtime};
@@ -55,6 +58,7 @@ use crate::{
         L7CollectorThread,
     },
     common::{
+        decapsulate::MplsDecapCounter,
         enums::CaptureNetworkType,
         flow::L7Stats,
         tagged_flow::{BoxedTaggedFlow, TaggedFlow},
@@ -65,16 +69,19 @@ use crate::{
     config::PcapStream,
     config::{
         handler::{ConfigHandler, DispatcherConfig, ModuleConfig},
-        Config, ConfigError, DpdkSource, UserConfig,
+        Config, ConfigError, DataCompression, DpdkSource, UserConfig,
     },
-    debug::{ConstructDebugCtx, Debugger},
+    debug::{CaptureDebugger, ConstructDebugCtx, Debugger},
     dispatcher::{
-        self, recv_engine::bpf, BpfOptions, Dispatcher, DispatcherBuilder, DispatcherListener,
+        self,
+        promisc::PromiscResolver,
+        recv_engine::{bpf, libpcap::PcapInterfaceResolver},
+        BpfOptions, Dispatcher, DispatcherBuilder, DispatcherListener,
     },
     exception::ExceptionHandler,
     flow_generator::{
-        protocol_logs::BoxAppProtoLogsData, protocol_logs::SessionAggregator, PacketSequenceParser,
-        TIME_UNIT,
+        protocol_logs::BoxAppProtoLogsData, protocol_logs::SessionAggregator, xflow::XflowReceiver,
+        PacketSequenceParser, TIME_UNIT,
     },
     handler::{NpbBuilder, PacketHandlerBuilder},
     integration_collector::{
@@ -89,6 +96,7 @@ use crate::{
     rpc::{Session, Synchronizer, DEFAULT_TIMEOUT},
     sender::{
         npb_sender::NpbArpTable,
+        otlp_exporter::OtlpExporter,
         uniform_sender::{Connection, UniformSenderThread},
     },
     utils::{
@@ -105,20 +113,30 @@ use crate::{
         stats::{self, Countable, QueueStats, RefCountable},
     },
 };
-#[cfg(any(target_os = "linux", target_os = "android"))]
-use crate::{
-    platform::SocketSynchronizer,
-    utils::{environment::core_file_check, lru::Lru, process::ProcessListener},
-};
 #[cfg(target_os = "linux")]
 use crate::{
+    dispatcher::{
+        bond_monitor::BondMonitor,
+        interface_assigner::InterfaceAssigner,
+        recv_engine::{
+            af_packet::fanout::{self, Fanout, FanoutGroupIds},
+            dpdk::{self, DpdkQueue},
+        },
+    },
     platform::{
         kubernetes::{GenericPoller, Poller, SidecarPoller},
         ApiWatcher, LibvirtXmlExtractor,
     },
     utils::environment::{IN_CONTAINER, K8S_WATCH_POLICY},
 };
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::{
+    platform::SocketSynchronizer,
+    utils::{environment::core_file_check, lru::Lru, process::ProcessListener},
+};
 
+#[cfg(feature = "kafka")]
+use crate::sender::kafka_exporter::KafkaExporter;
 #[cfg(feature = "enterprise-integration")]
 use integration_skywalking::SkyWalkingExtra;
 #[cfg(feature = "enterprise-integration")]
@@ -135,7 +153,7 @@ use public::{
     proto::agent::{self, Exception, PacketCaptureType, SocketType},
     queue::{self, DebugSender},
     utils::net::{get_route_src_ip, IpMacPair, Link, MacAddr},
-    LeakyBucket,
+    LeakyBucket, TokenBucketConfig,
 };
 #[cfg(target_os = "linux")]
 use public::{netns, packet, queue::Receiver};
@@ -202,6 +220,7 @@ pub struct AgentState {
     terminated: AtomicBool,
     state: Mutex<(InnerState, Option<ChangedConfig>)>,
     notifier: Condvar,
+    bond_changed: AtomicBool,
 }
 
 impl AgentState {
@@ -314,6 +333,14 @@ impl AgentState {
         }
         self.notifier.notify_one();
     }
+
+    // Wakes up the main loop to apply the changed bond membership to the dispatchers
+    pub fn notify_bond_change(&self) {
+        self.bond_changed.store(true, Ordering::Relaxed);
+        // locked to avoid missing the notification when the main loop is about to wait
+        let _sg = self.state.lock().unwrap();
+        self.notifier.notify_one();
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
@@ -418,17 +445,43 @@ pub enum SenderEncoder {
     Raw = 0,
 
     Zstd = 3,
+    // rejected by ingesters not supporting it
+    Lz4 = 4,
 }
 
 impl SenderEncoder {
-    pub fn encode(&self, encode_buffer: &[u8], dst_buffer: &mut Vec<u8>) -> std::io::Result<()> {
+    // outputs.socket.data_compression overrides the compression switches of flow logs
+    fn with_data_compression(compression: DataCompression, compressed: bool) -> Self {
+        match compression {
+            DataCompression::None if compressed => SenderEncoder::Zstd,
+            DataCompression::None => SenderEncoder::Raw,
+            DataCompression::Lz4 => SenderEncoder::Lz4,
+            DataCompression::Zstd => SenderEncoder::Zstd,
+        }
+    }
+
+    // level is only for zstd, 0 for the default level
+    pub fn encode(
+        &self,
+        encode_buffer: &[u8],
+        dst_buffer: &mut Vec<u8>,
+        level: i32,
+    ) -> std::io::Result<()> {
         match self {
             SenderEncoder::Zstd => {
-                let mut encoder = ZstdEncoder::new(dst_buffer, 0)?;
+                let mut encoder = ZstdEncoder::new(dst_buffer, level)?;
                 encoder.write_all(&encode_buffer)?;
                 encoder.finish()?;
                 Ok(())
             }
+            SenderEncoder::Lz4 => {
+                let mut encoder = Lz4Encoder::new(dst_buffer);
+                encoder.write_all(&encode_buffer)?;
+                encoder
+                    .finish()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
@@ -611,6 +664,10 @@ impl Trident {
             &stats::NoTagModule("log_counter"),
             stats::Countable::Owned(Box::new(log_level_counter)),
         );
+        stats_collector.register_countable(
+            &stats::NoTagModule("mpls_decap"),
+            stats::Countable::Owned(Box::new(MplsDecapCounter)),
+        );
 
         info!("static_config {:#?}", config);
         let state = Arc::new(AgentState::default());
@@ -1027,6 +1084,12 @@ impl Trident {
                 .wait_timeout(state_guard, MAIN_LOOP_LIVENESS_TIMEOUT)
                 .unwrap();
             state_guard = wait_result.0;
+            #[cfg(target_os = "linux")]
+            if state.bond_changed.swap(false, Ordering::Relaxed) {
+                if let Some(Components::Agent(c)) = components.as_ref() {
+                    c.on_bond_change();
+                }
+            }
             if wait_result.1.timed_out() {
                 mem::drop(state_guard);
                 continue;
@@ -1187,6 +1250,8 @@ impl Trident {
                         sidecar_poller.clone(),
                         #[cfg(target_os = "linux")]
                         api_watcher.clone(),
+                        #[cfg(target_os = "linux")]
+                        state.clone(),
                         vm_mac_addrs,
                         gateway_vmac_addrs,
                         config_handler.static_config.agent_mode,
@@ -1311,6 +1376,17 @@ impl Trident {
     }
 }
 
+fn exclude_listener_links(conf: &DispatcherConfig, links: Vec<Link>) -> Vec<Link> {
+    match public::utils::net::exclude_links_by_name_regex(links, &conf.tap_interface_exclude_regex)
+    {
+        Ok(links) => links,
+        Err(e) => {
+            warn!("exclude interfaces by name regex failed: {}", e);
+            vec![]
+        }
+    }
+}
+
 fn get_listener_links(
     conf: &DispatcherConfig,
     #[cfg(target_os = "linux")] netns: &netns::NsFile,
@@ -1326,6 +1402,7 @@ fn get_listener_links(
             vec![]
         }
         Ok(links) => {
+            let links = exclude_listener_links(conf, links);
             if links.is_empty() {
                 warn!(
                     "tap-interface-regex({}) do not match any interface in {:?}",
@@ -1344,6 +1421,7 @@ fn get_listener_links(
             vec![]
         }
         Ok(links) => {
+            let links = exclude_listener_links(conf, links);
             if links.is_empty() {
                 warn!(
                     "tap-interface-regex({}) do not match any interface, in local mode",
@@ -1370,17 +1448,39 @@ fn component_on_config_change(
     match conf.capture_mode {
         PacketCaptureType::Local => {
             let if_mac_source = conf.if_mac_source;
-            components.dispatcher_components.retain_mut(|d| {
-                let links = get_listener_links(
-                    conf,
-                    #[cfg(target_os = "linux")]
-                    d.dispatcher_listener.netns(),
-                );
-                if links.is_empty() && !conf.inner_interface_capture_enabled {
-                    info!("No interfaces found, stopping dispatcher {}", d.id);
-                    d.stop();
-                    return false;
+            #[cfg(target_os = "linux")]
+            let mut tap_interfaces = vec![];
+            // interfaces of each dispatcher, indexed by dispatcher id
+            #[cfg(target_os = "linux")]
+            let assignment = match components.interface_assigner.as_mut() {
+                Some(assigner) => {
+                    let assignment =
+                        assigner.assign(&get_listener_links(conf, &netns::NsFile::Root));
+                    components.debugger.set_dispatcher_interfaces(&assignment);
+                    assignment
                 }
+                None => vec![],
+            };
+            #[cfg(any(target_os = "windows", target_os = "android"))]
+            let assignment: Vec<Vec<Link>> = vec![];
+            components.dispatcher_components.retain_mut(|d| {
+                // dispatchers with assigned interfaces are kept even if there are none for now
+                let links = match assignment.get(d.id) {
+                    Some(links) => links.clone(),
+                    None => {
+                        let links = get_listener_links(
+                            conf,
+                            #[cfg(target_os = "linux")]
+                            d.dispatcher_listener.netns(),
+                        );
+                        if links.is_empty() && !conf.inner_interface_capture_enabled {
+                            info!("No interfaces found, stopping dispatcher {}", d.id);
+                            d.stop();
+                            return false;
+                        }
+                        links
+                    }
+                };
                 d.dispatcher_listener.on_tap_interface_change(
                     &links,
                     if_mac_source,
@@ -1389,8 +1489,12 @@ fn component_on_config_change(
                 );
                 d.dispatcher_listener
                     .on_vm_change(&vm_mac_addrs, &gateway_vmac_addrs);
+                #[cfg(target_os = "linux")]
+                tap_interfaces.extend(links);
                 true
             });
+            #[cfg(target_os = "linux")]
+            components.debugger.set_tap_interfaces(&tap_interfaces);
 
             if components.dispatcher_components.is_empty() {
                 let links = get_listener_links(
@@ -1401,17 +1505,20 @@ fn component_on_config_change(
                 if links.is_empty() && !conf.inner_interface_capture_enabled {
                     return;
                 }
+                #[cfg(target_os = "linux")]
+                components.debugger.set_tap_interfaces(&links);
                 match build_dispatchers(
                     components.last_dispatcher_component_id + 1,
                     links,
                     components.stats_collector.clone(),
                     config_handler,
                     components.debugger.clone_queue(),
+                    components.debugger.clone_capture(),
                     components.is_ce_version,
                     synchronizer,
                     components.npb_bps_limit.clone(),
                     components.npb_arp_table.clone(),
-                    components.rx_leaky_bucket.clone(),
+                    components.rx_token_bucket.clone(),
                     components.policy_getter,
                     components.exception_handler.clone(),
                     components.bpf_options.clone(),
@@ -1434,9 +1541,18 @@ fn component_on_config_change(
                     #[cfg(target_os = "linux")]
                     None,
                     #[cfg(target_os = "linux")]
-                    false,
+                    None,
+                    #[cfg(target_os = "linux")]
+                    None,
+                    #[cfg(target_os = "linux")]
+                    None,
                 ) {
                     Ok(mut d) => {
+                        // built with the configured bond group
+                        #[cfg(target_os = "linux")]
+                        if let Some(bond_group) = components.bond_group() {
+                            d.dispatcher_listener.on_bond_group_change(&bond_group);
+                        }
                         d.start();
                         components.dispatcher_components.push(d);
                         components.last_dispatcher_component_id += 1;
@@ -1494,6 +1610,8 @@ fn component_on_config_change(
                 &netns::NsFile::Root,
             );
             current_interfaces.sort();
+            #[cfg(target_os = "linux")]
+            components.debugger.set_tap_interfaces(&current_interfaces);
 
             if current_interfaces == components.tap_interfaces {
                 return;
@@ -1529,11 +1647,12 @@ fn component_on_config_change(
                     components.stats_collector.clone(),
                     config_handler,
                     debugger_queue.clone(),
+                    components.debugger.clone_capture(),
                     components.is_ce_version,
                     synchronizer,
                     components.npb_bps_limit.clone(),
                     components.npb_arp_table.clone(),
-                    components.rx_leaky_bucket.clone(),
+                    components.rx_token_bucket.clone(),
                     components.policy_getter,
                     components.exception_handler.clone(),
                     components.bpf_options.clone(),
@@ -1556,9 +1675,18 @@ fn component_on_config_change(
                     #[cfg(target_os = "linux")]
                     None,
                     #[cfg(target_os = "linux")]
-                    false,
+                    None,
+                    #[cfg(target_os = "linux")]
+                    None,
+                    #[cfg(target_os = "linux")]
+                    None,
                 ) {
                     Ok(mut d) => {
+                        // built with the configured bond group
+                        #[cfg(target_os = "linux")]
+                        if let Some(bond_group) = components.bond_group() {
+                            d.dispatcher_listener.on_bond_group_change(&bond_group);
+                        }
                         d.start();
                         components.dispatcher_components.push(d);
                     }
@@ -1817,6 +1945,29 @@ impl EbpfDispatcherComponent {
     }
 }
 
+pub struct XflowComponent {
+    pub receiver: XflowReceiver,
+    pub session_aggregator: SessionAggregator,
+    pub collector: CollectorThread,
+    pub l7_collector: L7CollectorThread,
+}
+
+impl XflowComponent {
+    pub fn start(&mut self) {
+        self.session_aggregator.start();
+        self.collector.start();
+        self.l7_collector.start();
+        self.receiver.start();
+    }
+
+    pub fn stop(&mut self) {
+        self.receiver.stop();
+        self.session_aggregator.stop();
+        self.collector.stop();
+        self.l7_collector.stop();
+    }
+}
+
 pub struct MetricsServerComponent {
     pub external_metrics_server: MetricServer,
     pub l7_collector: L7CollectorThread,
@@ -1882,13 +2033,16 @@ impl DispatcherComponent {
 
 pub struct AgentComponents {
     pub config: ModuleConfig,
-    pub rx_leaky_bucket: Arc<LeakyBucket>,
+    pub rx_token_bucket: Arc<TokenBucketConfig>,
     pub tap_typer: Arc<CaptureNetworkTyper>,
     pub cur_tap_types: Vec<agent::CaptureNetworkType>,
     pub dispatcher_components: Vec<DispatcherComponent>,
     pub l4_flow_uniform_sender: UniformSenderThread<BoxedTaggedFlow>,
     pub metrics_uniform_sender: UniformSenderThread<BoxedDocument>,
     pub l7_flow_uniform_sender: UniformSenderThread<BoxAppProtoLogsData>,
+    #[cfg(feature = "kafka")]
+    pub l7_flow_kafka_exporter: Option<KafkaExporter<BoxAppProtoLogsData>>,
+    pub l7_flow_otlp_exporter: Option<OtlpExporter>,
     pub platform_synchronizer: Arc<PlatformSynchronizer>,
     #[cfg(target_os = "linux")]
     pub kubernetes_poller: Arc<GenericPoller>,
@@ -1897,6 +2051,7 @@ pub struct AgentComponents {
     pub debugger: Debugger,
     #[cfg(all(unix, feature = "libtrace"))]
     pub ebpf_dispatcher_component: Option<EbpfDispatcherComponent>,
+    pub xflow_component: Option<XflowComponent>,
     pub running: AtomicBool,
     pub stats_collector: Arc<stats::Collector>,
     pub metrics_server_component: MetricsServerComponent,
@@ -1933,6 +2088,10 @@ pub struct AgentComponents {
     pub last_dispatcher_component_id: usize,
     #[cfg(any(target_os = "linux", target_os = "android"))]
     pub process_listener: Arc<ProcessListener>,
+    #[cfg(target_os = "linux")]
+    pub bond_monitor: Option<BondMonitor>,
+    #[cfg(target_os = "linux")]
+    pub interface_assigner: Option<InterfaceAssigner>,
     pub liveness_registry: Option<LivenessRegistry>,
     max_memory: u64,
     capture_mode: PacketCaptureType,
@@ -2030,6 +2189,24 @@ impl AgentComponents {
                 &stats::SingleTagModule("flow_aggr", "index", id),
                 Countable::Ref(Arc::downgrade(&flow_aggr_counter) as Weak<dyn RefCountable>),
             );
+            stats_collector.register_countable(
+                &stats::FlowLogThrottleStats {
+                    kind: "l4_flow_log",
+                    index: id,
+                },
+                Countable::Ref(
+                    Arc::downgrade(&flow_aggr_counter.throttle) as Weak<dyn RefCountable>
+                ),
+            );
+            for (observation_point, counter) in flow_aggr_counter.sampling.iter().enumerate() {
+                stats_collector.register_countable(
+                    &stats::FlowLogSamplingStats {
+                        index: id,
+                        observation_point: observation_point as u8,
+                    },
+                    Countable::Ref(Arc::downgrade(counter) as Weak<dyn RefCountable>),
+                );
+            }
         }
 
         let (second_sender, second_receiver, counter) = queue::bounded_with_debug(
@@ -2226,6 +2403,7 @@ impl AgentComponents {
         platform_synchronizer: Arc<PlatformSynchronizer>,
         #[cfg(target_os = "linux")] sidecar_poller: Option<Arc<GenericPoller>>,
         #[cfg(target_os = "linux")] api_watcher: Arc<ApiWatcher>,
+        #[cfg(target_os = "linux")] agent_state: Arc<AgentState>,
         vm_mac_addrs: Vec<MacAddr>,
         gateway_vmac_addrs: Vec<MacAddr>,
         agent_mode: RunningMode,
@@ -2303,11 +2481,65 @@ impl AgentComponents {
         #[cfg(any(target_os = "windows", target_os = "android"))]
         let packet_fanout_count = 1;
 
-        let links = get_listener_links(
+        #[allow(unused_mut)]
+        let mut links = get_listener_links(
             &candidate_config.dispatcher,
             #[cfg(target_os = "linux")]
             &netns::NsFile::Root,
         );
+        // dispatchers of the interface groups, by their index in interfaces_and_ns
+        #[cfg(target_os = "linux")]
+        let mut fanout_group_ids = FanoutGroupIds::default();
+        #[cfg(target_os = "linux")]
+        let mut group_fanouts: HashMap<usize, Option<Fanout>> = HashMap::new();
+        #[cfg(target_os = "linux")]
+        let grouped_interfaces = {
+            let af_packet = &user_config.inputs.cbpf.af_packet;
+            if af_packet.interface_groups.is_empty() || !interfaces_and_ns.is_empty() {
+                vec![]
+            } else if candidate_config.capture_mode == PacketCaptureType::Local {
+                warn!("inputs.cbpf.af_packet.interface_groups is not supported in local mode, ignored");
+                vec![]
+            } else {
+                fanout::split_by_group(
+                    &mut links,
+                    &af_packet.interface_groups,
+                    &af_packet.tunning,
+                    &mut fanout_group_ids,
+                )
+            }
+        };
+        #[cfg(target_os = "linux")]
+        let mut interface_assigner = {
+            let af_packet = &user_config.inputs.cbpf.af_packet;
+            if af_packet.interface_dispatcher_assignments.is_empty()
+                || !interfaces_and_ns.is_empty()
+            {
+                None
+            } else if candidate_config.capture_mode != PacketCaptureType::Local
+                || packet_fanout_count <= 1
+            {
+                warn!("inputs.cbpf.af_packet.interface_dispatcher_assignments is only supported in local mode with packet_fanout_count > 1, ignored");
+                None
+            } else {
+                Some(InterfaceAssigner::new(
+                    &af_packet.interface_dispatcher_assignments,
+                    packet_fanout_count,
+                ))
+            }
+        };
+        #[cfg(target_os = "linux")]
+        let mut dispatcher_interfaces = vec![];
+        #[cfg(target_os = "linux")]
+        if let Some(assigner) = interface_assigner.as_mut() {
+            if !links.is_empty() || candidate_config.dispatcher.inner_interface_capture_enabled {
+                // each dispatcher captures its own interfaces without packet fanout
+                dispatcher_interfaces = assigner.assign(&links);
+                for links in dispatcher_interfaces.iter() {
+                    interfaces_and_ns.push((links.clone(), netns::NsFile::Root));
+                }
+            }
+        }
         if interfaces_and_ns.is_empty()
             && (!links.is_empty() || candidate_config.dispatcher.inner_interface_capture_enabled)
         {
@@ -2329,20 +2561,64 @@ impl AgentComponents {
             }
         }
         #[cfg(target_os = "linux")]
+        for g in grouped_interfaces {
+            for _ in 0..g.count {
+                group_fanouts.insert(interfaces_and_ns.len(), g.fanout);
+                interfaces_and_ns.push((vec![g.link.clone()], netns::NsFile::Root));
+            }
+        }
+        #[cfg(target_os = "linux")]
+        let mut dpdk_queues: Vec<DpdkQueue> = vec![];
+        #[cfg(target_os = "linux")]
+        let mut vhost_sockets: Vec<(String, isize)> = vec![];
+        #[cfg(target_os = "linux")]
         if candidate_config.capture_mode != PacketCaptureType::Local {
-            if !user_config
+            let vhost_socket_path = &user_config
                 .inputs
                 .cbpf
                 .special_network
                 .vhost_user
-                .vhost_socket_path
-                .is_empty()
+                .vhost_socket_path;
+            if !vhost_socket_path.is_empty()
                 || candidate_config.dispatcher.dpdk_source == DpdkSource::PDump
             {
                 packet_fanout_count = 1;
                 interfaces_and_ns = vec![(vec![], netns::NsFile::Root)];
+                group_fanouts.clear();
+                let dpdk = &user_config.inputs.cbpf.special_network.dpdk;
+                if vhost_socket_path.is_empty() && !dpdk.ports.is_empty() {
+                    // one dispatcher for each RSS queue, the RSS hash keeps flows on the same queue
+                    dpdk_queues = dpdk::open_queues(
+                        &dpdk.ports,
+                        &dpdk.rss,
+                        &user_config.global.tunning.cpu_affinity,
+                    )?;
+                    interfaces_and_ns = dpdk_queues
+                        .iter()
+                        .map(|_| (vec![], netns::NsFile::Root))
+                        .collect();
+                }
+                if !vhost_socket_path.is_empty() {
+                    // one dispatcher for each socket, packets are tagged with the socket if_index
+                    vhost_sockets = vhost_socket_path
+                        .iter()
+                        .enumerate()
+                        .map(|(i, path)| {
+                            (
+                                path.clone(),
+                                (dispatcher::recv_engine::VHOST_USER_IF_INDEX_BASE + i as u32)
+                                    as isize,
+                            )
+                        })
+                        .collect();
+                    interfaces_and_ns = vhost_sockets
+                        .iter()
+                        .map(|_| (vec![], netns::NsFile::Root))
+                        .collect();
+                }
             } else if candidate_config.dispatcher.dpdk_source == DpdkSource::Ebpf {
                 interfaces_and_ns = vec![];
+                group_fanouts.clear();
                 for _ in 0..packet_fanout_count {
                     interfaces_and_ns.push((vec![], netns::NsFile::Root));
                 }
@@ -2500,15 +2776,18 @@ impl AgentComponents {
             process_listener.clone(),
         );
 
-        let rx_leaky_bucket = Arc::new(LeakyBucket::new(match candidate_config.capture_mode {
-            PacketCaptureType::Analyzer => None,
-            _ => Some(
-                config_handler
-                    .candidate_config
-                    .dispatcher
-                    .global_pps_threshold,
-            ),
-        }));
+        let rx_token_bucket = Arc::new(TokenBucketConfig::new(
+            match candidate_config.capture_mode {
+                PacketCaptureType::Analyzer => None,
+                _ => Some(
+                    config_handler
+                        .candidate_config
+                        .dispatcher
+                        .global_pps_threshold,
+                ),
+            },
+            config_handler.candidate_config.dispatcher.global_pps_burst,
+        ));
 
         let tap_typer = Arc::new(CaptureNetworkTyper::new());
 
@@ -2520,6 +2799,12 @@ impl AgentComponents {
             "static analyzer ip: '{}' actual analyzer ip '{}'",
             user_config.global.communication.ingester_ip, candidate_config.sender.dest_ip
         );
+        let data_compression = user_config.outputs.socket.effective_data_compression();
+        let data_compression_level = if data_compression == DataCompression::Zstd {
+            user_config.outputs.socket.data_compression_level
+        } else {
+            0
+        };
         let l4_flow_aggr_queue_name = "3-flowlog-to-collector-sender";
         let (l4_flow_aggr_sender, l4_flow_aggr_receiver, counter) = queue::bounded_with_debug(
             user_config
@@ -2544,13 +2829,13 @@ impl AgentComponents {
             stats_collector.clone(),
             exception_handler.clone(),
             None,
-            if candidate_config.metric_server.l4_flow_log_compressed {
-                SenderEncoder::Zstd
-            } else {
-                SenderEncoder::Raw
-            },
+            SenderEncoder::with_data_compression(
+                data_compression,
+                candidate_config.metric_server.l4_flow_log_compressed,
+            ),
             sender_leaky_bucket.clone(),
-        );
+        )
+        .with_compression_level(data_compression_level);
 
         let metrics_queue_name = "3-doc-to-collector-sender";
         let (metrics_sender, metrics_receiver, counter) = queue::bounded_with_debug(
@@ -2572,9 +2857,10 @@ impl AgentComponents {
             stats_collector.clone(),
             exception_handler.clone(),
             None,
-            SenderEncoder::Raw,
+            SenderEncoder::with_data_compression(data_compression, false),
             sender_leaky_bucket.clone(),
-        );
+        )
+        .with_compression_level(data_compression_level);
 
         let proto_log_queue_name = "2-protolog-to-collector-sender";
         let (proto_log_sender, proto_log_receiver, counter) = queue::bounded_with_debug(
@@ -2596,13 +2882,46 @@ impl AgentComponents {
             stats_collector.clone(),
             exception_handler.clone(),
             None,
-            if candidate_config.metric_server.l7_flow_log_compressed {
-                SenderEncoder::Zstd
-            } else {
-                SenderEncoder::Raw
-            },
+            SenderEncoder::with_data_compression(
+                data_compression,
+                candidate_config.metric_server.l7_flow_log_compressed,
+            ),
             sender_leaky_bucket.clone(),
-        );
+        )
+        .with_compression_level(data_compression_level);
+        #[cfg(feature = "kafka")]
+        let (l7_flow_uniform_sender, l7_flow_kafka_exporter) = if user_config.outputs.kafka.enabled
+        {
+            let exporter = KafkaExporter::new(
+                "3-protolog-to-kafka-exporter",
+                &user_config.outputs.kafka,
+                &stats_collector,
+            );
+            (
+                l7_flow_uniform_sender.with_tee(exporter.tee()),
+                Some(exporter),
+            )
+        } else {
+            (l7_flow_uniform_sender, None)
+        };
+        #[cfg(not(feature = "kafka"))]
+        if user_config.outputs.kafka.enabled {
+            warn!("outputs.kafka ignored, the agent is built without the kafka feature");
+        }
+        let (l7_flow_uniform_sender, l7_flow_otlp_exporter) = if user_config.outputs.otlp.enabled {
+            let exporter = OtlpExporter::new(
+                "3-protolog-to-otlp-exporter",
+                &user_config.outputs.otlp,
+                &stats_collector,
+                runtime.clone(),
+            );
+            (
+                l7_flow_uniform_sender.with_tee(exporter.tee()),
+                Some(exporter),
+            )
+        } else {
+            (l7_flow_uniform_sender, None)
+        };
 
         let analyzer_ip = if candidate_config
             .dispatcher
@@ -2702,8 +3021,8 @@ impl AgentComponents {
 
         let bpf_builder = bpf::Builder {
             is_ipv6: ctrl_ip.is_ipv6(),
-            vxlan_flags: user_config.outputs.npb.custom_vxlan_flags,
-            npb_port: user_config.outputs.npb.target_port,
+            vxlan_flags: user_config.outputs.npb.vxlan_flags(),
+            npb_port: user_config.outputs.npb.target_port(),
             controller_port: static_config.controller_port,
             controller_tls_port: static_config.controller_tls_port,
             proxy_controller_port: candidate_config.dispatcher.proxy_controller_port,
@@ -2727,6 +3046,16 @@ impl AgentComponents {
         #[cfg(all(unix, feature = "libtrace"))]
         let mut dpdk_ebpf_senders = vec![];
 
+        #[cfg(target_os = "linux")]
+        let default_fanout = if packet_fanout_count > 1 && interface_assigner.is_none() {
+            Some(Fanout {
+                mode: user_config.inputs.cbpf.af_packet.tunning.packet_fanout_mode,
+                group_id: fanout_group_ids.base(),
+                bound: false,
+            })
+        } else {
+            None
+        };
         let mut tap_interfaces = vec![];
         for (i, entry) in interfaces_and_ns.into_iter().enumerate() {
             #[cfg(target_os = "linux")]
@@ -2761,11 +3090,12 @@ impl AgentComponents {
                 stats_collector.clone(),
                 config_handler,
                 queue_debugger.clone(),
+                debugger.clone_capture(),
                 version_info.name != env!("AGENT_NAME"),
                 synchronizer,
                 npb_bps_limit.clone(),
                 npb_arp_table.clone(),
-                rx_leaky_bucket.clone(),
+                rx_token_bucket.clone(),
                 policy_getter,
                 exception_handler.clone(),
                 bpf_options.clone(),
@@ -2788,13 +3118,19 @@ impl AgentComponents {
                 #[cfg(target_os = "linux")]
                 dpdk_ebpf_receiver,
                 #[cfg(target_os = "linux")]
-                {
-                    packet_fanout_count > 1
-                },
+                group_fanouts.get(&i).copied().unwrap_or(default_fanout),
+                #[cfg(target_os = "linux")]
+                dpdk_queues.get(i).cloned(),
+                #[cfg(target_os = "linux")]
+                vhost_sockets.get(i).cloned(),
             )?;
             dispatcher_components.push(dispatcher_component);
         }
         tap_interfaces.sort();
+        #[cfg(target_os = "linux")]
+        debugger.set_tap_interfaces(&tap_interfaces);
+        #[cfg(target_os = "linux")]
+        debugger.set_dispatcher_interfaces(&dispatcher_interfaces);
         #[cfg(feature = "libtrace")]
         let (proc_event_sender, proc_event_uniform_sender) = {
             let proc_event_queue_name = "1-proc-event-to-sender";
@@ -3004,6 +3340,7 @@ impl AgentComponents {
                 ebpf_dispatcher_id as u32,
                 config_handler.log_parser(),
                 synchronizer.ntp_diff(),
+                stats_collector.clone(),
             );
             stats_collector.register_countable(
                 &stats::SingleTagModule("l7_session_aggr", "index", ebpf_dispatcher_id),
@@ -3119,6 +3456,124 @@ impl AgentComponents {
             agent_mode,
         );
 
+        let xflow_id = otel_dispatcher_id + 1;
+        let mut xflow_component = None;
+        let physical_switch = &user_config.inputs.cbpf.special_network.physical_switch;
+        if !physical_switch.sflow_ports.is_empty() || !physical_switch.netflow_ports.is_empty() {
+            let (flow_sender, flow_receiver, counter) = queue::bounded_with_debug(
+                user_config
+                    .processors
+                    .flow_log
+                    .tunning
+                    .flow_generator_queue_size,
+                "1-tagged-flow-to-quadruple-generator",
+                &queue_debugger,
+            );
+            stats_collector.register_countable(
+                &QueueStats {
+                    id: xflow_id,
+                    module: "1-tagged-flow-to-quadruple-generator",
+                },
+                Countable::Owned(Box::new(counter)),
+            );
+            let (l7_stats_sender, l7_stats_receiver, counter) = queue::bounded_with_debug(
+                user_config
+                    .processors
+                    .flow_log
+                    .tunning
+                    .flow_generator_queue_size,
+                "1-l7-stats-to-quadruple-generator",
+                &queue_debugger,
+            );
+            stats_collector.register_countable(
+                &QueueStats {
+                    id: xflow_id,
+                    module: "1-l7-stats-to-quadruple-generator",
+                },
+                Countable::Owned(Box::new(counter)),
+            );
+            let (log_sender, log_receiver, counter) = queue::bounded_with_debug(
+                user_config
+                    .processors
+                    .flow_log
+                    .tunning
+                    .flow_generator_queue_size,
+                "1-tagged-flow-to-app-protocol-logs",
+                &queue_debugger,
+            );
+            stats_collector.register_countable(
+                &QueueStats {
+                    id: xflow_id,
+                    module: "1-tagged-flow-to-app-protocol-logs",
+                },
+                Countable::Owned(Box::new(counter)),
+            );
+            let (session_aggregator, counter) = SessionAggregator::new(
+                log_receiver,
+                proto_log_sender.clone(),
+                xflow_id as u32,
+                config_handler.log_parser(),
+                synchronizer.ntp_diff(),
+                stats_collector.clone(),
+            );
+            stats_collector.register_countable(
+                &stats::SingleTagModule("l7_session_aggr", "index", xflow_id),
+                Countable::Ref(Arc::downgrade(&counter) as Weak<dyn RefCountable>),
+            );
+            let collector = Self::new_collector(
+                xflow_id,
+                stats_collector.clone(),
+                flow_receiver,
+                toa_sender.clone(),
+                Some(l4_flow_aggr_sender.clone()),
+                metrics_sender.clone(),
+                MetricsType::SECOND | MetricsType::MINUTE,
+                config_handler,
+                &queue_debugger,
+                &synchronizer,
+                agent_mode,
+            );
+            let l7_collector = Self::new_l7_collector(
+                xflow_id,
+                stats_collector.clone(),
+                l7_stats_receiver,
+                metrics_sender.clone(),
+                MetricsType::SECOND | MetricsType::MINUTE,
+                config_handler,
+                &queue_debugger,
+                &synchronizer,
+                agent_mode,
+            );
+            let receiver = XflowReceiver::new(
+                xflow_id,
+                physical_switch.sflow_ports.clone(),
+                physical_switch.netflow_ports.clone(),
+                user_config
+                    .inputs
+                    .cbpf
+                    .physical_mirror
+                    .default_capture_network_type
+                    .try_into()
+                    .unwrap_or(CaptureNetworkType::Cloud),
+                flow_sender,
+                l7_stats_sender,
+                log_sender,
+                policy_getter,
+                config_handler.flow(),
+                config_handler.log_parser(),
+                config_handler.collector(),
+                synchronizer.ntp_diff(),
+                runtime.clone(),
+                stats_collector.clone(),
+            );
+            xflow_component = Some(XflowComponent {
+                receiver,
+                session_aggregator,
+                collector,
+                l7_collector,
+            });
+        }
+
         let prometheus_queue_name = "1-prometheus-to-sender";
         let (prometheus_sender, prometheus_receiver, counter) = queue::bounded_with_debug(
             user_config
@@ -3218,9 +3673,15 @@ impl AgentComponents {
             skywalking_sender,
             datadog_sender,
             candidate_config.metric_server.port,
+            candidate_config.metric_server.listen_address,
+            candidate_config.metric_server.unix_socket_path.clone(),
+            candidate_config.metric_server.otlp_grpc_port,
+            user_config.inputs.integration.statsd.clone(),
             exception_handler.clone(),
             candidate_config.metric_server.compressed,
             candidate_config.metric_server.profile_compressed,
+            candidate_config.metric_server.max_decompressed_size,
+            candidate_config.metric_server.source_filter.clone(),
             candidate_config.platform.epc_id,
             policy_getter,
             synchronizer.ntp_diff(),
@@ -3253,7 +3714,11 @@ impl AgentComponents {
         );
 
         stats_collector.register_countable(
-            &stats::NoTagModule("integration_collector"),
+            &stats::SingleTagModule(
+                "integration_collector",
+                "endpoints",
+                external_metrics_server.endpoints().join(","),
+            ),
             Countable::Owned(Box::new(external_metrics_counter)),
         );
 
@@ -3265,11 +3730,37 @@ impl AgentComponents {
             npb_bps_limit.clone(),
             exception_handler.clone(),
         );
+        npb_bandwidth_watcher
+            .set_nic_recovery_rate(sender_config.server_tx_bandwidth_recovery_threshold);
+        npb_bandwidth_watcher.set_min_hold_time(sender_config.server_tx_bandwidth_min_hold_time);
+        npb_bandwidth_watcher
+            .set_over_limit_action(sender_config.server_tx_bandwidth_over_limit_action);
         synchronizer.add_flow_acl_listener(npb_bandwidth_watcher.clone());
         stats_collector.register_countable(
             &stats::NoTagModule("npb_bandwidth_watcher"),
             Countable::Ref(Arc::downgrade(&npb_bandwidth_watcher_counter) as Weak<dyn RefCountable>),
         );
+        #[cfg(target_os = "linux")]
+        let bond_monitor = if user_config.inputs.cbpf.af_packet.bond_interfaces.is_empty() {
+            None
+        } else {
+            let bond_monitor = BondMonitor::new(
+                user_config
+                    .inputs
+                    .cbpf
+                    .af_packet
+                    .bond_interfaces
+                    .iter()
+                    .map(|b| b.slave_interfaces.clone())
+                    .collect(),
+                agent_state,
+            );
+            stats_collector.register_countable(
+                &stats::NoTagModule("bond_monitor"),
+                Countable::Ref(Arc::downgrade(bond_monitor.counter()) as Weak<dyn RefCountable>),
+            );
+            Some(bond_monitor)
+        };
         #[cfg(feature = "enterprise-integration")]
         let vector_component = VectorComponent::new(
             user_config.inputs.vector.enabled,
@@ -3281,12 +3772,15 @@ impl AgentComponents {
 
         Ok(AgentComponents {
             config: candidate_config.clone(),
-            rx_leaky_bucket,
+            rx_token_bucket,
             tap_typer,
             cur_tap_types: vec![],
             l4_flow_uniform_sender,
             metrics_uniform_sender,
             l7_flow_uniform_sender,
+            #[cfg(feature = "kafka")]
+            l7_flow_kafka_exporter,
+            l7_flow_otlp_exporter,
             platform_synchronizer,
             #[cfg(target_os = "linux")]
             kubernetes_poller,
@@ -3295,6 +3789,7 @@ impl AgentComponents {
             debugger,
             #[cfg(all(unix, feature = "libtrace"))]
             ebpf_dispatcher_component,
+            xflow_component,
             stats_collector,
             running: AtomicBool::new(false),
             metrics_server_component: MetricsServerComponent {
@@ -3335,14 +3830,42 @@ impl AgentComponents {
             dispatcher_components,
             is_ce_version: version_info.name != env!("AGENT_NAME"),
             tap_interfaces,
-            last_dispatcher_component_id: otel_dispatcher_id,
+            last_dispatcher_component_id: xflow_id,
             bpf_options,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             process_listener,
+            #[cfg(target_os = "linux")]
+            bond_monitor,
+            #[cfg(target_os = "linux")]
+            interface_assigner,
             liveness_registry,
         })
     }
 
+    // The dispatchers use the first bond as `inputs.cbpf.af_packet.bond_interfaces` is applied
+    #[cfg(target_os = "linux")]
+    fn bond_group(&self) -> Option<Vec<String>> {
+        self.bond_monitor
+            .as_ref()
+            .map(|m| m.slaves().into_iter().next().unwrap_or_default())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_bond_change(&self) {
+        if !self
+            .bond_monitor
+            .as_ref()
+            .map(|m| m.take_changed())
+            .unwrap_or(false)
+        {
+            return;
+        }
+        let bond_group = self.bond_group().unwrap_or_default();
+        for d in self.dispatcher_components.iter() {
+            d.dispatcher_listener.on_bond_group_change(&bond_group);
+        }
+    }
+
     pub fn clear_dispatcher_components(&mut self) {
         self.dispatcher_components.iter_mut().for_each(|d| d.stop());
         self.dispatcher_components.clear();
@@ -3365,6 +3888,13 @@ impl AgentComponents {
         self.debugger.start();
         self.metrics_uniform_sender.start();
         self.l7_flow_uniform_sender.start();
+        #[cfg(feature = "kafka")]
+        if let Some(e) = self.l7_flow_kafka_exporter.as_mut() {
+            e.start();
+        }
+        if let Some(e) = self.l7_flow_otlp_exporter.as_mut() {
+            e.start();
+        }
         self.l4_flow_uniform_sender.start();
 
         // Enterprise Edition Feature: packet-sequence
@@ -3396,6 +3926,9 @@ impl AgentComponents {
         if let Some(ebpf_dispatcher_component) = self.ebpf_dispatcher_component.as_mut() {
             ebpf_dispatcher_component.start();
         }
+        if let Some(xflow_component) = self.xflow_component.as_mut() {
+            xflow_component.start();
+        }
         if matches!(self.agent_mode, RunningMode::Managed) {
             self.otel_uniform_sender.start();
             self.compressed_otel_uniform_sender.start();
@@ -3420,6 +3953,10 @@ impl AgentComponents {
         self.vector_component.start();
         #[cfg(any(target_os = "linux", target_os = "android"))]
         self.process_listener.start();
+        #[cfg(target_os = "linux")]
+        if let Some(m) = self.bond_monitor.as_ref() {
+            m.start();
+        }
         info!("Started agent components.");
     }
 
@@ -3449,6 +3986,21 @@ impl AgentComponents {
         if let Some(h) = self.l7_flow_uniform_sender.notify_stop() {
             join_handles.push(h);
         }
+        #[cfg(feature = "kafka")]
+        if let Some(h) = self
+            .l7_flow_kafka_exporter
+            .as_mut()
+            .and_then(|e| e.notify_stop())
+        {
+            join_handles.push(h);
+        }
+        if let Some(h) = self
+            .l7_flow_otlp_exporter
+            .as_mut()
+            .and_then(|e| e.notify_stop())
+        {
+            join_handles.push(h);
+        }
 
         self.debugger.stop();
 
@@ -3456,6 +4008,9 @@ impl AgentComponents {
         if let Some(d) = self.ebpf_dispatcher_component.as_mut() {
             d.stop();
         }
+        if let Some(x) = self.xflow_component.as_mut() {
+            x.stop();
+        }
 
         self.metrics_server_component.stop();
         if let Some(h) = self.otel_uniform_sender.notify_stop() {
@@ -3509,6 +4064,10 @@ impl AgentComponents {
         if let Some(h) = self.process_listener.notify_stop() {
             join_handles.push(h);
         }
+        #[cfg(target_os = "linux")]
+        if let Some(m) = self.bond_monitor.as_ref() {
+            m.stop();
+        }
         #[cfg(feature = "enterprise-integration")]
         if let Some(h) = self.vector_component.notify_stop() {
             join_handles.push(h);
@@ -3550,6 +4109,7 @@ impl Components {
         platform_synchronizer: Arc<PlatformSynchronizer>,
         #[cfg(target_os = "linux")] sidecar_poller: Option<Arc<GenericPoller>>,
         #[cfg(target_os = "linux")] api_watcher: Arc<ApiWatcher>,
+        #[cfg(target_os = "linux")] agent_state: Arc<AgentState>,
         vm_mac_addrs: Vec<MacAddr>,
         gateway_vmac_addrs: Vec<MacAddr>,
         agent_mode: RunningMode,
@@ -3577,6 +4137,8 @@ impl Components {
             sidecar_poller,
             #[cfg(target_os = "linux")]
             api_watcher,
+            #[cfg(target_os = "linux")]
+            agent_state,
             vm_mac_addrs,
             gateway_vmac_addrs,
             agent_mode,
@@ -3642,11 +4204,12 @@ fn build_dispatchers(
     stats_collector: Arc<stats::Collector>,
     config_handler: &ConfigHandler,
     queue_debugger: Arc<QueueDebugger>,
+    capture_debugger: Arc<CaptureDebugger>,
     is_ce_version: bool,
     synchronizer: &Arc<Synchronizer>,
     npb_bps_limit: Arc<LeakyBucket>,
     npb_arp_table: Arc<NpbArpTable>,
-    rx_leaky_bucket: Arc<LeakyBucket>,
+    rx_token_bucket: Arc<TokenBucketConfig>,
     policy_getter: PolicyGetter,
     exception_handler: ExceptionHandler,
     bpf_options: Arc<Mutex<BpfOptions>>,
@@ -3664,7 +4227,9 @@ fn build_dispatchers(
     #[cfg(target_os = "linux")] kubernetes_poller: Arc<GenericPoller>,
     #[cfg(target_os = "linux")] libvirt_xml_extractor: Arc<LibvirtXmlExtractor>,
     #[cfg(target_os = "linux")] dpdk_ebpf_receiver: Option<Receiver<Box<packet::Packet<'static>>>>,
-    #[cfg(target_os = "linux")] fanout_enabled: bool,
+    #[cfg(target_os = "linux")] fanout: Option<Fanout>,
+    #[cfg(target_os = "linux")] dpdk_queue: Option<DpdkQueue>,
+    #[cfg(target_os = "linux")] vhost_socket: Option<(String, isize)>,
 ) -> Result<DispatcherComponent> {
     let candidate_config = &config_handler.candidate_config;
     let user_config = &candidate_config.user_config;
@@ -3733,6 +4298,7 @@ fn build_dispatchers(
         id as u32,
         config_handler.log_parser(),
         synchronizer.ntp_diff(),
+        stats_collector.clone(),
     );
     stats_collector.register_countable(
         &stats::SingleTagModule("l7_session_aggr", "index", id),
@@ -3769,17 +4335,18 @@ fn build_dispatchers(
         id,
     );
 
-    let handler_builders = Arc::new(RwLock::new(vec![
-        PacketHandlerBuilder::Pcap(mini_packet_sender),
-        PacketHandlerBuilder::Npb(NpbBuilder::new(
+    let mut handler_builders = vec![PacketHandlerBuilder::Pcap(mini_packet_sender)];
+    if user_config.outputs.npb.enabled {
+        handler_builders.push(PacketHandlerBuilder::Npb(NpbBuilder::new(
             id,
             &candidate_config.npb,
             &queue_debugger,
             npb_bps_limit.clone(),
             npb_arp_table.clone(),
             stats_collector.clone(),
-        )),
-    ]));
+        )));
+    }
+    let handler_builders = Arc::new(RwLock::new(handler_builders));
 
     let pcap_interfaces = if candidate_config.capture_mode != PacketCaptureType::Local
         && candidate_config
@@ -3801,7 +4368,7 @@ fn build_dispatchers(
         .pause(agent_mode == RunningMode::Managed)
         .handler_builders(handler_builders.clone())
         .ctrl_mac(ctrl_mac)
-        .leaky_bucket(rx_leaky_bucket.clone())
+        .rx_token_bucket(rx_token_bucket.clone())
         .options(Arc::new(Mutex::new(dispatcher::Options {
             #[cfg(any(target_os = "linux", target_os = "android"))]
             af_packet_version: dispatcher_config.af_packet_version,
@@ -3814,24 +4381,35 @@ fn build_dispatchers(
                 .vm_mac_mapping_script
                 .clone(),
             is_ipv6: ctrl_ip.is_ipv6(),
-            npb_port: user_config.outputs.npb.target_port,
-            vxlan_flags: user_config.outputs.npb.custom_vxlan_flags,
+            npb_port: user_config.outputs.npb.target_port(),
+            vxlan_flags: user_config.outputs.npb.vxlan_flags(),
             controller_port: static_config.controller_port,
             controller_tls_port: static_config.controller_tls_port,
             libpcap_enabled: user_config.inputs.cbpf.special_network.libpcap.enabled,
             snap_len: dispatcher_config.capture_packet_size as usize,
             dpdk_source: dispatcher_config.dpdk_source,
             dispatcher_queue: dispatcher_config.dispatcher_queue,
+            #[cfg(target_os = "linux")]
+            packet_fanout_mode: fanout
+                .map(|f| f.mode)
+                .unwrap_or(user_config.inputs.cbpf.af_packet.tunning.packet_fanout_mode),
+            #[cfg(not(target_os = "linux"))]
             packet_fanout_mode: user_config.inputs.cbpf.af_packet.tunning.packet_fanout_mode,
-            vhost_socket_path: user_config
-                .inputs
-                .cbpf
-                .special_network
-                .vhost_user
-                .vhost_socket_path
-                .clone(),
-            #[cfg(any(target_os = "linux", target_os = "android"))]
+            #[cfg(target_os = "linux")]
+            vhost_socket_path: vhost_socket
+                .as_ref()
+                .map(|s| s.0.clone())
+                .unwrap_or_default(),
+            #[cfg(target_os = "linux")]
+            vhost_if_index: vhost_socket.as_ref().map(|s| s.1).unwrap_or_default(),
+            #[cfg(target_os = "linux")]
+            cpu_set: dpdk_queue
+                .as_ref()
+                .and_then(|q| q.cpu_set)
+                .unwrap_or(dispatcher_config.cpu_set),
+            #[cfg(target_os = "android")]
             cpu_set: dispatcher_config.cpu_set,
+            queue_cpu: user_config.inputs.cbpf.tunning.dispatcher_queue_cpu(id),
             #[cfg(target_os = "linux")]
             dpdk_ebpf_receiver,
             #[cfg(target_os = "linux")]
@@ -3842,9 +4420,23 @@ fn build_dispatchers(
                 .dpdk
                 .reorder_cache_window_size,
             #[cfg(target_os = "linux")]
-            fanout_enabled,
-            #[cfg(any(target_os = "linux", target_os = "android"))]
-            promisc: user_config.inputs.cbpf.af_packet.tunning.promisc,
+            fanout_enabled: fanout.is_some(),
+            #[cfg(target_os = "linux")]
+            packet_fanout_group_id: fanout.map(|f| f.group_id),
+            #[cfg(target_os = "linux")]
+            dpdk_queue,
+            pcap_resolver: PcapInterfaceResolver::new(
+                &user_config.inputs.cbpf.special_network.libpcap.interfaces,
+                Some(capture_debugger),
+            ),
+            promisc_resolver: PromiscResolver::new(
+                user_config.inputs.cbpf.af_packet.tunning.promisc,
+                &user_config
+                    .inputs
+                    .cbpf
+                    .af_packet
+                    .interface_promisc_overrides,
+            ),
             skip_npb_bpf: user_config.inputs.cbpf.af_packet.skip_npb_bpf,
             ..Default::default()
         })))
@@ -3859,7 +4451,7 @@ fn build_dispatchers(
                 .unwrap_or(CaptureNetworkType::Cloud),
         )
         .mirror_traffic_pcp(
-            user_config
+            &user_config
                 .inputs
                 .cbpf
                 .af_packet
@@ -3867,6 +4459,13 @@ fn build_dispatchers(
         )
         .tap_typer(tap_typer.clone())
         .analyzer_dedup_disabled(user_config.inputs.cbpf.tunning.dispatcher_queue_enabled)
+        .packet_dedup_config(
+            user_config
+                .inputs
+                .cbpf
+                .physical_mirror
+                .packet_dedup_config(),
+        )
         .flow_output_queue(flow_sender.clone())
         .l7_stats_output_queue(l7_stats_sender.clone())
         .log_output_queue(log_sender.clone())
@@ -3882,7 +4481,7 @@ fn build_dispatchers(
         .src_interface(
             if candidate_config.capture_mode != PacketCaptureType::Local {
                 #[cfg(target_os = "linux")]
-                if !fanout_enabled {
+                if fanout.map(|f| f.bound).unwrap_or(true) {
                     src_link.name.clone()
                 } else {
                     "".into()
@@ -3900,9 +4499,7 @@ fn build_dispatchers(
         .tunnel_type_trim_bitmap(dispatcher_config.tunnel_type_trim_bitmap)
         .bond_group(dispatcher_config.bond_group.clone())
         .liveness_registry(liveness_registry.clone())
-        .analyzer_raw_packet_block_size(
-            user_config.inputs.cbpf.tunning.raw_packet_buffer_block_size,
-        );
+        .analyzer_raw_packet_block_size(dispatcher_config.raw_packet_buffer_block_size);
     #[cfg(target_os = "linux")]
     let dispatcher_builder = dispatcher_builder
         .netns(netns)
@@ -16,11 +16,11 @@
 
 use std::net::IpAddr;
 use std::sync::{
-    atomic::{AtomicBool, AtomicU64, Ordering::Relaxed},
+    atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering::Relaxed},
     Arc, Mutex, RwLock,
 };
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::{error, info, warn};
 use sysinfo::{NetworkExt, System, SystemExt};
@@ -28,6 +28,7 @@ use sysinfo::{NetworkExt, System, SystemExt};
 use crate::common::platform_data::PlatformData;
 use crate::common::policy::{Acl, Cidr, IpGroupData, PeerConnection};
 use crate::common::{FlowAclListener, FlowAclListenerId};
+use crate::config::TxThroughputAction;
 use crate::exception::ExceptionHandler;
 use crate::utils::stats::{Counter, CounterType, CounterValue, RefCountable};
 use npb_pcap_policy::{NpbTunnelType, NOT_SUPPORT};
@@ -39,6 +40,8 @@ use public::LeakyBucket;
 pub struct InterfaceTraffic {
     tx_bps: AtomicU64,
     fuse_count: AtomicU64,
+    limiter_state: AtomicU8,
+    npb_bps: AtomicU64,
 }
 
 impl RefCountable for InterfaceTraffic {
@@ -54,16 +57,137 @@ impl RefCountable for InterfaceTraffic {
                 CounterType::Counted,
                 CounterValue::Unsigned(self.fuse_count.swap(0, Relaxed)),
             ),
+            (
+                "limiter_state",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.limiter_state.load(Relaxed) as u64),
+            ),
+            (
+                "npb_bps",
+                CounterType::Gauged,
+                CounterValue::Unsigned(self.npb_bps.load(Relaxed)),
+            ),
         ]
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LimiterState {
+    #[default]
+    Normal = 0,
+    Stopped = 1,
+    Sampling = 2,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LimiterConfig {
+    // the unit is bites per secend
+    trigger_threshold: u64,
+    // 0 means 90% of trigger_threshold
+    recovery_threshold: u64,
+    npb_bps_threshold: u64,
+    min_hold_time: Duration,
+    action: TxThroughputAction,
+}
+
+impl LimiterConfig {
+    fn recovery_threshold(&self) -> u64 {
+        if self.recovery_threshold == 0 {
+            self.trigger_threshold / 10 * 9
+        } else {
+            self.recovery_threshold
+        }
+    }
+}
+
+// Limits npb traffic by the tx bandwidth of the npb interface:
+// - Over trigger_threshold, npb is stopped or its rate is reduced proportionally.
+// - Npb resumes when the bandwidth with npb at full rate stays under
+//   recovery_threshold for min_hold_time.
+#[derive(Debug, Default)]
+struct Limiter {
+    state: LimiterState,
+    npb_bps: u64,
+    below_since: Option<Duration>,
+}
+
+impl Limiter {
+    const MIN_NPB_BPS: u64 = 1;
+
+    // Returns the new npb rate on change
+    fn evaluate(&mut self, now: Duration, tx_bps: u64, config: &LimiterConfig) -> Option<u64> {
+        if config.trigger_threshold == 0 {
+            if self.state == LimiterState::Normal {
+                return None;
+            }
+            return Some(self.resume(config));
+        }
+
+        if tx_bps > config.trigger_threshold {
+            self.below_since = None;
+            return match config.action {
+                TxThroughputAction::Stop if self.state != LimiterState::Stopped => {
+                    self.state = LimiterState::Stopped;
+                    self.npb_bps = Self::MIN_NPB_BPS;
+                    Some(self.npb_bps)
+                }
+                TxThroughputAction::Sample => {
+                    let npb_bps = if self.state != LimiterState::Sampling {
+                        config.npb_bps_threshold
+                    } else {
+                        self.npb_bps
+                    };
+                    self.state = LimiterState::Sampling;
+                    // reduce npb proportionally to bring tx bandwidth back to recovery_threshold
+                    self.npb_bps = ((npb_bps as u128 * config.recovery_threshold() as u128
+                        / tx_bps as u128) as u64)
+                        .max(Self::MIN_NPB_BPS);
+                    Some(self.npb_bps)
+                }
+                _ => None,
+            };
+        }
+        if self.state == LimiterState::Normal {
+            return None;
+        }
+
+        // the tx bandwidth if npb is restored to full rate
+        let npb_bps = if self.state == LimiterState::Stopped {
+            0
+        } else {
+            self.npb_bps
+        };
+        let projected_bps = tx_bps.saturating_sub(npb_bps) + config.npb_bps_threshold;
+        if projected_bps > config.recovery_threshold() {
+            self.below_since = None;
+            return None;
+        }
+        let below_since = *self.below_since.get_or_insert(now);
+        if now.saturating_sub(below_since) < config.min_hold_time {
+            return None;
+        }
+        Some(self.resume(config))
+    }
+
+    fn resume(&mut self, config: &LimiterConfig) -> u64 {
+        self.state = LimiterState::Normal;
+        self.npb_bps = config.npb_bps_threshold;
+        self.below_since = None;
+        self.npb_bps
+    }
+}
+
 struct Watcher {
     // the unit is bites per secend
     nic_bps_threshold: AtomicU64,
+    nic_bps_recovery_threshold: AtomicU64,
     npb_bps_threshold: AtomicU64,
     // the unit is second
     interval: AtomicU64,
+    min_hold_time: AtomicU64,
+    over_limit_action: AtomicU8,
+    limiter_state: AtomicU8,
 
     traffic_count: Arc<InterfaceTraffic>,
 
@@ -76,8 +200,6 @@ struct Watcher {
 }
 
 impl Watcher {
-    const CHECK_CYCLES: usize = 5;
-
     fn get_nic_name(&self) -> String {
         let ips = self.ips.read().unwrap().clone();
         let mut last_nic_name = self.nic_name.write().unwrap();
@@ -130,34 +252,73 @@ impl Watcher {
         return Ok(tx_bps);
     }
 
-    fn npb_stop(&self, nic_name: &str, tx_bps: u64) -> bool {
-        self.npb_leaky_bucket.set_rate(Some(1));
-        self.traffic_count.fuse_count.fetch_add(1, Relaxed);
-        self.exception_handler.set(Exception::NpbFuse, None);
-        warn!("Npb had fused {} tx bandwidth is {} bps.", nic_name, tx_bps);
-
-        true
+    fn limiter_config(&self) -> LimiterConfig {
+        LimiterConfig {
+            trigger_threshold: self.nic_bps_threshold.load(Relaxed),
+            recovery_threshold: self.nic_bps_recovery_threshold.load(Relaxed),
+            npb_bps_threshold: self.npb_bps_threshold.load(Relaxed),
+            min_hold_time: Duration::from_secs(self.min_hold_time.load(Relaxed)),
+            action: if self.over_limit_action.load(Relaxed) == TxThroughputAction::Sample as u8 {
+                TxThroughputAction::Sample
+            } else {
+                TxThroughputAction::Stop
+            },
+        }
     }
 
-    fn npb_start(&self, tx_bps: u64) -> bool {
-        self.npb_leaky_bucket
-            .set_rate(Some(self.npb_bps_threshold.load(Relaxed)));
-        self.exception_handler.clear(Exception::NpbFuse);
-        info!("Npb reopen, tx bandwidth is {} bps.", tx_bps);
+    fn apply(&self, last_state: LimiterState, limiter: &Limiter, tx_bps: u64) {
+        self.npb_leaky_bucket.set_rate(Some(limiter.npb_bps));
+        self.limiter_state.store(limiter.state as u8, Relaxed);
+        self.traffic_count
+            .limiter_state
+            .store(limiter.state as u8, Relaxed);
+        self.traffic_count.npb_bps.store(limiter.npb_bps, Relaxed);
+        if last_state == limiter.state {
+            if limiter.state == LimiterState::Sampling {
+                info!(
+                    "Npb sampling rate changed to {} bps, tx bandwidth is {} bps.",
+                    limiter.npb_bps, tx_bps
+                );
+            }
+            return;
+        }
 
-        false
+        let nic_name = self.nic_name.read().unwrap().clone();
+        match limiter.state {
+            LimiterState::Stopped => {
+                self.traffic_count.fuse_count.fetch_add(1, Relaxed);
+                self.exception_handler.set(Exception::NpbFuse, None);
+                warn!(
+                    "Npb limiter changed from {:?} to {:?}, {} tx bandwidth is {} bps.",
+                    last_state, limiter.state, nic_name, tx_bps
+                );
+            }
+            LimiterState::Sampling => {
+                self.exception_handler.clear(Exception::NpbFuse);
+                warn!(
+                    "Npb limiter changed from {:?} to {:?} at {} bps, {} tx bandwidth is {} bps.",
+                    last_state, limiter.state, limiter.npb_bps, nic_name, tx_bps
+                );
+            }
+            LimiterState::Normal => {
+                self.exception_handler.clear(Exception::NpbFuse);
+                info!(
+                    "Npb limiter changed from {:?} to {:?}, {} tx bandwidth is {} bps.",
+                    last_state, limiter.state, nic_name, tx_bps
+                );
+            }
+        }
     }
 
     fn run(&self) {
         let mut last_tx_bytes = 0;
-        let mut npb_is_closed = false;
-        let mut cycles = 0;
+        let mut limiter = Limiter::default();
         let mut first = true;
+        let start = Instant::now();
         while self.is_running.load(Relaxed) {
             thread::sleep(Duration::from_secs(self.interval.load(Relaxed)));
 
             let interval = self.interval.load(Relaxed);
-            let nic_bps_threshold = self.nic_bps_threshold.load(Relaxed);
             let Ok(tx_bps) = self.get_nic_bps(&mut last_tx_bytes, interval) else {
                 self.traffic_count.tx_bps.store(0, Relaxed);
                 continue;
@@ -168,35 +329,12 @@ impl Watcher {
             }
             self.traffic_count.tx_bps.store(tx_bps, Relaxed);
 
-            if nic_bps_threshold == 0 {
-                if npb_is_closed {
-                    npb_is_closed = self.npb_start(tx_bps);
-                }
-                continue;
-            }
-
-            if tx_bps > nic_bps_threshold {
-                if !npb_is_closed {
-                    npb_is_closed = self.npb_stop(self.nic_name.read().unwrap().as_str(), tx_bps);
-                }
-            } else {
-                let npb_bps_threshold = self.npb_bps_threshold.load(Relaxed);
-                if nic_bps_threshold <= npb_bps_threshold {
-                    continue;
-                }
-                if tx_bps < (nic_bps_threshold - npb_bps_threshold) * 90 / 100 {
-                    if npb_is_closed {
-                        cycles += 1;
-                        if cycles >= Self::CHECK_CYCLES {
-                            npb_is_closed = self.npb_start(tx_bps);
-                            cycles = 0;
-                        }
-                    } else {
-                        cycles = 0;
-                    }
-                } else {
-                    cycles = 0;
-                }
+            let last_state = limiter.state;
+            if limiter
+                .evaluate(start.elapsed(), tx_bps, &self.limiter_config())
+                .is_some()
+            {
+                self.apply(last_state, &limiter, tx_bps);
             }
         }
     }
@@ -205,7 +343,6 @@ impl Watcher {
 pub struct NpbBandwidthWatcher {
     watcher: Arc<Watcher>,
     thread_handler: Mutex<Option<JoinHandle<()>>>,
-    exception_handler: ExceptionHandler,
 }
 
 impl NpbBandwidthWatcher {
@@ -214,6 +351,7 @@ impl NpbBandwidthWatcher {
     const INTERVAL_DEFAULT: u64 = 10;
     const INTERVAL_MIN: u64 = 1;
     const INTERVAL_MAX: u64 = 60;
+    const MIN_HOLD_TIME_DEFAULT: u64 = 50;
 
     pub fn new(
         interval: u64,
@@ -223,22 +361,26 @@ impl NpbBandwidthWatcher {
         exception_handler: ExceptionHandler,
     ) -> (Box<Arc<Self>>, Arc<InterfaceTraffic>) {
         let traffic_count = Arc::new(InterfaceTraffic::default());
+        traffic_count.npb_bps.store(npb_bps_threshold, Relaxed);
 
         (
             Box::new(Arc::new(Self {
                 watcher: Arc::new(Watcher {
                     nic_bps_threshold: AtomicU64::new(nic_bps_threshold),
+                    nic_bps_recovery_threshold: AtomicU64::new(0),
                     npb_bps_threshold: AtomicU64::new(npb_bps_threshold),
                     interval: AtomicU64::new(interval),
+                    min_hold_time: AtomicU64::new(Self::MIN_HOLD_TIME_DEFAULT),
+                    over_limit_action: AtomicU8::new(TxThroughputAction::Stop as u8),
+                    limiter_state: AtomicU8::new(LimiterState::Normal as u8),
                     ips: RwLock::new(vec![]),
                     npb_leaky_bucket,
                     nic_name: RwLock::new("".to_string()),
                     is_running: AtomicBool::new(false),
                     traffic_count: traffic_count.clone(),
-                    exception_handler: exception_handler.clone(),
+                    exception_handler,
                 }),
                 thread_handler: Mutex::new(None),
-                exception_handler,
             })),
             traffic_count,
         )
@@ -246,13 +388,30 @@ impl NpbBandwidthWatcher {
 
     pub fn set_npb_rate(&self, threshold: u64) {
         self.watcher.npb_bps_threshold.store(threshold, Relaxed);
-        // When there is Exception::NpbFuse, npb_leaky_bucket cannot be
-        // set in order to not distribute traffic packet.
-        if !self.exception_handler.has(Exception::NpbFuse) {
+        // When npb is stopped or sampled by the limiter, npb_leaky_bucket
+        // cannot be set in order to keep the limited rate.
+        if self.watcher.limiter_state.load(Relaxed) == LimiterState::Normal as u8 {
             self.watcher.npb_leaky_bucket.set_rate(Some(threshold));
+            self.watcher.traffic_count.npb_bps.store(threshold, Relaxed);
         }
     }
 
+    pub fn set_nic_recovery_rate(&self, threshold: u64) {
+        self.watcher
+            .nic_bps_recovery_threshold
+            .store(threshold, Relaxed);
+    }
+
+    pub fn set_min_hold_time(&self, min_hold_time: Duration) {
+        self.watcher
+            .min_hold_time
+            .store(min_hold_time.as_secs(), Relaxed);
+    }
+
+    pub fn set_over_limit_action(&self, action: TxThroughputAction) {
+        self.watcher.over_limit_action.store(action as u8, Relaxed);
+    }
+
     pub fn set_nic_rate(&self, mut threshold: u64) {
         if threshold > Self::BANDWIDTH_MAX {
             info!(
@@ -361,3 +520,129 @@ impl FlowAclListener for Arc<NpbBandwidthWatcher> {
         u16::from(FlowAclListenerId::NpbBandWatcher) as usize
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MBPS: u64 = 1 << 20;
+
+    fn config(action: TxThroughputAction) -> LimiterConfig {
+        LimiterConfig {
+            trigger_threshold: 1000 * MBPS,
+            recovery_threshold: 800 * MBPS,
+            npb_bps_threshold: 100 * MBPS,
+            min_hold_time: Duration::from_secs(50),
+            action,
+        }
+    }
+
+    #[test]
+    fn recovery_threshold_default() {
+        let mut c = config(TxThroughputAction::Stop);
+        assert_eq!(c.recovery_threshold(), 800 * MBPS);
+        c.recovery_threshold = 0;
+        assert_eq!(c.recovery_threshold(), 900 * MBPS);
+    }
+
+    #[test]
+    fn stop_and_resume_after_hold_time() {
+        let c = config(TxThroughputAction::Stop);
+        let mut limiter = Limiter::default();
+
+        assert_eq!(limiter.evaluate(Duration::ZERO, 900 * MBPS, &c), None);
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(10), 1100 * MBPS, &c),
+            Some(Limiter::MIN_NPB_BPS)
+        );
+        assert_eq!(limiter.state, LimiterState::Stopped);
+        // still over trigger, no further change
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(20), 1100 * MBPS, &c),
+            None
+        );
+
+        // 750M + 100M npb exceeds recovery_threshold
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(30), 750 * MBPS, &c),
+            None
+        );
+        assert_eq!(limiter.below_since, None);
+
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(40), 600 * MBPS, &c),
+            None
+        );
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(80), 600 * MBPS, &c),
+            None
+        );
+        // dip above recovery_threshold restarts the hold time
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(85), 750 * MBPS, &c),
+            None
+        );
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(90), 600 * MBPS, &c),
+            None
+        );
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(130), 600 * MBPS, &c),
+            None
+        );
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(140), 600 * MBPS, &c),
+            Some(100 * MBPS)
+        );
+        assert_eq!(limiter.state, LimiterState::Normal);
+    }
+
+    #[test]
+    fn sample_proportionally() {
+        let c = config(TxThroughputAction::Sample);
+        let mut limiter = Limiter::default();
+
+        assert_eq!(
+            limiter.evaluate(Duration::ZERO, 1600 * MBPS, &c),
+            Some(50 * MBPS)
+        );
+        assert_eq!(limiter.state, LimiterState::Sampling);
+        // reduced further from the current rate
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(1), 1600 * MBPS, &c),
+            Some(25 * MBPS)
+        );
+
+        // 700M - 25M + 100M stays under recovery_threshold
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(2), 700 * MBPS, &c),
+            None
+        );
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(52), 700 * MBPS, &c),
+            Some(100 * MBPS)
+        );
+        assert_eq!(limiter.state, LimiterState::Normal);
+    }
+
+    #[test]
+    fn disabled_trigger_resumes() {
+        let mut c = config(TxThroughputAction::Stop);
+        let mut limiter = Limiter::default();
+
+        assert_eq!(
+            limiter.evaluate(Duration::ZERO, 1100 * MBPS, &c),
+            Some(Limiter::MIN_NPB_BPS)
+        );
+        c.trigger_threshold = 0;
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(1), 1100 * MBPS, &c),
+            Some(100 * MBPS)
+        );
+        assert_eq!(limiter.state, LimiterState::Normal);
+        assert_eq!(
+            limiter.evaluate(Duration::from_secs(2), 1100 * MBPS, &c),
+            None
+        );
+    }
+}
@@ -347,7 +347,7 @@ impl ProcessListener {
         let mut features: HashMap<String, ProcessNode> = HashMap::new();
         let mut current = self.features.write().unwrap();
 
-        for matcher in process_matcher.iter() {
+        for matcher in ProcessMatcher::resolve_features(process_matcher).iter() {
             for feature in matcher.enabled_features.iter() {
                 if let Some(node) = features.get_mut(feature) {
                     node.process_matcher.push(matcher.clone());
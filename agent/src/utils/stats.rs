@@ -149,6 +149,132 @@ impl<T: ToString> Module for SingleTagModule<T> {
     }
 }
 
+pub struct DpdkQueueStats {
+    pub port_id: u16,
+    pub queue_id: u16,
+}
+
+impl Module for DpdkQueueStats {
+    fn name(&self) -> &'static str {
+        "dpdk_queue"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("port", self.port_id.to_string()),
+            StatsOption::Tag("queue", self.queue_id.to_string()),
+        ]
+    }
+}
+
+pub struct DispatcherQueueStats {
+    pub index: usize,
+}
+
+impl Module for DispatcherQueueStats {
+    fn name(&self) -> &'static str {
+        "dispatcher_queue"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![StatsOption::Tag("queue", self.index.to_string())]
+    }
+}
+
+pub struct InterfacePromiscStats {
+    pub interface: String,
+    pub if_index: u32,
+    pub promisc_mode: &'static str,
+}
+
+impl Module for InterfacePromiscStats {
+    fn name(&self) -> &'static str {
+        "dispatcher_interface"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("interface", self.interface.clone()),
+            StatsOption::Tag("if_index", self.if_index.to_string()),
+            StatsOption::Tag("promisc_mode", self.promisc_mode.to_owned()),
+        ]
+    }
+}
+
+pub struct SenderSocketStats {
+    pub message_type: SendMessageType,
+    pub index: usize,
+}
+
+impl Module for SenderSocketStats {
+    fn name(&self) -> &'static str {
+        "collect_sender_socket"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("type", self.message_type.to_string()),
+            StatsOption::Tag("socket", self.index.to_string()),
+        ]
+    }
+}
+
+// l4_flow_log and l7_flow_log throttles, with the same counters
+pub struct FlowLogThrottleStats {
+    pub kind: &'static str,
+    pub index: usize,
+}
+
+impl Module for FlowLogThrottleStats {
+    fn name(&self) -> &'static str {
+        "flow_log_throttle"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("type", self.kind.to_owned()),
+            StatsOption::Tag("index", self.index.to_string()),
+        ]
+    }
+}
+
+// l4_flow_log sampled by observation point
+pub struct FlowLogSamplingStats {
+    pub index: usize,
+    pub observation_point: u8,
+}
+
+impl Module for FlowLogSamplingStats {
+    fn name(&self) -> &'static str {
+        "flow_log_sampling"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("index", self.index.to_string()),
+            StatsOption::Tag("observation_point", self.observation_point.to_string()),
+        ]
+    }
+}
+
+pub struct NpbVlanStats {
+    pub id: usize,
+    pub vlan_id: u16,
+}
+
+impl Module for NpbVlanStats {
+    fn name(&self) -> &'static str {
+        "npb_vlan_sender"
+    }
+
+    fn tags(&self) -> Vec<StatsOption> {
+        vec![
+            StatsOption::Tag("id", self.id.to_string()),
+            StatsOption::Tag("vlan", self.vlan_id.to_string()),
+        ]
+    }
+}
+
 #[derive(Default)]
 pub struct QueueStats {
     pub id: usize,
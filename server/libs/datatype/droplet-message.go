@@ -167,6 +167,7 @@ const (
 	MESSAGE_ENCODER_ZLIB
 	MESSAGE_ENCODER_GZIP
 	MESSAGE_ENCODER_ZSTD
+	MESSAGE_ENCODER_LZ4
 )
 
 type BaseHeader struct {
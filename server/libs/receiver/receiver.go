@@ -34,6 +34,7 @@ import (
 
 	"github.com/klauspost/compress/zstd"
 	logging "github.com/op/go-logging"
+	"github.com/pierrec/lz4/v4"
 
 	"github.com/deepflowio/deepflow/server/libs/app"
 	"github.com/deepflowio/deepflow/server/libs/cache"
@@ -772,7 +773,13 @@ func (r *Receiver) ProcessUDPServer() {
 			recvBuffer.TeamID = teamID
 			recvBuffer.OrgID = orgID
 			decodeBuffer, err := r.decompressBuffer(flowHeader.Encoder, recvBuffer.Buffer, recvBuffer.Begin, recvBuffer.End)
-			if err == nil && flowHeader.Encoder != datatype.MESSAGE_ENCODER_RAW {
+			if err != nil {
+				atomic.AddUint64(&r.counter.Invalid, 1)
+				ReleaseRecvBuffer(recvBuffer)
+				log.Warningf("UDP client (%s) message decompress failed: %s", remoteAddr.String(), err)
+				continue
+			}
+			if flowHeader.Encoder != datatype.MESSAGE_ENCODER_RAW {
 				// udp message contains header size
 				recvBuffer.End = len(decodeBuffer) + headerLen
 				copy(recvBuffer.Buffer[headerLen:], decodeBuffer)
@@ -812,6 +819,10 @@ func (r *Receiver) decompressBuffer(encoder uint8, receiveBuffer []byte, start,
 		}
 		reader = notReadCloser.IOReadCloser()
 		defer reader.Close()
+	case datatype.MESSAGE_ENCODER_LZ4: // lz4 frame
+		reader = io.NopCloser(lz4.NewReader(encodeBuffer))
+	default:
+		return receiveBuffer, fmt.Errorf("unsupported encoder %d", encoder)
 	}
 
 	result := make([]byte, 0, len(receiveBuffer))
@@ -985,7 +996,13 @@ func (r *Receiver) handleTCPConnection(conn net.Conn) {
 			recvBuffer.TeamID = teamID
 			recvBuffer.OrgID = orgID
 			decodeBuffer, err := r.decompressBuffer(flowHeader.Encoder, recvBuffer.Buffer, recvBuffer.Begin, recvBuffer.End)
-			if err == nil && flowHeader.Encoder != datatype.MESSAGE_ENCODER_RAW {
+			if err != nil {
+				atomic.AddUint64(&r.counter.Invalid, 1)
+				ReleaseRecvBuffer(recvBuffer)
+				log.Warningf("TCP client (%s) message decompress failed: %s", conn.RemoteAddr().String(), err)
+				continue
+			}
+			if flowHeader.Encoder != datatype.MESSAGE_ENCODER_RAW {
 				// after decompressed, should update buffer end here for decoder
 				recvBuffer.End = len(decodeBuffer)
 				recvBuffer.Buffer = decodeBuffer